@@ -0,0 +1,167 @@
+//! 自包含的可执行文件
+//!
+//! `mylang bundle` 把编译好的字节码块追加到运行时（`mylang` 自身）的一份副本之后，
+//! 文件末尾是固定长度的尾部：
+//!
+//! ```text
+//! [运行时][字节码块][载荷偏移 u64][载荷长度 u64][载荷哈希 u64][标志 u8][魔数 8 字节]
+//! ```
+//!
+//! 运行时启动时检查自身末尾的魔数，找到载荷就跳过命令行解析，直接运行其中的程序。
+//! 字节码只能由编译它的同一版本运行，所以只支持在同一平台上打包。
+
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+
+use crate::compiler::serialize::{decode_chunk, encode_chunk};
+use crate::compiler::{Chunk, OpCode};
+use crate::parser::cache::bytes_hash;
+use crate::stdlib::global_registry;
+
+/// 尾部魔数
+const MAGIC: &[u8; 8] = b"QBUNDLE\0";
+
+/// 尾部长度
+const FOOTER_LEN: usize = 8 * 3 + 1 + MAGIC.len();
+
+/// 标志位：载荷经过 deflate 压缩
+const FLAG_COMPRESSED: u8 = 1;
+
+/// 尾部
+struct Footer {
+    offset: u64,
+    len: u64,
+    hash: u64,
+    flags: u8,
+}
+
+impl Footer {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(FOOTER_LEN);
+        bytes.extend_from_slice(&self.offset.to_le_bytes());
+        bytes.extend_from_slice(&self.len.to_le_bytes());
+        bytes.extend_from_slice(&self.hash.to_le_bytes());
+        bytes.push(self.flags);
+        bytes.extend_from_slice(MAGIC);
+        bytes
+    }
+
+    /// 解析尾部，没有魔数时返回 None
+    fn parse(bytes: &[u8; FOOTER_LEN]) -> Option<Self> {
+        if &bytes[FOOTER_LEN - MAGIC.len()..] != MAGIC {
+            return None;
+        }
+        let word = |i: usize| u64::from_le_bytes(bytes[i * 8..i * 8 + 8].try_into().unwrap());
+        Some(Footer { offset: word(0), len: word(1), hash: word(2), flags: bytes[24] })
+    }
+}
+
+/// 读取文件末尾的尾部
+fn read_footer(file: &mut fs::File) -> std::io::Result<Option<Footer>> {
+    let size = file.metadata()?.len();
+    if size < FOOTER_LEN as u64 {
+        return Ok(None);
+    }
+    let mut bytes = [0u8; FOOTER_LEN];
+    file.seek(SeekFrom::Start(size - FOOTER_LEN as u64))?;
+    file.read_exact(&mut bytes)?;
+    Ok(Footer::parse(&bytes))
+}
+
+/// 把程序打包为可执行文件：复制运行时并追加字节码块
+///
+/// 运行时本身已经是打包好的程序时，只复制其中的运行时部分
+pub fn write_bundle(runtime: &Path, chunk: &Chunk, output: &Path, compress: bool) -> Result<(), String> {
+    let mut payload = encode_chunk(chunk)?;
+    let mut flags = 0;
+    if compress {
+        use std::io::Write;
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&payload).and_then(|_| encoder.finish()).map(|bytes| payload = bytes)
+            .map_err(|e| format!("cannot compress the program: {}", e))?;
+        flags |= FLAG_COMPRESSED;
+    }
+
+    let runtime_error = |e: std::io::Error| format!("cannot read runtime '{}': {}", runtime.display(), e);
+    let mut file = fs::File::open(runtime).map_err(runtime_error)?;
+    let runtime_len = match read_footer(&mut file).map_err(runtime_error)? {
+        Some(footer) => footer.offset,
+        None => file.metadata().map_err(runtime_error)?.len(),
+    };
+    let mut bytes = Vec::new();
+    file.seek(SeekFrom::Start(0)).map_err(runtime_error)?;
+    file.take(runtime_len).read_to_end(&mut bytes).map_err(runtime_error)?;
+
+    let footer = Footer { offset: bytes.len() as u64, len: payload.len() as u64, hash: bytes_hash(&payload), flags };
+    bytes.extend_from_slice(&payload);
+    bytes.extend_from_slice(&footer.to_bytes());
+
+    let output_error = |e: std::io::Error| format!("cannot write '{}': {}", output.display(), e);
+    fs::write(output, &bytes).map_err(output_error)?;
+    // 保留运行时的可执行权限
+    let permissions = fs::metadata(runtime).map_err(runtime_error)?.permissions();
+    fs::set_permissions(output, permissions).map_err(output_error)
+}
+
+/// 读取可执行文件中打包的程序，没有打包程序时返回 None
+///
+/// 载荷损坏、由其他版本编译或者需要当前运行时没有的模块时返回错误
+pub fn read_bundle(exe: &Path) -> Result<Option<Chunk>, String> {
+    let damaged = |e: std::io::Error| format!("cannot read the embedded program: {}", e);
+    let mut file = fs::File::open(exe).map_err(damaged)?;
+    let footer = match read_footer(&mut file).map_err(damaged)? {
+        Some(footer) => footer,
+        None => return Ok(None),
+    };
+    let size = file.metadata().map_err(damaged)?.len();
+    let corrupted = || "the embedded program is corrupted (checksum mismatch)".to_string();
+    if footer.offset.checked_add(footer.len) != Some(size - FOOTER_LEN as u64) {
+        return Err(corrupted());
+    }
+    let mut payload = Vec::new();
+    file.seek(SeekFrom::Start(footer.offset)).map_err(damaged)?;
+    file.take(footer.len).read_to_end(&mut payload).map_err(damaged)?;
+    if bytes_hash(&payload) != footer.hash {
+        return Err(corrupted());
+    }
+    if footer.flags & FLAG_COMPRESSED != 0 {
+        let mut inflated = Vec::new();
+        DeflateDecoder::new(payload.as_slice()).read_to_end(&mut inflated).map_err(|_| corrupted())?;
+        payload = inflated;
+    }
+    let chunk = decode_chunk(&payload).map_err(|e| format!("cannot load the embedded program: {}", e))?;
+    check_requirements(&chunk)?;
+    Ok(Some(chunk))
+}
+
+/// 检查程序用到的标准库函数和宿主函数在当前运行时中都存在
+fn check_requirements(chunk: &Chunk) -> Result<(), String> {
+    let registry = global_registry();
+    registry.host_functions().link(chunk)?;
+    let mut missing = Vec::new();
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        let instruction = chunk
+            .decode_instruction(offset)
+            .ok_or_else(|| "cannot load the embedded program: compiled program is damaged".to_string())?;
+        if instruction.opcode == OpCode::CallStdlib {
+            let name = |i: usize| chunk.constants.get(instruction.operands[i].1 as usize).and_then(|v| v.as_string());
+            if let (Some(module), Some(func)) = (name(0), name(1)) {
+                if !registry.has_function(module, func) && !missing.contains(func) {
+                    missing.push(func.clone());
+                }
+            }
+        }
+        offset += instruction.len;
+    }
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("program uses standard library functions that this runtime does not provide: {}", missing.join(", ")))
+    }
+}
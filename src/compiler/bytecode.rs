@@ -77,6 +77,13 @@ pub enum OpCode {
     SetUpvalue = 53,
     /// 关闭 Upvalue（将栈上的值移到堆上）: 操作数为槽位索引 (u16)
     CloseUpvalue = 54,
+    /// 把局部变量提升为 cell（被闭包捕获且会被赋值的变量在声明处执行）: 操作数为槽位索引 (u16)
+    /// 栈: [...] -> [...]，槽位中的值被替换为装着它的 cell
+    MakeCell = 55,
+    /// 读取槽位中 cell 的值: 操作数为槽位索引 (u16)
+    GetCell = 56,
+    /// 设置槽位中 cell 的值（不弹出栈顶）: 操作数为槽位索引 (u16)
+    SetCell = 57,
     
     // ============ 控制流 ============
     /// 无条件跳转: 操作数为偏移量 (i16)
@@ -101,8 +108,21 @@ pub enum OpCode {
     Panic = 74,
     /// 获取完整类型信息: pop value, push RuntimeTypeInfo 对象
     TypeInfo = 89,
+    /// 十六进制转储（hexdump / printHexdump）
+    /// 操作数: 参数数量 (u8, 1 或 2), 是否直接打印 (u8)
+    /// 栈: [..., value, options?] -> [..., 转储字符串或 null]
+    Hexdump = 114,
+    /// 跳转表（密集整数值的 switch）
+    /// 操作数: 最小值 (常量池索引, int), 表项数 count (u16), 之后是 count + 1 个 u16 向前偏移，
+    /// 最后一个是 default；偏移都相对于表的末尾
+    /// 栈: [..., value] -> [...]，值为 min + i 时跳到第 i 项，不在范围内或不是整数时跳到 default
+    JumpTable = 115,
     /// 将栈顶值转换为字符串: pop value, push string
     ToString = 83,
+    /// 拼接栈顶 N 个字符串（字符串插值）
+    /// 操作数: 部分数量 (u16)
+    /// 栈: [..., s1, s2, ..., sN] -> [..., s1s2...sN]
+    BuildString = 107,
     /// 获取当前时间戳（毫秒）: push timestamp
     /// [deprecated] 可能在未来版本移除
     Time = 88,
@@ -142,12 +162,15 @@ pub enum OpCode {
     NewRangeInclusive = 79,
     
     // ============ 迭代器 ============
-    /// 初始化迭代器（从数组或范围创建迭代器）
+    /// 初始化迭代器（从数组、范围、通道、map 的键值对，或对象的 entries() 结果创建迭代器）
     /// 栈: [..., iterable] -> [..., iterator]
     IterInit = 90,
     /// 获取迭代器下一个值
     /// 栈: [..., iterator] -> [..., iterator, value, has_next]
     IterNext = 91,
+    /// 把两个变量的 for-in 的当前元素拆成两个值，元素不是两个元素的数组时报错
+    /// 栈: [..., iterator, pair] -> [..., iterator, first, second]
+    UnpackPair = 112,
     
     // ============ Struct 操作 ============
     /// 创建 struct 实例
@@ -170,20 +193,28 @@ pub enum OpCode {
     /// 操作数: 类名索引 (u16), 参数数量 (u8)
     /// 栈: [..., arg1, ..., argN] -> [..., instance]
     NewClass = 96,
+    /// `new User { name: ... }` 的字段赋值，实例的类（或父类）没有声明这个字段时报错
+    /// 操作数: 字段名称索引 (u16)
+    /// 栈: [..., instance, value] -> [..., instance]
+    InitField = 118,
     /// 获取静态字段
     /// 操作数: 类名索引 (u16), 字段名索引 (u16)
     GetStatic = 97,
-    /// 设置静态字段
+    /// 设置静态字段，同一程序的虚拟机（协程、回调）共享静态字段
     /// 操作数: 类名索引 (u16), 字段名索引 (u16)
+    /// 栈: [..., value] -> [..., value]
     SetStatic = 98,
     /// 调用静态方法
     /// 操作数: 类名索引 (u16), 方法名索引 (u16), 参数数量 (u8)
     InvokeStatic = 99,
-    /// 调用父类方法
-    /// 操作数: 方法名索引 (u16), 参数数量 (u8)
+    /// 调用父类方法（父类在编译期确定：方法所在类的直接父类，而不是实例的父类）
+    /// 操作数: 父类名索引 (u16), 方法名索引 (u16), 参数数量 (u8)
     InvokeSuper = 100,
     /// 复制栈顶值
     Dup = 101,
+    /// 复制栈顶两个值: [..., a, b] -> [..., a, b, a, b]
+    /// 用于 `xs[i] op= v`，对象和索引只求值一次
+    Dup2 = 109,
     /// 如果为 null 则跳转: 操作数为偏移量 (i16)
     /// 栈: [..., value] -> [...]（如果跳转）或 [..., value]（不跳转）
     JumpIfNull = 102,
@@ -201,12 +232,20 @@ pub enum OpCode {
     NonNullInvokeMethod = 106,
     
     // ============ 函数调用 ============
-    /// 创建闭包
-    /// 操作数: 函数索引 (u16)
+    /// 创建捕获了外部变量的闭包
+    /// 操作数: 函数常量索引 (u16)；按函数的 upvalues 依次复制当前帧中的槽位（cell 或值）
+    /// 栈: [...] -> [..., closure]
     Closure = 80,
     /// 调用函数
     /// 操作数: 参数数量 (u8)
     Call = 81,
+    /// 调用标准库模块函数
+    /// 操作数: 模块名索引 (u16), 函数名索引 (u16), 参数数量 (u8)
+    CallStdlib = 108,
+    /// 调用宿主函数（嵌入程序注册的 Rust 函数）
+    /// 操作数: 宿主函数下标 (u16，指向 Chunk::host_functions), 参数数量 (u8)
+    /// 栈: [..., arg_1, ..., arg_n] -> [..., result]
+    HostCall = 113,
     /// 从函数返回
     /// 返回栈顶值
     Return = 82,
@@ -229,6 +268,16 @@ pub enum OpCode {
     /// 抛出异常
     /// 栈顶值为异常对象
     Throw = 111,
+    /// 移除最近设置的异常处理器（try 块正常结束，或 return/break/continue 离开 try 块）
+    PopTry = 116,
+    /// 设置 panic 处理器（recovering 块），只处理 panic，不处理异常；由 PopTry 移除
+    /// 操作数: onPanic 块偏移量 (i16)
+    SetupRecover = 117,
+    /// 设置 finally 处理器（带 finally 的 try 语句），只在 `Os.exit` 结束程序时进入；由 PopTry 移除
+    /// 操作数: 退出路径上 finally 块的偏移量 (i16)
+    SetupFinally = 119,
+    /// 退出路径上的 finally 块执行完毕，弹出退出码，继续结束程序
+    ResumeExit = 147,
     
     // ============ 专用整数指令 (性能优化) ============
     /// 整数加法 (无类型检查)
@@ -279,6 +328,9 @@ pub enum OpCode {
     GetLocalLeInt = 137,
     /// 条件返回（如果栈顶为真，返回第二个栈值）
     ReturnIf = 138,
+    /// 条件跳转并弹出 (如果为真)
+    /// 操作数: offset (u16)
+    JumpIfTruePop = 139,
     
     // ============ 并发指令 (140-160) ============
     /// 启动协程
@@ -287,8 +339,7 @@ pub enum OpCode {
     GoSpawn = 140,
     
     /// 创建 Channel
-    /// 操作数: 容量 (u16)
-    /// 栈: [...] -> [..., channel]
+    /// 栈: [..., capacity] -> [..., channel]（容量为 0 表示无缓冲）
     ChannelNew = 141,
     
     /// Channel 发送（阻塞）
@@ -352,12 +403,12 @@ pub enum OpCode {
     /// 栈: [..., select_builder] -> [..., select_builder]
     SelectAddDefault = 168,
     
-    /// Select 执行（阻塞）
-    /// 栈: [..., select_builder] -> [..., result_type, case_index, value?]
+    /// Select 执行（阻塞，有 default 分支时不阻塞）
+    /// 栈: [..., select_builder] -> [..., value, case_index]
     SelectExec = 169,
     
-    /// Select 尝试执行（非阻塞）
-    /// 栈: [..., select_builder] -> [..., result_type, case_index, value?]
+    /// Select 尝试执行（非阻塞，没有分支就绪且没有 default 时 case_index 为 -1）
+    /// 栈: [..., select_builder] -> [..., value, case_index]
     SelectTryExec = 170,
     
     // ============ 枚举操作 (175-180) ============
@@ -389,8 +440,8 @@ pub enum OpCode {
     /// 栈: [..., enum_value] -> [..., field_value]
     EnumGetField = 185,
     
-    /// 枚举匹配（检查是否为指定变体）
-    /// 操作数: variant_name_idx (u16)
+    /// 枚举匹配（检查是否为指定枚举的指定变体）
+    /// 操作数: enum_name_idx (u16), variant_name_idx (u16)
     /// 栈: [..., enum_value] -> [..., is_match:bool]
     EnumMatch = 186,
     
@@ -440,6 +491,21 @@ pub enum OpCode {
     /// 栈: [..., arg1, ..., argN] -> [..., result]
     RecursiveCall = 208,
     
+    /// 局部变量与常量比较后跳转（大于）
+    /// 操作数: slot (u8), const_val (i8), offset (i16)
+    /// 栈: [...] -> [...] (跳转或继续)
+    JumpIfLocalGtConst = 209,
+    
+    /// 局部变量与常量比较后跳转（大于等于）
+    /// 操作数: slot (u8), const_val (i8), offset (i16)
+    /// 栈: [...] -> [...] (跳转或继续)
+    JumpIfLocalGeConst = 210,
+    
+    /// 比较栈顶两个值后按结果跳转，不生成布尔值
+    /// 操作数: 条件 (u8，见 [`Comparison`]), offset (u16)
+    /// 栈: [..., a, b] -> [...] (跳转或继续)
+    CompareJump = 211,
+    
     // ============ 控制 ============
     /// 停止执行
     Halt = 255,
@@ -448,7 +514,18 @@ pub enum OpCode {
 impl From<u8> for OpCode {
     #[inline(always)]
     fn from(value: u8) -> Self {
-        match value {
+        match OpCode::from_byte(value) {
+            Some(op) => op,
+            None => panic!("Unknown opcode: {}", value),
+        }
+    }
+}
+
+impl OpCode {
+    /// 从字节解码操作码，未知字节返回 None
+    #[inline(always)]
+    pub fn from_byte(value: u8) -> Option<Self> {
+        Some(match value {
             0 => OpCode::Const,
             1 => OpCode::Pop,
             10 => OpCode::Add,
@@ -476,6 +553,9 @@ impl From<u8> for OpCode {
             52 => OpCode::GetUpvalue,
             53 => OpCode::SetUpvalue,
             54 => OpCode::CloseUpvalue,
+            55 => OpCode::MakeCell,
+            56 => OpCode::GetCell,
+            57 => OpCode::SetCell,
             60 => OpCode::Jump,
             61 => OpCode::JumpIfFalse,
             62 => OpCode::JumpIfTrue,
@@ -493,16 +573,22 @@ impl From<u8> for OpCode {
             79 => OpCode::NewRangeInclusive,
             90 => OpCode::IterInit,
             91 => OpCode::IterNext,
+            112 => OpCode::UnpackPair,
+            113 => OpCode::HostCall,
+            114 => OpCode::Hexdump,
+            115 => OpCode::JumpTable,
             92 => OpCode::NewStruct,
             93 => OpCode::GetField,
             94 => OpCode::SetField,
             95 => OpCode::InvokeMethod,
             96 => OpCode::NewClass,
+            118 => OpCode::InitField,
             97 => OpCode::GetStatic,
             98 => OpCode::SetStatic,
             99 => OpCode::InvokeStatic,
             100 => OpCode::InvokeSuper,
             101 => OpCode::Dup,
+            109 => OpCode::Dup2,
             102 => OpCode::JumpIfNull,
             103 => OpCode::SafeGetField,
             104 => OpCode::NonNullGetField,
@@ -510,8 +596,10 @@ impl From<u8> for OpCode {
             106 => OpCode::NonNullInvokeMethod,
             80 => OpCode::Closure,
             81 => OpCode::Call,
+            108 => OpCode::CallStdlib,
             82 => OpCode::Return,
             83 => OpCode::ToString,
+            107 => OpCode::BuildString,
             84 => OpCode::CastSafe,
             85 => OpCode::CastForce,
             86 => OpCode::TypeCheck,
@@ -527,6 +615,10 @@ impl From<u8> for OpCode {
             171 => OpCode::ArraySlice,
             110 => OpCode::SetupTry,
             111 => OpCode::Throw,
+            116 => OpCode::PopTry,
+            117 => OpCode::SetupRecover,
+            119 => OpCode::SetupFinally,
+            147 => OpCode::ResumeExit,
             // 专用整数指令
             120 => OpCode::AddInt,
             121 => OpCode::SubInt,
@@ -548,6 +640,7 @@ impl From<u8> for OpCode {
             136 => OpCode::DecInt,
             137 => OpCode::GetLocalLeInt,
             138 => OpCode::ReturnIf,
+            139 => OpCode::JumpIfTruePop,
             // 并发指令
             140 => OpCode::GoSpawn,
             141 => OpCode::ChannelNew,
@@ -586,12 +679,180 @@ impl From<u8> for OpCode {
             206 => OpCode::ReturnInt,
             207 => OpCode::LoadLocals2,
             208 => OpCode::RecursiveCall,
+            209 => OpCode::JumpIfLocalGtConst,
+            210 => OpCode::JumpIfLocalGeConst,
+            211 => OpCode::CompareJump,
             255 => OpCode::Halt,
-            _ => panic!("Unknown opcode: {}", value),
+            _ => return None,
+        })
+    }
+    
+    /// 指令的操作数布局
+    ///
+    /// 反汇编和执行追踪都按这张表解码，指令长度以此为准；
+    /// 新增或修改指令操作数时必须与 VM 的读取顺序保持一致。
+    pub fn operands(self) -> &'static [OperandKind] {
+        use OperandKind::*;
+        match self {
+            OpCode::Const | OpCode::Closure
+            | OpCode::CastSafe | OpCode::CastForce | OpCode::TypeCheck
+            | OpCode::GetField | OpCode::SetField | OpCode::InitField
+            | OpCode::SafeGetField | OpCode::NonNullGetField
+            | OpCode::EnumGetField => &[Const],
+            
+            OpCode::GetLocal | OpCode::SetLocal | OpCode::GetLocalInt
+            | OpCode::GetUpvalue | OpCode::SetUpvalue | OpCode::CloseUpvalue
+            | OpCode::MakeCell | OpCode::GetCell | OpCode::SetCell
+            | OpCode::BuildString | OpCode::NewArray | OpCode::NewMap | OpCode::NewSet => &[U16],
+            
+            OpCode::Jump | OpCode::JumpIfFalse | OpCode::JumpIfTrue
+            | OpCode::JumpIfNull | OpCode::JumpIfFalsePop | OpCode::JumpIfTruePop => &[Jump],
+            OpCode::Loop => &[Loop],
+            OpCode::SetupTry | OpCode::SetupRecover | OpCode::SetupFinally => &[Offset],
+            
+            OpCode::Call | OpCode::TailCall | OpCode::RecursiveCall | OpCode::GoSpawn
+            | OpCode::CallWithLocal | OpCode::ReturnLocal | OpCode::SelectBegin => &[U8],
+            OpCode::ConstInt8 | OpCode::ReturnInt => &[I8],
+            
+            OpCode::NewStruct => &[U8, Const],
+            OpCode::HostCall => &[U16, U8],
+            OpCode::Hexdump => &[U8, U8],
+            // 后面还有 count + 1 个跳转偏移，见 Chunk::decode_instruction
+            OpCode::JumpTable => &[Const, U16],
+            OpCode::InvokeMethod | OpCode::SafeInvokeMethod | OpCode::NonNullInvokeMethod
+            | OpCode::NewClass => &[Const, U8],
+            OpCode::GetStatic | OpCode::SetStatic
+            | OpCode::NewEnumSimple | OpCode::NewEnumValue | OpCode::EnumMatch => &[Const, Const],
+            OpCode::InvokeStatic | OpCode::InvokeSuper | OpCode::CallStdlib | OpCode::NewEnumFields => &[Const, Const, U8],
+            
+            OpCode::GetLocalAddInt | OpCode::GetLocalSubInt | OpCode::GetLocalLeInt => &[U16, I8],
+            OpCode::AddLocals | OpCode::SubLocals | OpCode::LoadLocals2 => &[U8, U8],
+            OpCode::JumpIfLocalLeConst | OpCode::JumpIfLocalLtConst
+            | OpCode::JumpIfLocalGtConst | OpCode::JumpIfLocalGeConst => &[U8, I8, Offset],
+            OpCode::CompareJump => &[Comparison, Jump],
+            
+            _ => &[],
+        }
+    }
+}
+
+/// 操作数编码方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperandKind {
+    /// 无符号字节（参数数量、u8 槽位等）
+    U8,
+    /// 有符号字节（小整数常量）
+    I8,
+    /// 16 位无符号整数（槽位、元素数量等）
+    U16,
+    /// 常量池索引 (u16)
+    Const,
+    /// 向前跳转偏移 (u16)，相对于下一条指令
+    Jump,
+    /// 向后跳转偏移 (u16)，相对于下一条指令
+    Loop,
+    /// 有符号跳转偏移 (i16)，相对于下一条指令
+    Offset,
+    /// [`CompareJump`](OpCode::CompareJump) 的条件 (u8)
+    Comparison,
+}
+
+impl OperandKind {
+    /// 编码字节数
+    pub fn size(self) -> usize {
+        match self {
+            OperandKind::U8 | OperandKind::I8 | OperandKind::Comparison => 1,
+            _ => 2,
+        }
+    }
+}
+
+/// [`CompareJump`](OpCode::CompareJump) 的比较运算
+///
+/// 条件操作数的低位是比较运算，最高位为 1 时比较结果为真跳转，为 0 时为假跳转
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Lt = 0,
+    Le = 1,
+    Gt = 2,
+    Ge = 3,
+    Eq = 4,
+    Ne = 5,
+}
+
+impl Comparison {
+    const JUMP_WHEN_TRUE: u8 = 0x80;
+
+    /// 编码为条件操作数
+    pub fn encode(self, jump_when: bool) -> u8 {
+        self as u8 | if jump_when { Self::JUMP_WHEN_TRUE } else { 0 }
+    }
+
+    /// 解码条件操作数，返回比较运算和跳转时的比较结果
+    #[inline(always)]
+    pub fn decode(byte: u8) -> Option<(Self, bool)> {
+        let comparison = match byte & !Self::JUMP_WHEN_TRUE {
+            0 => Comparison::Lt,
+            1 => Comparison::Le,
+            2 => Comparison::Gt,
+            3 => Comparison::Ge,
+            4 => Comparison::Eq,
+            5 => Comparison::Ne,
+            _ => return None,
+        };
+        Some((comparison, byte & Self::JUMP_WHEN_TRUE != 0))
+    }
+
+    /// 源代码中的运算符
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+            Comparison::Eq => "==",
+            Comparison::Ne => "!=",
         }
     }
 }
 
+/// 解码后的指令
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    /// 指令在代码中的偏移
+    pub offset: usize,
+    pub opcode: OpCode,
+    /// 操作数（按编码顺序）
+    pub operands: Vec<(OperandKind, i64)>,
+    /// 指令总长度（含操作码）
+    pub len: usize,
+}
+
+impl Instruction {
+    /// 跳转类操作数对应的目标地址
+    pub fn jump_target(&self, kind: OperandKind, value: i64) -> Option<usize> {
+        let next = (self.offset + self.len) as i64;
+        match kind {
+            OperandKind::Jump | OperandKind::Offset => Some((next + value) as usize),
+            OperandKind::Loop => Some((next - value) as usize),
+            _ => None,
+        }
+    }
+}
+
+/// 函数体在代码中的范围，用于把指令地址映射回函数名
+#[derive(Debug, Clone)]
+pub struct FunctionRange {
+    /// 函数名（方法为 `Type::method`，闭包为 `<closure>`）
+    pub name: String,
+    /// 函数体起始偏移
+    pub start: usize,
+    /// 函数体结束偏移（不含）
+    pub end: usize,
+    /// 函数的属性（`no_optimize`、`inline(never)`），反汇编时显示在函数名之后
+    pub attributes: Vec<String>,
+}
+
 /// struct/class 方法信息
 #[derive(Debug, Clone)]
 pub struct MethodInfo {
@@ -682,9 +943,14 @@ pub struct TypeInfo {
     pub is_abstract: bool,
     /// 抽象方法列表（方法名列表）
     pub abstract_methods: Vec<String>,
+    /// 直接实现的 interface 和使用的 trait（不含从父类继承的）
+    pub traits: Vec<String>,
 }
 
 /// 字节码块
+///
+/// 编译完成后不再修改：运行时以 `Arc<Chunk>` 在协程、回调和多个虚拟机之间共享，
+/// 所有查找都返回借用，静态字段等运行时状态保存在各自的虚拟机中
 #[derive(Debug, Clone, Default)]
 pub struct Chunk {
     /// 字节码指令
@@ -705,6 +971,36 @@ pub struct Chunk {
     pub named_functions: std::collections::HashMap<String, u16>,
     /// 命名函数信息表（函数名 -> (常量池索引, 参数名列表)）
     pub named_function_infos: std::collections::HashMap<String, NamedFunctionInfo>,
+    /// 函数体范围表（按注册顺序，嵌套的闭包排在外层函数之前）
+    pub function_ranges: Vec<FunctionRange>,
+    /// 源文件路径表（多文件程序，单文件或 REPL 时为空）
+    pub files: Vec<String>,
+    /// 文件切换点：(代码起始偏移, files 下标)，按偏移递增
+    pub file_starts: Vec<(usize, usize)>,
+    /// 源码位置表（指令之后的偏移 -> 以该指令结束的最内层表达式的位置）
+    ///
+    /// 调用指令之后的偏移就是返回地址，栈追踪用它找到调用表达式；
+    /// 其他指令出错时 ip 也停在指令之后，用它找到出错的表达式
+    pub spans: std::collections::HashMap<usize, SourceSpan>,
+    /// 引用的宿主函数名（`host.readConfig`），HostCall 的操作数是这里的下标，运行前按名字链接
+    pub host_functions: Vec<String>,
+}
+
+// 共享的字节码块必须能在线程间只读访问
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Chunk>();
+};
+
+/// 表达式在源码中的位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceSpan {
+    /// 起始行
+    pub line: usize,
+    /// 起始列（从 1 开始，按字符计数）
+    pub column: usize,
+    /// 表达式的字节长度
+    pub len: usize,
 }
 
 /// 命名函数信息
@@ -736,9 +1032,12 @@ impl Chunk {
     /// 添加常量并返回索引
     pub fn add_constant(&mut self, value: Value) -> u16 {
         // 检查是否已存在相同的常量
-        for (i, v) in self.constants.iter().enumerate() {
-            if v == &value {
-                return i as u16;
+        // null 不复用：预留的函数槽位在回填前也是 null，复用会让 null 字面量变成后来回填的函数
+        if !value.is_null() {
+            for (i, v) in self.constants.iter().enumerate() {
+                if v == &value {
+                    return i as u16;
+                }
             }
         }
         
@@ -789,6 +1088,24 @@ impl Chunk {
         self.write((slot & 0xFF) as u8, line);
     }
     
+    /// 写入把局部变量提升为 cell 的指令
+    pub fn write_make_cell(&mut self, slot: usize, line: usize) {
+        self.write_op(OpCode::MakeCell, line);
+        self.write_u16(slot as u16, line);
+    }
+    
+    /// 写入读取 cell 变量的指令
+    pub fn write_get_cell(&mut self, slot: usize, line: usize) {
+        self.write_op(OpCode::GetCell, line);
+        self.write_u16(slot as u16, line);
+    }
+    
+    /// 写入设置 cell 变量的指令
+    pub fn write_set_cell(&mut self, slot: usize, line: usize) {
+        self.write_op(OpCode::SetCell, line);
+        self.write_u16(slot as u16, line);
+    }
+    
     /// 写入跳转指令，返回跳转地址用于后续回填
     pub fn write_jump(&mut self, op: OpCode, line: usize) -> usize {
         self.write_op(op, line);
@@ -797,29 +1114,73 @@ impl Chunk {
         self.code.len() - 2 // 返回偏移量的位置
     }
     
+    /// 写入跳转表指令，表项先填 0，返回第一个表项的位置
+    ///
+    /// 值为 min + i 时跳到第 i 项，表项共 count + 1 个，最后一个是 default
+    pub fn write_jump_table(&mut self, min: i128, count: u16, line: usize) -> usize {
+        let index = self.add_constant(Value::int(min));
+        self.write_op(OpCode::JumpTable, line);
+        self.write_u16(index, line);
+        self.write_u16(count, line);
+        let table = self.code.len();
+        for _ in 0..=count {
+            self.write_u16(0, line);
+        }
+        table
+    }
+    
+    /// 回填跳转表的第 entry 项，跳到当前位置
+    ///
+    /// 偏移量超出 u16 时不修改代码，返回需要跳过的字节数，由编译器报告错误
+    pub fn patch_jump_table(&mut self, table: usize, count: u16, entry: usize) -> Result<(), usize> {
+        let table_end = table + (count as usize + 1) * 2;
+        let jump = self.code.len() - table_end;
+        let jump = u16::try_from(jump).map_err(|_| jump)?;
+        self.code[table + entry * 2..table + entry * 2 + 2].copy_from_slice(&jump.to_be_bytes());
+        Ok(())
+    }
+    
     /// 回填跳转偏移量
-    pub fn patch_jump(&mut self, offset: usize) {
+    ///
+    /// 偏移量超出 u16 时不修改代码，返回需要跳过的字节数，由编译器报告错误
+    pub fn patch_jump(&mut self, offset: usize) -> Result<(), usize> {
         let jump = self.code.len() - offset - 2;
         
         if jump > u16::MAX as usize {
-            panic!("Jump too large");
+            return Err(jump);
         }
         
         self.code[offset] = ((jump >> 8) & 0xFF) as u8;
         self.code[offset + 1] = (jump & 0xFF) as u8;
+        Ok(())
+    }
+    
+    /// 回填有符号（i16）的跳转偏移量，用于 JumpIfLocal*Const
+    ///
+    /// 偏移量超出 i16 时不修改代码，返回需要跳过的字节数
+    pub fn patch_signed_jump(&mut self, offset: usize) -> Result<(), usize> {
+        let jump = self.code.len() - offset - 2;
+        if jump > i16::MAX as usize {
+            return Err(jump);
+        }
+        self.patch_jump(offset)
     }
     
     /// 写入循环指令（向后跳转）
-    pub fn write_loop(&mut self, loop_start: usize, line: usize) {
+    ///
+    /// 偏移量超出 u16 时仍写入指令（操作数无效），返回需要回跳的字节数，由编译器报告错误
+    pub fn write_loop(&mut self, loop_start: usize, line: usize) -> Result<(), usize> {
         self.write_op(OpCode::Loop, line);
         
         let offset = self.code.len() - loop_start + 2;
+        let operand = offset.min(u16::MAX as usize);
+        self.write(((operand >> 8) & 0xFF) as u8, line);
+        self.write((operand & 0xFF) as u8, line);
+        
         if offset > u16::MAX as usize {
-            panic!("Loop body too large");
+            return Err(offset);
         }
-        
-        self.write(((offset >> 8) & 0xFF) as u8, line);
-        self.write((offset & 0xFF) as u8, line);
+        Ok(())
     }
     
     /// 写入函数调用指令
@@ -877,6 +1238,33 @@ impl Chunk {
         self.code.len() - 2
     }
     
+    /// 写入条件跳转并弹出（为真时跳转）
+    pub fn write_jump_if_true_pop(&mut self, line: usize) -> usize {
+        self.write_op(OpCode::JumpIfTruePop, line);
+        self.write(0xFF, line);
+        self.write(0xFF, line);
+        self.code.len() - 2
+    }
+    
+    /// 写入比较并跳转，返回偏移量的位置
+    pub fn write_compare_jump(&mut self, comparison: Comparison, jump_when: bool, line: usize) -> usize {
+        self.write_op(OpCode::CompareJump, line);
+        self.write(comparison.encode(jump_when), line);
+        self.write(0xFF, line);
+        self.write(0xFF, line);
+        self.code.len() - 2
+    }
+    
+    /// 写入超级指令：局部变量与小整数常量比较后跳转（op 为 JumpIfLocal*Const），返回偏移量的位置
+    pub fn write_jump_if_local_const(&mut self, op: OpCode, slot: u8, value: i8, line: usize) -> usize {
+        self.write_op(op, line);
+        self.write(slot, line);
+        self.write(value as u8, line);
+        self.write(0xFF, line);
+        self.write(0xFF, line);
+        self.code.len() - 2
+    }
+    
     /// 写入超级指令：两个局部变量相加（整数）
     /// 操作数: slot1 (u8), slot2 (u8)
     pub fn write_add_locals(&mut self, slot1: u8, slot2: u8, line: usize) {
@@ -935,6 +1323,7 @@ impl Chunk {
                 is_class: false,
                 is_abstract: false,
                 abstract_methods: Vec::new(),
+                traits: Vec::new(),
             });
         }
     }
@@ -958,6 +1347,7 @@ impl Chunk {
                 is_class: true,
                 is_abstract,
                 abstract_methods: Vec::new(),
+                traits: Vec::new(),
             });
         }
     }
@@ -976,6 +1366,15 @@ impl Chunk {
         }
     }
     
+    /// 记录类型实现了某个 interface 或使用了某个 trait
+    pub fn register_trait_impl(&mut self, type_name: &str, trait_name: String) {
+        if let Some(type_info) = self.types.get_mut(type_name) {
+            if !type_info.traits.contains(&trait_name) {
+                type_info.traits.push(trait_name);
+            }
+        }
+    }
+    
     /// 注册 interface
     pub fn register_interface(&mut self, name: String, methods: Vec<InterfaceMethodInfo>) {
         if !self.interfaces.contains_key(&name) {
@@ -1073,6 +1472,17 @@ impl Chunk {
         }
     }
     
+    /// 实例的全部字段：从最远的祖先类开始，每个类按注册顺序
+    pub fn instance_fields(&self, type_name: &str) -> Vec<&String> {
+        let mut chain = Vec::new();
+        let mut current = self.types.get(type_name);
+        while let Some(type_info) = current {
+            chain.push(type_info);
+            current = type_info.parent.as_deref().and_then(|parent| self.types.get(parent));
+        }
+        chain.iter().rev().flat_map(|type_info| &type_info.fields).collect()
+    }
+    
     /// 获取类型的静态方法函数索引
     pub fn get_static_method(&self, type_name: &str, method_name: &str) -> Option<u16> {
         self.types.get(type_name)
@@ -1108,51 +1518,208 @@ impl Chunk {
     pub fn get_named_function_info(&self, name: &str) -> Option<&NamedFunctionInfo> {
         self.named_function_infos.get(name)
     }
+    
+    /// 注册函数体范围
+    pub fn register_function_range(&mut self, name: String, start: usize, end: usize) {
+        self.register_function_range_with_attributes(name, start, end, Vec::new());
+    }
+    
+    /// 注册带属性的函数体范围
+    pub fn register_function_range_with_attributes(&mut self, name: String, start: usize, end: usize, attributes: Vec<String>) {
+        self.function_ranges.push(FunctionRange { name, start, end, attributes });
+    }
+    
+    /// 之后写入的代码属于源文件 `path`
+    pub fn begin_file(&mut self, path: &str) {
+        let index = match self.files.iter().position(|f| f == path) {
+            Some(index) => index,
+            None => {
+                self.files.push(path.to_string());
+                self.files.len() - 1
+            }
+        };
+        self.file_starts.push((self.code.len(), index));
+    }
+    
+    /// 指令地址 ip 所在的源文件；没有文件信息时返回 None
+    pub fn file_at(&self, ip: usize) -> Option<&str> {
+        let pos = self.file_starts.partition_point(|&(start, _)| start <= ip);
+        let (_, index) = self.file_starts.get(pos.checked_sub(1)?)?;
+        Some(self.files[*index].as_str())
+    }
+    
+    /// 宿主函数名在 host_functions 中的下标，第一次引用时加入
+    pub fn add_host_function(&mut self, name: &str) -> u16 {
+        let index = match self.host_functions.iter().position(|n| n == name) {
+            Some(index) => index,
+            None => {
+                self.host_functions.push(name.to_string());
+                self.host_functions.len() - 1
+            }
+        };
+        index as u16
+    }
+    
+    /// 记录刚写入的指令所属的表达式位置
+    ///
+    /// 内层表达式先编译，同一条指令已经有位置时保留内层的
+    pub fn mark_span(&mut self, line: usize, column: usize, len: usize) {
+        self.spans.entry(self.code.len()).or_insert(SourceSpan { line, column, len });
+    }
+    
+    /// 以 end 为结束偏移的指令所属的表达式位置（调用指令的 end 即返回地址）
+    pub fn span_at(&self, end: usize) -> Option<SourceSpan> {
+        self.spans.get(&end).copied()
+    }
+    
+    /// 查找包含指令地址 ip 的最内层函数；顶层代码返回 None
+    pub fn function_at(&self, ip: usize) -> Option<&str> {
+        // 范围要么嵌套要么不相交，包含 ip 且起点最大的就是最内层
+        self.function_ranges
+            .iter()
+            .filter(|r| r.start <= ip && ip < r.end)
+            .max_by_key(|r| r.start)
+            .map(|r| r.name.as_str())
+    }
+    
+    /// 解码 offset 处的指令；操作码未知或操作数越界时返回 None
+    pub fn decode_instruction(&self, offset: usize) -> Option<Instruction> {
+        let opcode = OpCode::from_byte(*self.code.get(offset)?)?;
+        let mut operands = Vec::new();
+        let mut pos = offset + 1;
+        for &kind in opcode.operands() {
+            let bytes = self.code.get(pos..pos + kind.size())?;
+            let value = match kind {
+                OperandKind::U8 | OperandKind::Comparison => bytes[0] as i64,
+                OperandKind::I8 => bytes[0] as i8 as i64,
+                OperandKind::Offset => i16::from_be_bytes([bytes[0], bytes[1]]) as i64,
+                _ => u16::from_be_bytes([bytes[0], bytes[1]]) as i64,
+            };
+            operands.push((kind, value));
+            pos += kind.size();
+        }
+        // 跳转表的表项跟在固定操作数之后，表项数是第二个操作数
+        if opcode == OpCode::JumpTable {
+            for _ in 0..=operands[1].1 {
+                let bytes = self.code.get(pos..pos + 2)?;
+                operands.push((OperandKind::Jump, u16::from_be_bytes([bytes[0], bytes[1]]) as i64));
+                pos += 2;
+            }
+        }
+        Some(Instruction { offset, opcode, operands, len: pos - offset })
+    }
+    
+    /// 格式化指令的操作码和操作数（不含偏移和行号）
+    ///
+    /// 常量池操作数附带常量值，跳转操作数附带目标地址：
+    /// `Const 3 ("hi")`、`JumpIfFalse 7 -> 0042`
+    pub fn format_instruction(&self, instruction: &Instruction) -> String {
+        let mut text = format!("{:?}", instruction.opcode);
+        for &(kind, value) in &instruction.operands {
+            text.push(' ');
+            text.push_str(&value.to_string());
+            match kind {
+                OperandKind::Const => {
+                    if let Some(constant) = self.constants.get(value as usize) {
+                        match constant.as_string() {
+                            Some(s) => text.push_str(&format!(" ({:?})", s)),
+                            None => text.push_str(&format!(" ({})", constant)),
+                        }
+                    }
+                }
+                OperandKind::Jump | OperandKind::Loop | OperandKind::Offset => {
+                    if let Some(target) = instruction.jump_target(kind, value) {
+                        text.push_str(&format!(" -> {:04}", target));
+                    }
+                }
+                OperandKind::Comparison => {
+                    if let Some((comparison, jump_when)) = Comparison::decode(value as u8) {
+                        text.push_str(&format!(" ({} {})", comparison.symbol(), jump_when));
+                    }
+                }
+                _ => {}
+            }
+        }
+        text
+    }
 }
 
 impl fmt::Display for Chunk {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "=== Chunk ===")?;
-        writeln!(f, "Constants:")?;
-        for (i, c) in self.constants.iter().enumerate() {
-            writeln!(f, "  {}: {}", i, c)?;
-        }
-        writeln!(f, "Code:")?;
-        
-        let mut offset = 0;
-        while offset < self.code.len() {
-            offset = self.disassemble_instruction(f, offset)?;
-        }
-        
-        Ok(())
+        f.write_str(&self.disassemble())
     }
 }
 
 impl Chunk {
-    /// 反汇编单条指令
-    fn disassemble_instruction(&self, f: &mut fmt::Formatter<'_>, offset: usize) -> Result<usize, fmt::Error> {
-        write!(f, "{:04} ", offset)?;
+    /// 反汇编整个代码块（`run --emit=bytecode`）
+    ///
+    /// 每条指令一行：偏移、跳转目标标记 `>`、源代码行号（与上一条相同时为 `|`）、指令和操作数。
+    /// 常量池操作数附带常量值，跳转指令附带 `-> 目标`，跳转目标行末尾列出跳转来源 `<- 来源`。
+    /// 函数体开始处输出 `-- 函数名 --`。
+    ///
+    /// ```text
+    /// 0012    3  JumpIfFalsePop 5 -> 0020
+    /// 0015    |  ConstInt8 1
+    /// 0020 >  4  GetLocal 0                          <- 0012
+    /// ```
+    pub fn disassemble(&self) -> String {
+        use std::fmt::Write;
         
-        // 显示行号
-        if offset > 0 && self.lines[offset] == self.lines[offset - 1] {
-            write!(f, "   | ")?;
-        } else {
-            write!(f, "{:4} ", self.lines[offset])?;
+        // 先解码全部指令，收集跳转来源
+        let mut instructions = Vec::new();
+        let mut sources: std::collections::HashMap<usize, Vec<usize>> = std::collections::HashMap::new();
+        let mut offset = 0;
+        while offset < self.code.len() {
+            let instruction = self.decode_instruction(offset);
+            let len = match &instruction {
+                Some(instruction) => {
+                    for &(kind, value) in &instruction.operands {
+                        if let Some(target) = instruction.jump_target(kind, value) {
+                            sources.entry(target).or_default().push(offset);
+                        }
+                    }
+                    instruction.len
+                }
+                None => 1,
+            };
+            instructions.push((offset, instruction));
+            offset += len;
         }
         
-        let instruction = OpCode::from(self.code[offset]);
+        let mut out = String::new();
+        let _ = writeln!(out, "== constants ==");
+        for (i, constant) in self.constants.iter().enumerate() {
+            match constant.as_string() {
+                Some(s) => { let _ = writeln!(out, "{:5}  {:?}", i, s); }
+                None => { let _ = writeln!(out, "{:5}  {}", i, constant); }
+            }
+        }
+        let _ = writeln!(out, "== code ==");
         
-        match instruction {
-            OpCode::Const => {
-                let index = ((self.code[offset + 1] as u16) << 8) | (self.code[offset + 2] as u16);
-                let value = &self.constants[index as usize];
-                writeln!(f, "CONST {:5} ({})", index, value)?;
-                Ok(offset + 3)
+        let mut previous_line = None;
+        for (offset, instruction) in instructions {
+            for range in self.function_ranges.iter().filter(|r| r.start == offset) {
+                let attributes: String = range.attributes.iter().map(|a| format!(" @{}", a)).collect();
+                let _ = writeln!(out, "-- {}{} --", range.name, attributes);
+                previous_line = None;
             }
-            _ => {
-                writeln!(f, "{:?}", instruction)?;
-                Ok(offset + 1)
+            
+            let line = self.lines.get(offset).copied().unwrap_or(0);
+            let line_text = if previous_line == Some(line) { "|".to_string() } else { line.to_string() };
+            previous_line = Some(line);
+            let marker = if sources.contains_key(&offset) { '>' } else { ' ' };
+            let text = match &instruction {
+                Some(instruction) => self.format_instruction(instruction),
+                None => format!("<invalid {}>", self.code[offset]),
+            };
+            
+            let mut row = format!("{:04} {} {:>4}  {}", offset, marker, line_text, text);
+            if let Some(from) = sources.get(&offset) {
+                let from: Vec<String> = from.iter().map(|o| format!("{:04}", o)).collect();
+                row = format!("{:<48} <- {}", row, from.join(", "));
             }
+            let _ = writeln!(out, "{}", row);
         }
+        out
     }
 }
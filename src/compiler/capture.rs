@@ -0,0 +1,419 @@
+//! 闭包捕获分析
+//!
+//! 编译一个函数体之前先扫描一遍语法树，找出被内层闭包引用的外部变量名，以及被赋值过的变量名
+//! （包括内层闭包中的赋值）。既被捕获又会被赋值的局部变量在声明处提升为堆上的 cell（`MakeCell`），
+//! 声明它的函数和所有闭包都通过 `GetCell` / `SetCell` 读写同一个 cell；只被读取的变量在创建闭包时按值复制，
+//! 仍然是普通的槽位，可以继续使用 `GetLocalAddInt` 等融合指令。
+//!
+//! 分析按名字进行，不区分同名的不同变量：多标记的变量只是多分配一个 cell，不影响结果。
+
+use std::collections::HashSet;
+
+use crate::parser::ast::{Expr, FnParam, MatchPattern, SelectCaseKind, Stmt, StringInterpPart};
+
+/// 一个函数体的捕获分析结果
+#[derive(Debug, Clone, Default)]
+pub struct Captures {
+    /// 被内层闭包引用、但不是闭包自己声明的变量名
+    pub referenced: HashSet<String>,
+    /// 被赋值过的变量名
+    pub assigned: HashSet<String>,
+}
+
+impl Captures {
+    /// 分析函数体或顶层语句；类型和函数定义是独立的函数，不在这里扫描
+    pub fn analyze(statements: &[Stmt]) -> Self {
+        let mut walker = Walker::default();
+        walker.scoped(|w| w.stmts(statements));
+        walker.captures
+    }
+
+    /// 分析一个函数体
+    pub fn analyze_body(body: &Stmt) -> Self {
+        Self::analyze(std::slice::from_ref(body))
+    }
+
+    /// 变量是否需要提升为 cell
+    pub fn needs_cell(&self, name: &str) -> bool {
+        self.referenced.contains(name) && self.assigned.contains(name)
+    }
+}
+
+/// 闭包引用的外部变量名，按第一次出现的顺序排列
+///
+/// `this` 和 `super` 记为对 `this` 的引用。
+pub fn free_variables(params: &[FnParam], body: &Stmt) -> Vec<String> {
+    let mut walker = Walker::default();
+    walker.closure(params, body);
+    walker.free
+}
+
+#[derive(Default)]
+struct Walker {
+    /// 作用域栈，每层是其中声明的名字
+    scopes: Vec<HashSet<String>>,
+    /// 最外层闭包的第一个作用域；None 表示正在扫描函数自身的代码
+    closure_base: Option<usize>,
+    captures: Captures,
+    /// 最外层闭包的外部变量，按出现顺序
+    free: Vec<String>,
+}
+
+impl Walker {
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(HashSet::new());
+        f(self);
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string());
+        }
+    }
+
+    fn reference(&mut self, name: &str) {
+        let Some(base) = self.closure_base else { return };
+        if self.scopes[base..].iter().any(|scope| scope.contains(name)) {
+            return;
+        }
+        if self.captures.referenced.insert(name.to_string()) {
+            self.free.push(name.to_string());
+        }
+    }
+
+    fn assign(&mut self, name: &str) {
+        self.captures.assigned.insert(name.to_string());
+        self.reference(name);
+    }
+
+    fn closure(&mut self, params: &[FnParam], body: &Stmt) {
+        let outer = self.closure_base;
+        if outer.is_none() {
+            self.closure_base = Some(self.scopes.len());
+        }
+        self.scopes.push(params.iter().map(|p| p.name.clone()).collect());
+        self.stmt(body);
+        self.scopes.pop();
+        self.closure_base = outer;
+    }
+
+    fn stmts(&mut self, statements: &[Stmt]) {
+        for stmt in statements {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expression { expr, .. } | Stmt::Print { expr, .. } | Stmt::Throw { value: expr, .. } => {
+                self.expr(expr);
+            }
+            Stmt::ParallelAssign { targets, values, .. } => {
+                for target in targets {
+                    match target {
+                        Expr::Identifier { name, .. } => self.assign(name),
+                        target => self.expr(target),
+                    }
+                }
+                for value in values {
+                    self.expr(value);
+                }
+            }
+            Stmt::VarDecl { name, initializer, .. } => {
+                if let Some(init) = initializer {
+                    self.expr(init);
+                }
+                self.declare(name);
+            }
+            Stmt::ConstDecl { name, initializer, .. } => {
+                self.expr(initializer);
+                self.declare(name);
+            }
+            Stmt::Block { statements, .. } => self.scoped(|w| w.stmts(statements)),
+            Stmt::If { condition, then_branch, else_branch, .. } => {
+                self.expr(condition);
+                self.stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.stmt(else_branch);
+                }
+            }
+            Stmt::ForLoop { initializer, condition, increment, body, .. } => self.scoped(|w| {
+                if let Some(init) = initializer {
+                    w.stmt(init);
+                }
+                if let Some(condition) = condition {
+                    w.expr(condition);
+                }
+                if let Some(increment) = increment {
+                    w.expr(increment);
+                }
+                w.stmt(body);
+            }),
+            Stmt::ForIn { variables, iterable, body, .. } => {
+                self.expr(iterable);
+                self.scoped(|w| {
+                    for name in variables {
+                        w.declare(name);
+                    }
+                    w.stmt(body);
+                });
+            }
+            Stmt::While { condition, body, .. } => {
+                if let Some(condition) = condition {
+                    self.expr(condition);
+                }
+                self.stmt(body);
+            }
+            Stmt::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+            }
+            Stmt::Match { expr, arms, .. } => {
+                self.expr(expr);
+                for arm in arms {
+                    self.scoped(|w| {
+                        w.pattern(&arm.pattern);
+                        if let Some(guard) = &arm.guard {
+                            w.expr(guard);
+                        }
+                        w.stmt(&arm.body);
+                    });
+                }
+            }
+            Stmt::Select { cases, .. } => {
+                for case in cases {
+                    match &case.kind {
+                        SelectCaseKind::Receive { channel, binding } => {
+                            self.expr(channel);
+                            self.scoped(|w| {
+                                if let Some(name) = binding {
+                                    w.declare(name);
+                                }
+                                w.stmt(&case.body);
+                            });
+                        }
+                        SelectCaseKind::Send { channel, value } => {
+                            self.expr(channel);
+                            self.expr(value);
+                            self.stmt(&case.body);
+                        }
+                        SelectCaseKind::Default => self.stmt(&case.body),
+                    }
+                }
+            }
+            Stmt::Switch { expr, cases, default, .. } => {
+                self.expr(expr);
+                for case in cases {
+                    for value in &case.values {
+                        self.expr(value);
+                    }
+                    self.stmt(&case.body);
+                }
+                if let Some(default) = default {
+                    self.stmt(default);
+                }
+            }
+            Stmt::TryCatch { try_block, catch_param, catch_block, finally_block, .. } => {
+                self.stmt(try_block);
+                self.scoped(|w| {
+                    if let Some(name) = catch_param {
+                        w.declare(name);
+                    }
+                    w.stmt(catch_block);
+                });
+                if let Some(finally_block) = finally_block {
+                    self.stmt(finally_block);
+                }
+            }
+            Stmt::Recovering { body, panic_param, handler, .. } => {
+                self.stmt(body);
+                self.scoped(|w| {
+                    if let Some(name) = panic_param {
+                        w.declare(name);
+                    }
+                    w.stmt(handler);
+                });
+            }
+            Stmt::Break { .. }
+            | Stmt::Continue { .. }
+            | Stmt::StructDef { .. }
+            | Stmt::ClassDef { .. }
+            | Stmt::InterfaceDef { .. }
+            | Stmt::TraitDef { .. }
+            | Stmt::EnumDef { .. }
+            | Stmt::TypeAlias { .. }
+            | Stmt::FnDef { .. }
+            | Stmt::Package { .. }
+            | Stmt::Import { .. } => {}
+        }
+    }
+
+    fn pattern(&mut self, pattern: &MatchPattern) {
+        match pattern {
+            MatchPattern::Literal(expr) => self.expr(expr),
+            MatchPattern::Variable(name) | MatchPattern::Type { name, .. } => self.declare(name),
+            MatchPattern::Or(patterns) | MatchPattern::EnumVariant { fields: patterns, .. } => {
+                patterns.iter().for_each(|p| self.pattern(p))
+            }
+            MatchPattern::Range { start, end, .. } => {
+                self.expr(start);
+                self.expr(end);
+            }
+            MatchPattern::Wildcard => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Identifier { name, .. } => self.reference(name),
+            Expr::This { .. } | Expr::Super { .. } => self.reference("this"),
+            Expr::Assign { target, value, .. } => {
+                match target.as_ref() {
+                    Expr::Identifier { name, .. } => self.assign(name),
+                    target => self.expr(target),
+                }
+                self.expr(value);
+            }
+            Expr::PostIncrement { operand, .. } | Expr::PostDecrement { operand, .. } => match operand.as_ref() {
+                Expr::Identifier { name, .. } => self.assign(name),
+                operand => self.expr(operand),
+            },
+            Expr::Closure { params, body, .. } => self.closure(params, body),
+            Expr::StringInterpolation { parts, .. } => {
+                for part in parts {
+                    if let StringInterpPart::Expr(expr) = part {
+                        self.expr(expr);
+                    }
+                }
+            }
+            Expr::Binary { left, right, .. } | Expr::NullCoalesce { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::Index { object: left, index: right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::Unary { operand: inner, .. }
+            | Expr::Grouping { expr: inner, .. }
+            | Expr::Go { call: inner, .. }
+            | Expr::Member { object: inner, .. }
+            | Expr::SafeMember { object: inner, .. }
+            | Expr::NonNullMember { object: inner, .. }
+            | Expr::Cast { expr: inner, .. }
+            | Expr::TypeCheck { expr: inner, .. } => self.expr(inner),
+            Expr::Call { callee, args, .. } => {
+                self.expr(callee);
+                args.iter().for_each(|(_, arg)| self.expr(arg));
+            }
+            Expr::ChannelNew { capacity, .. } => {
+                if let Some(capacity) = capacity {
+                    self.expr(capacity);
+                }
+            }
+            Expr::Range { start, end, .. } => {
+                for bound in [start, end].into_iter().flatten() {
+                    self.expr(bound);
+                }
+            }
+            Expr::IfExpr { condition, then_branch, else_branch, .. } => {
+                self.expr(condition);
+                self.expr(then_branch);
+                self.expr(else_branch);
+            }
+            Expr::Array { elements, .. } => elements.iter().for_each(|e| self.expr(e)),
+            Expr::New { args, fields, .. } => {
+                args.iter().for_each(|e| self.expr(e));
+                fields.iter().flatten().for_each(|(_, e)| self.expr(e));
+            }
+            Expr::MapLiteral { entries, .. } => {
+                for (key, value) in entries {
+                    self.expr(key);
+                    self.expr(value);
+                }
+            }
+            Expr::StructLiteral { fields, .. } => fields.iter().for_each(|(_, e)| self.expr(e)),
+            Expr::Integer { .. }
+            | Expr::Float { .. }
+            | Expr::String { .. }
+            | Expr::Bool { .. }
+            | Expr::Char { .. }
+            | Expr::Null { .. }
+            | Expr::Default { .. }
+            | Expr::StaticMember { .. } => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::Locale;
+    use crate::lexer::Scanner;
+    use crate::parser::Parser;
+
+    fn main_body(source: &str) -> Stmt {
+        let program = Parser::new(Scanner::new(source).scan_tokens(), Locale::En).parse().unwrap();
+        program.statements.into_iter().find_map(|stmt| match stmt {
+            Stmt::FnDef { name, body, .. } if name == "main" => Some(*body),
+            _ => None,
+        }).unwrap()
+    }
+
+    fn names(set: &HashSet<String>) -> Vec<&str> {
+        let mut names: Vec<&str> = set.iter().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_captured_and_assigned_names() {
+        let body = main_body(r#"
+func main() {
+    var total = 0
+    var step = 2
+    var local = 1
+    var add = func(n: int) {
+        var tmp = n * step
+        total = total + tmp
+    }
+    local = local + 1
+    var nested = func() {
+        var inner = func() int { return step + local }
+        inner()
+    }
+}
+"#);
+        let captures = Captures::analyze(std::slice::from_ref(&body));
+        // 闭包自己的参数和局部变量不算捕获，嵌套闭包引用的外部变量算在外层闭包上
+        assert_eq!(names(&captures.referenced), vec!["local", "step", "total"]);
+        assert!(captures.needs_cell("total"));
+        assert!(captures.needs_cell("local"));
+        assert!(!captures.needs_cell("step"));
+        assert!(!captures.needs_cell("tmp"));
+    }
+
+    #[test]
+    fn test_free_variables_respect_closure_scopes() {
+        let body = main_body(r#"
+func main() {
+    var f = func(a: int) int {
+        var b = a + x
+        for var i = 0; i < b; i = i + 1 {
+            y = i
+        }
+        if b > 0 {
+            var z = 1
+        }
+        return z + b + this.w
+    }
+}
+"#);
+        let Stmt::Block { statements, .. } = &body else { panic!() };
+        let Stmt::VarDecl { initializer: Some(Expr::Closure { params, body, .. }), .. } = &statements[0] else { panic!() };
+        // 块中声明的 z 出了块就不再可见，之后的 z 是外部变量
+        assert_eq!(free_variables(params, body), vec!["x", "y", "z", "this"]);
+    }
+}
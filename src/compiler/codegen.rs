@@ -5,12 +5,19 @@
 use std::sync::Arc;
 use parking_lot::Mutex;
 use crate::parser::{Expr, Stmt, Program, BinOp, UnaryOp};
-use crate::vm::{Value, value::Function};
+use crate::parser::ast::{Attribute, AttributeKind, ClassField, ImportDecl, ImportTarget, MatchPattern, SwitchCase, DISCARD};
+use crate::parser::{const_items, eval_const, fold_consts, ConstEvalError, ConstValue};
+use crate::vm::{Value, BUILTIN_NAMESPACES, HOST_NAMESPACE, value::{Function, UpvalueDescriptor}};
+use crate::diagnostics::{Diagnostic, Stage};
 use crate::i18n::Locale;
 use crate::lexer::Span;
 use crate::types::Type;
-use super::bytecode::{Chunk, OpCode};
-use super::symbol::SymbolTable;
+use crate::typechecker::TypeTable;
+use crate::timings::{self, Phase};
+use crate::stdlib::StdlibRegistry;
+use super::bytecode::{Chunk, Comparison, OpCode};
+use super::capture::{self, Captures};
+use super::symbol::{Definition, DefinitionKind, SymbolTable, TopLevelNames};
 
 /// 编译错误
 #[derive(Debug, Clone)]
@@ -23,8 +30,25 @@ impl CompileError {
     fn new(message: String, span: Span) -> Self {
         Self { message, span }
     }
+
+    /// 转换为结构化的诊断
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic::error(Stage::Compile, self.message.clone()).at(self.span)
+    }
 }
 
+/// 单个函数可用的局部变量槽位上限（GetLocal/SetLocal 的操作数为 u16）
+const MAX_LOCAL_SLOTS: usize = u16::MAX as usize;
+
+/// 单条跳转指令能跨越的字节数上限（Jump/JumpIfFalse/Loop 的偏移量为 u16）
+const MAX_JUMP_DISTANCE: usize = u16::MAX as usize;
+
+/// 单次调用的参数个数上限（Call/InvokeMethod 的参数个数操作数为 u8）
+const MAX_CALL_ARGS: usize = u8::MAX as usize;
+
+/// switch 生成跳转表所需的最少分支值个数，更少时依次比较更快
+const JUMP_TABLE_MIN_VALUES: usize = 4;
+
 /// 尾调用信息（用于尾调用优化）
 struct TailCallInfo {
     /// 被调用的函数表达式
@@ -35,15 +59,30 @@ struct TailCallInfo {
 
 /// 字节码编译器
 /// 循环信息（用于 break/continue）
+#[derive(Clone)]
 struct LoopInfo {
     /// 循环起始位置
     start: usize,
     /// break 跳转位置列表（用于回填）
     breaks: Vec<usize>,
+    /// continue 跳转位置列表：C 风格 for 循环的 continue 先跳到递增部分，其余循环为 None（直接回到起始位置）
+    continues: Option<Vec<usize>>,
+    /// 循环开始时的局部变量槽位数：break/continue 跳出循环体时弹出之后定义的局部变量
+    slots: usize,
     /// 循环标签（可选）
     label: Option<String>,
 }
 
+/// 条件上下文中等待回填的跳转
+#[derive(Debug, Clone, Copy)]
+struct CondJump {
+    /// 偏移量操作数的位置
+    offset: usize,
+    /// 偏移量是有符号的 i16（JumpIfLocal*Const），否则为 u16
+    signed: bool,
+}
+
+#[derive(Clone)]
 pub struct Compiler {
     /// 当前字节码块
     chunk: Chunk,
@@ -62,6 +101,57 @@ pub struct Compiler {
     type_aliases: std::collections::HashMap<String, Type>,
     /// 循环信息栈（支持带标签的 break/continue）
     loop_stack: Vec<LoopInfo>,
+    /// 当前函数中正在编译的 try 块：进入时 loop_stack 的长度。return/break/continue 离开 try 块前移除它的异常处理器
+    try_blocks: Vec<usize>,
+    /// 当前函数中正在编译的 onPanic 块中 panic 值的槽位，`recover()` 读取最内层的一个
+    panic_slots: Vec<usize>,
+    /// 通过 import 引入的标准库函数：函数名 -> 模块名
+    stdlib_functions: std::collections::HashMap<String, String>,
+    /// 编译期常量：顶层 `NAME` 和类型成员 `Type::NAME` -> 值
+    consts: std::collections::HashMap<String, ConstValue>,
+    /// 是否启用优化（`-O`）：常量折叠、删除不可达代码
+    optimize: bool,
+    /// 顶层语句的来源文件：按语句顺序的 (文件路径, 语句数)
+    source_files: Vec<(String, usize)>,
+    /// 可以调用的宿主函数名（`host.readConfig`），命令行运行时为空
+    host_functions: std::collections::HashSet<String>,
+    /// 解析标准库 import 的注册表（嵌入的程序可以注册自己的模块）
+    registry: Arc<StdlibRegistry>,
+    /// 类型检查记录的表达式类型，用于选择整数快速路径；没有设置时为空表
+    type_table: TypeTable,
+    /// 正在编译的顶层语句的下标（类型表中节点的标识）
+    statement: usize,
+    /// 正在编译的函数启用的优化
+    opt: OptFlags,
+    /// 实例化时会执行 init 的类：声明了 init、有实例字段初始化式或父类在这个集合中（没有声明 init 的生成一个）
+    constructed_classes: std::collections::HashSet<String>,
+    /// 程序中的 struct（`new Point { ... }` 按 struct 字面量编译），struct 可以声明在使用之后
+    struct_types: std::collections::HashSet<String>,
+    /// 正在编译 init 的第一条语句，只有这里可以调用 `super.init(...)`
+    in_super_init_call: bool,
+    /// 正在编译的 class 方法所在类的父类（`super` 指向它）
+    super_class: Option<String>,
+}
+
+/// 函数级的优化开关
+///
+/// `@no_optimize` 的函数全部关闭，排查编译器缺陷时可以只让一个函数按最直接的方式编译；
+/// 函数中定义的闭包沿用外层函数的设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct OptFlags {
+    /// 常量折叠、常量条件的分支裁剪和删除不可达代码（还需要 `-O`）
+    fold: bool,
+    /// 超级指令和融合指令（`AddLocals`、`GetLocalAddInt`、`ReturnLocal` 等）
+    fuse: bool,
+    /// `return f(x)` 编译为 `TailCall`
+    tail_calls: bool,
+    /// 分支条件中的比较和逻辑运算直接编译为跳转（测试中单独关闭，与生成布尔值的编译方式对照）
+    condition_jumps: bool,
+}
+
+impl OptFlags {
+    const ALL: Self = Self { fold: true, fuse: true, tail_calls: true, condition_jumps: true };
+    const NONE: Self = Self { fold: false, fuse: false, tail_calls: false, condition_jumps: false };
 }
 
 /// 简单的静态类型（用于优化）
@@ -86,6 +176,55 @@ impl Compiler {
             break_jumps: Vec::new(),
             type_aliases: std::collections::HashMap::new(),
             loop_stack: Vec::new(),
+            try_blocks: Vec::new(),
+            panic_slots: Vec::new(),
+            stdlib_functions: std::collections::HashMap::new(),
+            consts: std::collections::HashMap::new(),
+            optimize: false,
+            source_files: Vec::new(),
+            host_functions: std::collections::HashSet::new(),
+            registry: crate::stdlib::global_registry().clone(),
+            type_table: TypeTable::new(),
+            statement: 0,
+            opt: OptFlags::ALL,
+            constructed_classes: std::collections::HashSet::new(),
+            struct_types: std::collections::HashSet::new(),
+            in_super_init_call: false,
+            super_class: None,
+        }
+    }
+    
+    /// 启用或关闭优化（常量折叠、常量条件的分支裁剪、删除 return 之后的语句）
+    pub fn set_optimize(&mut self, enabled: bool) {
+        self.optimize = enabled;
+    }
+    
+    /// 设置顶层语句的来源文件（按语句顺序的 (文件路径, 语句数)），用于栈追踪显示文件名
+    pub fn set_source_files(&mut self, files: Vec<(String, usize)>) {
+        self.source_files = files;
+    }
+    
+    /// 设置可以调用的宿主函数名，`host.名称(...)` 编译为 HostCall
+    pub fn set_host_functions(&mut self, names: impl IntoIterator<Item = String>) {
+        self.host_functions = names.into_iter().collect();
+    }
+    
+    /// 设置解析标准库 import 的注册表（默认为只有内置模块的全局注册表）
+    pub fn set_registry(&mut self, registry: Arc<StdlibRegistry>) {
+        self.registry = registry;
+    }
+    
+    /// 设置类型检查（和单态化）得到的类型表
+    pub fn set_type_table(&mut self, table: TypeTable) {
+        self.type_table = table;
+    }
+    
+    /// 没有类型标注的局部变量的类型：类型检查确定初始值是 int、并且之后没有被赋过其他类型的值时为 int，
+    /// 读写和运算可以使用整数指令，否则不做假设
+    fn inferred_local_type(&self, name: &str, initializer: Option<&Expr>) -> Type {
+        match initializer.and_then(|init| self.type_table.expr_type(self.statement, init)) {
+            Some(Type::Int) if !self.type_table.is_loosely_assigned(self.statement, name) => Type::Int,
+            _ => Type::Infer,
         }
     }
     
@@ -107,7 +246,13 @@ impl Compiler {
                         _ => StaticType::Unknown,
                     }
                 } else {
-                    StaticType::Unknown
+                    match self.consts.get(name) {
+                        Some(ConstValue::Int(_)) => StaticType::Int,
+                        Some(ConstValue::Float(_)) => StaticType::Float,
+                        Some(ConstValue::Bool(_)) => StaticType::Bool,
+                        Some(ConstValue::String(_)) => StaticType::String,
+                        _ => StaticType::Unknown,
+                    }
                 }
             }
             Expr::Binary { left, op, right, .. } => {
@@ -136,6 +281,14 @@ impl Compiler {
                     }
                 }
             }
+            Expr::IfExpr { then_branch, else_branch, .. } => {
+                let then_type = self.infer_type(then_branch);
+                if then_type == self.infer_type(else_branch) {
+                    then_type
+                } else {
+                    StaticType::Unknown
+                }
+            }
             Expr::Unary { op, operand, .. } => {
                 match op {
                     UnaryOp::Neg => {
@@ -170,10 +323,71 @@ impl Compiler {
         }
     }
 
-    /// 编译程序
-    pub fn compile(&mut self, program: &Program) -> Result<Chunk, Vec<CompileError>> {
-        // 第一遍：预注册所有函数名（使前向引用成为可能）
-        // 这允许 main 函数调用在它之后定义的函数
+    /// 检查当前函数的局部变量槽位是否超出 u16 操作数能表示的范围
+    fn check_local_slots(&mut self, func_name: &str, span: Span) {
+        let slots = self.symbols.peak_slots();
+        if slots > MAX_LOCAL_SLOTS {
+            let msg = format!(
+                "function '{}' requires {} local slots, exceeding the limit of {}",
+                func_name, slots, MAX_LOCAL_SLOTS
+            );
+            self.errors.push(CompileError::new(msg, span));
+        }
+    }
+
+    /// 回填跳转，偏移量超出 u16 时报告错误
+    fn patch_jump(&mut self, offset: usize, span: Span) {
+        if let Err(distance) = self.chunk.patch_jump(offset) {
+            self.jump_too_far(distance, span);
+        }
+    }
+
+    /// 写入向后跳转的循环指令，偏移量超出 u16 时报告错误
+    fn emit_loop(&mut self, loop_start: usize, span: Span) {
+        if let Err(distance) = self.chunk.write_loop(loop_start, span.line) {
+            self.jump_too_far(distance, span);
+        }
+    }
+
+    /// 弹出循环体中（loop_stack[index] 开始之后）定义的局部变量，用于跳出循环体之前
+    fn emit_scope_exit(&mut self, index: usize, span: Span) {
+        for _ in self.loop_stack[index].slots..self.symbols.current_slot() {
+            self.chunk.write_op(OpCode::Pop, span.line);
+        }
+    }
+
+    /// 移除 try 块的异常处理器：进入 loop_stack[loop_index] 之后的 try 块，loop_index 为 None 时为当前函数中所有的 try 块
+    fn emit_try_exit(&mut self, loop_index: Option<usize>, span: Span) {
+        let count = self.try_blocks.iter().filter(|&&depth| loop_index.is_none_or(|index| depth > index)).count();
+        for _ in 0..count {
+            self.chunk.write_op(OpCode::PopTry, span.line);
+        }
+    }
+
+    /// continue 到 loop_stack[index] 所在的循环：有递增部分的循环向前跳到递增处（稍后回填），否则回到循环起始位置
+    fn emit_continue(&mut self, index: usize, span: Span) {
+        self.emit_scope_exit(index, span);
+        self.emit_try_exit(Some(index), span);
+        if self.loop_stack[index].continues.is_some() {
+            let jump = self.chunk.write_jump(OpCode::Jump, span.line);
+            if let Some(continues) = self.loop_stack[index].continues.as_mut() {
+                continues.push(jump);
+            }
+        } else {
+            self.emit_loop(self.loop_stack[index].start, span);
+        }
+    }
+
+    fn jump_too_far(&mut self, distance: usize, span: Span) {
+        let msg = format!(
+            "function too large: jump of {} bytes exceeds the limit of {}",
+            distance, MAX_JUMP_DISTANCE
+        );
+        self.errors.push(CompileError::new(msg, span));
+    }
+
+    /// 预注册所有顶层函数名，允许 main 函数调用在它之后定义的函数
+    fn predeclare_functions(&mut self, program: &Program) {
         for stmt in &program.statements {
             if let Stmt::FnDef { name, .. } = stmt {
                 // 预留常量池位置
@@ -183,10 +397,136 @@ impl Compiler {
                 self.chunk.register_named_function(name.clone(), func_index);
             }
         }
-        
-        // 第二遍：实际编译所有语句
+    }
+
+    /// 找出实例化时要执行 init 的类（见 `constructed_classes`），父类可以声明在子类之后；同时记录程序中的 struct
+    fn collect_constructed_classes(&mut self, program: &Program) {
         for stmt in &program.statements {
-            self.compile_stmt(stmt);
+            if let Stmt::StructDef { name, .. } = stmt {
+                self.struct_types.insert(name.clone());
+            }
+        }
+        let classes: Vec<(&String, Option<&String>, bool)> = program.statements.iter()
+            .filter_map(|stmt| match stmt {
+                Stmt::ClassDef { name, parent, fields, methods, .. } => {
+                    let has_init = methods.iter().any(|m| m.name == "init" && !m.is_static)
+                        || fields.iter().any(|f| !f.is_static && f.initializer.is_some());
+                    Some((name, parent.as_ref(), has_init))
+                }
+                _ => None,
+            })
+            .collect();
+        loop {
+            let before = self.constructed_classes.len();
+            for (name, parent, has_init) in &classes {
+                if *has_init || parent.is_some_and(|parent| self.constructed_classes.contains(parent)) {
+                    self.constructed_classes.insert((*name).clone());
+                }
+            }
+            if self.constructed_classes.len() == before {
+                break;
+            }
+        }
+    }
+
+    /// 检查合并后的顶层语句中没有重名的函数或类型
+    ///
+    /// 依赖文件的语句与主程序合并编译，后出现的同名定义会覆盖前面的，因此作为错误报告，
+    /// 信息中给出两处定义的文件和位置。需要在 [`set_source_files`](Self::set_source_files) 之后调用。
+    pub fn check_definitions(&self, program: &Program) -> Result<(), Vec<CompileError>> {
+        let mut files = self.source_files.iter().flat_map(|(path, count)| std::iter::repeat_n(path, *count));
+        let mut names = TopLevelNames::new();
+        let mut errors = Vec::new();
+        for stmt in &program.statements {
+            let file = files.next().cloned();
+            let (kind, name) = match stmt {
+                Stmt::FnDef { name, .. } => (DefinitionKind::Function, name),
+                Stmt::ClassDef { name, .. } => (DefinitionKind::Class, name),
+                Stmt::StructDef { name, .. } => (DefinitionKind::Struct, name),
+                Stmt::EnumDef { name, .. } => (DefinitionKind::Enum, name),
+                Stmt::InterfaceDef { name, .. } => (DefinitionKind::Interface, name),
+                Stmt::TraitDef { name, .. } => (DefinitionKind::Trait, name),
+                _ => continue,
+            };
+            let definition = Definition { kind, file, span: stmt.span() };
+            let message = match names.declare(name, definition.clone()) {
+                Ok(()) => continue,
+                Err(previous) if previous.kind == kind => format!(
+                    "{} '{}' is defined twice: at {} and at {}",
+                    kind.describe(), name, previous, definition
+                ),
+                Err(previous) => format!(
+                    "{} '{}' at {} conflicts with {} '{}' at {}",
+                    kind.describe(), name, definition, previous.kind.describe(), name, previous
+                ),
+            };
+            errors.push(CompileError::new(message, definition.span));
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    /// 检查函数参数个数是否超出调用指令能传递的范围
+    fn check_param_count(&mut self, func_name: &str, count: usize, span: Span) {
+        if count > MAX_CALL_ARGS {
+            let msg = format!(
+                "function '{}' has {} parameters, exceeding the limit of {}",
+                func_name, count, MAX_CALL_ARGS
+            );
+            self.errors.push(CompileError::new(msg, span));
+        }
+    }
+
+    /// 检查调用参数个数，超出 u8 操作数范围时记录错误并返回 false
+    fn check_arg_count(&mut self, count: usize, span: Span) -> bool {
+        if count > MAX_CALL_ARGS {
+            let msg = format!(
+                "call has {} arguments, exceeding the limit of {}",
+                count, MAX_CALL_ARGS
+            );
+            self.errors.push(CompileError::new(msg, span));
+            return false;
+        }
+        true
+    }
+
+    /// 编译程序
+    pub fn compile(&mut self, program: &Program) -> Result<Chunk, Vec<CompileError>> {
+        let started = timings::begin();
+        // 记录导入的标准库函数（如 import std.fs.readFile）
+        for import in &program.imports {
+            self.register_stdlib_import(import);
+        }
+        
+        // 重名的定义会互相覆盖，不再继续编译
+        self.check_definitions(program)?;
+        
+        // 第一遍：预注册所有函数名（使前向引用成为可能）
+        self.predeclare_functions(program);
+        self.collect_constructed_classes(program);
+        
+        // 计算顶层常量和类型成员常量（允许引用在后面声明的常量）
+        self.fold_program_consts(program);
+        
+        // 每条语句的来源文件；单态化追加的函数属于原来的泛型函数所在的文件
+        let files: Vec<String> = std::mem::take(&mut self.source_files)
+            .into_iter()
+            .flat_map(|(path, count)| std::iter::repeat_n(path, count))
+            .collect();
+        let mut current_file = None;
+        
+        // 第二遍：实际编译所有语句（顶层常量已经内联，不生成代码）
+        self.symbols.set_captures(Captures::analyze(&program.statements));
+        for (i, stmt) in program.statements.iter().enumerate() {
+            if let Some(file) = files.get(self.type_table.origin_statement(i)) {
+                if current_file != Some(file) {
+                    self.chunk.begin_file(file);
+                    current_file = Some(file);
+                }
+            }
+            self.statement = i;
+            if !matches!(stmt, Stmt::ConstDecl { .. }) {
+                self.compile_stmt(stmt);
+            }
         }
         
         // 如果有 main 函数，生成调用 main 函数的代码
@@ -197,13 +537,16 @@ impl Compiler {
             // 调用 main 函数（无参数）
             self.chunk.write_op(OpCode::Call, 0);
             self.chunk.write(0, 0);
-            // 弹出返回值（main 函数返回 void）
-            self.chunk.write_op(OpCode::Pop, 0);
+            // 返回值留在栈顶，Halt 后由 VM::result 取出作为退出码
         }
         
         // 添加 HALT 指令
         self.chunk.write_op(OpCode::Halt, 0);
         
+        // 顶层代码同样通过 u16 槽位访问局部变量
+        self.check_local_slots("<main>", Span::default());
+        timings::record(Phase::Codegen, started, || self.chunk.code.len() as u64);
+        
         if self.errors.is_empty() {
             Ok(std::mem::take(&mut self.chunk))
         } else {
@@ -211,6 +554,47 @@ impl Compiler {
         }
     }
 
+    /// 增量编译一段 REPL 输入，代码追加到之前的字节码之后，以 Halt 结束
+    ///
+    /// 之前输入定义的变量、函数和类型保持可见。最后一条语句是表达式时不弹出它的值，
+    /// 返回 (新代码的起始偏移, 栈顶是否留下了表达式的值)。出错时编译器状态不会回滚，
+    /// 调用方应丢弃这个编译器。`file` 是栈追踪中显示的输入名。
+    pub fn compile_repl(&mut self, program: &Program, file: &str) -> Result<(usize, bool), Vec<CompileError>> {
+        for import in &program.imports {
+            self.register_stdlib_import(import);
+        }
+        let start = self.chunk.code.len();
+        self.chunk.begin_file(file);
+        self.predeclare_functions(program);
+        self.collect_constructed_classes(program);
+        self.fold_program_consts(program);
+        
+        let mut leaves_value = false;
+        for (i, stmt) in program.statements.iter().enumerate() {
+            match stmt {
+                Stmt::ConstDecl { .. } => {}
+                Stmt::Expression { expr, .. } if i + 1 == program.statements.len() => {
+                    self.compile_expr(expr);
+                    leaves_value = true;
+                }
+                _ => self.compile_stmt(stmt),
+            }
+        }
+        self.chunk.write_op(OpCode::Halt, 0);
+        self.check_local_slots("<main>", Span::default());
+        
+        if self.errors.is_empty() {
+            Ok((start, leaves_value))
+        } else {
+            Err(self.errors.clone())
+        }
+    }
+    
+    /// 当前的字节码块
+    pub fn chunk(&self) -> &Chunk {
+        &self.chunk
+    }
+
     /// 编译语句
     fn compile_stmt(&mut self, stmt: &Stmt) {
         match stmt {
@@ -219,6 +603,7 @@ impl Compiler {
                 // 表达式语句的结果被丢弃
                 self.chunk.write_op(OpCode::Pop, expr.span().line);
             }
+            Stmt::ParallelAssign { targets, values, span } => self.compile_parallel_assign(targets, values, *span),
             Stmt::Print { expr, newline, span } => {
                 self.compile_expr(expr);
                 if *newline {
@@ -227,6 +612,13 @@ impl Compiler {
                     self.chunk.write_op(OpCode::Print, span.line);
                 }
             }
+            Stmt::VarDecl { name, initializer, span, .. } if name == DISCARD => {
+                // 丢弃绑定：只为副作用求值，不分配槽位
+                if let Some(init) = initializer {
+                    self.compile_expr(init);
+                    self.chunk.write_op(OpCode::Pop, span.line);
+                }
+            }
             Stmt::VarDecl { name, type_ann, initializer, span } => {
                 // 编译初始化表达式
                 if let Some(init) = initializer {
@@ -236,15 +628,13 @@ impl Compiler {
                     self.chunk.write_constant(Value::null(), span.line);
                 }
                 
-                // 推断类型
-                let ty = if let Some(ann) = type_ann {
-                    ann.ty.clone()
-                } else {
-                    Type::Infer // TODO: 实际类型推导
+                let ty = match type_ann {
+                    Some(ann) => ann.ty.clone(),
+                    None => self.inferred_local_type(name, initializer.as_ref()),
                 };
                 
                 // 定义变量
-                match self.symbols.define(name.clone(), ty, false) {
+                match self.declare_local(name.clone(), ty, span.line) {
                     Ok(_slot) => {
                         // 变量值已在栈上，不需要额外操作
                     }
@@ -276,20 +666,24 @@ impl Compiler {
             }
             Stmt::Block { statements, span: _ } => {
                 self.symbols.begin_scope();
-                for s in statements {
-                    self.compile_stmt(s);
-                }
+                self.compile_statements(statements);
                 let pop_count = self.symbols.end_scope();
                 // 弹出作用域内的局部变量
                 for _ in 0..pop_count {
                     self.chunk.write_op(OpCode::Pop, 0);
                 }
             }
+            Stmt::If { condition, then_branch, else_branch, .. } if self.constant_condition(condition).is_some() => {
+                // 常量条件：只编译会执行的分支
+                if self.constant_condition(condition) == Some(true) {
+                    self.compile_stmt(then_branch);
+                } else if let Some(else_branch) = else_branch {
+                    self.compile_stmt(else_branch);
+                }
+            }
             Stmt::If { condition, then_branch, else_branch, span } => {
-                // 尝试使用超级指令优化：检查是否是 `local <= int_const` 形式
-                // 编译条件并跳转
-                self.compile_expr(condition);
-                let then_jump = self.chunk.write_jump_if_false_pop(span.line);
+                // 条件为假时跳过 then 分支
+                let then_jumps = self.compile_condition(condition, false);
                 
                 // 编译 then 分支
                 self.compile_stmt(then_branch);
@@ -298,19 +692,17 @@ impl Compiler {
                     // 跳过 else 分支
                     let else_jump = self.chunk.write_jump(OpCode::Jump, span.line);
                     
-                    // 回填 then_jump
-                    self.chunk.patch_jump(then_jump);
+                    // 回填 then_jumps
+                    self.patch_condition_jumps(then_jumps, *span);
                     
                     // 编译 else 分支
                     self.compile_stmt(else_branch);
                     
                     // 回填 else_jump
-                    self.chunk.patch_jump(else_jump);
+                    self.patch_jump(else_jump, *span);
                 } else {
-                    // 回填 then_jump
-                    self.chunk.patch_jump(then_jump);
-                    
-                    // 条件值已在 JumpIfFalsePop 中弹出（或由超级指令处理）
+                    // 回填 then_jumps（条件跳转不在栈上留下值）
+                    self.patch_condition_jumps(then_jumps, *span);
                 }
             }
             Stmt::ForLoop { label, initializer, condition, increment, body, span } => {
@@ -339,39 +731,40 @@ impl Compiler {
                 self.loop_stack.push(LoopInfo {
                     start: loop_start,
                     breaks: Vec::new(),
+                    continues: Some(Vec::new()),
+                    slots: self.symbols.current_slot(),
                     label: label.clone(),
                 });
                 
                 // 3. 编译条件检查
-                let exit_jump = if let Some(cond) = condition {
-                    self.compile_expr(cond);
-                    let jump = self.chunk.write_jump_if_false_pop(span.line);
-                    Some(jump)
-                } else {
-                    None
+                let exit_jumps = match condition {
+                    Some(cond) => self.compile_condition(cond, false),
+                    None => Vec::new(),
                 };
                 
                 // 4. 编译循环体
                 self.compile_stmt(body);
                 
-                // 5. 编译递增部分
+                // 5. 回填 continue 跳转，然后编译递增部分
+                let continues = self.loop_stack.last_mut().and_then(|info| info.continues.take()).unwrap_or_default();
+                for continue_jump in continues {
+                    self.patch_jump(continue_jump, *span);
+                }
                 if let Some(incr) = increment {
                     self.compile_expr(incr);
                     self.chunk.write_op(OpCode::Pop, span.line); // 丢弃递增表达式的值
                 }
                 
                 // 6. 跳回循环开始
-                self.chunk.write_loop(loop_start, span.line);
+                self.emit_loop(loop_start, *span);
                 
                 // 7. 回填退出跳转
-                if let Some(exit) = exit_jump {
-                    self.chunk.patch_jump(exit);
-                }
+                self.patch_condition_jumps(exit_jumps, *span);
                 
                 // 8. 回填所有 break 跳转
                 let loop_info = self.loop_stack.pop().unwrap();
                 for break_jump in loop_info.breaks {
-                    self.chunk.patch_jump(break_jump);
+                    self.patch_jump(break_jump, *span);
                 }
                 
                 // 9. 结束 for 循环作用域
@@ -400,7 +793,7 @@ impl Compiler {
                 // 8.   IterNext -> 栈: [..., iter, loop_var, iter_copy, value, has_next]
                 // 9.   JumpIfFalse exit
                 // 10.  Pop (弹出 has_next)
-                // 11.  SetLocal loop_var (更新循环变量)
+                // 11.  SetLocal loop_var (更新循环变量；`for k, v in` 先用 UnpackPair 拆成两个值，再依次设置)
                 // 12.  Pop (弹出 value)
                 // 13.  Pop (弹出 iter_copy)
                 // 14.  body
@@ -433,25 +826,32 @@ impl Compiler {
                     }
                 };
                 
-                // 定义循环变量（先压入 null 作为初始值）
-                let loop_var_slot = if variables.len() >= 1 {
+                // 定义循环变量（先压入 null 作为初始值）；两个变量时把每个元素拆成一对值。
+                // `_` 不占槽位，对应的值取出后直接弹出
+                if variables.is_empty() || variables.len() > 2 {
+                    let msg = "For-in loop requires one or two variables".to_string();
+                    self.errors.push(CompileError::new(msg, *span));
+                    return;
+                }
+                let mut loop_vars = Vec::new();
+                for variable in variables {
+                    if variable == DISCARD {
+                        loop_vars.push(None);
+                        continue;
+                    }
                     self.chunk.write_constant(Value::null(), span.line);
                     match self.symbols.define(
-                        variables[0].clone(),
+                        variable.clone(),
                         crate::types::Type::Unknown,
                         false,
                     ) {
-                        Ok(slot) => slot,
+                        Ok(slot) => loop_vars.push(Some((slot, self.symbols.promote_to_cell(slot)))),
                         Err(msg) => {
                             self.errors.push(CompileError::new(msg, *span));
                             return;
                         }
                     }
-                } else {
-                    let msg = "For-in loop requires at least one variable".to_string();
-                    self.errors.push(CompileError::new(msg, *span));
-                    return;
-                };
+                }
                 
                 // 记录循环起始位置
                 let loop_start = self.chunk.current_offset();
@@ -460,6 +860,8 @@ impl Compiler {
                 self.loop_stack.push(LoopInfo {
                     start: loop_start,
                     breaks: Vec::new(),
+                    continues: None,
+                    slots: self.symbols.current_slot(),
                     label: label.clone(),
                 });
                 
@@ -473,9 +875,20 @@ impl Compiler {
                 self.chunk.write_op(OpCode::Pop, span.line); // 弹出 has_next
                 // 栈: [..., iter, loop_var, iter_copy, value]
                 
-                // 更新循环变量
-                self.chunk.write_set_local(loop_var_slot, span.line);
-                self.chunk.write_op(OpCode::Pop, span.line); // 弹出 value
+                // 更新循环变量（被闭包捕获时每次迭代换一个新的 cell）
+                if loop_vars.len() == 2 {
+                    self.chunk.write_op(OpCode::UnpackPair, span.line);
+                    // 栈: [..., iter, key, value, iter_copy, first, second]
+                }
+                for &loop_var in loop_vars.iter().rev() {
+                    if let Some((slot, is_cell)) = loop_var {
+                        self.chunk.write_set_local(slot, span.line);
+                        if is_cell {
+                            self.chunk.write_make_cell(slot, span.line);
+                        }
+                    }
+                    self.chunk.write_op(OpCode::Pop, span.line); // 弹出元素（或拆出的值）
+                }
                 self.chunk.write_op(OpCode::Pop, span.line); // 弹出 iter_copy
                 // 栈: [..., iter, loop_var]
                 
@@ -483,10 +896,10 @@ impl Compiler {
                 self.compile_stmt(body);
                 
                 // 跳回循环开始
-                self.chunk.write_loop(loop_start, span.line);
+                self.emit_loop(loop_start, *span);
                 
                 // 回填退出跳转
-                self.chunk.patch_jump(exit_jump);
+                self.patch_jump(exit_jump, *span);
                 // 退出时栈: [..., iter, loop_var, iter_copy, null, false]
                 self.chunk.write_op(OpCode::Pop, span.line); // 弹出 has_next (false)
                 self.chunk.write_op(OpCode::Pop, span.line); // 弹出 value (null)
@@ -495,7 +908,7 @@ impl Compiler {
                 // 处理 break 跳转
                 let loop_info = self.loop_stack.pop().unwrap();
                 for break_jump in loop_info.breaks {
-                        self.chunk.patch_jump(break_jump);
+                        self.patch_jump(break_jump, *span);
                     }
                 
                 // 结束 for-in 作用域（弹出 iterator 和 loop_var）
@@ -504,6 +917,9 @@ impl Compiler {
                     self.chunk.write_op(OpCode::Pop, span.line);
                 }
             }
+            Stmt::While { condition: Some(condition), .. } if self.constant_condition(condition) == Some(false) => {
+                // 条件恒为假的循环不会执行
+            }
             Stmt::While { label, condition, body, span } => {
                 // 记录循环起始位置
                 let loop_start = self.chunk.current_offset();
@@ -512,34 +928,30 @@ impl Compiler {
                 self.loop_stack.push(LoopInfo {
                     start: loop_start,
                     breaks: Vec::new(),
+                    continues: None,
+                    slots: self.symbols.current_slot(),
                     label: label.clone(),
                 });
                 
                 // 编译条件（如果有）
-                let exit_jump = if let Some(cond) = condition {
-                    self.compile_expr(cond);
-                    let jump = self.chunk.write_jump_if_false_pop(span.line);
-                    Some(jump)
-                } else {
-                    None
+                let exit_jumps = match condition {
+                    Some(cond) => self.compile_condition(cond, false),
+                    None => Vec::new(),
                 };
                 
                 // 编译循环体
                 self.compile_stmt(body);
                 
                 // 跳回循环开始
-                self.chunk.write_loop(loop_start, span.line);
+                self.emit_loop(loop_start, *span);
                 
-                // 回填退出跳转
-                if let Some(exit) = exit_jump {
-                    self.chunk.patch_jump(exit);
-                    self.chunk.write_op(OpCode::Pop, span.line);
-                }
+                // 回填退出跳转（条件跳转不在栈上留下值）
+                self.patch_condition_jumps(exit_jumps, *span);
                 
                 // 回填所有 break 跳转
                 let loop_info = self.loop_stack.pop().unwrap();
                 for break_jump in loop_info.breaks {
-                    self.chunk.patch_jump(break_jump);
+                    self.patch_jump(break_jump, *span);
                 }
             }
             Stmt::Break { label, span } => {
@@ -552,6 +964,8 @@ impl Compiler {
                         info.label.as_ref() == Some(target_label)
                     });
                     if let Some(idx) = idx {
+                        self.emit_scope_exit(idx, *span);
+                        self.emit_try_exit(Some(idx), *span);
                         let jump = self.chunk.write_jump(OpCode::Jump, span.line);
                         self.loop_stack[idx].breaks.push(jump);
                 } else {
@@ -560,6 +974,10 @@ impl Compiler {
                     }
                 } else {
                     // 无标签的 break - 跳出最近的循环
+                    if !self.loop_stack.is_empty() {
+                        self.emit_scope_exit(self.loop_stack.len() - 1, *span);
+                        self.emit_try_exit(Some(self.loop_stack.len() - 1), *span);
+                    }
                     let jump = self.chunk.write_jump(OpCode::Jump, span.line);
                     if let Some(info) = self.loop_stack.last_mut() {
                         info.breaks.push(jump);
@@ -574,22 +992,22 @@ impl Compiler {
                     self.errors.push(CompileError::new(msg, *span));
                 } else if let Some(target_label) = label {
                     // 带标签的 continue - 查找匹配的循环
-                    let info = self.loop_stack.iter().rev().find(|info| {
+                    let idx = self.loop_stack.iter().rposition(|info| {
                         info.label.as_ref() == Some(target_label)
                     });
-                    if let Some(info) = info {
-                        self.chunk.write_loop(info.start, span.line);
+                    if let Some(idx) = idx {
+                        self.emit_continue(idx, *span);
                     } else {
                         let msg = format!("Cannot find loop with label '{}'", target_label);
                         self.errors.push(CompileError::new(msg, *span));
                     }
                 } else {
                     // 无标签的 continue - 回到最近的循环开始
-                    if let Some(info) = self.loop_stack.last() {
-                        self.chunk.write_loop(info.start, span.line);
+                    if !self.loop_stack.is_empty() {
+                        self.emit_continue(self.loop_stack.len() - 1, *span);
                 } else {
                     let loop_start = *self.loop_starts.last().unwrap();
-                    self.chunk.write_loop(loop_start, span.line);
+                    self.emit_loop(loop_start, *span);
                     }
                 }
             }
@@ -597,6 +1015,10 @@ impl Compiler {
                 // 尾调用优化：如果返回值是函数调用，使用 TailCall 指令
                 if let Some(expr) = value {
                     if let Some(tail_call_info) = self.try_extract_tail_call(expr) {
+                        if !self.check_arg_count(tail_call_info.args.len(), *span) {
+                            return;
+                        }
+                        
                         // 这是一个尾调用，使用 TailCall 指令
                         // 1. 编译函数表达式
                         self.compile_expr(&tail_call_info.callee);
@@ -611,38 +1033,133 @@ impl Compiler {
                         self.chunk.write(tail_call_info.args.len() as u8, span.line);
                     } else if let Expr::Identifier { name, .. } = expr {
                         // 超级指令优化：返回局部变量
-                        if let Some(slot) = self.symbols.resolve_slot(name) {
+                        if let Some(slot) = self.plain_local_slot(name).filter(|_| self.opt.fuse) {
                             if slot <= 255 {
+                                self.emit_try_exit(None, *span);
                                 self.chunk.write_return_local(slot as u8, span.line);
                             } else {
                                 self.compile_expr(expr);
+                                self.emit_try_exit(None, *span);
                                 self.chunk.write_op(OpCode::Return, span.line);
                             }
                         } else {
                             self.compile_expr(expr);
+                            self.emit_try_exit(None, *span);
                             self.chunk.write_op(OpCode::Return, span.line);
                         }
                     } else if let Expr::Integer { value: int_val, .. } = expr {
                         // 超级指令优化：返回小整数常量
-                        if *int_val >= i8::MIN as i128 && *int_val <= i8::MAX as i128 {
+                        if self.opt.fuse && *int_val >= i8::MIN as i128 && *int_val <= i8::MAX as i128 {
+                            self.emit_try_exit(None, *span);
                             self.chunk.write_return_int(*int_val as i8, span.line);
                         } else {
                             self.compile_expr(expr);
+                            self.emit_try_exit(None, *span);
                             self.chunk.write_op(OpCode::Return, span.line);
                         }
                     } else {
                         // 普通返回
                         self.compile_expr(expr);
+                        self.emit_try_exit(None, *span);
                         self.chunk.write_op(OpCode::Return, span.line);
                     }
                 } else {
                     // 无返回值时返回 null
                     self.chunk.write_constant(Value::null(), span.line);
+                    self.emit_try_exit(None, *span);
                     self.chunk.write_op(OpCode::Return, span.line);
                 }
             }
+            Stmt::Switch { expr, cases, default, span } => {
+                self.compile_switch(expr, cases, default.as_deref(), *span);
+            }
+            Stmt::Select { cases, span } => {
+                use crate::parser::ast::SelectCaseKind;
+                
+                // select 语句编译：
+                // 1. 依次把各分支的通道操作加入 select builder
+                // 2. SelectExec 阻塞到某个分支就绪，留下 [接收值, 分支下标]，存入临时变量
+                // 3. 按分支下标跳转到对应的分支体
+                
+                if cases.len() > u8::MAX as usize {
+                    let msg = format!("select has too many cases (max {})", u8::MAX);
+                    self.errors.push(CompileError::new(msg, *span));
+                    return;
+                }
+                
+                self.symbols.begin_scope();
+                
+                self.chunk.write_op(OpCode::SelectBegin, span.line);
+                self.chunk.write(cases.len() as u8, span.line);
+                for case in cases {
+                    match &case.kind {
+                        SelectCaseKind::Receive { channel, .. } => {
+                            self.compile_expr(channel);
+                            self.chunk.write_op(OpCode::SelectAddRecv, case.span.line);
+                        }
+                        SelectCaseKind::Send { channel, value } => {
+                            self.compile_expr(channel);
+                            self.compile_expr(value);
+                            self.chunk.write_op(OpCode::SelectAddSend, case.span.line);
+                        }
+                        SelectCaseKind::Default => {
+                            self.chunk.write_op(OpCode::SelectAddDefault, case.span.line);
+                        }
+                    }
+                }
+                self.chunk.write_op(OpCode::SelectExec, span.line);
+                
+                // 栈: [..., value, case_index]
+                let mut slots = Vec::with_capacity(2);
+                for name in ["__select_value__", "__select_index__"] {
+                    match self.symbols.define(name.to_string(), crate::types::Type::Unknown, false) {
+                        Ok(slot) => slots.push(slot),
+                        Err(msg) => {
+                            self.errors.push(CompileError::new(msg, *span));
+                            return;
+                        }
+                    }
+                }
+                let (value_slot, index_slot) = (slots[0], slots[1]);
+                
+                let mut end_jumps = Vec::new();
+                for (idx, case) in cases.iter().enumerate() {
+                    self.chunk.write_get_local(index_slot, case.span.line);
+                    self.chunk.write_constant(Value::int(idx as i128), case.span.line);
+                    self.chunk.write_op(OpCode::Eq, case.span.line);
+                    let next_case_jump = self.chunk.write_jump(OpCode::JumpIfFalse, case.span.line);
+                    self.chunk.write_op(OpCode::Pop, case.span.line); // 弹出 true
+                    
+                    self.symbols.begin_scope();
+                    if let SelectCaseKind::Receive { binding: Some(name), .. } = &case.kind {
+                        self.chunk.write_get_local(value_slot, case.span.line);
+                        if let Err(msg) = self.declare_local(name.clone(), crate::types::Type::Unknown, case.span.line) {
+                            self.errors.push(CompileError::new(msg, case.span));
+                        }
+                    }
+                    self.compile_stmt(&case.body);
+                    let pop_count = self.symbols.end_scope();
+                    for _ in 0..pop_count {
+                        self.chunk.write_op(OpCode::Pop, case.span.line);
+                    }
+                    
+                    end_jumps.push(self.chunk.write_jump(OpCode::Jump, case.span.line));
+                    
+                    self.patch_jump(next_case_jump, *span);
+                    self.chunk.write_op(OpCode::Pop, case.span.line); // 弹出 false
+                }
+                
+                for end_jump in end_jumps {
+                    self.patch_jump(end_jump, *span);
+                }
+                
+                // 弹出 value 和 case_index 临时变量
+                let pop_count = self.symbols.end_scope();
+                for _ in 0..pop_count {
+                    self.chunk.write_op(OpCode::Pop, span.line);
+                }
+            }
             Stmt::Match { expr, arms, span } => {
-                use crate::parser::ast::MatchPattern;
                 
                 // match 语句编译：
                 // 1. 计算被匹配的表达式，存入临时变量
@@ -676,7 +1193,8 @@ impl Compiler {
                 for (_idx, arm) in arms.iter().enumerate() {
                     let _is_last = _idx == arms.len() - 1;
                     
-                    let next_arm_jump = match &arm.pattern {
+                    let pattern = self.resolve_const_pattern(&arm.pattern, arm.span);
+                    let next_arm_jump = match &pattern {
                         MatchPattern::Literal(lit_expr) => {
                             // 获取 match_value
                             self.chunk.write_get_local(match_slot, span.line);
@@ -698,10 +1216,10 @@ impl Compiler {
                             // 变量绑定：将 match_value 绑定到变量
                             // 获取 match_value 并定义为新变量
                             self.chunk.write_get_local(match_slot, span.line);
-                            match self.symbols.define(
+                            match self.declare_local(
                                 var_name.clone(),
                                 crate::types::Type::Unknown,
-                                false,
+                                span.line,
                             ) {
                                 Ok(_) => {}
                                 Err(msg) => {
@@ -773,7 +1291,7 @@ impl Compiler {
                                             fail_jumps.push(fail_jump);
                                             
                                             // 回填 range_fail1：不在范围内
-                                            self.chunk.patch_jump(range_fail1);
+                                            self.patch_jump(range_fail1, *span);
                                             self.chunk.write_op(OpCode::Pop, span.line);
                                             // 继续到下一分支（通过 fail_jump 跳转）
                                         } else {
@@ -783,7 +1301,7 @@ impl Compiler {
                                             success_jumps.push(success_jump);
                                             
                                             // 回填 range_fail1：不在范围内，继续下一子模式
-                                            self.chunk.patch_jump(range_fail1);
+                                            self.patch_jump(range_fail1, *span);
                                             self.chunk.write_op(OpCode::Pop, span.line);
                                         }
                                     }
@@ -800,12 +1318,16 @@ impl Compiler {
                                         let msg = "Type pattern in Or not yet implemented".to_string();
                                         self.errors.push(CompileError::new(msg, *span));
                                     }
+                                    MatchPattern::EnumVariant { .. } => {
+                                        let msg = "Enum variant pattern in Or not yet implemented".to_string();
+                                        self.errors.push(CompileError::new(msg, *span));
+                                    }
                                 }
                             }
                             
                             // 回填成功跳转：跳到分支体之前，需要先弹出 true
                             for jump in &success_jumps {
-                                self.chunk.patch_jump(*jump);
+                                self.patch_jump(*jump, *span);
                             }
                             if !success_jumps.is_empty() {
                                 self.chunk.write_op(OpCode::Pop, span.line); // 弹出 true
@@ -820,7 +1342,7 @@ impl Compiler {
                             
                             // 回填失败跳转：所有子模式都不匹配
                             for jump in fail_jumps {
-                                self.chunk.patch_jump(jump);
+                                self.patch_jump(jump, *span);
                             }
                             self.chunk.write_op(OpCode::Pop, span.line); // 弹出 false
                             
@@ -860,10 +1382,10 @@ impl Compiler {
                             end_jumps.push(end_jump);
                             
                             // 回填两个失败跳转：都跳到这里弹出 false 然后继续下一个分支
-                            self.chunk.patch_jump(fail_jump1);
+                            self.patch_jump(fail_jump1, *span);
                             self.chunk.write_op(OpCode::Pop, span.line); // 弹出 false (match < start)
                             // fail_jump2 跳到的地方
-                            self.chunk.patch_jump(fail_jump2);
+                            self.patch_jump(fail_jump2, *span);
                             self.chunk.write_op(OpCode::Pop, span.line); // 弹出 false (match >= end)
                             
                             // 范围模式已经处理了分支体和跳转，继续下一个分支
@@ -874,6 +1396,12 @@ impl Compiler {
                             self.errors.push(CompileError::new(msg, *span));
                             continue;
                         }
+                        MatchPattern::EnumVariant { enum_name, variant, fields } => {
+                            if let Some(end_jump) = self.compile_enum_variant_arm(match_slot, enum_name, variant, fields, &arm.body, arm.span) {
+                                end_jumps.push(end_jump);
+                            }
+                            continue;
+                        }
                     };
                     
                     // 执行分支体
@@ -885,14 +1413,14 @@ impl Compiler {
                     
                     // 回填跳转到下一个分支（跳转到这里意味着匹配失败）
                     if let Some(jump) = next_arm_jump {
-                        self.chunk.patch_jump(jump);
+                        self.patch_jump(jump, *span);
                         self.chunk.write_op(OpCode::Pop, span.line); // 弹出 false
                     }
                 }
                 
                 // 回填所有结束跳转
                 for end_jump in end_jumps {
-                    self.chunk.patch_jump(end_jump);
+                    self.patch_jump(end_jump, *span);
                 }
                 
                 // 结束 match 作用域（弹出 match_value 临时变量）
@@ -901,9 +1429,13 @@ impl Compiler {
                     self.chunk.write_op(OpCode::Pop, span.line);
                 }
             }
-            Stmt::StructDef { name, type_params: _, where_clauses: _, interfaces, fields: _, methods, span } => {
-                // 注册 struct 类型
+            Stmt::StructDef { name, type_params: _, where_clauses: _, interfaces, fields, static_fields, methods, span } => {
+                // 注册 struct 类型，字段按声明顺序记录（显示实例时使用）
                 self.chunk.register_type(name.clone());
+                for field in fields {
+                    self.chunk.register_field(name, field.name.clone());
+                }
+                self.register_static_fields(name, static_fields, *span);
                 
                 // 收集已定义的方法名
                 let defined_methods: std::collections::HashSet<String> = methods.iter()
@@ -912,6 +1444,7 @@ impl Compiler {
                 
                 // 检查接口实现
                 for interface_name in interfaces {
+                    self.chunk.register_trait_impl(name, interface_name.clone());
                     if let Some(interface_info) = self.chunk.get_interface(interface_name).cloned() {
                         // 检查 struct 是否实现了接口的所有方法
                         for interface_method in &interface_info.methods {
@@ -935,8 +1468,14 @@ impl Compiler {
                 }
             }
             Stmt::ClassDef { name, type_params: _, where_clauses: _, is_abstract, parent, interfaces, traits, fields, methods, span } => {
-                // 注册 class 类型（包括是否抽象）
+                // 注册 class 类型（包括是否抽象），实例字段按声明顺序记录，构造函数提升的字段在后
                 self.chunk.register_class_with_abstract(name.clone(), parent.clone(), *is_abstract);
+                for field in fields.iter().filter(|field| !field.is_static) {
+                    self.chunk.register_field(name, field.name.clone());
+                }
+                for param in methods.iter().filter(|m| m.name == "init").flat_map(|m| &m.params).filter(|p| p.is_field) {
+                    self.chunk.register_field(name, param.name.clone());
+                }
                 
                 // 收集类中已定义的方法名（用于避免覆盖）
                 let defined_methods: std::collections::HashSet<String> = methods.iter()
@@ -945,6 +1484,7 @@ impl Compiler {
                 
                 // 检查接口实现
                 for interface_name in interfaces {
+                    self.chunk.register_trait_impl(name, interface_name.clone());
                     if let Some(interface_info) = self.chunk.get_interface(interface_name).cloned() {
                         // 检查类是否实现了接口的所有方法
                         for interface_method in &interface_info.methods {
@@ -964,6 +1504,7 @@ impl Compiler {
                 
                 // 处理 traits：检查并将 trait 的默认方法复制到 class 中
                 for trait_name in traits {
+                    self.chunk.register_trait_impl(name, trait_name.clone());
                     if let Some(trait_info) = self.chunk.get_trait(trait_name).cloned() {
                         for trait_method in &trait_info.methods {
                             if !defined_methods.contains(&trait_method.name) {
@@ -991,43 +1532,9 @@ impl Compiler {
                 for field in fields {
                     if !field.is_static {
                         self.chunk.register_field(name, field.name.clone());
-                    } else {
-                        // 静态字段：编译初始值（如果有）并注册
-                        if let Some(init) = &field.initializer {
-                            // 先跳过初始化代码
-                            let jump_over = self.chunk.write_jump(OpCode::Jump, span.line);
-                            let value_start = self.chunk.current_offset();
-                            
-                            self.compile_expr(init);
-                            self.chunk.write_op(OpCode::Return, span.line);
-                            
-                            self.chunk.patch_jump(jump_over);
-                            
-                            // 创建一个"函数"来计算初始值
-                            let init_func = crate::vm::value::Function {
-                                name: Some(format!("{}::static_{}", name, field.name)),
-                                arity: 0,
-                                required_params: 0,
-                                defaults: Vec::new(),
-                                has_variadic: false,
-                                chunk_index: value_start,
-                                local_count: 0,
-                                upvalues: Vec::new(),
-                            };
-                            let func_index = self.chunk.add_constant(Value::function(Arc::new(init_func)));
-                            // 使用不同的注册方法取决于是否是常量
-                            if field.is_const {
-                                self.chunk.register_static_const(name, field.name.clone(), func_index);
-                            } else {
-                                self.chunk.register_static_field(name, field.name.clone(), func_index);
-                            }
-                        } else {
-                            // 没有初始值，使用 null（常量字段在解析器中已强制要求初始值）
-                            let null_index = self.chunk.add_constant(Value::null());
-                            self.chunk.register_static_field(name, field.name.clone(), null_index);
-                        }
                     }
                 }
+                self.register_static_fields(name, fields, *span);
                 
                 // 注册构造函数参数属性提升的字段
                 // 查找 init 方法，并注册 is_field=true 的参数为字段
@@ -1042,9 +1549,25 @@ impl Compiler {
                     }
                 }
                 
-                // 编译每个方法
+                // 编译每个方法；需要执行初始化但没有声明 init 的类生成一个无参的 init
                 for method in methods {
-                    self.compile_class_method(name, method, parent.as_deref(), *span);
+                    self.compile_class_method(name, method, parent.as_deref(), fields);
+                }
+                if self.constructed_classes.contains(name) && !methods.iter().any(|m| m.name == "init" && !m.is_static) {
+                    let implicit_init = crate::parser::ast::ClassMethod {
+                        name: "init".to_string(),
+                        params: Vec::new(),
+                        return_type: None,
+                        body: Some(Box::new(Stmt::Block { statements: Vec::new(), span: *span })),
+                        visibility: Default::default(),
+                        is_static: false,
+                        is_override: false,
+                        is_final: false,
+                        is_abstract: false,
+                        attributes: Vec::new(),
+                        span: *span,
+                    };
+                    self.compile_class_method(name, &implicit_init, parent.as_deref(), fields);
                 }
             }
             Stmt::InterfaceDef { name, type_params: _, super_interfaces: _, methods, span: _ } => {
@@ -1073,15 +1596,17 @@ impl Compiler {
                         // 保存符号表状态
                         let saved_state = self.symbols.save_state();
                         let saved_scope_depth = self.symbols.scope_depth();
-                        self.symbols.reset_for_function();
+                        let saved_try_blocks = std::mem::take(&mut self.try_blocks);
+                        let saved_panic_slots = std::mem::take(&mut self.panic_slots);
+                        self.symbols.reset_for_function(Captures::analyze_body(body));
                         
-                        // 定义 self 参数（trait 方法的第一个参数）
-                        if let Err(msg) = self.symbols.define("self".to_string(), Type::Unknown, false) {
+                        // 定义 this 参数（trait 方法的隐式第一个参数）
+                        if let Err(msg) = self.symbols.define("this".to_string(), Type::Unknown, false) {
                             self.errors.push(CompileError::new(msg, method.span));
                         }
                         
                         // 定义其他参数并处理默认值
-                        let mut required_params = 1; // self is required
+                        let mut required_params = 1; // this is required
                         let mut defaults = Vec::new();
                         let mut has_default = false;
                         let mut has_variadic = false;
@@ -1131,24 +1656,32 @@ impl Compiler {
                         self.chunk.write_op(OpCode::Return, method.span.line);
                         
                         // 计算局部变量数量
+                        let func_name = format!("{}::{}", name, method.name);
+                        self.check_local_slots(&func_name, method.span);
+                        self.check_param_count(&func_name, method.params.len() + 1, method.span);
                         let local_count = self.symbols.local_count();
                         
                         // 恢复符号表
                         self.symbols.restore_state_full(saved_state, saved_scope_depth);
+                        self.try_blocks = saved_try_blocks;
+                        self.panic_slots = saved_panic_slots;
                         
                         // 回填跳转
-                        self.chunk.patch_jump(jump_over);
+                        self.patch_jump(jump_over, method.span);
+                        let func_end = self.chunk.current_offset();
+                        self.chunk.register_function_range(format!("{}::{}", name, method.name), func_start, func_end);
                         
                         // 创建函数对象
                         let func = crate::vm::value::Function {
                             name: Some(format!("{}::{}", name, method.name)),
-                            arity: method.params.len() + 1, // +1 for self
+                            arity: method.params.len() + 1, // +1 for this
                             required_params,
                             defaults,
                             has_variadic,
                             chunk_index: func_start,
                             local_count,
                             upvalues: Vec::new(),
+                            captures: Vec::new(),
                         };
                         
                         Some(self.chunk.add_constant(Value::function(Arc::new(func))))
@@ -1158,7 +1691,7 @@ impl Compiler {
                     
                     method_infos.push(crate::compiler::bytecode::TraitMethodInfo {
                         name: method.name.clone(),
-                        arity: method.params.len() + 1, // +1 for self
+                        arity: method.params.len() + 1, // +1 for this
                         default_impl,
                     });
                 }
@@ -1207,22 +1740,32 @@ impl Compiler {
                 // 记录 try 块开始时的槽位，用于确保 catch 参数位置正确
                 let try_start_slot = self.symbols.current_slot();
                 
+                // 有 finally 时先设置 finally 处理器，覆盖 try 块和 catch 块：
+                // Os.exit 结束程序时也执行 finally 块
+                let setup_finally = finally_block.as_ref().map(|_| {
+                    self.try_blocks.push(self.loop_stack.len());
+                    self.chunk.write_jump(OpCode::SetupFinally, span.line)
+                });
+                
                 // 设置异常处理器
                 let setup_try = self.chunk.write_jump(OpCode::SetupTry, span.line);
                 
                 // 编译 try 块
+                self.try_blocks.push(self.loop_stack.len());
                 self.compile_stmt(try_block);
+                self.try_blocks.pop();
                 
-                // try 块正常结束，需要清理可能产生的局部变量，跳过 catch 块
+                // try 块正常结束，需要清理可能产生的局部变量，移除异常处理器，跳过 catch 块
                 // 弹出 try 块中可能产生的临时值
                 let try_end_slot = self.symbols.current_slot();
                 for _ in try_start_slot..try_end_slot {
                     self.chunk.write_op(OpCode::Pop, span.line);
                 }
+                self.chunk.write_op(OpCode::PopTry, span.line);
                 let skip_catch = self.chunk.write_jump(OpCode::Jump, span.line);
                 
                 // catch 块起始位置
-                self.chunk.patch_jump(setup_try);
+                self.patch_jump(setup_try, *span);
                 
                 // 开始 catch 作用域
                 self.symbols.begin_scope();
@@ -1233,7 +1776,7 @@ impl Compiler {
                 if let Some(param_name) = catch_param {
                     // 设置符号表槽位与 VM 栈位置匹配
                     self.symbols.set_current_slot(try_start_slot);
-                    if let Err(msg) = self.symbols.define(param_name.clone(), crate::types::Type::Unknown, false) {
+                    if let Err(msg) = self.declare_local(param_name.clone(), crate::types::Type::Unknown, span.line) {
                         self.errors.push(CompileError::new(msg, *span));
                     }
                 } else {
@@ -1256,12 +1799,76 @@ impl Compiler {
                 self.symbols.set_current_slot(try_start_slot);
                 
                 // 跳过 catch 的跳转目标
-                self.chunk.patch_jump(skip_catch);
+                self.patch_jump(skip_catch, *span);
                 
                 // 编译 finally 块（如果有）
-                if let Some(finally) = finally_block {
+                if let (Some(finally), Some(setup_finally)) = (finally_block, setup_finally) {
+                    self.try_blocks.pop();
+                    self.chunk.write_op(OpCode::PopTry, span.line);
+                    self.compile_stmt(finally);
+                    let skip_exit = self.chunk.write_jump(OpCode::Jump, span.line);
+                    
+                    // 退出路径：VM 把栈恢复到设置处理器时的深度后压入退出码，它在 try_start_slot 位置
+                    self.patch_jump(setup_finally, *span);
+                    self.symbols.begin_scope();
+                    self.symbols.set_current_slot(try_start_slot);
+                    if let Err(msg) = self.symbols.define(format!("__exit_{}__", span.line), Type::Unknown, false) {
+                        self.errors.push(CompileError::new(msg, *span));
+                        return;
+                    }
                     self.compile_stmt(finally);
+                    self.symbols.end_scope();
+                    self.chunk.write_op(OpCode::ResumeExit, span.line);
+                    self.symbols.set_current_slot(try_start_slot);
+                    self.patch_jump(skip_exit, *span);
+                }
+            }
+            Stmt::Recovering { body, panic_param, handler, span } => {
+                // 与 try-catch 相同，只是设置的处理器只处理 panic
+                let start_slot = self.symbols.current_slot();
+                let setup_recover = self.chunk.write_jump(OpCode::SetupRecover, span.line);
+                
+                self.try_blocks.push(self.loop_stack.len());
+                self.compile_stmt(body);
+                self.try_blocks.pop();
+                
+                for _ in start_slot..self.symbols.current_slot() {
+                    self.chunk.write_op(OpCode::Pop, span.line);
+                }
+                self.chunk.write_op(OpCode::PopTry, span.line);
+                let skip_handler = self.chunk.write_jump(OpCode::Jump, span.line);
+                
+                // VM 把栈恢复到设置处理器时的深度后压入 panic 的值，它在 start_slot 位置
+                self.patch_jump(setup_recover, *span);
+                self.symbols.begin_scope();
+                self.symbols.set_current_slot(start_slot);
+                let panic_slot = match self.symbols.define(format!("__panic_{}__", span.line), Type::Unknown, false) {
+                    Ok(slot) => slot,
+                    Err(msg) => {
+                        self.errors.push(CompileError::new(msg, *span));
+                        return;
+                    }
+                };
+                // 参数是 panic 值的副本，对参数赋值不影响 recover() 的结果
+                if let Some(param_name) = panic_param {
+                    self.chunk.write_get_local(panic_slot, span.line);
+                    if let Err(msg) = self.declare_local(param_name.clone(), Type::Unknown, span.line) {
+                        self.errors.push(CompileError::new(msg, *span));
+                    }
+                }
+                
+                self.panic_slots.push(panic_slot);
+                self.compile_stmt(handler);
+                self.panic_slots.pop();
+                
+                self.symbols.end_scope();
+                // 弹出 panic 值和参数
+                let locals = if panic_param.is_some() { 2 } else { 1 };
+                for _ in 0..locals {
+                    self.chunk.write_op(OpCode::Pop, span.line);
                 }
+                self.symbols.set_current_slot(start_slot);
+                self.patch_jump(skip_handler, *span);
             }
             Stmt::Throw { value, span } => {
                 // 编译要抛出的值
@@ -1269,7 +1876,7 @@ impl Compiler {
                 // 生成 Throw 操作码
                 self.chunk.write_op(OpCode::Throw, span.line);
             }
-            Stmt::FnDef { name, type_params: _, where_clauses: _, params, return_type: _, body, visibility: _, span } => {
+            Stmt::FnDef { name, type_params: _, where_clauses: _, params, return_type: _, body, visibility: _, attributes, span } => {
                 // 编译命名函数定义（支持递归和前向引用）
                 
                 // 1. 检查是否已经预注册了这个函数（在 compile 第一遍中）
@@ -1293,7 +1900,9 @@ impl Compiler {
                 // 5. 保存符号表状态，为函数创建独立作用域
                 let saved_state = self.symbols.save_state();
                 let saved_scope_depth = self.symbols.scope_depth();
-                self.symbols.reset_for_function();
+                let saved_try_blocks = std::mem::take(&mut self.try_blocks);
+                let saved_panic_slots = std::mem::take(&mut self.panic_slots);
+                self.symbols.reset_for_function(Captures::analyze_body(body));
                 
                 let arity = params.len();
                 let mut required_params = 0;
@@ -1348,7 +1957,9 @@ impl Compiler {
                 }
                 
                 // 7. 编译函数体
+                let saved_opt = self.enter_function_attributes(attributes);
                 self.compile_function_body(body);
+                self.opt = saved_opt;
                 
                 // 8. 添加隐式返回
                 let needs_return = self.chunk.code.is_empty() 
@@ -1360,13 +1971,19 @@ impl Compiler {
                 }
                 
                 // 9. 计算局部变量数量
+                self.check_local_slots(name, *span);
+                self.check_param_count(name, params.len(), *span);
                 let local_count = self.symbols.local_count();
                 
                 // 10. 恢复符号表
                 self.symbols.restore_state_full(saved_state, saved_scope_depth);
+                self.try_blocks = saved_try_blocks;
+                self.panic_slots = saved_panic_slots;
                 
                 // 11. 回填跳转
-                self.chunk.patch_jump(jump_over);
+                self.patch_jump(jump_over, *span);
+                let func_end = self.chunk.current_offset();
+                self.chunk.register_function_range_with_attributes(name.clone(), func_start, func_end, Self::attribute_names(attributes));
                 
                 // 12. 创建函数对象并替换常量池中的占位值
                 let func = crate::vm::value::Function {
@@ -1378,6 +1995,7 @@ impl Compiler {
                     chunk_index: func_start,
                     local_count,
                     upvalues: Vec::new(),
+                    captures: Vec::new(),
                 };
                 self.chunk.constants[func_index as usize] = Value::function(Arc::new(func));
                 
@@ -1400,29 +2018,891 @@ impl Compiler {
         }
     }
 
-    /// 编译函数体
-    /// 如果函数体是一个 Block，直接编译其内部语句，避免额外的作用域管理
-    fn compile_function_body(&mut self, body: &Stmt) {
-        match body {
-            Stmt::Block { statements, .. } => {
-                // 直接编译块内的语句，不调用 begin_scope/end_scope
-                // 因为函数体的局部变量应该在函数返回时清理
-                for stmt in statements {
-                    self.compile_stmt(stmt);
+    /// 记录 import 引入的标准库函数
+    fn register_stdlib_import(&mut self, import: &ImportDecl) {
+        let registry = self.registry.clone();
+        
+        // import std.fs 与 import std.fs.* 一样导入整个模块
+        let (module_path, names) = match &import.target {
+            ImportTarget::Single(name) if registry.has_module(&format!("{}.{}", import.path, name)) => {
+                (format!("{}.{}", import.path, name), None)
+            }
+            ImportTarget::All => (import.path.clone(), None),
+            ImportTarget::Single(name) => (import.path.clone(), Some(vec![name.clone()])),
+            ImportTarget::Multiple(names) => (import.path.clone(), Some(names.clone())),
+        };
+        let module = match registry.get(&module_path) {
+            Some(m) => m,
+            None => return,
+        };
+        let names = names.unwrap_or_else(|| module.exports().iter().map(|s| s.to_string()).collect());
+        for name in names {
+            if registry.has_function(&module_path, &name) {
+                self.stdlib_functions.insert(name.clone(), module_path.clone());
+            }
+            // 命名空间式导出（如 Json -> Json_parse、Json_stringify）
+            let prefix = format!("{}_", name);
+            for export in module.exports() {
+                if export.starts_with(&prefix) {
+                    self.stdlib_functions.insert(export.to_string(), module_path.clone());
                 }
             }
-            _ => {
+        }
+    }
+    
+    /// 解析 `Namespace.func` / `Namespace::func` 形式的标准库调用
+    /// 返回 (模块名, 导出函数名)；同名局部变量、函数和类优先
+    fn resolve_stdlib_namespace_call(&self, namespace: &str, member: &str) -> Option<(String, String)> {
+        if self.symbols.resolve_slot(namespace).is_some()
+            || self.chunk.get_named_function(namespace).is_some()
+            || self.chunk.get_type(namespace).is_some()
+        {
+            return None;
+        }
+        let func = format!("{}_{}", namespace, member);
+        let module = self.stdlib_functions.get(&func)?.clone();
+        Some((module, func))
+    }
+    
+    /// `Namespace.NAME` / `Namespace::NAME` 形式的标准库常量（如 `Math.PI`）
+    fn resolve_stdlib_constant(&self, namespace: &str, member: &str) -> Option<Value> {
+        let (module, func) = self.resolve_stdlib_namespace_call(namespace, member)?;
+        self.registry.get(&module)?.constant(&func)
+    }
+    
+    /// 生成标准库函数调用（CallStdlib）
+    fn emit_stdlib_call(&mut self, module: String, func: String, args: &[(Option<String>, Expr)], span: Span) {
+        if !self.check_arg_count(args.len(), span) {
+            return;
+        }
+        let module_index = self.chunk.add_constant(Value::string(module));
+        let func_index = self.chunk.add_constant(Value::string(func));
+        for (_, arg) in args {
+            self.compile_expr(arg);
+        }
+        self.chunk.write_op(OpCode::CallStdlib, span.line);
+        self.chunk.write_u16(module_index, span.line);
+        self.chunk.write_u16(func_index, span.line);
+        self.chunk.write(args.len() as u8, span.line);
+    }
+    
+    /// 生成宿主函数调用（HostCall），函数没有注册时报错
+    fn emit_host_call(&mut self, name: &str, args: &[(Option<String>, Expr)], span: Span) {
+        if !self.host_functions.contains(name) {
+            self.errors.push(CompileError::new(format!("unknown host function '{}'", name), span));
+            return;
+        }
+        if args.iter().any(|(arg_name, _)| arg_name.is_some()) {
+            self.errors.push(CompileError::new("Host functions do not support named arguments".to_string(), span));
+            return;
+        }
+        if !self.check_arg_count(args.len(), span) {
+            return;
+        }
+        let index = self.chunk.add_host_function(name);
+        for (_, arg) in args {
+            self.compile_expr(arg);
+        }
+        self.chunk.write_op(OpCode::HostCall, span.line);
+        self.chunk.write_u16(index, span.line);
+        self.chunk.write(args.len() as u8, span.line);
+    }
+    
+    /// 编译块内的语句序列
+    ///
+    /// 启用优化时，return/throw/break/continue 之后的语句不可达，不生成代码
+    fn compile_statements(&mut self, statements: &[Stmt]) {
+        for stmt in statements {
+            self.compile_stmt(stmt);
+            if self.folding() && matches!(
+                stmt,
+                Stmt::Return { .. } | Stmt::Throw { .. } | Stmt::Break { .. } | Stmt::Continue { .. }
+            ) {
+                break;
+            }
+        }
+    }
+    
+    /// 编译函数体
+    /// 如果函数体是一个 Block，直接编译其内部语句，避免额外的作用域管理
+    /// 不在 cell 中的局部变量的槽位（超级指令直接按槽位读取整数，只能用于这类变量）
+    fn plain_local_slot(&self, name: &str) -> Option<usize> {
+        self.symbols.resolve(name).filter(|s| !s.is_cell).map(|s| s.slot)
+    }
+    
+    /// 定义用户声明的变量（值已在对应槽位上），被闭包捕获且会被赋值时随即提升为 cell
+    fn declare_local(&mut self, name: String, ty: Type, line: usize) -> Result<usize, String> {
+        let slot = self.symbols.define(name, ty, false)?;
+        if self.symbols.promote_to_cell(slot) {
+            self.chunk.write_make_cell(slot, line);
+        }
+        Ok(slot)
+    }
+    
+    fn compile_function_body(&mut self, body: &Stmt) {
+        self.promote_captured_params(body.span().line);
+        match body {
+            Stmt::Block { statements, .. } => {
+                // 直接编译块内的语句，不调用 begin_scope/end_scope
+                // 因为函数体的局部变量应该在函数返回时清理
+                self.compile_statements(statements);
+            }
+            _ => {
                 // 如果不是块语句，直接编译
                 self.compile_stmt(body);
             }
         }
     }
     
+    /// 被闭包捕获且会被赋值的参数在入口处提升为 cell
+    fn promote_captured_params(&mut self, line: usize) {
+        for slot in 0..self.symbols.local_count() {
+            if self.symbols.promote_to_cell(slot) {
+                self.chunk.write_make_cell(slot, line);
+            }
+        }
+    }
+    
+    /// 编译 class 的 init，实例按以下顺序初始化：
+    ///
+    /// 1. 父类的 init：方法体的第一条语句是 `super.init(...)` 时执行它，否则父类有 init 时隐式调用 `super.init()`
+    /// 2. 本类的实例字段初始化式，按声明顺序
+    /// 3. 提升为字段的构造函数参数（`init(var x: int)`）
+    /// 4. 方法体其余的语句
+    fn compile_init_body(&mut self, parent: Option<&str>, fields: &[ClassField], params: &[crate::parser::ast::FnParam], body: &Stmt) {
+        let line = body.span().line;
+        self.promote_captured_params(line);
+        let statements = match body {
+            Stmt::Block { statements, .. } => statements.as_slice(),
+            other => std::slice::from_ref(other),
+        };
+        
+        let rest = match statements.split_first() {
+            Some((first, rest)) if first.is_super_init_call() => {
+                self.in_super_init_call = true;
+                self.compile_stmt(first);
+                self.in_super_init_call = false;
+                rest
+            }
+            _ => {
+                if let Some(parent) = parent.filter(|parent| self.constructed_classes.contains(*parent)) {
+                    self.chunk.write_get_local(0, line);
+                    self.write_invoke_super(parent, "init", 0, line);
+                    self.chunk.write_op(OpCode::Pop, line);
+                }
+                statements
+            }
+        };
+        
+        // SetField 弹出值并把对象留在栈上，之后弹出对象
+        for field in fields.iter().filter(|field| !field.is_static) {
+            if let Some(initializer) = &field.initializer {
+                self.chunk.write_get_local(0, field.span.line);
+                self.compile_expr(initializer);
+                let field_name_index = self.chunk.add_constant(Value::string(field.name.clone()));
+                self.chunk.write_op(OpCode::SetField, field.span.line);
+                self.chunk.write_u16(field_name_index, field.span.line);
+                self.chunk.write_op(OpCode::Pop, field.span.line);
+            }
+        }
+        for param in params.iter().filter(|param| param.is_field) {
+            self.chunk.write_get_local(0, param.span.line);
+            self.compile_expr(&Expr::Identifier { name: param.name.clone(), span: param.span });
+            let field_name_index = self.chunk.add_constant(Value::string(param.name.clone()));
+            self.chunk.write_op(OpCode::SetField, param.span.line);
+            self.chunk.write_u16(field_name_index, param.span.line);
+            self.chunk.write_op(OpCode::Pop, param.span.line);
+        }
+        
+        self.compile_statements(rest);
+    }
+    
+    /// 编译 struct 字面量 `Point { x: 1, y: 2 }`：依次压入字段名和值，由 NewStruct 创建实例
+    fn compile_struct_literal(&mut self, name: &str, fields: &[(String, Expr)], span: Span) {
+        if fields.len() > u8::MAX as usize {
+            let msg = format!("Struct literal '{}' has too many fields", name);
+            self.errors.push(CompileError::new(msg, span));
+            return;
+        }
+        let type_name_index = self.chunk.add_constant(Value::string(name.to_string()));
+        for (field_name, field_value) in fields {
+            self.chunk.write_constant(Value::string(field_name.clone()), span.line);
+            self.compile_expr(field_value);
+        }
+        self.chunk.write_op(OpCode::NewStruct, span.line);
+        self.chunk.write(fields.len() as u8, span.line);
+        self.chunk.write_u16(type_name_index, span.line);
+    }
+    
+    /// 编译 `new User { name: "a" }`：不带参数创建实例（执行字段初始化式和 init），再依次 InitField
+    fn compile_new_with_fields(&mut self, class_name: &str, fields: &[(String, Expr)], span: Span) {
+        let mut seen = std::collections::HashSet::new();
+        if let Some((name, value)) = fields.iter().find(|(name, _)| !seen.insert(name)) {
+            let msg = format!("Field '{}' is initialized more than once", name);
+            self.errors.push(CompileError::new(msg, value.span()));
+            return;
+        }
+        let class_name_index = self.chunk.add_constant(Value::string(class_name.to_string()));
+        self.chunk.write_op(OpCode::NewClass, span.line);
+        self.chunk.write_u16(class_name_index, span.line);
+        self.chunk.write(0, span.line);
+        for (field_name, value) in fields {
+            self.compile_expr(value);
+            let field_name_index = self.chunk.add_constant(Value::string(field_name.clone()));
+            self.chunk.write_op(OpCode::InitField, value.span().line);
+            self.chunk.write_u16(field_name_index, value.span().line);
+        }
+    }
+    
+    /// 生成调用父类方法的 InvokeSuper（this 和参数已经在栈上）
+    fn write_invoke_super(&mut self, parent: &str, method: &str, arg_count: usize, line: usize) {
+        let parent_index = self.chunk.add_constant(Value::string(parent.to_string()));
+        let method_index = self.chunk.add_constant(Value::string(method.to_string()));
+        self.chunk.write_op(OpCode::InvokeSuper, line);
+        self.chunk.write_u16(parent_index, line);
+        self.chunk.write_u16(method_index, line);
+        self.chunk.write(arg_count as u8, line);
+    }
+    
+    /// 静态字段赋值的目标不可用时的错误信息：类型或字段不存在、字段是常量、目标是枚举变体
+    fn static_assign_error(&self, type_name: &str, field: &str) -> Option<String> {
+        if self.chunk.get_enum(type_name).is_some() {
+            return Some(format!("Cannot assign to enum variant '{}::{}'", type_name, field));
+        }
+        let Some(type_info) = self.chunk.get_type(type_name) else {
+            return Some(format!("Unknown class '{}'", type_name));
+        };
+        if !type_info.static_fields.contains_key(field) {
+            Some(format!("Type '{}' has no static field '{}'", type_name, field))
+        } else if self.chunk.is_static_const(type_name, field) {
+            Some(format!("Cannot assign to constant '{}::{}'", type_name, field))
+        } else {
+            None
+        }
+    }
+    
+    /// 写入 GetStatic / SetStatic 及其操作数（类型名、字段名）
+    fn write_static_op(&mut self, op: OpCode, type_name: &str, field: &str, line: usize) {
+        let type_name_index = self.chunk.add_constant(Value::string(type_name.to_string()));
+        let field_index = self.chunk.add_constant(Value::string(field.to_string()));
+        self.chunk.write_op(op, line);
+        self.chunk.write_u16(type_name_index, line);
+        self.chunk.write_u16(field_index, line);
+    }
+    
+    /// 注册 class/struct 的静态字段
+    ///
+    /// 常量的值在编译期已经算出，直接放入常量池；普通静态字段编译为首次访问时执行的初始化函数
+    fn register_static_fields(&mut self, type_name: &str, fields: &[ClassField], span: Span) {
+        // 先登记有初始值的字段，初始值可以引用后面声明的字段（循环引用在运行时报错）
+        let initialized: Vec<&ClassField> = fields.iter().filter(|f| f.is_static && !f.is_const && f.initializer.is_some()).collect();
+        if !initialized.is_empty() {
+            let pending = self.chunk.add_constant(Value::null());
+            for field in &initialized {
+                self.chunk.register_static_field(type_name, field.name.clone(), pending);
+            }
+        }
+        for field in fields.iter().filter(|f| f.is_static) {
+            if field.is_const {
+                // 不可折叠的初始值已在 fold_program_consts 中报错
+                if let Some(value) = self.consts.get(&format!("{}::{}", type_name, field.name)) {
+                    let value_index = self.chunk.add_constant(Self::const_to_value(value));
+                    self.chunk.register_static_const(type_name, field.name.clone(), value_index);
+                }
+            } else if let Some(init) = &field.initializer {
+                // 先跳过初始化代码
+                let jump_over = self.chunk.write_jump(OpCode::Jump, span.line);
+                let value_start = self.chunk.current_offset();
+                
+                self.compile_expr(init);
+                self.chunk.write_op(OpCode::Return, span.line);
+                
+                self.patch_jump(jump_over, span);
+                let func_end = self.chunk.current_offset();
+                self.chunk.register_function_range(format!("{}::static_{}", type_name, field.name), value_start, func_end);
+                
+                // 创建一个"函数"来计算初始值
+                let init_func = crate::vm::value::Function {
+                    name: Some(format!("{}::static_{}", type_name, field.name)),
+                    arity: 0,
+                    required_params: 0,
+                    defaults: Vec::new(),
+                    has_variadic: false,
+                    chunk_index: value_start,
+                    local_count: 0,
+                    upvalues: Vec::new(),
+                    captures: Vec::new(),
+                };
+                let func_index = self.chunk.add_constant(Value::function(Arc::new(init_func)));
+                self.chunk.register_static_field(type_name, field.name.clone(), func_index);
+            } else {
+                // 没有初始值，使用 null（常量字段在解析器中已强制要求初始值）
+                let null_index = self.chunk.add_constant(Value::null());
+                self.chunk.register_static_field(type_name, field.name.clone(), null_index);
+            }
+        }
+    }
+    
+    /// 计算程序中所有顶层常量和 class/struct 常量成员
+    ///
+    /// 常量可以引用其他常量（包括后面声明的）；初始值引用了变量、函数调用等运行期的值时报告编译错误
+    fn fold_program_consts(&mut self, program: &Program) {
+        let items = const_items(program);
+        let (values, errors) = fold_consts(&items, &self.consts);
+        self.consts.extend(values);
+        for (name, err) in errors {
+            let span = items.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, expr)| expr.span())
+                .unwrap_or_default();
+            let msg = match err {
+                ConstEvalError::NotConstant => {
+                    format!("Initializer for const '{}' is not a compile-time constant expression", name)
+                }
+                ConstEvalError::Invalid(msg) => format!("Invalid initializer for const '{}': {}", name, msg),
+            };
+            self.errors.push(CompileError::new(msg, span));
+        }
+    }
+    
+    /// 把引用常量的变量模式改写为字面量模式（`match x { MAX => ... }` 比较而不是绑定）
+    fn resolve_const_pattern(&self, pattern: &MatchPattern, span: Span) -> MatchPattern {
+        match pattern {
+            MatchPattern::Variable(name)
+                if self.consts.contains_key(name) && self.symbols.resolve(name).is_none() =>
+            {
+                MatchPattern::Literal(Expr::Identifier { name: name.clone(), span })
+            }
+            MatchPattern::Or(patterns) => {
+                MatchPattern::Or(patterns.iter().map(|p| self.resolve_const_pattern(p, span)).collect())
+            }
+            MatchPattern::EnumVariant { enum_name, variant, fields } => MatchPattern::EnumVariant {
+                enum_name: enum_name.clone(),
+                variant: variant.clone(),
+                fields: fields.iter().map(|p| self.resolve_const_pattern(p, span)).collect(),
+            },
+            other => other.clone(),
+        }
+    }
+    
+    /// 编译枚举变体模式的 match 分支，返回分支体之后跳到 match 结束处的跳转
+    ///
+    /// 先检查变体和字面量字段，全部匹配之后才把要绑定的字段存入分支自己的作用域，
+    /// 所以不匹配时栈上只有比较结果，与其他模式的分支相同
+    fn compile_enum_variant_arm(
+        &mut self,
+        match_slot: usize,
+        enum_name: &str,
+        variant: &str,
+        fields: &[MatchPattern],
+        body: &Stmt,
+        span: Span,
+    ) -> Option<usize> {
+        let field_names = match self.chunk.get_enum(enum_name) {
+            Some(info) => match info.variants.iter().find(|v| v.name == variant) {
+                Some(info) => info.fields.clone(),
+                None => {
+                    let msg = format!("Enum '{}' has no variant '{}'", enum_name, variant);
+                    self.errors.push(CompileError::new(msg, span));
+                    return None;
+                }
+            },
+            None => {
+                let msg = format!("Unknown enum '{}'", enum_name);
+                self.errors.push(CompileError::new(msg, span));
+                return None;
+            }
+        };
+        if field_names.len() != fields.len() {
+            let msg = format!(
+                "pattern for {}::{} has {} fields, but the variant has {}",
+                enum_name, variant, fields.len(), field_names.len()
+            );
+            self.errors.push(CompileError::new(msg, span));
+            return None;
+        }
+        
+        let line = span.line;
+        let enum_name_index = self.chunk.add_constant(Value::string(enum_name.to_string()));
+        let variant_index = self.chunk.add_constant(Value::string(variant.to_string()));
+        self.chunk.write_get_local(match_slot, line);
+        self.chunk.write_op(OpCode::EnumMatch, line);
+        self.chunk.write_u16(enum_name_index, line);
+        self.chunk.write_u16(variant_index, line);
+        let mut fail_jumps = vec![self.chunk.write_jump(OpCode::JumpIfFalse, line)];
+        self.chunk.write_op(OpCode::Pop, line); // 弹出 true
+        
+        for (field, name) in fields.iter().zip(&field_names) {
+            if let MatchPattern::Literal(expr) = field {
+                let name_index = self.chunk.add_constant(Value::string(name.clone()));
+                self.chunk.write_get_local(match_slot, line);
+                self.chunk.write_op(OpCode::EnumGetField, line);
+                self.chunk.write_u16(name_index, line);
+                self.compile_expr(expr);
+                self.chunk.write_op(OpCode::Eq, line);
+                fail_jumps.push(self.chunk.write_jump(OpCode::JumpIfFalse, line));
+                self.chunk.write_op(OpCode::Pop, line); // 弹出 true
+            }
+        }
+        
+        self.symbols.begin_scope();
+        for (field, name) in fields.iter().zip(&field_names) {
+            if let MatchPattern::Variable(binding) = field {
+                let name_index = self.chunk.add_constant(Value::string(name.clone()));
+                self.chunk.write_get_local(match_slot, line);
+                self.chunk.write_op(OpCode::EnumGetField, line);
+                self.chunk.write_u16(name_index, line);
+                if let Err(msg) = self.declare_local(binding.clone(), Type::Unknown, line) {
+                    self.errors.push(CompileError::new(msg, span));
+                }
+            }
+        }
+        self.compile_stmt(body);
+        let pop_count = self.symbols.end_scope();
+        for _ in 0..pop_count {
+            self.chunk.write_op(OpCode::Pop, line);
+        }
+        let end_jump = self.chunk.write_jump(OpCode::Jump, line);
+        
+        for jump in fail_jumps {
+            self.patch_jump(jump, span);
+        }
+        self.chunk.write_op(OpCode::Pop, line); // 弹出 false
+        Some(end_jump)
+    }
+    
+    /// 复合赋值运算符对应的指令
+    fn compound_opcode(op: crate::parser::ast::AssignOp) -> OpCode {
+        match op.binary_op() {
+            Some(BinOp::Add) => OpCode::Add,
+            Some(BinOp::Sub) => OpCode::Sub,
+            Some(BinOp::Mul) => OpCode::Mul,
+            Some(BinOp::Div) => OpCode::Div,
+            Some(BinOp::Mod) => OpCode::Mod,
+            Some(BinOp::BitAnd) => OpCode::BitAnd,
+            Some(BinOp::BitOr) => OpCode::BitOr,
+            Some(BinOp::BitXor) => OpCode::BitXor,
+            Some(BinOp::Shl) => OpCode::Shl,
+            Some(BinOp::Shr) => OpCode::Shr,
+            _ => unreachable!("not a compound assignment operator"),
+        }
+    }
+    
+    /// 使用已知常量求值常量表达式
+    fn fold_const(&self, expr: &Expr) -> Result<ConstValue, ConstEvalError> {
+        eval_const(expr, &|name| self.consts.get(name).cloned())
+    }
+    
+    /// 优化时尝试在编译期求值表达式；未启用优化、含运行期的值或求值出错（留给运行时报告）时返回 None
+    fn fold_expr(&self, expr: &Expr) -> Option<ConstValue> {
+        if !self.folding() {
+            return None;
+        }
+        self.eval_const_expr(expr)
+    }
+    
+    /// 是否做常量折叠：启用了优化，且当前函数没有 `@no_optimize`
+    fn folding(&self) -> bool {
+        self.optimize && self.opt.fold
+    }
+    
+    /// 开始编译带属性的函数：`@no_optimize` 关闭全部优化。返回之前的设置，编译完函数后用它恢复
+    fn enter_function_attributes(&mut self, attributes: &[Attribute]) -> OptFlags {
+        let saved = self.opt;
+        if attributes.iter().any(|a| a.kind == AttributeKind::NoOptimize) {
+            self.opt = OptFlags::NONE;
+        }
+        saved
+    }
+    
+    /// 函数范围表中记录的属性名
+    fn attribute_names(attributes: &[Attribute]) -> Vec<String> {
+        attributes.iter().map(|a| a.kind.name().to_string()).collect()
+    }
+    
+    /// 编译期求值常量表达式（不论是否开启优化）
+    fn eval_const_expr(&self, expr: &Expr) -> Option<ConstValue> {
+        // 局部变量遮蔽同名常量
+        let lookup = |name: &str| {
+            if !name.contains("::") && self.symbols.resolve(name).is_some() {
+                return None;
+            }
+            self.consts.get(name).cloned()
+        };
+        eval_const(expr, &lookup).ok()
+    }
+    
+    /// 编译带关联数据的枚举变体 `Shape::Rect(w, h)`：参数按位置对应变体声明的字段
+    fn compile_enum_variant(&mut self, enum_name: &str, variant: &str, fields: &[String], args: &[(Option<String>, Expr)], span: Span) {
+        if fields.is_empty() {
+            let msg = format!("Enum variant '{}::{}' has no payload; write it without parentheses", enum_name, variant);
+            self.errors.push(CompileError::new(msg, span));
+            return;
+        }
+        if args.len() != fields.len() {
+            let msg = format!(
+                "Enum variant '{}::{}' takes {} payload values, got {}",
+                enum_name, variant, fields.len(), args.len()
+            );
+            self.errors.push(CompileError::new(msg, span));
+            return;
+        }
+        if !self.check_arg_count(args.len(), span) {
+            return;
+        }
+        
+        // 栈: [..., field_name_1, value_1, ..., field_name_n, value_n]
+        for (name, (_, arg)) in fields.iter().zip(args) {
+            let name_index = self.chunk.add_constant(Value::string(name.clone()));
+            self.chunk.write_op(OpCode::Const, span.line);
+            self.chunk.write_u16(name_index, span.line);
+            self.compile_expr(arg);
+        }
+        let enum_name_index = self.chunk.add_constant(Value::string(enum_name.to_string()));
+        let variant_index = self.chunk.add_constant(Value::string(variant.to_string()));
+        self.chunk.write_op(OpCode::NewEnumFields, span.line);
+        self.chunk.write_u16(enum_name_index, span.line);
+        self.chunk.write_u16(variant_index, span.line);
+        self.chunk.write(args.len() as u8, span.line);
+    }
+    
+    /// 编译并行赋值 `a, b = b, a`
+    ///
+    /// 先从左到右求值目标中的接收者和下标、再从左到右求值全部右值，都存入临时变量，
+    /// 最后按顺序赋值：每个子表达式只求值一次，右值不受前面赋值的影响
+    fn compile_parallel_assign(&mut self, targets: &[Expr], values: &[Expr], span: Span) {
+        use crate::parser::ast::AssignOp;
+        
+        self.symbols.begin_scope();
+        let mut lowered = Vec::with_capacity(targets.len());
+        for (i, target) in targets.iter().enumerate() {
+            lowered.push(match target {
+                Expr::Member { object, member, span } => Expr::Member {
+                    object: Box::new(self.compile_hidden_temp(object, format!("__assign_object{}__", i))),
+                    member: member.clone(),
+                    span: *span,
+                },
+                Expr::Index { object, index, span } => Expr::Index {
+                    object: Box::new(self.compile_hidden_temp(object, format!("__assign_object{}__", i))),
+                    index: Box::new(self.compile_hidden_temp(index, format!("__assign_index{}__", i))),
+                    span: *span,
+                },
+                target => target.clone(),
+            });
+        }
+        let values: Vec<Expr> = values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| self.compile_hidden_temp(value, format!("__assign_value{}__", i)))
+            .collect();
+        
+        for (target, value) in lowered.into_iter().zip(values) {
+            let assign = Expr::Assign { target: Box::new(target), op: AssignOp::Assign, value: Box::new(value), span };
+            self.compile_expr(&assign);
+            self.chunk.write_op(OpCode::Pop, span.line);
+        }
+        
+        let pop_count = self.symbols.end_scope();
+        for _ in 0..pop_count {
+            self.chunk.write_op(OpCode::Pop, span.line);
+        }
+    }
+    
+    /// 求值表达式存入当前作用域中的临时变量，返回读取这个临时变量的表达式
+    fn compile_hidden_temp(&mut self, expr: &Expr, name: String) -> Expr {
+        let span = expr.span();
+        self.compile_expr(expr);
+        if let Err(msg) = self.symbols.define(name.clone(), Type::Unknown, false) {
+            self.errors.push(CompileError::new(msg, span));
+        }
+        Expr::Identifier { name, span }
+    }
+    
+    /// 编译 switch 语句
+    ///
+    /// 主体表达式求值一次存入临时变量。分支的值都是整数常量、至少 JUMP_TABLE_MIN_VALUES 个
+    /// 并且表中至少一半是分支值时生成跳转表，否则依次与每个值比较（字符串、稀疏的整数、非常量）
+    fn compile_switch(&mut self, expr: &Expr, cases: &[SwitchCase], default: Option<&Stmt>, span: Span) {
+        self.symbols.begin_scope();
+        self.compile_expr(expr);
+        let slot = match self.symbols.define(format!("__switch_{}__", span.line), Type::Unknown, false) {
+            Ok(slot) => slot,
+            Err(msg) => {
+                self.errors.push(CompileError::new(msg, span));
+                return;
+            }
+        };
+        
+        // 常量值重复时后面的分支永远不会执行
+        let mut constants: Vec<(usize, ConstValue)> = Vec::new();
+        let mut all_ints = true;
+        for (index, case) in cases.iter().enumerate() {
+            for value in &case.values {
+                match self.eval_const_expr(value) {
+                    Some(constant) => {
+                        if constants.iter().any(|(_, seen)| *seen == constant) {
+                            let msg = format!("duplicate case value {} in switch", Self::const_to_value(&constant));
+                            self.errors.push(CompileError::new(msg, value.span()));
+                        }
+                        all_ints &= matches!(constant, ConstValue::Int(_));
+                        constants.push((index, constant));
+                    }
+                    None => all_ints = false,
+                }
+            }
+        }
+        let ints: Vec<(usize, i128)> = constants
+            .iter()
+            .filter_map(|(index, constant)| match constant {
+                ConstValue::Int(n) => Some((*index, *n)),
+                _ => None,
+            })
+            .collect();
+        let range = ints.iter().map(|&(_, n)| n).min().zip(ints.iter().map(|&(_, n)| n).max());
+        let table = match range {
+            Some((min, max)) if all_ints && ints.len() >= JUMP_TABLE_MIN_VALUES => {
+                // 表项数，溢出或超过一半是空项时不用跳转表
+                max.checked_sub(min)
+                    .and_then(|span| u16::try_from(span).ok())
+                    .and_then(|span| span.checked_add(1))
+                    .filter(|&count| count as usize <= ints.len() * 2)
+                    .map(|count| (min, count))
+            }
+            _ => None,
+        };
+        
+        let mut end_jumps = Vec::new();
+        if let Some((min, count)) = table {
+            self.chunk.write_get_local(slot, span.line);
+            let table = self.chunk.write_jump_table(min, count, span.line);
+            let mut filled = vec![false; count as usize];
+            for (index, case) in cases.iter().enumerate() {
+                for &(_, n) in ints.iter().filter(|(i, _)| *i == index) {
+                    let entry = (n - min) as usize;
+                    filled[entry] = true;
+                    if let Err(distance) = self.chunk.patch_jump_table(table, count, entry) {
+                        self.jump_too_far(distance, span);
+                    }
+                }
+                self.compile_stmt(&case.body);
+                end_jumps.push(self.chunk.write_jump(OpCode::Jump, span.line));
+            }
+            // 没有分支的值和 default 表项都跳到 default
+            for entry in (0..=count as usize).filter(|&entry| !filled.get(entry).copied().unwrap_or(false)) {
+                if let Err(distance) = self.chunk.patch_jump_table(table, count, entry) {
+                    self.jump_too_far(distance, span);
+                }
+            }
+        } else {
+            for case in cases {
+                let mut matched = Vec::new();
+                for value in &case.values {
+                    self.chunk.write_get_local(slot, span.line);
+                    self.compile_expr(value);
+                    self.chunk.write_op(OpCode::Eq, span.line);
+                    matched.push(self.chunk.write_jump(OpCode::JumpIfTrue, span.line));
+                    self.chunk.write_op(OpCode::Pop, span.line); // 弹出 false，继续比较下一个值
+                }
+                let next_case = self.chunk.write_jump(OpCode::Jump, span.line);
+                for jump in matched {
+                    self.patch_jump(jump, span);
+                }
+                self.chunk.write_op(OpCode::Pop, span.line); // 弹出 true
+                self.compile_stmt(&case.body);
+                end_jumps.push(self.chunk.write_jump(OpCode::Jump, span.line));
+                self.patch_jump(next_case, span);
+            }
+        }
+        
+        if let Some(default) = default {
+            self.compile_stmt(default);
+        }
+        for jump in end_jumps {
+            self.patch_jump(jump, span);
+        }
+        
+        // 弹出临时变量
+        let pop_count = self.symbols.end_scope();
+        for _ in 0..pop_count {
+            self.chunk.write_op(OpCode::Pop, span.line);
+        }
+    }
+    
+    /// 优化时求值为常量的 bool 条件
+    fn constant_condition(&self, condition: &Expr) -> Option<bool> {
+        match self.fold_expr(condition)? {
+            ConstValue::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+    
+    /// 在条件上下文中编译表达式：值为真（jump_when 为 true 时）或为假时跳转，否则继续执行下一条指令，
+    /// 两种情况下栈都与编译前相同。返回等待回填到跳转目标的跳转
+    ///
+    /// 比较直接编译为比较并跳转，`&&`、`||`、`!` 编译为跳转的组合，不在栈上生成中间的布尔值；
+    /// 操作数仍按从左到右的顺序求值，短路时不求值右侧
+    fn compile_condition(&mut self, expr: &Expr, jump_when: bool) -> Vec<CondJump> {
+        let line = expr.span().line;
+        if !self.opt.condition_jumps {
+            self.compile_expr(expr);
+            return vec![self.condition_value_jump(jump_when, line)];
+        }
+        if let Some(value) = self.constant_condition(expr) {
+            // 常量条件：总是跳转或总是继续
+            return if value == jump_when {
+                vec![CondJump { offset: self.chunk.write_jump(OpCode::Jump, line), signed: false }]
+            } else {
+                Vec::new()
+            };
+        }
+        match expr {
+            Expr::Grouping { expr, .. } => self.compile_condition(expr, jump_when),
+            Expr::Unary { op: UnaryOp::Not, operand, .. } => self.compile_condition(operand, !jump_when),
+            Expr::Binary { left, op: op @ (BinOp::And | BinOp::Or), right, span } => {
+                // `a && b` 为假、`a || b` 为真时都可以由任一侧决定：两侧的跳转去同一个目标
+                let decided_by_either = (*op == BinOp::Or) == jump_when;
+                if decided_by_either {
+                    let mut jumps = self.compile_condition(left, jump_when);
+                    jumps.extend(self.compile_condition(right, jump_when));
+                    jumps
+                } else {
+                    // 左侧已经决定了相反的结果时跳过右侧
+                    let skip = self.compile_condition(left, !jump_when);
+                    let jumps = self.compile_condition(right, jump_when);
+                    self.patch_condition_jumps(skip, *span);
+                    jumps
+                }
+            }
+            Expr::Binary { left, op, right, span } => match Self::comparison(*op) {
+                Some(comparison) => {
+                    if let Some(jump) = self.compile_local_const_jump(left, comparison, right, jump_when, line) {
+                        return vec![jump];
+                    }
+                    self.compile_expr(left);
+                    self.compile_expr(right);
+                    let offset = self.chunk.write_compare_jump(comparison, jump_when, span.line);
+                    // 比较出错时插入符号画在整个比较表达式下
+                    self.chunk.mark_span(span.line, span.column, span.end.saturating_sub(span.start));
+                    vec![CondJump { offset, signed: false }]
+                }
+                None => {
+                    self.compile_expr(expr);
+                    vec![self.condition_value_jump(jump_when, line)]
+                }
+            },
+            _ => {
+                self.compile_expr(expr);
+                vec![self.condition_value_jump(jump_when, line)]
+            }
+        }
+    }
+    
+    /// 按栈顶值的真假跳转并弹出它
+    fn condition_value_jump(&mut self, jump_when: bool, line: usize) -> CondJump {
+        let offset = if jump_when {
+            self.chunk.write_jump_if_true_pop(line)
+        } else {
+            self.chunk.write_jump_if_false_pop(line)
+        };
+        CondJump { offset, signed: false }
+    }
+    
+    /// 比较运算符对应的 [`Comparison`]
+    fn comparison(op: BinOp) -> Option<Comparison> {
+        Some(match op {
+            BinOp::Lt => Comparison::Lt,
+            BinOp::Le => Comparison::Le,
+            BinOp::Gt => Comparison::Gt,
+            BinOp::Ge => Comparison::Ge,
+            BinOp::Eq => Comparison::Eq,
+            BinOp::Ne => Comparison::Ne,
+            _ => None?,
+        })
+    }
+    
+    /// 整数局部变量与小整数常量的大小比较：生成不读写栈的 JumpIfLocal*Const，不能使用时返回 None
+    ///
+    /// 两侧都是整数，比较结果取反就是相反的比较（`!(x < c)` 即 `x >= c`），常量在左侧时交换两侧
+    fn compile_local_const_jump(&mut self, left: &Expr, comparison: Comparison, right: &Expr, jump_when: bool, line: usize) -> Option<CondJump> {
+        let (name, value, comparison) = match (left, right) {
+            (Expr::Identifier { name, .. }, Expr::Integer { value, .. }) => (name, *value, comparison),
+            (Expr::Integer { value, .. }, Expr::Identifier { name, .. }) => (name, *value, match comparison {
+                Comparison::Lt => Comparison::Gt,
+                Comparison::Le => Comparison::Ge,
+                Comparison::Gt => Comparison::Lt,
+                Comparison::Ge => Comparison::Le,
+                Comparison::Eq | Comparison::Ne => return None,
+            }),
+            _ => return None,
+        };
+        let value = i8::try_from(value).ok()?;
+        let symbol = self.symbols.resolve(name)?;
+        if symbol.is_cell || !self.is_fast_int_type(&symbol.ty) {
+            return None;
+        }
+        let slot = u8::try_from(symbol.slot).ok()?;
+        let comparison = if jump_when {
+            comparison
+        } else {
+            match comparison {
+                Comparison::Lt => Comparison::Ge,
+                Comparison::Le => Comparison::Gt,
+                Comparison::Gt => Comparison::Le,
+                Comparison::Ge => Comparison::Lt,
+                Comparison::Eq | Comparison::Ne => return None,
+            }
+        };
+        let op = match comparison {
+            Comparison::Lt => OpCode::JumpIfLocalLtConst,
+            Comparison::Le => OpCode::JumpIfLocalLeConst,
+            Comparison::Gt => OpCode::JumpIfLocalGtConst,
+            Comparison::Ge => OpCode::JumpIfLocalGeConst,
+            Comparison::Eq | Comparison::Ne => return None,
+        };
+        let offset = self.chunk.write_jump_if_local_const(op, slot, value, line);
+        Some(CondJump { offset, signed: true })
+    }
+    
+    /// 把条件跳转回填到当前位置
+    fn patch_condition_jumps(&mut self, jumps: Vec<CondJump>, span: Span) {
+        for jump in jumps {
+            if !jump.signed {
+                self.patch_jump(jump.offset, span);
+            } else if let Err(distance) = self.chunk.patch_signed_jump(jump.offset) {
+                let msg = format!(
+                    "function too large: jump of {} bytes exceeds the limit of {}",
+                    distance, i16::MAX
+                );
+                self.errors.push(CompileError::new(msg, span));
+            }
+        }
+    }
+    
+    /// 加载编译期常量
+    fn write_const_value(&mut self, value: &ConstValue, line: usize) {
+        match value {
+            ConstValue::Int(n) if (-128..=127).contains(n) => self.chunk.write_const_int8(*n as i8, line),
+            _ => self.chunk.write_constant(Self::const_to_value(value), line),
+        }
+    }
+    
+    /// 编译期常量转换为运行时值
+    fn const_to_value(value: &ConstValue) -> Value {
+        match value {
+            ConstValue::Int(n) => Value::int(*n),
+            ConstValue::Float(f) => Value::float(*f),
+            ConstValue::Bool(b) => Value::bool(*b),
+            ConstValue::Char(c) => Value::char(*c),
+            ConstValue::String(s) => Value::string(s.clone()),
+            ConstValue::Null => Value::null(),
+        }
+    }
+    
     /// 编译 struct 方法
     fn compile_struct_method(&mut self, struct_name: &str, method: &crate::parser::ast::StructMethod, _span: Span) {
         use crate::parser::ast::StructMethod;
         
-        let StructMethod { name, params, return_type: _, body, visibility: _, span: method_span } = method;
+        let StructMethod { name, params, return_type: _, body, visibility: _, is_static, attributes, span: method_span } = method;
         
         // 1. 写一个跳转指令跳过方法体
         let jump_over = self.chunk.write_jump(OpCode::Jump, method_span.line);
@@ -1433,16 +2913,22 @@ impl Compiler {
         // 3. 保存符号表状态
         let saved_state = self.symbols.save_state();
         let saved_scope_depth = self.symbols.scope_depth();
-        self.symbols.reset_for_function();
+        let saved_try_blocks = std::mem::take(&mut self.try_blocks);
+        let saved_panic_slots = std::mem::take(&mut self.panic_slots);
+        self.symbols.reset_for_function(Captures::analyze_body(body));
         
-        // 4. 定义 this 参数（隐式第一个参数）
-        if let Err(msg) = self.symbols.define("this".to_string(), Type::Unknown, false) {
-            self.errors.push(CompileError::new(msg, *method_span));
+        // 4. 对于非静态方法，定义 this 参数（隐式第一个参数）
+        let mut arity = params.len();
+        let mut required_params = 0;
+        if !*is_static {
+            if let Err(msg) = self.symbols.define("this".to_string(), Type::Unknown, false) {
+                self.errors.push(CompileError::new(msg, *method_span));
+            }
+            arity += 1;
+            required_params += 1;
         }
         
         // 5. 定义其他参数
-        let arity = params.len() + 1; // +1 for this
-        let mut required_params = 1; // this is required
         let mut defaults = Vec::new();
         let mut has_default = false;
         let mut has_variadic = false;
@@ -1493,7 +2979,9 @@ impl Compiler {
         }
         
         // 6. 编译方法体
+        let saved_opt = self.enter_function_attributes(attributes);
         self.compile_function_body(body);
+        self.opt = saved_opt;
         
         // 7. 添加隐式返回
         let needs_return = self.chunk.code.is_empty() 
@@ -1505,13 +2993,19 @@ impl Compiler {
         }
         
         // 8. 计算局部变量数量
+        self.check_local_slots(&format!("{}::{}", struct_name, name), *method_span);
+        self.check_param_count(&format!("{}::{}", struct_name, name), arity, *method_span);
         let local_count = self.symbols.local_count();
         
         // 9. 恢复符号表
         self.symbols.restore_state_full(saved_state, saved_scope_depth);
+        self.try_blocks = saved_try_blocks;
+        self.panic_slots = saved_panic_slots;
         
         // 10. 回填跳转
-        self.chunk.patch_jump(jump_over);
+        self.patch_jump(jump_over, *method_span);
+        let func_end = self.chunk.current_offset();
+        self.chunk.register_function_range_with_attributes(format!("{}::{}", struct_name, name), func_start, func_end, Self::attribute_names(attributes));
         
         // 11. 创建函数对象
         let func = crate::vm::value::Function {
@@ -1523,41 +3017,24 @@ impl Compiler {
             chunk_index: func_start,
             local_count,
             upvalues: Vec::new(),
+            captures: Vec::new(),
         };
         
-        // 12. 添加到常量池并注册方法
+        // 12. 添加到常量池并注册方法（静态或实例）
         let func_index = self.chunk.add_constant(Value::function(Arc::new(func)));
-        self.chunk.register_method(struct_name, name.clone(), func_index);
+        if *is_static {
+            self.chunk.register_static_method(struct_name, name.clone(), func_index);
+        } else {
+            self.chunk.register_method(struct_name, name.clone(), func_index);
+        }
     }
     
     /// 编译 class 方法
-    fn compile_class_method(&mut self, class_name: &str, method: &crate::parser::ast::ClassMethod, parent: Option<&str>, _span: Span) {
+    fn compile_class_method(&mut self, class_name: &str, method: &crate::parser::ast::ClassMethod, parent: Option<&str>, fields: &[ClassField]) {
         use crate::parser::ast::ClassMethod;
         
-        let ClassMethod { name, params, return_type: _, body, visibility: _, is_static, is_override, is_abstract, span: method_span } = method;
-        
-        // override 检查：如果标记了 override，父类必须有同名方法
-        if *is_override {
-            if let Some(parent_name) = parent {
-                // 检查父类是否有该方法
-                let parent_has_method = self.chunk.get_method(parent_name, name).is_some();
-                if !parent_has_method {
-                    let msg = format!(
-                        "Method '{}' is marked as override but parent class '{}' has no such method",
-                        name, parent_name
-                    );
-                    self.errors.push(CompileError::new(msg, *method_span));
-                    return;
-                }
-            } else {
-                let msg = format!(
-                    "Method '{}' is marked as override but class '{}' has no parent class",
-                    name, class_name
-                );
-                self.errors.push(CompileError::new(msg, *method_span));
-                return;
-            }
-        }
+        // override / final 由类型检查器验证
+        let ClassMethod { name, params, return_type: _, body, visibility: _, is_static, is_override: _, is_final: _, is_abstract, attributes, span: method_span } = method;
         
         // 抽象方法没有方法体，只注册签名
         if *is_abstract {
@@ -1586,7 +3063,9 @@ impl Compiler {
         // 3. 保存符号表状态
         let saved_state = self.symbols.save_state();
         let saved_scope_depth = self.symbols.scope_depth();
-        self.symbols.reset_for_function();
+        let saved_try_blocks = std::mem::take(&mut self.try_blocks);
+        let saved_panic_slots = std::mem::take(&mut self.panic_slots);
+        self.symbols.reset_for_function(Captures::analyze_body(body));
         
         let mut arity = params.len();
         let mut required_params = 0;
@@ -1649,28 +3128,16 @@ impl Compiler {
             }
         }
         
-        // 5.5 对于 init 方法，生成构造函数参数属性提升的字段赋值
+        // 6. 编译方法体（init 的开头还有父类 init 的调用、字段初始化式和参数属性提升）
+        let saved_opt = self.enter_function_attributes(attributes);
+        let saved_super = std::mem::replace(&mut self.super_class, parent.map(str::to_string));
         if name == "init" && !*is_static {
-            for (param_idx, param) in params.iter().enumerate() {
-                if param.is_field {
-                    // 生成 this.field = param 的字节码
-                    // 1. 加载 this（槽 0）
-                    self.chunk.write_get_local(0, method_span.line);
-                    // 2. 加载参数值（槽 1 + param_idx，因为 this 在槽 0）
-                    self.chunk.write_get_local(1 + param_idx, method_span.line);
-                    // 3. SetField
-                    let field_name_index = self.chunk.add_constant(Value::string(param.name.clone()));
-                    self.chunk.write_op(OpCode::SetField, method_span.line);
-                    self.chunk.write_u16(field_name_index, method_span.line);
-                    // SetField 会将栈顶的值弹出，保留对象在栈上
-                    // 我们需要弹出 this
-                    self.chunk.write_op(OpCode::Pop, method_span.line);
-                }
-            }
+            self.compile_init_body(parent, fields, params, body);
+        } else {
+            self.compile_function_body(body);
         }
-        
-        // 6. 编译方法体
-        self.compile_function_body(body);
+        self.super_class = saved_super;
+        self.opt = saved_opt;
         
         // 7. 添加隐式返回
         let needs_return = self.chunk.code.is_empty() 
@@ -1688,13 +3155,19 @@ impl Compiler {
         }
         
         // 8. 计算局部变量数量
+        self.check_local_slots(&format!("{}::{}", class_name, name), *method_span);
+        self.check_param_count(&format!("{}::{}", class_name, name), arity, *method_span);
         let local_count = self.symbols.local_count();
         
         // 9. 恢复符号表
         self.symbols.restore_state_full(saved_state, saved_scope_depth);
+        self.try_blocks = saved_try_blocks;
+        self.panic_slots = saved_panic_slots;
         
         // 10. 回填跳转
-        self.chunk.patch_jump(jump_over);
+        self.patch_jump(jump_over, *method_span);
+        let func_end = self.chunk.current_offset();
+        self.chunk.register_function_range_with_attributes(format!("{}::{}", class_name, name), func_start, func_end, Self::attribute_names(attributes));
         
         // 11. 创建函数对象
         let func = crate::vm::value::Function {
@@ -1706,6 +3179,7 @@ impl Compiler {
             chunk_index: func_start,
             local_count,
             upvalues: Vec::new(),
+            captures: Vec::new(),
         };
         
         // 12. 添加到常量池并注册方法（静态或实例）
@@ -1713,12 +3187,454 @@ impl Compiler {
         if *is_static {
             self.chunk.register_static_method(class_name, name.clone(), func_index);
         } else {
-            self.chunk.register_method(class_name, name.clone(), func_index);
+            self.chunk.register_method(class_name, name.clone(), func_index);
+        }
+    }
+
+    /// 编译函数调用（普通函数、方法、内置函数和标准库函数）
+    fn compile_call(&mut self, callee: &Expr, args: &[(Option<String>, Expr)], span: &Span) {
+        // 提取参数值（命名参数将在后面处理）
+        let has_named_args = args.iter().any(|(name, _)| name.is_some());
+        
+        // 检查是否是内置函数（内置函数不支持命名参数）
+        if let Expr::Identifier { name, .. } = callee {
+            if has_named_args {
+                // 内置函数不支持命名参数，但仍需检查
+                match name.as_str() {
+                    "print" | "println" | "typeof" | "typeinfo" | "sizeof" | "panic" | "time" | "hexdump" | "printHexdump" => {
+                        let msg = "Built-in functions do not support named arguments".to_string();
+                        self.errors.push(CompileError::new(msg, *span));
+                        return;
+                    }
+                    _ => {}
+                }
+            }
+            
+            match name.as_str() {
+                "print" if args.len() == 1 => {
+                    self.compile_expr(&args[0].1);
+                    self.chunk.write_op(OpCode::Print, span.line);
+                    // 内置函数需要返回值，以便作为表达式使用
+                    self.chunk.write_constant(Value::null(), span.line);
+                    return;
+                }
+                "println" if args.len() == 1 => {
+                    self.compile_expr(&args[0].1);
+                    self.chunk.write_op(OpCode::PrintLn, span.line);
+                    // 内置函数需要返回值，以便作为表达式使用
+                    self.chunk.write_constant(Value::null(), span.line);
+                    return;
+                }
+                "typeof" if args.len() == 1 => {
+                    self.compile_expr(&args[0].1);
+                    self.chunk.write_op(OpCode::TypeOf, span.line);
+                    return;
+                }
+                "typeinfo" if args.len() == 1 => {
+                    // 获取完整的运行时类型信息对象
+                    self.compile_expr(&args[0].1);
+                    self.chunk.write_op(OpCode::TypeInfo, span.line);
+                    return;
+                }
+                "sizeof" if args.len() == 1 => {
+                    self.compile_expr(&args[0].1);
+                    self.chunk.write_op(OpCode::SizeOf, span.line);
+                    return;
+                }
+                "panic" if args.len() == 1 => {
+                    self.compile_expr(&args[0].1);
+                    self.chunk.write_op(OpCode::Panic, span.line);
+                    return;
+                }
+                // onPanic 块中取得 panic 的值，其他地方（包括 onPanic 块中定义的函数）为 null
+                "recover"
+                    if args.is_empty()
+                        && self.symbols.resolve_slot(name).is_none()
+                        && self.chunk.get_named_function(name).is_none() =>
+                {
+                    match self.panic_slots.last() {
+                        Some(&slot) => self.chunk.write_get_local(slot, span.line),
+                        None => self.chunk.write_constant(Value::null(), span.line),
+                    }
+                    return;
+                }
+                // [deprecated] time() 函数可能在未来版本移除，请使用 std.time 的 Time.now()
+                "time" if args.is_empty() => {
+                    self.chunk.write_op(OpCode::Time, span.line);
+                    return;
+                }
+                // 同名的局部变量和函数优先
+                "hexdump" | "printHexdump"
+                    if matches!(args.len(), 1 | 2)
+                        && self.symbols.resolve_slot(name).is_none()
+                        && self.chunk.get_named_function(name).is_none() =>
+                {
+                    for (_, arg) in args {
+                        self.compile_expr(arg);
+                    }
+                    self.chunk.write_op(OpCode::Hexdump, span.line);
+                    self.chunk.write(args.len() as u8, span.line);
+                    self.chunk.write(u8::from(name == "printHexdump"), span.line);
+                    return;
+                }
+                _ => {}
+            }
+            
+            // 导入的标准库函数（局部变量和同名函数优先）
+            if self.symbols.resolve_slot(name).is_none() && self.chunk.get_named_function(name).is_none() {
+                if let Some(module) = self.stdlib_functions.get(name).cloned() {
+                    self.emit_stdlib_call(module, name.clone(), args, *span);
+                    return;
+                }
+            }
+        }
+        
+        // 宿主函数调用 host.name(args)（同名局部变量、函数和类优先）
+        if let Expr::Member { object, member, .. } = callee {
+            if let Expr::Identifier { name, .. } = object.as_ref() {
+                if name == HOST_NAMESPACE
+                    && self.symbols.resolve_slot(name).is_none()
+                    && self.chunk.get_named_function(name).is_none()
+                    && self.chunk.get_type(name).is_none()
+                {
+                    self.emit_host_call(&format!("{}.{}", name, member), args, *span);
+                    return;
+                }
+            }
+        }
+        
+        // 标准库命名空间调用 (Json.parse(args) / Json::parse(args))
+        let namespace_call = match callee {
+            Expr::Member { object, member, .. } => match object.as_ref() {
+                Expr::Identifier { name, .. } => self.resolve_stdlib_namespace_call(name, member),
+                _ => None,
+            },
+            Expr::StaticMember { class_name, member, .. } => {
+                self.resolve_stdlib_namespace_call(class_name, member)
+            }
+            _ => None,
+        };
+        if let Some((module, func)) = namespace_call {
+            self.emit_stdlib_call(module, func, args, *span);
+            return;
+        }
+        
+        // 检查是否是静态成员调用 (ClassName::method(args))
+        if let Expr::StaticMember { class_name, member, span: member_span } = callee {
+            // 带关联数据的枚举变体 Shape::Rect(w, h)
+            let variant_fields = self.chunk.get_enum(class_name)
+                .and_then(|info| info.variants.iter().find(|v| v.name == *member))
+                .map(|variant| variant.fields.clone());
+            if let Some(fields) = variant_fields {
+                self.compile_enum_variant(class_name, member, &fields, args, *span);
+                return;
+            }
+            
+            // 检查是否是枚举的内置方法
+            let is_enum_builtin = if self.chunk.get_enum(class_name).is_some() {
+                member == "fromValue" || member == "values"
+            } else {
+                false
+            };
+            
+            if is_enum_builtin {
+                if !self.check_arg_count(args.len(), *span) {
+                    return;
+                }
+                
+                // 枚举内置方法，生成 InvokeStatic 调用
+                let class_name_index = self.chunk.add_constant(Value::string(class_name.clone()));
+                let method_name_index = self.chunk.add_constant(Value::string(member.clone()));
+                
+                // 先编译所有参数
+                for (_, arg) in args {
+                    self.compile_expr(arg);
+                }
+                
+                // 生成 InvokeStatic 指令
+                self.chunk.write_op(OpCode::InvokeStatic, span.line);
+                self.chunk.write_u16(class_name_index, span.line);
+                self.chunk.write_u16(method_name_index, span.line);
+                self.chunk.write(args.len() as u8, span.line);
+                return;
+            }
+            
+            // 检查是否是静态方法
+            let has_static_method = self.chunk.get_static_method(class_name, member).is_some();
+            
+            if has_static_method {
+                let func_index = self.chunk.get_static_method(class_name, member).unwrap();
+                
+                // 检查参数数量
+                if !self.check_arg_count(args.len(), *span) {
+                    return;
+                }
+                
+                // 生成调用静态方法的指令
+                // 1. 先从常量池加载函数
+                self.chunk.write_op(OpCode::Const, span.line);
+                self.chunk.write_u16(func_index, span.line);
+                
+                // 2. 然后编译所有参数
+                for (_, arg) in args {
+                    self.compile_expr(arg);
+                }
+                
+                // 3. 发出调用指令
+                self.chunk.write_op(OpCode::Call, span.line);
+                self.chunk.write(args.len() as u8, span.line);
+                return;
+            } else {
+                let msg = format!("Type '{}' has no static method '{}'", class_name, member);
+                self.errors.push(CompileError::new(msg, *member_span));
+                return;
+            }
+        }
+        
+        // 检查是否是方法调用 (obj.method(args))
+        if let Expr::Member { object, member, span: member_span } = callee {
+            // 检查是否是 super 调用 (super.method(args))
+            if matches!(object.as_ref(), Expr::Super { .. }) {
+                if member == "init" && !self.in_super_init_call {
+                    let msg = "super.init(...) can only be called as the first statement of init".to_string();
+                    self.errors.push(CompileError::new(msg, *span));
+                    return;
+                }
+                let Some(parent) = self.super_class.clone() else {
+                    let msg = "'super' requires a parent class".to_string();
+                    self.errors.push(CompileError::new(msg, *span));
+                    return;
+                };
+                
+                // 编译 this（super 方法需要 this 作为 receiver）
+                if let Some(slot) = self.symbols.resolve_slot("this") {
+                    self.chunk.write_get_local(slot, span.line);
+                } else {
+                    let msg = "'super' can only be used inside a class method".to_string();
+                    self.errors.push(CompileError::new(msg, *span));
+                    return;
+                }
+                
+                // 编译所有参数
+                for (_, arg) in args {
+                    self.compile_expr(arg);
+                }
+                
+                // 检查参数数量
+                if !self.check_arg_count(args.len(), *span) {
+                    return;
+                }
+                
+                self.write_invoke_super(&parent, member, args.len(), span.line);
+                return;
+            }
+            
+            // 检查是否是静态方法调用 (ClassName.method(args))
+            if let Expr::Identifier { name: class_name, .. } = object.as_ref() {
+                // 检查是否是已注册的类名
+                if self.chunk.get_type(class_name).is_some() {
+                    // 静态方法调用
+                    if let Some(func_index) = self.chunk.get_static_method(class_name, member) {
+                        // 检查参数数量
+                        if !self.check_arg_count(args.len(), *span) {
+                            return;
+                        }
+                        
+                        // 生成调用静态方法的指令
+                        // 栈布局: [func, arg1, arg2, ...] -> Call -> [result]
+                        // 1. 先从常量池加载函数
+                        self.chunk.write_op(OpCode::Const, span.line);
+                        self.chunk.write_u16(func_index, span.line);
+                        
+                        // 2. 然后编译所有参数
+                        for (_, arg) in args {
+                            self.compile_expr(arg);
+                        }
+                        
+                        // 3. 发出调用指令
+                        self.chunk.write_op(OpCode::Call, span.line);
+                        self.chunk.write(args.len() as u8, span.line);
+                        return;
+                    } else {
+                        let msg = format!("Type '{}' has no static method '{}'", class_name, member);
+                        self.errors.push(CompileError::new(msg, *member_span));
+                        return;
+                    }
+                }
+            }
+            
+            // 实例方法调用
+            // 编译对象表达式（receiver）；内置数值类型的静态方法（Int.parse）的 receiver 是类型引用
+            match object.as_ref() {
+                Expr::Identifier { name, .. }
+                    if BUILTIN_NAMESPACES.contains(&name.as_str())
+                        && self.symbols.resolve_slot(name).is_none()
+                        && self.chunk.get_named_function(name).is_none() =>
+                {
+                    let index = self.chunk.add_constant(Value::type_ref(name.clone()));
+                    self.chunk.write_op(OpCode::Const, span.line);
+                    self.chunk.write_u16(index, span.line);
+                }
+                _ => self.compile_expr(object),
+            }
+            
+            // 编译所有参数
+            for (_, arg) in args {
+                self.compile_expr(arg);
+            }
+            
+            // 检查参数数量
+            if !self.check_arg_count(args.len(), *span) {
+                return;
+            }
+            
+            // 将方法名添加到常量池
+            let method_name_index = self.chunk.add_constant(Value::string(member.clone()));
+            
+            // 生成 InvokeMethod 指令
+            self.chunk.write_op(OpCode::InvokeMethod, span.line);
+            self.chunk.write_u16(method_name_index, span.line);
+            self.chunk.write(args.len() as u8, span.line);
+            return;
+        }
+        
+        // 检查是否是安全方法调用 (obj?.method(args))
+        if let Expr::SafeMember { object, member, span: member_span } = callee {
+            // 编译对象表达式
+            self.compile_expr(object);
+            
+            // 编译所有参数
+            for (_, arg) in args {
+                self.compile_expr(arg);
+            }
+            
+            // 检查参数数量
+            if !self.check_arg_count(args.len(), *span) {
+                return;
+            }
+            
+            // 将方法名添加到常量池
+            let method_name_index = self.chunk.add_constant(Value::string(member.clone()));
+            
+            // 生成 SafeInvokeMethod 指令（如果对象为 null 则返回 null）
+            self.chunk.write_op(OpCode::SafeInvokeMethod, member_span.line);
+            self.chunk.write_u16(method_name_index, member_span.line);
+            self.chunk.write(args.len() as u8, member_span.line);
+            return;
+        }
+        
+        // 检查是否是非空断言方法调用 (obj!.method(args))
+        if let Expr::NonNullMember { object, member, span: member_span } = callee {
+            // 编译对象表达式
+            self.compile_expr(object);
+            
+            // 编译所有参数
+            for (_, arg) in args {
+                self.compile_expr(arg);
+            }
+            
+            // 检查参数数量
+            if !self.check_arg_count(args.len(), *span) {
+                return;
+            }
+            
+            // 将方法名添加到常量池
+            let method_name_index = self.chunk.add_constant(Value::string(member.clone()));
+            
+            // 生成 NonNullInvokeMethod 指令（如果对象为 null 则 panic）
+            self.chunk.write_op(OpCode::NonNullInvokeMethod, member_span.line);
+            self.chunk.write_u16(method_name_index, member_span.line);
+            self.chunk.write(args.len() as u8, member_span.line);
+            return;
+        }
+        
+        // 用户定义函数调用
+        // 1. 编译被调用的表达式（将函数值压栈）
+        self.compile_expr(callee);
+        
+        // 2. 编译所有参数（依次压栈）
+        // 如果有命名参数，需要根据函数定义重新排列参数顺序
+        if has_named_args {
+            // 命名参数调用：需要根据函数定义重排参数
+            // 尝试获取函数的参数名列表
+            let param_names = if let Expr::Identifier { name, .. } = callee {
+                self.symbols.resolve(name).and_then(|s| s.param_names.clone())
+            } else {
+                None
+            };
+            
+            if let Some(param_names) = param_names {
+                // 有参数名信息，进行重排
+                // 1. 分离位置参数和命名参数
+                let mut positional_args: Vec<&Expr> = Vec::new();
+                let mut named_args: std::collections::HashMap<&str, &Expr> = std::collections::HashMap::new();
+                
+                for (name, arg) in args {
+                    if let Some(n) = name {
+                        named_args.insert(n.as_str(), arg);
+                    } else {
+                        positional_args.push(arg);
+                    }
+                }
+                
+                // 2. 按照函数定义的参数顺序编译参数
+                for (idx, param_name) in param_names.iter().enumerate() {
+                    if idx < positional_args.len() {
+                        // 使用位置参数
+                        self.compile_expr(positional_args[idx]);
+                    } else if let Some(arg) = named_args.get(param_name.as_str()) {
+                        // 使用命名参数
+                        self.compile_expr(arg);
+                    } else {
+                        // 参数缺失，报错（或者依赖默认参数处理）
+                        let msg = format!("Missing argument for parameter '{}'", param_name);
+                        self.errors.push(CompileError::new(msg, *span));
+                        // 压入 null 占位
+                        self.chunk.write_constant(Value::null(), span.line);
+                    }
+                }
+            } else {
+                // 没有参数名信息，按原顺序编译（可能产生错误结果）
+                for (_, arg) in args {
+                    self.compile_expr(arg);
+                }
+            }
+        } else {
+            // 位置参数：按顺序压栈
+            for (_, arg) in args {
+                self.compile_expr(arg);
+            }
         }
+        
+        // 3. 生成 Call 指令
+        if !self.check_arg_count(args.len(), *span) {
+            return;
+        }
+        self.chunk.write_call(args.len() as u8, span.line);
     }
 
     /// 编译表达式
     fn compile_expr(&mut self, expr: &Expr) {
+        self.compile_expr_code(expr);
+        // 记录表达式的位置：调用处和运行时出错处的插入符号画在对应的表达式下
+        if !matches!(
+            expr,
+            Expr::Integer { .. } | Expr::Float { .. } | Expr::String { .. } | Expr::Bool { .. } | Expr::Char { .. } | Expr::Null { .. }
+        ) {
+            let span = expr.span();
+            self.chunk.mark_span(span.line, span.column, span.end.saturating_sub(span.start));
+        }
+    }
+    
+    fn compile_expr_code(&mut self, expr: &Expr) {
+        // 优化：整个运算表达式是常量时直接加载结果
+        if matches!(expr, Expr::Binary { .. } | Expr::Unary { .. } | Expr::Grouping { .. } | Expr::IfExpr { .. }) {
+            if let Some(value) = self.fold_expr(expr) {
+                self.write_const_value(&value, expr.span().line);
+                return;
+            }
+        }
+        
         match expr {
             Expr::Integer { value, span } => {
                 // 优化：小整数使用 ConstInt8 指令
@@ -1735,38 +3651,40 @@ impl Compiler {
                 self.chunk.write_constant(Value::string(value.clone()), span.line);
             }
             Expr::StringInterpolation { parts, span } => {
-                // 编译字符串插值为一系列字符串连接操作
+                // 每个部分先转换为字符串，最后用 BuildString 一次性拼接（避免逐段 Add 的二次复制）
                 use crate::parser::ast::StringInterpPart;
                 
-                let mut first = true;
+                let mut count = 0usize;
                 for part in parts {
                     match part {
                         StringInterpPart::Literal(s) => {
-                            if !s.is_empty() {
-                                self.chunk.write_constant(Value::string(s.clone()), span.line);
-                                if !first {
-                                    // 将当前字符串与之前的结果连接
-                                    self.chunk.write_op(OpCode::Add, span.line);
-                                }
-                                first = false;
+                            if s.is_empty() {
+                                continue;
                             }
+                            self.chunk.write_constant(Value::string(s.clone()), span.line);
                         }
                         StringInterpPart::Expr(expr) => {
                             self.compile_expr(expr);
-                            // 将表达式结果转换为字符串
                             self.chunk.write_op(OpCode::ToString, span.line);
-                            if !first {
-                                // 将转换后的字符串与之前的结果连接
-                                self.chunk.write_op(OpCode::Add, span.line);
-                            }
-                            first = false;
                         }
                     }
+                    count += 1;
                 }
                 
-                // 如果没有任何部分，返回空字符串
-                if first {
-                    self.chunk.write_constant(Value::string(String::new()), span.line);
+                match count {
+                    // 没有任何部分，返回空字符串
+                    0 => self.chunk.write_constant(Value::string(String::new()), span.line),
+                    // 单个部分已经是字符串
+                    1 => {}
+                    _ => {
+                        if count > u16::MAX as usize {
+                            let msg = "String interpolation has too many parts".to_string();
+                            self.errors.push(CompileError::new(msg, *span));
+                            return;
+                        }
+                        self.chunk.write_op(OpCode::BuildString, span.line);
+                        self.chunk.write_u16(count as u16, span.line);
+                    }
                 }
             }
             Expr::Bool { value, span } => {
@@ -1780,16 +3698,19 @@ impl Compiler {
             }
             Expr::Identifier { name, span } => {
                 // 查找变量
-                if let Some(slot) = self.symbols.resolve_slot(name) {
-                    if let Some(symbol) = self.symbols.resolve(name) {
-                        if self.is_fast_int_type(&symbol.ty) {
-                            self.chunk.write_get_local_int(slot as u16, span.line);
-                        } else {
-                            self.chunk.write_get_local(slot, span.line);
-                        }
+                if let Some(symbol) = self.symbols.resolve(name) {
+                    let slot = symbol.slot;
+                    if symbol.is_cell {
+                        self.chunk.write_get_cell(slot, span.line);
+                    } else if self.is_fast_int_type(&symbol.ty) {
+                        self.chunk.write_get_local_int(slot as u16, span.line);
                     } else {
                         self.chunk.write_get_local(slot, span.line);
                     }
+                } else if let Some(value) = self.consts.get(name) {
+                    // 顶层常量：直接内联值
+                    let value = Self::const_to_value(value);
+                    self.chunk.write_constant(value, span.line);
                 } else if let Some(func_index) = self.chunk.get_named_function(name) {
                     // 如果是命名函数，从常量池加载
                     self.chunk.write_op(OpCode::Const, span.line);
@@ -1816,7 +3737,7 @@ impl Compiler {
                         self.compile_expr(right);
                         
                         // 回填跳转
-                        self.chunk.patch_jump(jump_if_false);
+                        self.patch_jump(jump_if_false, *span);
                         return;
                     }
                     BinOp::Or => {
@@ -1833,7 +3754,7 @@ impl Compiler {
                         self.compile_expr(right);
                         
                         // 回填跳转
-                        self.chunk.patch_jump(jump_if_true);
+                        self.patch_jump(jump_if_true, *span);
                         return;
                     }
                     _ => {}
@@ -1846,7 +3767,7 @@ impl Compiler {
                 let both_int = left_type == StaticType::Int && right_type == StaticType::Int;
 
                 // 融合指令优化：局部整数变量与小整数常量
-                if both_int {
+                if both_int && self.opt.fuse {
                     let try_emit_local_const = |compiler: &mut Compiler,
                                                 local_name: &str,
                                                 const_value: i128| {
@@ -1854,7 +3775,8 @@ impl Compiler {
                             return false;
                         }
                         if let Some(symbol) = compiler.symbols.resolve(local_name) {
-                            if compiler.is_fast_int_type(&symbol.ty) {
+                            // cell 变量的槽位中不是整数，不能融合
+                            if !symbol.is_cell && compiler.is_fast_int_type(&symbol.ty) {
                                 if let Some(slot) = compiler.symbols.resolve_slot(local_name) {
                                     let v = const_value as i8;
                                     match op {
@@ -1905,7 +3827,7 @@ impl Compiler {
                             (left.as_ref(), right.as_ref())
                         {
                             if let (Some(slot1), Some(slot2)) = 
-                                (self.symbols.resolve_slot(name1), self.symbols.resolve_slot(name2))
+                                (self.plain_local_slot(name1), self.plain_local_slot(name2))
                             {
                                 // 仅当槽位在 u8 范围内时使用超级指令
                                 if slot1 <= 255 && slot2 <= 255 {
@@ -1931,407 +3853,69 @@ impl Compiler {
                 
                 let opcode = if both_int {
                     // 使用专用整数指令（更快）
-                    match op {
-                        BinOp::Add => OpCode::AddInt,
-                        BinOp::Sub => OpCode::SubInt,
-                        BinOp::Mul => OpCode::MulInt,
-                        BinOp::Div => OpCode::DivInt,
-                        BinOp::Lt => OpCode::LtInt,
-                        BinOp::Le => OpCode::LeInt,
-                        BinOp::Gt => OpCode::GtInt,
-                        BinOp::Ge => OpCode::GeInt,
-                        BinOp::Eq => OpCode::EqInt,
-                        BinOp::Ne => OpCode::NeInt,
-                        // 其他操作没有专用整数版本，使用通用版本
-                        BinOp::Mod => OpCode::Mod,
-                        BinOp::Pow => OpCode::Pow,
-                        BinOp::BitAnd => OpCode::BitAnd,
-                        BinOp::BitOr => OpCode::BitOr,
-                        BinOp::BitXor => OpCode::BitXor,
-                        BinOp::Shl => OpCode::Shl,
-                        BinOp::Shr => OpCode::Shr,
-                        BinOp::And | BinOp::Or => unreachable!(),
-                    }
-                } else {
-                    // 使用通用指令
-                    match op {
-                    BinOp::Add => OpCode::Add,
-                    BinOp::Sub => OpCode::Sub,
-                    BinOp::Mul => OpCode::Mul,
-                    BinOp::Div => OpCode::Div,
-                    BinOp::Mod => OpCode::Mod,
-                    BinOp::Pow => OpCode::Pow,
-                    BinOp::Eq => OpCode::Eq,
-                    BinOp::Ne => OpCode::Ne,
-                    BinOp::Lt => OpCode::Lt,
-                    BinOp::Le => OpCode::Le,
-                    BinOp::Gt => OpCode::Gt,
-                    BinOp::Ge => OpCode::Ge,
-                    BinOp::BitAnd => OpCode::BitAnd,
-                    BinOp::BitOr => OpCode::BitOr,
-                    BinOp::BitXor => OpCode::BitXor,
-                    BinOp::Shl => OpCode::Shl,
-                    BinOp::Shr => OpCode::Shr,
-                    BinOp::And | BinOp::Or => unreachable!(), // 已在上面处理
-                    }
-                };
-                
-                self.chunk.write_op(opcode, span.line);
-            }
-            Expr::Unary { op, operand, span } => {
-                self.compile_expr(operand);
-                
-                let opcode = match op {
-                    UnaryOp::Neg => OpCode::Neg,
-                    UnaryOp::Not => OpCode::Not,
-                    UnaryOp::BitNot => OpCode::BitNot,
-                };
-                
-                self.chunk.write_op(opcode, span.line);
-            }
-            Expr::Grouping { expr, .. } => {
-                self.compile_expr(expr);
-            }
-            Expr::Call { callee, args, span } => {
-                // 提取参数值（命名参数将在后面处理）
-                let has_named_args = args.iter().any(|(name, _)| name.is_some());
-                
-                // 检查是否是内置函数（内置函数不支持命名参数）
-                if let Expr::Identifier { name, .. } = callee.as_ref() {
-                    if has_named_args {
-                        // 内置函数不支持命名参数，但仍需检查
-                        match name.as_str() {
-                            "print" | "println" | "typeof" | "typeinfo" | "sizeof" | "panic" | "time" => {
-                                let msg = "Built-in functions do not support named arguments".to_string();
-                                self.errors.push(CompileError::new(msg, *span));
-                                return;
-                            }
-                            _ => {}
-                        }
-                    }
-                    
-                    match name.as_str() {
-                        "print" if args.len() == 1 => {
-                            self.compile_expr(&args[0].1);
-                            self.chunk.write_op(OpCode::Print, span.line);
-                            // 内置函数需要返回值，以便作为表达式使用
-                            self.chunk.write_constant(Value::null(), span.line);
-                            return;
-                        }
-                        "println" if args.len() == 1 => {
-                            self.compile_expr(&args[0].1);
-                            self.chunk.write_op(OpCode::PrintLn, span.line);
-                            // 内置函数需要返回值，以便作为表达式使用
-                            self.chunk.write_constant(Value::null(), span.line);
-                            return;
-                        }
-                        "typeof" if args.len() == 1 => {
-                            self.compile_expr(&args[0].1);
-                            self.chunk.write_op(OpCode::TypeOf, span.line);
-                            return;
-                        }
-                        "typeinfo" if args.len() == 1 => {
-                            // 获取完整的运行时类型信息对象
-                            self.compile_expr(&args[0].1);
-                            self.chunk.write_op(OpCode::TypeInfo, span.line);
-                            return;
-                        }
-                        "sizeof" if args.len() == 1 => {
-                            self.compile_expr(&args[0].1);
-                            self.chunk.write_op(OpCode::SizeOf, span.line);
-                            return;
-                        }
-                        "panic" if args.len() == 1 => {
-                            self.compile_expr(&args[0].1);
-                            self.chunk.write_op(OpCode::Panic, span.line);
-                            return;
-                        }
-                        // [deprecated] time() 函数可能在未来版本移除
-                        "time" if args.is_empty() => {
-                            self.chunk.write_op(OpCode::Time, span.line);
-                            return;
-                        }
-                        _ => {}
-                    }
-                }
-                
-                // 检查是否是静态成员调用 (ClassName::method(args))
-                if let Expr::StaticMember { class_name, member, span: member_span } = callee.as_ref() {
-                    // 检查是否是枚举的内置方法
-                    let is_enum_builtin = if self.chunk.get_enum(class_name).is_some() {
-                        member == "fromValue" || member == "values"
-                    } else {
-                        false
-                    };
-                    
-                    if is_enum_builtin {
-                        // 枚举内置方法，生成 InvokeStatic 调用
-                        let class_name_index = self.chunk.add_constant(Value::string(class_name.clone()));
-                        let method_name_index = self.chunk.add_constant(Value::string(member.clone()));
-                        
-                        // 先编译所有参数
-                        for (_, arg) in args {
-                            self.compile_expr(arg);
-                        }
-                        
-                        // 生成 InvokeStatic 指令
-                        self.chunk.write_op(OpCode::InvokeStatic, span.line);
-                        self.chunk.write_u16(class_name_index, span.line);
-                        self.chunk.write_u16(method_name_index, span.line);
-                        self.chunk.write(args.len() as u8, span.line);
-                        return;
-                    }
-                    
-                    // 检查是否是静态方法
-                    let has_static_method = self.chunk.get_static_method(class_name, member).is_some();
-                    
-                    if has_static_method {
-                        let func_index = self.chunk.get_static_method(class_name, member).unwrap();
-                        
-                        // 检查参数数量
-                        if args.len() > u8::MAX as usize {
-                            let msg = "Too many arguments".to_string();
-                            self.errors.push(CompileError::new(msg, *span));
-                            return;
-                        }
-                        
-                        // 生成调用静态方法的指令
-                        // 1. 先从常量池加载函数
-                        self.chunk.write_op(OpCode::Const, span.line);
-                        self.chunk.write_u16(func_index, span.line);
-                        
-                        // 2. 然后编译所有参数
-                        for (_, arg) in args {
-                            self.compile_expr(arg);
-                        }
-                        
-                        // 3. 发出调用指令
-                        self.chunk.write_op(OpCode::Call, span.line);
-                        self.chunk.write(args.len() as u8, span.line);
-                        return;
-                    } else {
-                        let msg = format!("Type '{}' has no static method '{}'", class_name, member);
-                        self.errors.push(CompileError::new(msg, *member_span));
-                        return;
-                    }
-                }
-                
-                // 检查是否是方法调用 (obj.method(args))
-                if let Expr::Member { object, member, span: member_span } = callee.as_ref() {
-                    // 检查是否是 super 调用 (super.method(args))
-                    if matches!(object.as_ref(), Expr::Super { .. }) {
-                        // 编译 this（super 方法需要 this 作为 receiver）
-                        if let Some(slot) = self.symbols.resolve_slot("this") {
-                            self.chunk.write_get_local(slot, span.line);
-                        } else {
-                            let msg = "'super' can only be used inside a class method".to_string();
-                            self.errors.push(CompileError::new(msg, *span));
-                            return;
-                        }
-                        
-                        // 编译所有参数
-                        for (_, arg) in args {
-                            self.compile_expr(arg);
-                        }
-                        
-                        // 检查参数数量
-                        if args.len() > u8::MAX as usize {
-                            let msg = "Too many arguments".to_string();
-                            self.errors.push(CompileError::new(msg, *span));
-                            return;
-                        }
-                        
-                        // 将方法名添加到常量池
-                        let method_name_index = self.chunk.add_constant(Value::string(member.clone()));
-                        
-                        // 生成 InvokeSuper 指令
-                        self.chunk.write_op(OpCode::InvokeSuper, span.line);
-                        self.chunk.write_u16(method_name_index, span.line);
-                        self.chunk.write(args.len() as u8, span.line);
-                        return;
-                    }
-                    
-                    // 检查是否是静态方法调用 (ClassName.method(args))
-                    if let Expr::Identifier { name: class_name, .. } = object.as_ref() {
-                        // 检查是否是已注册的类名
-                        if self.chunk.get_type(class_name).is_some() {
-                            // 静态方法调用
-                            if let Some(func_index) = self.chunk.get_static_method(class_name, member) {
-                                // 检查参数数量
-                                if args.len() > u8::MAX as usize {
-                                    let msg = "Too many arguments".to_string();
-                                    self.errors.push(CompileError::new(msg, *span));
-                                    return;
-                                }
-                                
-                                // 生成调用静态方法的指令
-                                // 栈布局: [func, arg1, arg2, ...] -> Call -> [result]
-                                // 1. 先从常量池加载函数
-                                self.chunk.write_op(OpCode::Const, span.line);
-                                self.chunk.write_u16(func_index, span.line);
-                                
-                                // 2. 然后编译所有参数
-                                for (_, arg) in args {
-                                    self.compile_expr(arg);
-                                }
-                                
-                                // 3. 发出调用指令
-                                self.chunk.write_op(OpCode::Call, span.line);
-                                self.chunk.write(args.len() as u8, span.line);
-                                return;
-                            } else {
-                                let msg = format!("Type '{}' has no static method '{}'", class_name, member);
-                                self.errors.push(CompileError::new(msg, *member_span));
-                                return;
-                            }
-                        }
-                    }
-                    
-                    // 实例方法调用
-                    // 编译对象表达式（receiver）
-                    self.compile_expr(object);
-                    
-                    // 编译所有参数
-                    for (_, arg) in args {
-                        self.compile_expr(arg);
-                    }
-                    
-                    // 检查参数数量
-                    if args.len() > u8::MAX as usize {
-                        let msg = "Too many arguments".to_string();
-                        self.errors.push(CompileError::new(msg, *span));
-                        return;
-                    }
-                    
-                    // 将方法名添加到常量池
-                    let method_name_index = self.chunk.add_constant(Value::string(member.clone()));
-                    
-                    // 生成 InvokeMethod 指令
-                    self.chunk.write_op(OpCode::InvokeMethod, span.line);
-                    self.chunk.write_u16(method_name_index, span.line);
-                    self.chunk.write(args.len() as u8, span.line);
-                    return;
-                }
-                
-                // 检查是否是安全方法调用 (obj?.method(args))
-                if let Expr::SafeMember { object, member, span: member_span } = callee.as_ref() {
-                    // 编译对象表达式
-                    self.compile_expr(object);
-                    
-                    // 编译所有参数
-                    for (_, arg) in args {
-                        self.compile_expr(arg);
-                    }
-                    
-                    // 检查参数数量
-                    if args.len() > u8::MAX as usize {
-                        let msg = "Too many arguments".to_string();
-                        self.errors.push(CompileError::new(msg, *span));
-                        return;
-                    }
-                    
-                    // 将方法名添加到常量池
-                    let method_name_index = self.chunk.add_constant(Value::string(member.clone()));
-                    
-                    // 生成 SafeInvokeMethod 指令（如果对象为 null 则返回 null）
-                    self.chunk.write_op(OpCode::SafeInvokeMethod, member_span.line);
-                    self.chunk.write_u16(method_name_index, member_span.line);
-                    self.chunk.write(args.len() as u8, member_span.line);
-                    return;
-                }
-                
-                // 检查是否是非空断言方法调用 (obj!.method(args))
-                if let Expr::NonNullMember { object, member, span: member_span } = callee.as_ref() {
-                    // 编译对象表达式
-                    self.compile_expr(object);
-                    
-                    // 编译所有参数
-                    for (_, arg) in args {
-                        self.compile_expr(arg);
-                    }
-                    
-                    // 检查参数数量
-                    if args.len() > u8::MAX as usize {
-                        let msg = "Too many arguments".to_string();
-                        self.errors.push(CompileError::new(msg, *span));
-                        return;
-                    }
-                    
-                    // 将方法名添加到常量池
-                    let method_name_index = self.chunk.add_constant(Value::string(member.clone()));
-                    
-                    // 生成 NonNullInvokeMethod 指令（如果对象为 null 则 panic）
-                    self.chunk.write_op(OpCode::NonNullInvokeMethod, member_span.line);
-                    self.chunk.write_u16(method_name_index, member_span.line);
-                    self.chunk.write(args.len() as u8, member_span.line);
-                    return;
-                }
-                
-                // 用户定义函数调用
-                // 1. 编译被调用的表达式（将函数值压栈）
-                self.compile_expr(callee);
-                
-                // 2. 编译所有参数（依次压栈）
-                // 如果有命名参数，需要根据函数定义重新排列参数顺序
-                if has_named_args {
-                    // 命名参数调用：需要根据函数定义重排参数
-                    // 尝试获取函数的参数名列表
-                    let param_names = if let Expr::Identifier { name, .. } = callee.as_ref() {
-                        self.symbols.resolve(name).and_then(|s| s.param_names.clone())
-                    } else {
-                        None
-                    };
-                    
-                    if let Some(param_names) = param_names {
-                        // 有参数名信息，进行重排
-                        // 1. 分离位置参数和命名参数
-                        let mut positional_args: Vec<&Expr> = Vec::new();
-                        let mut named_args: std::collections::HashMap<&str, &Expr> = std::collections::HashMap::new();
-                        
-                        for (name, arg) in args {
-                            if let Some(n) = name {
-                                named_args.insert(n.as_str(), arg);
-                            } else {
-                                positional_args.push(arg);
-                            }
-                        }
-                        
-                        // 2. 按照函数定义的参数顺序编译参数
-                        for (idx, param_name) in param_names.iter().enumerate() {
-                            if idx < positional_args.len() {
-                                // 使用位置参数
-                                self.compile_expr(positional_args[idx]);
-                            } else if let Some(arg) = named_args.get(param_name.as_str()) {
-                                // 使用命名参数
-                                self.compile_expr(arg);
-                            } else {
-                                // 参数缺失，报错（或者依赖默认参数处理）
-                                let msg = format!("Missing argument for parameter '{}'", param_name);
-                                self.errors.push(CompileError::new(msg, *span));
-                                // 压入 null 占位
-                                self.chunk.write_constant(Value::null(), span.line);
-                            }
-                        }
-                    } else {
-                        // 没有参数名信息，按原顺序编译（可能产生错误结果）
-                        for (_, arg) in args {
-                            self.compile_expr(arg);
-                        }
+                    match op {
+                        BinOp::Add => OpCode::AddInt,
+                        BinOp::Sub => OpCode::SubInt,
+                        BinOp::Mul => OpCode::MulInt,
+                        BinOp::Div => OpCode::DivInt,
+                        BinOp::Lt => OpCode::LtInt,
+                        BinOp::Le => OpCode::LeInt,
+                        BinOp::Gt => OpCode::GtInt,
+                        BinOp::Ge => OpCode::GeInt,
+                        BinOp::Eq => OpCode::EqInt,
+                        BinOp::Ne => OpCode::NeInt,
+                        // 其他操作没有专用整数版本，使用通用版本
+                        BinOp::Mod => OpCode::Mod,
+                        BinOp::Pow => OpCode::Pow,
+                        BinOp::BitAnd => OpCode::BitAnd,
+                        BinOp::BitOr => OpCode::BitOr,
+                        BinOp::BitXor => OpCode::BitXor,
+                        BinOp::Shl => OpCode::Shl,
+                        BinOp::Shr => OpCode::Shr,
+                        BinOp::And | BinOp::Or => unreachable!(),
                     }
                 } else {
-                    // 位置参数：按顺序压栈
-                    for (_, arg) in args {
-                        self.compile_expr(arg);
+                    // 使用通用指令
+                    match op {
+                    BinOp::Add => OpCode::Add,
+                    BinOp::Sub => OpCode::Sub,
+                    BinOp::Mul => OpCode::Mul,
+                    BinOp::Div => OpCode::Div,
+                    BinOp::Mod => OpCode::Mod,
+                    BinOp::Pow => OpCode::Pow,
+                    BinOp::Eq => OpCode::Eq,
+                    BinOp::Ne => OpCode::Ne,
+                    BinOp::Lt => OpCode::Lt,
+                    BinOp::Le => OpCode::Le,
+                    BinOp::Gt => OpCode::Gt,
+                    BinOp::Ge => OpCode::Ge,
+                    BinOp::BitAnd => OpCode::BitAnd,
+                    BinOp::BitOr => OpCode::BitOr,
+                    BinOp::BitXor => OpCode::BitXor,
+                    BinOp::Shl => OpCode::Shl,
+                    BinOp::Shr => OpCode::Shr,
+                    BinOp::And | BinOp::Or => unreachable!(), // 已在上面处理
                     }
-                }
+                };
                 
-                // 3. 生成 Call 指令
-                if args.len() > u8::MAX as usize {
-                    let msg = "Too many arguments".to_string();
-                    self.errors.push(CompileError::new(msg, *span));
-                    return;
-                }
-                self.chunk.write_call(args.len() as u8, span.line);
+                self.chunk.write_op(opcode, span.line);
+            }
+            Expr::Unary { op, operand, span } => {
+                self.compile_expr(operand);
+                
+                let opcode = match op {
+                    UnaryOp::Neg => OpCode::Neg,
+                    UnaryOp::Not => OpCode::Not,
+                    UnaryOp::BitNot => OpCode::BitNot,
+                };
+                
+                self.chunk.write_op(opcode, span.line);
+            }
+            Expr::Grouping { expr, .. } => {
+                self.compile_expr(expr);
+            }
+            Expr::Call { callee, args, span } => {
+                self.compile_call(callee, args, span);
             }
             Expr::Assign { target, op, value, span } => {
                 use crate::parser::ast::AssignOp;
@@ -2346,7 +3930,8 @@ impl Compiler {
                     }
                     
                     // 获取变量槽位
-                    if let Some(slot) = self.symbols.resolve_slot(name) {
+                    if let Some(symbol) = self.symbols.resolve(name) {
+                        let (slot, is_cell) = (symbol.slot, symbol.is_cell);
                         match op {
                             AssignOp::Assign => {
                                 // 简单赋值：编译右侧值
@@ -2356,7 +3941,7 @@ impl Compiler {
                                 // 复合赋值：先获取当前值，再编译右侧，最后执行运算
                                 let mut fused_done = false;
                                 if let Some(symbol) = self.symbols.resolve(name) {
-                                    if self.is_fast_int_type(&symbol.ty) {
+                                    if !is_cell && self.opt.fuse && self.is_fast_int_type(&symbol.ty) {
                                         if let Expr::Integer { value: rhs, .. } = value.as_ref() {
                                             if *rhs >= -128 && *rhs <= 127 {
                                                 let v = *rhs as i8;
@@ -2377,30 +3962,25 @@ impl Compiler {
                                 }
 
                                 if !fused_done {
-                                    self.chunk.write_get_local(slot, span.line);
+                                    if is_cell {
+                                        self.chunk.write_get_cell(slot, span.line);
+                                    } else {
+                                        self.chunk.write_get_local(slot, span.line);
+                                    }
                                     self.compile_expr(value);
                                     
                                     // 执行对应的二元运算
-                                    let bin_op = match op {
-                                        AssignOp::AddAssign => OpCode::Add,
-                                        AssignOp::SubAssign => OpCode::Sub,
-                                        AssignOp::MulAssign => OpCode::Mul,
-                                        AssignOp::DivAssign => OpCode::Div,
-                                        AssignOp::ModAssign => OpCode::Mod,
-                                        AssignOp::BitAndAssign => OpCode::BitAnd,
-                                        AssignOp::BitOrAssign => OpCode::BitOr,
-                                        AssignOp::BitXorAssign => OpCode::BitXor,
-                                        AssignOp::ShlAssign => OpCode::Shl,
-                                        AssignOp::ShrAssign => OpCode::Shr,
-                                        AssignOp::Assign => unreachable!(),
-                                    };
-                                    self.chunk.write_op(bin_op, span.line);
+                                    self.chunk.write_op(Self::compound_opcode(*op), span.line);
                                 }
                             }
                         }
                         
                         // 存入变量
-                        self.chunk.write_set_local(slot, span.line);
+                        if is_cell {
+                            self.chunk.write_set_cell(slot, span.line);
+                        } else {
+                            self.chunk.write_set_local(slot, span.line);
+                        }
                     } else {
                         let msg = format!("Undefined variable: {}", name);
                         self.errors.push(CompileError::new(msg, *span));
@@ -2430,20 +4010,7 @@ impl Compiler {
                                 // 编译右侧值
                                 self.compile_expr(value);
                                 // 执行运算
-                                let bin_op = match op {
-                                    AssignOp::AddAssign => OpCode::Add,
-                                    AssignOp::SubAssign => OpCode::Sub,
-                                    AssignOp::MulAssign => OpCode::Mul,
-                                    AssignOp::DivAssign => OpCode::Div,
-                                    AssignOp::ModAssign => OpCode::Mod,
-                                    AssignOp::BitAndAssign => OpCode::BitAnd,
-                                    AssignOp::BitOrAssign => OpCode::BitOr,
-                                    AssignOp::BitXorAssign => OpCode::BitXor,
-                                    AssignOp::ShlAssign => OpCode::Shl,
-                                    AssignOp::ShrAssign => OpCode::Shr,
-                                    AssignOp::Assign => unreachable!(),
-                                };
-                                self.chunk.write_op(bin_op, span.line);
+                                self.chunk.write_op(Self::compound_opcode(*op), span.line);
                             }
                         }
                         
@@ -2462,18 +4029,34 @@ impl Compiler {
                                 self.compile_expr(value);
                             }
                             _ => {
-                                // 复合赋值需要先获取当前值
-                                // 这里需要复制 object 和 index
-                                // 暂不支持复合索引赋值，报错
-                                let msg = "Compound index assignment not yet supported".to_string();
-                                self.errors.push(CompileError::new(msg, *span));
-                                return;
+                                // 复合赋值: arr[i] op= value
+                                // 复制对象和索引，保证两者只求值一次
+                                // 栈布局: [..., obj, idx] -> [..., obj, idx, obj, idx] -> [..., obj, idx, old]
+                                self.chunk.write_op(OpCode::Dup2, span.line);
+                                self.chunk.write_op(OpCode::GetIndex, span.line);
+                                self.compile_expr(value);
+                                self.chunk.write_op(Self::compound_opcode(*op), span.line);
                             }
                         }
                         
                         // 设置索引
                         self.chunk.write_op(OpCode::SetIndex, index_span.line);
                     }
+                    Expr::StaticMember { class_name, member, span: member_span } => {
+                        // 静态字段赋值: Type::field = value，赋的值留在栈上
+                        if let Some(msg) = self.static_assign_error(class_name, member) {
+                            self.errors.push(CompileError::new(msg, *member_span));
+                            return;
+                        }
+                        if *op != AssignOp::Assign {
+                            self.write_static_op(OpCode::GetStatic, class_name, member, span.line);
+                            self.compile_expr(value);
+                            self.chunk.write_op(Self::compound_opcode(*op), span.line);
+                        } else {
+                            self.compile_expr(value);
+                        }
+                        self.write_static_op(OpCode::SetStatic, class_name, member, member_span.line);
+                    }
                     _ => {
                     let msg = "Invalid assignment target".to_string();
                     self.errors.push(CompileError::new(msg, *span));
@@ -2507,9 +4090,25 @@ impl Compiler {
                     self.chunk.write_op(OpCode::NewRange, span.line);
                 }
             }
-            Expr::IfExpr { span, .. } => {
-                let msg = "If expression not yet implemented".to_string();
-                self.errors.push(CompileError::new(msg, *span));
+            Expr::IfExpr { condition, then_branch, else_branch, .. } if self.constant_condition(condition).is_some() => {
+                // 常量条件：只编译选中的一侧
+                if self.constant_condition(condition) == Some(true) {
+                    self.compile_expr(then_branch);
+                } else {
+                    self.compile_expr(else_branch);
+                }
+            }
+            Expr::IfExpr { condition, then_branch, else_branch, span } => {
+                // cond ? a : b
+                //   cond（为假时跳到 else）; a; Jump end
+                //   else: b
+                //   end:
+                let else_jumps = self.compile_condition(condition, false);
+                self.compile_expr(then_branch);
+                let end_jump = self.chunk.write_jump(OpCode::Jump, span.line);
+                self.patch_condition_jumps(else_jumps, *span);
+                self.compile_expr(else_branch);
+                self.patch_jump(end_jump, *span);
             }
             Expr::Array { elements, span } => {
                 // 编译所有元素
@@ -2526,6 +4125,12 @@ impl Compiler {
                 self.chunk.write_u16(elements.len() as u16, span.line);
             }
             Expr::MapLiteral { entries, span } => {
+                if entries.len() > u16::MAX as usize {
+                    let msg = "Map literal too large".to_string();
+                    self.errors.push(CompileError::new(msg, *span));
+                    return;
+                }
+                
                 // 编译每个键值对
                 for (key, value) in entries {
                     self.compile_expr(key);
@@ -2537,6 +4142,15 @@ impl Compiler {
                 self.chunk.write_u16(entries.len() as u16, span.line);
             }
             Expr::Closure { params, return_type: _, body, span } => {
+                // 0. 闭包引用的外层局部变量：创建闭包时复制槽位（cell 或值），调用时放在参数之后
+                let captured: Vec<(String, usize, Type, bool, bool)> = capture::free_variables(params, body)
+                    .into_iter()
+                    .filter_map(|name| {
+                        let symbol = self.symbols.resolve(&name)?;
+                        Some((name, symbol.slot, symbol.ty.clone(), symbol.is_const, symbol.is_cell))
+                    })
+                    .collect();
+                
                 // 1. 先写一个跳转指令跳过函数体
                 let jump_over = self.chunk.write_jump(OpCode::Jump, span.line);
                 
@@ -2546,7 +4160,9 @@ impl Compiler {
                 // 3. 保存当前符号表状态，为函数创建独立的作用域
                 let saved_state = self.symbols.save_state();
                 let saved_scope_depth = self.symbols.scope_depth();
-                self.symbols.reset_for_function();
+                let saved_try_blocks = std::mem::take(&mut self.try_blocks);
+                let saved_panic_slots = std::mem::take(&mut self.panic_slots);
+                self.symbols.reset_for_function(Captures::analyze_body(body));
                 
                 let arity = params.len();
                 
@@ -2605,6 +4221,12 @@ impl Compiler {
                     }
                 }
                 
+                for (name, _, ty, is_const, is_cell) in &captured {
+                    if let Err(msg) = self.symbols.define_capture(name.clone(), ty.clone(), *is_const, *is_cell) {
+                        self.errors.push(CompileError::new(msg, *span));
+                    }
+                }
+                
                 // 4. 编译函数体
                 // 如果函数体是一个 Block，直接编译其内部语句，不增加额外作用域
                 // 因为函数体的局部变量应该在函数返回时清理，而不是在块结束时
@@ -2621,13 +4243,19 @@ impl Compiler {
                 }
                 
                 // 计算局部变量数量（包括参数）
+                self.check_local_slots("<closure>", *span);
+                self.check_param_count("<closure>", params.len(), *span);
                 let local_count = self.symbols.local_count();
                 
                 // 6. 恢复符号表状态
                 self.symbols.restore_state_full(saved_state, saved_scope_depth);
+                self.try_blocks = saved_try_blocks;
+                self.panic_slots = saved_panic_slots;
                 
                 // 7. 回填跳转指令
-                self.chunk.patch_jump(jump_over);
+                self.patch_jump(jump_over, *span);
+                let func_end = self.chunk.current_offset();
+                self.chunk.register_function_range("<closure>".to_string(), func_start, func_end);
                 
                 // 8. 创建 Function 对象并存入常量池
                 let func = Function {
@@ -2638,29 +4266,27 @@ impl Compiler {
                     has_variadic,
                     chunk_index: func_start,
                     local_count,
-                    upvalues: Vec::new(), // TODO: 实际填充捕获的 upvalues
+                    upvalues: captured.iter()
+                        .map(|(_, slot, ..)| UpvalueDescriptor { index: *slot as u16, is_local: true })
+                        .collect(),
+                    captures: Vec::new(),
                 };
-                self.chunk.write_constant(Value::function(Arc::new(func)), span.line);
-            }
-            Expr::StructLiteral { name, fields, span } => {
-                // 编译 struct 字面量
-                // 1. 将类型名称添加到常量池
-                let type_name_index = self.chunk.add_constant(Value::string(name.clone()));
-                
-                // 2. 编译每个字段：先压入字段名，再压入字段值
-                for (field_name, field_value) in fields {
-                    // 字段名
-                    self.chunk.write_constant(Value::string(field_name.clone()), span.line);
-                    // 字段值
-                    self.compile_expr(field_value);
+                if func.upvalues.is_empty() {
+                    self.chunk.write_constant(Value::function(Arc::new(func)), span.line);
+                } else {
+                    let index = self.chunk.add_constant(Value::function(Arc::new(func)));
+                    self.chunk.write_op(OpCode::Closure, span.line);
+                    self.chunk.write_u16(index as u16, span.line);
                 }
-                
-                // 3. 生成 NewStruct 指令
-                self.chunk.write_op(OpCode::NewStruct, span.line);
-                self.chunk.write(fields.len() as u8, span.line); // 字段数量
-                self.chunk.write_u16(type_name_index as u16, span.line); // 类型名称索引
             }
+            Expr::StructLiteral { name, fields, span } => self.compile_struct_literal(name, fields, *span),
             Expr::Member { object, member, span } => {
+                if let Expr::Identifier { name, .. } = object.as_ref() {
+                    if let Some(value) = self.resolve_stdlib_constant(name, member) {
+                        self.chunk.write_constant(value, span.line);
+                        return;
+                    }
+                }
                 // 编译成员访问表达式 obj.field
                 // 1. 编译对象表达式
                 self.compile_expr(object);
@@ -2703,7 +4329,7 @@ impl Compiler {
                 // 跳过右侧计算
                 let skip_right = self.chunk.write_jump(OpCode::Jump, span.line);
                 // compute_right: [left, left_copy (null)]
-                self.chunk.patch_jump(jump_if_null);
+                self.patch_jump(jump_if_null, *span);
                 // 弹出 left_copy (null): [left]
                 self.chunk.write_op(OpCode::Pop, span.line);
                 // 弹出原始 left (null): []
@@ -2711,7 +4337,7 @@ impl Compiler {
                 // 计算右侧: [right]
                 self.compile_expr(right);
                 // end:
-                self.chunk.patch_jump(skip_right);
+                self.patch_jump(skip_right, *span);
             }
             Expr::Index { object, index, span } => {
                 // 编译数组索引访问 arr[i]
@@ -2756,7 +4382,18 @@ impl Compiler {
                 self.chunk.write_op(OpCode::TypeCheck, span.line);
                 self.chunk.write_u16(type_name_index, span.line);
             }
-            Expr::New { class_name, args, span } => {
+            Expr::New { class_name, fields: Some(fields), span, .. } => {
+                if self.struct_types.contains(class_name) {
+                    self.compile_struct_literal(class_name, fields, *span);
+                } else {
+                    self.compile_new_with_fields(class_name, fields, *span);
+                }
+            }
+            Expr::New { class_name, args, span, .. } => {
+                if !self.check_arg_count(args.len(), *span) {
+                    return;
+                }
+                
                 // 编译参数
                 for arg in args {
                     self.compile_expr(arg);
@@ -2798,11 +4435,23 @@ impl Compiler {
             }
             Expr::StaticMember { class_name, member, span } => {
                 // 静态成员访问: ClassName::CONST 或 ClassName::field 或 EnumName::Variant
+                if let Some(value) = self.resolve_stdlib_constant(class_name, member) {
+                    self.chunk.write_constant(value, span.line);
+                    return;
+                }
                 
                 // 先检查是否是枚举变体访问
                 if let Some(enum_info) = self.chunk.get_enum(class_name) {
-                    let has_variant = enum_info.variants.iter().any(|v| v.name == *member);
-                    if has_variant {
+                    let variant = enum_info.variants.iter().find(|v| v.name == *member);
+                    if let Some(fields) = variant.map(|v| &v.fields).filter(|fields| !fields.is_empty()) {
+                        let msg = format!(
+                            "Enum variant '{}::{}' needs its payload: {}::{}({})",
+                            class_name, member, class_name, member, fields.join(", ")
+                        );
+                        self.errors.push(CompileError::new(msg, *span));
+                        return;
+                    }
+                    if variant.is_some() {
                         // 枚举变体访问
                         let enum_name_index = self.chunk.add_constant(Value::string(class_name.clone()));
                         let variant_name_index = self.chunk.add_constant(Value::string(member.clone()));
@@ -2843,6 +4492,10 @@ impl Compiler {
             Expr::Go { call, span } => {
                 // 编译被调用的表达式（必须是一个 Call 表达式）
                 if let Expr::Call { callee, args, .. } = call.as_ref() {
+                    if !self.check_arg_count(args.len(), *span) {
+                        return;
+                    }
+                    
                     // 编译闭包/函数
                     self.compile_expr(callee);
                     
@@ -2854,20 +4507,31 @@ impl Compiler {
                     // 生成 GoSpawn 指令
                     self.chunk.write_op(OpCode::GoSpawn, span.line);
                     self.chunk.write(args.len() as u8, span.line);
-                    
-                    // go 表达式返回 null（或者未来可以返回 JoinHandle）
-                    self.chunk.write_constant(Value::null(), span.line);
+                    // GoSpawn 执行后在栈上留下 null 作为 go 表达式的值
                 } else {
                     let msg = "go expression must be followed by a function call".to_string();
                     self.errors.push(CompileError::new(msg, *span));
                 }
             }
+            
+            Expr::ChannelNew { capacity, span, .. } => {
+                // 省略容量时压入 0：无缓冲通道
+                match capacity {
+                    Some(capacity) => self.compile_expr(capacity),
+                    None => self.chunk.write_constant(Value::int(0), span.line),
+                }
+                self.chunk.write_op(OpCode::ChannelNew, span.line);
+            }
         }
     }
     
     /// 尝试提取尾调用信息
     /// 如果表达式是一个简单的函数调用（不是方法调用），返回调用信息
     fn try_extract_tail_call(&self, expr: &Expr) -> Option<TailCallInfo> {
+        // try 块和 recovering 块中的调用不是尾调用：被调函数中的异常和 panic 要由这里的处理器处理
+        if !self.opt.tail_calls || !self.try_blocks.is_empty() {
+            return None;
+        }
         match expr {
             Expr::Call { callee, args, .. } => {
                 // 检查是否是简单的标识符调用（用户自定义函数）
@@ -2876,7 +4540,7 @@ impl Compiler {
                     Expr::Identifier { name, .. } => {
                         // 排除内置函数
                         match name.as_str() {
-                            "print" | "println" | "typeof" | "typeinfo" | "sizeof" | "panic" | "time" => None,
+                            "print" | "println" | "typeof" | "typeinfo" | "sizeof" | "panic" | "recover" | "time" | "hexdump" | "printHexdump" => None,
                             _ => Some(TailCallInfo {
                                 callee: callee.as_ref().clone(),
                                 args: args.iter().map(|(_, e)| e.clone()).collect(),
@@ -2898,39 +4562,10 @@ impl Compiler {
     /// 尝试将常量表达式转换为运行时值
     /// 仅支持字面量（数字、字符串、布尔值、null）
     fn expr_to_value(&self, expr: &Expr) -> Result<Value, String> {
-        match expr {
-            Expr::Integer { value, .. } => Ok(Value::int(*value)),
-            Expr::Float { value, .. } => Ok(Value::float(*value)),
-            Expr::String { value, .. } => Ok(Value::string(value.clone())),
-            Expr::Bool { value, .. } => Ok(Value::bool(*value)),
-            Expr::Null { .. } => Ok(Value::null()),
-            Expr::Char { value, .. } => Ok(Value::char(*value)),
-            Expr::Unary { op, operand, .. } => {
-                // 支持负数字面量，如 -1, -3.14
-                let val = self.expr_to_value(operand)?;
-                match op {
-                    UnaryOp::Neg => {
-                        if let Some(n) = val.as_int() {
-                            Ok(Value::int(-n))
-                        } else if let Some(f) = val.as_float() {
-                            Ok(Value::float(-f))
-                        } else {
-                            Err("Cannot negate non-numeric default value".to_string())
-                        }
-                    }
-                    UnaryOp::Not => {
-                        Ok(Value::bool(!val.is_truthy()))
-                    }
-                    UnaryOp::BitNot => {
-                        if let Some(n) = val.as_int() {
-                            Ok(Value::int(!n))
-                        } else {
-                            Err("Cannot bitwise NOT non-integer default value".to_string())
-                        }
-                    }
-                }
-            }
-            _ => Err("Default parameter value must be a constant expression".to_string()),
+        match self.fold_const(expr) {
+            Ok(value) => Ok(Self::const_to_value(&value)),
+            Err(ConstEvalError::NotConstant) => Err("Default parameter value must be a constant expression".to_string()),
+            Err(ConstEvalError::Invalid(msg)) => Err(msg),
         }
     }
 }
@@ -2951,6 +4586,33 @@ mod tests {
         compiler.compile(&program)
     }
 
+    fn compile_optimized(source: &str) -> Chunk {
+        let tokens = Scanner::new(source).scan_tokens();
+        let program = Parser::new(tokens, Locale::En).parse().unwrap();
+        let mut compiler = Compiler::new(Locale::En);
+        compiler.set_optimize(true);
+        compiler.compile(&program).unwrap()
+    }
+
+    /// 走一遍类型检查、单态化和代码生成，返回反汇编
+    fn compile_checked(source: &str) -> String {
+        let tokens = Scanner::new(source).scan_tokens();
+        let mut program = Parser::new(tokens, Locale::En).parse().unwrap();
+        let mut checker = crate::typechecker::TypeChecker::new();
+        checker.check_program(&program).unwrap();
+        let mut type_table = checker.take_type_table();
+        let mut monomorphizer = crate::typechecker::Monomorphizer::new();
+        monomorphizer.collect_definitions(&program);
+        monomorphizer.specialize(&mut program, &mut type_table);
+        let mut compiler = Compiler::new(Locale::En);
+        compiler.set_type_table(type_table);
+        compiler.compile(&program).unwrap().disassemble()
+    }
+
+    fn has_string_constant(chunk: &Chunk, s: &str) -> bool {
+        chunk.constants.iter().any(|c| c.as_string().map(|v| v.as_str() == s).unwrap_or(false))
+    }
+
     #[test]
     fn test_compile_integer() {
         let chunk = compile("123").unwrap();
@@ -2985,4 +4647,474 @@ mod tests {
         assert_eq!(chunk.constants.len(), 1);
         assert_eq!(chunk.constants[0].as_int(), Some(1000));
     }
+    
+    #[test]
+    fn test_too_many_local_slots() {
+        // 70 层嵌套块、每层 1000 个变量：同时存活 70000 个局部变量
+        let mut source = String::new();
+        for depth in 0..70 {
+            source.push_str("{\n");
+            for i in 0..1000 {
+                source.push_str(&format!("var v{}_{} = 0\n", depth, i));
+            }
+        }
+        source.push_str(&"}\n".repeat(70));
+        let errors = compile(&source).unwrap_err();
+        assert!(errors.iter().any(|e| e.message
+            == "function '<main>' requires 70000 local slots, exceeding the limit of 65535"));
+    }
+    
+    #[test]
+    fn test_jump_too_large() {
+        // 每条语句十几字节，一万条语句超过 u16 能表示的跳转距离
+        let body = "x = x + 1000\n".repeat(10_000);
+        let too_large = |errors: Vec<CompileError>, line: usize| {
+            assert!(errors.iter().any(|e| e.span.line == line
+                && e.message.starts_with("function too large: jump of ")
+                && e.message.ends_with(" bytes exceeds the limit of 65535")), "{:?}", errors);
+        };
+        too_large(compile(&format!("var x = 0\nif x == 0 {{\n{}}}", body)).unwrap_err(), 2);
+        too_large(compile(&format!("var x = 0\nfor x < 1 {{\n{}}}", body)).unwrap_err(), 2);
+        too_large(compile(&format!("var x = 0\nfunc f(x: int) {{\n{}}}", body)).unwrap_err(), 2);
+        
+        // 上限以内的跳转正常编译
+        let body = "x = x + 1000\n".repeat(1_000);
+        assert!(compile(&format!("var x = 0\nif x == 0 {{\n{}}}", body)).is_ok());
+    }
+    
+    #[test]
+    fn test_too_many_call_arguments() {
+        let args = vec!["0"; 300].join(", ");
+        let errors = compile(&format!("func f() {{}}\nf({})", args)).unwrap_err();
+        assert!(errors.iter().any(|e| e.message
+            == "call has 300 arguments, exceeding the limit of 255"));
+    }
+    
+    #[test]
+    fn test_static_field_assignment_targets() {
+        let source = "class C {\n    static var n: int = 0\n    const MAX = 3\n    static func f() {}\n}\nenum E { A }\n";
+        let chunk = compile(&format!("{}C::n += 2\n", source)).unwrap();
+        assert!(chunk.disassemble().contains("SetStatic"));
+        for (target, message) in [
+            ("C::MAX", "Cannot assign to constant 'C::MAX'"),
+            ("C::f", "Type 'C' has no static field 'f'"),
+            ("E::A", "Cannot assign to enum variant 'E::A'"),
+            ("D::n", "Unknown class 'D'"),
+        ] {
+            let errors = compile(&format!("{}{} = 1\n", source, target)).unwrap_err();
+            assert!(errors.iter().any(|e| e.message == message), "{}: {:?}", target, errors);
+        }
+    }
+    
+    #[test]
+    fn test_optimize_folds_constants() {
+        let source = "const HOURS = 2\nvar a = 1\nvar ms = 1000 * 60 * 60 * HOURS\nvar s = \"q\" + \"lang\"\n";
+        let plain = compile(source).unwrap();
+        assert!(plain.constants.iter().any(|c| c.as_int() == Some(1000)));
+        
+        let chunk = compile_optimized(source);
+        assert!(chunk.constants.iter().all(|c| c.as_int() != Some(1000)));
+        let folded = chunk.constants.iter().position(|c| c.as_int() == Some(7_200_000)).unwrap();
+        assert!(has_string_constant(&chunk, "qlang"));
+        
+        // 折叠结果保留原表达式的行号
+        let mut offset = 0;
+        let load = loop {
+            let instruction = chunk.decode_instruction(offset).unwrap();
+            if instruction.opcode == OpCode::Const && instruction.operands[0].1 as usize == folded {
+                break instruction;
+            }
+            offset += instruction.len;
+        };
+        assert_eq!(chunk.get_line(load.offset), 3);
+    }
+    
+    #[test]
+    fn test_optimize_matches_plain_run_for_shifts() {
+        // 运行一个字节码块，返回输出和错误消息
+        fn run(chunk: Chunk) -> (String, Option<String>) {
+            let capture = crate::vm::output::Capture::new();
+            let mut vm = crate::vm::VM::new(std::sync::Arc::new(chunk), Locale::En);
+            vm.set_output(crate::vm::output::Output::Capture(capture.clone()));
+            let error = vm.run().err().map(|e| e.message);
+            (capture.finish(), error)
+        }
+        
+        for expr in ["1 << 4", "1 << 63", "-8 >> 1", "5 >> 63", "1 << 64", "1 << 70", "1 >> 64", "1 << -1"] {
+            let source = format!("println({})\n", expr);
+            let plain = run(compile(&source).unwrap());
+            let optimized = run(compile_optimized(&source));
+            assert_eq!(plain, optimized, "{}", expr);
+        }
+    }
+    
+    #[test]
+    fn test_optimize_drops_unreachable_code() {
+        let source = r#"
+func f(n: int) int {
+    if false { print("if-dead") } else { print("else-live") }
+    for 1 > 2 { print("loop-dead") }
+    var v = true ? "then-live" : "else-dead"
+    return n
+    print("after-return")
+}
+"#;
+        let plain = compile(source).unwrap();
+        let chunk = compile_optimized(source);
+        for dead in ["if-dead", "loop-dead", "else-dead", "after-return"] {
+            assert!(has_string_constant(&plain, dead));
+            assert!(!has_string_constant(&chunk, dead), "{} should be removed", dead);
+        }
+        assert!(has_string_constant(&chunk, "else-live"));
+        assert!(has_string_constant(&chunk, "then-live"));
+        
+        // 局部变量遮蔽常量时不折叠
+        let chunk = compile_optimized("const N = 1000\nfunc f(N: int) int { return N * 2000 }\n");
+        assert!(chunk.constants.iter().all(|c| c.as_int() != Some(2_000_000)));
+    }
+    
+    #[test]
+    fn test_disassemble() {
+        let chunk = compile("func f(n: int) int {\n    if n < 2 {\n        return n\n    }\n    return f(n - 1) + 1\n}\nprint(\"done\")\n").unwrap();
+        let text = chunk.disassemble();
+        assert!(text.contains("-- f --"), "{}", text);
+        assert!(text.contains("\"done\""), "{}", text);
+        assert!(text.contains("Const 0 (<fn f>)"), "{}", text);
+        // 跳转指令标出目标，目标行标出来源（`n < 2` 为假时跳转，即 `n >= 2`）
+        let jump = text.lines().find(|l| l.contains("JumpIfLocalGeConst 0 2 ")).unwrap();
+        let target = jump.rsplit("-> ").next().unwrap();
+        let target_line = text.lines().find(|l| l.starts_with(&format!("{} >", target))).unwrap();
+        assert!(target_line.ends_with(&format!("<- {}", &jump[..4])), "{}", text);
+        
+        // 超级指令的多字节操作数按布局解码，后续偏移保持对齐
+        let mut chunk = Chunk::new();
+        chunk.write_op(OpCode::JumpIfLocalLeConst, 1);
+        for byte in [1, (-3i8) as u8, 0, 1] {
+            chunk.write(byte, 1);
+        }
+        chunk.write_op(OpCode::Pop, 2);
+        chunk.write_op(OpCode::Halt, 2);
+        let text = chunk.disassemble();
+        assert!(text.contains("0000      1  JumpIfLocalLeConst 1 -3 1 -> 0006"), "{}", text);
+        assert!(text.contains("0005      2  Pop"), "{}", text);
+        assert!(text.contains("0006 >    |  Halt"), "{}", text);
+    }
+
+    #[test]
+    fn test_generic_calls_use_specializations() {
+        let text = compile_checked("func pick<T>(value: T) T {\n    return value\n}\nfunc wrap<T>(value: T) T {\n    return pick(value)\n}\nfunc main() {\n    println(pick(1))\n    println(pick(\"a\"))\n    println(wrap(1.5))\n}\n");
+        for name in ["pick$$int", "pick$$string", "pick$$f64", "wrap$$f64"] {
+            assert!(text.contains(&format!("-- {} --", name)), "{}", text);
+        }
+        let section = |name: &str| {
+            let start = text.find(&format!("-- {} --", name)).unwrap() + name.len() + 6;
+            let rest = &text[start..];
+            rest[..rest.find("-- ").unwrap_or(rest.len())].to_string()
+        };
+        let main = section("main");
+        assert!(main.contains("<fn pick$$int>") && main.contains("<fn pick$$string>"), "{}", text);
+        assert!(main.contains("<fn wrap$$f64>") && !main.contains("<fn pick>"), "{}", text);
+        // 实例中的调用也指向实例
+        assert!(section("wrap$$f64").contains("<fn pick$$f64>"), "{}", text);
+    }
+
+    #[test]
+    fn test_checked_int_locals_use_int_instructions() {
+        // 推断为 int 的局部变量使用整数超级指令
+        let text = compile_checked("func main() {\n    var n = 3\n    println(n + 1)\n}\n");
+        assert!(text.contains("GetLocalAddInt"), "{}", text);
+
+        // dynamic 变量，以及被赋过 dynamic 值的变量都走通用指令
+        let text = compile_checked("func main() {\n    var d: dynamic = 3\n    println(d == 3)\n}\n");
+        assert!(!text.contains("Int "), "{}", text);
+        let text = compile_checked("func main() {\n    var d: dynamic = \"x\"\n    var n = 3\n    n = d\n    println(n + 1)\n}\n");
+        assert!(!text.contains("AddInt") && text.contains("Add"), "{}", text);
+    }
+
+    #[test]
+    fn test_captured_locals_become_cells() {
+        let chunk = compile("func f() int {\n    var n = 0\n    n += 2\n    var inc = func() {\n        n += 1\n    }\n    inc()\n    return n\n}\nfunc g() int {\n    var m = 0\n    m += 2\n    return m\n}\n").unwrap();
+        let text = chunk.disassemble();
+        // 函数体到下一个 "--" 标题为止
+        let section = |name: &str| {
+            let start = text.find(&format!("-- {} --", name)).unwrap() + name.len() + 6;
+            let rest = &text[start..];
+            rest[..rest.find("-- ").unwrap_or(rest.len())].to_string()
+        };
+        // 被捕获且被赋值的变量装进 cell，不使用融合指令
+        let f = section("f");
+        assert!(f.contains("MakeCell 0") && f.contains("SetCell 0"), "{}", text);
+        assert!(!f.contains("Local"), "{}", text);
+        assert!(section("<closure>").contains("GetCell 0"), "{}", text);
+        // 未被捕获的局部变量仍使用融合指令
+        let g = section("g");
+        assert!(!g.contains("Cell") && g.contains("ReturnLocal 0"), "{}", text);
+    }
+
+    #[test]
+    fn test_duplicate_definitions_across_files() {
+        let parse = |source: &str| Parser::new(Scanner::new(source).scan_tokens(), Locale::En).parse().unwrap();
+        let dep = parse("class User {}\nfunc helper() {}\n");
+        let main = parse("func helper() {}\n\nstruct User {}\nfunc main() {}\n");
+        let mut program = main.clone();
+        program.statements = dep.statements.iter().chain(&main.statements).cloned().collect();
+
+        let mut compiler = Compiler::new(Locale::En);
+        compiler.set_source_files(vec![
+            ("lib/user.q".to_string(), dep.statements.len()),
+            ("main.q".to_string(), main.statements.len()),
+        ]);
+        let errors = compiler.compile(&program).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, vec![
+            "function 'helper' is defined twice: at lib/user.q:2:1 and at main.q:1:1",
+            "struct 'User' at main.q:3:1 conflicts with class 'User' at lib/user.q:1:1",
+        ]);
+        assert_eq!(errors[1].span.line, 3);
+
+        // 函数与类型不在同一个命名空间
+        assert!(compile("class Point {}\nfunc Point() {}\n").is_ok());
+    }
+
+    #[test]
+    fn test_switch_jump_table_for_dense_ints() {
+        let opcodes = |source: &str| {
+            let chunk = compile(source).unwrap();
+            let mut offset = 0;
+            let mut opcodes = Vec::new();
+            while offset < chunk.code.len() {
+                let instruction = chunk.decode_instruction(offset).unwrap();
+                opcodes.push(instruction.opcode);
+                offset += instruction.len;
+            }
+            opcodes
+        };
+        let dense = "var n = 3\nswitch n {\n    case 1, 2 => print(\"a\")\n    case 4 => print(\"b\")\n    case 5 => print(\"c\")\n    default => print(\"d\")\n}\n";
+        assert!(opcodes(dense).contains(&OpCode::JumpTable));
+        // 稀疏的整数和字符串逐个比较
+        let sparse = "var n = 3\nswitch n {\n    case 1 => {}\n    case 10 => {}\n    case 100 => {}\n    case 1000 => {}\n}\n";
+        assert!(!opcodes(sparse).contains(&OpCode::JumpTable));
+        let strings = "var s = \"a\"\nswitch s {\n    case \"a\", \"b\", \"c\", \"d\" => {}\n}\n";
+        assert!(!opcodes(strings).contains(&OpCode::JumpTable));
+
+        let errors = compile("var n = 1\nswitch n {\n    case 1 => {}\n    case 2, 1 => {}\n}\n").unwrap_err();
+        assert_eq!(errors[0].message, "duplicate case value 1 in switch");
+        assert_eq!(errors[0].span.line, 4);
+    }
+
+    /// 编译（不做类型检查）并运行，返回输出和执行的指令数
+    fn run_counting(source: &str, condition_jumps: bool) -> (String, usize) {
+        use std::io::Write;
+        use crate::vm::output::{Capture, Output};
+        use crate::vm::{TraceOptions, Tracer, VM};
+
+        /// 只数行数的追踪输出：每条指令一行
+        #[derive(Clone, Default)]
+        struct LineCount(Arc<Mutex<usize>>);
+
+        impl Write for LineCount {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                *self.0.lock() += buf.iter().filter(|&&b| b == b'\n').count();
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let program = Parser::new(Scanner::new(source).scan_tokens(), Locale::En).parse().unwrap();
+        let mut compiler = Compiler::new(Locale::En);
+        compiler.opt.condition_jumps = condition_jumps;
+        let chunk = compiler.compile(&program).unwrap();
+        let capture = Capture::new();
+        let lines = LineCount::default();
+        let mut vm = VM::new(Arc::new(chunk), Locale::En);
+        vm.set_output(Output::Capture(capture.clone()));
+        vm.set_tracer(Tracer::new(TraceOptions::default(), Box::new(lines.clone())));
+        vm.run().unwrap();
+        let executed = *lines.0.lock();
+        (capture.finish(), executed)
+    }
+
+    #[test]
+    fn test_discard_loop_variable_has_no_slot() {
+        let text = compile("func f() {\n    for _ in 0..3 {\n    }\n}\n").unwrap().disassemble();
+        let body: Vec<&str> = text.lines().skip_while(|l| !l.contains("-- f --")).skip(1).collect();
+        // 唯一的 GetLocal 读迭代器；元素取出后直接弹出，不写入任何槽位
+        assert_eq!(body.iter().filter(|l| l.contains("GetLocal")).count(), 1, "{}", text);
+        assert!(body.iter().all(|l| !l.contains("SetLocal")), "{}", text);
+        // 循环只占迭代器一个槽位
+        assert!(body.iter().any(|l| l.contains("GetLocal 0")), "{}", text);
+
+        // 多个 `_` 参数各占一个槽位，`var _` 只求值不声明
+        let chunk = compile("func g(_: int, _: int, x: int) int {\n    var _ = x\n    return x\n}\n").unwrap();
+        let text = chunk.disassemble();
+        assert!(text.contains("GetLocal 2") || text.contains("GetLocalInt 2"), "{}", text);
+    }
+
+    #[test]
+    fn test_condition_jumps_do_not_materialize_bools() {
+        let source = "func f(a: int, b: int, c: int, d: int) int {\n    if (a < b && c < d) {\n        return 1\n    }\n    return 0\n}\nfunc g(x: int) int {\n    for x < 10 || !(x > 20) {\n        x = x + 100\n    }\n    return x\n}\n";
+        let text = compile(source).unwrap().disassemble();
+        let body = |name: &str| -> Vec<&str> {
+            text.lines()
+                .skip_while(|l| !l.contains(&format!("-- {} --", name)))
+                .skip(1)
+                .take_while(|l| !l.contains("-- "))
+                .collect()
+        };
+        let f = body("f");
+        assert_eq!(f.iter().filter(|l| l.contains("CompareJump")).count(), 2, "{:?}", f);
+        // 两个比较为假时跳到同一个目标
+        assert!(f.iter().all(|l| !l.contains("CompareJump") || l.contains("(< false)")), "{:?}", f);
+        for materialized in ["LtInt", "Lt ", "JumpIfFalse", "JumpIfTrue", "Pop"] {
+            assert!(f.iter().all(|l| !l.contains(materialized)), "{}: {:?}", materialized, f);
+        }
+        // 整数局部变量与小常量：`x < 10` 为真时进入循环体，`!(x > 20)` 为假时退出
+        let g = body("g");
+        assert!(g.iter().any(|l| l.contains("JumpIfLocalLtConst 0 10 ")), "{:?}", g);
+        assert!(g.iter().any(|l| l.contains("JumpIfLocalGtConst 0 20 ")), "{:?}", g);
+        assert!(g.iter().all(|l| !l.contains("Not") && !l.contains("GtInt")), "{:?}", g);
+    }
+
+    #[test]
+    fn test_no_optimize_attribute_disables_function_optimizations() {
+        use crate::vm::output::{Capture, Output};
+        use crate::vm::VM;
+
+        let body = "(n: int) int {\n    var total = 0\n    for var i = 0; i < n; i += 1 {\n        total = total + i\n    }\n    if n > 2 + 3 {\n        return total\n    }\n    return 0\n}\n";
+        let source = format!(
+            "func fast{}@no_optimize\nfunc slow{}func main() {{\n    println(fast(10))\n    println(slow(10))\n}}\n",
+            body, body
+        );
+        let chunk = compile_optimized(&source);
+        let text = chunk.disassemble();
+        let section = |header: &str| -> Vec<String> {
+            text.lines()
+                .skip_while(|l| !l.contains(header))
+                .skip(1)
+                .take_while(|l| !l.contains("-- "))
+                .map(str::to_string)
+                .collect()
+        };
+        let fast = section("-- fast --");
+        let slow = section("-- slow @no_optimize --");
+        assert!(!slow.is_empty(), "{}", text);
+        for fused in ["CompareJump", "ReturnLocal", "ReturnInt"] {
+            assert!(fast.iter().any(|l| l.contains(fused)), "{}: {:?}", fused, fast);
+            assert!(slow.iter().all(|l| !l.contains(fused)), "{}: {:?}", fused, slow);
+        }
+        // `2 + 3` 在 fast 中折叠成 5，在 slow 中保留原样
+        assert!(fast.iter().any(|l| l.contains("ConstInt8 5")), "{:?}", fast);
+        assert!(slow.iter().any(|l| l.contains("ConstInt8 3")), "{:?}", slow);
+
+        let capture = Capture::new();
+        let mut vm = VM::new(Arc::new(chunk), Locale::En);
+        vm.set_output(Output::Capture(capture.clone()));
+        vm.run().unwrap();
+        assert_eq!(capture.finish(), "45\n45\n");
+    }
+
+    #[test]
+    fn test_condition_jumps_match_materialized_bools() {
+        // 操作数打印自己的名字：输出记录了求值顺序和短路
+        let prelude = r#"
+func t(i: int) bool {
+    print("t${i} ")
+    return true
+}
+func f(i: int) bool {
+    print("f${i} ")
+    return false
+}
+func n(i: int) int {
+    print("n${i} ")
+    return i
+}
+func z(i: int) int {
+    print("z${i} ")
+    return 0
+}
+var x: int = 2
+var inf = 2.0
+for inf < inf * 2.0 {
+    inf = inf * inf
+}
+var nan = inf - inf
+"#;
+        let atoms = [
+            "t(1)", "f(2)", "n(3) < 2", "n(4) >= 4", "x <= 2", "3 > x", "-1 >= x", "x == 2", "x != n(5)",
+            "nan < 1.0", "!(nan >= 1.0)", "z(6)", "true", "(n(7) > 1) == f(8)",
+        ];
+        let mut exprs: Vec<String> = Vec::new();
+        for a in atoms {
+            exprs.push(a.to_string());
+            exprs.push(format!("!({})", a));
+        }
+        for a in atoms {
+            for b in atoms {
+                exprs.push(format!("{} && {}", a, b));
+                exprs.push(format!("{} || {}", a, b));
+            }
+        }
+        for (i, a) in atoms.iter().enumerate() {
+            let b = atoms[(i + 3) % atoms.len()];
+            let c = atoms[(i + 7) % atoms.len()];
+            for (op1, op2) in [("&&", "&&"), ("&&", "||"), ("||", "&&"), ("||", "||")] {
+                exprs.push(format!("!({} {} {}) {} {}", a, op1, b, op2, c));
+                exprs.push(format!("{} {} !({} {} {})", a, op1, b, op2, c));
+                exprs.push(format!("!(!({}) {} ({} {} !({})))", a, op1, b, op2, c));
+            }
+        }
+
+        let mut source = prelude.to_string();
+        for (case, expr) in exprs.iter().enumerate() {
+            // 每个用例在块中声明一个局部变量：条件跳转之后栈不平衡时读到的值不对
+            source.push_str(&format!(
+                "{{\n    print(\"{case}: \")\n    if ({e}) {{\n        print(\"then \")\n    }} else {{\n        print(\"else \")\n    }}\n    print({e} ? \"yes \" : \"no \")\n    var w = 0\n    for ({e}) {{\n        w = w + 1\n        break\n    }}\n    for var i = 0; i < 1 && ({e}); i = i + 1 {{\n        w = w + 10\n    }}\n    var check = {case}\n    println(\"${{w}} ${{check}} ${{{e}}}\")\n}}\n",
+                case = case,
+                e = expr
+            ));
+        }
+        let (expected, _) = run_counting(&source, false);
+        let (actual, _) = run_counting(&source, true);
+        assert_eq!(expected.lines().count(), exprs.len());
+        for ((expected, actual), expr) in expected.lines().zip(actual.lines()).zip(&exprs) {
+            assert_eq!(actual, expected, "{}", expr);
+        }
+    }
+
+    #[test]
+    fn test_condition_jumps_benchmark() {
+        // 分支密集的负载：按字符类别计数，类似词法分析器
+        let source = r#"
+func classify(c: int) int {
+    if c >= 48 && c <= 57 {
+        return 1
+    }
+    if (c >= 65 && c <= 90) || (c >= 97 && c <= 122) || c == 95 {
+        return 2
+    }
+    if c == 32 || c == 9 || !(c != 10) {
+        return 3
+    }
+    return 0
+}
+var counts = [0, 0, 0, 0]
+var i = 0
+for i < 2000 {
+    var k = classify(i % 128)
+    counts[k] = counts[k] + 1
+    i = i + 1
+}
+println(counts)
+"#;
+        let (naive_output, naive) = run_counting(source, false);
+        let (output, executed) = run_counting(source, true);
+        assert_eq!(output, naive_output);
+        assert_eq!(output, "[982, 160, 810, 48]\n");
+        // 不生成中间的布尔值，执行的指令少三成以上
+        assert!(executed * 10 < naive * 7, "{} vs {} instructions", executed, naive);
+    }
 }
@@ -3,8 +3,10 @@
 //! Compiles AST to bytecode
 
 pub mod bytecode;
+pub mod capture;
 pub mod codegen;
 pub mod symbol;
+pub mod serialize;
 
-pub use bytecode::{Chunk, OpCode};
+pub use bytecode::{Chunk, Comparison, OpCode};
 pub use codegen::Compiler;
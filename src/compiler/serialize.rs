@@ -0,0 +1,255 @@
+//! 字节码块的二进制格式
+//!
+//! `bundle` 把编译好的字节码块写入可执行文件，运行时读出后直接执行，不再解析和编译。
+//! 编码与解析缓存相同（见 [`crate::parser::cache`]），开头记录格式版本和编译器版本：
+//! 字节码只能由同一版本的虚拟机执行，版本不同时拒绝加载。
+//!
+//! 常量池中只会出现编译期常量：null、布尔、整数、浮点数、字符、字符串、类型引用和函数。
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::config::VERSION;
+use crate::parser::cache::{Codec, Decoder, Encoder};
+use crate::vm::value::{Function, UpvalueDescriptor};
+use crate::vm::Value;
+use super::bytecode::{
+    Chunk, EnumInfo, EnumVariantInfo, FunctionRange, InterfaceInfo, InterfaceMethodInfo, NamedFunctionInfo, SourceSpan,
+    TraitInfo, TraitMethodInfo, TypeInfo,
+};
+
+/// 魔数
+const MAGIC: &[u8; 4] = b"QBC\0";
+
+/// 编码格式版本，字节码块的结构变化时递增
+const FORMAT_VERSION: u32 = 1;
+
+/// 编码字节码块；常量池中有不能编码的值时返回错误
+pub fn encode_chunk(chunk: &Chunk) -> Result<Vec<u8>, String> {
+    for constant in &chunk.constants {
+        check_constant(constant)?;
+    }
+    let mut out = Encoder::default();
+    out.bytes.extend_from_slice(MAGIC);
+    out.bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    out.str(VERSION);
+    chunk.encode(&mut out);
+    Ok(out.bytes)
+}
+
+/// 解码字节码块；不是字节码、版本不同或数据损坏时返回错误
+pub fn decode_chunk(bytes: &[u8]) -> Result<Chunk, String> {
+    let mut input = Decoder::new(bytes);
+    if input.take(4) != Some(MAGIC.as_slice()) {
+        return Err("not a compiled program".to_string());
+    }
+    let format = input.take(4).and_then(|b| b.try_into().ok()).map(u32::from_le_bytes);
+    let version = input.string();
+    match (format, version) {
+        (Some(FORMAT_VERSION), Some(version)) if version == VERSION => {}
+        (_, Some(version)) => {
+            return Err(format!("program was compiled by version {}, this runtime is version {}", version, VERSION));
+        }
+        _ => return Err("compiled program is damaged".to_string()),
+    }
+    match Chunk::decode(&mut input) {
+        Some(chunk) if input.is_at_end() => Ok(chunk),
+        _ => Err("compiled program is damaged".to_string()),
+    }
+}
+
+fn check_constant(value: &Value) -> Result<(), String> {
+    if value.is_null() || value.is_bool() || value.is_char() || value.is_int() || value.is_string() || value.is_type_ref() {
+        return Ok(());
+    }
+    if let Some(function) = value.as_function() {
+        return function.defaults.iter().try_for_each(check_constant);
+    }
+    if value.is_float() {
+        return Ok(());
+    }
+    Err(format!("constant {} cannot be stored in a compiled program", value))
+}
+
+/// 按字段顺序编码的结构体
+macro_rules! struct_codec {
+    ($ty:ident { $($field:ident),* $(,)? }) => {
+        impl Codec for $ty {
+            fn encode(&self, out: &mut Encoder) {
+                $(self.$field.encode(out);)*
+            }
+            fn decode(input: &mut Decoder) -> Option<Self> {
+                Some($ty { $($field: Codec::decode(input)?),* })
+            }
+        }
+    };
+}
+
+struct_codec!(Chunk {
+    code, constants, lines, types, interfaces, traits, enums, named_functions, named_function_infos,
+    function_ranges, files, file_starts, spans, host_functions,
+});
+struct_codec!(TypeInfo {
+    name, parent, methods, static_methods, fields, static_fields, const_fields, is_class, is_abstract,
+    abstract_methods, traits,
+});
+struct_codec!(InterfaceInfo { name, methods });
+struct_codec!(InterfaceMethodInfo { name, arity });
+struct_codec!(TraitInfo { name, methods });
+struct_codec!(TraitMethodInfo { name, arity, default_impl });
+struct_codec!(EnumInfo { name, variants });
+struct_codec!(EnumVariantInfo { name, fields, value_index });
+struct_codec!(NamedFunctionInfo { func_index, param_names });
+struct_codec!(FunctionRange { name, start, end, attributes });
+struct_codec!(SourceSpan { line, column, len });
+struct_codec!(UpvalueDescriptor { index, is_local });
+
+impl Codec for u8 {
+    fn encode(&self, out: &mut Encoder) {
+        out.tag(*self);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        input.tag()
+    }
+}
+
+impl Codec for u16 {
+    fn encode(&self, out: &mut Encoder) {
+        out.uint(*self as u64);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        u16::try_from(input.uint()?).ok()
+    }
+}
+
+/// 映射按键排序后编码，同一个程序总是得到相同的字节
+impl<K: Codec + Ord + std::hash::Hash + Eq, V: Codec> Codec for HashMap<K, V> {
+    fn encode(&self, out: &mut Encoder) {
+        let mut entries: Vec<_> = self.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        out.uint(entries.len() as u64);
+        for (key, value) in entries {
+            key.encode(out);
+            value.encode(out);
+        }
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(Vec::<(K, V)>::decode(input)?.into_iter().collect())
+    }
+}
+
+impl<T: Codec + Ord + std::hash::Hash + Eq> Codec for HashSet<T> {
+    fn encode(&self, out: &mut Encoder) {
+        let mut items: Vec<_> = self.iter().collect();
+        items.sort();
+        out.uint(items.len() as u64);
+        for item in items {
+            item.encode(out);
+        }
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(Vec::<T>::decode(input)?.into_iter().collect())
+    }
+}
+
+/// 常量（编码前已由 `check_constant` 检查）
+impl Codec for Value {
+    fn encode(&self, out: &mut Encoder) {
+        if self.is_null() {
+            out.tag(0);
+        } else if let Some(b) = self.as_bool() {
+            out.tag(1);
+            b.encode(out);
+        } else if let Some(c) = self.as_char() {
+            out.tag(4);
+            c.encode(out);
+        } else if let Some(n) = self.as_int() {
+            out.tag(2);
+            n.encode(out);
+        } else if let Some(s) = self.as_string() {
+            out.tag(5);
+            s.encode(out);
+        } else if let Some(function) = self.as_function() {
+            out.tag(6);
+            function.encode(out);
+        } else if let Some(name) = self.as_type_ref() {
+            out.tag(7);
+            name.encode(out);
+        } else {
+            out.tag(3);
+            self.as_float().unwrap_or(f64::NAN).encode(out);
+        }
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(match input.tag()? {
+            0 => Value::null(),
+            1 => Value::bool(bool::decode(input)?),
+            2 => Value::int(i128::decode(input)?),
+            3 => Value::float(f64::decode(input)?),
+            4 => Value::char(char::decode(input)?),
+            5 => Value::string(String::decode(input)?),
+            6 => Value::function(Arc::new(Function::decode(input)?)),
+            7 => Value::type_ref(String::decode(input)?),
+            _ => return None,
+        })
+    }
+}
+
+/// 编译期的函数常量，闭包捕获的槽位在运行时才创建
+impl Codec for Function {
+    fn encode(&self, out: &mut Encoder) {
+        self.name.encode(out);
+        self.arity.encode(out);
+        self.required_params.encode(out);
+        self.defaults.encode(out);
+        self.has_variadic.encode(out);
+        self.chunk_index.encode(out);
+        self.local_count.encode(out);
+        self.upvalues.encode(out);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(Function {
+            name: Codec::decode(input)?,
+            arity: Codec::decode(input)?,
+            required_params: Codec::decode(input)?,
+            defaults: Codec::decode(input)?,
+            has_variadic: Codec::decode(input)?,
+            chunk_index: Codec::decode(input)?,
+            local_count: Codec::decode(input)?,
+            upvalues: Codec::decode(input)?,
+            captures: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile, run_chunk, CompileOptions, RunOptions};
+
+    #[test]
+    fn test_chunk_round_trip() {
+        let source = "enum Code {\n    Ok = 200,\n    Err(msg: string)\n}\n\ninterface Named {\n    func name() string\n}\n\nclass Box implements Named {\n    static var count: int = 2\n    var v: f64 = 1.5\n    func name() string { return \"box\" }\n}\n\nfunc add(a: int, b: int = 3) int {\n    return a + b\n}\n\nfunc main() int {\n    var k = 'x'\n    var f = func(n: int) int { return n + add(1) }\n    println(\"${new Box().name()} ${k} ${Box::count}\")\n    return f(170141183460469231731687303715884105727 - 170141183460469231731687303715884105700)\n}\n";
+        let chunk = compile(source, &CompileOptions::default()).unwrap();
+        let bytes = encode_chunk(&chunk).unwrap();
+        let decoded = decode_chunk(&bytes).unwrap();
+        assert_eq!(decoded.disassemble(), chunk.disassemble());
+        // 编码与映射的遍历顺序无关
+        assert_eq!(encode_chunk(&decoded).unwrap(), bytes);
+        assert_eq!(run_chunk(decoded, &RunOptions::default()).unwrap().as_int(), Some(31));
+    }
+
+    #[test]
+    fn test_decode_rejects_other_versions() {
+        let chunk = compile("func main() {}\n", &CompileOptions::default()).unwrap();
+        let mut bytes = encode_chunk(&chunk).unwrap();
+        let version_at = MAGIC.len() + 4 + 1;
+        bytes[version_at] = b'X';
+        let error = decode_chunk(&bytes).unwrap_err();
+        assert!(error.contains("this runtime is version"), "{}", error);
+
+        let bytes = encode_chunk(&chunk).unwrap();
+        assert_eq!(decode_chunk(&bytes[..bytes.len() - 1]).unwrap_err(), "compiled program is damaged");
+        assert_eq!(decode_chunk(b"MZ").unwrap_err(), "not a compiled program");
+    }
+}
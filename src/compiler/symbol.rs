@@ -1,11 +1,17 @@
 //! Symbol Table
 //!
-//! Manages variables, scopes, and closure captures (upvalues)
+//! Manages variables, scopes, and closure captures (cells)
 
 #![allow(dead_code)]
 
+use std::collections::hash_map::{Entry, HashMap};
+
+use crate::lexer::Span;
+use crate::parser::DISCARD;
 use crate::types::Type;
 
+use super::capture::Captures;
+
 /// Symbol information
 #[derive(Debug, Clone)]
 pub struct Symbol {
@@ -19,8 +25,10 @@ pub struct Symbol {
     pub slot: usize,
     /// Scope depth
     pub depth: usize,
-    /// 是否被闭包捕获
+    /// 是否被内层闭包引用
     pub is_captured: bool,
+    /// 槽位中是否是 cell（被捕获且会被赋值的变量），读写要经过 GetCell / SetCell
+    pub is_cell: bool,
     /// 函数参数名列表（仅函数类型有效，用于命名参数重排）
     pub param_names: Option<Vec<String>>,
 }
@@ -35,6 +43,7 @@ impl Symbol {
             slot,
             depth,
             is_captured: false,
+            is_cell: false,
             param_names: None,
         }
     }
@@ -48,69 +57,12 @@ impl Symbol {
             slot,
             depth,
             is_captured: false,
+            is_cell: false,
             param_names: Some(param_names),
         }
     }
 }
 
-/// Upvalue 描述符（闭包捕获的外部变量）
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct Upvalue {
-    /// 在父函数中的索引
-    pub index: u16,
-    /// true: 捕获的是父函数的局部变量; false: 捕获的是父函数的 upvalue
-    pub is_local: bool,
-}
-
-impl Upvalue {
-    /// 创建一个捕获局部变量的 upvalue
-    pub fn local(index: u16) -> Self {
-        Self { index, is_local: true }
-    }
-    
-    /// 创建一个捕获 upvalue 的 upvalue
-    pub fn upvalue(index: u16) -> Self {
-        Self { index, is_local: false }
-    }
-}
-
-/// 编译器的闭包上下文
-#[derive(Debug, Clone, Default)]
-pub struct ClosureContext {
-    /// 当前闭包的 upvalues
-    pub upvalues: Vec<Upvalue>,
-    /// 父上下文
-    pub parent: Option<Box<ClosureContext>>,
-}
-
-impl ClosureContext {
-    /// 创建新的闭包上下文
-    pub fn new() -> Self {
-        Self::default()
-    }
-    
-    /// 创建带父上下文的闭包上下文
-    pub fn with_parent(parent: ClosureContext) -> Self {
-        Self {
-            upvalues: Vec::new(),
-            parent: Some(Box::new(parent)),
-        }
-    }
-    
-    /// 添加 upvalue，返回索引
-    pub fn add_upvalue(&mut self, upvalue: Upvalue) -> u16 {
-        // 检查是否已存在相同的 upvalue
-        for (i, uv) in self.upvalues.iter().enumerate() {
-            if uv == &upvalue {
-                return i as u16;
-            }
-        }
-        let index = self.upvalues.len() as u16;
-        self.upvalues.push(upvalue);
-        index
-    }
-}
-
 /// Symbol table (supports nested scopes and closure captures)
 #[derive(Debug, Clone, Default)]
 pub struct SymbolTable {
@@ -120,8 +72,22 @@ pub struct SymbolTable {
     scope_depth: usize,
     /// Current slot (for allocating local variables)
     current_slot: usize,
-    /// 当前闭包上下文栈
-    closure_contexts: Vec<ClosureContext>,
+    /// 当前函数内同时存活的槽位峰值（用于检查 u16 槽位上限）
+    peak_slot: usize,
+    /// 当前函数的第一个符号，之前的符号属于外层函数，不能直接按槽位访问
+    function_start: usize,
+    /// 当前函数体的捕获分析结果
+    captures: Captures,
+}
+
+/// 编译嵌套函数前保存的外层函数状态
+#[derive(Debug, Clone)]
+pub struct SavedState {
+    current_slot: usize,
+    symbols_len: usize,
+    peak_slot: usize,
+    function_start: usize,
+    captures: Captures,
 }
 
 impl SymbolTable {
@@ -140,7 +106,7 @@ impl SymbolTable {
         let mut count = 0;
 
         // Remove all symbols in current scope
-        while !self.symbols.is_empty() && self.symbols.last().unwrap().depth == self.scope_depth {
+        while self.symbols.len() > self.function_start && self.symbols.last().unwrap().depth == self.scope_depth {
             self.symbols.pop();
             self.current_slot -= 1;
             count += 1;
@@ -161,10 +127,12 @@ impl SymbolTable {
     }
 
     /// Define a new symbol
+    ///
+    /// 丢弃绑定 `_`（如参数 `_`）仍然占一个槽位，但可以重复定义，也不会被 [`resolve`](Self::resolve) 找到
     pub fn define(&mut self, name: String, ty: Type, is_const: bool) -> Result<usize, String> {
         // Check if symbol already exists in current scope
-        for symbol in self.symbols.iter().rev() {
-            if symbol.depth < self.scope_depth {
+        for symbol in self.symbols[self.function_start..].iter().rev() {
+            if symbol.depth < self.scope_depth || name == DISCARD {
                 break;
             }
             if symbol.name == name {
@@ -176,9 +144,11 @@ impl SymbolTable {
         }
 
         let slot = self.current_slot;
-        let symbol = Symbol::new(name, ty, is_const, slot, self.scope_depth);
+        let mut symbol = Symbol::new(name, ty, is_const, slot, self.scope_depth);
+        symbol.is_captured = self.captures.referenced.contains(&symbol.name);
         self.symbols.push(symbol);
         self.current_slot += 1;
+        self.peak_slot = self.peak_slot.max(self.current_slot);
 
         Ok(slot)
     }
@@ -186,7 +156,7 @@ impl SymbolTable {
     /// 定义函数符号（包含参数名列表，用于命名参数重排）
     pub fn define_function(&mut self, name: String, ty: Type, param_names: Vec<String>) -> Result<usize, String> {
         // Check if symbol already exists in current scope
-        for symbol in self.symbols.iter().rev() {
+        for symbol in self.symbols[self.function_start..].iter().rev() {
             if symbol.depth < self.scope_depth {
                 break;
             }
@@ -202,6 +172,7 @@ impl SymbolTable {
         let symbol = Symbol::new_function(name, ty, slot, self.scope_depth, param_names);
         self.symbols.push(symbol);
         self.current_slot += 1;
+        self.peak_slot = self.peak_slot.max(self.current_slot);
 
         Ok(slot)
     }
@@ -209,8 +180,8 @@ impl SymbolTable {
     /// Resolve a symbol
     pub fn resolve(&self, name: &str) -> Option<&Symbol> {
         // Search from back to front (nearest scope first)
-        for symbol in self.symbols.iter().rev() {
-            if symbol.name == name {
+        for symbol in self.symbols[self.function_start..].iter().rev() {
+            if symbol.name == name && name != DISCARD {
                 return Some(symbol);
             }
         }
@@ -237,30 +208,48 @@ impl SymbolTable {
         self.current_slot
     }
     
+    /// 当前函数需要的槽位数（同时存活的局部变量峰值）
+    pub fn peak_slots(&self) -> usize {
+        self.peak_slot
+    }
+    
     /// Save current state for function compilation
-    /// Returns (current_slot, symbols_count)
-    pub fn save_state(&self) -> (usize, usize) {
-        (self.current_slot, self.symbols.len())
+    pub fn save_state(&self) -> SavedState {
+        SavedState {
+            current_slot: self.current_slot,
+            symbols_len: self.symbols.len(),
+            peak_slot: self.peak_slot,
+            function_start: self.function_start,
+            captures: self.captures.clone(),
+        }
     }
     
     /// Restore state after function compilation
-    pub fn restore_state(&mut self, state: (usize, usize)) {
-        self.current_slot = state.0;
-        self.symbols.truncate(state.1);
+    pub fn restore_state(&mut self, state: SavedState) {
+        self.current_slot = state.current_slot;
+        self.symbols.truncate(state.symbols_len);
+        self.peak_slot = state.peak_slot;
+        self.function_start = state.function_start;
+        self.captures = state.captures;
     }
     
     /// Restore full state including scope depth
-    pub fn restore_state_full(&mut self, state: (usize, usize), scope_depth: usize) {
-        self.current_slot = state.0;
-        self.symbols.truncate(state.1);
+    pub fn restore_state_full(&mut self, state: SavedState, scope_depth: usize) {
+        self.restore_state(state);
         self.scope_depth = scope_depth;
     }
     
     /// Reset slot counter for a new function scope
     /// This is used when compiling function bodies
-    pub fn reset_for_function(&mut self) {
+    ///
+    /// 外层函数的符号仍然保留（恢复时需要），但之后不再能解析到；
+    /// `captures` 是新函数体的捕获分析结果。
+    pub fn reset_for_function(&mut self, captures: Captures) {
         self.current_slot = 0;
+        self.peak_slot = 0;
         self.scope_depth = 0;
+        self.function_start = self.symbols.len();
+        self.captures = captures;
     }
     
     /// Get current scope depth (alias for depth)
@@ -268,109 +257,107 @@ impl SymbolTable {
         self.scope_depth
     }
     
-    // ============ 闭包捕获支持 ============
+    // ============ 闭包捕获 ============
     
-    /// 进入闭包编译上下文
-    pub fn begin_closure(&mut self) {
-        let parent = if self.closure_contexts.is_empty() {
-            ClosureContext::new()
-        } else {
-            let current = self.closure_contexts.pop().unwrap();
-            ClosureContext::with_parent(current)
-        };
-        self.closure_contexts.push(parent);
+    /// 设置顶层语句的捕获分析结果
+    pub fn set_captures(&mut self, captures: Captures) {
+        self.captures = captures;
     }
     
-    /// 退出闭包编译上下文，返回 upvalues
-    pub fn end_closure(&mut self) -> Vec<Upvalue> {
-        if let Some(ctx) = self.closure_contexts.pop() {
-            // 恢复父上下文
-            if let Some(parent) = ctx.parent {
-                self.closure_contexts.push(*parent);
-            }
-            ctx.upvalues
-        } else {
-            Vec::new()
+    /// 把槽位上刚定义的变量提升为 cell：被闭包捕获、会被赋值且不是常量时返回 true
+    pub fn promote_to_cell(&mut self, slot: usize) -> bool {
+        let start = self.function_start;
+        let Some(symbol) = self.symbols[start..].iter_mut().rev().find(|s| s.slot == slot) else {
+            return false;
+        };
+        if symbol.is_const || symbol.is_cell || !self.captures.needs_cell(&symbol.name) {
+            return false;
         }
+        symbol.is_cell = true;
+        true
     }
     
-    /// 解析变量，支持 upvalue 查找
-    /// 返回: (Option<slot>, Option<upvalue_index>, is_upvalue)
-    pub fn resolve_with_capture(&mut self, name: &str) -> VariableResolution {
-        // 首先在当前作用域查找
-        for symbol in self.symbols.iter().rev() {
-            if symbol.name == name {
-                return VariableResolution::Local(symbol.slot);
-            }
-        }
-        
-        // 如果在闭包上下文中，尝试解析为 upvalue
-        if !self.closure_contexts.is_empty() {
-            if let Some(upvalue_idx) = self.resolve_upvalue(name) {
-                return VariableResolution::Upvalue(upvalue_idx);
-            }
+    /// 定义闭包捕获的外部变量（紧跟在参数之后的槽位），与外层变量同样是 cell 或按值复制
+    pub fn define_capture(&mut self, name: String, ty: Type, is_const: bool, is_cell: bool) -> Result<usize, String> {
+        let slot = self.define(name, ty, is_const)?;
+        if let Some(symbol) = self.symbols.last_mut() {
+            symbol.is_cell = is_cell;
         }
-        
-        VariableResolution::NotFound
+        Ok(slot)
     }
-    
-    /// 递归解析 upvalue
-    fn resolve_upvalue(&mut self, name: &str) -> Option<u16> {
-        if self.closure_contexts.is_empty() {
-            return None;
-        }
-        
-        // 在父函数的局部变量中查找
-        for (i, symbol) in self.symbols.iter_mut().enumerate().rev() {
-            if symbol.name == name {
-                // 标记为被捕获
-                symbol.is_captured = true;
-                
-                // 添加 upvalue
-                if let Some(ctx) = self.closure_contexts.last_mut() {
-                    let upvalue = Upvalue::local(symbol.slot as u16);
-                    return Some(ctx.add_upvalue(upvalue));
-                }
-            }
+}
+
+/// 顶层定义的种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefinitionKind {
+    Function,
+    Class,
+    Struct,
+    Enum,
+    Interface,
+    Trait,
+}
+
+impl DefinitionKind {
+    /// 用于诊断信息的名称
+    pub fn describe(self) -> &'static str {
+        match self {
+            DefinitionKind::Function => "function",
+            DefinitionKind::Class => "class",
+            DefinitionKind::Struct => "struct",
+            DefinitionKind::Enum => "enum",
+            DefinitionKind::Interface => "interface",
+            DefinitionKind::Trait => "trait",
         }
-        
-        // 在父函数的 upvalues 中查找（嵌套闭包）
-        // 这里简化处理，实际实现可能需要更复杂的递归
-        None
     }
-    
-    /// 标记符号为被捕获
-    pub fn mark_captured(&mut self, name: &str) {
-        for symbol in self.symbols.iter_mut().rev() {
-            if symbol.name == name {
-                symbol.is_captured = true;
-                break;
-            }
+}
+
+/// 顶层定义及其位置
+#[derive(Debug, Clone)]
+pub struct Definition {
+    pub kind: DefinitionKind,
+    /// 所在文件，未知时为 None
+    pub file: Option<String>,
+    pub span: Span,
+}
+
+impl std::fmt::Display for Definition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file)?;
         }
-    }
-    
-    /// 检查符号是否被捕获
-    pub fn is_captured(&self, name: &str) -> bool {
-        self.resolve(name).map(|s| s.is_captured).unwrap_or(false)
-    }
-    
-    /// 获取当前闭包上下文的 upvalues
-    pub fn current_upvalues(&self) -> &[Upvalue] {
-        self.closure_contexts.last()
-            .map(|ctx| ctx.upvalues.as_slice())
-            .unwrap_or(&[])
+        write!(f, "{}:{}", self.span.line, self.span.column)
     }
 }
 
-/// 变量解析结果
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum VariableResolution {
-    /// 局部变量，包含槽位
-    Local(usize),
-    /// Upvalue，包含 upvalue 索引
-    Upvalue(u16),
-    /// 未找到
-    NotFound,
+/// 顶层名字表：函数和类型（类、结构体、枚举、接口、Trait）各占一个命名空间
+///
+/// 依赖文件的语句合并到同一个程序中，同名定义会互相覆盖，需要在编译前发现
+#[derive(Debug, Default)]
+pub struct TopLevelNames {
+    functions: HashMap<String, Definition>,
+    types: HashMap<String, Definition>,
+}
+
+impl TopLevelNames {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 登记一个定义；同一命名空间中已有同名定义时返回先前的定义
+    pub fn declare(&mut self, name: &str, definition: Definition) -> Result<(), &Definition> {
+        let names = match definition.kind {
+            DefinitionKind::Function => &mut self.functions,
+            _ => &mut self.types,
+        };
+        match names.entry(name.to_string()) {
+            Entry::Occupied(entry) => Err(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                entry.insert(definition);
+                Ok(())
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -426,6 +413,20 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_top_level_names() {
+        let at = |kind, file: &str, line| Definition { kind, file: Some(file.to_string()), span: Span::new(0, 0, line, 1) };
+        let mut names = TopLevelNames::new();
+        names.declare("User", at(DefinitionKind::Class, "a.q", 1)).unwrap();
+        names.declare("User", at(DefinitionKind::Function, "a.q", 5)).unwrap();
+
+        // 类型共用一个命名空间
+        let previous = names.declare("User", at(DefinitionKind::Struct, "b.q", 3)).unwrap_err();
+        assert_eq!((previous.kind, previous.to_string()), (DefinitionKind::Class, "a.q:1:1".to_string()));
+        let previous = names.declare("User", at(DefinitionKind::Function, "b.q", 7)).unwrap_err();
+        assert_eq!(previous.to_string(), "a.q:5:1");
+    }
+
     #[test]
     fn test_const() {
         let mut table = SymbolTable::new();
@@ -436,4 +437,46 @@ mod tests {
         assert!(symbol.is_const);
         assert!(table.is_const("PI").unwrap());
     }
+
+    #[test]
+    fn test_capture_marking() {
+        let mut captures = Captures::default();
+        for name in ["count", "step", "LIMIT"] {
+            captures.referenced.insert(name.to_string());
+        }
+        for name in ["count", "LIMIT", "plain"] {
+            captures.assigned.insert(name.to_string());
+        }
+
+        let mut table = SymbolTable::new();
+        table.define("outer".to_string(), Type::Int, false).unwrap();
+        let saved = table.save_state();
+        table.reset_for_function(captures);
+
+        let count = table.define("count".to_string(), Type::Int, false).unwrap();
+        let step = table.define("step".to_string(), Type::Int, false).unwrap();
+        let limit = table.define("LIMIT".to_string(), Type::Int, true).unwrap();
+        let plain = table.define("plain".to_string(), Type::Int, false).unwrap();
+        assert_eq!(count, 0);
+        assert!(table.resolve("count").unwrap().is_captured);
+        assert!(!table.resolve("plain").unwrap().is_captured);
+
+        // 只有被捕获且会被赋值的变量提升为 cell，只读的和常量按值复制
+        assert!(table.promote_to_cell(count));
+        assert!(!table.promote_to_cell(step));
+        assert!(!table.promote_to_cell(limit));
+        assert!(!table.promote_to_cell(plain));
+        assert!(table.resolve("count").unwrap().is_cell);
+        assert!(!table.resolve("step").unwrap().is_cell);
+
+        // 外层函数的变量不能直接访问，只能作为捕获变量重新定义
+        assert!(table.resolve("outer").is_none());
+        table.define_capture("outer".to_string(), Type::Int, false, true).unwrap();
+        assert_eq!(table.resolve_slot("outer"), Some(4));
+        assert!(table.resolve("outer").unwrap().is_cell);
+
+        table.restore_state(saved);
+        assert_eq!(table.resolve_slot("outer"), Some(0));
+        assert!(table.resolve("count").is_none());
+    }
 }
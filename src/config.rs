@@ -23,3 +23,6 @@ pub const STDLIB_DIR: &str = "stdlib";
 
 /// 版本号
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// 报告实现缺陷（内部错误）的地址
+pub const ISSUE_TRACKER: &str = "https://github.com/tangzhangming/qlang/issues";
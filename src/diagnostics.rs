@@ -0,0 +1,569 @@
+//! 诊断信息渲染
+//!
+//! 在错误消息下方输出出错的源码行和插入符号（`^`）下划线：
+//!
+//! ```text
+//!    3 | var x: int = "hello"
+//!      |              ^^^^^^^
+//! ```
+//!
+//! 列号策略：[`Span::column`] 按字符计数，制表符计为 1 列。渲染时制表符统一展开为
+//! [`TAB_WIDTH`] 个空格，CJK 全角字符和 emoji 占 2 个显示列，插入符号按显示宽度对齐。
+//! 超长的行（如压缩后的单行文件）只显示错误位置前后各 [`WINDOW_RADIUS`] 个字符，
+//! 被截掉的部分用 `…` 表示。
+//!
+//! 着色遵循 NO_COLOR 约定，见 [`use_color`]。
+//!
+//! 编译产生的错误和警告是结构化的 [`Diagnostic`]（文件、位置、严重程度、消息），
+//! 嵌入程序可以自己显示；[`Diagnostics::render`] 按命令行的格式分组输出。
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::i18n::{format_message, messages, Locale};
+use crate::lexer::Span;
+
+/// 制表符展开宽度
+pub const TAB_WIDTH: usize = 4;
+
+/// 长行窗口半径：错误位置前后各显示的字符数
+pub const WINDOW_RADIUS: usize = 60;
+
+/// 截断标记
+const ELLIPSIS: char = '…';
+
+/// 字符的显示宽度：制表符展开为 TAB_WIDTH，宽字符为 2，控制字符为 0，其余为 1
+pub fn char_display_width(c: char) -> usize {
+    if c == '\t' {
+        return TAB_WIDTH;
+    }
+    if c.is_control() {
+        return 0;
+    }
+    let cp = c as u32;
+    let wide = matches!(cp,
+        0x1100..=0x115F        // 谚文字母
+        | 0x2E80..=0x303E      // CJK 部首、标点
+        | 0x3041..=0x33FF      // 假名、CJK 符号
+        | 0x3400..=0x4DBF      // CJK 扩展 A
+        | 0x4E00..=0x9FFF      // CJK 统一汉字
+        | 0xA000..=0xA4CF      // 彝文
+        | 0xAC00..=0xD7A3      // 谚文音节
+        | 0xF900..=0xFAFF      // CJK 兼容汉字
+        | 0xFE30..=0xFE4F      // CJK 兼容形式
+        | 0xFF00..=0xFF60      // 全角字符
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F    // 符号与 emoji
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x3FFFD    // CJK 扩展 B 及以后
+    );
+    if wide { 2 } else { 1 }
+}
+
+/// 渲染用的字符串：展开制表符，去掉控制字符
+fn display_text(chars: &[char]) -> String {
+    let mut text = String::new();
+    for &c in chars {
+        if c == '\t' {
+            text.extend(std::iter::repeat_n(' ', TAB_WIDTH));
+        } else if !c.is_control() {
+            text.push(c);
+        }
+    }
+    text
+}
+
+fn display_width(chars: &[char]) -> usize {
+    chars.iter().map(|&c| char_display_width(c)).sum()
+}
+
+/// 获取第 line 行（从 1 开始）的内容，不含换行符
+pub fn source_line(source: &str, line: usize) -> Option<&str> {
+    let line = source.split('\n').nth(line.checked_sub(1)?)?;
+    Some(line.strip_suffix('\r').unwrap_or(line))
+}
+
+/// 渲染一行源码及其下方的插入符号
+///
+/// `column` 从 1 开始，`len` 为下划线覆盖的字符数（至少为 1），两者都按字符计数。
+/// 返回两行：源码行（过长时截取窗口）和对齐的插入符号行，不带行号栏。
+pub fn render_line(line: &str, column: usize, len: usize) -> (String, String) {
+    let chars: Vec<char> = line.chars().collect();
+    let start = column.saturating_sub(1).min(chars.len());
+    let end = (start + len.max(1)).min(chars.len());
+
+    // 长行只显示错误位置附近的窗口
+    let window_start = start.saturating_sub(WINDOW_RADIUS);
+    let window_end = (end + WINDOW_RADIUS).min(chars.len());
+
+    let mut text = String::new();
+    let mut padding = 0;
+    if window_start > 0 {
+        text.push(ELLIPSIS);
+        padding += 1;
+    }
+    text.push_str(&display_text(&chars[window_start..window_end]));
+    if window_end < chars.len() {
+        text.push(ELLIPSIS);
+    }
+
+    padding += display_width(&chars[window_start..start]);
+    // 位置在行尾（如缺少的右括号）时仍然画一个插入符号
+    let underline = display_width(&chars[start..end]).max(1);
+    let carets = format!("{}{}", " ".repeat(padding), "^".repeat(underline));
+
+    (text, carets)
+}
+
+/// 渲染 span 所在的源码片段（带行号栏），行号超出范围时返回 None
+///
+/// 跨行的 span 只在第一行画到行尾
+pub fn render_snippet(source: &str, span: &Span) -> Option<String> {
+    let line = source_line(source, span.line)?;
+
+    // span.start/end 是字节偏移；只有落在字符边界上时才用它计算下划线长度
+    let len = source
+        .get(span.start..span.end)
+        .map(|text| text.split('\n').next().unwrap_or("").chars().count())
+        .unwrap_or(1);
+
+    let (text, carets) = render_line(line, span.column, len);
+    let gutter = span.line.to_string().len();
+    Some(format!(
+        "{:>gutter$} | {}\n{:>gutter$} | {}",
+        span.line,
+        text,
+        "",
+        carets,
+        gutter = gutter
+    ))
+}
+
+/// 清理路径显示格式（移除 Windows 的 \\?\ 前缀）
+pub fn display_path(path: &Path) -> String {
+    let s = path.to_string_lossy();
+    // Windows canonicalize 返回 \\?\C:\... 格式，需要清理
+    match s.strip_prefix(r"\\?\") {
+        Some(rest) => rest.to_string(),
+        None => s.to_string(),
+    }
+}
+
+/// 诊断的严重程度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// 产生诊断的阶段，决定输出时的分组标题
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// 查找和读取 project.toml
+    Config,
+    /// 词法和语法分析
+    Syntax,
+    /// 读取文件、解析导入、检查包名
+    Import,
+    /// 类型检查和单态化
+    Type,
+    /// 生成字节码
+    Compile,
+}
+
+impl Stage {
+    fn label(self) -> &'static str {
+        match self {
+            Stage::Config => messages::MSG_CLI_CONFIG_ERROR,
+            Stage::Syntax => messages::MSG_CLI_SYNTAX_ERROR,
+            Stage::Import => messages::MSG_CLI_IMPORT_ERROR,
+            Stage::Type => messages::MSG_CLI_TYPE_ERROR,
+            Stage::Compile => messages::MSG_CLI_COMPILE_ERROR,
+        }
+    }
+}
+
+/// 一条错误或警告
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub stage: Stage,
+    /// 出错的源文件（显示用的路径），不属于某个文件或来源未知时为 None
+    pub file: Option<String>,
+    /// 出错位置，没有具体位置（如读取文件失败）时为 None
+    pub span: Option<Span>,
+    /// 诊断代码，如隐式 any 的 `W0001`
+    pub code: Option<&'static str>,
+    pub message: String,
+    /// 类型内部的冲突位置（由内向外）
+    pub path: Vec<String>,
+    /// 相关位置，如期望类型的来源
+    pub labels: Vec<(Span, String)>,
+    /// 附加信息
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(stage: Stage, message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, stage, message.into())
+    }
+
+    pub fn warning(stage: Stage, message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, stage, message.into())
+    }
+
+    fn new(severity: Severity, stage: Stage, message: String) -> Self {
+        Self {
+            severity,
+            stage,
+            file: None,
+            span: None,
+            code: None,
+            message,
+            path: Vec::new(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    /// 设置出错位置
+    pub fn at(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    /// 设置出错的源文件
+    pub fn in_file(mut self, file: Option<String>) -> Self {
+        self.file = file;
+        self
+    }
+
+    /// 渲染为多行文本：位置和消息、源码片段（有源码时）、类型路径、相关位置和附加信息
+    pub fn render(&self, source: Option<&str>) -> String {
+        let mut first = String::new();
+        if let Some(span) = &self.span {
+            first.push_str(&format!("[{}:{}] ", span.line, span.column));
+        }
+        if let Some(code) = self.code {
+            first.push_str(&format!("{}: ", code));
+        }
+        first.push_str(&self.message);
+        let mut lines = vec![first];
+        if let Some(snippet) = source.zip(self.span.as_ref()).and_then(|(source, span)| render_snippet(source, span)) {
+            lines.push(snippet);
+        }
+        if !self.path.is_empty() {
+            lines.push(format!("    {}", self.path.join(", ")));
+        }
+        for (span, message) in &self.labels {
+            lines.push(format!("    [{}:{}] {}", span.line, span.column, message));
+        }
+        for note in &self.notes {
+            lines.push(format!("    note: {}", note));
+        }
+        lines.join("\n")
+    }
+
+    /// 输出时所在分组的标题，如 `[Type Error] src/main.q`
+    fn title(&self, locale: Locale) -> String {
+        let label = match self.severity {
+            Severity::Error => self.stage.label(),
+            Severity::Warning => messages::MSG_CLI_WARNING,
+        };
+        let label = format_message(label, locale, &[]);
+        match &self.file {
+            Some(file) => format!("{} {}", label, file),
+            None => label,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(None))
+    }
+}
+
+/// 一次编译的所有诊断，按产生的顺序
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diagnostics(Vec<Diagnostic>);
+
+/// 一次最多输出的诊断条数，超出的只报告数量
+pub const MAX_REPORTED_ERRORS: usize = 50;
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.0.push(diagnostic);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Diagnostic> {
+        self.0.iter()
+    }
+
+    /// 是否有错误（而不只是警告）
+    pub fn has_errors(&self) -> bool {
+        self.0.iter().any(|d| d.severity == Severity::Error)
+    }
+
+    /// 按命令行的格式输出
+    ///
+    /// 标题相同（阶段和文件相同）的诊断归为一组，组按第一次出现的顺序排列；语法错误不缩进，其余缩进两格。
+    /// `load_source` 按文件名提供源码，用于显示出错位置的片段。
+    /// 总数超过 [`MAX_REPORTED_ERRORS`] 时省略其余的诊断，最后注明省略的条数。
+    pub fn render(&self, locale: Locale, load_source: &mut dyn FnMut(Option<&str>) -> Option<String>) -> String {
+        let mut groups: Vec<(String, Vec<&Diagnostic>)> = Vec::new();
+        for diagnostic in &self.0 {
+            let title = diagnostic.title(locale);
+            match groups.iter_mut().find(|(t, _)| *t == title) {
+                Some((_, group)) => group.push(diagnostic),
+                None => groups.push((title, vec![diagnostic])),
+            }
+        }
+
+        let mut sources: HashMap<String, Option<String>> = HashMap::new();
+        let mut lines = Vec::new();
+        let mut shown = 0;
+        for (title, group) in groups {
+            if shown == MAX_REPORTED_ERRORS {
+                break;
+            }
+            lines.push(title);
+            for diagnostic in group.into_iter().take(MAX_REPORTED_ERRORS - shown) {
+                let source = diagnostic
+                    .file
+                    .as_ref()
+                    .and_then(|file| sources.entry(file.clone()).or_insert_with(|| load_source(Some(file))).as_deref());
+                let text = diagnostic.render(source);
+                lines.push(match diagnostic.stage {
+                    Stage::Syntax => text,
+                    _ => format!("  {}", text.replace('\n', "\n  ")),
+                });
+                shown += 1;
+            }
+        }
+        if self.len() > shown {
+            lines.push(format!("... and {} more errors", self.len() - shown));
+        }
+        lines.join("\n")
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(Locale::En, &mut |_| None))
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+impl From<Diagnostic> for Diagnostics {
+    fn from(diagnostic: Diagnostic) -> Self {
+        Self(vec![diagnostic])
+    }
+}
+
+impl From<Vec<Diagnostic>> for Diagnostics {
+    fn from(diagnostics: Vec<Diagnostic>) -> Self {
+        Self(diagnostics)
+    }
+}
+
+impl Extend<Diagnostic> for Diagnostics {
+    fn extend<I: IntoIterator<Item = Diagnostic>>(&mut self, iter: I) {
+        self.0.extend(iter);
+    }
+}
+
+impl IntoIterator for Diagnostics {
+    type Item = Diagnostic;
+    type IntoIter = std::vec::IntoIter<Diagnostic>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Diagnostics {
+    type Item = &'a Diagnostic;
+    type IntoIter = std::slice::Iter<'a, Diagnostic>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+/// 终端着色样式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// 粗体红色：错误类型、出错位置的插入符号
+    Error,
+    /// 粗体：需要突出的位置
+    Emphasis,
+    /// 暗色：次要信息
+    Dim,
+}
+
+/// 是否输出 ANSI 颜色
+///
+/// 遵循 NO_COLOR 约定：指定 `--no-color` 或设置了非空的 `NO_COLOR` 环境变量时不着色，
+/// stderr 不是终端（重定向到文件或管道）时也不着色
+pub fn use_color(no_color_flag: bool) -> bool {
+    use std::io::IsTerminal;
+    !no_color_flag
+        && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+        && std::io::stderr().is_terminal()
+}
+
+/// 按样式给文本加上 ANSI 转义序列，`color` 为 false 时原样返回
+pub fn paint(text: &str, style: Style, color: bool) -> String {
+    if !color {
+        return text.to_string();
+    }
+    let code = match style {
+        Style::Error => "1;31",
+        Style::Emphasis => "1",
+        Style::Dim => "2",
+    };
+    format!("\x1b[{}m{}\x1b[0m", code, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Scanner;
+
+    #[test]
+    fn test_caret_alignment_with_wide_chars_and_tabs() {
+        // 列号按字符计数：`"中文😀"` 占 4 列，`\t` 占 1 列
+        let line = "\tvar s = \"中文😀\" + x";
+        let x_column = line.chars().position(|c| c == 'x').unwrap() + 1;
+        assert_eq!(x_column, 18);
+
+        let (text, carets) = render_line(line, x_column, 1);
+        assert_eq!(text, "    var s = \"中文😀\" + x");
+        assert_eq!(carets, format!("{}^", " ".repeat(23)));
+
+        // 下划线覆盖宽字符时按显示宽度计算
+        let (_, carets) = render_line(line, 10, 5);
+        assert_eq!(carets, "            ^^^^^^^^");
+    }
+
+    #[test]
+    fn test_scanner_columns_count_characters() {
+        let source = "\tvar s = \"中文😀\" + x";
+        let tokens = Scanner::new(source).scan_tokens();
+        let x = tokens.iter().find(|t| t.lexeme == "x").unwrap();
+        assert_eq!(x.span.column, 18);
+        // span 的 start/end 是字节偏移
+        assert_eq!(&source[x.span.start..x.span.end], "x");
+
+        let snippet = render_snippet(source, &x.span).unwrap();
+        assert_eq!(snippet, format!("1 |     var s = \"中文😀\" + x\n  | {}^", " ".repeat(23)));
+    }
+
+    #[test]
+    fn test_long_line_is_windowed() {
+        let line = format!("{}bad{}", "a + ".repeat(100), " + b".repeat(100));
+        let column = 401;
+        assert_eq!(&line[column - 1..column + 2], "bad");
+
+        let (text, carets) = render_line(&line, column, 3);
+        let expected_text = format!(
+            "…{}bad{}…",
+            &"a + ".repeat(100)[400 - WINDOW_RADIUS..],
+            &" + b".repeat(100)[..WINDOW_RADIUS]
+        );
+        assert_eq!(text, expected_text);
+        assert_eq!(carets, format!("{}^^^", " ".repeat(WINDOW_RADIUS + 1)));
+
+        // 靠近行首时不截断开头
+        let (text, carets) = render_line(&line, 1, 1);
+        assert!(text.starts_with("a + a"));
+        assert!(text.ends_with('…'));
+        assert_eq!(carets, "^");
+    }
+
+    #[test]
+    fn test_snippet_at_end_of_line_and_multiline_span() {
+        let source = "foo(1,\n  2";
+        let eof = Span::new(source.len(), source.len(), 2, 4);
+        assert_eq!(render_snippet(source, &eof).unwrap(), "2 |   2\n  |    ^");
+
+        let multiline = Span::new(0, source.len(), 1, 1);
+        assert_eq!(render_snippet(source, &multiline).unwrap(), "1 | foo(1,\n  | ^^^^^^");
+
+        assert!(render_snippet(source, &Span::new(0, 0, 5, 1)).is_none());
+    }
+
+    #[test]
+    fn test_diagnostics_render_grouped() {
+        let source = "func main() {\n    var x = \n}\n";
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.push(Diagnostic::error(Stage::Syntax, "Expected expression").at(Span::new(26, 27, 3, 1)).in_file(Some("a.q".to_string())));
+        diagnostics.push(Diagnostic::error(Stage::Import, "import cycle: a.q -> b.q -> a.q"));
+        let mut type_error = Diagnostic::error(Stage::Type, "mismatch").at(Span::new(18, 19, 2, 9)).in_file(Some("a.q".to_string()));
+        type_error.notes.push("see here".to_string());
+        diagnostics.push(type_error);
+        diagnostics.push(Diagnostic::error(Stage::Import, "package x is not provided"));
+
+        let mut loads = 0;
+        let rendered = diagnostics.render(Locale::En, &mut |file| {
+            loads += 1;
+            file.map(|_| source.to_string())
+        });
+        assert_eq!(loads, 1);
+        assert_eq!(
+            rendered,
+            "[Syntax Error] a.q\n[3:1] Expected expression\n3 | }\n  | ^\n\
+             [Import Error]\n  import cycle: a.q -> b.q -> a.q\n  package x is not provided\n\
+             [Type Error] a.q\n  [2:9] mismatch\n  2 |     var x = \n    |         ^\n      note: see here"
+        );
+
+        let many: Diagnostics = (0..MAX_REPORTED_ERRORS + 3).map(|i| Diagnostic::warning(Stage::Type, format!("w{}", i))).collect::<Vec<_>>().into();
+        let rendered = many.to_string();
+        assert!(rendered.starts_with("[Warning]\n  w0\n"), "{}", rendered);
+        assert!(rendered.ends_with("  w49\n... and 3 more errors"), "{}", rendered);
+    }
+
+    #[test]
+    fn test_single_line_file_scans_in_linear_time() {
+        use std::time::{Duration, Instant};
+
+        fn scan_time(statements: usize) -> (Duration, usize) {
+            let source = "var x = foo(1, \"s\") + y; ".repeat(statements);
+            let mut best = Duration::MAX;
+            let mut count = 0;
+            for _ in 0..3 {
+                let started = Instant::now();
+                let tokens = Scanner::new(&source).scan_tokens();
+                best = best.min(started.elapsed());
+                count = tokens.len();
+            }
+            (best, count)
+        }
+
+        // 约 0.6MB 与 2.4MB 的单行文件：线性扫描耗时约为 4 倍，二次复杂度会接近 16 倍
+        let (small, small_tokens) = scan_time(25_000);
+        let (large, large_tokens) = scan_time(100_000);
+        assert_eq!(large_tokens - 1, (small_tokens - 1) * 4);
+        assert!(
+            large < small * 10,
+            "scanning 4x input took {:?} vs {:?}",
+            large,
+            small
+        );
+    }
+}
@@ -0,0 +1,410 @@
+//! 编译驱动
+//!
+//! 命令行的 `run`、`build` 与嵌入程序共用的流程：查找项目、加载依赖、类型检查、单态化、生成字节码，
+//! 以及运行编译好的字节码块。错误以结构化的 [`Diagnostics`] 返回，由调用方决定如何显示。
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::compiler::{Chunk, Compiler};
+use crate::config::PROJECT_FILE;
+use crate::diagnostics::{display_path, Diagnostic, Diagnostics, Severity, Stage};
+use crate::i18n::{format_message, messages, Locale};
+use crate::package::{compute_expected_package, explicit_project_file, find_project, load_dependencies, PathCache, ProjectConfig, SourceStore, PROJECT_ENV};
+use crate::parser;
+use crate::timings::{self, Phase};
+use crate::typechecker::{CompileContext, Monomorphizer, TypeChecker};
+use crate::vm::vm::RuntimeError;
+use crate::vm::{self, TraceOptions, Tracer, Value, VM};
+use crate::{ice, parse_source_recovering, stdlib};
+
+/// 一次编译使用的项目：生效的 project.toml（独立文件没有）和本次编译读取的源文件快照
+///
+/// 克隆的句柄共用同一份快照，出错时显示的源码与编译的内容一致，即使文件之后被修改或删除
+#[derive(Debug, Clone)]
+pub struct Project {
+    config: Option<Arc<ProjectConfig>>,
+    sources: Arc<SourceStore>,
+    warnings: Diagnostics,
+}
+
+impl Default for Project {
+    fn default() -> Self {
+        Self::standalone()
+    }
+}
+
+impl Project {
+    /// 不属于任何项目的独立源码
+    pub fn standalone() -> Self {
+        Self { config: None, sources: Arc::new(SourceStore::default()), warnings: Diagnostics::new() }
+    }
+
+    /// 为 `start`（源文件或目录）确定项目
+    ///
+    /// `explicit` 对应命令行的 `--project`（project.toml 或它所在的目录），优先于环境变量 QLANG_PROJECT，
+    /// 都没有时从 `start` 向上查找；找不到时是独立文件。`overrides` 是 `--set` 的覆盖项。
+    /// 查找过程和配置本身的警告见 [`Project::warnings`]。
+    pub fn locate(start: &Path, explicit: Option<&Path>, overrides: &[(String, String)]) -> Result<Self, Diagnostics> {
+        let sources = Arc::new(SourceStore::default());
+        let config_error = |e: String| Diagnostics::from(Diagnostic::error(Stage::Config, e));
+        let abs_start = sources.paths().canonicalize(start);
+        let Some((project_file, warnings)) = locate_project(&abs_start, explicit, sources.paths()).map_err(config_error)? else {
+            return Ok(Self { config: None, sources, warnings: Diagnostics::new() });
+        };
+        let mut config = ProjectConfig::load(&project_file, overrides).map_err(config_error)?;
+        config.warnings.splice(0..0, warnings);
+        let warnings = config.warnings.iter().map(|w| Diagnostic::warning(Stage::Config, w.clone())).collect::<Vec<_>>();
+        Ok(Self { config: Some(Arc::new(config)), sources, warnings: warnings.into() })
+    }
+
+    /// 生效的项目配置，独立文件为 None
+    pub fn config(&self) -> Option<&ProjectConfig> {
+        self.config.as_deref()
+    }
+
+    /// 查找 project.toml 和读取配置时的警告
+    pub fn warnings(&self) -> &Diagnostics {
+        &self.warnings
+    }
+
+    /// 本次编译读取的源文件快照，主文件也应通过它读取
+    pub fn sources(&self) -> &Arc<SourceStore> {
+        &self.sources
+    }
+
+    /// 诊断中的文件（显示用的路径）在快照中的内容，供 [`Diagnostics::render`] 显示源码片段
+    pub fn source(&self, file: &str) -> Option<String> {
+        self.sources.snapshot(Path::new(file)).map(|source| source.to_string())
+    }
+
+    /// 写出本次编译读取的源文件及其内容哈希（与解析缓存的键相同），返回锁文件的路径
+    ///
+    /// 锁文件在项目根目录下，不在项目中时在主文件所在目录下
+    pub fn write_build_lock(&self, main_file: &Path) -> Result<PathBuf, String> {
+        let root = match &self.config {
+            Some(config) => config.root_dir.clone(),
+            None => {
+                let abs_path = self.sources.paths().canonicalize(main_file);
+                abs_path.parent().map(Path::to_path_buf).unwrap_or_default()
+            }
+        };
+        let dir = root.join(parser::cache::CACHE_DIR);
+        let path = dir.join(BUILD_LOCK);
+        fs::create_dir_all(&dir)
+            .and_then(|()| fs::write(&path, self.sources.manifest(&root)))
+            .map_err(|e| format!("cannot write {}: {}", display_path(&path), e))?;
+        Ok(path)
+    }
+}
+
+/// 锁文件名（在 .qcache 目录中）
+const BUILD_LOCK: &str = "build.lock";
+
+/// 确定使用哪个 project.toml，返回它的路径和查找过程中的警告
+fn locate_project(start: &Path, explicit: Option<&Path>, paths: &PathCache) -> Result<Option<(PathBuf, Vec<String>)>, String> {
+    if let Some(path) = explicit {
+        return Ok(Some((explicit_project_file("--project", path, paths)?, Vec::new())));
+    }
+    if let Some(path) = env::var_os(PROJECT_ENV).filter(|path| !path.is_empty()) {
+        return Ok(Some((explicit_project_file(PROJECT_ENV, Path::new(&path), paths)?, Vec::new())));
+    }
+
+    let Some(discovery) = find_project(start, paths) else {
+        return Ok(None);
+    };
+    let project_file = discovery.root.join(PROJECT_FILE);
+    let warnings = discovery
+        .shadowed
+        .iter()
+        .map(|outer| {
+            format!(
+                "using the nearest {}; ignoring {} in an enclosing directory (pass --project to choose)",
+                display_path(&project_file),
+                display_path(&outer.join(PROJECT_FILE)),
+            )
+        })
+        .collect();
+    Ok(Some((project_file, warnings)))
+}
+
+/// 编译选项
+#[derive(Debug, Clone, Default)]
+pub struct CompileOptions {
+    pub locale: Locale,
+    /// 源码所在的文件：决定期望的包名、导入环的起点和诊断中的文件名；没有时作为独立的源码编译
+    pub file: Option<PathBuf>,
+    /// 源码所属的项目，没有时按独立文件处理
+    pub project: Option<Project>,
+    /// 折叠常量表达式、去掉不可达代码（-O）
+    pub optimize: bool,
+    /// 隐式 any 是错误而不是警告，与项目配置的 `[build] strict-types` 取或
+    pub strict_types: bool,
+    /// 把警告当作错误（--deny warnings）
+    pub deny_warnings: bool,
+}
+
+/// 编译结果
+#[derive(Debug)]
+pub struct Compiled {
+    pub chunk: Chunk,
+    /// 类型检查的警告，以及编译期间被修改的源文件
+    pub warnings: Diagnostics,
+}
+
+/// 编译源码及其依赖，忽略警告
+pub fn compile(source: &str, options: &CompileOptions) -> Result<Chunk, Diagnostics> {
+    compile_with_warnings(source, options).map(|compiled| compiled.chunk)
+}
+
+/// 编译源码及其依赖
+///
+/// 主程序有语法错误时仍然加载依赖，一起报告所有文件的错误。
+/// 出错时返回的诊断也包含此前产生的警告（排在错误之前）。
+pub fn compile_with_warnings(source: &str, options: &CompileOptions) -> Result<Compiled, Diagnostics> {
+    let locale = options.locale;
+    let project = options.project.clone().unwrap_or_default();
+    let main_file = options.file.as_deref();
+    let main_name = main_file.map(display_path);
+    let mut context = compile_context(&project, main_file)?;
+
+    // 解析主程序（imports 决定要加载的依赖）
+    ice::set_file(main_name.as_deref().unwrap_or_default());
+    let parse = || parse_source_recovering(source, locale);
+    let (mut program, main_errors) = match &main_name {
+        Some(name) => timings::file(name, parse),
+        None => parse(),
+    };
+    let mut errors: Diagnostics = main_errors.into_iter().map(|e| e.in_file(main_name.clone())).collect::<Vec<_>>().into();
+
+    // 加载所有依赖
+    let started = timings::begin();
+    let dependencies = load_dependencies(&program, main_file.unwrap_or(Path::new("")), project.config.as_ref(), locale, &project.sources);
+    timings::record(Phase::LoadDependencies, started, || {
+        dependencies.as_ref().map_or(0, |dependencies| dependencies.files.len() as u64)
+    });
+    let dependencies = match dependencies {
+        Ok(dependencies) => dependencies,
+        Err(load_errors) => {
+            errors.extend(load_errors);
+            return Err(errors);
+        }
+    };
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    // 之后的阶段处理合并后的程序，内部错误报告主文件
+    ice::set_file(main_name.as_deref().unwrap_or_default());
+
+    // 顶层语句的来源文件（栈追踪显示文件名），依赖的语句放在主程序语句之前
+    let mut source_files = Vec::new();
+    for (path, count) in dependencies.files {
+        source_files.push((display_path(&path), count));
+    }
+    let mut statements = dependencies.statements;
+    statements.append(&mut program.statements);
+    program.statements = statements;
+    if let Some(name) = &main_name {
+        let count = program.statements.len() - source_files.iter().map(|(_, count)| count).sum::<usize>();
+        source_files.push((name.clone(), count));
+    }
+
+    // 合并后的顶层定义不能重名，先于类型检查报告，给出两处定义的文件
+    let compile_errors = |errors: Vec<crate::compiler::codegen::CompileError>| {
+        Diagnostics::from(errors.iter().map(|e| e.to_diagnostic()).collect::<Vec<_>>())
+    };
+    let mut compiler = Compiler::new(locale);
+    compiler.set_optimize(options.optimize);
+    compiler.set_source_files(source_files.clone());
+    ice::enter(ice::Phase::Check);
+    compiler.check_definitions(&program).map_err(compile_errors)?;
+
+    // 类型检查；只有一个文件时，不知道来源的错误（如约束求解的错误）也属于主文件
+    let single_file = source_files.len() <= 1;
+    let type_diagnostics = |errors: &[crate::typechecker::TypeError], severity: Severity| {
+        errors
+            .iter()
+            .map(|e| {
+                let diagnostic = e.to_diagnostic(severity);
+                let file = diagnostic.file.clone().or_else(|| main_name.clone().filter(|_| single_file));
+                diagnostic.in_file(file)
+            })
+            .collect::<Vec<_>>()
+    };
+    context.strict_types |= options.strict_types;
+    let mut type_checker = TypeChecker::with_context(context);
+    type_checker.set_source_files(source_files);
+    type_checker
+        .check_program(&program)
+        .map_err(|errors| Diagnostics::from(type_diagnostics(&errors, Severity::Error)))?;
+
+    // 警告不阻止编译，除非要求把警告当作错误
+    let mut warnings = Diagnostics::new();
+    let type_warnings = type_checker.warnings();
+    if !type_warnings.is_empty() {
+        if options.deny_warnings {
+            return Err(type_diagnostics(type_warnings, Severity::Error).into());
+        }
+        warnings.extend(type_diagnostics(type_warnings, Severity::Warning));
+    }
+    let with_warnings = |errors: Diagnostics| {
+        let mut all = warnings.clone();
+        all.extend(errors);
+        all
+    };
+
+    // 单态化：泛型函数调用改为调用按类型实参生成的实例
+    ice::enter(ice::Phase::Compile);
+    let mut type_table = type_checker.take_type_table();
+    let mut monomorphizer = Monomorphizer::new();
+    monomorphizer.collect_definitions(&program);
+    monomorphizer.specialize(&mut program, &mut type_table);
+    if !monomorphizer.errors().is_empty() {
+        return Err(with_warnings(type_diagnostics(monomorphizer.errors(), Severity::Error).into()));
+    }
+
+    // 编译（使用类型检查得到的表达式类型选择快速路径）
+    compiler.set_type_table(type_table);
+    let chunk = compiler.compile(&program).map_err(|errors| with_warnings(compile_errors(errors)))?;
+
+    // 编译期间被修改的源文件：编译用的是快照，结果可能与磁盘上的内容不一致
+    let changed = project.sources.changed_files();
+    if !changed.is_empty() {
+        let files: Vec<String> = changed.iter().map(|path| display_path(path)).collect();
+        let message = format_message(messages::MSG_CLI_SOURCES_CHANGED, locale, &[&files.join(", ")]);
+        warnings.push(Diagnostic::warning(Stage::Import, message));
+    }
+
+    Ok(Compiled { chunk, warnings })
+}
+
+/// 主文件的编译上下文；文件不在项目的源码目录下时无法确定包名，是配置错误
+fn compile_context(project: &Project, main_file: Option<&Path>) -> Result<CompileContext, Diagnostics> {
+    let Some(config) = project.config() else {
+        return Ok(CompileContext {
+            is_entry_file: true,
+            expected_package: None,
+            standalone_mode: true,
+            strict_types: false,
+        });
+    };
+    let expected_package = match main_file {
+        Some(file) => {
+            let abs_path = project.sources.paths().canonicalize(file);
+            let expected = compute_expected_package(config, &abs_path).ok_or_else(|| {
+                Diagnostic::error(Stage::Config, format!(
+                    "{} is not under {}, the source directory of {}",
+                    display_path(&abs_path),
+                    display_path(&config.root_dir.join(&config.src_dir)),
+                    display_path(&config.root_dir.join(PROJECT_FILE)),
+                ))
+            })?;
+            Some(expected)
+        }
+        None => None,
+    };
+    Ok(CompileContext {
+        is_entry_file: true,
+        expected_package,
+        standalone_mode: false,
+        strict_types: config.strict_types,
+    })
+}
+
+/// 运行选项
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    pub locale: Locale,
+    /// 指令级执行追踪（--trace）
+    pub trace: Option<TraceOptions>,
+    /// 传给程序的参数（Os.args）
+    pub args: Vec<String>,
+}
+
+/// 运行字节码块，返回 `main` 的返回值（没有 `main` 或不返回值时为 null）
+///
+/// 程序调用 `Os.exit(code)` 时返回 `exit_code` 为 `Some(code)` 的错误。
+/// 使用全局的标准库注册表；需要宿主函数时用 [`Engine`](crate::Engine)
+pub fn run_chunk(chunk: impl Into<Arc<Chunk>>, options: &RunOptions) -> Result<Value, RuntimeError> {
+    ice::enter(ice::Phase::Run);
+    stdlib::os::set_args(options.args.clone());
+    vm::value::enable_gc();
+    let mut vm = VM::new(chunk.into(), options.locale);
+    if let Some(trace) = &options.trace {
+        vm.set_tracer(Tracer::stderr(trace.clone()));
+    }
+    vm.run()?;
+    Ok(vm.result().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_project(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = env::temp_dir().join(format!("qlang_driver_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for (path, content) in files {
+            fs::create_dir_all(root.join(path).parent().unwrap()).unwrap();
+            fs::write(root.join(path), content).unwrap();
+        }
+        fs::canonicalize(&root).unwrap()
+    }
+
+    fn compile_file(file: &Path) -> (Project, Result<Compiled, Diagnostics>) {
+        let project = Project::locate(file, None, &[]).unwrap();
+        let source = project.sources().read(file).unwrap();
+        let options = CompileOptions { file: Some(file.to_path_buf()), project: Some(project.clone()), ..CompileOptions::default() };
+        (project.clone(), compile_with_warnings(&source, &options))
+    }
+
+    #[test]
+    fn test_project_diagnostics_are_structured() {
+        let root = temp_project("project", &[
+            ("project.toml", "[project]\nname = \"app\"\npackage = \"com.app\"\n"),
+            ("src/main.q", "package com.app\n\nimport com.app.lib.*\n\nclass Box {\n    var v = 1\n}\n\nfunc main() int {\n    println(new Box().v)\n    return helper() + 1\n}\n"),
+            ("src/lib/A.q", "package com.app.lib\n\nfunc helper() int {\n    var s: string = 3\n    return 41\n}\n"),
+        ]);
+        let main = root.join("src/main.q");
+        let (project, result) = compile_file(&main);
+        let errors = result.unwrap_err();
+        let lib = display_path(&root.join("src/lib/A.q"));
+        let error = errors.iter().find(|e| e.severity == Severity::Error).unwrap();
+        assert_eq!((error.stage, error.file.as_deref()), (Stage::Type, Some(lib.as_str())));
+        assert_eq!(error.span.map(|span| (span.line, span.column)), Some((4, 5)));
+
+        // 渲染时从快照取源码片段
+        let rendered = errors.render(Locale::En, &mut |file| file.and_then(|file| project.source(file)));
+        assert!(rendered.contains(&format!("[Type Error] {}\n  [4:5] ", lib)), "{}", rendered);
+        assert!(rendered.contains("4 |     var s: string = 3"), "{}", rendered);
+
+        fs::write(root.join("src/lib/A.q"), "package com.app.lib\n\nfunc helper() int {\n    return 41\n}\n").unwrap();
+        let (_, result) = compile_file(&main);
+        let compiled = result.unwrap();
+        let warning = compiled.warnings.iter().next().unwrap();
+        assert_eq!((warning.severity, warning.code), (Severity::Warning, Some("W0001")));
+        assert_eq!(warning.file, Some(display_path(&main)));
+        let value = run_chunk(compiled.chunk, &RunOptions::default()).unwrap();
+        assert_eq!(value.as_int(), Some(42));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_compile_source_without_file() {
+        let errors = compile("func main() {\n    var x = (1\n}\n", &CompileOptions::default()).unwrap_err();
+        let error = errors.iter().next().unwrap();
+        assert_eq!((error.stage, error.file.as_deref()), (Stage::Syntax, None));
+        assert_eq!(error.span.map(|span| span.line), Some(2));
+
+        let chunk = compile("func main() int {\n    return 6 * 7\n}\n", &CompileOptions::default()).unwrap();
+        assert_eq!(run_chunk(chunk, &RunOptions::default()).unwrap().as_int(), Some(42));
+
+        // 运行时错误保留结构
+        let chunk = compile("func main() {\n    panic(\"boom\")\n}\n", &CompileOptions::default()).unwrap();
+        let error = run_chunk(chunk, &RunOptions::default()).unwrap_err();
+        assert_eq!((error.message.as_str(), error.line), ("Panic: boom", 2));
+    }
+}
@@ -0,0 +1,330 @@
+//! 嵌入接口
+//!
+//! 宿主程序通过 [`Engine`] 注册宿主函数和原生模块、编译并运行 Q 源码。
+//! 编译出的字节码块记录用到的宿主函数名，运行前与引擎注册的函数按名字链接，
+//! 因此在一个引擎中编译的字节码块可以交给注册了相同函数的另一个引擎运行。
+//! 引擎的注册表从内置模块开始，注册时复制一份，不影响命令行程序使用的全局注册表。
+
+use std::sync::Arc;
+
+use crate::compiler::{Chunk, Compiler};
+use crate::diagnostics::use_color;
+use crate::i18n::{format_message, messages, Locale};
+use crate::lexer::Scanner;
+use crate::parser::Parser;
+use crate::stdlib::{global_registry, StdlibModule, StdlibRegistry};
+use crate::typechecker::{CompileContext, Monomorphizer, TypeChecker};
+use crate::types::Type;
+use crate::vm::{render_error, TraceFormat, Value, VM};
+
+/// 嵌入 Q 的引擎
+#[derive(Debug, Clone)]
+pub struct Engine {
+    locale: Locale,
+    registry: Arc<StdlibRegistry>,
+}
+
+impl Engine {
+    pub fn new(locale: Locale) -> Self {
+        Self { locale, registry: global_registry().clone() }
+    }
+
+    /// 使用宿主程序准备好的注册表
+    pub fn with_registry(locale: Locale, registry: StdlibRegistry) -> Self {
+        Self { locale, registry: Arc::new(registry) }
+    }
+
+    /// 引擎使用的注册表
+    pub fn registry(&self) -> &Arc<StdlibRegistry> {
+        &self.registry
+    }
+
+    /// 注册原生模块：脚本 import 它之后可以使用它声明的类（见 [`StdlibModule::type_declarations`]）
+    pub fn register_module(&mut self, module: Box<dyn StdlibModule>) {
+        Arc::make_mut(&mut self.registry).register(module);
+    }
+
+    /// 注册宿主函数，脚本中以 `host.名称(...)` 调用；参数和返回值不做类型检查
+    pub fn register_fn<F>(&mut self, name: &str, func: F) -> Result<(), String>
+    where
+        F: Fn(&[Value]) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        Arc::make_mut(&mut self.registry).register_fn(name, func)
+    }
+
+    /// 注册带类型的宿主函数，signature 是 Q 的函数类型（如 `func(string) int`），类型检查器据此检查调用
+    pub fn register_fn_with_signature<F>(&mut self, name: &str, signature: &str, func: F) -> Result<(), String>
+    where
+        F: Fn(&[Value]) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        let signature = parse_signature(signature, self.locale)
+            .map_err(|e| format!("invalid signature for host function '{}': {}", name, e))?;
+        Arc::make_mut(&mut self.registry).register_typed_fn(name, signature, func)
+    }
+
+    /// 编译单个源文件（需要 `func main()`），只能调用已注册的宿主函数
+    pub fn compile(&self, source: &str) -> Result<Chunk, String> {
+        let mut program = crate::parse_source(source, self.locale).map_err(|e| {
+            let label = format_message(messages::MSG_CLI_SYNTAX_ERROR, self.locale, &[]);
+            format!("{}\n{}", label, e)
+        })?;
+
+        let mut type_checker = TypeChecker::with_context(CompileContext {
+            is_entry_file: true,
+            expected_package: None,
+            standalone_mode: true,
+            ..CompileContext::default()
+        });
+        type_checker.set_registry(self.registry.clone());
+        type_checker.set_host_functions(self.registry.host_functions().signatures());
+        type_checker.check_program(&program).map_err(|errors| {
+            let label = format_message(messages::MSG_CLI_TYPE_ERROR, self.locale, &[]);
+            let error_list = errors
+                .iter()
+                .map(|e| format!("  {}", e.render(Some(source)).replace('\n', "\n  ")))
+                .collect::<Vec<_>>()
+                .join("\n");
+            format!("{}\n{}", label, error_list)
+        })?;
+
+        let mut type_table = type_checker.take_type_table();
+        let mut monomorphizer = Monomorphizer::new();
+        monomorphizer.collect_definitions(&program);
+        monomorphizer.specialize(&mut program, &mut type_table);
+
+        let mut compiler = Compiler::new(self.locale);
+        compiler.set_registry(self.registry.clone());
+        compiler.set_host_functions(self.registry.host_functions().signatures().into_keys());
+        compiler.set_type_table(type_table);
+        compiler.compile(&program).map_err(|errors| {
+            let label = format_message(messages::MSG_CLI_COMPILE_ERROR, self.locale, &[]);
+            let error_list = errors
+                .iter()
+                .map(|e| format!("  [{}:{}] {}", e.span.line, e.span.column, e.message))
+                .collect::<Vec<_>>()
+                .join("\n");
+            format!("{}\n{}", label, error_list)
+        })
+    }
+
+    /// 运行字节码块，返回 `main` 的返回值；字节码块用到的宿主函数必须都已在本引擎注册
+    pub fn run(&self, chunk: Arc<Chunk>) -> Result<Value, String> {
+        self.registry.host_functions().link(&chunk)?;
+        let mut vm = VM::with_registry(chunk, self.locale, self.registry.clone());
+        vm.run()
+            .map_err(|e| render_error(&e, TraceFormat::Compact, use_color(false), &mut |_: Option<&str>| None))?;
+        Ok(vm.result().unwrap_or_default())
+    }
+}
+
+/// 解析宿主函数的签名
+fn parse_signature(signature: &str, locale: Locale) -> Result<Type, String> {
+    let mut scanner = Scanner::new(signature);
+    let tokens = scanner.scan_tokens();
+    if let Some(token) = tokens.iter().find(|token| token.is_error()) {
+        return Err(format!("unexpected '{}'", token.lexeme));
+    }
+    Parser::new(tokens, locale).parse_type_only().map_err(|e| e.message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use parking_lot::Mutex;
+    use crate::stdlib::ClassDecl;
+    use crate::stdlib::declarations::param;
+    use crate::vm::value::ClassInstance;
+
+    fn engine_with_add() -> Engine {
+        let mut engine = Engine::new(Locale::En);
+        engine
+            .register_fn_with_signature("host.add", "func(int, int) int", |args| {
+                let a = args[0].as_int().ok_or("expected int")?;
+                let b = args[1].as_int().ok_or("expected int")?;
+                Ok(Value::int(a + b))
+            })
+            .unwrap();
+        engine
+    }
+
+    #[test]
+    fn test_call_host_functions() {
+        let mut engine = engine_with_add();
+        engine
+            .register_fn("host.greet", |args| {
+                let name = args[0].as_string().ok_or("expected string")?;
+                Ok(Value::string(format!("hello, {}", name)))
+            })
+            .unwrap();
+        engine.register_fn("host.fail", |_| Err("config file is missing".to_string())).unwrap();
+
+        let chunk = engine
+            .compile("func main() int {\n    var s: string = host.greet(\"q\")\n    if s == \"hello, q\" {\n        return host.add(40, 2)\n    }\n    return 0\n}\n")
+            .unwrap();
+        assert_eq!(chunk.host_functions, vec!["host.greet", "host.add"]);
+        assert_eq!(engine.run(Arc::new(chunk)).unwrap().as_int(), Some(42));
+
+        // 宿主函数返回的错误成为运行时错误
+        let chunk = engine.compile("func main() {\n    host.fail()\n}\n").unwrap();
+        let error = engine.run(Arc::new(chunk)).unwrap_err();
+        assert!(error.contains("config file is missing"), "{}", error);
+    }
+
+    #[test]
+    fn test_unknown_host_function() {
+        let engine = engine_with_add();
+        let error = engine.compile("func main() {\n    host.sub(1, 2)\n}\n").unwrap_err();
+        assert!(error.contains("unknown host function 'host.sub'"), "{}", error);
+
+        // 同名的局部变量优先于宿主函数命名空间
+        let chunk = engine
+            .compile("class Host {\n    func sub(a: int, b: int) int { return a - b }\n}\nfunc main() int {\n    var host = new Host()\n    return host.sub(5, 2)\n}\n")
+            .unwrap();
+        assert!(chunk.host_functions.is_empty());
+        assert_eq!(engine.run(Arc::new(chunk)).unwrap().as_int(), Some(3));
+    }
+
+    #[test]
+    fn test_signature_checks_calls() {
+        let engine = engine_with_add();
+        let error = engine.compile("func main() {\n    host.add(1)\n}\n").unwrap_err();
+        assert!(error.contains("[2:"), "{}", error);
+        let error = engine.compile("func main() {\n    host.add(1, \"2\")\n}\n").unwrap_err();
+        assert!(error.contains("[2:"), "{}", error);
+
+        let mut engine = Engine::new(Locale::En);
+        let error = engine.register_fn_with_signature("host.f", "int", |_| Ok(Value::null())).unwrap_err();
+        assert!(error.contains("must be a function type"), "{}", error);
+        let error = engine.register_fn_with_signature("host.f", "func(int) int x", |_| Ok(Value::null())).unwrap_err();
+        assert!(error.contains("invalid signature"), "{}", error);
+        assert!(engine.register_fn("readConfig", |_| Ok(Value::null())).is_err());
+        engine.register_fn("host.f", |_| Ok(Value::null())).unwrap();
+        assert!(engine.register_fn("host.f", |_| Ok(Value::null())).is_err());
+    }
+
+    #[test]
+    fn test_typed_host_results_are_checked() {
+        let mut engine = Engine::new(Locale::En);
+        engine
+            .register_fn_with_signature("host.count", "func() int", |_| Ok(Value::string("three".to_string())))
+            .unwrap();
+        engine
+            .register_fn_with_signature("host.lookup", "func() int?", |_| Ok(Value::null()))
+            .unwrap();
+
+        // 返回值与签名不符时抛出可捕获的异常，而不是进入整数特化指令
+        let chunk = engine
+            .compile("import std.lang.Exception\nfunc main() int {\n    try {\n        return host.count() + 1\n    } catch (e: Exception) {\n        return -1\n    }\n}\n")
+            .unwrap();
+        assert_eq!(engine.run(Arc::new(chunk)).unwrap().as_int(), Some(-1));
+        let chunk = engine.compile("func main() int {\n    return host.count() + 1\n}\n").unwrap();
+        let error = engine.run(Arc::new(chunk)).unwrap_err();
+        assert!(error.contains("host function 'host.count' returned string, expected int"), "{}", error);
+
+        let chunk = engine.compile("func main() int {\n    return host.lookup() ?? 7\n}\n").unwrap();
+        assert_eq!(engine.run(Arc::new(chunk)).unwrap().as_int(), Some(7));
+    }
+
+    /// 宿主程序的原生模块：`Counter` 类的实例记着一个整数
+    struct CounterLib;
+
+    impl StdlibModule for CounterLib {
+        fn name(&self) -> &'static str {
+            "app.counter"
+        }
+
+        fn exports(&self) -> Vec<&'static str> {
+            vec![]
+        }
+
+        fn call(&self, name: &str, _args: &[Value]) -> Result<Value, String> {
+            Err(format!("Unknown function: {}", name))
+        }
+
+        fn has_class(&self, class_name: &str) -> bool {
+            class_name == "app.counter.Counter"
+        }
+
+        fn create_class_instance(&self, class_name: &str, args: &[Value]) -> Result<Value, String> {
+            let fields = HashMap::from([("count".to_string(), args[0])]);
+            Ok(Value::class(Arc::new(Mutex::new(ClassInstance { class_name: class_name.to_string(), parent_class: None, fields }))))
+        }
+
+        fn call_method(&self, instance: &Value, method_name: &str, args: &[Value]) -> Result<Value, String> {
+            let instance = instance.as_class().ok_or("expected Counter")?;
+            let mut instance = instance.lock();
+            match method_name {
+                "add" => {
+                    let count = instance.fields["count"].as_int().unwrap_or(0) + args[0].as_int().ok_or("expected int")?;
+                    instance.fields.insert("count".to_string(), Value::int(count));
+                    Ok(Value::int(count))
+                }
+                _ => Err(format!("Method '{}' not found", method_name)),
+            }
+        }
+
+        fn type_declarations(&self) -> Vec<ClassDecl> {
+            vec![ClassDecl::new("Counter").constructor(vec![param("start", Type::Int)]).method("add", vec![param("n", Type::Int)], Type::Int)]
+        }
+    }
+
+    const COUNTER_PROGRAM: &str = "import app.counter.*\n\nfunc main() int {\n    var counter = new Counter(40)\n    counter.add(1)\n    return counter.add(1)\n}\n";
+
+    #[test]
+    fn test_register_module() {
+        let mut engine = Engine::new(Locale::En);
+        engine.register_module(Box::new(CounterLib));
+        let chunk = engine.compile(COUNTER_PROGRAM).unwrap();
+        assert_eq!(engine.run(Arc::new(chunk)).unwrap().as_int(), Some(42));
+
+        // 声明的签名参与类型检查
+        let error = engine.compile("import app.counter.*\n\nfunc main() {\n    new Counter(1).add(\"x\")\n}\n").unwrap_err();
+        assert!(error.contains("[4:"), "{}", error);
+
+        // 其他引擎和全局注册表没有这个模块
+        assert!(Engine::new(Locale::En).compile(COUNTER_PROGRAM).is_err());
+        assert!(!global_registry().has_module("app.counter"));
+    }
+
+    #[test]
+    fn test_vm_with_registry() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut registry = StdlibRegistry::new();
+        registry.register(Box::new(CounterLib));
+        let lines = log.clone();
+        registry
+            .register_fn("host.log", move |args| {
+                lines.lock().push(args[0].to_string());
+                Ok(Value::null())
+            })
+            .unwrap();
+        let chunk = Arc::new(
+            Engine::with_registry(Locale::En, registry.clone())
+                .compile("import app.counter.*\n\nfunc main() {\n    host.log(\"count\")\n    host.log(new Counter(6).add(1))\n}\n")
+                .unwrap(),
+        );
+
+        let mut vm = VM::with_registry(chunk.clone(), Locale::En, Arc::new(registry));
+        vm.run().unwrap();
+        assert_eq!(*log.lock(), ["count", "7"]);
+
+        // 注册表中没有的宿主函数在调用时报错
+        let mut vm = VM::with_registry(chunk, Locale::En, Arc::new(StdlibRegistry::new()));
+        let error = vm.run().unwrap_err();
+        assert!(error.to_string().contains("host function 'host.log' is not registered"), "{}", error);
+    }
+
+    #[test]
+    fn test_link_against_other_engine() {
+        let chunk = Arc::new(engine_with_add().compile("func main() int {\n    return host.add(1, 2)\n}\n").unwrap());
+
+        let error = Engine::new(Locale::En).run(chunk.clone()).unwrap_err();
+        assert_eq!(error, "program uses host functions that are not registered: host.add");
+
+        // 另一个引擎注册了同名函数即可运行，实现可以不同
+        let mut other = Engine::new(Locale::En);
+        other.register_fn("host.add", |args| Ok(Value::int(args[0].as_int().unwrap() * 10))).unwrap();
+        assert_eq!(other.run(chunk).unwrap().as_int(), Some(10));
+    }
+}
@@ -78,11 +78,13 @@ pub fn get(key: &str) -> &'static str {
         MSG_CLI_FILE_NOT_FOUND => "File not found: {}",
         MSG_CLI_INVALID_EXTENSION => "Invalid file extension: '{}'. Expected '.{}' file",
         MSG_CLI_CANNOT_READ_FILE => "Cannot read file {}: {}",
-        MSG_CLI_PARSE_FAILED => "Failed to parse {}:\n{}",
         MSG_CLI_SYNTAX_ERROR => "[Syntax Error]",
         MSG_CLI_IMPORT_ERROR => "[Import Error]",
+        MSG_CLI_CONFIG_ERROR => "[Config Error]",
         MSG_CLI_TYPE_ERROR => "[Type Error]",
         MSG_CLI_COMPILE_ERROR => "[Compile Error]",
+        MSG_CLI_WARNING => "[Warning]",
+        MSG_CLI_SOURCES_CHANGED => "Sources changed during compilation; results may be stale — rerun: {}",
         MSG_CLI_RUNTIME_ERROR => "[Runtime Error]",
         MSG_CLI_HELP => "Q Language - A modern, production-ready programming language",
         MSG_CLI_COMMANDS => "Commands:\n  run <file>     Run a Q source file\n  build <file>   Compile a Q source file\n  repl           Start interactive REPL\n  help           Show this help message",
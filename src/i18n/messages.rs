@@ -75,11 +75,13 @@ pub const MSG_CLI_ERROR: &str = "MSG_CLI_ERROR";
 pub const MSG_CLI_FILE_NOT_FOUND: &str = "MSG_CLI_FILE_NOT_FOUND";
 pub const MSG_CLI_INVALID_EXTENSION: &str = "MSG_CLI_INVALID_EXTENSION";
 pub const MSG_CLI_CANNOT_READ_FILE: &str = "MSG_CLI_CANNOT_READ_FILE";
-pub const MSG_CLI_PARSE_FAILED: &str = "MSG_CLI_PARSE_FAILED";
 pub const MSG_CLI_SYNTAX_ERROR: &str = "MSG_CLI_SYNTAX_ERROR";
 pub const MSG_CLI_IMPORT_ERROR: &str = "MSG_CLI_IMPORT_ERROR";
+pub const MSG_CLI_CONFIG_ERROR: &str = "MSG_CLI_CONFIG_ERROR";
 pub const MSG_CLI_TYPE_ERROR: &str = "MSG_CLI_TYPE_ERROR";
 pub const MSG_CLI_COMPILE_ERROR: &str = "MSG_CLI_COMPILE_ERROR";
+pub const MSG_CLI_WARNING: &str = "MSG_CLI_WARNING";
+pub const MSG_CLI_SOURCES_CHANGED: &str = "MSG_CLI_SOURCES_CHANGED";
 pub const MSG_CLI_RUNTIME_ERROR: &str = "MSG_CLI_RUNTIME_ERROR";
 pub const MSG_CLI_HELP: &str = "MSG_CLI_HELP";
 pub const MSG_CLI_COMMANDS: &str = "MSG_CLI_COMMANDS";
@@ -21,6 +21,7 @@ pub enum Locale {
 
 impl Locale {
     /// 从字符串解析语言
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Self> {
         match s.to_lowercase().as_str() {
             "en" | "english" => Some(Locale::En),
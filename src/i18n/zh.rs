@@ -78,11 +78,13 @@ pub fn get(key: &str) -> &'static str {
         MSG_CLI_FILE_NOT_FOUND => "文件未找到: {}",
         MSG_CLI_INVALID_EXTENSION => "无效的文件扩展名: '{}'。请使用 '.{}' 文件",
         MSG_CLI_CANNOT_READ_FILE => "无法读取文件 {}: {}",
-        MSG_CLI_PARSE_FAILED => "解析 {} 失败:\n{}",
         MSG_CLI_SYNTAX_ERROR => "[语法错误]",
         MSG_CLI_IMPORT_ERROR => "[导入错误]",
+        MSG_CLI_CONFIG_ERROR => "[配置错误]",
         MSG_CLI_TYPE_ERROR => "[类型检查错误]",
         MSG_CLI_COMPILE_ERROR => "[编译错误]",
+        MSG_CLI_WARNING => "[警告]",
+        MSG_CLI_SOURCES_CHANGED => "编译期间源文件被修改，结果可能已过时，请重新运行：{}",
         MSG_CLI_RUNTIME_ERROR => "[运行时错误]",
         MSG_CLI_HELP => "Q 语言 - 一个现代化、生产级的编程语言",
         MSG_CLI_COMMANDS => "命令:\n  run <文件>     运行 Q 源文件\n  build <文件>   编译 Q 源文件\n  repl           启动交互式 REPL\n  help           显示此帮助信息",
@@ -0,0 +1,167 @@
+//! 内部错误（ICE）报告
+//!
+//! 编译器或虚拟机自身的缺陷会以 Rust panic 的形式出现。命令行和 REPL 在入口处用 [`catch`] 捕获 panic，
+//! 由 [`install_hook`] 安装的钩子输出一份面向用户的报告：panic 消息、正在处理的 Q 源文件、所处阶段、
+//! 编译器版本和提交，以及提交问题的方式，而不是只有 Rust 内部的文件路径。
+//!
+//! 当前的文件和阶段保存在线程局部变量中，由流水线在各阶段入口处设置；钩子运行在发生 panic 的线程上，
+//! 并行解析的线程报告的是它正在解析的文件。
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::panic::{self, AssertUnwindSafe, PanicHookInfo};
+
+use crate::config::{ISSUE_TRACKER, LANG_NAME, VERSION};
+
+/// 内部错误时进程的退出码（sysexits 的 EX_SOFTWARE）
+pub const EXIT_CODE: i32 = 70;
+
+/// 构建时的 git 提交（见 build.rs），不在 git 仓库中构建时为 unknown
+pub const COMMIT: &str = env!("QLANG_COMMIT");
+
+/// 流水线的阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Lex,
+    Parse,
+    Check,
+    Compile,
+    Run,
+}
+
+impl Phase {
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Lex => "lex",
+            Phase::Parse => "parse",
+            Phase::Check => "check",
+            Phase::Compile => "compile",
+            Phase::Run => "run",
+        }
+    }
+}
+
+/// 当前线程正在处理的内容
+#[derive(Debug, Clone, Default)]
+struct Context {
+    file: Option<String>,
+    phase: Option<Phase>,
+}
+
+thread_local! {
+    static CONTEXT: RefCell<Context> = RefCell::new(Context::default());
+}
+
+/// 设置当前线程正在处理的源文件
+pub fn set_file(file: &str) {
+    CONTEXT.with(|context| context.borrow_mut().file = Some(file.to_string()));
+}
+
+/// 进入一个阶段
+///
+/// 调试构建中环境变量 `QLANG_INJECT_ICE` 等于阶段名（如 `check`）时在这里故意 panic，用于测试报告和恢复
+pub fn enter(phase: Phase) {
+    CONTEXT.with(|context| context.borrow_mut().phase = Some(phase));
+    #[cfg(debug_assertions)]
+    if injected(phase) {
+        panic!("injected internal error in the {} phase", phase.name());
+    }
+}
+
+#[cfg(debug_assertions)]
+fn injected(phase: Phase) -> bool {
+    #[cfg(test)]
+    if INJECTED.with(|injected| injected.get()) == Some(phase) {
+        return true;
+    }
+    std::env::var("QLANG_INJECT_ICE").is_ok_and(|target| target == phase.name())
+}
+
+#[cfg(test)]
+thread_local! {
+    static INJECTED: std::cell::Cell<Option<Phase>> = const { std::cell::Cell::new(None) };
+}
+
+/// 单元测试中在当前线程注入内部错误（环境变量是进程级的，会影响并行运行的其他测试）
+#[cfg(test)]
+pub fn inject(phase: Option<Phase>) {
+    INJECTED.with(|injected| injected.set(phase));
+}
+
+/// 安装 panic 钩子，把 panic 报告为内部错误（替换 Rust 默认的输出）
+///
+/// 钩子在展开之前运行，release 构建即使改为 `panic = "abort"` 也能输出报告
+pub fn install_hook() {
+    panic::set_hook(Box::new(|info: &PanicHookInfo<'_>| {
+        let location = info.location().map(|location| location.to_string());
+        let context = CONTEXT.with(|context| context.borrow().clone());
+        eprint!("{}", render_report(&panic_message(info.payload()), location.as_deref(), &context));
+    }));
+}
+
+/// 执行 f，发生 panic 时返回 Err（报告已由钩子输出）
+///
+/// 调用方不能继续使用 f 可能修改了一半的状态：命令行随即退出，REPL 重建整个会话，所以这里不要求 f 是 UnwindSafe
+#[allow(clippy::result_unit_err)]
+pub fn catch<T>(f: impl FnOnce() -> T) -> Result<T, ()> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|_| ())
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "(no message)".to_string()
+    }
+}
+
+fn render_report(message: &str, location: Option<&str>, context: &Context) -> String {
+    let kind = if context.phase == Some(Phase::Run) { "VM" } else { "compiler" };
+    let mut out = format!("error: internal {} error: {}\n", kind, message);
+    let _ = writeln!(out, "  file:     {}", context.file.as_deref().unwrap_or("(none)"));
+    let _ = writeln!(out, "  phase:    {}", context.phase.map_or("(unknown)", Phase::name));
+    if let Some(location) = location {
+        let _ = writeln!(out, "  location: {}", location);
+    }
+    let _ = writeln!(out, "  version:  {} {} (commit {})", LANG_NAME, VERSION, COMMIT);
+    let _ = writeln!(out);
+    let _ = writeln!(out, "This is a bug in the {} implementation, not in your program.", LANG_NAME);
+    let _ = writeln!(
+        out,
+        "Please report it at {} with this message, the source file above and the command that reproduces it.",
+        ISSUE_TRACKER
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let context = Context { file: Some("src/main.q".to_string()), phase: Some(Phase::Check) };
+        let report = render_report("index out of bounds", Some("src/typechecker/checker.rs:10:5"), &context);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "error: internal compiler error: index out of bounds");
+        assert_eq!(lines[1], "  file:     src/main.q");
+        assert_eq!(lines[2], "  phase:    check");
+        assert_eq!(lines[3], "  location: src/typechecker/checker.rs:10:5");
+        assert_eq!(lines[4], format!("  version:  {} {} (commit {})", LANG_NAME, VERSION, COMMIT));
+        assert!(lines[7].contains(ISSUE_TRACKER), "{}", report);
+
+        let report = render_report("boom", None, &Context { file: None, phase: Some(Phase::Run) });
+        assert!(report.starts_with("error: internal VM error: boom\n  file:     (none)\n  phase:    run\n  version:"), "{}", report);
+    }
+
+    #[test]
+    fn test_catch_returns_err_on_panic() {
+        assert_eq!(catch(|| 1), Ok(1));
+        let payload = panic::catch_unwind(|| panic!("formatted {}", 42)).unwrap_err();
+        assert_eq!(panic_message(payload.as_ref()), "formatted 42");
+        assert_eq!(catch(|| -> i32 { panic!("boom") }), Err(()));
+    }
+}
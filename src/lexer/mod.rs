@@ -5,5 +5,5 @@
 pub mod token;
 pub mod scanner;
 
-pub use token::{Token, TokenKind, Span};
+pub use token::{Token, TokenKind, Span, StringPart};
 pub use scanner::Scanner;
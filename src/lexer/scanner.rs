@@ -2,39 +2,49 @@
 //! 
 //! 将源代码字符串转换为 Token 流
 
-use super::token::{Token, TokenKind, Span};
+use std::borrow::Cow;
+
+use super::token::{Token, TokenKind, Span, StringPart};
 
 /// 词法扫描器
-pub struct Scanner {
-    /// 源代码字符
-    source: Vec<char>,
-    /// 当前位置
+/// 
+/// 扫描对源码做一次线性遍历：直接在源码字符串上按字节偏移前进，不复制源码，
+/// 不会从行首重新计算位置，因此超长的单行文件同样是 O(n)。
+/// 产生的 token 借用源码（见 [`Token`]），因此源码必须比 token 活得久。
+/// 列号按字符计数（制表符计为 1 列），见 [`Span`]。
+pub struct Scanner<'a> {
+    /// 源代码
+    source: &'a str,
+    /// 当前位置（字节偏移）
     current: usize,
-    /// 当前 token 起始位置
+    /// 当前 token 起始位置（字节偏移）
     start: usize,
     /// 当前行号
     line: usize,
     /// 当前列号
     column: usize,
+    /// token 起始行号
+    start_line: usize,
     /// token 起始列号
     start_column: usize,
 }
 
-impl Scanner {
+impl<'a> Scanner<'a> {
     /// 创建新的扫描器
-    pub fn new(source: &str) -> Self {
+    pub fn new(source: &'a str) -> Self {
         Self {
-            source: source.chars().collect(),
+            source,
             current: 0,
             start: 0,
             line: 1,
             column: 1,
+            start_line: 1,
             start_column: 1,
         }
     }
 
     /// 扫描所有 token
-    pub fn scan_tokens(&mut self) -> Vec<Token> {
+    pub fn scan_tokens(&mut self) -> Vec<Token<'a>> {
         let mut tokens = Vec::new();
         
         loop {
@@ -50,10 +60,11 @@ impl Scanner {
     }
 
     /// 扫描单个 token
-    pub fn scan_token(&mut self) -> Token {
+    pub fn scan_token(&mut self) -> Token<'a> {
         self.skip_whitespace();
         
         self.start = self.current;
+        self.start_line = self.line;
         self.start_column = self.column;
         
         if self.is_at_end() {
@@ -81,6 +92,7 @@ impl Scanner {
             ',' => self.make_token(TokenKind::Comma),
             ';' => self.make_token(TokenKind::Semicolon),
             '~' => self.make_token(TokenKind::Tilde),
+            '@' => self.make_token(TokenKind::At),
             
             // . 和 .. 和 ..= 和 ...
             '.' => {
@@ -318,8 +330,8 @@ impl Scanner {
         }
     }
 
-    /// 扫描字符串（双引号，支持转义）
-    fn scan_string(&mut self) -> Token {
+    /// 扫描字符串（双引号，支持转义和 `${...}` 插值）
+    fn scan_string(&mut self) -> Token<'a> {
         // 检查是否是三引号（多行字符串）
         if self.peek() == '"' && self.peek_next() == Some('"') {
             self.advance(); // 消费第二个 "
@@ -327,6 +339,20 @@ impl Scanner {
             return self.scan_multiline_string();
         }
         
+        // 没有转义、插值和换行的字符串直接借用源码
+        let rest = &self.source[self.current..];
+        if let Some(end) = rest.find(['"', '\\', '$', '\n']) {
+            if rest.as_bytes()[end] == b'"' {
+                let value = &rest[..end];
+                for _ in value.chars() {
+                    self.advance();
+                }
+                self.advance(); // 消费闭合的引号
+                return self.make_token(TokenKind::String(Cow::Borrowed(value)));
+            }
+        }
+        
+        let mut parts = Vec::new();
         let mut value = String::new();
         
         while !self.is_at_end() && self.peek() != '"' {
@@ -346,6 +372,7 @@ impl Scanner {
                     'r' => value.push('\r'),
                     '\\' => value.push('\\'),
                     '"' => value.push('"'),
+                    // \${ 产生字面量 "${"，不会被当作插值
                     '$' => value.push('$'),
                     '0' => value.push('\0'),
                     c => {
@@ -353,6 +380,18 @@ impl Scanner {
                         value.push(c);
                     }
                 }
+            } else if self.peek() == '$' && self.peek_next() == Some('{') {
+                self.advance(); // 消费 $
+                self.advance(); // 消费 {
+                match self.scan_interpolation_source() {
+                    Ok(source) => {
+                        if !value.is_empty() {
+                            parts.push(StringPart::Literal(std::mem::take(&mut value)));
+                        }
+                        parts.push(StringPart::Expr(source));
+                    }
+                    Err(message) => return self.error_token(message),
+                }
             } else {
                 value.push(self.advance());
             }
@@ -365,11 +404,108 @@ impl Scanner {
         // 消费闭合的引号
         self.advance();
         
-        self.make_token(TokenKind::String(value))
+        self.make_string_token(parts, value)
+    }
+    
+    /// 根据是否包含插值生成 String 或 InterpolatedString token
+    fn make_string_token(&self, mut parts: Vec<StringPart>, value: String) -> Token<'a> {
+        if parts.is_empty() {
+            return self.make_token(TokenKind::String(Cow::Owned(value)));
+        }
+        if !value.is_empty() {
+            parts.push(StringPart::Literal(value));
+        }
+        self.make_token(TokenKind::InterpolatedString(parts))
+    }
+    
+    /// 扫描 `${` 之后的表达式源码，直到匹配的 `}`（`${` 已被消费）
+    ///
+    /// 表达式内可以出现花括号和嵌套的字符串字面量（包括嵌套插值），
+    /// 例如 `"${a + "${b}"}"`
+    fn scan_interpolation_source(&mut self) -> Result<String, &'static str> {
+        let mut source = String::new();
+        let mut depth = 0usize;
+        
+        loop {
+            if self.is_at_end() {
+                return Err("Unterminated string interpolation");
+            }
+            match self.peek() {
+                '{' => {
+                    depth += 1;
+                    source.push(self.advance());
+                }
+                '}' => {
+                    self.advance();
+                    if depth == 0 {
+                        return Ok(source);
+                    }
+                    depth -= 1;
+                    source.push('}');
+                }
+                '"' => {
+                    source.push(self.advance());
+                    self.copy_nested_string(&mut source)?;
+                }
+                '\'' => {
+                    source.push(self.advance());
+                    while !self.is_at_end() && self.peek() != '\'' {
+                        if self.peek() == '\n' {
+                            self.line += 1;
+                            self.column = 0;
+                        }
+                        source.push(self.advance());
+                    }
+                    if self.is_at_end() {
+                        return Err("Unterminated string");
+                    }
+                    source.push(self.advance());
+                }
+                c => {
+                    if c == '\n' {
+                        self.line += 1;
+                        self.column = 0;
+                    }
+                    source.push(self.advance());
+                }
+            }
+        }
+    }
+    
+    /// 原样复制插值表达式内嵌套的双引号字符串（开头的 `"` 已复制），
+    /// 转义和嵌套插值留给解析该表达式时的扫描器处理
+    fn copy_nested_string(&mut self, source: &mut String) -> Result<(), &'static str> {
+        loop {
+            if self.is_at_end() {
+                return Err("Unterminated string");
+            }
+            if self.peek() == '\n' {
+                self.line += 1;
+                self.column = 0;
+            }
+            let c = self.advance();
+            source.push(c);
+            match c {
+                '"' => return Ok(()),
+                '\\' => {
+                    if !self.is_at_end() {
+                        source.push(self.advance());
+                    }
+                }
+                '$' if self.peek() == '{' => {
+                    source.push(self.advance());
+                    let inner = self.scan_interpolation_source()?;
+                    source.push_str(&inner);
+                    source.push('}');
+                }
+                _ => {}
+            }
+        }
     }
     
-    /// 扫描多行字符串（三引号）
-    fn scan_multiline_string(&mut self) -> Token {
+    /// 扫描多行字符串（三引号，不处理转义，支持 `${...}` 插值）
+    fn scan_multiline_string(&mut self) -> Token<'a> {
+        let mut parts = Vec::new();
         let mut value = String::new();
         
         // 跳过开头的换行符（如果有）
@@ -383,17 +519,29 @@ impl Scanner {
             // 检查是否遇到结束的三引号
             if self.peek() == '"' && self.peek_next() == Some('"') {
                 // 检查第三个引号
-                let pos = self.current;
+                let saved = (self.current, self.column);
                 self.advance(); // 消费第一个 "
                 self.advance(); // 消费第二个 "
                 if self.peek() == '"' {
                     self.advance(); // 消费第三个 "
-                    return self.make_token(TokenKind::String(value));
+                    return self.make_string_token(parts, value);
                 } else {
                     // 不是三引号，回退并添加到值中
-                    self.current = pos;
+                    (self.current, self.column) = saved;
                     value.push(self.advance());
                 }
+            } else if self.peek() == '$' && self.peek_next() == Some('{') {
+                self.advance(); // 消费 $
+                self.advance(); // 消费 {
+                match self.scan_interpolation_source() {
+                    Ok(source) => {
+                        if !value.is_empty() {
+                            parts.push(StringPart::Literal(std::mem::take(&mut value)));
+                        }
+                        parts.push(StringPart::Expr(source));
+                    }
+                    Err(message) => return self.error_token(message),
+                }
             } else if self.peek() == '\n' {
                 value.push(self.advance());
                 self.line += 1;
@@ -407,21 +555,22 @@ impl Scanner {
     }
 
     /// 扫描原始字符串（单引号，不支持转义）
-    fn scan_raw_string(&mut self) -> Token {
-        let mut value = String::new();
+    fn scan_raw_string(&mut self) -> Token<'a> {
+        let value_start = self.current;
         
         while !self.is_at_end() && self.peek() != '\'' {
             if self.peek() == '\n' {
                 self.line += 1;
                 self.column = 0;
             }
-            value.push(self.advance());
+            self.advance();
         }
         
         if self.is_at_end() {
             return self.error_token("Unterminated string");
         }
         
+        let value = &self.source[value_start..self.current];
         // 消费闭合的引号
         self.advance();
         
@@ -429,11 +578,9 @@ impl Scanner {
     }
 
     /// 扫描数字（支持各种进制和数字分隔符）
-    fn scan_number(&mut self) -> Token {
+    fn scan_number(&mut self) -> Token<'a> {
         // 检查进制前缀
-        let first_char = self.source[self.start];
-        
-        if first_char == '0' && !self.is_at_end() {
+        if self.source.as_bytes()[self.start] == b'0' && !self.is_at_end() {
             match self.peek() {
                 'x' | 'X' => return self.scan_hex_number(),
                 'b' | 'B' => return self.scan_binary_number(),
@@ -446,7 +593,7 @@ impl Scanner {
         while !self.is_at_end() && (self.peek().is_ascii_digit() || self.peek() == '_') {
             if self.peek() == '_' {
                 // 下划线不能在数字开头或连续出现
-                let prev = self.source.get(self.current - 1).copied().unwrap_or('0');
+                let prev = self.previous_char();
                 if !prev.is_ascii_digit() {
                     return self.error_token("Invalid number: underscore must be between digits");
                 }
@@ -455,7 +602,7 @@ impl Scanner {
         }
         
         // 检查下划线不能在数字末尾
-        let last = self.source.get(self.current - 1).copied().unwrap_or('0');
+        let last = self.previous_char();
         if last == '_' {
             return self.error_token("Invalid number: underscore cannot be at the end");
         }
@@ -469,7 +616,7 @@ impl Scanner {
                         self.advance();
                     }
                     // 检查下划线不能在小数末尾
-                    let last = self.source.get(self.current - 1).copied().unwrap_or('0');
+                    let last = self.previous_char();
                     if last == '_' {
                         return self.error_token("Invalid number: underscore cannot be at the end");
                     }
@@ -502,10 +649,7 @@ impl Scanner {
         };
         
         // 收集数字字符（移除下划线）
-        let lexeme: String = self.source[self.start..self.current]
-            .iter()
-            .filter(|&&c| c != '_')
-            .collect();
+        let lexeme = self.digits(self.start);
         
         if is_float || has_exponent {
             match lexeme.parse::<f64>() {
@@ -521,7 +665,7 @@ impl Scanner {
     }
     
     /// 扫描十六进制数字 (0x...)
-    fn scan_hex_number(&mut self) -> Token {
+    fn scan_hex_number(&mut self) -> Token<'a> {
         self.advance(); // 消费 'x' 或 'X'
         
         if !self.is_at_end() && !self.peek().is_ascii_hexdigit() {
@@ -530,7 +674,7 @@ impl Scanner {
         
         while !self.is_at_end() && (self.peek().is_ascii_hexdigit() || self.peek() == '_') {
             if self.peek() == '_' {
-                let prev = self.source.get(self.current - 1).copied().unwrap_or('0');
+                let prev = self.previous_char();
                 if !prev.is_ascii_hexdigit() {
                     return self.error_token("Invalid number: underscore must be between digits");
                 }
@@ -538,16 +682,13 @@ impl Scanner {
             self.advance();
         }
         
-        let last = self.source.get(self.current - 1).copied().unwrap_or('0');
+        let last = self.previous_char();
         if last == '_' {
             return self.error_token("Invalid number: underscore cannot be at the end");
         }
         
         // 移除 0x 前缀和下划线
-        let hex_str: String = self.source[self.start + 2..self.current]
-            .iter()
-            .filter(|&&c| c != '_')
-            .collect();
+        let hex_str = self.digits(self.start + 2);
         
         match i128::from_str_radix(&hex_str, 16) {
             Ok(value) => self.make_token(TokenKind::Integer(value)),
@@ -556,7 +697,7 @@ impl Scanner {
     }
     
     /// 扫描二进制数字 (0b...)
-    fn scan_binary_number(&mut self) -> Token {
+    fn scan_binary_number(&mut self) -> Token<'a> {
         self.advance(); // 消费 'b' 或 'B'
         
         if !self.is_at_end() && !matches!(self.peek(), '0' | '1') {
@@ -565,7 +706,7 @@ impl Scanner {
         
         while !self.is_at_end() && (matches!(self.peek(), '0' | '1') || self.peek() == '_') {
             if self.peek() == '_' {
-                let prev = self.source.get(self.current - 1).copied().unwrap_or('0');
+                let prev = self.previous_char();
                 if !matches!(prev, '0' | '1') {
                     return self.error_token("Invalid number: underscore must be between digits");
                 }
@@ -573,16 +714,13 @@ impl Scanner {
             self.advance();
         }
         
-        let last = self.source.get(self.current - 1).copied().unwrap_or('0');
+        let last = self.previous_char();
         if last == '_' {
             return self.error_token("Invalid number: underscore cannot be at the end");
         }
         
         // 移除 0b 前缀和下划线
-        let bin_str: String = self.source[self.start + 2..self.current]
-            .iter()
-            .filter(|&&c| c != '_')
-            .collect();
+        let bin_str = self.digits(self.start + 2);
         
         match i128::from_str_radix(&bin_str, 2) {
             Ok(value) => self.make_token(TokenKind::Integer(value)),
@@ -591,7 +729,7 @@ impl Scanner {
     }
     
     /// 扫描八进制数字 (0o...)
-    fn scan_octal_number(&mut self) -> Token {
+    fn scan_octal_number(&mut self) -> Token<'a> {
         self.advance(); // 消费 'o' 或 'O'
         
         if !self.is_at_end() && !matches!(self.peek(), '0'..='7') {
@@ -600,7 +738,7 @@ impl Scanner {
         
         while !self.is_at_end() && (matches!(self.peek(), '0'..='7') || self.peek() == '_') {
             if self.peek() == '_' {
-                let prev = self.source.get(self.current - 1).copied().unwrap_or('0');
+                let prev = self.previous_char();
                 if !matches!(prev, '0'..='7') {
                     return self.error_token("Invalid number: underscore must be between digits");
                 }
@@ -608,16 +746,13 @@ impl Scanner {
             self.advance();
         }
         
-        let last = self.source.get(self.current - 1).copied().unwrap_or('0');
+        let last = self.previous_char();
         if last == '_' {
             return self.error_token("Invalid number: underscore cannot be at the end");
         }
         
         // 移除 0o 前缀和下划线
-        let oct_str: String = self.source[self.start + 2..self.current]
-            .iter()
-            .filter(|&&c| c != '_')
-            .collect();
+        let oct_str = self.digits(self.start + 2);
         
         match i128::from_str_radix(&oct_str, 8) {
             Ok(value) => self.make_token(TokenKind::Integer(value)),
@@ -626,7 +761,7 @@ impl Scanner {
     }
 
     /// 扫描标识符或关键字（支持 Unicode 标识符）
-    fn scan_identifier(&mut self) -> Token {
+    fn scan_identifier(&mut self) -> Token<'a> {
         // 支持 Unicode 标识符：
         // - 第一个字符：字母、下划线、$、或 Unicode XID_Start
         // - 后续字符：字母数字、下划线、或 Unicode XID_Continue
@@ -634,8 +769,7 @@ impl Scanner {
             self.advance();
         }
         
-        let lexeme: String = self.source[self.start..self.current].iter().collect();
-        let kind = self.identifier_type(&lexeme);
+        let kind = Self::identifier_type(&self.source[self.start..self.current]);
         
         self.make_token(kind)
     }
@@ -653,7 +787,7 @@ impl Scanner {
     }
     
     /// 识别关键字或返回标识符
-    fn identifier_type(&self, lexeme: &str) -> TokenKind {
+    fn identifier_type(lexeme: &'a str) -> TokenKind<'a> {
         match lexeme {
             // 声明关键字
             "var" => TokenKind::Var,
@@ -703,6 +837,10 @@ impl Scanner {
             "return" => TokenKind::Return,
             "match" => TokenKind::Match,
             "go" => TokenKind::Go,
+            "select" => TokenKind::Select,
+            "switch" => TokenKind::Switch,
+            "case" => TokenKind::Case,
+            "chan" => TokenKind::Chan,
             
             // 面向对象关键字
             "new" => TokenKind::New,
@@ -713,6 +851,7 @@ impl Scanner {
             "abstract" => TokenKind::Abstract,
             "static" => TokenKind::Static,
             "override" => TokenKind::Override,
+            "final" => TokenKind::Final,
             
             // 字面量关键字
             "true" => TokenKind::True,
@@ -742,7 +881,7 @@ impl Scanner {
             "_" => TokenKind::Underscore,
             
             // 不是关键字，返回标识符
-            _ => TokenKind::Identifier(lexeme.to_string()),
+            _ => TokenKind::Identifier(lexeme),
         }
     }
 
@@ -753,56 +892,58 @@ impl Scanner {
 
     /// 前进一个字符并返回
     fn advance(&mut self) -> char {
-        let c = self.source[self.current];
-        self.current += 1;
+        let c = self.peek();
+        self.current += c.len_utf8();
         self.column += 1;
         c
     }
 
     /// 查看当前字符
     fn peek(&self) -> char {
-        if self.is_at_end() {
-            '\0'
-        } else {
-            self.source[self.current]
-        }
+        self.source[self.current..].chars().next().unwrap_or('\0')
     }
 
     /// 查看下一个字符
     fn peek_next(&self) -> Option<char> {
-        if self.current + 1 >= self.source.len() {
-            None
-        } else {
-            Some(self.source[self.current + 1])
-        }
+        self.source[self.current..].chars().nth(1)
+    }
+
+    /// 前一个字符（已消费的最后一个字符）
+    fn previous_char(&self) -> char {
+        self.source[..self.current].chars().next_back().unwrap_or('0')
     }
 
     /// 如果当前字符匹配，则前进
     fn match_char(&mut self, expected: char) -> bool {
-        if self.is_at_end() || self.source[self.current] != expected {
+        if self.is_at_end() || self.peek() != expected {
             false
         } else {
-            self.current += 1;
+            self.current += expected.len_utf8();
             self.column += 1;
             true
         }
     }
 
+    /// 从 from 到当前位置的数字，去掉分隔用的下划线（没有下划线时不复制）
+    fn digits(&self, from: usize) -> Cow<'a, str> {
+        let digits = &self.source[from..self.current];
+        if digits.contains('_') {
+            Cow::Owned(digits.replace('_', ""))
+        } else {
+            Cow::Borrowed(digits)
+        }
+    }
+
     /// 创建 token
-    fn make_token(&self, kind: TokenKind) -> Token {
-        let lexeme: String = self.source[self.start..self.current].iter().collect();
-        let span = Span::new(self.start, self.current, self.line, self.start_column);
-        Token::new(kind, lexeme, span)
+    fn make_token(&self, kind: TokenKind<'a>) -> Token<'a> {
+        let span = Span::new(self.start, self.current, self.start_line, self.start_column);
+        Token::new(kind, &self.source[self.start..self.current], span)
     }
 
     /// 创建错误 token
-    fn error_token(&self, message: &str) -> Token {
-        let span = Span::new(self.start, self.current, self.line, self.start_column);
-        Token::new(
-            TokenKind::Error(message.to_string()),
-            String::new(),
-            span,
-        )
+    fn error_token(&self, message: &str) -> Token<'a> {
+        let span = Span::new(self.start, self.current, self.start_line, self.start_column);
+        Token::new(TokenKind::Error(message.to_string()), "", span)
     }
 }
 
@@ -825,7 +966,25 @@ mod tests {
         let tokens = scanner.scan_tokens();
         
         assert!(matches!(&tokens[0].kind, TokenKind::String(s) if s == "hello"));
-        assert!(matches!(&tokens[1].kind, TokenKind::RawString(s) if s == "world"));
+        assert!(matches!(&tokens[1].kind, TokenKind::RawString(s) if *s == "world"));
+    }
+
+    #[test]
+    fn test_scan_interpolated_strings() {
+        let mut scanner = Scanner::new(r#""a ${x + 1} b" "\${x}" "${s + "${y}"}""#);
+        let tokens = scanner.scan_tokens();
+        
+        assert_eq!(tokens[0].kind, TokenKind::InterpolatedString(vec![
+            StringPart::Literal("a ".to_string()),
+            StringPart::Expr("x + 1".to_string()),
+            StringPart::Literal(" b".to_string()),
+        ]));
+        // 转义的 \${ 是普通字符串
+        assert!(matches!(&tokens[1].kind, TokenKind::String(s) if s == "${x}"));
+        // 嵌套插值作为表达式源码整体保留
+        assert_eq!(tokens[2].kind, TokenKind::InterpolatedString(vec![
+            StringPart::Expr("s + \"${y}\"".to_string()),
+        ]));
     }
 
     #[test]
@@ -885,13 +1044,13 @@ mod tests {
         let mut scanner = Scanner::new("foo bar_baz MyClass _private $name $value");
         let tokens = scanner.scan_tokens();
         
-        assert!(matches!(&tokens[0].kind, TokenKind::Identifier(s) if s == "foo"));
-        assert!(matches!(&tokens[1].kind, TokenKind::Identifier(s) if s == "bar_baz"));
-        assert!(matches!(&tokens[2].kind, TokenKind::Identifier(s) if s == "MyClass"));
-        assert!(matches!(&tokens[3].kind, TokenKind::Identifier(s) if s == "_private"));
+        assert!(matches!(&tokens[0].kind, TokenKind::Identifier(s) if *s == "foo"));
+        assert!(matches!(&tokens[1].kind, TokenKind::Identifier(s) if *s == "bar_baz"));
+        assert!(matches!(&tokens[2].kind, TokenKind::Identifier(s) if *s == "MyClass"));
+        assert!(matches!(&tokens[3].kind, TokenKind::Identifier(s) if *s == "_private"));
         // $ 开头的变量名（类似 PHP 风格，可选）
-        assert!(matches!(&tokens[4].kind, TokenKind::Identifier(s) if s == "$name"));
-        assert!(matches!(&tokens[5].kind, TokenKind::Identifier(s) if s == "$value"));
+        assert!(matches!(&tokens[4].kind, TokenKind::Identifier(s) if *s == "$name"));
+        assert!(matches!(&tokens[5].kind, TokenKind::Identifier(s) if *s == "$value"));
     }
     
     #[test]
@@ -900,10 +1059,74 @@ mod tests {
         let tokens = scanner.scan_tokens();
         
         assert!(matches!(tokens[0].kind, TokenKind::Var));
-        assert!(matches!(&tokens[1].kind, TokenKind::Identifier(s) if s == "x"));
+        assert!(matches!(&tokens[1].kind, TokenKind::Identifier(s) if *s == "x"));
         assert!(matches!(tokens[2].kind, TokenKind::Colon));
         assert!(matches!(tokens[3].kind, TokenKind::Int));
         assert!(matches!(tokens[4].kind, TokenKind::Equal));
         assert!(matches!(tokens[5].kind, TokenKind::Integer(10)));
     }
+    
+    /// 统计当前线程的堆分配次数（整个测试二进制的全局分配器，计数按线程隔离，不受并行测试影响）
+    struct CountingAlloc;
+    
+    thread_local! {
+        static ALLOCATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+    
+    unsafe impl std::alloc::GlobalAlloc for CountingAlloc {
+        unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+            unsafe { std::alloc::System.alloc(layout) }
+        }
+        
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+            unsafe { std::alloc::System.dealloc(ptr, layout) }
+        }
+    }
+    
+    #[global_allocator]
+    static ALLOCATOR: CountingAlloc = CountingAlloc;
+    
+    fn count_allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+        let before = ALLOCATIONS.with(|n| n.get());
+        let result = f();
+        (result, ALLOCATIONS.with(|n| n.get()) - before)
+    }
+    
+    #[test]
+    fn test_tokens_borrow_source() {
+        let source = "var name = 'raw' + \"plain\" + \"escaped\\n\" + 1_000";
+        let tokens = Scanner::new(source).scan_tokens();
+        let range = source.as_bytes().as_ptr_range();
+        for token in &tokens {
+            assert!(token.lexeme.is_empty() || range.contains(&token.lexeme.as_ptr()), "{}", token);
+        }
+        assert!(matches!(tokens[1].kind, TokenKind::Identifier(s) if range.contains(&s.as_ptr())));
+        assert!(matches!(tokens[3].kind, TokenKind::RawString(s) if range.contains(&s.as_ptr())));
+        assert!(matches!(&tokens[5].kind, TokenKind::String(Cow::Borrowed("plain"))));
+        assert!(matches!(&tokens[7].kind, TokenKind::String(Cow::Owned(s)) if s == "escaped\n"));
+        assert!(matches!(tokens[9].kind, TokenKind::Integer(1000)));
+    }
+    
+    #[test]
+    fn test_allocation_ceiling() {
+        let mut source = String::new();
+        for i in 0..500 {
+            source.push_str(&format!(
+                "func compute_{i}(first: int, second: int) int {{\n    var total = first * {i} + second\n    if total > 100 {{\n        total = total - second\n    }}\n    return total\n}}\n\n"
+            ));
+        }
+        
+        // 扫描只为 token 数组本身分配（数组扩容），与 token 数量无关
+        let (tokens, scan) = count_allocations(|| Scanner::new(&source).scan_tokens());
+        assert!(scan <= 64, "scanning {} tokens made {} allocations", tokens.len(), scan);
+        
+        // 解析只在构造 AST 时复制名字：每个 token 平均不超过一次分配
+        let token_count = tokens.len();
+        let (program, parse) = count_allocations(|| {
+            crate::parser::Parser::new(tokens, crate::i18n::Locale::En).parse().unwrap()
+        });
+        assert_eq!(program.statements.len(), 500);
+        assert!(parse <= token_count, "parsing {} tokens made {} allocations", token_count, parse);
+    }
 }
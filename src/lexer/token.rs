@@ -1,29 +1,35 @@
 //! Token 定义
 //! 
 //! 词法分析器产生的标记类型
+//!
+//! Token 借用源码缓冲区：标识符、原始字符串和不含转义的字符串直接是源码切片，
+//! 解析器只在放入 AST 时才复制成 `String`。
 
 #![allow(dead_code)]
 
+use std::borrow::Cow;
 use std::fmt;
 
 /// Token 类型
 #[derive(Debug, Clone, PartialEq)]
-pub enum TokenKind {
+pub enum TokenKind<'a> {
     // ============ 字面量 ============
     /// 整数字面量（使用 i128 以支持 u64 范围的值）
     Integer(i128),
     /// 浮点数字面量
     Float(f64),
-    /// 字符串字面量（双引号，支持插值）
-    String(String),
+    /// 字符串字面量（双引号，不含插值；没有转义时借用源码）
+    String(Cow<'a, str>),
+    /// 含 `${...}` 插值的字符串字面量，由扫描器切分为文本和表达式源码
+    InterpolatedString(Vec<StringPart>),
     /// 原始字符串字面量（单引号，不支持插值）
-    RawString(String),
+    RawString(&'a str),
     /// 字符字面量
     Char(char),
 
     // ============ 标识符和关键字 ============
     /// 标识符
-    Identifier(String),
+    Identifier(&'a str),
 
     // ============ 声明关键字 ============
     /// var
@@ -114,6 +120,14 @@ pub enum TokenKind {
     Match,
     /// go
     Go,
+    /// select
+    Select,
+    /// switch
+    Switch,
+    /// case（select 和 switch 分支）
+    Case,
+    /// chan（通道类型）
+    Chan,
 
     // ============ 面向对象关键字 ============
     /// new
@@ -132,6 +146,8 @@ pub enum TokenKind {
     Static,
     /// override
     Override,
+    /// final
+    Final,
 
     // ============ 字面量关键字 ============
     /// true
@@ -304,6 +320,8 @@ pub enum TokenKind {
     Colon,
     /// ;
     Semicolon,
+    /// @（函数和方法的属性）
+    At,
 
     // ============ 特殊 ============
     /// 换行
@@ -321,9 +339,12 @@ pub struct Span {
     pub start: usize,
     /// 结束位置（字节偏移）
     pub end: usize,
-    /// 行号（从1开始）
+    /// 起始行号（从1开始）
     pub line: usize,
-    /// 列号（从1开始）
+    /// 起始列号（从1开始）
+    /// 
+    /// 按字符（Unicode 标量值）计数而不是字节：制表符、CJK 字符和 emoji 都计为 1 列。
+    /// 显示宽度的换算（制表符展开、宽字符占 2 格）由诊断渲染负责。
     pub column: usize,
 }
 
@@ -334,20 +355,29 @@ impl Span {
     }
 }
 
+/// 插值字符串的组成部分
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringPart {
+    /// 字面文本（转义已处理）
+    Literal(String),
+    /// `${...}` 内的表达式源码
+    Expr(String),
+}
+
 /// Token 结构
 #[derive(Debug, Clone, PartialEq)]
-pub struct Token {
+pub struct Token<'a> {
     /// Token 类型
-    pub kind: TokenKind,
-    /// 原始文本
-    pub lexeme: String,
+    pub kind: TokenKind<'a>,
+    /// 原始文本（源码切片）
+    pub lexeme: &'a str,
     /// 位置信息
     pub span: Span,
 }
 
-impl Token {
+impl<'a> Token<'a> {
     /// 创建新的 Token
-    pub fn new(kind: TokenKind, lexeme: String, span: Span) -> Self {
+    pub fn new(kind: TokenKind<'a>, lexeme: &'a str, span: Span) -> Self {
         Self { kind, lexeme, span }
     }
 
@@ -367,19 +397,29 @@ impl Token {
     }
 }
 
-impl fmt::Display for Token {
+impl fmt::Display for Token<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:?} '{}' at {}:{}", self.kind, self.lexeme, self.span.line, self.span.column)
     }
 }
 
-impl fmt::Display for TokenKind {
+impl fmt::Display for TokenKind<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             // 字面量
             TokenKind::Integer(n) => write!(f, "{}", n),
             TokenKind::Float(n) => write!(f, "{}", n),
             TokenKind::String(s) => write!(f, "\"{}\"", s),
+            TokenKind::InterpolatedString(parts) => {
+                write!(f, "\"")?;
+                for part in parts {
+                    match part {
+                        StringPart::Literal(s) => write!(f, "{}", s)?,
+                        StringPart::Expr(src) => write!(f, "${{{}}}", src)?,
+                    }
+                }
+                write!(f, "\"")
+            }
             TokenKind::RawString(s) => write!(f, "'{}'", s),
             TokenKind::Char(c) => write!(f, "'{}'", c),
             TokenKind::Identifier(s) => write!(f, "{}", s),
@@ -432,6 +472,10 @@ impl fmt::Display for TokenKind {
             TokenKind::Return => write!(f, "return"),
             TokenKind::Match => write!(f, "match"),
             TokenKind::Go => write!(f, "go"),
+            TokenKind::Select => write!(f, "select"),
+            TokenKind::Switch => write!(f, "switch"),
+            TokenKind::Case => write!(f, "case"),
+            TokenKind::Chan => write!(f, "chan"),
             
             // 面向对象关键字
             TokenKind::New => write!(f, "new"),
@@ -442,6 +486,7 @@ impl fmt::Display for TokenKind {
             TokenKind::Abstract => write!(f, "abstract"),
             TokenKind::Static => write!(f, "static"),
             TokenKind::Override => write!(f, "override"),
+            TokenKind::Final => write!(f, "final"),
             
             // 字面量关键字
             TokenKind::True => write!(f, "true"),
@@ -540,6 +585,7 @@ impl fmt::Display for TokenKind {
             TokenKind::Dot => write!(f, "."),
             TokenKind::Colon => write!(f, ":"),
             TokenKind::Semicolon => write!(f, ";"),
+            TokenKind::At => write!(f, "@"),
             
             // 特殊
             TokenKind::Newline => write!(f, "\\n"),
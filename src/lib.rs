@@ -0,0 +1,94 @@
+//! Q 语言编译器和虚拟机
+//!
+//! 命令行程序（`mylang`）建立在这个库之上：[`compile`] 编译源文件及其依赖（项目由 [`Project`] 确定），
+//! 错误以结构化的 [`Diagnostics`] 返回，[`run_chunk`] 运行编译好的字节码块。
+//! 嵌入 Q 的 Rust 程序也可以使用 [`Engine`]，或者用 [`StdlibRegistry`] 注册自己的原生模块和宿主函数，
+//! 再用 [`VM::with_registry`] 运行编译好的字节码块。
+
+pub mod config;
+pub mod i18n;
+pub mod diagnostics;
+pub mod lexer;
+pub mod parser;
+pub mod compiler;
+pub mod vm;
+pub mod types;
+pub mod package;
+pub mod stdlib;
+pub mod runtime;
+pub mod typechecker;
+pub mod repl;
+pub mod engine;
+pub mod driver;
+pub mod bundle;
+pub mod timings;
+pub mod ice;
+
+pub use engine::Engine;
+pub use driver::{compile, compile_with_warnings, run_chunk, CompileOptions, Compiled, Project, RunOptions};
+pub use diagnostics::{Diagnostic, Diagnostics, Severity, Stage};
+pub use stdlib::{StdlibModule, StdlibRegistry};
+pub use vm::{Value, VM};
+pub use vm::vm::RuntimeError;
+
+use i18n::Locale;
+use lexer::Scanner;
+use parser::{Parser, Program};
+use timings::Phase;
+
+/// 解析单个源文件
+pub fn parse_source(source: &str, locale: Locale) -> Result<Program, String> {
+    let render = |errors: Vec<Diagnostic>| {
+        errors.iter().map(|e| e.render(Some(source))).collect::<Vec<_>>().join("\n")
+    };
+    let (tokens, lexer_errors) = scan_source(source);
+    if !lexer_errors.is_empty() {
+        return Err(render(lexer_errors));
+    }
+    
+    // 语法分析
+    ice::enter(ice::Phase::Parse);
+    let started = timings::begin();
+    let mut parser = Parser::new(tokens, locale);
+    let result = parser.parse();
+    timings::record(Phase::Parse, started, || parser.node_count() as u64);
+    result.map_err(|errors| render(syntax_diagnostics(&errors)))
+}
+
+/// 解析单个源文件，返回能解析的部分和所有错误（不带文件名）
+///
+/// 有词法错误时只报告词法错误，去掉错误 token 后解析出的程序仅用于读取 import
+pub fn parse_source_recovering(source: &str, locale: Locale) -> (Program, Vec<Diagnostic>) {
+    let (tokens, lexer_errors) = scan_source(source);
+    ice::enter(ice::Phase::Parse);
+    let started = timings::begin();
+    let mut parser = Parser::new(tokens, locale);
+    let (program, errors) = parser.parse_recovering();
+    timings::record(Phase::Parse, started, || parser.node_count() as u64);
+    if !lexer_errors.is_empty() {
+        return (program, lexer_errors);
+    }
+    (program, syntax_diagnostics(&errors))
+}
+
+/// 词法分析，返回去掉错误 token 的 token 列表和词法错误
+fn scan_source(source: &str) -> (Vec<lexer::Token<'_>>, Vec<Diagnostic>) {
+    ice::enter(ice::Phase::Lex);
+    let started = timings::begin();
+    let mut scanner = Scanner::new(source);
+    let mut tokens = scanner.scan_tokens();
+    timings::record(Phase::Lex, started, || tokens.len() as u64);
+    let errors = tokens
+        .iter()
+        .filter_map(|token| match &token.kind {
+            lexer::TokenKind::Error(msg) => Some(Diagnostic::error(Stage::Syntax, msg.clone()).at(token.span)),
+            _ => None,
+        })
+        .collect();
+    tokens.retain(|token| !token.is_error());
+    (tokens, errors)
+}
+
+fn syntax_diagnostics(errors: &[parser::ParseError]) -> Vec<Diagnostic> {
+    errors.iter().map(|e| Diagnostic::error(Stage::Syntax, e.message.clone()).at(e.span)).collect()
+}
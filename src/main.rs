@@ -1,483 +1,452 @@
-//! Q 语言编译器和虚拟机
+//! Q 语言命令行程序
 //! 
-//! 主入口点
+//! 主入口点；编译器和虚拟机在 qlang 库中（src/lib.rs）
 
-mod config;
-mod i18n;
-mod lexer;
-mod parser;
-mod compiler;
-mod vm;
-mod types;
-mod package;
-mod stdlib;
-mod runtime;
-mod typechecker;
+use qlang::{config, i18n, diagnostics, parser, vm, package, repl, timings, ice};
+use qlang::{bundle, compile_with_warnings, run_chunk, CompileOptions, Diagnostics, Project, RuntimeError};
+use qlang::compiler::Chunk;
 
-use std::collections::HashSet;
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process;
 
 use config::{LANG_NAME, VERSION, SOURCE_EXTENSION, PROJECT_FILE};
-
-/// 清理路径显示格式（移除 Windows 的 \\?\ 前缀）
-fn display_path(path: &Path) -> String {
-    let s = path.to_string_lossy();
-    // Windows canonicalize 返回 \\?\C:\... 格式，需要清理
-    if s.starts_with(r"\\?\") {
-        s[4..].to_string()
-    } else {
-        s.to_string()
-    }
-}
 use i18n::{Locale, format_message, messages};
-use lexer::Scanner;
-use parser::{Parser, Program, Stmt};
-use compiler::Compiler;
-use vm::VM;
-use typechecker::{TypeChecker, Monomorphizer, CompileContext};
-use package::{ProjectConfig, find_project_root, compute_expected_package, PackageResolver, ImportKind};
+use vm::{TraceOptions, TraceFormat, render_error, set_deterministic_hashing, set_allocation_limit, set_safe_vm};
+use diagnostics::{display_path, use_color};
+use package::parse_override;
 
-/// 解析单个源文件
-fn parse_source(source: &str, locale: Locale) -> Result<Program, String> {
-    // 词法分析
-    let mut scanner = Scanner::new(source);
-    let tokens = scanner.scan_tokens();
-    
-    // 检查词法错误
-    for token in &tokens {
-        if token.is_error() {
-            if let lexer::TokenKind::Error(msg) = &token.kind {
-                return Err(format!(
-                    "[{}:{}] {}",
-                    token.span.line, token.span.column, msg
-                ));
-            }
-        }
-    }
-    
-    // 语法分析
-    let mut parser = Parser::new(tokens, locale);
-    parser.parse().map_err(|errors| {
-        errors
-            .iter()
-            .map(|e| format!("[{}:{}] {}", e.span.line, e.span.column, e.message))
-            .collect::<Vec<_>>()
-            .join("\n")
-    })
+/// `run` 和 `build` 命令的选项
+#[derive(Debug, Default)]
+struct CliOptions {
+    /// 指令级执行追踪（--trace）
+    trace: Option<TraceOptions>,
+    /// map 使用固定哈希密钥（--deterministic）
+    deterministic: bool,
+    /// 额外的运行时检查，例如二分查找前确认数组有序（--safe-vm）
+    safe_vm: bool,
+    /// 单次分配的字节数上限（--max-allocation），默认按可用内存计算
+    max_allocation: Option<usize>,
+    /// 编译优化（-O）
+    optimize: bool,
+    /// 只输出字节码反汇编，不执行（--emit=bytecode）
+    emit_bytecode: bool,
+    /// 只编译不执行，并写出锁文件（`build` 命令）
+    build: bool,
+    /// 打包为可执行文件的输出路径（`bundle` 命令）
+    bundle: Option<PathBuf>,
+    /// 压缩打包的程序（--compress）
+    compress: bool,
+    /// 把类型检查警告当作错误（--deny warnings）
+    deny_warnings: bool,
+    /// 隐式 any 是错误而不是警告（--strict-types，也可在 project.toml 的 [build] 中开启）
+    strict_types: bool,
+    /// 未捕获错误的栈追踪格式（--trace-format）
+    trace_format: TraceFormat,
+    /// 不输出颜色（--no-color）
+    no_color: bool,
+    /// 覆盖项目配置（--set key=value）
+    config_overrides: Vec<(String, String)>,
+    /// 直接指定 project.toml，不再向上查找（--project）
+    project: Option<String>,
+    /// `--` 之后传给程序的参数（Os.args）
+    program_args: Vec<String>,
+    /// 结束时输出各编译阶段的耗时（--timings）
+    timings: bool,
+    /// 把各编译阶段的耗时写入 JSON 文件（--timings-json）
+    timings_json: Option<String>,
 }
 
-/// 加载依赖文件并合并 AST
-fn load_dependencies(
-    main_program: &Program,
-    main_file: &Path,
-    project: Option<&ProjectConfig>,
-    locale: Locale,
-) -> Result<Vec<Stmt>, String> {
-    let mut all_statements: Vec<Stmt> = Vec::new();
-    let mut loaded_files: HashSet<PathBuf> = HashSet::new();
-    
-    // 标记主文件已加载
-    if let Ok(abs_path) = fs::canonicalize(main_file) {
-        loaded_files.insert(abs_path);
-    }
-    
-    // 创建包解析器
-    let resolver = PackageResolver::new(project.cloned());
-    
-    // 处理主程序的 imports
-    for import in &main_program.imports {
-        match resolver.resolve(import) {
-            Ok(resolved) => {
-                match resolved.kind {
-                    ImportKind::StdBuiltin => {
-                        // 内置标准库，不需要加载源文件
-                        // 类型和函数由 VM 内置提供
-                    }
-                    ImportKind::StdSource => {
-                        // Q 语言标准库源文件
-                        if let Some(source_path) = &resolved.source_path {
-                            if source_path.exists() {
-                                load_source_file(
-                                    source_path,
-                                    &mut all_statements,
-                                    &mut loaded_files,
-                                    project,
-                                    locale,
-                                    &resolver,
-                                )?;
-                            }
-                        }
-                    }
-                    ImportKind::Project => {
-                        // 项目内部包
-                        if let Some(source_path) = &resolved.source_path {
-                            // 检查是文件还是目录
-                            if source_path.is_file() {
-                                load_source_file(
-                                    source_path,
-                                    &mut all_statements,
-                                    &mut loaded_files,
-                                    project,
-                                    locale,
-                                    &resolver,
-                                )?;
-                            } else if source_path.is_dir() {
-                                // 加载目录下所有 .q 文件
-                                load_directory(
-                                    source_path,
-                                    &mut all_statements,
-                                    &mut loaded_files,
-                                    project,
-                                    locale,
-                                    &resolver,
-                                )?;
-                            } else {
-                                // 尝试作为包路径解析
-                                // import com.test.demo.models.User -> models/User.q 或 models.q 中的 User
-                                let parent = source_path.parent().unwrap_or(source_path);
-                                if parent.is_dir() {
-                                    // 尝试加载目录下的所有文件
-                                    load_directory(
-                                        parent,
-                                        &mut all_statements,
-                                        &mut loaded_files,
-                                        project,
-                                        locale,
-                                        &resolver,
-                                    )?;
-                                }
-                            }
-                        }
-                    }
-                    ImportKind::External => {
-                        // 外部依赖，暂不支持
-                        return Err(format!("外部依赖暂不支持: {}", import.path));
-                    }
-                }
+/// 解析 `run` 命令的参数，返回选项和源文件路径
+///
+/// `--trace-filter` 和 `--trace-limit` 隐含 `--trace`；`--` 之后的参数原样交给程序
+fn parse_run_args<'a>(args: &[&'a str]) -> Result<(CliOptions, &'a str), String> {
+    let mut options = CliOptions::default();
+    let mut path = None;
+    let mut i = 0;
+    while i < args.len() {
+        match args[i] {
+            "--trace" => {
+                options.trace.get_or_insert_with(TraceOptions::default);
             }
-            Err(e) => {
-                // 导入解析失败，尝试智能查找
-                // 例如: import com.test.demo.models.User
-                // 可能是 models/function.q 中的 User 类
-                if let Some(proj) = project {
-                    if let Some(found_path) = find_import_source(import, proj) {
-                        load_source_file(
-                            &found_path,
-                            &mut all_statements,
-                            &mut loaded_files,
-                            project,
-                            locale,
-                            &resolver,
-                        )?;
-                    } else {
-                        return Err(e);
-                    }
-                } else {
-                    return Err(e);
-                }
+            "--deterministic" => options.deterministic = true,
+            "--safe-vm" => options.safe_vm = true,
+            "--strict-types" => options.strict_types = true,
+            "--timings" => options.timings = true,
+            "--timings-json" => {
+                let path = args.get(i + 1).ok_or("--timings-json requires an output file")?;
+                options.timings_json = Some(path.to_string());
+                i += 1;
             }
-        }
-    }
-    
-    Ok(all_statements)
-}
-
-/// 智能查找导入源文件
-fn find_import_source(import: &parser::ast::ImportDecl, project: &ProjectConfig) -> Option<PathBuf> {
-    use parser::ast::ImportTarget;
-    
-    // 获取导入路径的各部分
-    let full_path = match &import.target {
-        ImportTarget::Single(name) => format!("{}.{}", import.path, name),
-        _ => import.path.clone(),
-    };
-    
-    // 去除项目包前缀
-    let relative_path = full_path.strip_prefix(&project.package)
-        .and_then(|s| s.strip_prefix('.'))
-        .unwrap_or(&full_path);
-    
-    // 将点分隔的路径转换为目录结构
-    let parts: Vec<&str> = relative_path.split('.').collect();
-    if parts.is_empty() {
-        return None;
-    }
-    
-    let src_dir = project.root_dir.join(&project.src_dir);
-    
-    // 尝试不同的文件位置
-    // 1. 直接作为文件: models/User.q
-    // 2. 作为目录下的文件: models/function.q (包含 User)
-    
-    // 策略 1: 尝试 parent_path/*.q 加载整个包
-    if parts.len() >= 1 {
-        let dir_path: PathBuf = parts[..parts.len()-1].iter().collect();
-        let full_dir = src_dir.join(&dir_path);
-        if full_dir.is_dir() {
-            // 找到目录下的 .q 文件
-            if let Ok(entries) = fs::read_dir(&full_dir) {
-                for entry in entries.flatten() {
-                    let path = entry.path();
-                    if path.extension().map(|e| e == SOURCE_EXTENSION).unwrap_or(false) {
-                        return Some(path);
-                    }
+            "-O" => options.optimize = true,
+            "--emit=bytecode" => options.emit_bytecode = true,
+            arg if arg.starts_with("--emit=") => {
+                return Err(format!("Unknown --emit target: {} (expected: bytecode)", &arg["--emit=".len()..]));
+            }
+            "--no-color" => options.no_color = true,
+            "--set" => {
+                let arg = args.get(i + 1).ok_or("--set requires key=value")?;
+                options.config_overrides.push(parse_override(arg)?);
+                i += 1;
+            }
+            "--project" => {
+                let path = args.get(i + 1).ok_or("--project requires a path to project.toml")?;
+                options.project = Some(path.to_string());
+                i += 1;
+            }
+            arg if arg.starts_with("--trace-format=") => {
+                let name = &arg["--trace-format=".len()..];
+                options.trace_format = TraceFormat::parse(name)
+                    .ok_or_else(|| format!("Unknown --trace-format: {} (expected: compact, full, json)", name))?;
+            }
+            "--deny" => {
+                match args.get(i + 1) {
+                    Some(&"warnings") => options.deny_warnings = true,
+                    _ => return Err("--deny requires 'warnings'".to_string()),
                 }
+                i += 1;
             }
+            "--max-allocation" => {
+                let bytes = args
+                    .get(i + 1)
+                    .and_then(|n| n.parse().ok())
+                    .filter(|&n| n > 0)
+                    .ok_or("--max-allocation requires a positive number of bytes")?;
+                options.max_allocation = Some(bytes);
+                i += 1;
+            }
+            "--trace-filter" => {
+                let name = args.get(i + 1).ok_or("--trace-filter requires a function name")?;
+                options.trace.get_or_insert_with(TraceOptions::default).filter = Some(name.to_string());
+                i += 1;
+            }
+            "--trace-limit" => {
+                let limit = args
+                    .get(i + 1)
+                    .and_then(|n| n.parse().ok())
+                    .ok_or("--trace-limit requires a non-negative integer")?;
+                options.trace.get_or_insert_with(TraceOptions::default).limit = Some(limit);
+                i += 1;
+            }
+            "--" => {
+                options.program_args = args[i + 1..].iter().map(|arg| arg.to_string()).collect();
+                break;
+            }
+            arg if arg.starts_with("--") => return Err(format!("Unknown option: {}", arg)),
+            arg if path.is_none() => path = Some(arg),
+            arg => return Err(format!("Unexpected argument: {}", arg)),
         }
+        i += 1;
     }
-    
-    None
+    let path = path.ok_or("Missing source file")?;
+    Ok((options, path))
 }
 
-/// 加载单个源文件
-fn load_source_file(
-    path: &Path,
-    all_statements: &mut Vec<Stmt>,
-    loaded_files: &mut HashSet<PathBuf>,
-    project: Option<&ProjectConfig>,
-    locale: Locale,
-    resolver: &PackageResolver,
-) -> Result<(), String> {
-    let abs_path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
-    
-    // 检查是否已加载
-    if loaded_files.contains(&abs_path) {
-        return Ok(());
-    }
-    loaded_files.insert(abs_path.clone());
-    
-    // 读取文件
-    let source = fs::read_to_string(path)
-        .map_err(|e| format_message(messages::MSG_CLI_CANNOT_READ_FILE, locale, &[&display_path(path), &e.to_string()]))?;
-    
-    // 解析
-    let program = parse_source(&source, locale)
-        .map_err(|e| format_message(messages::MSG_CLI_PARSE_FAILED, locale, &[&display_path(path), &e]))?;
-    
-    // 递归加载依赖
-    for import in &program.imports {
-        if let Ok(resolved) = resolver.resolve(import) {
-            if let Some(source_path) = &resolved.source_path {
-                if resolved.kind == ImportKind::Project || resolved.kind == ImportKind::StdSource {
-                    if source_path.is_file() {
-                        load_source_file(source_path, all_statements, loaded_files, project, locale, resolver)?;
-                    } else if source_path.is_dir() {
-                        load_directory(source_path, all_statements, loaded_files, project, locale, resolver)?;
-                    } else if let Some(parent) = source_path.parent() {
-                        if parent.is_dir() {
-                            load_directory(parent, all_statements, loaded_files, project, locale, resolver)?;
-                        }
-                    }
-                }
+/// 解析 `bundle` 命令的参数，返回选项和主文件路径
+///
+/// 输入可以是源文件或项目目录（入口为源码目录下的 main.q）；默认输出为当前目录下与入口同名的可执行文件。
+/// 只接受编译选项，运行时选项在打包的程序中不起作用
+fn parse_bundle_args(args: &[&str]) -> Result<(CliOptions, String), String> {
+    let mut output = None;
+    let mut compress = false;
+    let mut rest = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        match args[i] {
+            "-o" | "--output" => {
+                output = Some(PathBuf::from(args.get(i + 1).ok_or("-o requires an output file")?));
+                i += 1;
             }
+            "--compress" => compress = true,
+            arg => rest.push(arg),
         }
+        i += 1;
     }
-    
-    // 添加语句（排除 package 和 import，只要类型和函数定义）
-    for stmt in program.statements {
-        match &stmt {
-            Stmt::Package { .. } | Stmt::Import { .. } => {
-                // 跳过 package 和 import 声明
-            }
-            _ => {
-                all_statements.push(stmt);
-            }
-        }
+    let (options, input) = parse_run_args(&rest)?;
+    if options.trace.is_some() || options.deterministic || options.safe_vm || options.max_allocation.is_some()
+        || options.emit_bytecode || !options.program_args.is_empty()
+    {
+        return Err("bundle only accepts compile options (-O, --strict-types, --deny warnings, --set, --project)".to_string());
     }
-    
-    Ok(())
+
+    let input = Path::new(input);
+    let (path, name) = if input.is_dir() {
+        let project = Project::locate(input, options.project.as_deref().map(Path::new), &options.config_overrides)
+            .map_err(|errors| errors.to_string())?;
+        let config = project.config().ok_or_else(|| format!("{} not found in {}", PROJECT_FILE, display_path(input)))?;
+        let name = if config.name.is_empty() { "main" } else { config.name.as_str() };
+        (config.root_dir.join(&config.src_dir).join(format!("main.{}", SOURCE_EXTENSION)), name.to_string())
+    } else {
+        let name = input.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        (input.to_path_buf(), name)
+    };
+    let output = output.unwrap_or_else(|| PathBuf::from(format!("{}{}", name, env::consts::EXE_SUFFIX)));
+    let path = path.to_string_lossy().into_owned();
+    Ok((CliOptions { bundle: Some(output), compress, ..options }, path))
 }
 
-/// 加载目录下所有源文件
-fn load_directory(
-    dir: &Path,
-    all_statements: &mut Vec<Stmt>,
-    loaded_files: &mut HashSet<PathBuf>,
-    project: Option<&ProjectConfig>,
-    locale: Locale,
-    resolver: &PackageResolver,
-) -> Result<(), String> {
-    if !dir.is_dir() {
-        return Ok(());
-    }
-    
-    let entries = fs::read_dir(dir)
-        .map_err(|e| format!("无法读取目录 {:?}: {}", dir, e))?;
-    
-    for entry in entries.flatten() {
-        let path = entry.path();
-        if path.is_file() && path.extension().map(|e| e == SOURCE_EXTENSION).unwrap_or(false) {
-            load_source_file(&path, all_statements, loaded_files, project, locale, resolver)?;
+/// 命令的结果，由 [`exit`] 统一转换为进程退出码
+///
+/// 错误在发生处输出（诊断需要本次编译的源码快照），用法错误由 [`exit`] 输出
+enum Outcome {
+    /// 正常结束，值为程序指定的退出码（main 返回的 int，否则为 0）
+    Exit(i32),
+    /// 程序运行时出错：未捕获的异常、panic 等
+    RuntimeError,
+    /// 程序没有通过编译：项目配置、语法、导入、类型或代码生成错误
+    CompileError,
+    /// 命令行用法错误，值为错误信息
+    Usage(String),
+    /// 写不了输出文件（锁文件、打包的程序、耗时统计）
+    OutputError,
+    /// 实现内部的错误，已由钩子报告
+    Internal,
+}
+
+impl Outcome {
+    const SUCCESS: Outcome = Outcome::Exit(0);
+
+    /// 进程退出码（见 `help` 的 Exit codes）
+    fn code(&self) -> i32 {
+        match self {
+            Outcome::Exit(code) => *code,
+            Outcome::RuntimeError => 1,
+            Outcome::CompileError => 2,
+            Outcome::Usage(_) => 64,
+            Outcome::OutputError => 74,
+            Outcome::Internal => ice::EXIT_CODE,
         }
     }
-    
-    Ok(())
 }
 
-/// 运行源代码（独立文件模式，用于 REPL）
-fn run(source: &str, locale: Locale) -> Result<(), String> {
-    // REPL 模式下不检查 main 函数和顶级代码限制
-    run_with_context(source, locale, CompileContext::default(), false, None, None)
+/// 唯一的退出点：输出用法错误并以结果对应的退出码结束进程
+fn exit(outcome: Outcome) -> ! {
+    if let Outcome::Usage(message) = &outcome {
+        eprintln!("{}", message);
+        eprintln!("Run '{} help' for the list of commands and options.", env!("CARGO_BIN_NAME"));
+    }
+    process::exit(outcome.code())
 }
 
-/// 运行源代码（带上下文）
-fn run_with_context(
-    source: &str, 
-    locale: Locale, 
-    context: CompileContext, 
-    type_check: bool,
-    extra_statements: Option<Vec<Stmt>>,
-    main_file: Option<&Path>,
-) -> Result<(), String> {
-    // 解析主程序
-    let mut program = parse_source(source, locale)
-        .map_err(|e| {
-            let label = format_message(messages::MSG_CLI_SYNTAX_ERROR, locale, &[]);
-            format!("{}\n{}", label, e)
-        })?;
-    
-    // 如果有额外的语句（来自依赖），添加到程序开头
-    if let Some(mut extra) = extra_statements {
-        // 将依赖的语句放在主程序语句之前
-        extra.append(&mut program.statements);
-        program.statements = extra;
-    }
-    
-    // 类型检查（可选）
-    if type_check {
-        let mut type_checker = TypeChecker::with_context(context);
-        type_checker.check_program(&program).map_err(|errors| {
-            let label = format_message(messages::MSG_CLI_TYPE_ERROR, locale, &[]);
-            let error_list = errors
-                .iter()
-                .map(|e| format!("  [{}:{}] {}", e.span.line, e.span.column, e))
-                .collect::<Vec<_>>()
-                .join("\n");
-            format!("{}\n{}", label, error_list)
-        })?;
-        
-        // 收集泛型定义用于单态化
-        let mut monomorphizer = Monomorphizer::new();
-        monomorphizer.collect_definitions(&program);
-        
-        // 处理所有待单态化的请求
-        monomorphizer.process_all();
-    }
-    
-    // 编译
-    let mut compiler = Compiler::new(locale);
-    let chunk = compiler.compile(&program).map_err(|errors| {
-        let label = format_message(messages::MSG_CLI_COMPILE_ERROR, locale, &[]);
-        let error_list = errors
-            .iter()
-            .map(|e| format!("  [{}:{}] {}", e.span.line, e.span.column, e.message))
-            .collect::<Vec<_>>()
-            .join("\n");
-        format!("{}\n{}", label, error_list)
-    })?;
-    
-    // 执行（从 main 函数开始）
-    let chunk_arc = std::sync::Arc::new(chunk);
-    let mut vm = VM::new(chunk_arc, locale);
-    vm.run().map_err(|e| {
-        let label = format_message(messages::MSG_CLI_RUNTIME_ERROR, locale, &[]);
-        format!("{}\n  [line {}] {}", label, e.line, e.message)
-    })?;
-    
-    Ok(())
+/// 输出诊断，源码片段取自本次编译的快照
+fn report(diagnostics: &Diagnostics, project: &Project, locale: Locale) {
+    eprintln!("{}", diagnostics.render(locale, &mut |file| file.and_then(|file| project.source(file))));
 }
 
-/// 构建编译上下文
-fn build_compile_context(file_path: &Path) -> CompileContext {
-    build_compile_context_with_project(file_path).0
+/// `config` 命令：输出生效的项目配置及每项的来源
+///
+/// 参数为若干 `--set key=value`、可选的 `--project <path>` 和可选的起始目录（默认当前目录）
+fn show_config(args: &[&str], locale: Locale) -> Outcome {
+    let (overrides, project_flag, dir) = match parse_config_args(args) {
+        Ok(parsed) => parsed,
+        Err(e) => return Outcome::Usage(e),
+    };
+    let dir = match dir.map_or_else(env::current_dir, Ok) {
+        Ok(dir) => dir,
+        Err(e) => return Outcome::Usage(format!("cannot determine the current directory: {}", e)),
+    };
+    let project = match Project::locate(&dir, project_flag.map(Path::new), &overrides) {
+        Ok(project) => project,
+        Err(errors) => {
+            report(&errors, &Project::standalone(), locale);
+            return Outcome::CompileError;
+        }
+    };
+    let Some(config) = project.config() else {
+        let abs_dir = project.sources().paths().canonicalize(&dir);
+        return Outcome::Usage(format!("{} not found in {} or any parent directory", PROJECT_FILE, display_path(&abs_dir)));
+    };
+    if !project.warnings().is_empty() {
+        report(project.warnings(), &project, locale);
+    }
+    println!("{}", config.describe());
+    Outcome::SUCCESS
 }
 
-/// 构建编译上下文（同时返回项目配置）
-fn build_compile_context_with_project(file_path: &Path) -> (CompileContext, Option<ProjectConfig>) {
-    // 获取文件的绝对路径
-    let abs_path = fs::canonicalize(file_path).unwrap_or_else(|_| file_path.to_path_buf());
-    
-    // 尝试查找 project.toml
-    if let Some(project_root) = find_project_root(&abs_path) {
-        let project_file = project_root.join(PROJECT_FILE);
-        if let Ok(project) = ProjectConfig::load(&project_file) {
-            // 计算期望包名
-            let expected_package = compute_expected_package(&project, &abs_path);
-            
-            let context = CompileContext {
-                is_entry_file: true,
-                expected_package,
-                standalone_mode: false,
-            };
-            return (context, Some(project));
+/// 解析 `config` 命令的参数，返回 (覆盖项, --project, 起始目录)
+#[allow(clippy::type_complexity)]
+fn parse_config_args<'a>(args: &[&'a str]) -> Result<(Vec<(String, String)>, Option<&'a str>, Option<PathBuf>), String> {
+    let mut overrides = Vec::new();
+    let mut project_flag = None;
+    let mut dir = None;
+    let mut i = 0;
+    while i < args.len() {
+        match args[i] {
+            "--set" => {
+                let arg = args.get(i + 1).ok_or("--set requires key=value")?;
+                overrides.push(parse_override(arg)?);
+                i += 1;
+            }
+            "--project" => {
+                project_flag = Some(*args.get(i + 1).ok_or("--project requires a path to project.toml")?);
+                i += 1;
+            }
+            arg if arg.starts_with("--") => return Err(format!("Unknown option: {}", arg)),
+            arg if dir.is_none() => dir = Some(PathBuf::from(arg)),
+            arg => return Err(format!("Unexpected argument: {}", arg)),
         }
+        i += 1;
     }
-    
-    // 独立文件模式
-    let context = CompileContext {
-        is_entry_file: true,
-        expected_package: None,
-        standalone_mode: true,
-    };
-    (context, None)
+    Ok((overrides, project_flag, dir))
 }
 
-/// 运行文件
-fn run_file(path: &str, locale: Locale) {
+/// 运行文件（`build` 命令只编译）
+fn run_file(path: &str, locale: Locale, options: &CliOptions) -> Outcome {
     // 检查文件后缀
     let expected_ext = format!(".{}", SOURCE_EXTENSION);
     if !path.ends_with(&expected_ext) {
-        let msg = format_message(
-            messages::MSG_CLI_INVALID_EXTENSION, 
-            locale, 
-            &[path, SOURCE_EXTENSION]
-        );
-        eprintln!("{}", msg);
-        process::exit(1);
+        return Outcome::Usage(format_message(messages::MSG_CLI_INVALID_EXTENSION, locale, &[path, SOURCE_EXTENSION]));
     }
     
-    let source = match fs::read_to_string(path) {
+    if options.timings || options.timings_json.is_some() {
+        timings::start();
+    }
+    // 实现内部的 panic 已由钩子报告为内部错误
+    let outcome = ice::catch(|| compile_and_run(Path::new(path), locale, options)).unwrap_or(Outcome::Internal);
+    if let Some(collected) = timings::finish() {
+        if options.timings {
+            eprint!("{}", collected.render_table());
+        }
+        if let Some(out) = &options.timings_json {
+            if let Err(e) = fs::write(out, collected.to_json()) {
+                eprintln!("cannot write {}: {}", out, e);
+                return Outcome::OutputError;
+            }
+        }
+    }
+    outcome
+}
+
+/// 加载、编译并运行主程序（错误已输出）
+fn compile_and_run(file_path: &Path, locale: Locale, options: &CliOptions) -> Outcome {
+    let project = match Project::locate(file_path, options.project.as_deref().map(Path::new), &options.config_overrides) {
+        Ok(project) => project,
+        Err(errors) => {
+            report(&errors, &Project::standalone(), locale);
+            return Outcome::CompileError;
+        }
+    };
+    
+    // 主文件和依赖文件都通过快照读取
+    let source = match project.sources().read(file_path) {
         Ok(content) => content,
         Err(_) => {
-            let msg = format_message(messages::MSG_CLI_FILE_NOT_FOUND, locale, &[path]);
-            eprintln!("{}", msg);
-            process::exit(1);
+            return Outcome::Usage(format_message(messages::MSG_CLI_FILE_NOT_FOUND, locale, &[&file_path.to_string_lossy()]));
         }
     };
+    if !project.warnings().is_empty() {
+        report(project.warnings(), &project, locale);
+    }
     
-    // 构建编译上下文
-    let file_path = Path::new(path);
-    let (context, project) = build_compile_context_with_project(file_path);
+    // 固定密钥必须在创建任何 map 之前设置
+    if options.deterministic {
+        set_deterministic_hashing(true);
+    }
+    if options.safe_vm {
+        set_safe_vm(true);
+    }
+    if let Some(bytes) = options.max_allocation {
+        set_allocation_limit(bytes);
+    }
     
-    // 先解析主程序以获取 imports
-    let main_program = match parse_source(&source, locale) {
-        Ok(p) => p,
-        Err(e) => {
-            let label = format_message(messages::MSG_CLI_SYNTAX_ERROR, locale, &[]);
-            eprintln!("{} {}\n{}", label, path, e);
-            process::exit(1);
+    let compile_options = CompileOptions {
+        locale,
+        file: Some(file_path.to_path_buf()),
+        project: Some(project.clone()),
+        optimize: options.optimize,
+        strict_types: options.strict_types,
+        deny_warnings: options.deny_warnings,
+    };
+    let compiled = match compile_with_warnings(&source, &compile_options) {
+        Ok(compiled) => compiled,
+        Err(errors) => {
+            report(&errors, &project, locale);
+            return Outcome::CompileError;
         }
     };
+    if !compiled.warnings.is_empty() {
+        report(&compiled.warnings, &project, locale);
+    }
     
-    // 加载所有依赖
-    let extra_statements = match load_dependencies(&main_program, file_path, project.as_ref(), locale) {
-        Ok(stmts) => Some(stmts),
+    if options.emit_bytecode {
+        print!("{}", compiled.chunk.disassemble());
+        return Outcome::SUCCESS;
+    }
+    if options.build {
+        return match project.write_build_lock(file_path) {
+            Ok(_) => Outcome::SUCCESS,
+            Err(e) => {
+                eprintln!("{}", e);
+                Outcome::OutputError
+            }
+        };
+    }
+    if let Some(output) = &options.bundle {
+        let written = env::current_exe()
+            .map_err(|e| format!("cannot locate the runtime: {}", e))
+            .and_then(|runtime| bundle::write_bundle(&runtime, &compiled.chunk, output, options.compress));
+        return match written {
+            Ok(()) => Outcome::SUCCESS,
+            Err(e) => {
+                eprintln!("{}", e);
+                Outcome::OutputError
+            }
+        };
+    }
+    
+    let run_options = qlang::RunOptions { locale, trace: options.trace.clone(), args: options.program_args.clone() };
+    let mut load_source = |file: Option<&str>| file.and_then(|file| project.source(file));
+    run_program(compiled.chunk, &run_options, options.trace_format, use_color(options.no_color), &mut load_source)
+}
+
+/// 执行程序（从 main 函数开始，错误已输出）
+///
+/// `main` 返回 int 时以它为退出码，优先于其他规则：协程中未捕获的错误只输出，不改变退出码。
+/// `Os.exit(code)` 与 main 返回 code 相同
+fn run_program(
+    chunk: Chunk,
+    run_options: &qlang::RunOptions,
+    trace_format: TraceFormat,
+    color: bool,
+    load_source: &mut dyn FnMut(Option<&str>) -> Option<String>,
+) -> Outcome {
+    let has_main = chunk.get_named_function("main").is_some();
+    let value = match run_chunk(chunk, run_options) {
+        Ok(value) => value,
+        Err(RuntimeError { exit_code: Some(code), .. }) => return Outcome::Exit(code),
         Err(e) => {
-            let label = format_message(messages::MSG_CLI_IMPORT_ERROR, locale, &[]);
-            eprintln!("{}\n  {}", label, e);
-            process::exit(1);
+            eprintln!("{}", render_error(&e, trace_format, color, load_source));
+            return Outcome::RuntimeError;
         }
     };
-    
-    if let Err(e) = run_with_context(&source, locale, context, true, extra_statements, Some(file_path)) {
-        eprintln!("{}", e);
-        process::exit(1);
+    match value.as_int() {
+        Some(code) if has_main => i32::try_from(code).map(Outcome::Exit).unwrap_or_else(|_| {
+            eprintln!("main returned exit code {} outside the i32 range", code);
+            Outcome::RuntimeError
+        }),
+        _ => Outcome::SUCCESS,
     }
 }
 
+/// 运行打包在可执行文件中的程序，不是打包的程序时返回 None
+///
+/// 命令行参数原样交给程序（Os.args），源文件不随程序打包，栈追踪中没有源码片段。
+/// 打包的程序损坏或者不能在这个运行时上加载时与编译错误相同
+fn run_bundled(args: &[String]) -> Option<Outcome> {
+    let exe = env::current_exe().ok()?;
+    let chunk = match bundle::read_bundle(&exe) {
+        Ok(chunk) => chunk?,
+        Err(e) => {
+            eprintln!("{}", e);
+            return Some(Outcome::CompileError);
+        }
+    };
+    let run_options = qlang::RunOptions { args: args.to_vec(), ..qlang::RunOptions::default() };
+    let color = use_color(false);
+    Some(ice::catch(|| run_program(chunk, &run_options, TraceFormat::default(), color, &mut |_| None)).unwrap_or(Outcome::Internal))
+}
+
 /// REPL 交互模式
 fn repl(locale: Locale) {
     use std::io::{self, Write};
@@ -485,26 +454,39 @@ fn repl(locale: Locale) {
     println!("{} {} REPL", LANG_NAME, VERSION);
     println!("Type 'exit' to quit.\n");
     
+    let mut session = repl::Repl::new(locale);
+    let mut input = String::new();
     loop {
-        print!("> ");
+        // 括号未闭合时显示续行提示符
+        print!("{}", if input.is_empty() { "> " } else { "..> " });
         io::stdout().flush().unwrap();
         
         let mut line = String::new();
-        if io::stdin().read_line(&mut line).is_err() {
-            break;
+        match io::stdin().read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
         }
         
-        let line = line.trim();
-        if line.is_empty() {
-            continue;
+        if input.is_empty() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            if trimmed == "exit" || trimmed == "quit" {
+                break;
+            }
         }
-        if line == "exit" || line == "quit" {
-            break;
+        input.push_str(&line);
+        if repl::is_incomplete(&input) {
+            continue;
         }
         
-        if let Err(e) = run(line, locale) {
-            eprintln!("{}", e);
+        match session.eval_or_reset(&input) {
+            Ok(Some(value)) => println!("{}", repl::display_value(&value)),
+            Ok(None) => {}
+            Err(e) => eprintln!("{}", e),
         }
+        input.clear();
     }
 }
 
@@ -515,12 +497,52 @@ fn print_help(locale: Locale) {
     println!();
     println!("Commands:");
     println!("  run <file>     Run a source file");
+    println!("    --emit=bytecode      Print the compiled bytecode instead of running it");
+    println!("    -O                   Fold constant expressions and drop unreachable code");
+    println!("    --trace              Print each executed instruction to stderr");
+    println!("    --trace-filter <fn>  Only trace instructions inside <fn> (methods: Type::method)");
+    println!("    --trace-limit <n>    Stop tracing after <n> instructions");
+    println!("    --trace-format=<f>   Stack trace style for uncaught errors: compact, full (default), json");
+    println!("    --no-color           Disable colored output (also honors NO_COLOR)");
+    println!("    --deterministic      Use a fixed map hash seed (stable iteration order; not for untrusted input)");
+    println!("    --safe-vm            Run extra checks whose cost grows with the data (e.g. binarySearch on unsorted arrays)");
+    println!("    --deny warnings      Treat type checker warnings as errors");
+    println!("    --strict-types       Report fields without a type annotation (implicit any) as errors");
+    println!("                         (also [build] strict-types = true in project.toml)");
+    println!("    --timings            Print the time spent in each compiler phase and file to stderr");
+    println!("    --timings-json <f>   Write the phase timings to <f> as JSON");
+    println!("    --max-allocation <n> Refuse single allocations larger than <n> bytes (default: available memory)");
+    println!("    -- <args>            Pass the remaining arguments to the program (Os.args())");
+    println!("    --set <key=value>    Override a project.toml setting (e.g. project.src=lib)");
+    println!("    --project <path>     Use this project.toml (or its directory) instead of searching upwards");
+    println!("                         (also QLANG_PROJECT=<path>)");
+    println!("  build <file>   Compile a source file without running it and write .qcache/build.lock");
+    println!("                 (accepts the compile options of run)");
+    println!("  bundle <file|dir> Compile a program into a self-contained executable for this platform");
+    println!("                 (a directory bundles the project's main.q; accepts the compile options of run)");
+    println!("    -o <file>            Output file (default: the entry name in the current directory)");
+    println!("    --compress           Compress the embedded program");
+    println!("  config [dir]   Print the effective project configuration and where each value comes from");
+    println!("    --set <key=value>    Apply an override before printing");
+    println!("    --project <path>     Use this project.toml instead of searching upwards");
+    println!("  grammar        Print the language grammar");
+    println!("    --format=ebnf        W3C-style EBNF (default; usable by railroad diagram tools)");
     println!("  repl           Start interactive mode");
     println!("  help           Show this help message");
     println!("  version        Show version information");
     println!();
     println!("Options:");
     println!("  --lang <en|zh> Set language (default: en)");
+    println!();
+    println!("Exit codes:");
+    println!("  0              Success");
+    println!("  1              Uncaught runtime error");
+    println!("  2              Compile error (syntax, import, type or project configuration)");
+    println!("  64             Invalid command line usage");
+    println!("  70             Internal compiler or runtime error");
+    println!("  74             Cannot write an output file");
+    println!("  An int returned from main is used as the exit code and takes precedence over errors");
+    println!("  reported by other goroutines.");
 }
 
 /// 打印版本信息
@@ -530,7 +552,11 @@ fn print_version(locale: Locale) {
 }
 
 fn main() {
+    ice::install_hook();
     let args: Vec<String> = env::args().collect();
+    if let Some(outcome) = run_bundled(&args[1..]) {
+        exit(outcome);
+    }
     
     // 默认语言
     let mut locale = Locale::En;
@@ -552,17 +578,43 @@ fn main() {
     // 剩余参数
     let remaining: Vec<&str> = args[i..].iter().map(|s| s.as_str()).collect();
     
-    match remaining.as_slice() {
-        [] | ["repl"] => repl(locale),
-        ["help"] | ["--help"] | ["-h"] => print_help(locale),
-        ["version"] | ["--version"] | ["-v"] => print_version(locale),
-        ["run", path] => run_file(path, locale),
-        [path] if path.ends_with(&format!(".{}", SOURCE_EXTENSION)) => {
-            run_file(path, locale)
+    let outcome = match remaining.as_slice() {
+        [] | ["repl"] => {
+            repl(locale);
+            Outcome::SUCCESS
         }
-        _ => {
+        ["help"] | ["--help"] | ["-h"] => {
             print_help(locale);
-            process::exit(1);
+            Outcome::SUCCESS
         }
-    }
+        ["version"] | ["--version"] | ["-v"] => {
+            print_version(locale);
+            Outcome::SUCCESS
+        }
+        ["run", args @ ..] => match parse_run_args(args) {
+            Ok((options, path)) => run_file(path, locale, &options),
+            Err(e) => Outcome::Usage(e),
+        },
+        ["build", args @ ..] => match parse_run_args(args) {
+            Ok((options, path)) => run_file(path, locale, &CliOptions { build: true, ..options }),
+            Err(e) => Outcome::Usage(e),
+        },
+        ["bundle", args @ ..] => match parse_bundle_args(args) {
+            Ok((options, path)) => run_file(&path, locale, &options),
+            Err(e) => Outcome::Usage(e),
+        },
+        ["grammar", args @ ..] => match args {
+            [] | ["--format=ebnf"] => {
+                print!("{}", parser::grammar::to_ebnf());
+                Outcome::SUCCESS
+            }
+            _ => Outcome::Usage(format!("Unsupported grammar option: {} (expected --format=ebnf)", args.join(" "))),
+        },
+        ["config", args @ ..] => show_config(args, locale),
+        [path] if path.ends_with(&format!(".{}", SOURCE_EXTENSION)) => {
+            run_file(path, locale, &CliOptions::default())
+        }
+        [command, ..] => Outcome::Usage(format!("Unknown command: {}", command)),
+    };
+    exit(outcome)
 }
@@ -0,0 +1,491 @@
+//! 依赖加载
+//!
+//! 从主程序的 import 出发读取并解析依赖的源文件，合并成一个程序。
+//! 同一个目录中还没加载的文件并行解析；一个文件出错时跳过它继续加载其余文件，最后一起报告。
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::config::{PROJECT_FILE, SOURCE_EXTENSION};
+use crate::diagnostics::{display_path, Diagnostic, Diagnostics, Stage};
+use crate::i18n::{format_message, messages, Locale};
+use crate::parser::{self, ParseCache, Program, Stmt};
+use crate::timings;
+use crate::ice;
+use crate::parse_source_recovering;
+use super::{compute_expected_package, ImportKind, PackageResolver, PathCache, ProjectConfig, SourceStore};
+
+/// 已加载的依赖文件
+#[derive(Debug, Default)]
+pub struct LoadedSources {
+    /// 所有依赖文件的顶层语句（按加载顺序）
+    pub statements: Vec<Stmt>,
+    /// 每个文件的 (路径, 语句数)，顺序与 statements 一致
+    pub files: Vec<(PathBuf, usize)>,
+}
+
+/// 加载依赖时的状态
+///
+/// 依赖文件的语句合并成一个程序，文件之间的引用本身不要求先后顺序，
+/// 但导入环说明两个文件互相需要对方，总是报错（只引用类型也不例外）。
+/// 同一目录中的文件属于同一个包，它们之间的互相导入和导入自己所在的包都不构成环。
+#[derive(Debug, Default)]
+struct ImportState {
+    /// 已经开始加载的文件（规范化路径）
+    loaded: HashSet<PathBuf>,
+    /// 当前的导入链，从入口文件开始
+    chain: Vec<PathBuf>,
+    /// 报告导入环时路径相对于这个目录显示
+    root: PathBuf,
+    /// 项目的解析缓存（不在项目中时为 None）
+    cache: Option<ParseCache>,
+    /// 本次编译读取的源文件快照
+    store: Arc<SourceStore>,
+    /// 并行预先解析、还没有加载的文件（规范化路径 -> AST）
+    parsed: HashMap<PathBuf, Result<Program, Vec<Diagnostic>>>,
+    /// 已经发现的错误，出错的文件跳过，其余文件继续加载
+    errors: Vec<Diagnostic>,
+}
+
+impl ImportState {
+    /// 从 chain 中 path 的位置到 path 自身组成的环，如 `a.q -> b.q -> a.q`
+    fn cycle_to(&self, path: &Path) -> Option<String> {
+        let start = self.chain.iter().position(|p| p == path)?;
+        let files: Vec<String> = self.chain[start..]
+            .iter()
+            .chain(std::iter::once(&path.to_path_buf()))
+            .map(|p| display_path(p.strip_prefix(&self.root).unwrap_or(p)))
+            .collect();
+        Some(files.join(" -> "))
+    }
+}
+
+/// 从快照读取并解析一个源文件，项目中的文件先查解析缓存
+fn read_and_parse(path: &Path, locale: Locale, store: &SourceStore, cache: Option<&ParseCache>) -> Result<Program, Vec<Diagnostic>> {
+    timings::file(&display_path(path), || read_and_parse_file(path, locale, store, cache))
+}
+
+fn read_and_parse_file(path: &Path, locale: Locale, store: &SourceStore, cache: Option<&ParseCache>) -> Result<Program, Vec<Diagnostic>> {
+    ice::set_file(&display_path(path));
+    let source = store.read(path).map_err(|e| {
+        let message = format_message(messages::MSG_CLI_CANNOT_READ_FILE, locale, &[&display_path(path), &e.to_string()]);
+        vec![Diagnostic::error(Stage::Import, message)]
+    })?;
+    if let Some(program) = cache.and_then(|cache| cache.get(&source)) {
+        timings::mark_cached();
+        return Ok(program);
+    }
+    let (program, errors) = parse_source_recovering(&source, locale);
+    if !errors.is_empty() {
+        let file = display_path(path);
+        return Err(errors.into_iter().map(|e| e.in_file(Some(file.clone()))).collect());
+    }
+    if let Some(cache) = cache {
+        cache.put(&source, &program);
+    }
+    Ok(program)
+}
+
+/// 解析线程的栈大小，与主线程相同，嵌套很深的源码不会在解析线程上溢出
+const PARSE_THREAD_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+/// 并行读取并解析多个源文件，结果的顺序与 paths 相同
+fn parse_files_parallel(
+    paths: &[PathBuf],
+    locale: Locale,
+    store: &SourceStore,
+    cache: Option<&ParseCache>,
+) -> Vec<Result<Program, Vec<Diagnostic>>> {
+    let workers = std::thread::available_parallelism().map_or(1, |n| n.get()).min(paths.len());
+    if workers <= 1 {
+        return paths.iter().map(|path| read_and_parse(path, locale, store, cache)).collect();
+    }
+    // 按顺序切成连续的几段，每个线程解析一段，再按段的顺序拼接
+    // 计时时每个线程单独收集，按段的顺序合并到当前线程
+    let chunk_size = paths.len().div_ceil(workers);
+    let collect_timings = timings::is_enabled();
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = paths
+            .chunks(chunk_size)
+            .map(|chunk| {
+                std::thread::Builder::new()
+                    .stack_size(PARSE_THREAD_STACK_SIZE)
+                    .spawn_scoped(scope, move || {
+                        if collect_timings {
+                            timings::start();
+                        }
+                        let results = chunk.iter().map(|path| read_and_parse(path, locale, store, cache)).collect::<Vec<_>>();
+                        (results, timings::finish())
+                    })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .zip(paths.chunks(chunk_size))
+            .flat_map(|(handle, chunk)| match handle {
+                Ok(handle) => {
+                    let (results, collected) = handle.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+                    if let Some(collected) = collected {
+                        timings::merge(collected);
+                    }
+                    results
+                }
+                // 无法创建线程时在当前线程解析
+                Err(_) => chunk.iter().map(|path| read_and_parse(path, locale, store, cache)).collect(),
+            })
+            .collect()
+    })
+}
+
+/// 加载依赖文件并合并 AST
+///
+/// 一个文件出错时跳过该文件继续加载其余文件，最后一起返回所有错误（语法错误在前，导入错误在后）
+pub fn load_dependencies(
+    main_program: &Program,
+    main_file: &Path,
+    shared_project: Option<&Arc<ProjectConfig>>,
+    locale: Locale,
+    store: &Arc<SourceStore>,
+) -> Result<LoadedSources, Diagnostics> {
+    let mut all_statements = LoadedSources::default();
+    let project = shared_project.map(Arc::as_ref);
+    
+    // 标记主文件已加载，导入链从主文件开始
+    let main_path = store.paths().canonicalize(main_file);
+    let mut imports = ImportState {
+        root: match project {
+            Some(project) => project.root_dir.clone(),
+            None => main_path.parent().map(Path::to_path_buf).unwrap_or_default(),
+        },
+        cache: project.map(|project| ParseCache::new(&project.root_dir)),
+        store: store.clone(),
+        ..ImportState::default()
+    };
+    imports.loaded.insert(main_path.clone());
+    imports.chain.push(main_path);
+    
+    // 创建包解析器（与加载器共用路径缓存），读取本地路径依赖
+    let mut resolver = PackageResolver::new(shared_project.cloned());
+    resolver.set_path_cache(store.paths().clone());
+    resolver.load_path_dependencies().map_err(|e| Diagnostic::error(Stage::Import, e))?;
+    
+    // 处理主程序的 imports
+    for import in &main_program.imports {
+        match resolver.resolve(import) {
+            Ok(resolved) => {
+                match resolved.kind {
+                    ImportKind::StdBuiltin => {
+                        // 内置标准库，不需要加载源文件
+                        // 类型和函数由 VM 内置提供
+                    }
+                    ImportKind::StdSource => {
+                        // Q 语言标准库源文件
+                        if let Some(source_path) = &resolved.source_path {
+                            if imports.store.paths().exists(source_path) {
+                                load_source_file(
+                                    source_path,
+                                    &mut all_statements,
+                                    &mut imports,
+                                    project,
+                                    locale,
+                                    &resolver,
+                                );
+                            }
+                        }
+                    }
+                    ImportKind::Project | ImportKind::External => {
+                        // 项目内部包或依赖提供的包
+                        // import com.test.demo.models.User -> models/User.q，找不到时加载 models 目录
+                        match &resolved.source_path {
+                            Some(source_path) => load_import_source(
+                                source_path,
+                                &mut all_statements,
+                                &mut imports,
+                                project,
+                                locale,
+                                &resolver,
+                            ),
+                            None => {
+                                imports.errors.push(Diagnostic::error(Stage::Import, format!(
+                                    "package {} is not provided by this project or any of its dependencies",
+                                    import.path,
+                                )));
+                            }
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                // 导入解析失败，尝试智能查找
+                // 例如: import com.test.demo.models.User
+                // 可能是 models/function.q 中的 User 类
+                if let Some(proj) = project {
+                    if let Some(found_path) = find_import_source(import, proj, store.paths()) {
+                        load_source_file(
+                            &found_path,
+                            &mut all_statements,
+                            &mut imports,
+                            project,
+                            locale,
+                            &resolver,
+                        );
+                    } else {
+                        imports.errors.push(Diagnostic::error(Stage::Import, e));
+                    }
+                } else {
+                    imports.errors.push(Diagnostic::error(Stage::Import, e));
+                }
+            }
+        }
+    }
+    
+    if imports.errors.is_empty() {
+        Ok(all_statements)
+    } else {
+        imports.errors.sort_by_key(|e| e.stage != Stage::Syntax);
+        Err(imports.errors.into())
+    }
+}
+
+/// 智能查找导入源文件
+fn find_import_source(import: &parser::ast::ImportDecl, project: &ProjectConfig, paths: &PathCache) -> Option<PathBuf> {
+    use parser::ast::ImportTarget;
+    
+    // 获取导入路径的各部分
+    let full_path = match &import.target {
+        ImportTarget::Single(name) => format!("{}.{}", import.path, name),
+        _ => import.path.clone(),
+    };
+    
+    // 去除项目包前缀
+    let relative_path = full_path.strip_prefix(&project.package)
+        .and_then(|s| s.strip_prefix('.'))
+        .unwrap_or(&full_path);
+    
+    // 将点分隔的路径转换为目录结构
+    let parts: Vec<&str> = relative_path.split('.').collect();
+    if parts.is_empty() {
+        return None;
+    }
+    
+    let src_dir = project.root_dir.join(&project.src_dir);
+    
+    // 尝试不同的文件位置
+    // 1. 直接作为文件: models/User.q
+    // 2. 作为目录下的文件: models/function.q (包含 User)
+    
+    // 策略 1: 尝试 parent_path/*.q 加载整个包
+    if parts.len() >= 1 {
+        let dir_path: PathBuf = parts[..parts.len()-1].iter().collect();
+        let full_dir = src_dir.join(&dir_path);
+        if paths.is_dir(&full_dir) {
+            // 找到目录下的 .q 文件
+            if let Ok(entries) = paths.read_dir(&full_dir) {
+                if let Some(path) = entries.iter().find(|path| path.extension().map(|e| e == SOURCE_EXTENSION).unwrap_or(false)) {
+                    return Some(path.clone());
+                }
+            }
+        }
+    }
+    
+    None
+}
+
+/// 加载单个源文件，出错时把错误记入 imports.errors 并跳过该文件
+fn load_source_file(
+    path: &Path,
+    all_statements: &mut LoadedSources,
+    imports: &mut ImportState,
+    project: Option<&ProjectConfig>,
+    locale: Locale,
+    resolver: &PackageResolver,
+) {
+    let abs_path = imports.store.paths().canonicalize(path);
+    
+    // 导入链上的文件再次被导入即构成环（同一目录中的文件属于同一个包，互相导入不算）；
+    // 已加载完的文件直接跳过
+    if let Some(cycle) = imports.cycle_to(&abs_path) {
+        let same_package = imports.chain.last().and_then(|importer| importer.parent()) == abs_path.parent();
+        if !same_package {
+            imports.errors.push(Diagnostic::error(Stage::Import, format!("import cycle: {}", cycle)));
+        }
+        return;
+    }
+    if !imports.loaded.insert(abs_path.clone()) {
+        return;
+    }
+    
+    // 读取并解析（所在目录加载时可能已经并行解析过）
+    let parsed = match imports.parsed.remove(&abs_path) {
+        Some(parsed) => parsed,
+        None => read_and_parse(path, locale, &imports.store, imports.cache.as_ref()),
+    };
+    let program = match parsed {
+        Ok(parsed) => parsed,
+        Err(errors) => {
+            imports.errors.extend(errors);
+            return;
+        }
+    };
+    
+    // 依赖中的文件按依赖自己的 project.toml 检查包名
+    if let Some(dependency) = resolver.dependency_for(&abs_path) {
+        let expected = compute_expected_package(dependency, &abs_path);
+        if let (Some(actual), Some(expected)) = (&program.package, expected) {
+            if *actual != expected {
+                imports.errors.push(Diagnostic::error(Stage::Import, format!(
+                    "{}: package {} does not match {} expected by {}",
+                    display_path(path), actual, expected, display_path(&dependency.root_dir.join(PROJECT_FILE)),
+                )));
+                return;
+            }
+        }
+    }
+    
+    // 递归加载依赖
+    imports.chain.push(abs_path);
+    for import in &program.imports {
+        if let Ok(resolved) = resolver.resolve(import) {
+            if let Some(source_path) = &resolved.source_path {
+                load_import_source(source_path, all_statements, imports, project, locale, resolver);
+            }
+        }
+    }
+    imports.chain.pop();
+    
+    // 添加语句（排除 package 和 import，只要类型和函数定义）
+    let before = all_statements.statements.len();
+    for stmt in program.statements {
+        match &stmt {
+            Stmt::Package { .. } | Stmt::Import { .. } => {
+                // 跳过 package 和 import 声明
+            }
+            _ => {
+                all_statements.statements.push(stmt);
+            }
+        }
+    }
+    let count = all_statements.statements.len() - before;
+    all_statements.files.push((path.to_path_buf(), count));
+}
+
+/// 加载导入解析出的源文件：文件、目录，或者文件不存在时加载它所在的目录
+fn load_import_source(
+    source_path: &Path,
+    all_statements: &mut LoadedSources,
+    imports: &mut ImportState,
+    project: Option<&ProjectConfig>,
+    locale: Locale,
+    resolver: &PackageResolver,
+) {
+    let paths = imports.store.paths().clone();
+    if paths.is_file(source_path) {
+        load_source_file(source_path, all_statements, imports, project, locale, resolver)
+    } else if paths.is_dir(source_path) {
+        load_directory(source_path, all_statements, imports, project, locale, resolver)
+    } else {
+        match source_path.parent() {
+            Some(parent) if paths.is_dir(parent) => load_directory(parent, all_statements, imports, project, locale, resolver),
+            _ => {}
+        }
+    }
+}
+
+/// 加载目录下所有源文件
+fn load_directory(
+    dir: &Path,
+    all_statements: &mut LoadedSources,
+    imports: &mut ImportState,
+    project: Option<&ProjectConfig>,
+    locale: Locale,
+    resolver: &PackageResolver,
+) {
+    let cache = imports.store.paths().clone();
+    if !cache.is_dir(dir) {
+        return;
+    }
+    
+    // 同一个目录被多次导入时只列出一次
+    let entries = match cache.read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            imports.errors.push(Diagnostic::error(Stage::Import, format!("无法读取目录 {:?}: {}", dir, e)));
+            return;
+        }
+    };
+    
+    // 条目按文件名排序，合并后的语句顺序不随文件系统变化
+    let paths: Vec<PathBuf> = entries
+        .iter()
+        .filter(|path| path.extension().map(|e| e == SOURCE_EXTENSION).unwrap_or(false) && cache.is_file(path))
+        .filter(|path| !cache.try_canonicalize(path).is_ok_and(|abs_path| imports.chain.contains(&abs_path)))
+        .cloned()
+        .collect();
+    
+    // 先并行解析还没加载的文件，再按顺序逐个加载（加载时递归处理各自的导入）
+    let pending: Vec<(PathBuf, PathBuf)> = paths
+        .iter()
+        .map(|path| (path.clone(), cache.canonicalize(path)))
+        .filter(|(_, abs_path)| !imports.loaded.contains(abs_path) && !imports.parsed.contains_key(abs_path))
+        .collect();
+    let to_parse: Vec<PathBuf> = pending.iter().map(|(path, _)| path.clone()).collect();
+    let results = parse_files_parallel(&to_parse, locale, &imports.store, imports.cache.as_ref());
+    for ((_, abs_path), result) in pending.into_iter().zip(results) {
+        imports.parsed.insert(abs_path, result);
+    }
+    
+    for path in &paths {
+        load_source_file(path, all_statements, imports, project, locale, resolver);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+    
+    /// 50 个包、每个包 10 个文件：每个文件导入上一个包的全部文件和第一个包的 File0
+    fn layered_project(name: &str) -> PathBuf {
+        let root = env::temp_dir().join(format!("qlang_loader_{}_{}", name, process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join(PROJECT_FILE), "[project]\nname = \"app\"\npackage = \"com.app\"\n").unwrap();
+        for package in 0..50 {
+            let dir = root.join(format!("src/pkg{}", package));
+            fs::create_dir_all(&dir).unwrap();
+            for file in 0..10 {
+                let mut source = format!("package com.app.pkg{}\n\n", package);
+                if package > 0 {
+                    source += &format!("import com.app.pkg{}.*\nimport com.app.pkg0.File0\n\n", package - 1);
+                }
+                source += &format!("class File{} {{\n}}\n", file);
+                fs::write(dir.join(format!("File{}.q", file)), source).unwrap();
+            }
+        }
+        fs::write(root.join("src/main.q"), "package com.app\n\nimport com.app.pkg49.*\n\nfunc main() {\n}\n").unwrap();
+        fs::canonicalize(&root).unwrap()
+    }
+    
+    #[test]
+    fn test_load_dependencies_reuses_path_queries() {
+        let root = layered_project("paths");
+        let main_file = root.join("src/main.q");
+        let store = Arc::new(SourceStore::default());
+        let project = Arc::new(ProjectConfig::load(&root.join(PROJECT_FILE), &[]).unwrap());
+        let (program, errors) = parse_source_recovering(&store.read(&main_file).unwrap(), Locale::En);
+        assert!(errors.is_empty(), "{:?}", errors);
+        
+        let loaded = load_dependencies(&program, &main_file, Some(&project), Locale::En, &store).unwrap_or_else(|e| panic!("{:?}", e));
+        assert_eq!(loaded.files.len(), 500);
+        
+        // 没有缓存时每次查询都访问文件系统；有缓存时每个路径、每个目录只访问一次
+        let stats = store.paths().stats();
+        assert!(stats.canonicalize.calls >= 5000, "{:?}", stats);
+        assert!(stats.canonicalize.syscalls <= 510, "{:?}", stats);
+        assert!(stats.read_dir.calls >= 490, "{:?}", stats);
+        assert_eq!(stats.read_dir.syscalls, 50, "{:?}", stats);
+        assert!(stats.metadata.syscalls * 10 < stats.metadata.calls, "{:?}", stats);
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -2,8 +2,14 @@
 //! 
 //! 负责处理包声明、导入解析、依赖管理
 
+mod loader;
+mod path_cache;
 mod project;
 mod resolver;
+mod source_store;
 
-pub use project::{ProjectConfig, find_project_root, compute_expected_package};
+pub use project::{ProjectConfig, PROJECT_ENV, find_project, explicit_project_file, compute_expected_package, parse_override};
 pub use resolver::{PackageResolver, ResolvedImport, ImportKind};
+pub use loader::{load_dependencies, LoadedSources};
+pub use path_cache::PathCache;
+pub use source_store::SourceStore;
@@ -0,0 +1,236 @@
+//! 路径查询缓存
+//!
+//! 加载依赖时同一个路径会被反复查询：每个源文件读取快照、检查导入环、按目录加载时都要规范化路径，
+//! 同一个目录被许多文件导入时每次都要重新列出。一次命令中这些查询的结果记在 [`PathCache`] 中，
+//! 每个路径只访问一次文件系统（Windows 上 `canonicalize` 尤其慢）。
+//!
+//! 缓存的结果可能过时：编译期间新建、删除或改名的文件不会被看到，与源文件快照一样以第一次看到的为准。
+//! 缓存只在一次命令内使用，不能跨越多次编译（例如编辑器集成中的多次检查）。
+//!
+//! 规范化的缓存把原始路径映射到规范路径，去重仍然以规范路径为键：通过不同符号链接到达的同一个文件只加载一次。
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+/// 路径指向的对象（跟随符号链接）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PathKind {
+    File,
+    Dir,
+    /// 其他类型（如设备文件）
+    Other,
+    /// 不存在或无法访问
+    Missing,
+}
+
+impl PathKind {
+    fn of(metadata: io::Result<fs::Metadata>) -> Self {
+        match metadata {
+            Ok(metadata) if metadata.is_file() => PathKind::File,
+            Ok(metadata) if metadata.is_dir() => PathKind::Dir,
+            Ok(_) => PathKind::Other,
+            Err(_) => PathKind::Missing,
+        }
+    }
+}
+
+/// 一种查询的次数
+#[cfg_attr(not(test), allow(dead_code))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueryCount {
+    /// 调用次数（没有缓存时访问文件系统的次数）
+    pub calls: usize,
+    /// 实际访问文件系统的次数
+    pub syscalls: usize,
+}
+
+/// 各种查询的次数（测试和性能分析用）
+#[cfg_attr(not(test), allow(dead_code))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PathCacheStats {
+    pub canonicalize: QueryCount,
+    /// `is_file`、`is_dir`、`exists`
+    pub metadata: QueryCount,
+    pub read_dir: QueryCount,
+}
+
+#[derive(Debug, Default)]
+struct Counter {
+    calls: AtomicUsize,
+    syscalls: AtomicUsize,
+}
+
+impl Counter {
+    fn call(&self) {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn syscall(&self) {
+        self.syscalls.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    fn get(&self) -> QueryCount {
+        QueryCount { calls: self.calls.load(Ordering::Relaxed), syscalls: self.syscalls.load(Ordering::Relaxed) }
+    }
+}
+
+/// 一次命令中的路径查询缓存，可以在解析线程之间共享
+#[derive(Debug, Default)]
+pub struct PathCache {
+    /// 原始路径 -> 规范路径（只记录成功的结果）
+    canonical: Mutex<HashMap<PathBuf, PathBuf>>,
+    kinds: Mutex<HashMap<PathBuf, PathKind>>,
+    /// 目录 -> 其中的条目（按路径排序）
+    listings: Mutex<HashMap<PathBuf, Arc<[PathBuf]>>>,
+    canonicalize_count: Counter,
+    metadata_count: Counter,
+    read_dir_count: Counter,
+}
+
+impl PathCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 规范化的绝对路径，失败时返回错误（失败不缓存）
+    pub fn try_canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.canonicalize_count.call();
+        if let Some(canonical) = self.canonical.lock().get(path) {
+            return Ok(canonical.clone());
+        }
+        self.canonicalize_count.syscall();
+        let canonical = fs::canonicalize(path)?;
+        self.canonical.lock().insert(path.to_path_buf(), canonical.clone());
+        Ok(canonical)
+    }
+
+    /// 规范化的绝对路径，失败时（如文件不存在）原样返回
+    pub fn canonicalize(&self, path: &Path) -> PathBuf {
+        self.try_canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+    }
+
+    fn kind(&self, path: &Path) -> PathKind {
+        self.metadata_count.call();
+        if let Some(kind) = self.kinds.lock().get(path) {
+            return *kind;
+        }
+        self.metadata_count.syscall();
+        let kind = PathKind::of(fs::metadata(path));
+        self.kinds.lock().insert(path.to_path_buf(), kind);
+        kind
+    }
+
+    pub fn is_file(&self, path: &Path) -> bool {
+        self.kind(path) == PathKind::File
+    }
+
+    pub fn is_dir(&self, path: &Path) -> bool {
+        self.kind(path) == PathKind::Dir
+    }
+
+    pub fn exists(&self, path: &Path) -> bool {
+        self.kind(path) != PathKind::Missing
+    }
+
+    /// 目录中的条目（按路径排序）
+    ///
+    /// 列出目录时顺便记下每个条目的类型，之后对这些条目的 `is_file` 等查询不再访问文件系统
+    pub fn read_dir(&self, dir: &Path) -> io::Result<Arc<[PathBuf]>> {
+        self.read_dir_count.call();
+        if let Some(entries) = self.listings.lock().get(dir) {
+            return Ok(entries.clone());
+        }
+        self.read_dir_count.syscall();
+        let mut entries = Vec::new();
+        let mut kinds = Vec::new();
+        for entry in fs::read_dir(dir)?.flatten() {
+            let path = entry.path();
+            // 符号链接要跟随到目标，留到查询时再取
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_file() => kinds.push((path.clone(), PathKind::File)),
+                Ok(file_type) if file_type.is_dir() => kinds.push((path.clone(), PathKind::Dir)),
+                _ => {}
+            }
+            entries.push(path);
+        }
+        entries.sort();
+        self.kinds.lock().extend(kinds);
+        let entries: Arc<[PathBuf]> = entries.into();
+        self.listings.lock().insert(dir.to_path_buf(), entries.clone());
+        Ok(entries)
+    }
+
+    /// 到目前为止的查询次数
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn stats(&self) -> PathCacheStats {
+        PathCacheStats {
+            canonicalize: self.canonicalize_count.get(),
+            metadata: self.metadata_count.get(),
+            read_dir: self.read_dir_count.get(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("qlang_path_cache_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("pkg")).unwrap();
+        fs::write(dir.join("pkg/A.q"), "").unwrap();
+        fs::write(dir.join("pkg/B.q"), "").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_queries_hit_the_file_system_once() {
+        let dir = temp_dir("once");
+        let cache = PathCache::new();
+        let file = dir.join("pkg/./A.q");
+        for _ in 0..3 {
+            assert_eq!(cache.canonicalize(&file), fs::canonicalize(&file).unwrap());
+            assert!(cache.is_file(&file));
+            assert!(!cache.exists(&dir.join("pkg/C.q")));
+        }
+        let entries = cache.read_dir(&dir.join("pkg")).unwrap();
+        assert_eq!(entries.iter().map(|p| p.file_name().unwrap().to_str().unwrap()).collect::<Vec<_>>(), ["A.q", "B.q"]);
+        cache.read_dir(&dir.join("pkg")).unwrap();
+        // 列出目录时已经知道条目的类型
+        assert!(cache.is_file(&entries[1]));
+
+        let stats = cache.stats();
+        assert_eq!(stats.canonicalize, QueryCount { calls: 3, syscalls: 1 });
+        assert_eq!(stats.metadata, QueryCount { calls: 7, syscalls: 2 });
+        assert_eq!(stats.read_dir, QueryCount { calls: 2, syscalls: 1 });
+
+        // 失败不缓存，之后创建的文件可以找到
+        let late = dir.join("pkg/Late.q");
+        assert_eq!(cache.canonicalize(&late), late);
+        fs::write(&late, "").unwrap();
+        assert_eq!(cache.canonicalize(&late), fs::canonicalize(&late).unwrap());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks_map_to_the_same_canonical_path() {
+        let dir = temp_dir("symlink");
+        std::os::unix::fs::symlink(dir.join("pkg"), dir.join("alias")).unwrap();
+        let cache = PathCache::new();
+        let real = cache.canonicalize(&dir.join("pkg/A.q"));
+        assert_eq!(cache.canonicalize(&dir.join("alias/A.q")), real);
+        // 经由符号链接列出的条目保留原始路径，规范化后与直接访问的相同
+        let entries = cache.read_dir(&dir.join("alias")).unwrap();
+        assert_eq!(cache.canonicalize(&entries[0]), real);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,11 +1,37 @@
 //! 项目配置解析
 //! 
-//! 解析 project.toml 文件，获取项目配置
+//! 解析 project.toml 文件，获取项目配置。
+//!
+//! 配置按层合并，后面的层覆盖前面的：默认值 < project.toml < 环境变量 < 命令行 `--set`。
+//! 环境变量为 `QLANG_PROJECT_<KEY>`（如 `QLANG_PROJECT_SRC`），只作用于 `[project]` 节。
+//!
+//! 字符串值中的 `${NAME}` 替换为环境变量 `NAME` 的值，变量未设置时报错；
+//! `$${` 表示字面的 `${`，单引号字符串不做替换。
+//! 值的类型不符合要求、节或键重复等错误带有 project.toml 中的行号和列号，未知的节只给出警告。
+//!
+//! `[dependencies]` 中的值是版本字符串，或 `{ path = "../util", version = "0.2" }` 形式的本地路径依赖，
+//! 路径相对于声明它的项目根目录。版本目前只记录，不参与解析。
+//!
+//! `[build]` 中是编译选项（布尔值），如 `strict-types = true` 把隐式 any 的警告升级为错误。
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
 use crate::config::PROJECT_FILE;
+use super::path_cache::PathCache;
+
+/// `[project]` 节的配置项（都是字符串）
+const PROJECT_KEYS: &[&str] = &["name", "version", "package", "src"];
+
+/// 依赖项内联表中的键
+const DEPENDENCY_KEYS: &[&str] = &["path", "version"];
+
+/// `[build]` 节的配置项（都是布尔值）
+const BUILD_KEYS: &[&str] = &["strict-types"];
+
+/// 环境变量层的前缀
+const ENV_PREFIX: &str = "QLANG_PROJECT_";
 
 /// 项目配置
 #[derive(Debug, Clone)]
@@ -21,7 +47,13 @@ pub struct ProjectConfig {
     /// 源码目录（相对于项目根目录）
     pub src_dir: String,
     /// 依赖项
-    pub dependencies: HashMap<String, String>,
+    pub dependencies: HashMap<String, Dependency>,
+    /// 隐式 any 视为错误（`[build] strict-types`）
+    pub strict_types: bool,
+    /// 每个配置项最终生效的值的来源（`project.src`、`dependencies.std` 等）
+    pub origins: BTreeMap<String, ConfigOrigin>,
+    /// 不影响加载的问题（如未知的节）
+    pub warnings: Vec<String>,
 }
 
 impl Default for ProjectConfig {
@@ -33,59 +65,152 @@ impl Default for ProjectConfig {
             root_dir: PathBuf::new(),
             src_dir: "src".to_string(),
             dependencies: HashMap::new(),
+            strict_types: false,
+            origins: BTreeMap::new(),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+/// 依赖项
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dependency {
+    /// 版本要求
+    pub version: Option<String>,
+    /// 本地路径（相对于项目根目录）
+    pub path: Option<String>,
+}
+
+/// 配置项的来源
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigOrigin {
+    /// 内置默认值
+    Default,
+    /// project.toml 的某一行
+    File(usize),
+    /// 环境变量
+    Env(String),
+    /// 命令行 `--set`
+    Cli,
+}
+
+impl fmt::Display for ConfigOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigOrigin::Default => write!(f, "default"),
+            ConfigOrigin::File(line) => write!(f, "{}:{}", PROJECT_FILE, line),
+            ConfigOrigin::Env(name) => write!(f, "env {}", name),
+            ConfigOrigin::Cli => write!(f, "--set"),
         }
     }
 }
 
+/// project.toml 中的错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    /// 行号（从 1 开始）
+    pub line: usize,
+    /// 列号（从 1 开始，按字符计数）
+    pub column: usize,
+    pub message: String,
+}
+
+impl ConfigError {
+    fn new(line: usize, column: usize, message: impl Into<String>) -> Self {
+        Self { line, column, message: message.into() }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}: {}", PROJECT_FILE, self.line, self.column, self.message)
+    }
+}
+
 impl ProjectConfig {
-    /// 从 project.toml 文件加载配置
-    pub fn load(project_file: &Path) -> Result<Self, String> {
+    /// 从 project.toml 文件加载配置，叠加环境变量和命令行覆盖（`key=value` 中的 key 如 `project.src`）
+    pub fn load(project_file: &Path, overrides: &[(String, String)]) -> Result<Self, String> {
         let content = fs::read_to_string(project_file)
             .map_err(|e| format!("无法读取项目配置文件: {}", e))?;
         
-        Self::parse(&content, project_file.parent().unwrap_or(Path::new(".")))
+        let root_dir = project_file.parent().unwrap_or(Path::new("."));
+        Self::parse_with(&content, root_dir, &|name| std::env::var(name).ok(), overrides)
     }
     
-    /// 解析 TOML 内容
+    /// 解析 TOML 内容（使用当前进程的环境变量，没有命令行覆盖）
+    #[cfg(test)]
     fn parse(content: &str, root_dir: &Path) -> Result<Self, String> {
-        let mut config = ProjectConfig::default();
-        config.root_dir = root_dir.to_path_buf();
-        
-        // 简单的 TOML 解析（不依赖外部库）
-        let mut current_section = "";
+        Self::parse_with(content, root_dir, &|name| std::env::var(name).ok(), &[])
+    }
+    
+    /// 解析 TOML 内容并合并各层配置
+    fn parse_with(
+        content: &str,
+        root_dir: &Path,
+        env: &dyn Fn(&str) -> Option<String>,
+        overrides: &[(String, String)],
+    ) -> Result<Self, String> {
+        let document = parse_document(content, env).map_err(|e| e.to_string())?;
         
-        for line in content.lines() {
-            let line = line.trim();
-            
-            // 跳过空行和注释
-            if line.is_empty() || line.starts_with('#') {
-                continue;
+        // 默认值 < project.toml < 环境变量 < 命令行
+        let mut settings: BTreeMap<String, (String, ConfigOrigin)> = BTreeMap::new();
+        settings.insert("project.version".to_string(), ("0.1.0".to_string(), ConfigOrigin::Default));
+        settings.insert("project.src".to_string(), ("src".to_string(), ConfigOrigin::Default));
+        settings.insert("build.strict-types".to_string(), ("false".to_string(), ConfigOrigin::Default));
+        for (key, value, line) in document.entries {
+            settings.insert(key, (value, ConfigOrigin::File(line)));
+        }
+        for key in PROJECT_KEYS {
+            let name = format!("{}{}", ENV_PREFIX, key.to_uppercase());
+            if let Some(value) = env(&name) {
+                settings.insert(format!("project.{}", key), (value, ConfigOrigin::Env(name)));
             }
-            
-            // 检查节标题
-            if line.starts_with('[') && line.ends_with(']') {
-                current_section = &line[1..line.len()-1];
-                continue;
+        }
+        for (key, value) in overrides {
+            let known = match key.split_once('.') {
+                Some(("project", k)) => PROJECT_KEYS.contains(&k),
+                Some(("build", k)) => BUILD_KEYS.contains(&k),
+                Some(("dependencies", k)) => match k.split_once('.') {
+                    Some((name, field)) => !name.is_empty() && DEPENDENCY_KEYS.contains(&field),
+                    None => !k.is_empty(),
+                },
+                _ => false,
+            };
+            if !known {
+                return Err(format!(
+                    "unknown setting `{}` (expected project.<{}>, build.<{}>, dependencies.<name> or dependencies.<name>.<{}>)",
+                    key, PROJECT_KEYS.join("|"), BUILD_KEYS.join("|"), DEPENDENCY_KEYS.join("|"),
+                ));
             }
-            
-            // 解析键值对
-            if let Some((key, value)) = parse_key_value(line) {
-                match current_section {
-                    "" | "project" => {
-                        match key {
-                            "name" => config.name = value,
-                            "version" => config.version = value,
-                            "package" => config.package = value,
-                            "src" => config.src_dir = value,
-                            _ => {}
-                        }
-                    }
-                    "dependencies" => {
-                        config.dependencies.insert(key.to_string(), value);
+            if key.starts_with("build.") && !matches!(value.as_str(), "true" | "false") {
+                return Err(format!("`{}` must be true or false, found `{}`", key, value));
+            }
+            settings.insert(key.clone(), (value.clone(), ConfigOrigin::Cli));
+        }
+        
+        let mut config = ProjectConfig {
+            root_dir: root_dir.to_path_buf(),
+            warnings: document.warnings,
+            ..ProjectConfig::default()
+        };
+        for (key, (value, origin)) in settings {
+            match key.split_once('.') {
+                Some(("project", "name")) => config.name = value,
+                Some(("project", "version")) => config.version = value,
+                Some(("project", "package")) => config.package = value,
+                Some(("project", "src")) => config.src_dir = value,
+                Some(("build", "strict-types")) => config.strict_types = value == "true",
+                Some(("dependencies", name)) => {
+                    let (name, field) = name.split_once('.').unwrap_or((name, "version"));
+                    let dependency = config.dependencies.entry(name.to_string()).or_default();
+                    match field {
+                        "path" => dependency.path = Some(value),
+                        _ => dependency.version = Some(value),
                     }
-                    _ => {}
                 }
+                _ => continue,
             }
+            config.origins.insert(key, origin);
         }
         
         // 验证必需字段
@@ -95,61 +220,482 @@ impl ProjectConfig {
         if config.package.is_empty() {
             // 如果没有指定 package，使用 name
             config.package = config.name.clone();
+            config.origins.insert("project.package".to_string(), ConfigOrigin::Default);
         }
         
         Ok(config)
     }
+    
+    /// 本地路径依赖：(依赖名, 依赖的项目根目录)，按依赖名排序
+    pub fn path_dependencies(&self) -> Vec<(&str, PathBuf)> {
+        let mut dependencies: Vec<_> = self.dependencies.iter()
+            .filter_map(|(name, dependency)| Some((name.as_str(), self.root_dir.join(dependency.path.as_ref()?))))
+            .collect();
+        dependencies.sort();
+        dependencies
+    }
+    
+    /// 最终生效的配置，每项一行并注明来源（`config` 命令的输出）
+    pub fn describe(&self) -> String {
+        let mut lines = vec![(format!("root = {:?}", self.root_dir.display().to_string()), None)];
+        let mut dependencies: Vec<_> = self.dependencies.iter().collect();
+        dependencies.sort_by(|a, b| a.0.cmp(b.0));
+        let mut dependency_values = Vec::new();
+        for (name, dependency) in dependencies {
+            // 只有版本时写成 `dependencies.<name> = "1.0"`，与 project.toml 中的写法对应
+            let version_key = match &dependency.path {
+                None => format!("dependencies.{}", name),
+                Some(path) => {
+                    dependency_values.push((format!("dependencies.{}.path", name), path));
+                    format!("dependencies.{}.version", name)
+                }
+            };
+            if let Some(version) = &dependency.version {
+                let plain = format!("dependencies.{}", name);
+                let key = if self.origins.contains_key(&version_key) { version_key } else { plain };
+                dependency_values.push((key, version));
+            }
+        }
+        let values = [
+            ("project.name", &self.name),
+            ("project.version", &self.version),
+            ("project.package", &self.package),
+            ("project.src", &self.src_dir),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .chain(dependency_values)
+        .map(|(key, value)| (key, format!("{:?}", value)))
+        // 布尔值不加引号，与 project.toml 中的写法对应
+        .chain([("build.strict-types".to_string(), self.strict_types.to_string())]);
+        for (key, value) in values {
+            let origin = self.origins.get(&key).cloned().unwrap_or(ConfigOrigin::Default);
+            lines.push((format!("{} = {}", key, value), Some(origin)));
+        }
+        
+        let width = lines.iter().map(|(text, _)| text.chars().count()).max().unwrap_or(0);
+        lines
+            .into_iter()
+            .map(|(text, origin)| match origin {
+                Some(origin) => format!("{:<width$}  # {}", text, origin, width = width),
+                None => text,
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
 }
 
-/// 解析键值对 "key = value" 或 "key = \"value\""
-fn parse_key_value(line: &str) -> Option<(&str, String)> {
-    let parts: Vec<&str> = line.splitn(2, '=').collect();
-    if parts.len() != 2 {
-        return None;
+/// 解析后的 project.toml：(节.键, 值, 行号) 和警告
+#[derive(Debug, Default)]
+struct Document {
+    entries: Vec<(String, String, usize)>,
+    warnings: Vec<String>,
+}
+
+/// TOML 值（只区分检查类型需要的种类）
+#[derive(Debug, Clone, PartialEq)]
+enum TomlValue {
+    String(String),
+    Integer,
+    Float,
+    Boolean(bool),
+    Array,
+    /// 内联表
+    Table(Vec<TableField>),
+}
+
+/// 内联表中的一项
+#[derive(Debug, Clone, PartialEq)]
+struct TableField {
+    key: String,
+    value: TomlValue,
+    /// 键和值所在的列（从 1 开始）
+    key_column: usize,
+    value_column: usize,
+}
+
+impl TomlValue {
+    fn type_name(&self) -> &'static str {
+        match self {
+            TomlValue::String(_) => "string",
+            TomlValue::Integer => "integer",
+            TomlValue::Float => "float",
+            TomlValue::Boolean(_) => "boolean",
+            TomlValue::Array => "array",
+            TomlValue::Table(_) => "table",
+        }
     }
+}
+
+/// 按行解析 project.toml 并检查结构（简单的 TOML 子集，不依赖外部库）
+fn parse_document(content: &str, env: &dyn Fn(&str) -> Option<String>) -> Result<Document, ConfigError> {
+    let mut document = Document::default();
+    // 当前节：None 表示忽略的未知节，顶层的键属于 project
+    let mut section = Some("project".to_string());
+    let mut seen_sections: Vec<String> = Vec::new();
+    let mut seen_keys: HashMap<String, usize> = HashMap::new();
     
-    let key = parts[0].trim();
-    let mut value = parts[1].trim();
-    
-    // 去除引号
-    if value.starts_with('"') && value.ends_with('"') {
-        value = &value[1..value.len()-1];
+    for (index, raw) in content.lines().enumerate() {
+        let line_no = index + 1;
+        let chars: Vec<char> = raw.chars().collect();
+        let mut pos = skip_spaces(&chars, 0);
+        
+        // 跳过空行和注释
+        if pos >= chars.len() || chars[pos] == '#' {
+            continue;
+        }
+        
+        // 节标题
+        if chars[pos] == '[' {
+            if chars.get(pos + 1) == Some(&'[') {
+                return Err(ConfigError::new(line_no, pos + 1, "array tables ([[...]]) are not supported"));
+            }
+            let close = chars[pos..].iter().position(|&c| c == ']').map(|i| pos + i)
+                .ok_or_else(|| ConfigError::new(line_no, pos + 1, "unterminated section header, expected `]`"))?;
+            let name: String = chars[pos + 1..close].iter().collect::<String>().trim().to_string();
+            expect_line_end(&chars, close + 1, line_no)?;
+            if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.')) {
+                return Err(ConfigError::new(line_no, pos + 2, format!("invalid section name `{}`", name)));
+            }
+            if seen_sections.contains(&name) {
+                return Err(ConfigError::new(line_no, pos + 1, format!("duplicate section [{}]", name)));
+            }
+            seen_sections.push(name.clone());
+            
+            section = match name.as_str() {
+                "project" | "dependencies" | "build" => Some(name),
+                _ => {
+                    // [project.xxx]、[dependencies.xxx] 这类子表放错了位置
+                    if let Some((parent, child)) = name.split_once('.') {
+                        if matches!(parent, "project" | "dependencies" | "build") {
+                            return Err(ConfigError::new(
+                                line_no,
+                                pos + 1,
+                                format!("[{}] is not supported: set `{} = ...` inside [{}] instead", name, child, parent),
+                            ));
+                        }
+                    }
+                    document.warnings.push(format!("{}:{}: unknown section [{}] is ignored", PROJECT_FILE, line_no, name));
+                    None
+                }
+            };
+            continue;
+        }
+        
+        // 键值对
+        let key_start = pos;
+        while pos < chars.len() && (chars[pos].is_alphanumeric() || matches!(chars[pos], '_' | '-')) {
+            pos += 1;
+        }
+        let key: String = chars[key_start..pos].iter().collect();
+        pos = skip_spaces(&chars, pos);
+        if key.is_empty() || chars.get(pos) != Some(&'=') {
+            return Err(ConfigError::new(line_no, key_start + 1, "expected `key = value`"));
+        }
+        let value_pos = skip_spaces(&chars, pos + 1);
+        let (value, end) = parse_value(&chars, value_pos, line_no, env)?;
+        expect_line_end(&chars, end, line_no)?;
+        
+        let Some(section) = &section else { continue };
+        let full_key = format!("{}.{}", section, key);
+        if let Some(first) = seen_keys.insert(full_key.clone(), line_no) {
+            return Err(ConfigError::new(line_no, key_start + 1, format!("duplicate key `{}` (first defined on line {})", key, first)));
+        }
+        
+        // 依赖项可以是 `{ path = "...", version = "..." }`，每个键展开为 dependencies.<name>.<key>
+        if let ("dependencies", TomlValue::Table(fields)) = (section.as_str(), &value) {
+            for field in fields {
+                if !DEPENDENCY_KEYS.contains(&field.key.as_str()) {
+                    return Err(ConfigError::new(line_no, field.key_column, format!(
+                        "unknown key `{}` in dependency `{}` (expected {})", field.key, key, DEPENDENCY_KEYS.join(" or "),
+                    )));
+                }
+                match &field.value {
+                    TomlValue::String(text) => document.entries.push((format!("{}.{}", full_key, field.key), text.clone(), line_no)),
+                    other => return Err(ConfigError::new(line_no, field.value_column, format!(
+                        "`{}` of dependency `{}` must be a string, found {}", field.key, key, other.type_name(),
+                    ))),
+                }
+            }
+            if !fields.iter().any(|field| field.key == "path") {
+                return Err(ConfigError::new(line_no, value_pos + 1, format!("dependency `{}` needs a `path`", key)));
+            }
+            continue;
+        }
+        
+        // 编译选项是布尔值，未知的键只警告
+        if section == "build" {
+            match value {
+                _ if !BUILD_KEYS.contains(&key.as_str()) => document.warnings.push(format!(
+                    "{}:{}: unknown key `{}` in [build] is ignored (expected {})", PROJECT_FILE, line_no, key, BUILD_KEYS.join(" or "),
+                )),
+                TomlValue::Boolean(flag) => document.entries.push((full_key, flag.to_string(), line_no)),
+                other => return Err(ConfigError::new(line_no, value_pos + 1, format!(
+                    "`{}` must be true or false, found {}", key, other.type_name(),
+                ))),
+            }
+            continue;
+        }
+        
+        // 结构检查：其余已知的配置项都是字符串
+        let expected = match section.as_str() {
+            "dependencies" => Some(format!("dependency `{}` must be a version string or {{ path = \"...\" }}", key)),
+            _ if PROJECT_KEYS.contains(&key.as_str()) => Some(format!("`{}` must be a string", key)),
+            _ => None,
+        };
+        match (value, expected) {
+            (TomlValue::String(value), Some(_)) => document.entries.push((full_key, value, line_no)),
+            (value, Some(expected)) => {
+                return Err(ConfigError::new(line_no, value_pos + 1, format!("{}, found {}", expected, value.type_name())));
+            }
+            // [project] 中未知的键保持原来的行为：忽略
+            (_, None) => {}
+        }
     }
     
-    Some((key, value.to_string()))
+    Ok(document)
 }
 
-/// 从指定路径向上查找项目根目录
-/// 
-/// 查找逻辑与 Go 相同：从入口文件所在目录向上查找，
-/// 直到找到包含 project.toml 的目录或到达文件系统根目录
-pub fn find_project_root(start_path: &Path) -> Option<PathBuf> {
-    let mut current = if start_path.is_file() {
-        start_path.parent()?.to_path_buf()
-    } else {
-        start_path.to_path_buf()
-    };
-    
+fn skip_spaces(chars: &[char], mut pos: usize) -> usize {
+    while pos < chars.len() && (chars[pos] == ' ' || chars[pos] == '\t') {
+        pos += 1;
+    }
+    pos
+}
+
+/// 值之后只能有空白和注释
+fn expect_line_end(chars: &[char], pos: usize, line_no: usize) -> Result<(), ConfigError> {
+    let pos = skip_spaces(chars, pos);
+    if pos < chars.len() && chars[pos] != '#' {
+        return Err(ConfigError::new(line_no, pos + 1, "unexpected characters after value"));
+    }
+    Ok(())
+}
+
+/// 解析 pos 处的值，返回值和结束位置
+fn parse_value(
+    chars: &[char],
+    pos: usize,
+    line_no: usize,
+    env: &dyn Fn(&str) -> Option<String>,
+) -> Result<(TomlValue, usize), ConfigError> {
+    match chars.get(pos) {
+        None | Some('#') => Err(ConfigError::new(line_no, pos + 1, "missing value after `=`")),
+        Some('"') => parse_basic_string(chars, pos, line_no, env),
+        Some('\'') => {
+            let close = chars[pos + 1..].iter().position(|&c| c == '\'')
+                .ok_or_else(|| ConfigError::new(line_no, pos + 1, "unterminated string"))?;
+            let value = chars[pos + 1..pos + 1 + close].iter().collect();
+            Ok((TomlValue::String(value), pos + close + 2))
+        }
+        Some('[') => {
+            let end = matching_close(chars, pos, '[', ']')
+                .ok_or_else(|| ConfigError::new(line_no, pos + 1, "unterminated array, expected `]`"))?;
+            Ok((TomlValue::Array, end + 1))
+        }
+        Some('{') => parse_inline_table(chars, pos, line_no, env),
+        Some(_) => {
+            let mut end = pos;
+            while end < chars.len() && !chars[end].is_whitespace() && chars[end] != '#' {
+                end += 1;
+            }
+            let word: String = chars[pos..end].iter().collect();
+            let digits = word.replace('_', "");
+            let value = if word == "true" || word == "false" {
+                TomlValue::Boolean(word == "true")
+            } else if digits.parse::<i64>().is_ok() {
+                TomlValue::Integer
+            } else if digits.parse::<f64>().is_ok() {
+                TomlValue::Float
+            } else {
+                return Err(ConfigError::new(line_no, pos + 1, format!("invalid value `{}` (strings must be quoted)", word)));
+            };
+            Ok((value, end))
+        }
+    }
+}
+
+/// 解析 `{ key = value, ... }`
+fn parse_inline_table(
+    chars: &[char],
+    pos: usize,
+    line_no: usize,
+    env: &dyn Fn(&str) -> Option<String>,
+) -> Result<(TomlValue, usize), ConfigError> {
+    let mut fields: Vec<TableField> = Vec::new();
+    let mut i = skip_spaces(chars, pos + 1);
+    if chars.get(i) == Some(&'}') {
+        return Ok((TomlValue::Table(fields), i + 1));
+    }
     loop {
-        let project_file = current.join(PROJECT_FILE);
-        if project_file.exists() {
-            return Some(current);
+        let key_start = i;
+        while i < chars.len() && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '-')) {
+            i += 1;
         }
-        
-        // 向上一级目录
-        match current.parent() {
-            Some(parent) => {
-                if parent == current {
-                    // 已到达根目录
-                    return None;
+        let key: String = chars[key_start..i].iter().collect();
+        i = skip_spaces(chars, i);
+        if key.is_empty() || chars.get(i) != Some(&'=') {
+            return Err(ConfigError::new(line_no, key_start + 1, "expected `key = value` in inline table"));
+        }
+        if fields.iter().any(|field| field.key == key) {
+            return Err(ConfigError::new(line_no, key_start + 1, format!("duplicate key `{}` in inline table", key)));
+        }
+        let value_pos = skip_spaces(chars, i + 1);
+        let (value, end) = parse_value(chars, value_pos, line_no, env)?;
+        fields.push(TableField { key, value, key_column: key_start + 1, value_column: value_pos + 1 });
+        i = skip_spaces(chars, end);
+        match chars.get(i) {
+            Some(',') => i = skip_spaces(chars, i + 1),
+            Some('}') => return Ok((TomlValue::Table(fields), i + 1)),
+            _ => return Err(ConfigError::new(line_no, pos + 1, "unterminated table, expected `}`")),
+        }
+    }
+}
+
+/// 找到与 pos 处的开括号匹配的闭括号（跳过字符串中的括号）
+fn matching_close(chars: &[char], pos: usize, open: char, close: char) -> Option<usize> {
+    let mut depth = 0;
+    let mut quote = None;
+    let mut i = pos;
+    while i < chars.len() {
+        let c = chars[i];
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some('"') if c == '\\' => i += 1,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == open => depth += 1,
+            None if c == close => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            None => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// 解析双引号字符串：处理转义和 `${NAME}` 环境变量替换，`$${` 表示字面的 `${`
+fn parse_basic_string(
+    chars: &[char],
+    pos: usize,
+    line_no: usize,
+    env: &dyn Fn(&str) -> Option<String>,
+) -> Result<(TomlValue, usize), ConfigError> {
+    let mut value = String::new();
+    let mut i = pos + 1;
+    loop {
+        match chars.get(i) {
+            None => return Err(ConfigError::new(line_no, pos + 1, "unterminated string")),
+            Some('"') => return Ok((TomlValue::String(value), i + 1)),
+            Some('\\') => {
+                let escaped = match chars.get(i + 1) {
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    _ => return Err(ConfigError::new(line_no, i + 1, "invalid escape sequence")),
+                };
+                value.push(escaped);
+                i += 2;
+            }
+            Some('$') if chars.get(i + 1) == Some(&'$') && chars.get(i + 2) == Some(&'{') => {
+                value.push_str("${");
+                i += 3;
+            }
+            Some('$') if chars.get(i + 1) == Some(&'{') => {
+                let close = chars[i + 2..].iter().position(|&c| c == '}' || c == '"')
+                    .filter(|&n| chars[i + 2 + n] == '}')
+                    .ok_or_else(|| ConfigError::new(line_no, i + 1, "unterminated `${`, expected `}`"))?;
+                let name: String = chars[i + 2..i + 2 + close].iter().collect();
+                if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                    return Err(ConfigError::new(line_no, i + 1, format!("invalid environment variable name `{}`", name)));
                 }
-                current = parent.to_path_buf();
+                let substituted = env(&name).ok_or_else(|| {
+                    ConfigError::new(line_no, i + 1, format!("environment variable `{}` is not set (write `$${{` for a literal `${{`)", name))
+                })?;
+                value.push_str(&substituted);
+                i += close + 3;
+            }
+            Some(&c) => {
+                value.push(c);
+                i += 1;
             }
-            None => return None,
         }
     }
 }
 
+/// 解析命令行的 `key=value` 覆盖
+pub fn parse_override(arg: &str) -> Result<(String, String), String> {
+    let (key, value) = arg.split_once('=').ok_or_else(|| format!("--set expects key=value, found `{}`", arg))?;
+    Ok((key.trim().to_string(), value.to_string()))
+}
+
+/// 直接指定 project.toml 的环境变量，优先级低于命令行 `--project`
+pub const PROJECT_ENV: &str = "QLANG_PROJECT";
+
+/// 向上查找项目的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectDiscovery {
+    /// 最近的项目根目录
+    pub root: PathBuf,
+    /// 查找路径上被最近的项目遮住的外层项目根目录（由近到远）
+    pub shadowed: Vec<PathBuf>,
+}
+
+/// 从指定路径向上查找项目，见 [`discover_project`]（用户主目录取自 `HOME`/`USERPROFILE`）
+pub fn find_project(start_path: &Path, paths: &PathCache) -> Option<ProjectDiscovery> {
+    discover_project(start_path, home_dir(paths).as_deref(), paths)
+}
+
+/// 从指定路径向上查找包含 project.toml 的目录
+///
+/// 查找在两种边界处停止，边界之外的 project.toml 属于别的工具或别的仓库：
+/// - 包含 `.git` 的目录（仓库根）：仍然检查它本身，但不再向上
+/// - 用户主目录：除非查找就从主目录开始，否则不检查它，也不再向上
+///
+/// 边界内找到多个 project.toml 时（嵌套项目）使用最近的一个，其余记录在 `shadowed` 中
+pub fn discover_project(start_path: &Path, home: Option<&Path>, paths: &PathCache) -> Option<ProjectDiscovery> {
+    let start = if paths.is_file(start_path) { start_path.parent()? } else { start_path };
+    let mut found: Vec<PathBuf> = Vec::new();
+    for dir in start.ancestors() {
+        if home == Some(dir) && dir != start {
+            break;
+        }
+        if paths.is_file(&dir.join(PROJECT_FILE)) {
+            found.push(dir.to_path_buf());
+        }
+        if paths.exists(&dir.join(".git")) {
+            break;
+        }
+    }
+    
+    let mut found = found.into_iter();
+    let root = found.next()?;
+    Some(ProjectDiscovery { root, shadowed: found.collect() })
+}
+
+/// 解析 `--project` 或 QLANG_PROJECT 给出的路径：可以是 project.toml 本身或它所在的目录
+///
+/// `origin` 用于错误信息（如 `--project`）
+pub fn explicit_project_file(origin: &str, path: &Path, paths: &PathCache) -> Result<PathBuf, String> {
+    let file = if paths.is_dir(path) { path.join(PROJECT_FILE) } else { path.to_path_buf() };
+    if !paths.is_file(&file) {
+        return Err(format!("{} points to {}, which does not exist", origin, file.display()));
+    }
+    Ok(paths.canonicalize(&file))
+}
+
+/// 用户主目录（规范化后，便于与规范化的源文件路径比较）
+fn home_dir(paths: &PathCache) -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")).filter(|home| !home.is_empty())?;
+    Some(paths.canonicalize(Path::new(&home)))
+}
+
 /// 计算期望的包名
 /// 
 /// 根据 project.toml 中的 package 和文件的相对路径计算期望包名
@@ -221,7 +767,7 @@ std = "1.0"
         assert_eq!(config.version, "1.0.0");
         assert_eq!(config.package, "com.example.myapp");
         assert_eq!(config.src_dir, "src");
-        assert_eq!(config.dependencies.get("std"), Some(&"1.0".to_string()));
+        assert_eq!(config.dependencies.get("std").and_then(|d| d.version.as_deref()), Some("1.0"));
     }
     
     #[test]
@@ -254,4 +800,172 @@ name = "minimal"
         let result = compute_expected_package(&config, Path::new("/project/src/xxx/yyy/main.q"));
         assert_eq!(result, Some("com.example.demo.xxx.yyy".to_string()));
     }
+    
+    /// 在临时目录中按相对路径创建文件（以 `/` 结尾的是目录）
+    fn make_tree(name: &str, paths: &[&str]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("qlang_discover_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for path in paths {
+            match path.strip_suffix('/') {
+                Some(dir) => fs::create_dir_all(root.join(dir)).unwrap(),
+                None => {
+                    fs::create_dir_all(root.join(path).parent().unwrap()).unwrap();
+                    fs::write(root.join(path), "[project]\nname = \"x\"\n").unwrap();
+                }
+            }
+        }
+        fs::canonicalize(&root).unwrap()
+    }
+    
+    #[test]
+    fn test_discover_project_boundaries() {
+        // 仓库外层的 project.toml 不会劫持仓库内的文件，仓库根自己的 project.toml 仍然有效
+        let root = make_tree("vcs", &["project.toml", "repo/.git/", "repo/src/app/"]);
+        assert_eq!(discover_project(&root.join("repo/src/app"), None, &PathCache::new()), None);
+        fs::write(root.join("repo/project.toml"), "").unwrap();
+        let found = discover_project(&root.join("repo/src/app"), None, &PathCache::new()).unwrap();
+        assert_eq!(found, ProjectDiscovery { root: root.join("repo"), shadowed: Vec::new() });
+        
+        // 主目录中的 project.toml 只在从主目录开始查找时生效
+        let home = make_tree("home", &["project.toml", "work/src/"]);
+        assert_eq!(discover_project(&home.join("work/src"), Some(&home), &PathCache::new()), None);
+        assert_eq!(discover_project(&home, Some(&home), &PathCache::new()).unwrap().root, home);
+        assert_eq!(discover_project(&home.join("work/src"), None, &PathCache::new()).unwrap().root, home);
+        
+        let _ = fs::remove_dir_all(&root);
+        let _ = fs::remove_dir_all(&home);
+    }
+    
+    #[test]
+    fn test_discover_nested_projects() {
+        let root = make_tree("nested", &["project.toml", "tools/project.toml", "tools/gen/project.toml", "tools/gen/src/main.q"]);
+        let found = discover_project(&root.join("tools/gen/src/main.q"), None, &PathCache::new()).unwrap();
+        assert_eq!(found.root, root.join("tools/gen"));
+        assert_eq!(found.shadowed, vec![root.join("tools"), root.clone()]);
+        
+        // 显式指定时可以是文件或目录
+        assert_eq!(explicit_project_file("--project", &root.join("tools"), &PathCache::new()).unwrap(), root.join("tools/project.toml"));
+        assert_eq!(explicit_project_file("--project", &root.join("project.toml"), &PathCache::new()).unwrap(), root.join("project.toml"));
+        let missing = root.join("tools/gen/src/project.toml");
+        assert_eq!(explicit_project_file("QLANG_PROJECT", &root.join("tools/gen/src"), &PathCache::new()).unwrap_err(),
+            format!("QLANG_PROJECT points to {}, which does not exist", missing.display()));
+        let _ = fs::remove_dir_all(&root);
+    }
+    
+    fn parse_env(content: &str, overrides: &[(&str, &str)]) -> Result<ProjectConfig, String> {
+        let env = |name: &str| match name {
+            "HOME" => Some("/home/q".to_string()),
+            "QLANG_PROJECT_PACKAGE" => Some("com.env".to_string()),
+            _ => None,
+        };
+        let overrides: Vec<(String, String)> = overrides
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        ProjectConfig::parse_with(content, Path::new("/proj"), &env, &overrides)
+    }
+    
+    #[test]
+    fn test_schema_errors() {
+        let error = |content: &str| parse_env(content, &[]).unwrap_err();
+        assert_eq!(error("[project]\nname = \"a\"\nsrc = 5\n"), "project.toml:3:7: `src` must be a string, found integer");
+        assert_eq!(error("name = \"a\"\n[dependencies]\nfoo = 1\n"),
+            "project.toml:3:7: dependency `foo` must be a version string or { path = \"...\" }, found integer");
+        assert_eq!(error("name = \"a\"\n[dependencies.foo]\n"),
+            "project.toml:2:1: [dependencies.foo] is not supported: set `foo = ...` inside [dependencies] instead");
+        assert_eq!(error("name = \"a\"\nname = \"b\"\n"), "project.toml:2:1: duplicate key `name` (first defined on line 1)");
+        assert_eq!(error("name = \"a\nversion = \"1\"\n"), "project.toml:1:8: unterminated string");
+        assert_eq!(error("name = a\n"), "project.toml:1:8: invalid value `a` (strings must be quoted)");
+        assert_eq!(error("  name\n"), "project.toml:1:3: expected `key = value`");
+        
+        // 未知的节只警告，里面的键被忽略
+        let config = parse_env("name = \"a\"\n[tools]\nsrc = 5\n", &[]).unwrap();
+        assert_eq!(config.src_dir, "src");
+        assert_eq!(config.warnings, vec!["project.toml:2: unknown section [tools] is ignored".to_string()]);
+    }
+    
+    #[test]
+    fn test_path_dependencies() {
+        let content = "name = \"app\"\n[dependencies]\nstd = \"1.0\"\nutil = { path = \"../util\", version = \"0.2\" }\nlog = { path = '${HOME}/log' }\n";
+        let config = parse_env(content, &[("dependencies.log.path", "vendor/log")]).unwrap();
+        assert_eq!(config.dependencies["util"], Dependency { version: Some("0.2".to_string()), path: Some("../util".to_string()) });
+        assert_eq!(config.path_dependencies(), vec![
+            ("log", PathBuf::from("/proj/vendor/log")),
+            ("util", PathBuf::from("/proj/../util")),
+        ]);
+        assert_eq!(config.describe(), "\
+root = \"/proj\"
+project.name = \"app\"                  # project.toml:1
+project.version = \"0.1.0\"             # default
+project.package = \"com.env\"           # env QLANG_PROJECT_PACKAGE
+project.src = \"src\"                   # default
+dependencies.log.path = \"vendor/log\"  # --set
+dependencies.std = \"1.0\"              # project.toml:3
+dependencies.util.path = \"../util\"    # project.toml:4
+dependencies.util.version = \"0.2\"     # project.toml:4
+build.strict-types = false            # default");
+        
+        let error = |content: &str| parse_env(content, &[]).unwrap_err();
+        assert_eq!(error("name = \"a\"\n[dependencies]\nutil = { version = \"1\" }\n"),
+            "project.toml:3:8: dependency `util` needs a `path`");
+        assert_eq!(error("name = \"a\"\n[dependencies]\nutil = { path = \"x\", git = \"y\" }\n"),
+            "project.toml:3:22: unknown key `git` in dependency `util` (expected path or version)");
+        assert_eq!(error("name = \"a\"\n[dependencies]\nutil = { path = 1 }\n"),
+            "project.toml:3:17: `path` of dependency `util` must be a string, found integer");
+        assert_eq!(error("name = \"a\"\n[dependencies]\nutil = { path = \"x\"\n"),
+            "project.toml:3:8: unterminated table, expected `}`");
+    }
+    
+    #[test]
+    fn test_env_interpolation() {
+        let config = parse_env("name = \"a\"\nsrc = \"${HOME}/src # not a comment\"\n", &[]).unwrap();
+        assert_eq!(config.src_dir, "/home/q/src # not a comment");
+        
+        // $${ 和单引号字符串都不替换
+        let config = parse_env("name = \"$${HOME}\"\nsrc = '${HOME}'\n", &[]).unwrap();
+        assert_eq!(config.name, "${HOME}");
+        assert_eq!(config.src_dir, "${HOME}");
+        
+        assert_eq!(parse_env("name = \"a\"\nsrc = \"x/${MISSING}\"\n", &[]).unwrap_err(),
+            "project.toml:2:10: environment variable `MISSING` is not set (write `$${` for a literal `${`)");
+        assert_eq!(parse_env("name = \"${HOME\"\n", &[]).unwrap_err(),
+            "project.toml:1:9: unterminated `${`, expected `}`");
+    }
+    
+    #[test]
+    fn test_override_chain() {
+        let content = "[project]\nname = \"demo\"\npackage = \"com.file\"\nsrc = \"source\"\n\n[dependencies]\nstd = \"1.0\"\n";
+        
+        // project.toml < 环境变量（package）< 命令行（src、dependencies.std）
+        let config = parse_env(content, &[("project.src", "lib"), ("dependencies.std", "2.0")]).unwrap();
+        assert_eq!(config.package, "com.env");
+        assert_eq!(config.src_dir, "lib");
+        assert_eq!(config.describe(), "\
+root = \"/proj\"
+project.name = \"demo\"        # project.toml:2
+project.version = \"0.1.0\"    # default
+project.package = \"com.env\"  # env QLANG_PROJECT_PACKAGE
+project.src = \"lib\"          # --set
+dependencies.std = \"2.0\"     # --set
+build.strict-types = false   # default");
+        
+        assert!(parse_env(content, &[("project.nope", "x")]).unwrap_err().starts_with("unknown setting `project.nope`"));
+    }
+    
+    #[test]
+    fn test_build_section() {
+        let config = parse_env("name = \"a\"\n[build]\nstrict-types = true\nfast = true\n", &[]).unwrap();
+        assert!(config.strict_types);
+        assert_eq!(config.origins["build.strict-types"], ConfigOrigin::File(3));
+        assert_eq!(config.warnings, vec!["project.toml:4: unknown key `fast` in [build] is ignored (expected strict-types)".to_string()]);
+        
+        // 命令行覆盖 project.toml
+        let config = parse_env("name = \"a\"\n[build]\nstrict-types = true\n", &[("build.strict-types", "false")]).unwrap();
+        assert!(!config.strict_types);
+        
+        assert_eq!(parse_env("name = \"a\"\n[build]\nstrict-types = \"yes\"\n", &[]).unwrap_err(),
+            "project.toml:3:16: `strict-types` must be true or false, found string");
+        assert_eq!(parse_env("name = \"a\"\n", &[("build.strict-types", "1")]).unwrap_err(),
+            "`build.strict-types` must be true or false, found `1`");
+    }
 }
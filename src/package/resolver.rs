@@ -1,12 +1,17 @@
 //! 包解析器
 //! 
 //! 负责解析导入路径，定位源文件
+//!
+//! project.toml 中的本地路径依赖各自有 project.toml，它们的 `package` 和 `src` 决定依赖提供的包名
+//! 和源文件位置。导入路径按最长的包名匹配到项目本身或某个依赖。
 
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use crate::config::{STD_PREFIX, STDLIB_DIR, SOURCE_EXTENSION};
+use std::sync::Arc;
+use crate::config::{STD_PREFIX, STDLIB_DIR, SOURCE_EXTENSION, PROJECT_FILE};
 use crate::parser::ast::{ImportDecl, ImportTarget};
 use super::project::ProjectConfig;
+use super::path_cache::PathCache;
 
 /// 导入类型
 #[derive(Debug, Clone, PartialEq)]
@@ -34,19 +39,32 @@ pub struct ResolvedImport {
     pub members: Vec<String>,
 }
 
+/// 本地路径依赖
+#[derive(Debug, Clone)]
+struct PathDependency {
+    /// project.toml 中的依赖名
+    name: String,
+    /// 依赖自己的项目配置（root_dir 为规范化的绝对路径）
+    project: ProjectConfig,
+}
+
 /// 包解析器
 pub struct PackageResolver {
-    /// 项目配置
-    project: Option<ProjectConfig>,
+    /// 项目配置（与加载器共享）
+    project: Option<Arc<ProjectConfig>>,
+    /// 本地路径依赖（包括依赖的依赖）
+    dependencies: Vec<PathDependency>,
     /// 标准库目录
     stdlib_dir: PathBuf,
     /// 内置标准库模块列表
     builtin_modules: HashMap<String, Vec<String>>,
+    /// 路径查询缓存
+    paths: Arc<PathCache>,
 }
 
 impl PackageResolver {
     /// 创建新的包解析器
-    pub fn new(project: Option<ProjectConfig>) -> Self {
+    pub fn new(project: Option<Arc<ProjectConfig>>) -> Self {
         // 获取标准库目录（相对于可执行文件或当前目录）
         let stdlib_dir = std::env::current_exe()
             .ok()
@@ -56,8 +74,10 @@ impl PackageResolver {
         
         let mut resolver = Self {
             project,
+            dependencies: Vec::new(),
             stdlib_dir,
             builtin_modules: HashMap::new(),
+            paths: Arc::new(PathCache::new()),
         };
         
         // 注册内置标准库模块
@@ -66,6 +86,54 @@ impl PackageResolver {
         resolver
     }
     
+    /// 读取项目的本地路径依赖及它们各自的依赖
+    ///
+    /// 两个依赖（或依赖与项目本身）提供同一个包名时报错；同一个目录被多次依赖时只读取一次
+    pub fn load_path_dependencies(&mut self) -> Result<(), String> {
+        let Some(project) = &self.project else { return Ok(()) };
+        let mut pending: Vec<(String, PathBuf)> = project.path_dependencies().into_iter()
+            .map(|(name, root)| (name.to_string(), root))
+            .collect();
+        let mut next = 0;
+        while let Some((name, root)) = pending.get(next).cloned() {
+            next += 1;
+            let root = self.paths.try_canonicalize(&root)
+                .map_err(|e| format!("dependency `{}`: cannot open {}: {}", name, root.display(), e))?;
+            if self.dependencies.iter().any(|dep| dep.project.root_dir == root) {
+                continue;
+            }
+            let config = ProjectConfig::load(&root.join(PROJECT_FILE), &[])
+                .map_err(|e| format!("dependency `{}` ({}): {}", name, root.display(), e))?;
+            if config.package == project.package {
+                return Err(format!(
+                    "dependency `{}` ({}) provides package {}, which is already the package of this project",
+                    name, root.display(), config.package,
+                ));
+            }
+            if let Some(other) = self.dependencies.iter().find(|dep| dep.project.package == config.package) {
+                return Err(format!(
+                    "dependencies `{}` ({}) and `{}` ({}) both provide package {}",
+                    other.name, other.project.root_dir.display(), name, root.display(), config.package,
+                ));
+            }
+            pending.extend(config.path_dependencies().into_iter().map(|(name, root)| (name.to_string(), root)));
+            self.dependencies.push(PathDependency { name, project: config });
+        }
+        Ok(())
+    }
+    
+    /// 源文件所属的依赖的项目配置（不属于任何依赖时为 None）
+    pub fn dependency_for(&self, file: &Path) -> Option<&ProjectConfig> {
+        self.dependencies.iter()
+            .map(|dep| &dep.project)
+            .find(|project| file.starts_with(project.root_dir.join(&project.src_dir)))
+    }
+    
+    /// 设置路径查询缓存（通常与源文件快照共用）
+    pub fn set_path_cache(&mut self, paths: Arc<PathCache>) {
+        self.paths = paths;
+    }
+    
     /// 设置标准库目录
     pub fn set_stdlib_dir(&mut self, path: PathBuf) {
         self.stdlib_dir = path;
@@ -100,9 +168,12 @@ impl PackageResolver {
                 "NullPointerException".to_string(),
                 "IndexOutOfBoundsException".to_string(),
                 "IllegalArgumentException".to_string(),
+                "IllegalStateException".to_string(),
                 "ArithmeticException".to_string(),
                 // IOException 分支
                 "IOException".to_string(),
+                "NetworkException".to_string(),
+                "TimeoutException".to_string(),
                 // 工具函数
                 "isThrowable".to_string(),
                 "isException".to_string(),
@@ -128,8 +199,131 @@ impl PackageResolver {
                 "HttpServer".to_string(),
                 "HttpRequest".to_string(),
                 "HttpResponse".to_string(),
+                "HttpClientResponse".to_string(),
+                "RequestContext".to_string(),
             ],
         );
+        
+        // std.net.udp - Rust 内置模块，提供 UDP 网络功能
+        self.builtin_modules.insert(
+            "std.net.udp".to_string(),
+            vec![
+                "UDPSocket".to_string(),
+                "UDPPacket".to_string(),
+            ],
+        );
+        
+        // std.fs - Rust 内置模块，提供文件读写和目录操作
+        self.builtin_modules.insert(
+            "std.fs".to_string(),
+            vec![
+                "readFile".to_string(),
+                "writeFile".to_string(),
+                "appendFile".to_string(),
+                "exists".to_string(),
+                "remove".to_string(),
+                "mkdir".to_string(),
+                "readDir".to_string(),
+                "open".to_string(),
+                "File".to_string(),
+            ],
+        );
+        
+        // std.json - Rust 内置模块，提供 JSON 解析和序列化
+        self.builtin_modules.insert(
+            "std.json".to_string(),
+            vec![
+                "Json".to_string(),
+            ],
+        );
+        
+        // std.sync - Rust 内置模块，提供原子变量和限流器
+        self.builtin_modules.insert(
+            "std.sync".to_string(),
+            vec![
+                "Atomic".to_string(),
+                "AtomicFlag".to_string(),
+                "RateLimiter".to_string(),
+            ],
+        );
+        
+        // std.async - Rust 内置模块，提供 Future
+        self.builtin_modules.insert(
+            "std.async".to_string(),
+            vec!["Future".to_string()],
+        );
+        
+        // std.time - Rust 内置模块，提供时钟、睡眠和定时器
+        self.builtin_modules.insert(
+            "std.time".to_string(),
+            vec!["Time".to_string(), "Instant".to_string(), "Duration".to_string()],
+        );
+        
+        // std.os - Rust 内置模块，提供命令行参数
+        self.builtin_modules.insert(
+            "std.os".to_string(),
+            vec!["Os".to_string()],
+        );
+        
+        // std.net.dns - Rust 内置模块，提供域名解析
+        self.builtin_modules.insert(
+            "std.net.dns".to_string(),
+            vec!["Dns".to_string()],
+        );
+        
+        // std.log - Rust 内置模块，提供日志记录
+        self.builtin_modules.insert(
+            "std.log".to_string(),
+            vec!["Log".to_string()],
+        );
+        
+        // std.uuid - Rust 内置模块，提供随机 ID
+        self.builtin_modules.insert(
+            "std.uuid".to_string(),
+            vec!["Uuid".to_string()],
+        );
+        
+        // std.runtime - Rust 内置模块，提供 GC 控制、内存统计和弱引用
+        self.builtin_modules.insert(
+            "std.runtime".to_string(),
+            vec!["Runtime".to_string(), "WeakRef".to_string(), "WeakMap".to_string()],
+        );
+        
+        // std.convert - Rust 内置模块，提供字符串到数值的转换
+        self.builtin_modules.insert(
+            "std.convert".to_string(),
+            vec!["Convert".to_string()],
+        );
+        
+        // std.math - Rust 内置模块，提供数学函数和常量
+        self.builtin_modules.insert(
+            "std.math".to_string(),
+            vec!["Math".to_string()],
+        );
+        
+        // std.random - Rust 内置模块，提供伪随机数
+        self.builtin_modules.insert(
+            "std.random".to_string(),
+            vec!["Random".to_string()],
+        );
+        
+        // std.process - Rust 内置模块，提供子进程
+        self.builtin_modules.insert(
+            "std.process".to_string(),
+            vec!["Process".to_string(), "ProcessResult".to_string()],
+        );
+        
+        // std.regex - Rust 内置模块，提供正则表达式
+        self.builtin_modules.insert(
+            "std.regex".to_string(),
+            vec!["Regex".to_string(), "RegexMatch".to_string()],
+        );
+        
+        // std.encoding - Rust 内置模块，提供 base64、十六进制和 URL 编码
+        self.builtin_modules.insert(
+            "std.encoding".to_string(),
+            vec!["Encoding".to_string()],
+        );
     }
     
     /// 解析导入声明
@@ -147,11 +341,19 @@ impl PackageResolver {
             return self.resolve_std_import(import);
         }
         
-        // 项目内部包
-        if let Some(ref project) = self.project {
-            if full_path.starts_with(&project.package) {
-                return self.resolve_project_import(import, project);
+        // 项目内部包或依赖提供的包，取包名最长的匹配
+        let project = self.project.as_ref().filter(|project| full_path.starts_with(&project.package));
+        let dependency = self.dependencies.iter()
+            .map(|dep| &dep.project)
+            .filter(|dep| full_path == dep.package || full_path.starts_with(&format!("{}.", dep.package)))
+            .max_by_key(|dep| dep.package.len());
+        match (project, dependency) {
+            (Some(project), Some(dep)) if project.package.len() >= dep.package.len() => {
+                return Ok(self.resolve_project_import(import, project, ImportKind::Project));
             }
+            (_, Some(dep)) => return Ok(self.resolve_project_import(import, dep, ImportKind::External)),
+            (Some(project), None) => return Ok(self.resolve_project_import(import, project, ImportKind::Project)),
+            (None, None) => {}
         }
         
         // 外部依赖
@@ -221,7 +423,7 @@ impl PackageResolver {
         let file_name = format!("{}.{}", relative_path.replace('.', "/"), SOURCE_EXTENSION);
         let source_path = self.stdlib_dir.join(&file_name);
         
-        if source_path.exists() {
+        if self.paths.exists(&source_path) {
             let members = match &import.target {
                 ImportTarget::All => vec![], // 需要解析源文件获取
                 ImportTarget::Single(name) => vec![name.clone()],
@@ -239,8 +441,8 @@ impl PackageResolver {
         Err(format!("找不到标准库模块: {}", module_path))
     }
     
-    /// 解析项目内部导入
-    fn resolve_project_import(&self, import: &ImportDecl, project: &ProjectConfig) -> Result<ResolvedImport, String> {
+    /// 解析项目内部（或依赖项目中）的导入
+    fn resolve_project_import(&self, import: &ImportDecl, project: &ProjectConfig, kind: ImportKind) -> ResolvedImport {
         let module_path = match &import.target {
             ImportTarget::All => import.path.clone(),
             ImportTarget::Single(name) => format!("{}.{}", import.path, name),
@@ -252,15 +454,19 @@ impl PackageResolver {
             .and_then(|s| s.strip_prefix('.'))
             .unwrap_or(&module_path);
         
-        // 构建源文件路径
+        // 构建源文件路径：`a.b.C` 中 C 是文件，`a.b.*` 和 `a.b.{C, D}` 中 a.b 是目录
         let file_path = if relative_path.is_empty() {
             // 导入项目根包
             project.root_dir.join(&project.src_dir)
         } else {
             // 导入子包
             let path_parts: Vec<&str> = relative_path.split('.').collect();
+            let dir_parts = match &import.target {
+                ImportTarget::Single(_) => &path_parts[..path_parts.len() - 1],
+                ImportTarget::All | ImportTarget::Multiple(_) => &path_parts[..],
+            };
             let mut path = project.root_dir.join(&project.src_dir);
-            for part in &path_parts[..path_parts.len()-1] {
+            for part in dir_parts {
                 path = path.join(part);
             }
             path
@@ -286,17 +492,16 @@ impl PackageResolver {
             ImportTarget::Multiple(names) => names.clone(),
         };
         
-        Ok(ResolvedImport {
+        ResolvedImport {
             decl: import.clone(),
-            kind: ImportKind::Project,
+            kind,
             source_path: Some(source_file),
             members,
-        })
+        }
     }
     
-    /// 解析外部依赖导入
+    /// 解析没有任何依赖提供的外部导入（没有源文件）
     fn resolve_external_import(&self, import: &ImportDecl) -> Result<ResolvedImport, String> {
-        // 外部依赖目前未实现
         let members = match &import.target {
             ImportTarget::All => vec![],
             ImportTarget::Single(name) => vec![name.clone()],
@@ -325,6 +530,7 @@ impl PackageResolver {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs;
     
     #[test]
     fn test_resolve_std_builtin() {
@@ -338,4 +544,53 @@ mod tests {
         let result = resolver.resolve(&import).unwrap();
         assert_eq!(result.kind, ImportKind::StdBuiltin);
     }
+    
+    #[test]
+    fn test_path_dependencies() {
+        let dir = std::env::temp_dir().join(format!("qlang-deps-test-{}", std::process::id()));
+        let write = |path: &str, content: &str| {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+        write("app/project.toml", "name = \"app\"\npackage = \"com.acme\"\n[dependencies]\nutil = { path = \"../util\" }\n");
+        write("util/project.toml", "name = \"util\"\npackage = \"com.acme.util\"\nsrc = \"lib\"\n[dependencies]\nlog = { path = \"../log\" }\n");
+        write("log/project.toml", "name = \"log\"\npackage = \"org.log\"\n");
+        let project = Arc::new(ProjectConfig::load(&fs::canonicalize(dir.join("app")).unwrap().join(PROJECT_FILE), &[]).unwrap());
+        let import = |path: &str, name: &str| ImportDecl { path: path.to_string(), target: ImportTarget::Single(name.to_string()) };
+        
+        let mut resolver = PackageResolver::new(Some(project.clone()));
+        resolver.load_path_dependencies().unwrap();
+        let util_root = fs::canonicalize(dir.join("util")).unwrap();
+        
+        // 最长的包名优先：com.acme.util 属于依赖而不是项目本身
+        let resolved = resolver.resolve(&import("com.acme.util.text", "Strings")).unwrap();
+        assert_eq!(resolved.kind, ImportKind::External);
+        assert_eq!(resolved.source_path, Some(util_root.join("lib/text/Strings.q")));
+        let resolved = resolver.resolve(&import("com.acme.models", "User")).unwrap();
+        assert_eq!(resolved.kind, ImportKind::Project);
+        let app_src = project.root_dir.join("src");
+        assert_eq!(resolved.source_path, Some(app_src.join("models/User.q")));
+        // `.*` 导入整个目录
+        let all = ImportDecl { path: "com.acme.models".to_string(), target: ImportTarget::All };
+        assert_eq!(resolver.resolve(&all).unwrap().source_path, Some(app_src.join("models")));
+        // 依赖的依赖也能解析，包名按段匹配
+        let resolved = resolver.resolve(&import("org.log", "Log")).unwrap();
+        assert_eq!(resolved.kind, ImportKind::External);
+        assert!(resolver.resolve(&import("org.logger", "Log")).unwrap().source_path.is_none());
+        assert_eq!(resolver.dependency_for(&util_root.join("lib/text/Strings.q")).map(|p| p.name.as_str()), Some("util"));
+        assert!(resolver.dependency_for(&util_root.join("project.toml")).is_none());
+        
+        // 两个依赖提供同一个包
+        write("log/project.toml", "name = \"log\"\npackage = \"com.acme.util\"\n");
+        let error = PackageResolver::new(Some(project.clone())).load_path_dependencies().unwrap_err();
+        assert!(error.starts_with("dependencies `util` ("), "{}", error);
+        assert!(error.ends_with(") both provide package com.acme.util"), "{}", error);
+        
+        write("log/project.toml", "name = \"log\"\npackage = \"com.acme\"\n");
+        let error = PackageResolver::new(Some(project)).load_path_dependencies().unwrap_err();
+        assert!(error.ends_with("provides package com.acme, which is already the package of this project"), "{}", error);
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,186 @@
+//! 源文件快照
+//!
+//! 一次编译中每个源文件只读取一次：第一次读取的内容记入快照，之后的并行解析、出错时显示源码都使用快照，
+//! 编译过程中文件被修改（例如编辑器保存时格式化）也不会混用新旧两个版本。
+//! 编译结束时重新读取一遍，内容与快照不同的文件说明编译结果可能已经过时。
+//!
+//! 文件内容通过 [`SourceProvider`] 读取：命令行直接读磁盘，编辑器集成可以优先返回尚未保存的内存内容。
+//! 快照以规范路径为键，规范化的结果记在同一次编译共用的 [`PathCache`] 中。
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::config::VERSION;
+use crate::parser::cache::content_hash;
+use super::path_cache::PathCache;
+
+/// 按路径提供源文件的当前内容
+pub trait SourceProvider: Send + Sync {
+    fn read(&self, path: &Path) -> io::Result<String>;
+}
+
+/// 从磁盘读取
+#[derive(Debug, Default)]
+pub struct FsProvider;
+
+impl SourceProvider for FsProvider {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+}
+
+/// 快照中的一个文件
+struct Snapshot {
+    content: Arc<str>,
+    /// 内容哈希，与解析缓存的键相同
+    hash: u64,
+}
+
+/// 一次编译读取的源文件（按规范化路径）
+pub struct SourceStore {
+    provider: Box<dyn SourceProvider>,
+    snapshots: Mutex<BTreeMap<PathBuf, Snapshot>>,
+    paths: Arc<PathCache>,
+}
+
+impl Default for SourceStore {
+    fn default() -> Self {
+        Self::new(FsProvider)
+    }
+}
+
+impl std::fmt::Debug for SourceStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SourceStore").field("files", &self.snapshots.lock().len()).finish()
+    }
+}
+
+impl SourceStore {
+    pub fn new(provider: impl SourceProvider + 'static) -> Self {
+        Self { provider: Box::new(provider), snapshots: Mutex::new(BTreeMap::new()), paths: Arc::new(PathCache::new()) }
+    }
+
+    /// 本次编译的路径查询缓存，加载依赖和查找项目时共用
+    pub fn paths(&self) -> &Arc<PathCache> {
+        &self.paths
+    }
+
+    fn key(&self, path: &Path) -> PathBuf {
+        self.paths.canonicalize(path)
+    }
+
+    /// 文件的内容：第一次读取时记入快照，之后返回快照中的内容
+    pub fn read(&self, path: &Path) -> io::Result<Arc<str>> {
+        let key = self.key(path);
+        if let Some(snapshot) = self.snapshots.lock().get(&key) {
+            return Ok(snapshot.content.clone());
+        }
+        // 读取时不持有锁，多个解析线程可以同时读不同的文件；同时读同一个文件时以先记入的为准
+        let content: Arc<str> = self.provider.read(path)?.into();
+        let hash = content_hash(&content);
+        let mut snapshots = self.snapshots.lock();
+        Ok(snapshots.entry(key).or_insert(Snapshot { content, hash }).content.clone())
+    }
+
+    /// 快照中的内容，没有读取过的文件返回 None
+    pub fn snapshot(&self, path: &Path) -> Option<Arc<str>> {
+        self.snapshots.lock().get(&self.key(path)).map(|snapshot| snapshot.content.clone())
+    }
+
+    /// 重新读取快照中的文件，返回内容已经改变（或无法再读取）的文件
+    pub fn changed_files(&self) -> Vec<PathBuf> {
+        self.snapshots
+            .lock()
+            .iter()
+            .filter(|(path, snapshot)| {
+                self.provider.read(path).map_or(true, |current| content_hash(&current) != snapshot.hash)
+            })
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    /// 锁文件格式的清单：编译器版本，以及每个文件的内容哈希和相对于 root 的路径（按路径排序）
+    pub fn manifest(&self, root: &Path) -> String {
+        let mut out = format!("# Source files of the last build. Generated by `build`, do not edit.\ncompiler {}\n", VERSION);
+        for (path, snapshot) in self.snapshots.lock().iter() {
+            let relative = path.strip_prefix(root).unwrap_or(path);
+            out.push_str(&format!("{:016x}  {}\n", snapshot.hash, relative.to_string_lossy().replace('\\', "/")));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// 内存中的文件，测试时在两次读取之间修改内容
+    #[derive(Clone, Default)]
+    struct MemoryProvider {
+        files: Arc<Mutex<HashMap<PathBuf, String>>>,
+    }
+
+    impl MemoryProvider {
+        fn set(&self, path: &str, content: &str) {
+            self.files.lock().insert(PathBuf::from(path), content.to_string());
+        }
+    }
+
+    impl SourceProvider for MemoryProvider {
+        fn read(&self, path: &Path) -> io::Result<String> {
+            self.files.lock().get(path).cloned().ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+        }
+    }
+
+    #[test]
+    fn test_reads_are_served_from_the_snapshot() {
+        let files = MemoryProvider::default();
+        files.set("/p/src/a.q", "func a() {}");
+        let store = SourceStore::new(files.clone());
+        assert_eq!(&*store.read(Path::new("/p/src/a.q")).unwrap(), "func a() {}");
+        assert!(store.changed_files().is_empty());
+
+        // 编译中途文件被保存：之后的读取仍然得到快照中的内容，但结束时能发现改变
+        files.set("/p/src/a.q", "func a() { println(1) }");
+        assert_eq!(&*store.read(Path::new("/p/src/a.q")).unwrap(), "func a() {}");
+        assert_eq!(store.changed_files(), vec![PathBuf::from("/p/src/a.q")]);
+
+        assert!(store.read(Path::new("/p/src/missing.q")).is_err());
+        assert!(store.snapshot(Path::new("/p/src/missing.q")).is_none());
+    }
+
+    #[test]
+    fn test_deleted_file_counts_as_changed() {
+        let files = MemoryProvider::default();
+        files.set("/p/b.q", "b");
+        let store = SourceStore::new(files.clone());
+        store.read(Path::new("/p/b.q")).unwrap();
+        files.files.lock().clear();
+        assert_eq!(store.changed_files(), vec![PathBuf::from("/p/b.q")]);
+    }
+
+    #[test]
+    fn test_manifest_lists_snapshot_hashes() {
+        let files = MemoryProvider::default();
+        files.set("/p/src/main.q", "main");
+        files.set("/p/src/lib/util.q", "util");
+        let store = SourceStore::new(files.clone());
+        store.read(Path::new("/p/src/main.q")).unwrap();
+        store.read(Path::new("/p/src/lib/util.q")).unwrap();
+        // 快照之后的修改不影响清单
+        files.set("/p/src/main.q", "changed");
+
+        let manifest = store.manifest(Path::new("/p"));
+        let lines: Vec<&str> = manifest.lines().collect();
+        assert_eq!(lines[1], format!("compiler {}", VERSION));
+        assert_eq!(lines[2], format!("{:016x}  src/lib/util.q", content_hash("util")));
+        assert_eq!(lines[3], format!("{:016x}  src/main.q", content_hash("main")));
+        assert_eq!(lines.len(), 4);
+    }
+}
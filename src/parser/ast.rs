@@ -7,6 +7,10 @@
 use crate::lexer::Span;
 use crate::types::Type;
 
+/// 丢弃绑定 `_` 的名字：出现在变量、参数和 for-in 变量的位置时不创建可读的绑定，
+/// 同一个模式或参数列表中可以出现多次
+pub const DISCARD: &str = "_";
+
 /// 二元运算符
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BinOp {
@@ -95,6 +99,25 @@ pub enum AssignOp {
     ShrAssign,
 }
 
+impl AssignOp {
+    /// 复合赋值对应的二元运算符（`a += b` 即 `a = a + b`），简单赋值返回 None
+    pub fn binary_op(self) -> Option<BinOp> {
+        match self {
+            AssignOp::Assign => None,
+            AssignOp::AddAssign => Some(BinOp::Add),
+            AssignOp::SubAssign => Some(BinOp::Sub),
+            AssignOp::MulAssign => Some(BinOp::Mul),
+            AssignOp::DivAssign => Some(BinOp::Div),
+            AssignOp::ModAssign => Some(BinOp::Mod),
+            AssignOp::BitAndAssign => Some(BinOp::BitAnd),
+            AssignOp::BitOrAssign => Some(BinOp::BitOr),
+            AssignOp::BitXorAssign => Some(BinOp::BitXor),
+            AssignOp::ShlAssign => Some(BinOp::Shl),
+            AssignOp::ShrAssign => Some(BinOp::Shr),
+        }
+    }
+}
+
 /// 类型注解
 #[derive(Debug, Clone, PartialEq)]
 pub struct TypeAnnotation {
@@ -238,6 +261,13 @@ pub enum Expr {
         call: Box<Expr>,  // 必须是一个 Call 表达式
         span: Span,
     },
+    /// 创建通道 chan<T>() / chan<T>(capacity)
+    ChannelNew {
+        element_type: Type,
+        /// 缓冲区容量，省略时为无缓冲通道
+        capacity: Option<Box<Expr>>,
+        span: Span,
+    },
     /// 赋值表达式
     Assign {
         target: Box<Expr>,
@@ -305,7 +335,7 @@ pub enum Expr {
         inclusive: bool,
         span: Span,
     },
-    /// if 表达式（返回值）
+    /// 条件表达式 `cond ? a : b`
     IfExpr {
         condition: Box<Expr>,
         then_branch: Box<Expr>,
@@ -342,12 +372,14 @@ pub enum Expr {
         /// 位置信息
         span: Span,
     },
-    /// new 表达式 new MyClass(args)
+    /// new 表达式 new MyClass(args) 或 new MyClass { field: value }
     New {
         /// 类名
         class_name: String,
         /// 构造参数
         args: Vec<Expr>,
+        /// 花括号形式的字段赋值（此时 args 为空）
+        fields: Option<Vec<(String, Expr)>>,
         /// 位置信息
         span: Span,
     },
@@ -389,6 +421,7 @@ impl Expr {
             Expr::Grouping { span, .. } => *span,
             Expr::Call { span, .. } => *span,
             Expr::Go { span, .. } => *span,
+            Expr::ChannelNew { span, .. } => *span,
             Expr::Assign { span, .. } => *span,
             Expr::Index { span, .. } => *span,
             Expr::Member { span, .. } => *span,
@@ -417,7 +450,7 @@ impl Expr {
     pub fn is_lvalue(&self) -> bool {
         matches!(
             self,
-            Expr::Identifier { .. } | Expr::Index { .. } | Expr::Member { .. }
+            Expr::Identifier { .. } | Expr::Index { .. } | Expr::Member { .. } | Expr::StaticMember { .. }
         )
     }
 }
@@ -430,6 +463,13 @@ pub enum Stmt {
         expr: Expr,
         span: Span,
     },
+    /// 并行赋值 `a, b = b, a`：先求值全部目标的接收者和下标、再求值全部右值，最后从左到右赋值
+    ParallelAssign {
+        /// 赋值目标（变量、成员或下标），与 values 一一对应
+        targets: Vec<Expr>,
+        values: Vec<Expr>,
+        span: Span,
+    },
     /// Print 语句（内置）
     Print {
         expr: Expr,
@@ -508,6 +548,19 @@ pub enum Stmt {
         arms: Vec<MatchArm>,
         span: Span,
     },
+    /// select 语句：等待多个通道操作中的一个完成
+    Select {
+        cases: Vec<SelectCase>,
+        span: Span,
+    },
+    /// switch 语句：执行值等于主体表达式的分支，分支之间不贯穿
+    Switch {
+        expr: Expr,
+        cases: Vec<SwitchCase>,
+        /// default 分支（可选）
+        default: Option<Box<Stmt>>,
+        span: Span,
+    },
     /// struct 定义
     StructDef {
         name: String,
@@ -518,6 +571,8 @@ pub enum Stmt {
         /// 实现的接口列表
         interfaces: Vec<String>,
         fields: Vec<StructField>,
+        /// 静态字段和常量（`static var` / `const`）
+        static_fields: Vec<ClassField>,
         methods: Vec<StructMethod>,
         span: Span,
     },
@@ -581,6 +636,14 @@ pub enum Stmt {
         finally_block: Option<Box<Stmt>>,
         span: Span,
     },
+    /// recovering-onPanic 语句：body 中（包括其中调用的函数）发生的 panic 由 handler 处理，
+    /// handler 中可以用 `recover()` 取得 panic 的值
+    Recovering {
+        body: Box<Stmt>,
+        panic_param: Option<String>,  // onPanic 的参数名，如 onPanic(e)
+        handler: Box<Stmt>,
+        span: Span,
+    },
     /// throw 语句
     Throw {
         value: Expr,
@@ -597,6 +660,8 @@ pub enum Stmt {
         return_type: Option<TypeAnnotation>,
         body: Box<Stmt>,
         visibility: Visibility,
+        /// 属性（`@no_optimize` 等）
+        attributes: Vec<Attribute>,
         span: Span,
     },
     /// 包声明（必须是文件第一条非注释语句）
@@ -645,6 +710,45 @@ pub struct MatchArm {
     pub span: Span,
 }
 
+/// select 分支
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectCase {
+    pub kind: SelectCaseKind,
+    /// 分支体
+    pub body: Box<Stmt>,
+    /// 位置信息
+    pub span: Span,
+}
+
+/// switch 分支：`case 1, 2 => 分支体`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwitchCase {
+    /// 分支的值，任意一个等于主体表达式时执行分支体
+    pub values: Vec<Expr>,
+    /// 分支体
+    pub body: Box<Stmt>,
+    /// 位置信息
+    pub span: Span,
+}
+
+/// select 分支的通道操作
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectCaseKind {
+    /// case var v = ch.receive() / case ch.receive()
+    Receive {
+        channel: Expr,
+        /// 接收到的值绑定的变量名
+        binding: Option<String>,
+    },
+    /// case ch.send(value)
+    Send {
+        channel: Expr,
+        value: Expr,
+    },
+    /// default：没有分支就绪时立即执行
+    Default,
+}
+
 /// match 模式
 #[derive(Debug, Clone, PartialEq)]
 pub enum MatchPattern {
@@ -667,6 +771,14 @@ pub enum MatchPattern {
         name: String,
         type_ann: TypeAnnotation,
     },
+    /// 带关联数据的枚举变体 Shape::Rect(w, _)，按声明顺序匹配每个字段
+    ///
+    /// 字段模式只能是变量绑定、`_` 或字面量
+    EnumVariant {
+        enum_name: String,
+        variant: String,
+        fields: Vec<MatchPattern>,
+    },
 }
 
 /// 可见性修饰符（Kotlin 风格）
@@ -706,6 +818,9 @@ pub struct StructMethod {
     pub return_type: Option<TypeAnnotation>,
     pub body: Box<Stmt>,
     pub visibility: Visibility,
+    /// 是否是静态方法（没有 this）
+    pub is_static: bool,
+    pub attributes: Vec<Attribute>,
     pub span: Span,
 }
 
@@ -733,11 +848,42 @@ pub struct ClassMethod {
     pub visibility: Visibility,
     pub is_static: bool,
     pub is_override: bool,
+    /// 是否禁止子类重写（final）
+    pub is_final: bool,
     /// 是否是抽象方法
     pub is_abstract: bool,
+    pub attributes: Vec<Attribute>,
+    pub span: Span,
+}
+
+/// 函数和方法上的属性，写在 `func` 之前：`@no_optimize`、`@inline(never)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attribute {
+    pub kind: AttributeKind,
     pub span: Span,
 }
 
+/// 已知的属性；其他名字是语法错误，避免拼写错误的属性被悄悄忽略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeKind {
+    /// 不对这个函数做常量折叠、指令融合、尾调用和条件跳转的优化（排查编译器缺陷时使用）
+    NoOptimize,
+    /// 内联提示（保留，目前没有内联优化）
+    InlineNever,
+    InlineAlways,
+}
+
+impl AttributeKind {
+    /// 源码中的写法（不含 `@`）
+    pub fn name(self) -> &'static str {
+        match self {
+            AttributeKind::NoOptimize => "no_optimize",
+            AttributeKind::InlineNever => "inline(never)",
+            AttributeKind::InlineAlways => "inline(always)",
+        }
+    }
+}
+
 /// interface 方法签名
 #[derive(Debug, Clone, PartialEq)]
 pub struct InterfaceMethod {
@@ -768,10 +914,19 @@ pub struct EnumVariant {
 }
 
 impl Stmt {
+    /// 是否是 `super.init(...)` 语句（只能作为子类 init 的第一条语句）
+    pub fn is_super_init_call(&self) -> bool {
+        let Stmt::Expression { expr: Expr::Call { callee, .. }, .. } = self else {
+            return false;
+        };
+        matches!(callee.as_ref(), Expr::Member { object, member, .. } if member == "init" && matches!(object.as_ref(), Expr::Super { .. }))
+    }
+    
     /// 获取语句的位置信息
     pub fn span(&self) -> Span {
         match self {
             Stmt::Expression { span, .. } => *span,
+            Stmt::ParallelAssign { span, .. } => *span,
             Stmt::Print { span, .. } => *span,
             Stmt::VarDecl { span, .. } => *span,
             Stmt::ConstDecl { span, .. } => *span,
@@ -784,6 +939,8 @@ impl Stmt {
             Stmt::Continue { span, .. } => *span,
             Stmt::Return { span, .. } => *span,
             Stmt::Match { span, .. } => *span,
+            Stmt::Select { span, .. } => *span,
+            Stmt::Switch { span, .. } => *span,
             Stmt::StructDef { span, .. } => *span,
             Stmt::ClassDef { span, .. } => *span,
             Stmt::InterfaceDef { span, .. } => *span,
@@ -791,6 +948,7 @@ impl Stmt {
             Stmt::EnumDef { span, .. } => *span,
             Stmt::TypeAlias { span, .. } => *span,
             Stmt::TryCatch { span, .. } => *span,
+            Stmt::Recovering { span, .. } => *span,
             Stmt::Throw { span, .. } => *span,
             Stmt::FnDef { span, .. } => *span,
             Stmt::Package { span, .. } => *span,
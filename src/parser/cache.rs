@@ -0,0 +1,1639 @@
+//! 解析缓存
+//!
+//! 项目中的源文件解析后，AST 以紧凑的二进制格式写入项目根目录的 `.qcache/parse/`，
+//! 文件名是源码内容的哈希。下次加载内容相同的文件时直接读出 AST，跳过词法和语法分析。
+//!
+//! 缓存文件开头记录格式版本和编译器版本，两者任一不同（或数据损坏）时视为未命中，重新解析后覆盖。
+//! 只缓存解析成功的文件；写缓存失败（如目录只读）不影响编译。
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::VERSION;
+use crate::lexer::Span;
+use crate::types::{Type, TypeBound, TypeVar};
+
+use super::ast::*;
+
+/// 缓存目录（相对于项目根目录）
+pub const CACHE_DIR: &str = ".qcache";
+
+/// 缓存文件的魔数
+const MAGIC: &[u8; 4] = b"QAST";
+
+/// 编码格式版本，AST 结构变化时递增
+const FORMAT_VERSION: u32 = 7;
+
+/// 源码内容的哈希（FNV-1a），不随编译器所用的 Rust 版本变化
+pub fn content_hash(source: &str) -> u64 {
+    bytes_hash(source.as_bytes())
+}
+
+/// 字节序列的哈希（FNV-1a）
+pub fn bytes_hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// 项目的解析缓存
+#[derive(Debug, Clone)]
+pub struct ParseCache {
+    dir: PathBuf,
+}
+
+impl ParseCache {
+    /// 项目根目录下的解析缓存
+    pub fn new(project_root: &Path) -> Self {
+        Self { dir: project_root.join(CACHE_DIR).join("parse") }
+    }
+
+    fn entry_path(&self, source: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}.ast", content_hash(source)))
+    }
+
+    /// 读取与源码内容对应的 AST
+    pub fn get(&self, source: &str) -> Option<Program> {
+        let bytes = fs::read(self.entry_path(source)).ok()?;
+        decode_program(&bytes, source)
+    }
+
+    /// 写入源码解析出的 AST
+    pub fn put(&self, source: &str, program: &Program) {
+        if fs::create_dir_all(&self.dir).is_err() {
+            return;
+        }
+        // 先写临时文件再改名，并发的进程不会读到写了一半的缓存
+        let path = self.entry_path(source);
+        let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+        if fs::write(&tmp, encode_program(program, source)).is_ok() && fs::rename(&tmp, &path).is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+    }
+}
+
+/// 编码 AST，头部记录版本和源码的长度与哈希
+pub fn encode_program(program: &Program, source: &str) -> Vec<u8> {
+    let mut out = Encoder::default();
+    out.bytes.extend_from_slice(MAGIC);
+    out.bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    out.str(VERSION);
+    out.uint(source.len() as u64);
+    out.bytes.extend_from_slice(&content_hash(source).to_le_bytes());
+    program.encode(&mut out);
+    out.bytes
+}
+
+/// 解码 AST；版本或源码不匹配、数据损坏时返回 None
+pub fn decode_program(bytes: &[u8], source: &str) -> Option<Program> {
+    let mut input = Decoder { bytes, pos: 0 };
+    if input.take(4)? != MAGIC || u32::from_le_bytes(input.take(4)?.try_into().ok()?) != FORMAT_VERSION {
+        return None;
+    }
+    if input.string()? != VERSION || input.uint()? != source.len() as u64 {
+        return None;
+    }
+    if u64::from_le_bytes(input.take(8)?.try_into().ok()?) != content_hash(source) {
+        return None;
+    }
+    let program = Program::decode(&mut input)?;
+    (input.pos == bytes.len()).then_some(program)
+}
+
+// ============================================================================
+// 编码器和解码器
+// ============================================================================
+
+/// 编码器，字节码块的二进制格式（见 [`crate::compiler::serialize`]）也使用它
+#[derive(Default)]
+pub(crate) struct Encoder {
+    pub(crate) bytes: Vec<u8>,
+}
+
+impl Encoder {
+    /// 无符号整数（LEB128 变长编码）
+    pub(crate) fn uint(&mut self, mut value: u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.bytes.push(byte);
+                return;
+            }
+            self.bytes.push(byte | 0x80);
+        }
+    }
+
+    pub(crate) fn tag(&mut self, tag: u8) {
+        self.bytes.push(tag);
+    }
+
+    pub(crate) fn str(&mut self, s: &str) {
+        self.uint(s.len() as u64);
+        self.bytes.extend_from_slice(s.as_bytes());
+    }
+}
+
+pub(crate) struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    /// 是否已经读完所有字节
+    pub(crate) fn is_at_end(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let end = self.pos.checked_add(len)?;
+        let slice = self.bytes.get(self.pos..end)?;
+        self.pos = end;
+        Some(slice)
+    }
+
+    pub(crate) fn uint(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self.take(1)?.first()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    pub(crate) fn tag(&mut self) -> Option<u8> {
+        self.take(1)?.first().copied()
+    }
+
+    pub(crate) fn string(&mut self) -> Option<String> {
+        let len = usize::try_from(self.uint()?).ok()?;
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+}
+
+/// 可以写入解析缓存的值
+pub(crate) trait Codec: Sized {
+    fn encode(&self, out: &mut Encoder);
+    fn decode(input: &mut Decoder) -> Option<Self>;
+}
+
+impl Codec for bool {
+    fn encode(&self, out: &mut Encoder) {
+        out.tag(*self as u8);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        match input.tag()? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl Codec for usize {
+    fn encode(&self, out: &mut Encoder) {
+        out.uint(*self as u64);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        usize::try_from(input.uint()?).ok()
+    }
+}
+
+impl Codec for u64 {
+    fn encode(&self, out: &mut Encoder) {
+        out.uint(*self);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        input.uint()
+    }
+}
+
+impl Codec for i128 {
+    fn encode(&self, out: &mut Encoder) {
+        out.bytes.extend_from_slice(&self.to_le_bytes());
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(i128::from_le_bytes(input.take(16)?.try_into().ok()?))
+    }
+}
+
+impl Codec for f64 {
+    fn encode(&self, out: &mut Encoder) {
+        out.bytes.extend_from_slice(&self.to_bits().to_le_bytes());
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(f64::from_bits(u64::from_le_bytes(input.take(8)?.try_into().ok()?)))
+    }
+}
+
+impl Codec for char {
+    fn encode(&self, out: &mut Encoder) {
+        out.uint(*self as u64);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        char::from_u32(u32::try_from(input.uint()?).ok()?)
+    }
+}
+
+impl Codec for String {
+    fn encode(&self, out: &mut Encoder) {
+        out.str(self);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        input.string()
+    }
+}
+
+impl<T: Codec> Codec for Box<T> {
+    fn encode(&self, out: &mut Encoder) {
+        (**self).encode(out);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        T::decode(input).map(Box::new)
+    }
+}
+
+impl<T: Codec> Codec for Option<T> {
+    fn encode(&self, out: &mut Encoder) {
+        match self {
+            None => out.tag(0),
+            Some(value) => {
+                out.tag(1);
+                value.encode(out);
+            }
+        }
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        match input.tag()? {
+            0 => Some(None),
+            1 => Some(Some(T::decode(input)?)),
+            _ => None,
+        }
+    }
+}
+
+impl<T: Codec> Codec for Vec<T> {
+    fn encode(&self, out: &mut Encoder) {
+        out.uint(self.len() as u64);
+        for item in self {
+            item.encode(out);
+        }
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        let len = usize::decode(input)?;
+        // 长度来自文件内容，不按它预先分配
+        let mut items = Vec::new();
+        for _ in 0..len {
+            items.push(T::decode(input)?);
+        }
+        Some(items)
+    }
+}
+
+impl<A: Codec, B: Codec> Codec for (A, B) {
+    fn encode(&self, out: &mut Encoder) {
+        self.0.encode(out);
+        self.1.encode(out);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some((A::decode(input)?, B::decode(input)?))
+    }
+}
+
+impl Codec for Span {
+    fn encode(&self, out: &mut Encoder) {
+        self.start.encode(out);
+        self.end.encode(out);
+        self.line.encode(out);
+        self.column.encode(out);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(Span::new(usize::decode(input)?, usize::decode(input)?, usize::decode(input)?, usize::decode(input)?))
+    }
+}
+
+// ============================================================================
+// 类型
+// ============================================================================
+
+impl Codec for TypeBound {
+    fn encode(&self, out: &mut Encoder) {
+        self.trait_name.encode(out);
+        self.type_args.encode(out);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(TypeBound { trait_name: String::decode(input)?, type_args: Vec::decode(input)? })
+    }
+}
+
+impl Codec for Type {
+    fn encode(&self, out: &mut Encoder) {
+        match self {
+            Type::Int => out.tag(0),
+            Type::Uint => out.tag(1),
+            Type::I8 => out.tag(2),
+            Type::I16 => out.tag(3),
+            Type::I32 => out.tag(4),
+            Type::I64 => out.tag(5),
+            Type::U8 => out.tag(6),
+            Type::U16 => out.tag(7),
+            Type::U32 => out.tag(8),
+            Type::U64 => out.tag(9),
+            Type::F32 => out.tag(10),
+            Type::F64 => out.tag(11),
+            Type::Bool => out.tag(12),
+            Type::Byte => out.tag(13),
+            Type::Char => out.tag(14),
+            Type::String => out.tag(15),
+            Type::Void => out.tag(16),
+            Type::Null => out.tag(17),
+            Type::Unknown => out.tag(18),
+            Type::Dynamic => out.tag(19),
+            Type::Never => out.tag(20),
+            Type::Array { element_type, size } => {
+                out.tag(21);
+                element_type.encode(out);
+                size.encode(out);
+            }
+            Type::Slice { element_type } => {
+                out.tag(22);
+                element_type.encode(out);
+            }
+            Type::Map { key_type, value_type } => {
+                out.tag(23);
+                key_type.encode(out);
+                value_type.encode(out);
+            }
+            Type::Channel { element_type } => {
+                out.tag(24);
+                element_type.encode(out);
+            }
+            Type::Tuple(types) => {
+                out.tag(25);
+                types.encode(out);
+            }
+            Type::Function { param_types, return_type, required_params } => {
+                out.tag(26);
+                param_types.encode(out);
+                return_type.encode(out);
+                required_params.encode(out);
+            }
+            Type::Nullable(inner) => {
+                out.tag(27);
+                inner.encode(out);
+            }
+            Type::Pointer(inner) => {
+                out.tag(28);
+                inner.encode(out);
+            }
+            Type::Class(name) => {
+                out.tag(29);
+                name.encode(out);
+            }
+            Type::Struct(name) => {
+                out.tag(30);
+                name.encode(out);
+            }
+            Type::Interface(name) => {
+                out.tag(31);
+                name.encode(out);
+            }
+            Type::Trait(name) => {
+                out.tag(32);
+                name.encode(out);
+            }
+            Type::Enum(name) => {
+                out.tag(33);
+                name.encode(out);
+            }
+            Type::Alias { name, actual_type } => {
+                out.tag(34);
+                name.encode(out);
+                actual_type.encode(out);
+            }
+            Type::TypeParameter { name, bounds } => {
+                out.tag(35);
+                name.encode(out);
+                bounds.encode(out);
+            }
+            Type::Generic { base_type, type_args } => {
+                out.tag(36);
+                base_type.encode(out);
+                type_args.encode(out);
+            }
+            Type::TypeVar(var) => {
+                out.tag(37);
+                var.id.encode(out);
+                var.name.encode(out);
+            }
+            Type::AssociatedType { base_type, name } => {
+                out.tag(38);
+                base_type.encode(out);
+                name.encode(out);
+            }
+            Type::Infer => out.tag(39),
+            Type::Error => out.tag(40),
+        }
+    }
+
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(match input.tag()? {
+            0 => Type::Int,
+            1 => Type::Uint,
+            2 => Type::I8,
+            3 => Type::I16,
+            4 => Type::I32,
+            5 => Type::I64,
+            6 => Type::U8,
+            7 => Type::U16,
+            8 => Type::U32,
+            9 => Type::U64,
+            10 => Type::F32,
+            11 => Type::F64,
+            12 => Type::Bool,
+            13 => Type::Byte,
+            14 => Type::Char,
+            15 => Type::String,
+            16 => Type::Void,
+            17 => Type::Null,
+            18 => Type::Unknown,
+            19 => Type::Dynamic,
+            20 => Type::Never,
+            21 => Type::Array { element_type: Box::decode(input)?, size: usize::decode(input)? },
+            22 => Type::Slice { element_type: Box::decode(input)? },
+            23 => Type::Map { key_type: Box::decode(input)?, value_type: Box::decode(input)? },
+            24 => Type::Channel { element_type: Box::decode(input)? },
+            25 => Type::Tuple(Vec::decode(input)?),
+            26 => Type::Function {
+                param_types: Vec::decode(input)?,
+                return_type: Box::decode(input)?,
+                required_params: usize::decode(input)?,
+            },
+            27 => Type::Nullable(Box::decode(input)?),
+            28 => Type::Pointer(Box::decode(input)?),
+            29 => Type::Class(String::decode(input)?),
+            30 => Type::Struct(String::decode(input)?),
+            31 => Type::Interface(String::decode(input)?),
+            32 => Type::Trait(String::decode(input)?),
+            33 => Type::Enum(String::decode(input)?),
+            34 => Type::Alias { name: String::decode(input)?, actual_type: Box::decode(input)? },
+            35 => Type::TypeParameter { name: String::decode(input)?, bounds: Vec::decode(input)? },
+            36 => Type::Generic { base_type: Box::decode(input)?, type_args: Vec::decode(input)? },
+            37 => Type::TypeVar(TypeVar { id: u64::decode(input)?, name: Option::decode(input)? }),
+            38 => Type::AssociatedType { base_type: Box::decode(input)?, name: String::decode(input)? },
+            39 => Type::Infer,
+            40 => Type::Error,
+            _ => return None,
+        })
+    }
+}
+
+// ============================================================================
+// AST
+// ============================================================================
+
+/// 无数据的枚举按变体序号编码
+macro_rules! unit_enum_codec {
+    ($ty:ident { $($variant:ident),* $(,)? }) => {
+        impl Codec for $ty {
+            fn encode(&self, out: &mut Encoder) {
+                out.tag(*self as u8);
+            }
+            fn decode(input: &mut Decoder) -> Option<Self> {
+                const VARIANTS: &[$ty] = &[$($ty::$variant),*];
+                VARIANTS.get(input.tag()? as usize).copied()
+            }
+        }
+    };
+}
+
+unit_enum_codec!(BinOp {
+    Add, Sub, Mul, Div, Mod, Pow, Eq, Ne, Lt, Le, Gt, Ge, And, Or, BitAnd, BitOr, BitXor, Shl, Shr,
+});
+unit_enum_codec!(UnaryOp { Neg, Not, BitNot });
+unit_enum_codec!(AssignOp {
+    Assign, AddAssign, SubAssign, MulAssign, DivAssign, ModAssign,
+    BitAndAssign, BitOrAssign, BitXorAssign, ShlAssign, ShrAssign,
+});
+unit_enum_codec!(Visibility { Public, Internal, Private, Protected });
+unit_enum_codec!(AttributeKind { NoOptimize, InlineNever, InlineAlways });
+
+impl Codec for Attribute {
+    fn encode(&self, out: &mut Encoder) {
+        self.kind.encode(out);
+        self.span.encode(out);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(Attribute { kind: AttributeKind::decode(input)?, span: Span::decode(input)? })
+    }
+}
+
+impl Codec for TypeAnnotation {
+    fn encode(&self, out: &mut Encoder) {
+        self.ty.encode(out);
+        self.span.encode(out);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(TypeAnnotation { ty: Type::decode(input)?, span: Span::decode(input)? })
+    }
+}
+
+impl Codec for TypeParam {
+    fn encode(&self, out: &mut Encoder) {
+        self.name.encode(out);
+        self.bounds.encode(out);
+        self.default_type.encode(out);
+        self.span.encode(out);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(TypeParam {
+            name: String::decode(input)?,
+            bounds: Vec::decode(input)?,
+            default_type: Option::decode(input)?,
+            span: Span::decode(input)?,
+        })
+    }
+}
+
+impl Codec for WhereClause {
+    fn encode(&self, out: &mut Encoder) {
+        self.type_param.encode(out);
+        self.bounds.encode(out);
+        self.span.encode(out);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(WhereClause { type_param: String::decode(input)?, bounds: Vec::decode(input)?, span: Span::decode(input)? })
+    }
+}
+
+impl Codec for StringInterpPart {
+    fn encode(&self, out: &mut Encoder) {
+        match self {
+            StringInterpPart::Literal(text) => {
+                out.tag(0);
+                text.encode(out);
+            }
+            StringInterpPart::Expr(expr) => {
+                out.tag(1);
+                expr.encode(out);
+            }
+        }
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        match input.tag()? {
+            0 => Some(StringInterpPart::Literal(String::decode(input)?)),
+            1 => Some(StringInterpPart::Expr(Expr::decode(input)?)),
+            _ => None,
+        }
+    }
+}
+
+impl Codec for FnParam {
+    fn encode(&self, out: &mut Encoder) {
+        self.name.encode(out);
+        self.type_ann.encode(out);
+        self.default.encode(out);
+        self.variadic.encode(out);
+        self.is_field.encode(out);
+        self.is_mutable.encode(out);
+        self.field_visibility.encode(out);
+        self.span.encode(out);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(FnParam {
+            name: String::decode(input)?,
+            type_ann: TypeAnnotation::decode(input)?,
+            default: Option::decode(input)?,
+            variadic: bool::decode(input)?,
+            is_field: bool::decode(input)?,
+            is_mutable: bool::decode(input)?,
+            field_visibility: Option::decode(input)?,
+            span: Span::decode(input)?,
+        })
+    }
+}
+
+impl Codec for Expr {
+    fn encode(&self, out: &mut Encoder) {
+        match self {
+            Expr::Integer { value, span } => {
+                out.tag(0);
+                value.encode(out);
+                span.encode(out);
+            }
+            Expr::Float { value, span } => {
+                out.tag(1);
+                value.encode(out);
+                span.encode(out);
+            }
+            Expr::String { value, span } => {
+                out.tag(2);
+                value.encode(out);
+                span.encode(out);
+            }
+            Expr::StringInterpolation { parts, span } => {
+                out.tag(3);
+                parts.encode(out);
+                span.encode(out);
+            }
+            Expr::Bool { value, span } => {
+                out.tag(4);
+                value.encode(out);
+                span.encode(out);
+            }
+            Expr::Char { value, span } => {
+                out.tag(5);
+                value.encode(out);
+                span.encode(out);
+            }
+            Expr::Null { span } => {
+                out.tag(6);
+                span.encode(out);
+            }
+            Expr::Identifier { name, span } => {
+                out.tag(7);
+                name.encode(out);
+                span.encode(out);
+            }
+            Expr::Binary { left, op, right, span } => {
+                out.tag(8);
+                left.encode(out);
+                op.encode(out);
+                right.encode(out);
+                span.encode(out);
+            }
+            Expr::Unary { op, operand, span } => {
+                out.tag(9);
+                op.encode(out);
+                operand.encode(out);
+                span.encode(out);
+            }
+            Expr::Grouping { expr, span } => {
+                out.tag(10);
+                expr.encode(out);
+                span.encode(out);
+            }
+            Expr::Call { callee, args, span } => {
+                out.tag(11);
+                callee.encode(out);
+                args.encode(out);
+                span.encode(out);
+            }
+            Expr::Go { call, span } => {
+                out.tag(12);
+                call.encode(out);
+                span.encode(out);
+            }
+            Expr::ChannelNew { element_type, capacity, span } => {
+                out.tag(13);
+                element_type.encode(out);
+                capacity.encode(out);
+                span.encode(out);
+            }
+            Expr::Assign { target, op, value, span } => {
+                out.tag(14);
+                target.encode(out);
+                op.encode(out);
+                value.encode(out);
+                span.encode(out);
+            }
+            Expr::Index { object, index, span } => {
+                out.tag(15);
+                object.encode(out);
+                index.encode(out);
+                span.encode(out);
+            }
+            Expr::Member { object, member, span } => {
+                out.tag(16);
+                object.encode(out);
+                member.encode(out);
+                span.encode(out);
+            }
+            Expr::SafeMember { object, member, span } => {
+                out.tag(17);
+                object.encode(out);
+                member.encode(out);
+                span.encode(out);
+            }
+            Expr::NonNullMember { object, member, span } => {
+                out.tag(18);
+                object.encode(out);
+                member.encode(out);
+                span.encode(out);
+            }
+            Expr::NullCoalesce { left, right, span } => {
+                out.tag(19);
+                left.encode(out);
+                right.encode(out);
+                span.encode(out);
+            }
+            Expr::PostIncrement { operand, span } => {
+                out.tag(20);
+                operand.encode(out);
+                span.encode(out);
+            }
+            Expr::PostDecrement { operand, span } => {
+                out.tag(21);
+                operand.encode(out);
+                span.encode(out);
+            }
+            Expr::Cast { expr, target_type, force, span } => {
+                out.tag(22);
+                expr.encode(out);
+                target_type.encode(out);
+                force.encode(out);
+                span.encode(out);
+            }
+            Expr::TypeCheck { expr, check_type, span } => {
+                out.tag(23);
+                expr.encode(out);
+                check_type.encode(out);
+                span.encode(out);
+            }
+            Expr::Range { start, end, inclusive, span } => {
+                out.tag(24);
+                start.encode(out);
+                end.encode(out);
+                inclusive.encode(out);
+                span.encode(out);
+            }
+            Expr::IfExpr { condition, then_branch, else_branch, span } => {
+                out.tag(25);
+                condition.encode(out);
+                then_branch.encode(out);
+                else_branch.encode(out);
+                span.encode(out);
+            }
+            Expr::Array { elements, span } => {
+                out.tag(26);
+                elements.encode(out);
+                span.encode(out);
+            }
+            Expr::MapLiteral { entries, span } => {
+                out.tag(27);
+                entries.encode(out);
+                span.encode(out);
+            }
+            Expr::Closure { params, return_type, body, span } => {
+                out.tag(28);
+                params.encode(out);
+                return_type.encode(out);
+                body.encode(out);
+                span.encode(out);
+            }
+            Expr::StructLiteral { name, fields, span } => {
+                out.tag(29);
+                name.encode(out);
+                fields.encode(out);
+                span.encode(out);
+            }
+            Expr::New { class_name, args, fields, span } => {
+                out.tag(30);
+                class_name.encode(out);
+                args.encode(out);
+                fields.encode(out);
+                span.encode(out);
+            }
+            Expr::This { span } => {
+                out.tag(31);
+                span.encode(out);
+            }
+            Expr::Super { span } => {
+                out.tag(32);
+                span.encode(out);
+            }
+            Expr::Default { type_name, span } => {
+                out.tag(33);
+                type_name.encode(out);
+                span.encode(out);
+            }
+            Expr::StaticMember { class_name, member, span } => {
+                out.tag(34);
+                class_name.encode(out);
+                member.encode(out);
+                span.encode(out);
+            }
+        }
+    }
+
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(match input.tag()? {
+            0 => Expr::Integer { value: i128::decode(input)?, span: Span::decode(input)? },
+            1 => Expr::Float { value: f64::decode(input)?, span: Span::decode(input)? },
+            2 => Expr::String { value: String::decode(input)?, span: Span::decode(input)? },
+            3 => Expr::StringInterpolation { parts: Vec::decode(input)?, span: Span::decode(input)? },
+            4 => Expr::Bool { value: bool::decode(input)?, span: Span::decode(input)? },
+            5 => Expr::Char { value: char::decode(input)?, span: Span::decode(input)? },
+            6 => Expr::Null { span: Span::decode(input)? },
+            7 => Expr::Identifier { name: String::decode(input)?, span: Span::decode(input)? },
+            8 => Expr::Binary {
+                left: Box::decode(input)?,
+                op: BinOp::decode(input)?,
+                right: Box::decode(input)?,
+                span: Span::decode(input)?,
+            },
+            9 => Expr::Unary { op: UnaryOp::decode(input)?, operand: Box::decode(input)?, span: Span::decode(input)? },
+            10 => Expr::Grouping { expr: Box::decode(input)?, span: Span::decode(input)? },
+            11 => Expr::Call { callee: Box::decode(input)?, args: Vec::decode(input)?, span: Span::decode(input)? },
+            12 => Expr::Go { call: Box::decode(input)?, span: Span::decode(input)? },
+            13 => Expr::ChannelNew {
+                element_type: Type::decode(input)?,
+                capacity: Option::decode(input)?,
+                span: Span::decode(input)?,
+            },
+            14 => Expr::Assign {
+                target: Box::decode(input)?,
+                op: AssignOp::decode(input)?,
+                value: Box::decode(input)?,
+                span: Span::decode(input)?,
+            },
+            15 => Expr::Index { object: Box::decode(input)?, index: Box::decode(input)?, span: Span::decode(input)? },
+            16 => Expr::Member { object: Box::decode(input)?, member: String::decode(input)?, span: Span::decode(input)? },
+            17 => Expr::SafeMember { object: Box::decode(input)?, member: String::decode(input)?, span: Span::decode(input)? },
+            18 => Expr::NonNullMember { object: Box::decode(input)?, member: String::decode(input)?, span: Span::decode(input)? },
+            19 => Expr::NullCoalesce { left: Box::decode(input)?, right: Box::decode(input)?, span: Span::decode(input)? },
+            20 => Expr::PostIncrement { operand: Box::decode(input)?, span: Span::decode(input)? },
+            21 => Expr::PostDecrement { operand: Box::decode(input)?, span: Span::decode(input)? },
+            22 => Expr::Cast {
+                expr: Box::decode(input)?,
+                target_type: TypeAnnotation::decode(input)?,
+                force: bool::decode(input)?,
+                span: Span::decode(input)?,
+            },
+            23 => Expr::TypeCheck {
+                expr: Box::decode(input)?,
+                check_type: TypeAnnotation::decode(input)?,
+                span: Span::decode(input)?,
+            },
+            24 => Expr::Range {
+                start: Option::decode(input)?,
+                end: Option::decode(input)?,
+                inclusive: bool::decode(input)?,
+                span: Span::decode(input)?,
+            },
+            25 => Expr::IfExpr {
+                condition: Box::decode(input)?,
+                then_branch: Box::decode(input)?,
+                else_branch: Box::decode(input)?,
+                span: Span::decode(input)?,
+            },
+            26 => Expr::Array { elements: Vec::decode(input)?, span: Span::decode(input)? },
+            27 => Expr::MapLiteral { entries: Vec::decode(input)?, span: Span::decode(input)? },
+            28 => Expr::Closure {
+                params: Vec::decode(input)?,
+                return_type: Option::decode(input)?,
+                body: Box::decode(input)?,
+                span: Span::decode(input)?,
+            },
+            29 => Expr::StructLiteral { name: String::decode(input)?, fields: Vec::decode(input)?, span: Span::decode(input)? },
+            30 => Expr::New {
+                class_name: String::decode(input)?,
+                args: Vec::decode(input)?,
+                fields: Option::decode(input)?,
+                span: Span::decode(input)?,
+            },
+            31 => Expr::This { span: Span::decode(input)? },
+            32 => Expr::Super { span: Span::decode(input)? },
+            33 => Expr::Default { type_name: String::decode(input)?, span: Span::decode(input)? },
+            34 => Expr::StaticMember {
+                class_name: String::decode(input)?,
+                member: String::decode(input)?,
+                span: Span::decode(input)?,
+            },
+            _ => return None,
+        })
+    }
+}
+
+impl Codec for Stmt {
+    fn encode(&self, out: &mut Encoder) {
+        match self {
+            Stmt::Expression { expr, span } => {
+                out.tag(0);
+                expr.encode(out);
+                span.encode(out);
+            }
+            Stmt::Print { expr, newline, span } => {
+                out.tag(1);
+                expr.encode(out);
+                newline.encode(out);
+                span.encode(out);
+            }
+            Stmt::VarDecl { name, type_ann, initializer, span } => {
+                out.tag(2);
+                name.encode(out);
+                type_ann.encode(out);
+                initializer.encode(out);
+                span.encode(out);
+            }
+            Stmt::ConstDecl { name, type_ann, initializer, span } => {
+                out.tag(3);
+                name.encode(out);
+                type_ann.encode(out);
+                initializer.encode(out);
+                span.encode(out);
+            }
+            Stmt::Block { statements, span } => {
+                out.tag(4);
+                statements.encode(out);
+                span.encode(out);
+            }
+            Stmt::If { condition, then_branch, else_branch, span } => {
+                out.tag(5);
+                condition.encode(out);
+                then_branch.encode(out);
+                else_branch.encode(out);
+                span.encode(out);
+            }
+            Stmt::ForLoop { label, initializer, condition, increment, body, span } => {
+                out.tag(6);
+                label.encode(out);
+                initializer.encode(out);
+                condition.encode(out);
+                increment.encode(out);
+                body.encode(out);
+                span.encode(out);
+            }
+            Stmt::ForIn { label, variables, iterable, body, span } => {
+                out.tag(7);
+                label.encode(out);
+                variables.encode(out);
+                iterable.encode(out);
+                body.encode(out);
+                span.encode(out);
+            }
+            Stmt::While { label, condition, body, span } => {
+                out.tag(8);
+                label.encode(out);
+                condition.encode(out);
+                body.encode(out);
+                span.encode(out);
+            }
+            Stmt::Break { label, span } => {
+                out.tag(9);
+                label.encode(out);
+                span.encode(out);
+            }
+            Stmt::Continue { label, span } => {
+                out.tag(10);
+                label.encode(out);
+                span.encode(out);
+            }
+            Stmt::Return { value, span } => {
+                out.tag(11);
+                value.encode(out);
+                span.encode(out);
+            }
+            Stmt::Match { expr, arms, span } => {
+                out.tag(12);
+                expr.encode(out);
+                arms.encode(out);
+                span.encode(out);
+            }
+            Stmt::Select { cases, span } => {
+                out.tag(13);
+                cases.encode(out);
+                span.encode(out);
+            }
+            Stmt::StructDef { name, type_params, where_clauses, interfaces, fields, static_fields, methods, span } => {
+                out.tag(14);
+                name.encode(out);
+                type_params.encode(out);
+                where_clauses.encode(out);
+                interfaces.encode(out);
+                fields.encode(out);
+                static_fields.encode(out);
+                methods.encode(out);
+                span.encode(out);
+            }
+            Stmt::ClassDef {
+                name, type_params, where_clauses, is_abstract, parent, interfaces, traits, fields, methods, span,
+            } => {
+                out.tag(15);
+                name.encode(out);
+                type_params.encode(out);
+                where_clauses.encode(out);
+                is_abstract.encode(out);
+                parent.encode(out);
+                interfaces.encode(out);
+                traits.encode(out);
+                fields.encode(out);
+                methods.encode(out);
+                span.encode(out);
+            }
+            Stmt::InterfaceDef { name, type_params, super_interfaces, methods, span } => {
+                out.tag(16);
+                name.encode(out);
+                type_params.encode(out);
+                super_interfaces.encode(out);
+                methods.encode(out);
+                span.encode(out);
+            }
+            Stmt::TraitDef { name, type_params, where_clauses, super_traits, methods, span } => {
+                out.tag(17);
+                name.encode(out);
+                type_params.encode(out);
+                where_clauses.encode(out);
+                super_traits.encode(out);
+                methods.encode(out);
+                span.encode(out);
+            }
+            Stmt::EnumDef { name, variants, span } => {
+                out.tag(18);
+                name.encode(out);
+                variants.encode(out);
+                span.encode(out);
+            }
+            Stmt::TypeAlias { name, target_type, span } => {
+                out.tag(19);
+                name.encode(out);
+                target_type.encode(out);
+                span.encode(out);
+            }
+            Stmt::TryCatch { try_block, catch_param, catch_type, catch_block, finally_block, span } => {
+                out.tag(20);
+                try_block.encode(out);
+                catch_param.encode(out);
+                catch_type.encode(out);
+                catch_block.encode(out);
+                finally_block.encode(out);
+                span.encode(out);
+            }
+            Stmt::Throw { value, span } => {
+                out.tag(21);
+                value.encode(out);
+                span.encode(out);
+            }
+            Stmt::FnDef { name, type_params, where_clauses, params, return_type, body, visibility, attributes, span } => {
+                out.tag(22);
+                name.encode(out);
+                type_params.encode(out);
+                where_clauses.encode(out);
+                params.encode(out);
+                return_type.encode(out);
+                body.encode(out);
+                visibility.encode(out);
+                attributes.encode(out);
+                span.encode(out);
+            }
+            Stmt::Package { path, span } => {
+                out.tag(23);
+                path.encode(out);
+                span.encode(out);
+            }
+            Stmt::Import { import, span } => {
+                out.tag(24);
+                import.encode(out);
+                span.encode(out);
+            }
+            Stmt::Switch { expr, cases, default, span } => {
+                out.tag(25);
+                expr.encode(out);
+                cases.encode(out);
+                default.encode(out);
+                span.encode(out);
+            }
+            Stmt::ParallelAssign { targets, values, span } => {
+                out.tag(26);
+                targets.encode(out);
+                values.encode(out);
+                span.encode(out);
+            }
+            Stmt::Recovering { body, panic_param, handler, span } => {
+                out.tag(27);
+                body.encode(out);
+                panic_param.encode(out);
+                handler.encode(out);
+                span.encode(out);
+            }
+        }
+    }
+
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(match input.tag()? {
+            0 => Stmt::Expression { expr: Expr::decode(input)?, span: Span::decode(input)? },
+            1 => Stmt::Print { expr: Expr::decode(input)?, newline: bool::decode(input)?, span: Span::decode(input)? },
+            2 => Stmt::VarDecl {
+                name: String::decode(input)?,
+                type_ann: Option::decode(input)?,
+                initializer: Option::decode(input)?,
+                span: Span::decode(input)?,
+            },
+            3 => Stmt::ConstDecl {
+                name: String::decode(input)?,
+                type_ann: Option::decode(input)?,
+                initializer: Expr::decode(input)?,
+                span: Span::decode(input)?,
+            },
+            4 => Stmt::Block { statements: Vec::decode(input)?, span: Span::decode(input)? },
+            5 => Stmt::If {
+                condition: Expr::decode(input)?,
+                then_branch: Box::decode(input)?,
+                else_branch: Option::decode(input)?,
+                span: Span::decode(input)?,
+            },
+            6 => Stmt::ForLoop {
+                label: Option::decode(input)?,
+                initializer: Option::decode(input)?,
+                condition: Option::decode(input)?,
+                increment: Option::decode(input)?,
+                body: Box::decode(input)?,
+                span: Span::decode(input)?,
+            },
+            7 => Stmt::ForIn {
+                label: Option::decode(input)?,
+                variables: Vec::decode(input)?,
+                iterable: Expr::decode(input)?,
+                body: Box::decode(input)?,
+                span: Span::decode(input)?,
+            },
+            8 => Stmt::While {
+                label: Option::decode(input)?,
+                condition: Option::decode(input)?,
+                body: Box::decode(input)?,
+                span: Span::decode(input)?,
+            },
+            9 => Stmt::Break { label: Option::decode(input)?, span: Span::decode(input)? },
+            10 => Stmt::Continue { label: Option::decode(input)?, span: Span::decode(input)? },
+            11 => Stmt::Return { value: Option::decode(input)?, span: Span::decode(input)? },
+            12 => Stmt::Match { expr: Expr::decode(input)?, arms: Vec::decode(input)?, span: Span::decode(input)? },
+            13 => Stmt::Select { cases: Vec::decode(input)?, span: Span::decode(input)? },
+            14 => Stmt::StructDef {
+                name: String::decode(input)?,
+                type_params: Vec::decode(input)?,
+                where_clauses: Vec::decode(input)?,
+                interfaces: Vec::decode(input)?,
+                fields: Vec::decode(input)?,
+                static_fields: Vec::decode(input)?,
+                methods: Vec::decode(input)?,
+                span: Span::decode(input)?,
+            },
+            15 => Stmt::ClassDef {
+                name: String::decode(input)?,
+                type_params: Vec::decode(input)?,
+                where_clauses: Vec::decode(input)?,
+                is_abstract: bool::decode(input)?,
+                parent: Option::decode(input)?,
+                interfaces: Vec::decode(input)?,
+                traits: Vec::decode(input)?,
+                fields: Vec::decode(input)?,
+                methods: Vec::decode(input)?,
+                span: Span::decode(input)?,
+            },
+            16 => Stmt::InterfaceDef {
+                name: String::decode(input)?,
+                type_params: Vec::decode(input)?,
+                super_interfaces: Vec::decode(input)?,
+                methods: Vec::decode(input)?,
+                span: Span::decode(input)?,
+            },
+            17 => Stmt::TraitDef {
+                name: String::decode(input)?,
+                type_params: Vec::decode(input)?,
+                where_clauses: Vec::decode(input)?,
+                super_traits: Vec::decode(input)?,
+                methods: Vec::decode(input)?,
+                span: Span::decode(input)?,
+            },
+            18 => Stmt::EnumDef { name: String::decode(input)?, variants: Vec::decode(input)?, span: Span::decode(input)? },
+            19 => Stmt::TypeAlias {
+                name: String::decode(input)?,
+                target_type: TypeAnnotation::decode(input)?,
+                span: Span::decode(input)?,
+            },
+            20 => Stmt::TryCatch {
+                try_block: Box::decode(input)?,
+                catch_param: Option::decode(input)?,
+                catch_type: Option::decode(input)?,
+                catch_block: Box::decode(input)?,
+                finally_block: Option::decode(input)?,
+                span: Span::decode(input)?,
+            },
+            21 => Stmt::Throw { value: Expr::decode(input)?, span: Span::decode(input)? },
+            22 => Stmt::FnDef {
+                name: String::decode(input)?,
+                type_params: Vec::decode(input)?,
+                where_clauses: Vec::decode(input)?,
+                params: Vec::decode(input)?,
+                return_type: Option::decode(input)?,
+                body: Box::decode(input)?,
+                visibility: Visibility::decode(input)?,
+                attributes: Vec::decode(input)?,
+                span: Span::decode(input)?,
+            },
+            23 => Stmt::Package { path: String::decode(input)?, span: Span::decode(input)? },
+            24 => Stmt::Import { import: ImportDecl::decode(input)?, span: Span::decode(input)? },
+            25 => Stmt::Switch {
+                expr: Expr::decode(input)?,
+                cases: Vec::decode(input)?,
+                default: Option::decode(input)?,
+                span: Span::decode(input)?,
+            },
+            26 => Stmt::ParallelAssign { targets: Vec::decode(input)?, values: Vec::decode(input)?, span: Span::decode(input)? },
+            27 => Stmt::Recovering {
+                body: Box::decode(input)?,
+                panic_param: Option::decode(input)?,
+                handler: Box::decode(input)?,
+                span: Span::decode(input)?,
+            },
+            _ => return None,
+        })
+    }
+}
+
+impl Codec for ImportDecl {
+    fn encode(&self, out: &mut Encoder) {
+        self.path.encode(out);
+        match &self.target {
+            ImportTarget::All => out.tag(0),
+            ImportTarget::Single(name) => {
+                out.tag(1);
+                name.encode(out);
+            }
+            ImportTarget::Multiple(names) => {
+                out.tag(2);
+                names.encode(out);
+            }
+        }
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        let path = String::decode(input)?;
+        let target = match input.tag()? {
+            0 => ImportTarget::All,
+            1 => ImportTarget::Single(String::decode(input)?),
+            2 => ImportTarget::Multiple(Vec::decode(input)?),
+            _ => return None,
+        };
+        Some(ImportDecl { path, target })
+    }
+}
+
+impl Codec for MatchArm {
+    fn encode(&self, out: &mut Encoder) {
+        self.pattern.encode(out);
+        self.guard.encode(out);
+        self.body.encode(out);
+        self.span.encode(out);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(MatchArm {
+            pattern: MatchPattern::decode(input)?,
+            guard: Option::decode(input)?,
+            body: Box::decode(input)?,
+            span: Span::decode(input)?,
+        })
+    }
+}
+
+impl Codec for SelectCase {
+    fn encode(&self, out: &mut Encoder) {
+        match &self.kind {
+            SelectCaseKind::Receive { channel, binding } => {
+                out.tag(0);
+                channel.encode(out);
+                binding.encode(out);
+            }
+            SelectCaseKind::Send { channel, value } => {
+                out.tag(1);
+                channel.encode(out);
+                value.encode(out);
+            }
+            SelectCaseKind::Default => out.tag(2),
+        }
+        self.body.encode(out);
+        self.span.encode(out);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        let kind = match input.tag()? {
+            0 => SelectCaseKind::Receive { channel: Expr::decode(input)?, binding: Option::decode(input)? },
+            1 => SelectCaseKind::Send { channel: Expr::decode(input)?, value: Expr::decode(input)? },
+            2 => SelectCaseKind::Default,
+            _ => return None,
+        };
+        Some(SelectCase { kind, body: Box::decode(input)?, span: Span::decode(input)? })
+    }
+}
+
+impl Codec for SwitchCase {
+    fn encode(&self, out: &mut Encoder) {
+        self.values.encode(out);
+        self.body.encode(out);
+        self.span.encode(out);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(SwitchCase { values: Vec::decode(input)?, body: Box::decode(input)?, span: Span::decode(input)? })
+    }
+}
+
+impl Codec for MatchPattern {
+    fn encode(&self, out: &mut Encoder) {
+        match self {
+            MatchPattern::Literal(expr) => {
+                out.tag(0);
+                expr.encode(out);
+            }
+            MatchPattern::Variable(name) => {
+                out.tag(1);
+                name.encode(out);
+            }
+            MatchPattern::Wildcard => out.tag(2),
+            MatchPattern::Or(patterns) => {
+                out.tag(3);
+                patterns.encode(out);
+            }
+            MatchPattern::Range { start, end, inclusive } => {
+                out.tag(4);
+                start.encode(out);
+                end.encode(out);
+                inclusive.encode(out);
+            }
+            MatchPattern::Type { name, type_ann } => {
+                out.tag(5);
+                name.encode(out);
+                type_ann.encode(out);
+            }
+            MatchPattern::EnumVariant { enum_name, variant, fields } => {
+                out.tag(6);
+                enum_name.encode(out);
+                variant.encode(out);
+                fields.encode(out);
+            }
+        }
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(match input.tag()? {
+            0 => MatchPattern::Literal(Expr::decode(input)?),
+            1 => MatchPattern::Variable(String::decode(input)?),
+            2 => MatchPattern::Wildcard,
+            3 => MatchPattern::Or(Vec::decode(input)?),
+            4 => MatchPattern::Range { start: Box::decode(input)?, end: Box::decode(input)?, inclusive: bool::decode(input)? },
+            5 => MatchPattern::Type { name: String::decode(input)?, type_ann: TypeAnnotation::decode(input)? },
+            6 => MatchPattern::EnumVariant {
+                enum_name: String::decode(input)?,
+                variant: String::decode(input)?,
+                fields: Vec::decode(input)?,
+            },
+            _ => return None,
+        })
+    }
+}
+
+impl Codec for StructField {
+    fn encode(&self, out: &mut Encoder) {
+        self.name.encode(out);
+        self.type_ann.encode(out);
+        self.visibility.encode(out);
+        self.span.encode(out);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(StructField {
+            name: String::decode(input)?,
+            type_ann: TypeAnnotation::decode(input)?,
+            visibility: Visibility::decode(input)?,
+            span: Span::decode(input)?,
+        })
+    }
+}
+
+impl Codec for StructMethod {
+    fn encode(&self, out: &mut Encoder) {
+        self.name.encode(out);
+        self.params.encode(out);
+        self.return_type.encode(out);
+        self.body.encode(out);
+        self.visibility.encode(out);
+        self.is_static.encode(out);
+        self.attributes.encode(out);
+        self.span.encode(out);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(StructMethod {
+            name: String::decode(input)?,
+            params: Vec::decode(input)?,
+            return_type: Option::decode(input)?,
+            body: Box::decode(input)?,
+            visibility: Visibility::decode(input)?,
+            is_static: bool::decode(input)?,
+            attributes: Vec::decode(input)?,
+            span: Span::decode(input)?,
+        })
+    }
+}
+
+impl Codec for ClassField {
+    fn encode(&self, out: &mut Encoder) {
+        self.name.encode(out);
+        self.type_ann.encode(out);
+        self.initializer.encode(out);
+        self.visibility.encode(out);
+        self.is_static.encode(out);
+        self.is_const.encode(out);
+        self.span.encode(out);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(ClassField {
+            name: String::decode(input)?,
+            type_ann: Option::decode(input)?,
+            initializer: Option::decode(input)?,
+            visibility: Visibility::decode(input)?,
+            is_static: bool::decode(input)?,
+            is_const: bool::decode(input)?,
+            span: Span::decode(input)?,
+        })
+    }
+}
+
+impl Codec for ClassMethod {
+    fn encode(&self, out: &mut Encoder) {
+        self.name.encode(out);
+        self.params.encode(out);
+        self.return_type.encode(out);
+        self.body.encode(out);
+        self.visibility.encode(out);
+        self.is_static.encode(out);
+        self.is_override.encode(out);
+        self.is_final.encode(out);
+        self.is_abstract.encode(out);
+        self.attributes.encode(out);
+        self.span.encode(out);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(ClassMethod {
+            name: String::decode(input)?,
+            params: Vec::decode(input)?,
+            return_type: Option::decode(input)?,
+            body: Option::decode(input)?,
+            visibility: Visibility::decode(input)?,
+            is_static: bool::decode(input)?,
+            is_override: bool::decode(input)?,
+            is_final: bool::decode(input)?,
+            is_abstract: bool::decode(input)?,
+            attributes: Vec::decode(input)?,
+            span: Span::decode(input)?,
+        })
+    }
+}
+
+impl Codec for InterfaceMethod {
+    fn encode(&self, out: &mut Encoder) {
+        self.name.encode(out);
+        self.params.encode(out);
+        self.return_type.encode(out);
+        self.span.encode(out);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(InterfaceMethod {
+            name: String::decode(input)?,
+            params: Vec::decode(input)?,
+            return_type: Option::decode(input)?,
+            span: Span::decode(input)?,
+        })
+    }
+}
+
+impl Codec for TraitMethod {
+    fn encode(&self, out: &mut Encoder) {
+        self.name.encode(out);
+        self.params.encode(out);
+        self.return_type.encode(out);
+        self.default_body.encode(out);
+        self.span.encode(out);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(TraitMethod {
+            name: String::decode(input)?,
+            params: Vec::decode(input)?,
+            return_type: Option::decode(input)?,
+            default_body: Option::decode(input)?,
+            span: Span::decode(input)?,
+        })
+    }
+}
+
+impl Codec for EnumVariant {
+    fn encode(&self, out: &mut Encoder) {
+        self.name.encode(out);
+        self.value.encode(out);
+        self.fields.encode(out);
+        self.span.encode(out);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(EnumVariant {
+            name: String::decode(input)?,
+            value: Option::decode(input)?,
+            fields: Vec::decode(input)?,
+            span: Span::decode(input)?,
+        })
+    }
+}
+
+impl Codec for Program {
+    fn encode(&self, out: &mut Encoder) {
+        self.package.encode(out);
+        self.imports.encode(out);
+        self.statements.encode(out);
+    }
+    fn decode(input: &mut Decoder) -> Option<Self> {
+        Some(Program {
+            package: Option::decode(input)?,
+            imports: Vec::decode(input)?,
+            statements: Vec::decode(input)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::Locale;
+    use crate::lexer::Scanner;
+    use crate::parser::Parser;
+
+    fn parse(source: &str) -> Program {
+        Parser::new(Scanner::new(source).scan_tokens(), Locale::En).parse().unwrap()
+    }
+
+    #[test]
+    fn test_program_round_trip() {
+        let source = r#"
+package com.example.app
+import com.example.app.models.{User, Role}
+
+enum Color { Red, Green = 5 }
+interface Shape { func area() f64 }
+struct Point<T: Comparable<T> > implements Shape {
+    x: T
+    static const ORIGIN = 0
+    @inline(always)
+    func area() f64 { return 0.0 }
+}
+abstract class Base<K, V> extends Object {
+    private var cache: map[K]V? = null
+    abstract func name() string
+    @no_optimize @inline(never)
+    static func create() {}
+    func init(private var id: int = 1, rest: int...) {}
+}
+@no_optimize
+func main() {
+    var xs: int[] = [1, 2, 3]
+    var m = {"a": 1.5, "b": -2e3}
+    var f = func(a: int) int { return a ** 2 }
+    var ch = chan<string>(4)
+    outer: for var i = 0; i < 10; i += 1 {
+        for k, v in m { continue outer }
+    }
+    match xs[0] {
+        1, 2 => println("small ${xs[1] + f(2)}")
+        3..=9 => print('c')
+        n: int if n > 100 => { throw new Exception("big") }
+        Shape::Rect(w, _, 0) => println(w)
+        _ => {}
+    }
+    select {
+        case var s = ch.receive() => println(s)
+        case ch.send("x") => {}
+        default => {}
+    }
+    try { go f(1) } catch (e: Exception) { println(e) } finally { xs = null ?? [] }
+    var p = Point { x: 1 }
+    var s = p?.x as! string
+    xs[0], p.x = p.x, xs[0]
+    println((Color::Red is int) ? ~1 : !true)
+}
+"#;
+        let program = parse(source);
+        let bytes = encode_program(&program, source);
+        assert_eq!(decode_program(&bytes, source).as_ref(), Some(&program));
+
+        // 源码、版本不匹配或数据被截断时视为未命中
+        assert!(decode_program(&bytes, "func main() {}").is_none());
+        assert!(decode_program(&bytes[..bytes.len() - 1], source).is_none());
+        let mut tampered = bytes.clone();
+        tampered[4] ^= 1;
+        assert!(decode_program(&tampered, source).is_none());
+    }
+
+    #[test]
+    fn test_parse_cache_on_disk() {
+        let root = std::env::temp_dir().join(format!("qlang_parse_cache_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let cache = ParseCache::new(&root);
+        let source = "func main() {\n    println(1)\n}\n";
+        assert!(cache.get(source).is_none());
+        cache.put(source, &parse(source));
+        assert_eq!(cache.get(source), Some(parse(source)));
+        assert!(cache.get("func main() {}\n").is_none());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
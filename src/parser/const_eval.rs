@@ -0,0 +1,302 @@
+//! 常量表达式求值
+//!
+//! 在编译期计算 `const` 初始值、默认参数和数组长度，`-O` 时也用于折叠普通表达式。
+//! 支持字面量、一元/二元运算、条件表达式、括号，以及通过 lookup 引用其他常量（`NAME` 或 `Class::NAME`）。
+//! 运算语义与 VM 一致；整数溢出、除以零等在运行时才会出错的情况视为不可折叠。
+
+use std::collections::HashMap;
+use std::fmt;
+
+use super::ast::{BinOp, Expr, Program, Stmt, UnaryOp};
+use crate::types::Type;
+
+/// 编译期常量值
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstValue {
+    Int(i128),
+    Float(f64),
+    Bool(bool),
+    Char(char),
+    String(String),
+    Null,
+}
+
+impl ConstValue {
+    /// 常量值对应的类型
+    pub fn ty(&self) -> Type {
+        match self {
+            ConstValue::Int(_) => Type::Int,
+            ConstValue::Float(_) => Type::F64,
+            ConstValue::Bool(_) => Type::Bool,
+            ConstValue::Char(_) => Type::Char,
+            ConstValue::String(_) => Type::String,
+            ConstValue::Null => Type::Null,
+        }
+    }
+}
+
+/// 常量求值失败的原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstEvalError {
+    /// 表达式含有运行期才能确定的部分（变量、函数调用等）
+    NotConstant,
+    /// 表达式是常量，但求值出错（除以零、溢出、类型不匹配）
+    Invalid(String),
+}
+
+impl fmt::Display for ConstEvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConstEvalError::NotConstant => write!(f, "not a compile-time constant expression"),
+            ConstEvalError::Invalid(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+/// 求值常量表达式
+///
+/// lookup 按名称查找已知常量：标识符传入 `NAME`，静态成员传入 `Class::NAME`
+pub fn eval_const(
+    expr: &Expr,
+    lookup: &dyn Fn(&str) -> Option<ConstValue>,
+) -> Result<ConstValue, ConstEvalError> {
+    match expr {
+        Expr::Integer { value, .. } => Ok(ConstValue::Int(*value)),
+        Expr::Float { value, .. } => Ok(ConstValue::Float(*value)),
+        Expr::String { value, .. } => Ok(ConstValue::String(value.clone())),
+        Expr::Bool { value, .. } => Ok(ConstValue::Bool(*value)),
+        Expr::Char { value, .. } => Ok(ConstValue::Char(*value)),
+        Expr::Null { .. } => Ok(ConstValue::Null),
+        Expr::Grouping { expr, .. } => eval_const(expr, lookup),
+        Expr::Identifier { name, .. } => lookup(name).ok_or(ConstEvalError::NotConstant),
+        Expr::StaticMember { class_name, member, .. } => {
+            lookup(&format!("{}::{}", class_name, member)).ok_or(ConstEvalError::NotConstant)
+        }
+        Expr::Unary { op, operand, .. } => eval_unary(*op, eval_const(operand, lookup)?),
+        Expr::Binary { left, op, right, .. } => {
+            let left = eval_const(left, lookup)?;
+            let right = eval_const(right, lookup)?;
+            eval_binary(*op, left, right)
+        }
+        Expr::IfExpr { condition, then_branch, else_branch, .. } => match eval_const(condition, lookup)? {
+            ConstValue::Bool(true) => eval_const(then_branch, lookup),
+            ConstValue::Bool(false) => eval_const(else_branch, lookup),
+            other => Err(invalid(format!("condition must be bool, got {}", other.ty()))),
+        },
+        _ => Err(ConstEvalError::NotConstant),
+    }
+}
+
+/// 求值一组可以相互引用的常量（引用顺序不限）
+///
+/// known 为已知常量；反复求值直到不再有进展。返回新求出的常量和失败的常量：
+/// 仍引用非常量值的报 [`ConstEvalError::NotConstant`]
+pub fn fold_consts(
+    items: &[(String, &Expr)],
+    known: &HashMap<String, ConstValue>,
+) -> (HashMap<String, ConstValue>, Vec<(String, ConstEvalError)>) {
+    let mut values: HashMap<String, ConstValue> = HashMap::new();
+    let mut errors = Vec::new();
+    let mut pending: Vec<&(String, &Expr)> = items.iter().collect();
+
+    loop {
+        let before = pending.len();
+        pending.retain(|(name, expr)| {
+            // 成员常量可以不加前缀地引用同一类型的其他常量
+            let owner = name.rsplit_once("::").map(|(owner, _)| owner);
+            let find = |n: &str| values.get(n).or_else(|| known.get(n)).cloned();
+            let lookup = |n: &str| {
+                owner
+                    .filter(|_| !n.contains("::"))
+                    .and_then(|owner| find(&format!("{}::{}", owner, n)))
+                    .or_else(|| find(n))
+            };
+            match eval_const(expr, &lookup) {
+                Ok(value) => {
+                    values.insert(name.clone(), value);
+                    false
+                }
+                Err(ConstEvalError::NotConstant) => true,
+                Err(err) => {
+                    errors.push((name.clone(), err));
+                    false
+                }
+            }
+        });
+        if pending.len() == before {
+            break;
+        }
+    }
+
+    errors.extend(pending.into_iter().map(|(name, _)| (name.clone(), ConstEvalError::NotConstant)));
+    (values, errors)
+}
+
+/// 程序中所有顶层常量和 class/struct 常量成员（成员以 `Type::NAME` 命名）
+pub fn const_items(program: &Program) -> Vec<(String, &Expr)> {
+    let mut items = Vec::new();
+    for stmt in &program.statements {
+        match stmt {
+            Stmt::ConstDecl { name, initializer, .. } => items.push((name.clone(), initializer)),
+            Stmt::ClassDef { name, fields, .. } | Stmt::StructDef { name, static_fields: fields, .. } => {
+                for field in fields.iter().filter(|f| f.is_const) {
+                    if let Some(init) = &field.initializer {
+                        items.push((format!("{}::{}", name, field.name), init));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    items
+}
+
+fn invalid(message: impl Into<String>) -> ConstEvalError {
+    ConstEvalError::Invalid(message.into())
+}
+
+fn overflow() -> ConstEvalError {
+    invalid("integer overflow in constant expression")
+}
+
+fn eval_unary(op: UnaryOp, value: ConstValue) -> Result<ConstValue, ConstEvalError> {
+    match (op, value) {
+        (UnaryOp::Neg, ConstValue::Int(n)) => n.checked_neg().map(ConstValue::Int).ok_or_else(overflow),
+        (UnaryOp::Neg, ConstValue::Float(f)) => Ok(ConstValue::Float(-f)),
+        (UnaryOp::Not, ConstValue::Bool(b)) => Ok(ConstValue::Bool(!b)),
+        (UnaryOp::BitNot, ConstValue::Int(n)) => Ok(ConstValue::Int(!n)),
+        (op, value) => Err(invalid(format!("cannot apply {:?} to {}", op, value.ty()))),
+    }
+}
+
+fn eval_binary(op: BinOp, left: ConstValue, right: ConstValue) -> Result<ConstValue, ConstEvalError> {
+    use ConstValue::*;
+
+    // 整数与浮点数混合运算时提升为浮点数（与 VM 一致）
+    let as_floats = |left: &ConstValue, right: &ConstValue| match (left, right) {
+        (Int(a), Float(b)) => Some((*a as f64, *b)),
+        (Float(a), Int(b)) => Some((*a, *b as f64)),
+        (Float(a), Float(b)) => Some((*a, *b)),
+        _ => None,
+    };
+
+    let result = match (op, &left, &right) {
+        (BinOp::Add, Int(a), Int(b)) => Int(a.checked_add(*b).ok_or_else(overflow)?),
+        (BinOp::Sub, Int(a), Int(b)) => Int(a.checked_sub(*b).ok_or_else(overflow)?),
+        (BinOp::Mul, Int(a), Int(b)) => Int(a.checked_mul(*b).ok_or_else(overflow)?),
+        (BinOp::Div, Int(_), Int(0)) => return Err(invalid("division by zero in constant expression")),
+        (BinOp::Mod, Int(_), Int(0)) => return Err(invalid("modulo by zero in constant expression")),
+        (BinOp::Div, Int(a), Int(b)) => Int(a.checked_div(*b).ok_or_else(overflow)?),
+        (BinOp::Mod, Int(a), Int(b)) => Int(a.checked_rem(*b).ok_or_else(overflow)?),
+        (BinOp::Pow, Int(a), Int(b)) if *b >= 0 => {
+            let exp = u32::try_from(*b).map_err(|_| overflow())?;
+            Int(a.checked_pow(exp).ok_or_else(overflow)?)
+        }
+        (BinOp::Pow, Int(a), Int(b)) => Float((*a as f64).powf(*b as f64)),
+        (BinOp::Add, String(a), String(b)) => String(format!("{}{}", a, b)),
+
+        (BinOp::BitAnd, Int(a), Int(b)) => Int(a & b),
+        (BinOp::BitOr, Int(a), Int(b)) => Int(a | b),
+        (BinOp::BitXor, Int(a), Int(b)) => Int(a ^ b),
+        // 与运行时的 Value::shl / Value::shr 相同的范围，超出时不折叠，由运行时报错
+        (BinOp::Shl | BinOp::Shr, Int(_), Int(b)) if !(0..=63).contains(b) => {
+            return Err(invalid(format!("shift amount {} out of range", b)));
+        }
+        (BinOp::Shl, Int(a), Int(b)) => Int(a << b),
+        (BinOp::Shr, Int(a), Int(b)) => Int(a >> b),
+
+        (BinOp::And, Bool(a), Bool(b)) => Bool(*a && *b),
+        (BinOp::Or, Bool(a), Bool(b)) => Bool(*a || *b),
+
+        (BinOp::Eq, _, _) => Bool(const_eq(&left, &right)),
+        (BinOp::Ne, _, _) => Bool(!const_eq(&left, &right)),
+        (BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge, _, _) => {
+            let ordering = match (&left, &right) {
+                (Int(a), Int(b)) => a.partial_cmp(b),
+                (String(a), String(b)) => a.partial_cmp(b),
+                (Char(a), Char(b)) => a.partial_cmp(b),
+                _ => as_floats(&left, &right).and_then(|(a, b)| a.partial_cmp(&b)),
+            };
+            let Some(ordering) = ordering else {
+                return Err(invalid(format!("cannot compare {} with {}", left.ty(), right.ty())));
+            };
+            Bool(match op {
+                BinOp::Lt => ordering.is_lt(),
+                BinOp::Le => ordering.is_le(),
+                BinOp::Gt => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            })
+        }
+
+        (BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod | BinOp::Pow, _, _) => {
+            let Some((a, b)) = as_floats(&left, &right) else {
+                return Err(invalid(format!("cannot apply {:?} to {} and {}", op, left.ty(), right.ty())));
+            };
+            Float(match op {
+                BinOp::Add => a + b,
+                BinOp::Sub => a - b,
+                BinOp::Mul => a * b,
+                BinOp::Div => a / b,
+                BinOp::Mod => a % b,
+                _ => a.powf(b),
+            })
+        }
+        _ => return Err(invalid(format!("cannot apply {:?} to {} and {}", op, left.ty(), right.ty()))),
+    };
+    Ok(result)
+}
+
+/// 常量相等比较：整数与浮点数按数值比较
+fn const_eq(left: &ConstValue, right: &ConstValue) -> bool {
+    match (left, right) {
+        (ConstValue::Int(a), ConstValue::Float(b)) | (ConstValue::Float(b), ConstValue::Int(a)) => *a as f64 == *b,
+        _ => left == right,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::Locale;
+    use crate::lexer::Scanner;
+    use crate::parser::{Parser, Stmt};
+
+    fn eval(source: &str) -> Result<ConstValue, ConstEvalError> {
+        let source = format!("{}\n", source);
+        let tokens = Scanner::new(&source).scan_tokens();
+        let program = Parser::new(tokens, Locale::En).parse().unwrap();
+        let Stmt::Expression { expr, .. } = &program.statements[0] else {
+            panic!("expected expression statement");
+        };
+        let lookup = |name: &str| match name {
+            "LIMIT" => Some(ConstValue::Int(10)),
+            "Config::NAME" => Some(ConstValue::String("q".to_string())),
+            _ => None,
+        };
+        eval_const(expr, &lookup)
+    }
+
+    #[test]
+    fn test_eval_const_expressions() {
+        assert_eq!(eval("(1 + 2) * LIMIT - 1"), Ok(ConstValue::Int(29)));
+        assert_eq!(eval("1 << 4 | 1"), Ok(ConstValue::Int(17)));
+        assert_eq!(eval("LIMIT / 4 + 0.5"), Ok(ConstValue::Float(2.5)));
+        assert_eq!(eval("Config::NAME + \"-lang\""), Ok(ConstValue::String("q-lang".to_string())));
+        assert_eq!(eval("LIMIT >= 10 && !false"), Ok(ConstValue::Bool(true)));
+        assert_eq!(eval("-LIMIT"), Ok(ConstValue::Int(-10)));
+        assert_eq!(eval("LIMIT > 5 ? \"big\" : \"small\""), Ok(ConstValue::String("big".to_string())));
+    }
+
+    #[test]
+    fn test_eval_const_rejects_runtime_values() {
+        assert_eq!(eval("LIMIT + count"), Err(ConstEvalError::NotConstant));
+        assert_eq!(eval("len(\"abc\")"), Err(ConstEvalError::NotConstant));
+        assert!(matches!(eval("LIMIT / 0"), Err(ConstEvalError::Invalid(_))));
+        assert!(matches!(eval("\"a\" * 2"), Err(ConstEvalError::Invalid(_))));
+        assert!(matches!(eval("2 ** 200"), Err(ConstEvalError::Invalid(_))));
+        assert_eq!(eval("1 << 63"), Ok(ConstValue::Int(1 << 63)));
+        assert!(matches!(eval("1 << 64"), Err(ConstEvalError::Invalid(_))));
+        assert!(matches!(eval("1 >> 70"), Err(ConstEvalError::Invalid(_))));
+        assert!(matches!(eval("1 << -1"), Err(ConstEvalError::Invalid(_))));
+    }
+}
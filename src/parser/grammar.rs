@@ -0,0 +1,407 @@
+//! 语法的声明式描述
+//!
+//! [`GRAMMAR`] 是语法的唯一来源：`grammar --format=ebnf` 从它生成 W3C 风格的 EBNF
+//! （可直接交给铁路图生成器），测试用每条产生式附带的正例和反例检查手写的解析器与它一致。
+//! 修改解析器时同步修改这张表，否则一致性测试会失败。
+//!
+//! 约定：
+//! - 终结符 `IDENT`、`INT`、`FLOAT`、`STRING`（含原始字符串和插值字符串）、`NEWLINE` 来自词法分析
+//! - 括号内部（参数列表、字面量、声明体）的换行会被跳过，表中不写出
+//! - 二元运算按优先级从低到高逐层列出，除 `**` 外都是左结合
+
+/// 产生式的例子放在什么上下文里解析
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Context {
+    /// 整个源文件
+    Program,
+    /// 变量初始化表达式：`var _e = <例子>`
+    Expression,
+    /// 变量类型注解：`var _t: <例子> = null`
+    Type,
+}
+
+/// 一条产生式
+#[derive(Debug, Clone, Copy)]
+pub struct Production {
+    /// 非终结符名
+    pub name: &'static str,
+    /// 右部（W3C EBNF 记法）
+    pub rule: &'static str,
+    /// 例子的解析上下文
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub context: Context,
+    /// 解析器必须接受的例子（同时作为 EBNF 中的注释输出）
+    pub accepts: &'static [&'static str],
+    /// 解析器必须拒绝的例子
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub rejects: &'static [&'static str],
+}
+
+const fn production(
+    name: &'static str,
+    rule: &'static str,
+    context: Context,
+    accepts: &'static [&'static str],
+    rejects: &'static [&'static str],
+) -> Production {
+    Production { name, rule, context, accepts, rejects }
+}
+
+use Context::{Expression, Program, Type};
+
+/// 语法表，第一条是起始符号
+pub const GRAMMAR: &[Production] = &[
+    // ---------- 文件结构 ----------
+    production("program", "package_decl? import_decl* statement*", Program,
+        &["package app.main\nimport std.time.*\nvar x = 1\n"],
+        &["var x = 1\nimport a.b\n"]),
+    production("package_decl", "'package' IDENT ( '.' IDENT )* terminator?", Program,
+        &["package com.example.demo\n"],
+        &["package 1\n", "package a.\n"]),
+    production("import_decl",
+        "'import' IDENT ( '.' IDENT )* '.' ( IDENT | '*' | '{' ( IDENT ( ',' IDENT )* ','? )? '}' ) terminator?", Program,
+        &["import std.time.*\n", "import com.models.{User, Product}\n", "import com.models.User\n"],
+        &["import models\n", "import a.*.b\n", "import a.{B C}\n"]),
+    production("terminator", "NEWLINE | ';'", Program,
+        &["var a = 1; var b = 2\n"],
+        &["var a = 1;; ;\n"]),
+
+    // ---------- 语句 ----------
+    production("statement",
+        "print_stmt | var_decl | const_decl | block | if_stmt | labeled_for | for_stmt | break_stmt | continue_stmt \
+         | return_stmt | struct_def | class_def | interface_def | trait_def | enum_def | type_alias | func_def \
+         | match_stmt | select_stmt | switch_stmt | try_stmt | recovering_stmt | throw_stmt | multi_assign | expr_stmt", Program,
+        &["{ }\n", "x = 1\n"],
+        &["=> 1\n"]),
+    production("print_stmt", "( 'print' | 'println' ) '(' expression ')' terminator?", Program,
+        &["println(1 + 2)\n", "print(\"a\")\n"],
+        &["println(1, 2)\n", "println 1\n"]),
+    production("var_decl", "'var' IDENT ( ':' type )? ( '=' ( 'default' | expression ) )? terminator?", Program,
+        &["var x = 1\n", "var y: int\n", "var p: Point = default\n"],
+        &["var = 1\n", "var x = default\n", "var 1x = 2\n"]),
+    production("const_decl", "'const' IDENT ( ':' type )? '=' expression terminator?", Program,
+        &["const MAX = 10\n", "const NAME: string = \"q\"\n"],
+        &["const MAX\n", "const MAX: int\n"]),
+    production("block", "'{' statement* '}'", Program,
+        &["{\n    var x = 1\n    println(x)\n}\n", "{}\n"],
+        &["{ var x = 1\n", "}\n"]),
+    production("if_stmt", "'if' expression block ( 'else' ( if_stmt | block ) )?", Program,
+        &["if a > 1 {\n} else if a < 0 {\n} else {\n}\n"],
+        &["if a > 1 println(a)\n", "if a {} else println(a)\n"]),
+    production("labeled_for", "IDENT ':' for_stmt", Program,
+        &["outer: for {\n    break outer\n}\n"],
+        &["outer: println(1)\n", "outer: if a {}\n"]),
+    production("for_stmt",
+        "'for' ( block | IDENT ( ',' IDENT )* 'in' expression block | expression block \
+         | ( var_decl | expression )? ';' expression? ';' expression? block )", Program,
+        &["for {\n}\n", "for item in items {\n}\n", "for i, v in items {\n}\n", "for n < 10 {\n}\n",
+          "for var i = 0; i < 10; i += 1 {\n}\n", "for ;; {\n}\n"],
+        &["for i in {\n}\n", "for i = 0; i < 10 {\n}\n", "for a b {\n}\n"]),
+    production("break_stmt", "'break' IDENT? terminator?", Program,
+        &["break\n", "break outer\n"],
+        &["break = 1\n"]),
+    production("continue_stmt", "'continue' IDENT? terminator?", Program,
+        &["continue\n", "continue outer\n"],
+        &["continue = 1\n"]),
+    production("return_stmt", "'return' expression? terminator?", Program,
+        &["return\n", "return a + 1\n"],
+        &["return +\n"]),
+    production("throw_stmt", "'throw' expression terminator?", Program,
+        &["throw new Error(\"bad\")\n"],
+        &["throw\n"]),
+    production("try_stmt", "'try' block 'catch' ( '(' IDENT ':' IDENT ')' )? block ( 'finally' block )?", Program,
+        &["try {\n} catch (e: Exception) {\n} finally {\n}\n", "try {} catch {}\n"],
+        &["try {}\n", "try {} catch (e) {}\n", "try {} finally {}\n"]),
+    production("recovering_stmt", "'recovering' block 'onPanic' ( '(' IDENT ')' )? block /* recovering 后面是 { 时才是关键字 */", Program,
+        &["recovering {\n} onPanic (e) {\n}\n", "recovering {} onPanic {}\n", "var recovering = 1\n"],
+        &["recovering {}\n", "recovering {} onPanic (e: Exception) {}\n", "recovering {} catch {}\n"]),
+    production("multi_assign",
+        "conditional ( ',' conditional )+ '=' conditional ( ',' conditional )+ terminator? \
+         /* 目标只能是变量、成员或下标，个数与右值相同 */", Program,
+        &["a, b = b, a\n", "p.x, items[i] = items[i], p.x\n"],
+        &["a, b = 1\n", "a, f() = 1, 2\n", "a, b += 1, 2\n"]),
+    production("expr_stmt", "expression terminator?", Program,
+        &["counter += 1\n", "list.push(3)\n"],
+        &["1 +\n"]),
+
+    // ---------- 声明 ----------
+    production("visibility", "'public' | 'internal' | 'private' | 'protected'", Program,
+        &["private func helper() {}\n"],
+        &["private var x = 1\n"]),
+    production("attribute", "'@' IDENT ( '(' IDENT ')' )? NEWLINE? /* no_optimize、inline(never)、inline(always) */", Program,
+        &["@no_optimize\nfunc f() {}\n", "@inline(never) @no_optimize\nfunc f() {}\n"],
+        &["@foo\nfunc f() {}\n", "@no_optimize\nvar x = 1\n", "@inline(sometimes)\nfunc f() {}\n", "@no_optimize @no_optimize\nfunc f() {}\n"]),
+    production("func_def", "attribute* visibility? 'func' IDENT type_params? '(' params? ')' type? block", Program,
+        &["func add(a: int, b: int) int {\n    return a + b\n}\n", "func first<T>(items: T[]) T {\n    return items[0]\n}\n"],
+        &["func (a: int) {}\n", "func f(a) {}\n", "func f() int\n"]),
+    production("params", "param ( ',' param )* ','?", Program,
+        &["func f(a: int, b: string = \"x\", rest: int...) {}\n", "func f(\n    a: int,\n    b: int,\n) {}\n"],
+        &["func f(a: int..., b: int) {}\n", "func f(a: int = ) {}\n"]),
+    production("param", "field_modifier? IDENT ':' type '...'? ( '=' expression )?", Program,
+        &["class P {\n    func init(private var x: int, val y: int) {}\n}\n"],
+        &["func f(var x: int) {}\n", "class P {\n    func init(private x: int) {}\n}\n"]),
+    production("field_modifier", "visibility? ( 'var' | 'val' | 'const' ) /* 只用于 init 的参数 */", Program,
+        &["class P {\n    func init(const id: int) {}\n}\n"],
+        &["class P {\n    func run(val id: int) {}\n}\n"]),
+    production("type_params", "'<' ( type_param ( ',' type_param )* ','? )? '>'", Program,
+        &["class Box<T> {}\n", "func max<T: Comparable<T> + Printable, U = int>() {}\n"],
+        &["class Box<T {}\n", "class Box<1> {}\n"]),
+    production("type_param", "IDENT ( ':' type_bound ( '+' type_bound )* )? ( '=' type )?", Program,
+        &["struct Pair<K: Hashable, V = string> {}\n"],
+        &["struct Pair<K:> {}\n"]),
+    production("type_bound", "IDENT ( '<' type_list? '>' )?", Program,
+        &["func sort<T: Comparable<T> >() {}\n"],
+        &["func sort<T: int>() {}\n"]),
+    production("struct_def",
+        "'struct' IDENT type_params? ( 'implements' IDENT ( ',' IDENT )* )? '{' struct_member* '}'", Program,
+        &["struct Point {\n    x: int\n    y: int\n    func len() int {\n        return x + y\n    }\n    static const ORIGIN = 0\n}\n"],
+        &["struct Point {\n    x\n}\n", "struct Point {\n    static x: int\n}\n"]),
+    production("struct_member",
+        "attribute* /* 只用于方法 */ visibility? ( 'static'? ( 'func' IDENT '(' params? ')' type? block | 'const' class_field ) \
+         | 'static' 'var' class_field | IDENT ':' type terminator? )", Program,
+        &["struct S {\n    private id: int\n    static var count = 0\n    static func create() S {\n        return S { id: 1 }\n    }\n}\n"],
+        &["struct S {\n    var id: int\n}\n"]),
+    production("class_def",
+        "'abstract'? 'class' IDENT type_params? ( 'extends' IDENT )? ( 'implements' IDENT ( ',' IDENT )* )? '{' class_member* '}'", Program,
+        &["class Dog extends Animal implements Named, Pet {\n    use Greets\n    var name: string\n    func init(name: string) {}\n    override func speak() string {\n        return \"woof\"\n    }\n}\n",
+          "abstract class Shape {\n    abstract func area() f64\n}\n"],
+        &["class {}\n", "abstract struct S {}\n", "class A extends {}\n"]),
+    production("class_member",
+        "'use' IDENT terminator* | attribute* /* 只用于方法 */ visibility? 'static'? ( 'const' class_field \
+         | ( 'override' | 'final' )* 'abstract'? ( method | 'var' class_field ) )", Program,
+        &["class C {\n    static const MAX = 3\n    private static var count: int = 0\n    final func run() {}\n}\n"],
+        &["class C {\n    name: string\n}\n", "class C {\n    abstract func run()\n}\n", "class C {\n    override var x = 1\n}\n",
+          "class C {\n    static final func run() {}\n}\n"]),
+    production("class_field", "IDENT ( ':' type )? ( '=' expression )? terminator?", Program,
+        &["class C {\n    var a: int = 1\n    var b = \"x\"\n    var c: bool\n}\n"],
+        &["class C {\n    const LIMIT: int\n}\n"]),
+    production("method", "'func' IDENT '(' params? ')' type? ( block | terminator? /* 抽象方法 */ )", Program,
+        &["class C {\n    func size() int {\n        return 0\n    }\n}\n"],
+        &["class C {\n    func init() int {}\n}\n", "class C {\n    func run()\n}\n"]),
+    production("interface_def", "'interface' IDENT '{' ( 'func' IDENT '(' params? ')' type? terminator? )* '}'", Program,
+        &["interface Shape {\n    func area() f64\n    func name() string\n}\n"],
+        &["interface Shape {\n    func area() f64 {}\n}\n", "interface Shape {\n    var x: int\n}\n"]),
+    production("trait_def", "'trait' IDENT type_params? '{' ( 'func' IDENT '(' params? ')' type? block? terminator* )* '}'", Program,
+        &["trait Greets {\n    func greet() string {\n        return \"hi\"\n    }\n    func name() string\n}\n"],
+        &["trait Greets {\n    var x: int\n}\n"]),
+    production("enum_def", "'enum' IDENT '{' ( enum_variant ','? )* '}'", Program,
+        &["enum Color {\n    Red,\n    Green = 2\n    Rgb(r: int, g: int, b: int)\n}\n"],
+        &["enum Color {\n    1\n}\n", "enum {\n}\n"]),
+    production("enum_variant", "IDENT ( '=' expression | '(' ( IDENT ':' type ','? )* ')' )?", Program,
+        &["enum Shape {\n    Circle(radius: f64)\n}\n"],
+        &["enum Shape {\n    Circle(f64)\n}\n"]),
+    production("type_alias", "'type' IDENT '=' type terminator?", Program,
+        &["type Callback = func(int) bool\n"],
+        &["type Callback\n", "type = int\n"]),
+
+    // ---------- match / select ----------
+    production("match_stmt", "'match' expression '{' ( match_arm ','? )* '}' /* 主体表达式中不识别 struct 字面量 */", Program,
+        &["match code {\n    200 => println(\"ok\")\n    404, 410 => {\n        println(\"gone\")\n    }\n    _ => println(\"?\")\n}\n"],
+        &["match code {\n    200 println(1)\n}\n", "match {\n}\n"]),
+    production("match_arm", "pattern ( ',' pattern )* ( 'if' expression )? '=>' arm_body", Program,
+        &["match v {\n    n if n > 10 => println(n)\n    s: string => println(s)\n    1..5 => println(1)\n    Config::MAX => println(2)\n}\n"],
+        &["match v {\n    n if => 1\n}\n", "match v {\n    1 =>\n}\n"]),
+    production("pattern", "'_' | ( INT | FLOAT | STRING | 'true' | 'false' | 'null' | IDENT '::' IDENT ) ( ( '..' | '..=' ) unary )? \
+         | IDENT '::' IDENT '(' ( field_pattern ( ',' field_pattern )* ','? )? ')' | IDENT ( ':' type )? | expression", Program,
+        &["match v {\n    null => 0\n    true => 1\n    0..=9 => 2\n    -1 => 3\n}\n",
+          "match shape {\n    Shape::Rect(w, _, 0) => w\n    Shape::Empty() => 0\n}\n"],
+        &["match v {\n    x: => 1\n}\n"]),
+    production("field_pattern", "'_' | IDENT | INT | FLOAT | STRING | 'true' | 'false' | 'null' | IDENT '::' IDENT \
+         /* 枚举变体按声明顺序的一个字段 */", Program,
+        &["match r {\n    Result::Ok(value) => value\n    Result::Err(\"timeout\", _) => 0\n}\n"],
+        &["match r {\n    Result::Ok(n: int) => n\n}\n", "match r {\n    Result::Ok(1..5) => 1\n}\n"]),
+    production("arm_body", "block | expression /* `{ 表达式 : 表达式` 开头时是 map 字面量，其余的 `{` 开始块 */", Program,
+        &["match k {\n    1 => {\"a\": 1}\n    2 => {}\n    3 => {\n        outer: for {\n            break outer\n        }\n    }\n}\n"],
+        &["match k {\n    1 => {\"a\": }\n}\n"]),
+    production("select_stmt", "'select' '{' ( select_case ','? )+ '}' /* 最多一个 default */", Program,
+        &["select {\n    case var v = ch.receive() => println(v)\n    case out.send(1) => {}\n    default => println(\"idle\")\n}\n"],
+        &["select {\n}\n", "select {\n    default => 1\n    default => 2\n}\n"]),
+    production("select_case", "( 'case' ( 'var' IDENT '=' )? expression | 'default' ) '=>' arm_body", Program,
+        &["select {\n    case ch.receive() => 1\n}\n"],
+        &["select {\n    case var 1 = ch.receive() => 1\n}\n", "select {\n    ch.receive() => 1\n}\n"]),
+    production("switch_stmt", "'switch' expression '{' ( switch_case ','? )* '}' /* 最多一个 default，分支之间不贯穿 */", Program,
+        &["switch code {\n    case 200, 204 => println(\"ok\")\n    case 404 => {\n        println(\"gone\")\n    }\n    default => println(\"?\")\n}\n"],
+        &["switch code {\n    200 => 1\n}\n", "switch code {\n    default => 1\n    default => 2\n}\n"]),
+    production("switch_case", "( 'case' expression ( ',' expression )* | 'default' ) '=>' arm_body", Program,
+        &["switch s {\n    case \"a\", \"b\" => 1\n}\n"],
+        &["switch s {\n    case => 1\n}\n", "switch s {\n    case 1 2\n}\n"]),
+
+    // ---------- 类型 ----------
+    production("type", "base_type ( '<' type_list? '>' )? ( '[' ( INT | IDENT )? ']' )* '?'?", Type,
+        &["int", "string?", "int[]", "int[4][]", "Box<int, string>", "map[string]int[]"],
+        &["int[0]", "int[-1]", "Box<int", "int??"]),
+    production("base_type",
+        "'int' | 'uint' | 'i8' | 'i16' | 'i32' | 'i64' | 'u8' | 'u16' | 'u32' | 'u64' | 'f32' | 'f64' | 'bool' | 'byte' \
+         | 'char' | 'string' | 'unknown' | 'dynamic' | IDENT | 'map' '[' type ']' type | 'chan' '<' type '>' \
+         | 'func' '(' ( type ( ',' type )* ','? )? ')' type?", Type,
+        &["f64", "User", "chan<int>", "func(int, string) bool", "func()"],
+        &["map[string]", "chan int", "1"]),
+    production("type_list", "type ( ',' type )* ','?", Type,
+        &["Pair<int, string,>"],
+        &["Pair<int,, string>"]),
+
+    // ---------- 表达式 ----------
+    production("expression", "assignment", Expression,
+        &["a = b = 3"],
+        &["= 3"]),
+    production("assignment",
+        "conditional ( ( '=' | '+=' | '-=' | '*=' | '/=' | '%=' | '&=' | '|=' | '^=' | '<<=' | '>>=' ) assignment )? \
+         /* 左侧只能是变量、成员或下标 */", Expression,
+        &["x += 1", "obj.count = 0", "items[0] = 2", "mask <<= 2"],
+        &["1 = 2", "f() = 3"]),
+    production("conditional", "or_expr ( '?' conditional ':' conditional )?", Expression,
+        &["ok ? 1 : 2", "a ? b : c ? d : e"],
+        &["ok ? 1", "ok ? : 2"]),
+    production("or_expr", "and_expr ( ( '||' | '??' ) and_expr )*", Expression,
+        &["a || b ?? c"],
+        &["a ||"]),
+    production("and_expr", "bitor_expr ( '&&' bitor_expr )*", Expression,
+        &["a && b && !c"],
+        &["&& b"]),
+    production("bitor_expr", "bitxor_expr ( '|' bitxor_expr )*", Expression,
+        &["a | b"],
+        &["a |"]),
+    production("bitxor_expr", "bitand_expr ( '^' bitand_expr )*", Expression,
+        &["a ^ b"],
+        &["a ^"]),
+    production("bitand_expr", "equality ( '&' equality )*", Expression,
+        &["a & 0xff"],
+        &["a &"]),
+    production("equality", "comparison ( ( '==' | '!=' ) comparison )*", Expression,
+        &["a == b != c"],
+        &["a == == b"]),
+    production("comparison",
+        "shift ( ( '<' | '<=' | '>' | '>=' ) shift | ( '..' | '..=' ) term | 'as' '!'? type | 'is' type )* \
+         /* 表达式中的 `<` 总是比较运算符，没有显式泛型实参的调用 */", Expression,
+        &["a < b > (c)", "0..10", "1..=n", "x as string", "x as! User", "x is int"],
+        &["a <", "x as", "x is 1"]),
+    production("shift", "term ( ( '<<' | '>>' ) term )*", Expression,
+        &["1 << 4 >> 2"],
+        &["1 <<"]),
+    production("term", "factor ( ( '+' | '-' ) factor )*", Expression,
+        &["a + b - c"],
+        &["a + * b"]),
+    production("factor", "power ( ( '*' | '/' | '%' ) power )*", Expression,
+        &["a * b / c % d"],
+        &["a * / b"]),
+    production("power", "unary ( '**' power )? /* 右结合 */", Expression,
+        &["2 ** 3 ** 2"],
+        &["2 **"]),
+    production("unary", "( '-' | '!' | '~' ) unary | postfix", Expression,
+        &["-x", "!!done", "~mask"],
+        &["-"]),
+    production("postfix",
+        "primary ( ( '.' | '?.' | '!.' ) IDENT ( '(' ( expression ( ',' expression )* )? ')' )? | '(' arguments? ')' | '[' expression ']' )*",
+        Expression,
+        &["user?.profile!.name", "list.get(0).trim()", "matrix[1][2]", "make_adder(1)(2)"],
+        &["user.", "list[]", "f(1"]),
+    production("arguments", "argument ( ',' argument )* /* 命名参数必须在位置参数之后 */", Expression,
+        &["f(1, 2)", "f(1, name: \"a\", age: 3)"],
+        &["f(name: \"a\", 1)", "f(1,)"]),
+    production("argument", "( IDENT ':' )? expression /* `IDENT :` 总是命名参数 */", Expression,
+        &["f(label: 1)", "f(flag ? a : b)"],
+        &["f(label:)", "f(: 1)"]),
+    production("primary",
+        "INT | FLOAT | STRING | 'true' | 'false' | 'null' | 'this' | 'super' | IDENT | IDENT '::' IDENT ( '(' ( expression ( ',' expression )* )? ')' )? \
+         | struct_literal | '(' expression ')' | closure | array_literal | map_literal | new_expr \
+         | 'go' expression | 'chan' '<' type '>' '(' expression? ')' | ( 'typeof' | 'sizeof' | 'make' ) '(' arguments? ')'", Expression,
+        &["42", "3.5", "\"hi ${name}\"", "null", "(a + b)", "Color::Red", "Math::max(1, 2)", "go worker(1)", "chan<int>(16)", "typeof(x)"],
+        &["()", "Color::", "chan<int>", "chan(1)"]),
+    production("struct_literal", "IDENT '{' ( IDENT ':' expression ( ',' IDENT ':' expression )* ','? )? '}' \
+         /* if/for/match 的条件中不识别 */", Expression,
+        &["Point { x: 1, y: 2 }", "Point {}", "Point {\n    x: 1,\n}"],
+        &["Point { 1 }", "Point { x 1 }"]),
+    production("closure", "'func' '(' params? ')' type? block", Expression,
+        &["func(x: int) int {\n    return x * 2\n}", "func() {}"],
+        &["func(x) {}", "func(x: int) => x"]),
+    production("array_literal", "'[' ( expression ( ',' expression )* ','? )? ']'", Expression,
+        &["[1, 2, 3]", "[]", "[[1], [2],]"],
+        &["[1 2]", "[,]"]),
+    production("map_literal", "'{' ( expression ':' expression ( ',' expression ':' expression )* ','? )? '}'", Expression,
+        &["{\"a\": 1, \"b\": 2}", "{}", "{\n    1: \"one\",\n}"],
+        &["{\"a\"}", "{\"a\": 1 \"b\": 2}"]),
+    production("new_expr", "'new' IDENT ( '(' ( expression ( ',' expression )* )? ')' \
+         | '{' ( IDENT ':' expression ( ',' IDENT ':' expression )* ','? )? '}' )", Expression,
+        &["new User(\"a\", 1)", "new Stack()", "new User { name: \"a\", age: 3 }", "new User {}"],
+        &["new User", "new (1)", "new User(name: \"a\")", "new User { \"a\" }"]),
+];
+
+/// 生成 W3C 风格的 EBNF：每条产生式一行，接受的例子作为注释
+pub fn to_ebnf() -> String {
+    let width = GRAMMAR.iter().map(|p| p.name.len()).max().unwrap_or(0);
+    let mut out = String::from("/* qlang grammar: generated from src/parser/grammar.rs */\n");
+    for production in GRAMMAR {
+        out.push('\n');
+        for example in production.accepts {
+            out.push_str(&format!("/* e.g. {} */\n", example.trim_end().replace('\n', "\u{21b5} ")));
+        }
+        out.push_str(&format!("{:<width$} ::= {}\n", production.name, production.rule, width = width));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::Locale;
+    use crate::lexer::Scanner;
+    use crate::parser::Parser;
+    use std::collections::HashSet;
+
+    /// 把例子放进可以直接解析的源码
+    fn wrap(context: Context, example: &str) -> String {
+        match context {
+            Context::Program => example.to_string(),
+            Context::Expression => format!("var _e = {}\n", example),
+            Context::Type => format!("var _t: {} = null\n", example),
+        }
+    }
+
+    fn parses(source: &str) -> bool {
+        let tokens = Scanner::new(source).scan_tokens();
+        Parser::new(tokens, Locale::En).parse().is_ok()
+    }
+
+    #[test]
+    fn test_parser_agrees_with_grammar() {
+        let mut failures = Vec::new();
+        for production in GRAMMAR {
+            for example in production.accepts {
+                if !parses(&wrap(production.context, example)) {
+                    failures.push(format!("{}: should accept {:?}", production.name, example));
+                }
+            }
+            for example in production.rejects {
+                if parses(&wrap(production.context, example)) {
+                    failures.push(format!("{}: should reject {:?}", production.name, example));
+                }
+            }
+        }
+        assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn test_grammar_is_closed() {
+        let names: HashSet<&str> = GRAMMAR.iter().map(|p| p.name).collect();
+        assert_eq!(names.len(), GRAMMAR.len(), "duplicate production");
+        for production in GRAMMAR {
+            assert!(!production.accepts.is_empty() && !production.rejects.is_empty(), "{} needs examples", production.name);
+            // 右部引用的小写名字都必须有定义（引号内的是终结符）
+            let unquoted: String = production.rule.split('\'').step_by(2).collect::<Vec<_>>().join(" ");
+            let unquoted = unquoted.split("/*").map(|s| s.split("*/").last().unwrap_or("")).collect::<String>();
+            for word in unquoted.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_')) {
+                if word.starts_with(|c: char| c.is_ascii_lowercase()) {
+                    assert!(names.contains(word), "{} refers to undefined {}", production.name, word);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_ebnf_output() {
+        let ebnf = to_ebnf();
+        assert!(ebnf.lines().nth(2).unwrap().starts_with("/* e.g. package app.main"));
+        assert!(ebnf.contains("\nprogram         ::= package_decl? import_decl* statement*\n"));
+        assert_eq!(ebnf.matches(" ::= ").count(), GRAMMAR.len());
+    }
+}
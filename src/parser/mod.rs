@@ -4,6 +4,11 @@
 
 pub mod ast;
 pub mod parser;
+pub mod const_eval;
+pub mod grammar;
+pub mod cache;
 
 pub use ast::*;
-pub use parser::Parser;
+pub use parser::{Parser, ParseError};
+pub use cache::ParseCache;
+pub use const_eval::{const_items, eval_const, fold_consts, ConstEvalError, ConstValue};
@@ -2,10 +2,11 @@
 //! 
 //! 使用递归下降法将 Token 流解析为 AST
 
-use crate::lexer::{Token, TokenKind, Span};
+use crate::lexer::{Token, TokenKind, Span, StringPart};
 use crate::i18n::{Locale, format_message, messages};
-use super::ast::{Expr, Stmt, Program, BinOp, UnaryOp, AssignOp, TypeAnnotation, FnParam, ImportDecl, ImportTarget};
+use super::ast::{Expr, Stmt, Program, BinOp, UnaryOp, AssignOp, TypeAnnotation, FnParam, ImportDecl, ImportTarget, DISCARD};
 use crate::types::Type;
+use super::const_eval::{eval_const, ConstValue};
 
 /// 运算符优先级
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -149,10 +150,16 @@ impl std::fmt::Display for ParseError {
     }
 }
 
+/// 解析器越过当前 token 最多向前查看的 token 数（[`Parser::peek`] 的上限）
+///
+/// `标识符 :`、`标识符 in`、`标识符 ::` 这类判断只需要下一个 token；
+/// 看一个 token 不能确定的结构（如分支体中的 `{`）用 [`Parser::speculate`] 试探解析
+pub const MAX_LOOKAHEAD: usize = 1;
+
 /// 语法解析器
-pub struct Parser {
-    /// Token 列表
-    tokens: Vec<Token>,
+pub struct Parser<'a> {
+    /// Token 列表（借用源码）
+    tokens: Vec<Token<'a>>,
     /// 当前位置
     current: usize,
     /// 错误列表
@@ -161,22 +168,60 @@ pub struct Parser {
     locale: Locale,
     /// 恐慌模式（遇到错误后进入，用于错误恢复）
     panic_mode: bool,
+    /// 禁止 struct 字面量（解析 `for x in items {` 的可迭代表达式时，`items {` 是循环体）
+    no_struct_literal: bool,
+    /// 已解析的顶层常量（用于类型注解中的数组长度，如 `int[SIZE]`）
+    consts: std::collections::HashMap<String, ConstValue>,
+    /// 已创建的语句和表达式节点数（`--timings` 的统计）
+    node_count: usize,
 }
 
-impl Parser {
+impl<'a> Parser<'a> {
     /// 创建新的解析器
-    pub fn new(tokens: Vec<Token>, locale: Locale) -> Self {
+    pub fn new(tokens: Vec<Token<'a>>, locale: Locale) -> Self {
         Self {
             tokens,
             current: 0,
             errors: Vec::new(),
             locale,
             panic_mode: false,
+            no_struct_literal: false,
+            consts: std::collections::HashMap::new(),
+            node_count: 0,
         }
     }
 
+    /// 已解析的语句和表达式节点数
+    pub fn node_count(&self) -> usize {
+        self.node_count
+    }
+
     /// 解析程序
     pub fn parse(&mut self) -> Result<Program, Vec<ParseError>> {
+        let (program, errors) = self.parse_recovering();
+        if errors.is_empty() {
+            Ok(program)
+        } else {
+            Err(errors)
+        }
+    }
+    
+    /// 解析单独的类型（如宿主函数的签名 `func(string) int`），类型后面不能有其他内容
+    pub fn parse_type_only(&mut self) -> Result<Type, ParseError> {
+        let ty = self.parse_type()?;
+        if !self.is_at_end() {
+            return Err(ParseError::new(
+                format!("unexpected '{}' after type", self.current_token().lexeme),
+                self.current_span(),
+            ));
+        }
+        Ok(ty)
+    }
+    
+    /// 解析程序，出错后在语句边界恢复并继续，返回能解析的部分和所有错误
+    ///
+    /// 出错的语句不在结果中；有错误时结果只用于读取 package 和 import 等信息，不能用于编译
+    pub fn parse_recovering(&mut self) -> (Program, Vec<ParseError>) {
         let mut package: Option<String> = None;
         let mut imports: Vec<ImportDecl> = Vec::new();
         let mut statements = Vec::new();
@@ -231,7 +276,15 @@ impl Parser {
             }
             
             match self.parse_statement() {
-                Ok(stmt) => statements.push(stmt),
+                Ok(stmt) => {
+                    if let Stmt::ConstDecl { name, initializer, .. } = &stmt {
+                        let consts = &self.consts;
+                        if let Ok(value) = eval_const(initializer, &|n| consts.get(n).cloned()) {
+                            self.consts.insert(name.clone(), value);
+                        }
+                    }
+                    statements.push(stmt);
+                }
                 Err(e) => {
                     self.errors.push(e);
                     self.synchronize();
@@ -239,11 +292,7 @@ impl Parser {
             }
         }
         
-        if self.errors.is_empty() {
-            Ok(Program::with_package_and_imports(package, imports, statements))
-        } else {
-            Err(self.errors.clone())
-        }
+        (Program::with_package_and_imports(package, imports, statements), std::mem::take(&mut self.errors))
     }
     
     /// 解析包声明
@@ -374,6 +423,7 @@ impl Parser {
 
     /// 解析语句
     fn parse_statement(&mut self) -> Result<Stmt, ParseError> {
+        self.node_count += 1;
         // 检查是否是 print/println 语句
         if self.check_identifier("print") {
             return self.parse_print_statement(false);
@@ -405,8 +455,8 @@ impl Parser {
         // 检查带标签的循环（label: for ...）
         if let TokenKind::Identifier(name) = &self.current_token().kind.clone() {
             // 检查是否是 label: for 语法
-            if self.peek_token().map(|t| &t.kind) == Some(&TokenKind::Colon) {
-                let label = name.clone();
+            if self.peek(1) == &TokenKind::Colon {
+                let label = name.to_string();
                 self.advance(); // 消费标识符
                 self.advance(); // 消费冒号
                 if self.check(&TokenKind::For) {
@@ -479,6 +529,9 @@ impl Parser {
             return self.parse_type_alias();
         }
         
+        // 属性只能写在函数定义之前
+        let attributes = self.parse_attributes()?;
+        
         // 检查可见性修饰符 + 函数定义
         let visibility = if self.check(&TokenKind::Public) || self.check(&TokenKind::Internal) 
                            || self.check(&TokenKind::Private) || self.check(&TokenKind::Protected) {
@@ -486,7 +539,7 @@ impl Parser {
             // 可见性后面必须跟 func
             if !self.check(&TokenKind::Func) {
                 return Err(ParseError::new(
-                    format!("Visibility modifier must be followed by 'func' keyword"),
+                    "Visibility modifier must be followed by 'func' keyword".to_string(),
                     self.current_span(),
                 ));
             }
@@ -500,7 +553,14 @@ impl Parser {
         
         // 检查命名函数定义 func name(params) return_type { }
         if self.check(&TokenKind::Func) {
-            return self.parse_named_function_with_visibility(visibility);
+            let mut function = self.parse_named_function_with_visibility(visibility)?;
+            if let Stmt::FnDef { attributes: slot, .. } = &mut function {
+                *slot = attributes;
+            }
+            return Ok(function);
+        }
+        if let Some(attribute) = attributes.first() {
+            return Err(Self::misplaced_attribute(attribute));
         }
         
         // 检查 match 语句
@@ -508,6 +568,16 @@ impl Parser {
             return self.parse_match_statement();
         }
         
+        // 检查 select 语句
+        if self.check(&TokenKind::Select) {
+            return self.parse_select_statement();
+        }
+        
+        // 检查 switch 语句
+        if self.check(&TokenKind::Switch) {
+            return self.parse_switch_statement();
+        }
+        
         // 检查 try 语句
         if self.check(&TokenKind::Try) {
             return self.parse_try_statement();
@@ -518,6 +588,11 @@ impl Parser {
             return self.parse_throw_statement();
         }
         
+        // 检查 recovering 语句（recovering 不是关键字，后面紧跟 { 时才是语句）
+        if self.check_identifier("recovering") && matches!(self.peek(1), TokenKind::LeftBrace) {
+            return self.parse_recovering_statement();
+        }
+        
         // 否则是表达式语句
         self.parse_expression_statement()
     }
@@ -559,8 +634,8 @@ impl Parser {
         let start_span = self.current_span();
         self.advance(); // 消费 'var'
         
-        // 变量名
-        let name = self.expect_identifier()?;
+        // 变量名，`_` 丢弃初始值
+        let name = if self.match_discard() { DISCARD.to_string() } else { self.expect_identifier()? };
         
         // 可选的类型注解
         let type_ann = if self.check(&TokenKind::Colon) {
@@ -656,7 +731,18 @@ impl Parser {
                 break;
             }
             
-            statements.push(self.parse_statement()?);
+            // 出错的语句记录错误后跳过，继续解析块中的其余语句
+            let start = self.current;
+            match self.parse_statement() {
+                Ok(stmt) => statements.push(stmt),
+                Err(e) => {
+                    self.errors.push(e);
+                    if self.current == start {
+                        self.advance();
+                    }
+                    self.synchronize_in_block();
+                }
+            }
         }
         
         self.expect(&TokenKind::RightBrace)?;
@@ -711,13 +797,14 @@ impl Parser {
             return Ok(Stmt::While { label, condition: None, body, span });
         }
         
-        // 尝试解析 C 风格 for 循环 (for init; cond; post {})
-        // 首先，我们需要检查是否存在分号来判断是否是 C 风格
-        // 但这需要 lookahead，所以我们尝试解析然后检查
-        
-        // 保存当前位置用于回溯
-        let saved_pos = self.current;
+        // for-in 循环：for x in items / for i, v in items
+        if matches!(self.peek(0), TokenKind::Identifier(_) | TokenKind::Underscore)
+            && matches!(self.peek(1), TokenKind::In | TokenKind::Comma)
+        {
+            return self.parse_for_in_statement(start_span, label);
+        }
         
+        // 否则是条件循环 for cond {} 或 C 风格循环 for init; cond; post {}
         // 尝试解析初始化部分（可能是 var 声明或表达式）
         let initializer = if self.check(&TokenKind::Semicolon) {
             None
@@ -740,10 +827,6 @@ impl Parser {
                 let end_span = self.previous_span();
                 let span = Span::new(start_span.start, end_span.end, start_span.line, start_span.column);
                 return Ok(Stmt::While { label: label.clone(), condition: Some(expr), body, span });
-            } else if self.check(&TokenKind::In) {
-                // 这是 for-in 循环，暂时回溯
-                self.current = saved_pos;
-                return self.parse_for_in_statement(start_span, label.clone());
             } else {
                 // 意外的 token
                 let msg = format!("Expected '{{' or ';' after for condition");
@@ -782,14 +865,14 @@ impl Parser {
     
     /// 解析 for-in 循环
     fn parse_for_in_statement(&mut self, start_span: Span, label: Option<String>) -> Result<Stmt, ParseError> {
-        // 解析变量名（可能有多个，如 for i, v in array）
+        // 解析变量名（可能有多个，如 for i, v in array；`_` 丢弃对应的值）
         let mut variables = Vec::new();
-        let first_var = self.expect_identifier()?;
+        let first_var = if self.match_discard() { DISCARD.to_string() } else { self.expect_identifier()? };
         variables.push(first_var);
         
         while self.check(&TokenKind::Comma) {
             self.advance();
-            let var = self.expect_identifier()?;
+            let var = if self.match_discard() { DISCARD.to_string() } else { self.expect_identifier()? };
             variables.push(var);
         }
         
@@ -797,7 +880,10 @@ impl Parser {
         self.expect(&TokenKind::In)?;
         
         // 解析可迭代表达式
-        let iterable = self.parse_expression()?;
+        let saved = std::mem::replace(&mut self.no_struct_literal, true);
+        let iterable = self.parse_expression();
+        self.no_struct_literal = saved;
+        let iterable = iterable?;
         
         // 解析循环体
         let body = Box::new(self.parse_block()?);
@@ -834,6 +920,7 @@ impl Parser {
         self.expect(&TokenKind::LeftBrace)?;
         
         let mut fields = Vec::new();
+        let mut static_fields = Vec::new();
         let mut methods = Vec::new();
         
         // 解析字段和方法
@@ -847,13 +934,35 @@ impl Parser {
                 break;
             }
             
+            let attributes = self.parse_attributes()?;
+            
             // 检查可见性修饰符
             let visibility = self.parse_visibility();
             
+            // 检查 static
+            let is_static = if self.check(&TokenKind::Static) {
+                self.advance();
+                true
+            } else {
+                false
+            };
+            
             // 检查是否是方法（func 关键字）
             if self.check(&TokenKind::Func) {
-                let method = self.parse_struct_method(visibility)?;
+                let mut method = self.parse_struct_method(visibility, is_static)?;
+                method.attributes = attributes;
                 methods.push(method);
+            } else if let Some(attribute) = attributes.first() {
+                return Err(Self::misplaced_attribute(attribute));
+            } else if self.check(&TokenKind::Const) || (is_static && self.check(&TokenKind::Var)) {
+                // 静态字段（static var）和常量（const / static const），与 class 共用字段结构
+                let is_const = self.check(&TokenKind::Const);
+                self.advance(); // 消费 var 或 const
+                let field = self.parse_class_field(visibility, true, is_const)?;
+                static_fields.push(field);
+            } else if is_static {
+                let msg = "'static' must be followed by 'func', 'var' or 'const'".to_string();
+                return Err(ParseError::new(msg, self.current_span()));
             } else {
                 // 解析字段
                 let field = self.parse_struct_field(visibility)?;
@@ -867,7 +976,7 @@ impl Parser {
         let end_span = self.previous_span();
         let span = Span::new(start_span.start, end_span.end, start_span.line, start_span.column);
         
-        Ok(Stmt::StructDef { name, type_params, where_clauses: Vec::new(), interfaces, fields, methods, span })
+        Ok(Stmt::StructDef { name, type_params, where_clauses: Vec::new(), interfaces, fields, static_fields, methods, span })
     }
     
     /// 解析可见性修饰符（Kotlin 风格，默认为 public）
@@ -1100,7 +1209,7 @@ impl Parser {
     }
     
     /// 解析 struct 方法
-    fn parse_struct_method(&mut self, visibility: super::ast::Visibility) -> Result<super::ast::StructMethod, ParseError> {
+    fn parse_struct_method(&mut self, visibility: super::ast::Visibility, is_static: bool) -> Result<super::ast::StructMethod, ParseError> {
         let start_span = self.current_span();
         self.advance(); // 消费 'func'
         
@@ -1125,7 +1234,7 @@ impl Parser {
         let end_span = self.previous_span();
         let span = Span::new(start_span.start, end_span.end, start_span.line, start_span.column);
         
-        Ok(super::ast::StructMethod { name, params, return_type, body, visibility, span })
+        Ok(super::ast::StructMethod { name, params, return_type, body, visibility, is_static, attributes: Vec::new(), span })
     }
     
     /// 解析 class 定义
@@ -1176,6 +1285,11 @@ impl Parser {
                 break;
             }
             
+            let attributes = self.parse_attributes()?;
+            if let Some(attribute) = attributes.first().filter(|_| self.check(&TokenKind::Use)) {
+                return Err(Self::misplaced_attribute(attribute));
+            }
+            
             // 检查 use trait 语法
             if self.check(&TokenKind::Use) {
                 self.advance();
@@ -1199,25 +1313,30 @@ impl Parser {
                 false
             };
             
-            // 检查 const (用于 static const 字段)
-            let is_const = if self.check(&TokenKind::Const) {
-                if !is_static {
-                    let msg = "'const' can only be used with 'static' (use 'static const')".to_string();
-                    return Err(ParseError::new(msg, self.current_span()));
+            // const 成员（`const X = 1` 或 `static const X = 1`）总是静态的，值在编译期求出
+            if self.check(&TokenKind::Const) {
+                if let Some(attribute) = attributes.first() {
+                    return Err(Self::misplaced_attribute(attribute));
                 }
                 self.advance();
-                true
-            } else {
-                false
-            };
+                let field = self.parse_class_field(visibility, true, true)?;
+                fields.push(field);
+                continue;
+            }
             
-            // 检查 override
-            let is_override = if self.check(&TokenKind::Override) {
+            // 检查 override 和 final（顺序不限）
+            let mut is_override = false;
+            let mut is_final = false;
+            loop {
+                if self.check(&TokenKind::Override) && !is_override {
+                    is_override = true;
+                } else if self.check(&TokenKind::Final) && !is_final {
+                    is_final = true;
+                } else {
+                    break;
+                }
                 self.advance();
-                true
-            } else {
-                false
-            };
+            }
             
             // 检查 abstract（仅在抽象类中允许）
             let is_method_abstract = if self.check(&TokenKind::Abstract) {
@@ -1231,15 +1350,29 @@ impl Parser {
                 false
             };
             
+            // final 只用于可以被重写的实例方法
+            if is_final && (is_static || is_method_abstract) {
+                let msg = "'final' cannot be combined with 'static' or 'abstract'".to_string();
+                return Err(ParseError::new(msg, self.current_span()));
+            }
+            
             // 检查是否是方法（func 关键字，包括构造函数 func init()）
             if self.check(&TokenKind::Func) {
-                let method = self.parse_class_method(visibility, is_static, is_override, is_method_abstract)?;
+                let mut method = self.parse_class_method(visibility, is_static, is_override, is_method_abstract)?;
+                method.is_final = is_final;
+                method.attributes = attributes;
                 methods.push(method);
-            } else if self.check(&TokenKind::Var) || self.check(&TokenKind::Const) {
-                // 解析字段（必须有 var 或 const 关键字）
-                let is_const_field = self.check(&TokenKind::Const);
-                self.advance(); // 消费 var 或 const
-                let field = self.parse_class_field(visibility, is_static, is_const_field || is_const)?;
+            } else if self.check(&TokenKind::Var) {
+                if let Some(attribute) = attributes.first() {
+                    return Err(Self::misplaced_attribute(attribute));
+                }
+                if is_override || is_final {
+                    let msg = "'override' and 'final' can only be applied to methods".to_string();
+                    return Err(ParseError::new(msg, self.current_span()));
+                }
+                // 解析字段（必须有 var 关键字）
+                self.advance(); // 消费 var
+                let field = self.parse_class_field(visibility, is_static, false)?;
                 fields.push(field);
             } else {
                 // 不是 func、var、const，报错
@@ -1367,7 +1500,7 @@ impl Parser {
         let end_span = self.previous_span();
         let span = Span::new(start_span.start, end_span.end, start_span.line, start_span.column);
         
-        Ok(super::ast::ClassMethod { name, params, return_type, body, visibility, is_static, is_override, is_abstract, span })
+        Ok(super::ast::ClassMethod { name, params, return_type, body, visibility, is_static, is_override, is_final: false, is_abstract, attributes: Vec::new(), span })
     }
     
     /// 解析 interface 定义
@@ -1676,6 +1809,243 @@ impl Parser {
         Ok(Stmt::Match { expr, arms, span })
     }
     
+    /// 解析 select 语句
+    ///
+    /// ```text
+    /// select {
+    ///     case var v = ch.receive() => { ... }
+    ///     case ch.send(x) => ...
+    ///     default => ...
+    /// }
+    /// ```
+    fn parse_select_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start_span = self.current_span();
+        self.advance(); // 消费 'select'
+        
+        self.expect(&TokenKind::LeftBrace)?;
+        
+        let mut cases = Vec::new();
+        let mut has_default = false;
+        
+        while !self.check(&TokenKind::RightBrace) && !self.is_at_end() {
+            // 跳过空行
+            while self.check(&TokenKind::Newline) {
+                self.advance();
+            }
+            
+            if self.check(&TokenKind::RightBrace) {
+                break;
+            }
+            
+            let case = self.parse_select_case()?;
+            if case.kind == super::ast::SelectCaseKind::Default {
+                if has_default {
+                    return Err(ParseError::new(
+                        "select can have at most one 'default' case".to_string(),
+                        case.span,
+                    ));
+                }
+                has_default = true;
+            }
+            cases.push(case);
+            
+            // 逗号分隔（可选）
+            if self.check(&TokenKind::Comma) {
+                self.advance();
+            }
+            
+            // 跳过空行
+            while self.check(&TokenKind::Newline) {
+                self.advance();
+            }
+        }
+        
+        self.expect(&TokenKind::RightBrace)?;
+        
+        let end_span = self.previous_span();
+        let span = Span::new(start_span.start, end_span.end, start_span.line, start_span.column);
+        
+        if cases.is_empty() {
+            return Err(ParseError::new("select must have at least one case".to_string(), span));
+        }
+        
+        Ok(Stmt::Select { cases, span })
+    }
+    
+    /// 解析 select 分支：`case <通道操作> => 分支体` 或 `default => 分支体`
+    fn parse_select_case(&mut self) -> Result<super::ast::SelectCase, ParseError> {
+        use super::ast::SelectCaseKind;
+        
+        let start_span = self.current_span();
+        
+        let kind = if self.check(&TokenKind::Default) {
+            self.advance();
+            SelectCaseKind::Default
+        } else {
+            self.expect(&TokenKind::Case)?;
+            
+            // case var v = ch.receive()
+            let binding = if self.check(&TokenKind::Var) {
+                self.advance();
+                let name = match &self.current_token().kind {
+                    TokenKind::Identifier(name) => name.to_string(),
+                    _ => {
+                        let msg = format_message(
+                            messages::ERR_COMPILE_EXPECTED_IDENTIFIER,
+                            self.locale,
+                            &[],
+                        );
+                        return Err(ParseError::new(msg, self.current_span()));
+                    }
+                };
+                self.advance();
+                self.expect(&TokenKind::Equal)?;
+                Some(name)
+            } else {
+                None
+            };
+            
+            let op = self.parse_expression()?;
+            let op_span = op.span();
+            match op {
+                Expr::Call { callee, mut args, .. } => match *callee {
+                    Expr::Member { object, member, .. } if member == "receive" && args.is_empty() => {
+                        SelectCaseKind::Receive { channel: *object, binding }
+                    }
+                    Expr::Member { object, member, .. } if member == "send" && args.len() == 1 && binding.is_none() => {
+                        let (_, value) = args.remove(0);
+                        SelectCaseKind::Send { channel: *object, value }
+                    }
+                    _ => return Err(Self::invalid_select_case(op_span, binding.is_some())),
+                },
+                _ => return Err(Self::invalid_select_case(op_span, binding.is_some())),
+            }
+        };
+        
+        self.expect(&TokenKind::FatArrow)?;
+        
+        let body = self.parse_arm_body()?;
+        
+        let end_span = self.previous_span();
+        let span = Span::new(start_span.start, end_span.end, start_span.line, start_span.column);
+        
+        Ok(super::ast::SelectCase { kind, body, span })
+    }
+    
+    /// 解析 switch 语句
+    ///
+    /// ```text
+    /// switch code {
+    ///     case 200, 204 => println("ok")
+    ///     case 404 => { ... }
+    ///     default => println("?")
+    /// }
+    /// ```
+    fn parse_switch_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start_span = self.current_span();
+        self.advance(); // 消费 'switch'
+        
+        // 与 match 相同，主体表达式中不识别 struct 字面量
+        let expr = self.parse_match_subject()?;
+        self.expect(&TokenKind::LeftBrace)?;
+        
+        let mut cases = Vec::new();
+        let mut default = None;
+        
+        while !self.check(&TokenKind::RightBrace) && !self.is_at_end() {
+            // 跳过空行
+            while self.check(&TokenKind::Newline) {
+                self.advance();
+            }
+            
+            if self.check(&TokenKind::RightBrace) {
+                break;
+            }
+            
+            let case_span = self.current_span();
+            if self.check(&TokenKind::Default) {
+                self.advance();
+                if default.is_some() {
+                    return Err(ParseError::new(
+                        "switch can have at most one 'default' case".to_string(),
+                        case_span,
+                    ));
+                }
+                self.expect(&TokenKind::FatArrow)?;
+                default = Some(self.parse_arm_body()?);
+            } else {
+                self.expect(&TokenKind::Case)?;
+                let mut values = vec![self.parse_expression()?];
+                while self.check(&TokenKind::Comma) {
+                    self.advance();
+                    values.push(self.parse_expression()?);
+                }
+                self.expect(&TokenKind::FatArrow)?;
+                let body = self.parse_arm_body()?;
+                let end_span = self.previous_span();
+                let span = Span::new(case_span.start, end_span.end, case_span.line, case_span.column);
+                cases.push(super::ast::SwitchCase { values, body, span });
+            }
+            
+            // 逗号分隔（可选）
+            if self.check(&TokenKind::Comma) {
+                self.advance();
+            }
+            
+            // 跳过空行
+            while self.check(&TokenKind::Newline) {
+                self.advance();
+            }
+        }
+        
+        self.expect(&TokenKind::RightBrace)?;
+        
+        let end_span = self.previous_span();
+        let span = Span::new(start_span.start, end_span.end, start_span.line, start_span.column);
+        
+        Ok(Stmt::Switch { expr, cases, default, span })
+    }
+    
+    /// 解析 match/select/switch 分支体：块或表达式
+    ///
+    /// `{` 在这里既可能开始块也可能开始 map 字面量：`{ 表达式 : 表达式` 开头时是 map 字面量，
+    /// 其余（包括 `{}` 和 `{ label: for ... }`）都是块
+    fn parse_arm_body(&mut self) -> Result<Box<Stmt>, ParseError> {
+        if self.check(&TokenKind::LeftBrace) && !self.brace_starts_map() {
+            return Ok(Box::new(self.parse_block()?));
+        }
+        let expr = self.parse_expression()?;
+        let expr_span = expr.span();
+        Ok(Box::new(Stmt::Expression { expr, span: expr_span }))
+    }
+
+    /// 当前的 `{` 是否开始一个 map 字面量（试探解析第一个键值对，不消费 token）
+    fn brace_starts_map(&mut self) -> bool {
+        let start = self.current;
+        let is_map = self
+            .speculate(|p| {
+                p.advance(); // 消费 '{'
+                while p.check(&TokenKind::Newline) {
+                    p.advance();
+                }
+                p.parse_expression()?;
+                p.expect(&TokenKind::Colon)?;
+                p.parse_expression()
+            })
+            .is_some();
+        self.current = start;
+        is_map
+    }
+    
+    fn invalid_select_case(span: Span, has_binding: bool) -> ParseError {
+        let msg = if has_binding {
+            "select case binding must be a channel receive: 'case var v = ch.receive()'"
+        } else {
+            "select case must be a channel operation: 'ch.receive()' or 'ch.send(value)'"
+        };
+        ParseError::new(msg.to_string(), span)
+    }
+    
     /// 解析 match 的主体表达式（不能是 struct 字面量）
     /// 这是因为 `match x { ... }` 和 `x { ... }` (struct 字面量) 有歧义
     fn parse_match_subject(&mut self) -> Result<Expr, ParseError> {
@@ -1698,13 +2068,14 @@ impl Parser {
         match &token.kind {
             TokenKind::Integer(n) => Ok(Expr::Integer { value: *n, span: token.span }),
             TokenKind::Float(f) => Ok(Expr::Float { value: *f, span: token.span }),
-            TokenKind::String(s) => self.parse_string_interpolation(s.clone(), token.span),
-            TokenKind::RawString(s) => Ok(Expr::String { value: s.clone(), span: token.span }),
+            TokenKind::String(s) => Ok(Expr::String { value: s.to_string(), span: token.span }),
+            TokenKind::InterpolatedString(parts) => self.parse_string_interpolation(parts, token.span),
+            TokenKind::RawString(s) => Ok(Expr::String { value: s.to_string(), span: token.span }),
             TokenKind::True => Ok(Expr::Bool { value: true, span: token.span }),
             TokenKind::False => Ok(Expr::Bool { value: false, span: token.span }),
             TokenKind::Null => Ok(Expr::Null { span: token.span }),
             TokenKind::Identifier(name) => {
-                let name = name.clone();
+                let name = name.to_string();
                 // 检查是否是函数调用
                 if self.check(&TokenKind::LeftParen) {
                     self.parse_call(name.clone(), token.span)
@@ -1723,6 +2094,7 @@ impl Parser {
                 })
             }
             TokenKind::This => Ok(Expr::This { span: token.span }),
+            TokenKind::Underscore => Err(Self::discard_read_error(token.span)),
             _ => {
                 let msg = format_message(
                     messages::ERR_COMPILE_EXPECTED_EXPRESSION,
@@ -1752,14 +2124,7 @@ impl Parser {
         // 期望 '=>'
         self.expect(&TokenKind::FatArrow)?;
         
-        // 解析分支体（可以是块或表达式）
-        let body = if self.check(&TokenKind::LeftBrace) {
-            Box::new(self.parse_block()?)
-        } else {
-            let expr = self.parse_expression()?;
-            let expr_span = expr.span();
-            Box::new(Stmt::Expression { expr, span: expr_span })
-        };
+        let body = self.parse_arm_body()?;
         
         let end_span = self.previous_span();
         let span = Span::new(start_span.start, end_span.end, start_span.line, start_span.column);
@@ -1784,20 +2149,14 @@ impl Parser {
         let mut patterns = vec![first];
         while self.check(&TokenKind::Comma) {
             // 查看逗号后面是什么
-            if let Some(next) = self.peek_token() {
-                // 如果下一个 token 是换行、=> 或 if，说明这个逗号是 arm 分隔符
-                let next_kind = &next.kind;
-                if matches!(next_kind, TokenKind::FatArrow | TokenKind::If | TokenKind::RightBrace | TokenKind::Newline) {
-                    break;
-                }
-                // 如果下一个 token 可能是模式的开始（数字、标识符、_），继续收集
-                if !matches!(next_kind, 
-                    TokenKind::Integer(_) | TokenKind::Float(_) | TokenKind::String(_) | 
-                    TokenKind::RawString(_) | TokenKind::True | TokenKind::False | 
-                    TokenKind::Null | TokenKind::Identifier(_) | TokenKind::Underscore
-                ) {
-                    break;
-                }
+            // 如果下一个 token 可能是模式的开始（数字、标识符、_），继续收集；
+            // 换行、=>、if 或 } 说明这个逗号是 arm 分隔符
+            if !matches!(self.peek(1),
+                TokenKind::Integer(_) | TokenKind::Float(_) | TokenKind::String(_) | 
+                TokenKind::RawString(_) | TokenKind::True | TokenKind::False | 
+                TokenKind::Null | TokenKind::Identifier(_) | TokenKind::Underscore
+            ) {
+                break;
             }
             
             self.advance(); // 消费逗号
@@ -1824,27 +2183,15 @@ impl Parser {
         match &self.current_token().kind {
             TokenKind::Integer(_) | TokenKind::Float(_) | TokenKind::String(_) | TokenKind::RawString(_) 
             | TokenKind::True | TokenKind::False | TokenKind::Null => {
-                // 先解析一个基本表达式（不包括中缀运算符）
-                let start_expr = self.parse_prefix()?;
-                
-                // 检查是否是范围模式（在中缀处理之前）
-                if self.check(&TokenKind::DotDot) || self.check(&TokenKind::DotDotEqual) {
-                    let inclusive = self.check(&TokenKind::DotDotEqual);
-                    self.advance();
-                    // 解析范围的结束值
-                    let end_expr = self.parse_prefix()?;
-                    return Ok(super::ast::MatchPattern::Range {
-                        start: Box::new(start_expr),
-                        end: Box::new(end_expr),
-                        inclusive,
-                    });
-                }
-                
-                return Ok(super::ast::MatchPattern::Literal(start_expr));
+                return self.parse_literal_pattern();
             }
             // 标识符可能是变量绑定
             TokenKind::Identifier(name) => {
-                let name = name.clone();
+                // 静态成员（如类常量 Config::MAX）按字面量比较，后面有括号时是枚举变体的关联数据
+                if self.peek(1) == &TokenKind::ColonColon {
+                    return self.parse_static_pattern();
+                }
+                let name = name.to_string();
                 self.advance();
                 
                 // 检查是否是类型模式 x:Type
@@ -1865,6 +2212,72 @@ impl Parser {
         }
     }
     
+    /// 解析字面量模式或范围模式（1、"a"、1..10）
+    fn parse_literal_pattern(&mut self) -> Result<super::ast::MatchPattern, ParseError> {
+        // 先解析一个基本表达式（不包括中缀运算符）
+        let start_expr = self.parse_prefix()?;
+        self.parse_range_pattern(start_expr)
+    }
+    
+    /// 解析以 `Type::member` 开头的模式：枚举变体 `Shape::Rect(w, _)`，或者按字面量比较的
+    /// 静态成员 `Config::MAX`（也可以是范围的起点）
+    fn parse_static_pattern(&mut self) -> Result<super::ast::MatchPattern, ParseError> {
+        let start_span = self.current_span();
+        let enum_name = self.expect_identifier()?;
+        self.expect(&TokenKind::ColonColon)?;
+        let variant = self.expect_identifier()?;
+        
+        if !self.check(&TokenKind::LeftParen) {
+            let end_span = self.previous_span();
+            let start_expr = Expr::StaticMember {
+                class_name: enum_name,
+                member: variant,
+                span: Span::new(start_span.start, end_span.end, start_span.line, start_span.column),
+            };
+            return self.parse_range_pattern(start_expr);
+        }
+        
+        self.advance(); // 消费 '('
+        let mut fields = Vec::new();
+        while !self.check(&TokenKind::RightParen) {
+            let field_span = self.current_span();
+            let field = self.parse_single_match_pattern()?;
+            if !matches!(
+                field,
+                super::ast::MatchPattern::Variable(_) | super::ast::MatchPattern::Wildcard | super::ast::MatchPattern::Literal(_)
+            ) {
+                let msg = "enum payload patterns can only be a name, `_` or a literal".to_string();
+                return Err(ParseError::new(msg, field_span));
+            }
+            fields.push(field);
+            if !self.check(&TokenKind::Comma) {
+                break;
+            }
+            self.advance(); // 消费 ','
+        }
+        self.expect(&TokenKind::RightParen)?;
+        
+        Ok(super::ast::MatchPattern::EnumVariant { enum_name, variant, fields })
+    }
+    
+    /// 已经解析了模式开头的表达式，后面有 `..` / `..=` 时是范围模式
+    fn parse_range_pattern(&mut self, start_expr: Expr) -> Result<super::ast::MatchPattern, ParseError> {
+        // 检查是否是范围模式（在中缀处理之前）
+        if self.check(&TokenKind::DotDot) || self.check(&TokenKind::DotDotEqual) {
+            let inclusive = self.check(&TokenKind::DotDotEqual);
+            self.advance();
+            // 解析范围的结束值
+            let end_expr = self.parse_prefix()?;
+            return Ok(super::ast::MatchPattern::Range {
+                start: Box::new(start_expr),
+                end: Box::new(end_expr),
+                inclusive,
+            });
+        }
+        
+        Ok(super::ast::MatchPattern::Literal(start_expr))
+    }
+    
     /// 解析 break 语句
     fn parse_break_statement(&mut self) -> Result<Stmt, ParseError> {
         let start_span = self.current_span();
@@ -1872,7 +2285,7 @@ impl Parser {
         
         // 可选的标签
         let label = if let TokenKind::Identifier(name) = &self.current_token().kind.clone() {
-            let name = name.clone();
+            let name = name.to_string();
             self.advance();
             Some(name)
         } else {
@@ -1897,7 +2310,7 @@ impl Parser {
         
         // 可选的标签
         let label = if let TokenKind::Identifier(name) = &self.current_token().kind.clone() {
-            let name = name.clone();
+            let name = name.to_string();
             self.advance();
             Some(name)
         } else {
@@ -1980,7 +2393,27 @@ impl Parser {
                 // 函数类型: func(int, string) bool
                 return self.parse_function_type();
             }
-            TokenKind::Identifier(name) => Type::Class(name.clone()),
+            TokenKind::Map => {
+                // Map 类型: map[string]int
+                self.expect(&TokenKind::LeftBracket)?;
+                let key_type = self.parse_type()?;
+                self.expect(&TokenKind::RightBracket)?;
+                let value_type = self.parse_type()?;
+                Type::Map {
+                    key_type: Box::new(key_type),
+                    value_type: Box::new(value_type),
+                }
+            }
+            TokenKind::Chan => {
+                // 通道类型: chan<int>
+                self.expect(&TokenKind::Less)?;
+                let element_type = self.parse_type()?;
+                self.expect(&TokenKind::Greater)?;
+                Type::Channel { element_type: Box::new(element_type) }
+            }
+            // `any` 只在类型位置有意义（`arr.any()` 仍是方法名），与省略注解的字段同为 unknown
+            TokenKind::Identifier(name) if *name == "any" => Type::Unknown,
+            TokenKind::Identifier(name) => Type::Class(name.to_string()),
             _ => {
                 let msg = format_message(
                     messages::ERR_COMPILE_EXPECTED_TYPE,
@@ -2005,14 +2438,15 @@ impl Parser {
             base_type
         };
         
-        // 检查是否是固定数组类型 int[10] 或动态切片 int[]
-        let result_type = if self.check(&TokenKind::LeftBracket) {
+        // 检查是否是固定数组类型 int[10] 或动态切片 int[]，可嵌套（int[][]）
+        let mut result_type = typed;
+        while self.check(&TokenKind::LeftBracket) {
             self.advance(); // 消费 '['
             
-            if self.check(&TokenKind::RightBracket) {
+            result_type = if self.check(&TokenKind::RightBracket) {
                 // int[] - 动态切片
                 self.advance(); // 消费 ']'
-                Type::Slice { element_type: Box::new(typed) }
+                Type::Slice { element_type: Box::new(result_type) }
             } else {
                 // int[10] - 固定数组
                 let size = match &self.current_token().kind {
@@ -2025,6 +2459,16 @@ impl Parser {
                         }
                         *n as usize
                     }
+                    // 在前面声明的整数常量：int[SIZE]
+                    TokenKind::Identifier(name) => match self.consts.get(*name) {
+                        Some(ConstValue::Int(n)) if *n > 0 => *n as usize,
+                        _ => {
+                            return Err(ParseError::new(
+                                format!("Array size '{}' must be a positive integer constant declared earlier", name),
+                                self.current_span(),
+                            ));
+                        }
+                    },
                     _ => {
                         return Err(ParseError::new(
                             "Expected array size (positive integer)".to_string(),
@@ -2034,12 +2478,10 @@ impl Parser {
                 };
                 self.advance(); // 消费数字
                 self.expect(&TokenKind::RightBracket)?;
-                Type::Array { element_type: Box::new(typed), size }
-            }
-        } else {
-            typed
-        };
-        
+                Type::Array { element_type: Box::new(result_type), size }
+            };
+        }
+        
         // 检查是否是可空类型
         if self.check(&TokenKind::Question) {
             self.advance();
@@ -2136,9 +2578,23 @@ impl Parser {
     }
     
     /// 期望一个标识符
+    /// 变量、参数或循环变量的位置是丢弃绑定 `_` 时消费它
+    fn match_discard(&mut self) -> bool {
+        let is_discard = self.check(&TokenKind::Underscore);
+        if is_discard {
+            self.advance();
+        }
+        is_discard
+    }
+
+    /// `_` 出现在表达式中
+    fn discard_read_error(span: Span) -> ParseError {
+        ParseError::new(format!("cannot read the discard binding '{}'", DISCARD), span)
+    }
+
     fn expect_identifier(&mut self) -> Result<String, ParseError> {
         if let TokenKind::Identifier(name) = &self.current_token().kind.clone() {
-            let name = name.clone();
+            let name = name.to_string();
             self.advance();
             Ok(name)
         } else {
@@ -2155,6 +2611,9 @@ impl Parser {
     fn parse_expression_statement(&mut self) -> Result<Stmt, ParseError> {
         let start_span = self.current_span();
         let expr = self.parse_expression()?;
+        if self.check(&TokenKind::Comma) && Self::is_assign_target(&expr) {
+            return self.parse_parallel_assign(expr, start_span);
+        }
         
         // 可选的换行或分号
         if self.check(&TokenKind::Newline) || self.check(&TokenKind::Semicolon) {
@@ -2167,6 +2626,43 @@ impl Parser {
         Ok(Stmt::Expression { expr, span })
     }
 
+    /// 解析并行赋值 `a, b = b, a` 的其余部分（第一个目标已经解析）
+    ///
+    /// 目标和右值的个数必须相同
+    fn parse_parallel_assign(&mut self, first: Expr, start_span: Span) -> Result<Stmt, ParseError> {
+        let mut targets = vec![first];
+        while self.check(&TokenKind::Comma) {
+            self.advance();
+            let target = self.parse_conditional()?;
+            if !Self::is_assign_target(&target) {
+                return Err(ParseError::new("Invalid assignment target".to_string(), target.span()));
+            }
+            targets.push(target);
+        }
+        let equal = self.expect(&TokenKind::Equal)?;
+        let mut values = vec![self.parse_conditional()?];
+        while self.check(&TokenKind::Comma) {
+            self.advance();
+            values.push(self.parse_conditional()?);
+        }
+        if values.len() != targets.len() {
+            let msg = format!("assignment to {} targets needs {} values, got {}", targets.len(), targets.len(), values.len());
+            return Err(ParseError::new(msg, equal.span));
+        }
+
+        if self.check(&TokenKind::Newline) || self.check(&TokenKind::Semicolon) {
+            self.advance();
+        }
+        let end_span = self.previous_span();
+        let span = Span::new(start_span.start, end_span.end, start_span.line, start_span.column);
+        Ok(Stmt::ParallelAssign { targets, values, span })
+    }
+
+    /// 表达式能否作为赋值目标（变量、成员、下标或静态字段）
+    fn is_assign_target(expr: &Expr) -> bool {
+        matches!(expr, Expr::Identifier { .. } | Expr::Member { .. } | Expr::Index { .. } | Expr::StaticMember { .. })
+    }
+
     /// 解析表达式
     fn parse_expression(&mut self) -> Result<Expr, ParseError> {
         self.parse_assignment()
@@ -2174,7 +2670,7 @@ impl Parser {
     
     /// 解析赋值表达式
     fn parse_assignment(&mut self) -> Result<Expr, ParseError> {
-        let expr = self.parse_precedence(Precedence::Or)?;
+        let expr = self.parse_conditional()?;
         
         // 检查是否有赋值运算符
         let assign_op = if self.check(&TokenKind::Equal) {
@@ -2246,6 +2742,16 @@ impl Parser {
                         span: Span::new(span.start, end_span.end, span.line, span.column),
                     });
                 }
+                Expr::StaticMember { span, .. } => {
+                    // 支持静态字段作为赋值目标 (e.g., Counter::total = value)
+                    let end_span = value.span();
+                    return Ok(Expr::Assign {
+                        target: Box::new(expr.clone()),
+                        op,
+                        value: Box::new(value),
+                        span: Span::new(span.start, end_span.end, span.line, span.column),
+                    });
+                }
                 _ => {
                     let msg = "Invalid assignment target".to_string();
                     return Err(ParseError::new(msg, op_token.span));
@@ -2256,14 +2762,39 @@ impl Parser {
         Ok(expr)
     }
 
+    /// 解析条件表达式 `cond ? a : b`
+    ///
+    /// 优先级低于 `??` 和 `||`，高于赋值；右结合，`a ? b : c ? d : e` 即 `a ? b : (c ? d : e)`
+    fn parse_conditional(&mut self) -> Result<Expr, ParseError> {
+        let condition = self.parse_precedence(Precedence::Or)?;
+        if !self.check(&TokenKind::Question) {
+            return Ok(condition);
+        }
+        self.advance(); // 消费 '?'
+        let then_branch = self.parse_conditional()?;
+        self.expect(&TokenKind::Colon)?;
+        let else_branch = self.parse_conditional()?;
+        
+        let start = condition.span();
+        let end = else_branch.span();
+        Ok(Expr::IfExpr {
+            condition: Box::new(condition),
+            then_branch: Box::new(then_branch),
+            else_branch: Box::new(else_branch),
+            span: Span::new(start.start, end.end, start.line, start.column),
+        })
+    }
+    
     /// 按优先级解析表达式
     fn parse_precedence(&mut self, precedence: Precedence) -> Result<Expr, ParseError> {
         // 解析前缀表达式
         let mut left = self.parse_prefix()?;
+        self.node_count += 1;
         
         // 循环解析中缀表达式
         while precedence <= self.current_precedence() {
             left = self.parse_infix(left)?;
+            self.node_count += 1;
         }
         
         Ok(left)
@@ -2283,19 +2814,13 @@ impl Parser {
                 value: *n,
                 span: token.span,
             }),
-            TokenKind::String(s) => {
-                // 检查是否包含字符串插值 ${...}
-                if s.contains("${") {
-                    self.parse_string_interpolation(s.clone(), token.span)
-                } else {
-                    Ok(Expr::String {
-                        value: s.clone(),
-                        span: token.span,
-                    })
-                }
-            }
+            TokenKind::String(s) => Ok(Expr::String {
+                value: s.to_string(),
+                span: token.span,
+            }),
+            TokenKind::InterpolatedString(parts) => self.parse_string_interpolation(parts, token.span),
             TokenKind::RawString(s) => Ok(Expr::String {
-                value: s.clone(),
+                value: s.to_string(),
                 span: token.span,
             }),
             TokenKind::True => Ok(Expr::Bool {
@@ -2314,16 +2839,16 @@ impl Parser {
             TokenKind::Identifier(name) => {
                 if self.check(&TokenKind::ColonColon) {
                     // 静态访问: ClassName::member 或 ClassName::method()
-                    self.parse_static_access(name.clone(), token.span)
+                    self.parse_static_access(name.to_string(), token.span)
                 } else if self.check(&TokenKind::LeftParen) {
                     // 函数调用
-                    self.parse_call(name.clone(), token.span)
-                } else if self.check(&TokenKind::LeftBrace) {
+                    self.parse_call(name.to_string(), token.span)
+                } else if self.check(&TokenKind::LeftBrace) && !self.no_struct_literal {
                     // struct 字面量: Point { x: 1, y: 2 }
-                    self.parse_struct_literal(name.clone(), token.span)
+                    self.parse_struct_literal(name.to_string(), token.span)
                 } else {
                     Ok(Expr::Identifier {
-                        name: name.clone(),
+                        name: name.to_string(),
                         span: token.span,
                     })
                 }
@@ -2400,6 +2925,27 @@ impl Parser {
                 })
             }
             
+            // 创建通道 chan<int>() / chan<int>(16)
+            TokenKind::Chan => {
+                let start_span = token.span;
+                self.expect(&TokenKind::Less)?;
+                let element_type = self.parse_type()?;
+                self.expect(&TokenKind::Greater)?;
+                self.expect(&TokenKind::LeftParen)?;
+                let capacity = if self.check(&TokenKind::RightParen) {
+                    None
+                } else {
+                    Some(Box::new(self.parse_expression()?))
+                };
+                self.expect(&TokenKind::RightParen)?;
+                let end_span = self.previous_span();
+                Ok(Expr::ChannelNew {
+                    element_type,
+                    capacity,
+                    span: Span::new(start_span.start, end_span.end, start_span.line, start_span.column),
+                })
+            }
+            
             // 数组字面量 [1, 2, 3]
             TokenKind::LeftBracket => {
                 let start_span = token.span;
@@ -2472,12 +3018,13 @@ impl Parser {
                 })
             }
             
-            // typeof/sizeof/make 内置函数（作为关键字处理）
-            TokenKind::Typeof | TokenKind::Sizeof | TokenKind::Make => {
+            // typeof/sizeof/make/panic 内置函数（作为关键字处理）
+            TokenKind::Typeof | TokenKind::Sizeof | TokenKind::Make | TokenKind::Panic => {
                 let func_name = match &token.kind {
                     TokenKind::Typeof => "typeof",
                     TokenKind::Sizeof => "sizeof",
                     TokenKind::Make => "make",
+                    TokenKind::Panic => "panic",
                     _ => unreachable!(),
                 };
                 self.parse_call(func_name.to_string(), token.span)
@@ -2487,6 +3034,16 @@ impl Parser {
             TokenKind::New => {
                 let start_span = token.span;
                 let class_name = self.expect_identifier()?;
+                if self.check(&TokenKind::LeftBrace) {
+                    let fields = self.parse_field_initializers()?;
+                    let end_span = self.previous_span();
+                    return Ok(Expr::New {
+                        class_name,
+                        args: Vec::new(),
+                        fields: Some(fields),
+                        span: Span::new(start_span.start, end_span.end, start_span.line, start_span.column),
+                    });
+                }
                 self.expect(&TokenKind::LeftParen)?;
                 
                 let mut args = Vec::new();
@@ -2503,10 +3060,12 @@ impl Parser {
                 Ok(Expr::New {
                     class_name,
                     args,
+                    fields: None,
                     span: Span::new(start_span.start, end_span.end, start_span.line, start_span.column),
                 })
             }
             
+            TokenKind::Underscore => Err(Self::discard_read_error(token.span)),
             _ => {
                 let msg = format_message(
                     messages::ERR_COMPILE_EXPECTED_EXPRESSION,
@@ -2698,50 +3257,7 @@ impl Parser {
             
             // 函数调用 func(args) - 支持命名参数
             TokenKind::LeftParen => {
-                let mut args: Vec<(Option<String>, Expr)> = Vec::new();
-                let mut seen_named = false;
-                
-                if !self.check(&TokenKind::RightParen) {
-                    loop {
-                        // 检查是否是命名参数
-                        let (param_name, value) = if let TokenKind::Identifier(name) = &self.current_token().kind {
-                            let name = name.clone();
-                            let save_pos = self.current;
-                            self.advance();
-                            
-                            if self.check(&TokenKind::Colon) {
-                                self.advance();
-                                seen_named = true;
-                                let value = self.parse_expression()?;
-                                (Some(name), value)
-                            } else {
-                                self.current = save_pos;
-                                if seen_named {
-                                    return Err(ParseError::new(
-                                        "Positional arguments cannot follow named arguments".to_string(),
-                                        self.current_span(),
-                                    ));
-                                }
-                                (None, self.parse_expression()?)
-                            }
-                        } else {
-                            if seen_named {
-                                return Err(ParseError::new(
-                                    "Positional arguments cannot follow named arguments".to_string(),
-                                    self.current_span(),
-                                ));
-                            }
-                            (None, self.parse_expression()?)
-                        };
-                        
-                        args.push((param_name, value));
-                        
-                        if !self.check(&TokenKind::Comma) {
-                            break;
-                        }
-                        self.advance();
-                    }
-                }
+                let args = self.parse_call_args()?;
                 self.expect(&TokenKind::RightParen)?;
                 
                 let end_span = self.previous_span();
@@ -2804,7 +3320,7 @@ impl Parser {
                 let msg = format_message(
                     messages::ERR_COMPILE_UNEXPECTED_TOKEN,
                     self.locale,
-                    &[&token.lexeme],
+                    &[token.lexeme],
                 );
                 return Err(ParseError::new(msg, token.span));
             }
@@ -2907,58 +3423,7 @@ impl Parser {
         });
         
         self.advance(); // 消费 '('
-        
-        let mut args: Vec<(Option<String>, Expr)> = Vec::new();
-        let mut seen_named = false; // 是否已经遇到命名参数
-        
-        if !self.check(&TokenKind::RightParen) {
-            loop {
-                // 检查是否是命名参数: name: value
-                let (param_name, value) = if let TokenKind::Identifier(name) = &self.current_token().kind {
-                    let name = name.clone();
-                    let save_pos = self.current;
-                    self.advance(); // 消费标识符
-                    
-                    if self.check(&TokenKind::Colon) {
-                        // 是命名参数
-                        self.advance(); // 消费 ':'
-                        seen_named = true;
-                        let value = self.parse_expression()?;
-                        (Some(name), value)
-                    } else {
-                        // 不是命名参数，回退
-                        self.current = save_pos;
-                        
-                        if seen_named {
-                            return Err(ParseError::new(
-                                "Positional arguments cannot follow named arguments".to_string(),
-                                self.current_span(),
-                            ));
-                        }
-                        
-                        let value = self.parse_expression()?;
-                        (None, value)
-                    }
-                } else {
-                    if seen_named {
-                        return Err(ParseError::new(
-                            "Positional arguments cannot follow named arguments".to_string(),
-                            self.current_span(),
-                        ));
-                    }
-                    
-                    let value = self.parse_expression()?;
-                    (None, value)
-                };
-                
-                args.push((param_name, value));
-                
-                if !self.check(&TokenKind::Comma) {
-                    break;
-                }
-                self.advance(); // 消费 ','
-            }
-        }
+        let args = self.parse_call_args()?;
         
         self.expect(&TokenKind::RightParen)?;
         let end_span = self.previous_span();
@@ -2970,8 +3435,56 @@ impl Parser {
         })
     }
     
+    /// 解析调用的参数列表（不含括号）
+    ///
+    /// `name: value` 是命名参数：参数列表里不会出现标签，`标识符 :` 只看两个 token 就能确定，
+    /// 不需要回溯
+    fn parse_call_args(&mut self) -> Result<Vec<(Option<String>, Expr)>, ParseError> {
+        let mut args: Vec<(Option<String>, Expr)> = Vec::new();
+        let mut seen_named = false; // 是否已经遇到命名参数
+        
+        if self.check(&TokenKind::RightParen) {
+            return Ok(args);
+        }
+        loop {
+            let param_name = if matches!(self.peek(0), TokenKind::Identifier(_)) && self.peek(1) == &TokenKind::Colon {
+                let name = self.expect_identifier()?;
+                self.advance(); // 消费 ':'
+                seen_named = true;
+                Some(name)
+            } else if seen_named {
+                return Err(ParseError::new(
+                    "Positional arguments cannot follow named arguments".to_string(),
+                    self.current_span(),
+                ));
+            } else {
+                None
+            };
+            let value = self.parse_expression()?;
+            args.push((param_name, value));
+            
+            if !self.check(&TokenKind::Comma) {
+                break;
+            }
+            self.advance(); // 消费 ','
+        }
+        Ok(args)
+    }
+    
     /// 解析 struct 字面量: Point { x: 1, y: 2 }
     fn parse_struct_literal(&mut self, name: String, start_span: Span) -> Result<Expr, ParseError> {
+        let fields = self.parse_field_initializers()?;
+        let end_span = self.previous_span();
+        
+        Ok(Expr::StructLiteral {
+            name,
+            fields,
+            span: Span::new(start_span.start, end_span.end, start_span.line, start_span.column),
+        })
+    }
+    
+    /// 解析花括号中的字段赋值 `{ x: 1, y: 2 }`（struct 字面量和 `new User { ... }`）
+    fn parse_field_initializers(&mut self) -> Result<Vec<(String, Expr)>, ParseError> {
         self.advance(); // 消费 '{'
         
         let mut fields = Vec::new();
@@ -3016,13 +3529,7 @@ impl Parser {
         }
         
         self.expect(&TokenKind::RightBrace)?;
-        let end_span = self.previous_span();
-        
-        Ok(Expr::StructLiteral {
-            name,
-            fields,
-            span: Span::new(start_span.start, end_span.end, start_span.line, start_span.column),
-        })
+        Ok(fields)
     }
 
     /// 解析命名函数定义
@@ -3055,7 +3562,57 @@ impl Parser {
         let end_span = self.previous_span();
         let span = Span::new(start_span.start, end_span.end, start_span.line, start_span.column);
         
-        Ok(Stmt::FnDef { name, type_params, where_clauses: Vec::new(), params, return_type, body, visibility, span })
+        Ok(Stmt::FnDef { name, type_params, where_clauses: Vec::new(), params, return_type, body, visibility, attributes: Vec::new(), span })
+    }
+    
+    /// 解析函数或方法之前的属性：`@no_optimize`、`@inline(never)`、`@inline(always)`，每个属性之后可以换行
+    fn parse_attributes(&mut self) -> Result<Vec<super::ast::Attribute>, ParseError> {
+        use super::ast::{Attribute, AttributeKind};
+        
+        let mut attributes: Vec<Attribute> = Vec::new();
+        while self.check(&TokenKind::At) {
+            let start_span = self.current_span();
+            self.advance(); // 消费 '@'
+            let name = self.expect_identifier()?;
+            let arg = if self.check(&TokenKind::LeftParen) {
+                self.advance();
+                let arg = self.expect_identifier()?;
+                self.expect(&TokenKind::RightParen)?;
+                Some(arg)
+            } else {
+                None
+            };
+            let end_span = self.previous_span();
+            let span = Span::new(start_span.start, end_span.end, start_span.line, start_span.column);
+            
+            let kind = match (name.as_str(), arg.as_deref()) {
+                ("no_optimize", None) => AttributeKind::NoOptimize,
+                ("inline", Some("never")) => AttributeKind::InlineNever,
+                ("inline", Some("always")) => AttributeKind::InlineAlways,
+                ("no_optimize", Some(_)) => {
+                    return Err(ParseError::new("attribute '@no_optimize' takes no arguments".to_string(), span));
+                }
+                ("inline", _) => {
+                    return Err(ParseError::new("attribute '@inline' expects 'never' or 'always': @inline(never)".to_string(), span));
+                }
+                _ => return Err(ParseError::new(format!("unknown attribute '@{}'", name), span)),
+            };
+            let is_inline = |kind: AttributeKind| matches!(kind, AttributeKind::InlineNever | AttributeKind::InlineAlways);
+            if attributes.iter().any(|a| a.kind == kind || (is_inline(a.kind) && is_inline(kind))) {
+                return Err(ParseError::new(format!("duplicate attribute '@{}'", name), span));
+            }
+            attributes.push(Attribute { kind, span });
+            
+            while self.check(&TokenKind::Newline) {
+                self.advance();
+            }
+        }
+        Ok(attributes)
+    }
+    
+    fn misplaced_attribute(attribute: &super::ast::Attribute) -> ParseError {
+        let msg = format!("attribute '@{}' can only be applied to functions and methods", attribute.kind.name());
+        ParseError::new(msg, attribute.span)
     }
     
     /// 解析闭包表达式
@@ -3139,14 +3696,23 @@ impl Parser {
                 }
             }
             
-            // 参数名
-            let name = self.expect_identifier()?;
+            // 参数名，`_` 忽略对应的实参
+            let name_span = self.current_span();
+            let name = if self.match_discard() { DISCARD.to_string() } else { self.expect_identifier()? };
+            if is_field && name == DISCARD {
+                let msg = "the discard binding '_' cannot be promoted to a field".to_string();
+                return Err(ParseError::new(msg, name_span));
+            }
             
-            // 冒号和类型
-            self.expect(&TokenKind::Colon)?;
+            // 冒号和类型；`_` 可以省略类型，接受任何值
+            let type_ann = if name == DISCARD && !self.check(&TokenKind::Colon) {
+                TypeAnnotation { ty: Type::Unknown, span: name_span }
+            } else {
+                self.expect(&TokenKind::Colon)?;
+                self.parse_type_annotation()?
+            };
             
             // 检查是否是可变参数 name:int...
-            let type_ann = self.parse_type_annotation()?;
             let variadic = if self.check(&TokenKind::DotDotDot) {
                 self.advance();
                 true
@@ -3243,11 +3809,11 @@ impl Parser {
         if self.is_at_end() {
             return false;
         }
-        matches!(&self.current_token().kind, TokenKind::Identifier(n) if n == name)
+        matches!(&self.current_token().kind, TokenKind::Identifier(n) if *n == name)
     }
 
     /// 前进一个 token 并返回之前的 token
-    fn advance(&mut self) -> Token {
+    fn advance(&mut self) -> Token<'a> {
         if !self.is_at_end() {
             self.current += 1;
         }
@@ -3255,14 +3821,14 @@ impl Parser {
     }
 
     /// 期望指定类型的 token
-    fn expect(&mut self, kind: &TokenKind) -> Result<Token, ParseError> {
+    fn expect(&mut self, kind: &TokenKind) -> Result<Token<'a>, ParseError> {
         if self.check(kind) {
             Ok(self.advance())
         } else {
             let msg = format_message(
                 messages::ERR_COMPILE_EXPECTED_TOKEN,
                 self.locale,
-                &[&format!("{}", kind), &self.current_token().lexeme],
+                &[&format!("{}", kind), self.current_token().lexeme],
             );
             Err(ParseError::new(msg, self.current_span()))
         }
@@ -3274,84 +3840,94 @@ impl Parser {
     }
 
     /// 获取当前 token
-    fn current_token(&self) -> &Token {
+    fn current_token(&self) -> &Token<'a> {
         &self.tokens[self.current.min(self.tokens.len() - 1)]
     }
     
-    /// 获取下一个 token（peek）
-    fn peek_token(&self) -> Option<&Token> {
-        if self.current + 1 < self.tokens.len() {
-            Some(&self.tokens[self.current + 1])
-        } else {
-            None
+    /// 向前查看第 n 个 token 的类型（0 为当前 token），越过末尾时返回 EOF
+    ///
+    /// n 不能超过 [`MAX_LOOKAHEAD`]：需要看得更远的结构改用 [`Parser::speculate`]
+    fn peek(&self, n: usize) -> &TokenKind<'a> {
+        debug_assert!(n <= MAX_LOOKAHEAD, "lookahead {} exceeds MAX_LOOKAHEAD", n);
+        match self.tokens.get(self.current + n) {
+            Some(token) => &token.kind,
+            None => &self.tokens[self.tokens.len() - 1].kind,
+        }
+    }
+
+    /// 试探解析：成功时保留结果和已消费的 token，失败时回到开始的位置并丢弃期间的错误
+    ///
+    /// 只保存 token 下标，不复制 token 序列
+    fn speculate<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, ParseError>) -> Option<T> {
+        let (current, errors, panic_mode, no_struct_literal) =
+            (self.current, self.errors.len(), self.panic_mode, self.no_struct_literal);
+        match parse(self) {
+            Ok(value) => Some(value),
+            Err(_) => {
+                self.current = current;
+                self.errors.truncate(errors);
+                self.panic_mode = panic_mode;
+                self.no_struct_literal = no_struct_literal;
+                None
+            }
         }
     }
 
     /// 获取前一个 token
     /// 解析字符串插值 "Hello, ${name}!"
-    fn parse_string_interpolation(&self, s: String, span: Span) -> Result<Expr, ParseError> {
+    /// 
+    /// 扫描器已将字符串切分为文本和表达式源码，这里逐个解析表达式
+    fn parse_string_interpolation(&self, string_parts: &[StringPart], span: Span) -> Result<Expr, ParseError> {
         use super::ast::StringInterpPart;
         
-        let mut parts = Vec::new();
-        let mut current = String::new();
-        let mut chars = s.chars().peekable();
-        
-        while let Some(c) = chars.next() {
-            if c == '$' && chars.peek() == Some(&'{') {
-                // 保存之前的字符串部分
-                if !current.is_empty() {
-                    parts.push(StringInterpPart::Literal(current.clone()));
-                    current.clear();
-                }
-                
-                // 跳过 '{'
-                chars.next();
-                
-                // 收集表达式字符串（直到找到匹配的 '}'）
-                let mut expr_str = String::new();
-                let mut brace_depth = 1;
-                
-                while let Some(ec) = chars.next() {
-                    if ec == '{' {
-                        brace_depth += 1;
-                        expr_str.push(ec);
-                    } else if ec == '}' {
-                        brace_depth -= 1;
-                        if brace_depth == 0 {
-                            break;
-                        }
-                        expr_str.push(ec);
-                    } else {
-                        expr_str.push(ec);
+        let mut parts = Vec::with_capacity(string_parts.len());
+        
+        for part in string_parts {
+            match part {
+                StringPart::Literal(s) => parts.push(StringInterpPart::Literal(s.clone())),
+                StringPart::Expr(source) => {
+                    if source.trim().is_empty() {
+                        return Err(ParseError::new(
+                            "Empty expression in string interpolation".to_string(),
+                            span,
+                        ));
                     }
-                }
-                
-                // 解析表达式
-                let mut scanner = crate::lexer::Scanner::new(&expr_str);
-                let tokens = scanner.scan_tokens();
-                let mut parser = Parser::new(tokens, self.locale.clone());
-                
-                match parser.parse_expression() {
-                    Ok(expr) => parts.push(StringInterpPart::Expr(expr)),
-                    Err(e) => return Err(ParseError::new(
+                    
+                    let mut scanner = crate::lexer::Scanner::new(source);
+                    let tokens = scanner.scan_tokens();
+                    let mut parser = Parser::new(tokens, self.locale);
+                    while parser.check(&TokenKind::Newline) {
+                        parser.advance();
+                    }
+                    
+                    let expr = parser.parse_expression().map_err(|e| ParseError::new(
                         format!("Error in string interpolation: {}", e.message),
                         span,
-                    )),
+                    ))?;
+                    
+                    // 插值内只能有一个表达式
+                    while parser.check(&TokenKind::Newline) {
+                        parser.advance();
+                    }
+                    if !parser.is_at_end() {
+                        return Err(ParseError::new(
+                            format!(
+                                "Error in string interpolation: unexpected '{}' after expression",
+                                parser.current_token().kind
+                            ),
+                            span,
+                        ));
+                    }
+                    
+                    parts.push(StringInterpPart::Expr(expr));
                 }
-            } else {
-                current.push(c);
             }
         }
         
-        // 添加剩余的字符串部分
-        if !current.is_empty() {
-            parts.push(StringInterpPart::Literal(current));
-        }
-        
         Ok(Expr::StringInterpolation { parts, span })
     }
 
-    fn previous_token(&self) -> &Token {
+    fn previous_token(&self) -> &Token<'a> {
         &self.tokens[(self.current - 1).max(0)]
     }
 
@@ -3415,6 +3991,29 @@ impl Parser {
         self.panic_mode = false;
     }
     
+    /// 块中的错误恢复：跳过出错语句的剩余部分，停在下一行的开头、语句关键字或块的 `}` 上
+    ///
+    /// 出错语句中未闭合的 `{` 连同对应的 `}` 一起跳过，不会越过所在的块
+    fn synchronize_in_block(&mut self) {
+        let mut depth = 0usize;
+        while !self.is_at_end() {
+            match self.current_token().kind {
+                TokenKind::Var | TokenKind::Const | TokenKind::If | TokenKind::For |
+                TokenKind::Match | TokenKind::Return | TokenKind::Break |
+                TokenKind::Continue | TokenKind::Throw | TokenKind::Try if depth == 0 => return,
+                TokenKind::LeftBrace => depth += 1,
+                TokenKind::RightBrace if depth == 0 => return,
+                TokenKind::RightBrace => depth -= 1,
+                TokenKind::Newline | TokenKind::Semicolon if depth == 0 => {
+                    self.advance();
+                    return;
+                }
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+    
     /// 在错误恢复模式下跳过到指定 token
     fn synchronize_to(&mut self, kinds: &[TokenKind]) {
         self.panic_mode = true;
@@ -3439,7 +4038,7 @@ impl Parser {
     }
     
     /// 期望其中一个 token
-    fn expect_one_of(&mut self, kinds: &[TokenKind]) -> Result<Token, ParseError> {
+    fn expect_one_of(&mut self, kinds: &[TokenKind]) -> Result<Token<'a>, ParseError> {
         for kind in kinds {
             if self.check(kind) {
                 return Ok(self.advance());
@@ -3447,12 +4046,12 @@ impl Parser {
         }
         
         let expected: Vec<String> = kinds.iter().map(|k| format!("{}", k)).collect();
-        let found = self.current_token().lexeme.clone();
+        let found = self.current_token().lexeme;
         let span = self.current_span();
         
         Err(ParseError::expected_token(
             &expected.join(" or "),
-            &found,
+            found,
             span,
         ))
     }
@@ -3516,6 +4115,42 @@ impl Parser {
         })
     }
     
+    /// 解析 recovering-onPanic 语句
+    fn parse_recovering_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start_span = self.current_span();
+        self.advance(); // 消费 'recovering'
+        
+        let body = self.parse_block()?;
+        
+        if !self.check_identifier("onPanic") {
+            let msg = "Expected 'onPanic' after recovering block".to_string();
+            return Err(ParseError::new(msg, self.current_span()));
+        }
+        self.advance(); // 消费 'onPanic'
+        
+        // 可选的参数名 (e)，panic 的值可以是任何类型，不写类型注解
+        let panic_param = if self.check(&TokenKind::LeftParen) {
+            self.advance(); // 消费 '('
+            let param = self.expect_identifier()?;
+            self.expect(&TokenKind::RightParen)?;
+            Some(param)
+        } else {
+            None
+        };
+        
+        let handler = self.parse_block()?;
+        
+        let end_span = self.previous_span();
+        let span = Span::new(start_span.start, end_span.end, start_span.line, start_span.column);
+        
+        Ok(Stmt::Recovering {
+            body: Box::new(body),
+            panic_param,
+            handler: Box::new(handler),
+            span,
+        })
+    }
+    
     /// 解析 throw 语句
     fn parse_throw_statement(&mut self) -> Result<Stmt, ParseError> {
         let start_span = self.current_span();
@@ -3614,6 +4249,46 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_parse_conditional() {
+        let expr_of = |source: &str| match parse(source).unwrap().statements.remove(0) {
+            Stmt::Expression { expr, .. } => expr,
+            other => panic!("Expected expression, got {:?}", other),
+        };
+        
+        // 右结合：a ? b : (c ? d : e)
+        let Expr::IfExpr { else_branch, .. } = expr_of("a ? b : c ? d : e") else {
+            panic!("Expected conditional");
+        };
+        assert!(matches!(*else_branch, Expr::IfExpr { .. }));
+        
+        // 优先级低于 ?? 和 ||：(x ?? y) ? 1 : 2
+        let Expr::IfExpr { condition, .. } = expr_of("x ?? y || z ? 1 : 2") else {
+            panic!("Expected conditional");
+        };
+        assert!(matches!(*condition, Expr::NullCoalesce { .. } | Expr::Binary { .. }));
+        
+        // 高于赋值：v = (c ? 1 : 2)
+        let Expr::Assign { value, .. } = expr_of("v = c ? 1 : 2") else {
+            panic!("Expected assignment");
+        };
+        assert!(matches!(*value, Expr::IfExpr { .. }));
+        
+        assert!(parse("c ? 1").is_err());
+    }
+    
+    #[test]
+    fn test_parse_final_method() {
+        let program = parse("class A {\n    final override func m() {}\n    override final func n() {}\n}").unwrap();
+        let Stmt::ClassDef { methods, .. } = &program.statements[0] else {
+            panic!("Expected class");
+        };
+        assert!(methods.iter().all(|m| m.is_final && m.is_override));
+        
+        assert!(parse("class A {\n    final static func m() {}\n}").is_err());
+        assert!(parse("class A {\n    final var x: int = 1\n}").is_err());
+    }
+    
     #[test]
     fn test_parse_if() {
         let program = parse("if x > 5 { print(x) }").unwrap();
@@ -3638,6 +4313,41 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_parse_string_interpolation() {
+        use super::super::ast::StringInterpPart;
+        
+        let program = parse(r#""n = ${a + "${b}"}!""#).unwrap();
+        if let Stmt::Expression { expr: Expr::StringInterpolation { parts, .. }, .. } = &program.statements[0] {
+            assert_eq!(parts.len(), 3);
+            assert!(matches!(&parts[0], StringInterpPart::Literal(s) if s == "n = "));
+            assert!(matches!(&parts[1], StringInterpPart::Expr(Expr::Binary { right, .. })
+                if matches!(right.as_ref(), Expr::StringInterpolation { .. })));
+        } else {
+            panic!("Expected StringInterpolation");
+        }
+        
+        assert!(parse(r#""${}""#).is_err());
+        assert!(parse(r#""${a b}""#).is_err());
+    }
+
+    #[test]
+    fn test_parse_collection_type_annotations() {
+        let program = parse("var m: map[string]int[] = {}\nvar g: f64[][] = []").unwrap();
+        if let Stmt::VarDecl { type_ann: Some(ann), .. } = &program.statements[0] {
+            assert_eq!(ann.ty.to_string(), "map[string]int[]");
+            assert!(matches!(&ann.ty, Type::Map { value_type, .. }
+                if matches!(value_type.as_ref(), Type::Slice { .. })));
+        } else {
+            panic!("Expected VarDecl with map type");
+        }
+        if let Stmt::VarDecl { type_ann: Some(ann), .. } = &program.statements[1] {
+            assert_eq!(ann.ty.to_string(), "f64[][]");
+        } else {
+            panic!("Expected VarDecl with nested slice type");
+        }
+    }
+
     #[test]
     fn test_parse_block() {
         let program = parse("{ var x = 1\nvar y = 2 }").unwrap();
@@ -3672,4 +4382,147 @@ mod tests {
             panic!("Expected VarDecl with Closure");
         }
     }
+    
+    #[test]
+    fn test_parse_select() {
+        use crate::parser::ast::SelectCaseKind;
+        
+        let source = "select {\n    case var v = a.receive() => println(v)\n    case b.send(1) => {}\n    case a.receive() => {}\n    default => {}\n}";
+        let program = parse(source).unwrap();
+        let Stmt::Select { cases, .. } = &program.statements[0] else {
+            panic!("Expected Select");
+        };
+        assert_eq!(cases.len(), 4);
+        assert!(matches!(&cases[0].kind, SelectCaseKind::Receive { binding: Some(name), .. } if name == "v"));
+        assert!(matches!(&cases[1].kind, SelectCaseKind::Send { value: Expr::Integer { value: 1, .. }, .. }));
+        assert!(matches!(&cases[2].kind, SelectCaseKind::Receive { binding: None, .. }));
+        assert_eq!(cases[3].kind, SelectCaseKind::Default);
+        
+        // 分支必须是通道操作，default 最多一个
+        assert!(parse("select {\n    case a.close() => {}\n}").is_err());
+        assert!(parse("select {\n    case var v = a.send(1) => {}\n}").is_err());
+        assert!(parse("select {\n    default => {}\n    default => {}\n}").is_err());
+    }
+
+    #[test]
+    fn test_parse_switch() {
+        let source = "switch x + 1 {\n    case 1, 2 => println(\"small\")\n    case 3 => {}\n    default => {}\n}";
+        let program = parse(source).unwrap();
+        let Stmt::Switch { expr, cases, default, .. } = &program.statements[0] else {
+            panic!("Expected Switch");
+        };
+        assert!(matches!(expr, Expr::Binary { .. }));
+        assert_eq!(cases.len(), 2);
+        assert_eq!(cases[0].values.len(), 2);
+        assert!(matches!(&cases[1].values[0], Expr::Integer { value: 3, .. }));
+        assert!(default.is_some());
+
+        // default 可以省略，但最多一个
+        assert!(parse("switch x {\n    case 1 => {}\n}").is_ok());
+        assert!(parse("switch x {\n    default => {}\n    default => {}\n}").is_err());
+        assert!(parse("switch x {\n    case => {}\n}").is_err());
+    }
+    
+    #[test]
+    fn test_parse_channel_capacity_and_for_in_identifier() {
+        let program = parse("var a = chan<int>()\nvar b = chan<int>(n + 1)").unwrap();
+        assert!(matches!(&program.statements[0], Stmt::VarDecl { initializer: Some(Expr::ChannelNew { capacity: None, .. }), .. }));
+        assert!(matches!(&program.statements[1], Stmt::VarDecl { initializer: Some(Expr::ChannelNew { capacity: Some(_), .. }), .. }));
+        
+        // 可迭代表达式后的 { 是循环体，不是 struct 字面量
+        let program = parse("for x in jobs {\n    total = total + x\n}").unwrap();
+        assert!(matches!(&program.statements[0], Stmt::ForIn { iterable: Expr::Identifier { .. }, .. }));
+        assert!(parse("var p = Point { x: 1 }").is_ok());
+    }
+    
+    #[test]
+    fn test_less_than_is_not_a_generic_call() {
+        // 表达式中没有显式泛型实参：a < b > (c) 是 (a < b) > (c)
+        let program = parse("var r = a < b > (c)").unwrap();
+        let Stmt::VarDecl { initializer: Some(Expr::Binary { left, op: BinOp::Gt, right, .. }), .. } = &program.statements[0] else {
+            panic!("Expected comparison chain");
+        };
+        assert!(matches!(left.as_ref(), Expr::Binary { op: BinOp::Lt, .. }));
+        assert!(matches!(right.as_ref(), Expr::Grouping { .. }));
+        // 类型位置的 < 仍然是类型实参
+        assert!(parse("var b: Box<int> = null").is_ok());
+    }
+    
+    #[test]
+    fn test_brace_in_arm_body() {
+        let source = "match k {\n    1 => {\"a\": 1}\n    2 => {}\n    3 => {\n        outer: for {\n            break outer\n        }\n    }\n    4 => {\n        x\n    }\n}";
+        let program = parse(source).unwrap();
+        let Stmt::Match { arms, .. } = &program.statements[0] else {
+            panic!("Expected Match");
+        };
+        assert!(matches!(arms[0].body.as_ref(), Stmt::Expression { expr: Expr::MapLiteral { .. }, .. }));
+        assert!(matches!(arms[1].body.as_ref(), Stmt::Block { statements, .. } if statements.is_empty()));
+        assert!(matches!(arms[2].body.as_ref(), Stmt::Block { statements, .. }
+            if matches!(&statements[0], Stmt::While { label: Some(l), .. } if l == "outer")));
+        assert!(matches!(arms[3].body.as_ref(), Stmt::Block { .. }));
+        // 试探失败不留下错误；真正的错误仍在原位置报告
+        let errors = parse("match k {\n    1 => {\"a\": }\n}").unwrap_err();
+        assert_eq!(errors[0].span.line, 2);
+        // 其他表达式位置的 { 总是 map 字面量
+        assert!(matches!(&parse("var m = {}").unwrap().statements[0], Stmt::VarDecl { initializer: Some(Expr::MapLiteral { .. }), .. }));
+    }
+    
+    #[test]
+    fn test_discard_bindings() {
+        let program = parse("var _ = f()\nfor _, v in m {}\nfunc g(_: int, _) {}").unwrap();
+        assert!(matches!(&program.statements[0], Stmt::VarDecl { name, .. } if name == DISCARD));
+        assert!(matches!(&program.statements[1], Stmt::ForIn { variables, .. } if variables[0] == DISCARD && variables[1] == "v"));
+        let Stmt::FnDef { params, .. } = &program.statements[2] else {
+            panic!("Expected FnDef");
+        };
+        // 没有类型的 `_` 参数接受任何值
+        assert_eq!(params[0].type_ann.ty, Type::Int);
+        assert_eq!(params[1].type_ann.ty, Type::Unknown);
+
+        let errors = parse("var x = _ + 1").unwrap_err();
+        assert_eq!(errors[0].message, "cannot read the discard binding '_'");
+        let errors = parse("class A {\n    func init(var _: int) {}\n}").unwrap_err();
+        assert!(errors[0].message.contains("cannot be promoted to a field"), "{}", errors[0].message);
+    }
+
+    #[test]
+    fn test_named_argument_vs_label() {
+        let errors = parse("f(outer: 1, 2 > 1 ? a : b)").unwrap_err();
+        assert!(errors[0].message.contains("Positional arguments cannot follow named arguments"));
+        let program = parse("f(outer: 1)\nouter: for {\n    break outer\n}").unwrap();
+        let Stmt::Expression { expr: Expr::Call { args, .. }, .. } = &program.statements[0] else {
+            panic!("Expected call");
+        };
+        assert_eq!(args[0].0.as_deref(), Some("outer"));
+        assert!(matches!(&program.statements[1], Stmt::While { label: Some(l), .. } if l == "outer"));
+        // 条件表达式里的冒号不是命名参数
+        let program = parse("f(ok ? a : b)").unwrap();
+        let Stmt::Expression { expr: Expr::Call { args, .. }, .. } = &program.statements[0] else {
+            panic!("Expected call");
+        };
+        assert!(args[0].0.is_none());
+        // 语句开头的 标识符: 只能用于 for
+        assert!(parse("outer: println(1)").is_err());
+    }
+    
+    #[test]
+    fn test_recovers_inside_blocks() {
+        let source = "func a() {\n    var x = 1 +\n    if x > 0 {\n        var y = ]\n    }\n    println(x)\n}\n\nfunc b() int {\n    return 1 +\n}\n\nfunc c() int {\n    return 3\n}\n";
+        let mut scanner = Scanner::new(source);
+        let mut parser = Parser::new(scanner.scan_tokens(), Locale::En);
+        let (program, errors) = parser.parse_recovering();
+        // 每个出错的语句只报告一次，不越过所在的块
+        let lines: Vec<usize> = errors.iter().map(|e| e.span.line).collect();
+        assert_eq!(lines, vec![2, 4, 10]);
+        let names: Vec<&str> = program
+            .statements
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Stmt::FnDef { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names, vec!["a", "b", "c"]);
+        assert_eq!(parse(source).unwrap_err().len(), 3);
+    }
 }
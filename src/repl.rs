@@ -0,0 +1,189 @@
+//! 交互式解释器（REPL）
+//!
+//! 一个会话共用一个编译器和一个虚拟机：每段输入增量编译后追加到同一个字节码块，
+//! 虚拟机从新代码处继续执行，之前定义的变量、函数和类型保持可见。
+//! 输入出错时编译器回到输入之前的状态，虚拟机丢弃这段输入压入的值。
+//!
+//! 括号没有闭合时继续读取下一行；最后一条语句是表达式时显示它的值（null 除外）。
+
+use std::sync::Arc;
+
+use crate::compiler::{Chunk, Compiler};
+use crate::diagnostics::use_color;
+use crate::ice;
+use crate::i18n::{format_message, messages, Locale};
+use crate::lexer::{Scanner, TokenKind};
+use crate::vm::{render_error, TraceFormat, Value, VM};
+
+/// REPL 会话
+pub struct Repl {
+    compiler: Compiler,
+    vm: VM,
+    locale: Locale,
+    /// 已执行的输入，栈追踪按 `<repl:N>` 取回源码
+    inputs: Vec<String>,
+}
+
+impl Repl {
+    pub fn new(locale: Locale) -> Self {
+        Self {
+            compiler: Compiler::new(locale),
+            vm: VM::new(Arc::new(Chunk::new()), locale),
+            locale,
+            inputs: Vec::new(),
+        }
+    }
+
+    /// 执行一段输入，实现内部的 panic（内部错误）不结束会话
+    ///
+    /// 报告已由 panic 钩子输出。panic 可能发生在编译器或虚拟机修改状态的中途，
+    /// 继续使用这个状态可能得到错误的结果，所以会话重建为初始状态，之前的定义都不再可用
+    pub fn eval_or_reset(&mut self, source: &str) -> Result<Option<Value>, String> {
+        match ice::catch(|| self.eval(source)) {
+            Ok(result) => result,
+            Err(()) => {
+                *self = Repl::new(self.locale);
+                Err("The REPL session was reset after the internal error; earlier definitions are no longer available.".to_string())
+            }
+        }
+    }
+
+    /// 执行一段完整的输入，返回需要显示的表达式值
+    pub fn eval(&mut self, source: &str) -> Result<Option<Value>, String> {
+        ice::set_file(&format!("<repl:{}>", self.inputs.len() + 1));
+        let program = crate::parse_source(source, self.locale).map_err(|e| {
+            let label = format_message(messages::MSG_CLI_SYNTAX_ERROR, self.locale, &[]);
+            format!("{}\n{}", label, e)
+        })?;
+
+        self.inputs.push(source.to_string());
+        let name = format!("<repl:{}>", self.inputs.len());
+        let snapshot = self.compiler.clone();
+        ice::enter(ice::Phase::Compile);
+        let (start, leaves_value) = match self.compiler.compile_repl(&program, &name) {
+            Ok(result) => result,
+            Err(errors) => {
+                self.compiler = snapshot;
+                let label = format_message(messages::MSG_CLI_COMPILE_ERROR, self.locale, &[]);
+                let error_list = errors
+                    .iter()
+                    .map(|e| format!("  [{}:{}] {}", e.span.line, e.span.column, e.message))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                return Err(format!("{}\n{}", label, error_list));
+            }
+        };
+
+        let depth = self.vm.stack_depth();
+        ice::enter(ice::Phase::Run);
+        match self.vm.resume(Arc::new(self.compiler.chunk().clone()), start) {
+            Ok(()) if leaves_value => Ok(self.vm.pop_result().filter(|value| !value.is_null())),
+            Ok(()) => Ok(None),
+            Err(e) => {
+                // 这段输入定义的变量没有全部压栈，整段作废
+                self.compiler = snapshot;
+                self.vm.recover(depth);
+                let inputs = &self.inputs;
+                let mut load_source = |file: Option<&str>| {
+                    let index: usize = file?.strip_prefix("<repl:")?.strip_suffix('>')?.parse().ok()?;
+                    inputs.get(index.checked_sub(1)?).cloned()
+                };
+                Err(render_error(&e, TraceFormat::Full, use_color(false), &mut load_source))
+            }
+        }
+    }
+}
+
+/// 输入是否还没有结束：有未闭合的 `(`、`[` 或 `{`
+pub fn is_incomplete(source: &str) -> bool {
+    let mut depth: i32 = 0;
+    for token in Scanner::new(source).scan_tokens() {
+        match token.kind {
+            TokenKind::LeftParen | TokenKind::LeftBracket | TokenKind::LeftBrace => depth += 1,
+            TokenKind::RightParen | TokenKind::RightBracket | TokenKind::RightBrace => depth -= 1,
+            _ => {}
+        }
+    }
+    depth > 0
+}
+
+/// 显示表达式的值：字符串带引号，其余按 `println` 的格式
+pub fn display_value(value: &Value) -> String {
+    match value.as_string() {
+        Some(s) => format!("{:?}", s),
+        None => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval_all(repl: &mut Repl, inputs: &[&str]) -> Vec<Result<Option<String>, String>> {
+        inputs
+            .iter()
+            .map(|input| repl.eval(input).map(|value| value.map(|v| display_value(&v))))
+            .collect()
+    }
+
+    #[test]
+    fn test_state_persists_between_inputs() {
+        let mut repl = Repl::new(Locale::En);
+        let results = eval_all(&mut repl, &[
+            "var x = 1",
+            "func double(n: int) int {\n    return n * 2\n}",
+            "x = double(x + 20)",
+            "x",
+            "\"a\" + \"b\"",
+            "println(x)",
+        ]);
+        assert_eq!(results, vec![
+            Ok(None),
+            Ok(None),
+            Ok(Some("42".to_string())),
+            Ok(Some("42".to_string())),
+            Ok(Some("\"ab\"".to_string())),
+            Ok(None),
+        ]);
+    }
+
+    #[test]
+    fn test_failed_input_is_discarded() {
+        let mut repl = Repl::new(Locale::En);
+        repl.eval("var a = 1").unwrap();
+        // 运行时出错：b 和 c 都不保留，a 的槽位不受影响
+        let err = repl.eval("var b = 2\nvar c = [1][3]").unwrap_err();
+        assert!(err.contains("Index 3 out of bounds"), "{}", err);
+        assert!(err.contains("<repl:2>:2"), "{}", err);
+        assert!(repl.eval("b").is_err());
+        // 编译出错同样整段作废
+        assert!(repl.eval("var d = 4\nundefined_name").is_err());
+        assert!(repl.eval("d").is_err());
+        repl.eval("var e = a + 10").unwrap();
+        assert_eq!(repl.eval("e").unwrap().and_then(|v| v.as_int()), Some(11));
+    }
+
+    #[test]
+    fn test_session_survives_internal_error() {
+        let mut repl = Repl::new(Locale::En);
+        repl.eval_or_reset("var a = 1").unwrap();
+        ice::inject(Some(ice::Phase::Run));
+        let err = repl.eval_or_reset("var b = 2").unwrap_err();
+        ice::inject(None);
+        assert!(err.contains("session was reset"), "{}", err);
+        // 会话重建为初始状态：之前的定义不再可用，新的输入正常执行
+        assert!(repl.eval_or_reset("a").is_err());
+        repl.eval_or_reset("var c = 3").unwrap();
+        assert_eq!(repl.eval_or_reset("c * 2").unwrap().and_then(|v| v.as_int()), Some(6));
+    }
+
+    #[test]
+    fn test_is_incomplete() {
+        assert!(!is_incomplete("var x = 1"));
+        assert!(is_incomplete("func f() {"));
+        assert!(is_incomplete("func f() {\n    var a = [1,"));
+        assert!(!is_incomplete("func f() {\n    var a = [1, 2]\n}"));
+        assert!(!is_incomplete("var s = \"{\""));
+        assert!(!is_incomplete("}"));
+    }
+}
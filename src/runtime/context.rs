@@ -10,7 +10,7 @@ pub struct CallFrameSnapshot {
     /// 返回地址
     pub return_ip: u32,
     /// 栈基址
-    pub base_slot: u16,
+    pub base_slot: u32,
     /// 是否是方法调用
     pub is_method_call: bool,
 }
@@ -36,6 +36,12 @@ pub struct PreemptState {
     safepoint_interval: AtomicU64,
 }
 
+impl Default for PreemptState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PreemptState {
     /// 创建新的抢占状态
     pub const fn new() -> Self {
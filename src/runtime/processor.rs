@@ -261,6 +261,7 @@ mod tests {
             chunk_index: 0,
             local_count: 0,
             upvalues: Vec::new(),
+            captures: Vec::new(),
         });
         Arc::new(Goroutine::new(id, func, Vec::new()).unwrap())
     }
@@ -0,0 +1,382 @@
+//! std.convert 字符串到数值的转换
+//!
+//! JSON 解析、`as` / `as!` 类型转换和 `Convert.toInt` 等需要把字符串转成数值的地方都使用这里的规则，
+//! 以保证同一个字符串在各处得到相同的结果：
+//!
+//! - 整数：可选的 `+` / `-`，然后是十进制数字，或 `0x` / `0o` / `0b` 前缀（大小写均可）加对应进制的数字；
+//!   不接受下划线、小数点、指数和首尾空白，超出 `int`（64 位）范围是溢出错误而不是回绕
+//! - 浮点数：可选的符号，然后是 `1`、`1.`、`.5`、`1.5e-3` 形式的十进制数，或 `inf` / `infinity` / `nan`（大小写均可）；
+//!   不接受十六进制和本地化的小数点，有限的数字超出 `f64` 范围是溢出错误，过小时舍入为 0
+//! - 布尔值：`true` / `false`，大小写均可
+//!
+//! 宽松模式（[`Mode::Lenient`]）用于 HTTP 查询参数这类缺省值常写成空串的输入：空字符串转换为 null。
+
+use std::fmt;
+
+use super::exception::stdlib_exception;
+use super::StdlibModule;
+use crate::vm::value::Value;
+
+/// 转换的目标类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Int,
+    Float,
+    Bool,
+}
+
+impl Target {
+    fn name(self) -> &'static str {
+        match self {
+            Target::Int => "int",
+            Target::Float => "f64",
+            Target::Bool => "bool",
+        }
+    }
+}
+
+/// 空字符串的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// 空字符串是错误
+    Strict,
+    /// 空字符串转换为 null
+    Lenient,
+}
+
+/// 转换失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Empty,
+    Invalid,
+    Overflow,
+}
+
+/// 转换错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConvertError {
+    pub kind: ErrorKind,
+    pub target: Target,
+    pub text: String,
+}
+
+impl ConvertError {
+    fn new(kind: ErrorKind, target: Target, text: &str) -> Self {
+        Self { kind, target, text: text.to_string() }
+    }
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ErrorKind::Empty => write!(f, "cannot convert empty string to {}", self.target.name()),
+            ErrorKind::Invalid => write!(f, "cannot convert '{}' to {}: invalid syntax", self.text, self.target.name()),
+            ErrorKind::Overflow => match self.target {
+                Target::Int => write!(
+                    f,
+                    "cannot convert '{}' to int: out of range ({}..={})",
+                    self.text,
+                    i64::MIN,
+                    i64::MAX
+                ),
+                _ => write!(f, "cannot convert '{}' to {}: out of range", self.text, self.target.name()),
+            },
+        }
+    }
+}
+
+/// 按整数规则转换
+pub fn parse_int(text: &str) -> Result<i64, ConvertError> {
+    if text.is_empty() {
+        return Err(ConvertError::new(ErrorKind::Empty, Target::Int, text));
+    }
+    let (negative, unsigned) = split_sign(text);
+    let (radix, digits) = match unsigned.get(..2) {
+        Some("0x" | "0X") => (16, &unsigned[2..]),
+        Some("0o" | "0O") => (8, &unsigned[2..]),
+        Some("0b" | "0B") => (2, &unsigned[2..]),
+        _ => (10, unsigned),
+    };
+    parse_digits(text, negative, digits, radix)
+}
+
+/// 按指定进制（2 到 36）转换：可选的符号加该进制的数字（字母大小写均可），不接受前缀
+pub fn parse_int_radix(text: &str, radix: u32) -> Result<i64, ConvertError> {
+    assert!((2..=36).contains(&radix), "radix {} out of range", radix);
+    if text.is_empty() {
+        return Err(ConvertError::new(ErrorKind::Empty, Target::Int, text));
+    }
+    let (negative, digits) = split_sign(text);
+    parse_digits(text, negative, digits, radix)
+}
+
+fn split_sign(text: &str) -> (bool, &str) {
+    match text.as_bytes()[0] {
+        b'-' => (true, &text[1..]),
+        b'+' => (false, &text[1..]),
+        _ => (false, text),
+    }
+}
+
+fn parse_digits(text: &str, negative: bool, digits: &str, radix: u32) -> Result<i64, ConvertError> {
+    let error = |kind| ConvertError::new(kind, Target::Int, text);
+    if digits.is_empty() {
+        return Err(error(ErrorKind::Invalid));
+    }
+
+    // 溢出后继续检查剩余的字符，很长的非法字符串报告语法错误而不是溢出
+    let mut magnitude: Option<u64> = Some(0);
+    for c in digits.chars() {
+        let digit = c.to_digit(radix).ok_or_else(|| error(ErrorKind::Invalid))?;
+        magnitude = magnitude
+            .and_then(|m| m.checked_mul(radix as u64))
+            .and_then(|m| m.checked_add(digit as u64));
+    }
+    let magnitude = magnitude.ok_or_else(|| error(ErrorKind::Overflow))?;
+    if negative {
+        0i64.checked_sub_unsigned(magnitude).ok_or_else(|| error(ErrorKind::Overflow))
+    } else {
+        i64::try_from(magnitude).map_err(|_| error(ErrorKind::Overflow))
+    }
+}
+
+/// 按浮点数规则转换
+pub fn parse_float(text: &str) -> Result<f64, ConvertError> {
+    let error = |kind| ConvertError::new(kind, Target::Float, text);
+    if text.is_empty() {
+        return Err(error(ErrorKind::Empty));
+    }
+    // Rust 标准库的浮点数语法与上面的规则相同，这里只需补上溢出检查
+    let value: f64 = text.parse().map_err(|_| error(ErrorKind::Invalid))?;
+    let spelled_infinity = text
+        .trim_start_matches(['+', '-'])
+        .to_ascii_lowercase()
+        .starts_with("inf");
+    if value.is_infinite() && !spelled_infinity {
+        return Err(error(ErrorKind::Overflow));
+    }
+    Ok(value)
+}
+
+/// 按布尔值规则转换
+pub fn parse_bool(text: &str) -> Result<bool, ConvertError> {
+    if text.is_empty() {
+        Err(ConvertError::new(ErrorKind::Empty, Target::Bool, text))
+    } else if text.eq_ignore_ascii_case("true") {
+        Ok(true)
+    } else if text.eq_ignore_ascii_case("false") {
+        Ok(false)
+    } else {
+        Err(ConvertError::new(ErrorKind::Invalid, Target::Bool, text))
+    }
+}
+
+/// 转换为 Q 值；宽松模式下空字符串得到 null
+pub fn convert(text: &str, target: Target, mode: Mode) -> Result<Value, ConvertError> {
+    if text.is_empty() && mode == Mode::Lenient {
+        return Ok(Value::null());
+    }
+    match target {
+        Target::Int => parse_int(text).map(|n| Value::int(n as i128)),
+        Target::Float => parse_float(text).map(Value::float),
+        Target::Bool => parse_bool(text).map(Value::bool),
+    }
+}
+
+/// Convert.toInt / toFloat / toBool(text: string)，失败时抛出 IllegalArgumentException
+fn convert_fn(name: &str, target: Target, args: &[Value]) -> Result<Value, String> {
+    let text = args.first()
+        .and_then(|v| v.as_string())
+        .ok_or_else(|| stdlib_exception("IllegalArgumentException", format!("Convert.{} expects a string", name)))?;
+    convert(text, target, Mode::Strict).map_err(|e| stdlib_exception("IllegalArgumentException", e))
+}
+
+/// std.convert 标准库
+#[derive(Default)]
+pub struct ConvertLib;
+
+impl ConvertLib {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl StdlibModule for ConvertLib {
+    fn name(&self) -> &'static str {
+        "std.convert"
+    }
+
+    fn exports(&self) -> Vec<&'static str> {
+        vec!["Convert_toInt", "Convert_toFloat", "Convert_toBool"]
+    }
+
+    fn call(&self, name: &str, args: &[Value]) -> Result<Value, String> {
+        match name {
+            "Convert_toInt" => convert_fn("toInt", Target::Int, args),
+            "Convert_toFloat" => convert_fn("toFloat", Target::Float, args),
+            "Convert_toBool" => convert_fn("toBool", Target::Bool, args),
+            _ => Err(format!("Unknown function: {}", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 接受的写法和结果
+    const INTS: &[(&str, i64)] = &[
+        ("0", 0),
+        ("42", 42),
+        ("+42", 42),
+        ("-42", -42),
+        ("007", 7),
+        ("0x1F", 31),
+        ("0XfF", 255),
+        ("-0x10", -16),
+        ("0o17", 15),
+        ("0b101", 5),
+        ("+0b1", 1),
+        ("9223372036854775807", i64::MAX),
+        ("-9223372036854775808", i64::MIN),
+        ("-0x8000000000000000", i64::MIN),
+        ("00000000000000000000000000001", 1),
+    ];
+
+    const INVALID_INTS: &[&str] = &[
+        "+", "-", "0x", "1_000", "1.0", "1e3", " 1", "1 ", "--1", "+-1", "0x1g", "0b2", "0o8", "١", "12a", "0x-1",
+    ];
+
+    const OVERFLOW_INTS: &[&str] = &[
+        "9223372036854775808",
+        "-9223372036854775809",
+        "0xffffffffffffffff",
+        "123456789012345678901234567890",
+    ];
+
+    const FLOATS: &[(&str, f64)] = &[
+        ("0", 0.0),
+        ("1.5", 1.5),
+        ("+1.5", 1.5),
+        ("-1.5", -1.5),
+        ("1.", 1.0),
+        (".5", 0.5),
+        ("1e3", 1000.0),
+        ("1E+3", 1000.0),
+        ("2.5e-3", 0.0025),
+        ("1e-400", 0.0),
+        ("inf", f64::INFINITY),
+        ("-Infinity", f64::NEG_INFINITY),
+        ("1.7976931348623157e308", f64::MAX),
+    ];
+
+    const INVALID_FLOATS: &[&str] = &[".", "e3", "1e", "1,5", "0x10", "1_0.0", " 1.0", "1.0 ", "1.2.3", "infinit", "--1"];
+
+    #[test]
+    fn test_int_matrix() {
+        for &(text, expected) in INTS {
+            assert_eq!(parse_int(text), Ok(expected), "{}", text);
+        }
+        for &text in INVALID_INTS {
+            assert_eq!(parse_int(text).unwrap_err().kind, ErrorKind::Invalid, "{}", text);
+        }
+        for &text in OVERFLOW_INTS {
+            assert_eq!(parse_int(text).unwrap_err().kind, ErrorKind::Overflow, "{}", text);
+        }
+        assert_eq!(parse_int("").unwrap_err().kind, ErrorKind::Empty);
+    }
+
+    #[test]
+    fn test_int_radix() {
+        assert_eq!(parse_int_radix("ff", 16), Ok(255));
+        assert_eq!(parse_int_radix("-Zz", 36), Ok(-1295));
+        assert_eq!(parse_int_radix("777", 8), Ok(511));
+        assert_eq!(parse_int_radix("10", 2), Ok(2));
+        // 指定进制时不接受前缀
+        assert_eq!(parse_int_radix("0xff", 16).unwrap_err().kind, ErrorKind::Invalid);
+        assert_eq!(parse_int_radix("12", 2).unwrap_err().kind, ErrorKind::Invalid);
+        assert_eq!(parse_int_radix("8000000000000000", 16).unwrap_err().kind, ErrorKind::Overflow);
+        assert_eq!(parse_int_radix("-8000000000000000", 16), Ok(i64::MIN));
+    }
+
+    #[test]
+    fn test_float_matrix() {
+        for &(text, expected) in FLOATS {
+            assert_eq!(parse_float(text), Ok(expected), "{}", text);
+        }
+        assert!(parse_float("NaN").unwrap().is_nan());
+        assert!(parse_float("-nan").unwrap().is_nan());
+        for &text in INVALID_FLOATS {
+            assert_eq!(parse_float(text).unwrap_err().kind, ErrorKind::Invalid, "{}", text);
+        }
+        for text in ["1e309", "-1e309", "1.8e308"] {
+            assert_eq!(parse_float(text).unwrap_err().kind, ErrorKind::Overflow, "{}", text);
+        }
+        assert_eq!(parse_float("").unwrap_err().kind, ErrorKind::Empty);
+    }
+
+    #[test]
+    fn test_bool_matrix() {
+        for (text, expected) in [("true", true), ("false", false), ("TRUE", true), ("False", false)] {
+            assert_eq!(parse_bool(text), Ok(expected), "{}", text);
+        }
+        for text in ["1", "0", "yes", "t", " true", "truee"] {
+            assert_eq!(parse_bool(text).unwrap_err().kind, ErrorKind::Invalid, "{}", text);
+        }
+        assert_eq!(parse_bool("").unwrap_err().kind, ErrorKind::Empty);
+    }
+
+    #[test]
+    fn test_lenient_mode_and_messages() {
+        for target in [Target::Int, Target::Float, Target::Bool] {
+            assert!(convert("", target, Mode::Lenient).unwrap().is_null());
+            assert_eq!(convert("", target, Mode::Strict).unwrap_err().kind, ErrorKind::Empty);
+        }
+        // 宽松模式只影响空字符串
+        assert_eq!(convert(" ", Target::Int, Mode::Lenient).unwrap_err().kind, ErrorKind::Invalid);
+        assert_eq!(convert("7", Target::Int, Mode::Lenient).unwrap().as_int(), Some(7));
+
+        assert_eq!(
+            parse_int("99999999999999999999").unwrap_err().to_string(),
+            "cannot convert '99999999999999999999' to int: out of range (-9223372036854775808..=9223372036854775807)"
+        );
+        assert_eq!(parse_float("x").unwrap_err().to_string(), "cannot convert 'x' to f64: invalid syntax");
+        assert_eq!(parse_bool("").unwrap_err().to_string(), "cannot convert empty string to bool");
+    }
+
+    /// Q 中的 Convert 函数与转换规则一致
+    #[test]
+    fn test_convert_functions_agree_with_matrix() {
+        let lib = ConvertLib::new();
+        let call = |name: &str, text: &str| lib.call(name, &[Value::string(text.to_string())]);
+        for &(text, expected) in INTS {
+            assert_eq!(call("Convert_toInt", text).unwrap().as_int(), Some(expected as i128), "{}", text);
+        }
+        for &text in INVALID_INTS.iter().chain(OVERFLOW_INTS).chain(&[""]) {
+            let error = call("Convert_toInt", text).unwrap_err();
+            assert_eq!(error, stdlib_exception("IllegalArgumentException", parse_int(text).unwrap_err()));
+        }
+        for &(text, expected) in FLOATS {
+            assert_eq!(call("Convert_toFloat", text).unwrap().as_float(), Some(expected), "{}", text);
+        }
+        for &text in INVALID_FLOATS {
+            assert!(call("Convert_toFloat", text).unwrap_err().contains("invalid syntax"), "{}", text);
+        }
+        assert_eq!(call("Convert_toBool", "True").unwrap().as_bool(), Some(true));
+        assert!(call("Convert_toBool", "1").is_err());
+    }
+
+    /// JSON 语法允许的数字与转换规则一致，超出 int 范围时退化为浮点数
+    #[test]
+    fn test_json_numbers_agree_with_matrix() {
+        let json = |text: &str| super::super::json::parse(&[Value::string(text.to_string())]);
+        for text in ["0", "42", "-42", "9223372036854775807", "-9223372036854775808"] {
+            assert_eq!(json(text).unwrap().as_int(), parse_int(text).ok().map(i128::from), "{}", text);
+        }
+        for text in ["1.5", "-1.5", "1e3", "1E+3", "2.5e-3", "1e-400", "1.7976931348623157e308"] {
+            assert_eq!(json(text).unwrap().as_float(), parse_float(text).ok(), "{}", text);
+        }
+        assert_eq!(json("9223372036854775808").unwrap().as_float(), Some(9223372036854775808.0));
+        assert!(json("1e999").is_err());
+    }
+}
@@ -0,0 +1,281 @@
+//! 日期时间格式化的公共部分
+//!
+//! 把 Unix 毫秒时间戳换算成公历的日期和时间（不依赖外部库），并在此基础上提供三种格式：
+//! ISO 8601 精确到毫秒（`Time.formatIso`，也用于日志时间戳）、HTTP 日期（响应头 `Date` 和 `Time.formatHttp`）
+//! 以及按模式格式化（`Time.format`）。
+//!
+//! 这里只处理 UTC，不读取系统时区；月份和星期的名称总是英文，与系统的区域设置无关。
+//! 时间戳可以是 i64 范围内的任意值，包括 1970 年之前的负数。
+
+use std::fmt::Write;
+
+const MILLIS_PER_DAY: i64 = 86_400_000;
+
+const MONTH_NAMES: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June",
+    "July", "August", "September", "October", "November", "December",
+];
+
+/// 从星期日开始
+const WEEKDAY_NAMES: [&str; 7] = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+
+/// UTC 的公历日期和时间
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CivilTime {
+    /// 公元前 1 年为 0，公元前 2 年为 -1
+    pub year: i64,
+    /// 1 到 12
+    pub month: u32,
+    /// 1 到 31
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub millis: u32,
+    /// 0 为星期日
+    pub weekday: u32,
+}
+
+impl CivilTime {
+    /// Unix 毫秒时间戳对应的 UTC 时间
+    pub fn from_millis(millis: i64) -> Self {
+        let days = millis.div_euclid(MILLIS_PER_DAY);
+        let of_day = millis.rem_euclid(MILLIS_PER_DAY) as u32;
+        let (year, month, day) = civil_from_days(days);
+        Self {
+            year,
+            month,
+            day,
+            hour: of_day / 3_600_000,
+            minute: of_day / 60_000 % 60,
+            second: of_day / 1000 % 60,
+            millis: of_day % 1000,
+            // 1970-01-01 是星期四
+            weekday: (days + 4).rem_euclid(7) as u32,
+        }
+    }
+}
+
+/// 1970-01-01 之后的天数对应的 (年, 月, 日)
+///
+/// 以 3 月 1 日为一年的开始，闰日落在年末，每 400 年（146097 天）为一个周期
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // 平移到 0000-03-01
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // 3 月为 0
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// 写出至少 width 位、不足时左侧补 0 的数，负数的 `-` 不计入位数
+fn push_padded(out: &mut String, value: i64, width: usize) {
+    if value < 0 {
+        out.push('-');
+    }
+    let _ = write!(out, "{:0width$}", value.unsigned_abs(), width = width);
+}
+
+/// ISO 8601 的年份：0 到 9999 年写 4 位，其余写符号加 6 位（`+010000`、`-000001`）
+fn push_iso_year(out: &mut String, year: i64) {
+    if (0..=9999).contains(&year) {
+        push_padded(out, year, 4);
+    } else {
+        out.push(if year < 0 { '-' } else { '+' });
+        push_padded(out, year.abs(), 6);
+    }
+}
+
+/// ISO 8601 格式，精确到毫秒：`2024-02-29T13:05:09.042Z`
+pub fn format_iso(millis: i64) -> String {
+    let t = CivilTime::from_millis(millis);
+    let mut out = String::with_capacity(24);
+    push_iso_year(&mut out, t.year);
+    out.push('-');
+    push_padded(&mut out, t.month.into(), 2);
+    out.push('-');
+    push_padded(&mut out, t.day.into(), 2);
+    out.push('T');
+    push_padded(&mut out, t.hour.into(), 2);
+    out.push(':');
+    push_padded(&mut out, t.minute.into(), 2);
+    out.push(':');
+    push_padded(&mut out, t.second.into(), 2);
+    out.push('.');
+    push_padded(&mut out, t.millis.into(), 3);
+    out.push('Z');
+    out
+}
+
+/// HTTP 日期（RFC 7231 的 IMF-fixdate），精确到秒：`Thu, 29 Feb 2024 13:05:09 GMT`
+pub fn format_http(millis: i64) -> String {
+    let t = CivilTime::from_millis(millis);
+    let mut out = String::with_capacity(29);
+    out.push_str(&WEEKDAY_NAMES[t.weekday as usize][..3]);
+    out.push_str(", ");
+    push_padded(&mut out, t.day.into(), 2);
+    out.push(' ');
+    out.push_str(&MONTH_NAMES[t.month as usize - 1][..3]);
+    out.push(' ');
+    push_padded(&mut out, t.year, 4);
+    out.push(' ');
+    push_padded(&mut out, t.hour.into(), 2);
+    out.push(':');
+    push_padded(&mut out, t.minute.into(), 2);
+    out.push(':');
+    push_padded(&mut out, t.second.into(), 2);
+    out.push_str(" GMT");
+    out
+}
+
+/// 按模式格式化
+///
+/// 模式中的字母表示字段，重复次数决定写法（字段不足位数时左侧补 0）：
+///
+/// | 字母 | 字段 | 写法 |
+/// |------|------|------|
+/// | `y` | 年 | `yy` 写后两位，其余至少写重复的位数 |
+/// | `M` | 月 | `M`、`MM` 写数字，`MMM` 写 `Jan`，`MMMM` 写 `January` |
+/// | `d` | 日 | |
+/// | `E` | 星期 | `E` 到 `EEE` 写 `Mon`，`EEEE` 写 `Monday` |
+/// | `H` | 时（0 到 23） | |
+/// | `h` | 时（1 到 12） | |
+/// | `a` | 上午或下午 | `AM` / `PM` |
+/// | `m` | 分 | |
+/// | `s` | 秒 | |
+/// | `S` | 毫秒 | 总是写 3 位 |
+///
+/// 其他 ASCII 字母保留，出现时返回错误；单引号中的文字原样输出，`''` 表示一个单引号。
+pub fn format_pattern(millis: i64, pattern: &str) -> Result<String, String> {
+    let t = CivilTime::from_millis(millis);
+    let mut out = String::with_capacity(pattern.len() + 8);
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\'' {
+            if chars.peek() == Some(&'\'') {
+                chars.next();
+                out.push('\'');
+                continue;
+            }
+            loop {
+                match chars.next() {
+                    Some('\'') if chars.peek() == Some(&'\'') => {
+                        chars.next();
+                        out.push('\'');
+                    }
+                    Some('\'') => break,
+                    Some(c) => out.push(c),
+                    None => return Err(format!("unterminated quote in time pattern '{}'", pattern)),
+                }
+            }
+            continue;
+        }
+        if !c.is_ascii_alphabetic() {
+            out.push(c);
+            continue;
+        }
+        let mut count = 1;
+        while chars.peek() == Some(&c) {
+            chars.next();
+            count += 1;
+        }
+        match c {
+            'y' if count == 2 => push_padded(&mut out, t.year.rem_euclid(100), 2),
+            'y' => push_padded(&mut out, t.year, count),
+            'M' if count >= 4 => out.push_str(MONTH_NAMES[t.month as usize - 1]),
+            'M' if count == 3 => out.push_str(&MONTH_NAMES[t.month as usize - 1][..3]),
+            'M' => push_padded(&mut out, t.month.into(), count),
+            'd' => push_padded(&mut out, t.day.into(), count),
+            'E' if count >= 4 => out.push_str(WEEKDAY_NAMES[t.weekday as usize]),
+            'E' => out.push_str(&WEEKDAY_NAMES[t.weekday as usize][..3]),
+            'H' => push_padded(&mut out, t.hour.into(), count),
+            'h' => push_padded(&mut out, ((t.hour + 11) % 12 + 1).into(), count),
+            'a' => out.push_str(if t.hour < 12 { "AM" } else { "PM" }),
+            'm' => push_padded(&mut out, t.minute.into(), count),
+            's' => push_padded(&mut out, t.second.into(), count),
+            'S' => push_padded(&mut out, t.millis.into(), 3),
+            _ => return Err(format!("unknown letter '{}' in time pattern '{}'", c, pattern)),
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// (时间戳, ISO 8601, HTTP 日期)
+    const KNOWN: &[(i64, &str, &str)] = &[
+        (0, "1970-01-01T00:00:00.000Z", "Thu, 01 Jan 1970 00:00:00 GMT"),
+        (-1, "1969-12-31T23:59:59.999Z", "Wed, 31 Dec 1969 23:59:59 GMT"),
+        (784_111_777_000, "1994-11-06T08:49:37.000Z", "Sun, 06 Nov 1994 08:49:37 GMT"),
+        (951_782_400_000, "2000-02-29T00:00:00.000Z", "Tue, 29 Feb 2000 00:00:00 GMT"),
+        (1_709_211_909_042, "2024-02-29T13:05:09.042Z", "Thu, 29 Feb 2024 13:05:09 GMT"),
+        (1_709_251_199_999, "2024-02-29T23:59:59.999Z", "Thu, 29 Feb 2024 23:59:59 GMT"),
+        (1_709_251_200_000, "2024-03-01T00:00:00.000Z", "Fri, 01 Mar 2024 00:00:00 GMT"),
+        (1_735_689_599_999, "2024-12-31T23:59:59.999Z", "Tue, 31 Dec 2024 23:59:59 GMT"),
+        (1_735_689_600_000, "2025-01-01T00:00:00.000Z", "Wed, 01 Jan 2025 00:00:00 GMT"),
+        // 1900 年不是闰年
+        (-2_203_891_200_000, "1900-03-01T00:00:00.000Z", "Thu, 01 Mar 1900 00:00:00 GMT"),
+        // 32 位秒数溢出之后
+        (2_147_483_648_000, "2038-01-19T03:14:08.000Z", "Tue, 19 Jan 2038 03:14:08 GMT"),
+        (253_402_300_799_999, "9999-12-31T23:59:59.999Z", "Fri, 31 Dec 9999 23:59:59 GMT"),
+        (253_402_300_800_000, "+010000-01-01T00:00:00.000Z", "Sat, 01 Jan 10000 00:00:00 GMT"),
+        (-62_135_596_800_000, "0001-01-01T00:00:00.000Z", "Mon, 01 Jan 0001 00:00:00 GMT"),
+        (-62_135_596_800_001, "0000-12-31T23:59:59.999Z", "Sun, 31 Dec 0000 23:59:59 GMT"),
+        (-62_167_305_600_000, "-000001-12-31T00:00:00.000Z", "Fri, 31 Dec -0001 00:00:00 GMT"),
+    ];
+
+    #[test]
+    fn test_known_timestamps() {
+        for &(millis, iso, http) in KNOWN {
+            assert_eq!(format_iso(millis), iso, "{}", millis);
+            assert_eq!(format_http(millis), http, "{}", millis);
+        }
+    }
+
+    #[test]
+    fn test_civil_time_round_trips_by_day() {
+        // 相邻两天的日期连续，跨过月末、年末和闰日
+        let mut previous = CivilTime::from_millis(-800 * 365 * MILLIS_PER_DAY);
+        for day in -800 * 365 + 1..800 * 365 {
+            let t = CivilTime::from_millis(day * MILLIS_PER_DAY);
+            assert_eq!(t.weekday, (previous.weekday + 1) % 7);
+            if t.day == 1 {
+                let leap = previous.year % 4 == 0 && (previous.year % 100 != 0 || previous.year % 400 == 0);
+                let days_in_month = [31, if leap { 29 } else { 28 }, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+                assert_eq!(previous.day, days_in_month[previous.month as usize - 1], "{:?}", t);
+                assert_eq!((t.year, t.month), if previous.month == 12 { (previous.year + 1, 1) } else { (previous.year, previous.month + 1) });
+            } else {
+                assert_eq!((t.year, t.month, t.day), (previous.year, previous.month, previous.day + 1));
+            }
+            previous = t;
+        }
+    }
+
+    #[test]
+    fn test_extreme_timestamps() {
+        assert_eq!(format_iso(i64::MAX), "+292278994-08-17T07:12:55.807Z");
+        assert_eq!(format_iso(i64::MIN), "-292275055-05-16T16:47:04.192Z");
+    }
+
+    #[test]
+    fn test_format_pattern() {
+        let millis = 1_709_211_909_042;
+        let format = |pattern| format_pattern(millis, pattern).unwrap();
+        assert_eq!(format("yyyy-MM-dd HH:mm:ss.SSS"), "2024-02-29 13:05:09.042");
+        assert_eq!(format("EEEE, MMMM d, yy"), "Thursday, February 29, 24");
+        assert_eq!(format("EEE MMM d h:mm a"), "Thu Feb 29 1:05 PM");
+        assert_eq!(format("'day' d, 'o''clock': H''"), "day 29, o'clock: 13'");
+        assert_eq!(format_pattern(0, "h a").unwrap(), "12 AM");
+        assert_eq!(format_pattern(-1, "y/M/d").unwrap(), "1969/12/31");
+        assert_eq!(format_pattern(0, "yyyy-QQ").unwrap_err(), "unknown letter 'Q' in time pattern 'yyyy-QQ'");
+        assert!(format_pattern(0, "'open").unwrap_err().contains("unterminated quote"));
+    }
+}
@@ -0,0 +1,86 @@
+//! 标准库类的类型声明
+//!
+//! 模块通过 [`StdlibModule::type_declarations`](super::StdlibModule::type_declarations) 发布类的构造函数、
+//! 方法和字段签名，类型检查器在出现对应的 import 时把它们载入类型环境。
+//! 参数列表末尾的可空参数可以省略。
+
+use crate::types::Type;
+
+/// 参数或字段
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamDecl {
+    pub name: &'static str,
+    pub ty: Type,
+}
+
+/// 实例方法
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodDecl {
+    pub name: &'static str,
+    pub params: Vec<ParamDecl>,
+    pub return_type: Type,
+}
+
+/// 标准库类
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassDecl {
+    /// 简短类名，如 `TCPSocket`
+    pub name: &'static str,
+    /// 父类（简短类名）
+    pub parent: Option<&'static str>,
+    /// 构造函数参数；None 表示不能用 `new` 创建
+    pub constructor: Option<Vec<ParamDecl>>,
+    pub methods: Vec<MethodDecl>,
+    pub fields: Vec<ParamDecl>,
+}
+
+impl ClassDecl {
+    pub fn new(name: &'static str) -> Self {
+        Self { name, parent: None, constructor: None, methods: Vec::new(), fields: Vec::new() }
+    }
+
+    pub fn parent(mut self, parent: &'static str) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    pub fn constructor(mut self, params: Vec<ParamDecl>) -> Self {
+        self.constructor = Some(params);
+        self
+    }
+
+    pub fn method(mut self, name: &'static str, params: Vec<ParamDecl>, return_type: Type) -> Self {
+        self.methods.push(MethodDecl { name, params, return_type });
+        self
+    }
+
+    pub fn field(mut self, name: &'static str, ty: Type) -> Self {
+        self.fields.push(ParamDecl { name, ty });
+        self
+    }
+}
+
+/// 参数声明
+pub fn param(name: &'static str, ty: Type) -> ParamDecl {
+    ParamDecl { name, ty }
+}
+
+/// 可省略的参数（类型为 `ty?`）
+pub fn optional(name: &'static str, ty: Type) -> ParamDecl {
+    ParamDecl { name, ty: Type::Nullable(Box::new(ty)) }
+}
+
+/// 类类型
+pub fn class(name: &str) -> Type {
+    Type::Class(name.to_string())
+}
+
+/// `int[]`，网络模块用它表示字节数据
+pub fn bytes() -> Type {
+    Type::Slice { element_type: Box::new(Type::Int) }
+}
+
+/// `map[string]string`
+pub fn string_map() -> Type {
+    Type::Map { key_type: Box::new(Type::String), value_type: Box::new(Type::String) }
+}
@@ -0,0 +1,281 @@
+//! std.encoding 编码与解码
+//!
+//! - `Encoding.base64Encode` / `base64Decode`：RFC 4648 的标准字母表，`urlSafe` 为 true 时用 URL 安全字母表
+//!   （`-` 和 `_` 代替 `+` 和 `/`，编码结果不带 `=` 填充）
+//! - `Encoding.hexEncode` / `hexDecode`：小写十六进制，解码时大小写都接受
+//! - `Encoding.urlEncode` / `urlDecode`：百分号编码，只保留 RFC 3986 的非保留字符
+//!
+//! 编码函数接受字符串（按 UTF-8 字节）或 int 数组（元素必须在 0..=255 之间）。
+//! 解码函数返回字符串，结果不是合法的 UTF-8 时报错；`base64DecodeBytes` / `hexDecodeBytes` 返回 int 数组。
+//! 输入不合法时抛出 `IllegalArgumentException`，消息中带出错处的字符下标。
+
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use super::exception::stdlib_exception;
+use super::StdlibModule;
+use crate::vm::hexdump::bytes_of;
+use crate::vm::value::Value;
+
+const BASE64_STANDARD: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const BASE64_URL_SAFE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+fn invalid(message: String) -> String {
+    stdlib_exception("IllegalArgumentException", message)
+}
+
+/// base64 编码；URL 安全字母表不带填充
+pub fn base64_encode(bytes: &[u8], url_safe: bool) -> String {
+    let alphabet = if url_safe { BASE64_URL_SAFE } else { BASE64_STANDARD };
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        // 3 个字节对应 4 个字符，不足 3 个字节时少输出的字符用 `=` 补齐
+        for i in 0..=chunk.len() {
+            out.push(alphabet[(n >> (18 - 6 * i)) as usize & 0x3f] as char);
+        }
+        if !url_safe {
+            out.extend(std::iter::repeat_n('=', 3 - chunk.len()));
+        }
+    }
+    out
+}
+
+/// base64 解码；填充可以省略，有填充时必须把长度补到 4 的倍数
+pub fn base64_decode(text: &str, url_safe: bool) -> Result<Vec<u8>, String> {
+    let alphabet = if url_safe { BASE64_URL_SAFE } else { BASE64_STANDARD };
+    let data = text.trim_end_matches('=');
+    let padding = text.len() - data.len();
+    let mut out = Vec::with_capacity(data.len() * 3 / 4);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for (index, c) in data.chars().enumerate() {
+        let value = alphabet
+            .iter()
+            .position(|&a| a as char == c)
+            .ok_or_else(|| format!("invalid base64 character '{}' at position {}", c, index))?;
+        buffer = (buffer << 6 | value as u32) & 0xffff;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    // 走到这里 data 只有 ASCII 字符，字节数就是字符数
+    if data.len() % 4 == 1 {
+        return Err(format!("truncated base64 input: a single character at position {} cannot form a byte", data.len() - 1));
+    }
+    if padding > 0 && (padding > 2 || !text.len().is_multiple_of(4)) {
+        return Err(format!("invalid base64 padding at position {}", data.len()));
+    }
+    Ok(out)
+}
+
+/// 小写十六进制编码
+pub fn hex_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for &b in bytes {
+        out.push(HEX_DIGITS[(b >> 4) as usize] as char);
+        out.push(HEX_DIGITS[(b & 0xf) as usize] as char);
+    }
+    out
+}
+
+/// 十六进制解码，大小写都接受
+pub fn hex_decode(text: &str) -> Result<Vec<u8>, String> {
+    let digits = text
+        .chars()
+        .enumerate()
+        .map(|(index, c)| {
+            c.to_digit(16).ok_or_else(|| format!("invalid hex character '{}' at position {}", c, index))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if !digits.len().is_multiple_of(2) {
+        return Err(format!("hex input has odd length {}", digits.len()));
+    }
+    Ok(digits.chunks(2).map(|pair| (pair[0] << 4 | pair[1]) as u8).collect())
+}
+
+/// 百分号编码：RFC 3986 的非保留字符（字母、数字和 `-._~`）原样保留，其他字节写成 `%XX`
+pub fn url_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len());
+    for &b in bytes {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+            out.push(b as char);
+        } else {
+            out.push('%');
+            out.push(HEX_DIGITS[(b >> 4) as usize].to_ascii_uppercase() as char);
+            out.push(HEX_DIGITS[(b & 0xf) as usize].to_ascii_uppercase() as char);
+        }
+    }
+    out
+}
+
+/// 百分号解码；`+` 原样保留，不当作空格
+pub fn url_decode(text: &str) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(text.len());
+    let mut chars = text.char_indices().enumerate();
+    while let Some((index, (byte, c))) = chars.next() {
+        if c != '%' {
+            let mut buffer = [0; 4];
+            out.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+        let escape = text.get(byte + 1..byte + 3).filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()));
+        let Some(hex) = escape else {
+            return Err(format!("invalid percent escape at position {}", index));
+        };
+        out.push(u8::from_str_radix(hex, 16).unwrap_or_default());
+        chars.next();
+        chars.next();
+    }
+    Ok(out)
+}
+
+/// 编码函数的输入：字符串或字节数组
+fn data_arg(function: &str, args: &[Value]) -> Result<Vec<u8>, String> {
+    let function = format!("Encoding.{}", function);
+    match args.first() {
+        Some(value) => bytes_of(value, &function).map_err(invalid),
+        None => Err(invalid(format!("{} expects an int array or a string", function))),
+    }
+}
+
+/// 解码函数的输入
+fn text_arg<'a>(function: &str, args: &'a [Value]) -> Result<&'a String, String> {
+    args.first()
+        .and_then(|v| v.as_string())
+        .ok_or_else(|| invalid(format!("Encoding.{} expects a string argument", function)))
+}
+
+/// 可选的 `urlSafe` 参数，省略或为 null 时是 false
+fn url_safe_arg(function: &str, args: &[Value]) -> Result<bool, String> {
+    match args.get(1).filter(|v| !v.is_null()) {
+        None => Ok(false),
+        Some(value) => value
+            .as_bool()
+            .ok_or_else(|| invalid(format!("Encoding.{} expects a bool for urlSafe, got {}", function, value))),
+    }
+}
+
+/// 解码结果作为字符串返回
+fn decoded_string(function: &str, bytes: Vec<u8>) -> Result<Value, String> {
+    String::from_utf8(bytes).map(Value::string).map_err(|_| {
+        invalid(format!("Encoding.{}: the decoded bytes are not valid UTF-8", function))
+    })
+}
+
+/// 解码结果作为 int 数组返回
+fn decoded_bytes(bytes: Vec<u8>) -> Value {
+    let elements = bytes.into_iter().map(|b| Value::int(b as i128)).collect();
+    Value::array(Arc::new(Mutex::new(elements)))
+}
+
+/// std.encoding 标准库
+#[derive(Default)]
+pub struct EncodingLib;
+
+impl EncodingLib {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl StdlibModule for EncodingLib {
+    fn name(&self) -> &'static str {
+        "std.encoding"
+    }
+
+    fn exports(&self) -> Vec<&'static str> {
+        vec![
+            "Encoding_base64Encode",
+            "Encoding_base64Decode",
+            "Encoding_base64DecodeBytes",
+            "Encoding_hexEncode",
+            "Encoding_hexDecode",
+            "Encoding_hexDecodeBytes",
+            "Encoding_urlEncode",
+            "Encoding_urlDecode",
+        ]
+    }
+
+    fn call(&self, name: &str, args: &[Value]) -> Result<Value, String> {
+        let function = name.strip_prefix("Encoding_").unwrap_or(name);
+        match function {
+            "base64Encode" => {
+                let bytes = data_arg(function, args)?;
+                Ok(Value::string(base64_encode(&bytes, url_safe_arg(function, args)?)))
+            }
+            "base64Decode" | "base64DecodeBytes" => {
+                let bytes = base64_decode(text_arg(function, args)?, url_safe_arg(function, args)?).map_err(invalid)?;
+                if function == "base64Decode" { decoded_string(function, bytes) } else { Ok(decoded_bytes(bytes)) }
+            }
+            "hexEncode" => Ok(Value::string(hex_encode(&data_arg(function, args)?))),
+            "hexDecode" | "hexDecodeBytes" => {
+                let bytes = hex_decode(text_arg(function, args)?).map_err(invalid)?;
+                if function == "hexDecode" { decoded_string(function, bytes) } else { Ok(decoded_bytes(bytes)) }
+            }
+            "urlEncode" => Ok(Value::string(url_encode(&data_arg(function, args)?))),
+            "urlDecode" => decoded_string(function, url_decode(text_arg(function, args)?).map_err(invalid)?),
+            _ => Err(format!("Unknown function: {}", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_round_trip() {
+        // RFC 4648 第 10 节的测试向量
+        let vectors = [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foob", "Zm9vYg=="), ("fooba", "Zm9vYmE="), ("foobar", "Zm9vYmFy")];
+        for (plain, encoded) in vectors {
+            assert_eq!(base64_encode(plain.as_bytes(), false), encoded);
+            assert_eq!(base64_decode(encoded, false).unwrap(), plain.as_bytes());
+            // 省略填充也能解码
+            assert_eq!(base64_decode(encoded.trim_end_matches('='), false).unwrap(), plain.as_bytes());
+        }
+        let bytes = [0xfb, 0xff, 0xbf];
+        assert_eq!(base64_encode(&bytes, false), "+/+/");
+        assert_eq!(base64_encode(&bytes, true), "-_-_");
+        assert_eq!(base64_encode(b"fo", true), "Zm8");
+        assert_eq!(base64_decode("-_-_", true).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_base64_errors() {
+        assert_eq!(base64_decode("Zm9v-A==", false).unwrap_err(), "invalid base64 character '-' at position 4");
+        assert_eq!(base64_decode("+/+/", true).unwrap_err(), "invalid base64 character '+' at position 0");
+        assert_eq!(base64_decode("Zg=a", false).unwrap_err(), "invalid base64 character '=' at position 2");
+        assert_eq!(base64_decode("日Zg==", false).unwrap_err(), "invalid base64 character '日' at position 0");
+        assert_eq!(base64_decode("Zm9vY", false).unwrap_err(), "truncated base64 input: a single character at position 4 cannot form a byte");
+        assert_eq!(base64_decode("Zg=", false).unwrap_err(), "invalid base64 padding at position 2");
+        assert_eq!(base64_decode("Zm8===", false).unwrap_err(), "invalid base64 padding at position 3");
+    }
+
+    #[test]
+    fn test_hex_and_url() {
+        assert_eq!(hex_encode(&[0x00, 0x7f, 0xab, 0xff]), "007fabff");
+        assert_eq!(hex_decode("007FabFF").unwrap(), [0x00, 0x7f, 0xab, 0xff]);
+        assert_eq!(hex_decode("abc").unwrap_err(), "hex input has odd length 3");
+        assert_eq!(hex_decode("0g").unwrap_err(), "invalid hex character 'g' at position 1");
+
+        assert_eq!(url_encode("a b&c=d/é~".as_bytes()), "a%20b%26c%3Dd%2F%C3%A9~");
+        assert_eq!(url_decode("a%20b+%c3%A9").unwrap(), "a b+é".as_bytes());
+        assert_eq!(url_decode("日%2").unwrap_err(), "invalid percent escape at position 1");
+        assert_eq!(url_decode("%zz").unwrap_err(), "invalid percent escape at position 0");
+    }
+
+    #[test]
+    fn test_call_errors_are_exceptions() {
+        let lib = EncodingLib::new();
+        let error = lib.call("Encoding_base64Decode", &[Value::string("@@".to_string())]).unwrap_err();
+        assert!(error.starts_with("IllegalArgumentException: "), "{}", error);
+        let error = lib.call("Encoding_hexDecode", &[Value::string("ff".to_string())]).unwrap_err();
+        assert_eq!(error, "IllegalArgumentException: Encoding.hexDecode: the decoded bytes are not valid UTF-8");
+        let bytes = lib.call("Encoding_hexDecodeBytes", &[Value::string("ff".to_string())]).unwrap();
+        assert_eq!(bytes.as_array().unwrap().lock()[0].as_int(), Some(255));
+    }
+}
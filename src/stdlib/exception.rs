@@ -21,6 +21,8 @@
 //!       - ...
 
 use super::StdlibModule;
+use super::declarations::{class, optional, ClassDecl};
+use crate::types::Type;
 use crate::vm::value::{Value, ClassInstance};
 use parking_lot::Mutex;
 use std::sync::Arc;
@@ -84,7 +86,31 @@ pub fn get_exception_parent(class_name: &str) -> Option<&'static str> {
     }
 }
 
+/// 将标准库错误编码为可抛出的异常，格式为 `"异常类名: 消息"`
+/// 
+/// VM 调用标准库失败时通过 [`parse_stdlib_exception`] 识别该格式，
+/// 作为异常抛出（可被 try/catch 捕获）；其他错误字符串仍是运行时错误。
+pub fn stdlib_exception(class_name: &str, message: impl std::fmt::Display) -> String {
+    format!("{}: {}", class_name, message)
+}
+
+/// 解析 [`stdlib_exception`] 生成的错误，返回 (异常类名, 消息)
+pub fn parse_stdlib_exception(error: &str) -> Option<(&str, &str)> {
+    let (class_name, message) = error.split_once(": ")?;
+    if is_throwable_type(class_name) {
+        Some((class_name, message))
+    } else {
+        None
+    }
+}
+
+/// 创建异常实例（供 VM 抛出标准库异常使用）
+pub fn new_exception(class_name: &str, message: &str) -> Value {
+    ExceptionLib::create_exception_instance(class_name, message.to_string(), None)
+}
+
 /// std.lang.Exception 标准库
+#[derive(Default)]
 pub struct ExceptionLib;
 
 impl ExceptionLib {
@@ -443,4 +469,61 @@ impl StdlibModule for ExceptionLib {
             _ => Err(format!("Unknown function: {}", name)),
         }
     }
+    
+    fn has_class(&self, class_name: &str) -> bool {
+        is_throwable_type(short_class_name(class_name))
+    }
+    
+    /// `new Exception(message?, cause?)`，实例使用简短类名，与 VM 抛出的标准库异常一致
+    fn create_class_instance(&self, class_name: &str, args: &[Value]) -> Result<Value, String> {
+        let class_name = short_class_name(class_name);
+        if !is_throwable_type(class_name) {
+            return Err(format!("Class '{}' not found in module '{}'", class_name, self.name()));
+        }
+        let message = args.first().and_then(|m| m.as_string().cloned()).unwrap_or_default();
+        let cause = args.get(1).filter(|c| !c.is_null()).cloned();
+        Ok(Self::create_exception_instance(class_name, message, cause))
+    }
+    
+    fn call_method(&self, instance: &Value, method_name: &str, _args: &[Value]) -> Result<Value, String> {
+        let Some(class_instance) = instance.as_class() else {
+            return Err("Value is not a class instance".to_string());
+        };
+        let guard = class_instance.lock();
+        let field = |name: &str| guard.fields.get(name).cloned().unwrap_or_else(Value::null);
+        match method_name {
+            "getMessage" => Ok(Value::string(field("message").as_string().cloned().unwrap_or_default())),
+            "getCause" => Ok(field("cause")),
+            "toString" => {
+                let message = field("message").as_string().cloned().unwrap_or_default();
+                Ok(Value::string(stdlib_exception(&guard.class_name, message)))
+            }
+            _ => Err(format!("{} has no method '{}'", guard.class_name, method_name)),
+        }
+    }
+    
+    /// cause 可以是任意异常类，参数声明为 unknown
+    fn type_declarations(&self) -> Vec<ClassDecl> {
+        THROWABLE_TYPES
+            .iter()
+            .map(|&name| {
+                let decl = ClassDecl::new(name)
+                    .constructor(vec![optional("message", Type::String), optional("cause", Type::Unknown)])
+                    .method("getMessage", vec![], Type::String)
+                    .method("getCause", vec![], Type::Nullable(Box::new(class("Throwable"))))
+                    .method("toString", vec![], Type::String)
+                    .field("message", Type::String)
+                    .field("cause", Type::Nullable(Box::new(class("Throwable"))));
+                match get_exception_parent(name) {
+                    Some(parent) => decl.parent(parent),
+                    None => decl,
+                }
+            })
+            .collect()
+    }
+}
+
+/// 去掉 `std.lang.` 前缀
+fn short_class_name(class_name: &str) -> &str {
+    class_name.strip_prefix("std.lang.").unwrap_or(class_name)
 }
@@ -0,0 +1,424 @@
+//! std.fs 文件系统模块
+//!
+//! 提供文件读写、目录操作和 File 类。
+//!
+//! I/O 失败（文件不存在、权限不足等）以 IOException 系列异常抛出，
+//! 可以被 try/catch 捕获，而不是终止程序。
+
+use super::StdlibModule;
+use super::exception::stdlib_exception;
+use crate::vm::gc::gc_attach_native;
+use crate::vm::value::{Value, ClassInstance};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+
+// 标准库类名常量
+pub const CLASS_FILE: &str = "std.fs.File";
+
+/// 已打开文件表：句柄 ID -> 文件
+///
+/// File 实例只在 "__handle" 字段保存 ID，关闭后从表中移除，
+/// 重复关闭或关闭后读写只会得到错误而不会访问已释放的内存。
+/// 每个文件有自己的锁，读写时不持有表的锁，一个文件上的阻塞 I/O 不影响其他文件
+fn open_files() -> &'static Mutex<HashMap<u64, Arc<Mutex<File>>>> {
+    static OPEN_FILES: OnceLock<Mutex<HashMap<u64, Arc<Mutex<File>>>>> = OnceLock::new();
+    OPEN_FILES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// 取出句柄对应的文件，文件已关闭时抛出 IOException
+///
+/// 读写期间文件被另一个线程关闭时，文件在读写结束后才真正关闭
+fn open_file(handle: u64, path: &str) -> Result<Arc<Mutex<File>>, String> {
+    open_files().lock().get(&handle).cloned()
+        .ok_or_else(|| stdlib_exception("IOException", format!("{}: file is closed", path)))
+}
+
+static NEXT_HANDLE: AtomicU64 = AtomicU64::new(1);
+
+/// File 实例持有的已打开文件表项，实例被回收时从表中移除：没有关闭的文件随实例一起关闭
+struct OpenFile(u64);
+
+impl Drop for OpenFile {
+    fn drop(&mut self) {
+        open_files().lock().remove(&self.0);
+    }
+}
+
+/// 将 I/O 错误转换为对应的异常
+fn io_exception(path: &str, err: io::Error) -> String {
+    let class_name = match err.kind() {
+        io::ErrorKind::NotFound => "FileNotFoundException",
+        io::ErrorKind::PermissionDenied => "PermissionDeniedException",
+        io::ErrorKind::AlreadyExists => "FileAlreadyExistsException",
+        io::ErrorKind::UnexpectedEof => "EOFException",
+        _ => "IOException",
+    };
+    stdlib_exception(class_name, format!("{}: {}", path, err))
+}
+
+/// 提取字符串参数
+fn string_arg<'a>(args: &'a [Value], index: usize, func: &str, name: &str) -> Result<&'a String, String> {
+    args.get(index)
+        .and_then(|v| v.as_string())
+        .ok_or_else(|| stdlib_exception(
+            "IllegalArgumentException",
+            format!("{} expects argument '{}' to be a string", func, name),
+        ))
+}
+
+// ============================================================================
+// 模块函数
+// ============================================================================
+
+/// readFile(path: string) -> string
+pub fn read_file(args: &[Value]) -> Result<Value, String> {
+    let path = string_arg(args, 0, "readFile", "path")?;
+    let content = fs::read_to_string(path).map_err(|e| io_exception(path, e))?;
+    Ok(Value::string(content))
+}
+
+/// writeFile(path: string, content: string) -> null
+/// 文件不存在时创建，存在时覆盖
+pub fn write_file(args: &[Value]) -> Result<Value, String> {
+    let path = string_arg(args, 0, "writeFile", "path")?;
+    let content = string_arg(args, 1, "writeFile", "content")?;
+    fs::write(path, content).map_err(|e| io_exception(path, e))?;
+    Ok(Value::null())
+}
+
+/// appendFile(path: string, content: string) -> null
+/// 文件不存在时创建
+pub fn append_file(args: &[Value]) -> Result<Value, String> {
+    let path = string_arg(args, 0, "appendFile", "path")?;
+    let content = string_arg(args, 1, "appendFile", "content")?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| io_exception(path, e))?;
+    file.write_all(content.as_bytes()).map_err(|e| io_exception(path, e))?;
+    Ok(Value::null())
+}
+
+/// exists(path: string) -> bool
+pub fn exists(args: &[Value]) -> Result<Value, String> {
+    let path = string_arg(args, 0, "exists", "path")?;
+    Ok(Value::bool(std::path::Path::new(path).exists()))
+}
+
+/// remove(path: string) -> null
+/// 删除文件或空目录
+pub fn remove(args: &[Value]) -> Result<Value, String> {
+    let path = string_arg(args, 0, "remove", "path")?;
+    let metadata = fs::symlink_metadata(path).map_err(|e| io_exception(path, e))?;
+    let result = if metadata.is_dir() {
+        fs::remove_dir(path)
+    } else {
+        fs::remove_file(path)
+    };
+    result.map_err(|e| io_exception(path, e))?;
+    Ok(Value::null())
+}
+
+/// mkdir(path: string) -> null
+/// 递归创建目录，目录已存在时不报错
+pub fn mkdir(args: &[Value]) -> Result<Value, String> {
+    let path = string_arg(args, 0, "mkdir", "path")?;
+    fs::create_dir_all(path).map_err(|e| io_exception(path, e))?;
+    Ok(Value::null())
+}
+
+/// readDir(path: string) -> string[]
+/// 返回目录下的条目名（按名称排序）
+pub fn read_dir(args: &[Value]) -> Result<Value, String> {
+    let path = string_arg(args, 0, "readDir", "path")?;
+    let mut names = Vec::new();
+    for entry in fs::read_dir(path).map_err(|e| io_exception(path, e))? {
+        let entry = entry.map_err(|e| io_exception(path, e))?;
+        names.push(entry.file_name().to_string_lossy().into_owned());
+    }
+    names.sort();
+    let values = names.into_iter().map(Value::string).collect();
+    Ok(Value::array(Arc::new(Mutex::new(values))))
+}
+
+// ============================================================================
+// File 类
+// ============================================================================
+
+/// 创建 File 类实例
+fn create_file_instance(handle: u64, path: &str) -> Value {
+    let mut fields = HashMap::new();
+    fields.insert("__handle".to_string(), Value::int(handle as i128));
+    fields.insert("path".to_string(), Value::string(path.to_string()));
+
+    let instance = ClassInstance {
+        class_name: CLASS_FILE.to_string(),
+        parent_class: None,
+        fields,
+    };
+
+    let file = Value::class(Arc::new(Mutex::new(instance)));
+    gc_attach_native(&file, Box::new(OpenFile(handle)));
+    file
+}
+
+/// 从 File 实例提取句柄 ID 和路径
+fn extract_file_handle(instance: &Value) -> Result<(u64, String), String> {
+    if let Some(class_instance) = instance.as_class() {
+        let instance = class_instance.lock();
+        let handle = instance.fields.get("__handle").and_then(|v| v.as_int());
+        let path = instance.fields.get("path").and_then(|v| v.as_string()).cloned();
+        if let (Some(handle), Some(path)) = (handle, path) {
+            return Ok((handle as u64, path));
+        }
+        Err("File instance has no valid handle".to_string())
+    } else {
+        Err("Value is not a File instance".to_string())
+    }
+}
+
+/// File 构造函数 / open(path: string, mode: string = "r") -> File
+///
+/// mode: "r" 读取，"w" 覆盖写入（不存在则创建），"a" 追加写入（不存在则创建）
+pub fn file_open(args: &[Value]) -> Result<Value, String> {
+    let path = string_arg(args, 0, "File.open", "path")?;
+    let mode = match args.get(1) {
+        Some(v) => v.as_string().map(|s| s.as_str()).ok_or_else(|| stdlib_exception(
+            "IllegalArgumentException",
+            "File.open expects argument 'mode' to be a string",
+        ))?,
+        None => "r",
+    };
+
+    let mut options = OpenOptions::new();
+    match mode {
+        "r" => options.read(true),
+        "w" => options.write(true).create(true).truncate(true),
+        "a" => options.append(true).create(true),
+        _ => return Err(stdlib_exception(
+            "IllegalArgumentException",
+            format!("Invalid file mode '{}', expected \"r\", \"w\" or \"a\"", mode),
+        )),
+    };
+
+    let file = options.open(path).map_err(|e| io_exception(path, e))?;
+    let handle = NEXT_HANDLE.fetch_add(1, Ordering::Relaxed);
+    open_files().lock().insert(handle, Arc::new(Mutex::new(file)));
+
+    Ok(create_file_instance(handle, path))
+}
+
+/// File.read() -> string
+/// 读取从当前位置到文件末尾的内容
+pub fn file_read(instance: &Value, _args: &[Value]) -> Result<Value, String> {
+    let (handle, path) = extract_file_handle(instance)?;
+    let file = open_file(handle, &path)?;
+
+    let mut content = String::new();
+    file.lock().read_to_string(&mut content).map_err(|e| io_exception(&path, e))?;
+    Ok(Value::string(content))
+}
+
+/// File.write(content: string) -> int
+/// 写入内容，返回写入的字节数
+pub fn file_write(instance: &Value, args: &[Value]) -> Result<Value, String> {
+    let content = string_arg(args, 0, "File.write", "content")?;
+    let (handle, path) = extract_file_handle(instance)?;
+    let file = open_file(handle, &path)?;
+
+    file.lock().write_all(content.as_bytes()).map_err(|e| io_exception(&path, e))?;
+    Ok(Value::int(content.len() as i128))
+}
+
+/// File.close() -> null
+/// 关闭文件，重复关闭无副作用
+pub fn file_close(instance: &Value, _args: &[Value]) -> Result<Value, String> {
+    let (handle, _) = extract_file_handle(instance)?;
+    // 移出表后 drop 即关闭文件（正在进行的读写结束后）
+    let file = open_files().lock().remove(&handle);
+    drop(file);
+    Ok(Value::null())
+}
+
+// ============================================================================
+// FsLib - 文件系统标准库模块
+// ============================================================================
+
+#[derive(Default)]
+pub struct FsLib;
+
+impl FsLib {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl StdlibModule for FsLib {
+    fn name(&self) -> &'static str {
+        "std.fs"
+    }
+
+    fn exports(&self) -> Vec<&'static str> {
+        vec![
+            "readFile",
+            "writeFile",
+            "appendFile",
+            "exists",
+            "remove",
+            "mkdir",
+            "readDir",
+            "open",
+        ]
+    }
+
+    fn call(&self, name: &str, args: &[Value]) -> Result<Value, String> {
+        match name {
+            "readFile" => read_file(args),
+            "writeFile" => write_file(args),
+            "appendFile" => append_file(args),
+            "exists" => exists(args),
+            "remove" => remove(args),
+            "mkdir" => mkdir(args),
+            "readDir" => read_dir(args),
+            "open" => file_open(args),
+            _ => Err(format!("Unknown function: {}", name)),
+        }
+    }
+
+    fn has_class(&self, class_name: &str) -> bool {
+        class_name == CLASS_FILE
+    }
+
+    fn create_class_instance(&self, class_name: &str, args: &[Value]) -> Result<Value, String> {
+        match class_name {
+            CLASS_FILE => file_open(args),
+            _ => Err(format!("Class '{}' not found in module '{}'", class_name, self.name())),
+        }
+    }
+
+    fn call_method(&self, instance: &Value, method_name: &str, args: &[Value]) -> Result<Value, String> {
+        match method_name {
+            "read" => file_read(instance, args),
+            "write" => file_write(instance, args),
+            "close" => file_close(instance, args),
+            _ => Err(format!("File has no method '{}'", method_name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stdlib::exception::parse_stdlib_exception;
+
+    /// 每个测试独用的空临时目录，测试结束时删除
+    fn temp_dir(test: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("qlang_fs_{}_{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn path_in(dir: &std::path::Path, name: &str) -> String {
+        dir.join(name).to_string_lossy().into_owned()
+    }
+
+    fn s(text: &str) -> Value {
+        Value::string(text.to_string())
+    }
+
+    #[test]
+    fn test_read_write_append() {
+        let dir = temp_dir("rw");
+        let path = path_in(&dir, "rw.txt");
+        write_file(&[s(&path), s("hello")]).unwrap();
+        append_file(&[s(&path), s(" world")]).unwrap();
+        let content = read_file(&[s(&path)]).unwrap();
+        assert_eq!(content.as_string().unwrap(), "hello world");
+        assert_eq!(exists(&[s(&path)]).unwrap().as_bool(), Some(true));
+
+        remove(&[s(&path)]).unwrap();
+        assert_eq!(exists(&[s(&path)]).unwrap().as_bool(), Some(false));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_missing_file_is_throwable() {
+        let dir = temp_dir("missing");
+        let path = path_in(&dir, "missing.txt");
+        let err = read_file(&[s(&path)]).unwrap_err();
+        let (class_name, message) = parse_stdlib_exception(&err).unwrap();
+        assert_eq!(class_name, "FileNotFoundException");
+        assert!(message.contains("missing.txt"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_mkdir_and_read_dir() {
+        let root = temp_dir("mkdir");
+        let dir = path_in(&root, "dir/nested");
+        mkdir(&[s(&dir)]).unwrap();
+        write_file(&[s(&format!("{}/b.txt", dir)), s("")]).unwrap();
+        write_file(&[s(&format!("{}/a.txt", dir)), s("")]).unwrap();
+
+        let entries = read_dir(&[s(&dir)]).unwrap();
+        let names: Vec<_> = entries.as_array().unwrap().lock().iter().map(|v| v.as_string().unwrap().clone()).collect();
+        assert_eq!(names, vec!["a.txt", "b.txt"]);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_file_class() {
+        let dir = temp_dir("class");
+        let path = path_in(&dir, "class.txt");
+        let file = file_open(&[s(&path), s("w")]).unwrap();
+        assert_eq!(file_write(&file, &[s("abc")]).unwrap().as_int(), Some(3));
+        file_close(&file, &[]).unwrap();
+        // 重复关闭无副作用，关闭后写入报错
+        file_close(&file, &[]).unwrap();
+        assert!(file_write(&file, &[s("x")]).is_err());
+
+        let file = file_open(&[s(&path)]).unwrap();
+        assert_eq!(file_read(&file, &[]).unwrap().as_string().unwrap(), "abc");
+        file_close(&file, &[]).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_dropping_the_owner_closes_the_file() {
+        let dir = temp_dir("owner");
+        let path = path_in(&dir, "owned.txt");
+        let file = file_open(&[s(&path), s("w")]).unwrap();
+        let (handle, _) = extract_file_handle(&file).unwrap();
+        assert!(open_file(handle, &path).is_ok());
+
+        drop(OpenFile(handle));
+        assert!(open_file(handle, &path).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_files_do_not_share_a_lock() {
+        let dir = temp_dir("locks");
+        let path = path_in(&dir, "held.txt");
+        let held = file_open(&[s(&path), s("w")]).unwrap();
+        let other = file_open(&[s(&path_in(&dir, "other.txt")), s("w")]).unwrap();
+
+        // 一个文件的锁被占用（如阻塞的读写）时，其他文件照常读写和关闭，表也可以修改
+        let (handle, _) = extract_file_handle(&held).unwrap();
+        let locked = open_file(handle, &path).unwrap();
+        let guard = locked.lock();
+        assert_eq!(file_write(&other, &[s("abc")]).unwrap().as_int(), Some(3));
+        file_close(&other, &[]).unwrap();
+        file_close(&held, &[]).unwrap();
+        drop(guard);
+
+        assert!(file_write(&held, &[s("x")]).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,501 @@
+//! std.async 异步结果模块
+//!
+//! `Future` 表示一个在后台执行的操作的结果。标准库的异步方法（`HttpClient.getAsync`、
+//! `Dns.resolveAsync`、`Time.after`）立即返回 Future，实际工作在 IO 线程池或定时器线程中执行，
+//! 完成后通过 [`FutureState::complete`] 写入结果并唤醒所有等待方。
+//!
+//! - `await()` 阻塞调用方直到完成。每个协程运行在自己的线程上，阻塞只挂起当前协程
+//! - `then(fn)` 注册完成回调，回调通过 [`CallbackChannel`] 交给 VM 执行
+//! - `Future.all` / `Future.race` 用完成回调组合，不占用额外线程
+//!
+//! 失败结果统一编码为 [`stdlib_exception`] 格式，`await()` 抛出的异常保持原来的异常类。
+
+use super::{StdlibModule, CallbackChannel};
+use super::exception::{stdlib_exception, parse_stdlib_exception};
+use super::net::io_thread_pool::IoThreadPool;
+use crate::vm::gc::{gc_attach_native, gc_escape};
+use crate::vm::value::{Value, ClassInstance};
+use crossbeam_channel::{Sender, RecvTimeoutError};
+use parking_lot::{Condvar, Mutex};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+// 标准库类名常量
+pub const CLASS_FUTURE: &str = "std.async.Future";
+
+/// 异步操作的结果，失败时为 stdlib_exception 格式的错误
+pub type Outcome = Result<Value, String>;
+
+type Continuation = Box<dyn FnOnce(&Outcome) + Send>;
+
+/// Future 的共享状态
+///
+/// 实例的 "__handle" 字段保存指向它的指针，实例通过 [`gc_attach_native`] 持有一份引用计数，
+/// 被回收时释放；定时器和尚未执行的回调各自持有一份，状态在它们都结束之后释放。
+/// 状态一旦写入就不再改变，之后注册的回调立即在注册线程上执行。
+pub struct FutureState {
+    inner: Mutex<FutureInner>,
+    done: Condvar,
+}
+
+struct FutureInner {
+    outcome: Option<Outcome>,
+    continuations: Vec<Continuation>,
+}
+
+impl FutureState {
+    fn new() -> Self {
+        Self {
+            inner: Mutex::new(FutureInner { outcome: None, continuations: Vec::new() }),
+            done: Condvar::new(),
+        }
+    }
+
+    /// 写入结果并唤醒等待方，只有第一次调用生效（返回 true）
+    ///
+    /// 结果只被这里引用，会在其他线程上被取出，标记为已逃逸，回收时不释放
+    pub fn complete(&self, outcome: Outcome) -> bool {
+        let outcome = outcome.map_err(as_exception);
+        if let Ok(value) = &outcome {
+            gc_escape(value);
+        }
+        let continuations = {
+            let mut inner = self.inner.lock();
+            if inner.outcome.is_some() {
+                return false;
+            }
+            inner.outcome = Some(outcome.clone());
+            std::mem::take(&mut inner.continuations)
+        };
+        self.done.notify_all();
+        // 在锁外执行回调，回调里可以再操作其他 Future
+        for continuation in continuations {
+            continuation(&outcome);
+        }
+        true
+    }
+
+    /// 阻塞到完成并返回结果
+    pub fn wait(&self) -> Outcome {
+        let mut inner = self.inner.lock();
+        loop {
+            if let Some(outcome) = &inner.outcome {
+                return outcome.clone();
+            }
+            self.done.wait(&mut inner);
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.inner.lock().outcome.is_some()
+    }
+
+    /// 注册完成回调；已经完成时立即执行
+    pub fn on_complete(&self, continuation: impl FnOnce(&Outcome) + Send + 'static) {
+        let outcome = {
+            let mut inner = self.inner.lock();
+            match &inner.outcome {
+                Some(outcome) => outcome.clone(),
+                None => {
+                    inner.continuations.push(Box::new(continuation));
+                    return;
+                }
+            }
+        };
+        continuation(&outcome);
+    }
+}
+
+/// 没有按异常格式编码的错误作为 RuntimeException；`Os.exit` 的请求原样保留，等待 Future 的一方随之退出
+fn as_exception(error: String) -> String {
+    if parse_stdlib_exception(&error).is_some() || super::os::parse_exit_request(&error).is_some() {
+        error
+    } else {
+        stdlib_exception("RuntimeException", error)
+    }
+}
+
+/// 创建未完成的 Future，返回 (实例, 共享状态)
+pub fn pending() -> (Value, Arc<FutureState>) {
+    let state = Arc::new(FutureState::new());
+
+    let mut fields = HashMap::new();
+    fields.insert("__handle".to_string(), Value::int(Arc::as_ptr(&state) as u64 as i128));
+    let instance = ClassInstance {
+        class_name: CLASS_FUTURE.to_string(),
+        parent_class: None,
+        fields,
+    };
+
+    let future = Value::class(Arc::new(Mutex::new(instance)));
+    gc_attach_native(&future, Box::new(state.clone()));
+    (future, state)
+}
+
+/// 在线程池中执行 work，立即返回代表其结果的 Future
+pub fn spawn(pool: &IoThreadPool, work: impl FnOnce() -> Outcome + Send + 'static) -> Value {
+    let (future, state) = pending();
+    pool.execute(move || {
+        state.complete(work());
+    });
+    future
+}
+
+/// 取出 Future 实例的共享状态
+pub fn future_state(value: &Value) -> Result<Arc<FutureState>, String> {
+    let ptr = value.as_class().and_then(|instance| {
+        let instance = instance.lock();
+        if instance.class_name != CLASS_FUTURE {
+            return None;
+        }
+        instance.fields.get("__handle").and_then(|v| v.as_int())
+    });
+    match ptr {
+        // SAFETY: 实例存活期间持有一份引用计数，指针有效
+        Some(ptr) => Ok(unsafe {
+            let ptr = ptr as u64 as *const FutureState;
+            Arc::increment_strong_count(ptr);
+            Arc::from_raw(ptr)
+        }),
+        None => Err(stdlib_exception(
+            "IllegalArgumentException",
+            format!("Expected a Future, got {}", value.type_name()),
+        )),
+    }
+}
+
+// ============================================================================
+// 定时器
+// ============================================================================
+
+/// 定时器线程：按截止时间完成 Future，所有 `Time.after` 共用一个线程
+fn timer() -> &'static Sender<(Instant, Arc<FutureState>)> {
+    static TIMER: OnceLock<Sender<(Instant, Arc<FutureState>)>> = OnceLock::new();
+    TIMER.get_or_init(|| {
+        let (sender, receiver) = crossbeam_channel::unbounded::<(Instant, Arc<FutureState>)>();
+        std::thread::Builder::new()
+            .name("timer".to_string())
+            .spawn(move || {
+                let mut waiting: Vec<(Instant, Arc<FutureState>)> = Vec::new();
+                loop {
+                    let next = waiting.iter().map(|(deadline, _)| *deadline).min();
+                    let received = match next {
+                        Some(deadline) => receiver.recv_deadline(deadline),
+                        None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                    };
+                    match received {
+                        Ok(entry) => waiting.push(entry),
+                        Err(RecvTimeoutError::Timeout) => {}
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                    let now = Instant::now();
+                    waiting.retain(|(deadline, state)| {
+                        if *deadline <= now {
+                            state.complete(Ok(Value::null()));
+                            false
+                        } else {
+                            true
+                        }
+                    });
+                }
+            })
+            .unwrap();
+        sender
+    })
+}
+
+/// 返回在 millis 毫秒后以 null 完成的 Future
+pub fn after(millis: u64) -> Value {
+    let (future, state) = pending();
+    let deadline = Instant::now() + Duration::from_millis(millis);
+    let _ = timer().send((deadline, state));
+    future
+}
+
+// ============================================================================
+// Future 方法
+// ============================================================================
+
+/// Future.all(futures: Future[]) -> Future
+/// 全部成功时按原顺序得到结果数组，任一失败时以第一个错误失败
+pub fn future_all(args: &[Value]) -> Result<Value, String> {
+    let states = future_array_arg(args, "Future.all")?;
+    let (future, result) = pending();
+    if states.is_empty() {
+        result.complete(Ok(Value::array(Arc::new(Mutex::new(Vec::new())))));
+        return Ok(future);
+    }
+
+    let values = Arc::new(Mutex::new(vec![Value::null(); states.len()]));
+    let remaining = Arc::new(AtomicUsize::new(states.len()));
+    for (index, state) in states.into_iter().enumerate() {
+        let values = values.clone();
+        let remaining = remaining.clone();
+        let result = result.clone();
+        state.on_complete(move |outcome| match outcome {
+            Ok(value) => {
+                values.lock()[index] = *value;
+                if remaining.fetch_sub(1, Ordering::AcqRel) == 1 {
+                    let values = std::mem::take(&mut *values.lock());
+                    result.complete(Ok(Value::array(Arc::new(Mutex::new(values)))));
+                }
+            }
+            Err(error) => {
+                result.complete(Err(error.clone()));
+            }
+        });
+    }
+    Ok(future)
+}
+
+/// Future.race(futures: Future[]) -> Future
+/// 以最先完成的 Future 的结果（成功或失败）完成
+pub fn future_race(args: &[Value]) -> Result<Value, String> {
+    let states = future_array_arg(args, "Future.race")?;
+    if states.is_empty() {
+        return Err(stdlib_exception("IllegalArgumentException", "Future.race requires at least one future"));
+    }
+
+    let (future, result) = pending();
+    for state in states {
+        let result = result.clone();
+        state.on_complete(move |outcome| {
+            result.complete(outcome.clone());
+        });
+    }
+    Ok(future)
+}
+
+fn future_array_arg(args: &[Value], func: &str) -> Result<Vec<Arc<FutureState>>, String> {
+    let array = args.first().and_then(|v| v.as_array()).ok_or_else(|| stdlib_exception(
+        "IllegalArgumentException",
+        format!("{} expects an array of futures", func),
+    ))?;
+    let futures = array.lock().clone();
+    futures.iter().map(future_state).collect()
+}
+
+/// future.await() -> 结果值，失败时抛出原来的异常
+pub fn future_await(instance: &Value) -> Result<Value, String> {
+    future_state(instance)?.wait()
+}
+
+/// future.isDone() -> bool
+pub fn future_is_done(instance: &Value) -> Result<Value, String> {
+    Ok(Value::bool(future_state(instance)?.is_done()))
+}
+
+/// future.then(fn) -> Future
+///
+/// 成功时在线程池中通过回调通道执行 fn(value)，新 Future 以 fn 的返回值完成；
+/// 失败时跳过 fn，新 Future 以同样的错误失败
+pub fn future_then(
+    pool: &Arc<IoThreadPool>,
+    instance: &Value,
+    args: &[Value],
+    callback_channel: Arc<CallbackChannel>,
+) -> Result<Value, String> {
+    let state = future_state(instance)?;
+    let handler = match args.first() {
+        Some(handler) if handler.as_function().is_some() => *handler,
+        _ => return Err(stdlib_exception("IllegalArgumentException", "Future.then expects a function")),
+    };
+    Ok(then(pool, &state, handler, callback_channel))
+}
+
+/// 在 state 成功完成后执行 handler(value)，返回以 handler 的返回值完成的 Future
+pub fn then(
+    pool: &Arc<IoThreadPool>,
+    state: &FutureState,
+    handler: Value,
+    callback_channel: Arc<CallbackChannel>,
+) -> Value {
+    chain(pool, state, handler, callback_channel, |value| vec![value])
+}
+
+/// 与 [`then`] 相同，但不带参数执行 handler()（`Time.after` 的回调）
+pub fn then_call(
+    pool: &Arc<IoThreadPool>,
+    state: &FutureState,
+    handler: Value,
+    callback_channel: Arc<CallbackChannel>,
+) -> Value {
+    chain(pool, state, handler, callback_channel, |_| vec![])
+}
+
+/// 在 state 成功完成后以 args(value) 为参数执行 handler
+///
+/// handler 在执行之前只被回调引用，标记为已逃逸，回收时不释放
+fn chain(
+    pool: &Arc<IoThreadPool>,
+    state: &FutureState,
+    handler: Value,
+    callback_channel: Arc<CallbackChannel>,
+    args: fn(Value) -> Vec<Value>,
+) -> Value {
+    gc_escape(&handler);
+    let (future, next) = pending();
+    let pool = pool.clone();
+    state.on_complete(move |outcome| match outcome {
+        Ok(value) => {
+            let args = args(*value);
+            pool.execute(move || {
+                next.complete(callback_channel.call(handler, args));
+            });
+        }
+        Err(error) => {
+            next.complete(Err(error.clone()));
+        }
+    });
+    future
+}
+
+// ============================================================================
+// AsyncLib - StdlibModule实现
+// ============================================================================
+
+/// std.async 标准库
+pub struct AsyncLib {
+    /// 执行 then 回调
+    thread_pool: Arc<IoThreadPool>,
+}
+
+impl Default for AsyncLib {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AsyncLib {
+    pub fn new() -> Self {
+        Self {
+            thread_pool: Arc::new(IoThreadPool::new(4)),
+        }
+    }
+}
+
+impl StdlibModule for AsyncLib {
+    fn name(&self) -> &'static str {
+        "std.async"
+    }
+
+    fn exports(&self) -> Vec<&'static str> {
+        vec!["Future_all", "Future_race"]
+    }
+
+    fn call(&self, name: &str, args: &[Value]) -> Result<Value, String> {
+        match name {
+            "Future_all" => future_all(args),
+            "Future_race" => future_race(args),
+            _ => Err(format!("Unknown function: {}", name)),
+        }
+    }
+
+    fn has_class(&self, class_name: &str) -> bool {
+        class_name == CLASS_FUTURE
+    }
+
+    fn create_class_instance(&self, class_name: &str, _args: &[Value]) -> Result<Value, String> {
+        Err(format!("{} cannot be constructed directly", class_name))
+    }
+
+    fn call_method(&self, instance: &Value, method_name: &str, _args: &[Value]) -> Result<Value, String> {
+        match method_name {
+            "await" => future_await(instance),
+            "isDone" => future_is_done(instance),
+            "then" => Err("Future.then requires callback support, use call_method_with_callback".to_string()),
+            _ => Err(format!("Future has no method '{}'", method_name)),
+        }
+    }
+
+    fn needs_callback(&self, class_name: &str, method_name: &str) -> bool {
+        class_name == CLASS_FUTURE && method_name == "then"
+    }
+
+    fn call_method_with_callback(
+        &self,
+        instance: &Value,
+        method_name: &str,
+        args: &[Value],
+        callback_channel: Arc<CallbackChannel>,
+    ) -> Result<Value, String> {
+        match method_name {
+            "then" => future_then(&self.thread_pool, instance, args, callback_channel),
+            _ => Err(format!("Method '{}' does not support callback", method_name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spawn_after(pool: &IoThreadPool, millis: u64, outcome: Outcome) -> Value {
+        spawn(pool, move || {
+            std::thread::sleep(Duration::from_millis(millis));
+            outcome
+        })
+    }
+
+    fn array(values: Vec<Value>) -> Value {
+        Value::array(Arc::new(Mutex::new(values)))
+    }
+
+    #[test]
+    fn test_all_keeps_input_order() {
+        let pool = IoThreadPool::new(4);
+        let futures = vec![
+            spawn_after(&pool, 60, Ok(Value::int(1))),
+            spawn_after(&pool, 10, Ok(Value::int(2))),
+            spawn_after(&pool, 30, Ok(Value::int(3))),
+        ];
+        let all = future_all(&[array(futures)]).unwrap();
+        let result = future_await(&all).unwrap();
+        let values: Vec<i128> = result.as_array().unwrap().lock().iter().map(|v| v.as_int().unwrap()).collect();
+        assert_eq!(values, vec![1, 2, 3]);
+
+        let empty = future_all(&[array(vec![])]).unwrap();
+        assert_eq!(future_await(&empty).unwrap().as_array().unwrap().lock().len(), 0);
+    }
+
+    #[test]
+    fn test_race_returns_first_completion() {
+        let pool = IoThreadPool::new(4);
+        let futures = vec![
+            spawn_after(&pool, 300, Ok(Value::int(1))),
+            spawn_after(&pool, 10, Ok(Value::int(2))),
+        ];
+        let race = future_race(&[array(futures)]).unwrap();
+        assert_eq!(future_await(&race).unwrap().as_int(), Some(2));
+        assert!(future_race(&[array(vec![])]).is_err());
+    }
+
+    #[test]
+    fn test_failure_keeps_exception_kind() {
+        let pool = IoThreadPool::new(2);
+        let failed = spawn_after(&pool, 5, Err(stdlib_exception("NetworkException", "connection refused")));
+        let error = future_await(&failed).unwrap_err();
+        assert_eq!(parse_stdlib_exception(&error), Some(("NetworkException", "connection refused")));
+
+        // all 以第一个错误失败，未编码的错误视为 RuntimeException
+        let futures = vec![spawn_after(&pool, 200, Ok(Value::int(1))), spawn_after(&pool, 5, Err("boom".to_string()))];
+        let all = future_all(&[array(futures)]).unwrap();
+        let error = future_await(&all).unwrap_err();
+        assert_eq!(parse_stdlib_exception(&error), Some(("RuntimeException", "boom")));
+    }
+
+    #[test]
+    fn test_after_completes_once_deadline_passes() {
+        let start = Instant::now();
+        let late = after(80);
+        let early = after(20);
+        assert!(!future_state(&late).unwrap().is_done());
+        future_await(&early).unwrap();
+        assert!(!future_state(&late).unwrap().is_done());
+        future_await(&late).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(80));
+        assert!(future_is_done(&late).unwrap().as_bool() == Some(true));
+    }
+}
@@ -0,0 +1,709 @@
+//! std.json 模块
+//!
+//! 提供 `Json.parse` 和 `Json.stringify`：
+//! - parse 将 JSON 文本解码为 map / 数组 / int / float / bool / null
+//! - stringify 将值编码为 JSON，支持数组、map、struct、类实例和枚举
+
+use super::StdlibModule;
+use super::convert::{self, ErrorKind};
+use super::exception::stdlib_exception;
+use crate::vm::value::Value;
+use crate::vm::MapData;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// 最大嵌套深度，防止恶意输入耗尽栈空间
+const MAX_DEPTH: usize = 512;
+
+/// 单个对象默认允许的最大键数，防止不可信输入构造超大 map
+pub const DEFAULT_MAX_OBJECT_KEYS: usize = 10_000;
+
+// ============================================================================
+// 解析
+// ============================================================================
+
+/// JSON 解析器（记录行列号用于错误报告）
+struct JsonParser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    column: usize,
+    depth: usize,
+    /// 单个对象允许的最大键数
+    max_object_keys: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn new(text: &'a str, max_object_keys: usize) -> Self {
+        Self {
+            chars: text.chars().peekable(),
+            line: 1,
+            column: 1,
+            depth: 0,
+            max_object_keys,
+        }
+    }
+
+    /// 在当前位置生成错误
+    fn error(&self, message: impl std::fmt::Display) -> String {
+        stdlib_exception(
+            "IllegalArgumentException",
+            format!("JSON parse error at line {}, column {}: {}", self.line, self.column, message),
+        )
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.advance();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.advance();
+                Ok(())
+            }
+            Some(c) => Err(self.error(format!("expected '{}', found '{}'", expected, c))),
+            None => Err(self.error(format!("expected '{}', found end of input", expected))),
+        }
+    }
+
+    /// 解析完整文档（值之后只允许空白）
+    fn parse_document(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        let value = self.parse_value()?;
+        self.skip_whitespace();
+        if let Some(c) = self.peek() {
+            return Err(self.error(format!("unexpected '{}' after JSON value", c)));
+        }
+        Ok(value)
+    }
+
+    fn parse_value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('{') => self.parse_object(),
+            Some('[') => self.parse_array(),
+            Some('"') => Ok(Value::string(self.parse_string()?)),
+            Some('t') => self.parse_keyword("true", Value::bool(true)),
+            Some('f') => self.parse_keyword("false", Value::bool(false)),
+            Some('n') => self.parse_keyword("null", Value::null()),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.parse_number(),
+            Some(c) => Err(self.error(format!("unexpected character '{}'", c))),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn parse_keyword(&mut self, keyword: &str, value: Value) -> Result<Value, String> {
+        for expected in keyword.chars() {
+            if self.peek() != Some(expected) {
+                return Err(self.error(format!("invalid literal, expected '{}'", keyword)));
+            }
+            self.advance();
+        }
+        Ok(value)
+    }
+
+    fn enter(&mut self) -> Result<(), String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.error(format!("nesting deeper than {} levels", MAX_DEPTH)));
+        }
+        Ok(())
+    }
+
+    fn parse_object(&mut self) -> Result<Value, String> {
+        self.enter()?;
+        self.advance(); // 消费 '{'
+        let mut map = MapData::default();
+
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.advance();
+        } else {
+            loop {
+                self.skip_whitespace();
+                if self.peek() != Some('"') {
+                    return Err(self.error("expected string key"));
+                }
+                let key = self.parse_string()?;
+                self.skip_whitespace();
+                self.expect(':')?;
+                self.skip_whitespace();
+                let value = self.parse_value()?;
+                map.insert(key, value);
+                if map.len() > self.max_object_keys {
+                    return Err(self.error(format!("object has more than {} keys", self.max_object_keys)));
+                }
+
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(',') => {
+                        self.advance();
+                    }
+                    Some('}') => {
+                        self.advance();
+                        break;
+                    }
+                    Some(c) => return Err(self.error(format!("expected ',' or '}}', found '{}'", c))),
+                    None => return Err(self.error("unterminated object")),
+                }
+            }
+        }
+
+        self.depth -= 1;
+        Ok(Value::map(Arc::new(Mutex::new(map))))
+    }
+
+    fn parse_array(&mut self) -> Result<Value, String> {
+        self.enter()?;
+        self.advance(); // 消费 '['
+        let mut items = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.advance();
+        } else {
+            loop {
+                self.skip_whitespace();
+                items.push(self.parse_value()?);
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(',') => {
+                        self.advance();
+                    }
+                    Some(']') => {
+                        self.advance();
+                        break;
+                    }
+                    Some(c) => return Err(self.error(format!("expected ',' or ']', found '{}'", c))),
+                    None => return Err(self.error("unterminated array")),
+                }
+            }
+        }
+
+        self.depth -= 1;
+        Ok(Value::array(Arc::new(Mutex::new(items))))
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.advance(); // 消费 '"'
+        let mut result = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error("unterminated string")),
+                Some('"') => {
+                    self.advance();
+                    return Ok(result);
+                }
+                Some('\\') => {
+                    self.advance();
+                    let escaped = match self.peek() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{08}',
+                        Some('f') => '\u{0C}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            self.advance();
+                            result.push(self.parse_unicode_escape()?);
+                            continue;
+                        }
+                        Some(c) => return Err(self.error(format!("invalid escape '\\{}'", c))),
+                        None => return Err(self.error("unterminated string")),
+                    };
+                    self.advance();
+                    result.push(escaped);
+                }
+                Some(c) if (c as u32) < 0x20 => {
+                    return Err(self.error("control character in string"));
+                }
+                Some(c) => {
+                    self.advance();
+                    result.push(c);
+                }
+            }
+        }
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, String> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self.peek()
+                .and_then(|c| c.to_digit(16))
+                .ok_or_else(|| self.error("invalid \\u escape"))?;
+            self.advance();
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    /// 解析 \uXXXX（已消费 "\u"），支持 UTF-16 代理对
+    fn parse_unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.parse_hex4()?;
+        if (0xD800..0xDC00).contains(&high) {
+            if self.peek() != Some('\\') {
+                return Err(self.error("unpaired surrogate in \\u escape"));
+            }
+            self.advance();
+            if self.peek() != Some('u') {
+                return Err(self.error("unpaired surrogate in \\u escape"));
+            }
+            self.advance();
+            let low = self.parse_hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error("invalid low surrogate in \\u escape"));
+            }
+            let code = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+            return char::from_u32(code).ok_or_else(|| self.error("invalid \\u escape"));
+        }
+        char::from_u32(high).ok_or_else(|| self.error("unpaired surrogate in \\u escape"))
+    }
+
+    /// 按 RFC 8259 的语法读取数字：`-? int frac? exp?`
+    ///
+    /// int 是 0 或不以 0 开头的数字串，frac 是小数点后至少一位数字，exp 是 `e`/`E`、可选的符号和至少一位数字
+    fn parse_number(&mut self) -> Result<Value, String> {
+        let (line, column) = (self.line, self.column);
+        let mut text = String::new();
+        let mut is_float = false;
+        let invalid = |text: &str| stdlib_exception(
+            "IllegalArgumentException",
+            format!("JSON parse error at line {}, column {}: invalid number '{}'", line, column, text),
+        );
+
+        if self.peek() == Some('-') {
+            text.push('-');
+            self.advance();
+        }
+        if self.peek() == Some('0') {
+            text.push('0');
+            self.advance();
+            // 不允许多余的前导零（01、-00）
+            if self.take_digits(&mut text) {
+                return Err(invalid(&text));
+            }
+        } else if !self.take_digits(&mut text) {
+            return Err(self.error("expected digit"));
+        }
+        if self.peek() == Some('.') {
+            is_float = true;
+            text.push('.');
+            self.advance();
+            if !self.take_digits(&mut text) {
+                return Err(invalid(&text));
+            }
+        }
+        if let Some(e @ ('e' | 'E')) = self.peek() {
+            is_float = true;
+            text.push(e);
+            self.advance();
+            if let Some(sign @ ('+' | '-')) = self.peek() {
+                text.push(sign);
+                self.advance();
+            }
+            if !self.take_digits(&mut text) {
+                return Err(invalid(&text));
+            }
+        }
+
+        // 上面已经按 JSON 的语法取出了数字，转换规则与 std.convert 相同
+        let float = || convert::parse_float(&text).map(Value::float).map_err(|_| invalid(&text));
+        if is_float {
+            float()
+        } else {
+            match convert::parse_int(&text) {
+                Ok(n) => Ok(Value::int(n as i128)),
+                // 超出 int 范围时退化为浮点数
+                Err(e) if e.kind == ErrorKind::Overflow => float(),
+                Err(_) => Err(invalid(&text)),
+            }
+        }
+    }
+
+    /// 读取连续的数字追加到 text，返回是否至少读到一位
+    fn take_digits(&mut self, text: &mut String) -> bool {
+        let start = text.len();
+        while let Some(c @ '0'..='9') = self.peek() {
+            text.push(c);
+            self.advance();
+        }
+        text.len() > start
+    }
+}
+
+/// Json.parse(text: string, maxKeys?: int) -> dynamic
+///
+/// 可选的第二个参数限制单个对象的最大键数（默认 [`DEFAULT_MAX_OBJECT_KEYS`]）
+pub fn parse(args: &[Value]) -> Result<Value, String> {
+    let text = args.first()
+        .and_then(|v| v.as_string())
+        .ok_or_else(|| stdlib_exception("IllegalArgumentException", "Json.parse expects a string"))?;
+    let max_object_keys = match args.get(1) {
+        None => DEFAULT_MAX_OBJECT_KEYS,
+        Some(v) => v.as_int()
+            .filter(|n| *n > 0)
+            .and_then(|n| usize::try_from(n).ok())
+            .ok_or_else(|| stdlib_exception("IllegalArgumentException", "Json.parse maxKeys must be a positive int"))?,
+    };
+    JsonParser::new(text, max_object_keys).parse_document()
+}
+
+// ============================================================================
+// 序列化
+// ============================================================================
+
+/// 把 `s` 编码为带引号的 JSON 字符串追加到 `out`
+pub fn write_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{08}' => out.push_str("\\b"),
+            '\u{0C}' => out.push_str("\\f"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// JSON 序列化器
+struct JsonWriter {
+    out: String,
+    pretty: bool,
+    /// 正在序列化的容器地址，用于检测循环引用
+    visiting: Vec<usize>,
+}
+
+impl JsonWriter {
+    fn error(message: impl std::fmt::Display) -> String {
+        stdlib_exception("IllegalArgumentException", format!("Json.stringify: {}", message))
+    }
+
+    /// 标记容器进入；已在路径上则说明存在循环引用
+    fn enter<T>(&mut self, container: &Arc<T>) -> Result<(), String> {
+        let addr = Arc::as_ptr(container) as *const u8 as usize;
+        if self.visiting.contains(&addr) {
+            return Err(Self::error("cyclic reference detected"));
+        }
+        self.visiting.push(addr);
+        Ok(())
+    }
+
+    fn leave(&mut self) {
+        self.visiting.pop();
+    }
+
+    fn newline(&mut self, indent: usize) {
+        if self.pretty {
+            self.out.push('\n');
+            for _ in 0..indent {
+                self.out.push_str("  ");
+            }
+        }
+    }
+
+    fn write_string(&mut self, s: &str) {
+        write_json_string(&mut self.out, s);
+    }
+
+    fn write_array(&mut self, items: &[Value], indent: usize) -> Result<(), String> {
+        if items.is_empty() {
+            self.out.push_str("[]");
+            return Ok(());
+        }
+        self.out.push('[');
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                self.out.push(',');
+            }
+            self.newline(indent + 1);
+            self.write_value(item, indent + 1)?;
+        }
+        self.newline(indent);
+        self.out.push(']');
+        Ok(())
+    }
+
+    /// 写入对象（按键排序，保证输出稳定；跳过 "__" 开头的内部字段）
+    fn write_object<S: std::hash::BuildHasher>(&mut self, fields: &HashMap<String, Value, S>, indent: usize) -> Result<(), String> {
+        let mut keys: Vec<&String> = fields.keys().filter(|k| !k.starts_with("__")).collect();
+        if keys.is_empty() {
+            self.out.push_str("{}");
+            return Ok(());
+        }
+        keys.sort();
+        self.out.push('{');
+        for (i, key) in keys.into_iter().enumerate() {
+            if i > 0 {
+                self.out.push(',');
+            }
+            self.newline(indent + 1);
+            self.write_string(key);
+            self.out.push(':');
+            if self.pretty {
+                self.out.push(' ');
+            }
+            self.write_value(&fields[key], indent + 1)?;
+        }
+        self.newline(indent);
+        self.out.push('}');
+        Ok(())
+    }
+
+    fn write_value(&mut self, value: &Value, indent: usize) -> Result<(), String> {
+        if value.is_null() {
+            self.out.push_str("null");
+        } else if let Some(b) = value.as_bool() {
+            self.out.push_str(if b { "true" } else { "false" });
+        } else if let Some(n) = value.as_int() {
+            let _ = write!(self.out, "{}", n);
+        } else if let Some(f) = value.as_float() {
+            if !f.is_finite() {
+                return Err(Self::error("cannot encode NaN or Infinity"));
+            }
+            // 保留小数点，解析回来仍是浮点数
+            if f.fract() == 0.0 && f.abs() < 1e16 {
+                let _ = write!(self.out, "{:.1}", f);
+            } else {
+                let _ = write!(self.out, "{}", f);
+            }
+        } else if let Some(c) = value.as_char() {
+            self.write_string(&c.to_string());
+        } else if let Some(s) = value.as_string() {
+            self.write_string(s);
+        } else if let Some((arr, start, end)) = value.as_array_slice() {
+            self.enter(arr)?;
+            let items = arr.lock()[start..end].to_vec();
+            let result = self.write_array(&items, indent);
+            self.leave();
+            result?;
+        } else if let Some(arr) = value.as_array().or_else(|| value.as_set()) {
+            self.enter(arr)?;
+            // 先复制再释放锁，嵌套元素可能引用同一个容器
+            let items = arr.lock().clone();
+            let result = self.write_array(&items, indent);
+            self.leave();
+            result?;
+        } else if let Some(map) = value.as_map() {
+            self.enter(map)?;
+            let fields = map.lock().clone();
+            let result = self.write_object(&fields, indent);
+            self.leave();
+            result?;
+        } else if let Some(s) = value.as_struct() {
+            self.enter(s)?;
+            let fields = s.lock().fields.clone();
+            let result = self.write_object(&fields, indent);
+            self.leave();
+            result?;
+        } else if let Some(c) = value.as_class() {
+            self.enter(c)?;
+            let fields = c.lock().fields.clone();
+            let result = self.write_object(&fields, indent);
+            self.leave();
+            result?;
+        } else if let Some(e) = value.as_enum() {
+            // 有值的变体输出值，无数据的变体输出名称，带关联数据的变体输出对象
+            if let Some(v) = &e.value {
+                self.write_value(v, indent)?;
+            } else if e.associated_data.is_empty() {
+                self.write_string(&e.variant_name);
+            } else {
+                let mut fields = e.associated_data.clone();
+                fields.insert("variant".to_string(), Value::string(e.variant_name.clone()));
+                self.write_object(&fields, indent)?;
+            }
+        } else {
+            return Err(Self::error(format!("cannot encode value of type {}", value.type_name())));
+        }
+        Ok(())
+    }
+}
+
+/// Json.stringify(value, pretty: bool = false) -> string
+pub fn stringify(args: &[Value]) -> Result<Value, String> {
+    let value = args.first()
+        .ok_or_else(|| stdlib_exception("IllegalArgumentException", "Json.stringify expects a value"))?;
+    let pretty = args.get(1).and_then(|v| v.as_bool()).unwrap_or(false);
+
+    let mut writer = JsonWriter {
+        out: String::new(),
+        pretty,
+        visiting: Vec::new(),
+    };
+    writer.write_value(value, 0)?;
+    Ok(Value::string(writer.out))
+}
+
+// ============================================================================
+// JsonLib - JSON 标准库模块
+// ============================================================================
+
+#[derive(Default)]
+pub struct JsonLib;
+
+impl JsonLib {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl StdlibModule for JsonLib {
+    fn name(&self) -> &'static str {
+        "std.json"
+    }
+
+    fn exports(&self) -> Vec<&'static str> {
+        vec![
+            "Json_parse",
+            "Json_stringify",
+        ]
+    }
+
+    fn call(&self, name: &str, args: &[Value]) -> Result<Value, String> {
+        match name {
+            "Json_parse" => parse(args),
+            "Json_stringify" => stringify(args),
+            _ => Err(format!("Unknown function: {}", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stdlib::exception::parse_stdlib_exception;
+
+    fn parse_str(text: &str) -> Result<Value, String> {
+        parse(&[Value::string(text.to_string())])
+    }
+
+    fn stringify_value(value: &Value, pretty: bool) -> Result<String, String> {
+        stringify(&[*value, Value::bool(pretty)]).map(|v| v.as_string().unwrap().clone())
+    }
+
+    #[test]
+    fn test_parse_values() {
+        let value = parse_str(r#"{"a": [1, 2.5, true, null], "b": {"c": "xé\n"}, "big": 1e3}"#).unwrap();
+        let map = value.as_map().unwrap().lock();
+        let a = map["a"].as_array().unwrap().lock();
+        assert_eq!(a[0].as_int(), Some(1));
+        assert_eq!(a[1].as_float(), Some(2.5));
+        assert_eq!(a[2].as_bool(), Some(true));
+        assert!(a[3].is_null());
+        let b = map["b"].as_map().unwrap().lock();
+        assert_eq!(b["c"].as_string().unwrap(), "xé\n");
+        assert_eq!(map["big"].as_float(), Some(1000.0));
+    }
+
+    #[test]
+    fn test_parse_error_position() {
+        let err = parse_str("{\n  \"a\": 1,\n  \"b\": tru\n}").unwrap_err();
+        let (class_name, message) = parse_stdlib_exception(&err).unwrap();
+        assert_eq!(class_name, "IllegalArgumentException");
+        assert!(message.contains("line 3, column 11"), "{}", message);
+
+        let err = parse_str("[1, 2").unwrap_err();
+        assert!(err.contains("line 1, column 6"), "{}", err);
+        assert!(parse_str("[1] x").is_err());
+        assert!(parse_str(&"[".repeat(MAX_DEPTH + 1)).is_err());
+    }
+
+    #[test]
+    fn test_parse_number_grammar() {
+        for text in ["1.", "1.e5", "1e", "1e+", "-", ".5", "[1., 2]", "-.5", "+1"] {
+            assert!(parse_str(text).is_err(), "{} should be rejected", text);
+        }
+        let err = parse_str("1.e5").unwrap_err();
+        assert!(err.contains("invalid number '1.'"), "{}", err);
+        let value = parse_str("[1.5e3, 2E-2, -3e+1, 0.0]").unwrap();
+        let items = value.as_array().unwrap().lock();
+        assert_eq!(items[0].as_float(), Some(1500.0));
+        assert_eq!(items[1].as_float(), Some(0.02));
+        assert_eq!(items[2].as_float(), Some(-30.0));
+        assert_eq!(items[3].as_float(), Some(0.0));
+    }
+
+    #[test]
+    fn test_parse_rejects_leading_zeros() {
+        for text in ["01", "-00", "[1, 007]", "00.5"] {
+            let err = parse_str(text).unwrap_err();
+            assert!(err.contains("invalid number"), "{}: {}", text, err);
+        }
+        let value = parse_str("[0, -0, 0.5, -0.25, 0e2, 10]").unwrap();
+        let items = value.as_array().unwrap().lock();
+        assert_eq!(items[0].as_int(), Some(0));
+        assert_eq!(items[1].as_int(), Some(0));
+        assert_eq!(items[2].as_float(), Some(0.5));
+        assert_eq!(items[3].as_float(), Some(-0.25));
+        assert_eq!(items[4].as_float(), Some(0.0));
+        assert_eq!(items[5].as_int(), Some(10));
+    }
+
+    #[test]
+    fn test_parse_object_key_limit() {
+        let text = r#"{"a": 1, "b": {"c": 2, "d": 3}}"#;
+        assert!(parse(&[Value::string(text.to_string()), Value::int(2)]).is_ok());
+
+        let err = parse(&[Value::string(r#"{"a": 1, "b": 2, "c": 3}"#.to_string()), Value::int(2)]).unwrap_err();
+        let (class_name, message) = parse_stdlib_exception(&err).unwrap();
+        assert_eq!(class_name, "IllegalArgumentException");
+        assert!(message.contains("more than 2 keys"), "{}", message);
+
+        let many: Vec<String> = (0..=DEFAULT_MAX_OBJECT_KEYS).map(|i| format!("\"k{}\": {}", i, i)).collect();
+        assert!(parse_str(&format!("{{{}}}", many.join(","))).is_err());
+        assert!(parse(&[Value::string("{}".to_string()), Value::int(0)]).is_err());
+    }
+
+    #[test]
+    fn test_stringify_round_trip() {
+        let text = r#"{"list":[1,2.0,"a\"b"],"n":null,"ok":true}"#;
+        let value = parse_str(text).unwrap();
+        assert_eq!(stringify_value(&value, false).unwrap(), text);
+        assert_eq!(
+            stringify_value(&parse_str("[1,{}]").unwrap(), true).unwrap(),
+            "[\n  1,\n  {}\n]"
+        );
+    }
+
+    #[test]
+    fn test_stringify_detects_cycles() {
+        let arr = Arc::new(Mutex::new(Vec::new()));
+        let value = Value::array(arr.clone());
+        arr.lock().push(value);
+        let err = stringify_value(&value, false).unwrap_err();
+        assert!(err.contains("cyclic reference"));
+
+        // 同一个容器出现两次但不成环不算循环
+        let shared = Value::array(Arc::new(Mutex::new(vec![Value::int(1)])));
+        let outer = Value::array(Arc::new(Mutex::new(vec![shared, shared])));
+        assert_eq!(stringify_value(&outer, false).unwrap(), "[[1],[1]]");
+    }
+}
@@ -0,0 +1,125 @@
+//! std.log 日志模块
+//!
+//! 提供 `Log.debug/info/warn/error(message)`，每条记录写成一行，默认输出到标准错误，
+//! `Log.setOutput(path)` 改为追加到文件。
+//!
+//! HttpServer 的 handler 执行期间，当前线程记录着请求 ID，这期间写出的记录会附加 `request_id=<id>`。
+
+use std::cell::RefCell;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+
+use parking_lot::Mutex;
+
+use super::StdlibModule;
+use super::exception::stdlib_exception;
+use crate::vm::value::Value;
+
+/// 日志输出文件，None 时写标准错误
+static OUTPUT: Mutex<Option<File>> = Mutex::new(None);
+
+thread_local! {
+    /// 当前线程正在处理的请求 ID
+    static REQUEST_ID: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// 在记录着请求 ID 的情况下执行 f，结束后恢复原来的请求 ID
+pub fn with_request_id<R>(id: Option<String>, f: impl FnOnce() -> R) -> R {
+    let previous = REQUEST_ID.with(|current| current.replace(id));
+    let result = f();
+    REQUEST_ID.with(|current| *current.borrow_mut() = previous);
+    result
+}
+
+/// 当前线程正在处理的请求 ID
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.with(|current| current.borrow().clone())
+}
+
+/// 格式化一条日志记录（不含换行）
+fn format_record(level: &str, message: &str) -> String {
+    match current_request_id() {
+        Some(id) => format!("[{}] {} request_id={}", level, message, id),
+        None => format!("[{}] {}", level, message),
+    }
+}
+
+/// Log.debug/info/warn/error(message: string) -> null
+fn log_record(level: &str, args: &[Value]) -> Result<Value, String> {
+    let message = match args.first() {
+        Some(value) => value.as_string().cloned().unwrap_or_else(|| value.to_string()),
+        None => return Err(format!("Log.{} requires 1 argument: message", level.to_lowercase())),
+    };
+    let line = format!("{}\n", format_record(level, &message));
+    // 整行一次写出，并发的记录不会交错
+    match OUTPUT.lock().as_mut() {
+        Some(file) => file.write_all(line.as_bytes()),
+        None => std::io::stderr().write_all(line.as_bytes()),
+    }
+    .map_err(|e| stdlib_exception("IOException", format!("Failed to write log record: {}", e)))?;
+    Ok(Value::null())
+}
+
+/// Log.setOutput(path?: string) -> null
+/// 追加写入 path；省略或传 null 时恢复为标准错误
+pub fn log_set_output(args: &[Value]) -> Result<Value, String> {
+    let file = match args.first().filter(|v| !v.is_null()) {
+        Some(path) => {
+            let path = path.as_string()
+                .ok_or_else(|| stdlib_exception("IllegalArgumentException", "Log.setOutput expects a file path"))?;
+            let file = OpenOptions::new().create(true).append(true).open(path)
+                .map_err(|e| stdlib_exception("IOException", format!("Failed to open {}: {}", path, e)))?;
+            Some(file)
+        }
+        None => None,
+    };
+    *OUTPUT.lock() = file;
+    Ok(Value::null())
+}
+
+/// std.log 标准库
+#[derive(Default)]
+pub struct LogLib;
+
+impl LogLib {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl StdlibModule for LogLib {
+    fn name(&self) -> &'static str {
+        "std.log"
+    }
+
+    fn exports(&self) -> Vec<&'static str> {
+        vec!["Log_debug", "Log_info", "Log_warn", "Log_error", "Log_setOutput"]
+    }
+
+    fn call(&self, name: &str, args: &[Value]) -> Result<Value, String> {
+        match name {
+            "Log_debug" => log_record("DEBUG", args),
+            "Log_info" => log_record("INFO", args),
+            "Log_warn" => log_record("WARN", args),
+            "Log_error" => log_record("ERROR", args),
+            "Log_setOutput" => log_set_output(args),
+            _ => Err(format!("Unknown function: {}", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_carry_request_id() {
+        assert_eq!(format_record("INFO", "start"), "[INFO] start");
+        let inside = with_request_id(Some("abc".to_string()), || {
+            let nested = with_request_id(Some("def".to_string()), || format_record("WARN", "nested"));
+            (nested, format_record("INFO", "outer"))
+        });
+        assert_eq!(inside, ("[WARN] nested request_id=def".to_string(), "[INFO] outer request_id=abc".to_string()));
+        assert_eq!(current_request_id(), None);
+    }
+}
@@ -0,0 +1,279 @@
+//! std.math 数学函数和常量
+//!
+//! 参数的 int / float 规则与算术运算一致：
+//!
+//! - `abs`、`min`、`max`、`floor`、`ceil`、`round`、`trunc` 的参数都是整数时结果是 `int`（取整函数原样返回），
+//!   有浮点数时结果是 `f64`
+//! - 其余函数（`sqrt`、`log`、三角函数等）总是返回 `f64`，整数参数先转换为浮点数
+//! - 浮点运算遵循 IEEE 754：定义域外的参数得到 NaN（如 `sqrt(-1)`），`log(0)` 是 `-inf`，不抛出异常；
+//!   `min` / `max` 的参数中有 NaN 时结果是 NaN
+//!
+//! 常量 `Math.PI`、`Math.E`、`Math.INFINITY`、`Math.NAN` 在编译时写入常量池。
+
+use super::exception::stdlib_exception;
+use super::StdlibModule;
+use crate::vm::value::Value;
+
+/// 数值参数：保留整数和浮点数的区别
+#[derive(Debug, Clone, Copy)]
+enum Number {
+    Int(i128),
+    Float(f64),
+}
+
+impl Number {
+    fn to_f64(self) -> f64 {
+        match self {
+            Number::Int(n) => n as f64,
+            Number::Float(x) => x,
+        }
+    }
+
+    fn into_value(self) -> Value {
+        match self {
+            Number::Int(n) => Value::int(n),
+            Number::Float(x) => Value::float(x),
+        }
+    }
+}
+
+/// 取第 index 个参数，必须是数字
+fn number(function: &str, args: &[Value], index: usize) -> Result<Number, String> {
+    let value = args.get(index).ok_or_else(|| {
+        stdlib_exception("IllegalArgumentException", format!("Math.{} expects {} argument(s)", function, index + 1))
+    })?;
+    if let Some(n) = value.as_int() {
+        Ok(Number::Int(n))
+    } else if let Some(x) = value.as_float() {
+        Ok(Number::Float(x))
+    } else {
+        Err(stdlib_exception(
+            "IllegalArgumentException",
+            format!("Math.{} expects a number, got {}", function, value.type_name()),
+        ))
+    }
+}
+
+/// 只接受浮点结果的函数：整数参数先转换为 f64
+fn float_fn(function: &str, args: &[Value], f: fn(f64) -> f64) -> Result<Value, String> {
+    Ok(Value::float(f(number(function, args, 0)?.to_f64())))
+}
+
+fn abs(args: &[Value]) -> Result<Value, String> {
+    match number("abs", args, 0)? {
+        Number::Int(n) => n
+            .checked_abs()
+            .map(Value::int)
+            .ok_or_else(|| stdlib_exception("ArithmeticException", "integer overflow in Math.abs")),
+        Number::Float(x) => Ok(Value::float(x.abs())),
+    }
+}
+
+/// min / max：两个整数比较得到整数，否则按浮点数比较；有 NaN 时结果是 NaN
+fn min_max(function: &str, args: &[Value], min: bool) -> Result<Value, String> {
+    let a = number(function, args, 0)?;
+    let b = number(function, args, 1)?;
+    let result = match (a, b) {
+        (Number::Int(x), Number::Int(y)) => Number::Int(if min { x.min(y) } else { x.max(y) }),
+        _ => {
+            let (x, y) = (a.to_f64(), b.to_f64());
+            if x.is_nan() || y.is_nan() {
+                Number::Float(f64::NAN)
+            } else {
+                Number::Float(if min { x.min(y) } else { x.max(y) })
+            }
+        }
+    };
+    Ok(result.into_value())
+}
+
+/// floor / ceil / trunc：整数原样返回
+fn integral(function: &str, args: &[Value], f: fn(f64) -> f64) -> Result<Value, String> {
+    Ok(match number(function, args, 0)? {
+        Number::Int(n) => Value::int(n),
+        Number::Float(x) => Value::float(f(x)),
+    })
+}
+
+/// round(x, digits = 0)：保留 digits 位小数，负数表示舍入到十位、百位……；一半时远离 0 舍入
+fn round(args: &[Value]) -> Result<Value, String> {
+    let x = number("round", args, 0)?;
+    let digits = match args.get(1) {
+        None => 0,
+        Some(value) => value.as_int().ok_or_else(|| {
+            stdlib_exception("IllegalArgumentException", format!("Math.round expects int digits, got {}", value.type_name()))
+        })?,
+    };
+    Ok(match x {
+        Number::Int(n) => Value::int(round_int(n, digits)),
+        Number::Float(x) => Value::float(round_float(x, digits)),
+    })
+}
+
+fn round_int(n: i128, digits: i128) -> i128 {
+    if digits >= 0 {
+        return n;
+    }
+    // 10 的幂超过 int 范围时结果总是 0
+    let Some(unit) = u32::try_from(-digits).ok().and_then(|exp| 10i128.checked_pow(exp)) else {
+        return 0;
+    };
+    let rounded = (n.abs() + unit / 2) / unit * unit;
+    if n < 0 { -rounded } else { rounded }
+}
+
+fn round_float(x: f64, digits: i128) -> f64 {
+    if !x.is_finite() {
+        return x;
+    }
+    let exp = digits.clamp(-400, 400) as i32;
+    if exp >= 0 {
+        let scale = 10f64.powi(exp);
+        let scaled = x * scale;
+        // 已经没有更多的小数位
+        if !scaled.is_finite() || scaled.abs() >= 2f64.powi(52) {
+            return x;
+        }
+        scaled.round() / scale
+    } else {
+        let scale = 10f64.powi(-exp);
+        (x / scale).round() * scale
+    }
+}
+
+fn is_nan(args: &[Value]) -> Result<Value, String> {
+    Ok(Value::bool(matches!(number("isNaN", args, 0)?, Number::Float(x) if x.is_nan())))
+}
+
+fn is_finite(args: &[Value]) -> Result<Value, String> {
+    Ok(Value::bool(match number("isFinite", args, 0)? {
+        Number::Int(_) => true,
+        Number::Float(x) => x.is_finite(),
+    }))
+}
+
+fn binary_float(function: &str, args: &[Value], f: fn(f64, f64) -> f64) -> Result<Value, String> {
+    let a = number(function, args, 0)?.to_f64();
+    let b = number(function, args, 1)?.to_f64();
+    Ok(Value::float(f(a, b)))
+}
+
+/// std.math 标准库
+#[derive(Default)]
+pub struct MathLib;
+
+impl MathLib {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl StdlibModule for MathLib {
+    fn name(&self) -> &'static str {
+        "std.math"
+    }
+
+    fn exports(&self) -> Vec<&'static str> {
+        vec![
+            "Math_PI", "Math_E", "Math_INFINITY", "Math_NAN",
+            "Math_abs", "Math_min", "Math_max",
+            "Math_floor", "Math_ceil", "Math_round", "Math_trunc",
+            "Math_sqrt", "Math_cbrt", "Math_pow", "Math_hypot",
+            "Math_exp", "Math_log", "Math_log2", "Math_log10",
+            "Math_sin", "Math_cos", "Math_tan", "Math_asin", "Math_acos", "Math_atan", "Math_atan2",
+            "Math_isNaN", "Math_isFinite",
+        ]
+    }
+
+    fn constant(&self, name: &str) -> Option<Value> {
+        match name {
+            "Math_PI" => Some(Value::float(std::f64::consts::PI)),
+            "Math_E" => Some(Value::float(std::f64::consts::E)),
+            "Math_INFINITY" => Some(Value::float(f64::INFINITY)),
+            "Math_NAN" => Some(Value::float(f64::NAN)),
+            _ => None,
+        }
+    }
+
+    fn call(&self, name: &str, args: &[Value]) -> Result<Value, String> {
+        if let Some(value) = self.constant(name) {
+            return Ok(value);
+        }
+        match name {
+            "Math_abs" => abs(args),
+            "Math_min" => min_max("min", args, true),
+            "Math_max" => min_max("max", args, false),
+            "Math_floor" => integral("floor", args, f64::floor),
+            "Math_ceil" => integral("ceil", args, f64::ceil),
+            "Math_round" => round(args),
+            "Math_trunc" => integral("trunc", args, f64::trunc),
+            "Math_sqrt" => float_fn("sqrt", args, f64::sqrt),
+            "Math_cbrt" => float_fn("cbrt", args, f64::cbrt),
+            "Math_pow" => binary_float("pow", args, f64::powf),
+            "Math_hypot" => binary_float("hypot", args, f64::hypot),
+            "Math_exp" => float_fn("exp", args, f64::exp),
+            "Math_log" => float_fn("log", args, f64::ln),
+            "Math_log2" => float_fn("log2", args, f64::log2),
+            "Math_log10" => float_fn("log10", args, f64::log10),
+            "Math_sin" => float_fn("sin", args, f64::sin),
+            "Math_cos" => float_fn("cos", args, f64::cos),
+            "Math_tan" => float_fn("tan", args, f64::tan),
+            "Math_asin" => float_fn("asin", args, f64::asin),
+            "Math_acos" => float_fn("acos", args, f64::acos),
+            "Math_atan" => float_fn("atan", args, f64::atan),
+            "Math_atan2" => binary_float("atan2", args, f64::atan2),
+            "Math_isNaN" => is_nan(args),
+            "Math_isFinite" => is_finite(args),
+            _ => Err(format!("Unknown function: {}", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, args: &[Value]) -> Value {
+        MathLib::new().call(name, args).unwrap()
+    }
+
+    #[test]
+    fn test_int_and_float_results() {
+        assert_eq!(call("Math_abs", &[Value::int(-3)]).as_int(), Some(3));
+        assert_eq!(call("Math_abs", &[Value::float(-2.5)]).as_float(), Some(2.5));
+        assert_eq!(call("Math_min", &[Value::int(4), Value::int(2)]).as_int(), Some(2));
+        assert_eq!(call("Math_max", &[Value::int(1), Value::float(2.5)]).as_float(), Some(2.5));
+        assert_eq!(call("Math_min", &[Value::int(1), Value::float(2.5)]).as_float(), Some(1.0));
+        assert_eq!(call("Math_floor", &[Value::int(7)]).as_int(), Some(7));
+        assert_eq!(call("Math_floor", &[Value::float(-1.5)]).as_float(), Some(-2.0));
+        assert_eq!(call("Math_sqrt", &[Value::int(9)]).as_float(), Some(3.0));
+        assert!(MathLib::new().call("Math_abs", &[Value::int(i128::MIN)]).unwrap_err().starts_with("ArithmeticException: "));
+        assert!(MathLib::new().call("Math_sqrt", &[Value::string("x".to_string())]).unwrap_err().starts_with("IllegalArgumentException: "));
+    }
+
+    #[test]
+    fn test_nan_and_infinity() {
+        assert!(call("Math_sqrt", &[Value::int(-1)]).as_float().unwrap().is_nan());
+        assert_eq!(call("Math_log", &[Value::int(0)]).as_float(), Some(f64::NEG_INFINITY));
+        assert!(call("Math_max", &[Value::float(f64::NAN), Value::int(1)]).as_float().unwrap().is_nan());
+        assert!(call("Math_isNaN", &[Value::float(f64::NAN)]).as_bool().unwrap());
+        assert!(!call("Math_isNaN", &[Value::int(1)]).as_bool().unwrap());
+        assert!(!call("Math_isFinite", &[Value::float(f64::INFINITY)]).as_bool().unwrap());
+        assert!(call("Math_isFinite", &[Value::int(1)]).as_bool().unwrap());
+    }
+
+    #[test]
+    fn test_round() {
+        assert_eq!(round_float(2.5, 0), 3.0);
+        assert_eq!(round_float(-2.5, 0), -3.0);
+        assert_eq!(round_float(1.23456, 2), 1.23);
+        assert_eq!(round_float(1234.5, -2), 1200.0);
+        assert_eq!(round_float(1e300, 20), 1e300);
+        assert!(round_float(f64::NAN, 2).is_nan());
+        assert_eq!(round_int(1250, -2), 1300);
+        assert_eq!(round_int(-1250, -2), -1300);
+        assert_eq!(round_int(1249, -2), 1200);
+        assert_eq!(round_int(7, 3), 7);
+        assert_eq!(round_int(7, -40), 0);
+    }
+}
@@ -3,19 +3,57 @@
 //! 实现用 Rust 编写的内置标准库
 
 mod vmtest;
+pub mod declarations;
 pub mod exception;
 pub mod net;
+pub mod fs;
+pub mod json;
+pub mod sync;
+pub mod future;
+pub mod time;
+pub mod datetime;
+pub mod os;
+pub mod log;
+pub mod uuid;
+pub mod runtime;
+pub mod convert;
+pub mod math;
+pub mod random;
+pub mod process;
+pub mod regex;
+pub mod encoding;
 
 pub use vmtest::VmTestLib;
 pub use exception::ExceptionLib;
 pub use exception::{THROWABLE_TYPES, is_throwable_type};
 pub use net::NetTcpLib;
 pub use net::NetHttpLib;
+pub use net::NetUdpLib;
+pub use net::NetDnsLib;
+pub use fs::FsLib;
+pub use json::JsonLib;
+pub use sync::SyncLib;
+pub use future::AsyncLib;
+pub use time::TimeLib;
+pub use os::OsLib;
+pub use log::LogLib;
+pub use uuid::UuidLib;
+pub use runtime::RuntimeLib;
+pub use convert::ConvertLib;
+pub use math::MathLib;
+pub use random::RandomLib;
+pub use process::ProcessLib;
+pub use self::regex::RegexLib;
+pub use encoding::EncodingLib;
+pub use declarations::{ClassDecl, ParamDecl};
 
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::fmt;
+use std::sync::{Arc, OnceLock};
 use crossbeam_channel::{Sender, Receiver, bounded};
+use crate::types::Type;
 use crate::vm::value::Value;
+use crate::vm::HostFunctions;
 
 /// 标准库函数类型
 pub type StdlibFn = fn(&[Value]) -> Result<Value, String>;
@@ -34,6 +72,8 @@ pub enum CallbackRequest {
         handler: Value,
         /// 回调参数
         args: Vec<Value>,
+        /// 处理 HTTP 请求时的请求 ID，回调执行期间附加到 std.log 记录
+        request_id: Option<String>,
         /// 响应通道（用于接收回调返回值）
         response_tx: Sender<CallbackResponse>,
     },
@@ -71,11 +111,17 @@ impl CallbackChannel {
     
     /// 发送回调请求并等待响应
     pub fn call(&self, handler: Value, args: Vec<Value>) -> Result<Value, String> {
+        self.call_in_request(handler, args, None)
+    }
+    
+    /// 发送回调请求并等待响应，回调执行期间记录着 request_id
+    pub fn call_in_request(&self, handler: Value, args: Vec<Value>, request_id: Option<String>) -> Result<Value, String> {
         let (response_tx, response_rx) = bounded(1);
         
         self.request_tx.send(CallbackRequest::Execute {
             handler,
             args,
+            request_id,
             response_tx,
         }).map_err(|e| format!("Failed to send callback request: {}", e))?;
         
@@ -119,6 +165,35 @@ pub trait StdlibModule: Send + Sync {
     /// 调用函数
     fn call(&self, name: &str, args: &[Value]) -> Result<Value, String>;
     
+    /// 命名空间常量（如 `Math.PI` 对应 `Math_PI`），编译器直接把值写入常量池
+    ///
+    /// 常量也要出现在 exports 中
+    fn constant(&self, _name: &str) -> Option<Value> {
+        None
+    }
+    
+    /// 函数是否会长时间阻塞调用方（如 `Time.sleep`）
+    ///
+    /// VM 调用这样的函数前交出根集，阻塞期间其他虚拟机可以回收
+    fn blocks(&self, _name: &str) -> bool {
+        false
+    }
+    
+    /// 这次函数调用是否需要回调支持（如传入了回调的 `Time.after`）
+    fn function_needs_callback(&self, _name: &str, _args: &[Value]) -> bool {
+        false
+    }
+    
+    /// 调用需要回调支持的函数，function_needs_callback 返回 true 时 VM 调用它而不是 call
+    fn call_with_callback(
+        &self,
+        name: &str,
+        _args: &[Value],
+        _callback_channel: Arc<CallbackChannel>,
+    ) -> Result<Value, String> {
+        Err(format!("Function '{}' does not support callback", name))
+    }
+    
     /// 检查模块是否包含指定的类
     /// 类名格式：完整类名，如 "std.net.tcp.TCPSocket"
     fn has_class(&self, class_name: &str) -> bool {
@@ -140,6 +215,13 @@ pub trait StdlibModule: Send + Sync {
         Err(format!("Method '{}' not found", method_name))
     }
     
+    /// 模块中类的类型声明，供类型检查器检查构造函数和方法调用
+    /// 
+    /// 声明的每个方法都必须能通过 call_method 调用
+    fn type_declarations(&self) -> Vec<ClassDecl> {
+        Vec::new()
+    }
+    
     /// 检查方法是否需要回调支持
     /// 需要回调的方法（如 HttpServer.listen）会通过回调通道与VM通信
     fn needs_callback(&self, _class_name: &str, _method_name: &str) -> bool {
@@ -165,16 +247,42 @@ pub trait StdlibModule: Send + Sync {
     }
 }
 
+/// 全局标准库注册表（延迟初始化，只有内置模块）
+///
+/// 命令行程序的编译器和 VM 共用；嵌入的程序可以创建自己的注册表，
+/// 通过 [`VM::with_registry`](crate::vm::VM::with_registry) 等交给编译器和 VM
+pub fn global_registry() -> &'static Arc<StdlibRegistry> {
+    static STDLIB_REGISTRY: OnceLock<Arc<StdlibRegistry>> = OnceLock::new();
+    STDLIB_REGISTRY.get_or_init(|| Arc::new(StdlibRegistry::new()))
+}
+
 /// 标准库注册表
+///
+/// 除了内置模块，宿主程序可以注册自己的模块（[`register`](Self::register)）
+/// 和单个的宿主函数（[`register_fn`](Self::register_fn)），脚本中以 `host.名称(...)` 调用
+#[derive(Clone)]
 pub struct StdlibRegistry {
-    modules: HashMap<String, Box<dyn StdlibModule>>,
+    modules: HashMap<String, Arc<dyn StdlibModule>>,
+    host_functions: HostFunctions,
+}
+
+impl fmt::Debug for StdlibRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut modules: Vec<&String> = self.modules.keys().collect();
+        modules.sort();
+        f.debug_struct("StdlibRegistry")
+            .field("modules", &modules)
+            .field("host_functions", &self.host_functions)
+            .finish()
+    }
 }
 
 impl StdlibRegistry {
-    /// 创建新的注册表
+    /// 创建只有内置模块的注册表
     pub fn new() -> Self {
         let mut registry = Self {
             modules: HashMap::new(),
+            host_functions: HostFunctions::default(),
         };
         
         // 注册内置模块
@@ -182,14 +290,54 @@ impl StdlibRegistry {
         registry.register(Box::new(ExceptionLib::new()));
         registry.register(Box::new(NetTcpLib::new()));
         registry.register(Box::new(NetHttpLib::new()));
+        registry.register(Box::new(NetUdpLib::new()));
+        registry.register(Box::new(NetDnsLib::new()));
+        registry.register(Box::new(FsLib::new()));
+        registry.register(Box::new(JsonLib::new()));
+        registry.register(Box::new(SyncLib::new()));
+        registry.register(Box::new(AsyncLib::new()));
+        registry.register(Box::new(TimeLib::new()));
+        registry.register(Box::new(OsLib::new()));
+        registry.register(Box::new(LogLib::new()));
+        registry.register(Box::new(UuidLib::new()));
+        registry.register(Box::new(RuntimeLib::new()));
+        registry.register(Box::new(ConvertLib::new()));
+        registry.register(Box::new(MathLib::new()));
+        registry.register(Box::new(RandomLib::new()));
+        registry.register(Box::new(ProcessLib::new()));
+        registry.register(Box::new(RegexLib::new()));
+        registry.register(Box::new(EncodingLib::new()));
         
         registry
     }
     
-    /// 注册模块
+    /// 注册模块，同名的模块（包括内置模块）被替换
     pub fn register(&mut self, module: Box<dyn StdlibModule>) {
         let name = module.name().to_string();
-        self.modules.insert(name, module);
+        self.modules.insert(name, Arc::from(module));
+    }
+    
+    /// 注册宿主函数，脚本中以 `host.名称(...)` 调用；参数和返回值不做类型检查
+    ///
+    /// 名称必须是 `host.标识符`，同名函数不能重复注册
+    pub fn register_fn<F>(&mut self, name: &str, func: F) -> Result<(), String>
+    where
+        F: Fn(&[Value]) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        self.host_functions.register(name, None, Arc::new(func))
+    }
+    
+    /// 注册带类型的宿主函数，类型检查器按 signature（函数类型）检查调用
+    pub fn register_typed_fn<F>(&mut self, name: &str, signature: Type, func: F) -> Result<(), String>
+    where
+        F: Fn(&[Value]) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        self.host_functions.register(name, Some(signature), Arc::new(func))
+    }
+    
+    /// 注册的宿主函数
+    pub fn host_functions(&self) -> &HostFunctions {
+        &self.host_functions
     }
     
     /// 获取模块
@@ -202,6 +350,13 @@ impl StdlibRegistry {
         self.modules.contains_key(name)
     }
     
+    /// 检查模块是否导出指定函数
+    pub fn has_function(&self, module: &str, func: &str) -> bool {
+        self.modules.get(module)
+            .map(|m| m.exports().contains(&func))
+            .unwrap_or(false)
+    }
+    
     /// 调用模块函数
     pub fn call(&self, module: &str, func: &str, args: &[Value]) -> Result<Value, String> {
         let module = self.modules.get(module)
@@ -223,11 +378,9 @@ impl StdlibRegistry {
     /// 根据简短类名查找完整类名
     /// 例如：HttpServer -> std.net.http.HttpServer
     pub fn resolve_class_name(&self, short_name: &str) -> Option<String> {
-        // 如果已经是完整名称，直接返回
-        if short_name.starts_with("std.") {
-            if self.find_class_module(short_name).is_some() {
-                return Some(short_name.to_string());
-            }
+        // 如果已经是完整名称，直接返回（宿主程序注册的模块不在 std 下）
+        if short_name.contains('.') && self.find_class_module(short_name).is_some() {
+            return Some(short_name.to_string());
         }
         
         // 搜索所有模块，查找匹配的简短类名
@@ -310,3 +463,203 @@ impl Default for StdlibRegistry {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+    use crate::i18n::Locale;
+    use crate::lexer::Scanner;
+    use crate::parser::Parser;
+    use crate::typechecker::TypeChecker;
+    use crate::vm::value::ClassInstance;
+    use crate::vm::VM;
+    use parking_lot::Mutex;
+
+    /// 声明了类型的模块
+    const DECLARING_MODULES: &[&str] = &["std.net.tcp", "std.net.http", "std.lang"];
+
+    /// 导出函数 `类名_构造名` 中表示构造函数的名字
+    const CONSTRUCTOR_EXPORTS: &[&str] = &["init", "connect", "bind"];
+
+    /// 没有 `__handle` 字段的实例：方法在访问原生资源之前就会因句柄或参数无效而失败
+    fn bare_instance(class_name: &str) -> Value {
+        Value::class(Arc::new(Mutex::new(ClassInstance {
+            class_name: class_name.to_string(),
+            parent_class: None,
+            fields: HashMap::new(),
+        })))
+    }
+
+    #[test]
+    fn test_declarations_match_dispatch() {
+        let registry = global_registry();
+        for &module_name in DECLARING_MODULES {
+            let module = registry.get(module_name).unwrap();
+            let decls = module.type_declarations();
+            assert!(!decls.is_empty(), "{} declares no classes", module_name);
+
+            // 声明的类和方法都能调用到实现
+            for decl in &decls {
+                let class_name = format!("{}.{}", module_name, decl.name);
+                assert!(module.has_class(&class_name), "{} is declared but not provided", class_name);
+                let constructed = module.create_class_instance(&class_name, &[]);
+                if decl.constructor.is_none() {
+                    assert!(constructed.is_err(), "{} can be constructed but declares no constructor", class_name);
+                } else if let Err(e) = constructed {
+                    assert!(!e.contains("not found") && !e.contains("cannot be"), "{}: {}", class_name, e);
+                }
+
+                let instance = bare_instance(&class_name);
+                for method in &decl.methods {
+                    if module.needs_callback(&class_name, method.name) {
+                        continue;
+                    }
+                    let args = vec![Value::null(); method.params.len()];
+                    if let Err(e) = module.call_method(&instance, method.name, &args) {
+                        assert!(!e.contains("has no method"), "{}.{} is declared but not dispatched", decl.name, method.name);
+                    }
+                }
+            }
+
+            // 导出的类和方法都有声明
+            let find = |name: &str| decls.iter().find(|decl| decl.name == name);
+            for export in module.exports() {
+                match export.split_once('_') {
+                    Some((class, member)) => {
+                        let decl = find(class).unwrap_or_else(|| panic!("{} is exported but {} is not declared", export, class));
+                        let declared = decl.methods.iter().any(|m| m.name == member)
+                            || (decl.constructor.is_some() && CONSTRUCTOR_EXPORTS.contains(&member));
+                        assert!(declared, "{} is exported but not declared", export);
+                    }
+                    None if export.starts_with(char::is_uppercase) => {
+                        let decl = find(export).unwrap_or_else(|| panic!("class {} is exported but not declared", export));
+                        assert!(decl.constructor.is_some(), "{} is exported but declares no constructor", export);
+                    }
+                    // 模块函数
+                    None => {}
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_declared_program_runs() {
+        let source = r#"
+import std.lang.*
+import std.net.http.{HttpClient, HttpResponse}
+
+func main() {
+    var client = new HttpClient(1000)
+    client.setTimeout(500)
+    client.close()
+
+    var response = new HttpResponse(200, "ok")
+    response.setHeader("Content-Type", "text/plain")
+    if response.text() != "ok" || response.status != 200 {
+        throw new IllegalStateException("unexpected response")
+    }
+
+    try {
+        throw new IOException("disk", new Exception("root"))
+    } catch (e: IOException) {
+        var cause = e.getCause()
+        if e.getMessage() != "disk" || cause == null {
+            throw new IllegalStateException(e.toString())
+        }
+    }
+}
+"#;
+        let program = Parser::new(Scanner::new(source).scan_tokens(), Locale::En).parse().unwrap();
+        TypeChecker::new().check_program(&program).unwrap();
+        let chunk = Compiler::new(Locale::En).compile(&program).unwrap();
+        VM::new(Arc::new(chunk), Locale::En).run().unwrap();
+    }
+
+    #[test]
+    fn test_request_id_propagates_to_log_and_client() {
+        use std::io::{Read, Write};
+        use std::net::{TcpListener, TcpStream};
+        use std::time::Duration;
+
+        // 下游服务：记录收到的请求原文
+        let downstream = TcpListener::bind("127.0.0.1:0").unwrap();
+        let downstream_addr = downstream.local_addr().unwrap();
+        let (seen_tx, seen_rx) = crossbeam_channel::unbounded();
+        std::thread::spawn(move || {
+            for stream in downstream.incoming() {
+                let Ok(mut stream) = stream else { break };
+                let mut buf = [0u8; 4096];
+                let n = stream.read(&mut buf).unwrap_or(0);
+                let _ = seen_tx.send(String::from_utf8_lossy(&buf[..n]).to_string());
+                let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok");
+            }
+        });
+
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let log_path = std::env::temp_dir().join(format!("qlang-request-id-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&log_path);
+        let source = format!(r#"
+import std.net.http.*
+import std.log.Log
+
+func main() {{
+    Log.setOutput("{log}")
+    var server = new HttpServer("127.0.0.1", {port})
+    server.get("/", func(req: HttpRequest, ctx: RequestContext) HttpResponse {{
+        Log.info("handling " + req.path)
+        var first = ctx.httpClient(2000).get("http://{downstream}/")
+        var second = new HttpClient(2000).withContext(ctx).get("http://{downstream}/")
+        return new HttpResponse(200, req.id() + " " + ctx.id() + " " + first.body() + second.body())
+    }})
+    server.listen()
+}}
+"#, log = log_path.display(), port = port, downstream = downstream_addr);
+        let program = Parser::new(Scanner::new(&source).scan_tokens(), Locale::En).parse().unwrap();
+        TypeChecker::new().check_program(&program).unwrap();
+        let chunk = Compiler::new(Locale::En).compile(&program).unwrap();
+        // 闭包还不能捕获 server 来调用 stop，服务线程一直运行到测试进程退出
+        std::thread::spawn(move || VM::new(Arc::new(chunk), Locale::En).run().unwrap());
+
+        let send = |request: &str| {
+            let mut stream = (0..100)
+                .find_map(|_| TcpStream::connect(("127.0.0.1", port)).ok().or_else(|| {
+                    std::thread::sleep(Duration::from_millis(20));
+                    None
+                }))
+                .expect("server did not start");
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        let header = |text: &str, name: &str| {
+            text.lines()
+                .find_map(|line| line.split_once(':').filter(|(k, _)| k.eq_ignore_ascii_case(name)))
+                .map(|(_, v)| v.trim().to_string())
+        };
+
+        // 没有 X-Request-Id 时生成，已有时沿用
+        let generated = send("GET / HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let given = send("GET / HTTP/1.1\r\nHost: localhost\r\nX-Request-Id: trace-42\r\n\r\n");
+        log::log_set_output(&[]).unwrap();
+
+        let id = header(&generated, "X-Request-Id").expect("response echoes the request id");
+        assert_eq!(id.len(), 36, "{}", id);
+        assert!(generated.ends_with(&format!("{} {} okok", id, id)), "{}", generated);
+        assert_eq!(header(&given, "X-Request-Id").as_deref(), Some("trace-42"));
+        assert!(given.ends_with("trace-42 trace-42 okok"), "{}", given);
+
+        let inbound: Vec<String> = seen_rx.try_iter().collect();
+        let inbound_ids: Vec<Option<String>> = inbound.iter().map(|r| header(r, "X-Request-Id")).collect();
+        assert_eq!(inbound_ids, vec![Some(id.clone()), Some(id.clone()), Some("trace-42".to_string()), Some("trace-42".to_string())]);
+
+        let log = std::fs::read_to_string(&log_path).unwrap();
+        let _ = std::fs::remove_file(&log_path);
+        assert_eq!(log, format!(
+            "[INFO] handling / request_id={}\n[INFO] handling / request_id=trace-42\n",
+            id
+        ));
+    }
+
+}
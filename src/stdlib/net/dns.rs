@@ -0,0 +1,65 @@
+//! std.net.dns 域名解析
+//!
+//! `Dns.resolve(host)` 同步解析主机名，返回全部 IP 地址；
+//! `Dns.resolveAsync(host)` 在 IO 线程池中解析，立即返回 Future。
+
+use super::io_thread_pool::IoThreadPool;
+use super::network_exception;
+use crate::stdlib::exception::stdlib_exception;
+use crate::stdlib::future;
+use crate::vm::value::Value;
+use parking_lot::Mutex;
+use std::net::ToSocketAddrs;
+use std::sync::Arc;
+
+fn host_arg(args: &[Value], func: &str) -> Result<String, String> {
+    args.first()
+        .and_then(|v| v.as_string())
+        .cloned()
+        .ok_or_else(|| stdlib_exception("IllegalArgumentException", format!("{} expects a host name", func)))
+}
+
+/// 解析主机名，按系统返回的顺序去重
+fn lookup(host: &str) -> Result<Value, String> {
+    let addrs = (host, 0)
+        .to_socket_addrs()
+        .map_err(|e| network_exception(&format!("Failed to resolve {}", host), &e))?;
+    let mut ips: Vec<String> = Vec::new();
+    for addr in addrs {
+        let ip = addr.ip().to_string();
+        if !ips.contains(&ip) {
+            ips.push(ip);
+        }
+    }
+    let ips = ips.into_iter().map(Value::string).collect();
+    Ok(Value::array(Arc::new(Mutex::new(ips))))
+}
+
+/// Dns.resolve(host: string) -> string[]
+pub fn dns_resolve(args: &[Value]) -> Result<Value, String> {
+    lookup(&host_arg(args, "Dns.resolve")?)
+}
+
+/// Dns.resolveAsync(host: string) -> Future
+pub fn dns_resolve_async(pool: &IoThreadPool, args: &[Value]) -> Result<Value, String> {
+    let host = host_arg(args, "Dns.resolveAsync")?;
+    Ok(future::spawn(pool, move || lookup(&host)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stdlib::exception::parse_stdlib_exception;
+
+    #[test]
+    fn test_resolve_localhost() {
+        let pool = IoThreadPool::new(1);
+        let future = dns_resolve_async(&pool, &[Value::string("127.0.0.1".to_string())]).unwrap();
+        let ips = future::future_await(&future).unwrap();
+        assert_eq!(*ips.as_array().unwrap().lock()[0].as_string().unwrap(), "127.0.0.1");
+
+        let future = dns_resolve_async(&pool, &[Value::string("no such host.invalid".to_string())]).unwrap();
+        let error = future::future_await(&future).unwrap_err();
+        assert_eq!(parse_stdlib_exception(&error).map(|(class, _)| class), Some("NetworkException"));
+    }
+}
@@ -2,17 +2,28 @@
 //!
 //! 提供HttpClient和HttpServer类，支持HTTP/1.1协议
 //! HttpServer支持回调方式处理请求
+//!
+//! 每个请求都有请求 ID（沿用 X-Request-Id 请求头，没有时随机生成），handler 执行期间
+//! 附加到 std.log 记录，由 RequestContext 创建或绑定的 HttpClient 在请求头中继续传递
 
 use std::collections::HashMap;
 use std::io::{Read, Write, BufRead, BufReader};
-use std::net::{TcpStream, TcpListener, SocketAddr};
+use std::net::{TcpStream, TcpListener, ToSocketAddrs};
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::thread;
 use parking_lot::Mutex;
 use crate::vm::value::{Value, ClassInstance};
+use crate::vm::MapData;
 use crate::stdlib::CallbackChannel;
+use crate::stdlib::exception::stdlib_exception;
+use crate::stdlib::{datetime, future, json};
+use crate::stdlib::uuid::uuid_v4;
+use crate::stdlib::declarations::{class, optional, param, string_map, ClassDecl};
+use crate::types::Type;
+use super::network_exception;
+use super::io_thread_pool::IoThreadPool;
 
 // ============================================================================
 // 常量定义
@@ -26,6 +37,10 @@ pub const CLASS_HTTP_SERVER: &str = "std.net.http.HttpServer";
 pub const CLASS_HTTP_REQUEST: &str = "std.net.http.HttpRequest";
 /// HttpResponse类名
 pub const CLASS_HTTP_RESPONSE: &str = "std.net.http.HttpResponse";
+/// HttpClientResponse类名（HttpClient请求的结果）
+pub const CLASS_HTTP_CLIENT_RESPONSE: &str = "std.net.http.HttpClientResponse";
+/// RequestContext类名（handler 的第二个参数）
+pub const CLASS_REQUEST_CONTEXT: &str = "std.net.http.RequestContext";
 
 /// 默认User-Agent
 const DEFAULT_USER_AGENT: &str = "Q-HttpClient/1.0";
@@ -33,6 +48,18 @@ const DEFAULT_USER_AGENT: &str = "Q-HttpClient/1.0";
 const DEFAULT_TIMEOUT_MS: u64 = 30000;
 /// 默认缓冲区大小
 const DEFAULT_BUFFER_SIZE: usize = 8192;
+/// 默认响应写缓冲区大小（状态行 + 头部 + 小响应体合并为一次写入，大响应体按此大小分块写入）
+const DEFAULT_WRITE_BUFFER_SIZE: usize = 64 * 1024;
+/// 服务端默认允许的最大请求头数量
+const DEFAULT_MAX_HEADERS: usize = 100;
+/// 服务端默认允许的最大查询参数数量
+const DEFAULT_MAX_QUERY_PARAMS: usize = 1000;
+/// 客户端允许的最大响应头数量
+const MAX_RESPONSE_HEADERS: usize = 1000;
+/// 传递请求 ID 的头部
+const REQUEST_ID_HEADER: &str = "X-Request-Id";
+/// 沿用的请求 ID 的最大长度，超出或含有非法字符时重新生成
+const MAX_REQUEST_ID_LEN: usize = 128;
 
 // ============================================================================
 // URL解析
@@ -272,21 +299,21 @@ fn parse_http_response(reader: &mut BufReader<&mut TcpStream>) -> Result<HttpRes
     // 读取状态行
     let mut status_line = String::new();
     reader.read_line(&mut status_line)
-        .map_err(|e| format!("Failed to read status line: {}", e))?;
+        .map_err(|e| network_exception("Failed to read status line", &e))?;
     
     let status_line = status_line.trim();
     if !status_line.starts_with("HTTP/") {
-        return Err(format!("Invalid HTTP response: {}", status_line));
+        return Err(stdlib_exception("NetworkException", format!("Invalid HTTP response: {}", status_line)));
     }
     
     // 解析状态码
     let parts: Vec<&str> = status_line.splitn(3, ' ').collect();
     if parts.len() < 2 {
-        return Err("Invalid status line".to_string());
+        return Err(stdlib_exception("NetworkException", "Invalid status line"));
     }
     
     let status = parts[1].parse::<i32>()
-        .map_err(|_| format!("Invalid status code: {}", parts[1]))?;
+        .map_err(|_| stdlib_exception("NetworkException", format!("Invalid status code: {}", parts[1])))?;
     let status_text = if parts.len() > 2 { parts[2].to_string() } else { String::new() };
     
     // 读取响应头
@@ -297,12 +324,18 @@ fn parse_http_response(reader: &mut BufReader<&mut TcpStream>) -> Result<HttpRes
     loop {
         let mut line = String::new();
         reader.read_line(&mut line)
-            .map_err(|e| format!("Failed to read header: {}", e))?;
+            .map_err(|e| network_exception("Failed to read header", &e))?;
         
         let line = line.trim();
         if line.is_empty() {
             break;
         }
+        if headers.len() >= MAX_RESPONSE_HEADERS {
+            return Err(stdlib_exception(
+                "NetworkException",
+                format!("Too many response headers (limit {})", MAX_RESPONSE_HEADERS),
+            ));
+        }
         
         if let Some(pos) = line.find(':') {
             let key = line[..pos].trim().to_string();
@@ -330,13 +363,13 @@ fn parse_http_response(reader: &mut BufReader<&mut TcpStream>) -> Result<HttpRes
         // 固定长度
         let mut body = vec![0u8; len];
         reader.read_exact(&mut body)
-            .map_err(|e| format!("Failed to read body: {}", e))?;
+            .map_err(|e| network_exception("Failed to read body", &e))?;
         String::from_utf8_lossy(&body).to_string()
     } else {
         // 读取到EOF
         let mut body = String::new();
         reader.read_to_string(&mut body)
-            .map_err(|e| format!("Failed to read body: {}", e))?;
+            .map_err(|e| network_exception("Failed to read body", &e))?;
         body
     };
     
@@ -356,10 +389,10 @@ fn read_chunked_body(reader: &mut BufReader<&mut TcpStream>) -> Result<String, S
         // 读取chunk大小
         let mut size_line = String::new();
         reader.read_line(&mut size_line)
-            .map_err(|e| format!("Failed to read chunk size: {}", e))?;
+            .map_err(|e| network_exception("Failed to read chunk size", &e))?;
         
         let size = usize::from_str_radix(size_line.trim(), 16)
-            .map_err(|_| format!("Invalid chunk size: {}", size_line.trim()))?;
+            .map_err(|_| stdlib_exception("NetworkException", format!("Invalid chunk size: {}", size_line.trim())))?;
         
         if size == 0 {
             // 最后一个chunk
@@ -372,7 +405,7 @@ fn read_chunked_body(reader: &mut BufReader<&mut TcpStream>) -> Result<String, S
         // 读取chunk数据
         let mut chunk = vec![0u8; size];
         reader.read_exact(&mut chunk)
-            .map_err(|e| format!("Failed to read chunk: {}", e))?;
+            .map_err(|e| network_exception("Failed to read chunk", &e))?;
         body.extend_from_slice(&chunk);
         
         // 读取chunk后的\r\n
@@ -391,12 +424,23 @@ fn read_chunked_body(reader: &mut BufReader<&mut TcpStream>) -> Result<String, S
 pub struct HttpClientHandle {
     /// 超时时间（毫秒）
     timeout_ms: Mutex<u64>,
+    /// 绑定的请求 ID，发出的请求都带上 X-Request-Id
+    request_id: Option<String>,
 }
 
 impl HttpClientHandle {
     fn new(timeout_ms: u64) -> Self {
         Self {
             timeout_ms: Mutex::new(timeout_ms),
+            request_id: None,
+        }
+    }
+    
+    /// 绑定到请求 ID 的客户端
+    fn with_request_id(timeout_ms: u64, request_id: String) -> Self {
+        Self {
+            timeout_ms: Mutex::new(timeout_ms),
+            request_id: Some(request_id),
         }
     }
     
@@ -409,26 +453,35 @@ impl HttpClientHandle {
         headers: &HashMap<String, String>,
     ) -> Result<HttpResponseData, String> {
         // 解析URL
-        let parsed_url = ParsedUrl::parse(url)?;
+        let parsed_url = ParsedUrl::parse(url)
+            .map_err(|e| stdlib_exception("IllegalArgumentException", e))?;
         
-        // 建立TCP连接
-        let addr = format!("{}:{}", parsed_url.host, parsed_url.port)
-            .parse::<SocketAddr>()
-            .map_err(|e| format!("Invalid address: {}", e))?;
+        // 解析地址（支持主机名）并建立TCP连接
+        let addr = (parsed_url.host.as_str(), parsed_url.port)
+            .to_socket_addrs()
+            .map_err(|e| network_exception(&format!("Failed to resolve {}", parsed_url.host), &e))?
+            .next()
+            .ok_or_else(|| stdlib_exception("NetworkException", format!("No address found for {}", parsed_url.host)))?;
         
         let timeout = Duration::from_millis(*self.timeout_ms.lock());
         let mut stream = TcpStream::connect_timeout(&addr, timeout)
-            .map_err(|e| format!("Connection failed: {}", e))?;
+            .map_err(|e| network_exception(&format!("Connection to {} failed", addr), &e))?;
         
         stream.set_read_timeout(Some(timeout)).ok();
         stream.set_write_timeout(Some(timeout)).ok();
         
-        // 构建并发送请求
-        let request = build_http_request(method, &parsed_url, headers, body);
+        // 构建并发送请求（调用方显式设置的 X-Request-Id 优先）
+        let mut headers = std::borrow::Cow::Borrowed(headers);
+        if let Some(id) = &self.request_id {
+            if !headers.keys().any(|k| k.eq_ignore_ascii_case(REQUEST_ID_HEADER)) {
+                headers.to_mut().insert(REQUEST_ID_HEADER.to_string(), id.clone());
+            }
+        }
+        let request = build_http_request(method, &parsed_url, &headers, body);
         stream.write_all(request.as_bytes())
-            .map_err(|e| format!("Failed to send request: {}", e))?;
+            .map_err(|e| network_exception("Failed to send request", &e))?;
         stream.flush()
-            .map_err(|e| format!("Failed to flush: {}", e))?;
+            .map_err(|e| network_exception("Failed to flush", &e))?;
         
         // 读取响应
         let mut reader = BufReader::new(&mut stream);
@@ -440,6 +493,24 @@ impl HttpClientHandle {
 // HttpServer Handle
 // ============================================================================
 
+/// 服务端解析请求时的数量上限，超出时直接响应 400
+#[derive(Debug, Clone, Copy)]
+pub struct RequestLimits {
+    /// 最大请求头数量
+    pub max_headers: usize,
+    /// 最大查询参数数量
+    pub max_query_params: usize,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        Self {
+            max_headers: DEFAULT_MAX_HEADERS,
+            max_query_params: DEFAULT_MAX_QUERY_PARAMS,
+        }
+    }
+}
+
 /// HttpServer句柄
 pub struct HttpServerHandle {
     /// TCP监听器
@@ -450,6 +521,12 @@ pub struct HttpServerHandle {
     port: u16,
     /// 运行标志
     running: Arc<AtomicBool>,
+    /// 响应写缓冲区大小（字节）
+    write_buffer_size: Arc<AtomicUsize>,
+    /// 请求头 / 查询参数数量上限
+    limits: Mutex<RequestLimits>,
+    /// 通过 route/get/post/put/delete 注册的路由（按注册顺序匹配）
+    routes: Mutex<Vec<Route>>,
 }
 
 impl HttpServerHandle {
@@ -457,6 +534,10 @@ impl HttpServerHandle {
         let addr = format!("{}:{}", host, port);
         let listener = TcpListener::bind(&addr)
             .map_err(|e| format!("Failed to bind {}: {}", addr, e))?;
+        // 端口为 0 时由系统分配，记录实际绑定的端口
+        let port = listener.local_addr()
+            .map_err(|e| format!("Failed to read local address: {}", e))?
+            .port();
         
         // 设置非阻塞模式以便能够检查停止标志
         listener.set_nonblocking(true)
@@ -467,6 +548,9 @@ impl HttpServerHandle {
             host,
             port,
             running: Arc::new(AtomicBool::new(false)),
+            write_buffer_size: Arc::new(AtomicUsize::new(DEFAULT_WRITE_BUFFER_SIZE)),
+            limits: Mutex::new(RequestLimits::default()),
+            routes: Mutex::new(Vec::new()),
         })
     }
     
@@ -481,8 +565,111 @@ impl HttpServerHandle {
     }
 }
 
+// ============================================================================
+// 路由
+// ============================================================================
+
+/// 路由模式中的一段
+#[derive(Debug, Clone, PartialEq)]
+enum RouteSegment {
+    /// 字面量段，如 `/users`
+    Literal(String),
+    /// 路径参数段，如 `/:id`
+    Param(String),
+}
+
+/// 已注册的路由
+struct Route {
+    /// HTTP方法（大写）
+    method: String,
+    /// 路径模式
+    segments: Vec<RouteSegment>,
+    /// 处理函数
+    handler: Value,
+}
+
+/// 按 `/` 切分路径，忽略空段（`/users/` 与 `/users` 等价）
+fn split_path(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|s| !s.is_empty())
+}
+
+impl Route {
+    /// 解析路由模式，如 `/users/:id`
+    fn new(method: &str, pattern: &str, handler: Value) -> Result<Self, String> {
+        if method.is_empty() {
+            return Err("Invalid method: expected non-empty string".to_string());
+        }
+        if !pattern.starts_with('/') {
+            return Err(format!("Invalid route pattern '{}': must start with '/'", pattern));
+        }
+        
+        let mut segments = Vec::new();
+        for segment in split_path(pattern) {
+            if let Some(name) = segment.strip_prefix(':') {
+                if name.is_empty() {
+                    return Err(format!("Invalid route pattern '{}': empty parameter name", pattern));
+                }
+                segments.push(RouteSegment::Param(name.to_string()));
+            } else {
+                segments.push(RouteSegment::Literal(segment.to_string()));
+            }
+        }
+        
+        Ok(Self {
+            method: method.to_uppercase(),
+            segments,
+            handler,
+        })
+    }
+    
+    /// 匹配请求，成功时返回提取出的路径参数
+    fn matches(&self, method: &str, path: &str) -> Option<HashMap<String, String>> {
+        if self.method != method {
+            return None;
+        }
+        
+        let mut params = HashMap::new();
+        let mut parts = split_path(path);
+        for segment in &self.segments {
+            let part = parts.next()?;
+            match segment {
+                RouteSegment::Literal(literal) => {
+                    if literal != part {
+                        return None;
+                    }
+                }
+                RouteSegment::Param(name) => {
+                    params.insert(name.clone(), url_decode(part));
+                }
+            }
+        }
+        
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(params)
+    }
+}
+
+/// 选择处理请求的handler：先按注册顺序匹配路由，再回退到 listen 的 catch-all handler
+/// 返回 handler 和路径参数；都不匹配时返回 None（响应404）
+fn resolve_handler(
+    routes: &[Route],
+    fallback: Option<Value>,
+    request: &HttpRequestData,
+) -> Option<(Value, HashMap<String, String>)> {
+    routes.iter()
+        .find_map(|route| {
+            route.matches(&request.method, &request.path)
+                .map(|params| (route.handler, params))
+        })
+        .or_else(|| fallback.map(|handler| (handler, HashMap::new())))
+}
+
 /// 解析HTTP请求（服务端）
-fn parse_http_request(stream: &mut TcpStream) -> Result<HttpRequestData, String> {
+///
+/// 请求头或查询参数超过 limits 时返回错误，由调用方响应 400
+fn parse_http_request<S: Read>(stream: &mut S, limits: RequestLimits) -> Result<HttpRequestData, String> {
     let mut reader = BufReader::new(stream);
     
     // 读取请求行
@@ -512,6 +699,9 @@ fn parse_http_request(stream: &mut TcpStream) -> Result<HttpRequestData, String>
     let mut query = HashMap::new();
     if !query_string.is_empty() {
         for pair in query_string.split('&') {
+            if query.len() >= limits.max_query_params {
+                return Err(format!("too many query parameters (limit {})", limits.max_query_params));
+            }
             if let Some(pos) = pair.find('=') {
                 let key = url_decode(&pair[..pos]);
                 let value = url_decode(&pair[pos + 1..]);
@@ -535,6 +725,9 @@ fn parse_http_request(stream: &mut TcpStream) -> Result<HttpRequestData, String>
         if line.is_empty() {
             break;
         }
+        if headers.len() >= limits.max_headers {
+            return Err(format!("too many headers (limit {})", limits.max_headers));
+        }
         
         if let Some(pos) = line.find(':') {
             let key = line[..pos].trim().to_string();
@@ -566,8 +759,10 @@ fn parse_http_request(stream: &mut TcpStream) -> Result<HttpRequestData, String>
         method,
         path: path.to_string(),
         query,
+        params: HashMap::new(),
         headers,
         body,
+        id: String::new(),
     })
 }
 
@@ -580,14 +775,28 @@ pub struct HttpRequestData {
     pub path: String,
     /// 查询参数
     pub query: HashMap<String, String>,
+    /// 路径参数（由匹配的路由提取）
+    pub params: HashMap<String, String>,
     /// 请求头
     pub headers: HashMap<String, String>,
     /// 请求体
     pub body: String,
+    /// 请求 ID（由服务端在交给 handler 之前确定）
+    pub id: String,
+}
+
+/// 确定请求 ID：沿用合法的 X-Request-Id 请求头，否则生成新的 UUID
+fn resolve_request_id(headers: &HashMap<String, String>) -> String {
+    headers.iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(REQUEST_ID_HEADER))
+        .map(|(_, v)| v.as_str())
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN && id.bytes().all(|b| b.is_ascii_graphic()))
+        .map(str::to_string)
+        .unwrap_or_else(uuid_v4)
 }
 
-/// 构建HTTP响应
-fn build_http_response(status: i32, headers: &HashMap<String, String>, body: &str) -> String {
+/// 构建HTTP响应头部（状态行 + 头部 + 空行）
+fn build_http_response_head(status: i32, headers: &HashMap<String, String>, body_len: usize) -> String {
     let status_text = match status {
         200 => "OK",
         201 => "Created",
@@ -615,7 +824,13 @@ fn build_http_response(status: i32, headers: &HashMap<String, String>, body: &st
     
     // Content-Length
     if !headers.contains_key("Content-Length") && !headers.contains_key("content-length") {
-        response.push_str(&format!("Content-Length: {}\r\n", body.len()));
+        response.push_str(&format!("Content-Length: {}\r\n", body_len));
+    }
+    
+    // Date（RFC 7231：有时钟的服务端应当发送）
+    if !headers.keys().any(|key| key.eq_ignore_ascii_case("Date")) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as i64);
+        response.push_str(&format!("Date: {}\r\n", datetime::format_http(now)));
     }
     
     // Connection: close
@@ -631,12 +846,34 @@ fn build_http_response(status: i32, headers: &HashMap<String, String>, body: &st
     // 空行结束头部
     response.push_str("\r\n");
     
-    // 添加body
-    response.push_str(body);
-    
     response
 }
 
+/// 写出HTTP响应
+///
+/// 头部与响应体的第一块合并为一次写入；响应体超过 `chunk_size` 时，
+/// 其余部分按 `chunk_size` 分块写入，因此总写入次数为 ceil(body / chunk_size)（至少一次）
+fn write_http_response<W: Write>(
+    stream: &mut W,
+    status: i32,
+    headers: &HashMap<String, String>,
+    body: &str,
+    chunk_size: usize,
+) -> std::io::Result<()> {
+    let chunk_size = chunk_size.max(1);
+    let body = body.as_bytes();
+    let first_len = body.len().min(chunk_size);
+    
+    let mut buffer = build_http_response_head(status, headers, body.len()).into_bytes();
+    buffer.extend_from_slice(&body[..first_len]);
+    stream.write_all(&buffer)?;
+    
+    for chunk in body[first_len..].chunks(chunk_size) {
+        stream.write_all(chunk)?;
+    }
+    stream.flush()
+}
+
 // ============================================================================
 // Value创建辅助函数
 // ============================================================================
@@ -682,9 +919,14 @@ pub fn create_http_request_instance(request: &HttpRequestData) -> Value {
     let query_map = create_string_map(&request.query);
     fields.insert("query".to_string(), query_map);
     
+    // 路径参数转为map
+    let params_map = create_string_map(&request.params);
+    fields.insert("params".to_string(), params_map);
+    
     // 请求头转为map
     let headers_map = create_string_map(&request.headers);
     fields.insert("headers".to_string(), headers_map);
+    fields.insert("__request_id".to_string(), Value::string(request.id.clone()));
     
     let instance = ClassInstance {
         class_name: CLASS_HTTP_REQUEST.to_string(),
@@ -695,8 +937,29 @@ pub fn create_http_request_instance(request: &HttpRequestData) -> Value {
     Value::class(Arc::new(Mutex::new(instance)))
 }
 
-/// 创建HttpResponse类实例（从响应数据）
-pub fn create_http_response_from_data(response: &HttpResponseData) -> Value {
+/// 创建RequestContext类实例
+pub fn create_request_context_instance(request_id: &str) -> Value {
+    let mut fields = HashMap::new();
+    fields.insert("__request_id".to_string(), Value::string(request_id.to_string()));
+    
+    let instance = ClassInstance {
+        class_name: CLASS_REQUEST_CONTEXT.to_string(),
+        parent_class: None,
+        fields,
+    };
+    
+    Value::class(Arc::new(Mutex::new(instance)))
+}
+
+/// 读取 HttpRequest / RequestContext 实例中的请求 ID
+fn extract_request_id(instance: &Value) -> Option<String> {
+    let instance = instance.as_class()?;
+    let instance = instance.lock();
+    instance.fields.get("__request_id").and_then(|v| v.as_string()).cloned()
+}
+
+/// 创建HttpClientResponse类实例（HttpClient请求的结果）
+pub fn create_http_client_response_instance(response: &HttpResponseData) -> Value {
     let mut fields = HashMap::new();
     
     fields.insert("status".to_string(), Value::int(response.status as i128));
@@ -707,7 +970,7 @@ pub fn create_http_response_from_data(response: &HttpResponseData) -> Value {
     fields.insert("headers".to_string(), headers_map);
     
     let instance = ClassInstance {
-        class_name: CLASS_HTTP_RESPONSE.to_string(),
+        class_name: CLASS_HTTP_CLIENT_RESPONSE.to_string(),
         parent_class: None,
         fields,
     };
@@ -736,7 +999,7 @@ pub fn create_http_response_instance(status: i128, body: String, headers: HashMa
 
 /// 创建字符串map的Value
 fn create_string_map(map: &HashMap<String, String>) -> Value {
-    let mut result = HashMap::new();
+    let mut result = MapData::default();
     for (k, v) in map {
         result.insert(k.clone(), Value::string(v.clone()));
     }
@@ -772,6 +1035,92 @@ fn extract_handle_ptr(instance: &Value, class_name: &str) -> Result<u64, String>
     }
 }
 
+// ============================================================================
+// 类型声明
+// ============================================================================
+
+/// std.net.http 中类的类型声明
+///
+/// 路由和 listen 的 handler 是 `func(HttpRequest) HttpResponse` 或 `func(HttpRequest, RequestContext) HttpResponse`，
+/// 声明为 unknown 以接受任意函数
+pub fn type_declarations() -> Vec<ClassDecl> {
+    let response = class("HttpClientResponse");
+    vec![
+        ClassDecl::new("HttpClient")
+            .constructor(vec![optional("timeout_ms", Type::Int)])
+            .method("get", vec![param("url", Type::String), optional("headers", string_map())], response.clone())
+            .method("getText", vec![param("url", Type::String), optional("headers", string_map())], Type::String)
+            .method("getAsync", vec![param("url", Type::String), optional("headers", string_map())], class("Future"))
+            .method(
+                "post",
+                vec![param("url", Type::String), optional("body", Type::String), optional("headers", string_map())],
+                response.clone(),
+            )
+            .method(
+                "put",
+                vec![param("url", Type::String), optional("body", Type::String), optional("headers", string_map())],
+                response.clone(),
+            )
+            .method("delete", vec![param("url", Type::String), optional("headers", string_map())], response.clone())
+            .method(
+                "request",
+                vec![
+                    param("method", Type::String),
+                    param("url", Type::String),
+                    optional("body", Type::String),
+                    optional("headers", string_map()),
+                ],
+                response,
+            )
+            .method("setTimeout", vec![param("timeout_ms", Type::Int)], Type::Null)
+            .method("withContext", vec![param("ctx", class("RequestContext"))], class("HttpClient"))
+            .method("close", vec![], Type::Null),
+        ClassDecl::new("HttpClientResponse")
+            .method("status", vec![], Type::Int)
+            .method("header", vec![param("name", Type::String)], Type::String)
+            .method("headers", vec![], string_map())
+            .method("body", vec![], Type::String)
+            .method("json", vec![], Type::Dynamic),
+        ClassDecl::new("HttpServer")
+            .constructor(vec![param("host", Type::String), param("port", Type::Int)])
+            .method("listen", vec![optional("handler", Type::Unknown)], Type::Null)
+            .method(
+                "route",
+                vec![param("method", Type::String), param("path", Type::String), param("handler", Type::Unknown)],
+                Type::Null,
+            )
+            .method("get", vec![param("path", Type::String), param("handler", Type::Unknown)], Type::Null)
+            .method("post", vec![param("path", Type::String), param("handler", Type::Unknown)], Type::Null)
+            .method("put", vec![param("path", Type::String), param("handler", Type::Unknown)], Type::Null)
+            .method("delete", vec![param("path", Type::String), param("handler", Type::Unknown)], Type::Null)
+            .method("stop", vec![], Type::Null)
+            .method("port", vec![], Type::Int)
+            .method("setWriteBufferSize", vec![param("size", Type::Int)], Type::Null)
+            .method("setRequestLimits", vec![param("maxHeaders", Type::Int), param("maxQueryParams", Type::Int)], Type::Null),
+        ClassDecl::new("HttpRequest")
+            .method("id", vec![], Type::String)
+            .method("getHeader", vec![param("name", Type::String)], Type::String)
+            .method("getQuery", vec![param("name", Type::String)], Type::String)
+            .method("getParam", vec![param("name", Type::String)], Type::String)
+            .field("method", Type::String)
+            .field("path", Type::String)
+            .field("body", Type::String)
+            .field("headers", string_map())
+            .field("query", string_map())
+            .field("params", string_map()),
+        ClassDecl::new("RequestContext")
+            .method("id", vec![], Type::String)
+            .method("httpClient", vec![optional("timeout_ms", Type::Int)], class("HttpClient")),
+        ClassDecl::new("HttpResponse")
+            .constructor(vec![param("status", Type::Int), optional("body", Type::String), optional("headers", string_map())])
+            .method("text", vec![], Type::String)
+            .method("setHeader", vec![param("name", Type::String), param("value", Type::String)], Type::Null)
+            .field("status", Type::Int)
+            .field("body", Type::String)
+            .field("headers", string_map()),
+    ]
+}
+
 // ============================================================================
 // HttpClient 类方法实现
 // ============================================================================
@@ -791,7 +1140,7 @@ pub fn http_client_init(args: &[Value]) -> Result<Value, String> {
     Ok(create_http_client_instance(ptr))
 }
 
-/// HttpClient.get(url: string, headers?: map) -> HttpResponse
+/// HttpClient.get(url: string, headers?: map) -> HttpClientResponse
 pub fn http_client_get(instance: &Value, args: &[Value]) -> Result<Value, String> {
     if args.is_empty() {
         return Err("HttpClient.get requires at least 1 argument: url".to_string());
@@ -810,10 +1159,46 @@ pub fn http_client_get(instance: &Value, args: &[Value]) -> Result<Value, String
     let handle = unsafe { &*(client_ptr as *const HttpClientHandle) };
     let response = handle.request("GET", &url, None, &headers)?;
     
-    Ok(create_http_response_from_data(&response))
+    Ok(create_http_client_response_instance(&response))
 }
 
-/// HttpClient.post(url: string, body?: string, headers?: map) -> HttpResponse
+/// HttpClient.getAsync(url: string, headers?: map) -> Future
+/// 请求在 IO 线程池中执行，立即返回 Future
+pub fn http_client_get_async(pool: &IoThreadPool, instance: &Value, args: &[Value]) -> Result<Value, String> {
+    if args.is_empty() {
+        return Err("HttpClient.getAsync requires at least 1 argument: url".to_string());
+    }
+    extract_handle_ptr(instance, "HttpClient")?;
+    
+    let instance = *instance;
+    let args = args.to_vec();
+    Ok(future::spawn(pool, move || http_client_get(&instance, &args)))
+}
+
+/// HttpClient.getText(url: string, headers?: map) -> string
+/// 只需要响应体时的简写，非2xx状态码同样返回响应体
+pub fn http_client_get_text(instance: &Value, args: &[Value]) -> Result<Value, String> {
+    if args.is_empty() {
+        return Err("HttpClient.getText requires at least 1 argument: url".to_string());
+    }
+    
+    let client_ptr = extract_handle_ptr(instance, "HttpClient")?;
+    let url = args[0].as_string()
+        .ok_or_else(|| "Invalid url: expected string".to_string())?;
+    
+    let headers = if args.len() > 1 {
+        extract_string_map(&args[1])
+    } else {
+        HashMap::new()
+    };
+    
+    let handle = unsafe { &*(client_ptr as *const HttpClientHandle) };
+    let response = handle.request("GET", url, None, &headers)?;
+    
+    Ok(Value::string(response.body))
+}
+
+/// HttpClient.post(url: string, body?: string, headers?: map) -> HttpClientResponse
 pub fn http_client_post(instance: &Value, args: &[Value]) -> Result<Value, String> {
     if args.is_empty() {
         return Err("HttpClient.post requires at least 1 argument: url".to_string());
@@ -838,10 +1223,10 @@ pub fn http_client_post(instance: &Value, args: &[Value]) -> Result<Value, Strin
     let handle = unsafe { &*(client_ptr as *const HttpClientHandle) };
     let response = handle.request("POST", &url, body.as_deref(), &headers)?;
     
-    Ok(create_http_response_from_data(&response))
+    Ok(create_http_client_response_instance(&response))
 }
 
-/// HttpClient.put(url: string, body?: string, headers?: map) -> HttpResponse
+/// HttpClient.put(url: string, body?: string, headers?: map) -> HttpClientResponse
 pub fn http_client_put(instance: &Value, args: &[Value]) -> Result<Value, String> {
     if args.is_empty() {
         return Err("HttpClient.put requires at least 1 argument: url".to_string());
@@ -866,10 +1251,10 @@ pub fn http_client_put(instance: &Value, args: &[Value]) -> Result<Value, String
     let handle = unsafe { &*(client_ptr as *const HttpClientHandle) };
     let response = handle.request("PUT", &url, body.as_deref(), &headers)?;
     
-    Ok(create_http_response_from_data(&response))
+    Ok(create_http_client_response_instance(&response))
 }
 
-/// HttpClient.delete(url: string, headers?: map) -> HttpResponse
+/// HttpClient.delete(url: string, headers?: map) -> HttpClientResponse
 pub fn http_client_delete(instance: &Value, args: &[Value]) -> Result<Value, String> {
     if args.is_empty() {
         return Err("HttpClient.delete requires at least 1 argument: url".to_string());
@@ -888,10 +1273,10 @@ pub fn http_client_delete(instance: &Value, args: &[Value]) -> Result<Value, Str
     let handle = unsafe { &*(client_ptr as *const HttpClientHandle) };
     let response = handle.request("DELETE", &url, None, &headers)?;
     
-    Ok(create_http_response_from_data(&response))
+    Ok(create_http_client_response_instance(&response))
 }
 
-/// HttpClient.request(method: string, url: string, body?: string, headers?: map) -> HttpResponse
+/// HttpClient.request(method: string, url: string, body?: string, headers?: map) -> HttpClientResponse
 pub fn http_client_request(instance: &Value, args: &[Value]) -> Result<Value, String> {
     if args.len() < 2 {
         return Err("HttpClient.request requires at least 2 arguments: method, url".to_string());
@@ -918,7 +1303,7 @@ pub fn http_client_request(instance: &Value, args: &[Value]) -> Result<Value, St
     let handle = unsafe { &*(client_ptr as *const HttpClientHandle) };
     let response = handle.request(&method, &url, body.as_deref(), &headers)?;
     
-    Ok(create_http_response_from_data(&response))
+    Ok(create_http_client_response_instance(&response))
 }
 
 /// HttpClient.setTimeout(timeout_ms: int) -> null
@@ -937,6 +1322,20 @@ pub fn http_client_set_timeout(instance: &Value, args: &[Value]) -> Result<Value
     Ok(Value::null())
 }
 
+/// HttpClient.withContext(ctx: RequestContext) -> HttpClient
+/// 返回绑定到 ctx 请求 ID 的新客户端（超时时间相同），原客户端不受影响
+pub fn http_client_with_context(instance: &Value, args: &[Value]) -> Result<Value, String> {
+    let client_ptr = extract_handle_ptr(instance, "HttpClient")?;
+    let request_id = args.first()
+        .and_then(extract_request_id)
+        .ok_or_else(|| stdlib_exception("IllegalArgumentException", "HttpClient.withContext expects a RequestContext"))?;
+    
+    let handle = unsafe { &*(client_ptr as *const HttpClientHandle) };
+    let timeout_ms = *handle.timeout_ms.lock();
+    let bound = Box::new(HttpClientHandle::with_request_id(timeout_ms, request_id));
+    Ok(create_http_client_instance(Box::into_raw(bound) as u64))
+}
+
 /// HttpClient.close() -> null
 pub fn http_client_close(instance: &Value, _args: &[Value]) -> Result<Value, String> {
     let client_ptr = extract_handle_ptr(instance, "HttpClient")?;
@@ -969,27 +1368,30 @@ pub fn http_server_init(args: &[Value]) -> Result<Value, String> {
     Ok(create_http_server_instance(ptr))
 }
 
-/// HttpServer.listen(handler: func(HttpRequest) HttpResponse) -> null
+/// HttpServer.listen(handler?: func(HttpRequest) HttpResponse) -> null
 /// 这是一个需要回调支持的方法
+/// 请求先按注册顺序匹配路由；都不匹配时交给 handler（catch-all），没有 handler 时响应404
 pub fn http_server_listen(
     instance: &Value,
     args: &[Value],
     callback_channel: Arc<CallbackChannel>,
 ) -> Result<Value, String> {
-    if args.is_empty() {
-        return Err("HttpServer.listen requires 1 argument: handler".to_string());
-    }
-    
     let server_ptr = extract_handle_ptr(instance, "HttpServer")?;
-    let handler = args[0].clone();
+    let fallback = args.first().copied().filter(|v| !v.is_null());
     
     // 验证handler是函数或闭包
-    if !handler.is_function() {
-        return Err("Invalid handler: expected function".to_string());
+    if let Some(handler) = fallback {
+        if !handler.is_function() {
+            return Err("Invalid handler: expected function".to_string());
+        }
     }
     
     let handle = unsafe { &mut *(server_ptr as *mut HttpServerHandle) };
     
+    if fallback.is_none() && handle.routes.lock().is_empty() {
+        return Err("HttpServer.listen requires a handler or at least one route".to_string());
+    }
+    
     // 设置运行标志
     handle.running.store(true, Ordering::SeqCst);
     
@@ -997,6 +1399,7 @@ pub fn http_server_listen(
         .ok_or_else(|| "Server listener not available".to_string())?;
     
     let running = handle.running.clone();
+    let write_buffer_size = handle.write_buffer_size.clone();
     
     // 服务器主循环
     while running.load(Ordering::SeqCst) {
@@ -1008,45 +1411,65 @@ pub fn http_server_listen(
                 stream.set_write_timeout(Some(Duration::from_secs(30))).ok();
                 
                 // 解析HTTP请求
-                match parse_http_request(&mut stream) {
-                    Ok(request_data) => {
-                        // 创建HttpRequest实例
-                        let request_value = create_http_request_instance(&request_data);
+                let limits = *handle.limits.lock();
+                match parse_http_request(&mut stream, limits) {
+                    Ok(mut request_data) => {
+                        let resolved = resolve_handler(&handle.routes.lock(), fallback, &request_data);
+                        let Some((handler, params)) = resolved else {
+                            write_http_response(
+                                &mut stream,
+                                404,
+                                &HashMap::new(),
+                                &format!("Not Found: {} {}", request_data.method, request_data.path),
+                                write_buffer_size.load(Ordering::SeqCst),
+                            ).ok();
+                            continue;
+                        };
+                        request_data.params = params;
+                        request_data.id = resolve_request_id(&request_data.headers);
                         
-                        // 通过回调通道调用handler
-                        match callback_channel.call(handler.clone(), vec![request_value]) {
+                        // 创建HttpRequest实例；声明了两个参数的 handler 还会收到 RequestContext
+                        let mut args = vec![create_http_request_instance(&request_data)];
+                        if handler.as_function().is_some_and(|f| f.arity >= 2) {
+                            args.push(create_request_context_instance(&request_data.id));
+                        }
+                        
+                        // 通过回调通道调用handler，执行期间的日志记录带上请求 ID
+                        match callback_channel.call_in_request(handler, args, Some(request_data.id.clone())) {
                             Ok(response_value) => {
-                                // 从response_value提取响应数据
-                                let (status, body, headers) = extract_response_data(&response_value)?;
+                                // 从response_value提取响应数据，并回传请求 ID
+                                let (status, body, mut headers) = extract_response_data(&response_value)?;
+                                if !headers.keys().any(|k| k.eq_ignore_ascii_case(REQUEST_ID_HEADER)) {
+                                    headers.insert(REQUEST_ID_HEADER.to_string(), request_data.id.clone());
+                                }
                                 
                                 // 构建并发送HTTP响应
-                                let response = build_http_response(status, &headers, &body);
-                                if let Err(e) = stream.write_all(response.as_bytes()) {
+                                let chunk_size = write_buffer_size.load(Ordering::SeqCst);
+                                if let Err(e) = write_http_response(&mut stream, status, &headers, &body, chunk_size) {
                                     eprintln!("Failed to send response: {}", e);
                                 }
-                                stream.flush().ok();
                             }
                             Err(e) => {
                                 // 发送500错误
-                                let response = build_http_response(
+                                write_http_response(
+                                    &mut stream,
                                     500,
-                                    &HashMap::new(),
+                                    &HashMap::from([(REQUEST_ID_HEADER.to_string(), request_data.id.clone())]),
                                     &format!("Internal Server Error: {}", e),
-                                );
-                                stream.write_all(response.as_bytes()).ok();
-                                stream.flush().ok();
+                                    write_buffer_size.load(Ordering::SeqCst),
+                                ).ok();
                             }
                         }
                     }
                     Err(e) => {
                         // 发送400错误
-                        let response = build_http_response(
+                        write_http_response(
+                            &mut stream,
                             400,
                             &HashMap::new(),
                             &format!("Bad Request: {}", e),
-                        );
-                        stream.write_all(response.as_bytes()).ok();
-                        stream.flush().ok();
+                            write_buffer_size.load(Ordering::SeqCst),
+                        ).ok();
                     }
                 }
             }
@@ -1065,6 +1488,47 @@ pub fn http_server_listen(
     Ok(Value::null())
 }
 
+/// HttpServer.route(method: string, path: string, handler: func(HttpRequest) HttpResponse) -> null
+/// 注册路由，路径中的 `:name` 段匹配任意一段并作为路径参数提取
+pub fn http_server_route(instance: &Value, args: &[Value]) -> Result<Value, String> {
+    if args.len() < 3 {
+        return Err("HttpServer.route requires 3 arguments: method, path, handler".to_string());
+    }
+    
+    let method = args[0].as_string()
+        .ok_or_else(|| "Invalid method: expected string".to_string())?;
+    add_route(instance, "route", method, &args[1..])
+}
+
+/// HttpServer.get/post/put/delete(path: string, handler) -> null
+/// 等价于 route(METHOD, path, handler)
+pub fn http_server_method_route(instance: &Value, method: &str, args: &[Value]) -> Result<Value, String> {
+    if args.len() < 2 {
+        return Err(format!("HttpServer.{} requires 2 arguments: path, handler", method.to_lowercase()));
+    }
+    
+    add_route(instance, &method.to_lowercase(), method, args)
+}
+
+/// 注册路由的公共实现，args 为 [path, handler]
+fn add_route(instance: &Value, api: &str, method: &str, args: &[Value]) -> Result<Value, String> {
+    let pattern = args[0].as_string()
+        .ok_or_else(|| "Invalid path: expected string".to_string())?;
+    let handler = args[1];
+    if !handler.is_function() {
+        return Err(format!("HttpServer.{}: invalid handler, expected function", api));
+    }
+    
+    let route = Route::new(method, pattern, handler)?;
+    let server_ptr = extract_handle_ptr(instance, "HttpServer")?;
+    let handle = unsafe { &*(server_ptr as *const HttpServerHandle) };
+    // 路由表不在 GC 根集中，handler 由回调线程使用
+    crate::vm::gc::gc_escape(&handler);
+    handle.routes.lock().push(route);
+    
+    Ok(Value::null())
+}
+
 /// 从HttpResponse实例提取响应数据
 fn extract_response_data(response: &Value) -> Result<(i32, String, HashMap<String, String>), String> {
     if let Some(class_instance) = response.as_class() {
@@ -1099,10 +1563,80 @@ pub fn http_server_stop(instance: &Value, _args: &[Value]) -> Result<Value, Stri
     Ok(Value::null())
 }
 
+/// HttpServer.port() -> int
+/// 实际监听的端口（构造时传入 0 则为系统分配的端口）
+pub fn http_server_port(instance: &Value, _args: &[Value]) -> Result<Value, String> {
+    let server_ptr = extract_handle_ptr(instance, "HttpServer")?;
+    let handle = unsafe { &*(server_ptr as *const HttpServerHandle) };
+    Ok(Value::int(handle.port as i128))
+}
+
+/// HttpServer.setWriteBufferSize(size: int) -> null
+/// 设置响应写缓冲区大小（字节），大于该大小的响应体分块写入
+pub fn http_server_set_write_buffer_size(instance: &Value, args: &[Value]) -> Result<Value, String> {
+    if args.is_empty() {
+        return Err("HttpServer.setWriteBufferSize requires 1 argument: size".to_string());
+    }
+    
+    let size = args[0].as_int()
+        .filter(|&n| n > 0)
+        .ok_or_else(|| "Invalid size: expected positive integer".to_string())?;
+    
+    let server_ptr = extract_handle_ptr(instance, "HttpServer")?;
+    let handle = unsafe { &*(server_ptr as *const HttpServerHandle) };
+    handle.write_buffer_size.store(size.min(usize::MAX as i128) as usize, Ordering::SeqCst);
+    
+    Ok(Value::null())
+}
+
+/// HttpServer.setRequestLimits(maxHeaders: int, maxQueryParams: int) -> null
+/// 设置请求头和查询参数的数量上限，超出的请求直接响应 400
+pub fn http_server_set_request_limits(instance: &Value, args: &[Value]) -> Result<Value, String> {
+    if args.len() < 2 {
+        return Err("HttpServer.setRequestLimits requires 2 arguments: maxHeaders, maxQueryParams".to_string());
+    }
+    
+    let positive = |value: &Value, name: &str| {
+        value.as_int()
+            .filter(|&n| n > 0)
+            .map(|n| n.min(usize::MAX as i128) as usize)
+            .ok_or_else(|| stdlib_exception("IllegalArgumentException", format!("Invalid {}: expected positive integer", name)))
+    };
+    let limits = RequestLimits {
+        max_headers: positive(&args[0], "maxHeaders")?,
+        max_query_params: positive(&args[1], "maxQueryParams")?,
+    };
+    
+    let server_ptr = extract_handle_ptr(instance, "HttpServer")?;
+    let handle = unsafe { &*(server_ptr as *const HttpServerHandle) };
+    *handle.limits.lock() = limits;
+    
+    Ok(Value::null())
+}
+
 // ============================================================================
 // HttpRequest 类方法实现
 // ============================================================================
 
+/// HttpRequest.id() / RequestContext.id() -> string
+/// 请求 ID：请求头 X-Request-Id 的值，没有时为服务端生成的 UUID
+pub fn http_request_id(instance: &Value, _args: &[Value]) -> Result<Value, String> {
+    Ok(Value::string(extract_request_id(instance).unwrap_or_default()))
+}
+
+/// RequestContext.httpClient(timeout_ms?: int) -> HttpClient
+/// 创建绑定到当前请求 ID 的客户端，发出的请求都带上 X-Request-Id
+pub fn request_context_http_client(instance: &Value, args: &[Value]) -> Result<Value, String> {
+    let request_id = extract_request_id(instance)
+        .ok_or_else(|| "RequestContext instance has no request id".to_string())?;
+    let timeout_ms = args.first()
+        .and_then(|v| v.as_int())
+        .map(|ms| ms as u64)
+        .unwrap_or(DEFAULT_TIMEOUT_MS);
+    let handle = Box::new(HttpClientHandle::with_request_id(timeout_ms, request_id));
+    Ok(create_http_client_instance(Box::into_raw(handle) as u64))
+}
+
 /// HttpRequest.getHeader(name: string) -> string
 pub fn http_request_get_header(instance: &Value, args: &[Value]) -> Result<Value, String> {
     if args.is_empty() {
@@ -1154,6 +1688,87 @@ pub fn http_request_get_query(instance: &Value, args: &[Value]) -> Result<Value,
     Ok(Value::string(String::new()))
 }
 
+/// HttpRequest.getParam(name: string) -> string
+/// 获取匹配路由提取的路径参数，不存在时返回空字符串
+pub fn http_request_get_param(instance: &Value, args: &[Value]) -> Result<Value, String> {
+    if args.is_empty() {
+        return Err("HttpRequest.getParam requires 1 argument: name".to_string());
+    }
+    
+    let name = args[0].as_string()
+        .ok_or_else(|| "Invalid name: expected string".to_string())?;
+    
+    if let Some(class_instance) = instance.as_class() {
+        let instance = class_instance.lock();
+        if let Some(params) = instance.fields.get("params") {
+            if let Some(map) = params.as_map() {
+                let map = map.lock();
+                if let Some(value) = map.get(name) {
+                    return Ok(*value);
+                }
+            }
+        }
+    }
+    
+    Ok(Value::string(String::new()))
+}
+
+// ============================================================================
+// HttpClientResponse 类方法实现
+// ============================================================================
+
+/// 读取HttpClientResponse实例的字段
+fn client_response_field(instance: &Value, field: &str) -> Option<Value> {
+    let class_instance = instance.as_class()?;
+    let instance = class_instance.lock();
+    instance.fields.get(field).copied()
+}
+
+/// HttpClientResponse.status() -> int
+pub fn http_client_response_status(instance: &Value, _args: &[Value]) -> Result<Value, String> {
+    Ok(client_response_field(instance, "status").unwrap_or_else(|| Value::int(0)))
+}
+
+/// HttpClientResponse.body() -> string
+pub fn http_client_response_body(instance: &Value, _args: &[Value]) -> Result<Value, String> {
+    Ok(client_response_field(instance, "body").unwrap_or_else(|| Value::string(String::new())))
+}
+
+/// HttpClientResponse.headers() -> map[string]string
+pub fn http_client_response_headers(instance: &Value, _args: &[Value]) -> Result<Value, String> {
+    Ok(client_response_field(instance, "headers")
+        .unwrap_or_else(|| create_string_map(&HashMap::new())))
+}
+
+/// HttpClientResponse.header(name: string) -> string
+/// 不区分大小写查找，不存在时返回空字符串
+pub fn http_client_response_header(instance: &Value, args: &[Value]) -> Result<Value, String> {
+    if args.is_empty() {
+        return Err("HttpClientResponse.header requires 1 argument: name".to_string());
+    }
+    
+    let name = args[0].as_string()
+        .ok_or_else(|| "Invalid name: expected string".to_string())?;
+    
+    if let Some(map) = client_response_field(instance, "headers").as_ref().and_then(|h| h.as_map()) {
+        let map = map.lock();
+        for (k, v) in map.iter() {
+            if k.eq_ignore_ascii_case(name) {
+                return Ok(*v);
+            }
+        }
+    }
+    
+    Ok(Value::string(String::new()))
+}
+
+/// HttpClientResponse.json() -> dynamic
+/// 按JSON解析响应体，与 Json.parse 相同
+pub fn http_client_response_json(instance: &Value, _args: &[Value]) -> Result<Value, String> {
+    let body = client_response_field(instance, "body").unwrap_or_else(|| Value::string(String::new()));
+    json::parse(&[body])
+}
+
 // ============================================================================
 // HttpResponse 类方法实现
 // ============================================================================
@@ -1217,3 +1832,214 @@ pub fn http_response_set_header(instance: &Value, args: &[Value]) -> Result<Valu
     
     Ok(Value::null())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 统计 write 调用次数的包装
+    struct CountingWriter {
+        data: Vec<u8>,
+        writes: usize,
+    }
+
+    impl Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.writes += 1;
+            self.data.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn write_response(body: &str, chunk_size: usize) -> CountingWriter {
+        let mut writer = CountingWriter { data: Vec::new(), writes: 0 };
+        let mut headers = HashMap::new();
+        headers.insert("X-Test".to_string(), "1".to_string());
+        write_http_response(&mut writer, 200, &headers, body, chunk_size).unwrap();
+        writer
+    }
+
+    #[test]
+    fn test_small_response_is_one_write() {
+        let writer = write_response("hello", DEFAULT_WRITE_BUFFER_SIZE);
+        assert_eq!(writer.writes, 1);
+        let text = String::from_utf8(writer.data).unwrap();
+        assert!(text.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(text.contains("Content-Length: 5\r\n"));
+        assert!(text.contains("X-Test: 1\r\n"));
+        assert!(text.ends_with("\r\n\r\nhello"));
+        let date = text.lines().find_map(|line| line.strip_prefix("Date: ")).expect("Date header");
+        assert!(date.ends_with(" GMT") && date.len() == 29, "{}", date);
+
+        assert_eq!(write_response("", DEFAULT_WRITE_BUFFER_SIZE).writes, 1);
+    }
+
+    #[test]
+    fn test_large_body_is_chunked() {
+        let body = "x".repeat(1024 * 1024);
+        for chunk_size in [DEFAULT_WRITE_BUFFER_SIZE, 100_000, 8192] {
+            let writer = write_response(&body, chunk_size);
+            assert_eq!(writer.writes, body.len().div_ceil(chunk_size), "chunk size {}", chunk_size);
+            assert!(writer.data.ends_with(body.as_bytes()));
+        }
+    }
+
+    fn request(method: &str, path: &str) -> HttpRequestData {
+        HttpRequestData {
+            method: method.to_string(),
+            path: path.to_string(),
+            query: HashMap::new(),
+            params: HashMap::new(),
+            headers: HashMap::new(),
+            body: String::new(),
+            id: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_request_parse_limits() {
+        let limits = RequestLimits { max_headers: 2, max_query_params: 2 };
+        let parse = |raw: &str| parse_http_request(&mut raw.as_bytes(), limits);
+
+        let ok = parse("GET /a?x=1&y=2 HTTP/1.1\r\nHost: h\r\nAccept: */*\r\n\r\n").unwrap();
+        assert_eq!(ok.query.len(), 2);
+        assert_eq!(ok.headers.len(), 2);
+
+        let err = parse("GET /a?x=1&y=2&z=3 HTTP/1.1\r\nHost: h\r\n\r\n").unwrap_err();
+        assert!(err.contains("too many query parameters (limit 2)"), "{}", err);
+        let err = parse("GET /a HTTP/1.1\r\nHost: h\r\nAccept: */*\r\nX-A: 1\r\n\r\n").unwrap_err();
+        assert!(err.contains("too many headers (limit 2)"), "{}", err);
+
+        // 超限的请求得到 400 响应
+        let mut out = Vec::new();
+        write_http_response(&mut out, 400, &HashMap::new(), &format!("Bad Request: {}", err), DEFAULT_WRITE_BUFFER_SIZE).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{}", text);
+        assert!(text.ends_with("Bad Request: too many headers (limit 2)"), "{}", text);
+    }
+
+    #[test]
+    fn test_route_extracts_path_params() {
+        let route = Route::new("get", "/users/:id/posts/:post", Value::int(1)).unwrap();
+        let params = route.matches("GET", "/users/42/posts/a%20b/").unwrap();
+        assert_eq!(params.get("id").map(String::as_str), Some("42"));
+        assert_eq!(params.get("post").map(String::as_str), Some("a b"));
+
+        assert!(route.matches("POST", "/users/42/posts/7").is_none());
+        assert!(route.matches("GET", "/users/42/posts").is_none());
+        assert!(route.matches("GET", "/users/42/posts/7/extra").is_none());
+        assert!(Route::new("GET", "users", Value::int(1)).is_err());
+        assert!(Route::new("GET", "/users/:", Value::int(1)).is_err());
+    }
+
+    #[test]
+    fn test_resolve_handler_order_and_fallback() {
+        let routes = vec![
+            Route::new("GET", "/users/me", Value::int(1)).unwrap(),
+            Route::new("GET", "/users/:id", Value::int(2)).unwrap(),
+        ];
+
+        let (handler, params) = resolve_handler(&routes, None, &request("GET", "/users/me")).unwrap();
+        assert_eq!(handler.as_int(), Some(1));
+        assert!(params.is_empty());
+
+        let (handler, params) = resolve_handler(&routes, None, &request("GET", "/users/7")).unwrap();
+        assert_eq!(handler.as_int(), Some(2));
+        assert_eq!(params.get("id").map(String::as_str), Some("7"));
+
+        // 未匹配：有 catch-all 时交给它，否则返回 None（404）
+        assert!(resolve_handler(&routes, None, &request("DELETE", "/users/7")).is_none());
+        let (handler, _) = resolve_handler(&routes, Some(Value::int(3)), &request("DELETE", "/users/7")).unwrap();
+        assert_eq!(handler.as_int(), Some(3));
+    }
+
+    /// 启动只处理一个连接的服务端，返回其地址；reply 为 None 时不回复（用于测试超时）
+    fn one_shot_server(reply: Option<&'static str>) -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _request = parse_http_request(&mut stream, RequestLimits::default());
+            match reply {
+                Some(reply) => stream.write_all(reply.as_bytes()).unwrap(),
+                None => thread::sleep(Duration::from_millis(1000)),
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn test_client_response_exposes_status_headers_and_body() {
+        let addr = one_shot_server(Some(
+            "HTTP/1.1 404 Not Found\r\nContent-Type: application/json\r\nContent-Length: 15\r\n\r\n{\"error\": true}",
+        ));
+        let client = HttpClientHandle::new(2000);
+        let data = client.request("GET", &format!("http://{}/x", addr), None, &HashMap::new()).unwrap();
+        let response = create_http_client_response_instance(&data);
+
+        assert_eq!(http_client_response_status(&response, &[]).unwrap().as_int(), Some(404));
+        let header = http_client_response_header(&response, &[Value::string("content-type".to_string())]).unwrap();
+        assert_eq!(header.as_string().map(|s| s.as_str()), Some("application/json"));
+        let body = http_client_response_body(&response, &[]).unwrap();
+        assert_eq!(body.as_string().map(|s| s.as_str()), Some("{\"error\": true}"));
+        let parsed = http_client_response_json(&response, &[]).unwrap();
+        let map = parsed.as_map().unwrap();
+        assert_eq!(map.lock().get("error").and_then(|v| v.as_bool()), Some(true));
+    }
+
+    #[test]
+    fn test_client_failures_are_throwable() {
+        use crate::stdlib::exception::parse_stdlib_exception;
+
+        let addr = one_shot_server(None);
+        let client = HttpClientHandle::new(100);
+        let err = client.request("GET", &format!("http://{}/", addr), None, &HashMap::new()).unwrap_err();
+        assert_eq!(parse_stdlib_exception(&err).map(|(class, _)| class), Some("TimeoutException"), "{}", err);
+
+        // 绑定后立即释放端口，连接会被拒绝
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let err = client.request("GET", &format!("http://{}/", closed), None, &HashMap::new()).unwrap_err();
+        assert_eq!(parse_stdlib_exception(&err).map(|(class, _)| class), Some("NetworkException"), "{}", err);
+
+        let err = client.request("GET", "ftp://example.com/", None, &HashMap::new()).unwrap_err();
+        assert_eq!(parse_stdlib_exception(&err).map(|(class, _)| class), Some("IllegalArgumentException"), "{}", err);
+    }
+
+    #[test]
+    fn test_get_async_requests_run_in_parallel() {
+        use std::time::Instant;
+        
+        // 每个请求延迟 200ms 才响应
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { break };
+                thread::spawn(move || {
+                    let mut buf = [0u8; 1024];
+                    let _ = stream.read(&mut buf);
+                    thread::sleep(Duration::from_millis(200));
+                    let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok");
+                });
+            }
+        });
+        
+        let pool = IoThreadPool::new(8);
+        let client = http_client_init(&[]).unwrap();
+        let url = Value::string(format!("http://{}/", addr));
+        let start = Instant::now();
+        let futures: Vec<Value> = (0..8)
+            .map(|_| http_client_get_async(&pool, &client, &[url]).unwrap())
+            .collect();
+        for future in &futures {
+            let response = future::future_await(future).unwrap();
+            assert_eq!(http_client_response_status(&response, &[]).unwrap().as_int(), Some(200));
+        }
+        // 8 个请求并行执行，总耗时接近单个请求
+        assert!(start.elapsed() < Duration::from_millis(800), "took {:?}", start.elapsed());
+    }
+}
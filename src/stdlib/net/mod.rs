@@ -1,12 +1,24 @@
 pub mod tcp;
 pub mod http;
+pub mod udp;
+pub mod dns;
 pub mod io_thread_pool;
 
-use super::{StdlibModule, CallbackChannel};
+use super::{StdlibModule, CallbackChannel, ClassDecl};
+use super::exception::stdlib_exception;
 use crate::vm::value::Value;
 use std::sync::Arc;
 use io_thread_pool::IoThreadPool;
 
+/// 将网络I/O错误转换为可抛出的异常：超时为 TimeoutException，其他为 NetworkException
+pub(crate) fn network_exception(context: &str, err: &std::io::Error) -> String {
+    let class_name = match err.kind() {
+        std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock => "TimeoutException",
+        _ => "NetworkException",
+    };
+    stdlib_exception(class_name, format!("{}: {}", context, err))
+}
+
 // ============================================================================
 // NetTcpLib - TCP标准库模块
 // ============================================================================
@@ -15,6 +27,12 @@ pub struct NetTcpLib {
     thread_pool: Arc<IoThreadPool>,
 }
 
+impl Default for NetTcpLib {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NetTcpLib {
     pub fn new() -> Self {
         Self {
@@ -27,11 +45,68 @@ impl NetTcpLib {
 // NetHttpLib - HTTP标准库模块
 // ============================================================================
 
-pub struct NetHttpLib;
+pub struct NetHttpLib {
+    /// 执行 getAsync 等异步请求
+    thread_pool: Arc<IoThreadPool>,
+}
+
+impl Default for NetHttpLib {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl NetHttpLib {
     pub fn new() -> Self {
-        Self
+        Self {
+            thread_pool: Arc::new(IoThreadPool::new(16)),
+        }
+    }
+}
+
+// ============================================================================
+// NetUdpLib - UDP标准库模块
+// ============================================================================
+
+pub struct NetUdpLib {
+    /// 执行阻塞的 receiveFrom，VM 线程只在读超时内等待
+    thread_pool: Arc<IoThreadPool>,
+}
+
+impl Default for NetUdpLib {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NetUdpLib {
+    pub fn new() -> Self {
+        Self {
+            thread_pool: Arc::new(IoThreadPool::new(16)),
+        }
+    }
+}
+
+// ============================================================================
+// NetDnsLib - DNS标准库模块
+// ============================================================================
+
+pub struct NetDnsLib {
+    /// 执行 resolveAsync
+    thread_pool: Arc<IoThreadPool>,
+}
+
+impl Default for NetDnsLib {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NetDnsLib {
+    pub fn new() -> Self {
+        Self {
+            thread_pool: Arc::new(IoThreadPool::new(4)),
+        }
     }
 }
 
@@ -109,12 +184,15 @@ impl StdlibModule for NetTcpLib {
                     "setWriteTimeout" => tcp::tcp_socket_set_write_timeout(instance, args),
                     "setNoDelay" => tcp::tcp_socket_set_no_delay(instance, args),
                     "shutdown" => tcp::tcp_socket_shutdown(instance, args),
+                    "setBuffered" => tcp::tcp_socket_set_buffered(instance, args),
+                    "flush" => tcp::tcp_socket_flush(instance, args),
                     _ => Err(format!("TCPSocket has no method '{}'", method_name)),
                 }
             }
             tcp::CLASS_TCPLISTENER => {
                 match method_name {
                     "accept" => tcp::tcp_listener_accept(instance, args),
+                    "port" => tcp::tcp_listener_port(instance, args),
                     "close" => tcp::tcp_listener_close(instance, args),
                     _ => Err(format!("TCPListener has no method '{}'", method_name)),
                 }
@@ -122,6 +200,10 @@ impl StdlibModule for NetTcpLib {
             _ => Err(format!("Unknown class '{}'", class_name)),
         }
     }
+
+    fn type_declarations(&self) -> Vec<ClassDecl> {
+        tcp::type_declarations()
+    }
 }
 
 // ============================================================================
@@ -138,19 +220,41 @@ impl StdlibModule for NetHttpLib {
             // HttpClient方法
             "HttpClient_init",
             "HttpClient_get",
+            "HttpClient_getText",
+            "HttpClient_getAsync",
             "HttpClient_post",
             "HttpClient_put",
             "HttpClient_delete",
             "HttpClient_request",
             "HttpClient_setTimeout",
+            "HttpClient_withContext",
             "HttpClient_close",
             // HttpServer方法
             "HttpServer_init",
             "HttpServer_listen",
+            "HttpServer_route",
+            "HttpServer_get",
+            "HttpServer_post",
+            "HttpServer_put",
+            "HttpServer_delete",
             "HttpServer_stop",
+            "HttpServer_port",
+            "HttpServer_setWriteBufferSize",
+            "HttpServer_setRequestLimits",
             // HttpRequest方法
+            "HttpRequest_id",
             "HttpRequest_getHeader",
             "HttpRequest_getQuery",
+            "HttpRequest_getParam",
+            // RequestContext方法
+            "RequestContext_id",
+            "RequestContext_httpClient",
+            // HttpClientResponse方法
+            "HttpClientResponse_status",
+            "HttpClientResponse_header",
+            "HttpClientResponse_headers",
+            "HttpClientResponse_body",
+            "HttpClientResponse_json",
             // HttpResponse方法
             "HttpResponse_init",
             "HttpResponse_text",
@@ -174,6 +278,8 @@ impl StdlibModule for NetHttpLib {
                 | http::CLASS_HTTP_SERVER
                 | http::CLASS_HTTP_REQUEST
                 | http::CLASS_HTTP_RESPONSE
+                | http::CLASS_HTTP_CLIENT_RESPONSE
+                | http::CLASS_REQUEST_CONTEXT
         )
     }
     
@@ -184,6 +290,10 @@ impl StdlibModule for NetHttpLib {
             http::CLASS_HTTP_RESPONSE => http::http_response_init(args),
             // HttpRequest不能直接构造，只能从服务端接收
             http::CLASS_HTTP_REQUEST => Err("HttpRequest cannot be constructed directly".to_string()),
+            // HttpClientResponse只能由HttpClient请求返回
+            http::CLASS_HTTP_CLIENT_RESPONSE => Err("HttpClientResponse cannot be constructed directly".to_string()),
+            // RequestContext由服务端传给handler
+            http::CLASS_REQUEST_CONTEXT => Err("RequestContext cannot be constructed directly".to_string()),
             _ => Err(format!("Class '{}' not found in module '{}'", class_name, self.name())),
         }
     }
@@ -202,11 +312,14 @@ impl StdlibModule for NetHttpLib {
             http::CLASS_HTTP_CLIENT => {
                 match method_name {
                     "get" => http::http_client_get(instance, args),
+                    "getText" => http::http_client_get_text(instance, args),
+                    "getAsync" => http::http_client_get_async(&self.thread_pool, instance, args),
                     "post" => http::http_client_post(instance, args),
                     "put" => http::http_client_put(instance, args),
                     "delete" => http::http_client_delete(instance, args),
                     "request" => http::http_client_request(instance, args),
                     "setTimeout" => http::http_client_set_timeout(instance, args),
+                    "withContext" => http::http_client_with_context(instance, args),
                     "close" => http::http_client_close(instance, args),
                     _ => Err(format!("HttpClient has no method '{}'", method_name)),
                 }
@@ -215,17 +328,44 @@ impl StdlibModule for NetHttpLib {
                 match method_name {
                     // listen需要回调支持，不能通过普通call_method调用
                     "listen" => Err("HttpServer.listen requires callback support, use call_method_with_callback".to_string()),
+                    "route" => http::http_server_route(instance, args),
+                    "get" => http::http_server_method_route(instance, "GET", args),
+                    "post" => http::http_server_method_route(instance, "POST", args),
+                    "put" => http::http_server_method_route(instance, "PUT", args),
+                    "delete" => http::http_server_method_route(instance, "DELETE", args),
                     "stop" => http::http_server_stop(instance, args),
+                    "port" => http::http_server_port(instance, args),
+                    "setWriteBufferSize" => http::http_server_set_write_buffer_size(instance, args),
+                    "setRequestLimits" => http::http_server_set_request_limits(instance, args),
                     _ => Err(format!("HttpServer has no method '{}'", method_name)),
                 }
             }
             http::CLASS_HTTP_REQUEST => {
                 match method_name {
+                    "id" => http::http_request_id(instance, args),
                     "getHeader" => http::http_request_get_header(instance, args),
                     "getQuery" => http::http_request_get_query(instance, args),
+                    "getParam" => http::http_request_get_param(instance, args),
                     _ => Err(format!("HttpRequest has no method '{}'", method_name)),
                 }
             }
+            http::CLASS_REQUEST_CONTEXT => {
+                match method_name {
+                    "id" => http::http_request_id(instance, args),
+                    "httpClient" => http::request_context_http_client(instance, args),
+                    _ => Err(format!("RequestContext has no method '{}'", method_name)),
+                }
+            }
+            http::CLASS_HTTP_CLIENT_RESPONSE => {
+                match method_name {
+                    "status" => http::http_client_response_status(instance, args),
+                    "header" => http::http_client_response_header(instance, args),
+                    "headers" => http::http_client_response_headers(instance, args),
+                    "body" => http::http_client_response_body(instance, args),
+                    "json" => http::http_client_response_json(instance, args),
+                    _ => Err(format!("HttpClientResponse has no method '{}'", method_name)),
+                }
+            }
             http::CLASS_HTTP_RESPONSE => {
                 match method_name {
                     "text" => http::http_response_text(instance, args),
@@ -237,6 +377,10 @@ impl StdlibModule for NetHttpLib {
         }
     }
     
+    fn type_declarations(&self) -> Vec<ClassDecl> {
+        http::type_declarations()
+    }
+    
     fn needs_callback(&self, class_name: &str, method_name: &str) -> bool {
         // HttpServer.listen需要回调支持
         class_name == http::CLASS_HTTP_SERVER && method_name == "listen"
@@ -268,3 +412,77 @@ impl StdlibModule for NetHttpLib {
         }
     }
 }
+
+// ============================================================================
+// NetUdpLib - StdlibModule实现
+// ============================================================================
+
+impl StdlibModule for NetUdpLib {
+    fn name(&self) -> &'static str {
+        "std.net.udp"
+    }
+
+    fn exports(&self) -> Vec<&'static str> {
+        vec!["UDPSocket_bind"]
+    }
+
+    fn call(&self, name: &str, args: &[Value]) -> Result<Value, String> {
+        match name {
+            "UDPSocket_bind" => udp::udp_socket_bind(args),
+            _ => Err(format!("Unknown function: {}", name)),
+        }
+    }
+
+    fn has_class(&self, class_name: &str) -> bool {
+        class_name == udp::CLASS_UDPSOCKET || class_name == udp::CLASS_UDPPACKET
+    }
+
+    fn create_class_instance(&self, class_name: &str, args: &[Value]) -> Result<Value, String> {
+        match class_name {
+            udp::CLASS_UDPSOCKET => udp::udp_socket_bind(args),
+            _ => Err(format!("Class '{}' cannot be instantiated directly", class_name)),
+        }
+    }
+
+    fn call_method(&self, instance: &Value, method_name: &str, args: &[Value]) -> Result<Value, String> {
+        // 从实例中提取类名
+        let class_name = match instance.as_class() {
+            Some(class_instance) => class_instance.lock().class_name.clone(),
+            None => return Err("Value is not a class instance".to_string()),
+        };
+
+        match class_name.as_str() {
+            udp::CLASS_UDPSOCKET => match method_name {
+                "sendTo" => udp::udp_socket_send_to(instance, args),
+                "receiveFrom" => udp::udp_socket_receive_from(&self.thread_pool, instance, args),
+                "setReadTimeout" => udp::udp_socket_set_read_timeout(instance, args),
+                "localAddress" => udp::udp_socket_local_address(instance, args),
+                "close" => udp::udp_socket_close(instance, args),
+                _ => Err(format!("UDPSocket has no method '{}'", method_name)),
+            },
+            _ => Err(format!("{} has no method '{}'", class_name, method_name)),
+        }
+    }
+}
+
+// ============================================================================
+// NetDnsLib - StdlibModule实现
+// ============================================================================
+
+impl StdlibModule for NetDnsLib {
+    fn name(&self) -> &'static str {
+        "std.net.dns"
+    }
+
+    fn exports(&self) -> Vec<&'static str> {
+        vec!["Dns_resolve", "Dns_resolveAsync"]
+    }
+
+    fn call(&self, name: &str, args: &[Value]) -> Result<Value, String> {
+        match name {
+            "Dns_resolve" => dns::dns_resolve(args),
+            "Dns_resolveAsync" => dns::dns_resolve_async(&self.thread_pool, args),
+            _ => Err(format!("Unknown function: {}", name)),
+        }
+    }
+}
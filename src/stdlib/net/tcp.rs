@@ -5,11 +5,82 @@ use std::sync::Arc;
 use parking_lot::Mutex;
 use crate::vm::value::Value;
 use std::collections::HashMap;
+use crate::stdlib::declarations::{bytes, class, optional, param, ClassDecl};
+use crate::types::Type;
+
+/// 缓冲模式下写缓冲区的容量，超过后自动刷新
+const SOCKET_WRITE_BUFFER_SIZE: usize = 8192;
 
 // Socket包装（存储在堆上）
 pub struct TcpSocketHandle {
     stream: Arc<Mutex<Option<TcpStream>>>,
     closed: Arc<Mutex<bool>>,
+    /// 写缓冲区：None 表示非缓冲模式，每次 send 直接写入
+    write_buffer: Arc<Mutex<Option<Vec<u8>>>>,
+}
+
+impl TcpSocketHandle {
+    fn new(stream: TcpStream) -> Self {
+        Self {
+            stream: Arc::new(Mutex::new(Some(stream))),
+            closed: Arc::new(Mutex::new(false)),
+            write_buffer: Arc::new(Mutex::new(None)),
+        }
+    }
+}
+
+/// 写入数据：缓冲模式下先追加到缓冲区，缓冲区满时一次性写出
+fn buffered_write<W: Write>(buffer: &mut Option<Vec<u8>>, stream: &mut W, bytes: &[u8]) -> Result<usize, String> {
+    match buffer {
+        Some(pending) => {
+            pending.extend_from_slice(bytes);
+            if pending.len() >= SOCKET_WRITE_BUFFER_SIZE {
+                flush_buffer(pending, stream)?;
+            }
+            Ok(bytes.len())
+        }
+        None => stream.write(bytes).map_err(|e| format!("Write error: {}", e)),
+    }
+}
+
+/// 写出缓冲区中的全部数据
+fn flush_buffer<W: Write>(pending: &mut Vec<u8>, stream: &mut W) -> Result<(), String> {
+    if pending.is_empty() {
+        return Ok(());
+    }
+    stream.write_all(pending).map_err(|e| format!("Write error: {}", e))?;
+    pending.clear();
+    Ok(())
+}
+
+/// 刷新 socket 的写缓冲区（非缓冲模式下无操作）
+fn flush_pending(handle: &TcpSocketHandle, stream: &mut TcpStream) -> Result<(), String> {
+    match handle.write_buffer.lock().as_mut() {
+        Some(pending) => flush_buffer(pending, stream),
+        None => Ok(()),
+    }
+}
+
+/// 关闭 socket：先刷新缓冲区，再关闭连接
+fn close_socket(handle: &TcpSocketHandle) -> Result<(), String> {
+    let mut closed = handle.closed.lock();
+    if *closed {
+        // 已经关闭，直接返回（防止双重释放）
+        return Ok(());
+    }
+
+    // 标记为已关闭
+    *closed = true;
+
+    // 关闭stream（缓冲的数据必须在关闭前写出）
+    let mut result = Ok(());
+    if let Some(mut stream) = handle.stream.lock().take() {
+        result = flush_pending(handle, &mut stream);
+        drop(stream);  // 显式关闭TCP连接
+    }
+    handle.write_buffer.lock().take();
+
+    result
 }
 
 // Listener包装
@@ -91,6 +162,32 @@ pub fn create_tcp_listener_instance(ptr: u64) -> Value {
     Value::class(Arc::new(Mutex::new(instance)))
 }
 
+// ============================================================================
+// 类型声明
+// ============================================================================
+
+/// std.net.tcp 中类的类型声明
+pub fn type_declarations() -> Vec<ClassDecl> {
+    vec![
+        ClassDecl::new("TCPSocket")
+            .constructor(vec![param("host", Type::String), param("port", Type::Int), optional("timeout", Type::Int)])
+            .method("send", vec![param("data", bytes())], Type::Int)
+            .method("receive", vec![param("buffer", bytes())], Type::Int)
+            .method("close", vec![], Type::Null)
+            .method("setReadTimeout", vec![param("timeout_ms", Type::Int)], Type::Null)
+            .method("setWriteTimeout", vec![param("timeout_ms", Type::Int)], Type::Null)
+            .method("setNoDelay", vec![param("enabled", Type::Bool)], Type::Null)
+            .method("setBuffered", vec![param("enabled", Type::Bool)], Type::Null)
+            .method("flush", vec![], Type::Null)
+            .method("shutdown", vec![], Type::Null),
+        ClassDecl::new("TCPListener")
+            .constructor(vec![param("host", Type::String), param("port", Type::Int)])
+            .method("accept", vec![], class("TCPSocket"))
+            .method("port", vec![], Type::Int)
+            .method("close", vec![], Type::Null),
+    ]
+}
+
 // ============================================================================
 // TCPSocket 类方法实现
 // ============================================================================
@@ -122,10 +219,7 @@ pub fn tcp_socket_init(args: &[Value]) -> Result<Value, String> {
         .map_err(|e| format!("Connection failed: {}", e))?;
 
     // 创建handle并包装为类实例
-    let handle = Box::new(TcpSocketHandle {
-        stream: Arc::new(Mutex::new(Some(stream))),
-        closed: Arc::new(Mutex::new(false)),
-    });
+    let handle = Box::new(TcpSocketHandle::new(stream));
     let ptr = Box::into_raw(handle) as u64;
 
     Ok(create_tcp_socket_instance(ptr))
@@ -159,8 +253,7 @@ pub fn tcp_socket_send(instance: &Value, args: &[Value]) -> Result<Value, String
         .filter_map(|v: &Value| v.as_int().map(|i| i as u8))
         .collect();
 
-    let n = stream.write(&bytes)
-        .map_err(|e| format!("Write error: {}", e))?;
+    let n = buffered_write(&mut handle.write_buffer.lock(), stream, &bytes)?;
 
     Ok(Value::int(n as i128))
 }
@@ -187,6 +280,9 @@ pub fn tcp_socket_receive(instance: &Value, args: &[Value]) -> Result<Value, Str
     let stream = stream_opt.as_mut()
         .ok_or_else(|| "Socket is closed".to_string())?;
 
+    // 等待对端响应前必须先写出缓冲的请求数据
+    flush_pending(handle, stream)?;
+
     let buffer_len = buffer.lock().len();
     let mut buf = vec![0u8; buffer_len];
 
@@ -210,19 +306,7 @@ pub fn tcp_socket_close(instance: &Value, _args: &[Value]) -> Result<Value, Stri
     let socket_ptr = extract_socket_ptr_from_instance(instance)?;
     let handle = unsafe { &*(socket_ptr as *const TcpSocketHandle) };
 
-    let mut closed = handle.closed.lock();
-    if *closed {
-        // 已经关闭，直接返回（防止双重释放）
-        return Ok(Value::null());
-    }
-
-    // 标记为已关闭
-    *closed = true;
-
-    // 关闭stream
-    if let Some(stream) = handle.stream.lock().take() {
-        drop(stream);  // 显式关闭TCP连接
-    }
+    close_socket(handle)?;
 
     Ok(Value::null())
 }
@@ -322,16 +406,74 @@ pub fn tcp_socket_shutdown(instance: &Value, _args: &[Value]) -> Result<Value, S
         return Err("Socket is closed".to_string());
     }
 
-    let stream_opt = handle.stream.lock();
-    let stream = stream_opt.as_ref()
+    let mut stream_opt = handle.stream.lock();
+    let stream = stream_opt.as_mut()
         .ok_or_else(|| "Socket is closed".to_string())?;
 
+    // 关闭写端前写出缓冲的数据
+    flush_pending(handle, stream)?;
+
     stream.shutdown(Shutdown::Write)
         .map_err(|e| format!("Failed to shutdown: {}", e))?;
 
     Ok(Value::null())
 }
 
+/// TCPSocket.setBuffered(enabled: bool) -> null
+/// 开启或关闭缓冲写模式；关闭时先写出已缓冲的数据
+pub fn tcp_socket_set_buffered(instance: &Value, args: &[Value]) -> Result<Value, String> {
+    if args.is_empty() {
+        return Err("TCPSocket.setBuffered requires 1 argument: enabled".to_string());
+    }
+
+    let socket_ptr = extract_socket_ptr_from_instance(instance)?;
+    let enabled = args[0].as_bool()
+        .ok_or_else(|| "Invalid boolean value: expected boolean".to_string())?;
+
+    let handle = unsafe { &*(socket_ptr as *const TcpSocketHandle) };
+
+    // 检查是否已关闭
+    if *handle.closed.lock() {
+        return Err("Socket is closed".to_string());
+    }
+
+    let mut stream_opt = handle.stream.lock();
+    let stream = stream_opt.as_mut()
+        .ok_or_else(|| "Socket is closed".to_string())?;
+
+    let mut buffer = handle.write_buffer.lock();
+    match (enabled, buffer.as_mut()) {
+        (true, None) => *buffer = Some(Vec::with_capacity(SOCKET_WRITE_BUFFER_SIZE)),
+        (false, Some(pending)) => {
+            flush_buffer(pending, stream)?;
+            *buffer = None;
+        }
+        _ => {}
+    }
+
+    Ok(Value::null())
+}
+
+/// TCPSocket.flush() -> null
+/// 写出缓冲区中的数据（非缓冲模式下无操作）
+pub fn tcp_socket_flush(instance: &Value, _args: &[Value]) -> Result<Value, String> {
+    let socket_ptr = extract_socket_ptr_from_instance(instance)?;
+    let handle = unsafe { &*(socket_ptr as *const TcpSocketHandle) };
+
+    // 检查是否已关闭
+    if *handle.closed.lock() {
+        return Err("Socket is closed".to_string());
+    }
+
+    let mut stream_opt = handle.stream.lock();
+    let stream = stream_opt.as_mut()
+        .ok_or_else(|| "Socket is closed".to_string())?;
+
+    flush_pending(handle, stream)?;
+
+    Ok(Value::null())
+}
+
 // ============================================================================
 // TCPListener 类方法实现
 // ============================================================================
@@ -379,15 +521,27 @@ pub fn tcp_listener_accept(instance: &Value, _args: &[Value]) -> Result<Value, S
     let (stream, _) = listener.accept()
         .map_err(|e| format!("Accept failed: {}", e))?;
 
-    let socket_handle = Box::new(TcpSocketHandle {
-        stream: Arc::new(Mutex::new(Some(stream))),
-        closed: Arc::new(Mutex::new(false)),
-    });
+    let socket_handle = Box::new(TcpSocketHandle::new(stream));
     let ptr = Box::into_raw(socket_handle) as u64;
 
     Ok(create_tcp_socket_instance(ptr))
 }
 
+/// TCPListener.port() -> int
+/// 实际监听的端口（构造时传入 0 则为系统分配的端口）
+pub fn tcp_listener_port(instance: &Value, _args: &[Value]) -> Result<Value, String> {
+    let listener_ptr = extract_listener_ptr_from_instance(instance)?;
+    let handle = unsafe { &*(listener_ptr as *const TcpListenerHandle) };
+
+    let listener_opt = handle.listener.lock();
+    let listener = listener_opt.as_ref()
+        .ok_or_else(|| "Listener is closed".to_string())?;
+    let addr = listener.local_addr()
+        .map_err(|e| format!("Failed to read local address: {}", e))?;
+
+    Ok(Value::int(addr.port() as i128))
+}
+
 /// TCPListener.close() -> null
 /// 关闭listener
 pub fn tcp_listener_close(instance: &Value, _args: &[Value]) -> Result<Value, String> {
@@ -470,8 +624,7 @@ pub fn socket_send(args: &[Value]) -> Result<Value, String> {
         .filter_map(|v: &Value| v.as_int().map(|i| i as u8))
         .collect();
 
-    let n = stream.write(&bytes)
-        .map_err(|e| format!("Write error: {}", e))?;
+    let n = buffered_write(&mut handle.write_buffer.lock(), stream, &bytes)?;
 
     Ok(Value::int(n as i128))
 }
@@ -497,6 +650,9 @@ pub fn socket_receive(args: &[Value]) -> Result<Value, String> {
     let stream = stream_opt.as_mut()
         .ok_or_else(|| "Socket is closed".to_string())?;
 
+    // 等待对端响应前必须先写出缓冲的请求数据
+    flush_pending(handle, stream)?;
+
     let buffer_len = buffer.lock().len();
     let mut buf = vec![0u8; buffer_len];
 
@@ -521,19 +677,7 @@ pub fn socket_close(args: &[Value]) -> Result<Value, String> {
     let socket_ptr = extract_socket_ptr(&args[0])?;
     let handle = unsafe { &*(socket_ptr as *const TcpSocketHandle) };
 
-    let mut closed = handle.closed.lock();
-    if *closed {
-        // 已经关闭，直接返回（防止双重释放）
-        return Ok(Value::null());
-    }
-
-    // 标记为已关闭
-    *closed = true;
-
-    // 关闭stream
-    if let Some(stream) = handle.stream.lock().take() {
-        drop(stream);  // 显式关闭TCP连接
-    }
+    close_socket(handle)?;
 
     Ok(Value::null())
 }
@@ -629,10 +773,13 @@ pub fn socket_shutdown(args: &[Value]) -> Result<Value, String> {
         return Err("Socket is closed".to_string());
     }
 
-    let stream_opt = handle.stream.lock();
-    let stream = stream_opt.as_ref()
+    let mut stream_opt = handle.stream.lock();
+    let stream = stream_opt.as_mut()
         .ok_or_else(|| "Socket is closed".to_string())?;
 
+    // 关闭写端前写出缓冲的数据
+    flush_pending(handle, stream)?;
+
     stream.shutdown(Shutdown::Write)
         .map_err(|e| format!("Failed to shutdown: {}", e))?;
 
@@ -664,10 +811,7 @@ pub fn listener_accept(args: &[Value]) -> Result<Value, String> {
     let (stream, _) = listener.accept()
         .map_err(|e| format!("Accept failed: {}", e))?;
 
-    let socket_handle = Box::new(TcpSocketHandle {
-        stream: Arc::new(Mutex::new(Some(stream))),
-        closed: Arc::new(Mutex::new(false)),
-    });
+    let socket_handle = Box::new(TcpSocketHandle::new(stream));
     let ptr = Box::into_raw(socket_handle) as u64;
 
     Ok(create_tcp_socket_value(ptr))
@@ -698,3 +842,107 @@ pub fn listener_close(args: &[Value]) -> Result<Value, String> {
 
     Ok(Value::null())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    struct CountingWriter {
+        data: Vec<u8>,
+        writes: usize,
+    }
+
+    impl Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.writes += 1;
+            self.data.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_buffered_write_coalesces() {
+        let mut writer = CountingWriter { data: Vec::new(), writes: 0 };
+
+        let mut unbuffered = None;
+        buffered_write(&mut unbuffered, &mut writer, b"ab").unwrap();
+        buffered_write(&mut unbuffered, &mut writer, b"cd").unwrap();
+        assert_eq!(writer.writes, 2);
+
+        let mut buffer = Some(Vec::new());
+        for _ in 0..3 {
+            assert_eq!(buffered_write(&mut buffer, &mut writer, b"xyz").unwrap(), 3);
+        }
+        assert_eq!(writer.writes, 2);
+        flush_buffer(buffer.as_mut().unwrap(), &mut writer).unwrap();
+        assert_eq!(writer.writes, 3);
+        assert_eq!(writer.data, b"abcdxyzxyzxyz");
+
+        // 缓冲区满时自动写出
+        let big = vec![0u8; SOCKET_WRITE_BUFFER_SIZE];
+        buffered_write(&mut buffer, &mut writer, &big).unwrap();
+        assert_eq!(writer.writes, 4);
+        assert!(buffer.unwrap().is_empty());
+    }
+
+    fn bytes_value(bytes: &[u8]) -> Value {
+        Value::array(Arc::new(Mutex::new(bytes.iter().map(|&b| Value::int(b as i128)).collect())))
+    }
+
+    fn receive_exact(socket: &Value, len: usize) -> Vec<u8> {
+        let mut received = Vec::new();
+        while received.len() < len {
+            let buffer = bytes_value(&vec![0u8; len - received.len()]);
+            let n = tcp_socket_receive(socket, &[buffer]).unwrap().as_int().unwrap() as usize;
+            assert!(n > 0, "connection closed early");
+            received.extend(buffer.as_array().unwrap().lock()[..n].iter().map(|v| v.as_int().unwrap() as u8));
+        }
+        received
+    }
+
+    #[test]
+    fn test_buffered_socket_round_trip() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 1024];
+            let mut all = Vec::new();
+            loop {
+                match stream.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        all.extend_from_slice(&buf[..n]);
+                        // 对端关闭后回写可能失败，忽略
+                        let _ = stream.write_all(&buf[..n]);
+                    }
+                }
+            }
+            all
+        });
+
+        let socket = tcp_socket_init(&[Value::string("127.0.0.1".to_string()), Value::int(port as i128)]).unwrap();
+        tcp_socket_set_read_timeout(&socket, &[Value::int(5000)]).unwrap();
+        tcp_socket_set_buffered(&socket, &[Value::bool(true)]).unwrap();
+
+        // 显式 flush
+        tcp_socket_send(&socket, &[bytes_value(b"he")]).unwrap();
+        tcp_socket_send(&socket, &[bytes_value(b"llo")]).unwrap();
+        tcp_socket_flush(&socket, &[]).unwrap();
+        assert_eq!(receive_exact(&socket, 5), b"hello");
+
+        // receive 前自动 flush
+        tcp_socket_send(&socket, &[bytes_value(b"ping")]).unwrap();
+        assert_eq!(receive_exact(&socket, 4), b"ping");
+
+        // close 时写出缓冲的数据
+        tcp_socket_send(&socket, &[bytes_value(b"bye")]).unwrap();
+        tcp_socket_close(&socket, &[]).unwrap();
+        assert_eq!(server.join().unwrap(), b"hellopingbye");
+    }
+}
@@ -0,0 +1,315 @@
+//! UDP标准库实现
+//!
+//! 提供UDPSocket类：绑定本地地址后向任意地址发送数据报，并接收来自任意地址的数据报。
+//! 接收在 IO 线程池中执行，VM 线程只在设置的读超时内等待结果。
+
+use std::collections::HashMap;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::Arc;
+use std::time::Duration;
+use crossbeam_channel::{bounded, RecvTimeoutError};
+use parking_lot::Mutex;
+use crate::vm::value::{Value, ClassInstance};
+use crate::stdlib::exception::stdlib_exception;
+use super::io_thread_pool::IoThreadPool;
+use super::network_exception;
+
+// 标准库类名常量
+pub const CLASS_UDPSOCKET: &str = "std.net.udp.UDPSocket";
+pub const CLASS_UDPPACKET: &str = "std.net.udp.UDPPacket";
+
+/// 单个数据报的最大长度（IPv4 UDP 负载上限）
+const MAX_DATAGRAM_SIZE: usize = 65507;
+
+/// VM 线程在读超时之外额外等待的时间，留给 IO 线程把超时结果送回来
+const RECEIVE_GRACE: Duration = Duration::from_millis(100);
+
+// Socket包装（存储在堆上）
+pub struct UdpSocketHandle {
+    /// 关闭后为 None；接收任务持有 Arc 的克隆，不会访问已释放的 socket
+    socket: Mutex<Option<Arc<UdpSocket>>>,
+    read_timeout: Mutex<Option<Duration>>,
+}
+
+impl UdpSocketHandle {
+    fn new(socket: UdpSocket) -> Self {
+        Self {
+            socket: Mutex::new(Some(Arc::new(socket))),
+            read_timeout: Mutex::new(None),
+        }
+    }
+
+    fn socket(&self) -> Result<Arc<UdpSocket>, String> {
+        self.socket.lock()
+            .clone()
+            .ok_or_else(|| stdlib_exception("NetworkException", "Socket is closed"))
+    }
+}
+
+/// 解析 "host:port" 形式的地址
+fn resolve_addr(addr: &str) -> Result<SocketAddr, String> {
+    addr.to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .ok_or_else(|| stdlib_exception("IllegalArgumentException", format!("Invalid address: {}", addr)))
+}
+
+fn string_arg<'a>(args: &'a [Value], index: usize, func: &str, name: &str) -> Result<&'a String, String> {
+    args.get(index)
+        .and_then(|v| v.as_string())
+        .ok_or_else(|| stdlib_exception(
+            "IllegalArgumentException",
+            format!("{} expects argument '{}' to be a string", func, name),
+        ))
+}
+
+fn int_arg(args: &[Value], index: usize, func: &str, name: &str) -> Result<i128, String> {
+    args.get(index)
+        .and_then(|v| v.as_int())
+        .ok_or_else(|| stdlib_exception(
+            "IllegalArgumentException",
+            format!("{} expects argument '{}' to be an int", func, name),
+        ))
+}
+
+/// 将 int[] 转换为字节，元素必须在 0..=255 范围内
+fn bytes_arg(args: &[Value], index: usize, func: &str) -> Result<Vec<u8>, String> {
+    let data = args.get(index)
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| stdlib_exception(
+            "IllegalArgumentException",
+            format!("{} expects argument 'data' to be an int[]", func),
+        ))?;
+    data.lock()
+        .iter()
+        .map(|v| v.as_int().and_then(|n| u8::try_from(n).ok()))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(|| stdlib_exception(
+            "IllegalArgumentException",
+            format!("{} expects every byte in 'data' to be between 0 and 255", func),
+        ))
+}
+
+// 从ClassInstance提取原生指针（存储在"__handle"字段中）
+fn extract_socket_handle(instance: &Value) -> Result<&'static UdpSocketHandle, String> {
+    if let Some(class_instance) = instance.as_class() {
+        let instance = class_instance.lock();
+        if let Some(ptr) = instance.fields.get("__handle").and_then(|v| v.as_int()) {
+            return Ok(unsafe { &*(ptr as u64 as *const UdpSocketHandle) });
+        }
+        Err("UDPSocket instance has no valid handle".to_string())
+    } else {
+        Err("Value is not a UDPSocket instance".to_string())
+    }
+}
+
+// 创建UDPSocket类实例
+fn create_udp_socket_instance(ptr: u64) -> Value {
+    let mut fields = HashMap::new();
+    fields.insert("__handle".to_string(), Value::int(ptr as i128));
+
+    let instance = ClassInstance {
+        class_name: CLASS_UDPSOCKET.to_string(),
+        parent_class: None,
+        fields,
+    };
+
+    Value::class(Arc::new(Mutex::new(instance)))
+}
+
+/// 创建UDPPacket实例：data 为收到的字节，address 为发送方的 "host:port"
+fn create_udp_packet_instance(data: &[u8], addr: SocketAddr) -> Value {
+    let bytes: Vec<Value> = data.iter().map(|&b| Value::int(b as i128)).collect();
+
+    let mut fields = HashMap::new();
+    fields.insert("data".to_string(), Value::array(Arc::new(Mutex::new(bytes))));
+    fields.insert("address".to_string(), Value::string(addr.to_string()));
+
+    let instance = ClassInstance {
+        class_name: CLASS_UDPPACKET.to_string(),
+        parent_class: None,
+        fields,
+    };
+
+    Value::class(Arc::new(Mutex::new(instance)))
+}
+
+// ============================================================================
+// UDPSocket 类方法实现
+// ============================================================================
+
+/// UDPSocket 构造函数 / bind(address: string) -> UDPSocket
+///
+/// 绑定本地地址，端口为 0 时由系统分配（可通过 localAddress() 获取）
+pub fn udp_socket_bind(args: &[Value]) -> Result<Value, String> {
+    let addr = resolve_addr(string_arg(args, 0, "UDPSocket.bind", "address")?)?;
+    let socket = UdpSocket::bind(addr)
+        .map_err(|e| network_exception(&format!("Failed to bind {}", addr), &e))?;
+
+    let handle = Box::new(UdpSocketHandle::new(socket));
+    let ptr = Box::into_raw(handle) as u64;
+
+    Ok(create_udp_socket_instance(ptr))
+}
+
+/// UDPSocket.sendTo(data: int[], address: string) -> int
+/// 发送一个数据报，返回发送的字节数
+pub fn udp_socket_send_to(instance: &Value, args: &[Value]) -> Result<Value, String> {
+    let handle = extract_socket_handle(instance)?;
+    let bytes = bytes_arg(args, 0, "UDPSocket.sendTo")?;
+    let addr = resolve_addr(string_arg(args, 1, "UDPSocket.sendTo", "address")?)?;
+
+    let n = handle.socket()?
+        .send_to(&bytes, addr)
+        .map_err(|e| network_exception(&format!("Failed to send to {}", addr), &e))?;
+
+    Ok(Value::int(n as i128))
+}
+
+/// UDPSocket.receiveFrom(maxLen: int) -> UDPPacket
+///
+/// 接收一个数据报，超出 maxLen 的部分被丢弃。接收在 IO 线程池中进行；
+/// 设置了读超时时，超时后抛出 TimeoutException，VM 线程不会被无限期阻塞。
+pub fn udp_socket_receive_from(pool: &IoThreadPool, instance: &Value, args: &[Value]) -> Result<Value, String> {
+    let handle = extract_socket_handle(instance)?;
+    let max_len = int_arg(args, 0, "UDPSocket.receiveFrom", "maxLen")?;
+    if max_len <= 0 || max_len > MAX_DATAGRAM_SIZE as i128 {
+        return Err(stdlib_exception(
+            "IllegalArgumentException",
+            format!("UDPSocket.receiveFrom maxLen must be between 1 and {}, got {}", MAX_DATAGRAM_SIZE, max_len),
+        ));
+    }
+
+    let socket = handle.socket()?;
+    let timeout = *handle.read_timeout.lock();
+
+    let (tx, rx) = bounded(1);
+    pool.execute(move || {
+        let mut buf = vec![0u8; max_len as usize];
+        let result = socket.recv_from(&mut buf).map(|(n, addr)| {
+            buf.truncate(n);
+            (buf, addr)
+        });
+        // VM 线程已放弃等待时接收端已关闭，结果直接丢弃
+        let _ = tx.send(result);
+    });
+
+    let timed_out = |timeout: Duration| stdlib_exception(
+        "TimeoutException",
+        format!("UDPSocket.receiveFrom timed out after {}ms", timeout.as_millis()),
+    );
+    let result = match timeout {
+        Some(timeout) => rx.recv_timeout(timeout + RECEIVE_GRACE).map_err(|e| match e {
+            RecvTimeoutError::Timeout => timed_out(timeout),
+            RecvTimeoutError::Disconnected => stdlib_exception("NetworkException", "Receive task was dropped"),
+        })?,
+        None => rx.recv().map_err(|_| stdlib_exception("NetworkException", "Receive task was dropped"))?,
+    };
+
+    let (data, addr) = result.map_err(|e| match (e.kind(), timeout) {
+        // socket 自身的读超时先于 VM 线程的等待触发
+        (std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut, Some(timeout)) => timed_out(timeout),
+        _ => network_exception("Failed to receive", &e),
+    })?;
+    Ok(create_udp_packet_instance(&data, addr))
+}
+
+/// UDPSocket.setReadTimeout(timeout_ms: int) -> null
+/// 设置 receiveFrom 的超时时间（毫秒），0 表示不超时
+pub fn udp_socket_set_read_timeout(instance: &Value, args: &[Value]) -> Result<Value, String> {
+    let handle = extract_socket_handle(instance)?;
+    let timeout_ms = int_arg(args, 0, "UDPSocket.setReadTimeout", "timeout_ms")?;
+    if timeout_ms < 0 {
+        return Err(stdlib_exception(
+            "IllegalArgumentException",
+            format!("UDPSocket.setReadTimeout expects a non-negative timeout, got {}", timeout_ms),
+        ));
+    }
+
+    let timeout = if timeout_ms == 0 {
+        None
+    } else {
+        Some(Duration::from_millis(timeout_ms.min(u64::MAX as i128) as u64))
+    };
+    handle.socket()?
+        .set_read_timeout(timeout)
+        .map_err(|e| network_exception("Failed to set read timeout", &e))?;
+    *handle.read_timeout.lock() = timeout;
+
+    Ok(Value::null())
+}
+
+/// UDPSocket.localAddress() -> string
+/// 返回实际绑定的本地地址 "host:port"
+pub fn udp_socket_local_address(instance: &Value, _args: &[Value]) -> Result<Value, String> {
+    let handle = extract_socket_handle(instance)?;
+    let addr = handle.socket()?
+        .local_addr()
+        .map_err(|e| network_exception("Failed to get local address", &e))?;
+    Ok(Value::string(addr.to_string()))
+}
+
+/// UDPSocket.close() -> null
+/// 关闭socket，重复关闭不报错
+pub fn udp_socket_close(instance: &Value, _args: &[Value]) -> Result<Value, String> {
+    let handle = extract_socket_handle(instance)?;
+    handle.socket.lock().take();
+    Ok(Value::null())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn bytes_value(bytes: &[u8]) -> Value {
+        Value::array(Arc::new(Mutex::new(bytes.iter().map(|&b| Value::int(b as i128)).collect())))
+    }
+
+    fn bind_local() -> Value {
+        udp_socket_bind(&[Value::string("127.0.0.1:0".to_string())]).unwrap()
+    }
+
+    fn field(instance: &Value, name: &str) -> Value {
+        *instance.as_class().unwrap().lock().fields.get(name).unwrap()
+    }
+
+    #[test]
+    fn test_send_to_and_receive_from() {
+        let pool = IoThreadPool::new(2);
+        let server = bind_local();
+        let client = bind_local();
+        let server_addr = udp_socket_local_address(&server, &[]).unwrap();
+        let client_addr = udp_socket_local_address(&client, &[]).unwrap();
+
+        let sent = udp_socket_send_to(&client, &[bytes_value(b"ping!"), server_addr]).unwrap();
+        assert_eq!(sent.as_int(), Some(5));
+
+        // maxLen 小于数据报长度时截断
+        udp_socket_set_read_timeout(&server, &[Value::int(2000)]).unwrap();
+        let packet = udp_socket_receive_from(&pool, &server, &[Value::int(4)]).unwrap();
+        let data: Vec<i128> = field(&packet, "data").as_array().unwrap().lock().iter().map(|v| v.as_int().unwrap()).collect();
+        assert_eq!(data, vec![b'p' as i128, b'i' as i128, b'n' as i128, b'g' as i128]);
+        assert_eq!(field(&packet, "address").as_string(), client_addr.as_string());
+
+        let err = udp_socket_send_to(&client, &[bytes_value(b"x"), Value::string("not an address".to_string())]).unwrap_err();
+        assert!(err.starts_with("IllegalArgumentException"), "{}", err);
+
+        udp_socket_close(&client, &[]).unwrap();
+        udp_socket_close(&client, &[]).unwrap();
+        let err = udp_socket_send_to(&client, &[bytes_value(b"x"), server_addr]).unwrap_err();
+        assert!(err.starts_with("NetworkException"), "{}", err);
+    }
+
+    #[test]
+    fn test_receive_honors_read_timeout() {
+        let pool = IoThreadPool::new(2);
+        let socket = bind_local();
+        udp_socket_set_read_timeout(&socket, &[Value::int(100)]).unwrap();
+
+        let started = Instant::now();
+        let err = udp_socket_receive_from(&pool, &socket, &[Value::int(16)]).unwrap_err();
+        assert!(err.starts_with("TimeoutException"), "{}", err);
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
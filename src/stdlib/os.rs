@@ -0,0 +1,320 @@
+//! std.os 操作系统模块
+//!
+//! - `Os.args()`：`run` 命令中 `--` 之后的参数，由 CLI 在运行前设置
+//! - 环境变量 `Os.env` / `Os.setEnv` / `Os.envAll`，当前目录 `Os.cwd` / `Os.chdir`，
+//!   `Os.platform()` 和 `Os.hostname()`
+//! - `Os.exit(code)` 结束程序：返回 [`exit_request`] 编码的错误，虚拟机识别后执行 finally 块
+//!   并展开到顶层（不被 catch 捕获），由调用方以该退出码结束进程，而不是在这里调用 `process::exit`；
+//!   协程中的请求记在 [`PendingExit`] 中，交给主虚拟机处理
+
+use parking_lot::{Mutex, RwLock};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::exception::stdlib_exception;
+use super::StdlibModule;
+use crate::vm::value::Value;
+use crate::vm::MapData;
+
+/// 传给程序的命令行参数
+static ARGS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// 设置 `Os.args()` 返回的参数
+pub fn set_args(args: Vec<String>) {
+    *ARGS.write() = args;
+}
+
+/// `Os.exit(code)` 返回的错误的前缀
+const EXIT_PREFIX: &str = "Os.exit:";
+
+/// 请求以 `code` 结束程序的错误
+pub fn exit_request(code: i32) -> String {
+    format!("{}{}", EXIT_PREFIX, code)
+}
+
+/// 是 [`exit_request`] 时返回退出码
+pub fn parse_exit_request(error: &str) -> Option<i32> {
+    error.strip_prefix(EXIT_PREFIX)?.parse().ok()
+}
+
+/// 协程中 `Os.exit` 请求的退出码，同一程序的虚拟机共享
+///
+/// 协程在其他线程上，无法展开主虚拟机的栈，只在这里记下退出码；
+/// 主虚拟机在安全点和阻塞等待中取走它，像自己调用 `Os.exit` 一样执行 finally 块后结束程序。
+/// 只记第一个请求，取走之后的请求被忽略（程序已经在退出）
+pub struct PendingExit(AtomicI64);
+
+impl PendingExit {
+    const NONE: i64 = i64::MIN;
+    const TAKEN: i64 = i64::MIN + 1;
+    
+    /// 请求以 `code` 结束程序
+    pub fn request(&self, code: i32) {
+        let _ = self.0.compare_exchange(Self::NONE, code as i64, Ordering::AcqRel, Ordering::Acquire);
+    }
+    
+    /// 取走请求的退出码，只由主虚拟机调用
+    pub fn take(&self) -> Option<i32> {
+        let code = self.0.load(Ordering::Acquire);
+        if code == Self::NONE || code == Self::TAKEN {
+            return None;
+        }
+        self.0.store(Self::TAKEN, Ordering::Release);
+        Some(code as i32)
+    }
+    
+    fn is_requested(&self) -> bool {
+        !matches!(self.0.load(Ordering::Acquire), Self::NONE | Self::TAKEN)
+    }
+}
+
+impl Default for PendingExit {
+    fn default() -> Self {
+        Self(AtomicI64::new(Self::NONE))
+    }
+}
+
+/// 主虚拟机阻塞等待时检查退出请求的间隔
+pub const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+thread_local! {
+    /// 当前线程上的主虚拟机正在等待的退出请求
+    static WATCHED_EXIT: RefCell<Option<Arc<PendingExit>>> = const { RefCell::new(None) };
+}
+
+/// 执行阻塞的标准库调用 f，期间 [`sleep`] 在 `pending` 中有退出请求时提前返回
+pub fn watching_exit<R>(pending: &Arc<PendingExit>, f: impl FnOnce() -> R) -> R {
+    let previous = WATCHED_EXIT.with(|watched| watched.replace(Some(pending.clone())));
+    let result = f();
+    WATCHED_EXIT.with(|watched| *watched.borrow_mut() = previous);
+    result
+}
+
+/// 睡眠 `duration`；在 [`watching_exit`] 中时每隔 [`EXIT_POLL_INTERVAL`] 检查一次退出请求，有请求时提前返回
+pub fn sleep(duration: Duration) {
+    let Some(pending) = WATCHED_EXIT.with(|watched| watched.borrow().clone()) else {
+        std::thread::sleep(duration);
+        return;
+    };
+    let deadline = Instant::now().checked_add(duration);
+    while !pending.is_requested() {
+        let remaining = deadline.map_or(Duration::MAX, |deadline| deadline.saturating_duration_since(Instant::now()));
+        if remaining.is_zero() {
+            break;
+        }
+        std::thread::sleep(remaining.min(EXIT_POLL_INTERVAL));
+    }
+}
+
+/// Os.args() -> string[]
+pub fn os_args(_args: &[Value]) -> Result<Value, String> {
+    let args = ARGS.read().iter().map(|arg| Value::string(arg.clone())).collect();
+    Ok(Value::array(Arc::new(Mutex::new(args))))
+}
+
+/// 第 index 个参数，必须是字符串
+fn string_arg<'a>(function: &str, args: &'a [Value], index: usize) -> Result<&'a String, String> {
+    args.get(index).and_then(|v| v.as_string()).ok_or_else(|| {
+        stdlib_exception("IllegalArgumentException", format!("Os.{} expects a string argument", function))
+    })
+}
+
+/// 环境变量名不能为空，不能包含 `=` 和 NUL
+fn env_name<'a>(function: &str, args: &'a [Value]) -> Result<&'a String, String> {
+    let name = string_arg(function, args, 0)?;
+    if name.is_empty() || name.contains(['=', '\0']) {
+        return Err(stdlib_exception(
+            "IllegalArgumentException",
+            format!("Os.{}: invalid environment variable name '{}'", function, name),
+        ));
+    }
+    Ok(name)
+}
+
+/// Os.env(name: string) -> string?，值不是合法的 UTF-8 时无效的字节替换为 U+FFFD
+pub fn os_env(args: &[Value]) -> Result<Value, String> {
+    let name = env_name("env", args)?;
+    Ok(std::env::var_os(name).map_or_else(Value::null, |value| Value::string(value.to_string_lossy().into_owned())))
+}
+
+/// Os.setEnv(name: string, value: string?)，value 为 null 时删除变量；只影响当前进程和之后启动的子进程
+pub fn os_set_env(args: &[Value]) -> Result<Value, String> {
+    let name = env_name("setEnv", args)?;
+    match args.get(1).filter(|v| !v.is_null()) {
+        None => std::env::remove_var(name),
+        Some(value) => {
+            let value = value.as_string().filter(|v| !v.contains('\0')).ok_or_else(|| {
+                stdlib_exception("IllegalArgumentException", "Os.setEnv expects a string value without NUL characters")
+            })?;
+            std::env::set_var(name, value);
+        }
+    }
+    Ok(Value::null())
+}
+
+/// Os.envAll() -> map[string]string
+pub fn os_env_all(_args: &[Value]) -> Result<Value, String> {
+    let mut map = MapData::default();
+    for (name, value) in std::env::vars_os() {
+        map.insert(name.to_string_lossy().into_owned(), Value::string(value.to_string_lossy().into_owned()));
+    }
+    Ok(Value::map(Arc::new(Mutex::new(map))))
+}
+
+/// Os.cwd() -> string
+pub fn os_cwd(_args: &[Value]) -> Result<Value, String> {
+    std::env::current_dir()
+        .map(|dir| Value::string(dir.to_string_lossy().into_owned()))
+        .map_err(|e| stdlib_exception("IOException", format!("cannot determine the current directory: {}", e)))
+}
+
+/// Os.chdir(path: string)，相对路径按当前目录解析；影响整个进程，包括其他协程
+pub fn os_chdir(args: &[Value]) -> Result<Value, String> {
+    let path = string_arg("chdir", args, 0)?;
+    std::env::set_current_dir(path).map_err(|e| {
+        let class_name = match e.kind() {
+            std::io::ErrorKind::NotFound => "FileNotFoundException",
+            std::io::ErrorKind::PermissionDenied => "PermissionDeniedException",
+            _ => "IOException",
+        };
+        stdlib_exception(class_name, format!("{}: {}", path, e))
+    })?;
+    Ok(Value::null())
+}
+
+/// 平台名："windows"、"linux"、"macos"，其他系统为 Rust 的 `std::env::consts::OS`
+pub fn platform() -> &'static str {
+    std::env::consts::OS
+}
+
+/// Os.platform() -> string
+pub fn os_platform(_args: &[Value]) -> Result<Value, String> {
+    Ok(Value::string(platform().to_string()))
+}
+
+/// 主机名，取不到时返回 None
+#[cfg(unix)]
+fn hostname() -> Option<String> {
+    let mut buffer = [0u8; 256];
+    // SAFETY: 缓冲区可写且长度正确；结果以 NUL 结尾，截断时下面按缓冲区长度处理
+    let result = unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) };
+    if result != 0 {
+        return None;
+    }
+    let len = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+    Some(String::from_utf8_lossy(&buffer[..len]).into_owned())
+}
+
+/// 主机名，取不到时返回 None
+#[cfg(not(unix))]
+fn hostname() -> Option<String> {
+    std::env::var("COMPUTERNAME").ok()
+}
+
+/// Os.hostname() -> string
+pub fn os_hostname(_args: &[Value]) -> Result<Value, String> {
+    hostname()
+        .map(Value::string)
+        .ok_or_else(|| stdlib_exception("IOException", "cannot determine the host name"))
+}
+
+/// Os.exit(code: int = 0)
+pub fn os_exit(args: &[Value]) -> Result<Value, String> {
+    let code = match args.first().filter(|v| !v.is_null()) {
+        None => 0,
+        Some(code) => code.as_int().and_then(|n| i32::try_from(n).ok()).ok_or_else(|| {
+            stdlib_exception("IllegalArgumentException", format!("Os.exit expects an exit code in the 32-bit range, got {}", code))
+        })?,
+    };
+    Err(exit_request(code))
+}
+
+/// std.os 标准库
+#[derive(Default)]
+pub struct OsLib;
+
+impl OsLib {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl StdlibModule for OsLib {
+    fn name(&self) -> &'static str {
+        "std.os"
+    }
+
+    fn exports(&self) -> Vec<&'static str> {
+        vec![
+            "Os_args",
+            "Os_env",
+            "Os_setEnv",
+            "Os_envAll",
+            "Os_cwd",
+            "Os_chdir",
+            "Os_platform",
+            "Os_hostname",
+            "Os_exit",
+        ]
+    }
+
+    fn call(&self, name: &str, args: &[Value]) -> Result<Value, String> {
+        match name {
+            "Os_args" => os_args(args),
+            "Os_env" => os_env(args),
+            "Os_setEnv" => os_set_env(args),
+            "Os_envAll" => os_env_all(args),
+            "Os_cwd" => os_cwd(args),
+            "Os_chdir" => os_chdir(args),
+            "Os_platform" => os_platform(args),
+            "Os_hostname" => os_hostname(args),
+            "Os_exit" => os_exit(args),
+            _ => Err(format!("Unknown function: {}", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, args: &[Value]) -> Result<Value, String> {
+        OsLib::new().call(name, args)
+    }
+
+    fn string(text: &str) -> Value {
+        Value::string(text.to_string())
+    }
+
+    #[test]
+    fn test_environment_variables() {
+        let name = format!("QLANG_OS_TEST_{}", std::process::id());
+        assert!(call("Os_env", &[string(&name)]).unwrap().is_null());
+        call("Os_setEnv", &[string(&name), string("on")]).unwrap();
+        assert_eq!(call("Os_env", &[string(&name)]).unwrap().as_string().map(String::as_str), Some("on"));
+        let all = call("Os_envAll", &[]).unwrap();
+        assert_eq!(all.as_map().unwrap().lock().get(&name).and_then(|v| v.as_string().cloned()).as_deref(), Some("on"));
+        call("Os_setEnv", &[string(&name), Value::null()]).unwrap();
+        assert!(call("Os_env", &[string(&name)]).unwrap().is_null());
+
+        let error = call("Os_setEnv", &[string("A=B"), string("x")]).unwrap_err();
+        assert!(error.starts_with("IllegalArgumentException: "), "{}", error);
+    }
+
+    #[test]
+    fn test_exit_request() {
+        assert_eq!(call("Os_exit", &[Value::int(3)]).map_err(|e| parse_exit_request(&e)), Err(Some(3)));
+        assert_eq!(call("Os_exit", &[]).map_err(|e| parse_exit_request(&e)), Err(Some(0)));
+        let error = call("Os_exit", &[Value::int(1 << 40)]).unwrap_err();
+        assert_eq!(parse_exit_request(&error), None);
+        assert_eq!(parse_exit_request("IOException: Os.exit:3"), None);
+    }
+
+    #[test]
+    fn test_platform_and_hostname() {
+        assert!(["windows", "linux", "macos"].contains(&platform()) || !platform().is_empty());
+        assert!(!call("Os_hostname", &[]).unwrap().as_string().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,441 @@
+//! std.process 子进程
+//!
+//! - `Process.run(command, args)` 运行程序并等待结束，返回 `ProcessResult`（退出码、标准输出和标准错误）
+//! - `Process.runShell(command)` 同上，但由 shell 解释命令（Unix 为 `sh -c`，Windows 为 `cmd /C`）
+//! - `Process.spawn(command, args)` 启动程序并立即返回 `Process` 实例，通过 `writeStdin` / `readStdout`
+//!   与它交互，`wait` 等待结束，`kill` 结束它
+//!
+//! `run` 和 `spawn` 的参数数组原样传给程序，不经过 shell，参数中的空格、引号和 `$` 没有特殊含义。
+//!
+//! 等待子进程的操作（`run`、`readStdout`、`writeStdin`、`wait`）在 IO 线程池中执行，
+//! 调用方线程只等待结果。被信号结束的进程的退出码是 128 + 信号编号，与 shell 一致。
+
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, ExitStatus, Output, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use crossbeam_channel::bounded;
+use parking_lot::Mutex;
+
+use super::exception::stdlib_exception;
+use super::net::io_thread_pool::IoThreadPool;
+use super::{ClassDecl, StdlibModule};
+use crate::types::Type;
+use crate::vm::gc::gc_attach_native;
+use crate::vm::value::{ClassInstance, Value};
+
+// 标准库类名常量
+pub const CLASS_PROCESS: &str = "std.process.Process";
+pub const CLASS_PROCESS_RESULT: &str = "std.process.ProcessResult";
+
+/// `readStdout` 每次最多读取的字节数
+const READ_CHUNK: usize = 64 * 1024;
+
+/// 轮询子进程是否结束的间隔；轮询期间不持有子进程的锁，`kill` 不必等待 `wait` 返回
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// `spawn` 启动的子进程
+struct ChildHandle {
+    child: Mutex<Child>,
+    /// `closeStdin` 之后为 None
+    stdin: Mutex<Option<ChildStdin>>,
+    /// 标准输出和上次读取留下的不完整的 UTF-8 序列
+    stdout: Mutex<(ChildStdout, Vec<u8>)>,
+}
+
+/// 子进程表：句柄 ID -> 子进程
+///
+/// Process 实例只在 "__handle" 字段保存 ID，`wait` 返回后或实例被回收时从表中移除
+fn children() -> &'static Mutex<HashMap<u64, Arc<ChildHandle>>> {
+    static CHILDREN: OnceLock<Mutex<HashMap<u64, Arc<ChildHandle>>>> = OnceLock::new();
+    CHILDREN.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+static NEXT_HANDLE: AtomicU64 = AtomicU64::new(1);
+
+/// Process 实例持有的子进程表项，实例被回收时移除
+///
+/// 没有 `wait` 的子进程在这时回收：已经结束的立即回收，还在运行的关闭标准输入后
+/// 由后台线程等待它结束，不留下僵尸进程，也不阻塞回收
+struct SpawnedChild(u64);
+
+impl Drop for SpawnedChild {
+    fn drop(&mut self) {
+        let Some(handle) = children().lock().remove(&self.0) else {
+            return;
+        };
+        handle.stdin.lock().take();
+        if !matches!(handle.child.lock().try_wait(), Ok(None)) {
+            return;
+        }
+        std::thread::spawn(move || {
+            let _ = handle.child.lock().wait();
+        });
+    }
+}
+
+/// 启动失败、读写管道失败等 I/O 错误对应的异常
+fn process_exception(command: &str, err: io::Error) -> String {
+    let class_name = match err.kind() {
+        io::ErrorKind::NotFound => "FileNotFoundException",
+        io::ErrorKind::PermissionDenied => "PermissionDeniedException",
+        _ => "IOException",
+    };
+    stdlib_exception(class_name, format!("{}: {}", command, err))
+}
+
+fn string_arg<'a>(args: &'a [Value], index: usize, func: &str, name: &str) -> Result<&'a String, String> {
+    args.get(index).and_then(|v| v.as_string()).ok_or_else(|| {
+        stdlib_exception("IllegalArgumentException", format!("{} expects argument '{}' to be a string", func, name))
+    })
+}
+
+/// 命令和参数数组；参数可以省略或为 null
+fn command_args(args: &[Value], func: &str) -> Result<(String, Vec<String>), String> {
+    let command = string_arg(args, 0, func, "command")?.clone();
+    let invalid = || stdlib_exception("IllegalArgumentException", format!("{} expects 'args' to be a string array", func));
+    let list = match args.get(1).filter(|v| !v.is_null()) {
+        None => Vec::new(),
+        Some(value) => {
+            let array = value.as_array().ok_or_else(invalid)?;
+            let array = array.lock();
+            array.iter().map(|arg| arg.as_string().cloned().ok_or_else(invalid)).collect::<Result<_, _>>()?
+        }
+    };
+    Ok((command, list))
+}
+
+/// 由 shell 解释的命令
+fn shell_command(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    }
+}
+
+/// 退出码；被信号结束时为 128 + 信号编号
+fn exit_code(status: ExitStatus) -> i128 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal as i128;
+        }
+    }
+    status.code().map_or(-1, i128::from)
+}
+
+/// 在线程池中执行 `work`，当前线程等待结果
+fn on_pool<T: Send + 'static>(pool: &IoThreadPool, work: impl FnOnce() -> T + Send + 'static) -> Result<T, String> {
+    let (tx, rx) = bounded(1);
+    pool.execute(move || {
+        let _ = tx.send(work());
+    });
+    rx.recv().map_err(|_| stdlib_exception("IOException", "process task was dropped"))
+}
+
+fn instance(class_name: &str, fields: HashMap<String, Value>) -> Value {
+    Value::class(Arc::new(Mutex::new(ClassInstance {
+        class_name: class_name.to_string(),
+        parent_class: None,
+        fields,
+    })))
+}
+
+/// 运行到结束，标准输入为空，收集标准输出和标准错误
+fn run_command(pool: &IoThreadPool, mut command: Command, name: String) -> Result<Value, String> {
+    command.stdin(Stdio::null());
+    let output: Output = on_pool(pool, move || command.output())?.map_err(|e| process_exception(&name, e))?;
+    let mut fields = HashMap::new();
+    fields.insert("status".to_string(), Value::int(exit_code(output.status)));
+    fields.insert("stdout".to_string(), Value::string(String::from_utf8_lossy(&output.stdout).into_owned()));
+    fields.insert("stderr".to_string(), Value::string(String::from_utf8_lossy(&output.stderr).into_owned()));
+    Ok(instance(CLASS_PROCESS_RESULT, fields))
+}
+
+/// Process.run(command: string, args: string[]? = null) -> ProcessResult
+pub fn process_run(pool: &IoThreadPool, args: &[Value]) -> Result<Value, String> {
+    let (name, list) = command_args(args, "Process.run")?;
+    let mut command = Command::new(&name);
+    command.args(list);
+    run_command(pool, command, name)
+}
+
+/// Process.runShell(command: string) -> ProcessResult
+pub fn process_run_shell(pool: &IoThreadPool, args: &[Value]) -> Result<Value, String> {
+    let command = string_arg(args, 0, "Process.runShell", "command")?;
+    run_command(pool, shell_command(command), command.clone())
+}
+
+/// Process.spawn(command: string, args: string[]? = null) -> Process
+///
+/// 标准输入和标准输出接到管道，标准错误沿用当前进程的
+pub fn process_spawn(args: &[Value]) -> Result<Value, String> {
+    let (name, list) = command_args(args, "Process.spawn")?;
+    let mut child = Command::new(&name)
+        .args(list)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| process_exception(&name, e))?;
+    let stdin = child.stdin.take();
+    let stdout = child.stdout.take().ok_or_else(|| stdlib_exception("IOException", "child has no stdout pipe"))?;
+    let pid = child.id();
+    let handle = Arc::new(ChildHandle {
+        child: Mutex::new(child),
+        stdin: Mutex::new(stdin),
+        stdout: Mutex::new((stdout, Vec::new())),
+    });
+    let id = NEXT_HANDLE.fetch_add(1, Ordering::Relaxed);
+    children().lock().insert(id, handle);
+
+    let mut fields = HashMap::new();
+    fields.insert("__handle".to_string(), Value::int(id as i128));
+    fields.insert("pid".to_string(), Value::int(pid as i128));
+    let process = instance(CLASS_PROCESS, fields);
+    gc_attach_native(&process, Box::new(SpawnedChild(id)));
+    Ok(process)
+}
+
+/// 实例的句柄 ID
+fn handle_id(instance: &Value) -> Result<u64, String> {
+    instance
+        .as_class()
+        .and_then(|instance| instance.lock().fields.get("__handle").and_then(|v| v.as_int()))
+        .map(|id| id as u64)
+        .ok_or_else(|| "Process instance has no valid handle".to_string())
+}
+
+/// 还没有被 `wait` 回收的子进程
+fn child_handle(instance: &Value, method: &str) -> Result<Arc<ChildHandle>, String> {
+    let id = handle_id(instance)?;
+    children().lock().get(&id).cloned().ok_or_else(|| {
+        stdlib_exception("IllegalStateException", format!("Process.{}: the process has already been waited for", method))
+    })
+}
+
+/// 末尾不完整的 UTF-8 序列的长度，留到下次读取时拼接
+fn incomplete_tail(bytes: &[u8]) -> usize {
+    (1..=bytes.len().min(3))
+        .find(|&len| {
+            matches!(std::str::from_utf8(&bytes[bytes.len() - len..]), Err(e) if e.valid_up_to() == 0 && e.error_len().is_none())
+        })
+        .unwrap_or(0)
+}
+
+/// readStdout() -> string?：阻塞到有输出，返回已有的输出；输出结束时返回 null
+fn read_stdout(pool: &IoThreadPool, handle: Arc<ChildHandle>) -> Result<Value, String> {
+    let text = on_pool(pool, move || -> io::Result<Option<String>> {
+        let mut stdout = handle.stdout.lock();
+        let (pipe, pending) = &mut *stdout;
+        let mut buffer = vec![0u8; READ_CHUNK];
+        loop {
+            let n = pipe.read(&mut buffer)?;
+            if n == 0 {
+                if pending.is_empty() {
+                    return Ok(None);
+                }
+                return Ok(Some(String::from_utf8_lossy(&std::mem::take(pending)).into_owned()));
+            }
+            pending.extend_from_slice(&buffer[..n]);
+            let complete = pending.len() - incomplete_tail(pending);
+            if complete > 0 {
+                let rest = pending.split_off(complete);
+                let text = String::from_utf8_lossy(pending).into_owned();
+                *pending = rest;
+                return Ok(Some(text));
+            }
+        }
+    })?
+    .map_err(|e| process_exception("Process.readStdout", e))?;
+    Ok(text.map_or_else(Value::null, Value::string))
+}
+
+/// writeStdin(text: string)：写入并刷新；子进程不读取时阻塞
+fn write_stdin(pool: &IoThreadPool, handle: Arc<ChildHandle>, args: &[Value]) -> Result<Value, String> {
+    let text = string_arg(args, 0, "Process.writeStdin", "text")?.clone();
+    on_pool(pool, move || -> Result<(), String> {
+        let mut stdin = handle.stdin.lock();
+        let pipe = stdin.as_mut().ok_or_else(|| {
+            stdlib_exception("IllegalStateException", "Process.writeStdin: stdin has been closed")
+        })?;
+        pipe.write_all(text.as_bytes())
+            .and_then(|_| pipe.flush())
+            .map_err(|e| process_exception("Process.writeStdin", e))
+    })??;
+    Ok(Value::null())
+}
+
+/// wait() -> int：等待子进程结束，返回退出码；之后实例不能再使用
+fn wait(pool: &IoThreadPool, id: u64, handle: Arc<ChildHandle>) -> Result<Value, String> {
+    // 子进程可能在等待输入，先关闭标准输入
+    handle.stdin.lock().take();
+    let status = on_pool(pool, move || loop {
+        if let Some(status) = handle.child.lock().try_wait()? {
+            return Ok(status);
+        }
+        std::thread::sleep(WAIT_POLL_INTERVAL);
+    })?
+    .map_err(|e| process_exception("Process.wait", e))?;
+    children().lock().remove(&id);
+    Ok(Value::int(exit_code(status)))
+}
+
+/// kill()：结束子进程，已经结束时什么也不做
+fn kill(handle: &ChildHandle) -> Result<Value, String> {
+    let mut child = handle.child.lock();
+    if child.try_wait().map_err(|e| process_exception("Process.kill", e))?.is_none() {
+        child.kill().map_err(|e| process_exception("Process.kill", e))?;
+    }
+    Ok(Value::null())
+}
+
+/// std.process 标准库
+pub struct ProcessLib {
+    /// 执行等待子进程的操作
+    thread_pool: Arc<IoThreadPool>,
+}
+
+impl Default for ProcessLib {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProcessLib {
+    pub fn new() -> Self {
+        Self {
+            thread_pool: Arc::new(IoThreadPool::new(8)),
+        }
+    }
+}
+
+impl StdlibModule for ProcessLib {
+    fn name(&self) -> &'static str {
+        "std.process"
+    }
+
+    fn exports(&self) -> Vec<&'static str> {
+        vec!["Process_run", "Process_runShell", "Process_spawn"]
+    }
+
+    fn call(&self, name: &str, args: &[Value]) -> Result<Value, String> {
+        match name {
+            "Process_run" => process_run(&self.thread_pool, args),
+            "Process_runShell" => process_run_shell(&self.thread_pool, args),
+            "Process_spawn" => process_spawn(args),
+            _ => Err(format!("Unknown function: {}", name)),
+        }
+    }
+
+    fn blocks(&self, name: &str) -> bool {
+        name == "Process_run" || name == "Process_runShell"
+    }
+
+    fn has_class(&self, class_name: &str) -> bool {
+        class_name == CLASS_PROCESS || class_name == CLASS_PROCESS_RESULT
+    }
+
+    fn create_class_instance(&self, class_name: &str, _args: &[Value]) -> Result<Value, String> {
+        Err(format!("Class '{}' cannot be instantiated directly", class_name))
+    }
+
+    fn call_method(&self, instance: &Value, method_name: &str, args: &[Value]) -> Result<Value, String> {
+        let handle = child_handle(instance, method_name)?;
+        match method_name {
+            "writeStdin" => write_stdin(&self.thread_pool, handle, args),
+            "closeStdin" => {
+                handle.stdin.lock().take();
+                Ok(Value::null())
+            }
+            "readStdout" => read_stdout(&self.thread_pool, handle),
+            "wait" => wait(&self.thread_pool, handle_id(instance)?, handle),
+            "kill" => kill(&handle),
+            _ => Err(format!("Process has no method '{}'", method_name)),
+        }
+    }
+
+    fn type_declarations(&self) -> Vec<ClassDecl> {
+        vec![
+            ClassDecl::new("ProcessResult")
+                .field("status", Type::Int)
+                .field("stdout", Type::String)
+                .field("stderr", Type::String),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_incomplete_tail() {
+        let text = "中文".as_bytes();
+        assert_eq!(incomplete_tail(text), 0);
+        assert_eq!(incomplete_tail(&text[..4]), 1);
+        assert_eq!(incomplete_tail(&text[..5]), 2);
+        assert_eq!(incomplete_tail(b"ab\xff"), 0);
+        assert_eq!(incomplete_tail(b""), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_and_spawn() {
+        let lib = ProcessLib::new();
+        let string = |s: &str| Value::string(s.to_string());
+        let strings = |items: &[&str]| Value::array(Arc::new(Mutex::new(items.iter().map(|s| string(s)).collect())));
+        let field = |result: &Value, name: &str| result.as_class().unwrap().lock().fields.get(name).copied().unwrap();
+
+        // 参数不经过 shell
+        let result = lib.call("Process_run", &[string("echo"), strings(&["a  b", "$HOME"])]).unwrap();
+        assert_eq!(field(&result, "status").as_int(), Some(0));
+        assert_eq!(field(&result, "stdout").as_string().map(String::as_str), Some("a  b $HOME\n"));
+
+        let result = lib.call("Process_runShell", &[string("echo out; echo err >&2; exit 3")]).unwrap();
+        assert_eq!(field(&result, "status").as_int(), Some(3));
+        assert_eq!(field(&result, "stderr").as_string().map(String::as_str), Some("err\n"));
+
+        let error = lib.call("Process_run", &[string("qlang-no-such-program")]).unwrap_err();
+        assert!(error.starts_with("FileNotFoundException: "), "{}", error);
+
+        let child = lib.call("Process_spawn", &[string("cat")]).unwrap();
+        lib.call_method(&child, "writeStdin", &[string("hello")]).unwrap();
+        assert_eq!(lib.call_method(&child, "readStdout", &[]).unwrap().as_string().map(String::as_str), Some("hello"));
+        lib.call_method(&child, "closeStdin", &[]).unwrap();
+        assert!(lib.call_method(&child, "readStdout", &[]).unwrap().is_null());
+        assert_eq!(lib.call_method(&child, "wait", &[]).unwrap().as_int(), Some(0));
+        assert!(lib.call_method(&child, "wait", &[]).unwrap_err().starts_with("IllegalStateException: "));
+
+        let child = lib.call("Process_spawn", &[string("sleep"), strings(&["10"])]).unwrap();
+        lib.call_method(&child, "kill", &[]).unwrap();
+        assert_eq!(lib.call_method(&child, "wait", &[]).unwrap().as_int(), Some(128 + 9));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_dropping_the_owner_reaps_the_child() {
+        let lib = ProcessLib::new();
+        // cat 在标准输入关闭后结束
+        let child = lib.call("Process_spawn", &[Value::string("cat".to_string())]).unwrap();
+        let id = handle_id(&child).unwrap();
+        let pid = child.as_class().unwrap().lock().fields["pid"].as_int().unwrap();
+
+        drop(SpawnedChild(id));
+        assert!(children().lock().get(&id).is_none());
+        // 回收后 /proc 中不再有这个进程（僵尸进程仍然存在）
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while std::path::Path::new(&format!("/proc/{}", pid)).exists() {
+            assert!(std::time::Instant::now() < deadline, "child {} was not reaped", pid);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
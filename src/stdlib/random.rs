@@ -0,0 +1,287 @@
+//! std.random 伪随机数
+//!
+//! - `Random.random()` / `Random.randInt(lo, hi)` / `Random.shuffle(array)` / `Random.choice(array)`
+//!   使用进程共享的生成器，种子来自操作系统的随机源（见 [`super::uuid::random_u128`]）
+//! - `new Random(seed)` 创建独立的生成器，同一个种子总是得到同一个序列，测试可以用它得到确定的结果；
+//!   省略种子时和共享生成器一样随机播种
+//!
+//! 生成器是 SplitMix64，不适合用于密码学。实例的状态保存在 "__state" 字段。
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use parking_lot::Mutex;
+
+use super::exception::stdlib_exception;
+use super::uuid::random_u128;
+use super::StdlibModule;
+use crate::vm::value::{ClassInstance, Value};
+
+// 标准库类名常量
+pub const CLASS_RANDOM: &str = "std.random.Random";
+
+/// SplitMix64 每一步给状态加上的增量
+const GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// SplitMix64 的输出函数：打散一个状态值
+fn mix(state: u64) -> u64 {
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// 把 int 种子折叠为 64 位状态
+fn seed_state(seed: i128) -> u64 {
+    let seed = seed as u128;
+    (seed as u64) ^ ((seed >> 64) as u64)
+}
+
+/// 共享生成器的下一个值；状态只需要原子加法，多个协程同时调用不需要加锁
+fn shared_next() -> u64 {
+    static STATE: OnceLock<AtomicU64> = OnceLock::new();
+    let state = STATE.get_or_init(|| AtomicU64::new(random_u128() as u64));
+    mix(state.fetch_add(GAMMA, Ordering::Relaxed).wrapping_add(GAMMA))
+}
+
+/// [0, bound) 中均匀分布的整数，bound 为 0 表示整个 u128 范围
+///
+/// 丢弃落在最后一段不完整区间中的值，避免取模带来的偏差
+fn below(next: &mut dyn FnMut() -> u64, bound: u128) -> u128 {
+    if bound == 0 {
+        return ((next() as u128) << 64) | next() as u128;
+    }
+    if let Ok(bound) = u64::try_from(bound) {
+        let threshold = bound.wrapping_neg() % bound;
+        loop {
+            let x = next();
+            if x >= threshold {
+                return (x % bound) as u128;
+            }
+        }
+    }
+    let threshold = bound.wrapping_neg() % bound;
+    loop {
+        let x = ((next() as u128) << 64) | next() as u128;
+        if x >= threshold {
+            return x % bound;
+        }
+    }
+}
+
+/// [0, 1) 中的浮点数，使用 53 位随机数
+fn random_float(next: &mut dyn FnMut() -> u64) -> f64 {
+    (next() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// randInt(lo, hi)：[lo, hi] 中的整数，包含两端
+fn rand_int(next: &mut dyn FnMut() -> u64, args: &[Value]) -> Result<Value, String> {
+    let bound = |index: usize| {
+        args.get(index).and_then(|v| v.as_int()).ok_or_else(|| {
+            stdlib_exception("IllegalArgumentException", "Random.randInt expects two int bounds")
+        })
+    };
+    let (lo, hi) = (bound(0)?, bound(1)?);
+    if lo > hi {
+        return Err(stdlib_exception(
+            "IllegalArgumentException",
+            format!("Random.randInt expects lo <= hi, got {} and {}", lo, hi),
+        ));
+    }
+    // hi - lo 可能超出 i128，但一定能用 u128 表示；整个 i128 范围时加 1 溢出为 0
+    let span = (hi.wrapping_sub(lo) as u128).wrapping_add(1);
+    Ok(Value::int(lo.wrapping_add(below(next, span) as i128)))
+}
+
+/// 数组或切片底层的 Vec 和元素范围 [start, end)
+type Elements = (Arc<Mutex<Vec<Value>>>, usize, usize);
+
+fn elements(function: &str, value: Option<&Value>) -> Result<Elements, String> {
+    let value = value.copied().unwrap_or_else(Value::null);
+    if let Some(array) = value.as_array() {
+        let len = array.lock().len();
+        Ok((array.clone(), 0, len))
+    } else if let Some((source, start, end)) = value.as_array_slice() {
+        let end = end.min(source.lock().len());
+        Ok((source.clone(), start, end.max(start)))
+    } else {
+        Err(stdlib_exception(
+            "IllegalArgumentException",
+            format!("Random.{} expects an array, got {}", function, value.type_name()),
+        ))
+    }
+}
+
+/// shuffle(array)：原地打乱（Fisher-Yates），空数组是错误
+fn shuffle(next: &mut dyn FnMut() -> u64, args: &[Value]) -> Result<Value, String> {
+    let (array, start, end) = elements("shuffle", args.first())?;
+    if start == end {
+        return Err(stdlib_exception("IllegalArgumentException", "Random.shuffle expects a non-empty array"));
+    }
+    let mut array = array.lock();
+    let items = &mut array[start..end];
+    for i in (1..items.len()).rev() {
+        let j = below(next, i as u128 + 1) as usize;
+        items.swap(i, j);
+    }
+    Ok(Value::null())
+}
+
+/// choice(array)：随机取一个元素，空数组返回 null
+fn choice(next: &mut dyn FnMut() -> u64, args: &[Value]) -> Result<Value, String> {
+    let (array, start, end) = elements("choice", args.first())?;
+    if start == end {
+        return Ok(Value::null());
+    }
+    let index = start + below(next, (end - start) as u128) as usize;
+    let value = array.lock()[index];
+    Ok(value)
+}
+
+/// 共享生成器和实例共用的方法
+fn call_with(next: &mut dyn FnMut() -> u64, method_name: &str, args: &[Value]) -> Result<Value, String> {
+    match method_name {
+        "random" => Ok(Value::float(random_float(next))),
+        "randInt" => rand_int(next, args),
+        "shuffle" => shuffle(next, args),
+        "choice" => choice(next, args),
+        _ => Err(format!("Random has no method '{}'", method_name)),
+    }
+}
+
+/// `new Random(seed?)`
+fn random_init(args: &[Value]) -> Result<Value, String> {
+    let state = match args.first() {
+        None => random_u128() as u64,
+        Some(seed) if seed.is_null() => random_u128() as u64,
+        Some(seed) => seed_state(seed.as_int().ok_or_else(|| {
+            stdlib_exception("IllegalArgumentException", format!("Random expects an int seed, got {}", seed.type_name()))
+        })?),
+    };
+    let mut fields = HashMap::new();
+    fields.insert("__state".to_string(), Value::int(state as i128));
+    Ok(Value::class(Arc::new(Mutex::new(ClassInstance {
+        class_name: CLASS_RANDOM.to_string(),
+        parent_class: None,
+        fields,
+    }))))
+}
+
+/// 实例方法：调用期间持有实例的锁，状态推进后写回
+fn call_random_method(instance: &Value, method_name: &str, args: &[Value]) -> Result<Value, String> {
+    let instance = instance.as_class().ok_or("Value is not a class instance")?;
+    let mut instance = instance.lock();
+    let mut state = instance.fields.get("__state").and_then(|v| v.as_int()).ok_or("Random instance has no state")? as u64;
+    let mut next = || {
+        state = state.wrapping_add(GAMMA);
+        mix(state)
+    };
+    let result = call_with(&mut next, method_name, args);
+    instance.fields.insert("__state".to_string(), Value::int(state as i128));
+    result
+}
+
+/// std.random 标准库
+#[derive(Default)]
+pub struct RandomLib;
+
+impl RandomLib {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl StdlibModule for RandomLib {
+    fn name(&self) -> &'static str {
+        "std.random"
+    }
+
+    fn exports(&self) -> Vec<&'static str> {
+        vec!["Random_random", "Random_randInt", "Random_shuffle", "Random_choice"]
+    }
+
+    fn call(&self, name: &str, args: &[Value]) -> Result<Value, String> {
+        match name.strip_prefix("Random_") {
+            Some(method_name) => call_with(&mut shared_next, method_name, args),
+            None => Err(format!("Unknown function: {}", name)),
+        }
+    }
+
+    fn has_class(&self, class_name: &str) -> bool {
+        class_name == CLASS_RANDOM
+    }
+
+    fn create_class_instance(&self, class_name: &str, args: &[Value]) -> Result<Value, String> {
+        match class_name {
+            CLASS_RANDOM => random_init(args),
+            _ => Err(format!("Class '{}' not found in module '{}'", class_name, self.name())),
+        }
+    }
+
+    fn call_method(&self, instance: &Value, method_name: &str, args: &[Value]) -> Result<Value, String> {
+        call_random_method(instance, method_name, args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ints(values: &[i128]) -> Value {
+        Value::array(Arc::new(Mutex::new(values.iter().map(|&n| Value::int(n)).collect())))
+    }
+
+    fn sequence(rng: &Value) -> Vec<i128> {
+        (0..5)
+            .map(|_| call_random_method(rng, "randInt", &[Value::int(0), Value::int(1_000_000)]).unwrap().as_int().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_seeded_sequences_repeat() {
+        let a = random_init(&[Value::int(42)]).unwrap();
+        let b = random_init(&[Value::int(42)]).unwrap();
+        let c = random_init(&[Value::int(43)]).unwrap();
+        assert_eq!(sequence(&a), sequence(&b));
+        assert_ne!(sequence(&a), sequence(&c));
+        for _ in 0..100 {
+            let x = call_random_method(&a, "random", &[]).unwrap().as_float().unwrap();
+            assert!((0.0..1.0).contains(&x), "{}", x);
+        }
+    }
+
+    #[test]
+    fn test_rand_int_bounds() {
+        let mut next = shared_next;
+        for _ in 0..200 {
+            let n = rand_int(&mut next, &[Value::int(-2), Value::int(2)]).unwrap().as_int().unwrap();
+            assert!((-2..=2).contains(&n), "{}", n);
+        }
+        assert_eq!(rand_int(&mut next, &[Value::int(7), Value::int(7)]).unwrap().as_int(), Some(7));
+        // 整个 i128 范围和超过 64 位的范围
+        rand_int(&mut next, &[Value::int(i128::MIN), Value::int(i128::MAX)]).unwrap();
+        let big = rand_int(&mut next, &[Value::int(0), Value::int(1 << 100)]).unwrap().as_int().unwrap();
+        assert!((0..=1 << 100).contains(&big));
+        let err = rand_int(&mut next, &[Value::int(3), Value::int(1)]).unwrap_err();
+        assert!(err.starts_with("IllegalArgumentException: "), "{}", err);
+    }
+
+    #[test]
+    fn test_shuffle_and_choice() {
+        let mut next = shared_next;
+        let array = ints(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        shuffle(&mut next, &[array]).unwrap();
+        let mut values: Vec<i128> = array.as_array().unwrap().lock().iter().map(|v| v.as_int().unwrap()).collect();
+        values.sort();
+        assert_eq!(values, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+
+        let picked = choice(&mut next, &[array]).unwrap().as_int().unwrap();
+        assert!((1..=8).contains(&picked));
+
+        let empty = ints(&[]);
+        assert!(choice(&mut next, &[empty]).unwrap().is_null());
+        assert!(shuffle(&mut next, &[empty]).unwrap_err().contains("non-empty"));
+        assert!(shuffle(&mut next, &[Value::int(1)]).unwrap_err().contains("expects an array, got int"));
+    }
+}
@@ -0,0 +1,248 @@
+//! std.regex 正则表达式
+//!
+//! - `new Regex(pattern)` 编译模式，语法错误抛出 `IllegalArgumentException`，消息中带出错的位置
+//! - `test` / `find` / `findAll` / `replace` / `groups` 使用编译好的模式，不会重新编译
+//!
+//! 语法是 Rust `regex` crate 的语法：没有反向引用和环视，匹配时间与输入长度成线性关系。
+//! 下标都是字符下标，与字符串的 `indexOf` / `substring` 一致。
+//!
+//! 实例只在 "pattern" 字段保存模式文本，编译结果按模式文本缓存在进程共享的表中，
+//! 同一个模式的多个实例共用一份；表满时清空，之后按需重新编译。
+
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+use ::regex::{Captures, Regex};
+use parking_lot::Mutex;
+
+use super::declarations::{class, param, ClassDecl};
+use super::exception::stdlib_exception;
+use super::StdlibModule;
+use crate::types::Type;
+use crate::vm::value::{ClassInstance, Value};
+
+// 标准库类名常量
+pub const CLASS_REGEX: &str = "std.regex.Regex";
+pub const CLASS_REGEX_MATCH: &str = "std.regex.RegexMatch";
+
+/// 编译结果缓存的容量
+const CACHE_CAPACITY: usize = 256;
+
+fn cache() -> &'static Mutex<HashMap<String, Arc<Regex>>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, Arc<Regex>>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// 模式的语法错误：位置是出错处的字符下标
+fn syntax_error(pattern: &str, error: &regex_syntax::Error) -> String {
+    let (offset, message) = match error {
+        regex_syntax::Error::Parse(e) => (e.span().start.offset, e.kind().to_string()),
+        regex_syntax::Error::Translate(e) => (e.span().start.offset, e.kind().to_string()),
+        other => (0, other.to_string()),
+    };
+    let position = pattern[..offset.min(pattern.len())].chars().count();
+    stdlib_exception("IllegalArgumentException", format!("invalid regex at position {}: {}", position, message))
+}
+
+/// 编译模式，已经编译过的直接取缓存
+fn compile(pattern: &str) -> Result<Arc<Regex>, String> {
+    if let Some(regex) = cache().lock().get(pattern) {
+        return Ok(regex.clone());
+    }
+    // 先单独解析一遍，得到带位置的语法错误；`Regex::new` 的错误只有格式化好的文本
+    regex_syntax::Parser::new().parse(pattern).map_err(|e| syntax_error(pattern, &e))?;
+    let regex = Arc::new(
+        Regex::new(pattern).map_err(|e| stdlib_exception("IllegalArgumentException", format!("invalid regex: {}", e)))?,
+    );
+    let mut cache = cache().lock();
+    if cache.len() >= CACHE_CAPACITY {
+        cache.clear();
+    }
+    cache.insert(pattern.to_string(), regex.clone());
+    Ok(regex)
+}
+
+fn instance(class_name: &str, fields: HashMap<String, Value>) -> Value {
+    Value::class(Arc::new(Mutex::new(ClassInstance {
+        class_name: class_name.to_string(),
+        parent_class: None,
+        fields,
+    })))
+}
+
+fn string_arg<'a>(args: &'a [Value], index: usize, func: &str) -> Result<&'a String, String> {
+    args.get(index).and_then(|v| v.as_string()).ok_or_else(|| {
+        stdlib_exception("IllegalArgumentException", format!("Regex.{} expects a string argument", func))
+    })
+}
+
+/// `new Regex(pattern)`
+fn regex_init(args: &[Value]) -> Result<Value, String> {
+    let pattern = args.first().and_then(|v| v.as_string()).ok_or_else(|| {
+        stdlib_exception("IllegalArgumentException", "Regex expects a string pattern")
+    })?;
+    compile(pattern)?;
+    let mut fields = HashMap::new();
+    fields.insert("pattern".to_string(), Value::string(pattern.clone()));
+    Ok(instance(CLASS_REGEX, fields))
+}
+
+/// 匹配结果：匹配的文本和它的字符下标
+fn match_value(text: &str, index: usize) -> Value {
+    let mut fields = HashMap::new();
+    fields.insert("text".to_string(), Value::string(text.to_string()));
+    fields.insert("index".to_string(), Value::int(index as i128));
+    instance(CLASS_REGEX_MATCH, fields)
+}
+
+/// 第一个匹配的捕获组（不含整个匹配），没有参与匹配的组为 null
+fn group_values(captures: &Captures) -> Value {
+    let groups = captures
+        .iter()
+        .skip(1)
+        .map(|group| group.map_or_else(Value::null, |m| Value::string(m.as_str().to_string())))
+        .collect();
+    Value::array(Arc::new(Mutex::new(groups)))
+}
+
+fn call_regex_method(instance: &Value, method_name: &str, args: &[Value]) -> Result<Value, String> {
+    let pattern = instance
+        .as_class()
+        .and_then(|instance| instance.lock().fields.get("pattern").and_then(|v| v.as_string().cloned()))
+        .ok_or("Regex instance has no pattern")?;
+    let regex = compile(&pattern)?;
+    match method_name {
+        "test" => Ok(Value::bool(regex.is_match(string_arg(args, 0, "test")?))),
+        "find" => {
+            let text = string_arg(args, 0, "find")?;
+            Ok(regex.find(text).map_or_else(Value::null, |m| match_value(m.as_str(), text[..m.start()].chars().count())))
+        }
+        "findAll" => {
+            let text = string_arg(args, 0, "findAll")?;
+            // 从上一个匹配处继续数字符，整个输入只数一遍
+            let (mut byte, mut index) = (0, 0);
+            let matches = regex
+                .find_iter(text)
+                .map(|m| {
+                    index += text[byte..m.start()].chars().count();
+                    byte = m.start();
+                    match_value(m.as_str(), index)
+                })
+                .collect();
+            Ok(Value::array(Arc::new(Mutex::new(matches))))
+        }
+        "replace" => {
+            let text = string_arg(args, 0, "replace")?;
+            let replacement = string_arg(args, 1, "replace")?;
+            Ok(Value::string(regex.replace_all(text, replacement.as_str()).into_owned()))
+        }
+        "groups" => {
+            let text = string_arg(args, 0, "groups")?;
+            Ok(regex.captures(text).map_or_else(Value::null, |captures| group_values(&captures)))
+        }
+        _ => Err(format!("Regex has no method '{}'", method_name)),
+    }
+}
+
+/// std.regex 标准库
+#[derive(Default)]
+pub struct RegexLib;
+
+impl RegexLib {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl StdlibModule for RegexLib {
+    fn name(&self) -> &'static str {
+        "std.regex"
+    }
+
+    fn exports(&self) -> Vec<&'static str> {
+        vec![]
+    }
+
+    fn call(&self, name: &str, _args: &[Value]) -> Result<Value, String> {
+        Err(format!("Unknown function: {}", name))
+    }
+
+    fn has_class(&self, class_name: &str) -> bool {
+        class_name == CLASS_REGEX || class_name == CLASS_REGEX_MATCH
+    }
+
+    fn create_class_instance(&self, class_name: &str, args: &[Value]) -> Result<Value, String> {
+        match class_name {
+            CLASS_REGEX => regex_init(args),
+            _ => Err(format!("Class '{}' not found in module '{}'", class_name, self.name())),
+        }
+    }
+
+    fn call_method(&self, instance: &Value, method_name: &str, args: &[Value]) -> Result<Value, String> {
+        call_regex_method(instance, method_name, args)
+    }
+
+    fn type_declarations(&self) -> Vec<ClassDecl> {
+        let text = || param("text", Type::String);
+        let matched = || class("RegexMatch");
+        vec![
+            ClassDecl::new("Regex")
+                .constructor(vec![param("pattern", Type::String)])
+                .field("pattern", Type::String)
+                .method("test", vec![text()], Type::Bool)
+                .method("find", vec![text()], Type::Nullable(Box::new(matched())))
+                .method("findAll", vec![text()], Type::Slice { element_type: Box::new(matched()) })
+                .method("replace", vec![text(), param("replacement", Type::String)], Type::String)
+                .method(
+                    "groups",
+                    vec![text()],
+                    Type::Nullable(Box::new(Type::Slice { element_type: Box::new(Type::Nullable(Box::new(Type::String))) })),
+                ),
+            ClassDecl::new("RegexMatch").field("text", Type::String).field("index", Type::Int),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(text: &str) -> Value {
+        Value::string(text.to_string())
+    }
+
+    fn field(value: &Value, name: &str) -> Value {
+        value.as_class().unwrap().lock().fields[name]
+    }
+
+    #[test]
+    fn test_syntax_error_position() {
+        let error = regex_init(&[string("ab(c")]).unwrap_err();
+        assert!(error.starts_with("IllegalArgumentException: invalid regex at position 2: "), "{}", error);
+        // 位置按字符计算
+        let error = regex_init(&[string("日本[")]).unwrap_err();
+        assert!(error.contains("at position 2:"), "{}", error);
+    }
+
+    #[test]
+    fn test_match_indices_are_char_indices() {
+        let regex = regex_init(&[string(r"\d+")]).unwrap();
+        let found = call_regex_method(&regex, "find", &[string("价格 42 元")]).unwrap();
+        assert_eq!(field(&found, "index").as_int(), Some(3));
+        assert_eq!(field(&found, "text").as_string().map(String::as_str), Some("42"));
+
+        let all = call_regex_method(&regex, "findAll", &[string("é1 é22 é333")]).unwrap();
+        let indices: Vec<_> = all.as_array().unwrap().lock().iter().map(|m| field(m, "index").as_int().unwrap()).collect();
+        assert_eq!(indices, vec![1, 4, 8]);
+    }
+
+    #[test]
+    fn test_compiled_once() {
+        let pattern = "^cache-test-[a-z]+$";
+        let a = regex_init(&[string(pattern)]).unwrap();
+        let b = regex_init(&[string(pattern)]).unwrap();
+        assert!(call_regex_method(&a, "test", &[string("cache-test-x")]).unwrap().as_bool().unwrap());
+        assert!(!call_regex_method(&b, "test", &[string("cache-test-1")]).unwrap().as_bool().unwrap());
+        assert!(Arc::ptr_eq(&compile(pattern).unwrap(), &compile(pattern).unwrap()));
+    }
+}
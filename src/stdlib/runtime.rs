@@ -0,0 +1,221 @@
+//! std.runtime 运行时信息
+//!
+//! 提供 `Runtime.gc()`、`Runtime.memoryStats()` 和 `Runtime.setGcThreshold(bytes)`，
+//! 在程序里触发和观察全局堆的回收。
+//!
+//! 回收只能在虚拟机的安全点进行：`Runtime.gc()` 只记下要求，虚拟机从这次调用返回后立即回收；
+//! 还有其他协程在运行时推迟到只剩一个虚拟机运行的安全点。没有启用 GC 的虚拟机里什么也不做。
+//!
+//! 弱引用：
+//!
+//! - `new WeakRef(target)`：不让目标存活的引用，目标被回收后 `get()` 返回 null
+//! - `new WeakMap()`：以对象的身份为键、弱引用键的 map，键被回收后条目随之删除；值是强引用
+//!
+//! 目标和键只能是类实例、结构体、数组和 map。弱引用登记在堆的弱引用表中（见 [`Heap::set_weak_ref`]），
+//! 实例自身是普通的值，可以保存和传递。
+//!
+//! [`Heap::set_weak_ref`]: crate::vm::gc::Heap::set_weak_ref
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use super::{ClassDecl, StdlibModule};
+use super::declarations::param;
+use super::exception::stdlib_exception;
+use crate::types::Type;
+use crate::vm::gc::{gc_write_barrier, get_heap, weak_key};
+use crate::vm::value::{ClassInstance, Value};
+use crate::vm::MapData;
+
+// 标准库类名常量
+pub const CLASS_WEAK_REF: &str = "std.runtime.WeakRef";
+pub const CLASS_WEAK_MAP: &str = "std.runtime.WeakMap";
+
+/// Runtime.gc() -> null
+pub fn runtime_gc(_args: &[Value]) -> Result<Value, String> {
+    get_heap().request_gc();
+    Ok(Value::null())
+}
+
+/// Runtime.memoryStats() -> map[string]int
+pub fn runtime_memory_stats(_args: &[Value]) -> Result<Value, String> {
+    let heap = get_heap();
+    let stats = heap.stats();
+    let entries = [
+        ("heapBytes", stats.heap_size as u64),
+        ("objects", heap.object_count() as u64),
+        ("allocations", stats.total_allocations),
+        ("frees", stats.total_frees),
+        ("collections", stats.collections()),
+        ("lastPauseNs", stats.last_gc_time_ns),
+        ("maxPauseNs", stats.max_pause_ns),
+        ("totalPauseNs", stats.total_pause_time_ns),
+        ("gcThreshold", heap.min_trigger() as u64),
+    ];
+    let mut map = MapData::default();
+    for (key, value) in entries {
+        map.insert(key.to_string(), Value::int(value as i128));
+    }
+    Ok(Value::map(Arc::new(Mutex::new(map))))
+}
+
+/// Runtime.setGcThreshold(bytes: int) -> null
+/// 两次回收之间至少分配的字节数；存活对象较多时阈值按存活量增长
+pub fn runtime_set_gc_threshold(args: &[Value]) -> Result<Value, String> {
+    let bytes = args.first()
+        .and_then(|v| v.as_int())
+        .filter(|&n| n > 0)
+        .ok_or_else(|| stdlib_exception(
+            "IllegalArgumentException",
+            "Runtime.setGcThreshold expects a positive number of bytes",
+        ))?;
+    get_heap().set_min_trigger(bytes as usize);
+    Ok(Value::null())
+}
+
+/// 可以被弱引用的值：类实例、结构体、数组和 map
+fn weak_target(what: &str, value: Option<&Value>) -> Result<Value, String> {
+    let value = value.copied().unwrap_or_else(Value::null);
+    if value.as_class().is_some() || value.as_struct().is_some() || value.as_array().is_some() || value.as_map().is_some() {
+        Ok(value)
+    } else {
+        Err(stdlib_exception(
+            "IllegalArgumentException",
+            format!("{} expects an object, array or map, got {}", what, value.type_name()),
+        ))
+    }
+}
+
+fn instance(class_name: &str, fields: HashMap<String, Value>) -> Value {
+    Value::class(Arc::new(Mutex::new(ClassInstance {
+        class_name: class_name.to_string(),
+        parent_class: None,
+        fields,
+    })))
+}
+
+/// `new WeakRef(target)`
+fn weak_ref_init(args: &[Value]) -> Result<Value, String> {
+    let target = weak_target("WeakRef", args.first())?;
+    let weak_ref = instance(CLASS_WEAK_REF, HashMap::new());
+    get_heap().set_weak_ref(&weak_ref, target);
+    Ok(weak_ref)
+}
+
+/// WeakRef 的方法：`get()` 返回目标或 null，`isAlive()` 返回目标是否还没有被回收
+fn call_weak_ref_method(weak_ref: &Value, method_name: &str) -> Result<Value, String> {
+    let target = get_heap().weak_ref_target(weak_ref);
+    match method_name {
+        "get" => Ok(target.unwrap_or_else(Value::null)),
+        "isAlive" => Ok(Value::bool(target.is_some())),
+        _ => Err(format!("WeakRef has no method '{}'", method_name)),
+    }
+}
+
+/// `new WeakMap()`：值保存在 "__entries" 字段的 map 中，键是键对象的指针
+fn weak_map_init() -> Value {
+    let entries = Value::map(Arc::new(Mutex::new(MapData::default())));
+    let mut fields = HashMap::new();
+    fields.insert("__entries".to_string(), entries);
+    instance(CLASS_WEAK_MAP, fields)
+}
+
+/// WeakMap 的方法：set / get / has / delete / size
+fn call_weak_map_method(weak_map: &Value, method_name: &str, args: &[Value]) -> Result<Value, String> {
+    let entries = weak_map
+        .as_class()
+        .and_then(|instance| instance.lock().fields.get("__entries").copied())
+        .ok_or("WeakMap instance has no entries")?;
+    let map = entries.as_map().ok_or("WeakMap instance has no entries")?;
+    if method_name == "size" {
+        return Ok(Value::int(map.lock().len() as i128));
+    }
+    let key = weak_target(&format!("WeakMap.{}", method_name), args.first())?;
+    let id = weak_key(key.as_ptr());
+    match method_name {
+        "set" => {
+            let value = args.get(1).copied().unwrap_or_else(Value::null);
+            map.lock().insert(id, value);
+            gc_write_barrier(&entries);
+            get_heap().add_weak_key(weak_map, &entries, &key);
+            Ok(Value::null())
+        }
+        "get" => Ok(map.lock().get(&id).copied().unwrap_or_else(Value::null)),
+        "has" => Ok(Value::bool(map.lock().contains_key(&id))),
+        "delete" => {
+            let removed = map.lock().remove(&id).is_some();
+            get_heap().remove_weak_key(weak_map, &key);
+            Ok(Value::bool(removed))
+        }
+        _ => Err(format!("WeakMap has no method '{}'", method_name)),
+    }
+}
+
+/// std.runtime 标准库
+#[derive(Default)]
+pub struct RuntimeLib;
+
+impl RuntimeLib {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl StdlibModule for RuntimeLib {
+    fn name(&self) -> &'static str {
+        "std.runtime"
+    }
+
+    fn exports(&self) -> Vec<&'static str> {
+        vec!["Runtime_gc", "Runtime_memoryStats", "Runtime_setGcThreshold"]
+    }
+
+    fn call(&self, name: &str, args: &[Value]) -> Result<Value, String> {
+        match name {
+            "Runtime_gc" => runtime_gc(args),
+            "Runtime_memoryStats" => runtime_memory_stats(args),
+            "Runtime_setGcThreshold" => runtime_set_gc_threshold(args),
+            _ => Err(format!("Unknown function: {}", name)),
+        }
+    }
+
+    fn has_class(&self, class_name: &str) -> bool {
+        class_name == CLASS_WEAK_REF || class_name == CLASS_WEAK_MAP
+    }
+
+    fn create_class_instance(&self, class_name: &str, args: &[Value]) -> Result<Value, String> {
+        match class_name {
+            CLASS_WEAK_REF => weak_ref_init(args),
+            CLASS_WEAK_MAP => Ok(weak_map_init()),
+            _ => Err(format!("Class '{}' not found in module '{}'", class_name, self.name())),
+        }
+    }
+
+    fn call_method(&self, instance: &Value, method_name: &str, args: &[Value]) -> Result<Value, String> {
+        let class_name = instance.as_class().map(|instance| instance.lock().class_name.clone());
+        match class_name.as_deref() {
+            Some(CLASS_WEAK_REF) => call_weak_ref_method(instance, method_name),
+            Some(CLASS_WEAK_MAP) => call_weak_map_method(instance, method_name, args),
+            _ => Err("Value is not a class instance".to_string()),
+        }
+    }
+
+    fn type_declarations(&self) -> Vec<ClassDecl> {
+        let key = || param("key", Type::Unknown);
+        vec![
+            ClassDecl::new("WeakRef")
+                .constructor(vec![param("target", Type::Unknown)])
+                .method("get", vec![], Type::Dynamic)
+                .method("isAlive", vec![], Type::Bool),
+            ClassDecl::new("WeakMap")
+                .constructor(vec![])
+                .method("set", vec![key(), param("value", Type::Unknown)], Type::Null)
+                .method("get", vec![key()], Type::Dynamic)
+                .method("has", vec![key()], Type::Bool)
+                .method("delete", vec![key()], Type::Bool)
+                .method("size", vec![], Type::Int),
+        ]
+    }
+}
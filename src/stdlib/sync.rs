@@ -0,0 +1,433 @@
+//! std.sync 同步原语模块
+//!
+//! 提供无锁的 Atomic 整数、AtomicFlag 标志和基于它们实现的 RateLimiter 令牌桶。
+//!
+//! 实例的 "__handle" 字段保存指向 Rust 原子变量的指针，实例本身在 goroutine 之间
+//! 共享（同一个 Arc），所以所有操作都直接作用于同一个原子变量，不需要加锁。
+
+use super::StdlibModule;
+use super::exception::stdlib_exception;
+use crate::vm::value::{Value, ClassInstance};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::time::Instant;
+
+// 标准库类名常量
+pub const CLASS_ATOMIC: &str = "std.sync.Atomic";
+pub const CLASS_ATOMIC_FLAG: &str = "std.sync.AtomicFlag";
+pub const CLASS_RATE_LIMITER: &str = "std.sync.RateLimiter";
+
+/// 每个令牌在令牌桶内部的计量单位：1 个令牌 = 10^9 单位，
+/// 这样按纳秒补充令牌时不会因为整数除法丢失零头
+const TOKEN_UNIT: i128 = 1_000_000_000;
+
+/// 令牌桶限流器
+///
+/// 令牌数和上次补充时间都是原子变量：补充时先用 CAS 抢占时间段，
+/// 抢到的线程把这段时间产生的令牌加回桶里，获取令牌同样是 CAS 循环。
+pub struct RateLimiterHandle {
+    /// 当前令牌数（单位为 TOKEN_UNIT）
+    tokens: AtomicI64,
+    /// 上次补充时间（相对 start 的纳秒数）
+    last_refill: AtomicI64,
+    start: Instant,
+    capacity: i64,
+    refill_per_second: i64,
+}
+
+impl RateLimiterHandle {
+    fn new(capacity: i64, refill_per_second: i64) -> Self {
+        Self {
+            tokens: AtomicI64::new((capacity as i128 * TOKEN_UNIT) as i64),
+            last_refill: AtomicI64::new(0),
+            start: Instant::now(),
+            capacity,
+            refill_per_second,
+        }
+    }
+
+    fn max_tokens(&self) -> i64 {
+        (self.capacity as i128 * TOKEN_UNIT) as i64
+    }
+
+    /// 把上次补充以来产生的令牌加回桶里
+    fn refill(&self) {
+        let now = self.start.elapsed().as_nanos().min(i64::MAX as u128) as i64;
+        let last = self.last_refill.load(Ordering::Acquire);
+        if now <= last {
+            return;
+        }
+        // 只有抢到这段时间的线程负责补充，其他线程直接使用现有令牌
+        if self.last_refill.compare_exchange(last, now, Ordering::AcqRel, Ordering::Acquire).is_err() {
+            return;
+        }
+        let max = self.max_tokens();
+        let added = ((now - last) as i128 * self.refill_per_second as i128).min(max as i128) as i64;
+        let _ = self.tokens.fetch_update(Ordering::AcqRel, Ordering::Acquire, |tokens| {
+            Some(tokens.saturating_add(added).min(max))
+        });
+    }
+
+    fn try_acquire(&self) -> bool {
+        self.refill();
+        let unit = TOKEN_UNIT as i64;
+        self.tokens
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |tokens| {
+                if tokens >= unit { Some(tokens - unit) } else { None }
+            })
+            .is_ok()
+    }
+
+    fn available(&self) -> i64 {
+        self.refill();
+        self.tokens.load(Ordering::Acquire) / TOKEN_UNIT as i64
+    }
+}
+
+/// 提取整数参数（必须落在 64 位有符号整数范围内）
+fn int_arg(args: &[Value], index: usize, func: &str, name: &str) -> Result<i64, String> {
+    args.get(index)
+        .and_then(|v| v.as_int())
+        .and_then(|n| i64::try_from(n).ok())
+        .ok_or_else(|| stdlib_exception(
+            "IllegalArgumentException",
+            format!("{} expects argument '{}' to be a 64-bit integer", func, name),
+        ))
+}
+
+// 从ClassInstance提取原生指针（存储在"__handle"字段中）
+fn extract_handle_ptr(instance: &Value, class: &str) -> Result<u64, String> {
+    if let Some(class_instance) = instance.as_class() {
+        let instance = class_instance.lock();
+        if let Some(ptr) = instance.fields.get("__handle").and_then(|v| v.as_int()) {
+            return Ok(ptr as u64);
+        }
+        Err(format!("{} instance has no valid handle", class))
+    } else {
+        Err(format!("Value is not a {} instance", class))
+    }
+}
+
+fn atomic_ref(instance: &Value) -> Result<&'static AtomicI64, String> {
+    let ptr = extract_handle_ptr(instance, "Atomic")?;
+    Ok(unsafe { &*(ptr as *const AtomicI64) })
+}
+
+fn flag_ref(instance: &Value) -> Result<&'static AtomicBool, String> {
+    let ptr = extract_handle_ptr(instance, "AtomicFlag")?;
+    Ok(unsafe { &*(ptr as *const AtomicBool) })
+}
+
+fn limiter_ref(instance: &Value) -> Result<&'static RateLimiterHandle, String> {
+    let ptr = extract_handle_ptr(instance, "RateLimiter")?;
+    Ok(unsafe { &*(ptr as *const RateLimiterHandle) })
+}
+
+// 创建带原生句柄的类实例
+fn create_handle_instance(class_name: &str, ptr: u64) -> Value {
+    let mut fields = HashMap::new();
+    fields.insert("__handle".to_string(), Value::int(ptr as i128));
+
+    let instance = ClassInstance {
+        class_name: class_name.to_string(),
+        parent_class: None,
+        fields,
+    };
+
+    Value::class(Arc::new(Mutex::new(instance)))
+}
+
+// ============================================================================
+// Atomic 类方法实现
+// ============================================================================
+
+/// Atomic 构造函数
+/// init(initial: int = 0) -> Atomic
+pub fn atomic_init(args: &[Value]) -> Result<Value, String> {
+    let initial = match args.first() {
+        Some(v) if !v.is_null() => int_arg(args, 0, "Atomic.init", "initial")?,
+        _ => 0,
+    };
+    let ptr = Box::into_raw(Box::new(AtomicI64::new(initial))) as u64;
+    Ok(create_handle_instance(CLASS_ATOMIC, ptr))
+}
+
+/// Atomic.get() -> int
+pub fn atomic_get(instance: &Value, _args: &[Value]) -> Result<Value, String> {
+    Ok(Value::int(atomic_ref(instance)?.load(Ordering::SeqCst) as i128))
+}
+
+/// Atomic.set(value: int) -> null
+pub fn atomic_set(instance: &Value, args: &[Value]) -> Result<Value, String> {
+    let value = int_arg(args, 0, "Atomic.set", "value")?;
+    atomic_ref(instance)?.store(value, Ordering::SeqCst);
+    Ok(Value::null())
+}
+
+/// Atomic.add(delta: int) -> int
+///
+/// 返回相加后的新值，溢出时按 64 位补码回绕
+pub fn atomic_add(instance: &Value, args: &[Value]) -> Result<Value, String> {
+    let delta = int_arg(args, 0, "Atomic.add", "delta")?;
+    let previous = atomic_ref(instance)?.fetch_add(delta, Ordering::SeqCst);
+    Ok(Value::int(previous.wrapping_add(delta) as i128))
+}
+
+/// Atomic.compareAndSwap(expected: int, new: int) -> bool
+///
+/// 当前值等于 expected 时替换为 new 并返回 true，否则不修改并返回 false
+pub fn atomic_compare_and_swap(instance: &Value, args: &[Value]) -> Result<Value, String> {
+    let expected = int_arg(args, 0, "Atomic.compareAndSwap", "expected")?;
+    let new = int_arg(args, 1, "Atomic.compareAndSwap", "new")?;
+    let swapped = atomic_ref(instance)?
+        .compare_exchange(expected, new, Ordering::SeqCst, Ordering::SeqCst)
+        .is_ok();
+    Ok(Value::bool(swapped))
+}
+
+/// Atomic.swap(value: int) -> int
+///
+/// 写入新值并返回旧值
+pub fn atomic_swap(instance: &Value, args: &[Value]) -> Result<Value, String> {
+    let value = int_arg(args, 0, "Atomic.swap", "value")?;
+    Ok(Value::int(atomic_ref(instance)?.swap(value, Ordering::SeqCst) as i128))
+}
+
+// ============================================================================
+// AtomicFlag 类方法实现
+// ============================================================================
+
+/// AtomicFlag 构造函数
+/// init() -> AtomicFlag，初始为未设置
+pub fn atomic_flag_init(_args: &[Value]) -> Result<Value, String> {
+    let ptr = Box::into_raw(Box::new(AtomicBool::new(false))) as u64;
+    Ok(create_handle_instance(CLASS_ATOMIC_FLAG, ptr))
+}
+
+/// AtomicFlag.testAndSet() -> bool
+///
+/// 设置标志并返回设置前的值：返回 false 说明本次调用抢到了标志
+pub fn atomic_flag_test_and_set(instance: &Value, _args: &[Value]) -> Result<Value, String> {
+    Ok(Value::bool(flag_ref(instance)?.swap(true, Ordering::SeqCst)))
+}
+
+/// AtomicFlag.clear() -> null
+pub fn atomic_flag_clear(instance: &Value, _args: &[Value]) -> Result<Value, String> {
+    flag_ref(instance)?.store(false, Ordering::SeqCst);
+    Ok(Value::null())
+}
+
+// ============================================================================
+// RateLimiter 类方法实现
+// ============================================================================
+
+/// RateLimiter 构造函数
+/// init(capacity: int, refillPerSecond: int) -> RateLimiter
+///
+/// 桶初始为满，最多允许 capacity 个请求的突发，之后按每秒 refillPerSecond 个补充
+pub fn rate_limiter_init(args: &[Value]) -> Result<Value, String> {
+    let capacity = int_arg(args, 0, "RateLimiter.init", "capacity")?;
+    let refill_per_second = int_arg(args, 1, "RateLimiter.init", "refillPerSecond")?;
+    if capacity <= 0 || capacity as i128 * TOKEN_UNIT > i64::MAX as i128 {
+        return Err(stdlib_exception(
+            "IllegalArgumentException",
+            format!("RateLimiter capacity must be between 1 and {}, got {}", i64::MAX as i128 / TOKEN_UNIT, capacity),
+        ));
+    }
+    if refill_per_second < 0 {
+        return Err(stdlib_exception(
+            "IllegalArgumentException",
+            format!("RateLimiter refillPerSecond must not be negative, got {}", refill_per_second),
+        ));
+    }
+    let handle = Box::new(RateLimiterHandle::new(capacity, refill_per_second));
+    let ptr = Box::into_raw(handle) as u64;
+    Ok(create_handle_instance(CLASS_RATE_LIMITER, ptr))
+}
+
+/// RateLimiter.tryAcquire() -> bool
+///
+/// 有可用令牌时取走一个并返回 true，否则立即返回 false（不阻塞）
+pub fn rate_limiter_try_acquire(instance: &Value, _args: &[Value]) -> Result<Value, String> {
+    Ok(Value::bool(limiter_ref(instance)?.try_acquire()))
+}
+
+/// RateLimiter.available() -> int
+pub fn rate_limiter_available(instance: &Value, _args: &[Value]) -> Result<Value, String> {
+    Ok(Value::int(limiter_ref(instance)?.available() as i128))
+}
+
+// ============================================================================
+// SyncLib - 同步原语标准库模块
+// ============================================================================
+
+#[derive(Default)]
+pub struct SyncLib;
+
+impl SyncLib {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl StdlibModule for SyncLib {
+    fn name(&self) -> &'static str {
+        "std.sync"
+    }
+
+    fn exports(&self) -> Vec<&'static str> {
+        vec![]
+    }
+
+    fn call(&self, name: &str, _args: &[Value]) -> Result<Value, String> {
+        Err(format!("Unknown function: {}", name))
+    }
+
+    fn has_class(&self, class_name: &str) -> bool {
+        matches!(class_name, CLASS_ATOMIC | CLASS_ATOMIC_FLAG | CLASS_RATE_LIMITER)
+    }
+
+    fn create_class_instance(&self, class_name: &str, args: &[Value]) -> Result<Value, String> {
+        match class_name {
+            CLASS_ATOMIC => atomic_init(args),
+            CLASS_ATOMIC_FLAG => atomic_flag_init(args),
+            CLASS_RATE_LIMITER => rate_limiter_init(args),
+            _ => Err(format!("Class '{}' not found in module '{}'", class_name, self.name())),
+        }
+    }
+
+    fn call_method(&self, instance: &Value, method_name: &str, args: &[Value]) -> Result<Value, String> {
+        // 从实例中提取类名
+        let class_name = match instance.as_class() {
+            Some(class_instance) => class_instance.lock().class_name.clone(),
+            None => return Err("Value is not a class instance".to_string()),
+        };
+
+        match class_name.as_str() {
+            CLASS_ATOMIC => match method_name {
+                "get" => atomic_get(instance, args),
+                "set" => atomic_set(instance, args),
+                "add" => atomic_add(instance, args),
+                "compareAndSwap" => atomic_compare_and_swap(instance, args),
+                "swap" => atomic_swap(instance, args),
+                _ => Err(format!("Atomic has no method '{}'", method_name)),
+            },
+            CLASS_ATOMIC_FLAG => match method_name {
+                "testAndSet" => atomic_flag_test_and_set(instance, args),
+                "clear" => atomic_flag_clear(instance, args),
+                _ => Err(format!("AtomicFlag has no method '{}'", method_name)),
+            },
+            CLASS_RATE_LIMITER => match method_name {
+                "tryAcquire" => rate_limiter_try_acquire(instance, args),
+                "available" => rate_limiter_available(instance, args),
+                _ => Err(format!("RateLimiter has no method '{}'", method_name)),
+            },
+            _ => Err(format!("Unknown class: {}", class_name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    fn call(instance: &Value, method: &str, args: &[Value]) -> Value {
+        SyncLib::new().call_method(instance, method, args).unwrap()
+    }
+
+    #[test]
+    fn test_concurrent_increments_sum_exactly() {
+        let counter = atomic_init(&[Value::int(0)]).unwrap();
+        let workers: Vec<_> = (0..100)
+            .map(|_| {
+                thread::spawn(move || {
+                    for _ in 0..10_000 {
+                        call(&counter, "add", &[Value::int(1)]);
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert_eq!(call(&counter, "get", &[]).as_int(), Some(1_000_000));
+    }
+
+    #[test]
+    fn test_compare_and_swap_contention_terminates() {
+        let counter = atomic_init(&[Value::int(0)]).unwrap();
+        let workers: Vec<_> = (0..8)
+            .map(|_| {
+                thread::spawn(move || {
+                    for _ in 0..1_000 {
+                        // 经典 CAS 循环：读取、计算、比较交换，失败则重试
+                        loop {
+                            let current = call(&counter, "get", &[]).as_int().unwrap();
+                            let swapped = call(&counter, "compareAndSwap", &[Value::int(current), Value::int(current * 2 + 1)]);
+                            if swapped.as_bool() == Some(true) {
+                                break;
+                            }
+                        }
+                        call(&counter, "swap", &[Value::int(0)]);
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        assert_eq!(call(&counter, "swap", &[Value::int(5)]).as_int().map(|n| n >= 0), Some(true));
+        assert_eq!(call(&counter, "compareAndSwap", &[Value::int(4), Value::int(9)]).as_bool(), Some(false));
+        assert_eq!(call(&counter, "compareAndSwap", &[Value::int(5), Value::int(9)]).as_bool(), Some(true));
+        assert_eq!(call(&counter, "get", &[]).as_int(), Some(9));
+
+        let flag = atomic_flag_init(&[]).unwrap();
+        assert_eq!(call(&flag, "testAndSet", &[]).as_bool(), Some(false));
+        assert_eq!(call(&flag, "testAndSet", &[]).as_bool(), Some(true));
+        call(&flag, "clear", &[]);
+        assert_eq!(call(&flag, "testAndSet", &[]).as_bool(), Some(false));
+    }
+
+    #[test]
+    fn test_rate_limiter_admits_configured_rate() {
+        // 容量 10，每秒补充 200：300ms 内的突发请求应放行约 10 + 60 个
+        let limiter = rate_limiter_init(&[Value::int(10), Value::int(200)]).unwrap();
+        let started = Instant::now();
+        let window = Duration::from_millis(300);
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                thread::spawn(move || {
+                    let mut admitted = 0;
+                    while started.elapsed() < window {
+                        if call(&limiter, "tryAcquire", &[]).as_bool() == Some(true) {
+                            admitted += 1;
+                        }
+                    }
+                    admitted
+                })
+            })
+            .collect();
+        let admitted: usize = workers.into_iter().map(|worker| worker.join().unwrap()).sum();
+
+        let elapsed = started.elapsed().as_secs_f64();
+        let expected = 10.0 + 200.0 * elapsed;
+        assert!(
+            (admitted as f64) <= expected + 1.0 && (admitted as f64) >= expected * 0.5,
+            "admitted {} requests in {:.3}s, expected about {:.0}",
+            admitted,
+            elapsed,
+            expected
+        );
+
+        let empty = rate_limiter_init(&[Value::int(2), Value::int(0)]).unwrap();
+        assert_eq!(call(&empty, "tryAcquire", &[]).as_bool(), Some(true));
+        assert_eq!(call(&empty, "tryAcquire", &[]).as_bool(), Some(true));
+        assert_eq!(call(&empty, "tryAcquire", &[]).as_bool(), Some(false));
+        assert!(rate_limiter_init(&[Value::int(0), Value::int(1)]).is_err());
+    }
+}
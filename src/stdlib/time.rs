@@ -0,0 +1,343 @@
+//! std.time 时间模块
+//!
+//! - `Time.now()` 返回当前的 Unix 毫秒时间戳，`Time.monotonicNanos()` 返回单调时钟的纳秒数，只用于计算时间差
+//! - `Time.sleep(millis)` 挂起当前协程。每个协程运行在自己的线程上，睡眠不影响其他协程；
+//!   睡眠期间虚拟机交出根集（见 [`StdlibModule::blocks`]），其他协程仍然可以回收
+//! - `Time.after(millis, callback?)` 返回在指定毫秒数后完成的 Future，传入回调时以回调的返回值完成，
+//!   所有定时器共用一个后台线程，不占用 IO 线程池
+//! - `Instant` 是单调时钟上的时刻，`Duration` 是纳秒精度的时间长度
+//!
+//! 以及把 Unix 毫秒时间戳格式化为 UTC 时间的 `Time.formatIso` / `Time.formatHttp` / `Time.format`，
+//! 格式的实现见 [`super::datetime`]。
+
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use parking_lot::Mutex;
+
+use super::{CallbackChannel, ClassDecl, StdlibModule};
+use super::datetime;
+use super::declarations::{class, param};
+use super::exception::stdlib_exception;
+use super::future;
+use super::net::io_thread_pool::IoThreadPool;
+use crate::types::Type;
+use crate::vm::value::{ClassInstance, Value};
+
+// 标准库类名常量
+pub const CLASS_INSTANT: &str = "std.time.Instant";
+pub const CLASS_DURATION: &str = "std.time.Duration";
+
+/// 第一个参数：非负的毫秒数
+fn millis_arg(function: &str, args: &[Value]) -> Result<u64, String> {
+    args.first()
+        .and_then(|v| v.as_int())
+        .and_then(|n| u64::try_from(n).ok())
+        .ok_or_else(|| stdlib_exception(
+            "IllegalArgumentException",
+            format!("Time.{} expects a non-negative number of milliseconds", function),
+        ))
+}
+
+/// Time.after(millis: int) -> Future
+pub fn time_after(args: &[Value]) -> Result<Value, String> {
+    Ok(future::after(millis_arg("after", args)?))
+}
+
+/// Time.now() -> int
+pub fn time_now(_args: &[Value]) -> Result<Value, String> {
+    let millis = match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_millis() as i128,
+        Err(before) => -(before.duration().as_millis() as i128),
+    };
+    Ok(Value::int(millis))
+}
+
+/// 单调时钟的纳秒数，从进程中第一次读取单调时钟时开始计算
+fn monotonic_nanos() -> i128 {
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_nanos() as i128
+}
+
+/// Time.monotonicNanos() -> int
+pub fn time_monotonic_nanos(_args: &[Value]) -> Result<Value, String> {
+    Ok(Value::int(monotonic_nanos()))
+}
+
+/// Time.sleep(millis: int)
+pub fn time_sleep(args: &[Value]) -> Result<Value, String> {
+    super::os::sleep(Duration::from_millis(millis_arg("sleep", args)?));
+    Ok(Value::null())
+}
+
+/// 第一个参数：i64 范围内的毫秒时间戳
+fn timestamp_arg(function: &str, args: &[Value]) -> Result<i64, String> {
+    args.first()
+        .and_then(|v| v.as_int())
+        .and_then(|n| i64::try_from(n).ok())
+        .ok_or_else(|| stdlib_exception(
+            "IllegalArgumentException",
+            format!("Time.{} expects a timestamp in milliseconds within the 64-bit range", function),
+        ))
+}
+
+/// Time.formatIso(millis: int) -> string
+pub fn time_format_iso(args: &[Value]) -> Result<Value, String> {
+    Ok(Value::string(datetime::format_iso(timestamp_arg("formatIso", args)?)))
+}
+
+/// Time.formatHttp(millis: int) -> string
+pub fn time_format_http(args: &[Value]) -> Result<Value, String> {
+    Ok(Value::string(datetime::format_http(timestamp_arg("formatHttp", args)?)))
+}
+
+/// Time.format(millis: int, pattern: string) -> string
+pub fn time_format(args: &[Value]) -> Result<Value, String> {
+    let millis = timestamp_arg("format", args)?;
+    let pattern = args.get(1)
+        .and_then(|v| v.as_string())
+        .ok_or_else(|| stdlib_exception("IllegalArgumentException", "Time.format expects a pattern string"))?;
+    datetime::format_pattern(millis, pattern)
+        .map(Value::string)
+        .map_err(|e| stdlib_exception("IllegalArgumentException", e))
+}
+
+// ============================================================================
+// Instant 和 Duration
+// ============================================================================
+
+/// 创建 Instant 或 Duration 实例，纳秒数保存在 "__nanos" 字段
+fn nanos_instance(class_name: &str, nanos: i128) -> Value {
+    let mut fields = HashMap::new();
+    fields.insert("__nanos".to_string(), Value::int(nanos));
+    Value::class(Arc::new(Mutex::new(ClassInstance {
+        class_name: class_name.to_string(),
+        parent_class: None,
+        fields,
+    })))
+}
+
+/// 取出 Instant 或 Duration 实例的纳秒数
+fn instance_nanos(class_name: &str, value: &Value) -> Result<i128, String> {
+    let nanos = value.as_class().and_then(|instance| {
+        let instance = instance.lock();
+        if instance.class_name != class_name {
+            return None;
+        }
+        instance.fields.get("__nanos").and_then(|v| v.as_int())
+    });
+    let short_name = class_name.rsplit('.').next().unwrap_or(class_name);
+    nanos.ok_or_else(|| stdlib_exception(
+        "IllegalArgumentException",
+        format!("Expected {} {}, got {}", if short_name == "Instant" { "an" } else { "a" }, short_name, value.type_name()),
+    ))
+}
+
+/// 按数量级选择单位，如 `1.5s`、`250ms`、`12.34µs`、`800ns`
+fn format_duration(nanos: i128) -> String {
+    let units = [(1_000_000_000, "s"), (1_000_000, "ms"), (1_000, "µs")];
+    let sign = if nanos < 0 { "-" } else { "" };
+    let magnitude = nanos.unsigned_abs();
+    for (scale, unit) in units {
+        if magnitude >= scale {
+            let fraction = format!("{:0width$}", magnitude % scale, width = scale.ilog10() as usize);
+            let fraction = fraction.trim_end_matches('0');
+            let dot = if fraction.is_empty() { "" } else { "." };
+            return format!("{}{}{}{}{}", sign, magnitude / scale, dot, fraction, unit);
+        }
+    }
+    format!("{}{}ns", sign, magnitude)
+}
+
+/// Instant 的方法
+fn call_instant_method(instance: &Value, method_name: &str, args: &[Value]) -> Result<Value, String> {
+    let nanos = instance_nanos(CLASS_INSTANT, instance)?;
+    match method_name {
+        "elapsed" => Ok(nanos_instance(CLASS_DURATION, monotonic_nanos() - nanos)),
+        "since" => {
+            let earlier = instance_nanos(CLASS_INSTANT, args.first().unwrap_or(&Value::null()))?;
+            Ok(nanos_instance(CLASS_DURATION, nanos - earlier))
+        }
+        "plus" => {
+            let duration = instance_nanos(CLASS_DURATION, args.first().unwrap_or(&Value::null()))?;
+            Ok(nanos_instance(CLASS_INSTANT, nanos + duration))
+        }
+        "nanos" => Ok(Value::int(nanos)),
+        _ => Err(format!("Instant has no method '{}'", method_name)),
+    }
+}
+
+/// Duration 的方法，毫秒和秒都向零取整
+fn call_duration_method(instance: &Value, method_name: &str, args: &[Value]) -> Result<Value, String> {
+    let nanos = instance_nanos(CLASS_DURATION, instance)?;
+    let other = || instance_nanos(CLASS_DURATION, args.first().unwrap_or(&Value::null()));
+    match method_name {
+        "nanos" => Ok(Value::int(nanos)),
+        "millis" => Ok(Value::int(nanos / 1_000_000)),
+        "seconds" => Ok(Value::float(nanos as f64 / 1e9)),
+        "plus" => Ok(nanos_instance(CLASS_DURATION, nanos + other()?)),
+        "minus" => Ok(nanos_instance(CLASS_DURATION, nanos - other()?)),
+        "toString" => Ok(Value::string(format_duration(nanos))),
+        _ => Err(format!("Duration has no method '{}'", method_name)),
+    }
+}
+
+/// std.time 标准库
+#[derive(Default)]
+pub struct TimeLib {
+    /// 执行 `Time.after` 的回调，第一次使用时创建
+    thread_pool: OnceLock<Arc<IoThreadPool>>,
+}
+
+impl TimeLib {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StdlibModule for TimeLib {
+    fn name(&self) -> &'static str {
+        "std.time"
+    }
+
+    fn exports(&self) -> Vec<&'static str> {
+        vec![
+            "Time_now",
+            "Time_monotonicNanos",
+            "Time_sleep",
+            "Time_after",
+            "Time_formatIso",
+            "Time_formatHttp",
+            "Time_format",
+        ]
+    }
+
+    fn call(&self, name: &str, args: &[Value]) -> Result<Value, String> {
+        match name {
+            "Time_now" => time_now(args),
+            "Time_monotonicNanos" => time_monotonic_nanos(args),
+            "Time_sleep" => time_sleep(args),
+            "Time_after" => time_after(args),
+            "Time_formatIso" => time_format_iso(args),
+            "Time_formatHttp" => time_format_http(args),
+            "Time_format" => time_format(args),
+            _ => Err(format!("Unknown function: {}", name)),
+        }
+    }
+
+    fn blocks(&self, name: &str) -> bool {
+        name == "Time_sleep"
+    }
+
+    fn function_needs_callback(&self, name: &str, args: &[Value]) -> bool {
+        name == "Time_after" && args.get(1).is_some_and(|callback| !callback.is_null())
+    }
+
+    /// Time.after(millis: int, callback: func) -> Future，回调在到期后执行，Future 以它的返回值完成
+    fn call_with_callback(
+        &self,
+        name: &str,
+        args: &[Value],
+        callback_channel: Arc<CallbackChannel>,
+    ) -> Result<Value, String> {
+        if name != "Time_after" {
+            return Err(format!("Function '{}' does not support callback", name));
+        }
+        let millis = millis_arg("after", args)?;
+        let callback = match args.get(1) {
+            Some(callback) if callback.as_function().is_some() => *callback,
+            _ => return Err(stdlib_exception("IllegalArgumentException", "Time.after expects a function as callback")),
+        };
+        let timer = future::future_state(&future::after(millis))?;
+        let pool = self.thread_pool.get_or_init(|| Arc::new(IoThreadPool::new(2)));
+        Ok(future::then_call(pool, &timer, callback, callback_channel))
+    }
+
+    fn has_class(&self, class_name: &str) -> bool {
+        class_name == CLASS_INSTANT || class_name == CLASS_DURATION
+    }
+
+    /// `new Instant()` 为当前时刻，`new Duration(millis)` 为给定的毫秒数
+    fn create_class_instance(&self, class_name: &str, args: &[Value]) -> Result<Value, String> {
+        match class_name {
+            CLASS_INSTANT => Ok(nanos_instance(CLASS_INSTANT, monotonic_nanos())),
+            CLASS_DURATION => {
+                let millis = args.first().and_then(|v| v.as_int()).ok_or_else(|| {
+                    stdlib_exception("IllegalArgumentException", "Duration expects a number of milliseconds")
+                })?;
+                let nanos = millis.checked_mul(1_000_000).ok_or_else(|| {
+                    stdlib_exception("IllegalArgumentException", format!("Duration of {} milliseconds is out of range", millis))
+                })?;
+                Ok(nanos_instance(CLASS_DURATION, nanos))
+            }
+            _ => Err(format!("Class '{}' not found in module '{}'", class_name, self.name())),
+        }
+    }
+
+    fn call_method(&self, instance: &Value, method_name: &str, args: &[Value]) -> Result<Value, String> {
+        let class_name = instance.as_class().map(|instance| instance.lock().class_name.clone());
+        match class_name.as_deref() {
+            Some(CLASS_INSTANT) => call_instant_method(instance, method_name, args),
+            Some(CLASS_DURATION) => call_duration_method(instance, method_name, args),
+            _ => Err("Value is not a class instance".to_string()),
+        }
+    }
+
+    fn type_declarations(&self) -> Vec<ClassDecl> {
+        let duration = || class("Duration");
+        vec![
+            ClassDecl::new("Instant")
+                .constructor(vec![])
+                .method("elapsed", vec![], duration())
+                .method("since", vec![param("earlier", class("Instant"))], duration())
+                .method("plus", vec![param("duration", duration())], class("Instant"))
+                .method("nanos", vec![], Type::Int),
+            ClassDecl::new("Duration")
+                .constructor(vec![param("millis", Type::Int)])
+                .method("nanos", vec![], Type::Int)
+                .method("millis", vec![], Type::Int)
+                .method("seconds", vec![], Type::F64)
+                .method("plus", vec![param("other", duration())], duration())
+                .method("minus", vec![param("other", duration())], duration())
+                .method("toString", vec![], Type::String),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(1_500_000_000), "1.5s");
+        assert_eq!(format_duration(60_000_000_000), "60s");
+        assert_eq!(format_duration(250_000_000), "250ms");
+        assert_eq!(format_duration(12_340), "12.34µs");
+        assert_eq!(format_duration(800), "800ns");
+        assert_eq!(format_duration(-100_000_000), "-100ms");
+        assert_eq!(format_duration(0), "0ns");
+    }
+
+    #[test]
+    fn test_declared_methods_are_dispatched() {
+        let lib = TimeLib::new();
+        for decl in lib.type_declarations() {
+            let class_name = format!("std.time.{}", decl.name);
+            assert!(lib.has_class(&class_name));
+            let instance = lib.create_class_instance(&class_name, &[Value::int(1)]).unwrap();
+            for method in &decl.methods {
+                // 参数都是同类实例或者 Duration
+                let arg = match method.params.first().map(|p| &p.ty) {
+                    Some(Type::Class(name)) => lib.create_class_instance(&format!("std.time.{}", name), &[Value::int(1)]).unwrap(),
+                    _ => Value::null(),
+                };
+                lib.call_method(&instance, method.name, &[arg])
+                    .unwrap_or_else(|e| panic!("{}.{}: {}", decl.name, method.name, e));
+            }
+        }
+        let error = lib.call_method(&lib.create_class_instance(CLASS_DURATION, &[Value::int(1)]).unwrap(), "plus", &[Value::int(1)]);
+        assert!(error.unwrap_err().contains("Expected a Duration, got int"));
+    }
+}
@@ -0,0 +1,94 @@
+//! std.uuid 随机 ID
+//!
+//! 提供 `Uuid.v4()`：RFC 4122 第 4 版格式的随机 ID，HttpServer 也用它为请求生成 ID。
+//! 随机数来自标准库 `RandomState` 的进程级随机密钥，不依赖外部 crate。
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::StdlibModule;
+use crate::vm::value::Value;
+
+/// 生成 128 位随机数
+///
+/// 用随机密钥的 SipHash 打散调用计数和当前时间，同一进程内不会重复
+pub(crate) fn random_u128() -> u128 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    let state = RandomState::new();
+    let mut halves = [0u64; 2];
+    for (i, half) in halves.iter_mut().enumerate() {
+        let mut hasher = state.build_hasher();
+        hasher.write_u64(count);
+        hasher.write_u64(nanos);
+        hasher.write_usize(i);
+        *half = hasher.finish();
+    }
+    ((halves[0] as u128) << 64) | halves[1] as u128
+}
+
+/// 生成第 4 版 UUID，如 `3f2b8c1e-9a4d-4e6f-b1c2-7d8e9f0a1b2c`
+pub fn uuid_v4() -> String {
+    let mut bytes = random_u128().to_be_bytes();
+    // 版本号 4，变体 10xx
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+/// Uuid.v4() -> string
+pub fn uuid_v4_fn(_args: &[Value]) -> Result<Value, String> {
+    Ok(Value::string(uuid_v4()))
+}
+
+/// std.uuid 标准库
+#[derive(Default)]
+pub struct UuidLib;
+
+impl UuidLib {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl StdlibModule for UuidLib {
+    fn name(&self) -> &'static str {
+        "std.uuid"
+    }
+
+    fn exports(&self) -> Vec<&'static str> {
+        vec!["Uuid_v4"]
+    }
+
+    fn call(&self, name: &str, args: &[Value]) -> Result<Value, String> {
+        match name {
+            "Uuid_v4" => uuid_v4_fn(args),
+            _ => Err(format!("Unknown function: {}", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uuid_v4_format() {
+        let id = uuid_v4();
+        let groups: Vec<&str> = id.split('-').collect();
+        assert_eq!(groups.iter().map(|g| g.len()).collect::<Vec<_>>(), vec![8, 4, 4, 4, 12], "{}", id);
+        assert!(id.chars().all(|c| c == '-' || c.is_ascii_hexdigit() && !c.is_ascii_uppercase()), "{}", id);
+        assert!(groups[2].starts_with('4'), "{}", id);
+        assert!(matches!(groups[3].as_bytes()[0], b'8' | b'9' | b'a' | b'b'), "{}", id);
+
+        let ids: std::collections::HashSet<String> = (0..1000).map(|_| uuid_v4()).collect();
+        assert_eq!(ids.len(), 1000);
+    }
+}
@@ -6,6 +6,7 @@ use super::StdlibModule;
 use crate::vm::value::Value;
 
 /// std.Vmtest 标准库
+#[derive(Default)]
 pub struct VmTestLib;
 
 impl VmTestLib {
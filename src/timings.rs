@@ -0,0 +1,383 @@
+//! 编译阶段计时（`--timings`）
+//!
+//! 收集器保存在线程局部变量中，各阶段在自己的入口处记录耗时和统计数，不需要在函数签名中传递。
+//! 没有启用时收集器为 None，[`begin`] 不读取时钟，[`record`] 不计算统计数。
+//!
+//! 词法和语法分析按文件记录（[`file`] 期间记录的阶段同时计入该文件），
+//! 类型检查、单态化和代码生成作用于合并后的程序，只有总计。
+//! 并行解析时每个解析线程有自己的收集器，结束后由调用方 [`merge`] 到当前线程。
+
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use crate::stdlib::json::write_json_string;
+
+/// 编译阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// 词法分析，统计 token 数
+    Lex,
+    /// 语法分析，统计 AST 节点数（语句和表达式）
+    Parse,
+    /// 加载依赖（包含依赖文件的读取和解析），统计加载的文件数
+    LoadDependencies,
+    /// 类型检查，统计求解的约束数
+    TypeCheck,
+    /// 单态化，统计生成的泛型实例数
+    Monomorphize,
+    /// 代码生成，统计字节码的字节数
+    Codegen,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 6] = [
+        Phase::Lex,
+        Phase::Parse,
+        Phase::LoadDependencies,
+        Phase::TypeCheck,
+        Phase::Monomorphize,
+        Phase::Codegen,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Lex => "lex",
+            Phase::Parse => "parse",
+            Phase::LoadDependencies => "load_dependencies",
+            Phase::TypeCheck => "type_check",
+            Phase::Monomorphize => "monomorphize",
+            Phase::Codegen => "codegen",
+        }
+    }
+
+    /// 统计数的单位
+    pub fn unit(self) -> &'static str {
+        match self {
+            Phase::Lex => "tokens",
+            Phase::Parse => "nodes",
+            Phase::LoadDependencies => "files",
+            Phase::TypeCheck => "constraints",
+            Phase::Monomorphize => "instantiations",
+            Phase::Codegen => "bytes",
+        }
+    }
+}
+
+/// 一个阶段的累计耗时和统计数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhaseStats {
+    pub wall: Duration,
+    pub count: u64,
+    /// 记录的次数（如词法分析的文件数）
+    pub runs: u64,
+}
+
+impl PhaseStats {
+    fn add(&mut self, wall: Duration, count: u64) {
+        self.wall += wall;
+        self.count += count;
+        self.runs += 1;
+    }
+
+    fn merge(&mut self, other: &PhaseStats) {
+        self.wall += other.wall;
+        self.count += other.count;
+        self.runs += other.runs;
+    }
+}
+
+/// 一个源文件的词法和语法分析
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileTimings {
+    pub path: String,
+    pub lex: PhaseStats,
+    pub parse: PhaseStats,
+    /// AST 来自解析缓存，没有词法和语法分析
+    pub cached: bool,
+}
+
+/// 收集到的计时
+#[derive(Debug, Clone, Default)]
+pub struct Timings {
+    phases: [PhaseStats; Phase::ALL.len()],
+    files: Vec<FileTimings>,
+    /// 正在记录的文件（files 的下标）
+    current_file: Option<usize>,
+}
+
+impl Timings {
+    fn new() -> Self {
+        #[cfg(test)]
+        tests::CREATED.with(|created| created.set(created.get() + 1));
+        Self::default()
+    }
+
+    pub fn phase(&self, phase: Phase) -> PhaseStats {
+        self.phases[phase as usize]
+    }
+
+    #[cfg(test)]
+    pub fn files(&self) -> &[FileTimings] {
+        &self.files
+    }
+
+    fn add(&mut self, phase: Phase, wall: Duration, count: u64) {
+        self.phases[phase as usize].add(wall, count);
+        if let Some(file) = self.current_file.map(|index| &mut self.files[index]) {
+            match phase {
+                Phase::Lex => file.lex.add(wall, count),
+                Phase::Parse => file.parse.add(wall, count),
+                _ => {}
+            }
+        }
+    }
+
+    /// 输出表格：各阶段的总计，然后是每个文件的词法和语法分析
+    pub fn render_table(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "{:<20} {:>12} {:>10}", "phase", "time", "count");
+        for phase in Phase::ALL {
+            let stats = self.phase(phase);
+            let _ = writeln!(
+                out,
+                "{:<20} {:>12} {:>10} {}",
+                phase.name(),
+                format_duration(stats.wall),
+                stats.count,
+                phase.unit()
+            );
+        }
+        // 依赖加载包含依赖文件的解析，不计入总计
+        let total: Duration = Phase::ALL
+            .iter()
+            .filter(|&&phase| phase != Phase::LoadDependencies)
+            .map(|&phase| self.phase(phase).wall)
+            .sum();
+        let _ = writeln!(out, "{:<20} {:>12}", "total", format_duration(total));
+
+        if !self.files.is_empty() {
+            out.push('\n');
+            let _ = writeln!(out, "{:>12} {:>12} {:>8} {:>8}  file", "lex", "parse", "tokens", "nodes");
+            for file in &self.files {
+                if file.cached {
+                    let _ = writeln!(out, "{:>43}  {}", "(cached)", file.path);
+                    continue;
+                }
+                let _ = writeln!(
+                    out,
+                    "{:>12} {:>12} {:>8} {:>8}  {}",
+                    format_duration(file.lex.wall),
+                    format_duration(file.parse.wall),
+                    file.lex.count,
+                    file.parse.count,
+                    file.path
+                );
+            }
+        }
+        out
+    }
+
+    /// 输出 JSON，耗时以纳秒为单位
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\"phases\":[");
+        for (i, phase) in Phase::ALL.into_iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let stats = self.phase(phase);
+            let _ = write!(
+                out,
+                "{{\"name\":\"{}\",\"wall_ns\":{},\"count\":{},\"unit\":\"{}\",\"runs\":{}}}",
+                phase.name(),
+                stats.wall.as_nanos(),
+                stats.count,
+                phase.unit(),
+                stats.runs
+            );
+        }
+        out.push_str("],\"files\":[");
+        for (i, file) in self.files.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str("{\"path\":");
+            write_json_string(&mut out, &file.path);
+            let _ = write!(
+                out,
+                ",\"cached\":{},\"lex_ns\":{},\"parse_ns\":{},\"tokens\":{},\"nodes\":{}}}",
+                file.cached,
+                file.lex.wall.as_nanos(),
+                file.parse.wall.as_nanos(),
+                file.lex.count,
+                file.parse.count
+            );
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.3}ms", duration.as_secs_f64() * 1000.0)
+}
+
+thread_local! {
+    static COLLECTOR: RefCell<Option<Timings>> = const { RefCell::new(None) };
+}
+
+/// 在当前线程开始收集
+pub fn start() {
+    COLLECTOR.with(|collector| *collector.borrow_mut() = Some(Timings::new()));
+}
+
+/// 结束收集，返回收集到的计时（没有开始时为 None）
+pub fn finish() -> Option<Timings> {
+    COLLECTOR.with(|collector| collector.borrow_mut().take())
+}
+
+pub fn is_enabled() -> bool {
+    COLLECTOR.with(|collector| collector.borrow().is_some())
+}
+
+/// 阶段开始的时刻，没有启用时为 None
+pub fn begin() -> Option<Instant> {
+    is_enabled().then(Instant::now)
+}
+
+/// 记录从 started 开始的阶段；没有启用时不调用 count
+pub fn record(phase: Phase, started: Option<Instant>, count: impl FnOnce() -> u64) {
+    let Some(started) = started else { return };
+    let wall = started.elapsed();
+    COLLECTOR.with(|collector| {
+        if let Some(timings) = collector.borrow_mut().as_mut() {
+            timings.add(phase, wall, count());
+        }
+    });
+}
+
+/// 在 f 执行期间记录的词法和语法分析计入文件 path
+pub fn file<T>(path: &str, f: impl FnOnce() -> T) -> T {
+    let index = COLLECTOR.with(|collector| {
+        let mut collector = collector.borrow_mut();
+        let timings = collector.as_mut()?;
+        timings.files.push(FileTimings { path: path.to_string(), ..FileTimings::default() });
+        let index = timings.files.len() - 1;
+        Some(timings.current_file.replace(index))
+    });
+    let result = f();
+    if let Some(previous) = index {
+        COLLECTOR.with(|collector| {
+            if let Some(timings) = collector.borrow_mut().as_mut() {
+                timings.current_file = previous;
+            }
+        });
+    }
+    result
+}
+
+/// 标记当前文件的 AST 来自解析缓存
+pub fn mark_cached() {
+    COLLECTOR.with(|collector| {
+        if let Some(timings) = collector.borrow_mut().as_mut() {
+            if let Some(index) = timings.current_file {
+                timings.files[index].cached = true;
+            }
+        }
+    });
+}
+
+/// 合并另一个线程收集的计时，文件排在当前线程已有的文件之后
+pub fn merge(other: Timings) {
+    COLLECTOR.with(|collector| {
+        if let Some(timings) = collector.borrow_mut().as_mut() {
+            for (stats, other) in timings.phases.iter_mut().zip(&other.phases) {
+                stats.merge(other);
+            }
+            timings.files.extend(other.files);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    use crate::compiler::Compiler;
+    use crate::i18n::Locale;
+    use crate::typechecker::{Monomorphizer, TypeChecker};
+
+    thread_local! {
+        /// 本线程创建收集器的次数
+        pub(super) static CREATED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    const SOURCE: &str = "func pick<T>(value: T) T {\n    return value\n}\nfunc main() {\n    var x = pick(1)\n    println(x * 2)\n}\n";
+
+    /// 走一遍 run 的编译流程
+    fn compile(source: &str) {
+        let mut program = file("main.q", || crate::parse_source_recovering(source, Locale::En)).0;
+        let mut checker = TypeChecker::new();
+        checker.check_program(&program).unwrap();
+        let mut type_table = checker.take_type_table();
+        let mut monomorphizer = Monomorphizer::new();
+        monomorphizer.collect_definitions(&program);
+        monomorphizer.specialize(&mut program, &mut type_table);
+        let mut compiler = Compiler::new(Locale::En);
+        compiler.set_type_table(type_table);
+        compiler.compile(&program).unwrap();
+    }
+
+    #[test]
+    fn test_disabled_does_not_create_collector() {
+        let created = CREATED.with(Cell::get);
+        compile(SOURCE);
+        assert!(!is_enabled());
+        assert!(finish().is_none());
+        assert_eq!(CREATED.with(Cell::get), created);
+    }
+
+    #[test]
+    fn test_records_phases_and_files() {
+        start();
+        compile(SOURCE);
+        let timings = finish().unwrap();
+
+        for phase in [Phase::Lex, Phase::Parse, Phase::TypeCheck, Phase::Monomorphize, Phase::Codegen] {
+            let stats = timings.phase(phase);
+            assert_eq!(stats.runs, 1, "{:?}", phase);
+            assert!(stats.count > 0, "{:?}", phase);
+        }
+        assert_eq!(timings.phase(Phase::LoadDependencies).runs, 0);
+
+        let files = timings.files();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, "main.q");
+        assert_eq!(files[0].lex, timings.phase(Phase::Lex));
+        assert_eq!(files[0].parse, timings.phase(Phase::Parse));
+
+        let json = timings.to_json();
+        assert!(json.starts_with("{\"phases\":[{\"name\":\"lex\""), "{}", json);
+        assert!(json.contains("\"files\":[{\"path\":\"main.q\",\"cached\":false"), "{}", json);
+        assert!(timings.render_table().contains("main.q"));
+    }
+
+    #[test]
+    fn test_merge_other_thread() {
+        start();
+        let other = std::thread::spawn(|| {
+            start();
+            file("a.q", || record(Phase::Lex, begin(), || 3));
+            finish().unwrap()
+        })
+        .join()
+        .unwrap();
+        file("main.q", || record(Phase::Lex, begin(), || 2));
+        merge(other);
+        let timings = finish().unwrap();
+        assert_eq!(timings.phase(Phase::Lex).count, 5);
+        let paths: Vec<&str> = timings.files().iter().map(|file| file.path.as_str()).collect();
+        assert_eq!(paths, ["main.q", "a.q"]);
+    }
+}
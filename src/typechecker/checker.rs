@@ -2,15 +2,23 @@
 //! 
 //! 对 AST 进行类型检查和推导
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use crate::parser::{Expr, Stmt, Program, BinOp, UnaryOp, AssignOp, MatchPattern};
-use crate::parser::ast::{TypeParam, WhereClause, FnParam, TypeAnnotation};
+use crate::parser::{const_items, fold_consts, ConstEvalError, ConstValue};
+use crate::parser::ast::{TypeParam, WhereClause, FnParam, TypeAnnotation, SelectCaseKind, DISCARD};
 use crate::types::{Type, TypeBound, GenericParam, Substitution};
 use crate::lexer::Span;
-use super::environment::{TypeEnvironment, TypeInfo, FunctionInfo, ClassInfo, StructInfo, FieldInfo, Visibility};
+use crate::timings::{self, Phase};
+use super::environment::{TypeEnvironment, TypeInfo, FunctionInfo, ClassInfo, StructInfo, EnumInfo, FieldInfo, Visibility};
 use super::constraint::{Constraint, ConstraintSolver};
 use super::unify::Unifier;
-use super::error::{TypeError, TypeErrorKind};
+use super::table::{GenericCall, TypeTable};
+use super::error::{similar_name, TypeError, TypeErrorKind};
+use crate::stdlib::{global_registry, ClassDecl, ParamDecl, StdlibRegistry};
+
+/// 标准库方法签名：(方法名, [(参数名, 参数类型)], 返回类型)
+type StdlibMethod<'a> = (&'a str, Vec<(&'a str, Type)>, Type);
 
 /// 编译上下文
 #[derive(Debug, Clone, Default)]
@@ -21,6 +29,8 @@ pub struct CompileContext {
     pub expected_package: Option<String>,
     /// 是否是独立文件模式（无 project.toml）
     pub standalone_mode: bool,
+    /// 隐式 any 报告为错误而不是警告（`--strict-types` 或 project.toml 中的 `[build] strict-types`）
+    pub strict_types: bool,
 }
 
 /// 类型检查器
@@ -29,14 +39,38 @@ pub struct TypeChecker {
     env: TypeEnvironment,
     /// 约束求解器
     solver: ConstraintSolver,
+    /// 泛型调用各自的求解器已求解的约束数（`--timings` 的统计）
+    generic_constraints_solved: usize,
     /// 错误列表
     errors: Vec<TypeError>,
+    /// 警告列表（不阻止运行，`--deny warnings` 时视为错误）
+    warnings: Vec<TypeError>,
     /// 是否在函数内部
     in_function: bool,
+    /// 当前函数或闭包的参数及其位置，局部变量与之同名时发出警告
+    function_params: Vec<(String, Span)>,
     /// 是否在循环内部
     in_loop: bool,
     /// 编译上下文
     context: CompileContext,
+    /// 空字面量类型变量的替换（由后续使用确定）
+    literal_types: Unifier,
+    /// 空字面量 `[]` / `{}` 的类型及位置，检查结束时仍未确定的会报错
+    pending_empty_literals: Vec<(Type, Span)>,
+    /// 编译期求值的常量（顶级 `NAME` 与类/结构体 `Type::NAME`）
+    consts: HashMap<String, ConstValue>,
+    /// 顶层语句的来源文件：(文件名, 连续的语句数)，按语句顺序排列
+    source_files: Vec<(String, usize)>,
+    /// 可以调用的宿主函数及其类型（`host.readConfig`），没有类型的不检查参数
+    host_functions: HashMap<String, Option<Type>>,
+    /// 声明标准库类的模块（嵌入的程序可以注册自己的模块）
+    registry: Arc<StdlibRegistry>,
+    /// 表达式类型和泛型调用的记录，检查结束后交给单态化和代码生成
+    table: TypeTable,
+    /// 正在检查的顶层语句的下标（表中节点的标识）
+    statement: usize,
+    /// 每个 class 中有初始化式的实例字段，`new User { ... }` 中可以省略
+    field_defaults: HashMap<String, HashSet<String>>,
 }
 
 impl TypeChecker {
@@ -45,10 +79,22 @@ impl TypeChecker {
         Self {
             env: TypeEnvironment::new(),
             solver: ConstraintSolver::new(),
+            generic_constraints_solved: 0,
             errors: Vec::new(),
+            warnings: Vec::new(),
             in_function: false,
+            function_params: Vec::new(),
             in_loop: false,
             context: CompileContext::default(),
+            literal_types: Unifier::new(),
+            pending_empty_literals: Vec::new(),
+            consts: HashMap::new(),
+            source_files: Vec::new(),
+            host_functions: HashMap::new(),
+            registry: global_registry().clone(),
+            table: TypeTable::new(),
+            statement: 0,
+            field_defaults: HashMap::new(),
         }
         // 注意：不再自动注册标准库类型，必须通过 import 显式导入
     }
@@ -58,184 +104,611 @@ impl TypeChecker {
         Self {
             env: TypeEnvironment::new(),
             solver: ConstraintSolver::new(),
+            generic_constraints_solved: 0,
             errors: Vec::new(),
+            warnings: Vec::new(),
             in_function: false,
+            function_params: Vec::new(),
             in_loop: false,
             context,
+            literal_types: Unifier::new(),
+            pending_empty_literals: Vec::new(),
+            consts: HashMap::new(),
+            source_files: Vec::new(),
+            host_functions: HashMap::new(),
+            registry: global_registry().clone(),
+            table: TypeTable::new(),
+            statement: 0,
+            field_defaults: HashMap::new(),
         }
         // 注意：不再自动注册标准库类型，必须通过 import 显式导入
     }
     
     // ==================== 按模块注册标准库类型 ====================
     
-    /// 注册 std.net.tcp 模块的所有类型
-    fn register_net_tcp_types(&mut self) {
-        self.register_tcp_socket();
-        self.register_tcp_listener();
+    /// 注册 std.net.udp 模块的所有类型
+    fn register_net_udp_types(&mut self) {
+        self.register_udp_socket();
+        self.register_udp_packet();
     }
     
-    /// 注册 std.net.http 模块的所有类型
-    fn register_net_http_types(&mut self) {
-        self.register_http_client();
-        self.register_http_server();
-        self.register_http_request();
-        self.register_http_response();
+    /// 注册 std.sync 模块的所有类型
+    fn register_sync_types(&mut self) {
+        self.register_atomic();
+        self.register_atomic_flag();
+        self.register_rate_limiter();
     }
     
-    /// 注册 std.lang 模块的所有类型（异常类）
-    fn register_lang_types(&mut self) {
-        for exc_name in &[
-            "Throwable", "Error", "Exception", 
-            "RuntimeException", "NullPointerException", "IndexOutOfBoundsException",
-            "IllegalArgumentException", "ArithmeticException", "ClassCastException",
-            "IOException", "FileNotFoundException", "NetworkException", "TimeoutException",
-        ] {
-            self.register_exception_class(exc_name);
+    /// 注册 std.fs 模块的所有函数和类型
+    fn register_fs_types(&mut self) {
+        for name in ["readFile", "writeFile", "appendFile", "exists", "remove", "mkdir", "readDir", "open", "File"] {
+            self.register_fs_item(name);
         }
     }
     
-    // ==================== 按类注册标准库类型 ====================
+    /// 注册 std.json 模块的 Json 类型
+    fn register_json_types(&mut self) {
+        self.register_stdlib_namespace(
+            "Json",
+            vec![
+                ("parse", vec![("text", Type::String), ("maxKeys", Type::Int)], 1, Type::Dynamic),
+                ("stringify", vec![("value", Type::Dynamic), ("pretty", Type::Bool)], 1, Type::String),
+            ],
+            vec![],
+        );
+    }
     
-    /// 注册 TCPSocket 类
-    fn register_tcp_socket(&mut self) {
-        self.register_stdlib_class(
-            "TCPSocket",
+    /// 注册 std.async 模块的 Future 类型（包括 `Future.all` / `Future.race`）
+    fn register_future(&mut self) {
+        let future = Type::Class("Future".to_string());
+        let futures = Type::Slice { element_type: Box::new(future.clone()) };
+        self.register_stdlib_namespace(
+            "Future",
             vec![
-                ("send", vec![("data", Type::Slice { element_type: Box::new(Type::Int) })], Type::Int),
-                ("receive", vec![("buffer", Type::Slice { element_type: Box::new(Type::Int) })], Type::Int),
-                ("close", vec![], Type::Null),
-                ("setReadTimeout", vec![("timeout_ms", Type::Int)], Type::Null),
-                ("setWriteTimeout", vec![("timeout_ms", Type::Int)], Type::Null),
-                ("setNoDelay", vec![("enabled", Type::Bool)], Type::Null),
-                ("shutdown", vec![], Type::Null),
+                ("all", vec![("futures", futures.clone())], 1, future.clone()),
+                ("race", vec![("futures", futures)], 1, future.clone()),
+            ],
+            vec![
+                ("await", vec![], 0, Type::Dynamic),
+                ("isDone", vec![], 0, Type::Bool),
+                ("then", vec![("handler", Type::Unknown)], 1, future),
             ],
-            Some(vec![
-                ("host", Type::String),
-                ("port", Type::Int),
-            ]),
         );
     }
     
-    /// 注册 TCPListener 类
-    fn register_tcp_listener(&mut self) {
-        self.register_stdlib_class(
-            "TCPListener",
+    /// 注册 std.time 模块的 Time 类型和 Instant、Duration 类
+    fn register_time_types(&mut self) {
+        self.register_future();
+        self.register_module_declarations("std.time");
+        self.register_stdlib_namespace(
+            "Time",
             vec![
-                ("accept", vec![], Type::Class("TCPSocket".to_string())),
-                ("close", vec![], Type::Null),
+                ("now", vec![], 0, Type::Int),
+                ("monotonicNanos", vec![], 0, Type::Int),
+                ("sleep", vec![("millis", Type::Int)], 1, Type::Null),
+                ("after", vec![("millis", Type::Int), ("callback", Type::Unknown)], 1, Type::Class("Future".to_string())),
+                ("formatIso", vec![("millis", Type::Int)], 1, Type::String),
+                ("formatHttp", vec![("millis", Type::Int)], 1, Type::String),
+                ("format", vec![("millis", Type::Int), ("pattern", Type::String)], 2, Type::String),
             ],
-            Some(vec![
-                ("host", Type::String),
-                ("port", Type::Int),
-            ]),
+            vec![],
         );
     }
     
-    /// 注册 HttpClient 类
-    fn register_http_client(&mut self) {
-        self.register_stdlib_class(
-            "HttpClient",
+    /// 注册 std.os 模块的 Os 类型
+    fn register_os_types(&mut self) {
+        let optional_string = || Type::Nullable(Box::new(Type::String));
+        self.register_stdlib_namespace(
+            "Os",
             vec![
-                ("get", vec![("url", Type::String)], Type::Class("HttpResponse".to_string())),
-                ("post", vec![("url", Type::String)], Type::Class("HttpResponse".to_string())),
-                ("put", vec![("url", Type::String)], Type::Class("HttpResponse".to_string())),
-                ("delete", vec![("url", Type::String)], Type::Class("HttpResponse".to_string())),
-                ("request", vec![("method", Type::String), ("url", Type::String)], Type::Class("HttpResponse".to_string())),
-                ("setTimeout", vec![("timeout_ms", Type::Int)], Type::Null),
-                ("close", vec![], Type::Null),
+                ("args", vec![], 0, Type::Slice { element_type: Box::new(Type::String) }),
+                ("env", vec![("name", Type::String)], 1, optional_string()),
+                ("setEnv", vec![("name", Type::String), ("value", optional_string())], 2, Type::Null),
+                ("envAll", vec![], 0, Type::Map { key_type: Box::new(Type::String), value_type: Box::new(Type::String) }),
+                ("cwd", vec![], 0, Type::String),
+                ("chdir", vec![("path", Type::String)], 1, Type::Null),
+                ("platform", vec![], 0, Type::String),
+                ("hostname", vec![], 0, Type::String),
+                ("exit", vec![("code", Type::Int)], 0, Type::Null),
             ],
-            None,
+            vec![],
         );
     }
     
-    /// 注册 HttpServer 类
-    fn register_http_server(&mut self) {
-        self.register_stdlib_class(
-            "HttpServer",
+    /// 注册 std.log 模块的 Log 类型
+    fn register_log_types(&mut self) {
+        let record = |level| (level, vec![("message", Type::String)], 1, Type::Null);
+        self.register_stdlib_namespace(
+            "Log",
             vec![
-                ("listen", vec![("handler", Type::Unknown)], Type::Null),
-                ("stop", vec![], Type::Null),
+                record("debug"),
+                record("info"),
+                record("warn"),
+                record("error"),
+                ("setOutput", vec![("path", Type::Nullable(Box::new(Type::String)))], 0, Type::Null),
             ],
-            Some(vec![
-                ("host", Type::String),
-                ("port", Type::Int),
-            ]),
+            vec![],
         );
     }
     
-    /// 注册 HttpRequest 类
-    fn register_http_request(&mut self) {
-        self.register_stdlib_class_with_fields(
-            "HttpRequest",
+    /// 注册 std.uuid 模块的 Uuid 类型
+    fn register_uuid_types(&mut self) {
+        self.register_stdlib_namespace("Uuid", vec![("v4", vec![], 0, Type::String)], vec![]);
+    }
+    
+    /// 注册 std.runtime 模块的 Runtime 类型和 WeakRef、WeakMap 类
+    fn register_runtime_types(&mut self) {
+        self.register_module_declarations("std.runtime");
+        self.register_stdlib_namespace(
+            "Runtime",
             vec![
-                ("getHeader", vec![("name", Type::String)], Type::String),
-                ("getQuery", vec![("name", Type::String)], Type::String),
+                ("gc", vec![], 0, Type::Null),
+                ("memoryStats", vec![], 0, Type::Map { key_type: Box::new(Type::String), value_type: Box::new(Type::Int) }),
+                ("setGcThreshold", vec![("bytes", Type::Int)], 1, Type::Null),
             ],
-            None,
+            vec![],
+        );
+    }
+    
+    /// 注册 std.convert 模块的 Convert 类型
+    fn register_convert_types(&mut self) {
+        let convert = |name, return_type| (name, vec![("text", Type::String)], 1, return_type);
+        self.register_stdlib_namespace(
+            "Convert",
             vec![
-                ("method", Type::String),
-                ("path", Type::String),
-                ("body", Type::String),
-                ("headers", Type::Map { key_type: Box::new(Type::String), value_type: Box::new(Type::String) }),
+                convert("toInt", Type::Int),
+                convert("toFloat", Type::F64),
+                convert("toBool", Type::Bool),
             ],
+            vec![],
         );
     }
     
-    /// 注册 HttpResponse 类
-    fn register_http_response(&mut self) {
+    /// 注册 std.encoding 模块的 Encoding 类型
+    ///
+    /// 编码函数的 `data` 是字符串或 int 数组，用 Unknown 表示
+    fn register_encoding_types(&mut self) {
+        let bytes = || Type::Slice { element_type: Box::new(Type::Int) };
+        let text = || ("text", Type::String);
+        let url_safe = || ("urlSafe", Type::Bool);
+        self.register_stdlib_namespace(
+            "Encoding",
+            vec![
+                ("base64Encode", vec![("data", Type::Unknown), url_safe()], 1, Type::String),
+                ("base64Decode", vec![text(), url_safe()], 1, Type::String),
+                ("base64DecodeBytes", vec![text(), url_safe()], 1, bytes()),
+                ("hexEncode", vec![("data", Type::Unknown)], 1, Type::String),
+                ("hexDecode", vec![text()], 1, Type::String),
+                ("hexDecodeBytes", vec![text()], 1, bytes()),
+                ("urlEncode", vec![("data", Type::Unknown)], 1, Type::String),
+                ("urlDecode", vec![text()], 1, Type::String),
+            ],
+            vec![],
+        );
+    }
+    
+    /// 注册 std.math 模块的 Math 类型和常量
+    ///
+    /// 取整和 abs / min / max 的结果类型随参数变化，由 [`Self::infer_math_call`] 推导
+    fn register_math_types(&mut self) {
+        let x = || vec![("x", Type::F64)];
+        let unary = |name| (name, x(), 1, Type::F64);
+        let binary = |name, a, b| (name, vec![(a, Type::F64), (b, Type::F64)], 2, Type::F64);
+        let mut info = Self::stdlib_namespace_info(
+            "Math",
+            vec![
+                unary("abs"),
+                binary("min", "a", "b"),
+                binary("max", "a", "b"),
+                unary("floor"),
+                unary("ceil"),
+                ("round", vec![("x", Type::F64), ("digits", Type::Int)], 1, Type::F64),
+                unary("trunc"),
+                unary("sqrt"),
+                unary("cbrt"),
+                binary("pow", "base", "exponent"),
+                binary("hypot", "x", "y"),
+                unary("exp"),
+                unary("log"),
+                unary("log2"),
+                unary("log10"),
+                unary("sin"),
+                unary("cos"),
+                unary("tan"),
+                unary("asin"),
+                unary("acos"),
+                unary("atan"),
+                binary("atan2", "y", "x"),
+                ("isNaN", x(), 1, Type::Bool),
+                ("isFinite", x(), 1, Type::Bool),
+            ],
+            vec![],
+        );
+        for constant in ["PI", "E", "INFINITY", "NAN"] {
+            let field = FieldInfo {
+                name: constant.to_string(),
+                ty: Type::F64,
+                is_mutable: false,
+                visibility: Visibility::Public,
+            };
+            info.fields.insert(constant.to_string(), field.clone());
+            info.static_fields.insert(constant.to_string(), field);
+        }
+        self.register_namespace_info(info);
+    }
+    
+    /// 注册 std.random 模块的 Random 类型
+    ///
+    /// 同一组方法既是使用共享生成器的静态方法，也是 `new Random(seed)` 实例的方法
+    fn register_random_types(&mut self) {
+        let methods = || vec![
+            ("random", vec![], 0, Type::F64),
+            ("randInt", vec![("lo", Type::Int), ("hi", Type::Int)], 2, Type::Int),
+            ("shuffle", vec![("array", Type::Unknown)], 1, Type::Null),
+            ("choice", vec![("array", Type::Unknown)], 1, Type::Dynamic),
+        ];
+        let mut info = Self::stdlib_namespace_info("Random", methods(), vec![]);
+        let init = Self::stdlib_class_info(
+            "Random",
+            vec![],
+            Some(vec![("seed", Type::Nullable(Box::new(Type::Int)))]),
+            vec![],
+        );
+        info.methods.extend(init.methods);
+        self.register_namespace_info(info);
+    }
+    
+    /// 注册 std.process 模块的 Process 类型和 ProcessResult 类
+    ///
+    /// `run` / `runShell` / `spawn` 是静态方法，其余是 `spawn` 返回的实例的方法
+    fn register_process_types(&mut self) {
+        self.register_module_declarations("std.process");
+        let args = || ("args", Type::Nullable(Box::new(Type::Slice { element_type: Box::new(Type::String) })));
+        let result = || Type::Class("ProcessResult".to_string());
+        let mut info = Self::stdlib_namespace_info(
+            "Process",
+            vec![
+                ("run", vec![("command", Type::String), args()], 1, result()),
+                ("runShell", vec![("command", Type::String)], 1, result()),
+                ("spawn", vec![("command", Type::String), args()], 1, Type::Class("Process".to_string())),
+            ],
+            vec![
+                ("writeStdin", vec![("text", Type::String)], 1, Type::Null),
+                ("closeStdin", vec![], 0, Type::Null),
+                ("readStdout", vec![], 0, Type::Nullable(Box::new(Type::String))),
+                ("wait", vec![], 0, Type::Int),
+                ("kill", vec![], 0, Type::Null),
+            ],
+        );
+        let pid = FieldInfo {
+            name: "pid".to_string(),
+            ty: Type::Int,
+            is_mutable: false,
+            visibility: Visibility::Public,
+        };
+        info.fields.insert("pid".to_string(), pid);
+        self.register_namespace_info(info);
+    }
+    
+    /// 注册内置数值类型的静态方法（`Int.parse` / `Float.parse`），不需要 import；转换失败时抛出异常
+    fn register_builtin_namespaces(&mut self) {
+        self.register_stdlib_namespace(
+            "Int",
+            vec![("parse", vec![("text", Type::String), ("radix", Type::Int)], 1, Type::Int)],
+            vec![],
+        );
+        self.register_stdlib_namespace("Float", vec![("parse", vec![("text", Type::String)], 1, Type::F64)], vec![]);
+    }
+    
+    /// 注册 std.net.dns 模块的 Dns 类型
+    fn register_dns_types(&mut self) {
+        self.register_future();
+        self.register_stdlib_namespace(
+            "Dns",
+            vec![
+                ("resolve", vec![("host", Type::String)], 1, Type::Slice { element_type: Box::new(Type::String) }),
+                ("resolveAsync", vec![("host", Type::String)], 1, Type::Class("Future".to_string())),
+            ],
+            vec![],
+        );
+    }
+    
+    /// 注册命名空间式的标准库类型（如 Json、Time）
+    ///
+    /// 静态方法既可以 `Json::parse(text)` 调用，也可以 `Json.parse(text)` 调用，
+    /// 后者通过注册一个同名常量实现。方法元组为 (名称, 参数, 必需参数数量, 返回类型)
+    #[allow(clippy::type_complexity)]
+    fn register_stdlib_namespace(
+        &mut self,
+        name: &str,
+        static_methods: Vec<(&str, Vec<(&str, Type)>, usize, Type)>,
+        instance_methods: Vec<(&str, Vec<(&str, Type)>, usize, Type)>,
+    ) {
+        let class_info = Self::stdlib_namespace_info(name, static_methods, instance_methods);
+        self.register_namespace_info(class_info);
+    }
+    
+    /// 标准库命名空间的类信息，见 [`Self::register_stdlib_namespace`]
+    #[allow(clippy::type_complexity)]
+    fn stdlib_namespace_info(
+        name: &str,
+        static_methods: Vec<(&str, Vec<(&str, Type)>, usize, Type)>,
+        instance_methods: Vec<(&str, Vec<(&str, Type)>, usize, Type)>,
+    ) -> ClassInfo {
+        let method = |(method_name, params, required_params, return_type): (&str, Vec<(&str, Type)>, usize, Type)| {
+            (method_name.to_string(), FunctionInfo {
+                name: method_name.to_string(),
+                type_params: vec![],
+                param_names: params.iter().map(|(n, _)| n.to_string()).collect(),
+                param_spans: Vec::new(),
+                param_types: params.into_iter().map(|(_, t)| t).collect(),
+                required_params,
+                return_type,
+                is_method: true,
+                owner_type: Some(name.to_string()),
+            })
+        };
+        let static_methods: HashMap<String, FunctionInfo> = static_methods.into_iter().map(method).collect();
+        let mut methods = static_methods.clone();
+        methods.extend(instance_methods.into_iter().map(method));
+        
+        ClassInfo {
+            name: name.to_string(),
+            type_params: vec![],
+            parent: None,
+            interfaces: vec![],
+            traits: vec![],
+            fields: HashMap::new(),
+            methods,
+            static_fields: HashMap::new(),
+            static_methods,
+            is_abstract: false,
+            final_methods: HashMap::new(),
+            abstract_methods: HashMap::new(),
+            is_native: true,
+        }
+    }
+    
+    /// 注册命名空间类型和同名常量
+    fn register_namespace_info(&mut self, class_info: ClassInfo) {
+        let name = class_info.name.clone();
+        // 重复导入时忽略
+        let _ = self.env.register_type(name.clone(), TypeInfo::Class(class_info));
+        let _ = self.env.define_variable(name.clone(), Type::Class(name), true);
+    }
+    
+    /// 注册 std.fs 模块的单个函数或类型
+    fn register_fs_item(&mut self, name: &str) {
+        let string_slice = Type::Slice { element_type: Box::new(Type::String) };
+        match name {
+            "readFile" => self.register_stdlib_function(name, vec![("path", Type::String)], Type::String),
+            "writeFile" | "appendFile" => self.register_stdlib_function(
+                name,
+                vec![("path", Type::String), ("content", Type::String)],
+                Type::Null,
+            ),
+            "exists" => self.register_stdlib_function(name, vec![("path", Type::String)], Type::Bool),
+            "remove" | "mkdir" => self.register_stdlib_function(name, vec![("path", Type::String)], Type::Null),
+            "readDir" => self.register_stdlib_function(name, vec![("path", Type::String)], string_slice),
+            "open" => {
+                self.register_file();
+                self.register_stdlib_function(
+                    name,
+                    vec![("path", Type::String), ("mode", Type::String)],
+                    Type::Class("File".to_string()),
+                );
+            }
+            "File" => self.register_file(),
+            _ => {}
+        }
+    }
+    
+    /// 注册标准库函数
+    fn register_stdlib_function(&mut self, name: &str, params: Vec<(&str, Type)>, return_type: Type) {
+        let param_names: Vec<String> = params.iter().map(|(n, _)| n.to_string()).collect();
+        let param_types: Vec<Type> = params.into_iter().map(|(_, t)| t).collect();
+        let required_params = param_types.len();
+        // 重复导入时忽略
+        let _ = self.env.register_function(name.to_string(), FunctionInfo {
+            name: name.to_string(),
+            type_params: vec![],
+            param_types,
+            param_names,
+            param_spans: Vec::new(),
+            required_params,
+            return_type,
+            is_method: false,
+            owner_type: None,
+        });
+    }
+    
+    // ==================== 按类注册标准库类型 ====================
+    
+    /// 注册 File 类
+    fn register_file(&mut self) {
         self.register_stdlib_class_with_fields(
-            "HttpResponse",
+            "File",
             vec![
-                ("text", vec![], Type::String),
-                ("setHeader", vec![("name", Type::String), ("value", Type::String)], Type::Null),
+                ("read", vec![], Type::String),
+                ("write", vec![("content", Type::String)], Type::Int),
+                ("close", vec![], Type::Null),
             ],
             Some(vec![
-                ("status", Type::Int),
-                ("body", Type::String),
+                ("path", Type::String),
+                ("mode", Type::String),
             ]),
+            vec![("path", Type::String)],
+        );
+    }
+    
+    /// 注册 UDPSocket 类
+    fn register_udp_socket(&mut self) {
+        self.register_udp_packet();
+        let bytes = Type::Slice { element_type: Box::new(Type::Int) };
+        self.register_stdlib_class(
+            "UDPSocket",
             vec![
-                ("status", Type::Int),
-                ("body", Type::String),
-                ("headers", Type::Map { key_type: Box::new(Type::String), value_type: Box::new(Type::String) }),
+                ("sendTo", vec![("data", bytes), ("address", Type::String)], Type::Int),
+                ("receiveFrom", vec![("maxLen", Type::Int)], Type::Class("UDPPacket".to_string())),
+                ("setReadTimeout", vec![("timeout_ms", Type::Int)], Type::Null),
+                ("localAddress", vec![], Type::String),
+                ("close", vec![], Type::Null),
             ],
+            Some(vec![("address", Type::String)]),
         );
     }
     
-    /// 注册异常类
-    fn register_exception_class(&mut self, name: &str) {
+    /// 注册 UDPPacket 类（receiveFrom 的返回值）
+    fn register_udp_packet(&mut self) {
         self.register_stdlib_class_with_fields(
-            name,
+            "UDPPacket",
+            vec![],
+            None,
+            vec![
+                ("data", Type::Slice { element_type: Box::new(Type::Int) }),
+                ("address", Type::String),
+            ],
+        );
+    }
+    
+    /// 注册 Atomic 类
+    fn register_atomic(&mut self) {
+        self.register_stdlib_class(
+            "Atomic",
+            vec![
+                ("get", vec![], Type::Int),
+                ("set", vec![("value", Type::Int)], Type::Null),
+                ("add", vec![("delta", Type::Int)], Type::Int),
+                ("compareAndSwap", vec![("expected", Type::Int), ("new", Type::Int)], Type::Bool),
+                ("swap", vec![("value", Type::Int)], Type::Int),
+            ],
+            Some(vec![("initial", Type::Nullable(Box::new(Type::Int)))]),
+        );
+    }
+    
+    /// 注册 AtomicFlag 类
+    fn register_atomic_flag(&mut self) {
+        self.register_stdlib_class(
+            "AtomicFlag",
+            vec![
+                ("testAndSet", vec![], Type::Bool),
+                ("clear", vec![], Type::Null),
+            ],
+            Some(vec![]),
+        );
+    }
+    
+    /// 注册 RateLimiter 类
+    fn register_rate_limiter(&mut self) {
+        self.register_stdlib_class(
+            "RateLimiter",
             vec![
-                ("toString", vec![], Type::String),
-                ("getMessage", vec![], Type::String),
+                ("tryAcquire", vec![], Type::Bool),
+                ("available", vec![], Type::Int),
             ],
-            Some(vec![("message", Type::String)]),
-            vec![("message", Type::String)],
+            Some(vec![
+                ("capacity", Type::Int),
+                ("refillPerSecond", Type::Int),
+            ]),
         );
     }
     
     /// 根据类名注册单个标准库类型
     fn register_stdlib_type_by_name(&mut self, name: &str) {
+        if self.register_declared_class(name) {
+            return;
+        }
         match name {
-            // std.net.tcp
-            "TCPSocket" => self.register_tcp_socket(),
-            "TCPListener" => self.register_tcp_listener(),
-            // std.net.http
-            "HttpClient" => self.register_http_client(),
-            "HttpServer" => self.register_http_server(),
-            "HttpRequest" => self.register_http_request(),
-            "HttpResponse" => self.register_http_response(),
-            // std.lang - 异常类
-            "Throwable" | "Error" | "Exception" | 
-            "RuntimeException" | "NullPointerException" | "IndexOutOfBoundsException" |
-            "IllegalArgumentException" | "ArithmeticException" | "ClassCastException" |
-            "IOException" | "FileNotFoundException" | "NetworkException" | "TimeoutException" => {
-                self.register_exception_class(name);
-            }
+            // std.net.udp
+            "UDPSocket" => self.register_udp_socket(),
+            "UDPPacket" => self.register_udp_packet(),
+            // std.net.dns
+            "Dns" => self.register_dns_types(),
+            // std.json
+            "Json" => self.register_json_types(),
+            // std.async
+            "Future" => self.register_future(),
+            // std.time
+            "Time" => self.register_time_types(),
+            // std.os
+            "Os" => self.register_os_types(),
+            // std.log
+            "Log" => self.register_log_types(),
+            // std.uuid
+            "Uuid" => self.register_uuid_types(),
+            // std.runtime
+            "Runtime" => self.register_runtime_types(),
+            // std.convert
+            "Convert" => self.register_convert_types(),
+            // std.encoding
+            "Encoding" => self.register_encoding_types(),
+            // std.math
+            "Math" => self.register_math_types(),
+            // std.random
+            "Random" => self.register_random_types(),
+            // std.process
+            "Process" => self.register_process_types(),
+            // std.sync
+            "Atomic" => self.register_atomic(),
+            "AtomicFlag" => self.register_atomic_flag(),
+            "RateLimiter" => self.register_rate_limiter(),
             _ => {} // 未知类型，忽略
         }
     }
     
+    /// 注册模块通过 `type_declarations` 声明的全部类
+    fn register_module_declarations(&mut self, module: &str) {
+        if let Some(module) = self.registry.get(module) {
+            for decl in module.type_declarations() {
+                self.register_class_decl(&decl);
+            }
+        }
+    }
+    
+    /// 注册标准库模块声明的类，没有模块声明该类时返回 false
+    fn register_declared_class(&mut self, name: &str) -> bool {
+        let registry = self.registry.clone();
+        let decl = registry
+            .resolve_class_name(name)
+            .and_then(|full_name| registry.find_class_module(&full_name))
+            .and_then(|(_, module)| module.type_declarations().into_iter().find(|decl| decl.name == name));
+        match decl {
+            Some(decl) => {
+                self.register_class_decl(&decl);
+                true
+            }
+            None => false,
+        }
+    }
+    
+    /// 按声明注册标准库类，连同签名中引用到的其他标准库类型和父类
+    fn register_class_decl(&mut self, decl: &ClassDecl) {
+        if self.env.lookup_type(decl.name).is_some() {
+            return;
+        }
+        let params = |params: &[ParamDecl]| params.iter().map(|p| (p.name, p.ty.clone())).collect::<Vec<_>>();
+        let mut info = Self::stdlib_class_info(
+            decl.name,
+            decl.methods.iter().map(|m| (m.name, params(&m.params), m.return_type.clone())).collect(),
+            decl.constructor.as_deref().map(params),
+            params(&decl.fields),
+        );
+        info.parent = decl.parent.map(str::to_string);
+        let _ = self.env.register_type(decl.name.to_string(), TypeInfo::Class(info));
+        
+        let mut referenced: Vec<String> = decl.parent.iter().map(|p| p.to_string()).collect();
+        let signatures = decl.methods.iter().flat_map(|m| m.params.iter().map(|p| &p.ty).chain([&m.return_type]));
+        let constructor = decl.constructor.iter().flatten().map(|p| &p.ty);
+        for ty in signatures.chain(constructor).chain(decl.fields.iter().map(|f| &f.ty)) {
+            collect_class_names(ty, &mut referenced);
+        }
+        for name in referenced {
+            if self.env.lookup_type(&name).is_none() {
+                self.register_stdlib_type_by_name(&name);
+            }
+        }
+    }
+    
     /// 处理 import 声明，注册相应的类型
     fn process_import(&mut self, path: &str, target: &crate::parser::ast::ImportTarget) {
         use crate::parser::ast::ImportTarget;
@@ -244,16 +717,52 @@ impl TypeChecker {
             ImportTarget::All => {
                 // import std.net.http.* - 注册模块所有类型
                 match path {
-                    "std.net.tcp" => self.register_net_tcp_types(),
-                    "std.net.http" => self.register_net_http_types(),
-                    "std.lang" => self.register_lang_types(),
-                    _ => {}
+                    "std.net.tcp" | "std.net.http" | "std.lang" => self.register_module_declarations(path),
+                    "std.net.udp" => self.register_net_udp_types(),
+                    "std.fs" => self.register_fs_types(),
+                    "std.json" => self.register_json_types(),
+                    "std.sync" => self.register_sync_types(),
+                    "std.async" => self.register_future(),
+                    "std.time" => self.register_time_types(),
+                    "std.os" => self.register_os_types(),
+                    "std.log" => self.register_log_types(),
+                    "std.uuid" => self.register_uuid_types(),
+                    "std.runtime" => self.register_runtime_types(),
+                    "std.convert" => self.register_convert_types(),
+                    "std.encoding" => self.register_encoding_types(),
+                    "std.math" => self.register_math_types(),
+                    "std.random" => self.register_random_types(),
+                    "std.process" => self.register_process_types(),
+                    "std.net.dns" => self.register_dns_types(),
+                    // 宿主程序注册的模块
+                    _ => self.register_module_declarations(path),
                 }
             }
+            ImportTarget::Single(name) if path == "std" && name == "fs" => self.register_fs_types(),
+            ImportTarget::Single(name) if path == "std.fs" => self.register_fs_item(name),
+            ImportTarget::Single(name) if path == "std" && name == "json" => self.register_json_types(),
+            ImportTarget::Single(name) if path == "std" && name == "sync" => self.register_sync_types(),
+            ImportTarget::Single(name) if path == "std" && name == "async" => self.register_future(),
+            ImportTarget::Single(name) if path == "std" && name == "time" => self.register_time_types(),
+            ImportTarget::Single(name) if path == "std" && name == "os" => self.register_os_types(),
+            ImportTarget::Single(name) if path == "std" && name == "log" => self.register_log_types(),
+            ImportTarget::Single(name) if path == "std" && name == "uuid" => self.register_uuid_types(),
+            ImportTarget::Single(name) if path == "std" && name == "runtime" => self.register_runtime_types(),
+            ImportTarget::Single(name) if path == "std" && name == "convert" => self.register_convert_types(),
+            ImportTarget::Single(name) if path == "std" && name == "math" => self.register_math_types(),
+            ImportTarget::Single(name) if path == "std" && name == "random" => self.register_random_types(),
+            ImportTarget::Single(name) if path == "std" && name == "process" => self.register_process_types(),
+            ImportTarget::Single(name) if path == "std" && name == "regex" => self.register_module_declarations("std.regex"),
+            ImportTarget::Single(name) if path == "std" && name == "encoding" => self.register_encoding_types(),
             ImportTarget::Single(name) => {
                 // import std.net.http.HttpServer - 只注册单个类型
                 self.register_stdlib_type_by_name(name);
             }
+            ImportTarget::Multiple(names) if path == "std.fs" => {
+                for name in names {
+                    self.register_fs_item(name);
+                }
+            }
             ImportTarget::Multiple(names) => {
                 // import std.net.http.{HttpClient, HttpServer} - 注册多个类型
                 for name in names {
@@ -267,7 +776,7 @@ impl TypeChecker {
     fn register_stdlib_class(
         &mut self,
         name: &str,
-        methods: Vec<(&str, Vec<(&str, Type)>, Type)>,
+        methods: Vec<StdlibMethod>,
         init_params: Option<Vec<(&str, Type)>>,
     ) {
         self.register_stdlib_class_with_fields(name, methods, init_params, vec![]);
@@ -277,10 +786,22 @@ impl TypeChecker {
     fn register_stdlib_class_with_fields(
         &mut self,
         name: &str,
-        methods: Vec<(&str, Vec<(&str, Type)>, Type)>,
+        methods: Vec<StdlibMethod>,
         init_params: Option<Vec<(&str, Type)>>,
         fields: Vec<(&str, Type)>,
     ) {
+        let class_info = Self::stdlib_class_info(name, methods, init_params, fields);
+        // 忽略注册错误（可能已存在）
+        let _ = self.env.register_type(name.to_string(), TypeInfo::Class(class_info));
+    }
+    
+    /// 由方法、构造函数和字段签名构造标准库类的类型信息
+    fn stdlib_class_info(
+        name: &str,
+        methods: Vec<StdlibMethod>,
+        init_params: Option<Vec<(&str, Type)>>,
+        fields: Vec<(&str, Type)>,
+    ) -> ClassInfo {
         let mut method_map = HashMap::new();
         let mut field_map = HashMap::new();
         
@@ -298,13 +819,14 @@ impl TypeChecker {
         if let Some(params) = init_params {
             let param_names: Vec<String> = params.iter().map(|(n, _)| n.to_string()).collect();
             let param_types: Vec<Type> = params.iter().map(|(_, t)| t.clone()).collect();
-            let required = param_types.len();
+            let required = Self::required_stdlib_params(&param_types);
             
             method_map.insert("init".to_string(), FunctionInfo {
                 name: "init".to_string(),
                 type_params: vec![],
                 param_types,
                 param_names,
+                param_spans: Vec::new(),
                 required_params: required,
                 return_type: Type::Class(name.to_string()),
                 is_method: true,
@@ -316,13 +838,14 @@ impl TypeChecker {
         for (method_name, params, return_type) in methods {
             let param_names: Vec<String> = params.iter().map(|(n, _)| n.to_string()).collect();
             let param_types: Vec<Type> = params.iter().map(|(_, t)| t.clone()).collect();
-            let required = param_types.len();
+            let required = Self::required_stdlib_params(&param_types);
             
             method_map.insert(method_name.to_string(), FunctionInfo {
                 name: method_name.to_string(),
                 type_params: vec![],
                 param_types,
                 param_names,
+                param_spans: Vec::new(),
                 required_params: required,
                 return_type,
                 is_method: true,
@@ -330,7 +853,7 @@ impl TypeChecker {
             });
         }
         
-        let class_info = ClassInfo {
+        ClassInfo {
             name: name.to_string(),
             type_params: vec![],
             parent: None,
@@ -341,10 +864,257 @@ impl TypeChecker {
             static_fields: HashMap::new(),
             static_methods: HashMap::new(),
             is_abstract: false,
+            final_methods: HashMap::new(),
+            abstract_methods: HashMap::new(),
+            is_native: true,
+        }
+    }
+    
+    /// 标准库方法的必需参数数量：末尾的可空参数可以省略
+    fn required_stdlib_params(param_types: &[Type]) -> usize {
+        let optional = param_types.iter().rev().take_while(|t| matches!(t, Type::Nullable(_))).count();
+        param_types.len() - optional
+    }
+    
+    /// 取出检查过程中记录的类型表（在 [`check_program`](Self::check_program) 之后调用）
+    pub fn take_type_table(&mut self) -> TypeTable {
+        std::mem::take(&mut self.table)
+    }
+    
+    /// 检查过程中产生的警告
+    pub fn warnings(&self) -> &[TypeError] {
+        &self.warnings
+    }
+    
+    /// 设置顶层语句的来源文件，错误报告中注明出错的文件
+    pub fn set_source_files(&mut self, files: Vec<(String, usize)>) {
+        self.source_files = files;
+    }
+    
+    /// 设置声明标准库类的注册表（默认为只有内置模块的全局注册表）
+    pub fn set_registry(&mut self, registry: Arc<StdlibRegistry>) {
+        self.registry = registry;
+    }
+    
+    /// 设置可以调用的宿主函数及其类型
+    pub fn set_host_functions(&mut self, functions: HashMap<String, Option<Type>>) {
+        self.host_functions = functions;
+    }
+    
+    /// 宿主函数调用 `host.name(args)`：同名的变量、函数和类型优先
+    fn infer_host_call(&mut self, callee: &Expr, args: &[(Option<String>, Expr)], span: Span) -> Option<Result<Type, TypeError>> {
+        let Expr::Member { object, member, .. } = callee else {
+            return None;
+        };
+        let Expr::Identifier { name, .. } = object.as_ref() else {
+            return None;
+        };
+        if name != crate::vm::HOST_NAMESPACE
+            || self.env.lookup_variable(name).is_some()
+            || self.env.lookup_function(name).is_some()
+            || self.env.lookup_type(name).is_some()
+        {
+            return None;
+        }
+        let full_name = format!("{}.{}", name, member);
+        let Some(signature) = self.host_functions.get(&full_name).cloned() else {
+            return Some(Err(TypeError::new(
+                TypeErrorKind::Other(format!("unknown host function '{}'", full_name)),
+                span,
+            )));
+        };
+        let arg_exprs: Vec<&Expr> = args.iter().map(|(_, e)| e).collect();
+        if let Some(signature) = signature {
+            return Some(self.infer_call(&signature, &arg_exprs, None, span));
+        }
+        for arg in arg_exprs {
+            if let Err(e) = self.infer_expr(arg) {
+                return Some(Err(e));
+            }
+        }
+        Some(Ok(Type::Dynamic))
+    }
+    
+    /// struct 字面量 `Point { x: 1 }`（也用于 `new Point { x: 1 }`）：字段必须存在，值与字段类型兼容
+    fn infer_struct_literal(&mut self, name: &str, fields: &[(String, Expr)], span: Span) -> Result<Type, TypeError> {
+        // 先克隆 struct 信息以避免借用冲突
+        let struct_fields = if let Some(TypeInfo::Struct(info)) = self.env.lookup_type(name) {
+            info.fields.clone()
+        } else {
+            return Err(TypeError::undefined_type(name.to_string(), span));
         };
         
-        // 忽略注册错误（可能已存在）
-        let _ = self.env.register_type(name.to_string(), TypeInfo::Class(class_info));
+        // 检查字段
+        for (field_name, field_expr) in fields {
+            if let Some(field_info) = struct_fields.get(field_name) {
+                let expr_ty = self.infer_expr(field_expr)?;
+                if !self.check_assignable(&expr_ty, &field_info.ty, field_expr.span()) {
+                    return Err(self.mismatch_error(&field_info.ty, &expr_ty, field_expr.span()));
+                }
+            } else {
+                return Err(TypeError::new(
+                    TypeErrorKind::UndefinedField {
+                        type_name: name.to_string(),
+                        field_name: field_name.clone(),
+                    },
+                    span,
+                ));
+            }
+        }
+        Ok(Type::Struct(name.to_string()))
+    }
+    
+    /// `new User { name: "a" }`：先执行无参的 init（字段初始化式和 init 方法体），再按花括号赋值
+    ///
+    /// 字段必须在类或父类中声明，值与字段类型兼容；init 有必需参数时不能使用这种形式。
+    /// 没有赋值的字段若没有初始化式、不可为 null，且所在的类没有声明 init（init 可能给它赋值），报告缺少字段
+    fn infer_new_with_fields(&mut self, class_name: &str, fields: &[(String, Expr)], span: Span) -> Result<Type, TypeError> {
+        let info = match self.env.lookup_type(class_name) {
+            Some(TypeInfo::Struct(_)) => return self.infer_struct_literal(class_name, fields, span),
+            Some(TypeInfo::Class(info)) => info.clone(),
+            _ => return Err(TypeError::undefined_type(class_name.to_string(), span)),
+        };
+        let other = |message: String| Err(TypeError::new(TypeErrorKind::Other(message), span));
+        if info.is_abstract {
+            return Err(TypeError::new(TypeErrorKind::CannotInstantiateAbstract(class_name.to_string()), span));
+        }
+        if self.env.class_chain(class_name).any(|c| c.is_native) {
+            return other(format!("standard library class '{}' cannot be created with field initializers", class_name));
+        }
+        if let Some(init) = self.env.class_chain(class_name).find_map(|c| c.methods.get("init")) {
+            if init.required_params > 0 {
+                return other(format!(
+                    "'{}' has an init with required parameters; create it with new {}(...) instead",
+                    class_name, class_name
+                ));
+            }
+        }
+        
+        let class_type = Type::Class(class_name.to_string());
+        let mut assigned = HashSet::new();
+        for (field_name, value) in fields {
+            if !assigned.insert(field_name.as_str()) {
+                return other(format!("field '{}' is initialized more than once", field_name));
+            }
+            let Some(field_ty) = self.env.get_field(&class_type, field_name).map(|f| f.ty.clone()) else {
+                return Err(TypeError::new(
+                    TypeErrorKind::UndefinedField { type_name: class_name.to_string(), field_name: field_name.clone() },
+                    value.span(),
+                ));
+            };
+            let value_ty = self.infer_expr_expecting(value, &field_ty)?;
+            if !self.check_assignable(&value_ty, &field_ty, value.span()) {
+                return Err(self.mismatch_error(&field_ty, &value_ty, value.span()));
+            }
+        }
+        
+        let mut missing: Vec<&str> = Vec::new();
+        let chain: Vec<&ClassInfo> = self.env.class_chain(class_name).collect();
+        for class in chain.iter().rev().filter(|c| !c.methods.contains_key("init")) {
+            let defaults = self.field_defaults.get(&class.name);
+            let mut required: Vec<&str> = class.fields.values()
+                .filter(|f| !matches!(f.ty, Type::Nullable(_) | Type::Unknown | Type::Dynamic))
+                .map(|f| f.name.as_str())
+                .filter(|name| !assigned.contains(name) && !defaults.is_some_and(|d| d.contains(*name)))
+                .collect();
+            required.sort_unstable();
+            missing.extend(required);
+        }
+        if !missing.is_empty() {
+            let list = missing.iter().map(|name| format!("'{}'", name)).collect::<Vec<_>>().join(", ");
+            return other(format!("missing field{} {} in new {} {{ ... }}", if missing.len() > 1 { "s" } else { "" }, list, class_name));
+        }
+        Ok(class_type)
+    }
+    
+    /// `Math.abs` / `min` / `max` / `floor` / `ceil` / `round` / `trunc`：参数都是整数时结果是 int，否则是 f64
+    ///
+    /// round 只看第一个参数；有 dynamic 参数时结果也是 dynamic
+    fn infer_math_call(&mut self, callee: &Expr, args: &[(Option<String>, Expr)], span: Span) -> Option<Result<Type, TypeError>> {
+        let (namespace, member) = match callee {
+            Expr::Member { object, member, .. } => match object.as_ref() {
+                Expr::Identifier { name, .. } => (name, member),
+                _ => return None,
+            },
+            Expr::StaticMember { class_name, member, .. } => (class_name, member),
+            _ => return None,
+        };
+        let arity = match member.as_str() {
+            "abs" | "floor" | "ceil" | "trunc" => 1..=1,
+            "min" | "max" => 2..=2,
+            "round" => 1..=2,
+            _ => return None,
+        };
+        let is_namespace = matches!(self.env.lookup_type(namespace), Some(TypeInfo::Class(info)) if info.is_native)
+            && self.env.variable_type(namespace) == Some(Type::Class(namespace.clone()));
+        if namespace != "Math" || !is_namespace || args.iter().any(|(name, _)| name.is_some()) {
+            return None;
+        }
+        if !arity.contains(&args.len()) {
+            return Some(Err(if arity.start() == arity.end() {
+                TypeError::argument_count_mismatch(*arity.start(), args.len(), span)
+            } else {
+                TypeError::argument_count_mismatch_range(*arity.start(), *arity.end(), args.len(), span)
+            }));
+        }
+        let mut numbers = Vec::new();
+        for (i, (_, arg)) in args.iter().enumerate() {
+            let expected = if member == "round" && i == 1 { Type::Int } else { Type::F64 };
+            let arg_ty = match self.infer_expr(arg) {
+                Ok(ty) => ty,
+                Err(e) => return Some(Err(e)),
+            };
+            if !self.check_assignable(&arg_ty, &expected, arg.span()) {
+                return Some(Err(self.mismatch_error(&expected, &arg_ty, arg.span())));
+            }
+            if expected == Type::F64 {
+                numbers.push(arg_ty);
+            }
+        }
+        Some(Ok(if numbers.iter().any(|ty| matches!(ty, Type::Dynamic | Type::Unknown)) {
+            Type::Dynamic
+        } else if numbers.iter().all(Type::is_integer) {
+            Type::Int
+        } else {
+            Type::F64
+        }))
+    }
+    
+    /// `str.format(args...)` 的参数个数不固定，不经过方法签名检查，结果为 string
+    fn infer_string_format(&mut self, callee: &Expr, args: &[(Option<String>, Expr)]) -> Option<Result<Type, TypeError>> {
+        let Expr::Member { object, member, .. } = callee else {
+            return None;
+        };
+        if member != "format" {
+            return None;
+        }
+        match self.infer_expr(object) {
+            Ok(Type::String) => {}
+            Ok(_) => return None,
+            Err(e) => return Some(Err(e)),
+        }
+        for (_, arg) in args {
+            if let Err(e) = self.infer_expr(arg) {
+                return Some(Err(e));
+            }
+        }
+        Some(Ok(Type::String))
+    }
+    
+    /// 检查第 index 条顶层语句时新产生的错误和警告归到该语句所在的文件
+    fn attribute_to_statement(&mut self, index: usize, errors_before: usize, warnings_before: usize) {
+        let mut end = 0;
+        let Some((file, _)) = self.source_files.iter().find(|(_, count)| {
+            end += count;
+            index < end
+        }) else {
+            return;
+        };
+        for error in self.errors[errors_before..].iter_mut().chain(&mut self.warnings[warnings_before..]) {
+            if error.file.is_none() {
+                error.file = Some(file.clone());
+            }
+        }
     }
     
     /// 设置编译上下文
@@ -354,7 +1124,7 @@ impl TypeChecker {
     
     /// 检查是否是内置函数
     fn is_builtin_function(name: &str) -> bool {
-        matches!(name, "print" | "println" | "typeof" | "typeinfo" | "sizeof" | "panic" | "time")
+        matches!(name, "print" | "println" | "typeof" | "typeinfo" | "sizeof" | "panic" | "recover" | "time" | "hexdump" | "printHexdump")
     }
     
     /// 获取内置函数的类型
@@ -380,16 +1150,31 @@ impl TypeChecker {
                 return_type: Box::new(Type::Int),
                 required_params: 1,
             },
+            // panic 的值可以是任何类型，由 recover() 原样取回
             "panic" => Type::Function {
-                param_types: vec![Type::String],
+                param_types: vec![Type::Unknown],
                 return_type: Box::new(Type::Never),
                 required_params: 1,
             },
+            "recover" => Type::Function {
+                param_types: vec![],
+                return_type: Box::new(Type::Dynamic),
+                required_params: 0,
+            },
             "time" => Type::Function {
                 param_types: vec![],
                 return_type: Box::new(Type::Int),
                 required_params: 0,
             },
+            // hexdump(int[] 或 string, 选项?)，选项为 offset/length/width
+            "hexdump" | "printHexdump" => Type::Function {
+                param_types: vec![
+                    Type::Unknown,
+                    Type::Map { key_type: Box::new(Type::String), value_type: Box::new(Type::Int) },
+                ],
+                return_type: Box::new(if name == "hexdump" { Type::String } else { Type::Void }),
+                required_params: 1,
+            },
             _ => Type::Unknown,
         }
     }
@@ -403,6 +1188,7 @@ impl TypeChecker {
             Stmt::TraitDef { .. } |
             Stmt::EnumDef { .. } |
             Stmt::FnDef { .. } |
+            Stmt::ConstDecl { .. } |
             Stmt::TypeAlias { .. } |
             Stmt::Package { .. } |
             Stmt::Import { .. }
@@ -415,13 +1201,11 @@ impl TypeChecker {
         if !params.is_empty() {
             return false;
         }
-        // main 函数应该无返回值或返回 void
-        if let Some(ret) = return_type {
-            if ret.ty != Type::Void {
-                return false;
-            }
+        // main 函数无返回值，或返回 int 作为进程退出码
+        match return_type {
+            Some(ret) => matches!(ret.ty, Type::Void | Type::Int),
+            None => true,
         }
-        true
     }
     
     /// 验证包名
@@ -456,10 +1240,12 @@ impl TypeChecker {
     
     /// 检查整个程序
     pub fn check_program(&mut self, program: &Program) -> Result<(), Vec<TypeError>> {
+        let started = timings::begin();
         // 0. 验证包名
         self.validate_package(program);
         
         // 0.5. 处理 import 声明，注册导入的类型
+        self.register_builtin_namespaces();
         for import in &program.imports {
             self.process_import(&import.path, &import.target);
         }
@@ -508,27 +1294,62 @@ impl TypeChecker {
             }
         }
         
-        // 3. 第一遍：收集所有类型定义
-        for stmt in &program.statements {
+        // 2.5. 在编译期求值所有常量，类常量的类型依赖这里的结果
+        self.fold_program_consts(program);
+        
+        // 3. 第一遍：收集所有类型定义（类型别名先注册，签名中使用别名与声明顺序无关）
+        let (aliases, definitions): (Vec<_>, Vec<_>) = program.statements.iter().enumerate()
+            .partition(|(_, stmt)| matches!(stmt, Stmt::TypeAlias { .. }));
+        for (index, stmt) in aliases.into_iter().chain(definitions) {
+            self.statement = index;
+            let (errors, warnings) = (self.errors.len(), self.warnings.len());
             self.collect_type_definitions(stmt);
+            self.attribute_to_statement(index, errors, warnings);
         }
         
+        // 3.5. 拒绝无法展开的类型别名和按值包含自身的结构体
+        self.check_recursive_types(program);
+        
         // 4. 第二遍：检查类型实现
-        for stmt in &program.statements {
+        for (index, stmt) in program.statements.iter().enumerate() {
+            self.statement = index;
+            let (errors, warnings) = (self.errors.len(), self.warnings.len());
             self.check_type_implementations(stmt);
+            self.attribute_to_statement(index, errors, warnings);
         }
         
-        // 5. 第三遍：检查所有语句
+        // 4.5. 顶级常量在整个文件中可见，与声明顺序无关
         for stmt in &program.statements {
+            if let Stmt::ConstDecl { name, type_ann, span, .. } = stmt {
+                self.define_top_level_const(name, type_ann, *span);
+            }
+        }
+        
+        // 5. 第三遍：检查所有语句
+        for (index, stmt) in program.statements.iter().enumerate() {
+            if let Stmt::ConstDecl { .. } = stmt {
+                continue;
+            }
+            self.statement = index;
+            let (errors, warnings) = (self.errors.len(), self.warnings.len());
             if let Err(e) = self.check_stmt(stmt) {
                 self.errors.push(e);
             }
+            self.attribute_to_statement(index, errors, warnings);
+        }
+        
+        // 6. 检查无法确定元素类型的空字面量（已有错误时跳过，避免级联）
+        if self.errors.is_empty() {
+            self.report_uninferred_literals();
         }
         
-        // 6. 求解约束
+        // 7. 求解约束
         if let Err(mut errs) = self.solver.solve() {
             self.errors.append(&mut errs);
         }
+        timings::record(Phase::TypeCheck, started, || {
+            (self.solver.solved_count() + self.generic_constraints_solved) as u64
+        });
         
         if self.errors.is_empty() {
             Ok(())
@@ -537,17 +1358,60 @@ impl TypeChecker {
         }
     }
     
-    /// 收集类型定义（第一遍）
-    fn collect_type_definitions(&mut self, stmt: &Stmt) {
-        match stmt {
-            Stmt::StructDef { name, type_params, interfaces, fields, methods, .. } => {
+    /// 求值顶级常量与类/结构体常量，无法求值的报错
+    fn fold_program_consts(&mut self, program: &Program) {
+        let items = const_items(program);
+        let (consts, errors) = fold_consts(&items, &self.consts);
+        self.consts.extend(consts);
+        for (name, err) in errors {
+            let span = items.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, expr)| expr.span())
+                .unwrap_or_default();
+            let detail = match err {
+                ConstEvalError::NotConstant => format!("'{}'", name),
+                ConstEvalError::Invalid(msg) => format!("'{}': {}", name, msg),
+            };
+            self.errors.push(TypeError::new(TypeErrorKind::NonConstantInitializer(detail), span));
+        }
+    }
+    
+    /// 定义顶级常量，类型取编译期求得的值的类型
+    fn define_top_level_const(&mut self, name: &str, type_ann: &Option<TypeAnnotation>, span: Span) {
+        let value_ty = match self.consts.get(name) {
+            Some(value) => value.ty(),
+            // 求值失败已经报过错
+            None => Type::Unknown,
+        };
+        let ty = match type_ann {
+            Some(ann) if !self.check_assignable(&value_ty, &ann.ty, span) => {
+                let err = self.mismatch_error(&ann.ty, &value_ty, span)
+                    .with_label(ann.span, "expected type declared here");
+                self.errors.push(err);
+                ann.ty.clone()
+            }
+            Some(ann) => ann.ty.clone(),
+            None => value_ty,
+        };
+        if self.env.define_variable(name.to_string(), ty, true).is_err() {
+            self.errors.push(TypeError::new(TypeErrorKind::DuplicateDefinition(name.to_string()), span));
+        }
+    }
+    
+    /// 收集类型定义（第一遍）
+    fn collect_type_definitions(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::StructDef { name, type_params, interfaces, fields, static_fields, methods, .. } => {
                 let info = StructInfo {
                     name: name.clone(),
                     type_params: self.convert_type_params(type_params),
                     interfaces: interfaces.clone(),
                     fields: self.collect_struct_fields(fields),
-                    methods: self.collect_struct_methods(methods),
+                    methods: self.collect_struct_methods(methods, false),
+                    static_fields: self.collect_class_static_fields(name, static_fields),
+                    static_methods: self.collect_struct_methods(methods, true),
                 };
+                self.report_implicit_any_fields(name, static_fields);
                 if let Err(e) = self.env.register_type(name.clone(), TypeInfo::Struct(info)) {
                     self.errors.push(TypeError::new(
                         TypeErrorKind::DuplicateDefinition(name.clone()),
@@ -556,6 +1420,9 @@ impl TypeChecker {
                 }
             }
             Stmt::ClassDef { name, type_params, is_abstract, parent, interfaces, traits, fields, methods, .. } => {
+                self.report_implicit_any_fields(name, fields);
+                let defaults = fields.iter().filter(|f| !f.is_static && f.initializer.is_some()).map(|f| f.name.clone());
+                self.field_defaults.insert(name.clone(), defaults.collect());
                 let info = ClassInfo {
                     name: name.clone(),
                     type_params: self.convert_type_params(type_params),
@@ -564,9 +1431,18 @@ impl TypeChecker {
                     traits: traits.clone(),
                     fields: self.collect_class_fields(fields),
                     methods: self.collect_class_methods(methods),
-                    static_fields: self.collect_class_static_fields(fields),
+                    static_fields: self.collect_class_static_fields(name, fields),
                     static_methods: self.collect_class_static_methods(methods),
                     is_abstract: *is_abstract,
+                    final_methods: methods.iter()
+                        .filter(|m| m.is_final)
+                        .map(|m| (m.name.clone(), m.span))
+                        .collect(),
+                    abstract_methods: methods.iter()
+                        .filter(|m| m.is_abstract)
+                        .map(|m| (m.name.clone(), m.span))
+                        .collect(),
+                    is_native: false,
                 };
                 if let Err(e) = self.env.register_type(name.clone(), TypeInfo::Class(info)) {
                     self.errors.push(TypeError::new(
@@ -607,7 +1483,7 @@ impl TypeChecker {
                 }
             }
             Stmt::EnumDef { name, variants, .. } => {
-                let info = super::environment::EnumInfo {
+                let info = EnumInfo {
                     name: name.clone(),
                     variants: variants.iter().map(|v| {
                         (v.name.clone(), super::environment::EnumVariantInfo {
@@ -647,10 +1523,11 @@ impl TypeChecker {
                 let info = FunctionInfo {
                     name: name.clone(),
                     type_params: self.convert_type_params(type_params),
-                    param_types: params.iter().map(|p| p.type_ann.ty.clone()).collect(),
+                    param_types: params.iter().map(|p| self.expand_aliases(&p.type_ann.ty)).collect(),
                     param_names: params.iter().map(|p| p.name.clone()).collect(),
+                    param_spans: params.iter().map(|p| p.span).collect(),
                     required_params,
-                    return_type: return_type.as_ref().map(|t| t.ty.clone()).unwrap_or(Type::Void),
+                    return_type: return_type.as_ref().map(|t| self.expand_aliases(&t.ty)).unwrap_or(Type::Void),
                     is_method: false,
                     owner_type: None,
                 };
@@ -668,7 +1545,13 @@ impl TypeChecker {
     /// 检查类型实现（第二遍）
     fn check_type_implementations(&mut self, stmt: &Stmt) {
         match stmt {
-            Stmt::ClassDef { name, interfaces, traits, methods, span, .. } => {
+            Stmt::ClassDef { name, is_abstract, parent, interfaces, traits, methods, span, .. } => {
+                self.check_overrides(parent.as_deref(), interfaces, traits, methods);
+                if !is_abstract {
+                    self.check_abstract_methods_implemented(name, *span);
+                }
+                self.check_super_init(name, parent.as_deref(), methods, *span);
+                
                 // 检查接口实现
                 for interface_name in interfaces {
                     if let Some(TypeInfo::Interface(interface_info)) = self.env.lookup_type(interface_name) {
@@ -715,6 +1598,316 @@ impl TypeChecker {
         }
     }
     
+    /// 检查方法重写
+    ///
+    /// - 标记 override 的方法必须在父类链、接口或 Trait 中有同名方法，且签名兼容
+    /// - 重写父类方法但没有标记 override 的给出警告
+    /// - 父类链上的 final 方法不能重写
+    fn check_overrides(
+        &mut self,
+        parent: Option<&str>,
+        interfaces: &[String],
+        traits: &[String],
+        methods: &[crate::parser::ast::ClassMethod],
+    ) {
+        for method in methods.iter().filter(|m| !m.is_static && m.name != "init") {
+            let inherited = self.find_inherited_method(parent, &method.name);
+            
+            if let Some((parent_name, _, Some(final_span))) = &inherited {
+                let err = TypeError::new(
+                    TypeErrorKind::OverrideFinal { method_name: method.name.clone(), parent_name: parent_name.clone() },
+                    method.span,
+                ).with_label(*final_span, format!("'{}::{}' is declared final here", parent_name, method.name));
+                self.errors.push(err);
+                continue;
+            }
+            
+            let overridden = inherited
+                .map(|(parent_name, info, _)| (parent_name, info))
+                .or_else(|| self.find_contract_method(interfaces, traits, &method.name));
+            match overridden {
+                Some((parent_name, info)) => {
+                    if let Some(detail) = self.override_mismatch(method, &info) {
+                        self.errors.push(TypeError::new(
+                            TypeErrorKind::OverrideSignatureMismatch {
+                                method_name: method.name.clone(),
+                                parent_name,
+                                detail,
+                            },
+                            method.span,
+                        ));
+                    } else if !method.is_override && self.find_inherited_method(parent, &method.name).is_some() {
+                        self.warnings.push(TypeError::new(
+                            TypeErrorKind::MissingOverride { method_name: method.name.clone(), parent_name },
+                            method.span,
+                        ));
+                    }
+                }
+                None if method.is_override => {
+                    let candidates = self.overridable_method_names(parent, interfaces, traits);
+                    let suggestion = similar_name(&method.name, candidates.iter().map(String::as_str))
+                        .map(str::to_string);
+                    self.errors.push(TypeError::new(
+                        TypeErrorKind::NothingToOverride { method_name: method.name.clone(), suggestion },
+                        method.span,
+                    ));
+                }
+                None => {}
+            }
+        }
+    }
+    
+    /// 父类（用户定义的类）的 init 需要参数时，子类必须声明 init 并以 `super.init(...)` 开头；
+    /// 否则父类的 init 由子类的 init 隐式调用（见代码生成的 `compile_init_body`）
+    fn check_super_init(&mut self, class_name: &str, parent: Option<&str>, methods: &[crate::parser::ast::ClassMethod], span: Span) {
+        let Some(parent_name) = parent else {
+            return;
+        };
+        let required = match self.env.lookup_type(parent_name) {
+            Some(TypeInfo::Class(info)) if !info.is_native => {
+                info.methods.get("init").map_or(0, |init| init.required_params)
+            }
+            _ => return,
+        };
+        if required == 0 {
+            return;
+        }
+        let init = methods.iter().find(|m| m.name == "init" && !m.is_static);
+        let starts_with_super_init = init
+            .and_then(|m| m.body.as_deref())
+            .is_some_and(|body| match body {
+                Stmt::Block { statements, .. } => statements.first().is_some_and(Stmt::is_super_init_call),
+                other => other.is_super_init_call(),
+            });
+        if starts_with_super_init {
+            return;
+        }
+        self.errors.push(TypeError::new(
+            TypeErrorKind::MissingSuperInit {
+                class_name: class_name.to_string(),
+                parent_name: parent_name.to_string(),
+                required,
+                declares_init: init.is_some(),
+            },
+            init.map_or(span, |m| m.span),
+        ));
+    }
+    
+    /// 检查非抽象类实现了父类链上声明的全部抽象方法
+    ///
+    /// 从类自身向上查找：某个类中的抽象方法，如果在它下面（更靠近 class_name）的类或这些类使用的 Trait 中
+    /// 没有具体实现，就是缺少的方法。缺少的方法一起报告在类的定义处，并指向声明它们的位置
+    fn check_abstract_methods_implemented(&mut self, class_name: &str, span: Span) {
+        let mut implemented = std::collections::HashSet::new();
+        let mut missing: Vec<(String, String, Span)> = Vec::new();
+        for info in self.env.class_chain(class_name) {
+            let mut method_names: Vec<&String> = info.methods.keys().collect();
+            method_names.sort();
+            for method in method_names {
+                match info.abstract_methods.get(method) {
+                    Some(declared) if !implemented.contains(method) => {
+                        if !missing.iter().any(|(m, _, _)| m == method) {
+                            missing.push((method.clone(), info.name.clone(), *declared));
+                        }
+                    }
+                    Some(_) => {}
+                    None => {
+                        implemented.insert(method.clone());
+                    }
+                }
+            }
+            for trait_name in &info.traits {
+                if let Some(TypeInfo::Trait(trait_info)) = self.env.lookup_type(trait_name) {
+                    implemented.extend(trait_info.default_methods.iter()
+                        .filter(|(_, has_default)| **has_default)
+                        .map(|(method, _)| method.clone()));
+                }
+            }
+        }
+        if missing.is_empty() {
+            return;
+        }
+        let mut err = TypeError::new(
+            TypeErrorKind::MissingAbstractMethods {
+                class_name: class_name.to_string(),
+                methods: missing.iter().map(|(method, owner, _)| (owner.clone(), method.clone())).collect(),
+            },
+            span,
+        );
+        for (method, owner, declared) in missing {
+            err = err.with_label(declared, format!("'{}::{}' is declared abstract here", owner, method));
+        }
+        self.errors.push(err);
+    }
+    
+    /// 沿父类链查找实例方法，返回声明它的类、签名和 final 声明位置
+    fn find_inherited_method(&self, parent: Option<&str>, method: &str) -> Option<(String, FunctionInfo, Option<Span>)> {
+        let mut current = parent.map(str::to_string);
+        let mut visited = std::collections::HashSet::new();
+        while let Some(class_name) = current {
+            if !visited.insert(class_name.clone()) {
+                break;
+            }
+            let Some(TypeInfo::Class(info)) = self.env.lookup_type(&class_name) else {
+                break;
+            };
+            if let Some(found) = info.methods.get(method) {
+                return Some((class_name, found.clone(), info.final_methods.get(method).copied()));
+            }
+            current = info.parent.clone();
+        }
+        None
+    }
+    
+    /// 在类实现的接口和 Trait 中查找方法
+    fn find_contract_method(&self, interfaces: &[String], traits: &[String], method: &str) -> Option<(String, FunctionInfo)> {
+        interfaces.iter().chain(traits).find_map(|name| {
+            let methods = match self.env.lookup_type(name)? {
+                TypeInfo::Interface(info) => &info.methods,
+                TypeInfo::Trait(info) => &info.methods,
+                _ => return None,
+            };
+            methods.get(method).map(|info| (name.clone(), info.clone()))
+        })
+    }
+    
+    /// 父类链、接口和 Trait 中所有可重写的方法名（用于拼写建议）
+    fn overridable_method_names(&self, parent: Option<&str>, interfaces: &[String], traits: &[String]) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut current = parent.map(str::to_string);
+        let mut visited = std::collections::HashSet::new();
+        while let Some(class_name) = current {
+            if !visited.insert(class_name.clone()) {
+                break;
+            }
+            let Some(TypeInfo::Class(info)) = self.env.lookup_type(&class_name) else {
+                break;
+            };
+            names.extend(info.methods.keys().cloned());
+            current = info.parent.clone();
+        }
+        for name in interfaces.iter().chain(traits) {
+            match self.env.lookup_type(name) {
+                Some(TypeInfo::Interface(info)) => names.extend(info.methods.keys().cloned()),
+                Some(TypeInfo::Trait(info)) => names.extend(info.methods.keys().cloned()),
+                _ => {}
+            }
+        }
+        names.sort();
+        names
+    }
+    
+    /// 重写方法与被重写方法的签名差异；参数数量必须相同，类型已知时参数逆变、返回值协变
+    fn override_mismatch(&mut self, method: &crate::parser::ast::ClassMethod, overridden: &FunctionInfo) -> Option<String> {
+        if method.params.len() != overridden.param_types.len() {
+            return Some(format!(
+                "expected {} parameter(s), found {}",
+                overridden.param_types.len(),
+                method.params.len()
+            ));
+        }
+        let known = |ty: &Type| {
+            !ty.has_type_params()
+                && !matches!(ty, Type::Unknown | Type::Dynamic | Type::Infer | Type::TypeVar(_))
+        };
+        for (i, (param, expected)) in method.params.iter().zip(&overridden.param_types).enumerate() {
+            let actual = &param.type_ann.ty;
+            if known(actual) && known(expected) && !self.check_assignable(expected, actual, param.span) {
+                return Some(format!("parameter {} has type {}, expected {}", i + 1, actual, expected));
+            }
+        }
+        let actual = method.return_type.as_ref().map(|t| t.ty.clone()).unwrap_or(Type::Void);
+        let expected = &overridden.return_type;
+        if known(&actual) && known(expected) && !self.check_assignable(&actual, expected, method.span) {
+            return Some(format!("returns {}, expected {}", actual, expected));
+        }
+        None
+    }
+    
+    /// `Type::field = value` 的目标必须是声明过的静态变量，而不是常量、静态方法或枚举变体
+    fn check_static_field_target(&self, class_name: &str, member: &str, span: Span) -> Result<(), TypeError> {
+        let field = match self.env.lookup_type(class_name) {
+            Some(TypeInfo::Class(info)) => info.static_fields.get(member),
+            Some(TypeInfo::Struct(info)) => info.static_fields.get(member),
+            _ => None,
+        };
+        match field {
+            Some(field) if field.is_mutable => Ok(()),
+            Some(_) => Err(TypeError::new(
+                TypeErrorKind::ConstantReassignment(format!("{}::{}", class_name, member)),
+                span,
+            )),
+            None => Err(TypeError::new(
+                TypeErrorKind::UndefinedField { type_name: class_name.to_string(), field_name: member.to_string() },
+                span,
+            )),
+        }
+    }
+    
+    /// 检查一次赋值：目标必须是左值且不是常量，值的类型必须能赋给目标（复合赋值按 `a = a op b` 检查）
+    ///
+    /// 目标和值的类型已经推断好；赋的是非空值时收窄可空的变量
+    fn check_assignment(&mut self, target: &Expr, op: AssignOp, target_ty: &Type, value_ty: &Type, span: Span) -> Result<(), TypeError> {
+        if let Expr::Identifier { name, .. } = target {
+            if value_ty != target_ty {
+                self.table.record_loose_assignment(self.statement, name);
+            }
+        }
+        
+        // 检查赋值目标是否是左值
+        if !target.is_lvalue() {
+            return Err(TypeError::new(
+                TypeErrorKind::Other("Cannot assign to non-lvalue".to_string()),
+                span,
+            ));
+        }
+        
+        // 检查常量重新赋值
+        if let Expr::Identifier { name, .. } = target {
+            if let Some(var) = self.env.lookup_variable(name) {
+                if var.is_const {
+                    return Err(TypeError::new(
+                        TypeErrorKind::ConstantReassignment(name.clone()),
+                        span,
+                    ));
+                }
+            }
+        }
+        if let Expr::StaticMember { class_name, member, .. } = target {
+            self.check_static_field_target(class_name, member, span)?;
+        }
+        
+        // 检查类型兼容性
+        match op {
+            AssignOp::Assign => {
+                if !self.check_assignable(value_ty, target_ty, span) {
+                    return Err(self.mismatch_error(target_ty, value_ty, span));
+                }
+                // 赋的是非空值时，之后的使用同样可以视为非空
+                if let (Expr::Identifier { name, .. }, Type::Nullable(inner)) = (target, target_ty) {
+                    if !matches!(value_ty, Type::Nullable(_) | Type::Null | Type::Unknown) {
+                        self.env.narrow_variable(name.clone(), inner.as_ref().clone());
+                    }
+                }
+            }
+            // 复合赋值 `a op= b` 按 `a = a op b` 检查：运算结果必须能赋回目标；
+            // map 条目读出来是可空的，复合赋值按值类型检查（键不存在时运行时报错）
+            _ => {
+                let operand_ty = match (target, target_ty) {
+                    (Expr::Index { .. }, Type::Nullable(inner)) => inner.as_ref().clone(),
+                    _ => target_ty.clone(),
+                };
+                let bin_op = op.binary_op().expect("compound assignment operator");
+                let result_ty = self.infer_binary_op(&operand_ty, &bin_op, value_ty, span)?;
+                if !self.check_assignable(&result_ty, &operand_ty, span) {
+                    return Err(self.mismatch_error(&operand_ty, &result_ty, span));
+                }
+            }
+        }
+        
+        Ok(())
+    }
+
     /// 检查语句
     fn check_stmt(&mut self, stmt: &Stmt) -> Result<(), TypeError> {
         match stmt {
@@ -722,48 +1915,73 @@ impl TypeChecker {
                 self.infer_expr(expr)?;
                 Ok(())
             }
+            Stmt::ParallelAssign { targets, values, .. } => {
+                // 右值都在赋值之前求值，按赋值之前的收窄推断
+                let value_tys = values.iter().map(|value| self.infer_expr(value)).collect::<Result<Vec<_>, _>>()?;
+                for ((target, value), value_ty) in targets.iter().zip(values).zip(&value_tys) {
+                    if let Expr::Identifier { name, .. } = target {
+                        self.env.invalidate_narrowing(name);
+                    }
+                    let target_ty = self.infer_expr(target)?;
+                    self.check_assignment(target, AssignOp::Assign, &target_ty, value_ty, value.span())?;
+                }
+                Ok(())
+            }
             Stmt::Print { expr, span, .. } => {
                 self.infer_expr(expr)?;
                 Ok(())
             }
             Stmt::VarDecl { name, type_ann, initializer, span } => {
+                if let Some(ann) = type_ann {
+                    self.check_type_arguments(&ann.ty, ann.span)?;
+                }
                 let ty = if let Some(init) = initializer {
-                    let init_ty = self.infer_expr(init)?;
+                    let init_ty = match type_ann {
+                        Some(ann) => self.infer_expr_expecting(init, &ann.ty)?,
+                        None => self.infer_expr(init)?,
+                    };
                     
                     if let Some(ann) = type_ann {
                         // 检查初始化类型与声明类型是否兼容
-                        if !init_ty.is_assignable_to(&ann.ty) {
-                            return Err(TypeError::type_mismatch(ann.ty.clone(), init_ty, *span));
+                        if !self.check_assignable(&init_ty, &ann.ty, *span) {
+                            return Err(self.mismatch_error(&ann.ty, &init_ty, *span)
+                                .with_label(ann.span, "expected type declared here"));
                         }
-                        ann.ty.clone()
+                        self.expand_aliases(&ann.ty)
                     } else {
                         init_ty
                     }
                 } else if let Some(ann) = type_ann {
-                    ann.ty.clone()
+                    self.expand_aliases(&ann.ty)
                 } else {
                     return Err(TypeError::new(TypeErrorKind::CannotInferType, *span));
                 };
                 
                 self.env.define_variable(name.clone(), ty, false)
                     .map_err(|e| TypeError::new(TypeErrorKind::DuplicateDefinition(name.clone()), *span))?;
+                self.warn_if_shadows_parameter(name, *span);
                 
                 Ok(())
             }
             Stmt::ConstDecl { name, type_ann, initializer, span } => {
-                let init_ty = self.infer_expr(initializer)?;
+                let init_ty = match type_ann {
+                    Some(ann) => self.infer_expr_expecting(initializer, &ann.ty)?,
+                    None => self.infer_expr(initializer)?,
+                };
                 
                 let ty = if let Some(ann) = type_ann {
-                    if !init_ty.is_assignable_to(&ann.ty) {
-                        return Err(TypeError::type_mismatch(ann.ty.clone(), init_ty, *span));
+                    if !self.check_assignable(&init_ty, &ann.ty, *span) {
+                        return Err(self.mismatch_error(&ann.ty, &init_ty, *span)
+                            .with_label(ann.span, "expected type declared here"));
                     }
-                    ann.ty.clone()
+                    self.expand_aliases(&ann.ty)
                 } else {
                     init_ty
                 };
                 
                 self.env.define_variable(name.clone(), ty, true)
-                    .map_err(|e| TypeError::new(TypeErrorKind::DuplicateDefinition(name.clone()), *span))?;
+                    .map_err(|_| TypeError::new(TypeErrorKind::DuplicateDefinition(name.clone()), *span))?;
+                self.warn_if_shadows_parameter(name, *span);
                 
                 Ok(())
             }
@@ -781,13 +1999,41 @@ impl TypeChecker {
                     return Err(TypeError::type_mismatch(Type::Bool, cond_ty, *span));
                 }
                 
-                self.check_stmt(then_branch)?;
+                let then_narrowings = self.condition_narrowings(condition, true);
+                let else_narrowings = self.condition_narrowings(condition, false);
+                let entered = self.enter_narrowings(then_narrowings.clone());
+                let result = self.check_stmt(then_branch);
+                self.leave_narrowings(entered);
+                result?;
                 if let Some(else_stmt) = else_branch {
-                    self.check_stmt(else_stmt)?;
+                    let entered = self.enter_narrowings(else_narrowings.clone());
+                    let result = self.check_stmt(else_stmt);
+                    self.leave_narrowings(entered);
+                    result?;
+                }
+                
+                // 一个分支一定跳出时，if 之后只可能来自另一个分支，它的收窄继续有效
+                // （除非那个分支给变量重新赋了值）
+                let (survivor, narrowings) = if self.stmt_exits(then_branch) {
+                    (else_branch.as_deref(), else_narrowings)
+                } else if else_branch.as_deref().is_some_and(|stmt| self.stmt_exits(stmt)) {
+                    (Some(then_branch.as_ref()), then_narrowings)
+                } else {
+                    (None, Vec::new())
+                };
+                let mut assigned = HashSet::new();
+                if let Some(stmt) = survivor {
+                    collect_assigned_variables(stmt, &mut assigned);
+                }
+                for (name, ty) in narrowings {
+                    if !assigned.contains(&name) {
+                        self.env.narrow_variable(name, ty);
+                    }
                 }
                 Ok(())
             }
             Stmt::ForLoop { initializer, condition, increment, body, .. } => {
+                self.invalidate_assigned_in_loop(stmt);
                 self.env.enter_scope();
                 let was_in_loop = self.in_loop;
                 self.in_loop = true;
@@ -811,6 +2057,7 @@ impl TypeChecker {
                 Ok(())
             }
             Stmt::ForIn { variables, iterable, body, span, .. } => {
+                self.invalidate_assigned_in_loop(stmt);
                 self.env.enter_scope();
                 let was_in_loop = self.in_loop;
                 self.in_loop = true;
@@ -826,13 +2073,14 @@ impl TypeChecker {
                             *span,
                         ))?;
                 } else if variables.len() == 2 {
-                    // index, value 形式
-                    self.env.define_variable(variables[0].clone(), Type::Int, false)
+                    // 每个元素拆成两个值：map 的键和值，或二元组/数组的两个元素
+                    let (first_ty, second_ty) = Self::pair_types(&elem_ty, *span)?;
+                    self.env.define_variable(variables[0].clone(), first_ty, false)
                         .map_err(|_| TypeError::new(
                             TypeErrorKind::DuplicateDefinition(variables[0].clone()),
                             *span,
                         ))?;
-                    self.env.define_variable(variables[1].clone(), elem_ty, false)
+                    self.env.define_variable(variables[1].clone(), second_ty, false)
                         .map_err(|_| TypeError::new(
                             TypeErrorKind::DuplicateDefinition(variables[1].clone()),
                             *span,
@@ -846,16 +2094,23 @@ impl TypeChecker {
                 Ok(())
             }
             Stmt::While { condition, body, .. } => {
+                self.invalidate_assigned_in_loop(stmt);
                 let was_in_loop = self.in_loop;
                 self.in_loop = true;
                 
+                // 条件每轮都重新判断，循环体内可以使用条件的收窄
+                let mut narrowings = Vec::new();
                 if let Some(cond) = condition {
                     let cond_ty = self.infer_expr(cond)?;
                     if cond_ty != Type::Bool {
                         return Err(TypeError::type_mismatch(Type::Bool, cond_ty, cond.span()));
                     }
+                    narrowings = self.condition_narrowings(cond, true);
                 }
-                self.check_stmt(body)?;
+                let entered = self.enter_narrowings(narrowings);
+                let result = self.check_stmt(body);
+                self.leave_narrowings(entered);
+                result?;
                 
                 self.in_loop = was_in_loop;
                 Ok(())
@@ -876,19 +2131,21 @@ impl TypeChecker {
                     Type::Void
                 };
                 
-                if let Some(expected) = self.env.get_return_type() {
-                    if !return_ty.is_assignable_to(expected) {
-                        return Err(TypeError::type_mismatch(expected.clone(), return_ty, *span));
+                if let Some(expected) = self.env.get_return_type().cloned() {
+                    if !self.check_assignable(&return_ty, &expected, *span) {
+                        return Err(self.mismatch_error(&expected, &return_ty, *span));
                     }
                 }
                 Ok(())
             }
-            Stmt::Match { expr, arms, span } => {
+            Stmt::Match { expr, arms, .. } => {
+                // 注解中的枚举名（`s: Shape`）解析为枚举类型，枚举值的模式才能与它比较
                 let match_ty = self.infer_expr(expr)?;
+                let match_ty = self.resolve_struct_names(&match_ty);
                 
                 for arm in arms {
                     self.env.enter_scope();
-                    self.check_pattern(&arm.pattern, &match_ty, *span)?;
+                    self.check_pattern(&arm.pattern, &match_ty, arm.span)?;
                     
                     if let Some(guard) = &arm.guard {
                         let guard_ty = self.infer_expr(guard)?;
@@ -902,6 +2159,58 @@ impl TypeChecker {
                 }
                 Ok(())
             }
+            Stmt::Select { cases, .. } => {
+                for case in cases {
+                    self.env.enter_scope();
+                    match &case.kind {
+                        SelectCaseKind::Receive { channel, binding } => {
+                            let element_ty = self.infer_channel_element(channel)?;
+                            if let Some(name) = binding {
+                                // 与 receive() 相同：通道关闭且取空后绑定 null
+                                let binding_ty = Type::Nullable(Box::new(element_ty));
+                                self.env.define_variable(name.clone(), binding_ty, false)
+                                    .map_err(|_| TypeError::new(
+                                        TypeErrorKind::DuplicateDefinition(name.clone()),
+                                        case.span,
+                                    ))?;
+                            }
+                        }
+                        SelectCaseKind::Send { channel, value } => {
+                            let element_ty = self.infer_channel_element(channel)?;
+                            let value_ty = self.infer_expr(value)?;
+                            if !self.check_assignable(&value_ty, &element_ty, value.span()) {
+                                return Err(self.mismatch_error(&element_ty, &value_ty, value.span()));
+                            }
+                        }
+                        SelectCaseKind::Default => {}
+                    }
+                    self.check_stmt(&case.body)?;
+                    self.env.leave_scope();
+                }
+                Ok(())
+            }
+            Stmt::Switch { expr, cases, default, .. } => {
+                let subject_ty = self.infer_expr(expr)?;
+                
+                for case in cases {
+                    // 每个值都要能与主体表达式比较
+                    for value in &case.values {
+                        let value_ty = self.infer_expr(value)?;
+                        if !value_ty.is_assignable_to(&subject_ty) {
+                            return Err(TypeError::type_mismatch(subject_ty.clone(), value_ty, value.span()));
+                        }
+                    }
+                    self.env.enter_scope();
+                    self.check_stmt(&case.body)?;
+                    self.env.leave_scope();
+                }
+                if let Some(default) = default {
+                    self.env.enter_scope();
+                    self.check_stmt(default)?;
+                    self.env.leave_scope();
+                }
+                Ok(())
+            }
             Stmt::FnDef { name, type_params, params, return_type, body, span, .. } => {
                 self.env.enter_scope();
                 let was_in_function = self.in_function;
@@ -918,7 +2227,8 @@ impl TypeChecker {
                 
                 // 定义参数变量
                 for param in params {
-                    self.env.define_variable(param.name.clone(), param.type_ann.ty.clone(), false)
+                    self.check_type_arguments(&param.type_ann.ty, param.type_ann.span)?;
+                    self.env.define_variable(param.name.clone(), self.expand_aliases(&param.type_ann.ty), false)
                         .map_err(|_| TypeError::new(
                             TypeErrorKind::DuplicateDefinition(param.name.clone()),
                             param.span,
@@ -926,11 +2236,17 @@ impl TypeChecker {
                 }
                 
                 // 设置返回类型
-                let ret_ty = return_type.as_ref().map(|t| t.ty.clone()).unwrap_or(Type::Void);
+                let ret_ty = return_type.as_ref().map(|t| self.expand_aliases(&t.ty)).unwrap_or(Type::Void);
                 self.env.set_return_type(Some(ret_ty.clone()));
                 
                 // 检查函数体
-                self.check_stmt(body)?;
+                let outer_params = std::mem::replace(
+                    &mut self.function_params,
+                    params.iter().map(|p| (p.name.clone(), p.span)).collect(),
+                );
+                let result = self.check_stmt(body);
+                self.function_params = outer_params;
+                result?;
                 
                 // 检查是否缺少 return 语句
                 // 如果函数有非 void 返回类型，必须确保所有路径都返回
@@ -972,6 +2288,22 @@ impl TypeChecker {
                 }
                 Ok(())
             }
+            Stmt::Recovering { body, panic_param, handler, span } => {
+                self.check_stmt(body)?;
+                
+                self.env.enter_scope();
+                // panic 的值可以是任何类型，与 recover() 一样在运行时检查
+                if let Some(param_name) = panic_param {
+                    self.env.define_variable(param_name.clone(), Type::Dynamic, false)
+                        .map_err(|_| TypeError::new(
+                            TypeErrorKind::DuplicateDefinition(param_name.clone()),
+                            *span,
+                        ))?;
+                }
+                self.check_stmt(handler)?;
+                self.env.leave_scope();
+                Ok(())
+            }
             Stmt::Throw { value, span } => {
                 self.infer_expr(value)?;
                 Ok(())
@@ -986,8 +2318,35 @@ impl TypeChecker {
         }
     }
     
+    /// 按声明的类型推导初始化表达式
+    ///
+    /// 数组字面量的元素逐个对照声明的元素类型检查，
+    /// 这样 `var xs: Named[] = [doc, point]` 中不同的实现类型可以放进同一个数组
+    fn infer_expr_expecting(&mut self, expr: &Expr, expected: &Type) -> Result<Type, TypeError> {
+        if let (Expr::Array { elements, .. }, Type::Slice { element_type }) = (expr, expected) {
+            if !elements.is_empty() {
+                for elem in elements {
+                    let elem_ty = self.infer_expr_expecting(elem, element_type)?;
+                    if !self.check_assignable(&elem_ty, element_type, elem.span()) {
+                        return Err(self.mismatch_error(element_type, &elem_ty, elem.span()));
+                    }
+                }
+                return Ok(expected.clone());
+            }
+        }
+        self.infer_expr(expr)
+    }
+    
     /// 推导表达式类型
     fn infer_expr(&mut self, expr: &Expr) -> Result<Type, TypeError> {
+        let ty = self.infer_expr_inner(expr)?;
+        // 代入已确定的空字面量元素类型
+        let ty = self.literal_types.apply(&ty);
+        self.table.record_expr(self.statement, expr.span(), ty.clone());
+        Ok(ty)
+    }
+    
+    fn infer_expr_inner(&mut self, expr: &Expr) -> Result<Type, TypeError> {
         match expr {
             Expr::Integer { .. } => Ok(Type::Int),
             Expr::Float { .. } => Ok(Type::F64),
@@ -997,8 +2356,8 @@ impl TypeChecker {
             Expr::Null { .. } => Ok(Type::Null),
             
             Expr::Identifier { name, span } => {
-                if let Some(var) = self.env.lookup_variable(name) {
-                    Ok(var.ty.clone())
+                if let Some(ty) = self.env.variable_type(name) {
+                    Ok(ty)
                 } else if let Some(func) = self.env.lookup_function(name) {
                     Ok(Type::Function {
                         param_types: func.param_types.clone(),
@@ -1015,7 +2374,17 @@ impl TypeChecker {
             
             Expr::Binary { left, op, right, span } => {
                 let left_ty = self.infer_expr(left)?;
-                let right_ty = self.infer_expr(right)?;
+                // && 的右侧只在左侧为真时求值，|| 的右侧只在左侧为假时求值
+                let right_ty = match op {
+                    BinOp::And | BinOp::Or => {
+                        let narrowings = self.condition_narrowings(left, *op == BinOp::And);
+                        let entered = self.enter_narrowings(narrowings);
+                        let result = self.infer_expr(right);
+                        self.leave_narrowings(entered);
+                        result?
+                    }
+                    _ => self.infer_expr(right)?,
+                };
                 self.infer_binary_op(&left_ty, op, &right_ty, *span)
             }
             
@@ -1026,8 +2395,45 @@ impl TypeChecker {
             
             Expr::Grouping { expr, .. } => self.infer_expr(expr),
             
+            Expr::IfExpr { condition, then_branch, else_branch, span } => {
+                let cond_ty = self.infer_expr(condition)?;
+                if cond_ty != Type::Bool {
+                    return Err(TypeError::type_mismatch(Type::Bool, cond_ty, condition.span()));
+                }
+                // 两个分支的类型合并为公共类型（int 与 f64 合并为 f64，T 与 null 合并为 T?）
+                let then_narrowings = self.condition_narrowings(condition, true);
+                let else_narrowings = self.condition_narrowings(condition, false);
+                let entered = self.enter_narrowings(then_narrowings);
+                let then_ty = self.infer_expr(then_branch);
+                self.leave_narrowings(entered);
+                let entered = self.enter_narrowings(else_narrowings);
+                let else_ty = self.infer_expr(else_branch);
+                self.leave_narrowings(entered);
+                let (then_ty, else_ty) = (then_ty?, else_ty?);
+                match self.try_join_types(&then_ty, &else_ty, *span) {
+                    Some(ty) => Ok(ty),
+                    None => Err(TypeError::new(
+                        TypeErrorKind::IncompatibleTypes {
+                            types: vec![then_ty, else_ty],
+                            context: "conditional expression".to_string(),
+                        },
+                        *span,
+                    )),
+                }
+            }
+            
             Expr::Call { callee, args, span } => {
+                if let Some(result) = self.infer_host_call(callee, args, *span) {
+                    return result;
+                }
+                if let Some(result) = self.infer_string_format(callee, args) {
+                    return result;
+                }
+                if let Some(result) = self.infer_math_call(callee, args, *span) {
+                    return result;
+                }
                 let callee_ty = self.infer_expr(callee)?;
+                let target = self.call_target(callee);
                 
                 // 检查是否有命名参数
                 let has_named_args = args.iter().any(|(name, _)| name.is_some());
@@ -1064,16 +2470,16 @@ impl TypeChecker {
                             // 缺失的参数在 infer_call 中会报错
                         }
                         
-                        self.infer_call(&callee_ty, &reordered_args, *span)
+                        self.infer_call(&callee_ty, &reordered_args, target.as_ref(), *span)
                     } else {
                         // 没有参数名信息，按原顺序检查
                         let arg_exprs: Vec<&Expr> = args.iter().map(|(_, e)| e).collect();
-                        self.infer_call(&callee_ty, &arg_exprs, *span)
+                        self.infer_call(&callee_ty, &arg_exprs, target.as_ref(), *span)
                     }
                 } else {
                     // 纯位置参数，按顺序检查
                     let arg_exprs: Vec<&Expr> = args.iter().map(|(_, e)| e).collect();
-                    self.infer_call(&callee_ty, &arg_exprs, *span)
+                    self.infer_call(&callee_ty, &arg_exprs, target.as_ref(), *span)
                 }
             }
             
@@ -1088,6 +2494,27 @@ impl TypeChecker {
                 self.infer_member(&obj_ty, member, *span)
             }
             
+            Expr::StaticMember { class_name, member, span } => {
+                if let Some(TypeInfo::Enum(info)) = self.env.lookup_type(class_name) {
+                    return Self::enum_static_member(info, member, *span);
+                }
+                // 静态方法 (Type::method) 与静态字段/常量 (Type::NAME)，其余暂不检查
+                let (method, field) = match self.env.lookup_type(class_name) {
+                    Some(TypeInfo::Class(info)) => (info.static_methods.get(member), info.static_fields.get(member)),
+                    Some(TypeInfo::Struct(info)) => (info.static_methods.get(member), info.static_fields.get(member)),
+                    _ => (None, None),
+                };
+                Ok(match (method, field) {
+                    (Some(func), _) => Type::Function {
+                        param_types: func.param_types.clone(),
+                        return_type: Box::new(func.return_type.clone()),
+                        required_params: func.required_params,
+                    },
+                    (None, Some(field)) => field.ty.clone(),
+                    (None, None) => Type::Unknown,
+                })
+            }
+            
             Expr::SafeMember { object, member, span } => {
                 let obj_ty = self.infer_expr(object)?;
                 let inner_ty = match &obj_ty {
@@ -1116,96 +2543,51 @@ impl TypeChecker {
             }
             
             Expr::Assign { target, op, value, span } => {
-                let target_ty = self.infer_expr(target)?;
+                // 右侧在赋值之前求值，仍然使用之前的收窄（`c = c.next`）；
+                // 重新赋值后收窄不再成立，目标按声明类型检查
                 let value_ty = self.infer_expr(value)?;
-                
-                // 检查赋值目标是否是左值
-                if !target.is_lvalue() {
-                    return Err(TypeError::new(
-                        TypeErrorKind::Other("Cannot assign to non-lvalue".to_string()),
-                        *span,
-                    ));
-                }
-                
-                // 检查常量重新赋值
                 if let Expr::Identifier { name, .. } = target.as_ref() {
-                    if let Some(var) = self.env.lookup_variable(name) {
-                        if var.is_const {
-                            return Err(TypeError::new(
-                                TypeErrorKind::ConstantReassignment(name.clone()),
-                                *span,
-                            ));
-                        }
-                    }
-                }
-                
-                // 检查类型兼容性
-                match op {
-                    AssignOp::Assign => {
-                        if !value_ty.is_assignable_to(&target_ty) {
-                            return Err(TypeError::type_mismatch(target_ty, value_ty, *span));
-                        }
-                    }
-                    // 复合赋值运算符
-                    _ => {
-                        // 检查操作数类型
-                        if !target_ty.is_numeric() {
-                            return Err(TypeError::new(
-                                TypeErrorKind::Other("Compound assignment requires numeric type".to_string()),
-                                *span,
-                            ));
-                        }
-                    }
+                    self.env.invalidate_narrowing(name);
                 }
-                
+                let target_ty = self.infer_expr(target)?;
+                self.check_assignment(target, *op, &target_ty, &value_ty, *span)?;
                 Ok(target_ty)
             }
             
             Expr::Array { elements, span } => {
                 if elements.is_empty() {
-                    // 空数组，使用类型变量
-                    Ok(Type::Slice { element_type: Box::new(Type::fresh_var()) })
+                    // 空数组：元素类型由注解或后续使用确定
+                    let ty = Type::Slice { element_type: Box::new(Type::fresh_var()) };
+                    self.pending_empty_literals.push((ty.clone(), *span));
+                    Ok(ty)
                 } else {
-                    let first_ty = self.infer_expr(&elements[0])?;
-                    for elem in &elements[1..] {
-                        let elem_ty = self.infer_expr(elem)?;
-                        if elem_ty != first_ty {
-                            return Err(TypeError::new(
-                                TypeErrorKind::IncompatibleTypes {
-                                    types: vec![first_ty, elem_ty],
-                                    context: "array literal".to_string(),
-                                },
-                                *span,
-                            ));
-                        }
+                    let mut elem_ty = self.infer_expr(&elements[0])?;
+                    for (index, elem) in elements.iter().enumerate().skip(1) {
+                        let next_ty = self.infer_expr(elem)?;
+                        elem_ty = self.join_literal_types(&elem_ty, next_ty, "array literal element", index, elem.span())?;
                     }
-                    Ok(Type::Slice { element_type: Box::new(first_ty) })
+                    Ok(Type::Slice { element_type: Box::new(elem_ty) })
                 }
             }
             
             Expr::MapLiteral { entries, span } => {
                 if entries.is_empty() {
-                    Ok(Type::Map {
+                    let ty = Type::Map {
                         key_type: Box::new(Type::fresh_var()),
                         value_type: Box::new(Type::fresh_var()),
-                    })
+                    };
+                    self.pending_empty_literals.push((ty.clone(), *span));
+                    Ok(ty)
                 } else {
                     let (first_key, first_val) = &entries[0];
-                    let key_ty = self.infer_expr(first_key)?;
-                    let val_ty = self.infer_expr(first_val)?;
+                    let mut key_ty = self.infer_expr(first_key)?;
+                    let mut val_ty = self.infer_expr(first_val)?;
                     
-                    for (k, v) in &entries[1..] {
+                    for (index, (k, v)) in entries.iter().enumerate().skip(1) {
                         let k_ty = self.infer_expr(k)?;
+                        key_ty = self.join_literal_types(&key_ty, k_ty, "map literal key", index, k.span())?;
                         let v_ty = self.infer_expr(v)?;
-                        if k_ty != key_ty || v_ty != val_ty {
-                            return Err(TypeError::new(
-                                TypeErrorKind::IncompatibleTypes {
-                                    types: vec![key_ty, val_ty, k_ty, v_ty],
-                                    context: "map literal".to_string(),
-                                },
-                                *span,
-                            ));
-                        }
+                        val_ty = self.join_literal_types(&val_ty, v_ty, "map literal value", index, v.span())?;
                     }
                     
                     Ok(Type::Map {
@@ -1221,6 +2603,12 @@ impl TypeChecker {
             }
             
             Expr::Closure { params, return_type, body, span } => {
+                // 闭包可能在任何时候运行，它赋值的外部变量不能再视为已收窄
+                let mut assigned = HashSet::new();
+                collect_assigned_variables(body, &mut assigned);
+                for name in &assigned {
+                    self.env.invalidate_narrowing(name);
+                }
                 self.env.enter_scope();
                 
                 let param_types: Vec<Type> = params.iter()
@@ -1239,7 +2627,13 @@ impl TypeChecker {
                 let ret_ty = return_type.as_ref().map(|t| t.ty.clone()).unwrap_or(Type::Void);
                 self.env.set_return_type(Some(ret_ty.clone()));
                 
-                self.check_stmt(body)?;
+                let outer_params = std::mem::replace(
+                    &mut self.function_params,
+                    params.iter().map(|p| (p.name.clone(), p.span)).collect(),
+                );
+                let result = self.check_stmt(body);
+                self.function_params = outer_params;
+                result?;
                 
                 self.env.set_return_type(None);
                 self.env.leave_scope();
@@ -1251,42 +2645,27 @@ impl TypeChecker {
                 })
             }
             
-            Expr::StructLiteral { name, fields, span } => {
-                // 先克隆 struct 信息以避免借用冲突
-                let struct_fields = if let Some(TypeInfo::Struct(info)) = self.env.lookup_type(name) {
-                    info.fields.clone()
-                } else {
-                    return Err(TypeError::undefined_type(name.clone(), *span));
-                };
-                
-                // 检查字段
-                for (field_name, field_expr) in fields {
-                    if let Some(field_info) = struct_fields.get(field_name) {
-                        let expr_ty = self.infer_expr(field_expr)?;
-                        if !expr_ty.is_assignable_to(&field_info.ty) {
-                            return Err(TypeError::type_mismatch(
-                                field_info.ty.clone(),
-                                expr_ty,
-                                field_expr.span(),
-                            ));
-                        }
-                    } else {
-                        return Err(TypeError::new(
-                            TypeErrorKind::UndefinedField {
-                                type_name: name.clone(),
-                                field_name: field_name.clone(),
-                            },
-                            *span,
-                        ));
-                    }
-                }
-                Ok(Type::Struct(name.clone()))
-            }
+            Expr::StructLiteral { name, fields, span } => self.infer_struct_literal(name, fields, *span),
+            
+            Expr::New { class_name, fields: Some(fields), span, .. } => self.infer_new_with_fields(class_name, fields, *span),
             
-            Expr::New { class_name, args, span } => {
+            Expr::New { class_name, args, span, .. } => {
                 // 先克隆 class 信息以避免借用冲突
                 let (is_abstract, init_info) = if let Some(TypeInfo::Class(info)) = self.env.lookup_type(class_name) {
-                    let init_info = info.methods.get("init").cloned();
+                    // 没有声明 init 的类有一个无参的隐式 init（继承标准库类的除外，构造由运行时完成）
+                    let init_info = info.methods.get("init").cloned().or_else(|| {
+                        self.env.class_chain(class_name).all(|c| !c.is_native).then(|| FunctionInfo {
+                            name: "init".to_string(),
+                            type_params: Vec::new(),
+                            param_types: Vec::new(),
+                            param_names: Vec::new(),
+                            param_spans: Vec::new(),
+                            required_params: 0,
+                            return_type: Type::Void,
+                            is_method: true,
+                            owner_type: Some(class_name.clone()),
+                        })
+                    });
                     (info.is_abstract, init_info)
                 } else {
                     return Err(TypeError::undefined_type(class_name.clone(), *span));
@@ -1322,13 +2701,13 @@ impl TypeChecker {
                     }
                     
                     // 类型检查提供的参数
-                    for (arg, param_ty) in args.iter().zip(param_types) {
+                    for (i, (arg, param_ty)) in args.iter().zip(param_types).enumerate() {
                         let arg_ty = self.infer_expr(arg)?;
-                        if !arg_ty.is_assignable_to(param_ty) {
-                            return Err(TypeError::type_mismatch(
-                                param_ty.clone(),
-                                arg_ty,
-                                arg.span(),
+                        if !self.check_assignable(&arg_ty, param_ty, arg.span()) {
+                            return Err(Self::label_parameter(
+                                self.mismatch_error(param_ty, &arg_ty, arg.span()),
+                                Some(&init),
+                                i,
                             ));
                         }
                     }
@@ -1387,20 +2766,145 @@ impl TypeChecker {
                 Ok(Type::Void)
             }
             
+            Expr::ChannelNew { element_type, capacity, .. } => {
+                if let Some(capacity) = capacity {
+                    let capacity_type = self.infer_expr(capacity)?;
+                    if !capacity_type.is_integer() && !matches!(capacity_type, Type::Dynamic | Type::Unknown) {
+                        return Err(TypeError::type_mismatch(Type::Int, capacity_type, capacity.span()));
+                    }
+                }
+                Ok(Type::Channel { element_type: Box::new(element_type.clone()) })
+            }
+            
             _ => Ok(Type::Unknown),  // 未知表达式返回 unknown 类型
         }
     }
     
-    /// 推导二元运算结果类型
-    fn infer_binary_op(&self, left: &Type, op: &BinOp, right: &Type, span: Span) -> Result<Type, TypeError> {
-        use BinOp::*;
+    /// 检查 `value` 能否赋值给 `target`
+    /// 
+    /// 任一侧含有空字面量的类型变量时尝试统一，成功则记录替换，
+    /// 使 `var xs = []` 之后的 `xs.push(1)` 能确定元素类型。
+    fn check_assignable(&mut self, value: &Type, target: &Type, span: Span) -> bool {
+        let value = self.resolve_struct_names(&self.expand_aliases(&self.literal_types.apply(value)));
+        let target = self.resolve_struct_names(&self.expand_aliases(&self.literal_types.apply(target)));
+        if value.is_assignable_to(&target) || self.is_nominal_subtype(&value, &target) {
+            return true;
+        }
+        if value.free_type_vars().is_empty() && target.free_type_vars().is_empty() {
+            return false;
+        }
         
-        match op {
-            Add | Sub | Mul | Div | Mod | Pow => {
-                if left.is_numeric() && right.is_numeric() {
-                    // 返回更宽的类型
-                    if left.is_float() || right.is_float() {
-                        Ok(Type::F64)
+        // 在副本上统一，失败时不留下部分替换
+        let mut trial = Unifier::with_substitution(self.literal_types.get_substitution().clone());
+        if trial.unify(&value, &target, span).is_ok() {
+            self.literal_types = trial;
+            true
+        } else {
+            false
+        }
+    }
+    
+    /// 类或结构体的值可以赋给它继承的父类、实现的接口和使用的 Trait（包括它们的可空类型）
+    fn is_nominal_subtype(&self, value: &Type, target: &Type) -> bool {
+        match (value, target) {
+            (Type::Nullable(value), Type::Nullable(target)) => self.is_nominal_subtype(value, target),
+            (_, Type::Nullable(target)) => self.is_nominal_subtype(value, target),
+            (Type::Class(name) | Type::Struct(name), Type::Class(bound) | Type::Interface(bound) | Type::Trait(bound)) => {
+                self.env.lookup_type_param(name).is_none() && self.extends(name, bound)
+            }
+            _ => false,
+        }
+    }
+    
+    /// 合并字面量中已推导的元素类型与下一个元素的类型
+    /// 
+    /// 可互相赋值时取较宽者；整数与浮点混合时按二元运算规则提升为 f64；
+    /// 与 null 混合时取可空类型；嵌套的切片 / Map 逐层合并。
+    /// 无法合并时报告该元素的位置和双方类型。
+    fn join_literal_types(
+        &mut self,
+        current: &Type,
+        next: Type,
+        context: &str,
+        index: usize,
+        span: Span,
+    ) -> Result<Type, TypeError> {
+        match self.try_join_types(current, &next, span) {
+            Some(ty) => Ok(ty),
+            None => Err(TypeError::new(
+                TypeErrorKind::LiteralElementMismatch {
+                    context: context.to_string(),
+                    index,
+                    expected: self.literal_types.apply(current),
+                    actual: self.literal_types.apply(&next),
+                },
+                span,
+            )),
+        }
+    }
+    
+    fn try_join_types(&mut self, current: &Type, next: &Type, span: Span) -> Option<Type> {
+        let current = self.literal_types.apply(current);
+        let next = self.literal_types.apply(next);
+        if next.is_assignable_to(&current) || self.is_nominal_subtype(&next, &current) {
+            return Some(current);
+        }
+        if current.is_assignable_to(&next) || self.is_nominal_subtype(&current, &next) {
+            return Some(next);
+        }
+        
+        match (&current, &next) {
+            (a, b) if a.is_numeric() && b.is_numeric() && (a.is_float() || b.is_float()) => {
+                Some(Type::F64)
+            }
+            (Type::Null, other) | (other, Type::Null) => {
+                Some(Type::Nullable(Box::new(other.clone())))
+            }
+            (Type::Slice { element_type: a }, Type::Slice { element_type: b }) => {
+                let element_type = self.try_join_types(a, b, span)?;
+                Some(Type::Slice { element_type: Box::new(element_type) })
+            }
+            (
+                Type::Map { key_type: k1, value_type: v1 },
+                Type::Map { key_type: k2, value_type: v2 },
+            ) => {
+                let key_type = self.try_join_types(k1, k2, span)?;
+                let value_type = self.try_join_types(v1, v2, span)?;
+                Some(Type::Map {
+                    key_type: Box::new(key_type),
+                    value_type: Box::new(value_type),
+                })
+            }
+            _ => {
+                if self.check_assignable(&next, &current, span) {
+                    Some(self.literal_types.apply(&current))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+    
+    /// 报告检查结束时仍无法确定元素类型的空字面量
+    fn report_uninferred_literals(&mut self) {
+        let pending = std::mem::take(&mut self.pending_empty_literals);
+        for (ty, span) in pending {
+            if !self.literal_types.apply(&ty).free_type_vars().is_empty() {
+                self.errors.push(TypeError::new(TypeErrorKind::CannotInferEmptyLiteral, span));
+            }
+        }
+    }
+    
+    /// 推导二元运算结果类型
+    fn infer_binary_op(&self, left: &Type, op: &BinOp, right: &Type, span: Span) -> Result<Type, TypeError> {
+        use BinOp::*;
+        
+        match op {
+            Add | Sub | Mul | Div | Mod | Pow => {
+                if left.is_numeric() && right.is_numeric() {
+                    // 返回更宽的类型
+                    if left.is_float() || right.is_float() {
+                        Ok(Type::F64)
                     } else {
                         Ok(Type::Int)
                     }
@@ -1485,8 +2989,103 @@ impl TypeChecker {
         }
     }
     
+    /// 静态确定调用目标：顶层函数，或变量上的方法
+    fn call_target(&self, callee: &Expr) -> Option<FunctionInfo> {
+        match callee {
+            Expr::Identifier { name, .. } if self.env.lookup_variable(name).is_none() => {
+                self.env.lookup_function(name).cloned()
+            }
+            Expr::Member { object, member, .. } => match object.as_ref() {
+                Expr::Identifier { name, .. } => {
+                    let object_ty = self.env.lookup_variable(name)?.ty.clone();
+                    self.env.get_method(&object_ty, member).cloned()
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+    
+    /// 泛型函数调用：每个类型参数实例化为类型变量，由实参推导，再代入返回类型
+    ///
+    /// 推导出的类型实参必须满足类型参数的约束，不满足时报告在调用处
+    fn infer_generic_call(&mut self, info: &FunctionInfo, args: &[&Expr], span: Span) -> Result<Type, TypeError> {
+        let instantiation: HashMap<String, Type> = info.type_params.iter()
+            .map(|p| (p.name.clone(), Type::named_var(p.name.clone())))
+            .collect();
+        
+        let mut solver = ConstraintSolver::new();
+        for (i, (arg, param_ty)) in args.iter().zip(&info.param_types).enumerate() {
+            let arg_ty = self.infer_expr(arg)?;
+            let arg_ty = self.literal_types.apply(&arg_ty);
+            let mut constraint = Constraint::subtype(
+                arg_ty,
+                instantiate_type_params(param_ty, &instantiation),
+                arg.span(),
+                format!("argument {}", i + 1),
+            );
+            if let (Some(span), Some(name)) = (info.param_spans.get(i), info.param_names.get(i)) {
+                constraint = constraint.with_origin(*span, format!("parameter `{}` declared here", name));
+            }
+            solver.add_constraint(constraint);
+        }
+        
+        let result = solver.solve();
+        self.generic_constraints_solved += solver.solved_count();
+        let substitution = result.map_err(|mut errors| errors.remove(0))?;
+        let type_args: Vec<Type> = info.type_params.iter()
+            .map(|p| self.literal_types.apply(&instantiation[&p.name].substitute(&substitution)))
+            .collect();
+        self.check_bounds(&info.type_params, &type_args, span)?;
+        if !info.is_method {
+            let call = GenericCall { function: info.name.clone(), type_args };
+            self.table.record_generic_call(self.statement, span, call);
+        }
+        Ok(instantiate_type_params(&info.return_type, &instantiation).substitute(&substitution))
+    }
+    
+    /// 局部变量与当前函数的参数同名时记录警告
+    fn warn_if_shadows_parameter(&mut self, name: &str, span: Span) {
+        if name == DISCARD {
+            return;
+        }
+        if let Some((_, param_span)) = self.function_params.iter().find(|(param, _)| param == name) {
+            let warning = TypeError::new(TypeErrorKind::ShadowedParameter(name.to_string()), span)
+                .with_label(*param_span, "parameter declared here");
+            self.warnings.push(warning);
+        }
+    }
+
+    /// 构造类型不匹配错误：在嵌套类型中定位真正冲突的位置
+    fn mismatch_error(&self, expected: &Type, actual: &Type, span: Span) -> TypeError {
+        let expected = self.literal_types.apply(expected);
+        let actual = self.literal_types.apply(actual);
+        match Unifier::new().unify(&expected, &actual, span) {
+            Err(err) if !err.path.is_empty() => err
+                .with_note(format!("expected `{}`, found `{}`", expected, actual)),
+            _ => TypeError::type_mismatch(expected, actual, span),
+        }
+    }
+    
+    /// 为实参类型错误补充对应参数的声明位置
+    fn label_parameter(err: TypeError, target: Option<&FunctionInfo>, index: usize) -> TypeError {
+        let Some(info) = target else { return err };
+        match (info.param_spans.get(index), info.param_names.get(index)) {
+            (Some(span), Some(name)) => err.with_label(*span, format!("parameter `{}` declared here", name)),
+            _ => err,
+        }
+    }
+    
     /// 推导函数调用结果类型
-    fn infer_call(&mut self, callee: &Type, args: &[&Expr], span: Span) -> Result<Type, TypeError> {
+    ///
+    /// `target` 是被调用函数的声明信息（能静态确定时），用于泛型参数推导和定位参数声明
+    fn infer_call(
+        &mut self,
+        callee: &Type,
+        args: &[&Expr],
+        target: Option<&FunctionInfo>,
+        span: Span,
+    ) -> Result<Type, TypeError> {
         match callee {
             Type::Function { param_types, return_type, required_params } => {
                 // 检查参数数量：最少 required_params 个，最多 param_types.len() 个
@@ -1509,11 +3108,19 @@ impl TypeChecker {
                     }
                 }
                 
+                if let Some(info) = target.filter(|f| !f.type_params.is_empty()) {
+                    return self.infer_generic_call(info, args, span);
+                }
+                
                 // 只检查提供的参数类型
-                for (arg, param_ty) in args.iter().zip(param_types) {
+                for (i, (arg, param_ty)) in args.iter().zip(param_types).enumerate() {
                     let arg_ty = self.infer_expr(arg)?;
-                    if !arg_ty.is_assignable_to(param_ty) {
-                        return Err(TypeError::type_mismatch(param_ty.clone(), arg_ty, arg.span()));
+                    if !self.check_assignable(&arg_ty, param_ty, arg.span()) {
+                        return Err(Self::label_parameter(
+                            self.mismatch_error(param_ty, &arg_ty, arg.span()),
+                            target,
+                            i,
+                        ));
                     }
                 }
                 
@@ -1527,12 +3134,31 @@ impl TypeChecker {
                     _ => Err(TypeError::not_callable(callee.clone(), span)),
                 }
             }
+            Type::Dynamic => {
+                for arg in args {
+                    self.infer_expr(arg)?;
+                }
+                Ok(Type::Dynamic)
+            }
             _ => Err(TypeError::not_callable(callee.clone(), span)),
         }
     }
     
+    /// 推导 select 分支中通道表达式的元素类型
+    fn infer_channel_element(&mut self, channel: &Expr) -> Result<Type, TypeError> {
+        match self.infer_expr(channel)? {
+            Type::Channel { element_type } => Ok(*element_type),
+            Type::Dynamic => Ok(Type::Dynamic),
+            other => Err(TypeError::type_mismatch(
+                Type::Channel { element_type: Box::new(Type::Unknown) },
+                other,
+                channel.span(),
+            )),
+        }
+    }
+    
     /// 推导索引访问结果类型
-    fn infer_index(&self, obj: &Type, idx: &Type, span: Span) -> Result<Type, TypeError> {
+    fn infer_index(&mut self, obj: &Type, idx: &Type, span: Span) -> Result<Type, TypeError> {
         match obj {
             Type::Array { element_type, .. } | Type::Slice { element_type } => {
                 if !idx.is_integer() {
@@ -1541,7 +3167,7 @@ impl TypeChecker {
                 Ok(element_type.as_ref().clone())
             }
             Type::Map { key_type, value_type } => {
-                if !idx.is_assignable_to(key_type) {
+                if !self.check_assignable(idx, key_type, span) {
                     return Err(TypeError::type_mismatch(key_type.as_ref().clone(), idx.clone(), span));
                 }
                 Ok(Type::Nullable(value_type.clone()))
@@ -1552,12 +3178,83 @@ impl TypeChecker {
                 }
                 Ok(Type::Char)
             }
+            // 元组（如 map 遍历的键值对）按下标取值，元素类型不同时为 dynamic
+            Type::Tuple(types) => {
+                if !idx.is_integer() {
+                    return Err(TypeError::type_mismatch(Type::Int, idx.clone(), span));
+                }
+                match types.split_first() {
+                    Some((first, rest)) if rest.iter().all(|t| t == first) => Ok(first.clone()),
+                    _ => Ok(Type::Dynamic),
+                }
+            }
+            // dynamic 跳过编译时检查，结果仍为 dynamic
+            Type::Dynamic => Ok(Type::Dynamic),
             _ => Err(TypeError::new(TypeErrorKind::NotIndexable(obj.clone()), span)),
         }
     }
     
     /// 推导成员访问结果类型
+    /// `Enum::Variant` 的类型：没有关联数据的变体是枚举值，有关联数据的变体是按字段顺序接收参数的构造函数；
+    /// 另外有内置的 `values()` 和 `fromValue(value)`
+    fn enum_static_member(info: &EnumInfo, member: &str, span: Span) -> Result<Type, TypeError> {
+        let enum_ty = Type::Enum(info.name.clone());
+        if let Some(variant) = info.variants.get(member) {
+            if variant.fields.is_empty() {
+                return Ok(enum_ty);
+            }
+            return Ok(Type::Function {
+                param_types: variant.fields.iter().map(|(_, ty)| ty.clone()).collect(),
+                return_type: Box::new(enum_ty),
+                required_params: variant.fields.len(),
+            });
+        }
+        match member {
+            "values" => Ok(Type::Function {
+                param_types: vec![],
+                return_type: Box::new(Type::Slice { element_type: Box::new(enum_ty) }),
+                required_params: 0,
+            }),
+            "fromValue" => Ok(Type::Function {
+                param_types: vec![Type::Dynamic],
+                return_type: Box::new(Type::Nullable(Box::new(enum_ty))),
+                required_params: 1,
+            }),
+            _ => Err(TypeError::new(
+                TypeErrorKind::Other(format!("Enum '{}' has no variant '{}'", info.name, member)),
+                span,
+            )),
+        }
+    }
+    
+    /// 枚举值的成员：`name`、`value` 和变体的关联数据字段
+    ///
+    /// 字段属于哪个变体在运行时才知道，读取其他变体的字段是运行时错误；
+    /// 几个变体有同名但类型不同的字段时结果是 dynamic
+    fn enum_member(info: &EnumInfo, member: &str) -> Option<Type> {
+        match member {
+            "name" => return Some(Type::String),
+            "value" => return Some(Type::Dynamic),
+            _ => {}
+        }
+        let mut types = info.variants.values()
+            .flat_map(|variant| variant.fields.iter())
+            .filter(|(name, _)| name == member)
+            .map(|(_, ty)| ty);
+        let first = types.next()?.clone();
+        Some(if types.all(|ty| *ty == first) { first } else { Type::Dynamic })
+    }
+    
     fn infer_member(&self, obj: &Type, member: &str, span: Span) -> Result<Type, TypeError> {
+        if let Type::Enum(name) | Type::Class(name) = obj {
+            if let Some(TypeInfo::Enum(info)) = self.env.lookup_type(name) {
+                return Self::enum_member(info, member).ok_or_else(|| TypeError::new(
+                    TypeErrorKind::UndefinedField { type_name: name.clone(), field_name: member.to_string() },
+                    span,
+                ));
+            }
+        }
+        
         // 首先检查是否是方法
         if let Some(method) = self.env.get_method(obj, member) {
             return Ok(Type::Function {
@@ -1589,6 +3286,59 @@ impl TypeChecker {
                         return_type: Box::new(Type::String),
                         required_params: 2,
                     }),
+                    "repeat" => Ok(Type::Function {
+                        param_types: vec![Type::Int],
+                        return_type: Box::new(Type::String),
+                        required_params: 1,
+                    }),
+                    // 填充字符默认为空格
+                    "padStart" | "padEnd" => Ok(Type::Function {
+                        param_types: vec![Type::Int, Type::String],
+                        return_type: Box::new(Type::String),
+                        required_params: 1,
+                    }),
+                    "lines" | "chars" => Ok(Type::Function {
+                        param_types: vec![],
+                        return_type: Box::new(Type::Slice { element_type: Box::new(Type::String) }),
+                        required_params: 0,
+                    }),
+                    "splitN" => Ok(Type::Function {
+                        param_types: vec![Type::String, Type::Int],
+                        return_type: Box::new(Type::Slice { element_type: Box::new(Type::String) }),
+                        required_params: 2,
+                    }),
+                    // 转换失败时返回 null，进制可选
+                    "toInt" => Ok(Type::Function {
+                        param_types: vec![Type::Int],
+                        return_type: Box::new(Type::Nullable(Box::new(Type::Int))),
+                        required_params: 0,
+                    }),
+                    "toFloat" => Ok(Type::Function {
+                        param_types: vec![],
+                        return_type: Box::new(Type::Nullable(Box::new(Type::F64))),
+                        required_params: 0,
+                    }),
+                    "compareTo" => Ok(Type::Function {
+                        param_types: vec![Type::String],
+                        return_type: Box::new(Type::Int),
+                        required_params: 1,
+                    }),
+                    "codePointAt" => Ok(Type::Function {
+                        param_types: vec![Type::Int],
+                        return_type: Box::new(Type::Int),
+                        required_params: 1,
+                    }),
+                    // 字符可以是 char 或单个字符的字符串（字面量 'a' 推导为 string），在运行时检查；起始位置默认为 0
+                    "indexOfChar" => Ok(Type::Function {
+                        param_types: vec![Type::Unknown, Type::Int],
+                        return_type: Box::new(Type::Int),
+                        required_params: 1,
+                    }),
+                    "count" => Ok(Type::Function {
+                        param_types: vec![Type::String],
+                        return_type: Box::new(Type::Int),
+                        required_params: 1,
+                    }),
                     _ => Err(TypeError::new(
                         TypeErrorKind::UndefinedMethod {
                             type_name: "string".to_string(),
@@ -1612,6 +3362,160 @@ impl TypeChecker {
                         return_type: Box::new(Type::Nullable(element_type.clone())),
                         required_params: 0,
                     }),
+                    "concat" => Ok(Type::Function {
+                        param_types: vec![Type::Slice { element_type: element_type.clone() }],
+                        return_type: Box::new(Type::Slice { element_type: element_type.clone() }),
+                        required_params: 1,
+                    }),
+                    // sort 原地排序，sorted 返回新数组；比较函数可选（不传时按值的全序）
+                    "sort" | "sorted" => Ok(Type::Function {
+                        param_types: vec![Type::Function {
+                            param_types: vec![element_type.as_ref().clone(), element_type.as_ref().clone()],
+                            return_type: Box::new(Type::Int),
+                            required_params: 2,
+                        }],
+                        return_type: Box::new(if member == "sort" {
+                            Type::Void
+                        } else {
+                            Type::Slice { element_type: element_type.clone() }
+                        }),
+                        required_params: 0,
+                    }),
+                    // 按键函数的返回值排序，键之间按值的全序比较；
+                    // 键的类型任意，函数类型的返回值又要求完全一致，所以键函数在运行时检查
+                    "sortBy" | "sortedBy" => Ok(Type::Function {
+                        param_types: vec![Type::Unknown],
+                        return_type: Box::new(if member == "sortBy" {
+                            Type::Void
+                        } else {
+                            Type::Slice { element_type: element_type.clone() }
+                        }),
+                        required_params: 1,
+                    }),
+                    // 有序数组上的查找和插入，返回下标（binarySearch 没有找到时为 -(插入点 + 1)）
+                    "binarySearch" | "sortedInsert" => Ok(Type::Function {
+                        param_types: vec![element_type.as_ref().clone()],
+                        return_type: Box::new(Type::Int),
+                        required_params: 1,
+                    }),
+                    // 与 sortBy 一样，键函数和键的类型在运行时检查
+                    "binarySearchBy" => Ok(Type::Function {
+                        param_types: vec![Type::Unknown, Type::Unknown],
+                        return_type: Box::new(Type::Int),
+                        required_params: 2,
+                    }),
+                    "isSorted" => Ok(Type::Function {
+                        param_types: vec![],
+                        return_type: Box::new(Type::Bool),
+                        required_params: 0,
+                    }),
+                    "insert" => Ok(Type::Function {
+                        param_types: vec![Type::Int, element_type.as_ref().clone()],
+                        return_type: Box::new(Type::Void),
+                        required_params: 2,
+                    }),
+                    "removeAt" => Ok(Type::Function {
+                        param_types: vec![Type::Int],
+                        return_type: element_type.clone(),
+                        required_params: 1,
+                    }),
+                    "fill" => Ok(Type::Function {
+                        param_types: vec![element_type.as_ref().clone()],
+                        return_type: Box::new(Type::Void),
+                        required_params: 1,
+                    }),
+                    "unique" => Ok(Type::Function {
+                        param_types: vec![],
+                        return_type: Box::new(Type::Slice { element_type: element_type.clone() }),
+                        required_params: 0,
+                    }),
+                    // 元素是一层数组时展开后的元素类型确定，嵌套更深时取决于 depth
+                    "flat" => {
+                        let flat_element = match element_type.as_ref() {
+                            Type::Array { element_type: inner, .. } | Type::Slice { element_type: inner }
+                                if !matches!(inner.as_ref(), Type::Array { .. } | Type::Slice { .. }) => inner.as_ref().clone(),
+                            Type::Array { .. } | Type::Slice { .. } => Type::Dynamic,
+                            other => other.clone(),
+                        };
+                        Ok(Type::Function {
+                            param_types: vec![Type::Int],
+                            return_type: Box::new(Type::Slice { element_type: Box::new(flat_element) }),
+                            required_params: 0,
+                        })
+                    }
+                    // 另一个数组的元素类型在这里未知（参数在运行时检查），配对的第二项为 dynamic
+                    "zip" => Ok(Type::Function {
+                        param_types: vec![Type::Unknown],
+                        return_type: Box::new(Type::Slice {
+                            element_type: Box::new(Type::Tuple(vec![element_type.as_ref().clone(), Type::Dynamic])),
+                        }),
+                        required_params: 1,
+                    }),
+                    "chunk" => Ok(Type::Function {
+                        param_types: vec![Type::Int],
+                        return_type: Box::new(Type::Slice {
+                            element_type: Box::new(Type::Slice { element_type: element_type.clone() }),
+                        }),
+                        required_params: 1,
+                    }),
+                    // 只有数字数组有 min、max 和 sum
+                    "min" | "max" | "sum" if element_type.is_numeric() || matches!(element_type.as_ref(), Type::Dynamic | Type::Unknown) => {
+                        let return_type = if member == "sum" {
+                            element_type.as_ref().clone()
+                        } else {
+                            Type::Nullable(element_type.clone())
+                        };
+                        Ok(Type::Function { param_types: vec![], return_type: Box::new(return_type), required_params: 0 })
+                    }
+                    _ => Err(TypeError::new(
+                        TypeErrorKind::UndefinedMethod {
+                            type_name: obj.to_string(),
+                            method_name: member.to_string(),
+                        },
+                        span,
+                    ))
+                }
+            }
+            Type::F32 | Type::F64 => {
+                match member {
+                    // 保留的小数位数 / 有效数字位数
+                    "toFixed" | "toPrecision" => Ok(Type::Function {
+                        param_types: vec![Type::Int],
+                        return_type: Box::new(Type::String),
+                        required_params: 1,
+                    }),
+                    _ => Err(TypeError::new(
+                        TypeErrorKind::UndefinedMethod {
+                            type_name: obj.to_string(),
+                            method_name: member.to_string(),
+                        },
+                        span,
+                    ))
+                }
+            }
+            Type::Channel { element_type } => {
+                match member {
+                    "send" => Ok(Type::Function {
+                        param_types: vec![element_type.as_ref().clone()],
+                        return_type: Box::new(Type::Void),
+                        required_params: 1,
+                    }),
+                    // 通道关闭且缓冲区已取空后 receive 返回 null
+                    "receive" => Ok(Type::Function {
+                        param_types: vec![],
+                        return_type: Box::new(Type::Nullable(element_type.clone())),
+                        required_params: 0,
+                    }),
+                    "close" => Ok(Type::Function {
+                        param_types: vec![],
+                        return_type: Box::new(Type::Void),
+                        required_params: 0,
+                    }),
+                    "isClosed" => Ok(Type::Function {
+                        param_types: vec![],
+                        return_type: Box::new(Type::Bool),
+                        required_params: 0,
+                    }),
                     _ => Err(TypeError::new(
                         TypeErrorKind::UndefinedMethod {
                             type_name: obj.to_string(),
@@ -1621,6 +3525,37 @@ impl TypeChecker {
                     ))
                 }
             }
+            Type::Dynamic => Ok(Type::Dynamic),
+            // 类型参数的值只能使用约束声明的方法
+            Type::Class(name) | Type::TypeParameter { name, .. } if self.env.lookup_type_param(name).is_some() => {
+                let param = self.env.lookup_type_param(name).expect("type parameter in scope");
+                match param.bounds.iter().find_map(|bound| self.bound_method(&bound.trait_name, member)) {
+                    Some(method) => Ok(Type::Function {
+                        param_types: method.param_types.clone(),
+                        return_type: Box::new(method.return_type.clone()),
+                        required_params: method.required_params,
+                    }),
+                    None => Err(TypeError::new(
+                        TypeErrorKind::MethodNotInBounds {
+                            type_param: name.clone(),
+                            method_name: member.to_string(),
+                            bounds: param.bounds.iter().map(|b| b.to_string()).collect(),
+                        },
+                        span,
+                    )),
+                }
+            }
+            Type::Class(name) if matches!(self.env.lookup_type(name), Some(TypeInfo::Class(_))) => {
+                let candidates = self.member_names(name);
+                Err(TypeError::new(
+                    TypeErrorKind::UndefinedMember {
+                        type_name: name.clone(),
+                        member: member.to_string(),
+                        suggestion: similar_name(member, candidates.iter().map(String::as_str)).map(str::to_string),
+                    },
+                    span,
+                ))
+            }
             _ => Err(TypeError::new(
                 TypeErrorKind::UndefinedField {
                     type_name: obj.to_string(),
@@ -1631,44 +3566,385 @@ impl TypeChecker {
         }
     }
     
-    /// 获取迭代器元素类型
-    fn get_iterator_element_type(&self, ty: &Type, span: Span) -> Result<Type, TypeError> {
+    /// 类及其父类的方法和字段名（不含构造函数）
+    fn member_names(&self, class_name: &str) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut current = Some(class_name.to_string());
+        while let Some(TypeInfo::Class(info)) = current.and_then(|name| self.env.lookup_type(&name)) {
+            names.extend(info.methods.keys().filter(|m| *m != "init").cloned());
+            names.extend(info.fields.keys().cloned());
+            current = info.parent.clone();
+        }
+        names
+    }
+    
+    /// 约束（接口、Trait 或类）及其父类型中声明的方法
+    fn bound_method(&self, bound: &str, member: &str) -> Option<&FunctionInfo> {
+        if let Some(method) = self.env.get_method(&Type::Class(bound.to_string()), member) {
+            return Some(method);
+        }
+        match self.env.lookup_type(bound)? {
+            TypeInfo::Interface(info) => info.super_interfaces.iter().find_map(|s| self.bound_method(s, member)),
+            TypeInfo::Trait(info) => info.super_traits.iter().find_map(|s| self.bound_method(&s.trait_name, member)),
+            TypeInfo::Class(info) => self.bound_method(info.parent.as_deref()?, member),
+            _ => None,
+        }
+    }
+    
+    /// 类型是否满足约束：实现了约束接口、使用了约束 Trait，或者就是（继承自）约束类型
+    fn satisfies_bound(&self, ty: &Type, bound: &str) -> bool {
         match ty {
-            Type::Array { element_type, .. } | Type::Slice { element_type } => {
-                Ok(element_type.as_ref().clone())
+            Type::Dynamic | Type::Unknown | Type::TypeVar(_) => true,
+            Type::Class(name) | Type::TypeParameter { name, .. } if self.env.lookup_type_param(name).is_some() => {
+                let param = self.env.lookup_type_param(name).expect("type parameter in scope");
+                param.bounds.iter().any(|b| self.extends(&b.trait_name, bound))
             }
-            Type::String => Ok(Type::Char),
-            Type::Map { key_type, value_type } => {
-                // Map 迭代返回 (key, value) 元组
-                Ok(Type::Tuple(vec![
-                    key_type.as_ref().clone(),
-                    value_type.as_ref().clone(),
-                ]))
+            Type::Class(name) | Type::Struct(name) | Type::Interface(name) | Type::Trait(name) => {
+                self.extends(name, bound) || self.env.find_trait_impl(ty, bound).is_some()
             }
-            _ => Err(TypeError::new(TypeErrorKind::NotIterable(ty.clone()), span))
+            Type::Generic { base_type, .. } => self.satisfies_bound(base_type, bound),
+            _ => self.env.find_trait_impl(ty, bound).is_some(),
         }
     }
     
-    /// 检查模式
-    fn check_pattern(&mut self, pattern: &MatchPattern, expected_ty: &Type, span: Span) -> Result<(), TypeError> {
-        match pattern {
-            MatchPattern::Literal(expr) => {
-                let lit_ty = self.infer_expr(expr)?;
-                if !lit_ty.is_assignable_to(expected_ty) {
-                    return Err(TypeError::type_mismatch(expected_ty.clone(), lit_ty, span));
-                }
+    /// 名为 name 的类型是否就是 bound，或者通过继承、实现、使用 Trait 成为它的子类型
+    fn extends(&self, name: &str, bound: &str) -> bool {
+        if name == bound {
+            return true;
+        }
+        match self.env.lookup_type(name) {
+            Some(TypeInfo::Class(info)) => {
+                info.interfaces.iter().chain(&info.traits).chain(&info.parent).any(|s| self.extends(s, bound))
             }
-            MatchPattern::Variable(name) => {
-                self.env.define_variable(name.clone(), expected_ty.clone(), false)
-                    .map_err(|_| TypeError::new(
-                        TypeErrorKind::DuplicateDefinition(name.clone()),
-                        span,
-                    ))?;
+            Some(TypeInfo::Struct(info)) => info.interfaces.iter().any(|s| self.extends(s, bound)),
+            Some(TypeInfo::Interface(info)) => info.super_interfaces.iter().any(|s| self.extends(s, bound)),
+            Some(TypeInfo::Trait(info)) => info.super_traits.iter().any(|s| self.extends(&s.trait_name, bound)),
+            _ => false,
+        }
+    }
+    
+    /// 检查类型实参满足对应类型参数的约束，不满足时报告在 span（实例化的位置）
+    fn check_bounds(&self, params: &[GenericParam], args: &[Type], span: Span) -> Result<(), TypeError> {
+        for (param, arg) in params.iter().zip(args) {
+            if let Some(bound) = param.bounds.iter().find(|b| !self.satisfies_bound(arg, &b.trait_name)) {
+                return Err(TypeError::constraint_not_satisfied(&param.name, bound.to_string(), arg.clone(), span));
             }
-            MatchPattern::Wildcard => {}
-            MatchPattern::Or(patterns) => {
-                for p in patterns {
-                    self.check_pattern(p, expected_ty, span)?;
+        }
+        Ok(())
+    }
+    
+    /// 检查类型别名和结构体的递归
+    /// 
+    /// 类型别名在使用处展开，展开结果中任何位置引用到自身都无法展开完；
+    /// 结构体的字段按值存放，经可空类型、切片、Map、类等引用之外的路径包含自身时没有有限的大小。
+    /// 每个环只在环上第一个声明处报告一次。
+    fn check_recursive_types(&mut self, program: &Program) {
+        let mut reported = HashSet::new();
+        for stmt in &program.statements {
+            if let Stmt::TypeAlias { name, span, .. } = stmt {
+                if reported.contains(name) {
+                    continue;
+                }
+                if let Some(chain) = self.alias_cycle(&mut vec![name.clone()]) {
+                    reported.extend(chain.iter().cloned());
+                    self.errors.push(TypeError::new(TypeErrorKind::CyclicTypeDependency(chain), *span));
+                }
+            }
+        }
+        
+        let structs: HashMap<&str, (&[TypeParam], &[crate::parser::ast::StructField])> = program.statements.iter()
+            .filter_map(|stmt| match stmt {
+                Stmt::StructDef { name, type_params, fields, .. } => Some((name.as_str(), (type_params.as_slice(), fields.as_slice()))),
+                _ => None,
+            })
+            .collect();
+        for stmt in &program.statements {
+            let Stmt::StructDef { name, .. } = stmt else { continue };
+            if reported.contains(name) {
+                continue;
+            }
+            let mut path = Vec::new();
+            let mut visited = HashSet::new();
+            if self.struct_cycle(name, name, &HashMap::new(), &structs, &mut path, &mut visited) {
+                let (_, first_field, field_type) = &path[0];
+                let mut chain: Vec<String> = path.iter().map(|(owner, field, _)| format!("{}.{}", owner, field.name)).collect();
+                chain.push(name.clone());
+                let error = TypeError::new(
+                    TypeErrorKind::RecursiveStruct { name: name.clone(), field_type: field_type.clone() },
+                    first_field.span,
+                ).with_note(chain.join(" -> "));
+                reported.extend(path.iter().map(|(owner, _, _)| owner.clone()));
+                self.errors.push(error);
+            }
+        }
+    }
+    
+    /// 从 `stack` 最后一个类型别名出发，找回到 `stack[0]` 的展开路径
+    fn alias_cycle(&self, stack: &mut Vec<String>) -> Option<Vec<String>> {
+        let current = stack.last()?;
+        let Some(TypeInfo::Alias { actual_type, .. }) = self.env.lookup_type(current) else {
+            return None;
+        };
+        let mut referenced = Vec::new();
+        collect_class_names(actual_type, &mut referenced);
+        referenced.retain(|name| matches!(self.env.lookup_type(name), Some(TypeInfo::Alias { .. })));
+        for name in referenced {
+            if name == stack[0] {
+                let mut chain = stack.clone();
+                chain.push(name);
+                return Some(chain);
+            }
+            // 不经过起点的环由环上的别名自己报告
+            if stack.contains(&name) {
+                continue;
+            }
+            stack.push(name);
+            if let Some(chain) = self.alias_cycle(stack) {
+                return Some(chain);
+            }
+            stack.pop();
+        }
+        None
+    }
+    
+    /// 从结构体 `current`（类型参数按 `args` 替换）出发，按值的字段能否回到 `start`
+    /// 
+    /// 找到时 `path` 为经过的 (结构体, 字段, 建议改为可空的类型)
+    #[allow(clippy::type_complexity)]
+    fn struct_cycle<'a>(
+        &self,
+        start: &str,
+        current: &str,
+        args: &HashMap<String, Type>,
+        structs: &HashMap<&str, (&[TypeParam], &'a [crate::parser::ast::StructField])>,
+        path: &mut Vec<(String, &'a crate::parser::ast::StructField, String)>,
+        visited: &mut HashSet<String>,
+    ) -> bool {
+        let Some((_, fields)) = structs.get(current) else {
+            return false;
+        };
+        for field in fields.iter() {
+            let field_type = instantiate_type_params(&field.type_ann.ty, args);
+            let mut contained = Vec::new();
+            self.by_value_structs(&field_type, structs, &mut contained, &mut Vec::new());
+            for (target, target_args) in contained {
+                // 建议改为可空的类型：字段直接是结构体时用它的写法，否则（如数组的元素）用结构体名
+                let suggested = match &field.type_ann.ty {
+                    ty @ (Type::Class(_) | Type::Generic { .. }) => ty.to_string(),
+                    _ => target.clone(),
+                };
+                path.push((current.to_string(), field, suggested));
+                if target == start {
+                    return true;
+                }
+                if visited.insert(target.clone()) {
+                    let params = structs[target.as_str()].0;
+                    let target_args = params.iter().map(|p| p.name.clone()).zip(target_args).collect();
+                    if self.struct_cycle(start, &target, &target_args, structs, path, visited) {
+                        return true;
+                    }
+                }
+                path.pop();
+            }
+        }
+        false
+    }
+    
+    /// 类型按值包含的结构体及其类型实参，类型别名展开后再找（`expanding` 防止循环的别名）
+    fn by_value_structs(
+        &self,
+        ty: &Type,
+        structs: &HashMap<&str, (&[TypeParam], &[crate::parser::ast::StructField])>,
+        out: &mut Vec<(String, Vec<Type>)>,
+        expanding: &mut Vec<String>,
+    ) {
+        match ty {
+            Type::Class(name) | Type::Struct(name) => {
+                if structs.contains_key(name.as_str()) {
+                    out.push((name.clone(), Vec::new()));
+                } else if let Some(TypeInfo::Alias { actual_type, .. }) = self.env.lookup_type(name) {
+                    if !expanding.contains(name) {
+                        expanding.push(name.clone());
+                        self.by_value_structs(actual_type, structs, out, expanding);
+                        expanding.pop();
+                    }
+                }
+            }
+            Type::Generic { base_type, type_args } => {
+                if let Type::Class(name) | Type::Struct(name) = base_type.as_ref() {
+                    if structs.contains_key(name.as_str()) {
+                        out.push((name.clone(), type_args.clone()));
+                    }
+                }
+            }
+            Type::Array { element_type, .. } => self.by_value_structs(element_type, structs, out, expanding),
+            Type::Tuple(types) => {
+                for ty in types {
+                    self.by_value_structs(ty, structs, out, expanding);
+                }
+            }
+            // 可空类型、切片、Map、通道、函数和类都是堆上的引用
+            _ => {}
+        }
+    }
+    
+    /// 展开类型中的类型别名
+    /// 
+    /// 循环的别名已在声明处报告，引用到它的类型整个展开为 Error，避免连锁错误
+    fn expand_aliases(&self, ty: &Type) -> Type {
+        self.expand_aliases_in(ty, &mut Vec::new())
+    }
+    
+    fn expand_aliases_in(&self, ty: &Type, expanding: &mut Vec<String>) -> Type {
+        let mut names = Vec::new();
+        collect_class_names(ty, &mut names);
+        let mut expansions = HashMap::new();
+        for name in names {
+            if expansions.contains_key(&name) || self.env.lookup_type_param(&name).is_some() {
+                continue;
+            }
+            let Some(TypeInfo::Alias { actual_type, .. }) = self.env.lookup_type(&name) else {
+                continue;
+            };
+            if expanding.contains(&name) {
+                return Type::Error;
+            }
+            expanding.push(name.clone());
+            let expanded = self.expand_aliases_in(actual_type, expanding);
+            expanding.pop();
+            if expanded == Type::Error {
+                return Type::Error;
+            }
+            expansions.insert(name, expanded);
+        }
+        if expansions.is_empty() {
+            return ty.clone();
+        }
+        instantiate_type_params(ty, &expansions)
+    }
+    
+    /// 把类型中指向结构体和枚举的 `Class(name)` 换成 `Struct(name)` / `Enum(name)`
+    ///
+    /// 类型注解中的名字都解析为 `Class`，结构体字面量的类型是 `Struct`，枚举变体的类型是 `Enum`；
+    /// 嵌套在函数、数组等类型中时需要先统一写法才能比较（如 `fn(User) int` 与数组元素类型）
+    fn resolve_struct_names(&self, ty: &Type) -> Type {
+        let mut names = Vec::new();
+        collect_class_names(ty, &mut names);
+        let structs: HashMap<String, Type> = names
+            .into_iter()
+            .filter(|name| self.env.lookup_type_param(name).is_none())
+            .filter_map(|name| match self.env.lookup_type(&name) {
+                Some(TypeInfo::Struct(_)) => Some((name.clone(), Type::Struct(name))),
+                Some(TypeInfo::Enum(_)) => Some((name.clone(), Type::Enum(name))),
+                _ => None,
+            })
+            .collect();
+        if structs.is_empty() {
+            return ty.clone();
+        }
+        instantiate_type_params(ty, &structs)
+    }
+    
+    /// 检查类型注解中泛型类/结构体的类型实参（如 `Box<Num>`）满足约束
+    fn check_type_arguments(&self, ty: &Type, span: Span) -> Result<(), TypeError> {
+        match ty {
+            Type::Generic { base_type, type_args } => {
+                if let Type::Class(name) | Type::Struct(name) = base_type.as_ref() {
+                    let params = match self.env.lookup_type(name) {
+                        Some(TypeInfo::Class(info)) => &info.type_params,
+                        Some(TypeInfo::Struct(info)) => &info.type_params,
+                        _ => return Ok(()),
+                    };
+                    self.check_bounds(params, type_args, span)?;
+                }
+                for arg in type_args {
+                    self.check_type_arguments(arg, span)?;
+                }
+                Ok(())
+            }
+            Type::Array { element_type, .. } | Type::Slice { element_type } | Type::Channel { element_type } => {
+                self.check_type_arguments(element_type, span)
+            }
+            Type::Nullable(inner) => self.check_type_arguments(inner, span),
+            Type::Map { key_type, value_type } => {
+                self.check_type_arguments(key_type, span)?;
+                self.check_type_arguments(value_type, span)
+            }
+            _ => Ok(()),
+        }
+    }
+    
+    /// 获取迭代器元素类型
+    fn get_iterator_element_type(&self, ty: &Type, span: Span) -> Result<Type, TypeError> {
+        match ty {
+            Type::Array { element_type, .. } | Type::Slice { element_type } => {
+                Ok(element_type.as_ref().clone())
+            }
+            Type::String => Ok(Type::Char),
+            // 通道逐个接收直到关闭
+            Type::Channel { element_type } => Ok(element_type.as_ref().clone()),
+            Type::Map { key_type, value_type } => {
+                // Map 迭代返回 (key, value) 元组
+                Ok(Type::Tuple(vec![
+                    key_type.as_ref().clone(),
+                    value_type.as_ref().clone(),
+                ]))
+            }
+            Type::Dynamic => Ok(Type::Dynamic),
+            // 提供 entries() 的对象遍历 entries() 的结果
+            _ => match self.env.get_method(ty, "entries") {
+                Some(entries) if entries.param_types.is_empty() && entries.return_type != *ty => {
+                    let entries_ty = entries.return_type.clone();
+                    self.get_iterator_element_type(&entries_ty, span)
+                }
+                _ => Err(TypeError::new(TypeErrorKind::NotIterable(ty.clone()), span)),
+            },
+        }
+    }
+    
+    /// 两个变量的 for-in 中，一个元素拆出的两个值的类型
+    fn pair_types(elem_ty: &Type, span: Span) -> Result<(Type, Type), TypeError> {
+        match elem_ty {
+            Type::Tuple(types) if types.len() == 2 => Ok((types[0].clone(), types[1].clone())),
+            // 数组元素的长度在运行时检查
+            Type::Array { element_type, .. } | Type::Slice { element_type } => {
+                Ok((element_type.as_ref().clone(), element_type.as_ref().clone()))
+            }
+            Type::Dynamic | Type::Unknown => Ok((elem_ty.clone(), elem_ty.clone())),
+            _ => Err(TypeError::new(
+                TypeErrorKind::Other(format!("for-in with two variables requires pairs; elements are {}", elem_ty)),
+                span,
+            )),
+        }
+    }
+    
+    /// 检查模式
+    fn check_pattern(&mut self, pattern: &MatchPattern, expected_ty: &Type, span: Span) -> Result<(), TypeError> {
+        match pattern {
+            MatchPattern::Literal(expr) => {
+                let lit_ty = self.infer_expr(expr)?;
+                if !lit_ty.is_assignable_to(expected_ty) {
+                    return Err(TypeError::type_mismatch(expected_ty.clone(), lit_ty, span));
+                }
+            }
+            // 与常量同名的模式按常量值匹配，而不是绑定新变量
+            MatchPattern::Variable(name) if self.is_const_pattern(name) => {
+                let const_ty = self.consts[name.as_str()].ty();
+                if !const_ty.is_assignable_to(expected_ty) {
+                    return Err(TypeError::type_mismatch(expected_ty.clone(), const_ty, span));
+                }
+            }
+            MatchPattern::Variable(name) => {
+                self.env.define_variable(name.clone(), expected_ty.clone(), false)
+                    .map_err(|_| TypeError::new(
+                        TypeErrorKind::DuplicateDefinition(name.clone()),
+                        span,
+                    ))?;
+            }
+            MatchPattern::Wildcard => {}
+            MatchPattern::Or(patterns) => {
+                for p in patterns {
+                    self.check_pattern(p, expected_ty, span)?;
                 }
             }
             MatchPattern::Range { start, end, .. } => {
@@ -1685,10 +3961,45 @@ impl TypeChecker {
                         span,
                     ))?;
             }
+            MatchPattern::EnumVariant { enum_name, variant, fields } => {
+                let field_tys = match self.env.lookup_type(enum_name) {
+                    Some(TypeInfo::Enum(info)) => match info.variants.get(variant) {
+                        Some(info) => info.fields.iter().map(|(_, ty)| ty.clone()).collect::<Vec<_>>(),
+                        None => {
+                            let msg = format!("Enum '{}' has no variant '{}'", enum_name, variant);
+                            return Err(TypeError::new(TypeErrorKind::Other(msg), span));
+                        }
+                    },
+                    _ => {
+                        let msg = format!("'{}::{}(...)' pattern needs an enum, but '{}' is not an enum", enum_name, variant, enum_name);
+                        return Err(TypeError::new(TypeErrorKind::Other(msg), span));
+                    }
+                };
+                if fields.len() != field_tys.len() {
+                    let msg = format!(
+                        "pattern for {}::{} has {} fields, but the variant has {}",
+                        enum_name, variant, fields.len(), field_tys.len()
+                    );
+                    return Err(TypeError::new(TypeErrorKind::Other(msg), span));
+                }
+                let enum_ty = Type::Enum(enum_name.clone());
+                if !self.check_assignable(&enum_ty, expected_ty, span) {
+                    return Err(TypeError::type_mismatch(expected_ty.clone(), enum_ty, span));
+                }
+                for (field, field_ty) in fields.iter().zip(&field_tys) {
+                    self.check_pattern(field, field_ty, span)?;
+                }
+            }
         }
         Ok(())
     }
     
+    /// 名字是否指向顶级常量（没有被同名局部变量遮蔽）
+    fn is_const_pattern(&self, name: &str) -> bool {
+        self.consts.contains_key(name)
+            && self.env.lookup_variable(name).is_some_and(|var| var.is_const)
+    }
+    
     // 辅助方法：转换类型参数
     fn convert_type_params(&self, params: &[TypeParam]) -> Vec<GenericParam> {
         params.iter().map(|p| GenericParam {
@@ -1714,12 +4025,13 @@ impl TypeChecker {
     }
     
     // 辅助方法：收集 struct 方法
-    fn collect_struct_methods(&self, methods: &[crate::parser::ast::StructMethod]) -> HashMap<String, FunctionInfo> {
-        methods.iter().map(|m| (m.name.clone(), FunctionInfo {
+    fn collect_struct_methods(&self, methods: &[crate::parser::ast::StructMethod], is_static: bool) -> HashMap<String, FunctionInfo> {
+        methods.iter().filter(|m| m.is_static == is_static).map(|m| (m.name.clone(), FunctionInfo {
             name: m.name.clone(),
             type_params: Vec::new(),
             param_types: m.params.iter().map(|p| p.type_ann.ty.clone()).collect(),
             param_names: m.params.iter().map(|p| p.name.clone()).collect(),
+            param_spans: m.params.iter().map(|p| p.span).collect(),
             required_params: m.params.iter().filter(|p| p.default.is_none() && !p.variadic).count(),
             return_type: m.return_type.as_ref().map(|t| t.ty.clone()).unwrap_or(Type::Void),
             is_method: true,
@@ -1745,13 +4057,35 @@ impl TypeChecker {
             .collect()
     }
     
+    /// 没有类型注解的字段（常量除外，它们取初始值的类型）默认为 any：记录警告，严格模式下为错误
+    ///
+    /// 写上 `: any` 表示有意如此，不再报告
+    fn report_implicit_any_fields(&mut self, type_name: &str, fields: &[crate::parser::ast::ClassField]) {
+        for field in fields.iter().filter(|f| f.type_ann.is_none() && !f.is_const) {
+            let kind = TypeErrorKind::ImplicitAny {
+                declaration: format!("{}field '{}' of '{}'", if field.is_static { "static " } else { "" }, field.name, type_name),
+                hint: field.initializer.as_ref().and_then(initializer_hint),
+            };
+            let diagnostic = TypeError::new(kind, field.span)
+                .with_note(format!("write '{}: any' if it is meant to hold values of any type", field.name));
+            if self.context.strict_types {
+                self.errors.push(diagnostic.with_note("implicit any is an error under --strict-types"));
+            } else {
+                self.warnings.push(diagnostic);
+            }
+        }
+    }
+    
     // 辅助方法：收集 class 静态字段
-    fn collect_class_static_fields(&self, fields: &[crate::parser::ast::ClassField]) -> HashMap<String, FieldInfo> {
+    fn collect_class_static_fields(&self, type_name: &str, fields: &[crate::parser::ast::ClassField]) -> HashMap<String, FieldInfo> {
         fields.iter()
             .filter(|f| f.is_static)
             .map(|f| (f.name.clone(), FieldInfo {
                 name: f.name.clone(),
-                ty: f.type_ann.as_ref().map(|t| t.ty.clone()).unwrap_or(Type::Unknown),  // 无类型注解默认为 unknown
+                // 无类型注解时常量取求得的值的类型，其余默认为 unknown
+                ty: f.type_ann.as_ref().map(|t| t.ty.clone())
+                    .or_else(|| self.consts.get(&format!("{}::{}", type_name, f.name)).map(ConstValue::ty))
+                    .unwrap_or(Type::Unknown),
                 is_mutable: !f.is_const,
                 visibility: match f.visibility {
                     crate::parser::ast::Visibility::Public => Visibility::Public,
@@ -1772,6 +4106,7 @@ impl TypeChecker {
                 type_params: Vec::new(),
                 param_types: m.params.iter().map(|p| p.type_ann.ty.clone()).collect(),
                 param_names: m.params.iter().map(|p| p.name.clone()).collect(),
+                param_spans: m.params.iter().map(|p| p.span).collect(),
                 required_params: m.params.iter().filter(|p| p.default.is_none() && !p.variadic).count(),
                 return_type: m.return_type.as_ref().map(|t| t.ty.clone()).unwrap_or(Type::Void),
                 is_method: true,
@@ -1789,6 +4124,7 @@ impl TypeChecker {
                 type_params: Vec::new(),
                 param_types: m.params.iter().map(|p| p.type_ann.ty.clone()).collect(),
                 param_names: m.params.iter().map(|p| p.name.clone()).collect(),
+                param_spans: m.params.iter().map(|p| p.span).collect(),
                 required_params: m.params.iter().filter(|p| p.default.is_none() && !p.variadic).count(),
                 return_type: m.return_type.as_ref().map(|t| t.ty.clone()).unwrap_or(Type::Void),
                 is_method: false,
@@ -1804,6 +4140,7 @@ impl TypeChecker {
             type_params: Vec::new(),
             param_types: m.params.iter().map(|p| p.type_ann.ty.clone()).collect(),
             param_names: m.params.iter().map(|p| p.name.clone()).collect(),
+            param_spans: m.params.iter().map(|p| p.span).collect(),
             required_params: m.params.iter().filter(|p| p.default.is_none() && !p.variadic).count(),
             return_type: m.return_type.as_ref().map(|t| t.ty.clone()).unwrap_or(Type::Void),
             is_method: true,
@@ -1818,6 +4155,7 @@ impl TypeChecker {
             type_params: Vec::new(),
             param_types: m.params.iter().map(|p| p.type_ann.ty.clone()).collect(),
             param_names: m.params.iter().map(|p| p.name.clone()).collect(),
+            param_spans: m.params.iter().map(|p| p.span).collect(),
             required_params: m.params.iter().filter(|p| p.default.is_none() && !p.variadic).count(),
             return_type: m.return_type.as_ref().map(|t| t.ty.clone()).unwrap_or(Type::Void),
             is_method: true,
@@ -1826,6 +4164,79 @@ impl TypeChecker {
     }
     
     /// 检查语句是否一定会返回（即所有执行路径都以 return 结尾）
+    /// 条件为 when 时可以收窄的变量：`x != null` 为真（或 `x == null` 为假）时 x 不为 null
+    ///
+    /// `a && b` 为真时两侧的收窄都成立，`a || b` 为假时同理；`!` 翻转条件
+    fn condition_narrowings(&self, condition: &Expr, when: bool) -> Vec<(String, Type)> {
+        match condition {
+            Expr::Grouping { expr, .. } => self.condition_narrowings(expr, when),
+            Expr::Unary { op: UnaryOp::Not, operand, .. } => self.condition_narrowings(operand, !when),
+            Expr::Binary { left, op: op @ (BinOp::And | BinOp::Or), right, .. } => {
+                if (*op == BinOp::And) != when {
+                    return Vec::new();
+                }
+                let mut narrowings = self.condition_narrowings(left, when);
+                narrowings.extend(self.condition_narrowings(right, when));
+                narrowings
+            }
+            Expr::Binary { left, op: op @ (BinOp::Eq | BinOp::Ne), right, .. } => {
+                if (*op == BinOp::Ne) != when {
+                    return Vec::new();
+                }
+                let name = match (left.as_ref(), right.as_ref()) {
+                    (Expr::Identifier { name, .. }, Expr::Null { .. })
+                    | (Expr::Null { .. }, Expr::Identifier { name, .. }) => name,
+                    _ => return Vec::new(),
+                };
+                match self.env.variable_type(name) {
+                    Some(Type::Nullable(inner)) => vec![(name.clone(), *inner)],
+                    _ => Vec::new(),
+                }
+            }
+            _ => Vec::new(),
+        }
+    }
+    
+    /// 进入带有收窄的新作用域，返回值交给 `leave_narrowings`
+    fn enter_narrowings(&mut self, narrowings: Vec<(String, Type)>) -> bool {
+        if narrowings.is_empty() {
+            return false;
+        }
+        self.env.enter_scope();
+        for (name, ty) in narrowings {
+            self.env.narrow_variable(name, ty);
+        }
+        true
+    }
+    
+    fn leave_narrowings(&mut self, entered: bool) {
+        if entered {
+            self.env.leave_scope();
+        }
+    }
+    
+    /// 循环体会重复执行：进入循环前撤销循环内被赋值的变量的收窄，
+    /// 否则第二轮迭代会沿用第一轮赋值前的收窄
+    fn invalidate_assigned_in_loop(&mut self, stmt: &Stmt) {
+        let mut assigned = HashSet::new();
+        collect_assigned_variables(stmt, &mut assigned);
+        for name in &assigned {
+            self.env.invalidate_narrowing(name);
+        }
+    }
+    
+    /// 语句执行完后控制流一定不会继续到下一条语句（return、throw、break、continue）
+    fn stmt_exits(&self, stmt: &Stmt) -> bool {
+        match stmt {
+            Stmt::Break { .. } | Stmt::Continue { .. } => true,
+            Stmt::Block { statements, .. } => statements.iter().any(|s| self.stmt_exits(s)),
+            Stmt::If { then_branch, else_branch: Some(else_stmt), .. } => {
+                self.stmt_exits(then_branch) && self.stmt_exits(else_stmt)
+            }
+            _ => self.stmt_returns(stmt),
+        }
+    }
+    
     fn stmt_returns(&self, stmt: &Stmt) -> bool {
         match stmt {
             Stmt::Return { .. } => true,
@@ -1856,10 +4267,22 @@ impl TypeChecker {
                 }
                 arms.iter().all(|arm| self.stmt_returns(&arm.body))
             }
+            Stmt::Select { cases, .. } => {
+                // select 恰好执行一个分支
+                cases.iter().all(|case| self.stmt_returns(&case.body))
+            }
+            Stmt::Switch { cases, default, .. } => {
+                // 没有 default 时可能一个分支都不执行
+                default.as_ref().is_some_and(|default| self.stmt_returns(default))
+                    && cases.iter().all(|case| self.stmt_returns(&case.body))
+            }
             Stmt::TryCatch { try_block, catch_block, .. } => {
                 // try 和 catch 都一定返回，则整个 try-catch 一定返回
                 self.stmt_returns(try_block) && self.stmt_returns(catch_block)
             }
+            Stmt::Recovering { body, handler, .. } => {
+                self.stmt_returns(body) && self.stmt_returns(handler)
+            }
             Stmt::ForLoop { .. } | Stmt::ForIn { .. } => {
                 // 循环可能不执行，不能保证返回
                 false
@@ -1882,3 +4305,1118 @@ impl Default for TypeChecker {
         Self::new()
     }
 }
+
+/// 收集语句中被赋值的变量名（包括嵌套的块、循环和闭包）
+fn collect_assigned_variables(stmt: &Stmt, out: &mut HashSet<String>) {
+    match stmt {
+        Stmt::Expression { expr: e, .. } | Stmt::Print { expr: e, .. } | Stmt::Throw { value: e, .. } => {
+            collect_assigned_in_expr(e, out)
+        }
+        Stmt::ParallelAssign { targets, values, .. } => {
+            for target in targets {
+                if let Expr::Identifier { name, .. } = target {
+                    out.insert(name.clone());
+                } else {
+                    collect_assigned_in_expr(target, out);
+                }
+            }
+            for value in values {
+                collect_assigned_in_expr(value, out);
+            }
+        }
+        Stmt::VarDecl { initializer: Some(e), .. }
+        | Stmt::ConstDecl { initializer: e, .. }
+        | Stmt::Return { value: Some(e), .. } => collect_assigned_in_expr(e, out),
+        Stmt::Block { statements, .. } => {
+            for s in statements {
+                collect_assigned_variables(s, out);
+            }
+        }
+        Stmt::If { condition, then_branch, else_branch, .. } => {
+            collect_assigned_in_expr(condition, out);
+            collect_assigned_variables(then_branch, out);
+            if let Some(s) = else_branch {
+                collect_assigned_variables(s, out);
+            }
+        }
+        Stmt::ForLoop { initializer, condition, increment, body, .. } => {
+            if let Some(s) = initializer {
+                collect_assigned_variables(s, out);
+            }
+            for e in condition.iter().chain(increment.iter()) {
+                collect_assigned_in_expr(e, out);
+            }
+            collect_assigned_variables(body, out);
+        }
+        Stmt::ForIn { iterable, body, .. } => {
+            collect_assigned_in_expr(iterable, out);
+            collect_assigned_variables(body, out);
+        }
+        Stmt::While { condition, body, .. } => {
+            if let Some(e) = condition {
+                collect_assigned_in_expr(e, out);
+            }
+            collect_assigned_variables(body, out);
+        }
+        Stmt::Match { expr: subject, arms, .. } => {
+            collect_assigned_in_expr(subject, out);
+            for arm in arms {
+                if let Some(guard) = &arm.guard {
+                    collect_assigned_in_expr(guard, out);
+                }
+                collect_assigned_variables(&arm.body, out);
+            }
+        }
+        Stmt::Select { cases, .. } => {
+            for case in cases {
+                match &case.kind {
+                    SelectCaseKind::Receive { channel, .. } => collect_assigned_in_expr(channel, out),
+                    SelectCaseKind::Send { channel, value } => {
+                        collect_assigned_in_expr(channel, out);
+                        collect_assigned_in_expr(value, out);
+                    }
+                    SelectCaseKind::Default => {}
+                }
+                collect_assigned_variables(&case.body, out);
+            }
+        }
+        Stmt::Switch { expr: subject, cases, default, .. } => {
+            collect_assigned_in_expr(subject, out);
+            for case in cases {
+                for value in &case.values {
+                    collect_assigned_in_expr(value, out);
+                }
+                collect_assigned_variables(&case.body, out);
+            }
+            if let Some(default) = default {
+                collect_assigned_variables(default, out);
+            }
+        }
+        Stmt::TryCatch { try_block, catch_block, finally_block, .. } => {
+            collect_assigned_variables(try_block, out);
+            collect_assigned_variables(catch_block, out);
+            if let Some(s) = finally_block {
+                collect_assigned_variables(s, out);
+            }
+        }
+        Stmt::Recovering { body, handler, .. } => {
+            collect_assigned_variables(body, out);
+            collect_assigned_variables(handler, out);
+        }
+        // 嵌套的函数和类型定义不会给外层的局部变量赋值
+        _ => {}
+    }
+}
+
+fn collect_assigned_in_expr(expr: &Expr, out: &mut HashSet<String>) {
+    let mut walk = |e: &Expr| collect_assigned_in_expr(e, out);
+    match expr {
+        Expr::Assign { target, value, .. } => {
+            if let Expr::Identifier { name, .. } = target.as_ref() {
+                out.insert(name.clone());
+            } else {
+                collect_assigned_in_expr(target, out);
+            }
+            collect_assigned_in_expr(value, out);
+        }
+        Expr::PostIncrement { operand, .. } | Expr::PostDecrement { operand, .. } => {
+            if let Expr::Identifier { name, .. } = operand.as_ref() {
+                out.insert(name.clone());
+            }
+        }
+        Expr::Closure { body, .. } => collect_assigned_variables(body, out),
+        Expr::Binary { left, right, .. } | Expr::NullCoalesce { left, right, .. } => {
+            walk(left);
+            walk(right);
+        }
+        Expr::Index { object, index, .. } => {
+            walk(object);
+            walk(index);
+        }
+        Expr::Unary { operand: e, .. }
+        | Expr::Grouping { expr: e, .. }
+        | Expr::Go { call: e, .. }
+        | Expr::Member { object: e, .. }
+        | Expr::SafeMember { object: e, .. }
+        | Expr::NonNullMember { object: e, .. }
+        | Expr::Cast { expr: e, .. }
+        | Expr::TypeCheck { expr: e, .. } => walk(e),
+        Expr::Call { callee, args, .. } => {
+            walk(callee);
+            for (_, arg) in args {
+                walk(arg);
+            }
+        }
+        Expr::IfExpr { condition, then_branch, else_branch, .. } => {
+            walk(condition);
+            walk(then_branch);
+            walk(else_branch);
+        }
+        Expr::Range { start, end, .. } => {
+            for e in start.iter().chain(end.iter()) {
+                walk(e);
+            }
+        }
+        Expr::ChannelNew { capacity: Some(e), .. } => walk(e),
+        Expr::Array { elements, .. } => {
+            for e in elements {
+                walk(e);
+            }
+        }
+        Expr::New { args, fields, .. } => {
+            for e in args.iter().chain(fields.iter().flatten().map(|(_, e)| e)) {
+                walk(e);
+            }
+        }
+        Expr::MapLiteral { entries, .. } => {
+            for (key, value) in entries {
+                walk(key);
+                walk(value);
+            }
+        }
+        Expr::StructLiteral { fields, .. } => {
+            for (_, value) in fields {
+                walk(value);
+            }
+        }
+        Expr::StringInterpolation { parts, .. } => {
+            for part in parts {
+                if let crate::parser::ast::StringInterpPart::Expr(e) = part {
+                    walk(e);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 将类型中的泛型参数替换为实例化类型
+/// 声明中的泛型参数可能被解析为 `Class(name)` 或 `TypeParameter`
+pub(super) fn instantiate_type_params(ty: &Type, instantiation: &HashMap<String, Type>) -> Type {
+    let recurse = |t: &Type| instantiate_type_params(t, instantiation);
+    match ty {
+        Type::Class(name) | Type::TypeParameter { name, .. } if instantiation.contains_key(name) => {
+            instantiation[name].clone()
+        }
+        Type::Array { element_type, size } => Type::Array {
+            element_type: Box::new(recurse(element_type)),
+            size: *size,
+        },
+        Type::Slice { element_type } => Type::Slice {
+            element_type: Box::new(recurse(element_type)),
+        },
+        Type::Map { key_type, value_type } => Type::Map {
+            key_type: Box::new(recurse(key_type)),
+            value_type: Box::new(recurse(value_type)),
+        },
+        Type::Tuple(types) => Type::Tuple(types.iter().map(recurse).collect()),
+        Type::Function { param_types, return_type, required_params } => Type::Function {
+            param_types: param_types.iter().map(recurse).collect(),
+            return_type: Box::new(recurse(return_type)),
+            required_params: *required_params,
+        },
+        Type::Nullable(inner) => Type::Nullable(Box::new(recurse(inner))),
+        Type::Pointer(inner) => Type::Pointer(Box::new(recurse(inner))),
+        Type::Generic { base_type, type_args } => Type::Generic {
+            base_type: Box::new(recurse(base_type)),
+            type_args: type_args.iter().map(recurse).collect(),
+        },
+        _ => ty.clone(),
+    }
+}
+
+/// 从字段初始值的字面形式推测类型，作为隐式 any 警告中的建议（不做完整推导，推测不出时为 None）
+fn initializer_hint(expr: &Expr) -> Option<Type> {
+    match expr {
+        Expr::Integer { .. } => Some(Type::Int),
+        Expr::Float { .. } => Some(Type::F64),
+        Expr::String { .. } | Expr::StringInterpolation { .. } => Some(Type::String),
+        Expr::Bool { .. } => Some(Type::Bool),
+        Expr::Char { .. } => Some(Type::Char),
+        Expr::Grouping { expr, .. } => initializer_hint(expr),
+        Expr::Unary { op: UnaryOp::Neg, operand, .. } => initializer_hint(operand).filter(Type::is_numeric),
+        Expr::Unary { op: UnaryOp::Not, .. } => Some(Type::Bool),
+        Expr::New { class_name, .. } => Some(Type::Class(class_name.clone())),
+        // 元素的推测一致时才给出数组类型
+        Expr::Array { elements, .. } => {
+            let mut hints = elements.iter().map(initializer_hint);
+            let first = hints.next()??;
+            hints
+                .all(|hint| hint.as_ref() == Some(&first))
+                .then(|| Type::Slice { element_type: Box::new(first) })
+        }
+        _ => None,
+    }
+}
+
+/// 收集类型中引用到的类名
+fn collect_class_names(ty: &Type, out: &mut Vec<String>) {
+    match ty {
+        Type::Class(name) => out.push(name.clone()),
+        Type::Array { element_type, .. } | Type::Slice { element_type } | Type::Channel { element_type } => {
+            collect_class_names(element_type, out)
+        }
+        Type::Map { key_type, value_type } => {
+            collect_class_names(key_type, out);
+            collect_class_names(value_type, out);
+        }
+        Type::Tuple(types) => types.iter().for_each(|t| collect_class_names(t, out)),
+        Type::Function { param_types, return_type, .. } => {
+            param_types.iter().for_each(|t| collect_class_names(t, out));
+            collect_class_names(return_type, out);
+        }
+        Type::Nullable(inner) | Type::Pointer(inner) => collect_class_names(inner, out),
+        Type::Generic { base_type, type_args } => {
+            collect_class_names(base_type, out);
+            type_args.iter().for_each(|t| collect_class_names(t, out));
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::error::TypePathSegment;
+    use crate::lexer::Scanner;
+    use crate::parser::Parser;
+    use crate::i18n::Locale;
+
+    fn check(source: &str) -> Result<(), Vec<TypeError>> {
+        let mut scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens();
+        let mut parser = Parser::new(tokens, Locale::En);
+        let program = parser.parse().expect("parse failed");
+        TypeChecker::new().check_program(&program)
+    }
+
+    fn first_error(source: &str) -> TypeError {
+        check(source).expect_err("expected a type error").remove(0)
+    }
+
+    #[test]
+    fn test_annotated_empty_literals() {
+        check(r#"
+func main() {
+    var m: map[string]int = {}
+    m["a"] = 1
+    var xs: int[] = []
+    xs.push(2)
+}
+"#).unwrap();
+
+        let err = first_error(r#"
+func main() {
+    var m: map[string]int = {}
+    m["a"] = "one"
+}
+"#);
+        assert!(matches!(err.kind, TypeErrorKind::TypeMismatch { .. }), "{}", err);
+    }
+
+    #[test]
+    fn test_empty_literal_inferred_from_usage() {
+        // 从 push 推导
+        check(r#"
+func main() {
+    var xs = []
+    xs.push(1)
+    var n: int = xs[0]
+}
+"#).unwrap();
+
+        // 从索引赋值推导
+        check(r#"
+func main() {
+    var m = {}
+    m["k"] = 1.5
+}
+"#).unwrap();
+
+        // 从参数类型推导
+        check(r#"
+func total(xs: int[]) int {
+    return 0
+}
+func main() {
+    var xs = []
+    total(xs)
+}
+"#).unwrap();
+
+        // 推导后的类型约束后续使用
+        let err = first_error(r#"
+func main() {
+    var xs = []
+    xs.push(1)
+    xs.push("two")
+}
+"#);
+        assert!(matches!(err.kind, TypeErrorKind::TypeMismatch { .. }), "{}", err);
+    }
+
+    #[test]
+    fn test_cannot_infer_empty_literal() {
+        let err = first_error("func main() {\n    var xs = []\n}\n");
+        assert!(matches!(err.kind, TypeErrorKind::CannotInferEmptyLiteral));
+        assert_eq!(err.span.line, 2);
+        assert_eq!(
+            err.to_string(),
+            "cannot infer the element type of this empty literal; add a type annotation"
+        );
+
+        let err = first_error("func main() {\n    var m = {}\n}\n");
+        assert!(matches!(err.kind, TypeErrorKind::CannotInferEmptyLiteral));
+    }
+
+    #[test]
+    fn test_heterogeneous_literal_errors() {
+        let err = first_error("func main() {\n    var xs = [1, 2, \"three\", 4]\n}\n");
+        match &err.kind {
+            TypeErrorKind::LiteralElementMismatch { index, expected, actual, .. } => {
+                assert_eq!(*index, 2);
+                assert_eq!(*expected, Type::Int);
+                assert_eq!(*actual, Type::String);
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert_eq!((err.span.line, err.span.column), (2, 21));
+        assert!(err.to_string().contains("'string'"));
+        assert!(err.to_string().contains("'int'"));
+
+        let err = first_error(r#"
+func main() {
+    var m = {"a": 1, "b": true}
+}
+"#);
+        match &err.kind {
+            TypeErrorKind::LiteralElementMismatch { context, index, .. } => {
+                assert_eq!(context, "map literal value");
+                assert_eq!(*index, 1);
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_nested_literal_numeric_promotion() {
+        // int 与 f64 按数值提升规则统一为 f64
+        check(r#"
+func main() {
+    var xs: f64[][] = [[1], [2.0]]
+    var ys: f64[] = [1, 2.5]
+    var zs: int[][] = [[], [1]]
+}
+"#).unwrap();
+
+        let err = first_error(r#"
+func main() {
+    var xs = [[1], ["a"]]
+}
+"#);
+        match &err.kind {
+            TypeErrorKind::LiteralElementMismatch { expected, actual, .. } => {
+                assert_eq!(expected.to_string(), "int[]");
+                assert_eq!(actual.to_string(), "string[]");
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_json_calls_are_typed() {
+        check(r#"
+import std.json
+func main() {
+    var data = Json.parse("{}")
+    var name: string = Json::stringify(data["name"], true)
+    println(name)
+}
+"#).unwrap();
+
+        let err = first_error("import std.json\nfunc main() {\n    var n: int = Json.stringify(1)\n}\n");
+        assert!(matches!(err.kind, TypeErrorKind::TypeMismatch { .. }), "{:?}", err.kind);
+        assert!(check("func main() {\n    Json.parse(\"1\")\n}\n").is_err());
+    }
+
+    #[test]
+    fn test_sync_types_are_typed() {
+        check(r#"
+import std.sync.*
+func main() {
+    var hits = new Atomic()
+    var next: int = hits.add(1)
+    var swapped: bool = hits.compareAndSwap(next, 0)
+    var busy = new AtomicFlag()
+    if !busy.testAndSet() {
+        busy.clear()
+    }
+    var limiter = new RateLimiter(10, 5)
+    var allowed: bool = limiter.tryAcquire()
+    println(swapped && allowed)
+}
+"#).unwrap();
+
+        let err = first_error("import std.sync.Atomic\nfunc main() {\n    var a = new Atomic(0)\n    var s: string = a.get()\n}\n");
+        assert!(matches!(err.kind, TypeErrorKind::TypeMismatch { .. }), "{:?}", err.kind);
+        assert!(check("import std.sync.RateLimiter\nfunc main() {\n    var r = new RateLimiter(1)\n}\n").is_err());
+    }
+
+    #[test]
+    fn test_future_types_are_typed() {
+        check(r#"
+import std.async.Future
+import std.time.Time
+import std.net.dns.Dns
+import std.net.http.HttpClient
+func main() {
+    var client = new HttpClient()
+    var pages: Future = client.getAsync("http://localhost/")
+    var all = Future.all([pages, Dns.resolveAsync("localhost"), Time.after(10)])
+    var done: bool = all.isDone()
+    var results = all.await()
+    var ips: string[] = Dns.resolve("localhost")
+}
+"#).unwrap();
+
+        let err = first_error("import std.time.Time\nfunc main() {\n    var f: int = Time.after(10)\n}\n");
+        assert!(matches!(err.kind, TypeErrorKind::TypeMismatch { .. }), "{:?}", err.kind);
+        assert!(check("import std.async.Future\nfunc main() {\n    Future.race()\n}\n").is_err());
+    }
+
+    #[test]
+    fn test_channel_and_select_are_typed() {
+        check(r#"
+func main() {
+    var numbers: chan<int> = chan<int>()
+    var names = chan<string>()
+    select {
+        case var n = numbers.receive() => {
+            var doubled: int = (n ?? 0) * 2
+        }
+        case names.send("q") => {}
+        default => {}
+    }
+}
+"#).unwrap();
+
+        let err = first_error("func main() {\n    var c = chan<int>()\n    select {\n        case var s = c.receive() => {\n            var t: string = s\n        }\n    }\n}\n");
+        assert!(matches!(err.kind, TypeErrorKind::TypeMismatch { .. }), "{:?}", err.kind);
+        let err = first_error("func main() {\n    var c = chan<int>()\n    c.send(\"x\")\n}\n");
+        assert!(matches!(err.kind, TypeErrorKind::TypeMismatch { .. }), "{:?}", err.kind);
+        assert!(check("func main() {\n    var c = 1\n    select {\n        case c.receive() => {}\n    }\n}\n").is_err());
+
+        // 通道关闭且取空后 receive() 返回 null：结果和 select 的绑定都是可空类型
+        let err = first_error("func main() {\n    var closed = chan<int>(1)\n    closed.close()\n    var v = closed.receive() + 1\n}\n");
+        assert!(matches!(err.kind, TypeErrorKind::IncompatibleTypes { .. }), "{:?}", err.kind);
+        let err = first_error("func main() {\n    var c = chan<int>()\n    select {\n        case var n = c.receive() => {\n            var m: int = n\n        }\n    }\n}\n");
+        assert!(matches!(err.kind, TypeErrorKind::TypeMismatch { .. }), "{:?}", err.kind);
+        check("func main() {\n    var c = chan<int>(1)\n    c.close()\n    var v: int? = c.receive()\n    var w: int = (c.receive() ?? 0) + 1\n}\n").unwrap();
+
+        // 缓冲通道：容量必须是整数，for-in 按元素类型绑定
+        check("func main() {\n    var c = chan<int>(4)\n    c.close()\n    var closed: bool = c.isClosed()\n    for x in c {\n        var y: int = x\n    }\n}\n").unwrap();
+        let err = first_error("func main() {\n    var c = chan<int>(\"4\")\n}\n");
+        assert!(matches!(err.kind, TypeErrorKind::TypeMismatch { .. }), "{:?}", err.kind);
+    }
+
+    #[test]
+    fn test_nested_mismatch_reports_type_path() {
+        let err = first_error("func main() {\n    var m: map[string]int[] = {\"a\": [\"x\"]}\n}\n");
+        match &err.kind {
+            TypeErrorKind::TypeMismatch { expected, actual } => {
+                assert_eq!(*expected, Type::Int);
+                assert_eq!(*actual, Type::String);
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        let segments: Vec<_> = err.path.iter().map(|step| step.segment.clone()).collect();
+        assert_eq!(segments, vec![TypePathSegment::ElementType, TypePathSegment::MapValue]);
+        // 期望类型来自变量注解
+        assert_eq!(err.labels.len(), 1);
+        assert_eq!(err.labels[0].0.line, 2);
+        assert!(err.render(None).contains("in the value type of `map[string]int[]`"), "{}", err.render(None));
+    }
+
+    #[test]
+    fn test_argument_mismatch_points_at_parameter() {
+        let err = first_error(r#"
+func greet(prefix: string, count: int) string {
+    return prefix
+}
+
+func main() {
+    greet("hi", "three")
+}
+"#);
+        assert!(matches!(err.kind, TypeErrorKind::TypeMismatch { .. }), "{:?}", err.kind);
+        assert_eq!(err.span.line, 7);
+        assert_eq!(err.labels.len(), 1);
+        let (span, label) = &err.labels[0];
+        assert_eq!(span.line, 2);
+        assert!(label.contains("`count`"), "{}", label);
+    }
+
+    #[test]
+    fn test_generic_call_reports_inference_provenance() {
+        let source = r#"
+func pick<T>(a: T, b: T) T {
+    return a
+}
+
+func main() {
+    var ok: int = pick(1, 2)
+    var bad = pick(1, "x")
+}
+"#;
+        let err = first_error(source);
+        match &err.kind {
+            TypeErrorKind::TypeMismatch { expected, actual } => {
+                assert_eq!(*expected, Type::Int);
+                assert_eq!(*actual, Type::String);
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert_eq!(err.span.line, 8);
+        assert!(err.labels.iter().any(|(span, label)| span.line == 2 && label.contains("`b`")), "{:?}", err.labels);
+        assert!(
+            err.notes.iter().any(|n| n == "T was inferred as int from argument 1 at line 8"),
+            "{:?}",
+            err.notes
+        );
+
+        // 推导出的返回类型参与后续检查
+        let err = first_error("func pick<T>(a: T, b: T) T {\n    return a\n}\nfunc main() {\n    var s: string = pick(1, 2)\n}\n");
+        assert!(matches!(err.kind, TypeErrorKind::TypeMismatch { .. }), "{:?}", err.kind);
+    }
+
+    #[test]
+    fn test_const_declarations() {
+        // 常量取字面量类型，可用于 match 分支和数组长度
+        check(r#"
+const SIZE = 4 * 2
+const NAME: string = "q" + "lang"
+
+class Status {
+    const OK = 200
+    const CREATED = OK + 1
+}
+
+struct Point {
+    x: int
+    static func origin() Point {
+        return Point { x: 0 }
+    }
+}
+
+func main() {
+    var buf: int[SIZE]
+    var n: int = SIZE
+    var code = 201
+    match code {
+        Status::OK => { println(NAME) }
+        Status::CREATED => { println("created") }
+        SIZE => { println("size") }
+        _ => {}
+    }
+    var p: Point = Point::origin()
+}
+"#).unwrap();
+
+        // 类常量的类型来自它的值
+        let err = first_error("class Status {\n    const OK = 200\n}\nfunc main() {\n    var s: string = Status::OK\n}\n");
+        assert!(matches!(err.kind, TypeErrorKind::TypeMismatch { .. }), "{:?}", err.kind);
+
+        // 无法在编译期求值的初始值
+        let err = first_error("func now() int {\n    return 1\n}\nconst STARTED = now() + 1\nfunc main() {}\n");
+        assert!(matches!(&err.kind, TypeErrorKind::NonConstantInitializer(name) if name.contains("STARTED")), "{:?}", err.kind);
+        assert_eq!(err.span.line, 4);
+    }
+
+    #[test]
+    fn test_static_field_assignment() {
+        let source = "class Counter {\n    static var total: int = 0\n    const LIMIT = 10\n}\n";
+        check(&format!("{}func main() {{\n    Counter::total = Counter::total + 1\n    Counter::total *= 2\n}}\n", source)).unwrap();
+
+        let err = first_error(&format!("{}func main() {{\n    Counter::LIMIT = 3\n}}\n", source));
+        assert!(matches!(&err.kind, TypeErrorKind::ConstantReassignment(name) if name == "Counter::LIMIT"), "{:?}", err.kind);
+        let err = first_error(&format!("{}func main() {{\n    Counter::count = 3\n}}\n", source));
+        assert!(matches!(&err.kind, TypeErrorKind::UndefinedField { field_name, .. } if field_name == "count"), "{:?}", err.kind);
+        let err = first_error(&format!("{}func main() {{\n    Counter::total = \"many\"\n}}\n", source));
+        assert!(matches!(err.kind, TypeErrorKind::TypeMismatch { .. }), "{:?}", err.kind);
+    }
+
+    #[test]
+    fn test_compound_assignment_types() {
+        check(r#"
+func main() {
+    var n = 1
+    n += 2
+    n <<= 1
+    var x = 1.5
+    x *= 2
+    var s = "a"
+    s += "b"
+    var xs = [1, 2]
+    xs[0] -= 1
+    var m = {"a": 1}
+    m["a"] %= 2
+}
+"#).unwrap();
+
+        // 结果类型不能赋回目标
+        let err = first_error("func main() {\n    var n = 1\n    n += 1.5\n}\n");
+        assert!(matches!(err.kind, TypeErrorKind::TypeMismatch { .. }), "{:?}", err.kind);
+
+        // 运算本身不成立
+        let err = first_error("func main() {\n    var s = \"a\"\n    s -= \"b\"\n}\n");
+        assert!(matches!(err.kind, TypeErrorKind::IncompatibleTypes { .. }), "{:?}", err.kind);
+        let err = first_error("func main() {\n    var x = 1.5\n    x |= 1\n}\n");
+        assert!(matches!(err.kind, TypeErrorKind::IncompatibleTypes { .. }), "{:?}", err.kind);
+    }
+
+    #[test]
+    fn test_conditional_expression_types() {
+        check(r#"
+func main() {
+    var n = 3
+    var s: string = n > 0 ? "pos" : n < 0 ? "neg" : "zero"
+    var f: f64 = n > 0 ? 1 : 2.5
+    var maybe: string? = n > 10 ? "big" : null
+}
+"#).unwrap();
+
+        let err = first_error("func main() {\n    var v = true ? 1 : \"one\"\n}\n");
+        assert!(matches!(err.kind, TypeErrorKind::IncompatibleTypes { .. }), "{:?}", err.kind);
+
+        let err = first_error("func main() {\n    var v = 1 ? 1 : 2\n}\n");
+        assert!(matches!(err.kind, TypeErrorKind::TypeMismatch { .. }), "{:?}", err.kind);
+    }
+    fn warnings(source: &str) -> Vec<TypeError> {
+        let mut scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens();
+        let mut parser = Parser::new(tokens, Locale::En);
+        let program = parser.parse().expect("parse failed");
+        let mut checker = TypeChecker::new();
+        checker.check_program(&program).expect("unexpected type errors");
+        checker.warnings().to_vec()
+    }
+
+    #[test]
+    fn test_shadowed_parameter_warning() {
+        let shadowed = warnings(r#"
+func scale(x: int, factor: int) int {
+    if factor > 1 {
+        var x = factor * 2
+        return x
+    }
+    if x > 100 {
+        const factor = 3
+        return x * factor
+    }
+    return x
+}
+func main() {
+    var f = func(n: int) int {
+        if n > 0 {
+            var n = 1
+            return n
+        }
+        var x = 2
+        return n + x
+    }
+}
+"#);
+        let found: Vec<String> = shadowed.iter().map(|w| w.to_string()).collect();
+        assert_eq!(found, vec![
+            "local variable 'x' shadows parameter 'x'",
+            "local variable 'factor' shadows parameter 'factor'",
+            "local variable 'n' shadows parameter 'n'",
+        ]);
+        assert!(matches!(shadowed[0].kind, TypeErrorKind::ShadowedParameter(_)));
+        assert_eq!(shadowed[0].span.line, 4);
+        assert_eq!(shadowed[0].labels[0].0.line, 2);
+    }
+
+    #[test]
+    fn test_implicit_any_fields() {
+        let annotated = r#"
+class Counter {
+    var n: int = 0
+    var items: string[] = []
+    var extra: any = null
+    static var total: int = 0
+    static const LIMIT = 10
+}
+func main() {}
+"#;
+        assert!(warnings(annotated).is_empty());
+
+        let source = r#"
+class Counter {
+    var n: int = 0
+    var items = ["a", "b"]
+}
+func main() {}
+"#;
+        let found = warnings(source);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].kind.code(), Some("W0001"));
+        assert_eq!(found[0].to_string(), "field 'items' of 'Counter' is implicitly any; based on its initializer it may be string[] — annotate it");
+        assert_eq!((found[0].span.line, found[0].span.column), (4, 9));
+
+        // 推测不出类型时只要求注解
+        let unhinted = warnings("class C {\n    static var cache = null\n}\nfunc main() {}\n");
+        assert_eq!(unhinted[0].to_string(), "static field 'cache' of 'C' is implicitly any; annotate it");
+
+        // 严格模式下是错误
+        let program = Parser::new(Scanner::new(source).scan_tokens(), Locale::En).parse().unwrap();
+        let mut checker = TypeChecker::with_context(CompileContext { strict_types: true, ..CompileContext::default() });
+        let errors = checker.check_program(&program).expect_err("strict mode should reject implicit any");
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(errors[0].kind, TypeErrorKind::ImplicitAny { .. }));
+        assert!(checker.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_override_checks() {
+        let ok = r#"
+class A {
+    func m(x: int) int { return x }
+}
+class B extends A {
+    override func m(x: int) int { return x + 1 }
+}
+func main() {}
+"#;
+        assert!(warnings(ok).is_empty());
+
+        let typo = first_error(r#"
+class A {
+    func toString() string { return "A" }
+}
+class B extends A {
+    override func tostring() string { return "B" }
+}
+func main() {}
+"#);
+        assert_eq!(typo.to_string(), "no method 'tostring' to override; did you mean 'toString'?");
+
+        let missing = warnings(r#"
+class A {
+    func m() int { return 1 }
+}
+class B extends A {
+    func m() int { return 2 }
+}
+func main() {}
+"#);
+        assert_eq!(missing.len(), 1);
+        assert!(matches!(missing[0].kind, TypeErrorKind::MissingOverride { .. }), "{:?}", missing[0].kind);
+
+        let arity = first_error(r#"
+class A {
+    func m(x: int) int { return x }
+}
+class B extends A {
+    override func m() int { return 2 }
+}
+func main() {}
+"#);
+        assert!(matches!(arity.kind, TypeErrorKind::OverrideSignatureMismatch { .. }), "{:?}", arity.kind);
+    }
+
+    #[test]
+    fn test_final_method_across_levels() {
+        let err = first_error(r#"
+class A {
+    final func m() int { return 1 }
+}
+class B extends A {}
+class C extends B {
+    override func m() int { return 2 }
+}
+func main() {}
+"#);
+        assert!(matches!(err.kind, TypeErrorKind::OverrideFinal { .. }), "{:?}", err.kind);
+        assert_eq!(err.labels.len(), 1);
+        assert_eq!(err.labels[0].0.line, 3);
+    }
+
+    #[test]
+    fn test_abstract_methods_must_be_implemented() {
+        let shapes = r#"
+abstract class Shape {
+    abstract func area() f64
+    abstract func name() string
+}
+abstract class Named extends Shape {
+    override func name() string { return "named" }
+}
+"#;
+        // 由中间的抽象类实现的方法也算实现，子类型的值可以调用继承的方法
+        check(&format!("{}class Circle extends Named {{
+    override func area() f64 {{ return 3.0 }}
+}}
+func main() {{
+    var c = new Circle()
+    var s: string = c.name()
+}}
+", shapes)).unwrap();
+
+        let err = first_error(&format!("{}class Square extends Named {{}}
+func main() {{}}
+", shapes));
+        assert_eq!(err.to_string(), "class 'Square' must implement abstract method Shape::area");
+        assert_eq!(err.span.line, 9);
+        assert_eq!(err.labels.len(), 1);
+        assert_eq!(err.labels[0].0.line, 3);
+    }
+
+    #[test]
+    fn test_super_init_required() {
+        let base = "class Base {\n    var id: int\n    func init(id: int, tag: string = \"x\") {\n        this.id = id\n    }\n}\n";
+        check(&format!("{}class Ok extends Base {{\n    func init() {{\n        super.init(1)\n    }}\n}}\nfunc main() {{}}\n", base)).unwrap();
+        // 父类的 init 没有必需参数时可以省略，隐式调用
+        check("class A {\n    func init(n: int = 1) {}\n}\nclass B extends A {}\nfunc main() {\n    var b = new B()\n}\n").unwrap();
+        
+        let err = first_error(&format!("{}class Bad extends Base {{}}\nfunc main() {{}}\n", base));
+        assert_eq!(err.to_string(), "class 'Bad' must declare init and call super.init(...): 'Base::init' requires 1 argument");
+        assert_eq!(err.span.line, 7);
+        
+        // 没有声明 init 的类只能无参构造
+        let err = first_error("class P {}\nfunc main() {\n    var p = new P(1)\n}\n");
+        assert_eq!(err.span.line, 3);
+    }
+
+    #[test]
+    fn test_main_exit_code() {
+        check("func main() int {\n    return 3\n}\n").unwrap();
+        check("import std.os\nfunc main() {\n    for arg in Os.args() {\n        var s: string = arg\n    }\n}\n").unwrap();
+        let err = first_error("func main() string {\n    return \"\"\n}\n");
+        assert!(matches!(err.kind, TypeErrorKind::InvalidMainSignature), "{:?}", err.kind);
+    }
+
+    const USER: &str = "struct User {\n    name: string\n}\n";
+
+    #[test]
+    fn test_null_check_narrows() {
+        let ok = |body: &str| {
+            check(&format!("{}func show(u: User?, fallback: User) string {{\n{}\n}}\nfunc main() {{}}\n", USER, body))
+        };
+        // 分支内、提前返回之后、&& 右侧、|| 右侧、else 分支、while 条件
+        ok("    if u != null {\n        return u.name\n    }\n    return \"\"").unwrap();
+        ok("    if u == null {\n        return \"\"\n    }\n    return u.name").unwrap();
+        ok("    if u != null && u.name == \"a\" {\n        return u.name\n    }\n    return \"\"").unwrap();
+        ok("    if u == null || u.name == \"\" {\n        return \"\"\n    }\n    return u.name").unwrap();
+        ok("    if !(null == u) {\n        return u.name\n    } else {\n        return \"\"\n    }").unwrap();
+        ok("    if u == null {\n        return \"\"\n    } else {\n        return u.name\n    }").unwrap();
+        ok("    var s = u != null ? u.name : \"\"\n    return s").unwrap();
+        ok("    var v = u\n    for v != null {\n        return v.name\n    }\n    return \"\"").unwrap();
+
+        // 条件不足以排除 null：|| 为真、else 之后、没有提前返回
+        for body in [
+            "    if u != null || true {\n        return u.name\n    }\n    return \"\"",
+            "    if u != null {\n        return \"\"\n    }\n    return u.name",
+            "    if u == null {\n        println(\"none\")\n    }\n    return u.name",
+        ] {
+            assert!(ok(body).is_err(), "{}", body);
+        }
+    }
+
+    #[test]
+    fn test_reassignment_invalidates_narrowing() {
+        let ok = |body: &str| {
+            check(&format!("{}func show(u: User?, other: User?, fallback: User) string {{\n{}\n}}\nfunc main() {{}}\n", USER, body))
+        };
+        ok("    var v = u\n    if v != null {\n        v = fallback\n        return v.name\n    }\n    return \"\"").unwrap();
+        for body in [
+            // 分支内重新赋值为可能为 null 的值
+            "    var v = u\n    if v != null {\n        v = other\n        return v.name\n    }\n    return \"\"",
+            // 提前返回之后重新赋值
+            "    var v = u\n    if v == null {\n        return \"\"\n    }\n    v = other\n    return v.name",
+            // 循环的下一轮可能看到赋值后的值
+            "    var v = u\n    if v == null {\n        return \"\"\n    }\n    for i in 0..3 {\n        var s = v.name\n        v = other\n    }\n    return \"\"",
+            // 闭包可能在任何时候改写变量
+            "    var v = u\n    if v == null {\n        return \"\"\n    }\n    var reset = func() {\n        v = null\n    }\n    return v.name",
+        ] {
+            assert!(ok(body).is_err(), "{}", body);
+        }
+    }
+
+    #[test]
+    fn test_linked_list_traversal_keeps_narrowing() {
+        let ok = |body: &str| {
+            let node = "class Node {\n    var value: int\n    var next: Node?\n    func init(value: int) {\n        this.value = value\n        this.next = null\n    }\n}\n";
+            check(&format!("{}func walk(head: Node?) int {{\n    var c = head\n    var sum = 0\n{}\n    return sum\n}}\nfunc main() {{}}\n", node, body))
+        };
+        // 右侧在赋值之前求值，仍然可以使用收窄后的类型
+        ok("    if c != null {\n        c = c.next\n    }").unwrap();
+        ok("    for c != null {\n        sum = sum + c.value\n        c = c.next\n    }").unwrap();
+        // 赋值之后 c 又可能为 null
+        assert!(ok("    if c != null {\n        c = c.next\n        sum = c.value\n    }").is_err());
+    }
+    
+    const COMPARABLE: &str = "interface Comparable {
+    func compare(other: int) int
+}
+class Base implements Comparable {
+    var v: int
+    func init(v: int) { this.v = v }
+    func compare(other: int) int { return this.v - other }
+}
+class Num extends Base { func init(v: int) { super.init(v) } }
+class Plain {}
+func best<T: Comparable>(a: T, b: T) T {
+    if a.compare(0) > 0 {
+        return a
+    }
+    return b
+}
+class Box<T: Comparable> {
+    var item: T
+}
+";
+    
+    #[test]
+    fn test_generic_bounds_at_call_site() {
+        check(&format!("{}func main() {{\n    var n: Num = best(new Num(1), new Num(2))\n    var b: Box<Num>? = null\n}}\n", COMPARABLE)).unwrap();
+        
+        let err = first_error(&format!("{}func main() {{\n    best(new Plain(), new Plain())\n}}\n", COMPARABLE));
+        assert!(matches!(&err.kind, TypeErrorKind::ConstraintNotSatisfied { type_param, constraint, actual_type }
+            if type_param == "T" && constraint == "Comparable" && *actual_type == Type::Class("Plain".to_string())), "{:?}", err.kind);
+        assert_eq!((err.span.line, err.span.column), (21, 5));
+        
+        let err = first_error(&format!("{}func main() {{\n    best(1, 2)\n}}\n", COMPARABLE));
+        assert!(matches!(err.kind, TypeErrorKind::ConstraintNotSatisfied { .. }), "{:?}", err.kind);
+        let err = first_error(&format!("{}func main() {{\n    var b: Box<Plain>? = null\n}}\n", COMPARABLE));
+        assert!(matches!(err.kind, TypeErrorKind::ConstraintNotSatisfied { .. }), "{:?}", err.kind);
+    }
+    
+    #[test]
+    fn test_generic_body_uses_bound_methods() {
+        // 约束声明的方法可以调用，值可以传给约束相同的泛型函数
+        check(&format!("{}func twice<T: Comparable>(a: T) T {{\n    var c = a.compare(1)\n    return best(a, a)\n}}\nfunc main() {{}}\n", COMPARABLE)).unwrap();
+        
+        let err = first_error(&format!("{}func bad<T>(a: T) int {{\n    return a.compare(1)\n}}\nfunc main() {{}}\n", COMPARABLE));
+        assert!(matches!(&err.kind, TypeErrorKind::MethodNotInBounds { bounds, .. } if bounds.is_empty()), "{:?}", err.kind);
+        let err = first_error(&format!("{}func bad<T: Comparable>(a: T) int {{\n    return a.size()\n}}\nfunc main() {{}}\n", COMPARABLE));
+        assert!(matches!(&err.kind, TypeErrorKind::MethodNotInBounds { method_name, bounds, .. }
+            if method_name == "size" && bounds == &["Comparable".to_string()]), "{:?}", err.kind);
+    }
+    
+    #[test]
+    fn test_stdlib_class_declarations() {
+        let err = first_error("import std.net.tcp.TCPSocket\nfunc main() {\n    var s = new TCPSocket(\"host\", \"not-a-port\")\n}\n");
+        assert!(matches!(&err.kind, TypeErrorKind::TypeMismatch { expected, .. } if *expected == Type::Int), "{:?}", err.kind);
+        assert_eq!((err.span.line, err.span.column), (3, 35));
+        
+        // 返回类型参与推导：get 的结果是 HttpClientResponse
+        check("import std.net.http.*\nfunc main() {\n    var client = new HttpClient()\n    var code: int = client.get(\"http://localhost/\").status()\n}\n").unwrap();
+        let err = first_error("import std.net.http.*\nfunc main() {\n    var client = new HttpClient()\n    var body: int = client.get(\"http://localhost/\").body()\n}\n");
+        assert!(matches!(err.kind, TypeErrorKind::TypeMismatch { .. }), "{:?}", err.kind);
+        
+        let err = first_error("import std.net.http.HttpClient\nfunc main() {\n    var client = new HttpClient()\n    client.gett(\"http://localhost/\")\n}\n");
+        assert!(matches!(&err.kind, TypeErrorKind::UndefinedMember { type_name, member, suggestion }
+            if type_name == "HttpClient" && member == "gett" && suggestion.as_deref() == Some("get")), "{:?}", err.kind);
+        assert_eq!(err.to_string(), "type 'HttpClient' has no method or field 'gett'; did you mean 'get'?");
+    }
+    
+    #[test]
+    fn test_trait_object_subtyping() {
+        let defs = "trait Printable {\n    func format() string\n}\ninterface Named {\n    func name() string\n}\nclass Doc implements Named {\n    use Printable\n    func format() string {\n        return \"d\"\n    }\n    func name() string {\n        return \"d\"\n    }\n}\nstruct Point implements Named {\n    x: int\n    func name() string {\n        return \"p\"\n    }\n}\nfunc show(p: Printable) string {\n    return p.format()\n}\n";
+        check(&format!("{}func main() {{\n    var d = new Doc()\n    var n: Named = d\n    var p: Printable? = d\n    show(d)\n    var items: Named[] = [d, Point {{ x: 1 }}]\n}}\n", defs)).unwrap();
+        
+        let err = first_error(&format!("{}func main() {{\n    show(Point {{ x: 1 }})\n}}\n", defs));
+        assert!(matches!(err.kind, TypeErrorKind::TypeMismatch { .. }), "{:?}", err.kind);
+        let err = first_error(&format!("{}func main() {{\n    var items: Printable[] = [new Doc(), Point {{ x: 1 }}]\n}}\n", defs));
+        assert!(matches!(err.kind, TypeErrorKind::TypeMismatch { .. }), "{:?}", err.kind);
+        assert_eq!((err.span.line, err.span.column), (26, 42));
+    }
+
+    #[test]
+    fn test_recursive_struct_layouts() {
+        let err = first_error("struct Node {\n    value: int\n    next: Node\n}\nfunc main() {\n}\n");
+        assert_eq!(err.to_string(), "struct 'Node' contains itself by value; use a nullable reference (Node?) to break the cycle");
+        assert_eq!((err.span.line, err.span.column), (3, 5));
+        assert_eq!(err.notes, vec!["Node.next -> Node".to_string()]);
+
+        // 互相包含的环只报告一次
+        let errors = check("struct A {\n    b: B\n}\nstruct B {\n    a: A\n}\nfunc main() {\n}\n").unwrap_err();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(errors[0].to_string(), "struct 'A' contains itself by value; use a nullable reference (B?) to break the cycle");
+        assert_eq!(errors[0].notes, vec!["A.b -> B.a -> A".to_string()]);
+
+        // 经过泛型结构体的类型实参、定长数组也是按值包含
+        let err = first_error("struct Pair<T> {\n    left: T\n}\nstruct S {\n    p: Pair<S>\n}\nfunc main() {\n}\n");
+        assert_eq!(err.notes, vec!["S.p -> Pair.left -> S".to_string()]);
+        assert!(matches!(first_error("struct G {\n    cells: G[2]\n}\nfunc main() {\n}\n").kind, TypeErrorKind::RecursiveStruct { .. }));
+
+        // 经过可空类型、切片和类的引用是合法的递归，可以运行
+        let source = "struct Node {\n    value: int\n    next: Node?\n    children: Node[]\n}\nclass Tree {\n    var root: Node\n}\nfunc main() int {\n    var list = Node { value: 1, next: Node { value: 2, next: null, children: [] }, children: [] }\n    var second = list.next\n    if second != null {\n        return list.value + second.value\n    }\n    return 0\n}\n";
+        check(source).unwrap();
+        let program = Parser::new(Scanner::new(source).scan_tokens(), Locale::En).parse().unwrap();
+        let chunk = crate::compiler::Compiler::new(Locale::En).compile(&program).unwrap();
+        let mut vm = crate::vm::VM::new(std::sync::Arc::new(chunk), Locale::En);
+        vm.run().unwrap();
+        assert_eq!(vm.result().and_then(|v| v.as_int()), Some(3));
+    }
+
+    #[test]
+    fn test_struct_types_nested_in_function_types() {
+        // 结构体字面量数组的元素类型与回调参数的注解是同一个类型
+        check("struct User {\n    age: int\n}\nfunc main() {\n    var users = [User { age: 1 }]\n    users.sort(func(a: User, b: User) int { return a.age - b.age })\n    var sorted: User[] = users.sorted()\n    users.sortBy(func(u: User) int { return u.age })\n}\n").unwrap();
+        let err = first_error("struct User {\n    age: int\n}\nstruct Other {\n    age: int\n}\nfunc main() {\n    var users = [User { age: 1 }]\n    users.sort(func(a: Other, b: Other) int { return 0 })\n}\n");
+        assert!(matches!(err.kind, TypeErrorKind::TypeMismatch { .. }), "{:?}", err);
+    }
+
+    #[test]
+    fn test_recursive_type_aliases() {
+        let errors = check("type A = B\ntype B = map[string]A[]\ntype C = A\nfunc main() {\n    var x: C = 1\n}\n").unwrap_err();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(errors[0].to_string(), "type alias 'A' refers to itself: A -> B -> A");
+        assert_eq!((errors[0].span.line, errors[0].span.column), (1, 1));
+        assert!(matches!(first_error("type L = L?\nfunc main() {\n}\n").kind, TypeErrorKind::CyclicTypeDependency(ref chain) if chain.len() == 2));
+
+        // 非循环的别名在使用处展开，与声明顺序无关
+        check("func sum(xs: Ints) Total {\n    var total: Total = 0\n    for x in xs {\n        total = total + x\n    }\n    return total\n}\ntype Ints = Total[]\ntype Total = int\nfunc main() {\n    var n: int = sum([1, 2])\n}\n").unwrap();
+        assert!(matches!(first_error("type Total = int\nfunc main() {\n    var n: Total = \"x\"\n}\n").kind, TypeErrorKind::TypeMismatch { .. }));
+    }
+
+    #[test]
+    fn test_for_in_pair_destructuring() {
+        // map 拆成键和值，二元数组拆成两个元素，对象遍历 entries() 的结果
+        check(r#"
+class Scores {
+    func entries() map[string]int {
+        return {"a": 1}
+    }
+}
+func main() {
+    var ages = {"ann": 30}
+    for name, age in ages {
+        var n: string = name
+        var a: int = age
+    }
+    for entry in ages {
+        var key: dynamic = entry[0]
+    }
+    for x, y in [[1, 2]] {
+        var sum: int = x + y
+    }
+    for name, score in new Scores() {
+        var s: int = score
+    }
+}
+"#).unwrap();
+
+        // map 的值不会被当成键的类型
+        let err = first_error("func main() {\n    for k, v in {\"a\": 1} {\n        var s: string = v\n    }\n}\n");
+        assert!(matches!(err.kind, TypeErrorKind::TypeMismatch { .. }), "{}", err);
+
+        // 元素不能拆成两个值
+        let err = first_error("func main() {\n    for a, b in [1, 2] {\n    }\n}\n");
+        assert_eq!(err.to_string(), "for-in with two variables requires pairs; elements are int");
+        assert_eq!(err.span.line, 2);
+    }
+
+}
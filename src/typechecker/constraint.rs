@@ -5,7 +5,7 @@
 use std::collections::HashMap;
 use crate::types::{Type, TypeBound, TypeVar, Substitution};
 use crate::lexer::Span;
-use super::error::{TypeError, TypeErrorKind};
+use super::error::{TypeError, TypeErrorKind, TypePathSegment};
 
 /// 约束种类
 #[derive(Debug, Clone, PartialEq)]
@@ -38,6 +38,8 @@ pub struct Constraint {
     pub span: Span,
     /// 约束原因描述
     pub reason: String,
+    /// 期望类型的来源（如参数声明、类型注解的位置和说明）
+    pub origin: Option<(Span, String)>,
 }
 
 impl Constraint {
@@ -47,6 +49,30 @@ impl Constraint {
             kind,
             span,
             reason: reason.into(),
+            origin: None,
+        }
+    }
+    
+    /// 记录期望类型的来源
+    pub fn with_origin(mut self, span: Span, label: impl Into<String>) -> Self {
+        self.origin = Some((span, label.into()));
+        self
+    }
+    
+    /// 约束中出现的类型变量
+    fn type_vars(&self) -> Vec<TypeVar> {
+        match &self.kind {
+            ConstraintKind::Equal(t1, t2) | ConstraintKind::Subtype { sub: t1, super_: t2 } => {
+                let mut vars = t1.free_type_vars();
+                for var in t2.free_type_vars() {
+                    if !vars.contains(&var) {
+                        vars.push(var);
+                    }
+                }
+                vars
+            }
+            ConstraintKind::TraitBound { ty, .. } => ty.free_type_vars(),
+            ConstraintKind::Instantiate { .. } => Vec::new(),
         }
     }
     
@@ -84,6 +110,10 @@ pub struct ConstraintSolver {
     var_constraints: HashMap<u64, Vec<TypeBound>>,
     /// 错误列表
     errors: Vec<TypeError>,
+    /// 每个替换项由哪条约束推导而来（替换键 -> (约束位置, 约束原因)）
+    provenance: HashMap<String, (Span, String)>,
+    /// 已求解的约束数（同一约束在多轮中各计一次）
+    solved: usize,
 }
 
 impl ConstraintSolver {
@@ -94,9 +124,16 @@ impl ConstraintSolver {
             substitution: Substitution::new(),
             var_constraints: HashMap::new(),
             errors: Vec::new(),
+            provenance: HashMap::new(),
+            solved: 0,
         }
     }
     
+    /// 已求解的约束数
+    pub fn solved_count(&self) -> usize {
+        self.solved
+    }
+    
     /// 添加约束
     pub fn add_constraint(&mut self, constraint: Constraint) {
         self.constraints.push(constraint);
@@ -121,17 +158,22 @@ impl ConstraintSolver {
             let constraints = std::mem::take(&mut self.constraints);
             
             for constraint in constraints {
+                self.solved += 1;
                 match self.solve_constraint(&constraint) {
                     Ok(new_substitution) => {
                         if !new_substitution.is_empty() {
                             changed = true;
-                            // 合并替换
+                            // 合并替换，记录首次推导出该替换的约束
                             for (name, ty) in new_substitution {
+                                self.provenance
+                                    .entry(name.clone())
+                                    .or_insert_with(|| (constraint.span, constraint.reason.clone()));
                                 self.substitution.insert(name, ty);
                             }
                         }
                     }
                     Err(err) => {
+                        let err = self.explain_failure(err, &constraint);
                         self.errors.push(err);
                     }
                 }
@@ -163,11 +205,13 @@ impl ConstraintSolver {
             }
             ConstraintKind::Subtype { sub, super_ } => {
                 // 子类型检查（简化：目前只检查相等或可空）
-                if sub.is_assignable_to(super_) {
+                let sub = sub.substitute(&self.substitution);
+                let super_ = super_.substitute(&self.substitution);
+                if sub.is_assignable_to(&super_) {
                     Ok(Substitution::new())
                 } else {
-                    // 尝试统一
-                    self.unify(sub, super_, constraint.span)
+                    // 尝试统一（以父类型作为期望类型，错误信息中的期望/实际才不会颠倒）
+                    self.unify(&super_, &sub, constraint.span)
                 }
             }
             ConstraintKind::TraitBound { ty, bound } => {
@@ -189,6 +233,26 @@ impl ConstraintSolver {
         }
     }
     
+    /// 为失败的约束补充来源信息：
+    /// 期望类型的出处，以及约束中已推导的类型变量是从哪里推导出来的
+    fn explain_failure(&self, mut err: TypeError, constraint: &Constraint) -> TypeError {
+        if let Some((span, label)) = &constraint.origin {
+            err = err.with_label(*span, label.clone());
+        }
+        for var in constraint.type_vars() {
+            let key = format!("?T{}", var.id);
+            if let Some((span, reason)) = self.provenance.get(&key) {
+                let name = var.name.clone().unwrap_or_else(|| key.clone());
+                let inferred = Type::TypeVar(var).substitute(&self.substitution);
+                err = err.with_note(format!(
+                    "{} was inferred as {} from {} at line {}",
+                    name, inferred, reason, span.line
+                ));
+            }
+        }
+        err
+    }
+    
     /// 统一两个类型
     fn unify(&self, t1: &Type, t2: &Type, span: Span) -> Result<Substitution, TypeError> {
         use Type::*;
@@ -250,15 +314,17 @@ impl ConstraintSolver {
                 }
                 
                 // 统一参数类型
-                for (pt1, pt2) in p1.iter().zip(p2.iter()) {
-                    let param_subst = self.unify(pt1, pt2, span)?;
+                for (i, (pt1, pt2)) in p1.iter().zip(p2.iter()).enumerate() {
+                    let param_subst = self.unify(pt1, pt2, span)
+                        .map_err(|e| e.within(TypePathSegment::Parameter(i), &t1))?;
                     for (k, v) in param_subst {
                         subst.insert(k, v);
                     }
                 }
                 
                 // 统一返回类型
-                let ret_subst = self.unify(r1, r2, span)?;
+                let ret_subst = self.unify(r1, r2, span)
+                    .map_err(|e| e.within(TypePathSegment::ReturnType, &t1))?;
                 for (k, v) in ret_subst {
                     subst.insert(k, v);
                 }
@@ -272,11 +338,13 @@ impl ConstraintSolver {
                     return Err(TypeError::type_mismatch(t1.clone(), t2.clone(), span));
                 }
                 self.unify(e1, e2, span)
+                    .map_err(|e| e.within(TypePathSegment::ElementType, &t1))
             }
             
             // 切片类型统一
             (Slice { element_type: e1 }, Slice { element_type: e2 }) => {
                 self.unify(e1, e2, span)
+                    .map_err(|e| e.within(TypePathSegment::ElementType, &t1))
             }
             
             // Map 类型统一
@@ -284,12 +352,14 @@ impl ConstraintSolver {
                 Map { key_type: k1, value_type: v1 },
                 Map { key_type: k2, value_type: v2 },
             ) => {
-                let key_subst = self.unify(k1, k2, span)?;
+                let key_subst = self.unify(k1, k2, span)
+                    .map_err(|e| e.within(TypePathSegment::MapKey, &t1))?;
                 for (k, v) in key_subst {
                     subst.insert(k, v);
                 }
                 
-                let val_subst = self.unify(v1, v2, span)?;
+                let val_subst = self.unify(v1, v2, span)
+                    .map_err(|e| e.within(TypePathSegment::MapValue, &t1))?;
                 for (k, v) in val_subst {
                     subst.insert(k, v);
                 }
@@ -300,6 +370,7 @@ impl ConstraintSolver {
             // 可空类型统一
             (Nullable(n1), Nullable(n2)) => {
                 self.unify(n1, n2, span)
+                    .map_err(|e| e.within(TypePathSegment::NullableInner, &t1))
             }
             
             // 泛型类型统一
@@ -324,8 +395,9 @@ impl ConstraintSolver {
                     ));
                 }
                 
-                for (arg1, arg2) in a1.iter().zip(a2.iter()) {
-                    let arg_subst = self.unify(arg1, arg2, span)?;
+                for (i, (arg1, arg2)) in a1.iter().zip(a2.iter()).enumerate() {
+                    let arg_subst = self.unify(arg1, arg2, span)
+                        .map_err(|e| e.within(TypePathSegment::TypeArgument(i), &t1))?;
                     for (k, v) in arg_subst {
                         subst.insert(k, v);
                     }
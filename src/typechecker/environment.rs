@@ -4,6 +4,8 @@
 
 use std::collections::HashMap;
 use crate::types::{Type, TypeBound, GenericParam, FunctionSignature, TraitDef, InterfaceDef, TraitImpl};
+use crate::lexer::Span;
+use crate::parser::DISCARD;
 
 /// 变量/常量信息
 #[derive(Debug, Clone)]
@@ -29,6 +31,8 @@ pub struct FunctionInfo {
     pub param_types: Vec<Type>,
     /// 参数名
     pub param_names: Vec<String>,
+    /// 参数声明位置（标准库函数为空）
+    pub param_spans: Vec<Span>,
     /// 必需参数数量（不包括有默认值的参数）
     pub required_params: usize,
     /// 返回类型
@@ -62,6 +66,12 @@ pub struct ClassInfo {
     pub static_methods: HashMap<String, FunctionInfo>,
     /// 是否是抽象类
     pub is_abstract: bool,
+    /// final 方法及其声明位置
+    pub final_methods: HashMap<String, Span>,
+    /// 抽象方法及其声明位置
+    pub abstract_methods: HashMap<String, Span>,
+    /// 是否是标准库实现的类（构造由运行时完成，子类不会调用它的 init）
+    pub is_native: bool,
 }
 
 /// 结构体信息
@@ -77,6 +87,10 @@ pub struct StructInfo {
     pub fields: HashMap<String, FieldInfo>,
     /// 方法
     pub methods: HashMap<String, FunctionInfo>,
+    /// 静态字段（含 const）
+    pub static_fields: HashMap<String, FieldInfo>,
+    /// 静态方法
+    pub static_methods: HashMap<String, FunctionInfo>,
 }
 
 /// 字段信息
@@ -175,6 +189,8 @@ pub struct TypeScope {
     functions: HashMap<String, FunctionInfo>,
     /// 泛型参数（当前作用域的类型参数）
     type_params: HashMap<String, GenericParam>,
+    /// 在这个作用域内收窄的变量类型（如 `if x != null` 分支中去掉可空性的 x）
+    narrowed: HashMap<String, Type>,
     /// 父作用域索引（-1 表示全局）
     parent: Option<usize>,
 }
@@ -186,6 +202,7 @@ impl TypeScope {
             variables: HashMap::new(),
             functions: HashMap::new(),
             type_params: HashMap::new(),
+            narrowed: HashMap::new(),
             parent,
         }
     }
@@ -277,8 +294,11 @@ impl TypeEnvironment {
         }
     }
     
-    /// 定义变量
+    /// 定义变量；丢弃绑定 `_` 不定义任何名字，可以重复出现
     pub fn define_variable(&mut self, name: String, ty: Type, is_const: bool) -> Result<(), String> {
+        if name == DISCARD {
+            return Ok(());
+        }
         self.scopes[self.current_scope].define_variable(name, ty, is_const)
     }
     
@@ -294,6 +314,40 @@ impl TypeEnvironment {
         None
     }
     
+    /// 查找变量在当前位置的类型：内层作用域的收窄优先于声明类型
+    pub fn variable_type(&self, name: &str) -> Option<Type> {
+        let mut scope_idx = Some(self.current_scope);
+        while let Some(idx) = scope_idx {
+            let scope = &self.scopes[idx];
+            if let Some(ty) = scope.narrowed.get(name) {
+                return Some(ty.clone());
+            }
+            if let Some(var) = scope.get_variable(name) {
+                return Some(var.ty.clone());
+            }
+            scope_idx = scope.parent;
+        }
+        None
+    }
+    
+    /// 在当前作用域内把变量收窄为 ty，离开作用域时失效
+    pub fn narrow_variable(&mut self, name: String, ty: Type) {
+        self.scopes[self.current_scope].narrowed.insert(name, ty);
+    }
+    
+    /// 变量被重新赋值：撤销它在当前位置可见的所有收窄
+    pub fn invalidate_narrowing(&mut self, name: &str) {
+        let mut scope_idx = Some(self.current_scope);
+        while let Some(idx) = scope_idx {
+            let scope = &mut self.scopes[idx];
+            scope.narrowed.remove(name);
+            if scope.variables.contains_key(name) {
+                break;
+            }
+            scope_idx = scope.parent;
+        }
+    }
+    
     /// 定义类型参数（在当前作用域）
     pub fn define_type_param(&mut self, param: GenericParam) {
         self.scopes[self.current_scope].define_type_param(param);
@@ -423,13 +477,30 @@ impl TypeEnvironment {
         };
         
         match self.lookup_type(type_name)? {
-            TypeInfo::Class(info) => info.fields.get(field_name),
+            TypeInfo::Class(_) => self.class_chain(type_name).find_map(|info| info.fields.get(field_name)),
             TypeInfo::Struct(info) => info.fields.get(field_name),
             _ => None,
         }
     }
     
-    /// 获取类型的方法
+    /// 类自身及其父类链（子类在前，遇到循环继承时停止）
+    pub fn class_chain<'a>(&'a self, class_name: &str) -> impl Iterator<Item = &'a ClassInfo> + 'a {
+        let mut visited = std::collections::HashSet::new();
+        let mut current = Some(class_name.to_string());
+        std::iter::from_fn(move || {
+            let name = current.take()?;
+            if !visited.insert(name.clone()) {
+                return None;
+            }
+            let Some(TypeInfo::Class(info)) = self.lookup_type(&name) else {
+                return None;
+            };
+            current = info.parent.clone();
+            Some(info)
+        })
+    }
+    
+    /// 获取类型的方法（类的方法包括从父类继承的）
     pub fn get_method(&self, ty: &Type, method_name: &str) -> Option<&FunctionInfo> {
         let type_name = match ty {
             Type::Class(name) | Type::Struct(name) => name,
@@ -441,7 +512,7 @@ impl TypeEnvironment {
         };
         
         match self.lookup_type(type_name)? {
-            TypeInfo::Class(info) => info.methods.get(method_name),
+            TypeInfo::Class(_) => self.class_chain(type_name).find_map(|info| info.methods.get(method_name)),
             TypeInfo::Struct(info) => info.methods.get(method_name),
             TypeInfo::Trait(info) => info.methods.get(method_name),
             TypeInfo::Interface(info) => info.methods.get(method_name),
@@ -2,6 +2,7 @@
 //! 
 //! 定义类型检查过程中可能产生的所有错误
 
+use crate::diagnostics::{Diagnostic, Severity, Stage};
 use crate::lexer::Span;
 use crate::types::Type;
 use std::fmt;
@@ -32,6 +33,13 @@ pub enum TypeErrorKind {
         type_name: String,
         method_name: String,
     },
+    /// 类既没有该方法也没有该字段
+    UndefinedMember {
+        type_name: String,
+        member: String,
+        /// 名称相近的方法或字段
+        suggestion: Option<String>,
+    },
     /// 重复定义
     DuplicateDefinition(String),
     /// 参数数量不匹配
@@ -62,10 +70,34 @@ pub enum TypeErrorKind {
         constraint: String,
         actual_type: Type,
     },
+    /// 在类型参数的值上调用了约束中没有声明的方法
+    MethodNotInBounds {
+        type_param: String,
+        method_name: String,
+        /// 类型参数的约束（为空表示没有约束）
+        bounds: Vec<String>,
+    },
     /// 无法推导类型
     CannotInferType,
-    /// 循环类型依赖
-    CyclicTypeDependency(String),
+    /// 空字面量的元素类型无法确定
+    CannotInferEmptyLiteral,
+    /// 字面量中某个元素与之前元素的类型无法统一
+    LiteralElementMismatch {
+        context: String,
+        index: usize,
+        expected: Type,
+        actual: Type,
+    },
+    /// 类型别名展开后引用到自身（展开路径，如 `A -> B -> A`）
+    CyclicTypeDependency(Vec<String>),
+    /// 结构体按值包含自身
+    RecursiveStruct {
+        name: String,
+        /// 环上第一个字段的类型，改为它的可空类型即可断开
+        field_type: String,
+    },
+    /// 泛型实例化无法终止（实例化路径）
+    UnboundedInstantiation(Vec<String>),
     /// 不可空类型赋值 null
     NullNotAllowed(Type),
     /// 无效的类型转换
@@ -81,6 +113,21 @@ pub enum TypeErrorKind {
     UnreachableCode,
     /// 抽象类不能实例化
     CannotInstantiateAbstract(String),
+    /// 非抽象类没有实现父类链上的抽象方法
+    MissingAbstractMethods {
+        class_name: String,
+        /// （声明抽象方法的类，方法名）
+        methods: Vec<(String, String)>,
+    },
+    /// 父类的 init 需要参数，子类却没有以 `super.init(...)` 开头的 init
+    MissingSuperInit {
+        class_name: String,
+        parent_name: String,
+        /// 父类 init 必需的参数个数
+        required: usize,
+        /// 子类是否声明了 init
+        declares_init: bool,
+    },
     /// 缺少接口方法实现
     MissingInterfaceMethod {
         interface_name: String,
@@ -91,6 +138,38 @@ pub enum TypeErrorKind {
         trait_name: String,
         method_name: String,
     },
+    /// 标记了 override，但父类、接口和 Trait 中都没有同名方法
+    NothingToOverride {
+        method_name: String,
+        /// 名称相近的可重写方法
+        suggestion: Option<String>,
+    },
+    /// 重写方法的签名与被重写的方法不兼容
+    OverrideSignatureMismatch {
+        method_name: String,
+        /// 被重写方法所属的类型
+        parent_name: String,
+        detail: String,
+    },
+    /// 重写了父类方法但没有标记 override（警告）
+    MissingOverride {
+        method_name: String,
+        parent_name: String,
+    },
+    /// 局部变量与所在函数的参数同名（警告）
+    ShadowedParameter(String),
+    /// 声明没有类型注解，默认为 any（警告，`--strict-types` 时为错误）
+    ImplicitAny {
+        /// 需要注解的声明，如 `field 'n' of 'User'`
+        declaration: String,
+        /// 根据初始值推测的类型
+        hint: Option<Type>,
+    },
+    /// 重写了 final 方法
+    OverrideFinal {
+        method_name: String,
+        parent_name: String,
+    },
     /// 类型不兼容
     IncompatibleTypes {
         types: Vec<Type>,
@@ -100,6 +179,8 @@ pub enum TypeErrorKind {
     InfiniteType,
     /// 顶级代码不允许
     TopLevelCodeNotAllowed,
+    /// 常量的初始值无法在编译期求值
+    NonConstantInitializer(String),
     /// 入口文件缺少 main 函数
     MissingMainFunction,
     /// 同一包内 main 函数重复
@@ -117,15 +198,90 @@ pub enum TypeErrorKind {
     Other(String),
 }
 
+impl TypeErrorKind {
+    /// 诊断编号，显示在报告开头，便于在文档中查找（目前只有隐式 any 有编号）
+    pub fn code(&self) -> Option<&'static str> {
+        match self {
+            TypeErrorKind::ImplicitAny { .. } => Some("W0001"),
+            _ => None,
+        }
+    }
+}
+
+/// 统一失败时所在的类型内部位置
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypePathSegment {
+    /// 数组 / 切片的元素类型
+    ElementType,
+    /// Map 的键类型
+    MapKey,
+    /// Map 的值类型
+    MapValue,
+    /// 可空类型的内部类型
+    NullableInner,
+    /// 指针指向的类型
+    PointerTarget,
+    /// 元组的第 n 个元素（从 0 开始）
+    TupleElement(usize),
+    /// 函数的第 n 个参数（从 0 开始）
+    Parameter(usize),
+    /// 函数的返回类型
+    ReturnType,
+    /// 泛型的第 n 个类型参数（从 0 开始）
+    TypeArgument(usize),
+}
+
+/// 类型路径中的一步：位置 + 所在的外层类型
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypePathStep {
+    pub segment: TypePathSegment,
+    pub container: Type,
+}
+
+/// 序数词（1st, 2nd, 3rd, 4th ...）
+fn ordinal(n: usize) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", n, suffix)
+}
+
+impl fmt::Display for TypePathStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let position = match &self.segment {
+            TypePathSegment::ElementType => "the element type".to_string(),
+            TypePathSegment::MapKey => "the key type".to_string(),
+            TypePathSegment::MapValue => "the value type".to_string(),
+            TypePathSegment::NullableInner => "the inner type".to_string(),
+            TypePathSegment::PointerTarget => "the target type".to_string(),
+            TypePathSegment::TupleElement(i) => format!("the {} element", ordinal(i + 1)),
+            TypePathSegment::Parameter(i) => format!("the {} parameter", ordinal(i + 1)),
+            TypePathSegment::ReturnType => "the return type".to_string(),
+            TypePathSegment::TypeArgument(i) => format!("the {} type argument", ordinal(i + 1)),
+        };
+        write!(f, "in {} of `{}`", position, self.container)
+    }
+}
+
 /// 类型错误
 #[derive(Debug, Clone)]
 pub struct TypeError {
     /// 错误种类
     pub kind: TypeErrorKind,
-    /// 错误位置
+    /// 错误位置（冲突的表达式）
     pub span: Span,
     /// 附加信息
     pub notes: Vec<String>,
+    /// 类型内部的冲突位置（由内向外）
+    pub path: Vec<TypePathStep>,
+    /// 相关位置，如期望类型的来源（类型注解、参数声明）
+    pub labels: Vec<(Span, String)>,
+    /// 出错的源文件（多文件程序中由检查器按顶层语句填写，未知时为 None）
+    pub file: Option<String>,
 }
 
 impl TypeError {
@@ -135,6 +291,9 @@ impl TypeError {
             kind,
             span,
             notes: Vec::new(),
+            path: Vec::new(),
+            labels: Vec::new(),
+            file: None,
         }
     }
     
@@ -144,6 +303,45 @@ impl TypeError {
         self
     }
     
+    /// 添加相关位置
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push((span, message.into()));
+        self
+    }
+    
+    /// 记录错误发生在外层类型 `container` 的 `segment` 位置
+    /// 
+    /// 统一算法由内向外返回错误，因此路径按由内向外的顺序累积
+    pub fn within(mut self, segment: TypePathSegment, container: &Type) -> Self {
+        self.path.push(TypePathStep {
+            segment,
+            container: container.clone(),
+        });
+        self
+    }
+    
+    /// 生成带位置前缀的完整报告（路径、相关位置和注释各占一行）
+    ///
+    /// 提供出错文件的源码时，在第一行下方附加出错位置的源码片段
+    pub fn render(&self, source: Option<&str>) -> String {
+        self.to_diagnostic(Severity::Error).render(source)
+    }
+    
+    /// 转换为结构化的诊断（文件为错误记录的来源文件）
+    pub fn to_diagnostic(&self, severity: Severity) -> Diagnostic {
+        let mut diagnostic = match severity {
+            Severity::Error => Diagnostic::error(Stage::Type, self.to_string()),
+            Severity::Warning => Diagnostic::warning(Stage::Type, self.to_string()),
+        }
+        .at(self.span)
+        .in_file(self.file.clone());
+        diagnostic.code = self.kind.code();
+        diagnostic.path = self.path.iter().map(|step| step.to_string()).collect();
+        diagnostic.labels = self.labels.clone();
+        diagnostic.notes = self.notes.clone();
+        diagnostic
+    }
+    
     /// 创建类型不匹配错误
     pub fn type_mismatch(expected: Type, actual: Type, span: Span) -> Self {
         Self::new(
@@ -230,6 +428,13 @@ impl fmt::Display for TypeError {
             TypeErrorKind::UndefinedMethod { type_name, method_name } => {
                 write!(f, "类型 {} 没有方法 {}", type_name, method_name)
             }
+            TypeErrorKind::UndefinedMember { type_name, member, suggestion } => {
+                write!(f, "type '{}' has no method or field '{}'", type_name, member)?;
+                if let Some(suggestion) = suggestion {
+                    write!(f, "; did you mean '{}'?", suggestion)?;
+                }
+                Ok(())
+            }
             TypeErrorKind::DuplicateDefinition(name) => {
                 write!(f, "重复定义: {}", name)
             }
@@ -254,11 +459,34 @@ impl fmt::Display for TypeError {
             TypeErrorKind::ConstraintNotSatisfied { type_param, constraint, actual_type } => {
                 write!(f, "类型 {} 不满足约束 {}: {}", actual_type, type_param, constraint)
             }
+            TypeErrorKind::MethodNotInBounds { type_param, method_name, bounds } => {
+                if bounds.is_empty() {
+                    write!(f, "type parameter '{}' has no bounds, so method '{}' cannot be called on it; add a bound such as '{}: SomeInterface'", type_param, method_name, type_param)
+                } else {
+                    write!(f, "method '{}' is not declared by the bounds of type parameter '{}: {}'", method_name, type_param, bounds.join(" + "))
+                }
+            }
             TypeErrorKind::CannotInferType => {
                 write!(f, "无法推导类型")
             }
-            TypeErrorKind::CyclicTypeDependency(name) => {
-                write!(f, "循环类型依赖: {}", name)
+            TypeErrorKind::CannotInferEmptyLiteral => {
+                write!(f, "cannot infer the element type of this empty literal; add a type annotation")
+            }
+            TypeErrorKind::LiteralElementMismatch { context, index, expected, actual } => {
+                write!(
+                    f,
+                    "{} {} has type '{}', which does not unify with '{}' inferred from the preceding elements",
+                    context, index, actual, expected
+                )
+            }
+            TypeErrorKind::CyclicTypeDependency(chain) => {
+                write!(f, "type alias '{}' refers to itself: {}", chain[0], chain.join(" -> "))
+            }
+            TypeErrorKind::RecursiveStruct { name, field_type } => {
+                write!(f, "struct '{}' contains itself by value; use a nullable reference ({}?) to break the cycle", name, field_type)
+            }
+            TypeErrorKind::UnboundedInstantiation(chain) => {
+                write!(f, "generic instantiation does not terminate: {} -> ...", chain.join(" -> "))
             }
             TypeErrorKind::NullNotAllowed(ty) => {
                 write!(f, "不能将 null 赋值给非空类型 {}", ty)
@@ -278,12 +506,51 @@ impl fmt::Display for TypeError {
             TypeErrorKind::CannotInstantiateAbstract(name) => {
                 write!(f, "不能实例化抽象类: {}", name)
             }
+            TypeErrorKind::MissingAbstractMethods { class_name, methods } => {
+                let names: Vec<String> = methods.iter().map(|(owner, method)| format!("{}::{}", owner, method)).collect();
+                write!(f, "class '{}' must implement abstract method{} {}", class_name, if names.len() == 1 { "" } else { "s" }, names.join(", "))
+            }
+            TypeErrorKind::MissingSuperInit { class_name, parent_name, required, declares_init } => {
+                if *declares_init {
+                    write!(f, "init of class '{}' must start with super.init(...)", class_name)?;
+                } else {
+                    write!(f, "class '{}' must declare init and call super.init(...)", class_name)?;
+                }
+                let plural = if *required == 1 { "" } else { "s" };
+                write!(f, ": '{}::init' requires {} argument{}", parent_name, required, plural)
+            }
             TypeErrorKind::MissingInterfaceMethod { interface_name, method_name } => {
                 write!(f, "缺少接口 {} 的方法实现: {}", interface_name, method_name)
             }
             TypeErrorKind::MissingTraitMethod { trait_name, method_name } => {
                 write!(f, "缺少 Trait {} 的方法实现: {}", trait_name, method_name)
             }
+            TypeErrorKind::NothingToOverride { method_name, suggestion } => {
+                write!(f, "no method '{}' to override", method_name)?;
+                if let Some(suggestion) = suggestion {
+                    write!(f, "; did you mean '{}'?", suggestion)?;
+                }
+                Ok(())
+            }
+            TypeErrorKind::OverrideSignatureMismatch { method_name, parent_name, detail } => {
+                write!(f, "method '{}' does not match '{}::{}' that it overrides: {}", method_name, parent_name, method_name, detail)
+            }
+            TypeErrorKind::MissingOverride { method_name, parent_name } => {
+                write!(f, "method '{}' overrides '{}::{}' but is not marked 'override'", method_name, parent_name, method_name)
+            }
+            TypeErrorKind::ShadowedParameter(name) => {
+                write!(f, "local variable '{}' shadows parameter '{}'", name, name)
+            }
+            TypeErrorKind::ImplicitAny { declaration, hint } => {
+                write!(f, "{} is implicitly any", declaration)?;
+                match hint {
+                    Some(ty) => write!(f, "; based on its initializer it may be {} — annotate it", ty),
+                    None => write!(f, "; annotate it"),
+                }
+            }
+            TypeErrorKind::OverrideFinal { method_name, parent_name } => {
+                write!(f, "cannot override final method '{}::{}'", parent_name, method_name)
+            }
             TypeErrorKind::IncompatibleTypes { types, context } => {
                 let type_strs: Vec<_> = types.iter().map(|t| t.to_string()).collect();
                 write!(f, "类型不兼容 ({}): {}", context, type_strs.join(", "))
@@ -294,6 +561,9 @@ impl fmt::Display for TypeError {
             TypeErrorKind::TopLevelCodeNotAllowed => {
                 write!(f, "顶级代码不允许：只能在类/结构体/函数内编写代码")
             }
+            TypeErrorKind::NonConstantInitializer(msg) => {
+                write!(f, "常量初始值必须是编译期常量表达式: {}", msg)
+            }
             TypeErrorKind::MissingMainFunction => {
                 write!(f, "入口文件缺少 main 函数")
             }
@@ -301,7 +571,7 @@ impl fmt::Display for TypeError {
                 write!(f, "同一包内不允许多个 main 函数")
             }
             TypeErrorKind::InvalidMainSignature => {
-                write!(f, "main 函数签名错误：应为 func main()")
+                write!(f, "main 函数签名错误：应为 func main() 或 func main() int")
             }
             TypeErrorKind::PackageMismatch { expected, actual } => {
                 write!(f, "包名不匹配：期望 {}, 实际 {}", expected, actual)
@@ -317,3 +587,33 @@ impl fmt::Display for TypeError {
 }
 
 impl std::error::Error for TypeError {}
+
+/// 在 candidates 中找与 name 最接近的名字（只忽略大小写相同，或编辑距离不超过长度的三分之一）
+pub fn similar_name<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| {
+            let distance = if candidate.eq_ignore_ascii_case(name) { 0 } else { edit_distance(name, candidate) };
+            (distance, candidate)
+        })
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// 两个字符串的编辑距离（插入、删除、替换各计 1）
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
@@ -8,10 +8,12 @@ mod constraint;
 mod error;
 mod checker;
 mod monomorphize;
+mod table;
 
 pub use environment::{TypeEnvironment, TypeScope, TypeInfo, FunctionInfo, ClassInfo, TraitInfo};
 pub use unify::{Unifier, UnifyResult};
 pub use constraint::{Constraint, ConstraintKind, ConstraintSolver};
-pub use error::{TypeError, TypeErrorKind};
+pub use error::{TypeError, TypeErrorKind, TypePathSegment, TypePathStep};
 pub use checker::{TypeChecker, CompileContext};
+pub use table::TypeTable;
 pub use monomorphize::{Monomorphizer, MonoKey, MonomorphizedClass, MonomorphizedStruct, MonomorphizedFunction};
@@ -7,6 +7,16 @@ use crate::parser::{Stmt, Expr, Program};
 use crate::parser::ast::{ClassMethod, StructMethod, TypeAnnotation};
 use crate::types::{Type, Substitution, GenericParam};
 use crate::lexer::Span;
+use crate::timings::{self, Phase};
+use crate::parser::ast::{FnParam, MatchPattern, SelectCaseKind, StringInterpPart};
+use super::checker::instantiate_type_params;
+use super::error::{TypeError, TypeErrorKind};
+use super::table::TypeTable;
+
+/// 实例化链的最大深度
+///
+/// 字段引用了类型实参更深的自身（如 `class Box<T> { var inner: Box<Box<T> >? }`）时实例化不会终止
+const MAX_INSTANTIATION_DEPTH: usize = 64;
 
 /// 单态化实例的唯一标识
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -166,6 +176,8 @@ pub struct MonomorphizedFunction {
 struct PendingRequest {
     key: MonoKey,
     type_args: Vec<Type>,
+    /// 导致这次实例化的实例（由外向内，如 `Box<int>`）
+    chain: Vec<String>,
 }
 
 /// 单态化器
@@ -184,6 +196,8 @@ pub struct Monomorphizer {
     struct_defs: HashMap<String, StructDefInfo>,
     /// 原始函数定义
     function_defs: HashMap<String, FunctionDefInfo>,
+    /// 无法终止的实例化
+    errors: Vec<TypeError>,
 }
 
 /// 类定义信息（用于单态化）
@@ -214,6 +228,10 @@ struct FunctionDefInfo {
     param_types: Vec<Type>,
     param_names: Vec<String>,
     return_type: Type,
+    /// 定义所在的顶层语句的下标
+    statement: usize,
+    /// 定义本身（`Stmt::FnDef`），实例由它复制而来
+    definition: Stmt,
 }
 
 /// 方法信息
@@ -237,12 +255,13 @@ impl Monomorphizer {
             class_defs: HashMap::new(),
             struct_defs: HashMap::new(),
             function_defs: HashMap::new(),
+            errors: Vec::new(),
         }
     }
     
     /// 收集程序中的泛型定义
     pub fn collect_definitions(&mut self, program: &Program) {
-        for stmt in &program.statements {
+        for (index, stmt) in program.statements.iter().enumerate() {
             match stmt {
                 Stmt::ClassDef { name, type_params, fields, methods, parent, is_abstract, .. } => {
                     if !type_params.is_empty() {
@@ -304,6 +323,8 @@ impl Monomorphizer {
                             param_types: params.iter().map(|p| p.type_ann.ty.clone()).collect(),
                             param_names: params.iter().map(|p| p.name.clone()).collect(),
                             return_type: return_type.as_ref().map(|t| t.ty.clone()).unwrap_or(Type::Void),
+                            statement: index,
+                            definition: stmt.clone(),
                         };
                         self.function_defs.insert(name.clone(), info);
                     }
@@ -325,7 +346,7 @@ impl Monomorphizer {
         // 添加到待处理队列
         let already_pending = self.pending.iter().any(|r| r.key == key);
         if !already_pending {
-            self.pending.push(PendingRequest { key: key.clone(), type_args });
+            self.pending.push(PendingRequest { key: key.clone(), type_args, chain: Vec::new() });
         }
         
         key.mangled_name()
@@ -341,7 +362,7 @@ impl Monomorphizer {
         
         let already_pending = self.pending.iter().any(|r| r.key == key);
         if !already_pending {
-            self.pending.push(PendingRequest { key: key.clone(), type_args });
+            self.pending.push(PendingRequest { key: key.clone(), type_args, chain: Vec::new() });
         }
         
         key.mangled_name()
@@ -357,16 +378,106 @@ impl Monomorphizer {
         
         let already_pending = self.pending.iter().any(|r| r.key == key);
         if !already_pending {
-            self.pending.push(PendingRequest { key: key.clone(), type_args });
+            self.pending.push(PendingRequest { key: key.clone(), type_args, chain: Vec::new() });
         }
         
         key.mangled_name()
     }
     
     /// 处理所有待单态化的请求
+    ///
+    /// 类和结构体的字段类型中的泛型实例随之实例化
     pub fn process_all(&mut self) {
+        let started = timings::begin();
+        while let Some(request) = self.pending.pop() {
+            self.monomorphize(&request);
+        }
+        timings::record(Phase::Monomorphize, started, || self.instantiation_count() as u64);
+    }
+    
+    /// 把泛型函数调用改写为对实例的调用，实例作为普通函数追加到程序末尾
+    ///
+    /// 类型实参来自类型检查记录的 `table`。泛型定义之外、类型实参已确定的调用直接改写；
+    /// 泛型函数体中的调用在生成实例时代入类型实参后改写，所以实例只调用实例。
+    /// 原来的泛型函数保留，作为值使用（如 `var f = pick`）时仍然调用它。
+    /// 追加的语句在 `table` 中指向原来的定义，代码生成按原来的语句查找类型和来源文件。
+    pub fn specialize(&mut self, program: &mut Program, table: &mut TypeTable) {
+        let started = timings::begin();
+        for (index, stmt) in program.statements.iter_mut().enumerate() {
+            let mut rewriter = CallRewriter::new(table, &self.function_defs, index, Substitution::new());
+            rewriter.top_level(stmt);
+            for (name, type_args) in rewriter.requests {
+                self.request_function(&name, type_args);
+            }
+        }
+        
         while let Some(request) = self.pending.pop() {
             self.monomorphize(&request);
+            let Some(def) = self.function_defs.get(&request.key.base_name) else { continue };
+            let substitution = self.monomorphized_functions[&request.key].substitution.clone();
+            // 类型实参越嵌越深的递归（如 `f([x])`）到达上限后调用原来的泛型函数
+            let nested = request.chain.len() + 1 < MAX_INSTANTIATION_DEPTH;
+            let mut rewriter = CallRewriter::new(table, &self.function_defs, def.statement, substitution);
+            rewriter.rewrite_calls = nested;
+            let mut definition = def.definition.clone();
+            rewriter.instance(&mut definition, request.key.mangled_name());
+            let origin = def.statement;
+            
+            let mut chain = request.chain.clone();
+            chain.push(request.key.mangled_name());
+            for (name, type_args) in rewriter.requests {
+                let key = MonoKey::new(&name, type_args.clone());
+                if !self.monomorphized_functions.contains_key(&key) && !self.pending.iter().any(|r| r.key == key) {
+                    self.pending.push(PendingRequest { key, type_args, chain: chain.clone() });
+                }
+            }
+            table.alias_statement(program.statements.len(), origin);
+            program.statements.push(definition);
+        }
+        timings::record(Phase::Monomorphize, started, || self.instantiation_count() as u64);
+    }
+    
+    /// 已生成的实例数
+    pub fn instantiation_count(&self) -> usize {
+        self.monomorphized_classes.len() + self.monomorphized_structs.len() + self.monomorphized_functions.len()
+    }
+    
+    /// 实例化过程中发现的错误
+    pub fn errors(&self) -> &[TypeError] {
+        &self.errors
+    }
+    
+    /// 请求实例化字段类型中的泛型类和结构体
+    fn request_field_types(&mut self, request: &PendingRequest, field_types: Vec<Type>) {
+        let mut chain = request.chain.clone();
+        chain.push(Type::Generic {
+            base_type: Box::new(Type::Class(request.key.base_name.clone())),
+            type_args: request.type_args.clone(),
+        }.to_string());
+        let mut nested = Vec::new();
+        for ty in &field_types {
+            collect_generic_instances(ty, &mut nested);
+        }
+        for (name, type_args) in nested {
+            if !self.class_defs.contains_key(&name) && !self.struct_defs.contains_key(&name) {
+                continue;
+            }
+            let key = MonoKey::new(&name, type_args.clone());
+            if self.monomorphized_classes.contains_key(&key)
+                || self.monomorphized_structs.contains_key(&key)
+                || self.pending.iter().any(|r| r.key == key)
+            {
+                continue;
+            }
+            if chain.len() >= MAX_INSTANTIATION_DEPTH {
+                // 只列出开头几层，之后的实例只是继续嵌套
+                let kind = TypeErrorKind::UnboundedInstantiation(chain.iter().take(3).cloned().collect());
+                if !self.errors.iter().any(|e| e.kind == kind) {
+                    self.errors.push(TypeError::new(kind, Span::default()));
+                }
+                return;
+            }
+            self.pending.push(PendingRequest { key, type_args, chain: chain.clone() });
         }
     }
     
@@ -378,12 +489,16 @@ impl Monomorphizer {
         // 尝试作为类单态化
         if let Some(class_def) = self.class_defs.get(&key.base_name).cloned() {
             self.monomorphize_class(key, type_args, &class_def);
+            let field_types = self.monomorphized_classes[key].fields.iter().map(|f| f.ty.clone()).collect();
+            self.request_field_types(request, field_types);
             return;
         }
         
         // 尝试作为结构体单态化
         if let Some(struct_def) = self.struct_defs.get(&key.base_name).cloned() {
             self.monomorphize_struct(key, type_args, &struct_def);
+            let field_types = self.monomorphized_structs[key].fields.iter().map(|f| f.ty.clone()).collect();
+            self.request_field_types(request, field_types);
             return;
         }
         
@@ -402,7 +517,7 @@ impl Monomorphizer {
         let fields: Vec<MonomorphizedField> = class_def.fields.iter().map(|(name, ty, is_mutable)| {
             MonomorphizedField {
                 name: name.clone(),
-                ty: ty.substitute(&substitution),
+                ty: instantiate_type_params(ty, &substitution),
                 is_mutable: *is_mutable,
             }
         }).collect();
@@ -438,7 +553,7 @@ impl Monomorphizer {
         let fields: Vec<MonomorphizedField> = struct_def.fields.iter().map(|(name, ty, is_mutable)| {
             MonomorphizedField {
                 name: name.clone(),
-                ty: ty.substitute(&substitution),
+                ty: instantiate_type_params(ty, &substitution),
                 is_mutable: *is_mutable,
             }
         }).collect();
@@ -529,8 +644,419 @@ impl Monomorphizer {
     }
 }
 
+/// 类型中没有类型参数和待推导的部分
+fn is_concrete(ty: &Type) -> bool {
+    !ty.has_type_params() && !ty.has_type_vars() && !matches!(ty, Type::Infer | Type::Unknown | Type::Error)
+}
+
+/// 改写一个顶层语句（或泛型函数的实例）中的泛型函数调用，并代入类型参数
+///
+/// 类型参数出现在类型标注中：参数和返回类型、`var x: T`、`as T`、`is T`、闭包的签名和类型模式
+struct CallRewriter<'a> {
+    table: &'a TypeTable,
+    function_defs: &'a HashMap<String, FunctionDefInfo>,
+    /// 语法树所在的顶层语句（表中节点的标识）
+    statement: usize,
+    /// 类型参数 -> 类型实参，不在泛型函数的实例中时为空
+    substitution: Substitution,
+    /// 是否改写调用；为 false 时只代入类型参数
+    rewrite_calls: bool,
+    /// 需要的实例：(函数名, 类型实参)
+    requests: Vec<(String, Vec<Type>)>,
+}
+
+impl<'a> CallRewriter<'a> {
+    fn new(
+        table: &'a TypeTable,
+        function_defs: &'a HashMap<String, FunctionDefInfo>,
+        statement: usize,
+        substitution: Substitution,
+    ) -> Self {
+        Self { table, function_defs, statement, substitution, rewrite_calls: true, requests: Vec::new() }
+    }
+
+    /// 顶层语句：泛型的函数、类和结构体在生成实例时处理（泛型类的方法不生成实例，保持原样）
+    fn top_level(&mut self, stmt: &mut Stmt) {
+        match stmt {
+            Stmt::FnDef { type_params, params, body, .. } if type_params.is_empty() => {
+                self.params(params);
+                self.stmt(body);
+            }
+            Stmt::ClassDef { type_params, fields, methods, .. } if type_params.is_empty() => {
+                for field in fields {
+                    if let Some(init) = &mut field.initializer {
+                        self.expr(init);
+                    }
+                }
+                for method in methods {
+                    self.params(&mut method.params);
+                    if let Some(body) = &mut method.body {
+                        self.stmt(body);
+                    }
+                }
+            }
+            Stmt::StructDef { type_params, static_fields, methods, .. } if type_params.is_empty() => {
+                for field in static_fields {
+                    if let Some(init) = &mut field.initializer {
+                        self.expr(init);
+                    }
+                }
+                for method in methods {
+                    self.params(&mut method.params);
+                    self.stmt(&mut method.body);
+                }
+            }
+            Stmt::FnDef { .. } | Stmt::ClassDef { .. } | Stmt::StructDef { .. } => {}
+            stmt => self.stmt(stmt),
+        }
+    }
+
+    /// 泛型函数 `definition` 的实例：改名为 `name`，去掉类型参数
+    fn instance(&mut self, definition: &mut Stmt, name: String) {
+        if let Stmt::FnDef { name: def_name, type_params, where_clauses, params, return_type, body, .. } = definition {
+            *def_name = name;
+            type_params.clear();
+            where_clauses.clear();
+            self.params(params);
+            if let Some(ann) = return_type {
+                self.ty(&mut ann.ty);
+            }
+            self.stmt(body);
+        }
+    }
+
+    fn ty(&self, ty: &mut Type) {
+        if !self.substitution.is_empty() {
+            *ty = instantiate_type_params(ty, &self.substitution);
+        }
+    }
+
+    fn params(&mut self, params: &mut [FnParam]) {
+        for param in params {
+            self.ty(&mut param.type_ann.ty);
+            if let Some(default) = &mut param.default {
+                self.expr(default);
+            }
+        }
+    }
+
+    fn stmt(&mut self, stmt: &mut Stmt) {
+        match stmt {
+            Stmt::Expression { expr, .. } | Stmt::Print { expr, .. } | Stmt::Throw { value: expr, .. } => {
+                self.expr(expr);
+            }
+            Stmt::ParallelAssign { targets, values, .. } => {
+                for expr in targets.iter_mut().chain(values) {
+                    self.expr(expr);
+                }
+            }
+            Stmt::VarDecl { type_ann, initializer, .. } => {
+                if let Some(ann) = type_ann {
+                    self.ty(&mut ann.ty);
+                }
+                if let Some(init) = initializer {
+                    self.expr(init);
+                }
+            }
+            Stmt::ConstDecl { type_ann, initializer, .. } => {
+                if let Some(ann) = type_ann {
+                    self.ty(&mut ann.ty);
+                }
+                self.expr(initializer);
+            }
+            Stmt::Block { statements, .. } => statements.iter_mut().for_each(|s| self.stmt(s)),
+            Stmt::If { condition, then_branch, else_branch, .. } => {
+                self.expr(condition);
+                self.stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.stmt(else_branch);
+                }
+            }
+            Stmt::ForLoop { initializer, condition, increment, body, .. } => {
+                if let Some(init) = initializer {
+                    self.stmt(init);
+                }
+                if let Some(condition) = condition {
+                    self.expr(condition);
+                }
+                if let Some(increment) = increment {
+                    self.expr(increment);
+                }
+                self.stmt(body);
+            }
+            Stmt::ForIn { iterable, body, .. } => {
+                self.expr(iterable);
+                self.stmt(body);
+            }
+            Stmt::While { condition, body, .. } => {
+                if let Some(condition) = condition {
+                    self.expr(condition);
+                }
+                self.stmt(body);
+            }
+            Stmt::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+            }
+            Stmt::Match { expr, arms, .. } => {
+                self.expr(expr);
+                for arm in arms {
+                    self.pattern(&mut arm.pattern);
+                    if let Some(guard) = &mut arm.guard {
+                        self.expr(guard);
+                    }
+                    self.stmt(&mut arm.body);
+                }
+            }
+            Stmt::Select { cases, .. } => {
+                for case in cases {
+                    match &mut case.kind {
+                        SelectCaseKind::Receive { channel, .. } => self.expr(channel),
+                        SelectCaseKind::Send { channel, value } => {
+                            self.expr(channel);
+                            self.expr(value);
+                        }
+                        SelectCaseKind::Default => {}
+                    }
+                    self.stmt(&mut case.body);
+                }
+            }
+            Stmt::Switch { expr, cases, default, .. } => {
+                self.expr(expr);
+                for case in cases {
+                    case.values.iter_mut().for_each(|value| self.expr(value));
+                    self.stmt(&mut case.body);
+                }
+                if let Some(default) = default {
+                    self.stmt(default);
+                }
+            }
+            Stmt::TryCatch { try_block, catch_block, finally_block, .. } => {
+                self.stmt(try_block);
+                self.stmt(catch_block);
+                if let Some(finally_block) = finally_block {
+                    self.stmt(finally_block);
+                }
+            }
+            Stmt::Recovering { body, handler, .. } => {
+                self.stmt(body);
+                self.stmt(handler);
+            }
+            Stmt::Break { .. }
+            | Stmt::Continue { .. }
+            | Stmt::StructDef { .. }
+            | Stmt::ClassDef { .. }
+            | Stmt::InterfaceDef { .. }
+            | Stmt::TraitDef { .. }
+            | Stmt::EnumDef { .. }
+            | Stmt::TypeAlias { .. }
+            | Stmt::FnDef { .. }
+            | Stmt::Package { .. }
+            | Stmt::Import { .. } => {}
+        }
+    }
+
+    fn pattern(&mut self, pattern: &mut MatchPattern) {
+        match pattern {
+            MatchPattern::Literal(expr) => self.expr(expr),
+            MatchPattern::Type { type_ann, .. } => self.ty(&mut type_ann.ty),
+            MatchPattern::Or(patterns) | MatchPattern::EnumVariant { fields: patterns, .. } => {
+                patterns.iter_mut().for_each(|p| self.pattern(p))
+            }
+            MatchPattern::Range { start, end, .. } => {
+                self.expr(start);
+                self.expr(end);
+            }
+            MatchPattern::Variable(_) | MatchPattern::Wildcard => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Call { callee, args, span } => {
+                self.call(callee, *span);
+                self.expr(callee);
+                args.iter_mut().for_each(|(_, arg)| self.expr(arg));
+            }
+            Expr::Closure { params, return_type, body, .. } => {
+                self.params(params);
+                if let Some(ann) = return_type {
+                    self.ty(&mut ann.ty);
+                }
+                self.stmt(body);
+            }
+            Expr::Cast { expr: inner, target_type, .. } => {
+                self.ty(&mut target_type.ty);
+                self.expr(inner);
+            }
+            Expr::TypeCheck { expr: inner, check_type, .. } => {
+                self.ty(&mut check_type.ty);
+                self.expr(inner);
+            }
+            Expr::ChannelNew { element_type, capacity, .. } => {
+                self.ty(element_type);
+                if let Some(capacity) = capacity {
+                    self.expr(capacity);
+                }
+            }
+            Expr::StringInterpolation { parts, .. } => {
+                for part in parts {
+                    if let StringInterpPart::Expr(expr) = part {
+                        self.expr(expr);
+                    }
+                }
+            }
+            Expr::Binary { left, right, .. } | Expr::NullCoalesce { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::Assign { target: left, value: right, .. } | Expr::Index { object: left, index: right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::Unary { operand: inner, .. }
+            | Expr::Grouping { expr: inner, .. }
+            | Expr::Go { call: inner, .. }
+            | Expr::Member { object: inner, .. }
+            | Expr::SafeMember { object: inner, .. }
+            | Expr::NonNullMember { object: inner, .. }
+            | Expr::PostIncrement { operand: inner, .. }
+            | Expr::PostDecrement { operand: inner, .. } => self.expr(inner),
+            Expr::Range { start, end, .. } => {
+                for bound in [start, end].into_iter().flatten() {
+                    self.expr(bound);
+                }
+            }
+            Expr::IfExpr { condition, then_branch, else_branch, .. } => {
+                self.expr(condition);
+                self.expr(then_branch);
+                self.expr(else_branch);
+            }
+            Expr::Array { elements, .. } => elements.iter_mut().for_each(|e| self.expr(e)),
+            Expr::New { args, fields, .. } => {
+                args.iter_mut().for_each(|e| self.expr(e));
+                fields.iter_mut().flatten().for_each(|(_, e)| self.expr(e));
+            }
+            Expr::MapLiteral { entries, .. } => {
+                for (key, value) in entries {
+                    self.expr(key);
+                    self.expr(value);
+                }
+            }
+            Expr::StructLiteral { fields, .. } => fields.iter_mut().for_each(|(_, e)| self.expr(e)),
+            Expr::Integer { .. }
+            | Expr::Float { .. }
+            | Expr::String { .. }
+            | Expr::Bool { .. }
+            | Expr::Char { .. }
+            | Expr::Null { .. }
+            | Expr::Identifier { .. }
+            | Expr::This { .. }
+            | Expr::Super { .. }
+            | Expr::Default { .. }
+            | Expr::StaticMember { .. } => {}
+        }
+    }
+
+    /// 调用的类型实参（代入外层的类型参数后）都已确定时，改为调用对应的实例
+    fn call(&mut self, callee: &mut Expr, span: Span) {
+        if !self.rewrite_calls {
+            return;
+        }
+        let Expr::Identifier { name, .. } = callee else { return };
+        let Some(call) = self.table.generic_call(self.statement, span) else { return };
+        if call.function != *name || !self.function_defs.contains_key(name) {
+            return;
+        }
+        let type_args: Vec<Type> = call.type_args.iter()
+            .map(|t| instantiate_type_params(t, &self.substitution))
+            .collect();
+        if !type_args.iter().all(is_concrete) {
+            return;
+        }
+        *name = MonoKey::new(name.clone(), type_args.clone()).mangled_name();
+        self.requests.push((call.function.clone(), type_args));
+    }
+}
+
+/// 收集类型中的泛型实例 (基类型名, 类型实参)，包括嵌套在类型实参中的
+fn collect_generic_instances(ty: &Type, out: &mut Vec<(String, Vec<Type>)>) {
+    match ty {
+        Type::Generic { base_type, type_args } => {
+            if let Type::Class(name) | Type::Struct(name) = base_type.as_ref() {
+                out.push((name.clone(), type_args.clone()));
+            }
+            type_args.iter().for_each(|t| collect_generic_instances(t, out));
+        }
+        Type::Array { element_type, .. } | Type::Slice { element_type } | Type::Channel { element_type } => {
+            collect_generic_instances(element_type, out)
+        }
+        Type::Map { key_type, value_type } => {
+            collect_generic_instances(key_type, out);
+            collect_generic_instances(value_type, out);
+        }
+        Type::Tuple(types) => types.iter().for_each(|t| collect_generic_instances(t, out)),
+        Type::Nullable(inner) | Type::Pointer(inner) => collect_generic_instances(inner, out),
+        _ => {}
+    }
+}
+
 impl Default for Monomorphizer {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::Locale;
+    use crate::lexer::Scanner;
+    use crate::parser::Parser;
+
+    fn monomorphizer(source: &str) -> Monomorphizer {
+        let program = Parser::new(Scanner::new(source).scan_tokens(), Locale::En).parse().expect("parse failed");
+        let mut monomorphizer = Monomorphizer::new();
+        monomorphizer.collect_definitions(&program);
+        monomorphizer
+    }
+
+    #[test]
+    fn test_field_instantiations_terminate() {
+        // 字段中的实例随之实例化，回到已有的实例时停止
+        let mut mono = monomorphizer("class Box<T> {\n    var value: T\n    var next: Box<T>?\n    var pairs: Pair<T>[]\n}\nstruct Pair<T> {\n    left: T\n}\n");
+        mono.request_class("Box", vec![Type::Int]);
+        mono.process_all();
+        assert!(mono.errors().is_empty());
+        let boxed = mono.get_monomorphized_class(&MonoKey::new("Box", vec![Type::Int])).unwrap();
+        assert_eq!(boxed.fields[0].ty, Type::Int);
+        assert!(mono.get_monomorphized_struct(&MonoKey::new("Pair", vec![Type::Int])).is_some());
+
+        // 字段引用了类型实参更深的自身
+        let mut mono = monomorphizer("class Box<T> {\n    var inner: Box<Box<T> >?\n}\n");
+        mono.request_class("Box", vec![Type::Int]);
+        mono.process_all();
+        assert_eq!(mono.errors().len(), 1);
+        assert_eq!(
+            mono.errors()[0].to_string(),
+            "generic instantiation does not terminate: Box<int> -> Box<Box<int>> -> Box<Box<Box<int>>> -> ..."
+        );
+    }
+
+    #[test]
+    fn test_specialize_polymorphic_recursion_terminates() {
+        // 每层调用的类型实参都更深：实例化到深度上限为止，更深的调用仍然调用原来的泛型函数
+        let source = "func nest<T>(x: T, n: int) int {\n    if n == 0 {\n        return 0\n    }\n    return 1 + nest([x], n - 1)\n}\nfunc main() {\n    println(nest(1, 3))\n}\n";
+        let mut program = Parser::new(Scanner::new(source).scan_tokens(), Locale::En).parse().unwrap();
+        let mut checker = super::super::TypeChecker::new();
+        checker.check_program(&program).unwrap();
+        let mut table = checker.take_type_table();
+        let mut mono = Monomorphizer::new();
+        mono.collect_definitions(&program);
+        mono.specialize(&mut program, &mut table);
+        let instances = program.statements.len() - 2;
+        assert_eq!(instances, MAX_INSTANTIATION_DEPTH);
+        assert_eq!(table.origin_statement(program.statements.len() - 1), 0);
+    }
+}
@@ -0,0 +1,94 @@
+//! 类型检查的结果表，供单态化和代码生成使用
+//!
+//! 语法树没有节点编号，节点用 (顶层语句下标, 源码范围) 标识：合并编译时不同文件的语句的源码范围可能重合，
+//! 加上顶层语句下标后在整个程序中唯一。范围相同的嵌套节点记录最外层节点的类型。
+//! 单态化追加的函数与原来的泛型函数共用语法树的范围，通过 [`TypeTable::alias_statement`] 指向原来的语句。
+
+use std::collections::{HashMap, HashSet};
+
+use crate::lexer::Span;
+use crate::parser::Expr;
+use crate::types::Type;
+
+/// 语法树节点的标识
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeKey {
+    /// 所在的顶层语句的下标
+    pub statement: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl NodeKey {
+    pub fn new(statement: usize, span: Span) -> Self {
+        Self { statement, start: span.start, end: span.end }
+    }
+}
+
+/// 泛型函数调用推导出的类型实参
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenericCall {
+    /// 被调用的顶层泛型函数
+    pub function: String,
+    /// 按类型参数的声明顺序；在泛型函数体中可能引用外层的类型参数
+    pub type_args: Vec<Type>,
+}
+
+/// 表达式类型表
+#[derive(Debug, Clone, Default)]
+pub struct TypeTable {
+    /// 表达式的类型（已代入确定的空字面量类型）
+    expr_types: HashMap<NodeKey, Type>,
+    /// 泛型函数调用（按调用表达式）
+    generic_calls: HashMap<NodeKey, GenericCall>,
+    /// 被赋过与声明类型不同的值（如 dynamic）的变量名，按顶层语句记录
+    loosely_assigned: HashSet<(usize, String)>,
+    /// 追加的顶层语句 -> 它的语法树来自的语句
+    aliases: HashMap<usize, usize>,
+}
+
+impl TypeTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 顶层语句的语法树来自的语句（不是追加的语句时为它自己）
+    pub fn origin_statement(&self, statement: usize) -> usize {
+        self.aliases.get(&statement).copied().unwrap_or(statement)
+    }
+
+    pub(super) fn record_expr(&mut self, statement: usize, span: Span, ty: Type) {
+        self.expr_types.insert(NodeKey::new(statement, span), ty);
+    }
+
+    pub(super) fn record_generic_call(&mut self, statement: usize, span: Span, call: GenericCall) {
+        self.generic_calls.insert(NodeKey::new(statement, span), call);
+    }
+
+    pub(super) fn record_loose_assignment(&mut self, statement: usize, name: &str) {
+        self.loosely_assigned.insert((statement, name.to_string()));
+    }
+
+    /// 追加的顶层语句 `statement` 的语法树复制自 `origin`
+    pub fn alias_statement(&mut self, statement: usize, origin: usize) {
+        let origin = self.origin_statement(origin);
+        self.aliases.insert(statement, origin);
+    }
+
+    /// 顶层语句 `statement` 中表达式的类型
+    pub fn expr_type(&self, statement: usize, expr: &Expr) -> Option<&Type> {
+        self.expr_types.get(&NodeKey::new(self.origin_statement(statement), expr.span()))
+    }
+
+    /// 顶层语句 `statement` 中位于 `span` 的泛型函数调用
+    pub fn generic_call(&self, statement: usize, span: Span) -> Option<&GenericCall> {
+        self.generic_calls.get(&NodeKey::new(self.origin_statement(statement), span))
+    }
+
+    /// 变量 `name` 在顶层语句 `statement` 中是否被赋过与声明类型不同的值
+    ///
+    /// 按名字记录，同名的不同变量一起算：只会让代码生成少用一些快速路径
+    pub fn is_loosely_assigned(&self, statement: usize, name: &str) -> bool {
+        self.loosely_assigned.contains(&(self.origin_statement(statement), name.to_string()))
+    }
+}
@@ -5,7 +5,7 @@
 use std::collections::HashMap;
 use crate::types::{Type, TypeVar, Substitution};
 use crate::lexer::Span;
-use super::error::{TypeError, TypeErrorKind};
+use super::error::{TypeError, TypeErrorKind, TypePathSegment};
 
 /// 统一结果
 pub type UnifyResult = Result<Substitution, TypeError>;
@@ -89,11 +89,13 @@ impl Unifier {
                     ));
                 }
                 
-                for (pt1, pt2) in p1.iter().zip(p2.iter()) {
-                    self.unify(pt1, pt2, span)?;
+                for (i, (pt1, pt2)) in p1.iter().zip(p2.iter()).enumerate() {
+                    self.unify(pt1, pt2, span)
+                        .map_err(|e| e.within(TypePathSegment::Parameter(i), t1))?;
                 }
                 
                 self.unify(r1, r2, span)
+                    .map_err(|e| e.within(TypePathSegment::ReturnType, t1))
             }
             
             // 数组类型
@@ -105,11 +107,13 @@ impl Unifier {
                     return Err(TypeError::type_mismatch(t1.clone(), t2.clone(), span));
                 }
                 self.unify(e1, e2, span)
+                    .map_err(|e| e.within(TypePathSegment::ElementType, t1))
             }
             
             // 切片类型
             (Slice { element_type: e1 }, Slice { element_type: e2 }) => {
                 self.unify(e1, e2, span)
+                    .map_err(|e| e.within(TypePathSegment::ElementType, t1))
             }
             
             // Map 类型
@@ -117,8 +121,10 @@ impl Unifier {
                 Map { key_type: k1, value_type: v1 },
                 Map { key_type: k2, value_type: v2 },
             ) => {
-                self.unify(k1, k2, span)?;
+                self.unify(k1, k2, span)
+                    .map_err(|e| e.within(TypePathSegment::MapKey, t1))?;
                 self.unify(v1, v2, span)
+                    .map_err(|e| e.within(TypePathSegment::MapValue, t1))
             }
             
             // 元组类型
@@ -126,8 +132,9 @@ impl Unifier {
                 if ts1.len() != ts2.len() {
                     return Err(TypeError::type_mismatch(t1.clone(), t2.clone(), span));
                 }
-                for (tt1, tt2) in ts1.iter().zip(ts2.iter()) {
-                    self.unify(tt1, tt2, span)?;
+                for (i, (tt1, tt2)) in ts1.iter().zip(ts2.iter()).enumerate() {
+                    self.unify(tt1, tt2, span)
+                        .map_err(|e| e.within(TypePathSegment::TupleElement(i), t1))?;
                 }
                 Ok(self.substitution.clone())
             }
@@ -135,6 +142,7 @@ impl Unifier {
             // 可空类型
             (Nullable(n1), Nullable(n2)) => {
                 self.unify(n1, n2, span)
+                    .map_err(|e| e.within(TypePathSegment::NullableInner, t1))
             }
             
             // 非空类型到可空类型的统一
@@ -145,6 +153,7 @@ impl Unifier {
             // 指针类型
             (Pointer(p1), Pointer(p2)) => {
                 self.unify(p1, p2, span)
+                    .map_err(|e| e.within(TypePathSegment::PointerTarget, t1))
             }
             
             // 泛型类型
@@ -164,8 +173,9 @@ impl Unifier {
                     ));
                 }
                 
-                for (arg1, arg2) in a1.iter().zip(a2.iter()) {
-                    self.unify(arg1, arg2, span)?;
+                for (i, (arg1, arg2)) in a1.iter().zip(a2.iter()).enumerate() {
+                    self.unify(arg1, arg2, span)
+                        .map_err(|e| e.within(TypePathSegment::TypeArgument(i), t1))?;
                 }
                 
                 Ok(self.substitution.clone())
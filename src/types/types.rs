@@ -339,6 +339,10 @@ pub enum Type {
         key_type: Box<Type>,
         value_type: Box<Type>,
     },
+    /// 通道类型 chan<T>
+    Channel {
+        element_type: Box<Type>,
+    },
     /// 元组类型
     Tuple(Vec<Type>),
     /// 函数/闭包类型
@@ -481,6 +485,11 @@ impl Type {
                     element_type: Box::new(element_type.substitute(subst)),
                 }
             }
+            Type::Channel { element_type } => {
+                Type::Channel {
+                    element_type: Box::new(element_type.substitute(subst)),
+                }
+            }
             Type::Map { key_type, value_type } => {
                 Type::Map {
                     key_type: Box::new(key_type.substitute(subst)),
@@ -516,8 +525,13 @@ impl Type {
                 }
             }
             
-            // 类型变量：保持不变（由 unification 处理）
-            Type::TypeVar(_) => self.clone(),
+            // 类型变量：查找 unification 记录的替换（键为 "?T{id}"）
+            Type::TypeVar(v) => {
+                match subst.get(&format!("?T{}", v.id)) {
+                    Some(replacement) => replacement.substitute(subst),
+                    None => self.clone(),
+                }
+            }
             
             // 关联类型：递归替换基类型
             Type::AssociatedType { base_type, name } => {
@@ -544,6 +558,7 @@ impl Type {
                 }
             }
             Type::Array { element_type, .. } | Type::Slice { element_type } | 
+            Type::Channel { element_type } |
             Type::Nullable(element_type) | Type::Pointer(element_type) => {
                 element_type.collect_type_vars(vars);
             }
@@ -595,6 +610,7 @@ impl Type {
         match self {
             Type::TypeParameter { .. } => true,
             Type::Array { element_type, .. } | Type::Slice { element_type } |
+            Type::Channel { element_type } |
             Type::Nullable(element_type) | Type::Pointer(element_type) => {
                 element_type.has_type_params()
             }
@@ -859,6 +875,7 @@ impl fmt::Display for Type {
             Type::Never => write!(f, "never"),
             Type::Array { element_type, size } => write!(f, "{}[{}]", element_type, size),
             Type::Slice { element_type } => write!(f, "{}[]", element_type),
+            Type::Channel { element_type } => write!(f, "chan<{}>", element_type),
             Type::Map { key_type, value_type } => write!(f, "map[{}]{}", key_type, value_type),
             Type::Tuple(types) => {
                 write!(f, "(")?;
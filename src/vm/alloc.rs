@@ -0,0 +1,146 @@
+//! 分配前的大小检查
+//!
+//! 脚本可以请求任意大的字符串或数组（`"x".repeat(n)`、`(0..n).toArray()`）。
+//! 直接分配时，超大的请求要么让 Rust 的分配失败处理中止整个进程，要么让机器陷入交换。
+//! 嵌入宿主时脚本不能拖垮宿主进程，所以在分配前先算出需要的字节数，
+//! 超过上限时抛出可捕获的 RuntimeException，而不是尝试分配。
+//!
+//! 上限默认取进程启动后第一次检查时的可用内存，宿主可以用 [`set_allocation_limit`] 调整。
+
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use std::fmt::Write;
+
+use super::value::Value;
+use crate::stdlib::exception::stdlib_exception;
+
+/// 读不到可用内存时的上限
+const FALLBACK_LIMIT: usize = 8 << 30;
+
+/// 宿主设置的上限，0 表示按可用内存计算
+static LIMIT: AtomicUsize = AtomicUsize::new(0);
+
+/// 设置单次分配的字节数上限，0 恢复为按可用内存计算
+pub fn set_allocation_limit(bytes: usize) {
+    LIMIT.store(bytes, Ordering::Relaxed);
+}
+
+/// 当前单次分配的字节数上限
+pub fn allocation_limit() -> usize {
+    match LIMIT.load(Ordering::Relaxed) {
+        0 => available_memory(),
+        limit => limit,
+    }
+}
+
+/// 可用物理内存（只读取一次）
+fn available_memory() -> usize {
+    static AVAILABLE: OnceLock<usize> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| read_mem_available().unwrap_or(FALLBACK_LIMIT))
+}
+
+/// 从 /proc/meminfo 读取 MemAvailable
+fn read_mem_available() -> Option<usize> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|line| line.starts_with("MemAvailable:"))?;
+    let kb: usize = line.split_whitespace().nth(1)?.parse().ok()?;
+    kb.checked_mul(1024)
+}
+
+/// 拒绝分配时的错误（按 `stdlib_exception` 约定编码，VM 作为异常抛出）
+pub fn refused(bytes: u128) -> String {
+    stdlib_exception("RuntimeException", format!("allocation of {} bytes refused", bytes))
+}
+
+/// 检查一次分配的字节数
+pub fn check_bytes(bytes: u128) -> Result<(), String> {
+    if bytes > allocation_limit() as u128 {
+        return Err(refused(bytes));
+    }
+    Ok(())
+}
+
+/// 检查 `count` 个 `T` 的分配
+pub fn check_elements<T>(count: u128) -> Result<(), String> {
+    check_bytes(count.saturating_mul(std::mem::size_of::<T>() as u128))
+}
+
+/// 预先分配 `count` 个元素的 Vec，超过上限或分配失败时返回错误
+pub fn vec_with_capacity<T>(count: u128) -> Result<Vec<T>, String> {
+    check_elements::<T>(count)?;
+    let mut vec = Vec::new();
+    // 通过了上限检查说明 count 能放进 usize
+    vec.try_reserve_exact(count as usize)
+        .map_err(|_| refused(count * std::mem::size_of::<T>() as u128))?;
+    Ok(vec)
+}
+
+/// 追加一个元素，扩容失败时返回错误
+pub fn push<T>(vec: &mut Vec<T>, value: T) -> Result<(), String> {
+    if vec.len() == vec.capacity() {
+        // 扩容大约翻倍，按扩容后的大小检查
+        let grown = (vec.capacity().max(4) as u128) * 2;
+        check_elements::<T>(grown)?;
+        vec.try_reserve(1)
+            .map_err(|_| refused(grown * std::mem::size_of::<T>() as u128))?;
+    }
+    vec.push(value);
+    Ok(())
+}
+
+/// `s.repeat(count)`，先检查结果长度
+pub fn repeat_str(s: &str, count: i128) -> Result<String, String> {
+    if count < 0 {
+        return Err(stdlib_exception("IllegalArgumentException", format!("repeat count must be non-negative, got {}", count)));
+    }
+    check_bytes((s.len() as u128).saturating_mul(count as u128))?;
+    Ok(s.repeat(count as usize))
+}
+
+/// `s.padStart(width, fill)` / `s.padEnd(width, fill)`：用 fill 补足到 width 个字符，先检查结果长度
+pub fn pad_str(s: &str, width: i128, fill: char, at_start: bool) -> Result<String, String> {
+    let missing = width.saturating_sub(s.chars().count() as i128);
+    if missing <= 0 {
+        return Ok(s.to_string());
+    }
+    check_bytes((missing as u128).saturating_mul(fill.len_utf8() as u128) + s.len() as u128)?;
+    let padding: String = std::iter::repeat_n(fill, missing as usize).collect();
+    Ok(if at_start { padding + s } else { s.to_string() + &padding })
+}
+
+/// 拼接两个字符串，先检查结果长度
+pub fn concat_str(a: &str, b: &str) -> Result<String, String> {
+    check_bytes(a.len() as u128 + b.len() as u128)?;
+    let mut result = String::new();
+    result.try_reserve_exact(a.len() + b.len())
+        .map_err(|_| refused(a.len() as u128 + b.len() as u128))?;
+    result.push_str(a);
+    result.push_str(b);
+    Ok(result)
+}
+
+/// `arr.join(sep)`：元素按显示格式转换后用 sep 连接，先检查结果长度
+///
+/// 字符串元素和分隔符的总长度事先算出，一次分配；全是字符串时这就是结果的准确长度。
+/// 其他元素直接格式化到结果的末尾，不为每个元素单独分配字符串
+pub fn join_values(values: &[Value], sep: &str) -> Result<String, String> {
+    let separators = (sep.len() as u128).saturating_mul(values.len().saturating_sub(1) as u128);
+    let known = values.iter().filter_map(Value::as_string).fold(separators, |total, s| total + s.len() as u128);
+    check_bytes(known)?;
+    let mut result = String::new();
+    result.try_reserve_exact(known as usize).map_err(|_| refused(known))?;
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            result.push_str(sep);
+        }
+        match value.as_string() {
+            Some(s) => result.push_str(s),
+            None => {
+                let _ = write!(result, "{}", value);
+                check_bytes(result.len() as u128)?;
+            }
+        }
+    }
+    Ok(result)
+}
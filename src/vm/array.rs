@@ -0,0 +1,291 @@
+//! 数组方法中与虚拟机状态无关的部分（`flat`、`min` / `max`、`sum`、`chunk`、`unique`，
+//! 以及 `binarySearch` / `sortedInsert` / `isSorted` 用到的有序查找）
+
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use super::alloc;
+use super::value::Value;
+
+/// `arr.flat(depth)`：把嵌套的数组展开 depth 层，其他元素原样保留
+///
+/// 数组直接或间接包含自身时报错，而不是无限展开
+pub fn flat(elements: &[Value], depth: usize) -> Result<Vec<Value>, String> {
+    let mut result = Vec::new();
+    flat_into(elements, depth, &mut Vec::new(), &mut result)?;
+    Ok(result)
+}
+
+fn flat_into(
+    elements: &[Value],
+    depth: usize,
+    visiting: &mut Vec<*const Mutex<Vec<Value>>>,
+    out: &mut Vec<Value>,
+) -> Result<(), String> {
+    for element in elements {
+        match element.as_array().filter(|_| depth > 0) {
+            Some(inner) => {
+                let ptr = Arc::as_ptr(inner);
+                if visiting.contains(&ptr) {
+                    return Err("flat() cannot flatten an array that contains itself".to_string());
+                }
+                visiting.push(ptr);
+                let inner = inner.lock().clone();
+                flat_into(&inner, depth - 1, visiting, out)?;
+                visiting.pop();
+            }
+            None => alloc::push(out, *element)?,
+        }
+    }
+    Ok(())
+}
+
+/// 数值元素的值：整数保持精确，浮点数按 f64 比较
+#[derive(Clone, Copy)]
+enum Number {
+    Int(i128),
+    Float(f64),
+}
+
+impl Number {
+    fn of(value: &Value, method: &str, index: usize) -> Result<Self, String> {
+        if let Some(n) = value.as_int() {
+            Ok(Number::Int(n))
+        } else if let Some(f) = value.as_float() {
+            Ok(Number::Float(f))
+        } else {
+            Err(format!("{}() expects numeric elements, found {} at index {}", method, value.type_name(), index))
+        }
+    }
+
+    fn as_f64(self) -> f64 {
+        match self {
+            Number::Int(n) => n as f64,
+            Number::Float(f) => f,
+        }
+    }
+
+    fn is_nan(self) -> bool {
+        matches!(self, Number::Float(f) if f.is_nan())
+    }
+
+    fn less_than(self, other: Number) -> bool {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) => a < b,
+            _ => self.as_f64() < other.as_f64(),
+        }
+    }
+}
+
+/// `arr.min()` / `arr.max()`：元素必须都是数字，返回原元素；空数组返回 null，有 NaN 时结果为 NaN
+pub fn extreme(elements: &[Value], method: &str, want_max: bool) -> Result<Value, String> {
+    let mut best: Option<(Number, Value)> = None;
+    for (i, element) in elements.iter().enumerate() {
+        let number = Number::of(element, method, i)?;
+        let replace = match best {
+            None => true,
+            Some((current, _)) if current.is_nan() => false,
+            Some((current, _)) => {
+                number.is_nan() || if want_max { current.less_than(number) } else { number.less_than(current) }
+            }
+        };
+        if replace {
+            best = Some((number, *element));
+        }
+    }
+    Ok(best.map_or(Value::null(), |(_, value)| value))
+}
+
+/// `arr.sum()`：全是整数时结果为整数，有浮点数时为浮点数；空数组为 0
+pub fn sum(elements: &[Value]) -> Result<Value, String> {
+    let mut int_sum: i128 = 0;
+    let mut float_sum: Option<f64> = None;
+    for (i, element) in elements.iter().enumerate() {
+        match Number::of(element, "sum", i)? {
+            Number::Int(n) => {
+                int_sum = int_sum.checked_add(n).ok_or("sum() overflowed the integer range")?;
+            }
+            Number::Float(f) => *float_sum.get_or_insert(0.0) += f,
+        }
+    }
+    Ok(match float_sum {
+        Some(f) => Value::float(f + int_sum as f64),
+        None => Value::int(int_sum),
+    })
+}
+
+/// `arr.chunk(size)`：按顺序每 size 个元素一组，最后一组可以不满
+pub fn chunk(elements: &[Value], size: usize) -> Vec<Value> {
+    elements
+        .chunks(size)
+        .map(|chunk| Value::array(Arc::new(Mutex::new(chunk.to_vec()))))
+        .collect()
+}
+
+/// `arr.unique()`：去掉与前面的元素相等（`==`）的元素，保留第一次出现的顺序
+pub fn unique(elements: &[Value]) -> Vec<Value> {
+    let mut result: Vec<Value> = Vec::new();
+    for element in elements {
+        if !result.contains(element) {
+            result.push(*element);
+        }
+    }
+    result
+}
+
+/// 在有序的序列中二分查找，`compare(i)` 给出第 i 个元素与目标的顺序
+///
+/// 找到时返回 `Ok(下标)`，有多个相等的元素时是第一个；没有找到时返回 `Err(插入点)`，
+/// 即第一个大于目标的元素的位置。每次探测调用一次 `compare`，共 ⌈log₂(len + 1)⌉ 次以内
+pub fn binary_search<E>(len: usize, mut compare: impl FnMut(usize) -> Result<Ordering, E>) -> Result<Result<usize, usize>, E> {
+    let (mut low, mut high, mut found) = (0, len, false);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        match compare(mid)? {
+            Ordering::Less => low = mid + 1,
+            ordering => {
+                // 最后的 low 是某次探测到的不小于目标的位置，其中有相等的元素时 low 处就是第一个
+                found |= ordering == Ordering::Equal;
+                high = mid;
+            }
+        }
+    }
+    Ok(if found { Ok(low) } else { Err(low) })
+}
+
+/// 有序序列中第一个大于目标的位置，`sortedInsert` 把新元素放在相等的元素之后
+pub fn upper_bound<E>(len: usize, mut compare: impl FnMut(usize) -> Result<Ordering, E>) -> Result<usize, E> {
+    let (mut low, mut high) = (0, len);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        match compare(mid)? {
+            Ordering::Greater => high = mid,
+            _ => low = mid + 1,
+        }
+    }
+    Ok(low)
+}
+
+/// `binarySearch` 的返回值：找到时为下标，否则为 -(插入点 + 1)，总是负数
+pub fn search_result(result: Result<usize, usize>) -> i128 {
+    match result {
+        Ok(index) => index as i128,
+        Err(insertion_point) => -(insertion_point as i128) - 1,
+    }
+}
+
+/// 第一个大于下一个元素的位置，元素按值的全序不递减时返回 `None`
+pub fn first_unsorted(elements: &[Value]) -> Result<Option<usize>, String> {
+    for (i, pair) in elements.windows(2).enumerate() {
+        if pair[0].total_cmp(&pair[1])? == Ordering::Greater {
+            return Ok(Some(i));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn array(values: Vec<Value>) -> Value {
+        Value::array(Arc::new(Mutex::new(values)))
+    }
+
+    fn ints(values: &[i128]) -> Vec<Value> {
+        values.iter().map(|&n| Value::int(n)).collect()
+    }
+
+    fn to_ints(values: &[Value]) -> Vec<i128> {
+        values.iter().map(|v| v.as_int().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_flat_depth_and_cycles() {
+        let nested = vec![Value::int(1), array(vec![Value::int(2), array(ints(&[3, 4]))]), array(vec![])];
+        let once = flat(&nested, 1).unwrap();
+        assert_eq!(once.len(), 3);
+        assert_eq!(once[0].as_int(), Some(1));
+        assert!(once[2].as_array().is_some());
+        assert_eq!(to_ints(&flat(&nested, 5).unwrap()), vec![1, 2, 3, 4]);
+        assert_eq!(flat(&nested, 0).unwrap().len(), 3);
+
+        let cyclic = array(ints(&[1]));
+        cyclic.as_array().unwrap().lock().push(cyclic);
+        assert_eq!(flat(&[cyclic], 1).unwrap().len(), 2);
+        assert_eq!(flat(&[cyclic], 3).unwrap_err(), "flat() cannot flatten an array that contains itself");
+    }
+
+    #[test]
+    fn test_min_max_sum() {
+        let mixed = vec![Value::int(3), Value::float(-1.5), Value::int(7)];
+        assert_eq!(extreme(&mixed, "min", false).unwrap().as_float(), Some(-1.5));
+        assert_eq!(extreme(&mixed, "max", true).unwrap().as_int(), Some(7));
+        assert!(extreme(&[], "min", false).unwrap().is_null());
+        // 超出 f64 精度的整数仍然精确比较
+        let big = ints(&[(1 << 60) + 1, 1 << 60]);
+        assert_eq!(extreme(&big, "max", true).unwrap().as_int(), Some((1 << 60) + 1));
+        let nan = vec![Value::int(1), Value::float(f64::NAN), Value::int(2)];
+        assert!(extreme(&nan, "max", true).unwrap().as_float().unwrap().is_nan());
+
+        assert_eq!(sum(&ints(&[1, 2, 3])).unwrap().as_int(), Some(6));
+        assert_eq!(sum(&mixed).unwrap().as_float(), Some(8.5));
+        assert_eq!(sum(&[]).unwrap().as_int(), Some(0));
+        assert_eq!(
+            sum(&[Value::int(1), Value::string("2".to_string())]).unwrap_err(),
+            "sum() expects numeric elements, found string at index 1"
+        );
+        assert_eq!(sum(&ints(&[i128::MAX, 1])).unwrap_err(), "sum() overflowed the integer range");
+    }
+
+    #[test]
+    fn test_chunk_and_unique() {
+        let chunks = chunk(&ints(&[1, 2, 3, 4, 5]), 2);
+        let sizes: Vec<usize> = chunks.iter().map(|c| c.as_array().unwrap().lock().len()).collect();
+        assert_eq!(sizes, vec![2, 2, 1]);
+        assert_eq!(to_ints(&unique(&ints(&[3, 1, 3, 2, 1]))), vec![3, 1, 2]);
+    }
+
+    fn search(elements: &[Value], target: i128) -> (i128, usize) {
+        let mut probes = 0;
+        let result = binary_search(elements.len(), |i| {
+            probes += 1;
+            elements[i].total_cmp(&Value::int(target))
+        });
+        (search_result(result.unwrap()), probes)
+    }
+
+    #[test]
+    fn test_binary_search() {
+        let sorted = ints(&[1, 3, 3, 3, 5, 7]);
+        assert_eq!(search(&sorted, 1).0, 0);
+        assert_eq!(search(&sorted, 7).0, 5);
+        // 重复元素返回第一个
+        assert_eq!(search(&sorted, 3).0, 1);
+        // 没有找到：-(插入点 + 1)
+        assert_eq!(search(&sorted, 0).0, -1);
+        assert_eq!(search(&sorted, 4).0, -5);
+        assert_eq!(search(&sorted, 8).0, -7);
+        assert_eq!(search(&[], 1).0, -1);
+
+        let large = ints(&(0..1000).map(|n| n * 2).collect::<Vec<_>>());
+        for (target, expected) in [(0, 0), (998, 499), (1998, 999), (1999, -1001), (-5, -1)] {
+            let (result, probes) = search(&large, target);
+            assert_eq!(result, expected);
+            assert!(probes <= 10, "{} probes for {}", probes, target);
+        }
+
+        let upper = upper_bound(sorted.len(), |i| sorted[i].total_cmp(&Value::int(3))).unwrap();
+        assert_eq!(upper, 4);
+    }
+
+    #[test]
+    fn test_first_unsorted() {
+        assert_eq!(first_unsorted(&ints(&[1, 2, 2, 9])).unwrap(), None);
+        assert_eq!(first_unsorted(&ints(&[1, 5, 2, 9])).unwrap(), Some(1));
+        assert_eq!(first_unsorted(&[]).unwrap(), None);
+        assert!(first_unsorted(&[Value::int(1), Value::string("a".to_string())]).is_err());
+    }
+}
@@ -0,0 +1,298 @@
+//! 未捕获运行时错误的栈追踪输出
+//!
+//! 错误类型和消息放在第一行，之后从出错位置开始逐帧列出函数名和 `文件:行`：
+//!
+//! ```text
+//! RuntimeError: Index 5 out of bounds for array of length 1
+//!   at f (app.q:4)
+//!     4 |         return a[5]
+//!       |         ^^^^^^^^^^^
+//!   at f (app.q:6:12)
+//!     6 |     return f(n - 1) + 1
+//!       |            ^^^^^^^^
+//!   [frame repeated 49 times]
+//! ```
+//!
+//! 源码在出错时才按路径重新读取，由调用方提供的加载函数负责；文件已删除或内容已改变时
+//! 加载函数返回 None，对应的帧只显示位置。递归产生的连续相同帧折叠为一行。
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use super::vm::{RuntimeError, StackFrame};
+use crate::diagnostics::{paint, render_line, source_line, Style};
+use crate::stdlib::json::write_json_string;
+
+/// 栈追踪输出格式（`--trace-format`）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// 每帧一行，不带源码
+    Compact,
+    /// 每帧附带源码行和插入符号
+    #[default]
+    Full,
+    /// 单行 JSON，便于日志收集
+    Json,
+}
+
+impl TraceFormat {
+    /// 解析格式名
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "compact" => Some(Self::Compact),
+            "full" => Some(Self::Full),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+/// 源码加载函数：参数为帧的文件路径（没有文件信息时为 None），返回当前的源码
+pub type SourceLoader<'a> = dyn FnMut(Option<&str>) -> Option<String> + 'a;
+
+/// 渲染未捕获的运行时错误
+pub fn render_error(error: &RuntimeError, format: TraceFormat, color: bool, load_source: &mut SourceLoader) -> String {
+    let frames = collapse(&error.stack_trace);
+    match format {
+        TraceFormat::Json => render_json(error, &frames),
+        TraceFormat::Compact | TraceFormat::Full => {
+            let mut out = format!("{}: {}", paint("RuntimeError", Style::Error, color), error.message);
+            if frames.is_empty() {
+                let _ = write!(out, " (line {})", error.line);
+            }
+            let mut sources: HashMap<Option<String>, Option<String>> = HashMap::new();
+            for (i, &(frame, repeated)) in frames.iter().enumerate() {
+                let location = paint(&format!("at {} ({})", frame.function_name, location(frame)), Style::Emphasis, color && i == 0);
+                let _ = write!(out, "\n  {}", location);
+                if format == TraceFormat::Full {
+                    let source = sources
+                        .entry(frame.file_name.clone())
+                        .or_insert_with(|| load_source(frame.file_name.as_deref()));
+                    match source.as_deref().and_then(|source| source_line(source, frame.line)) {
+                        Some(line) => out.push_str(&render_frame_line(frame, line, color && i == 0)),
+                        None => out.push_str("\n      (source unavailable)"),
+                    }
+                }
+                if repeated > 0 {
+                    let _ = write!(out, "\n  {}", paint(&format!("[frame repeated {} times]", repeated), Style::Dim, color));
+                }
+            }
+            out
+        }
+    }
+}
+
+/// 合并连续的相同帧，返回 (帧, 额外重复次数)
+fn collapse(frames: &[StackFrame]) -> Vec<(&StackFrame, usize)> {
+    let mut collapsed: Vec<(&StackFrame, usize)> = Vec::new();
+    for frame in frames {
+        match collapsed.last_mut() {
+            Some((last, repeated)) if same_frame(last, frame) => *repeated += 1,
+            _ => collapsed.push((frame, 0)),
+        }
+    }
+    collapsed
+}
+
+fn same_frame(a: &StackFrame, b: &StackFrame) -> bool {
+    a.function_name == b.function_name && a.file_name == b.file_name && a.line == b.line && a.column == b.column
+}
+
+/// `文件:行[:列]`
+fn location(frame: &StackFrame) -> String {
+    let file = frame.file_name.as_deref().unwrap_or("<unknown>");
+    match frame.column {
+        Some(column) => format!("{}:{}:{}", file, frame.line, column),
+        None => format!("{}:{}", file, frame.line),
+    }
+}
+
+/// 帧对应的源码行和插入符号（带行号栏）
+///
+/// 有表达式位置时下划线覆盖出错的表达式或调用表达式，否则覆盖整行去掉缩进后的内容
+fn render_frame_line(frame: &StackFrame, line: &str, highlight: bool) -> String {
+    let (column, len) = match frame.column {
+        Some(column) => (column, chars_in_bytes(line, column, frame.len)),
+        None => {
+            let indent = line.chars().take_while(|c| c.is_whitespace()).count();
+            (indent + 1, line.trim().chars().count())
+        }
+    };
+    let (text, carets) = render_line(line, column, len);
+    let padding = carets.len() - carets.trim_start().len();
+    let gutter = frame.line.to_string().len();
+    format!(
+        "\n    {:>gutter$} | {}\n    {:>gutter$} | {}{}",
+        frame.line,
+        text,
+        "",
+        &carets[..padding],
+        paint(&carets[padding..], Style::Error, highlight),
+        gutter = gutter
+    )
+}
+
+/// 从第 column 个字符开始、覆盖 len 个字节的字符数
+fn chars_in_bytes(line: &str, column: usize, len: usize) -> usize {
+    let mut bytes = 0;
+    line.chars()
+        .skip(column.saturating_sub(1))
+        .take_while(|c| {
+            let inside = bytes < len;
+            bytes += c.len_utf8();
+            inside
+        })
+        .count()
+}
+
+fn render_json(error: &RuntimeError, frames: &[(&StackFrame, usize)]) -> String {
+    let mut out = String::from("{\"type\":\"RuntimeError\",\"message\":");
+    write_json_string(&mut out, &error.message);
+    let _ = write!(out, ",\"line\":{},\"frames\":[", error.line);
+    for (i, &(frame, repeated)) in frames.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str("{\"function\":");
+        write_json_string(&mut out, &frame.function_name);
+        out.push_str(",\"file\":");
+        match &frame.file_name {
+            Some(file) => write_json_string(&mut out, file),
+            None => out.push_str("null"),
+        }
+        let _ = write!(out, ",\"line\":{},\"column\":", frame.line);
+        match frame.column {
+            Some(column) => { let _ = write!(out, "{}", column); }
+            None => out.push_str("null"),
+        }
+        let _ = write!(out, ",\"repeated\":{}}}", repeated);
+    }
+    out.push_str("]}");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+    use crate::i18n::Locale;
+    use crate::lexer::Scanner;
+    use crate::parser::Parser;
+    use crate::vm::VM;
+    use std::sync::Arc;
+
+    const LIB: &str = "func check(n: int) int {
+    var items: int[] = [1, 2]
+    return items[n]
+}
+
+func lookup(n: int) int {
+    return check(n) * 2
+}
+";
+
+    /// 把多个 (文件名, 源码) 按顺序合并编译运行，返回运行时错误
+    fn run_files(files: &[(&str, &str)]) -> RuntimeError {
+        let mut statements = Vec::new();
+        let mut source_files = Vec::new();
+        for (name, source) in files {
+            let tokens = Scanner::new(source).scan_tokens();
+            let program = Parser::new(tokens, Locale::En).parse().unwrap();
+            source_files.push((name.to_string(), program.statements.len()));
+            statements.extend(program.statements);
+        }
+        let program = crate::parser::Program { package: None, imports: Vec::new(), statements };
+        let mut compiler = Compiler::new(Locale::En);
+        compiler.set_source_files(source_files);
+        let chunk = compiler.compile(&program).unwrap();
+        VM::new(Arc::new(chunk), Locale::En).run().unwrap_err()
+    }
+
+    fn render(error: &RuntimeError, format: TraceFormat, files: &[(&str, &str)]) -> String {
+        let mut load_source = |file: Option<&str>| {
+            files.iter().find(|(name, _)| Some(*name) == file).map(|(_, source)| source.to_string())
+        };
+        render_error(error, format, false, &mut load_source)
+    }
+
+    #[test]
+    fn test_call_chain_across_files() {
+        let files = [
+            ("lib.q", LIB),
+            ("app.q", "func main() {\n    var total = lookup(5)\n    println(total)\n}\n"),
+        ];
+        let error = run_files(&files);
+        assert_eq!(render(&error, TraceFormat::Full, &files), "\
+RuntimeError: Index 5 out of bounds for array of length 2
+  at check (lib.q:3:12)
+    3 |     return items[n]
+      |            ^^^^^^^^
+  at lookup (lib.q:7:12)
+    7 |     return check(n) * 2
+      |            ^^^^^^^^
+  at main (app.q:2:17)
+    2 |     var total = lookup(5)
+      |                 ^^^^^^^^^");
+        assert_eq!(render(&error, TraceFormat::Compact, &files), "\
+RuntimeError: Index 5 out of bounds for array of length 2
+  at check (lib.q:3:12)
+  at lookup (lib.q:7:12)
+  at main (app.q:2:17)");
+    }
+
+    #[test]
+    fn test_recursion_collapsed() {
+        let files = [("app.q", "func depth(n: int) int {
+    if n == 0 {
+        return [0][1]
+    }
+    return depth(n - 1) + 1
+}
+
+func main() {
+    println(depth(20))
+}
+")];
+        let error = run_files(&files);
+        assert_eq!(render(&error, TraceFormat::Full, &files), "\
+RuntimeError: Index 1 out of bounds for array of length 1
+  at depth (app.q:3:16)
+    3 |         return [0][1]
+      |                ^^^^^^
+  at depth (app.q:5:12)
+    5 |     return depth(n - 1) + 1
+      |            ^^^^^^^^^^^^
+  [frame repeated 19 times]
+  at main (app.q:9:13)
+    9 |     println(depth(20))
+      |             ^^^^^^^^^");
+        assert_eq!(render(&error, TraceFormat::Json, &files), concat!(
+            r#"{"type":"RuntimeError","message":"Index 1 out of bounds for array of length 1","line":3,"frames":["#,
+            r#"{"function":"depth","file":"app.q","line":3,"column":16,"repeated":0},"#,
+            r#"{"function":"depth","file":"app.q","line":5,"column":12,"repeated":19},"#,
+            r#"{"function":"main","file":"app.q","line":9,"column":13,"repeated":0}]}"#,
+        ));
+    }
+
+    #[test]
+    fn test_missing_source() {
+        let files = [
+            ("lib.q", LIB),
+            ("app.q", "func main() {\n    println(lookup(9))\n}\n"),
+        ];
+        let error = run_files(&files);
+        // lib.q 已经不存在，或者改动后的行数不够
+        let changed = [("app.q", files[1].1), ("lib.q", "func check() {}\n")];
+        let expected = "\
+RuntimeError: Index 9 out of bounds for array of length 2
+  at check (lib.q:3:12)
+      (source unavailable)
+  at lookup (lib.q:7:12)
+      (source unavailable)
+  at main (app.q:2:13)
+    2 |     println(lookup(9))
+      |             ^^^^^^^^^";
+        assert_eq!(render(&error, TraceFormat::Full, &files[1..]), expected);
+        assert_eq!(render(&error, TraceFormat::Full, &changed), expected);
+    }
+}
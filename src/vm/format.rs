@@ -0,0 +1,87 @@
+//! 字符串模板格式化（`str.format(args...)`）
+//!
+//! `{}` 依次取下一个参数，`{N}` 取第 N 个参数（从 0 开始），`{{` 和 `}}` 输出花括号本身。
+//! 参数按字符串插值的规则转换为字符串。
+
+use super::alloc::check_bytes;
+use crate::stdlib::exception::stdlib_exception;
+use super::value::Value;
+
+/// 按模板格式化；占位符不完整或引用了不存在的参数时返回 IllegalArgumentException
+pub fn format_template(template: &str, args: &[Value]) -> Result<String, String> {
+    let mut result = String::with_capacity(template.len());
+    let mut next = 0;
+    let mut chars = template.char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            '{' if chars.next_if(|&(_, c)| c == '{').is_some() => result.push('{'),
+            '}' if chars.next_if(|&(_, c)| c == '}').is_some() => result.push('}'),
+            '{' => {
+                let mut index = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '}')) => break,
+                        Some((_, d)) if d.is_ascii_digit() => index.push(d),
+                        _ => return Err(invalid(format!("invalid placeholder at position {}", position(template, offset)))),
+                    }
+                }
+                let index = if index.is_empty() {
+                    next += 1;
+                    next - 1
+                } else {
+                    index.parse().unwrap_or(usize::MAX)
+                };
+                let arg = args.get(index).ok_or_else(|| {
+                    invalid(format!("placeholder {{{}}} has no argument ({} given)", index, args.len()))
+                })?;
+                match arg.as_string() {
+                    Some(s) => result.push_str(s),
+                    None => result.push_str(&arg.to_string()),
+                }
+                check_bytes(result.len() as u128)?;
+            }
+            '}' => return Err(invalid(format!("unmatched '}}' at position {}", position(template, offset)))),
+            c => result.push(c),
+        }
+    }
+    Ok(result)
+}
+
+/// 字节偏移对应的字符位置
+fn position(template: &str, offset: usize) -> usize {
+    template[..offset].chars().count()
+}
+
+fn invalid(message: String) -> String {
+    stdlib_exception("IllegalArgumentException", format!("format(): {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(template: &str, args: &[Value]) -> String {
+        let error = format_template(template, args).unwrap_err();
+        error.strip_prefix("IllegalArgumentException: ").unwrap().to_string()
+    }
+
+    #[test]
+    fn test_placeholders() {
+        let args = [Value::string("q".to_string()), Value::int(3), Value::float(1.5)];
+        assert_eq!(format_template("{} has {} items", &args).unwrap(), "q has 3 items");
+        assert_eq!(format_template("{2}-{0}-{0}", &args).unwrap(), "1.5-q-q");
+        assert_eq!(format_template("{{{}}} }}", &args).unwrap(), "{q} }");
+        assert_eq!(format_template("世界{}", &args).unwrap(), "世界q");
+        assert_eq!(format_template("no placeholders", &[]).unwrap(), "no placeholders");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(error("{} {}", &[Value::int(1)]), "format(): placeholder {1} has no argument (1 given)");
+        assert_eq!(error("{9}", &[]), "format(): placeholder {9} has no argument (0 given)");
+        assert_eq!(error("a {x}", &[]), "format(): invalid placeholder at position 2");
+        assert_eq!(error("a {", &[]), "format(): invalid placeholder at position 2");
+        assert_eq!(error("a } b", &[]), "format(): unmatched '}' at position 2");
+        assert_eq!(error("世界 {", &[]), "format(): invalid placeholder at position 3");
+    }
+}
@@ -1,20 +1,44 @@
 //! 垃圾回收器实现
 //!
 //! 实现标记-清除和分代收集的 GC 系统
+//!
+//! 触发时机由分配速率决定：上次回收后新分配的字节数超过
+//! 存活集大小 × 增长系数（不低于最小阈值）时才需要下一次回收，
+//! 存活集越大，两次回收之间允许分配的越多。
+//!
+//! [`ConcurrentMarkGc`] 是增量收集器：根集扫描之后，标记和清除都在安全点分片进行，
+//! 每片不超过暂停目标的一半；只有根集扫描和重新标记需要一次完整暂停。
+//! 标记期间修改过的对象和新分配的对象经写屏障记入记忆集，在后续分片中重新扫描，
+//! 所以老对象在标记中途指向新对象不会导致新对象被提前回收。
+//!
+//! 虚拟机在安全点（开始运行时和循环回跳处）发起回收，根集是它的值栈和静态字段。
+//! 回收期间其他线程不能修改对象图，所以只有一个启用了 GC 的虚拟机在运行时才会回收：
+//! 其余虚拟机要么已经结束，要么停在会回调 Q 代码的原生方法里并交出了根集快照
+//! （见 [`park_mutator`]）。传到其他线程的值（协程参数、通道消息、回调返回值）
+//! 经 [`gc_escape`] 标记后不再释放。
+//!
+//! 弱引用（`WeakRef`、`WeakMap` 的键）登记在堆的弱引用表中，标记时不算作引用；
+//! 标记结束、清除开始之前删除目标不可达的条目（见 [`Heap::set_weak_ref`]）。
 
+use std::any::Any;
+use std::cell::Cell;
 use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use parking_lot::{Mutex, RwLock};
-use std::collections::{HashSet, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 
-use super::value::{Value, HeapTag, HeapObject};
+use super::value::{Value, HeapTag, HeapObject, IteratorSource};
 
 // ============================================================================
 // GC 常量配置
 // ============================================================================
 
-/// 年轻代大小阈值（字节）- 触发 Minor GC
-const YOUNG_GEN_THRESHOLD: usize = 1024 * 1024; // 1MB
+/// 两次 GC 之间至少分配的字节数
+const MIN_GC_TRIGGER: usize = 1024 * 1024; // 1MB
+
+/// 默认增长系数（百分比）：新分配量达到存活集大小的这个比例时触发下一次 GC
+const DEFAULT_GROWTH_PERCENT: usize = 100;
 
 /// 老年代大小阈值（字节）- 触发 Major GC
 const OLD_GEN_THRESHOLD: usize = 8 * 1024 * 1024; // 8MB
@@ -22,8 +46,14 @@ const OLD_GEN_THRESHOLD: usize = 8 * 1024 * 1024; // 8MB
 /// 对象晋升到老年代的年龄阈值
 const PROMOTION_AGE: u8 = 3;
 
-/// 分配计数触发 GC 的阈值
-const ALLOCATION_THRESHOLD: usize = 10000;
+/// 增量 GC 的默认暂停目标
+const DEFAULT_PAUSE_TARGET: Duration = Duration::from_millis(2);
+
+/// 分片中每处理这么多对象检查一次耗时
+const SLICE_CHECK_INTERVAL: usize = 16;
+
+/// 暂停时间直方图的桶数
+pub const PAUSE_BUCKETS: usize = 20;
 
 // ============================================================================
 // 堆对象元数据
@@ -95,6 +125,27 @@ pub struct GcStats {
     pub last_gc_time_ns: u64,
     /// 总 GC 暂停时间（纳秒）
     pub total_pause_time_ns: u64,
+    /// 增量 GC 完成的周期数
+    pub incremental_gc_count: u64,
+    /// 最长的单次暂停（纳秒）
+    pub max_pause_ns: u64,
+    /// 暂停时间直方图，见 [`GcStats::pause_bucket`]
+    pub pause_histogram: [u64; PAUSE_BUCKETS],
+}
+
+impl GcStats {
+    /// 完成的回收次数（所有收集器合计）
+    pub fn collections(&self) -> u64 {
+        self.minor_gc_count + self.major_gc_count + self.incremental_gc_count
+    }
+    
+    /// 暂停时间所在的直方图桶：第 0 个桶是不到 1 微秒，
+    /// 第 i 个桶是 [2^(i-1), 2^i) 微秒，最后一个桶包含所有更长的暂停
+    pub fn pause_bucket(pause_ns: u64) -> usize {
+        let micros = pause_ns / 1000;
+        let bucket = (u64::BITS - micros.leading_zeros()) as usize;
+        bucket.min(PAUSE_BUCKETS - 1)
+    }
 }
 
 // ============================================================================
@@ -127,14 +178,54 @@ pub struct Heap {
     young_size: AtomicUsize,
     /// 老年代大小
     old_size: AtomicUsize,
-    /// 分配计数
-    allocation_count: AtomicUsize,
+    /// 上次 GC 之后分配的字节数
+    allocated_since_gc: AtomicUsize,
+    /// 触发下一次 GC 的分配字节数
+    next_trigger: AtomicUsize,
+    /// 触发阈值的下限
+    min_trigger: AtomicUsize,
+    /// 增长系数（百分比）
+    growth_percent: AtomicUsize,
+    /// 程序要求在下一个安全点回收（`Runtime.gc()`）
+    collection_requested: AtomicBool,
     /// GC 统计
     stats: Mutex<GcStats>,
     /// 是否启用 GC
     enabled: AtomicBool,
     /// GC 正在运行标志
     gc_running: AtomicBool,
+    /// 增量标记进行中（写屏障只检查这个标志）
+    marking: AtomicBool,
+    /// 记忆集：标记期间被修改或新分配的对象，需要重新扫描
+    remembered: Mutex<Vec<Value>>,
+    /// 传到其他线程的对象：不可达时从堆中移除但不释放
+    escaped: Mutex<HashSet<u64>>,
+    /// 弱引用表
+    weak: Mutex<WeakTable>,
+    /// 标准库类的实例持有的原生资源，以实例的指针为键，实例被释放时丢弃
+    native: Mutex<HashMap<u64, Box<dyn Any + Send>>>,
+}
+
+/// 一个 WeakMap 的键
+struct WeakKeys {
+    /// 保存值的 map（WeakMap 实例的字段），键是 [`weak_key`]
+    entries: Value,
+    /// 键对象的指针
+    keys: HashSet<u64>,
+}
+
+/// 弱引用表：以持有者（WeakRef / WeakMap 实例）的指针为键，持有者被释放时删除它的条目
+#[derive(Default)]
+struct WeakTable {
+    /// WeakRef 实例 -> 目标
+    refs: HashMap<u64, Value>,
+    /// WeakMap 实例 -> 它的键
+    maps: HashMap<u64, WeakKeys>,
+}
+
+/// WeakMap 在保存值的 map 中使用的键：键对象的指针
+pub fn weak_key(ptr: u64) -> String {
+    format!("{:x}", ptr)
 }
 
 impl Heap {
@@ -145,10 +236,19 @@ impl Heap {
             old_gen: Mutex::new(Vec::with_capacity(256)),
             young_size: AtomicUsize::new(0),
             old_size: AtomicUsize::new(0),
-            allocation_count: AtomicUsize::new(0),
+            allocated_since_gc: AtomicUsize::new(0),
+            next_trigger: AtomicUsize::new(MIN_GC_TRIGGER),
+            min_trigger: AtomicUsize::new(MIN_GC_TRIGGER),
+            growth_percent: AtomicUsize::new(DEFAULT_GROWTH_PERCENT),
+            collection_requested: AtomicBool::new(false),
             stats: Mutex::new(GcStats::default()),
             enabled: AtomicBool::new(true),
             gc_running: AtomicBool::new(false),
+            marking: AtomicBool::new(false),
+            remembered: Mutex::new(Vec::new()),
+            escaped: Mutex::new(HashSet::new()),
+            weak: Mutex::new(WeakTable::default()),
+            native: Mutex::new(HashMap::new()),
         }
     }
     
@@ -169,7 +269,12 @@ impl Heap {
         
         self.young_gen.lock().push(obj);
         self.young_size.fetch_add(size, Ordering::Relaxed);
-        self.allocation_count.fetch_add(1, Ordering::Relaxed);
+        self.allocated_since_gc.fetch_add(size, Ordering::Relaxed);
+        
+        // 标记期间新分配的对象还没有被扫描过，和被修改的对象一样记入记忆集
+        if self.marking.load(Ordering::Acquire) {
+            self.remembered.lock().push(Value::from_heap_object(ptr, tag));
+        }
         
         // 更新统计
         {
@@ -179,12 +284,211 @@ impl Heap {
         }
     }
     
-    /// 检查是否需要 GC
+    /// 检查是否需要 GC：上次 GC 之后的分配量超过了触发阈值，或程序要求回收
     pub fn should_gc(&self) -> bool {
-        let young_size = self.young_size.load(Ordering::Relaxed);
-        let alloc_count = self.allocation_count.load(Ordering::Relaxed);
-        
-        young_size >= YOUNG_GEN_THRESHOLD || alloc_count >= ALLOCATION_THRESHOLD
+        self.allocated_since_gc.load(Ordering::Relaxed) >= self.next_trigger.load(Ordering::Relaxed)
+            || self.collection_requested.load(Ordering::Relaxed)
+    }
+    
+    /// 要求在下一个安全点回收，不论分配量
+    pub fn request_gc(&self) {
+        self.collection_requested.store(true, Ordering::Relaxed);
+    }
+    
+    /// 设置触发阈值的下限和增长系数
+    ///
+    /// 每次 GC 之后，下一次的触发阈值为 max(`min_trigger`, 存活字节数 × `growth_factor`)
+    pub fn set_pacing(&self, min_trigger: usize, growth_factor: f64) {
+        self.min_trigger.store(min_trigger, Ordering::Relaxed);
+        self.growth_percent.store((growth_factor * 100.0).max(0.0) as usize, Ordering::Relaxed);
+        self.update_trigger();
+    }
+    
+    /// 只设置触发阈值的下限，上次 GC 之后的分配量照常计入
+    pub fn set_min_trigger(&self, min_trigger: usize) {
+        self.min_trigger.store(min_trigger, Ordering::Relaxed);
+        self.recompute_trigger();
+    }
+    
+    /// 触发阈值的下限
+    pub fn min_trigger(&self) -> usize {
+        self.min_trigger.load(Ordering::Relaxed)
+    }
+    
+    /// 根据当前存活集重新计算触发阈值，并清零分配计数
+    fn update_trigger(&self) {
+        self.recompute_trigger();
+        self.allocated_since_gc.store(0, Ordering::Relaxed);
+        self.collection_requested.store(false, Ordering::Relaxed);
+    }
+    
+    fn recompute_trigger(&self) {
+        let live = self.young_size.load(Ordering::Relaxed) + self.old_size.load(Ordering::Relaxed);
+        let growth = live.saturating_mul(self.growth_percent.load(Ordering::Relaxed)) / 100;
+        self.next_trigger.store(growth.max(self.min_trigger.load(Ordering::Relaxed)), Ordering::Relaxed);
+    }
+    
+    /// 记录一次暂停
+    fn record_pause(&self, pause: Duration) {
+        let ns = pause.as_nanos() as u64;
+        let mut stats = self.stats.lock();
+        stats.last_gc_time_ns = ns;
+        stats.total_pause_time_ns += ns;
+        stats.max_pause_ns = stats.max_pause_ns.max(ns);
+        stats.pause_histogram[GcStats::pause_bucket(ns)] += 1;
+    }
+    
+    /// 写屏障：修改 `object` 的引用（字段、元素）之后调用
+    ///
+    /// 没有进行增量标记时只检查一个标志
+    #[inline]
+    pub fn write_barrier(&self, object: &Value) {
+        if self.marking.load(Ordering::Acquire) && object.is_heap_object() {
+            self.remembered.lock().push(*object);
+        }
+    }
+    
+    /// 把 `value` 及其可达的对象标记为已逃逸：其他线程可能还在使用它们，
+    /// 不可达时只从堆中移除，不释放
+    pub fn escape(&self, value: &Value) {
+        if !value.is_heap_object()
+            || self.young_size.load(Ordering::Relaxed) + self.old_size.load(Ordering::Relaxed) == 0
+        {
+            return;
+        }
+        let mut escaped = self.escaped.lock();
+        let mut visited = HashSet::new();
+        let mut pending = vec![*value];
+        while let Some(value) = pending.pop() {
+            if !value.is_heap_object() {
+                continue;
+            }
+            let ptr = value.as_ptr();
+            if ptr == 0 || !visited.insert(ptr) {
+                continue;
+            }
+            escaped.insert(ptr);
+            trace_references(&value, &mut |child| pending.push(*child));
+        }
+    }
+    
+    /// 处理一个不可达的对象：已逃逸的只移出堆，其余释放
+    ///
+    /// 返回对象是否被释放
+    fn reclaim(&self, obj: &AllocatedObject) -> bool {
+        if self.escaped.lock().remove(&obj.ptr) {
+            return false;
+        }
+        let mut resource = None;
+        if obj.tag == HeapTag::Class {
+            self.forget_weak_owner(obj.ptr);
+            resource = self.native.lock().remove(&obj.ptr);
+        }
+        free_object(obj);
+        // 在锁外丢弃资源
+        drop(resource);
+        true
+    }
+    
+    /// 把原生资源交给 `owner`（标准库类的实例）持有，实例被释放时丢弃资源
+    ///
+    /// 已逃逸的实例不可达时不释放，资源也一直保留
+    pub fn attach_native(&self, owner: &Value, resource: Box<dyn Any + Send>) {
+        self.native.lock().insert(owner.as_ptr(), resource);
+    }
+    
+    /// 登记弱引用：`owner` 是 WeakRef 实例，`target` 是堆对象
+    ///
+    /// 目标只被弱引用引用时，下一次回收在标记结束后删除这个条目，之后 [`Heap::weak_ref_target`] 返回 None
+    pub fn set_weak_ref(&self, owner: &Value, target: Value) {
+        self.weak.lock().refs.insert(owner.as_ptr(), target);
+    }
+    
+    /// 弱引用的目标，已被回收时返回 None
+    ///
+    /// 增量标记期间取出的目标记入记忆集：它可能被存到已扫描的对象里，本周期不能回收
+    pub fn weak_ref_target(&self, owner: &Value) -> Option<Value> {
+        let target = self.weak.lock().refs.get(&owner.as_ptr()).copied()?;
+        self.write_barrier(&target);
+        Some(target)
+    }
+    
+    /// 登记 WeakMap 的键；值由调用方以 [`weak_key`] 为键保存在 `entries` 中，键不可达时一起删除
+    pub fn add_weak_key(&self, owner: &Value, entries: &Value, key: &Value) {
+        self.weak.lock()
+            .maps
+            .entry(owner.as_ptr())
+            .or_insert_with(|| WeakKeys { entries: *entries, keys: HashSet::new() })
+            .keys
+            .insert(key.as_ptr());
+    }
+    
+    /// 注销 WeakMap 的键（条目被程序删除）
+    pub fn remove_weak_key(&self, owner: &Value, key: &Value) {
+        if let Some(map) = self.weak.lock().maps.get_mut(&owner.as_ptr()) {
+            map.keys.remove(&key.as_ptr());
+        }
+    }
+    
+    /// 标记结束后、清除之前调用：删除目标不可达的弱引用和键不可达的 WeakMap 条目
+    ///
+    /// 已逃逸的对象不可达时也不释放，它们的条目保留。此时还没有释放任何对象，可以安全地修改保存值的 map。
+    fn clear_weak(&self, is_marked: impl Fn(u64) -> bool) {
+        let mut weak = self.weak.lock();
+        if weak.refs.is_empty() && weak.maps.is_empty() {
+            return;
+        }
+        let escaped = self.escaped.lock();
+        let dead = |ptr: u64| !is_marked(ptr) && !escaped.contains(&ptr);
+        weak.refs.retain(|_, target| !dead(target.as_ptr()));
+        for map in weak.maps.values_mut() {
+            let dead_keys: Vec<u64> = map.keys.iter().copied().filter(|&key| dead(key)).collect();
+            if dead_keys.is_empty() {
+                continue;
+            }
+            if let Some(entries) = map.entries.as_map() {
+                let mut entries = entries.lock();
+                for key in &dead_keys {
+                    entries.remove(&weak_key(*key));
+                }
+            }
+            for key in &dead_keys {
+                map.keys.remove(key);
+            }
+        }
+    }
+    
+    /// 释放 WeakRef / WeakMap 实例时删除它的条目
+    fn forget_weak_owner(&self, ptr: u64) {
+        let mut weak = self.weak.lock();
+        if !weak.refs.is_empty() || !weak.maps.is_empty() {
+            weak.refs.remove(&ptr);
+            weak.maps.remove(&ptr);
+        }
+    }
+    
+    /// 清除结束后丢掉逃逸集合中不在堆里的指针（逃逸时未登记的对象）
+    fn prune_escaped(&self) {
+        let mut escaped = self.escaped.lock();
+        if escaped.is_empty() {
+            return;
+        }
+        let live: HashSet<u64> = self.young_gen.lock().iter()
+            .chain(self.old_gen.lock().iter())
+            .map(|o| o.ptr)
+            .collect();
+        escaped.retain(|ptr| live.contains(ptr));
+    }
+    
+    /// 是否正在进行增量标记
+    #[inline]
+    pub fn is_marking(&self) -> bool {
+        self.marking.load(Ordering::Acquire)
+    }
+    
+    /// 当前登记的对象数
+    pub fn object_count(&self) -> usize {
+        self.young_gen.lock().len() + self.old_gen.lock().len()
     }
     
     /// 检查是否需要 Major GC
@@ -249,16 +553,11 @@ impl MarkSweepGc {
         self.heap.gc_running.store(false, Ordering::Release);
         
         // 更新统计
-        {
-            let elapsed = start.elapsed().as_nanos() as u64;
-            let mut stats = self.heap.stats.lock();
-            stats.minor_gc_count += 1;
-            stats.last_gc_time_ns = elapsed;
-            stats.total_pause_time_ns += elapsed;
-        }
+        self.heap.record_pause(start.elapsed());
+        self.heap.stats.lock().minor_gc_count += 1;
         
-        // 重置分配计数
-        self.heap.allocation_count.store(0, Ordering::Relaxed);
+        // 按存活集计算下一次的触发阈值
+        self.heap.update_trigger();
         
         result
     }
@@ -274,6 +573,7 @@ impl MarkSweepGc {
         root_scanner(&mut |value| {
             self.mark_value(value, &mut marked_ptrs);
         });
+        self.heap.clear_weak(|ptr| marked_ptrs.contains(&ptr));
         
         // 2. 清除阶段：释放未标记的对象
         let mut young = self.heap.young_gen.lock();
@@ -300,13 +600,16 @@ impl MarkSweepGc {
                 }
             } else {
                 // 对象不可达，释放
-                freed_count += 1;
                 freed_size += obj.size;
-                self.free_object(&obj);
+                if self.heap.reclaim(&obj) {
+                    freed_count += 1;
+                }
             }
         }
         
         *young = survivors;
+        drop(young);
+        self.heap.prune_escaped();
         
         // 更新大小统计
         self.heap.young_size.fetch_sub(freed_size, Ordering::Relaxed);
@@ -340,15 +643,10 @@ impl MarkSweepGc {
         
         self.heap.gc_running.store(false, Ordering::Release);
         
-        {
-            let elapsed = start.elapsed().as_nanos() as u64;
-            let mut stats = self.heap.stats.lock();
-            stats.major_gc_count += 1;
-            stats.last_gc_time_ns = elapsed;
-            stats.total_pause_time_ns += elapsed;
-        }
+        self.heap.record_pause(start.elapsed());
+        self.heap.stats.lock().major_gc_count += 1;
         
-        self.heap.allocation_count.store(0, Ordering::Relaxed);
+        self.heap.update_trigger();
         
         result
     }
@@ -363,6 +661,7 @@ impl MarkSweepGc {
         root_scanner(&mut |value| {
             self.mark_value(value, &mut marked_ptrs);
         });
+        self.heap.clear_weak(|ptr| marked_ptrs.contains(&ptr));
         
         let mut total_freed = 0usize;
         let mut total_freed_size = 0usize;
@@ -378,9 +677,10 @@ impl MarkSweepGc {
                     obj.age = 0; // Major GC 后重置年龄
                     survivors.push(obj);
                 } else {
-                    total_freed += 1;
                     total_freed_size += obj.size;
-                    self.free_object(&obj);
+                    if self.heap.reclaim(&obj) {
+                        total_freed += 1;
+                    }
                 }
             }
             
@@ -397,15 +697,17 @@ impl MarkSweepGc {
                     obj.marked = false;
                     survivors.push(obj);
                 } else {
-                    total_freed += 1;
                     total_freed_size += obj.size;
                     self.heap.old_size.fetch_sub(obj.size, Ordering::Relaxed);
-                    self.free_object(&obj);
+                    if self.heap.reclaim(&obj) {
+                        total_freed += 1;
+                    }
                 }
             }
             
             *old = survivors;
         }
+        self.heap.prune_escaped();
         
         // 重新计算大小
         let young_size: usize = self.heap.young_gen.lock().iter().map(|o| o.size).sum();
@@ -427,150 +729,158 @@ impl MarkSweepGc {
         }
     }
     
-    /// 标记一个值
+    /// 标记一个值及其可达的所有对象
     fn mark_value(&self, value: &Value, marked: &mut HashSet<u64>) {
-        // 只处理堆对象
-        if !value.is_heap_object() {
-            return;
-        }
-        
-        let ptr = value.as_ptr();
-        if ptr == 0 || marked.contains(&ptr) {
-            return;
+        // 用显式工作栈代替递归，长链表不会耗尽调用栈
+        let mut pending = vec![*value];
+        while let Some(value) = pending.pop() {
+            // 只处理堆对象
+            if !value.is_heap_object() {
+                continue;
+            }
+            
+            let ptr = value.as_ptr();
+            if ptr == 0 || !marked.insert(ptr) {
+                continue;
+            }
+            
+            trace_references(&value, &mut |child| pending.push(*child));
         }
-        
-        marked.insert(ptr);
-        
-        // 递归标记引用的对象
-        self.mark_references(value, marked);
     }
-    
-    /// 标记值引用的其他对象
-    fn mark_references(&self, value: &Value, marked: &mut HashSet<u64>) {
-        // 根据类型递归标记
-        match value.heap_tag() {
-            Some(HeapTag::Array) => {
-                if let Some(arr) = value.as_array() {
-                    let arr = arr.lock();
-                    for v in arr.iter() {
-                        self.mark_value(v, marked);
-                    }
-                }
+}
+
+/// 遍历值直接引用的堆对象
+fn trace_references(value: &Value, visit: &mut dyn FnMut(&Value)) {
+    match value.heap_tag() {
+        Some(HeapTag::Array) => {
+            if let Some(arr) = value.as_array() {
+                arr.lock().iter().for_each(&mut *visit);
             }
-            Some(HeapTag::Map) => {
-                if let Some(map) = value.as_map() {
-                    let map = map.lock();
-                    for v in map.values() {
-                        self.mark_value(v, marked);
-                    }
-                }
+        }
+        Some(HeapTag::ArraySlice) => {
+            // 切片持有整个源数组
+            if let Some((source, _, _)) = value.as_array_slice() {
+                source.lock().iter().for_each(&mut *visit);
             }
-            Some(HeapTag::Set) => {
-                if let Some(set) = value.as_set() {
-                    let set = set.lock();
-                    for v in set.iter() {
-                        self.mark_value(v, marked);
-                    }
-                }
+        }
+        Some(HeapTag::Map) => {
+            if let Some(map) = value.as_map() {
+                map.lock().values().for_each(&mut *visit);
             }
-            Some(HeapTag::Struct) => {
-                if let Some(s) = value.as_struct() {
-                    let s = s.lock();
-                    for v in s.fields.values() {
-                        self.mark_value(v, marked);
-                    }
-                }
+        }
+        Some(HeapTag::Set) => {
+            if let Some(set) = value.as_set() {
+                set.lock().iter().for_each(&mut *visit);
             }
-            Some(HeapTag::Class) => {
-                if let Some(c) = value.as_class() {
-                    let c = c.lock();
-                    for v in c.fields.values() {
-                        self.mark_value(v, marked);
-                    }
+        }
+        Some(HeapTag::Iterator) => {
+            if let Some(iter) = value.as_iterator() {
+                if let IteratorSource::Array(arr) = &iter.lock().source {
+                    arr.lock().iter().for_each(&mut *visit);
                 }
             }
-            Some(HeapTag::Enum) => {
-                if let Some(e) = value.as_enum() {
-                    if let Some(v) = &e.value {
-                        self.mark_value(v, marked);
-                    }
-                    for v in e.associated_data.values() {
-                        self.mark_value(v, marked);
-                    }
-                }
+        }
+        Some(HeapTag::Struct) => {
+            if let Some(s) = value.as_struct() {
+                s.lock().fields.values().for_each(&mut *visit);
             }
-            Some(HeapTag::Function) => {
-                if let Some(f) = value.as_function() {
-                    for v in &f.defaults {
-                        self.mark_value(v, marked);
-                    }
+        }
+        Some(HeapTag::Class) => {
+            if let Some(c) = value.as_class() {
+                c.lock().fields.values().for_each(&mut *visit);
+            }
+        }
+        Some(HeapTag::Enum) => {
+            if let Some(e) = value.as_enum() {
+                if let Some(v) = &e.value {
+                    visit(v);
                 }
+                e.associated_data.values().for_each(&mut *visit);
+            }
+        }
+        Some(HeapTag::Function) => {
+            if let Some(f) = value.as_function() {
+                f.defaults.iter().for_each(&mut *visit);
+                f.captures.iter().for_each(&mut *visit);
+            }
+        }
+        Some(HeapTag::Cell) => {
+            if let Some(cell) = value.as_cell() {
+                visit(&cell.lock());
+            }
+        }
+        Some(HeapTag::MutexValue) => {
+            if let Some(m) = value.as_mutex() {
+                visit(&m.lock());
             }
-            _ => {}
         }
+        _ => {}
     }
-    
-    /// 释放对象
-    fn free_object(&self, obj: &AllocatedObject) {
-        // 安全地释放堆内存
-        // 注意：由于 NaN-boxing 使用原始指针，我们需要小心处理
-        // 这里我们通过重建 Box 来释放内存
-        unsafe {
-            match obj.tag {
-                HeapTag::String => {
-                    let _ = Box::from_raw(obj.ptr as *mut super::value::HeapString);
-                }
-                HeapTag::Function => {
-                    let _ = Box::from_raw(obj.ptr as *mut super::value::HeapFunction);
-                }
-                HeapTag::Array => {
-                    let _ = Box::from_raw(obj.ptr as *mut super::value::HeapArray);
-                }
-                HeapTag::ArraySlice => {
-                    let _ = Box::from_raw(obj.ptr as *mut super::value::HeapArraySlice);
-                }
-                HeapTag::Map => {
-                    let _ = Box::from_raw(obj.ptr as *mut super::value::HeapMap);
-                }
-                HeapTag::Set => {
-                    let _ = Box::from_raw(obj.ptr as *mut super::value::HeapSet);
-                }
-                HeapTag::Range => {
-                    let _ = Box::from_raw(obj.ptr as *mut super::value::HeapRange);
-                }
-                HeapTag::Iterator => {
-                    let _ = Box::from_raw(obj.ptr as *mut super::value::HeapIterator);
-                }
-                HeapTag::Struct => {
-                    let _ = Box::from_raw(obj.ptr as *mut super::value::HeapStruct);
-                }
-                HeapTag::Class => {
-                    let _ = Box::from_raw(obj.ptr as *mut super::value::HeapClass);
-                }
-                HeapTag::Enum => {
-                    let _ = Box::from_raw(obj.ptr as *mut super::value::HeapEnum);
-                }
-                HeapTag::TypeRef => {
-                    let _ = Box::from_raw(obj.ptr as *mut super::value::HeapTypeRef);
-                }
-                HeapTag::Int64 => {
-                    let _ = Box::from_raw(obj.ptr as *mut super::value::HeapInt64);
-                }
-                HeapTag::Int128 => {
-                    let _ = Box::from_raw(obj.ptr as *mut super::value::HeapInt128);
-                }
-                HeapTag::Channel => {
-                    let _ = Box::from_raw(obj.ptr as *mut super::value::HeapChannel);
-                }
-                HeapTag::MutexValue => {
-                    let _ = Box::from_raw(obj.ptr as *mut super::value::HeapMutex);
-                }
-                HeapTag::WaitGroup => {
-                    let _ = Box::from_raw(obj.ptr as *mut super::value::HeapWaitGroup);
-                }
-                HeapTag::RuntimeTypeInfo => {
-                    let _ = Box::from_raw(obj.ptr as *mut super::value::HeapRuntimeTypeInfo);
-                }
+}
+
+/// 释放对象
+fn free_object(obj: &AllocatedObject) {
+    // 安全地释放堆内存
+    // 注意：由于 NaN-boxing 使用原始指针，我们需要小心处理
+    // 这里我们通过重建 Box 来释放内存
+    unsafe {
+        match obj.tag {
+            HeapTag::String => {
+                let _ = Box::from_raw(obj.ptr as *mut super::value::HeapString);
+            }
+            HeapTag::Function => {
+                let _ = Box::from_raw(obj.ptr as *mut super::value::HeapFunction);
+            }
+            HeapTag::Array => {
+                let _ = Box::from_raw(obj.ptr as *mut super::value::HeapArray);
+            }
+            HeapTag::ArraySlice => {
+                let _ = Box::from_raw(obj.ptr as *mut super::value::HeapArraySlice);
+            }
+            HeapTag::Map => {
+                let _ = Box::from_raw(obj.ptr as *mut super::value::HeapMap);
+            }
+            HeapTag::Set => {
+                let _ = Box::from_raw(obj.ptr as *mut super::value::HeapSet);
+            }
+            HeapTag::Range => {
+                let _ = Box::from_raw(obj.ptr as *mut super::value::HeapRange);
+            }
+            HeapTag::Iterator => {
+                let _ = Box::from_raw(obj.ptr as *mut super::value::HeapIterator);
+            }
+            HeapTag::Struct => {
+                let _ = Box::from_raw(obj.ptr as *mut super::value::HeapStruct);
+            }
+            HeapTag::Class => {
+                let _ = Box::from_raw(obj.ptr as *mut super::value::HeapClass);
+            }
+            HeapTag::Enum => {
+                let _ = Box::from_raw(obj.ptr as *mut super::value::HeapEnum);
+            }
+            HeapTag::TypeRef => {
+                let _ = Box::from_raw(obj.ptr as *mut super::value::HeapTypeRef);
+            }
+            HeapTag::Int64 => {
+                let _ = Box::from_raw(obj.ptr as *mut super::value::HeapInt64);
+            }
+            HeapTag::Int128 => {
+                let _ = Box::from_raw(obj.ptr as *mut super::value::HeapInt128);
+            }
+            HeapTag::Channel => {
+                let _ = Box::from_raw(obj.ptr as *mut super::value::HeapChannel);
+            }
+            HeapTag::MutexValue => {
+                let _ = Box::from_raw(obj.ptr as *mut super::value::HeapMutex);
+            }
+            HeapTag::WaitGroup => {
+                let _ = Box::from_raw(obj.ptr as *mut super::value::HeapWaitGroup);
+            }
+            HeapTag::RuntimeTypeInfo => {
+                let _ = Box::from_raw(obj.ptr as *mut super::value::HeapRuntimeTypeInfo);
+            }
+            HeapTag::Cell => {
+                let _ = Box::from_raw(obj.ptr as *mut super::value::HeapCell);
             }
         }
     }
@@ -620,6 +930,160 @@ pub fn gc_stats() -> GcStats {
     get_heap().stats()
 }
 
+/// 全局增量 GC 实例
+static GLOBAL_GC: OnceLock<ConcurrentMarkGc> = OnceLock::new();
+
+/// 获取管理全局堆的增量 GC
+pub fn get_incremental_gc() -> &'static ConcurrentMarkGc {
+    GLOBAL_GC.get_or_init(|| ConcurrentMarkGc::new(get_heap().clone()))
+}
+
+/// 全局堆的写屏障（见 [`Heap::write_barrier`]）
+#[inline]
+pub fn gc_write_barrier(object: &Value) {
+    if let Some(heap) = GLOBAL_HEAP.get() {
+        heap.write_barrier(object);
+    }
+}
+
+/// 把原生资源交给 `owner` 持有（见 [`Heap::attach_native`]）
+///
+/// 当前线程分配的对象没有登记到堆时，`owner` 永远不会被释放，资源也一直保留
+pub fn gc_attach_native(owner: &Value, resource: Box<dyn Any + Send>) {
+    let heap = get_heap();
+    if is_registering() && heap.enabled.load(Ordering::Relaxed) {
+        heap.attach_native(owner, resource);
+    } else {
+        std::mem::forget(resource);
+    }
+}
+
+/// 把传到其他线程的值标记为已逃逸（见 [`Heap::escape`]）
+#[inline]
+pub fn gc_escape(value: &Value) {
+    if let Some(heap) = GLOBAL_HEAP.get() {
+        heap.escape(value);
+    }
+}
+
+// ============================================================================
+// 虚拟机登记
+// ============================================================================
+
+thread_local! {
+    /// 当前线程分配的对象是否登记到全局堆：只在启用了 GC 的虚拟机运行期间登记
+    static REGISTERING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// 当前线程分配的对象是否登记到全局堆
+#[inline(always)]
+pub fn is_registering() -> bool {
+    REGISTERING.with(Cell::get)
+}
+
+/// 启用了 GC 的虚拟机
+struct Mutators {
+    /// 存在且没有停在原生方法里的虚拟机数
+    running: usize,
+    /// 停在原生方法里的虚拟机交出的根集
+    parked: Vec<(u64, Vec<Value>)>,
+    next_park_id: u64,
+}
+
+/// 回收期间一直持有，新虚拟机登记和停靠的虚拟机恢复都要等回收结束
+static MUTATORS: Mutex<Mutators> = Mutex::new(Mutators { running: 0, parked: Vec::new(), next_park_id: 0 });
+
+/// `MUTATORS.running` 的副本，安全点不加锁就能判断能否回收
+static RUNNING: AtomicUsize = AtomicUsize::new(0);
+
+/// 启用了 GC 的虚拟机持有的登记，丢弃时注销
+pub struct MutatorGuard(());
+
+/// 登记一个启用了 GC 的虚拟机
+pub fn enter_mutator() -> MutatorGuard {
+    let mut mutators = MUTATORS.lock();
+    mutators.running += 1;
+    RUNNING.store(mutators.running, Ordering::Release);
+    MutatorGuard(())
+}
+
+impl Drop for MutatorGuard {
+    fn drop(&mut self) {
+        let mut mutators = MUTATORS.lock();
+        mutators.running -= 1;
+        RUNNING.store(mutators.running, Ordering::Release);
+    }
+}
+
+/// 虚拟机运行期间持有：当前线程分配的对象登记到堆，丢弃时恢复原来的设置
+pub struct RegisteringGuard {
+    previous: bool,
+}
+
+/// 开始把当前线程分配的对象登记到堆
+pub fn start_registering() -> RegisteringGuard {
+    RegisteringGuard { previous: REGISTERING.with(|r| r.replace(true)) }
+}
+
+impl Drop for RegisteringGuard {
+    fn drop(&mut self) {
+        REGISTERING.with(|r| r.set(self.previous));
+    }
+}
+
+/// 停在原生方法里的虚拟机持有，丢弃时恢复运行
+pub struct ParkGuard {
+    id: u64,
+    registering: bool,
+}
+
+/// 虚拟机进入可能长时间阻塞、并回调 Q 代码的原生方法前调用
+///
+/// `roots` 是虚拟机此时的根集，停靠期间它不再修改自己的栈。期间当前线程分配的对象不登记，
+/// 其他虚拟机（比如执行回调的虚拟机）可以在只剩自己运行时回收。
+pub fn park_mutator(roots: Vec<Value>) -> ParkGuard {
+    let mut mutators = MUTATORS.lock();
+    let id = mutators.next_park_id;
+    mutators.next_park_id += 1;
+    mutators.parked.push((id, roots));
+    mutators.running -= 1;
+    RUNNING.store(mutators.running, Ordering::Release);
+    ParkGuard { id, registering: REGISTERING.with(|r| r.replace(false)) }
+}
+
+impl Drop for ParkGuard {
+    fn drop(&mut self) {
+        let mut mutators = MUTATORS.lock();
+        mutators.parked.retain(|(id, _)| *id != self.id);
+        mutators.running += 1;
+        RUNNING.store(mutators.running, Ordering::Release);
+        REGISTERING.with(|r| r.set(self.registering));
+    }
+}
+
+/// 在安全点回收全局堆，调用方必须是正在运行的、启用了 GC 的虚拟机
+///
+/// 还有其他虚拟机在运行时跳过，返回 None。根集是 `root_scanner` 给出的值加上停靠的虚拟机的根集。
+pub fn collect_at_safepoint<F>(root_scanner: F) -> Option<GcResult>
+where
+    F: Fn(&mut dyn FnMut(&Value)),
+{
+    if RUNNING.load(Ordering::Acquire) != 1 {
+        return None;
+    }
+    let mutators = MUTATORS.lock();
+    if mutators.running != 1 {
+        return None;
+    }
+    let result = get_incremental_gc().collect(|visit| {
+        root_scanner(visit);
+        for (_, roots) in &mutators.parked {
+            roots.iter().for_each(&mut *visit);
+        }
+    });
+    Some(result)
+}
+
 // ============================================================================
 // 并发标记 GC（增量/并发）
 // ============================================================================
@@ -639,77 +1103,179 @@ pub enum ConcurrentMarkState {
     ConcurrentSweeping,
 }
 
-/// 并发标记 GC
+/// 增量标记 GC
 /// 
 /// 使用三色标记算法：
 /// - 白色：未访问（可能是垃圾）
 /// - 灰色：已访问但引用未扫描
 /// - 黑色：已访问且引用已扫描
+///
+/// 由宿主在安全点调用 [`ConcurrentMarkGc::step`] 推进，每次调用最多暂停 `pause_target` 的一半。
+/// 标记期间对象的修改需要经过 [`Heap::write_barrier`]，否则已扫描（黑色）的对象
+/// 指向的新引用不会被发现。
 pub struct ConcurrentMarkGc {
     heap: Arc<Heap>,
     /// 当前状态
     state: Mutex<ConcurrentMarkState>,
+    /// 是否处于回收周期中（安全点只检查这个标志）
+    active: AtomicBool,
     /// 灰色对象队列
-    gray_queue: Mutex<VecDeque<u64>>,
+    gray_queue: Mutex<VecDeque<Value>>,
     /// 已标记对象集合
     marked: RwLock<HashSet<u64>>,
-    /// 写屏障记录（并发标记期间的新引用）
-    write_barrier_buffer: Mutex<Vec<u64>>,
+    /// 待清除的对象（重新标记时从堆中取出，分片清除）
+    sweep_queue: Mutex<Vec<AllocatedObject>>,
+    /// 本周期已释放的对象数和字节数
+    swept: Mutex<(usize, usize)>,
+    /// 单次暂停的目标时长
+    pause_target: Duration,
+}
+
+/// 把未标记的对象标记为灰色
+fn shade(value: &Value, marked: &mut HashSet<u64>, gray: &mut VecDeque<Value>) {
+    if value.is_heap_object() {
+        let ptr = value.as_ptr();
+        if ptr != 0 && marked.insert(ptr) {
+            gray.push_back(*value);
+        }
+    }
 }
 
 impl ConcurrentMarkGc {
-    /// 创建并发标记 GC
+    /// 创建增量标记 GC
     pub fn new(heap: Arc<Heap>) -> Self {
         Self {
             heap,
             state: Mutex::new(ConcurrentMarkState::Idle),
+            active: AtomicBool::new(false),
             gray_queue: Mutex::new(VecDeque::new()),
             marked: RwLock::new(HashSet::new()),
-            write_barrier_buffer: Mutex::new(Vec::new()),
+            sweep_queue: Mutex::new(Vec::new()),
+            swept: Mutex::new((0, 0)),
+            pause_target: DEFAULT_PAUSE_TARGET,
         }
     }
     
+    /// 设置单次暂停的目标时长
+    pub fn with_pause_target(mut self, target: Duration) -> Self {
+        self.pause_target = target;
+        self
+    }
+    
     /// 获取当前状态
     pub fn state(&self) -> ConcurrentMarkState {
         *self.state.lock()
     }
     
-    /// 启动并发 GC 周期
+    /// 是否处于回收周期中
+    #[inline]
+    pub fn in_cycle(&self) -> bool {
+        self.active.load(Ordering::Acquire)
+    }
+    
+    /// 分片的时间预算：留一半给检查粒度和分片之外的开销
+    fn slice_budget(&self) -> Duration {
+        self.pause_target / 2
+    }
+    
+    /// 在安全点推进一步：空闲时按分配速率决定是否开始新周期，否则执行当前阶段的一个分片
+    ///
+    /// 周期结束（清除完成）时返回结果
+    pub fn step<F>(&self, root_scanner: F) -> Option<GcResult>
+    where
+        F: Fn(&mut dyn FnMut(&Value)),
+    {
+        match self.state() {
+            ConcurrentMarkState::Idle => {
+                if self.heap.should_gc() {
+                    self.start_cycle(root_scanner);
+                }
+                None
+            }
+            ConcurrentMarkState::RootScanning => None,
+            ConcurrentMarkState::ConcurrentMarking => {
+                self.incremental_mark(usize::MAX);
+                None
+            }
+            ConcurrentMarkState::Remarking => {
+                self.remark(root_scanner);
+                None
+            }
+            ConcurrentMarkState::ConcurrentSweeping => self.incremental_sweep(usize::MAX),
+        }
+    }
+    
+    /// 完成一个完整周期：空闲时开始新周期，已在周期中时把当前周期执行完
+    pub fn collect<F>(&self, root_scanner: F) -> GcResult
+    where
+        F: Fn(&mut dyn FnMut(&Value)),
+    {
+        if self.state() == ConcurrentMarkState::Idle && !self.start_cycle(&root_scanner) {
+            return GcResult::Skipped;
+        }
+        loop {
+            match self.state() {
+                ConcurrentMarkState::Idle | ConcurrentMarkState::RootScanning => return GcResult::Skipped,
+                ConcurrentMarkState::ConcurrentMarking => {
+                    self.incremental_mark(usize::MAX);
+                }
+                ConcurrentMarkState::Remarking => {
+                    self.remark(&root_scanner);
+                }
+                ConcurrentMarkState::ConcurrentSweeping => {
+                    if let Some(result) = self.incremental_sweep(usize::MAX) {
+                        return result;
+                    }
+                }
+            }
+        }
+    }
+    
+    /// 启动 GC 周期（扫描根集，需要 STW）
     pub fn start_cycle<F>(&self, root_scanner: F) -> bool
     where
         F: Fn(&mut dyn FnMut(&Value)),
     {
-        let mut state = self.state.lock();
-        if *state != ConcurrentMarkState::Idle {
-            return false;
+        {
+            let mut state = self.state.lock();
+            if *state != ConcurrentMarkState::Idle {
+                return false;
+            }
+            // 与 MarkSweepGc 互斥，整个周期内不会发生晋升或其他回收
+            if self.heap.gc_running.swap(true, Ordering::AcqRel) {
+                return false;
+            }
+            *state = ConcurrentMarkState::RootScanning;
         }
         
-        *state = ConcurrentMarkState::RootScanning;
-        drop(state);
-        
-        // 1. 根集扫描（需要 STW）
+        let start = Instant::now();
         {
             let mut gray = self.gray_queue.lock();
             let mut marked = self.marked.write();
             marked.clear();
             gray.clear();
+            self.heap.remembered.lock().clear();
             
-            root_scanner(&mut |value| {
-                if value.is_heap_object() {
-                    let ptr = value.as_ptr();
-                    if ptr != 0 && !marked.contains(&ptr) {
-                        marked.insert(ptr);
-                        gray.push_back(ptr);
-                    }
-                }
-            });
+            root_scanner(&mut |value| shade(value, &mut marked, &mut gray));
         }
         
+        self.heap.marking.store(true, Ordering::Release);
+        self.active.store(true, Ordering::Release);
         *self.state.lock() = ConcurrentMarkState::ConcurrentMarking;
+        self.heap.record_pause(start.elapsed());
         true
     }
     
-    /// 执行增量标记（可以在应用运行时调用）
+    /// 把记忆集中的对象重新放回灰色队列（已标记的也要重新扫描引用）
+    fn drain_remembered(&self, marked: &mut HashSet<u64>, gray: &mut VecDeque<Value>) {
+        let remembered = std::mem::take(&mut *self.heap.remembered.lock());
+        for value in remembered {
+            marked.insert(value.as_ptr());
+            gray.push_back(value);
+        }
+    }
+    
+    /// 执行一个标记分片，最多扫描 `max_objects` 个对象，且不超过时间预算
     /// 
     /// 返回是否还有更多工作要做
     pub fn incremental_mark(&self, max_objects: usize) -> bool {
@@ -718,34 +1284,37 @@ impl ConcurrentMarkGc {
             return false;
         }
         
-        let mut processed = 0;
-        
-        while processed < max_objects {
-            let ptr = {
-                let mut gray = self.gray_queue.lock();
-                gray.pop_front()
-            };
+        let start = Instant::now();
+        let budget = self.slice_budget();
+        let has_more = {
+            let mut gray = self.gray_queue.lock();
+            let mut marked = self.marked.write();
+            self.drain_remembered(&mut marked, &mut gray);
             
-            let Some(ptr) = ptr else {
-                break;
-            };
+            let mut processed = 0;
+            while processed < max_objects {
+                if processed % SLICE_CHECK_INTERVAL == 0 && processed > 0 && start.elapsed() >= budget {
+                    break;
+                }
+                let Some(value) = gray.pop_front() else {
+                    break;
+                };
+                trace_references(&value, &mut |child| shade(child, &mut marked, &mut gray));
+                processed += 1;
+            }
             
-            // 标记此对象的引用
-            self.mark_object_references(ptr);
-            processed += 1;
-        }
-        
-        // 检查是否完成
-        let has_more = !self.gray_queue.lock().is_empty();
+            !gray.is_empty() || !self.heap.remembered.lock().is_empty()
+        };
         
         if !has_more {
             *self.state.lock() = ConcurrentMarkState::Remarking;
         }
+        self.heap.record_pause(start.elapsed());
         
         has_more
     }
     
-    /// 重新标记（处理写屏障缓冲区，需要短暂 STW）
+    /// 重新标记（处理记忆集并重新扫描根集，需要短暂 STW）
     pub fn remark<F>(&self, root_scanner: F) -> bool
     where
         F: Fn(&mut dyn FnMut(&Value)),
@@ -755,143 +1324,120 @@ impl ConcurrentMarkGc {
             return false;
         }
         
-        // 处理写屏障记录
+        let start = Instant::now();
         {
-            let barrier_buffer = std::mem::take(&mut *self.write_barrier_buffer.lock());
             let mut gray = self.gray_queue.lock();
-            let marked = self.marked.read();
+            let mut marked = self.marked.write();
+            self.drain_remembered(&mut marked, &mut gray);
+            root_scanner(&mut |value| shade(value, &mut marked, &mut gray));
             
-            for ptr in barrier_buffer {
-                if !marked.contains(&ptr) {
-                    gray.push_back(ptr);
-                }
+            while let Some(value) = gray.pop_front() {
+                trace_references(&value, &mut |child| shade(child, &mut marked, &mut gray));
             }
+            self.heap.clear_weak(|ptr| marked.contains(&ptr));
         }
         
-        // 重新扫描根集
+        // 标记结束：之后分配的对象不在本周期的清除范围内
+        self.heap.marking.store(false, Ordering::Release);
         {
-            let mut gray = self.gray_queue.lock();
-            let mut marked = self.marked.write();
-            
-            root_scanner(&mut |value| {
-                if value.is_heap_object() {
-                    let ptr = value.as_ptr();
-                    if ptr != 0 && !marked.contains(&ptr) {
-                        marked.insert(ptr);
-                        gray.push_back(ptr);
-                    }
-                }
-            });
-        }
-        
-        // 处理剩余灰色对象
-        while let Some(ptr) = self.gray_queue.lock().pop_front() {
-            self.mark_object_references(ptr);
+            let mut queue = self.sweep_queue.lock();
+            queue.extend(self.heap.young_gen.lock().drain(..));
+            queue.extend(self.heap.old_gen.lock().drain(..));
         }
         
         *self.state.lock() = ConcurrentMarkState::ConcurrentSweeping;
+        self.heap.record_pause(start.elapsed());
         true
     }
     
-    /// 并发清除
-    pub fn concurrent_sweep(&self) -> GcResult {
+    /// 执行一个清除分片，最多处理 `max_objects` 个对象，且不超过时间预算
+    ///
+    /// 清除完成时返回本周期的结果
+    pub fn incremental_sweep(&self, max_objects: usize) -> Option<GcResult> {
         let state = *self.state.lock();
         if state != ConcurrentMarkState::ConcurrentSweeping {
-            return GcResult::Skipped;
+            return None;
         }
         
-        let marked = self.marked.read();
-        let mut total_freed = 0usize;
-        let mut total_freed_size = 0usize;
-        
-        // 清除年轻代
-        {
-            let mut young = self.heap.young_gen.lock();
-            let mut survivors = Vec::with_capacity(young.len());
+        let start = Instant::now();
+        let budget = self.slice_budget();
+        let done = {
+            let marked = self.marked.read();
+            let mut queue = self.sweep_queue.lock();
+            let mut swept = self.swept.lock();
+            let mut young_survivors = Vec::new();
+            let mut old_survivors = Vec::new();
             
-            for obj in young.drain(..) {
-                if marked.contains(&obj.ptr) {
-                    survivors.push(obj);
-                } else {
-                    total_freed += 1;
-                    total_freed_size += obj.size;
-                    // 注意：实际释放需要在没有其他线程访问时进行
-                    // 这里我们只是标记，实际释放在安全点进行
+            let mut processed = 0;
+            while processed < max_objects {
+                if processed % SLICE_CHECK_INTERVAL == 0 && processed > 0 && start.elapsed() >= budget {
+                    break;
                 }
-            }
-            
-            *young = survivors;
-        }
-        
-        // 清除老年代
-        {
-            let mut old = self.heap.old_gen.lock();
-            let mut survivors = Vec::with_capacity(old.len());
-            
-            for obj in old.drain(..) {
+                let Some(obj) = queue.pop() else {
+                    break;
+                };
                 if marked.contains(&obj.ptr) {
-                    survivors.push(obj);
+                    if obj.in_old_gen {
+                        old_survivors.push(obj);
+                    } else {
+                        young_survivors.push(obj);
+                    }
                 } else {
-                    total_freed += 1;
-                    total_freed_size += obj.size;
+                    swept.1 += obj.size;
+                    if self.heap.reclaim(&obj) {
+                        swept.0 += 1;
+                    }
                 }
+                processed += 1;
             }
             
-            *old = survivors;
+            self.heap.young_gen.lock().extend(young_survivors);
+            self.heap.old_gen.lock().extend(old_survivors);
+            queue.is_empty()
+        };
+        
+        if !done {
+            self.heap.record_pause(start.elapsed());
+            return None;
         }
         
-        // 重置状态
-        *self.state.lock() = ConcurrentMarkState::Idle;
+        let (freed_count, freed_bytes) = std::mem::take(&mut *self.swept.lock());
+        self.marked.write().clear();
+        self.heap.prune_escaped();
+        
+        // 重新计算大小
+        let young_size: usize = self.heap.young_gen.lock().iter().map(|o| o.size).sum();
+        let old_size: usize = self.heap.old_gen.lock().iter().map(|o| o.size).sum();
+        self.heap.young_size.store(young_size, Ordering::Relaxed);
+        self.heap.old_size.store(old_size, Ordering::Relaxed);
         
         // 更新统计
         {
             let mut stats = self.heap.stats.lock();
-            stats.total_frees += total_freed as u64;
-            stats.heap_size = stats.heap_size.saturating_sub(total_freed_size);
-        }
-        
-        GcResult::Completed {
-            freed_count: total_freed,
-            freed_bytes: total_freed_size,
-            promoted_count: 0,
-        }
-    }
-    
-    /// 写屏障：当写入引用时调用
-    #[inline]
-    pub fn write_barrier(&self, ptr: u64) {
-        let state = *self.state.lock();
-        if state == ConcurrentMarkState::ConcurrentMarking || state == ConcurrentMarkState::Remarking {
-            self.write_barrier_buffer.lock().push(ptr);
+            stats.total_frees += freed_count as u64;
+            stats.heap_size = young_size + old_size;
+            stats.incremental_gc_count += 1;
         }
-    }
-    
-    fn mark_object_references(&self, ptr: u64) {
-        // 根据对象类型扫描引用
-        // 这需要从指针恢复对象类型，然后扫描其字段
-        // 简化实现：假设我们有一个全局的对象类型映射
+        self.heap.update_trigger();
         
-        // 注意：这是一个简化的实现
-        // 完整实现需要遍历堆中的对象来找到对应的类型信息
-        let young = self.heap.young_gen.lock();
-        let old = self.heap.old_gen.lock();
-        
-        // 查找对象
-        let obj = young.iter().find(|o| o.ptr == ptr)
-            .or_else(|| old.iter().find(|o| o.ptr == ptr));
+        // 重置状态
+        *self.state.lock() = ConcurrentMarkState::Idle;
+        self.active.store(false, Ordering::Release);
+        self.heap.gc_running.store(false, Ordering::Release);
+        self.heap.record_pause(start.elapsed());
         
-        if let Some(obj) = obj {
-            // 根据类型标记引用
-            // 这里需要实际访问堆对象的内容
-            // 简化起见，我们跳过具体的引用扫描
-            let _ = obj.tag;
-        }
+        Some(GcResult::Completed {
+            freed_count,
+            freed_bytes,
+            promoted_count: 0,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::vm::MapData;
     
     #[test]
     fn test_heap_creation() {
@@ -908,4 +1454,216 @@ mod tests {
         assert_eq!(stats.total_allocations, 0);
         assert_eq!(stats.minor_gc_count, 0);
     }
+
+    #[test]
+    fn test_pause_bucket() {
+        assert_eq!(GcStats::pause_bucket(500), 0);
+        assert_eq!(GcStats::pause_bucket(1_000), 1);
+        assert_eq!(GcStats::pause_bucket(3_000), 2);
+        assert_eq!(GcStats::pause_bucket(u64::MAX), PAUSE_BUCKETS - 1);
+    }
+    
+    /// 分配一个数组并登记到指定的堆
+    fn alloc_array(heap: &Heap, elements: Vec<Value>) -> Value {
+        let size = std::mem::size_of::<super::super::value::HeapArray>() + elements.len() * 8;
+        let value = Value::array(Arc::new(Mutex::new(elements)));
+        heap.register(value.as_ptr(), HeapTag::Array, size);
+        value
+    }
+    
+    fn is_tracked(heap: &Heap, value: &Value) -> bool {
+        let ptr = value.as_ptr();
+        heap.young_gen.lock().iter().chain(heap.old_gen.lock().iter()).any(|o| o.ptr == ptr)
+    }
+    
+    const LIVE_SLOTS: usize = 500;
+    
+    /// 存活集中的一个元素：外层数组加一个内层数组
+    fn live_entry(heap: &Heap) -> Value {
+        let inner = alloc_array(heap, vec![Value::int(1)]);
+        alloc_array(heap, vec![inner])
+    }
+    
+    /// 服务器式负载：存活集大小不变，持续分配短命对象并替换存活集中的元素，
+    /// 每处理一个“请求”经过一次安全点
+    fn run_workload(heap: &Heap, root: &Value, mut safepoint: impl FnMut()) {
+        let live = root.as_array().unwrap();
+        for round in 0..100 {
+            for _ in 0..200 {
+                let leaf = alloc_array(heap, vec![Value::int(1)]);
+                alloc_array(heap, vec![leaf, leaf]);
+                safepoint();
+            }
+            for i in 0..10 {
+                let entry = live_entry(heap);
+                live.lock()[(round * 10 + i) % LIVE_SLOTS] = entry;
+                heap.write_barrier(root);
+                safepoint();
+            }
+        }
+    }
+    
+    fn workload_heap() -> (Arc<Heap>, Value) {
+        let heap = Arc::new(Heap::new());
+        heap.set_pacing(64 * 1024, 1.0);
+        let entries = (0..LIVE_SLOTS).map(|_| live_entry(&heap)).collect();
+        let root = alloc_array(&heap, entries);
+        (heap, root)
+    }
+    
+    #[test]
+    fn test_incremental_gc_bounds_pauses_and_matches_stop_the_world() {
+        const PAUSE_TARGET: Duration = Duration::from_millis(10);
+        let expected_live = 1 + LIVE_SLOTS * 2;
+        
+        let (stw_heap, stw_root) = workload_heap();
+        let stw = MarkSweepGc::new(stw_heap.clone());
+        let stw_roots = |visit: &mut dyn FnMut(&Value)| visit(&stw_root);
+        run_workload(&stw_heap, &stw_root, || {
+            if stw_heap.should_gc() {
+                stw.major_gc(stw_roots);
+            }
+        });
+        stw.major_gc(stw_roots);
+        let stw_stats = stw_heap.stats();
+        
+        let (inc_heap, inc_root) = workload_heap();
+        let inc = ConcurrentMarkGc::new(inc_heap.clone()).with_pause_target(PAUSE_TARGET);
+        let inc_roots = |visit: &mut dyn FnMut(&Value)| visit(&inc_root);
+        run_workload(&inc_heap, &inc_root, || {
+            inc.step(inc_roots);
+        });
+        // 先结束进行中的周期（可能留下浮动垃圾），再完整回收一次
+        inc.collect(inc_roots);
+        inc.collect(inc_roots);
+        let inc_stats = inc_heap.stats();
+        
+        assert!(stw_stats.major_gc_count > 1);
+        assert!(inc_stats.incremental_gc_count > 1);
+        assert_eq!(stw_heap.object_count(), expected_live);
+        assert_eq!(inc_heap.object_count(), stw_heap.object_count());
+        assert_eq!(inc_stats.total_allocations, stw_stats.total_allocations);
+        assert_eq!(inc_stats.total_frees, stw_stats.total_frees);
+        
+        assert!(
+            inc_stats.max_pause_ns < PAUSE_TARGET.as_nanos() as u64,
+            "max pause {}ns exceeds target {:?}", inc_stats.max_pause_ns, PAUSE_TARGET
+        );
+        let pauses: u64 = inc_stats.pause_histogram.iter().sum();
+        assert!(pauses > inc_stats.incremental_gc_count);
+    }
+    
+    /// 标记到一半时让已扫描的对象指向只被未扫描对象引用的对象，再断开原来的引用
+    fn mutate_mid_mark(with_barrier: bool) -> bool {
+        let heap = Arc::new(Heap::new());
+        let moved = alloc_array(&heap, Vec::new());
+        let scanned = alloc_array(&heap, Vec::new());
+        let unscanned = alloc_array(&heap, vec![moved]);
+        let root = alloc_array(&heap, vec![scanned, unscanned]);
+        let roots = |visit: &mut dyn FnMut(&Value)| visit(&root);
+        
+        let gc = ConcurrentMarkGc::new(heap.clone());
+        assert!(gc.start_cycle(roots));
+        assert!(gc.incremental_mark(1)); // root
+        assert!(gc.incremental_mark(1)); // scanned
+        
+        scanned.as_array().unwrap().lock().push(moved);
+        if with_barrier {
+            heap.write_barrier(&scanned);
+        }
+        unscanned.as_array().unwrap().lock().clear();
+        
+        gc.collect(roots);
+        let survived = is_tracked(&heap, &moved);
+        // 没有写屏障时 moved 已被释放，去掉悬垂引用
+        scanned.as_array().unwrap().lock().clear();
+        survived
+    }
+    
+    #[test]
+    fn test_write_barrier_keeps_new_reference_alive() {
+        assert!(mutate_mid_mark(true));
+        assert!(!mutate_mid_mark(false));
+    }
+    
+    #[test]
+    fn test_objects_allocated_during_mark_survive() {
+        let heap = Arc::new(Heap::new());
+        let root = alloc_array(&heap, Vec::new());
+        let roots = |visit: &mut dyn FnMut(&Value)| visit(&root);
+        
+        let gc = ConcurrentMarkGc::new(heap.clone());
+        assert!(gc.start_cycle(roots));
+        assert!(heap.is_marking());
+        // 新对象在 root 扫描之后分配，挂到 root 下时没有经过写屏障
+        gc.incremental_mark(usize::MAX);
+        let fresh = alloc_array(&heap, Vec::new());
+        let child = alloc_array(&heap, Vec::new());
+        fresh.as_array().unwrap().lock().push(child);
+        root.as_array().unwrap().lock().push(fresh);
+        
+        assert!(matches!(gc.collect(roots), GcResult::Completed { freed_count: 0, .. }));
+        assert!(!heap.is_marking());
+        assert_eq!(heap.object_count(), 3);
+    }
+    
+    #[test]
+    fn test_weak_references_cleared_when_unreachable() {
+        let heap = Arc::new(Heap::new());
+        let kept = alloc_array(&heap, Vec::new());
+        let dropped = alloc_array(&heap, Vec::new());
+        let root = alloc_array(&heap, vec![kept, dropped]);
+        let roots = |visit: &mut dyn FnMut(&Value)| visit(&root);
+        
+        // 持有者没有登记到堆，不会被释放
+        let kept_ref = Value::array(Arc::new(Mutex::new(Vec::new())));
+        let dropped_ref = Value::array(Arc::new(Mutex::new(Vec::new())));
+        heap.set_weak_ref(&kept_ref, kept);
+        heap.set_weak_ref(&dropped_ref, dropped);
+        let map = Value::array(Arc::new(Mutex::new(Vec::new())));
+        let entries = Value::map(Arc::new(Mutex::new(MapData::default())));
+        for key in [kept, dropped] {
+            entries.as_map().unwrap().lock().insert(weak_key(key.as_ptr()), Value::int(1));
+            heap.add_weak_key(&map, &entries, &key);
+        }
+        
+        let gc = ConcurrentMarkGc::new(heap.clone());
+        gc.collect(roots);
+        assert!(heap.weak_ref_target(&dropped_ref).is_some());
+        
+        root.as_array().unwrap().lock().pop();
+        gc.collect(roots);
+        assert_eq!(heap.weak_ref_target(&kept_ref).map(|v| v.as_ptr()), Some(kept.as_ptr()));
+        assert!(heap.weak_ref_target(&dropped_ref).is_none());
+        let entries = entries.as_map().unwrap().lock();
+        assert_eq!(entries.keys().collect::<Vec<_>>(), vec![&weak_key(kept.as_ptr())]);
+    }
+    
+    #[test]
+    fn test_native_resource_dropped_with_owner() {
+        let heap = Arc::new(Heap::new());
+        let class = |heap: &Heap| {
+            let instance = super::super::value::ClassInstance {
+                class_name: "Native".to_string(),
+                parent_class: None,
+                fields: HashMap::new(),
+            };
+            let value = Value::class(Arc::new(Mutex::new(instance)));
+            heap.register(value.as_ptr(), HeapTag::Class, std::mem::size_of::<super::super::value::HeapClass>());
+            value
+        };
+        let kept = class(&heap);
+        let dropped = class(&heap);
+        let root = alloc_array(&heap, vec![kept]);
+        let roots = |visit: &mut dyn FnMut(&Value)| visit(&root);
+        
+        let kept_resource = Arc::new(());
+        let dropped_resource = Arc::new(());
+        heap.attach_native(&kept, Box::new(kept_resource.clone()));
+        heap.attach_native(&dropped, Box::new(dropped_resource.clone()));
+        
+        ConcurrentMarkGc::new(heap.clone()).collect(roots);
+        assert_eq!(Arc::strong_count(&kept_resource), 2);
+        assert_eq!(Arc::strong_count(&dropped_resource), 1);
+    }
 }
@@ -0,0 +1,120 @@
+//! Q map 的哈希函数
+//!
+//! map 的键常常来自不可信输入（HTTP 查询参数、请求头、JSON 对象键）。
+//! 如果哈希函数可以预测，攻击者能构造大量落入同一个桶的键，
+//! 让每次插入退化为 O(n)（hashDoS）。所以默认使用带随机密钥的 SipHash，
+//! 密钥在进程内随机生成，外部无法预先计算冲突。
+//!
+//! `run --deterministic` 改用固定密钥，使 map 的遍历顺序在多次运行之间保持一致，
+//! 便于比较输出；这种模式不应用于处理不可信输入。
+
+use std::collections::HashMap;
+use std::collections::hash_map::{DefaultHasher, RandomState};
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::value::Value;
+
+/// 是否使用固定密钥
+static DETERMINISTIC: AtomicBool = AtomicBool::new(false);
+
+/// 切换固定密钥模式，只影响之后创建的 map
+pub fn set_deterministic_hashing(enabled: bool) {
+    DETERMINISTIC.store(enabled, Ordering::Relaxed);
+}
+
+/// 当前是否使用固定密钥
+pub fn deterministic_hashing() -> bool {
+    DETERMINISTIC.load(Ordering::Relaxed)
+}
+
+/// Q map 的存储类型
+pub type MapData = HashMap<String, Value, MapHasher>;
+
+/// map 的哈希函数：随机密钥的 SipHash，`--deterministic` 时为固定密钥的 SipHash
+#[derive(Clone, Debug)]
+pub enum MapHasher {
+    Keyed(RandomState),
+    Fixed,
+}
+
+impl Default for MapHasher {
+    fn default() -> Self {
+        if deterministic_hashing() {
+            MapHasher::Fixed
+        } else {
+            MapHasher::Keyed(RandomState::new())
+        }
+    }
+}
+
+impl BuildHasher for MapHasher {
+    type Hasher = DefaultHasher;
+
+    fn build_hasher(&self) -> DefaultHasher {
+        match self {
+            MapHasher::Keyed(state) => state.build_hasher(),
+            MapHasher::Fixed => DefaultHasher::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    /// 在固定密钥下哈希值低 `bits` 位全为 0 的键：它们在固定密钥的表里挤在 1/2^bits 的桶中
+    fn colliding_keys(count: usize, bits: u32) -> Vec<String> {
+        let mask = (1u64 << bits) - 1;
+        (0u64..)
+            .map(|n| format!("k{}", n))
+            .filter(|key| MapHasher::Fixed.hash_one(key) & mask == 0)
+            .take(count)
+            .collect()
+    }
+
+    fn insert_all(keys: &[String], hasher: MapHasher) -> Duration {
+        let mut map = MapData::with_hasher(hasher);
+        let start = Instant::now();
+        for key in keys {
+            map.insert(key.clone(), Value::null());
+        }
+        assert_eq!(map.len(), keys.len());
+        start.elapsed()
+    }
+
+    #[test]
+    fn test_fixed_seed_collisions_spread_under_keyed_hasher() {
+        const COUNT: usize = 100_000;
+        let adversarial = colliding_keys(COUNT, 6);
+        let ordinary: Vec<String> = (0..COUNT).map(|n| format!("k{}", n)).collect();
+
+        // 换成随机密钥后，这些键的低位重新均匀分布
+        let keyed = MapHasher::Keyed(RandomState::new());
+        let mut buckets = [0usize; 64];
+        for key in &adversarial {
+            buckets[(keyed.hash_one(key) & 0x3f) as usize] += 1;
+        }
+        let expected = COUNT / 64;
+        assert!(buckets.iter().all(|&n| n > expected / 2 && n < expected * 2), "{:?}", buckets);
+
+        // 插入时间与普通键同一量级（留足余量，只防止退化为平方级）
+        let adversarial_time = insert_all(&adversarial, MapHasher::Keyed(RandomState::new()));
+        let ordinary_time = insert_all(&ordinary, MapHasher::Keyed(RandomState::new()));
+        assert!(
+            adversarial_time < ordinary_time * 5 + Duration::from_millis(50),
+            "adversarial {:?} vs ordinary {:?}",
+            adversarial_time,
+            ordinary_time
+        );
+    }
+
+    #[test]
+    fn test_deterministic_mode_uses_fixed_seed() {
+        let fixed = MapHasher::Fixed;
+        assert_eq!(fixed.hash_one("key"), MapHasher::Fixed.hash_one("key"));
+        let keyed = MapHasher::Keyed(RandomState::new());
+        assert_ne!(keyed.hash_one("key"), fixed.hash_one("key"));
+    }
+}
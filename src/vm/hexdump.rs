@@ -0,0 +1,183 @@
+//! 十六进制转储
+//!
+//! `hexdump(value, options?)` 把字节数据排成经典的每行 16 字节的格式：
+//!
+//! ```text
+//! 00000000  48 65 6c 6c 6f 2c 20 51  21 0a                    |Hello, Q!.|
+//! ```
+//!
+//! 左侧是 8 位十六进制的偏移，字节每 8 个一组，右侧是 ASCII（不可打印的字节显示为 `.`）。
+//! 接受 int 数组（元素必须在 0..=255 之间）和字符串（按 UTF-8 字节）。
+//! 行之间以换行分隔，最后一行后面没有换行；没有字节时结果为空字符串。
+
+use std::fmt::Write;
+
+use super::value::Value;
+
+/// 转储选项，对应 options map 中的 `offset`、`length` 和 `width`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HexdumpOptions {
+    /// 从第几个字节开始，偏移列显示的是在原数据中的位置
+    pub offset: usize,
+    /// 最多转储的字节数，None 表示到末尾
+    pub length: Option<usize>,
+    /// 每行的字节数
+    pub width: usize,
+}
+
+impl Default for HexdumpOptions {
+    fn default() -> Self {
+        Self { offset: 0, length: None, width: 16 }
+    }
+}
+
+impl HexdumpOptions {
+    /// 从 options map 读取，未知的键和非法的值报错
+    pub fn from_value(value: &Value) -> Result<Self, String> {
+        let Some(map) = value.as_map() else {
+            return Err(format!("hexdump options must be a map, found {}", value.type_name()));
+        };
+        let mut options = Self::default();
+        for (key, value) in map.lock().iter() {
+            let number = value
+                .as_int()
+                .and_then(|n| usize::try_from(n).ok())
+                .ok_or_else(|| format!("hexdump option '{}' must be a non-negative int, found {}", key, value))?;
+            match key.as_str() {
+                "offset" => options.offset = number,
+                "length" => options.length = Some(number),
+                "width" if number == 0 => return Err("hexdump option 'width' must be at least 1".to_string()),
+                "width" => options.width = number,
+                _ => return Err(format!("unknown hexdump option '{}' (expected offset, length or width)", key)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// 转储一个值：int 数组或字符串，options 为可选的选项 map
+pub fn dump_value(value: &Value, options: Option<&Value>) -> Result<String, String> {
+    let options = match options {
+        Some(options) => HexdumpOptions::from_value(options)?,
+        None => HexdumpOptions::default(),
+    };
+    hexdump(&bytes_of(value, "hexdump")?, options)
+}
+
+/// 取出值中的字节，错误信息以函数名 `function` 开头
+pub fn bytes_of(value: &Value, function: &str) -> Result<Vec<u8>, String> {
+    if let Some(s) = value.as_string() {
+        return Ok(s.as_bytes().to_vec());
+    }
+    let elements = if let Some(array) = value.as_array() {
+        array.lock().clone()
+    } else if let Some((source, start, end)) = value.as_array_slice() {
+        source.lock()[start..end].to_vec()
+    } else {
+        return Err(format!("{} expects an int array or a string, found {}", function, value.type_name()));
+    };
+    elements
+        .iter()
+        .enumerate()
+        .map(|(index, element)| {
+            element
+                .as_int()
+                .and_then(|n| u8::try_from(n).ok())
+                .ok_or_else(|| format!("{}: element at index {} is {}, not a byte (0..255)", function, index, element))
+        })
+        .collect()
+}
+
+/// 按选项排版字节
+pub fn hexdump(bytes: &[u8], options: HexdumpOptions) -> Result<String, String> {
+    if options.offset > bytes.len() {
+        return Err(format!(
+            "hexdump offset {} is past the end of the data ({} bytes)",
+            options.offset,
+            bytes.len()
+        ));
+    }
+    let rest = &bytes[options.offset..];
+    let data = &rest[..options.length.map_or(rest.len(), |length| length.min(rest.len()))];
+
+    let mut out = String::new();
+    for (row, line) in data.chunks(options.width).enumerate() {
+        if row > 0 {
+            out.push('\n');
+        }
+        let _ = write!(out, "{:08x}  ", options.offset + row * options.width);
+        for i in 0..options.width {
+            if i > 0 && i % 8 == 0 {
+                out.push(' ');
+            }
+            match line.get(i) {
+                Some(byte) => {
+                    let _ = write!(out, "{:02x} ", byte);
+                }
+                None => out.push_str("   "),
+            }
+        }
+        out.push_str(" |");
+        out.extend(line.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }));
+        out.push('|');
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+    use std::sync::Arc;
+
+    fn int_array(values: impl IntoIterator<Item = i128>) -> Value {
+        Value::array(Arc::new(Mutex::new(values.into_iter().map(Value::int).collect())))
+    }
+
+    #[test]
+    fn test_full_lines_and_tail() {
+        let dump = dump_value(&int_array(0..100), None).unwrap();
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines.len(), 7);
+        assert_eq!(lines[0], "00000000  00 01 02 03 04 05 06 07  08 09 0a 0b 0c 0d 0e 0f  |................|");
+        assert_eq!(lines[2], "00000020  20 21 22 23 24 25 26 27  28 29 2a 2b 2c 2d 2e 2f  | !\"#$%&'()*+,-./|");
+        assert_eq!(lines[4], "00000040  40 41 42 43 44 45 46 47  48 49 4a 4b 4c 4d 4e 4f  |@ABCDEFGHIJKLMNO|");
+        // 最后一行不满 16 字节：十六进制列用空格补齐，ASCII 列只有实际的字节
+        assert_eq!(lines[6], "00000060  60 61 62 63                                       |`abc|");
+        assert!(lines.iter().all(|line| line.find('|') == Some(60)));
+        assert!(!dump.ends_with('\n'));
+    }
+
+    #[test]
+    fn test_empty_and_strings() {
+        assert_eq!(dump_value(&int_array([]), None).unwrap(), "");
+        assert_eq!(dump_value(&Value::string(String::new()), None).unwrap(), "");
+        // 多字节字符按 UTF-8 字节转储
+        assert_eq!(
+            dump_value(&Value::string("héllo, 世界".to_string()), None).unwrap(),
+            "00000000  68 c3 a9 6c 6c 6f 2c 20  e4 b8 96 e7 95 8c        |h..llo, ......|"
+        );
+    }
+
+    #[test]
+    fn test_options() {
+        let bytes: Vec<u8> = (0..40).collect();
+        let options = HexdumpOptions { offset: 18, length: Some(10), width: 8 };
+        assert_eq!(
+            hexdump(&bytes, options).unwrap(),
+            "00000012  12 13 14 15 16 17 18 19  |........|\n0000001a  1a 1b                    |..|"
+        );
+        // length 超出末尾时截断，offset 等于长度时没有内容
+        assert_eq!(hexdump(&bytes, HexdumpOptions { offset: 36, length: Some(100), width: 16 }).unwrap().lines().count(), 1);
+        assert_eq!(hexdump(&bytes, HexdumpOptions { offset: 40, ..Default::default() }).unwrap(), "");
+        assert!(hexdump(&bytes, HexdumpOptions { offset: 41, ..Default::default() }).is_err());
+    }
+
+    #[test]
+    fn test_rejects_non_bytes() {
+        let error = dump_value(&int_array([1, 2, 300]), None).unwrap_err();
+        assert_eq!(error, "hexdump: element at index 2 is 300, not a byte (0..255)");
+        assert!(dump_value(&int_array([-1]), None).unwrap_err().contains("index 0"));
+        assert!(dump_value(&Value::int(1), None).is_err());
+    }
+}
@@ -0,0 +1,158 @@
+//! 宿主函数
+//!
+//! 嵌入 Q 的程序可以把 Rust 函数注册为宿主函数，脚本中用 `host.名称(...)` 调用。
+//! 宿主函数都在保留的 `host` 命名空间下，编译时按名字解析：字节码块记录引用的宿主函数名，
+//! `HostCall` 指令的操作数是名字在这张表中的下标；运行前按名字链接到引擎注册的函数，
+//! 缺少的函数在运行前一次列出。
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::compiler::bytecode::Chunk;
+use crate::stdlib::exception::stdlib_exception;
+use crate::types::Type;
+use super::value::Value;
+
+/// 宿主函数的命名空间，`host.readConfig` 中的 `host`
+pub const HOST_NAMESPACE: &str = "host";
+
+/// 宿主函数的实现：参数按调用顺序传入，Err 在脚本中成为运行时错误
+///
+/// 带类型的宿主函数返回的值不符合声明的返回类型时，脚本中抛出 ClassCastException
+pub type HostFn = Arc<dyn Fn(&[Value]) -> Result<Value, String> + Send + Sync>;
+
+/// 注册的宿主函数
+#[derive(Clone)]
+pub struct HostFunction {
+    /// 完整名称，如 `host.readConfig`
+    pub name: String,
+    /// 函数类型（`func(string) int`），没有时参数不检查、返回 dynamic
+    pub signature: Option<Type>,
+    /// 实现
+    pub func: HostFn,
+}
+
+impl fmt::Debug for HostFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostFunction")
+            .field("name", &self.name)
+            .field("signature", &self.signature)
+            .finish_non_exhaustive()
+    }
+}
+
+/// 宿主函数表
+#[derive(Debug, Clone, Default)]
+pub struct HostFunctions {
+    functions: HashMap<String, HostFunction>,
+}
+
+impl HostFunctions {
+    /// 注册宿主函数；名称必须是 `host.标识符`，同名函数不能重复注册
+    pub fn register(&mut self, name: &str, signature: Option<Type>, func: HostFn) -> Result<(), String> {
+        let valid = name
+            .strip_prefix(HOST_NAMESPACE)
+            .and_then(|rest| rest.strip_prefix('.'))
+            .is_some_and(is_identifier);
+        if !valid {
+            return Err(format!("host function name '{}' must have the form {}.<identifier>", name, HOST_NAMESPACE));
+        }
+        let func = match &signature {
+            Some(Type::Function { return_type, .. }) => checked_result(name, (**return_type).clone(), func),
+            Some(signature) => {
+                return Err(format!("signature of host function '{}' must be a function type, found {}", name, signature));
+            }
+            None => func,
+        };
+        if self.functions.contains_key(name) {
+            return Err(format!("host function '{}' is already registered", name));
+        }
+        self.functions.insert(name.to_string(), HostFunction { name: name.to_string(), signature, func });
+        Ok(())
+    }
+
+    /// 所有函数的名称和类型，供类型检查器和编译器解析 `host.名称(...)`
+    pub fn signatures(&self) -> HashMap<String, Option<Type>> {
+        self.functions.iter().map(|(name, f)| (name.clone(), f.signature.clone())).collect()
+    }
+
+    /// 按字节码块引用的宿主函数名链接实现，顺序与 `chunk.host_functions` 相同
+    ///
+    /// 缺少的函数全部列在错误中
+    pub fn link(&self, chunk: &Chunk) -> Result<Arc<[HostFn]>, String> {
+        let mut missing = Vec::new();
+        let mut linked = Vec::with_capacity(chunk.host_functions.len());
+        for name in &chunk.host_functions {
+            match self.functions.get(name) {
+                Some(f) => linked.push(f.func.clone()),
+                None => missing.push(name.as_str()),
+            }
+        }
+        if !missing.is_empty() {
+            return Err(format!("program uses host functions that are not registered: {}", missing.join(", ")));
+        }
+        Ok(linked.into())
+    }
+
+    /// 与 [`link`](Self::link) 相同，但缺少的函数不报错，调用它们时产生运行时错误
+    pub fn link_available(&self, chunk: &Chunk) -> Arc<[HostFn]> {
+        chunk
+            .host_functions
+            .iter()
+            .map(|name| match self.functions.get(name) {
+                Some(f) => f.func.clone(),
+                None => {
+                    let message = format!("host function '{}' is not registered", name);
+                    Arc::new(move |_: &[Value]| Err(message.clone())) as HostFn
+                }
+            })
+            .collect()
+    }
+}
+
+/// 检查返回值的包装：类型检查器按声明的返回类型为调用处生成特化指令，
+/// 不符合的值不能进入脚本
+fn checked_result(name: &str, return_type: Type, func: HostFn) -> HostFn {
+    let name = name.to_string();
+    Arc::new(move |args: &[Value]| {
+        let result = func(args)?;
+        if value_has_type(&result, &return_type) {
+            Ok(result)
+        } else {
+            Err(stdlib_exception(
+                "ClassCastException",
+                format!("host function '{}' returned {}, expected {}", name, result.type_name(), return_type),
+            ))
+        }
+    })
+}
+
+/// 值是否属于类型；只检查编译器会据以特化的类型，其余类型一律接受
+fn value_has_type(value: &Value, ty: &Type) -> bool {
+    match ty {
+        Type::Int | Type::Uint | Type::I8 | Type::I16 | Type::I32 | Type::I64
+        | Type::U8 | Type::U16 | Type::U32 | Type::U64 | Type::Byte => value.is_int(),
+        Type::F32 | Type::F64 => value.is_float(),
+        Type::Bool => value.is_bool(),
+        Type::Char => value.is_char(),
+        Type::String => value.is_string(),
+        Type::Null => value.is_null(),
+        Type::Nullable(inner) => value.is_null() || value_has_type(value, inner),
+        Type::Alias { actual_type, .. } => value_has_type(value, actual_type),
+        Type::Array { element_type, .. } | Type::Slice { element_type } => {
+            let Some(len) = value.array_len() else { return false };
+            (0..len).all(|i| value.array_get(i).is_some_and(|element| value_has_type(&element, element_type)))
+        }
+        Type::Map { value_type, .. } => match value.as_map() {
+            Some(map) => map.lock().values().all(|v| value_has_type(v, value_type)),
+            None => false,
+        },
+        _ => true,
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_') && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
@@ -0,0 +1,100 @@
+//! 用户提供的下标的统一解析
+//!
+//! 所有按位置访问数组和字符串的地方（`a[i]`、`a[i] = v`、`slice`、`insert`、`substring`、`charAt`）
+//! 都经过这里，规则只有一套：
+//!
+//! - 下标必须是整数，浮点数即使是整数值（`1.0`）也报错
+//! - 负数从末尾倒数，`-1` 是最后一个元素
+//! - 读写元素时越界报错；插入位置可以等于长度，超出范围时报错；切片边界可以等于长度，超出范围时截断到 `[0, len]`
+//!
+//! 字符串按字符（Unicode 标量值）计数，与 `len()` 一致。
+
+use super::value::Value;
+
+/// 下标的用途
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexPolicy {
+    /// 读写单个元素：结果必须小于长度
+    Element,
+    /// 切片边界：结果可以等于长度，越界时截断
+    Bound,
+    /// 插入位置：结果可以等于长度，越界时报错
+    Insert,
+}
+
+/// 解析下标，`container` 用于错误信息（"array"、"string"）
+pub fn resolve_index(value: &Value, len: usize, policy: IndexPolicy, container: &str) -> Result<usize, String> {
+    let Some(index) = value.as_int() else {
+        return Err(format!("{} index must be int, found {}", container, value.type_name()));
+    };
+    let resolved = if index < 0 { index + len as i128 } else { index };
+    let in_bounds = match policy {
+        IndexPolicy::Element => (0..len as i128).contains(&resolved),
+        IndexPolicy::Insert => (0..=len as i128).contains(&resolved),
+        IndexPolicy::Bound => return Ok(resolved.clamp(0, len as i128) as usize),
+    };
+    if !in_bounds {
+        return Err(format!(
+            "Index {} out of bounds for {} of length {}",
+            index, container, len
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+/// 解析切片的起止位置，省略 `end` 表示到末尾；`end` 在 `start` 之前时得到空范围
+pub fn resolve_range(start: &Value, end: Option<&Value>, len: usize, container: &str) -> Result<(usize, usize), String> {
+    let start = resolve_index(start, len, IndexPolicy::Bound, container)?;
+    let end = match end {
+        Some(end) => resolve_index(end, len, IndexPolicy::Bound, container)?,
+        None => len,
+    };
+    Ok((start, end.max(start)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_element() {
+        let at = |i: i128| resolve_index(&Value::int(i), 3, IndexPolicy::Element, "array");
+        assert_eq!(at(0), Ok(0));
+        assert_eq!(at(-0), Ok(0));
+        assert_eq!(at(-1), Ok(2));
+        assert_eq!(at(-3), Ok(0));
+        assert_eq!(at(3), Err("Index 3 out of bounds for array of length 3".to_string()));
+        assert_eq!(at(-4), Err("Index -4 out of bounds for array of length 3".to_string()));
+        assert_eq!(
+            resolve_index(&Value::float(1.0), 3, IndexPolicy::Element, "array"),
+            Err("array index must be int, found float".to_string())
+        );
+    }
+
+    #[test]
+    fn test_resolve_insert() {
+        let at = |i: i128| resolve_index(&Value::int(i), 3, IndexPolicy::Insert, "array");
+        assert_eq!(at(3), Ok(3));
+        assert_eq!(at(-1), Ok(2));
+        assert_eq!(at(-3), Ok(0));
+        assert_eq!(at(4), Err("Index 4 out of bounds for array of length 3".to_string()));
+        assert_eq!(at(-4), Err("Index -4 out of bounds for array of length 3".to_string()));
+    }
+
+    #[test]
+    fn test_resolve_range() {
+        let range = |s: i128, e: Option<i128>| {
+            resolve_range(&Value::int(s), e.map(Value::int).as_ref(), 3, "string")
+        };
+        assert_eq!(range(0, None), Ok((0, 3)));
+        assert_eq!(range(1, Some(3)), Ok((1, 3)));
+        assert_eq!(range(-2, None), Ok((1, 3)));
+        assert_eq!(range(0, Some(-1)), Ok((0, 2)));
+        assert_eq!(range(-10, Some(10)), Ok((0, 3)));
+        assert_eq!(range(2, Some(1)), Ok((2, 2)));
+        assert_eq!(
+            resolve_range(&Value::int(0), Some(&Value::float(2.0)), 3, "string"),
+            Err("string index must be int, found float".to_string())
+        );
+    }
+}
@@ -6,8 +6,29 @@ pub mod value;
 pub mod vm;
 pub mod vtable;
 pub mod gc;
+pub mod trace;
+pub mod hasher;
+pub mod alloc;
+pub mod sort;
+pub mod index;
+pub mod backtrace;
+pub mod output;
+pub mod host;
+pub mod hexdump;
+pub mod format;
+pub mod array;
+pub mod number;
+pub mod safety;
+pub mod statics;
 
 pub use value::Value;
 pub use vm::VM;
+pub use trace::{Tracer, TraceOptions};
+pub use backtrace::{TraceFormat, render_error};
+pub use hasher::{MapData, set_deterministic_hashing};
+pub use alloc::set_allocation_limit;
+pub use safety::set_safe_vm;
+pub use host::{HostFunctions, HOST_NAMESPACE};
+pub use number::BUILTIN_NAMESPACES;
 pub use vtable::{VTable, VTableRegistry, TraitVTable, RuntimeTypeInfo};
-pub use gc::{Heap, MarkSweepGc, ConcurrentMarkGc, GcResult, GcStats, get_heap, gc_register, gc_should_run, gc_stats};
+pub use gc::{Heap, MarkSweepGc, ConcurrentMarkGc, GcResult, GcStats, get_heap, get_incremental_gc, gc_register, gc_should_run, gc_stats, gc_write_barrier};
@@ -0,0 +1,195 @@
+//! 数值与字符串的互相转换：浮点数的显示（`println`、字符串插值）、`toFixed` / `toPrecision`，
+//! 以及内置数值类型的静态方法 `Int.parse` / `Float.parse`
+//!
+//! 结果与系统的区域设置无关：小数点总是 `.`，没有千位分隔符，指数写成 `e` 加十进制数（如 `1.5e-7`）。
+//! 非有限值写成 `NaN`、`inf` 和 `-inf`。解析的规则见 [`crate::stdlib::convert`]。
+
+use super::value::Value;
+use crate::stdlib::convert::{parse_float, parse_int, parse_int_radix};
+use crate::stdlib::exception::stdlib_exception;
+
+/// 有静态方法的内置数值类型，调用时 receiver 是类型引用
+pub const BUILTIN_NAMESPACES: &[&str] = &["Int", "Float"];
+
+/// 显示时使用指数形式的界限：绝对值不小于 1e21 或小于 1e-6（非零）
+const EXPONENT_ABOVE: f64 = 1e21;
+const EXPONENT_BELOW: f64 = 1e-6;
+
+/// `toFixed` / `toPrecision` 允许的最多位数
+pub const MAX_DIGITS: usize = 100;
+
+/// 浮点数转换为字符串（字符串插值、`as string`）：能还原为同一个浮点数的最短十进制数
+///
+/// 很大或很小的数用指数形式，不会写出几百个 0
+pub fn format_float(x: f64) -> String {
+    if !x.is_finite() {
+        return non_finite(x).to_string();
+    }
+    let magnitude = x.abs();
+    if magnitude >= EXPONENT_ABOVE || (magnitude < EXPONENT_BELOW && x != 0.0) {
+        format!("{:e}", x)
+    } else {
+        format!("{}", x)
+    }
+}
+
+/// `println` 显示浮点数：与 [`format_float`] 相同，但整数值带 `.0` 以区别于 int
+pub fn display_float(x: f64) -> String {
+    let text = format_float(x);
+    if x.is_finite() && x.fract() == 0.0 && !text.contains('e') {
+        text + ".0"
+    } else {
+        text
+    }
+}
+
+/// `x.toFixed(digits)`：保留 digits 位小数
+///
+/// 按浮点数精确的十进制值舍入，恰好在两个结果中间时取末位为偶数的一个（`2.5` 保留 0 位是 `2`）
+pub fn to_fixed(x: f64, digits: usize) -> String {
+    if !x.is_finite() {
+        return non_finite(x).to_string();
+    }
+    format!("{:.*}", digits, x)
+}
+
+/// `x.toPrecision(precision)`：保留 precision 位有效数字（precision 至少为 1）
+///
+/// 舍入后的十进制指数小于 -6 或不小于 precision 时使用指数形式，否则补足小数位
+pub fn to_precision(x: f64, precision: usize) -> String {
+    if !x.is_finite() {
+        return non_finite(x).to_string();
+    }
+    // 先按指数形式舍入，得到舍入之后的指数（9.99 保留两位是 1.0e1）
+    let scientific = format!("{:.*e}", precision - 1, x);
+    let (mantissa, exponent) = scientific.split_once('e').expect("exponent in {:e} output");
+    let exponent: i32 = exponent.parse().expect("integer exponent");
+    if exponent < -6 || exponent >= precision as i32 {
+        format!("{}e{}", mantissa, exponent)
+    } else {
+        format!("{:.*}", (precision as i32 - 1 - exponent) as usize, x)
+    }
+}
+
+/// 内置数值类型的静态方法，不是内置方法时返回 None；转换失败时抛出 IllegalArgumentException
+pub fn call_static(type_name: &str, method: &str, args: &[Value]) -> Option<Result<Value, String>> {
+    match (type_name, method) {
+        ("Int", "parse") => Some(int_parse(args)),
+        ("Float", "parse") => Some(float_parse(args)),
+        _ => None,
+    }
+}
+
+/// `Int.parse(text, radix?)`：不指定进制时接受 `0x` / `0o` / `0b` 前缀，进制为 2 到 36
+fn int_parse(args: &[Value]) -> Result<Value, String> {
+    let text = parse_text("Int", args, 2)?;
+    let parsed = match args.get(1) {
+        None => parse_int(text),
+        Some(radix) => match radix.as_int() {
+            Some(radix @ 2..=36) => parse_int_radix(text, radix as u32),
+            _ => return Err(invalid(format!("Int.parse() radix must be an integer between 2 and 36, got {}", radix))),
+        },
+    };
+    parsed.map(|n| Value::int(n as i128)).map_err(invalid)
+}
+
+/// `Float.parse(text)`
+fn float_parse(args: &[Value]) -> Result<Value, String> {
+    let text = parse_text("Float", args, 1)?;
+    parse_float(text).map(Value::float).map_err(invalid)
+}
+
+/// 第一个参数（要解析的字符串），同时检查参数个数
+fn parse_text<'a>(type_name: &str, args: &'a [Value], max_args: usize) -> Result<&'a str, String> {
+    if args.is_empty() || args.len() > max_args {
+        return Err(invalid(format!("{}.parse() expects at most {} arguments, got {}", type_name, max_args, args.len())));
+    }
+    args[0].as_string().map(String::as_str).ok_or_else(|| invalid(format!("{}.parse() expects a string", type_name)))
+}
+
+fn invalid(message: impl std::fmt::Display) -> String {
+    stdlib_exception("IllegalArgumentException", message)
+}
+
+fn non_finite(x: f64) -> &'static str {
+    if x.is_nan() {
+        "NaN"
+    } else if x > 0.0 {
+        "inf"
+    } else {
+        "-inf"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_float() {
+        assert_eq!(format_float(0.1 + 0.2), "0.30000000000000004");
+        assert_eq!(format_float(1.0), "1");
+        assert_eq!(format_float(-0.0), "-0");
+        assert_eq!(format_float(100.0 / 3.0), "33.333333333333336");
+        assert_eq!(format_float(123456789012345680000.0), "123456789012345680000");
+        assert_eq!(format_float(1e21), "1e21");
+        assert_eq!(format_float(-1.5e300), "-1.5e300");
+        assert_eq!(format_float(0.000001), "0.000001");
+        assert_eq!(format_float(1.5e-7), "1.5e-7");
+        assert_eq!(format_float(f64::MIN_POSITIVE), "2.2250738585072014e-308");
+        assert_eq!(format_float(f64::NAN), "NaN");
+        assert_eq!(format_float(f64::NEG_INFINITY), "-inf");
+        // 显示的结果能解析回同一个值
+        assert_eq!(display_float(1.0), "1.0");
+        assert_eq!(display_float(-0.0), "-0.0");
+        assert_eq!(display_float(2.5), "2.5");
+        assert_eq!(display_float(1e21), "1e21");
+        assert_eq!(display_float(f64::INFINITY), "inf");
+        for x in [0.1, 1.0 / 3.0, 2.0f64.powi(60), 1e-300, 6.02214076e23, -7.25e-9] {
+            assert_eq!(format_float(x).parse::<f64>().unwrap(), x, "{}", x);
+        }
+    }
+
+    #[test]
+    fn test_to_fixed() {
+        assert_eq!(to_fixed(1.23456, 2), "1.23");
+        assert_eq!(to_fixed(0.1 + 0.2, 2), "0.30");
+        // 恰好在中间时取偶数
+        assert_eq!(to_fixed(2.5, 0), "2");
+        assert_eq!(to_fixed(0.375, 2), "0.38");
+        assert_eq!(to_fixed(1.0, 3), "1.000");
+        // 1.005 实际是 1.00499999999999989...，舍入为 1.00
+        assert_eq!(to_fixed(1.005, 2), "1.00");
+        assert_eq!(to_fixed(-1.25e-3, 4), "-0.0013");
+        assert_eq!(to_fixed(1e21, 1), "1000000000000000000000.0");
+        assert_eq!(to_fixed(f64::INFINITY, 2), "inf");
+    }
+
+    #[test]
+    fn test_to_precision() {
+        assert_eq!(to_precision(123.456, 4), "123.5");
+        assert_eq!(to_precision(123.456, 2), "1.2e2");
+        assert_eq!(to_precision(9.99, 2), "10");
+        assert_eq!(to_precision(9.99, 1), "1e1");
+        assert_eq!(to_precision(0.000123, 2), "0.00012");
+        assert_eq!(to_precision(0.0000001234, 3), "1.23e-7");
+        assert_eq!(to_precision(1.0, 3), "1.00");
+        assert_eq!(to_precision(0.0, 2), "0.0");
+        assert_eq!(to_precision(-2.5, 1), "-2");
+        assert_eq!(to_precision(-3.5, 1), "-4");
+        assert_eq!(to_precision(f64::NAN, 3), "NaN");
+    }
+
+    #[test]
+    fn test_parse() {
+        let parse = |type_name: &str, args: Vec<Value>| call_static(type_name, "parse", &args).unwrap();
+        let text = |s: &str| Value::string(s.to_string());
+        assert_eq!(parse("Int", vec![text("-0x1f")]).unwrap().as_int(), Some(-31));
+        assert_eq!(parse("Int", vec![text("zz"), Value::int(36)]).unwrap().as_int(), Some(1295));
+        assert_eq!(parse("Float", vec![text("2.5e-3")]).unwrap().as_float(), Some(0.0025));
+        assert!(parse("Int", vec![text("1.5")]).unwrap_err().contains("cannot convert '1.5' to int: invalid syntax"));
+        assert!(parse("Int", vec![text("1"), Value::int(1)]).unwrap_err().contains("radix must be an integer between 2 and 36"));
+        assert!(parse("Float", vec![Value::int(1)]).unwrap_err().contains("Float.parse() expects a string"));
+        assert!(call_static("Int", "max", &[]).is_none());
+    }
+}
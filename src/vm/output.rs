@@ -0,0 +1,106 @@
+//! 程序输出的去向
+//!
+//! `print` / `println` 以及协程的未捕获错误都写到虚拟机的 [`Output`]：默认直接写标准输出和标准错误，
+//! 每次写入整段一次写出，并发的协程不会把一行拆开；换成 [`Capture`] 后写入内存缓冲区。
+//! `go` 创建的协程沿用创建者的 `Output`，因此协程的输出也归属到同一个缓冲区。
+//!
+//! 测试运行器为每个测试创建一个 `Capture`，测试的虚拟机结束后调用 [`Capture::finish`] 取出输出；
+//! 之后仍在运行的协程写出的内容记为迟到的输出，用 [`Capture::take_late`] 单独取出。
+
+use std::io::Write;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+/// 虚拟机输出的去向
+#[derive(Clone, Default)]
+pub enum Output {
+    /// 写标准输出和标准错误
+    #[default]
+    Stdio,
+    /// 写入内存缓冲区
+    Capture(Arc<Capture>),
+}
+
+impl Output {
+    /// 写到标准输出（或缓冲区）
+    pub fn print(&self, text: &str) {
+        match self {
+            Output::Stdio => {
+                let _ = std::io::stdout().lock().write_all(text.as_bytes());
+            }
+            Output::Capture(capture) => capture.write(text),
+        }
+    }
+
+    /// 写到标准错误（或缓冲区）
+    pub fn eprint(&self, text: &str) {
+        match self {
+            Output::Stdio => {
+                let _ = std::io::stderr().lock().write_all(text.as_bytes());
+            }
+            Output::Capture(capture) => capture.write(text),
+        }
+    }
+}
+
+/// 一个测试的输出缓冲区
+#[derive(Debug, Default)]
+pub struct Capture {
+    state: Mutex<CaptureState>,
+}
+
+#[derive(Debug, Default)]
+struct CaptureState {
+    text: String,
+    /// finish 之后写入的内容
+    late: String,
+    finished: bool,
+}
+
+impl Capture {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// 追加一段输出
+    pub fn write(&self, text: &str) {
+        let mut state = self.state.lock();
+        if state.finished {
+            state.late.push_str(text);
+        } else {
+            state.text.push_str(text);
+        }
+    }
+
+    /// 结束捕获并取出已写入的内容，之后的写入记为迟到的输出
+    pub fn finish(&self) -> String {
+        let mut state = self.state.lock();
+        state.finished = true;
+        std::mem::take(&mut state.text)
+    }
+
+    /// 取出 finish 之后写入的内容
+    pub fn take_late(&self) -> String {
+        std::mem::take(&mut self.state.lock().late)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_separates_late_output() {
+        let capture = Capture::new();
+        let output = Output::Capture(capture.clone());
+        output.print("hello ");
+        output.eprint("world\n");
+        assert_eq!(capture.finish(), "hello world\n");
+
+        output.print("after teardown\n");
+        assert_eq!(capture.finish(), "");
+        assert_eq!(capture.take_late(), "after teardown\n");
+        assert_eq!(capture.take_late(), "");
+    }
+}
@@ -0,0 +1,18 @@
+//! `run --safe-vm` 打开的额外运行时检查
+//!
+//! 这些检查的代价与数据规模成正比（例如二分查找前确认数组有序），默认关闭；
+//! 同一进程中的所有虚拟机（协程、回调）共用这个设置
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static SAFE_VM: AtomicBool = AtomicBool::new(false);
+
+/// 打开或关闭额外检查
+pub fn set_safe_vm(enabled: bool) {
+    SAFE_VM.store(enabled, Ordering::Relaxed);
+}
+
+/// 当前是否执行额外检查
+pub fn safe_vm() -> bool {
+    SAFE_VM.load(Ordering::Relaxed)
+}
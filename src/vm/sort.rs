@@ -0,0 +1,83 @@
+//! 稳定排序
+//!
+//! 排序的比较可能失败（元素之间没有顺序、比较函数抛出异常），`slice::sort_by` 无法中途停止，
+//! 而且比较结果不一致时可能 panic。这里的归并排序在第一次比较失败时立即返回错误。
+
+use std::cmp::Ordering;
+
+/// 不超过这个长度的片段使用插入排序
+const INSERTION_THRESHOLD: usize = 16;
+
+/// 稳定的归并排序：O(n log n) 次比较，相等的元素保持原有顺序
+///
+/// 比较失败时立即返回错误，此时 items 的内容不确定（调用方应排序副本，成功后再写回）
+pub fn merge_sort_by<T: Clone, E>(
+    items: &mut [T],
+    cmp: &mut impl FnMut(&T, &T) -> Result<Ordering, E>,
+) -> Result<(), E> {
+    let len = items.len();
+    if len <= INSERTION_THRESHOLD {
+        for i in 1..len {
+            let mut j = i;
+            while j > 0 && cmp(&items[j - 1], &items[j])? == Ordering::Greater {
+                items.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+        return Ok(());
+    }
+
+    let mid = len / 2;
+    merge_sort_by(&mut items[..mid], cmp)?;
+    merge_sort_by(&mut items[mid..], cmp)?;
+    // 两半已经有序
+    if cmp(&items[mid - 1], &items[mid])? != Ordering::Greater {
+        return Ok(());
+    }
+
+    let left = items[..mid].to_vec();
+    let (mut i, mut j, mut k) = (0, mid, 0);
+    while i < left.len() && j < len {
+        // 只有右边严格更小时才取右边，保证稳定
+        if cmp(&items[j], &left[i])? == Ordering::Less {
+            items[k] = items[j].clone();
+            j += 1;
+        } else {
+            items[k] = left[i].clone();
+            i += 1;
+        }
+        k += 1;
+    }
+    for item in &left[i..] {
+        items[k] = item.clone();
+        k += 1;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_sort_is_stable() {
+        // 键相同的元素带不同的序号，排序后序号保持递增
+        let mut items: Vec<(i32, usize)> = (0..1000).map(|n| ((n * 7919) % 10, n as usize)).collect();
+        merge_sort_by(&mut items, &mut |a: &(i32, usize), b: &(i32, usize)| Ok::<_, ()>(a.0.cmp(&b.0))).unwrap();
+        for pair in items.windows(2) {
+            assert!(pair[0].0 < pair[1].0 || (pair[0].0 == pair[1].0 && pair[0].1 < pair[1].1), "{:?}", pair);
+        }
+    }
+
+    #[test]
+    fn test_merge_sort_stops_at_first_error() {
+        let mut items: Vec<i32> = (0..100).rev().collect();
+        let mut calls = 0;
+        let result = merge_sort_by(&mut items, &mut |a: &i32, b: &i32| {
+            calls += 1;
+            if *a == 42 || *b == 42 { Err("no order") } else { Ok(a.cmp(b)) }
+        });
+        assert_eq!(result, Err("no order"));
+        assert!(calls < 100 * 7);
+    }
+}
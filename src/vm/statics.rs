@@ -0,0 +1,191 @@
+//! 静态字段表
+//!
+//! 同一程序的虚拟机（协程、回调）共享一张表，键是 `类型名::字段名`。有初始化函数的字段在第一次读取时
+//! 运行初始化函数，并且只运行一次：
+//!
+//! - 第一个读取的线程认领字段并运行初始化函数，同时读取的其他线程等待它完成
+//! - 初始化函数出错时不记录结果，等待的线程中的一个重新认领并运行
+//! - 初始化函数直接或经由其他线程的初始化函数读到自己正在初始化的字段时，认领的结果是 [`Claim::Cycle`]，
+//!   由调用方报告递归初始化，而不是互相等待
+//!
+//! 值的读写和状态的变化都在同一把锁内进行，等待的线程被唤醒后看到的一定是完整的值。
+
+use std::collections::HashMap;
+use std::thread::{self, ThreadId};
+
+use parking_lot::{Condvar, Mutex};
+
+use super::value::Value;
+
+/// [`StaticTable::claim`] 的结果
+#[derive(Debug)]
+pub enum Claim {
+    /// 字段已经有值
+    Ready(Value),
+    /// 当前线程认领了字段：运行初始化函数，然后调用 [`StaticTable::finish`] 或 [`StaticTable::abandon`]
+    Claimed,
+    /// 其他线程正在初始化，当前线程已登记为等待者，接着调用 [`StaticTable::wait`]
+    Busy,
+    /// 等待会回到当前线程：沿等待关系经过的字段，首尾都是读取的字段
+    Cycle(Vec<String>),
+}
+
+#[derive(Default)]
+struct State {
+    values: HashMap<String, Value>,
+    /// 正在初始化的字段 -> 运行初始化函数的线程
+    initializing: HashMap<String, ThreadId>,
+    /// 等待其他线程完成初始化的线程 -> 等待的字段
+    waiting: HashMap<ThreadId, String>,
+}
+
+/// 静态字段的值和初始化状态
+#[derive(Default)]
+pub struct StaticTable {
+    state: Mutex<State>,
+    /// 字段完成初始化、放弃初始化或被赋值时通知等待的线程
+    changed: Condvar,
+}
+
+impl StaticTable {
+    /// 已经初始化或赋值过的字段的值
+    pub fn get(&self, key: &str) -> Option<Value> {
+        self.state.lock().values.get(key).copied()
+    }
+
+    /// 赋值，之后的读取不再运行初始化函数
+    pub fn set(&self, key: String, value: Value) {
+        self.state.lock().values.insert(key, value);
+        self.changed.notify_all();
+    }
+
+    /// 初值是常量的字段：记下初值，已经有值时沿用
+    pub fn get_or_insert(&self, key: String, value: Value) -> Value {
+        *self.state.lock().values.entry(key).or_insert(value)
+    }
+
+    /// 遍历所有的值（GC 根集）
+    pub fn for_each_value(&self, mut f: impl FnMut(&Value)) {
+        for value in self.state.lock().values.values() {
+            f(value);
+        }
+    }
+
+    /// 读取有初始化函数的字段：已经有值时返回它，否则认领字段或者登记为等待者
+    pub fn claim(&self, key: &str) -> Claim {
+        let me = thread::current().id();
+        let mut state = self.state.lock();
+        if let Some(value) = state.values.get(key) {
+            return Claim::Ready(*value);
+        }
+        let Some(&owner) = state.initializing.get(key) else {
+            state.initializing.insert(key.to_string(), me);
+            return Claim::Claimed;
+        };
+        // 沿“字段 -> 初始化它的线程 -> 该线程等待的字段”前进，回到当前线程说明等待会形成环
+        let mut chain = vec![key.to_string()];
+        let mut owner = owner;
+        while owner != me {
+            let next = state.waiting.get(&owner).and_then(|field| Some((field, *state.initializing.get(field)?)));
+            let Some((field, next_owner)) = next else {
+                state.waiting.insert(me, key.to_string());
+                return Claim::Busy;
+            };
+            chain.push(field.clone());
+            owner = next_owner;
+        }
+        chain.push(key.to_string());
+        Claim::Cycle(chain)
+    }
+
+    /// 在 [`Claim::Busy`] 之后阻塞，直到字段有值或者初始化它的线程放弃；返回后重新 [`claim`](Self::claim)
+    pub fn wait(&self, key: &str) {
+        let me = thread::current().id();
+        let mut state = self.state.lock();
+        while !state.values.contains_key(key) && state.initializing.contains_key(key) {
+            self.changed.wait(&mut state);
+        }
+        state.waiting.remove(&me);
+    }
+
+    /// 初始化函数返回了 `value`，记下并唤醒等待者；初始化期间字段被赋值时沿用赋的值
+    pub fn finish(&self, key: &str, value: Value) -> Value {
+        let mut state = self.state.lock();
+        state.initializing.remove(key);
+        let value = *state.values.entry(key.to_string()).or_insert(value);
+        self.changed.notify_all();
+        value
+    }
+
+    /// 初始化函数出错：释放认领，等待者中的一个重新运行初始化函数
+    pub fn abandon(&self, key: &str) {
+        self.state.lock().initializing.remove(key);
+        self.changed.notify_all();
+    }
+}
+
+/// 递归初始化的错误信息；经过其他字段时列出依次读取的字段
+pub fn recursive_static_message(chain: &[String]) -> String {
+    let first = chain.first().map_or("", String::as_str);
+    if chain.len() > 2 {
+        format!("recursive static initialization of {} ({})", first, chain.join(" -> "))
+    } else {
+        format!("recursive static initialization of {}", first)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_claim_wait_finish() {
+        let table = Arc::new(StaticTable::default());
+        assert!(matches!(table.claim("A::x"), Claim::Claimed));
+        // 同一线程再次读取是递归初始化
+        assert!(matches!(table.claim("A::x"), Claim::Cycle(chain) if chain == ["A::x", "A::x"]));
+
+        let waiter = {
+            let table = table.clone();
+            thread::spawn(move || {
+                assert!(matches!(table.claim("A::x"), Claim::Busy));
+                table.wait("A::x");
+                match table.claim("A::x") {
+                    Claim::Ready(value) => value.as_int(),
+                    other => panic!("{:?}", other),
+                }
+            })
+        };
+        // 等待者登记后再完成
+        while table.state.lock().waiting.is_empty() {
+            thread::yield_now();
+        }
+        assert_eq!(table.finish("A::x", Value::int(7)).as_int(), Some(7));
+        assert_eq!(waiter.join().unwrap(), Some(7));
+        assert!(table.state.lock().waiting.is_empty());
+    }
+
+    #[test]
+    fn test_cross_thread_cycle() {
+        let table = Arc::new(StaticTable::default());
+        // 另一个线程初始化 B::y 并等待当前线程正在初始化的 A::x
+        assert!(matches!(table.claim("A::x"), Claim::Claimed));
+        let other = {
+            let table = table.clone();
+            thread::spawn(move || {
+                assert!(matches!(table.claim("B::y"), Claim::Claimed));
+                assert!(matches!(table.claim("A::x"), Claim::Busy));
+                table.wait("A::x");
+                table.abandon("B::y");
+            })
+        };
+        while table.state.lock().waiting.is_empty() {
+            thread::yield_now();
+        }
+        assert!(matches!(table.claim("B::y"), Claim::Cycle(chain) if chain == ["B::y", "A::x", "B::y"]));
+        table.abandon("A::x");
+        other.join().unwrap();
+        assert!(matches!(table.claim("A::x"), Claim::Claimed));
+    }
+}
@@ -0,0 +1,208 @@
+//! 指令级执行追踪
+//!
+//! `run --trace` 在执行每条指令前向 stderr 输出一行：
+//!
+//! ```text
+//! [trace] 0012 main         GetLocal 1                     depth=4 top=[5, "abc", array#1]
+//! ```
+//!
+//! 依次是指令地址、所在函数、指令及其操作数、栈深度和栈顶最多 3 个值（栈顶在前）。
+//! 指令解码与反汇编共用 [`OpCode::operands`](crate::compiler::OpCode::operands)。
+//! 堆对象按首次出现的顺序编号（如 `array#3`），不输出地址，同一程序的追踪输出可以直接比较。
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::compiler::Chunk;
+use super::value::Value;
+
+/// 栈顶显示的值数量
+const TOP_VALUES: usize = 3;
+
+/// 字符串值显示的最大字符数
+const MAX_STRING_CHARS: usize = 16;
+
+/// 追踪选项
+#[derive(Debug, Clone, Default)]
+pub struct TraceOptions {
+    /// 只追踪该函数内的指令（方法写作 `Type::method`）
+    pub filter: Option<String>,
+    /// 最多输出的指令行数
+    pub limit: Option<usize>,
+}
+
+/// 执行追踪器
+pub struct Tracer {
+    options: TraceOptions,
+    out: Box<dyn Write + Send>,
+    /// 已输出的指令行数
+    lines: usize,
+    /// 是否已达到行数上限
+    truncated: bool,
+    /// 堆对象编号（按首次出现顺序）
+    object_ids: HashMap<u64, usize>,
+}
+
+impl Tracer {
+    /// 创建追踪器，输出写入 out
+    pub fn new(options: TraceOptions, out: Box<dyn Write + Send>) -> Self {
+        Self {
+            options,
+            out,
+            lines: 0,
+            truncated: false,
+            object_ids: HashMap::new(),
+        }
+    }
+
+    /// 创建输出到 stderr 的追踪器
+    pub fn stderr(options: TraceOptions) -> Self {
+        Self::new(options, Box::new(std::io::stderr()))
+    }
+
+    /// 记录即将执行的指令
+    ///
+    /// 达到行数上限时输出一行截断提示，之后不再记录
+    pub fn record(&mut self, chunk: &Chunk, ip: usize, stack: &[Value]) {
+        if self.truncated {
+            return;
+        }
+
+        let function = chunk.function_at(ip).unwrap_or("<main>");
+        if let Some(filter) = &self.options.filter {
+            if filter != function {
+                return;
+            }
+        }
+
+        if self.options.limit.is_some_and(|limit| self.lines >= limit) {
+            let _ = writeln!(self.out, "[trace] trace truncated after {} instructions", self.lines);
+            self.truncated = true;
+            return;
+        }
+
+        let instruction = match chunk.decode_instruction(ip) {
+            Some(instruction) => chunk.format_instruction(&instruction),
+            None => format!("<invalid {}>", chunk.code.get(ip).copied().unwrap_or(0)),
+        };
+        let top: Vec<String> = stack
+            .iter()
+            .rev()
+            .take(TOP_VALUES)
+            .map(|value| self.summarize(value))
+            .collect();
+
+        let _ = writeln!(
+            self.out,
+            "[trace] {:04} {:<12} {:<30} depth={} top=[{}]",
+            ip,
+            function,
+            instruction,
+            stack.len(),
+            top.join(", ")
+        );
+        self.lines += 1;
+    }
+
+    /// 值的简短表示：标量原样输出，字符串截断，其他堆对象输出类型名和编号
+    fn summarize(&mut self, value: &Value) -> String {
+        if let Some(s) = value.as_string() {
+            if s.chars().count() > MAX_STRING_CHARS {
+                let head: String = s.chars().take(MAX_STRING_CHARS).collect();
+                return format!("{:?}…", head);
+            }
+            return format!("{:?}", s);
+        }
+        if value.heap_tag().is_none() || value.is_int() || value.as_function().is_some() {
+            return value.to_string();
+        }
+
+        let type_name = if let Some(instance) = value.as_class() {
+            instance.lock().class_name.clone()
+        } else if let Some(instance) = value.as_struct() {
+            instance.lock().type_name.clone()
+        } else {
+            value.type_name().to_string()
+        };
+        let next_id = self.object_ids.len() + 1;
+        let id = *self.object_ids.entry(value.to_bits()).or_insert(next_id);
+        format!("{}#{}", type_name, id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use parking_lot::Mutex;
+    use crate::compiler::Compiler;
+    use crate::i18n::Locale;
+    use crate::lexer::Scanner;
+    use crate::parser::Parser;
+    use crate::vm::VM;
+
+    /// 测试用的共享输出缓冲区
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn trace(source: &str, options: TraceOptions) -> Vec<String> {
+        let tokens = Scanner::new(source).scan_tokens();
+        let program = Parser::new(tokens, Locale::En).parse().unwrap();
+        let chunk = Compiler::new(Locale::En).compile(&program).unwrap();
+        let buf = SharedBuf::default();
+        let mut vm = VM::new(Arc::new(chunk), Locale::En);
+        vm.set_tracer(Tracer::new(options, Box::new(buf.clone())));
+        vm.run().unwrap();
+        let output = String::from_utf8(buf.0.lock().clone()).unwrap();
+        output.lines().map(|line| line.to_string()).collect()
+    }
+
+    const PROGRAM: &str = "func twice(x: int) int {\n    return x * 2\n}\nvar xs = [twice(3)]\nvar s = \"a long string literal\"\n";
+
+    #[test]
+    fn test_trace_snapshot() {
+        let lines = trace(PROGRAM, TraceOptions::default());
+        let expected = [
+            "[trace] 0000 <main>       Jump 7 -> 0010                 depth=0 top=[]",
+            "[trace] 0010 <main>       Const 0 (<fn twice>)           depth=0 top=[]",
+            "[trace] 0013 <main>       ConstInt8 3                    depth=1 top=[<fn twice>]",
+            "[trace] 0015 <main>       Call 1                         depth=2 top=[3, <fn twice>]",
+            "[trace] 0003 twice        GetLocalInt 0                  depth=2 top=[3, <fn twice>]",
+            "[trace] 0006 twice        ConstInt8 2                    depth=3 top=[3, 3, <fn twice>]",
+            "[trace] 0008 twice        MulInt                         depth=4 top=[2, 3, 3]",
+            "[trace] 0009 twice        Return                         depth=3 top=[6, 3, <fn twice>]",
+            "[trace] 0017 <main>       NewArray 1                     depth=1 top=[6]",
+            "[trace] 0020 <main>       Const 1 (\"a long string literal\") depth=1 top=[array#1]",
+            "[trace] 0023 <main>       Halt                           depth=2 top=[\"a long string li\"…, array#1]",
+        ];
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn test_trace_filter_restricts_to_function() {
+        let options = TraceOptions { filter: Some("twice".to_string()), limit: None };
+        let lines = trace(PROGRAM, options);
+        assert_eq!(lines.len(), 4);
+        assert!(lines.iter().all(|line| line.contains(" twice ")), "{:?}", lines);
+    }
+
+    #[test]
+    fn test_trace_limit_truncates() {
+        let source = "var n = 0\nfor var i = 0; i < 1000; i = i + 1 {\n    n = n + i\n}\n";
+        let options = TraceOptions { filter: None, limit: Some(5) };
+        let lines = trace(source, options);
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[5], "[trace] trace truncated after 5 instructions");
+    }
+}
@@ -11,14 +11,30 @@
 //!   - QNAN | 0x04_xxxx_xxxx = Int32 (低 32 位)
 //!   - QNAN | 0x05_xxxx_xxxx_xxxx = Pointer (低 48 位)
 //!   - QNAN | 0x06_xxxx_xxxx_xxxx = Int64 boxed pointer
+//!   - QNAN | 0x07_xxxx_xxxx = Char (低 32 位)
+//!   - QNAN | 0x08_xxxx_xxxx_xxxx = Int128 boxed pointer
+//!
+//! 取舍：
+//! - 浮点数不需要装箱，`Value` 是 `Copy`，栈和数组里每个元素只占 8 字节；
+//!   i32 范围内的整数内联，运算结果超出 i32 时装箱为 Int64，超出 i64 时装箱为 Int128，
+//!   装箱整数的运算比内联整数多一次间接访问和一次分配。
+//! - 运算产生的 NaN 统一存为 `CANONICAL_NAN`，原来的 NaN 载荷和符号位不保留。
+//! - 指针只有 48 位，依赖 x86-64 / AArch64 的用户态地址空间。
+//! - 堆对象不做引用计数，由 GC 登记回收（未启用 GC 时不释放）。
+//!
+//! 这是唯一的表示，没有按 feature 切换的枚举版本，VM 和标准库都直接依赖 8 字节的位布局
+//! （栈顶缓存用 `to_bits` / `from_bits`）。
 
 #![allow(dead_code)]
 
+use std::collections::hash_map::DefaultHasher;
 use std::fmt;
+use std::hash::{BuildHasher, Hash, Hasher};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use parking_lot::Mutex;
 use std::collections::HashMap;
+use super::hasher::MapData;
 use dashmap::DashMap;
 use std::sync::OnceLock;
 
@@ -26,7 +42,7 @@ use std::sync::OnceLock;
 // GC 集成
 // ============================================================================
 
-/// GC 是否启用（默认禁用，以保持最佳性能）
+/// 新建的虚拟机是否启用 GC（默认禁用，以保持最佳性能）
 static GC_ENABLED: AtomicBool = AtomicBool::new(false);
 
 /// 启用 GC
@@ -47,10 +63,10 @@ pub fn is_gc_enabled() -> bool {
     GC_ENABLED.load(Ordering::Relaxed)
 }
 
-/// 注册堆对象到 GC（如果启用）
+/// 注册堆对象到 GC（当前线程正在运行启用了 GC 的虚拟机时）
 #[inline(always)]
 fn gc_register_object(ptr: u64, tag: HeapTag, size: usize) {
-    if GC_ENABLED.load(Ordering::Relaxed) {
+    if super::gc::is_registering() {
         super::gc::gc_register(ptr, tag, size);
     }
 }
@@ -110,6 +126,11 @@ const TAG_CHAR: u64 = QNAN | 0x0007_0000_0000_0000;
 /// Int128 标签（指向堆上的 i128）(TAG = 0x8)
 const TAG_INT128: u64 = QNAN | 0x0008_0000_0000_0000;
 
+/// 规范 NaN（TAG = 0x0）
+/// 运算产生的 NaN 尾数最高位为 1，会落入 TAG = 0x8 的范围，所以统一存为这个值。
+/// 无穷大的尾数为 0，TAG 同样为 0x0，不需要转换
+const CANONICAL_NAN: u64 = QNAN | 1;
+
 /// 指针掩码（低 48 位）
 const PTR_MASK: u64 = 0x0000_FFFF_FFFF_FFFF;
 
@@ -143,6 +164,7 @@ pub enum HeapTag {
     Set = 15,
     ArraySlice = 16,
     RuntimeTypeInfo = 17,
+    Cell = 18,
 }
 
 /// 堆对象头部
@@ -200,6 +222,8 @@ pub struct Function {
     pub local_count: usize,
     /// Upvalue 描述符（闭包捕获的变量）
     pub upvalues: Vec<UpvalueDescriptor>,
+    /// `Closure` 指令创建闭包时按 upvalues 复制的槽位（cell 或值），调用时压在参数之后
+    pub captures: Vec<Value>,
 }
 
 /// Upvalue 描述符
@@ -249,7 +273,7 @@ pub struct HeapArraySlice {
 #[repr(C)]
 pub struct HeapMap {
     pub header: HeapObject,
-    pub data: Arc<Mutex<HashMap<String, Value>>>,
+    pub data: Arc<Mutex<MapData>>,
 }
 
 /// 堆上的 Set（集合）
@@ -267,6 +291,8 @@ pub struct HeapSet {
 pub enum IteratorSource {
     Array(Arc<Mutex<Vec<Value>>>),
     Range(i64, i64, bool),
+    /// 通道：逐个接收直到通道关闭且缓冲区取空
+    Channel(crossbeam_channel::Receiver<Value>),
 }
 
 /// 迭代器对象
@@ -529,6 +555,15 @@ pub struct HeapMutex {
     pub inner: Arc<Mutex<Value>>,
 }
 
+/// 堆上的 cell：被闭包捕获且会被赋值的局部变量
+///
+/// 只出现在局部变量槽位和闭包的 captures 中，由 `GetCell` / `SetCell` 读写，不会作为普通值传给用户代码
+#[repr(C)]
+pub struct HeapCell {
+    pub header: HeapObject,
+    pub value: Mutex<Value>,
+}
+
 /// WaitGroup 内部状态（优化版本）
 /// 
 /// 使用无锁快速路径 + 自旋等待 + Condvar 等待的分层策略
@@ -608,6 +643,20 @@ impl WaitGroupState {
         self.waiters.fetch_sub(1, Ordering::AcqRel);
     }
     
+    /// 最多等待 `timeout`，返回计数器是否已经归零
+    pub fn wait_timeout(&self, timeout: std::time::Duration) -> bool {
+        if self.counter.load(Ordering::Acquire) == 0 {
+            return true;
+        }
+        self.waiters.fetch_add(1, Ordering::AcqRel);
+        let mut guard = self.mutex.lock();
+        if self.counter.load(Ordering::Acquire) > 0 {
+            self.condvar.wait_for(&mut guard, timeout);
+        }
+        self.waiters.fetch_sub(1, Ordering::AcqRel);
+        self.counter.load(Ordering::Acquire) == 0
+    }
+    
     /// 获取当前计数
     #[inline]
     pub fn count(&self) -> usize {
@@ -700,7 +749,11 @@ impl Value {
     /// 创建浮点数值
     #[inline(always)]
     pub fn float(f: f64) -> Self {
-        Value(f.to_bits())
+        if f.is_nan() {
+            Value(CANONICAL_NAN)
+        } else {
+            Value(f.to_bits())
+        }
     }
     
     /// 创建字符值
@@ -724,14 +777,12 @@ impl Value {
                 return Value(TAG_PTR | (*ptr & PTR_MASK));
             }
             
-            // 不存在则创建并插入
-            let str_len = s.len();
+            // 不存在则创建并插入；驻留的字符串归字符串池所有，不登记到 GC
             let boxed = Box::new(HeapString {
                 header: HeapObject { tag: HeapTag::String },
                 data: s.clone(),
             });
             let ptr = Box::into_raw(boxed) as u64;
-            gc_register_object(ptr, HeapTag::String, std::mem::size_of::<HeapString>() + str_len);
             pool.insert(s, ptr);
             Value(TAG_PTR | (ptr & PTR_MASK))
         } else {
@@ -804,7 +855,7 @@ impl Value {
     
     /// 创建 Map 值
     #[inline]
-    pub fn map(m: Arc<Mutex<HashMap<String, Value>>>) -> Self {
+    pub fn map(m: Arc<Mutex<MapData>>) -> Self {
         let boxed = Box::new(HeapMap {
             header: HeapObject { tag: HeapTag::Map },
             data: m,
@@ -943,6 +994,18 @@ impl Value {
         Value(TAG_PTR | (ptr & PTR_MASK))
     }
     
+    /// 创建 cell
+    #[inline]
+    pub fn cell(value: Value) -> Self {
+        let boxed = Box::new(HeapCell {
+            header: HeapObject { tag: HeapTag::Cell },
+            value: Mutex::new(value),
+        });
+        let ptr = Box::into_raw(boxed) as u64;
+        gc_register_object(ptr, HeapTag::Cell, std::mem::size_of::<HeapCell>());
+        Value(TAG_PTR | (ptr & PTR_MASK))
+    }
+    
     /// 创建 WaitGroup 值
     #[inline]
     pub fn waitgroup(state: Arc<WaitGroupState>) -> Self {
@@ -986,8 +1049,8 @@ impl Value {
     /// 是否是浮点数
     #[inline(always)]
     pub fn is_float(&self) -> bool {
-        // 不是 NaN-boxed 值就是浮点数
-        (self.0 & QNAN) != QNAN
+        // 不是 NaN-boxed 值就是浮点数；TAG 为 0 的是无穷大和规范 NaN
+        (self.0 & QNAN) != QNAN || (self.0 & (QNAN | TAG_MASK)) == QNAN
     }
     
     /// 是否是数字
@@ -1046,7 +1109,18 @@ impl Value {
             0
         }
     }
-    
+
+    /// 由 GC 记录的对象指针和标签重建值（`as_ptr` 的逆操作）
+    #[inline]
+    pub fn from_heap_object(ptr: u64, tag: HeapTag) -> Self {
+        let tag_bits = match tag {
+            HeapTag::Int64 => TAG_INT64,
+            HeapTag::Int128 => TAG_INT128,
+            _ => TAG_PTR,
+        };
+        Value(tag_bits | (ptr & PTR_MASK))
+    }
+
     /// 是否是字符串
     #[inline]
     pub fn is_string(&self) -> bool {
@@ -1370,7 +1444,7 @@ impl Value {
     
     /// 获取 Map 引用
     #[inline]
-    pub fn as_map(&self) -> Option<&Arc<Mutex<HashMap<String, Value>>>> {
+    pub fn as_map(&self) -> Option<&Arc<Mutex<MapData>>> {
         if self.heap_tag() == Some(HeapTag::Map) {
             let ptr = (self.0 & PTR_MASK) as *const HeapMap;
             unsafe { Some(&(*ptr).data) }
@@ -1489,6 +1563,17 @@ impl Value {
         }
     }
     
+    /// 获取 cell 的内容
+    #[inline]
+    pub fn as_cell(&self) -> Option<&Mutex<Value>> {
+        if self.heap_tag() == Some(HeapTag::Cell) {
+            let ptr = (self.0 & PTR_MASK) as *const HeapCell;
+            unsafe { Some(&(*ptr).value) }
+        } else {
+            None
+        }
+    }
+    
     /// 获取 WaitGroup 引用
     #[inline]
     pub fn as_waitgroup(&self) -> Option<&Arc<WaitGroupState>> {
@@ -1559,6 +1644,7 @@ impl Value {
             Some(HeapTag::MutexValue) => "mutex",
             Some(HeapTag::WaitGroup) => "waitgroup",
             Some(HeapTag::RuntimeTypeInfo) => "Type",
+            Some(HeapTag::Cell) => "cell",
             None => "unknown",
         }
     }
@@ -1648,6 +1734,59 @@ impl Value {
         Err(format!("Cannot compare {} and {}", self.type_name(), other.type_name()))
     }
     
+    /// 值的全序，用于无比较函数的排序、min/max 等需要排序的地方
+    ///
+    /// - int 与 float 按数值比较，float 使用 `f64::total_cmp`（NaN 排在所有数之后，-0.0 在 0.0 之前）
+    /// - string 按 Unicode 标量值逐个比较，char 按标量值，bool 为 false < true，null 只等于 null
+    /// - 数组逐元素比较，前缀相同时短的在前
+    /// - 其他类型之间、以及不同类型之间没有顺序，返回错误并写明两边的类型
+    ///
+    /// 比较数组时先复制出元素再释放锁，任何时刻最多持有一把锁，不会因加锁顺序死锁
+    pub fn total_cmp(&self, other: &Self) -> Result<std::cmp::Ordering, String> {
+        use std::cmp::Ordering;
+        
+        if let (Some(a), Some(b)) = (self.as_int(), other.as_int()) {
+            return Ok(a.cmp(&b));
+        }
+        if let (Some(a), Some(b)) = (self.as_f64(), other.as_f64()) {
+            return Ok(a.total_cmp(&b));
+        }
+        if let (Some(a), Some(b)) = (self.as_string(), other.as_string()) {
+            // UTF-8 的字节序与标量值的顺序一致
+            return Ok(a.cmp(b));
+        }
+        if let (Some(a), Some(b)) = (self.as_char(), other.as_char()) {
+            return Ok(a.cmp(&b));
+        }
+        if let (Some(a), Some(b)) = (self.as_bool(), other.as_bool()) {
+            return Ok(a.cmp(&b));
+        }
+        if self.is_null() && other.is_null() {
+            return Ok(Ordering::Equal);
+        }
+        if let (Some(a), Some(b)) = (self.array_elements(), other.array_elements()) {
+            for (x, y) in a.iter().zip(&b) {
+                match x.total_cmp(y)? {
+                    Ordering::Equal => continue,
+                    ordering => return Ok(ordering),
+                }
+            }
+            return Ok(a.len().cmp(&b.len()));
+        }
+        Err(format!("Cannot order {} and {}", self.type_name(), other.type_name()))
+    }
+    
+    /// 数组或数组切片的元素副本（只在复制期间持有锁）
+    fn array_elements(&self) -> Option<Vec<Value>> {
+        if let Some(arr) = self.as_array() {
+            return Some(arr.lock().clone());
+        }
+        let (source, start, end) = self.as_array_slice()?;
+        let source = source.lock();
+        let end = end.min(source.len());
+        Some(source[start.min(end)..end].to_vec())
+    }
+    
     /// 幂运算
     pub fn pow(self, rhs: Self) -> Result<Value, String> {
         match (self.as_int(), rhs.as_int()) {
@@ -1746,7 +1885,7 @@ impl std::ops::Add for Value {
         }
         // 字符串连接
         if let (Some(a), Some(b)) = (self.as_string(), rhs.as_string()) {
-            return Ok(Value::string(format!("{}{}", a, b)));
+            return super::alloc::concat_str(a, b).map(Value::string);
         }
         Err(format!("Cannot add {} and {}", self.type_name(), rhs.type_name()))
     }
@@ -1835,15 +1974,34 @@ impl std::ops::Neg for Value {
 }
 
 // ============================================================================
-// PartialEq 实现
+// PartialEq / Hash 实现
 // ============================================================================
 
+/// 相等比较和哈希的最大嵌套深度：更深的部分比较时视为不相等、哈希时忽略，
+/// 互相引用的数组、map 和对象不会无限递归
+const MAX_EQ_DEPTH: usize = 256;
+
+/// 相等的语义：
+/// - 数字按数值比较（`1 == 1.0`），NaN 与任何值（包括自身）都不相等
+/// - 字符串、数组、map、Set、struct 和 enum 按内容比较，struct 要求类型名相同且每个字段相等
+/// - class 实例按引用比较；`==` 运算符对定义了 `equals(other)` 的类调用该方法（见 VM），
+///   但数组、map 中嵌套的实例仍按引用比较
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
-        // 快速路径：位完全相同
+        self.equals_at(other, 0)
+    }
+}
+
+impl Value {
+    fn equals_at(&self, other: &Self, depth: usize) -> bool {
+        // 快速路径：位完全相同（NaN 与自身也不相等）
         if self.0 == other.0 {
-            return true;
+            return self.0 != CANONICAL_NAN;
         }
+        if depth >= MAX_EQ_DEPTH {
+            return false;
+        }
+        let depth = depth + 1;
         
         // 整数比较
         if let (Some(a), Some(b)) = (self.as_int(), other.as_int()) {
@@ -1865,33 +2023,158 @@ impl PartialEq for Value {
             return a == b;
         }
         
+        // 容器先复制出内容再比较，比较元素时不持有锁：元素引用容器自身时不会重复加锁
+        
         // 数组比较
         if let (Some(a), Some(b)) = (self.as_array(), other.as_array()) {
-            return *a.lock() == *b.lock();
+            let (a, b) = (a.lock().clone(), b.lock().clone());
+            return a.len() == b.len() && a.iter().zip(&b).all(|(x, y)| x.equals_at(y, depth));
         }
         
         // Map 比较
         if let (Some(a), Some(b)) = (self.as_map(), other.as_map()) {
-            return *a.lock() == *b.lock();
+            let (a, b) = (a.lock().clone(), b.lock().clone());
+            return fields_equal(&a, &b, depth);
         }
         
-        // Struct 比较
-        if let (Some(a), Some(b)) = (self.as_struct(), other.as_struct()) {
-            return *a.lock() == *b.lock();
+        // Set 比较：元素相同，与插入顺序无关
+        if let (Some(a), Some(b)) = (self.as_set(), other.as_set()) {
+            let (a, b) = (a.lock().clone(), b.lock().clone());
+            return a.len() == b.len() && a.iter().all(|x| b.iter().any(|y| x.equals_at(y, depth)));
         }
         
-        // Class 比较
-        if let (Some(a), Some(b)) = (self.as_class(), other.as_class()) {
-            return *a.lock() == *b.lock();
+        // Struct 比较：类型相同且字段逐个相等
+        if let (Some(a), Some(b)) = (self.as_struct(), other.as_struct()) {
+            let (a, b) = (a.lock().clone(), b.lock().clone());
+            return a.type_name == b.type_name && fields_equal(&a.fields, &b.fields, depth);
         }
         
         // Enum 比较
         if let (Some(a), Some(b)) = (self.as_enum(), other.as_enum()) {
-            return a == b;
+            return a.enum_name == b.enum_name
+                && a.variant_name == b.variant_name
+                && match (a.value, b.value) {
+                    (Some(x), Some(y)) => x.equals_at(&y, depth),
+                    (x, y) => x.is_none() && y.is_none(),
+                }
+                && fields_equal(&a.associated_data, &b.associated_data, depth);
         }
         
+        // class 实例、函数等按引用比较，引用相同时已在快速路径返回
         false
     }
+    
+    fn hash_at<H: Hasher>(&self, state: &mut H, depth: usize) {
+        if depth >= MAX_EQ_DEPTH {
+            return;
+        }
+        let depth = depth + 1;
+        
+        // 相等的数字哈希相同：值为整数的浮点数按整数哈希（`1.0` 与 `1`，`-0.0` 与 `0`）
+        let integral = self.as_int().or_else(|| {
+            self.as_float().filter(|f| f.fract() == 0.0 && f.abs() < 2f64.powi(127)).map(|f| f as i128)
+        });
+        if let Some(n) = integral {
+            0u8.hash(state);
+            n.hash(state);
+        } else if let Some(f) = self.as_float() {
+            1u8.hash(state);
+            f.to_bits().hash(state);
+        } else if let Some(s) = self.as_string() {
+            2u8.hash(state);
+            s.hash(state);
+        } else if let Some(a) = self.as_array() {
+            3u8.hash(state);
+            let items = a.lock().clone();
+            items.len().hash(state);
+            for item in &items {
+                item.hash_at(state, depth);
+            }
+        } else if let Some(m) = self.as_map() {
+            4u8.hash(state);
+            let entries = m.lock().clone();
+            hash_fields(&entries, state, depth);
+        } else if let Some(set) = self.as_set() {
+            5u8.hash(state);
+            let items = set.lock().clone();
+            hash_unordered(items.iter().map(|item| ((), item)), state, depth);
+        } else if let Some(s) = self.as_struct() {
+            6u8.hash(state);
+            let instance = s.lock().clone();
+            instance.type_name.hash(state);
+            hash_fields(&instance.fields, state, depth);
+        } else if let Some(e) = self.as_enum() {
+            7u8.hash(state);
+            e.enum_name.hash(state);
+            e.variant_name.hash(state);
+            if let Some(value) = e.value {
+                value.hash_at(state, depth);
+            }
+            hash_fields(&e.associated_data, state, depth);
+        } else {
+            // null、bool、char 以及按引用比较的值：相等当且仅当位相同
+            8u8.hash(state);
+            self.0.hash(state);
+        }
+    }
+}
+
+/// 哈希与 `==` 一致：相等的值哈希相同，可以作为 `HashMap` / `HashSet` 的键
+///
+/// 按内容比较的值哈希其内容，map、Set 和 struct 的字段与顺序无关。
+/// 内容可变的值（数组、map 等）放入集合之后修改，哈希随之改变，需要重新插入
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.hash_at(state, 0);
+    }
+}
+
+/// 两组字段（或 map 的条目）键相同且对应的值相等
+fn fields_equal<S: BuildHasher>(a: &HashMap<String, Value, S>, b: &HashMap<String, Value, S>, depth: usize) -> bool {
+    a.len() == b.len() && a.iter().all(|(key, x)| b.get(key).is_some_and(|y| x.equals_at(y, depth)))
+}
+
+fn hash_fields<S: BuildHasher, H: Hasher>(fields: &HashMap<String, Value, S>, state: &mut H, depth: usize) {
+    hash_unordered(fields.iter(), state, depth);
+}
+
+/// 与顺序无关的哈希：每个条目单独哈希后相加
+fn hash_unordered<'a, K: Hash + 'a, H: Hasher>(
+    entries: impl std::iter::Iterator<Item = (K, &'a Value)>,
+    state: &mut H,
+    depth: usize,
+) {
+    let mut count = 0usize;
+    let mut sum = 0u64;
+    for (key, value) in entries {
+        let mut entry = DefaultHasher::new();
+        key.hash(&mut entry);
+        value.hash_at(&mut entry, depth);
+        sum = sum.wrapping_add(entry.finish());
+        count += 1;
+    }
+    count.hash(state);
+    sum.hash(state);
+}
+
+/// 实例的字段（没有类型信息，按名称排序），字符串带引号
+///
+/// 虚拟机的 `println` 等按声明顺序显示并调用 `toString()`，见 VM 的 `display_string`
+fn write_instance_fields(f: &mut fmt::Formatter<'_>, type_name: &str, fields: &HashMap<String, Value>) -> fmt::Result {
+    let mut fields: Vec<_> = fields.iter().collect();
+    fields.sort_by(|a, b| a.0.cmp(b.0));
+    if fields.is_empty() {
+        return write!(f, "{} {{}}", type_name);
+    }
+    write!(f, "{} {{ ", type_name)?;
+    for (i, (name, value)) in fields.into_iter().enumerate() {
+        if i > 0 { write!(f, ", ")?; }
+        match value.as_string() {
+            Some(s) => write!(f, "{}: {:?}", name, s)?,
+            None => write!(f, "{}: {}", name, value)?,
+        }
+    }
+    write!(f, " }}")
 }
 
 // ============================================================================
@@ -1951,11 +2234,7 @@ impl fmt::Display for Value {
         } else if let Some(n) = self.as_int() {
             write!(f, "{}", n)
         } else if let Some(n) = self.as_float() {
-            if n.fract() == 0.0 {
-                write!(f, "{}.0", n)
-            } else {
-                write!(f, "{}", n)
-            }
+            write!(f, "{}", super::number::display_float(n))
         } else if let Some(c) = self.as_char() {
             write!(f, "{}", c)
         } else if let Some(s) = self.as_string() {
@@ -2010,34 +2289,20 @@ impl fmt::Display for Value {
         } else if self.is_iterator() {
             write!(f, "<iterator>")
         } else if let Some(s) = self.as_struct() {
-            let s = s.lock();
-            write!(f, "{} {{ ", s.type_name)?;
-            let mut first = true;
-            for (name, value) in &s.fields {
-                if !first { write!(f, ", ")?; }
-                first = false;
-                write!(f, "{}: {}", name, value)?;
-            }
-            write!(f, " }}")
+            let s = s.lock().clone();
+            write_instance_fields(f, &s.type_name, &s.fields)
         } else if let Some(c) = self.as_class() {
-            let c = c.lock();
-            write!(f, "{} {{ ", c.class_name)?;
-            let mut first = true;
-            for (name, value) in &c.fields {
-                if !first { write!(f, ", ")?; }
-                first = false;
-                write!(f, "{}: {}", name, value)?;
-            }
-            write!(f, " }}")
+            let c = c.lock().clone();
+            write_instance_fields(f, &c.class_name, &c.fields)
         } else if let Some(e) = self.as_enum() {
             if e.associated_data.is_empty() {
                 write!(f, "{}::{}", e.enum_name, e.variant_name)
             } else {
                 write!(f, "{}::{}(", e.enum_name, e.variant_name)?;
-                let mut first = true;
-                for (name, value) in &e.associated_data {
-                    if !first { write!(f, ", ")?; }
-                    first = false;
+                let mut fields: Vec<_> = e.associated_data.iter().collect();
+                fields.sort_by(|a, b| a.0.cmp(b.0));
+                for (i, (name, value)) in fields.into_iter().enumerate() {
+                    if i > 0 { write!(f, ", ")?; }
                     write!(f, "{}: {}", name, value)?;
                 }
                 write!(f, ")")
@@ -2123,3 +2388,115 @@ macro_rules! value {
 
 // 验证 Value 大小
 const _: () = assert!(std::mem::size_of::<Value>() == 8);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_int_encoding_boundaries() {
+        let cases: [(i128, u64); 8] = [
+            (0, TAG_INT32),
+            (INT32_MAX as i128, TAG_INT32),
+            (INT32_MIN as i128, TAG_INT32),
+            (INT32_MAX as i128 + 1, TAG_INT64),
+            (INT32_MIN as i128 - 1, TAG_INT64),
+            (i64::MAX as i128, TAG_INT64),
+            (i64::MIN as i128 - 1, TAG_INT128),
+            (i128::MAX, TAG_INT128),
+        ];
+        for (n, tag) in cases {
+            let value = Value::int(n);
+            assert_eq!(value.to_bits() & (QNAN | TAG_MASK), tag, "{}", n);
+            assert_eq!(value.as_int(), Some(n));
+            assert!(value.is_int() && !value.is_float(), "{}", n);
+            assert_eq!(value.to_string(), n.to_string());
+        }
+
+        // 运算越过 i32 边界时装箱，回到范围内时重新内联
+        let big = (Value::int(INT32_MAX as i128) + Value::int(1)).unwrap();
+        assert!(!big.is_int32());
+        assert_eq!(big, Value::int(INT32_MAX as i128 + 1));
+        let back = (big - Value::int(1)).unwrap();
+        assert!(back.is_int32());
+        assert_eq!(back.to_bits(), Value::int(INT32_MAX as i128).to_bits());
+    }
+
+    #[test]
+    fn test_float_encoding() {
+        for f in [0.0, -0.0, 1.5, -2.25, f64::MAX, f64::MIN_POSITIVE, f64::INFINITY, f64::NEG_INFINITY] {
+            let value = Value::float(f);
+            assert_eq!(value.to_bits(), f.to_bits(), "{}", f);
+            assert!(value.is_float() && !value.is_int() && !value.is_null(), "{}", f);
+        }
+
+        // 任何 NaN（包括负 NaN 和运算结果）都存为规范 NaN，不会被误认为带标签的值
+        let nan = (Value::float(f64::INFINITY) - Value::float(f64::INFINITY)).unwrap();
+        for value in [nan, Value::float(f64::NAN), Value::float(-f64::NAN)] {
+            assert_eq!(value.to_bits(), CANONICAL_NAN);
+            assert!(value.is_float() && !value.is_int());
+            assert!(value.as_float().unwrap().is_nan());
+            assert_ne!(value, value);
+        }
+        assert_eq!(Value::float(-0.0), Value::float(0.0));
+    }
+
+    fn hash_of(value: &Value) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn point(x: Value, y: Value) -> Value {
+        let fields = HashMap::from([("x".to_string(), x), ("y".to_string(), y)]);
+        Value::struct_val(Arc::new(Mutex::new(StructInstance { type_name: "Point".to_string(), fields })))
+    }
+
+    fn array(items: Vec<Value>) -> Value {
+        Value::array(Arc::new(Mutex::new(items)))
+    }
+
+    #[test]
+    fn test_equal_values_hash_equal() {
+        let text = |s: &str| Value::string_uninterned(s.to_string());
+        let set = |items: Vec<Value>| Value::set(Arc::new(Mutex::new(items)));
+        let pairs = [
+            (Value::int(1), Value::float(1.0)),
+            (Value::int(0), Value::float(-0.0)),
+            (Value::int(i64::MAX as i128 + 1), Value::int(i64::MAX as i128 + 1)),
+            (text("ab"), text("ab")),
+            (point(Value::int(1), text("y")), point(Value::float(1.0), text("y"))),
+            (array(vec![point(Value::int(1), Value::int(2))]), array(vec![point(Value::int(1), Value::int(2))])),
+            (set(vec![Value::int(1), Value::int(2)]), set(vec![Value::int(2), Value::int(1)])),
+        ];
+        for (a, b) in &pairs {
+            assert_eq!(a, b);
+            assert_eq!(hash_of(a), hash_of(b), "{} / {}", a, b);
+        }
+
+        assert_ne!(point(Value::int(1), Value::int(2)), point(Value::int(2), Value::int(1)));
+        assert_ne!(point(Value::float(f64::NAN), Value::int(0)), point(Value::float(f64::NAN), Value::int(0)));
+        assert_ne!(array(vec![Value::int(1)]), array(vec![Value::int(1), Value::int(1)]));
+
+        // class 实例按引用比较
+        let instance = || {
+            let fields = HashMap::from([("v".to_string(), Value::int(1))]);
+            Value::class(Arc::new(Mutex::new(ClassInstance { class_name: "Box".to_string(), parent_class: None, fields })))
+        };
+        let a = instance();
+        assert_eq!(a, a);
+        assert_ne!(a, instance());
+    }
+
+    #[test]
+    fn test_self_referential_values_do_not_deadlock() {
+        let a = array(vec![]);
+        let b = array(vec![]);
+        a.as_array().unwrap().lock().push(a);
+        b.as_array().unwrap().lock().push(b);
+        // 嵌套超过上限的部分视为不相等，哈希时忽略
+        assert_ne!(a, b);
+        assert_eq!(a, a);
+        assert_eq!(hash_of(&a), hash_of(&b));
+    }
+}
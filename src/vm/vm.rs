@@ -2,15 +2,26 @@
 //! 
 //! 执行字节码指令
 
-use crate::compiler::{Chunk, OpCode};
+use crate::compiler::{Chunk, Comparison, OpCode};
 use crate::i18n::{Locale, format_message, messages};
-use super::value::{Value, Iterator, IteratorSource, StructInstance, Function};
+use super::value::{Value, Iterator, IteratorSource, StructInstance, Function, EnumVariantValue};
+use super::trace::Tracer;
+use super::output::Output;
+use super::host::HostFn;
+use super::sort::merge_sort_by;
+use super::array::{binary_search, first_unsorted, search_result, upper_bound};
+use super::safety::safe_vm;
+use super::index::{resolve_index, resolve_range, IndexPolicy};
+use super::number;
+use super::gc::{gc_escape, gc_should_run, gc_write_barrier, MutatorGuard};
+use super::statics::{recursive_static_message, Claim, StaticTable};
 use crate::stdlib::StdlibRegistry;
+use crate::stdlib::os::PendingExit;
+use crate::stdlib::convert::{self, convert, Mode, Target};
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, AtomicUsize};
 use parking_lot::Mutex;
-use std::sync::OnceLock;
 
 /// 栈大小（预分配容量，避免运行时扩容）
 const STACK_SIZE: usize = 1024;
@@ -18,12 +29,13 @@ const STACK_SIZE: usize = 1024;
 /// 最大调用深度
 const MAX_FRAMES: usize = 64;
 
-/// 全局标准库注册表（延迟初始化）
-static STDLIB_REGISTRY: OnceLock<StdlibRegistry> = OnceLock::new();
+/// 转换为字符串时最多嵌套的 class / struct 实例层数，更深的实例显示为 `...`
+const MAX_STRINGIFY_DEPTH: usize = 16;
 
-/// 获取标准库注册表
-fn get_stdlib_registry() -> &'static StdlibRegistry {
-    STDLIB_REGISTRY.get_or_init(|| StdlibRegistry::new())
+/// 错误信息中带冠词的类型名："an int"、"a string"
+fn with_article(type_name: &str) -> String {
+    let article = if type_name.starts_with(['a', 'e', 'i', 'o', 'u']) { "an" } else { "a" };
+    format!("{} {}", article, type_name)
 }
 
 /// 栈帧信息（用于栈追踪）
@@ -37,6 +49,8 @@ pub struct StackFrame {
     pub line: usize,
     /// 列号（如果有）
     pub column: Option<usize>,
+    /// 从列号开始的出错表达式或调用表达式的字节长度（未知时为 0）
+    pub len: usize,
 }
 
 impl std::fmt::Display for StackFrame {
@@ -59,6 +73,10 @@ pub struct RuntimeError {
     pub line: usize,
     /// 栈追踪
     pub stack_trace: Vec<StackFrame>,
+    /// 没有被 recovering 块处理的 panic 的值（其他错误为 None）
+    pub panic_payload: Option<Value>,
+    /// 程序调用 `Os.exit(code)` 结束时的退出码：这时不是错误，调用方应以该退出码结束而不输出错误
+    pub exit_code: Option<i32>,
 }
 
 impl RuntimeError {
@@ -68,12 +86,14 @@ impl RuntimeError {
             message, 
             line,
             stack_trace: Vec::new(),
+            panic_payload: None,
+            exit_code: None,
         }
     }
     
     /// 创建带栈追踪的运行时错误
     pub fn with_trace(message: String, line: usize, stack_trace: Vec<StackFrame>) -> Self {
-        Self { message, line, stack_trace }
+        Self { message, line, stack_trace, panic_payload: None, exit_code: None }
     }
     
     /// 格式化完整的错误信息（包括栈追踪）
@@ -97,21 +117,47 @@ impl std::fmt::Display for RuntimeError {
 }
 
 /// 调用帧（优化：使用 Copy 而非 Clone）
+///
+/// base_slot 使用 u32：值栈深度可以超过 65535（深递归且每帧局部变量较多时）
 #[derive(Debug, Clone, Copy)]
 struct CallFrame {
     /// 返回地址（调用后继续执行的位置）
     return_ip: u32,
     /// 栈基址（参数和局部变量的起始位置）
-    base_slot: u16,
+    base_slot: u32,
     /// 是否是方法调用（影响返回时的栈截断位置）
     is_method_call: bool,
     // 注意：移除了 func 字段，因为大多数情况下不需要
 }
 
-/// 异常处理器
+/// 静态字段的值（`类型名::字段名` -> 值），同一程序的虚拟机（协程、回调）共享
+type StaticFields = Arc<StaticTable>;
+
+/// 协程和回调的虚拟机从创建者那里沿用的设置和共享的状态
+#[derive(Clone)]
+struct Inherited {
+    gc_enabled: bool,
+    output: Output,
+    host_functions: Arc<[HostFn]>,
+    registry: Arc<StdlibRegistry>,
+    static_fields: StaticFields,
+    pending_exit: Arc<PendingExit>,
+}
+
+/// 处理器的种类：异常只由 catch 块处理，panic 只由 onPanic 块处理，
+/// finally 处理器只在 `Os.exit` 结束程序时进入
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HandlerKind {
+    Catch,
+    Panic,
+    Finally,
+}
+
+/// 异常处理器（try-catch 和 recovering-onPanic 共用）
 #[derive(Debug)]
 struct ExceptionHandler {
-    /// catch 块的地址
+    kind: HandlerKind,
+    /// catch 块（或 onPanic 块）的地址
     catch_ip: usize,
     /// 设置处理器时的栈深度
     stack_depth: usize,
@@ -135,8 +181,8 @@ pub struct VM {
     locale: Locale,
     /// 当前栈基址（缓存，避免每次访问 frames.last()）
     current_base: usize,
-    /// 静态字段缓存 (类名::字段名 -> 值)
-    static_fields: std::collections::HashMap<String, Value>,
+    /// 静态字段：第一次读取时运行初始化函数并记下结果，赋值直接写入
+    static_fields: StaticFields,
     /// VTable 注册表（用于虚方法派发）
     vtable_registry: super::vtable::VTableRegistry,
     /// 抢占标志（用于协程调度）
@@ -147,11 +193,34 @@ pub struct VM {
     inline_cache: std::collections::HashMap<(String, String), u16>,
     /// 回调通道（用于处理异步回调）
     callback_channel: Option<Arc<crate::stdlib::CallbackChannel>>,
+    /// 指令级执行追踪（`run --trace`）
+    tracer: Option<Box<Tracer>>,
+    /// 启用 GC 时的登记：运行期间分配的对象登记到全局堆，安全点上回收
+    gc_mutator: Option<MutatorGuard>,
+    /// print/println 的去向，协程沿用创建者的设置
+    output: Output,
+    /// 链接好的宿主函数，顺序与 chunk.host_functions 相同；协程和回调沿用创建者的
+    host_functions: Arc<[HostFn]>,
+    /// 标准库类和函数的实现；协程和回调沿用创建者的
+    registry: Arc<StdlibRegistry>,
+    /// 正在执行的 [`call_closure`](Self::call_closure) 回调层数
+    callback_depth: usize,
+    /// 回调中没有被回调内的处理器捕获的异常或 panic，回到调用回调的那一层后重新抛出
+    escaped: Option<(HandlerKind, Value)>,
+    /// 正在转换为字符串的实例（位表示），`toString()` 再次转换同一个实例时显示为 `...`
+    stringifying: Vec<u64>,
+    /// 正在运行初始化函数的静态字段（`Type::field`），按开始的顺序；初始值再次读取其中的字段时报告循环
+    initializing_statics: Vec<String>,
+    /// 协程请求的退出码，同一程序的虚拟机共享
+    pending_exit: Arc<PendingExit>,
+    /// 是否由这个虚拟机处理 `pending_exit`：只有主虚拟机处理，协程和回调的虚拟机不处理
+    handles_exit: bool,
 }
 
 impl VM {
     /// 创建新的虚拟机
     pub fn new(chunk: Arc<Chunk>, locale: Locale) -> Self {
+        let vtable_registry = super::vtable::VTableRegistry::from_chunk(&chunk);
         Self {
             chunk,
             ip: 0,
@@ -160,16 +229,28 @@ impl VM {
             exception_handlers: Vec::new(),
             locale,
             current_base: 0,
-            static_fields: std::collections::HashMap::new(),
-            vtable_registry: super::vtable::VTableRegistry::new(),
+            static_fields: StaticFields::default(),
+            vtable_registry,
             preempt_flag: None,
             inline_cache: std::collections::HashMap::with_capacity(64),
             callback_channel: None,
+            tracer: None,
+            gc_mutator: super::value::is_gc_enabled().then(super::gc::enter_mutator),
+            output: Output::default(),
+            host_functions: Arc::new([]),
+            registry: crate::stdlib::global_registry().clone(),
+            callback_depth: 0,
+            escaped: None,
+            stringifying: Vec::new(),
+            initializing_statics: Vec::new(),
+            pending_exit: Arc::default(),
+            handles_exit: true,
         }
     }
     
     /// 创建带抢占支持的虚拟机
     pub fn with_preempt(chunk: Arc<Chunk>, locale: Locale, preempt_flag: Arc<std::sync::atomic::AtomicBool>) -> Self {
+        let vtable_registry = super::vtable::VTableRegistry::from_chunk(&chunk);
         Self {
             chunk,
             ip: 0,
@@ -178,11 +259,58 @@ impl VM {
             exception_handlers: Vec::new(),
             locale,
             current_base: 0,
-            static_fields: std::collections::HashMap::new(),
-            vtable_registry: super::vtable::VTableRegistry::new(),
+            static_fields: StaticFields::default(),
+            vtable_registry,
             preempt_flag: Some(preempt_flag),
             inline_cache: std::collections::HashMap::with_capacity(64),
             callback_channel: None,
+            tracer: None,
+            gc_mutator: super::value::is_gc_enabled().then(super::gc::enter_mutator),
+            output: Output::default(),
+            host_functions: Arc::new([]),
+            registry: crate::stdlib::global_registry().clone(),
+            callback_depth: 0,
+            escaped: None,
+            stringifying: Vec::new(),
+            initializing_statics: Vec::new(),
+            pending_exit: Arc::default(),
+            handles_exit: true,
+        }
+    }
+    
+    /// 创建使用指定注册表的虚拟机：标准库调用和 `host.名称(...)` 都使用注册表中的实现
+    ///
+    /// 字节码块引用而注册表中没有的宿主函数在调用时报错；需要在运行前检查时先调用
+    /// [`HostFunctions::link`](super::host::HostFunctions::link)
+    pub fn with_registry(chunk: Arc<Chunk>, locale: Locale, registry: Arc<StdlibRegistry>) -> Self {
+        let host_functions = registry.host_functions().link_available(&chunk);
+        let mut vm = Self::new(chunk, locale);
+        vm.host_functions = host_functions;
+        vm.registry = registry;
+        vm
+    }
+    
+    /// 启用指令级执行追踪
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(Box::new(tracer));
+    }
+    
+    /// 设置宿主函数的实现，由 [`HostFunctions::link`](super::host::HostFunctions::link) 按字节码块链接得到
+    pub fn set_host_functions(&mut self, functions: Arc<[HostFn]>) {
+        self.host_functions = functions;
+    }
+    
+    /// 改变 print/println 的去向（例如测试运行器捕获每个测试的输出）
+    pub fn set_output(&mut self, output: Output) {
+        self.output = output;
+    }
+    
+    /// 启用或禁用 GC，默认取决于 [`enable_gc`](super::value::enable_gc)
+    ///
+    /// 同一程序的虚拟机（协程、回调）沿用创建者的设置
+    pub fn set_gc_enabled(&mut self, enabled: bool) {
+        if enabled != self.gc_mutator.is_some() {
+            self.gc_mutator = enabled.then(super::gc::enter_mutator);
         }
     }
     
@@ -258,8 +386,16 @@ impl VM {
         }
         
         // 扫描静态字段
-        for value in self.static_fields.values() {
-            callback(value);
+        self.static_fields.for_each_value(&mut callback);
+    }
+    
+    /// 安全点：启用了 GC 且上次回收后的分配量达到阈值时回收
+    ///
+    /// 回调执行期间不回收：调用回调的原生方法可能在 Rust 局部变量中持有栈上没有的值
+    #[inline]
+    fn gc_safepoint(&self) {
+        if self.callback_depth == 0 && self.gc_mutator.is_some() && gc_should_run() {
+            super::gc::collect_at_safepoint(|visit| self.scan_gc_roots(|value| visit(value)));
         }
     }
     
@@ -283,162 +419,110 @@ impl VM {
         Self::new(chunk, locale)
     }
     
+    /// 协程和回调的虚拟机沿用的设置
+    fn inherited(&self) -> Inherited {
+        Inherited {
+            gc_enabled: self.gc_mutator.is_some(),
+            output: self.output.clone(),
+            host_functions: self.host_functions.clone(),
+            registry: self.registry.clone(),
+            static_fields: self.static_fields.clone(),
+            pending_exit: self.pending_exit.clone(),
+        }
+    }
+    
+    /// 创建沿用 `inherited` 的设置、与创建者共享静态字段的虚拟机
+    fn inheriting(chunk: Arc<Chunk>, locale: Locale, inherited: Inherited) -> Self {
+        let mut vm = Self::new(chunk, locale);
+        vm.set_gc_enabled(inherited.gc_enabled);
+        vm.set_output(inherited.output);
+        vm.set_host_functions(inherited.host_functions);
+        vm.registry = inherited.registry;
+        vm.static_fields = inherited.static_fields;
+        vm.pending_exit = inherited.pending_exit;
+        vm.handles_exit = false;
+        vm
+    }
+    
     /// 运行协程（函数返回时自动退出）
+    ///
+    /// 协程函数在哨兵帧中执行（见 GoSpawn），返回到哨兵帧时主循环随之退出。协程与主线程使用同一个解释器循环，
+    /// 支持全部指令（方法调用、标准库类、异常等）。
     pub fn run_coroutine(&mut self) -> Result<(), RuntimeError> {
-        self.run_coroutine_internal()
+        self.run()
+    }
+
+    /// 运行字节码
+    ///
+    /// 追踪与不追踪各用一份单态化的解释器循环，未启用追踪时热路径上没有任何额外检查
+    pub fn run(&mut self) -> Result<(), RuntimeError> {
+        let _registering = self.gc_mutator.as_ref().map(|_| super::gc::start_registering());
+        self.gc_safepoint();
+        self.execute()?;
+        // 主函数返回前协程请求的退出
+        self.check_exit()
     }
     
-    /// 内部协程运行
-    fn run_coroutine_internal(&mut self) -> Result<(), RuntimeError> {
-        // 运行直到顶层帧返回
+    /// 执行解释器循环，直到程序结束或返回到回调的哨兵帧
+    ///
+    /// 内层回调抛出的异常（或 panic）逃出回调后，在这一层重新抛出，由这一层的处理器捕获；
+    /// 一直没有被捕获时报告最初抛出时的错误（栈追踪指向抛出的位置）。
+    /// `Os.exit` 结束程序时依次进入外层的 finally 处理器，执行完所有 finally 块后才返回退出错误
+    fn execute(&mut self) -> Result<(), RuntimeError> {
+        self.escaped = None;
         loop {
-            let op = self.read_byte();
-            
-            // 检查是否是返回指令
-            if op == 82 { // OpCode::Return
-                let return_value = self.pop_fast();
-                
-                if self.frames.is_empty() {
-                    // 没有调用帧了，协程结束
-                    return Ok(());
-                }
-                
-                let frame = self.frames.pop().unwrap();
-                
-                // 检查是否是协程的顶层帧（return_ip == u32::MAX）
-                if frame.return_ip == u32::MAX {
-                    // 协程结束
-                    return Ok(());
+            let result = if self.tracer.is_some() {
+                self.run_loop::<true>()
+            } else {
+                self.run_loop::<false>()
+            };
+            match (result, self.escaped.take()) {
+                (Err(error), Some((kind, value))) => {
+                    if !self.unwind(kind, value) {
+                        return Err(error);
+                    }
                 }
-                
-                let truncate_to = if frame.is_method_call {
-                    frame.base_slot as usize
-                } else {
-                    (frame.base_slot as usize).saturating_sub(1)
-                };
-                self.stack.truncate(truncate_to);
-                self.push_fast(return_value);
-                self.ip = frame.return_ip as usize;
-                self.current_base = self.frames.last().map(|f| f.base_slot as usize).unwrap_or(0);
-                continue;
-            }
-            
-            // 检查是否是 Halt 指令
-            if op == 255 { // OpCode::Halt
-                return Ok(());
-            }
-            
-            // 回退并使用主循环处理
-            self.ip -= 1;
-            
-            // 执行主循环的一次迭代
-            if let Err(e) = self.execute_one_instruction_sync() {
-                return Err(e);
+                (Err(error), None) => match error.exit_code {
+                    Some(code) if self.unwind(HandlerKind::Finally, Value::int(code as i128)) => {}
+                    _ => return Err(error),
+                },
+                (result, _) => return result,
             }
         }
     }
     
-    /// 执行一条指令（用于协程，同步版本）
-    fn execute_one_instruction_sync(&mut self) -> Result<(), RuntimeError> {
-        // 保存当前 IP，用于检测是否处理了指令
-        let saved_ip = self.ip;
-        
-        // 调用 run()，但只执行一条指令就返回
-        // 这是一个简化的实现，实际上我们复用主循环
-        let op = self.read_byte();
-        let opcode = OpCode::from(op);
-        
-        // 执行简化版的指令
-        match opcode {
-            OpCode::Const => {
-                let index = self.read_u16() as usize;
-                let value = unsafe { self.chunk.constants.get_unchecked(index).clone() };
-                self.push_fast(value);
-            }
-            OpCode::Pop => {
-                self.pop()?;
-            }
-            OpCode::Add => {
-                let b = self.pop_fast();
-                let a = self.pop_fast();
-                if let (Some(x), Some(y)) = (a.as_int(), b.as_int()) {
-                    self.push_fast(Value::int(x + y));
-                } else {
-                    let result = (a + b).map_err(|e| self.runtime_error(&e))?;
-                    self.push_fast(result);
-                }
-            }
-            OpCode::GetLocal => {
-                let slot = self.read_u16() as usize;
-                let actual_slot = self.current_base + slot;
-                let value = self.stack[actual_slot].clone();
-                self.push_fast(value);
-            }
-            OpCode::SetLocal => {
-                let slot = self.read_u16() as usize;
-                let value = self.peek()?.clone();
-                let actual_slot = self.current_base + slot;
-                self.stack[actual_slot] = value;
-            }
-            OpCode::GetUpvalue => {
-                // 获取 upvalue（当前简化实现，实际需要 upvalue 运行时支持）
-                let _index = self.read_u16() as usize;
-                // TODO: 实现完整的 upvalue 支持
-                self.push_fast(Value::null());
-            }
-            OpCode::SetUpvalue => {
-                let _index = self.read_u16() as usize;
-                // TODO: 实现完整的 upvalue 支持
-            }
-            OpCode::CloseUpvalue => {
-                let _slot = self.read_u16() as usize;
-                // TODO: 实现完整的 upvalue 关闭
-            }
-            OpCode::PrintLn => {
-                let value = self.pop_fast();
-                println!("{}", value);
-                self.push_fast(Value::null());
-            }
-            OpCode::Print => {
-                let value = self.pop_fast();
-                print!("{}", value);
-                self.push_fast(Value::null());
-            }
-            OpCode::Call => {
-                let arg_count = self.read_byte() as usize;
-                let callee_idx = self.stack.len() - arg_count - 1;
-                let callee = self.stack[callee_idx].clone();
-                
-                if let Some(func) = callee.as_function() {
-                    if self.frames.len() >= MAX_FRAMES {
-                        return Err(self.runtime_error("Stack overflow"));
-                    }
-                    let base_slot = callee_idx + 1;
-                    self.frames.push(CallFrame {
-                        return_ip: self.ip as u32,
-                        base_slot: base_slot as u16,
-                        is_method_call: false,
-                    });
-                    self.current_base = base_slot;
-                    self.ip = func.chunk_index;
-                } else {
-                    return Err(self.runtime_error(&format!("Cannot call {}", callee.type_name())));
-                }
-            }
-            _ => {
-                // 其他指令：回退并报告不支持
-                // 实际上应该支持更多指令，这里简化处理
-                return Err(self.runtime_error(&format!("Unsupported instruction {:?} in coroutine", opcode)));
-            }
-        }
-        
-        Ok(())
+    /// 换用扩展后的字节码块，从 ip 处继续执行（REPL 增量执行）
+    ///
+    /// 栈上已有的顶层变量保持不变，新代码通过相同的槽位访问它们
+    pub fn resume(&mut self, chunk: Arc<Chunk>, ip: usize) -> Result<(), RuntimeError> {
+        self.vtable_registry = super::vtable::VTableRegistry::from_chunk(&chunk);
+        self.chunk = chunk;
+        self.ip = ip;
+        self.run()
     }
-
-    /// 运行字节码
+    
+    /// 运行出错后回到顶层：丢弃调用帧和异常处理器，栈截断到 depth
+    pub fn recover(&mut self, depth: usize) {
+        self.frames.clear();
+        self.exception_handlers.clear();
+        self.stack.truncate(depth);
+        self.current_base = 0;
+    }
+    
+    /// 弹出栈顶值（REPL 取出表达式的值）
+    pub fn pop_result(&mut self) -> Option<Value> {
+        self.stack.pop()
+    }
+    
+    /// 运行结束后的栈顶值，即 `main` 的返回值（无 `main` 或栈为空时为 None）
+    pub fn result(&self) -> Option<Value> {
+        self.stack.last().copied()
+    }
+    
+    /// 解释器主循环
     /// 
     /// 使用直接 u8 匹配优化热路径指令，避免 OpCode::from() 转换开销
-    pub fn run(&mut self) -> Result<(), RuntimeError> {
+    fn run_loop<const TRACE: bool>(&mut self) -> Result<(), RuntimeError> {
         // 热路径 opcode 常量（避免每次转换）
         const OP_CONST_INT8: u8 = 130;
         const OP_GET_LOCAL: u8 = 50;
@@ -469,6 +553,12 @@ impl VM {
         // 这样避免在热路径上增加开销
         
         loop {
+            if TRACE {
+                if let Some(tracer) = self.tracer.as_mut() {
+                    tracer.record(&self.chunk, self.ip, &self.stack);
+                }
+            }
+            
             let op = self.read_byte();
             
             // 热路径：直接 u8 匹配，避免 OpCode::from() 开销
@@ -597,13 +687,14 @@ impl VM {
                             unsafe {
                                 let frame = CallFrame {
                                     return_ip: self.ip as u32,
-                                    base_slot: base_slot as u16,
+                                    base_slot: base_slot as u32,
                                     is_method_call: false,
                                 };
                                 std::ptr::write(self.frames.as_mut_ptr().add(frames_len), frame);
                                 self.frames.set_len(frames_len + 1);
                             }
                             self.current_base = base_slot;
+                            self.push_captures(func);
                             self.ip = func.chunk_index;
                             continue;
                         }
@@ -667,11 +758,12 @@ impl VM {
                         let base_slot = callee_idx + 1;
                         self.frames.push(CallFrame {
                             return_ip: self.ip as u32,
-                            base_slot: base_slot as u16,
+                            base_slot: base_slot as u32,
                             is_method_call: false,
                         });
                         
                         self.current_base = base_slot;
+                        self.push_captures(func);
                         self.ip = func.chunk_index;
                         continue;
                     } else {
@@ -704,6 +796,10 @@ impl VM {
                     // unsafe 截断栈
                     unsafe { self.stack.set_len(truncate_to); }
                     self.push_fast(return_value);
+                    // call_closure 压入的哨兵帧：回调返回，回到调用它的原生方法
+                    if frame.return_ip == u32::MAX {
+                        return Ok(());
+                    }
                     
                     self.ip = frame.return_ip as usize;
                     // 优化：直接读取新的 base，避免 last().map() 开销
@@ -790,6 +886,10 @@ impl VM {
                     };
                     self.stack.truncate(truncate_to);
                     self.push_fast(return_value);
+                    // call_closure 压入的哨兵帧：回调返回，回到调用它的原生方法
+                    if frame.return_ip == u32::MAX {
+                        return Ok(());
+                    }
                     self.ip = frame.return_ip as usize;
                     self.current_base = self.frames.last().map(|f| f.base_slot as usize).unwrap_or(0);
                     continue;
@@ -812,6 +912,10 @@ impl VM {
                     };
                     self.stack.truncate(truncate_to);
                     self.push_fast(return_value);
+                    // call_closure 压入的哨兵帧：回调返回，回到调用它的原生方法
+                    if frame.return_ip == u32::MAX {
+                        return Ok(());
+                    }
                     self.ip = frame.return_ip as usize;
                     self.current_base = self.frames.last().map(|f| f.base_slot as usize).unwrap_or(0);
                     continue;
@@ -859,15 +963,25 @@ impl VM {
                     } else if let (Some(x), Some(y)) = (a.as_float(), b.as_int()) {
                         self.push_fast(Value::float(x + y as f64));
                     } else if let (Some(s1), Some(s2)) = (a.as_string(), b.as_string()) {
-                        self.push_fast(Value::string(format!("{}{}", s1, s2)));
+                        match super::alloc::concat_str(s1, s2) {
+                            Ok(s) => self.push_fast(Value::string(s)),
+                            Err(e) => self.stdlib_error(&e)?,
+                        }
                     } else if a.is_class() || a.is_struct() {
                         // 只对 Class/Struct 类型检查运算符重载
                         if let Some(result) = self.try_operator_overload(&a, &b, "add")? {
                             self.push_fast(result);
+                        } else if b.is_string() {
+                            // 实例与字符串相加：实例按 toString() 转换
+                            let text = self.display_string(a)?;
+                            self.push_string_concat(&text, b.as_string().unwrap())?;
                         } else {
                     let result = (a + b).map_err(|e| self.runtime_error(&e))?;
                             self.push_fast(result);
                         }
+                    } else if a.is_string() && (b.is_class() || b.is_struct()) {
+                        let text = self.display_string(b)?;
+                        self.push_string_concat(a.as_string().unwrap(), &text)?;
                     } else {
                         let result = (a + b).map_err(|e| self.runtime_error(&e))?;
                         self.push_fast(result);
@@ -1011,7 +1125,8 @@ impl VM {
                     } else if let (Some(x), Some(y)) = (a.as_bool(), b.as_bool()) {
                         self.push_fast(Value::bool(x == y));
                     } else {
-                        self.push_fast(a.eq_value(&b));
+                        let equal = self.values_equal(&a, &b)?;
+                        self.push_fast(Value::bool(equal));
                     }
                 }
                 
@@ -1024,7 +1139,8 @@ impl VM {
                     } else if let (Some(x), Some(y)) = (a.as_bool(), b.as_bool()) {
                         self.push_fast(Value::bool(x != y));
                     } else {
-                        self.push_fast(a.ne_value(&b));
+                        let equal = self.values_equal(&a, &b)?;
+                        self.push_fast(Value::bool(!equal));
                     }
                 }
                 
@@ -1132,12 +1248,34 @@ impl VM {
                 
                 OpCode::Print => {
                     let value = self.pop()?;
-                    print!("{}", value);
+                    let text = self.display_string(value)?;
+                    self.output.print(&text);
                 }
                 
                 OpCode::PrintLn => {
                     let value = self.pop()?;
-                    println!("{}", value);
+                    let text = self.display_string(value)?;
+                    self.output.print(&format!("{}\n", text));
+                }
+                
+                OpCode::Hexdump => {
+                    let arg_count = self.read_byte();
+                    let print = self.read_byte() != 0;
+                    let options = if arg_count == 2 { Some(self.pop()?) } else { None };
+                    let value = self.pop()?;
+                    match super::hexdump::dump_value(&value, options.as_ref()) {
+                        Ok(dump) if print => {
+                            if !dump.is_empty() {
+                                self.output.print(&format!("{}\n", dump));
+                            }
+                            self.push(Value::null());
+                        }
+                        Ok(dump) => self.push(Value::string(dump)),
+                        Err(e) => {
+                            use crate::stdlib::exception::stdlib_exception;
+                            self.stdlib_error(&stdlib_exception("IllegalArgumentException", e))?;
+                        }
+                    }
                 }
                 
                 OpCode::TypeOf => {
@@ -1319,7 +1457,7 @@ impl VM {
                 
                 OpCode::Panic => {
                     let value = self.pop()?;
-                    return Err(self.runtime_error(&format!("Panic: {}", value)));
+                    self.raise_panic(value)?;
                 }
                 
                 OpCode::ToString => {
@@ -1329,29 +1467,48 @@ impl VM {
                     } else if let Some(n) = value.as_int() {
                         n.to_string()
                     } else if let Some(f) = value.as_float() {
-                        f.to_string()
+                        number::format_float(f)
                     } else if let Some(b) = value.as_bool() {
                         b.to_string()
                     } else if let Some(c) = value.as_char() {
                         c.to_string()
                     } else if value.is_null() {
                         "null".to_string()
-                    } else if let Some(arr) = value.as_array() {
-                        format!("{:?}", arr.lock())
-                    } else if let Some(m) = value.as_map() {
-                        format!("{:?}", m.lock())
-                    } else if let Some(s) = value.as_struct() {
-                        format!("struct {}{{...}}", s.lock().type_name)
-                    } else if let Some(c) = value.as_class() {
-                        format!("class {}{{...}}", c.lock().class_name)
-                    } else if let Some(e) = value.as_enum() {
+                    } else if let Some(e) = value.as_enum().filter(|e| e.associated_data.is_empty()) {
                         format!("{}::{}", e.enum_name, e.variant_name)
                     } else {
-                        format!("{}", value)
+                        self.display_string(value)?
                     };
                     self.push(Value::string(string_value));
                 }
                 
+                OpCode::BuildString => {
+                    let count = self.read_u16() as usize;
+                    if self.stack.len() < count {
+                        return Err(self.runtime_error("Stack underflow in string interpolation"));
+                    }
+                    let start = self.stack.len() - count;
+                    // 预先计算总长度，只分配一次
+                    let total: usize = self.stack[start..]
+                        .iter()
+                        .map(|v| v.as_string().map_or(0, |s| s.len()))
+                        .sum();
+                    if let Err(e) = super::alloc::check_bytes(total as u128) {
+                        self.stack.truncate(start);
+                        self.stdlib_error(&e)?;
+                        continue;
+                    }
+                    let mut result = String::with_capacity(total);
+                    for value in self.stack.drain(start..) {
+                        if let Some(s) = value.as_string() {
+                            result.push_str(s);
+                        } else {
+                            result.push_str(&value.to_string());
+                        }
+                    }
+                    self.push(Value::string(result));
+                }
+                
                 OpCode::CastSafe => {
                     let type_name_index = self.read_u16() as usize;
                     let type_name = if let Some(s) = self.chunk.constants[type_name_index].as_string() {
@@ -1412,7 +1569,7 @@ impl VM {
                 
                 OpCode::NewMap => {
                     let count = self.read_u16() as usize;
-                    let mut map = std::collections::HashMap::with_capacity(count);
+                    let mut map = crate::vm::MapData::with_capacity_and_hasher(count, Default::default());
                     // 从栈上弹出键值对（逆序）
                     let mut pairs = Vec::with_capacity(count);
                     for _ in 0..count {
@@ -1525,30 +1682,20 @@ impl VM {
                 
                 OpCode::ArraySlice => {
                     // 创建数组切片
-                    let end = self.pop()?.as_int().unwrap_or(0) as usize;
-                    let start = self.pop()?.as_int().unwrap_or(0) as usize;
+                    let end = self.pop()?;
+                    let start = self.pop()?;
                     let array_val = self.pop()?;
                     
                     if let Some(arr) = array_val.as_array() {
                         let arr_len = arr.lock().len();
-                        // 边界检查
-                        let actual_start = start.min(arr_len);
-                        let actual_end = end.min(arr_len);
-                        if actual_start <= actual_end {
-                            self.push(Value::array_slice(arr.clone(), actual_start, actual_end));
-                        } else {
-                            // 无效范围，返回空数组
-                            self.push(Value::array(Arc::new(Mutex::new(Vec::new()))));
-                        }
+                        let (start, end) = resolve_range(&start, Some(&end), arr_len, "array")
+                            .map_err(|e| self.runtime_error(&e))?;
+                        self.push(Value::array_slice(arr.clone(), start, end));
                     } else if let Some((source, slice_start, slice_end)) = array_val.as_array_slice() {
                         // 切片的切片
-                        let actual_start = (slice_start + start).min(slice_end);
-                        let actual_end = (slice_start + end).min(slice_end);
-                        if actual_start <= actual_end {
-                            self.push(Value::array_slice(source.clone(), actual_start, actual_end));
-                        } else {
-                            self.push(Value::array(Arc::new(Mutex::new(Vec::new()))));
-                        }
+                        let (start, end) = resolve_range(&start, Some(&end), slice_end - slice_start, "array")
+                            .map_err(|e| self.runtime_error(&e))?;
+                        self.push(Value::array_slice(source.clone(), slice_start + start, slice_start + end));
                     } else {
                         return Err(self.runtime_error(&format!(
                             "Cannot slice non-array type: {}",
@@ -1561,32 +1708,15 @@ impl VM {
                     let index = self.pop()?;
                     let object = self.pop()?;
                     
-                    if let (Some(arr), Some(i)) = (object.as_array(), index.as_int()) {
-                            let arr = arr.lock();
-                        let idx = if i < 0 {
-                            (arr.len() as i128 + i) as usize
-                            } else {
-                            i as usize
-                            };
-                            if idx >= arr.len() {
-                                return Err(self.runtime_error(&format!(
-                                    "Index {} out of bounds for array of length {}", i, arr.len()
-                                )));
-                            }
-                            self.push(arr[idx].clone());
-                    } else if let (Some(s), Some(i)) = (object.as_string(), index.as_int()) {
-                        let idx = if i < 0 {
-                            (s.len() as i128 + i) as usize
-                            } else {
-                            i as usize
-                            };
-                            if let Some(c) = s.chars().nth(idx) {
-                            self.push(Value::char(c));
-                            } else {
-                                return Err(self.runtime_error(&format!(
-                                    "Index {} out of bounds for string of length {}", i, s.len()
-                                )));
-                            }
+                    if let Some(arr) = object.as_array() {
+                        let arr = arr.lock();
+                        let idx = resolve_index(&index, arr.len(), IndexPolicy::Element, "array")
+                            .map_err(|e| self.runtime_error(&e))?;
+                        self.push(arr[idx]);
+                    } else if let Some(s) = object.as_string() {
+                        let idx = resolve_index(&index, s.chars().count(), IndexPolicy::Element, "string")
+                            .map_err(|e| self.runtime_error(&e))?;
+                        self.push(Value::char(s.chars().nth(idx).unwrap()));
                     } else if let (Some(m), Some(key)) = (object.as_map(), index.as_string()) {
                         let m = m.lock();
                         if let Some(v) = m.get(key) {
@@ -1606,23 +1736,17 @@ impl VM {
                     let index = self.pop()?;
                     let object = self.pop()?;
                     
-                    if let (Some(arr), Some(i)) = (object.as_array(), index.as_int()) {
-                            let mut arr = arr.lock();
-                        let idx = if i < 0 {
-                            (arr.len() as i128 + i) as usize
-                            } else {
-                            i as usize
-                            };
-                            if idx >= arr.len() {
-                                return Err(self.runtime_error(&format!(
-                                    "Index {} out of bounds for array of length {}", i, arr.len()
-                                )));
-                            }
+                    if let Some(arr) = object.as_array() {
+                        let mut arr = arr.lock();
+                        let idx = resolve_index(&index, arr.len(), IndexPolicy::Element, "array")
+                            .map_err(|e| self.runtime_error(&e))?;
                         arr[idx] = value;
+                        gc_write_barrier(&object);
                             self.push(value);
                     } else if let (Some(m), Some(key)) = (object.as_map(), index.as_string()) {
                         let mut m = m.lock();
                         m.insert(key.clone(), value);
+                        gc_write_barrier(&object);
                         self.push(value);
                     } else {
                             return Err(self.runtime_error(&format!(
@@ -1662,7 +1786,16 @@ impl VM {
                 OpCode::IterInit => {
                     let iterable = self.pop()?;
                     
-                    let iter = if let Some(arr) = iterable.as_array() {
+                    let iter = if let Some(map) = iterable.as_map() {
+                        // 遍历开始时的键值对快照，每个元素是 [key, value]
+                        let entries = map.lock().iter()
+                            .map(|(k, v)| Value::array(Arc::new(Mutex::new(vec![Value::string(k.clone()), *v]))))
+                            .collect();
+                        Iterator {
+                            source: IteratorSource::Array(Arc::new(Mutex::new(entries))),
+                            index: 0,
+                        }
+                    } else if let Some(arr) = iterable.as_array() {
                             Iterator {
                             source: IteratorSource::Array(arr.clone()),
                                 index: 0,
@@ -1672,6 +1805,32 @@ impl VM {
                                 source: IteratorSource::Range(start, end, inclusive),
                                 index: 0,
                             }
+                    } else if let Some(state) = iterable.as_channel() {
+                        let Some(receiver) = state.lock().receiver.lock().clone() else {
+                            return Err(self.runtime_error("Channel receiver is closed"));
+                        };
+                        Iterator {
+                            source: IteratorSource::Channel(receiver),
+                            index: 0,
+                        }
+                    } else if let Some(func) = self.entries_method(&iterable) {
+                        // 对象提供 entries()：像普通方法调用一样执行，返回后回到本条指令遍历其结果
+                        if func.arity != 1 {
+                            return Err(self.runtime_error("entries() used by for-in must take no arguments"));
+                        }
+                        if self.frames.len() >= MAX_FRAMES {
+                            return Err(self.runtime_error("Stack overflow: too many nested function calls"));
+                        }
+                        let receiver_idx = self.stack.len();
+                        self.push(iterable);
+                        self.frames.push(CallFrame {
+                            return_ip: (self.ip - 1) as u32,
+                            base_slot: receiver_idx as u32,
+                            is_method_call: true,
+                        });
+                        self.current_base = receiver_idx;
+                        self.ip = func.chunk_index;
+                        continue;
                     } else {
                             return Err(self.runtime_error(&format!(
                                 "Cannot iterate over {}",
@@ -1681,6 +1840,30 @@ impl VM {
                     self.push(Value::iterator(Arc::new(Mutex::new(iter))));
                 }
                 
+                OpCode::UnpackPair => {
+                    let element = self.pop()?;
+                    let pair = element.as_array().map(|arr| arr.lock().clone());
+                    match pair.as_deref() {
+                        Some([first, second]) => {
+                            let (first, second) = (*first, *second);
+                            self.push(first);
+                            self.push(second);
+                        }
+                        _ => {
+                            // 迭代器在元素下方，IterNext 取出元素后已经前进了一位
+                            let index = self.peek()?.as_iterator().map_or(0, |iter| iter.lock().index.saturating_sub(1));
+                            let description = match &pair {
+                                Some(items) => format!("an array of {} elements", items.len()),
+                                None => with_article(element.type_name()),
+                            };
+                            return Err(self.runtime_error(&format!(
+                                "for-in with two variables requires pairs; element {} is {}",
+                                index, description
+                            )));
+                        }
+                    }
+                }
+                
                 OpCode::IterNext => {
                     // 获取迭代器但不弹出
                     let iter_val = self.peek()?.clone();
@@ -1716,6 +1899,11 @@ impl VM {
                                     (Value::null(), false)
                                 }
                             }
+                            // 阻塞到有值可取，通道关闭且取空后结束循环
+                            IteratorSource::Channel(receiver) => match self.receive_blocking(&receiver)? {
+                                Some(value) => (value, true),
+                                None => (Value::null(), false),
+                            },
                         };
                         
                         // 如果有下一个元素，更新索引
@@ -1768,6 +1956,8 @@ impl VM {
                     self.ip += offset;
                 }
                 
+                OpCode::JumpTable => self.jump_table()?,
+                
                 OpCode::JumpIfFalse => {
                     let offset = self.read_u16() as usize;
                     // SAFETY: peek 在非空栈上调用
@@ -1792,44 +1982,119 @@ impl VM {
                         // 可以在这里让出 CPU，但对于单线程 VM 我们只是清除标志
                         self.clear_preempt();
                     }
+                    self.gc_safepoint();
+                    self.check_exit()?;
                     let offset = self.read_u16() as usize;
                     self.ip -= offset;
                 }
                 
                 OpCode::Closure => {
-                    // Closure 指令已被替换为直接加载函数常量
-                    // 这个分支不应该被执行
-                    let _func_index = self.read_u16();
-                    let msg = "Unexpected Closure opcode";
-                    return Err(self.runtime_error(msg));
+                    let index = self.read_u16() as usize;
+                    self.make_closure(index)?;
                 }
                 
-                OpCode::Call => {
+                OpCode::MakeCell => {
+                    let slot = self.read_u16() as usize;
+                    self.make_cell(slot);
+                }
+                
+                OpCode::GetCell => {
+                    let slot = self.read_u16() as usize;
+                    self.get_cell(slot)?;
+                }
+                
+                OpCode::SetCell => {
+                    let slot = self.read_u16() as usize;
+                    self.set_cell(slot)?;
+                }
+                
+                OpCode::CallStdlib => {
+                    let module_index = self.read_u16() as usize;
+                    let func_index = self.read_u16() as usize;
                     let arg_count = self.read_byte() as usize;
                     
-                    // 获取被调用的函数（在参数下方）
-                    let callee_idx = self.stack.len() - arg_count - 1;
-                    let callee = self.stack[callee_idx].clone();
+                    let (module, func) = match (
+                        self.chunk.constants[module_index].as_string(),
+                        self.chunk.constants[func_index].as_string(),
+                    ) {
+                        (Some(m), Some(f)) => (m.clone(), f.clone()),
+                        _ => return Err(self.runtime_error("Invalid standard library function")),
+                    };
                     
-                    if let Some(func) = callee.as_function() {
-                        // 快速路径：简单函数调用（参数数量匹配，无默认值，无可变参数）
-                        if !func.has_variadic && func.defaults.is_empty() && arg_count == func.arity {
-                            // 检查调用深度
-                            if self.frames.len() >= MAX_FRAMES {
-                                let msg = "Stack overflow: too many nested function calls";
-                                return Err(self.runtime_error(msg));
-                            }
-                            
-                            // 创建调用帧
+                    let args_start = self.stack.len() - arg_count;
+                    let args: Vec<Value> = self.stack.drain(args_start..).collect();
+                    
+                    let registry = self.registry.clone();
+                    let result = match registry.get(&module) {
+                        Some(lib) if lib.function_needs_callback(&func, &args) => {
+                            let callback_channel = self.callback_channel();
+                            lib.call_with_callback(&func, &args, callback_channel)
+                        }
+                        Some(lib) if lib.blocks(&func) => {
+                            // 阻塞期间交出根集，其他协程仍可回收
+                            let parked = self.park_for_native(&Value::null(), &args);
+                            let result = if self.handles_exit {
+                                crate::stdlib::os::watching_exit(&self.pending_exit, || lib.call(&func, &args))
+                            } else {
+                                lib.call(&func, &args)
+                            };
+                            drop(parked);
+                            // Time.sleep 等调用因协程请求退出而提前返回
+                            self.check_exit()?;
+                            result
+                        }
+                        _ => registry.call(&module, &func, &args),
+                    };
+                    match result {
+                        Ok(result) => self.push(result),
+                        Err(e) => self.stdlib_error(&e)?,
+                    }
+                    // Runtime.gc() 等调用要求的回收在返回后立即进行
+                    self.gc_safepoint();
+                }
+                
+                OpCode::HostCall => {
+                    let index = self.read_u16() as usize;
+                    let arg_count = self.read_byte() as usize;
+                    let Some(func) = self.host_functions.get(index).cloned() else {
+                        let name = self.chunk.host_functions.get(index).map_or("?", String::as_str);
+                        return Err(self.runtime_error(&format!("host function '{}' is not available", name)));
+                    };
+                    let args_start = self.stack.len() - arg_count;
+                    let args: Vec<Value> = self.stack.drain(args_start..).collect();
+                    match func(&args) {
+                        Ok(result) => self.push(result),
+                        Err(e) => self.stdlib_error(&e)?,
+                    }
+                }
+                
+                OpCode::Call => {
+                    let arg_count = self.read_byte() as usize;
+                    
+                    // 获取被调用的函数（在参数下方）
+                    let callee_idx = self.stack.len() - arg_count - 1;
+                    let callee = self.stack[callee_idx].clone();
+                    
+                    if let Some(func) = callee.as_function() {
+                        // 快速路径：简单函数调用（参数数量匹配，无默认值，无可变参数）
+                        if !func.has_variadic && func.defaults.is_empty() && arg_count == func.arity {
+                            // 检查调用深度
+                            if self.frames.len() >= MAX_FRAMES {
+                                let msg = "Stack overflow: too many nested function calls";
+                                return Err(self.runtime_error(msg));
+                            }
+                            
+                            // 创建调用帧
                             let base_slot = callee_idx + 1;
                             self.frames.push(CallFrame {
                                 return_ip: self.ip as u32,
-                                base_slot: base_slot as u16,
+                                base_slot: base_slot as u32,
                                 is_method_call: false,
                             });
                             
                             // 更新缓存的栈基址
                             self.current_base = base_slot;
+                            self.push_captures(func);
                             
                             // 跳转到函数体
                             self.ip = func.chunk_index;
@@ -1904,12 +2169,13 @@ impl VM {
                             let base_slot = callee_idx + 1;
                             self.frames.push(CallFrame {
                                 return_ip: self.ip as u32,
-                                base_slot: base_slot as u16,
+                                base_slot: base_slot as u32,
                                 is_method_call: false,
                             });
                             
                             // 更新缓存的栈基址
                             self.current_base = base_slot;
+                            self.push_captures(func);
                         
                         // 跳转到函数体
                         self.ip = func.chunk_index;
@@ -2063,6 +2329,7 @@ impl VM {
                             let mut s = s.lock();
                             if s.fields.contains_key(&field_name) {
                                 s.fields.insert(field_name, value);
+                                gc_write_barrier(&obj_val);
                             } else {
                                 return Err(self.runtime_error(&format!(
                                     "Struct '{}' has no field '{}'",
@@ -2073,6 +2340,7 @@ impl VM {
                         let mut c = c.lock();
                         // 对于 class，允许设置已定义的字段或新字段
                         c.fields.insert(field_name, value);
+                        gc_write_barrier(&obj_val);
                     } else {
                         return Err(self.runtime_error(&format!(
                             "Cannot set field '{}' on {}",
@@ -2082,6 +2350,31 @@ impl VM {
                     }
                 }
                 
+                OpCode::InitField => {
+                    let field_name_index = self.read_u16() as usize;
+                    let field_name_value = self.chunk.constants[field_name_index];
+                    let Some(field_name) = field_name_value.as_string() else {
+                        return Err(self.runtime_error("Invalid field name"));
+                    };
+                    let value = self.pop()?;
+                    let obj_val = *self.peek()?;
+                    let Some(c) = obj_val.as_class() else {
+                        return Err(self.runtime_error(&format!("Cannot set field '{}' on {}", field_name, obj_val.type_name())));
+                    };
+                    let mut c = c.lock();
+                    // 实例创建时已经有类和父类声明的全部字段
+                    match c.fields.get_mut(field_name) {
+                        Some(slot) => *slot = value,
+                        None => {
+                            let msg = format!("Class '{}' has no field '{}'", c.class_name, field_name);
+                            drop(c);
+                            return Err(self.runtime_error(&msg));
+                        }
+                    }
+                    drop(c);
+                    gc_write_barrier(&obj_val);
+                }
+                
                 OpCode::JumpIfNull => {
                     let offset = self.read_u16() as usize;
                     let value = self.peek()?;
@@ -2188,14 +2481,14 @@ impl VM {
                         continue;
                     }
                     
-                    // 检查是否是标准库类实例
+                    // 检查是否是标准库类实例（同名的用户类优先）
                     if let Some(class_instance) = receiver.as_class() {
                         let instance_guard = class_instance.lock();
                         let class_name = instance_guard.class_name.clone();
                         drop(instance_guard);
                         
-                        let registry = get_stdlib_registry();
-                        if let Some((_, _)) = registry.find_class_module(&class_name) {
+                        let registry = self.registry.clone();
+                        if self.chunk.get_type(&class_name).is_none() && registry.find_class_module(&class_name).is_some() {
                             // 是标准库类实例，从栈中获取参数
                             let args_start = receiver_idx + 1;
                             let args = self.stack[args_start..].to_vec();
@@ -2204,33 +2497,18 @@ impl VM {
 
                             // 检查是否需要回调支持
                             if registry.needs_callback(&class_name, &method_name) {
-                                // 需要回调支持的方法 - 创建回调通道
-                                use crate::stdlib::CallbackChannel;
-
-                                // 获取或创建回调通道
-                                let callback_channel = if let Some(ch) = &self.callback_channel {
-                                    ch.clone()
-                                } else {
-                                    // 第一次创建回调通道时启动处理线程
-                                    let new_channel = Arc::new(CallbackChannel::new());
-                                    let chunk = self.chunk.clone();
-                                    let locale = self.locale;
-                                    let channel = new_channel.clone();
-                                    std::thread::spawn(move || {
-                                        Self::callback_handler_loop(chunk, locale, channel);
-                                    });
-                                    // 保存到 VM 实例
-                                    self.callback_channel = Some(new_channel.clone());
-                                    new_channel
-                                };
-
-                                match registry.call_class_method_with_callback(&receiver, &method_name, &args, callback_channel) {
+                                // 需要回调支持的方法：可能一直阻塞（如 HttpServer.listen），期间交出根集
+                                let callback_channel = self.callback_channel();
+                                let parked = self.park_for_native(&receiver, &args);
+                                let result = registry.call_class_method_with_callback(&receiver, &method_name, &args, callback_channel);
+                                drop(parked);
+                                match result {
                                     Ok(result) => {
                                         self.push(result);
                                         continue;
                                     }
                                     Err(e) => {
-                                        return Err(self.runtime_error(&e));
+                                        self.stdlib_error(&e)?;                                        continue;
                                     }
                                 }
                             } else {
@@ -2241,7 +2519,7 @@ impl VM {
                                         continue;
                                     }
                                     Err(e) => {
-                                        return Err(self.runtime_error(&e));
+                                        self.stdlib_error(&e)?;                                        continue;
                                     }
                                 }
                             }
@@ -2249,104 +2527,17 @@ impl VM {
                     }
                     
                     // 否则执行类/结构体方法调用
-                    if let Some(instance) = receiver.as_class() {
+                    let invoked = if let Some(instance) = receiver.as_class() {
                         let instance = instance.lock();
-                        let class_name = instance.class_name.clone();
-                        drop(instance);
-                        
-                        if let Some(type_info) = self.chunk.get_type(&class_name).cloned() {
-                            if let Some(&method_index) = type_info.methods.get(&method_name) {
-                                let method_index = method_index as usize;
-                                if let Some(func) = self.chunk.constants[method_index].as_function() {
-                                    let func = func.clone();
-                                    
-                                    // 将 receiver 移到参数前面作为 this
-                                    let this_slot = receiver_idx;
-                                    
-                                    // 检查参数数量
-                                    if arg_count < func.required_params.saturating_sub(1) {
-                                        let msg = format!(
-                                            "Method '{}' expected at least {} arguments but got {}",
-                                            method_name, func.required_params.saturating_sub(1), arg_count
-                                        );
-                                        return Err(self.runtime_error(&msg));
-                                    }
-                                    
-                                    // 填充默认参数
-                                    let missing = func.arity.saturating_sub(arg_count + 1);
-                                    if missing > 0 && !func.defaults.is_empty() {
-                                        let start = func.defaults.len().saturating_sub(missing);
-                                        for i in 0..missing {
-                                            if start + i < func.defaults.len() {
-                                                self.push(func.defaults[start + i].clone());
-                                            }
-                                        }
-                                    }
-                                    
-                                    if self.frames.len() >= MAX_FRAMES {
-                                        return Err(self.runtime_error("Stack overflow"));
-                                    }
-                                    
-                                    let frame = CallFrame {
-                                        return_ip: self.ip as u32,
-                                        base_slot: this_slot as u16,
-                                        is_method_call: true,
-                                    };
-                                    self.frames.push(frame);
-                                    self.current_base = this_slot;
-                                    self.ip = func.chunk_index;
-                                    continue;
-                                }
-                            }
-                        }
-                    }
-                    
-                    if let Some(instance) = receiver.as_struct() {
+                        self.invoke_own_method(&instance.class_name, &method_name, receiver_idx, arg_count)?
+                    } else if let Some(instance) = receiver.as_struct() {
                         let instance = instance.lock();
-                        let type_name = instance.type_name.clone();
-                        drop(instance);
-                        
-                        if let Some(type_info) = self.chunk.get_type(&type_name).cloned() {
-                            if let Some(&method_index) = type_info.methods.get(&method_name) {
-                                let method_index = method_index as usize;
-                                if let Some(func) = self.chunk.constants[method_index].as_function() {
-                                    let func = func.clone();
-                                    let this_slot = receiver_idx;
-                                    
-                                    if arg_count < func.required_params.saturating_sub(1) {
-                                        let msg = format!(
-                                            "Method '{}' expected at least {} arguments but got {}",
-                                            method_name, func.required_params.saturating_sub(1), arg_count
-                                        );
-                                        return Err(self.runtime_error(&msg));
-                                    }
-                                    
-                                    let missing = func.arity.saturating_sub(arg_count + 1);
-                                    if missing > 0 && !func.defaults.is_empty() {
-                                        let start = func.defaults.len().saturating_sub(missing);
-                                        for i in 0..missing {
-                                            if start + i < func.defaults.len() {
-                                                self.push(func.defaults[start + i].clone());
-                                            }
-                                        }
-                                    }
-                                    
-                                    if self.frames.len() >= MAX_FRAMES {
-                                        return Err(self.runtime_error("Stack overflow"));
-                                    }
-                                    
-                                    let frame = CallFrame {
-                                        return_ip: self.ip as u32,
-                                        base_slot: this_slot as u16,
-                                        is_method_call: true,
-                                    };
-                                    self.frames.push(frame);
-                                    self.current_base = this_slot;
-                                    self.ip = func.chunk_index;
-                                    continue;
-                                }
-                            }
-                        }
+                        self.invoke_own_method(&instance.type_name, &method_name, receiver_idx, arg_count)?
+                    } else {
+                        false
+                    };
+                    if invoked {
+                        continue;
                     }
                     
                     return Err(self.runtime_error(&format!(
@@ -2378,14 +2569,14 @@ impl VM {
                         )));
                     }
                     
-                    // 检查是否是标准库类实例
+                    // 检查是否是标准库类实例（同名的用户类优先）
                     if let Some(class_instance) = receiver.as_class() {
                         let instance_guard = class_instance.lock();
                         let class_name = instance_guard.class_name.clone();
                         drop(instance_guard);
                         
-                        let registry = get_stdlib_registry();
-                        if let Some((_, _)) = registry.find_class_module(&class_name) {
+                        let registry = self.registry.clone();
+                        if self.chunk.get_type(&class_name).is_none() && registry.find_class_module(&class_name).is_some() {
                             // 是标准库类实例，从栈中获取参数
                             let args_start = receiver_idx + 1;
                             let args = self.stack[args_start..].to_vec();
@@ -2394,33 +2585,18 @@ impl VM {
 
                             // 检查是否需要回调支持
                             if registry.needs_callback(&class_name, &method_name) {
-                                // 需要回调支持的方法 - 创建回调通道
-                                use crate::stdlib::CallbackChannel;
-
-                                // 获取或创建回调通道
-                                let callback_channel = if let Some(ch) = &self.callback_channel {
-                                    ch.clone()
-                                } else {
-                                    // 第一次创建回调通道时启动处理线程
-                                    let new_channel = Arc::new(CallbackChannel::new());
-                                    let chunk = self.chunk.clone();
-                                    let locale = self.locale;
-                                    let channel = new_channel.clone();
-                                    std::thread::spawn(move || {
-                                        Self::callback_handler_loop(chunk, locale, channel);
-                                    });
-                                    // 保存到 VM 实例
-                                    self.callback_channel = Some(new_channel.clone());
-                                    new_channel
-                                };
-
-                                match registry.call_class_method_with_callback(&receiver, &method_name, &args, callback_channel) {
+                                // 需要回调支持的方法：可能一直阻塞（如 HttpServer.listen），期间交出根集
+                                let callback_channel = self.callback_channel();
+                                let parked = self.park_for_native(&receiver, &args);
+                                let result = registry.call_class_method_with_callback(&receiver, &method_name, &args, callback_channel);
+                                drop(parked);
+                                match result {
                                     Ok(result) => {
                                         self.push(result);
                                         continue;
                                     }
                                     Err(e) => {
-                                        return Err(self.runtime_error(&e));
+                                        self.stdlib_error(&e)?;                                        continue;
                                     }
                                 }
                             } else {
@@ -2431,7 +2607,7 @@ impl VM {
                                         continue;
                                     }
                                     Err(e) => {
-                                        return Err(self.runtime_error(&e));
+                                        self.stdlib_error(&e)?;                                        continue;
                                     }
                                 }
                             }
@@ -2439,100 +2615,17 @@ impl VM {
                     }
                     
                     // 否则执行类/结构体方法调用
-                    if let Some(instance) = receiver.as_class() {
+                    let invoked = if let Some(instance) = receiver.as_class() {
                         let instance = instance.lock();
-                        let class_name = instance.class_name.clone();
-                        drop(instance);
-                        
-                        if let Some(type_info) = self.chunk.get_type(&class_name).cloned() {
-                            if let Some(&method_index) = type_info.methods.get(&method_name) {
-                                let method_index = method_index as usize;
-                                if let Some(func) = self.chunk.constants[method_index].as_function() {
-                                    let func = func.clone();
-                                    let this_slot = receiver_idx;
-                                    
-                                    if arg_count < func.required_params.saturating_sub(1) {
-                                        let msg = format!(
-                                            "Method '{}' expected at least {} arguments but got {}",
-                                            method_name, func.required_params.saturating_sub(1), arg_count
-                                        );
-                                        return Err(self.runtime_error(&msg));
-                                    }
-                                    
-                                    let missing = func.arity.saturating_sub(arg_count + 1);
-                                    if missing > 0 && !func.defaults.is_empty() {
-                                        let start = func.defaults.len().saturating_sub(missing);
-                                        for i in 0..missing {
-                                            if start + i < func.defaults.len() {
-                                                self.push(func.defaults[start + i].clone());
-                                            }
-                                        }
-                                    }
-                                    
-                                    if self.frames.len() >= MAX_FRAMES {
-                                        return Err(self.runtime_error("Stack overflow"));
-                                    }
-                                    
-                                    let frame = CallFrame {
-                                        return_ip: self.ip as u32,
-                                        base_slot: this_slot as u16,
-                                        is_method_call: true,
-                                    };
-                                    self.frames.push(frame);
-                                    self.current_base = this_slot;
-                                    self.ip = func.chunk_index;
-                                    continue;
-                                }
-                            }
-                        }
-                    }
-                    
-                    if let Some(instance) = receiver.as_struct() {
+                        self.invoke_own_method(&instance.class_name, &method_name, receiver_idx, arg_count)?
+                    } else if let Some(instance) = receiver.as_struct() {
                         let instance = instance.lock();
-                        let type_name = instance.type_name.clone();
-                        drop(instance);
-                        
-                        if let Some(type_info) = self.chunk.get_type(&type_name).cloned() {
-                            if let Some(&method_index) = type_info.methods.get(&method_name) {
-                                let method_index = method_index as usize;
-                                if let Some(func) = self.chunk.constants[method_index].as_function() {
-                                    let func = func.clone();
-                                    let this_slot = receiver_idx;
-                                    
-                                    if arg_count < func.required_params.saturating_sub(1) {
-                                        let msg = format!(
-                                            "Method '{}' expected at least {} arguments but got {}",
-                                            method_name, func.required_params.saturating_sub(1), arg_count
-                                        );
-                                        return Err(self.runtime_error(&msg));
-                                    }
-                                    
-                                    let missing = func.arity.saturating_sub(arg_count + 1);
-                                    if missing > 0 && !func.defaults.is_empty() {
-                                        let start = func.defaults.len().saturating_sub(missing);
-                                        for i in 0..missing {
-                                            if start + i < func.defaults.len() {
-                                                self.push(func.defaults[start + i].clone());
-                                            }
-                                        }
-                                    }
-                                    
-                                    if self.frames.len() >= MAX_FRAMES {
-                                        return Err(self.runtime_error("Stack overflow"));
-                                    }
-                                    
-                                    let frame = CallFrame {
-                                        return_ip: self.ip as u32,
-                                        base_slot: this_slot as u16,
-                                        is_method_call: true,
-                                    };
-                                    self.frames.push(frame);
-                                    self.current_base = this_slot;
-                                    self.ip = func.chunk_index;
-                                    continue;
-                                }
-                            }
-                        }
+                        self.invoke_own_method(&instance.type_name, &method_name, receiver_idx, arg_count)?
+                    } else {
+                        false
+                    };
+                    if invoked {
+                        continue;
                     }
                     
                     return Err(self.runtime_error(&format!(
@@ -2545,10 +2638,9 @@ impl VM {
                     let method_name_index = self.read_u16() as usize;
                     let arg_count = self.read_byte() as usize;
                     
-                    // 获取方法名
-                    let method_name = if let Some(s) = self.chunk.constants[method_name_index].as_string() {
-                        s.clone()
-                    } else {
+                    // 获取方法名（常量是 Copy 的句柄，借用它而不是复制字符串）
+                    let method_name_value = self.chunk.constants[method_name_index];
+                    let Some(method_name) = method_name_value.as_string() else {
                         return Err(self.runtime_error("Invalid method name"));
                     };
                     
@@ -2556,14 +2648,17 @@ impl VM {
                     let receiver_idx = self.stack.len() - arg_count - 1;
                     let receiver = self.stack[receiver_idx].clone();
                     
-                    // 检查是否是标准库类实例
+                    // 检查是否是标准库类实例（同名的用户类优先）
                     if let Some(class_instance) = receiver.as_class() {
-                        let instance_guard = class_instance.lock();
-                        let class_name = instance_guard.class_name.clone();
-                        drop(instance_guard);
-                        
-                        let registry = get_stdlib_registry();
-                        if let Some((_, _)) = registry.find_class_module(&class_name) {
+                        let registry = self.registry.clone();
+                        // 只有标准库类实例才需要复制类名
+                        let stdlib_class = {
+                            let instance = class_instance.lock();
+                            (self.chunk.get_type(&instance.class_name).is_none()
+                                && registry.find_class_module(&instance.class_name).is_some())
+                                .then(|| instance.class_name.clone())
+                        };
+                        if let Some(class_name) = stdlib_class {
                             // 是标准库类实例，从栈中获取参数
                             let args_start = receiver_idx + 1;
                             let args = self.stack[args_start..].to_vec();
@@ -2571,45 +2666,30 @@ impl VM {
                             self.stack.truncate(receiver_idx);
 
                             // 检查是否需要回调支持
-                            if registry.needs_callback(&class_name, &method_name) {
-                                // 需要回调支持的方法 - 创建回调通道
-                                use crate::stdlib::CallbackChannel;
-
-                                // 获取或创建回调通道
-                                let callback_channel = if let Some(ch) = &self.callback_channel {
-                                    ch.clone()
-                                } else {
-                                    // 第一次创建回调通道时启动处理线程
-                                    let new_channel = Arc::new(CallbackChannel::new());
-                                    let chunk = self.chunk.clone();
-                                    let locale = self.locale;
-                                    let channel = new_channel.clone();
-                                    std::thread::spawn(move || {
-                                        Self::callback_handler_loop(chunk, locale, channel);
-                                    });
-                                    // 保存到 VM 实例
-                                    self.callback_channel = Some(new_channel.clone());
-                                    new_channel
-                                };
-
-                                match registry.call_class_method_with_callback(&receiver, &method_name, &args, callback_channel) {
+                            if registry.needs_callback(&class_name, method_name) {
+                                // 需要回调支持的方法：可能一直阻塞（如 HttpServer.listen），期间交出根集
+                                let callback_channel = self.callback_channel();
+                                let parked = self.park_for_native(&receiver, &args);
+                                let result = registry.call_class_method_with_callback(&receiver, method_name, &args, callback_channel);
+                                drop(parked);
+                                match result {
                                     Ok(result) => {
                                         self.push(result);
                                         continue;
                                     }
                                     Err(e) => {
-                                        return Err(self.runtime_error(&e));
+                                        self.stdlib_error(&e)?;                                        continue;
                                     }
                                 }
                             } else {
                                 // 普通方法调用
-                                match registry.call_class_method(&receiver, &method_name, &args) {
+                                match registry.call_class_method(&receiver, method_name, &args) {
                                     Ok(result) => {
                                         self.push(result);
                                         continue;
                                     }
                                     Err(e) => {
-                                        return Err(self.runtime_error(&e));
+                                        self.stdlib_error(&e)?;                                        continue;
                                     }
                                 }
                             }
@@ -2618,9 +2698,17 @@ impl VM {
                     
                     // 检查是否是类型引用（静态方法调用）
                     if let Some(class_name) = receiver.as_type_ref() {
-                        let class_name = class_name.clone();
+                        // 内置数值类型的静态方法（Int.parse）
+                        if let Some(result) = number::call_static(class_name, method_name, &self.stack[receiver_idx + 1..]) {
+                            self.stack.truncate(receiver_idx);
+                            match result {
+                                Ok(value) => self.push(value),
+                                Err(e) => self.stdlib_error(&e)?,
+                            }
+                            continue;
+                        }
                         // 查找静态方法
-                        let func_index = match self.chunk.get_static_method(&class_name, &method_name) {
+                        let func_index = match self.chunk.get_static_method(class_name, method_name) {
                             Some(idx) => idx as usize,
                             None => return Err(self.runtime_error(&format!(
                                 "Class '{}' has no static method '{}'",
@@ -2628,9 +2716,8 @@ impl VM {
                             ))),
                         };
                         
-                        let func = if let Some(f) = self.chunk.constants[func_index].as_function() {
-                            f.clone()
-                        } else {
+                        let func_value = self.chunk.constants[func_index];
+                        let Some(func) = func_value.as_function() else {
                             return Err(self.runtime_error("Static method is not a function"));
                         };
                         
@@ -2642,11 +2729,7 @@ impl VM {
                         let missing = func.arity.saturating_sub(arg_count);
                         if missing > 0 && !func.defaults.is_empty() {
                             let start = func.defaults.len().saturating_sub(missing);
-                            for i in 0..missing {
-                                if start + i < func.defaults.len() {
-                                    self.push(func.defaults[start + i].clone());
-                                }
-                            }
+                            self.stack.extend_from_slice(&func.defaults[start..]);
                         }
                         
                         if self.frames.len() >= MAX_FRAMES {
@@ -2655,14 +2738,55 @@ impl VM {
                         
                         let frame = CallFrame {
                             return_ip: self.ip as u32,
-                            base_slot: base as u16,
+                            base_slot: base as u32,
                             is_method_call: false, // 静态方法没有 this，类似普通函数调用
                         };
                         self.frames.push(frame);
+                        self.current_base = base;
                         self.ip = func.chunk_index;
                         continue;
                     }
                     
+                    // 检查是否是通道方法调用
+                    if receiver.is_channel() {
+                        let result = match (method_name.as_str(), arg_count) {
+                            // 阻塞期间参数留在栈上，保证发送中的值对 GC 可达
+                            ("send", 1) => {
+                                if !self.channel_send(&receiver, self.stack[receiver_idx + 1])? {
+                                    self.throw_channel_closed("send on closed channel")?;
+                                    continue;
+                                }
+                                Value::null()
+                            }
+                            ("receive", 0) => self.channel_receive(&receiver)?,
+                            ("close", 0) => {
+                                if !self.channel_close(&receiver)? {
+                                    self.throw_channel_closed("close of closed channel")?;
+                                    continue;
+                                }
+                                Value::null()
+                            }
+                            ("isClosed", 0) => {
+                                let closed = receiver.as_channel()
+                                    .is_some_and(|state| state.lock().closed.load(std::sync::atomic::Ordering::Acquire));
+                                Value::bool(closed)
+                            }
+                            ("send", _) | ("receive", _) | ("close", _) | ("isClosed", _) => {
+                                return Err(self.runtime_error(&format!(
+                                    "{}() expects {} argument(s)",
+                                    method_name,
+                                    if method_name == "send" { 1 } else { 0 }
+                                )));
+                            }
+                            _ => {
+                                return Err(self.runtime_error(&format!("Unknown channel method '{}'", method_name)));
+                            }
+                        };
+                        self.stack.truncate(receiver_idx);
+                        self.push(result);
+                        continue;
+                    }
+                    
                     // 检查是否是数组方法调用
                     if let Some(arr) = receiver.as_array() {
                         match method_name.as_str() {
@@ -2672,10 +2796,14 @@ impl VM {
                                     return Err(self.runtime_error("push() expects 1 argument"));
                                 }
                                 let value = self.stack[receiver_idx + 1].clone();
-                                arr.lock().push(value);
+                                let pushed = super::alloc::push(&mut arr.lock(), value);
+                                gc_write_barrier(&receiver);
                                 // 移除参数和 receiver，返回 null
                                 self.stack.truncate(receiver_idx);
-                                self.push(Value::null());
+                                match pushed {
+                                    Ok(()) => self.push(Value::null()),
+                                    Err(e) => self.stdlib_error(&e)?,
+                                }
                                 continue;
                             }
                             "pop" => {
@@ -2786,12 +2914,12 @@ impl VM {
                                 } else {
                                     return Err(self.runtime_error("join() expects a string argument"));
                                 };
-                                let result: String = arr.lock().iter()
-                                    .map(|v| format!("{}", v))
-                                    .collect::<Vec<_>>()
-                                    .join(&sep);
+                                let result = super::alloc::join_values(&arr.lock(), &sep);
                                 self.stack.truncate(receiver_idx);
-                                self.push(Value::string(result));
+                                match result {
+                                    Ok(result) => self.push(Value::string(result)),
+                                    Err(e) => self.stdlib_error(&e)?,
+                                }
                                 continue;
                             }
                             "slice" => {
@@ -2799,22 +2927,11 @@ impl VM {
                                 if arg_count < 1 || arg_count > 2 {
                                     return Err(self.runtime_error("slice() expects 1 or 2 arguments"));
                                 }
-                                let start = if let Some(i) = self.stack[receiver_idx + 1].as_int() {
-                                    i as usize
-                                } else {
-                                    return Err(self.runtime_error("slice() first argument must be integer"));
-                                };
+                                let end = (arg_count == 2).then(|| self.stack[receiver_idx + 2]);
                                 let arr_len = arr.lock().len();
-                                let end = if arg_count == 2 {
-                                    if let Some(i) = self.stack[receiver_idx + 2].as_int() {
-                                        (i as usize).min(arr_len)
-                                    } else {
-                                        return Err(self.runtime_error("slice() second argument must be integer"));
-                                    }
-                                } else {
-                                    arr_len
-                                };
-                                let result: Vec<Value> = arr.lock()[start.min(arr_len)..end].to_vec();
+                                let (start, end) = resolve_range(&self.stack[receiver_idx + 1], end.as_ref(), arr_len, "array")
+                                    .map_err(|e| self.runtime_error(&e))?;
+                                let result: Vec<Value> = arr.lock()[start..end].to_vec();
                                 self.stack.truncate(receiver_idx);
                                 self.push(Value::array(Arc::new(Mutex::new(result))));
                                 continue;
@@ -2829,10 +2946,19 @@ impl VM {
                                 } else {
                                     return Err(self.runtime_error("concat() expects an array argument"));
                                 };
-                                let mut result = arr.lock().clone();
-                                result.extend(other);
+                                let this = arr.lock();
+                                let result = super::alloc::vec_with_capacity(this.len() as u128 + other.len() as u128)
+                                    .map(|mut result| {
+                                        result.extend_from_slice(&this);
+                                        result.extend(other);
+                                        result
+                                    });
+                                drop(this);
                                 self.stack.truncate(receiver_idx);
-                                self.push(Value::array(Arc::new(Mutex::new(result))));
+                                match result {
+                                    Ok(result) => self.push(Value::array(Arc::new(Mutex::new(result)))),
+                                    Err(e) => self.stdlib_error(&e)?,
+                                }
                                 continue;
                             }
                             "copy" => {
@@ -3018,126 +3144,331 @@ impl VM {
                                 self.push(Value::bool(any_pass));
                                 continue;
                             }
-                            "sort" => {
-                                // arr.sort(fn?) - 排序（可选比较函数）
+                            "sort" | "sorted" => {
+                                // arr.sort(fn?) - 原地排序（可选比较函数）；arr.sorted(fn?) 返回排好序的新数组
                                 if arg_count > 1 {
-                                    return Err(self.runtime_error("sort() expects 0 or 1 argument"));
+                                    return Err(self.runtime_error(&format!("{}() expects 0 or 1 argument", method_name)));
                                 }
-                                let mut elements = arr.lock().clone();
-                                if arg_count == 1 {
+                                let comparator = if arg_count == 1 {
                                     let callback = self.stack[receiver_idx + 1].clone();
-                                    let func = callback.as_function().ok_or_else(|| {
-                                        self.runtime_error("sort() argument must be a function")
-                                    })?.clone();
-                                    self.stack.truncate(receiver_idx);
-                                    // 使用冒泡排序来支持自定义比较函数
-                                    let len = elements.len();
-                                    for i in 0..len {
-                                        for j in 0..len - 1 - i {
-                                            let cmp = self.call_closure(&func, &[elements[j].clone(), elements[j + 1].clone()])?;
-                                            if let Some(n) = cmp.as_int() {
-                                                if n > 0 {
-                                                    elements.swap(j, j + 1);
-                                                }
-                                            }
-                                        }
-                                    }
+                                    Some(callback.as_function().ok_or_else(|| {
+                                        self.runtime_error(&format!("{}() argument must be a function", method_name))
+                                    })?.clone())
                                 } else {
-                                    self.stack.truncate(receiver_idx);
-                                    // 默认排序：数字按大小，字符串按字典序
-                                    elements.sort_by(|a, b| {
-                                        if let (Some(x), Some(y)) = (a.as_int(), b.as_int()) {
-                                            x.cmp(&y)
-                                        } else if let (Some(x), Some(y)) = (a.as_float(), b.as_float()) {
-                                            x.partial_cmp(&y).unwrap_or(std::cmp::Ordering::Equal)
-                                        } else if let (Some(x), Some(y)) = (a.as_string(), b.as_string()) {
-                                            x.cmp(y)
-                                        } else {
-                                            std::cmp::Ordering::Equal
-                                        }
-                                    });
+                                    None
+                                };
+                                let mut elements = arr.lock().clone();
+                                self.stack.truncate(receiver_idx);
+                                self.sort_values(&mut elements, method_name, comparator.as_ref())?;
+                                if method_name == "sort" {
+                                    *arr.lock() = elements;
+                                    self.push(Value::null());
+                                } else {
+                                    self.push(Value::array(Arc::new(Mutex::new(elements))));
                                 }
-                                *arr.lock() = elements;
-                                self.push(Value::null());
                                 continue;
                             }
-                            _ => {
-                                return Err(self.runtime_error(&format!(
-                                    "Array has no method '{}'",
-                                    method_name
-                                )));
-                            }
-                        }
-                    }
-                    
-                    // 检查是否是字符串方法调用
-                    if let Some(s) = receiver.as_string() {
-                        let s = s.clone();
-                        match method_name.as_str() {
-                            "len" => {
-                                // str.len() - 返回字符串长度
-                                if arg_count != 0 {
-                                    return Err(self.runtime_error("len() expects 0 arguments"));
+                            "sortBy" | "sortedBy" => {
+                                // arr.sortBy(keyFn) - 按 keyFn 提取的键原地排序；arr.sortedBy(keyFn) 返回新数组
+                                if arg_count != 1 {
+                                    return Err(self.runtime_error(&format!("{}() expects 1 argument", method_name)));
                                 }
-                                let len = s.chars().count() as i128;
+                                let callback = self.stack[receiver_idx + 1].clone();
+                                let func = callback.as_function().ok_or_else(|| {
+                                    self.runtime_error(&format!("{}() argument must be a function", method_name))
+                                })?.clone();
+                                let elements = arr.lock().clone();
                                 self.stack.truncate(receiver_idx);
-                                self.push(Value::int(len));
+                                let elements = self.sort_values_by_key(elements, &func)?;
+                                if method_name == "sortBy" {
+                                    *arr.lock() = elements;
+                                    self.push(Value::null());
+                                } else {
+                                    self.push(Value::array(Arc::new(Mutex::new(elements))));
+                                }
                                 continue;
                             }
-                            "split" => {
-                                // str.split(delimiter) - 按分隔符分割字符串
+                            "binarySearch" | "sortedInsert" => {
+                                // arr.binarySearch(value) - 在按全序排好的数组中查找，找到时返回下标（重复时为第一个），否则返回 -(插入点 + 1)
+                                // arr.sortedInsert(value) - 插入到保持有序的位置（相等的元素之后），返回插入的下标
                                 if arg_count != 1 {
-                                    return Err(self.runtime_error("split() expects 1 argument"));
+                                    return Err(self.runtime_error(&format!("{}() expects 1 argument", method_name)));
                                 }
-                                let delimiter = if let Some(d) = self.stack[receiver_idx + 1].as_string() {
-                                    d.clone()
+                                let value = self.stack[receiver_idx + 1];
+                                let elements = arr.lock().clone();
+                                self.stack.truncate(receiver_idx);
+                                self.check_sorted(&elements, method_name, "element")?;
+                                let compare = |i: usize| elements[i].total_cmp(&value);
+                                if method_name == "binarySearch" {
+                                    let result = binary_search(elements.len(), compare).map_err(|e| self.runtime_error(&e))?;
+                                    self.push(Value::int(search_result(result)));
+                                    continue;
+                                }
+                                let index = upper_bound(elements.len(), compare).map_err(|e| self.runtime_error(&e))?;
+                                let mut elements = arr.lock();
+                                let reserved = super::alloc::check_elements::<Value>(elements.len() as u128 + 1);
+                                if reserved.is_ok() {
+                                    let index = index.min(elements.len());
+                                    elements.insert(index, value);
+                                }
+                                drop(elements);
+                                gc_write_barrier(&receiver);
+                                match reserved {
+                                    Ok(()) => self.push(Value::int(index as i128)),
+                                    Err(e) => self.stdlib_error(&e)?,
+                                }
+                                continue;
+                            }
+                            "binarySearchBy" => {
+                                // arr.binarySearchBy(keyFn, key) - 数组按 keyFn 的结果排好序时查找 key，每次探测调用一次 keyFn
+                                if arg_count != 2 {
+                                    return Err(self.runtime_error("binarySearchBy() expects 2 arguments"));
+                                }
+                                let callback = self.stack[receiver_idx + 1];
+                                let func = callback.as_function().ok_or_else(|| {
+                                    self.runtime_error("binarySearchBy() argument must be a function")
+                                })?.clone();
+                                let key = self.stack[receiver_idx + 2];
+                                let elements = arr.lock().clone();
+                                self.stack.truncate(receiver_idx);
+                                let result = if safe_vm() {
+                                    // 检查有序需要所有的键，之后直接在键上查找
+                                    let mut keys = Vec::with_capacity(elements.len());
+                                    for element in elements {
+                                        keys.push(self.call_closure(&func, &[element])?);
+                                    }
+                                    self.check_sorted(&keys, method_name, "key")?;
+                                    binary_search(keys.len(), |i| keys[i].total_cmp(&key)).map_err(|e| self.runtime_error(&e))?
                                 } else {
-                                    return Err(self.runtime_error("split() expects a string argument"));
+                                    binary_search(elements.len(), |i| {
+                                        let probe = self.call_closure(&func, &[elements[i]])?;
+                                        probe.total_cmp(&key).map_err(|e| self.runtime_error(&e))
+                                    })?
                                 };
-                                let parts: Vec<Value> = s.split(&delimiter)
-                                    .map(|part| Value::string(part.to_string()))
-                                    .collect();
-                                self.stack.truncate(receiver_idx);
-                                self.push(Value::array(Arc::new(Mutex::new(parts))));
+                                self.push(Value::int(search_result(result)));
                                 continue;
                             }
-                            "trim" => {
-                                // str.trim() - 去除首尾空白
+                            "isSorted" => {
+                                // arr.isSorted() - 元素是否按值的全序不递减
                                 if arg_count != 0 {
-                                    return Err(self.runtime_error("trim() expects 0 arguments"));
+                                    return Err(self.runtime_error("isSorted() expects 0 arguments"));
                                 }
-                                let trimmed = s.trim().to_string();
+                                let elements = arr.lock().clone();
+                                let unsorted = first_unsorted(&elements).map_err(|e| self.runtime_error(&e))?;
                                 self.stack.truncate(receiver_idx);
-                                self.push(Value::string(trimmed));
+                                self.push(Value::bool(unsorted.is_none()));
                                 continue;
                             }
-                            "trimStart" => {
-                                // str.trimStart() - 去除开头空白
-                                if arg_count != 0 {
-                                    return Err(self.runtime_error("trimStart() expects 0 arguments"));
+                            "insert" => {
+                                // arr.insert(index, value) - 在 index 之前插入，index 可以等于长度（追加到末尾）
+                                if arg_count != 2 {
+                                    return Err(self.runtime_error("insert() expects 2 arguments"));
                                 }
-                                let trimmed = s.trim_start().to_string();
+                                let value = self.stack[receiver_idx + 2];
+                                let mut elements = arr.lock();
+                                let index = resolve_index(&self.stack[receiver_idx + 1], elements.len(), IndexPolicy::Insert, "array")
+                                    .map_err(|e| self.runtime_error(&e))?;
+                                let reserved = super::alloc::check_elements::<Value>(elements.len() as u128 + 1);
+                                if reserved.is_ok() {
+                                    elements.insert(index, value);
+                                }
+                                drop(elements);
+                                gc_write_barrier(&receiver);
                                 self.stack.truncate(receiver_idx);
-                                self.push(Value::string(trimmed));
+                                match reserved {
+                                    Ok(()) => self.push(Value::null()),
+                                    Err(e) => self.stdlib_error(&e)?,
+                                }
                                 continue;
                             }
-                            "trimEnd" => {
-                                // str.trimEnd() - 去除结尾空白
+                            "removeAt" => {
+                                // arr.removeAt(index) - 移除并返回 index 处的元素
+                                if arg_count != 1 {
+                                    return Err(self.runtime_error("removeAt() expects 1 argument"));
+                                }
+                                let mut elements = arr.lock();
+                                let index = resolve_index(&self.stack[receiver_idx + 1], elements.len(), IndexPolicy::Element, "array")
+                                    .map_err(|e| self.runtime_error(&e))?;
+                                let removed = elements.remove(index);
+                                drop(elements);
+                                self.stack.truncate(receiver_idx);
+                                self.push(removed);
+                                continue;
+                            }
+                            "unique" => {
+                                // arr.unique() - 去掉重复的元素，保留第一次出现的顺序
                                 if arg_count != 0 {
-                                    return Err(self.runtime_error("trimEnd() expects 0 arguments"));
+                                    return Err(self.runtime_error("unique() expects 0 arguments"));
                                 }
-                                let trimmed = s.trim_end().to_string();
+                                let result = super::array::unique(&arr.lock());
                                 self.stack.truncate(receiver_idx);
-                                self.push(Value::string(trimmed));
+                                self.push(Value::array(Arc::new(Mutex::new(result))));
                                 continue;
                             }
-                            "replace" => {
-                                // str.replace(old, new) - 替换所有匹配项
-                                if arg_count != 2 {
-                                    return Err(self.runtime_error("replace() expects 2 arguments"));
+                            "flat" => {
+                                // arr.flat(depth?) - 展开嵌套的数组，默认一层
+                                if arg_count > 1 {
+                                    return Err(self.runtime_error("flat() expects 0 or 1 argument"));
                                 }
-                                let old_str = if let Some(o) = self.stack[receiver_idx + 1].as_string() {
-                                    o.clone()
+                                let depth = if arg_count == 1 {
+                                    match self.stack[receiver_idx + 1].as_int() {
+                                        Some(depth) if depth >= 0 => depth.min(usize::MAX as i128) as usize,
+                                        Some(depth) => return Err(self.runtime_error(&format!("flat() depth must be non-negative, got {}", depth))),
+                                        None => return Err(self.runtime_error("flat() depth must be an integer")),
+                                    }
+                                } else {
+                                    1
+                                };
+                                let elements = arr.lock().clone();
+                                self.stack.truncate(receiver_idx);
+                                match super::array::flat(&elements, depth) {
+                                    Ok(result) => self.push(Value::array(Arc::new(Mutex::new(result)))),
+                                    Err(e) => self.stdlib_error(&e)?,
+                                }
+                                continue;
+                            }
+                            "zip" => {
+                                // arr.zip(other) - 按位置配对为 [a, b]，长度取较短的一个
+                                if arg_count != 1 {
+                                    return Err(self.runtime_error("zip() expects 1 argument"));
+                                }
+                                let other = if let Some(other) = self.stack[receiver_idx + 1].as_array() {
+                                    other.lock().clone()
+                                } else {
+                                    return Err(self.runtime_error("zip() expects an array argument"));
+                                };
+                                let pairs: Vec<Value> = arr.lock().iter().zip(other)
+                                    .map(|(&a, b)| Value::array(Arc::new(Mutex::new(vec![a, b]))))
+                                    .collect();
+                                self.stack.truncate(receiver_idx);
+                                self.push(Value::array(Arc::new(Mutex::new(pairs))));
+                                continue;
+                            }
+                            "min" | "max" => {
+                                // arr.min() / arr.max() - 最小 / 最大的数字，空数组返回 null
+                                if arg_count != 0 {
+                                    return Err(self.runtime_error(&format!("{}() expects 0 arguments", method_name)));
+                                }
+                                let result = super::array::extreme(&arr.lock(), method_name, method_name == "max")
+                                    .map_err(|e| self.runtime_error(&e))?;
+                                self.stack.truncate(receiver_idx);
+                                self.push(result);
+                                continue;
+                            }
+                            "sum" => {
+                                // arr.sum() - 数字之和，有浮点数时结果为浮点数
+                                if arg_count != 0 {
+                                    return Err(self.runtime_error("sum() expects 0 arguments"));
+                                }
+                                let result = super::array::sum(&arr.lock()).map_err(|e| self.runtime_error(&e))?;
+                                self.stack.truncate(receiver_idx);
+                                self.push(result);
+                                continue;
+                            }
+                            "chunk" => {
+                                // arr.chunk(size) - 每 size 个元素一组
+                                if arg_count != 1 {
+                                    return Err(self.runtime_error("chunk() expects 1 argument"));
+                                }
+                                let size = match self.stack[receiver_idx + 1].as_int() {
+                                    Some(size) if size >= 1 => size.min(usize::MAX as i128) as usize,
+                                    Some(size) => return Err(self.runtime_error(&format!("chunk() size must be at least 1, got {}", size))),
+                                    None => return Err(self.runtime_error("chunk() size must be an integer")),
+                                };
+                                let result = super::array::chunk(&arr.lock(), size);
+                                self.stack.truncate(receiver_idx);
+                                self.push(Value::array(Arc::new(Mutex::new(result))));
+                                continue;
+                            }
+                            "fill" => {
+                                // arr.fill(value) - 把所有元素设为 value
+                                if arg_count != 1 {
+                                    return Err(self.runtime_error("fill() expects 1 argument"));
+                                }
+                                let value = self.stack[receiver_idx + 1];
+                                arr.lock().fill(value);
+                                gc_write_barrier(&receiver);
+                                self.stack.truncate(receiver_idx);
+                                self.push(Value::null());
+                                continue;
+                            }
+                            _ => {
+                                return Err(self.runtime_error(&format!(
+                                    "Array has no method '{}'",
+                                    method_name
+                                )));
+                            }
+                        }
+                    }
+                    
+                    // 检查是否是字符串方法调用
+                    if let Some(s) = receiver.as_string() {
+                        let s = s.clone();
+                        match method_name.as_str() {
+                            "len" => {
+                                // str.len() - 返回字符串长度
+                                if arg_count != 0 {
+                                    return Err(self.runtime_error("len() expects 0 arguments"));
+                                }
+                                let len = s.chars().count() as i128;
+                                self.stack.truncate(receiver_idx);
+                                self.push(Value::int(len));
+                                continue;
+                            }
+                            "split" => {
+                                // str.split(delimiter) - 按分隔符分割字符串
+                                if arg_count != 1 {
+                                    return Err(self.runtime_error("split() expects 1 argument"));
+                                }
+                                let delimiter = if let Some(d) = self.stack[receiver_idx + 1].as_string() {
+                                    d.clone()
+                                } else {
+                                    return Err(self.runtime_error("split() expects a string argument"));
+                                };
+                                let parts: Vec<Value> = s.split(&delimiter)
+                                    .map(|part| Value::string(part.to_string()))
+                                    .collect();
+                                self.stack.truncate(receiver_idx);
+                                self.push(Value::array(Arc::new(Mutex::new(parts))));
+                                continue;
+                            }
+                            "trim" => {
+                                // str.trim() - 去除首尾空白
+                                if arg_count != 0 {
+                                    return Err(self.runtime_error("trim() expects 0 arguments"));
+                                }
+                                let trimmed = s.trim().to_string();
+                                self.stack.truncate(receiver_idx);
+                                self.push(Value::string(trimmed));
+                                continue;
+                            }
+                            "trimStart" => {
+                                // str.trimStart() - 去除开头空白
+                                if arg_count != 0 {
+                                    return Err(self.runtime_error("trimStart() expects 0 arguments"));
+                                }
+                                let trimmed = s.trim_start().to_string();
+                                self.stack.truncate(receiver_idx);
+                                self.push(Value::string(trimmed));
+                                continue;
+                            }
+                            "trimEnd" => {
+                                // str.trimEnd() - 去除结尾空白
+                                if arg_count != 0 {
+                                    return Err(self.runtime_error("trimEnd() expects 0 arguments"));
+                                }
+                                let trimmed = s.trim_end().to_string();
+                                self.stack.truncate(receiver_idx);
+                                self.push(Value::string(trimmed));
+                                continue;
+                            }
+                            "replace" => {
+                                // str.replace(old, new) - 替换所有匹配项
+                                if arg_count != 2 {
+                                    return Err(self.runtime_error("replace() expects 2 arguments"));
+                                }
+                                let old_str = if let Some(o) = self.stack[receiver_idx + 1].as_string() {
+                                    o.clone()
                                 } else {
                                     return Err(self.runtime_error("replace() first argument must be string"));
                                 };
@@ -3241,14 +3572,9 @@ impl VM {
                                 if arg_count != 1 {
                                     return Err(self.runtime_error("charAt() expects 1 argument"));
                                 }
-                                let index = if let Some(i) = self.stack[receiver_idx + 1].as_int() {
-                                    i as usize
-                                } else {
-                                    return Err(self.runtime_error("charAt() expects an integer argument"));
-                                };
-                                let result = s.chars().nth(index)
-                                    .map(|c| Value::string(c.to_string()))
-                                    .unwrap_or(Value::null());
+                                let index = resolve_index(&self.stack[receiver_idx + 1], s.chars().count(), IndexPolicy::Element, "string")
+                                    .map_err(|e| self.runtime_error(&e))?;
+                                let result = Value::string(s.chars().nth(index).unwrap().to_string());
                                 self.stack.truncate(receiver_idx);
                                 self.push(result);
                                 continue;
@@ -3263,13 +3589,58 @@ impl VM {
                                 } else {
                                     return Err(self.runtime_error("indexOf() expects a string argument"));
                                 };
+                                // 返回字符位置，与 charAt()/substring() 的下标一致
                                 let result = s.find(&substr)
-                                    .map(|i| Value::int(i as i128))
+                                    .map(|i| Value::int(s[..i].chars().count() as i128))
                                     .unwrap_or(Value::int(-1));
                                 self.stack.truncate(receiver_idx);
                                 self.push(result);
                                 continue;
                             }
+                            "indexOfChar" => {
+                                // str.indexOfChar(c, from?) - 从字符位置 from 开始查找字符 c，from 为负数时从末尾倒数
+                                if !(1..=2).contains(&arg_count) {
+                                    return Err(self.runtime_error("indexOfChar() expects 1 or 2 arguments"));
+                                }
+                                // 字符字面量和 charAt() 的结果在运行时可能是单个字符的字符串
+                                let target = self.stack[receiver_idx + 1];
+                                let single = target.as_string().and_then(|t| {
+                                    let mut chars = t.chars();
+                                    chars.next().filter(|_| chars.next().is_none())
+                                });
+                                let Some(c) = target.as_char().or(single) else {
+                                    return Err(self.runtime_error(&format!(
+                                        "indexOfChar() expects a char or a one-character string, got {}", target.type_name()
+                                    )));
+                                };
+                                let from = if arg_count == 2 {
+                                    resolve_index(&self.stack[receiver_idx + 2], s.chars().count(), IndexPolicy::Bound, "string")
+                                        .map_err(|e| self.runtime_error(&e))?
+                                } else {
+                                    0
+                                };
+                                let result = s.chars().skip(from).position(|x| x == c)
+                                    .map(|i| (from + i) as i128)
+                                    .unwrap_or(-1);
+                                self.stack.truncate(receiver_idx);
+                                self.push(Value::int(result));
+                                continue;
+                            }
+                            "count" => {
+                                // str.count(substr) - 不重叠的子串出现次数
+                                if arg_count != 1 {
+                                    return Err(self.runtime_error("count() expects 1 argument"));
+                                }
+                                let substr = match self.stack[receiver_idx + 1].as_string() {
+                                    Some(sub) if !sub.is_empty() => sub.clone(),
+                                    Some(_) => return Err(self.runtime_error("count() substring must not be empty")),
+                                    None => return Err(self.runtime_error("count() expects a string argument")),
+                                };
+                                let result = s.matches(substr.as_str()).count() as i128;
+                                self.stack.truncate(receiver_idx);
+                                self.push(Value::int(result));
+                                continue;
+                            }
                             "lastIndexOf" => {
                                 // str.lastIndexOf(substr) - 查找最后一个子串位置
                                 if arg_count != 1 {
@@ -3280,8 +3651,9 @@ impl VM {
                                 } else {
                                     return Err(self.runtime_error("lastIndexOf() expects a string argument"));
                                 };
+                                // 返回字符位置，与 charAt()/substring() 的下标一致
                                 let result = s.rfind(&substr)
-                                    .map(|i| Value::int(i as i128))
+                                    .map(|i| Value::int(s[..i].chars().count() as i128))
                                     .unwrap_or(Value::int(-1));
                                 self.stack.truncate(receiver_idx);
                                 self.push(result);
@@ -3292,20 +3664,9 @@ impl VM {
                                 if arg_count < 1 || arg_count > 2 {
                                     return Err(self.runtime_error("substring() expects 1 or 2 arguments"));
                                 }
-                                let start = if let Some(i) = self.stack[receiver_idx + 1].as_int() {
-                                    i as usize
-                                } else {
-                                    return Err(self.runtime_error("substring() first argument must be integer"));
-                                };
-                                let end = if arg_count == 2 {
-                                    if let Some(i) = self.stack[receiver_idx + 2].as_int() {
-                                        i as usize
-                                    } else {
-                                        return Err(self.runtime_error("substring() second argument must be integer"));
-                                    }
-                                } else {
-                                    s.chars().count()
-                                };
+                                let end = (arg_count == 2).then(|| self.stack[receiver_idx + 2]);
+                                let (start, end) = resolve_range(&self.stack[receiver_idx + 1], end.as_ref(), s.chars().count(), "string")
+                                    .map_err(|e| self.runtime_error(&e))?;
                                 let result: String = s.chars().skip(start).take(end - start).collect();
                                 self.stack.truncate(receiver_idx);
                                 self.push(Value::string(result));
@@ -3317,13 +3678,15 @@ impl VM {
                                     return Err(self.runtime_error("repeat() expects 1 argument"));
                                 }
                                 let n = if let Some(i) = self.stack[receiver_idx + 1].as_int() {
-                                    i as usize
+                                    i
                                 } else {
                                     return Err(self.runtime_error("repeat() expects an integer argument"));
                                 };
-                                let result = s.repeat(n);
                                 self.stack.truncate(receiver_idx);
-                                self.push(Value::string(result));
+                                match super::alloc::repeat_str(&s, n) {
+                                    Ok(result) => self.push(Value::string(result)),
+                                    Err(e) => self.stdlib_error(&e)?,
+                                }
                                 continue;
                             }
                             "isEmpty" => {
@@ -3346,6 +3709,138 @@ impl VM {
                                 self.push(Value::string(result));
                                 continue;
                             }
+                            "padStart" | "padEnd" => {
+                                // str.padStart(width, fill?) / str.padEnd(width, fill?) - 用单个字符补足到 width 个字符，默认补空格
+                                if !(1..=2).contains(&arg_count) {
+                                    return Err(self.runtime_error(&format!("{}() expects 1 or 2 arguments", method_name)));
+                                }
+                                let width = self.stack[receiver_idx + 1].as_int().ok_or_else(|| {
+                                    self.runtime_error(&format!("{}() width must be an integer", method_name))
+                                })?;
+                                let fill = if arg_count == 2 {
+                                    let fill = self.stack[receiver_idx + 2];
+                                    let mut chars = fill.as_string().map(|f| f.chars());
+                                    match (fill.as_char(), chars.as_mut().and_then(|c| c.next().filter(|_| c.next().is_none()))) {
+                                        (Some(c), _) | (None, Some(c)) => c,
+                                        _ => return Err(self.runtime_error(&format!("{}() fill must be a single character", method_name))),
+                                    }
+                                } else {
+                                    ' '
+                                };
+                                self.stack.truncate(receiver_idx);
+                                match super::alloc::pad_str(&s, width, fill, method_name == "padStart") {
+                                    Ok(result) => self.push(Value::string(result)),
+                                    Err(e) => self.stdlib_error(&e)?,
+                                }
+                                continue;
+                            }
+                            "lines" => {
+                                // str.lines() - 按 \n 或 \r\n 分行，末尾的换行不产生空行
+                                if arg_count != 0 {
+                                    return Err(self.runtime_error("lines() expects 0 arguments"));
+                                }
+                                let lines: Vec<Value> = s.lines().map(|line| Value::string(line.to_string())).collect();
+                                self.stack.truncate(receiver_idx);
+                                self.push(Value::array(Arc::new(Mutex::new(lines))));
+                                continue;
+                            }
+                            "chars" => {
+                                // str.chars() - 每个字符一个字符串
+                                if arg_count != 0 {
+                                    return Err(self.runtime_error("chars() expects 0 arguments"));
+                                }
+                                let chars: Vec<Value> = s.chars().map(|c| Value::string(c.to_string())).collect();
+                                self.stack.truncate(receiver_idx);
+                                self.push(Value::array(Arc::new(Mutex::new(chars))));
+                                continue;
+                            }
+                            "splitN" => {
+                                // str.splitN(delimiter, n) - 最多分成 n 段，最后一段包含剩余部分
+                                if arg_count != 2 {
+                                    return Err(self.runtime_error("splitN() expects 2 arguments"));
+                                }
+                                let delimiter = if let Some(d) = self.stack[receiver_idx + 1].as_string() {
+                                    d.clone()
+                                } else {
+                                    return Err(self.runtime_error("splitN() first argument must be string"));
+                                };
+                                let n = match self.stack[receiver_idx + 2].as_int() {
+                                    Some(n) if n >= 1 => n.min(usize::MAX as i128) as usize,
+                                    Some(n) => return Err(self.runtime_error(&format!("splitN() count must be at least 1, got {}", n))),
+                                    None => return Err(self.runtime_error("splitN() second argument must be an integer")),
+                                };
+                                let parts: Vec<Value> = s.splitn(n, &delimiter)
+                                    .map(|part| Value::string(part.to_string()))
+                                    .collect();
+                                self.stack.truncate(receiver_idx);
+                                self.push(Value::array(Arc::new(Mutex::new(parts))));
+                                continue;
+                            }
+                            "toInt" => {
+                                // str.toInt(radix?) - 按 std.convert 的规则转换，失败时返回 null
+                                if arg_count > 1 {
+                                    return Err(self.runtime_error("toInt() expects 0 or 1 argument"));
+                                }
+                                let parsed = if arg_count == 1 {
+                                    let radix = match self.stack[receiver_idx + 1].as_int() {
+                                        Some(radix @ 2..=36) => radix as u32,
+                                        Some(radix) => return Err(self.runtime_error(&format!("toInt() radix must be between 2 and 36, got {}", radix))),
+                                        None => return Err(self.runtime_error("toInt() radix must be an integer")),
+                                    };
+                                    convert::parse_int_radix(&s, radix)
+                                } else {
+                                    convert::parse_int(&s)
+                                };
+                                self.stack.truncate(receiver_idx);
+                                self.push(parsed.map(|n| Value::int(n as i128)).unwrap_or_default());
+                                continue;
+                            }
+                            "toFloat" => {
+                                // str.toFloat() - 按 std.convert 的规则转换，失败时返回 null
+                                if arg_count != 0 {
+                                    return Err(self.runtime_error("toFloat() expects 0 arguments"));
+                                }
+                                let result = convert::parse_float(&s).map(Value::float).unwrap_or_default();
+                                self.stack.truncate(receiver_idx);
+                                self.push(result);
+                                continue;
+                            }
+                            "compareTo" => {
+                                // str.compareTo(other) - 按码点逐个比较，返回 -1、0 或 1
+                                if arg_count != 1 {
+                                    return Err(self.runtime_error("compareTo() expects 1 argument"));
+                                }
+                                let ordering = if let Some(other) = self.stack[receiver_idx + 1].as_string() {
+                                    s.as_str().cmp(other.as_str())
+                                } else {
+                                    return Err(self.runtime_error("compareTo() expects a string argument"));
+                                };
+                                self.stack.truncate(receiver_idx);
+                                self.push(Value::int(ordering as i128));
+                                continue;
+                            }
+                            "codePointAt" => {
+                                // str.codePointAt(index) - 指定位置字符的 Unicode 码点，下标规则与 charAt() 相同
+                                if arg_count != 1 {
+                                    return Err(self.runtime_error("codePointAt() expects 1 argument"));
+                                }
+                                let index = resolve_index(&self.stack[receiver_idx + 1], s.chars().count(), IndexPolicy::Element, "string")
+                                    .map_err(|e| self.runtime_error(&e))?;
+                                let result = Value::int(s.chars().nth(index).unwrap() as i128);
+                                self.stack.truncate(receiver_idx);
+                                self.push(result);
+                                continue;
+                            }
+                            "format" => {
+                                // str.format(args...) - {} 依次取参数，{N} 取第 N 个参数
+                                let args: Vec<Value> = self.stack[receiver_idx + 1..].to_vec();
+                                self.stack.truncate(receiver_idx);
+                                match super::format::format_template(&s, &args) {
+                                    Ok(result) => self.push(Value::string(result)),
+                                    Err(e) => self.stdlib_error(&e)?,
+                                }
+                                continue;
+                            }
                             _ => {
                                 return Err(self.runtime_error(&format!(
                                     "String has no method '{}'",
@@ -3355,6 +3850,32 @@ impl VM {
                         }
                     }
                     
+                    // 浮点数的格式化方法（声明为浮点数的变量可能保存着整数，一起处理）
+                    if matches!(method_name.as_str(), "toFixed" | "toPrecision") {
+                        if let Some(x) = receiver.as_float().or_else(|| receiver.as_int().map(|n| n as f64)) {
+                            if arg_count != 1 {
+                                return Err(self.runtime_error(&format!("{}() expects 1 argument", method_name)));
+                            }
+                            let min = if method_name == "toFixed" { 0 } else { 1 };
+                            let digits = match self.stack[receiver_idx + 1].as_int() {
+                                Some(n) if (min..=number::MAX_DIGITS as i128).contains(&n) => n as usize,
+                                Some(n) => return Err(self.runtime_error(&format!(
+                                    "{}() digits must be between {} and {}, got {}",
+                                    method_name, min, number::MAX_DIGITS, n
+                                ))),
+                                None => return Err(self.runtime_error(&format!("{}() digits must be an integer", method_name))),
+                            };
+                            let result = if method_name == "toFixed" {
+                                number::to_fixed(x, digits)
+                            } else {
+                                number::to_precision(x, digits)
+                            };
+                            self.stack.truncate(receiver_idx);
+                            self.push(Value::string(result));
+                            continue;
+                        }
+                    }
+                    
                     // 检查是否是 Map 方法调用
                     if let Some(map) = receiver.as_map() {
                         match method_name.as_str() {
@@ -3545,13 +4066,19 @@ impl VM {
                                 if arg_count != 0 {
                                     return Err(self.runtime_error("toArray() expects 0 arguments"));
                                 }
-                                let arr: Vec<Value> = if inclusive {
-                                    (start..=end).map(|i| Value::int(i as i128)).collect()
-                                } else {
-                                    (start..end).map(|i| Value::int(i as i128)).collect()
-                                };
+                                let count = (end as i128 - start as i128 + inclusive as i128).max(0);
                                 self.stack.truncate(receiver_idx);
-                                self.push(Value::array(Arc::new(Mutex::new(arr))));
+                                match super::alloc::vec_with_capacity(count as u128) {
+                                    Ok(mut arr) => {
+                                        if inclusive {
+                                            arr.extend((start..=end).map(|i| Value::int(i as i128)));
+                                        } else {
+                                            arr.extend((start..end).map(|i| Value::int(i as i128)));
+                                        }
+                                        self.push(Value::array(Arc::new(Mutex::new(arr))));
+                                    }
+                                    Err(e) => self.stdlib_error(&e)?,
+                                }
                                 continue;
                             }
                             "isInclusive" => {
@@ -3610,11 +4137,11 @@ impl VM {
                         }
                     }
                     
-                    // 获取类型名和方法
-                    let type_name = if let Some(s) = receiver.as_struct() {
-                        s.lock().type_name.clone()
+                    // 按实例的类型名查找方法（加锁期间直接读取类型名，不复制）
+                    let func_index = if let Some(s) = receiver.as_struct() {
+                        self.instance_method_index(&s.lock().type_name, method_name)?
                     } else if let Some(c) = receiver.as_class() {
-                        c.lock().class_name.clone()
+                        self.instance_method_index(&c.lock().class_name, method_name)?
                     } else {
                         return Err(self.runtime_error(&format!(
                             "Cannot call method '{}' on {}",
@@ -3623,19 +4150,9 @@ impl VM {
                         )));
                     };
                     
-                    // 查找方法
-                    let func_index = match self.chunk.get_method(&type_name, &method_name) {
-                        Some(idx) => idx as usize,
-                        None => return Err(self.runtime_error(&format!(
-                            "Type '{}' has no method '{}'",
-                            type_name, method_name
-                        ))),
-                    };
-                    
                     // 获取函数对象
-                    let func = if let Some(f) = self.chunk.constants[func_index].as_function() {
-                        f.clone()
-                    } else {
+                    let func_value = self.chunk.constants[func_index];
+                    let Some(func) = func_value.as_function() else {
                         return Err(self.runtime_error("Method is not a function"));
                     };
                     
@@ -3664,11 +4181,7 @@ impl VM {
                     let missing_count = fixed_params.saturating_sub(actual_args);
                     if missing_count > 0 && !func.defaults.is_empty() {
                         let defaults_start = func.defaults.len().saturating_sub(missing_count);
-                        for i in 0..missing_count {
-                            if defaults_start + i < func.defaults.len() {
-                                self.push(func.defaults[defaults_start + i].clone());
-                            }
-                        }
+                        self.stack.extend_from_slice(&func.defaults[defaults_start..]);
                     }
                     
                     // 检查调用深度
@@ -3681,7 +4194,7 @@ impl VM {
                     // 创建调用帧：base_slot 指向 receiver 位置
                     let frame = CallFrame {
                         return_ip: self.ip as u32,
-                        base_slot: receiver_idx as u16, // receiver 作为第一个局部变量 (this)
+                        base_slot: receiver_idx as u32, // receiver 作为第一个局部变量 (this)
                         is_method_call: true, // 实例方法调用
                     };
                     self.frames.push(frame);
@@ -3695,16 +4208,20 @@ impl VM {
                     let class_name_index = self.read_u16() as usize;
                     let arg_count = self.read_byte() as usize;
                     
-                    // 获取类名
-                    let class_name = if let Some(s) = self.chunk.constants[class_name_index].as_string() {
-                        s.clone()
-                    } else {
+                    // 获取类名（常量是 Copy 的句柄，借用它而不是复制字符串）
+                    let class_name_value = self.chunk.constants[class_name_index];
+                    let Some(class_name) = class_name_value.as_string() else {
                         return Err(self.runtime_error("Invalid class name"));
                     };
                     
-                    // 检查是否是标准库类（支持简短名称和完整名称）
-                    let registry = get_stdlib_registry();
-                    if let Some(full_class_name) = registry.resolve_class_name(&class_name) {
+                    // 检查是否是标准库类（支持简短名称和完整名称，同名的用户类优先）
+                    let registry = self.registry.clone();
+                    let stdlib_class = if self.chunk.get_type(class_name).is_none() {
+                        registry.resolve_class_name(class_name)
+                    } else {
+                        None
+                    };
+                    if let Some(full_class_name) = stdlib_class {
                         // 是标准库类，从栈中获取参数
                         let args_start = self.stack.len() - arg_count;
                         let args = self.stack[args_start..].to_vec();
@@ -3718,18 +4235,17 @@ impl VM {
                                 continue;
                             }
                             Err(e) => {
-                                return Err(self.runtime_error(&e));
+                                self.stdlib_error(&e)?;                                continue;
                             }
                         }
                     }
                     
                     // 不是标准库类，按普通类处理
-                    // 获取类型信息
-                    let type_info = match self.chunk.get_type(&class_name) {
-                        Some(t) => t.clone(),
-                        None => return Err(self.runtime_error(&format!(
+                    // 获取类型信息：直接借用字节码块中的表，下面只通过字段访问修改栈和调用帧
+                    let Some(type_info) = self.chunk.get_type(class_name) else {
+                        return Err(self.runtime_error(&format!(
                             "Undefined class: {}", class_name
-                        ))),
+                        )));
                     };
                     
                     // 检查是否是抽象类
@@ -3739,9 +4255,9 @@ impl VM {
                         )));
                     }
                     
-                    // 创建实例，初始化字段为 null
+                    // 创建实例，包括从父类继承的字段，都初始化为 null（字段初始化式在 init 中执行）
                     let mut fields = std::collections::HashMap::new();
-                    for field_name in &type_info.fields {
+                    for field_name in self.chunk.instance_fields(class_name) {
                         fields.insert(field_name.clone(), Value::null());
                     }
                     
@@ -3754,9 +4270,7 @@ impl VM {
                     
                     // 查找 init 构造函数
                     if let Some(init_index) = type_info.methods.get("init") {
-                        let init_func = if let Some(f) = self.chunk.constants[*init_index as usize].as_function() {
-                            f.clone()
-                        } else {
+                        let Some(init_func) = self.chunk.constants[*init_index as usize].as_function() else {
                             return Err(self.runtime_error("init is not a function"));
                         };
                         
@@ -3790,11 +4304,7 @@ impl VM {
                         let missing_count = fixed_params.saturating_sub(actual_args);
                         if missing_count > 0 && !init_func.defaults.is_empty() {
                             let defaults_start = init_func.defaults.len().saturating_sub(missing_count);
-                            for i in 0..missing_count {
-                                if defaults_start + i < init_func.defaults.len() {
-                                    self.push(init_func.defaults[defaults_start + i].clone());
-                                }
-                            }
+                            self.stack.extend_from_slice(&init_func.defaults[defaults_start..]);
                         }
                         
                         // 检查调用深度
@@ -3805,10 +4315,11 @@ impl VM {
                         // 创建调用帧
                         let frame = CallFrame {
                             return_ip: self.ip as u32,
-                            base_slot: insert_pos as u16,
+                            base_slot: insert_pos as u32,
                             is_method_call: true, // init 方法调用
                         };
                         self.frames.push(frame);
+                        self.current_base = insert_pos;
                         
                         // 跳转到 init 方法
                         self.ip = init_func.chunk_index;
@@ -3826,121 +4337,54 @@ impl VM {
                     let class_name_index = self.read_u16() as usize;
                     let field_name_index = self.read_u16() as usize;
                     
-                    let class_name = if let Some(s) = self.chunk.constants[class_name_index].as_string() {
-                        s.clone()
-                    } else {
+                    let (class_name_value, field_name_value) =
+                        (self.chunk.constants[class_name_index], self.chunk.constants[field_name_index]);
+                    let Some(class_name) = class_name_value.as_string() else {
                         return Err(self.runtime_error("Invalid class name"));
                     };
-                    
-                    let field_name = if let Some(s) = self.chunk.constants[field_name_index].as_string() {
-                        s.clone()
-                    } else {
+                    let Some(field_name) = field_name_value.as_string() else {
                         return Err(self.runtime_error("Invalid field name"));
                     };
                     
                     // 检查是否是枚举变体访问
-                    if let Some(enum_info) = self.chunk.get_enum(&class_name).cloned() {
+                    if let Some(enum_info) = self.chunk.get_enum(class_name) {
                         // 查找变体
-                        let variant = enum_info.variants.iter().find(|v| v.name == field_name);
-                        if let Some(variant) = variant {
-                            // 创建枚举实例
-                            let value = if let Some(value_idx) = variant.value_index {
-                                Some(self.chunk.constants[value_idx as usize].clone())
-                            } else {
-                                None
-                            };
-                            let enum_val = super::value::EnumVariantValue {
-                                enum_name: class_name.clone(),
-                                variant_name: variant.name.clone(),
-                                value,
-                                associated_data: std::collections::HashMap::new(),
-                            };
-                            self.push(Value::enum_val(Box::new(enum_val)));
-                            continue;
-                        } else {
+                        let Some(variant) = enum_info.variants.iter().find(|v| v.name == *field_name) else {
                             return Err(self.runtime_error(&format!(
                                 "Enum '{}' has no variant '{}'", class_name, field_name
                             )));
-                        }
+                        };
+                        // 创建枚举实例
+                        let enum_val = super::value::EnumVariantValue {
+                            enum_name: class_name.clone(),
+                            variant_name: variant.name.clone(),
+                            value: variant.value_index.map(|value_idx| self.chunk.constants[value_idx as usize]),
+                            associated_data: std::collections::HashMap::new(),
+                        };
+                        self.stack.push(Value::enum_val(Box::new(enum_val)));
+                        continue;
                     }
                     
                     // 构造缓存键
                     let cache_key = format!("{}::{}", class_name, field_name);
                     
-                    // 检查缓存
-                    if let Some(value) = self.static_fields.get(&cache_key) {
-                        self.push(value.clone());
+                    // 已经初始化或赋值过
+                    let current = self.static_fields.get(&cache_key);
+                    if let Some(value) = current {
+                        self.push(value);
                     } else {
                         // 第一次访问，需要执行初始化函数
-                        if let Some(type_info) = self.chunk.get_type(&class_name) {
-                            if let Some(init_func_index) = type_info.static_fields.get(&field_name) {
+                        if let Some(type_info) = self.chunk.get_type(class_name) {
+                            if let Some(init_func_index) = type_info.static_fields.get(field_name) {
                                 let init_func_index = *init_func_index as usize;
                                 // 获取初始化函数
-                                if let Some(func) = self.chunk.constants[init_func_index].as_function() {
-                                    // 保存当前状态
-                                    let saved_ip = self.ip;
-                                    let saved_base = self.current_base;
-                                    let saved_stack_len = self.stack.len();
-                                    
-                                    // 执行初始化函数
-                                    self.ip = func.chunk_index;
-                                    self.current_base = self.stack.len();
-                                    
-                                    // 创建一个临时调用帧
-                                    let frame = CallFrame {
-                                        return_ip: 0, // 不会使用
-                                        base_slot: self.current_base as u16,
-                                        is_method_call: false,
-                                    };
-                                    self.frames.push(frame);
-                                    
-                                    // 执行直到返回
-                                    let mut result_value: Option<Value> = None;
-                                    loop {
-                                        let op_byte = self.read_byte();
-                                        let op = OpCode::from(op_byte);
-                                        
-                                        match op {
-                                            OpCode::Return => {
-                                                let result = self.pop()?;
-                                                self.frames.pop();
-                                                
-                                                // 缓存结果
-                                                self.static_fields.insert(cache_key.clone(), result.clone());
-                                                result_value = Some(result);
-                                                
-                                                // 恢复状态
-                                                self.ip = saved_ip;
-                                                self.current_base = saved_base;
-                                                self.stack.truncate(saved_stack_len);
-                                                break;
-                                            }
-                                            OpCode::Const => {
-                                                let idx = self.read_u16() as usize;
-                                                let value = self.chunk.constants[idx].clone();
-                                                self.push(value);
-                                            }
-                                            OpCode::ConstInt8 => {
-                                                let value = self.read_byte() as i8 as i128;
-                                                self.push(Value::int(value));
-                                            }
-                                            _ => {
-                                                // 其他指令可能需要处理
-                                                return Err(self.runtime_error(&format!(
-                                                    "Unsupported opcode in static initializer: {:?}", op
-                                                )));
-                                            }
-                                        }
-                                    }
-                                    
-                                    // 在恢复栈后推送结果
-                                    if let Some(result) = result_value {
-                                        self.push(result);
-                                    }
+                                if let Some(func) = self.chunk.constants[init_func_index].as_function().cloned() {
+                                    let value = self.run_static_initializer(cache_key, &func)?;
+                                    self.push(value);
                                 } else {
                                     // 不是函数，直接使用常量值
-                                    let value = self.chunk.constants[init_func_index].clone();
-                                    self.static_fields.insert(cache_key, value.clone());
+                                    let value = self.chunk.constants[init_func_index];
+                                    let value = self.static_fields.get_or_insert(cache_key, value);
                                     self.push(value);
                                 }
                             } else {
@@ -3957,9 +4401,18 @@ impl VM {
                 }
                 
                 OpCode::SetStatic => {
-                    let _class_name_index = self.read_u16();
-                    let _field_name_index = self.read_u16();
-                    // 静态字段设置 - 简化实现
+                    // 栈: [..., value] -> [..., value]，之后的读取不再运行初始化函数
+                    let class_name_index = self.read_u16() as usize;
+                    let field_name_index = self.read_u16() as usize;
+                    let (Some(class_name), Some(field_name)) = (
+                        self.chunk.constants[class_name_index].as_string(),
+                        self.chunk.constants[field_name_index].as_string(),
+                    ) else {
+                        return Err(self.runtime_error("Invalid static field name"));
+                    };
+                    let cache_key = format!("{}::{}", class_name, field_name);
+                    let value = *self.peek()?;
+                    self.static_fields.set(cache_key, value);
                 }
                 
                 OpCode::InvokeStatic => {
@@ -3967,26 +4420,23 @@ impl VM {
                     let method_name_index = self.read_u16() as usize;
                     let arg_count = self.read_byte() as usize;
                     
-                    let class_name = if let Some(s) = self.chunk.constants[class_name_index].as_string() {
-                        s.clone()
-                    } else {
+                    let (class_name_value, method_name_value) =
+                        (self.chunk.constants[class_name_index], self.chunk.constants[method_name_index]);
+                    let Some(class_name) = class_name_value.as_string() else {
                         return Err(self.runtime_error("Invalid class name"));
                     };
-                    
-                    let method_name = if let Some(s) = self.chunk.constants[method_name_index].as_string() {
-                        s.clone()
-                    } else {
+                    let Some(method_name) = method_name_value.as_string() else {
                         return Err(self.runtime_error("Invalid method name"));
                     };
                     
                     // 检查是否是枚举的内置方法
-                    if let Some(enum_info) = self.chunk.get_enum(&class_name).cloned() {
+                    if let Some(enum_info) = self.chunk.get_enum(class_name) {
                         if method_name == "fromValue" {
                             // Enum.fromValue(value) - 根据值查找枚举变体
                             if arg_count != 1 {
                                 return Err(self.runtime_error("fromValue() expects exactly 1 argument"));
                             }
-                            let search_value = self.pop()?;
+                            let search_value = self.stack.pop().unwrap_or_default();
                             
                             // 遍历所有变体查找匹配的值
                             let mut found = None;
@@ -4033,9 +4483,7 @@ impl VM {
                             }
                             
                             // 弹出可能的参数（虽然 values() 不需要参数）
-                            for _ in 0..arg_count {
-                                self.pop()?;
-                            }
+                            self.stack.truncate(self.stack.len() - arg_count);
                             
                             self.push(Value::array(Arc::new(Mutex::new(values))));
                             continue;
@@ -4043,7 +4491,7 @@ impl VM {
                     }
                     
                     // 查找静态方法
-                    let func_index = match self.chunk.get_static_method(&class_name, &method_name) {
+                    let func_index = match self.chunk.get_static_method(class_name, method_name) {
                         Some(idx) => idx as usize,
                         None => return Err(self.runtime_error(&format!(
                             "Class '{}' has no static method '{}'",
@@ -4051,9 +4499,8 @@ impl VM {
                         ))),
                     };
                     
-                    let func = if let Some(f) = self.chunk.constants[func_index].as_function() {
-                        f.clone()
-                    } else {
+                    let func_value = self.chunk.constants[func_index];
+                    let Some(func) = func_value.as_function() else {
                         return Err(self.runtime_error("Static method is not a function"));
                     };
                     
@@ -4073,11 +4520,7 @@ impl VM {
                     let missing = func.arity.saturating_sub(arg_count);
                     if missing > 0 && !func.defaults.is_empty() {
                         let start = func.defaults.len().saturating_sub(missing);
-                        for i in 0..missing {
-                            if start + i < func.defaults.len() {
-                                self.push(func.defaults[start + i].clone());
-                            }
-                        }
+                        self.stack.extend_from_slice(&func.defaults[start..]);
                     }
                     
                     if self.frames.len() >= MAX_FRAMES {
@@ -4087,7 +4530,7 @@ impl VM {
                     // 静态方法不需要在栈上插入函数值，直接使用参数位置
                     let frame = CallFrame {
                         return_ip: self.ip as u32,
-                        base_slot: base as u16,
+                        base_slot: base as u32,
                         is_method_call: true, // 没有函数值在栈上，类似方法调用
                     };
                     self.frames.push(frame);
@@ -4096,53 +4539,57 @@ impl VM {
                 }
                 
                 OpCode::InvokeSuper => {
+                    let parent_index = self.read_u16() as usize;
                     let method_name_index = self.read_u16() as usize;
                     let arg_count = self.read_byte() as usize;
                     
-                    let method_name = if let Some(s) = self.chunk.constants[method_name_index].as_string() {
-                        s.clone()
-                    } else {
-                        return Err(self.runtime_error("Invalid method name"));
+                    let (Some(parent_name), Some(method_name)) = (
+                        self.chunk.constants[parent_index].as_string(),
+                        self.chunk.constants[method_name_index].as_string(),
+                    ) else {
+                        return Err(self.runtime_error("Invalid super call"));
                     };
                     
-                    // 获取 this（在参数下方）
+                    // this 在参数下方
                     let receiver_idx = self.stack.len() - arg_count - 1;
-                    let receiver = self.stack[receiver_idx].clone();
-                    
-                    // 获取父类名
-                    let parent_class = if let Some(c) = receiver.as_class() {
-                        c.lock().parent_class.clone()
-                    } else {
+                    if self.stack[receiver_idx].as_class().is_none() {
                         return Err(self.runtime_error("super can only be used in a class method"));
-                    };
-                    
-                    let parent_name = match parent_class {
-                        Some(name) => name,
-                        None => return Err(self.runtime_error("Class has no parent")),
-                    };
+                    }
                     
-                    // 查找父类方法
-                    let func_index = match self.chunk.get_method(&parent_name, &method_name) {
-                        Some(idx) => idx as usize,
-                        None => return Err(self.runtime_error(&format!(
+                    // 从编译期确定的父类开始查找：多层继承中，中间类的 super 指向它自己的父类
+                    let Some(func_index) = self.chunk.get_method(parent_name, method_name) else {
+                        return Err(self.runtime_error(&format!(
                             "Parent class '{}' has no method '{}'",
                             parent_name, method_name
-                        ))),
+                        )));
                     };
-                    
-                    let func = if let Some(f) = self.chunk.constants[func_index].as_function() {
-                        f.clone()
-                    } else {
+                    let Some(func) = self.chunk.constants[func_index as usize].as_function() else {
                         return Err(self.runtime_error("Method is not a function"));
                     };
                     
+                    // 参数数量和默认参数与普通方法调用相同（+1 是 this）
+                    let actual_args = arg_count + 1;
+                    if actual_args < func.required_params || (!func.has_variadic && actual_args > func.arity) {
+                        let msg = format!(
+                            "Method '{}' expected {} to {} arguments but got {}",
+                            method_name, func.required_params - 1, func.arity - 1, arg_count
+                        );
+                        return Err(self.runtime_error(&msg));
+                    }
+                    let fixed_params = if func.has_variadic { func.arity - 1 } else { func.arity };
+                    let missing_count = fixed_params.saturating_sub(actual_args);
+                    if missing_count > 0 && !func.defaults.is_empty() {
+                        let defaults_start = func.defaults.len().saturating_sub(missing_count);
+                        self.stack.extend_from_slice(&func.defaults[defaults_start..]);
+                    }
+                    
                     if self.frames.len() >= MAX_FRAMES {
                         return Err(self.runtime_error("Stack overflow"));
                     }
                     
                     let frame = CallFrame {
                         return_ip: self.ip as u32,
-                        base_slot: receiver_idx as u16,
+                        base_slot: receiver_idx as u32,
                         is_method_call: true, // super 方法调用
                     };
                     self.frames.push(frame);
@@ -4155,6 +4602,17 @@ impl VM {
                     self.push(value);
                 }
                 
+                OpCode::Dup2 => {
+                    let len = self.stack.len();
+                    if len < 2 {
+                        let msg = format_message(messages::ERR_RUNTIME_STACK_UNDERFLOW, self.locale, &[]);
+                        return Err(self.runtime_error(&msg));
+                    }
+                    let (a, b) = (self.stack[len - 2], self.stack[len - 1]);
+                    self.push(a);
+                    self.push(b);
+                }
+                
                 OpCode::SetupTry => {
                     // 读取 catch 块的偏移量
                     let catch_offset = self.read_u16() as i16;
@@ -4163,12 +4621,42 @@ impl VM {
                     // 暂时存储在一个简单的字段中
                     let catch_ip = (self.ip as i32 + catch_offset as i32) as usize;
                     self.exception_handlers.push(ExceptionHandler {
+                        kind: HandlerKind::Catch,
                         catch_ip,
                         stack_depth: self.stack.len(),
                         frame_depth: self.frames.len(),
                     });
                 }
                 
+                OpCode::SetupRecover => {
+                    let handler_offset = self.read_u16() as i16;
+                    self.exception_handlers.push(ExceptionHandler {
+                        kind: HandlerKind::Panic,
+                        catch_ip: (self.ip as i32 + handler_offset as i32) as usize,
+                        stack_depth: self.stack.len(),
+                        frame_depth: self.frames.len(),
+                    });
+                }
+                
+                OpCode::SetupFinally => {
+                    let handler_offset = self.read_u16() as i16;
+                    self.exception_handlers.push(ExceptionHandler {
+                        kind: HandlerKind::Finally,
+                        catch_ip: (self.ip as i32 + handler_offset as i32) as usize,
+                        stack_depth: self.stack.len(),
+                        frame_depth: self.frames.len(),
+                    });
+                }
+                
+                OpCode::PopTry => {
+                    self.exception_handlers.pop();
+                }
+                
+                OpCode::ResumeExit => {
+                    let code = self.pop()?.as_int().unwrap_or(0) as i32;
+                    return Err(self.exit_error(code));
+                }
+                
                 OpCode::Throw => {
                     use crate::stdlib::exception::THROWABLE_TYPES;
                     
@@ -4195,22 +4683,7 @@ impl VM {
                         ));
                     }
                     
-                    // 查找最近的异常处理器
-                    if let Some(handler) = self.exception_handlers.pop() {
-                        // 恢复栈到处理器设置时的深度
-                        self.stack.truncate(handler.stack_depth);
-                        // 恢复调用帧
-                        while self.frames.len() > handler.frame_depth {
-                            self.frames.pop();
-                        }
-                        // 压入异常值（供 catch 块使用）
-                        self.push(exception);
-                        // 跳转到 catch 块
-                        self.ip = handler.catch_ip;
-                    } else {
-                        // 没有异常处理器，返回错误
-                        return Err(self.runtime_error(&format!("Uncaught exception: {}", exception)));
-                    }
+                    self.throw_exception(exception)?;
                 }
                 
                 OpCode::Halt => {
@@ -4236,12 +4709,17 @@ impl VM {
                     if let Some(func) = callee.as_function() {
                         let chunk = self.chunk.clone();
                         let func = func.clone();
+                        let inherited = self.inherited();
+                        // 参数交给协程线程使用
+                        args.iter().for_each(gc_escape);
+                        func.captures.iter().for_each(gc_escape);
                         
                         // 简化实现：使用标准线程执行协程
                         // 注意：这是一个临时的简化实现，后续会改为真正的协程调度
                         std::thread::spawn(move || {
                             // 创建协程 VM（同步执行）
-                            let mut coroutine_vm = VM::new_sync(chunk, Locale::En);
+                            let output = inherited.output.clone();
+                            let mut coroutine_vm = VM::inheriting(chunk, Locale::En, inherited);
                             
                             // 压入函数值（占位）
                             coroutine_vm.push_fast(Value::null());
@@ -4251,9 +4729,12 @@ impl VM {
                                 coroutine_vm.push_fast(arg.clone());
                             }
                             
-                            // 创建调用帧，return_ip 设为一个特殊值表示协程退出
+                            coroutine_vm.push_captures(&func);
+                            
+                            // 函数体在哨兵帧中执行，返回到哨兵帧时协程结束；
+                            // 函数体中的调用返回后按哨兵帧恢复栈基址
                             coroutine_vm.frames.push(CallFrame {
-                                return_ip: u32::MAX, // 特殊标记：协程返回时退出
+                                return_ip: u32::MAX,
                                 base_slot: 1,
                                 is_method_call: false,
                             });
@@ -4263,8 +4744,11 @@ impl VM {
                             coroutine_vm.ip = func.chunk_index;
                             
                             // 同步执行协程
-                            if let Err(e) = coroutine_vm.run_coroutine() {
-                                eprintln!("Coroutine error at line {}: {}", e.line, e.message);
+                            match coroutine_vm.run_coroutine() {
+                                // 协程中的 Os.exit 结束整个程序：主虚拟机在其他线程上，交给它展开
+                                Err(RuntimeError { exit_code: Some(code), .. }) => coroutine_vm.pending_exit.request(code),
+                                Err(e) => output.eprint(&format!("Coroutine error at line {}: {}\n", e.line, e.message)),
+                                Ok(()) => {}
                             }
                         });
                     } else {
@@ -4277,17 +4761,19 @@ impl VM {
                 
                 OpCode::ChannelNew => {
                     use super::value::ChannelState;
-                    
-                    // 创建 Channel（容量为 0 表示无缓冲）
-                    let capacity = self.read_u16() as usize;
-                    
-                    let (sender, receiver) = if capacity == 0 {
-                        // 无缓冲 channel（rendezvous）
-                        crossbeam_channel::bounded(0)
-                    } else {
-                        // 有缓冲 channel
-                        crossbeam_channel::bounded(capacity)
+                    use crate::stdlib::exception::new_exception;
+                    
+                    // 容量为 0 表示无缓冲（rendezvous）通道，否则发送方在缓冲区满之前不阻塞
+                    let capacity = self.pop()?;
+                    let capacity = match capacity.as_int().map(usize::try_from) {
+                        Some(Ok(capacity)) => capacity,
+                        _ => {
+                            let message = format!("Invalid channel capacity: {}", capacity);
+                            self.throw_exception(new_exception("IllegalArgumentException", &message))?;
+                            continue;
+                        }
                     };
+                    let (sender, receiver) = crossbeam_channel::bounded(capacity);
                     
                     let state = Arc::new(Mutex::new(ChannelState {
                         sender: Arc::new(Mutex::new(Some(sender))),
@@ -4301,49 +4787,17 @@ impl VM {
                 OpCode::ChannelSend => {
                     let value = self.pop()?;
                     let channel = self.pop()?;
-                    
-                    if let Some(ch_state) = channel.as_channel() {
-                        let state = ch_state.lock();
-                        let sender = state.sender.lock();
-                        
-                        if let Some(ref s) = *sender {
-                            if s.send(value).is_err() {
-                                return Err(self.runtime_error("Channel send failed: receiver closed"));
-                            }
-                        } else {
-                            return Err(self.runtime_error("Channel is closed"));
-                        }
-                    } else {
-                        return Err(self.runtime_error(&format!("Cannot send to {}", channel.type_name())));
+                    if !self.channel_send(&channel, value)? {
+                        self.throw_channel_closed("send on closed channel")?;
+                        continue;
                     }
-                    
                     self.push_fast(Value::null());
                 }
                 
                 OpCode::ChannelReceive => {
                     let channel = self.pop()?;
-                    
-                    if let Some(ch_state) = channel.as_channel() {
-                        let state = ch_state.lock();
-                        let receiver = state.receiver.lock();
-                        
-                        if let Some(ref r) = *receiver {
-                            // 阻塞接收
-                            match r.recv() {
-                                Ok(value) => {
-                                    self.push_fast(value);
-                                }
-                                Err(_) => {
-                                    // Channel 已关闭且为空
-                                    self.push_fast(Value::null());
-                                }
-                            }
-                        } else {
-                            return Err(self.runtime_error("Channel receiver is closed"));
-                        }
-                    } else {
-                        return Err(self.runtime_error(&format!("Cannot receive from {}", channel.type_name())));
-                    }
+                    let value = self.channel_receive(&channel)?;
+                    self.push_fast(value);
                 }
                 
                 OpCode::ChannelTrySend => {
@@ -4355,6 +4809,7 @@ impl VM {
                         let sender = state.sender.lock();
                         
                         if let Some(ref s) = *sender {
+                            gc_escape(&value);
                             let success = s.send(value).is_ok();
                             self.push_fast(Value::bool(success));
                         } else {
@@ -4393,21 +4848,11 @@ impl VM {
                 }
                 
                 OpCode::ChannelClose => {
-                    use std::sync::atomic::Ordering;
-                    
                     let channel = self.pop()?;
-                    
-                    if let Some(ch_state) = channel.as_channel() {
-                        let state = ch_state.lock();
-                        state.closed.store(true, Ordering::Relaxed);
-                        
-                        // 关闭 sender
-                        let mut sender = state.sender.lock();
-                        *sender = None;
-                    } else {
-                        return Err(self.runtime_error(&format!("Cannot close {}", channel.type_name())));
+                    if !self.channel_close(&channel)? {
+                        self.throw_channel_closed("close of closed channel")?;
+                        continue;
                     }
-                    
                     self.push_fast(Value::null());
                 }
                 
@@ -4473,7 +4918,9 @@ impl VM {
                     
                     if let Some(state) = wg.as_waitgroup() {
                         // 使用优化的 wait 方法（包含快速路径 + 自旋 + 阻塞）
-                        state.wait();
+                        while !state.wait_timeout(self.block_interval()) {
+                            self.check_exit()?;
+                        }
                     } else {
                         return Err(self.runtime_error(&format!("Cannot wait on {}", wg.type_name())));
                     }
@@ -4545,275 +4992,23 @@ impl VM {
                 }
                 
                 OpCode::SelectExec => {
-                    use crossbeam_channel::{select, Sender, Receiver};
-                    use std::sync::atomic::Ordering;
-                    
                     let builder = self.pop()?;
-                    
-                    if let Some(arr) = builder.as_array() {
-                        let arr = arr.lock();
-                        
-                        // 收集所有 cases 的信息
-                        struct CaseInfo {
-                            case_type: i64, // 0=send, 1=recv, 2=default
-                            sender: Option<Sender<Value>>,
-                            receiver: Option<Receiver<Value>>,
-                            value: Option<Value>,
-                            closed: bool,
-                        }
-                        
-                        let mut cases_info: Vec<CaseInfo> = Vec::new();
-                        let mut default_idx: Option<usize> = None;
-                        
-                        let mut i = 0;
-                        while i + 2 < arr.len() {
-                            let case_type = arr[i].as_int().unwrap_or(0);
-                            match case_type {
-                                0 => {
-                                    // Send
-                                    if let Some(ch) = arr[i + 1].as_channel() {
-                                        let state = ch.lock();
-                                        let sender = state.sender.lock().clone();
-                                        let closed = state.closed.load(Ordering::Acquire);
-                                        cases_info.push(CaseInfo {
-                                            case_type: 0,
-                                            sender,
-                                            receiver: None,
-                                            value: Some(arr[i + 2].clone()),
-                                            closed,
-                                        });
-                                    }
-                                }
-                                1 => {
-                                    // Recv
-                                    if let Some(ch) = arr[i + 1].as_channel() {
-                                        let state = ch.lock();
-                                        let receiver = state.receiver.lock().clone();
-                                        let closed = state.closed.load(Ordering::Acquire);
-                                        cases_info.push(CaseInfo {
-                                            case_type: 1,
-                                            sender: None,
-                                            receiver,
-                                            value: None,
-                                            closed,
-                                        });
-                                    }
-                                }
-                                2 => {
-                                    // Default
-                                    default_idx = Some(cases_info.len());
-                                    cases_info.push(CaseInfo {
-                                        case_type: 2,
-                                        sender: None,
-                                        receiver: None,
-                                        value: None,
-                                        closed: false,
-                                    });
-                                }
-                                _ => {}
-                            }
-                            i += 3;
-                        }
-                        drop(arr);
-                        
-                        // 简单实现：轮询检查可用的 case
-                        let mut result_type = -1i64;
-                        let mut result_idx = -1i64;
-                        let mut result_value = Value::null();
-                        
-                        // 首先尝试非阻塞操作
-                        for (idx, case) in cases_info.iter().enumerate() {
-                            match case.case_type {
-                                0 => {
-                                    // Try send
-                                    if !case.closed {
-                                        if let (Some(sender), Some(val)) = (&case.sender, &case.value) {
-                                            if let Ok(()) = sender.try_send(val.clone()) {
-                                                result_type = 0;
-                                                result_idx = idx as i64;
-                                                break;
-                                            }
-                                        }
-                                    }
-                                }
-                                1 => {
-                                    // Try recv
-                                    if let Some(receiver) = &case.receiver {
-                                        match receiver.try_recv() {
-                                            Ok(val) => {
-                                                result_type = 1;
-                                                result_idx = idx as i64;
-                                                result_value = val;
-                                                break;
-                                            }
-                                            Err(crossbeam_channel::TryRecvError::Disconnected) => {
-                                                result_type = 2; // closed
-                                                result_idx = idx as i64;
-                                                break;
-                                            }
-                                            _ => {}
-                                        }
-                                    }
-                                }
-                                _ => {}
-                            }
-                        }
-                        
-                        // 如果没有成功且有 default，执行 default
-                        if result_type == -1 {
-                            if let Some(idx) = default_idx {
-                                result_type = 3;
-                                result_idx = idx as i64;
-                            } else {
-                                // 没有 default，需要阻塞等待
-                                // 使用简单的轮询
-                                'outer: loop {
-                                    for (idx, case) in cases_info.iter().enumerate() {
-                                        match case.case_type {
-                                            0 => {
-                                                if !case.closed {
-                                                    if let (Some(sender), Some(val)) = (&case.sender, &case.value) {
-                                                        if let Ok(()) = sender.try_send(val.clone()) {
-                                                            result_type = 0;
-                                                            result_idx = idx as i64;
-                                                            break 'outer;
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                            1 => {
-                                                if let Some(receiver) = &case.receiver {
-                                                    match receiver.try_recv() {
-                                                        Ok(val) => {
-                                                            result_type = 1;
-                                                            result_idx = idx as i64;
-                                                            result_value = val;
-                                                            break 'outer;
-                                                        }
-                                                        Err(crossbeam_channel::TryRecvError::Disconnected) => {
-                                                            result_type = 2;
-                                                            result_idx = idx as i64;
-                                                            break 'outer;
-                                                        }
-                                                        _ => {}
-                                                    }
-                                                }
-                                            }
-                                            _ => {}
-                                        }
-                                    }
-                                    
-                                    // 检查是否所有 channel 都关闭
-                                    let all_closed = cases_info.iter().all(|c| {
-                                        c.case_type == 2 || c.closed || 
-                                        (c.case_type == 1 && c.receiver.as_ref().map(|r| r.is_empty() && r.len() == 0).unwrap_or(true))
-                                    });
-                                    
-                                    if all_closed {
-                                        result_type = 4; // all_closed
-                                        break;
-                                    }
-                                    
-                                    std::thread::sleep(std::time::Duration::from_micros(10));
-                                }
-                            }
-                        }
-                        
-                        self.push(Value::int(result_type as i128));
-                        self.push(Value::int(result_idx as i128));
-                        self.push(result_value);
-                    } else {
-                        return Err(self.runtime_error("Invalid select builder"));
-                    }
+                    let Some((case_idx, value)) = self.exec_select(&builder, true)? else {
+                        self.throw_channel_closed("send on closed channel")?;
+                        continue;
+                    };
+                    self.push(value);
+                    self.push(Value::int(case_idx as i128));
                 }
                 
                 OpCode::SelectTryExec => {
-                    use std::sync::atomic::Ordering;
-                    
                     let builder = self.pop()?;
-                    
-                    if let Some(arr) = builder.as_array() {
-                        let arr = arr.lock();
-                        
-                        let mut result_type = -1i64;
-                        let mut result_idx = -1i64;
-                        let mut result_value = Value::null();
-                        let mut default_idx: Option<usize> = None;
-                        
-                        let mut i = 0;
-                        let mut case_idx = 0usize;
-                        while i + 2 < arr.len() {
-                            let case_type = arr[i].as_int().unwrap_or(0);
-                            match case_type {
-                                0 => {
-                                    // Try send
-                                    if let Some(ch) = arr[i + 1].as_channel() {
-                                        let state = ch.lock();
-                                        let closed = state.closed.load(Ordering::Acquire);
-                                        let sender = state.sender.lock().clone();
-                                        drop(state);
-                                        if !closed {
-                                            if let Some(ref s) = sender {
-                                                if let Ok(()) = s.try_send(arr[i + 2].clone()) {
-                                                    result_type = 0;
-                                                    result_idx = case_idx as i64;
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                                1 => {
-                                    // Try recv
-                                    if let Some(ch) = arr[i + 1].as_channel() {
-                                        let state = ch.lock();
-                                        let receiver = state.receiver.lock().clone();
-                                        drop(state);
-                                        if let Some(ref r) = receiver {
-                                            match r.try_recv() {
-                                                Ok(val) => {
-                                                    result_type = 1;
-                                                    result_idx = case_idx as i64;
-                                                    result_value = val;
-                                                }
-                                                Err(crossbeam_channel::TryRecvError::Disconnected) => {
-                                                    result_type = 2;
-                                                    result_idx = case_idx as i64;
-                                                }
-                                                _ => {}
-                                            }
-                                        }
-                                    }
-                                }
-                                2 => {
-                                    // Default
-                                    default_idx = Some(case_idx);
-                                }
-                                _ => {}
-                            }
-                            
-                            if result_type != -1 {
-                                break;
-                            }
-                            
-                            i += 3;
-                            case_idx += 1;
-                        }
-                        drop(arr);
-                        
-                        // 如果没有成功且有 default，执行 default
-                        if result_type == -1 {
-                            if let Some(idx) = default_idx {
-                                result_type = 3;
-                                result_idx = idx as i64;
-                            }
-                        }
-                        
-                        self.push(Value::int(result_type as i128));
-                        self.push(Value::int(result_idx as i128));
-                        self.push(result_value);
-                    } else {
-                        return Err(self.runtime_error("Invalid select builder"));
-                    }
+                    let Some((case_idx, value)) = self.exec_select(&builder, false)? else {
+                        self.throw_channel_closed("send on closed channel")?;
+                        continue;
+                    };
+                    self.push(value);
+                    self.push(Value::int(case_idx as i128));
                 }
                 
                 // ============ 专用整数指令 (性能优化) ============
@@ -4946,6 +5141,26 @@ impl VM {
                     }
                 }
                 
+                OpCode::JumpIfTruePop => {
+                    let offset = self.read_u16() as usize;
+                    let condition = self.pop_fast();
+                    if condition.is_truthy() {
+                        self.ip += offset;
+                    }
+                }
+                
+                OpCode::CompareJump => {
+                    let condition = self.read_byte();
+                    let offset = self.read_u16() as usize;
+                    let b = self.pop_fast();
+                    let a = self.pop_fast();
+                    let (comparison, jump_when) = Comparison::decode(condition)
+                        .ok_or_else(|| self.runtime_error(&format!("invalid comparison {}", condition)))?;
+                    if self.compare(comparison, &a, &b)? == jump_when {
+                        self.ip += offset;
+                    }
+                }
+                
                 OpCode::TailCall => {
                     // 尾调用优化：复用当前调用帧
                     let arg_count = self.read_byte() as usize;
@@ -4969,6 +5184,7 @@ impl VM {
                         
                         // 截断栈
                         self.stack.truncate(current_base + arg_count);
+                        self.push_captures(func);
                         
                         // 直接跳转到函数体，不创建新帧
                         self.ip = func.chunk_index;
@@ -5013,6 +5229,10 @@ impl VM {
                         };
                         self.stack.truncate(truncate_to);
                         self.push_fast(return_value);
+                        // call_closure 压入的哨兵帧：回调返回，回到调用它的原生方法
+                        if frame.return_ip == u32::MAX {
+                            return Ok(());
+                        }
                         self.ip = frame.return_ip as usize;
                         self.current_base = self.frames.last().map(|f| f.base_slot as usize).unwrap_or(0);
                     }
@@ -5137,13 +5357,15 @@ impl VM {
                 }
                 
                 OpCode::EnumMatch => {
+                    let enum_name_idx = self.read_u16();
                     let variant_name_idx = self.read_u16();
                     let enum_val = self.pop()?;
                     
+                    let enum_name = self.chunk.get_string(enum_name_idx);
                     let variant_name = self.chunk.get_string(variant_name_idx);
                     
                     if let Some(variant) = enum_val.as_enum() {
-                        let is_match = variant.variant_name == variant_name;
+                        let is_match = variant.enum_name == enum_name && variant.variant_name == variant_name;
                         self.push(Value::bool(is_match));
                     } else {
                         // 非枚举类型总是不匹配
@@ -5199,6 +5421,30 @@ impl VM {
                     }
                 }
                 
+                OpCode::JumpIfLocalGtConst => {
+                    let slot = self.read_byte() as usize;
+                    let const_val = self.read_byte() as i8 as i128;
+                    let offset = self.read_i16();
+                    let actual = self.current_base + slot;
+                    if let Some(n) = self.stack[actual].as_int() {
+                        if n > const_val {
+                            self.ip = (self.ip as isize + offset as isize) as usize;
+                        }
+                    }
+                }
+                
+                OpCode::JumpIfLocalGeConst => {
+                    let slot = self.read_byte() as usize;
+                    let const_val = self.read_byte() as i8 as i128;
+                    let offset = self.read_i16();
+                    let actual = self.current_base + slot;
+                    if let Some(n) = self.stack[actual].as_int() {
+                        if n >= const_val {
+                            self.ip = (self.ip as isize + offset as isize) as usize;
+                        }
+                    }
+                }
+                
                 OpCode::CallWithLocal => {
                     // 简化实现：读取操作数但不执行特殊处理
                     let _slot = self.read_byte();
@@ -5223,6 +5469,10 @@ impl VM {
                     };
                     self.stack.truncate(truncate_to);
                     self.push_fast(return_value);
+                    // call_closure 压入的哨兵帧：回调返回，回到调用它的原生方法
+                    if frame.return_ip == u32::MAX {
+                        return Ok(());
+                    }
                     self.ip = frame.return_ip as usize;
                     self.current_base = self.frames.last().map(|f| f.base_slot as usize).unwrap_or(0);
                 }
@@ -5244,6 +5494,10 @@ impl VM {
                     };
                     self.stack.truncate(truncate_to);
                     self.push_fast(return_value);
+                    // call_closure 压入的哨兵帧：回调返回，回到调用它的原生方法
+                    if frame.return_ip == u32::MAX {
+                        return Ok(());
+                    }
                     self.ip = frame.return_ip as usize;
                     self.current_base = self.frames.last().map(|f| f.base_slot as usize).unwrap_or(0);
                 }
@@ -5285,6 +5539,31 @@ impl VM {
         byte
     }
 
+    /// 执行 JumpTable：弹出值，按值跳到表项，不在表中时跳到最后的 default 表项
+    ///
+    /// 与 `==` 一致，整数值的浮点数也能匹配
+    fn jump_table(&mut self) -> Result<(), RuntimeError> {
+        let min_index = self.read_u16() as usize;
+        let count = self.read_u16() as usize;
+        let table = self.ip;
+        self.ip += (count + 1) * 2;
+        
+        let value = self.pop()?;
+        let n = value
+            .as_int()
+            .or_else(|| value.as_float().filter(|f| f.fract() == 0.0 && f.abs() < 1e30).map(|f| f as i128));
+        let min = self.chunk.constants[min_index].as_int().unwrap_or_default();
+        let entry = n
+            .and_then(|n| n.checked_sub(min))
+            .and_then(|i| usize::try_from(i).ok())
+            .filter(|&i| i < count)
+            .unwrap_or(count);
+        let code = &self.chunk.code;
+        let offset = u16::from_be_bytes([code[table + entry * 2], code[table + entry * 2 + 1]]);
+        self.ip += offset as usize;
+        Ok(())
+    }
+    
     /// 读取一个 u16（大端序）
     #[inline(always)]
     fn read_u16(&mut self) -> u16 {
@@ -5303,19 +5582,246 @@ impl VM {
         self.read_u16() as i16
     }
 
-    /// 压栈
+    /// CompareJump 的比较，结果与对应的比较指令（Lt、Eq 等）相同
     #[inline(always)]
-    fn push(&mut self, value: Value) {
-        self.stack.push(value);
+    fn compare(&mut self, comparison: Comparison, a: &Value, b: &Value) -> Result<bool, RuntimeError> {
+        if let (Some(x), Some(y)) = (a.as_int(), b.as_int()) {
+            return Ok(match comparison {
+                Comparison::Lt => x < y,
+                Comparison::Le => x <= y,
+                Comparison::Gt => x > y,
+                Comparison::Ge => x >= y,
+                Comparison::Eq => x == y,
+                Comparison::Ne => x != y,
+            });
+        }
+        let result = match comparison {
+            Comparison::Lt => a.lt(b),
+            Comparison::Le => a.le(b),
+            Comparison::Gt => a.gt(b),
+            Comparison::Ge => a.ge(b),
+            Comparison::Eq => return self.values_equal(a, b),
+            Comparison::Ne => return self.values_equal(a, b).map(|equal| !equal),
+        };
+        result.map(|value| value.is_truthy()).map_err(|e| self.runtime_error(&e))
     }
 
-    /// 出栈
-    #[inline(always)]
-    fn pop(&mut self) -> Result<Value, RuntimeError> {
-        self.stack.pop().ok_or_else(|| {
-            let msg = format_message(messages::ERR_RUNTIME_STACK_UNDERFLOW, self.locale, &[]);
-            self.runtime_error(&msg)
-        })
+    /// 两个字符串相加的结果压栈，超出分配限制时抛出异常
+    fn push_string_concat(&mut self, left: &str, right: &str) -> Result<(), RuntimeError> {
+        match super::alloc::concat_str(left, right) {
+            Ok(s) => self.push_fast(Value::string(s)),
+            Err(e) => self.stdlib_error(&e)?,
+        }
+        Ok(())
+    }
+
+    /// 值转换为显示的字符串（`print` / `println`、字符串插值、与字符串相加）
+    ///
+    /// class / struct 实例定义了无参数的 `toString()` 时调用它，否则按声明顺序列出字段的值
+    /// （`Point { x: 1, name: "a" }`）；数组、map 和 Set 中的实例同样处理。
+    /// `toString()` 再次转换正在转换的实例（例如打印 `this`），或实例嵌套超过
+    /// [`MAX_STRINGIFY_DEPTH`] 层时，该实例显示为 `...`
+    fn display_string(&mut self, value: Value) -> Result<String, RuntimeError> {
+        if let Some(s) = value.as_string() {
+            return Ok(s.clone());
+        }
+        let mut out = String::new();
+        self.write_display(value, &mut out)?;
+        Ok(out)
+    }
+
+    fn write_display(&mut self, value: Value, out: &mut String) -> Result<(), RuntimeError> {
+        let write_all = |vm: &mut Self, items: Vec<Value>, out: &mut String| -> Result<(), RuntimeError> {
+            for (i, item) in items.into_iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                vm.write_display(item, out)?;
+            }
+            Ok(())
+        };
+        if value.is_class() || value.is_struct() {
+            return self.write_instance(value, out);
+        }
+        if let Some(arr) = value.as_array() {
+            let items = arr.lock().clone();
+            out.push('[');
+            write_all(self, items, out)?;
+            out.push(']');
+        } else if let Some((source, start, end)) = value.as_array_slice() {
+            let items = {
+                let source = source.lock();
+                source.get(start..end.min(source.len())).unwrap_or_default().to_vec()
+            };
+            out.push('[');
+            write_all(self, items, out)?;
+            out.push(']');
+        } else if let Some(set) = value.as_set() {
+            let items = set.lock().clone();
+            out.push_str("set{");
+            write_all(self, items, out)?;
+            out.push('}');
+        } else if let Some(m) = value.as_map() {
+            let entries: Vec<(String, Value)> = m.lock().iter().map(|(k, v)| (k.clone(), *v)).collect();
+            out.push('{');
+            for (i, (key, item)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                out.push_str(&format!("\"{}\": ", key));
+                self.write_display(item, out)?;
+            }
+            out.push('}');
+        } else if let Some(variant) = value.as_enum().filter(|variant| !variant.associated_data.is_empty()) {
+            self.write_enum_payload(variant.clone(), out)?;
+        } else {
+            out.push_str(&value.to_string());
+        }
+        Ok(())
+    }
+
+    /// 带关联数据的枚举变体：`Shape::Rect(w: 2.0, h: 3.0)`，字段按变体声明的顺序，字符串带引号
+    fn write_enum_payload(&mut self, variant: EnumVariantValue, out: &mut String) -> Result<(), RuntimeError> {
+        let EnumVariantValue { enum_name, variant_name, mut associated_data, .. } = variant;
+        let declared = self.chunk.get_enum(&enum_name)
+            .and_then(|info| info.variants.iter().find(|v| v.name == variant_name))
+            .map(|v| v.fields.clone())
+            .unwrap_or_default();
+        let mut ordered: Vec<(String, Value)> = declared.into_iter()
+            .filter_map(|name| associated_data.remove(&name).map(|value| (name, value)))
+            .collect();
+        let mut rest: Vec<(String, Value)> = associated_data.into_iter().collect();
+        rest.sort_by(|a, b| a.0.cmp(&b.0));
+        ordered.extend(rest);
+
+        out.push_str(&format!("{}::{}(", enum_name, variant_name));
+        for (i, (name, value)) in ordered.into_iter().enumerate() {
+            if i > 0 {
+                out.push_str(", ");
+            }
+            out.push_str(&name);
+            out.push_str(": ");
+            match value.as_string() {
+                Some(s) => out.push_str(&format!("{:?}", s)),
+                None => self.write_display(value, out)?,
+            }
+        }
+        out.push(')');
+        Ok(())
+    }
+
+    /// 实例：调用 `toString()`，没有时列出字段，字段中的字符串带引号
+    fn write_instance(&mut self, value: Value, out: &mut String) -> Result<(), RuntimeError> {
+        if self.stringifying.len() >= MAX_STRINGIFY_DEPTH || self.stringifying.contains(&value.to_bits()) {
+            out.push_str("...");
+            return Ok(());
+        }
+        let (type_name, fields) = if let Some(instance) = value.as_class() {
+            let instance = instance.lock();
+            (instance.class_name.clone(), instance.fields.clone())
+        } else if let Some(instance) = value.as_struct() {
+            let instance = instance.lock();
+            (instance.type_name.clone(), instance.fields.clone())
+        } else {
+            unreachable!("write_instance on {}", value.type_name());
+        };
+        let to_string = self.chunk.get_method(&type_name, "toString")
+            .and_then(|index| self.chunk.constants[index as usize].as_function().cloned())
+            .filter(|func| func.arity == 1);
+
+        self.stringifying.push(value.to_bits());
+        let result = match to_string {
+            Some(func) => self.call_closure(&func, &[value]).and_then(|text| match text.as_string() {
+                Some(text) => {
+                    out.push_str(text);
+                    Ok(())
+                }
+                None => Err(self.runtime_error(&format!(
+                    "{}.toString() must return string, got {}", type_name, text.type_name()
+                ))),
+            }),
+            None => self.write_fields(&type_name, fields, out),
+        };
+        self.stringifying.pop();
+        result
+    }
+
+    fn write_fields(&mut self, type_name: &str, mut fields: HashMap<String, Value>, out: &mut String) -> Result<(), RuntimeError> {
+        // 声明顺序：先父类的字段；不在类型信息中的字段按名称排在最后
+        let mut chain = Vec::new();
+        let mut current = Some(type_name.to_string());
+        while let Some(name) = current {
+            let Some(info) = self.chunk.get_type(&name) else { break };
+            current = info.parent.clone();
+            chain.push(info.fields.clone());
+        }
+        let mut ordered: Vec<(String, Value)> = Vec::with_capacity(fields.len());
+        for declared in chain.into_iter().rev() {
+            for name in declared {
+                if let Some(value) = fields.remove(&name) {
+                    ordered.push((name, value));
+                }
+            }
+        }
+        let mut rest: Vec<(String, Value)> = fields.into_iter().collect();
+        rest.sort_by(|a, b| a.0.cmp(&b.0));
+        ordered.extend(rest);
+
+        out.push_str(type_name);
+        if ordered.is_empty() {
+            out.push_str(" {}");
+            return Ok(());
+        }
+        out.push_str(" { ");
+        for (i, (name, value)) in ordered.into_iter().enumerate() {
+            if i > 0 {
+                out.push_str(", ");
+            }
+            out.push_str(&name);
+            out.push_str(": ");
+            match value.as_string() {
+                Some(s) => out.push_str(&format!("{:?}", s)),
+                None => self.write_display(value, out)?,
+            }
+        }
+        out.push_str(" }");
+        Ok(())
+    }
+
+    /// `==` / `!=` 的相等比较：两边都是 class 实例且左边的类（或其父类）定义了 `equals(other)` 时，
+    /// 结果由该方法决定，否则按 [`Value`] 的相等比较（class 实例按引用）
+    fn values_equal(&mut self, a: &Value, b: &Value) -> Result<bool, RuntimeError> {
+        let (Some(instance), true) = (a.as_class(), b.as_class().is_some()) else {
+            return Ok(a == b);
+        };
+        let class_name = instance.lock().class_name.clone();
+        let Some(func) = self.chunk.get_method(&class_name, "equals")
+            .and_then(|index| self.chunk.constants[index as usize].as_function().cloned())
+        else {
+            return Ok(a == b);
+        };
+        if func.arity != 2 {
+            return Err(self.runtime_error(&format!("{}.equals() used by == must take exactly one argument", class_name)));
+        }
+        let result = self.call_closure(&func, &[*a, *b])?;
+        result.as_bool().ok_or_else(|| {
+            self.runtime_error(&format!("{}.equals() must return bool, got {}", class_name, result.type_name()))
+        })
+    }
+
+    /// 压栈
+    #[inline(always)]
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    /// 出栈
+    #[inline(always)]
+    fn pop(&mut self) -> Result<Value, RuntimeError> {
+        self.stack.pop().ok_or_else(|| {
+            let msg = format_message(messages::ERR_RUNTIME_STACK_UNDERFLOW, self.locale, &[]);
+            self.runtime_error(&msg)
+        })
     }
     
     /// 快速出栈（无检查，仅在确定栈非空时使用）
@@ -5330,19 +5836,116 @@ impl VM {
         }
     }
     
-    /// 快速入栈（无检查，假设容量足够）
+    /// 快速入栈
     /// 
-    /// 使用 unsafe 直接写入，避免容量检查开销
+    /// 使用 unsafe 直接写入；容量不足时先扩容（局部变量很多或调用很深时会超出预分配容量）
     #[inline(always)]
     fn push_fast(&mut self, value: Value) {
+        let len = self.stack.len();
+        if len == self.stack.capacity() {
+            self.stack.reserve(len.max(STACK_SIZE));
+        }
         unsafe {
-            let len = self.stack.len();
-            // 确保容量足够（仅在调试模式下检查）
-            debug_assert!(len < self.stack.capacity(), "stack overflow");
             std::ptr::write(self.stack.as_mut_ptr().add(len), value);
             self.stack.set_len(len + 1);
         }
     }
+    
+    /// 实例方法在常量池中的位置：先查类型及其父类的方法表，再查 VTable 中记录的 trait 实现
+    fn instance_method_index(&self, type_name: &str, method_name: &str) -> Result<usize, RuntimeError> {
+        if let Some(index) = self.chunk.get_method(type_name, method_name) {
+            return Ok(index as usize);
+        }
+        self.vtable_registry
+            .lookup_by_name(type_name)
+            .and_then(|vtable| vtable.lookup_trait_method(method_name))
+            .ok_or_else(|| self.runtime_error(&format!("Type '{}' has no method '{}'", type_name, method_name)))
+    }
+    
+    /// `?.` 和 `!.` 调用类型自身定义的方法：receiver 和参数已在栈上，找到方法时压入调用帧并返回 true
+    ///
+    /// 方法表和函数都借用自字节码块，不复制
+    fn invoke_own_method(&mut self, type_name: &str, method_name: &str, receiver_idx: usize, arg_count: usize) -> Result<bool, RuntimeError> {
+        let Some(&method_index) = self.chunk.get_type(type_name).and_then(|t| t.methods.get(method_name)) else {
+            return Ok(false);
+        };
+        let Some(func) = self.chunk.constants[method_index as usize].as_function() else {
+            return Ok(false);
+        };
+        
+        // 检查参数数量（this 不计入）
+        if arg_count < func.required_params.saturating_sub(1) {
+            let msg = format!(
+                "Method '{}' expected at least {} arguments but got {}",
+                method_name, func.required_params.saturating_sub(1), arg_count
+            );
+            return Err(self.runtime_error(&msg));
+        }
+        
+        // 填充默认参数
+        let missing = func.arity.saturating_sub(arg_count + 1);
+        if missing > 0 && !func.defaults.is_empty() {
+            let start = func.defaults.len().saturating_sub(missing);
+            self.stack.extend_from_slice(&func.defaults[start..]);
+        }
+        
+        if self.frames.len() >= MAX_FRAMES {
+            return Err(self.runtime_error("Stack overflow"));
+        }
+        
+        // receiver 作为 this
+        self.frames.push(CallFrame {
+            return_ip: self.ip as u32,
+            base_slot: receiver_idx as u32,
+            is_method_call: true,
+        });
+        self.current_base = receiver_idx;
+        self.ip = func.chunk_index;
+        Ok(true)
+    }
+    
+    /// 稳定排序 `sort()` / `sorted()` 的元素：有比较函数时按它的返回值（负数、0、正数），否则按值的全序
+    fn sort_values(&mut self, elements: &mut [Value], method: &str, comparator: Option<&Arc<Function>>) -> Result<(), RuntimeError> {
+        match comparator {
+            Some(func) => merge_sort_by(elements, &mut |a: &Value, b: &Value| {
+                let result = self.call_closure(func, &[*a, *b])?;
+                match result.as_int() {
+                    Some(n) => Ok(n.cmp(&0)),
+                    None => Err(self.runtime_error(&format!(
+                        "{}() comparator must return int, got {}", method, result.type_name()
+                    ))),
+                }
+            }),
+            None => merge_sort_by(elements, &mut |a: &Value, b: &Value| a.total_cmp(b))
+                .map_err(|e| self.runtime_error(&e)),
+        }
+    }
+    
+    /// `--safe-vm` 下确认有序查找的数组（或它的键）确实有序，否则查找的结果没有意义
+    fn check_sorted(&self, values: &[Value], method: &str, what: &str) -> Result<(), RuntimeError> {
+        if !safe_vm() {
+            return Ok(());
+        }
+        match first_unsorted(values).map_err(|e| self.runtime_error(&e))? {
+            Some(i) => Err(self.runtime_error(&format!(
+                "{}() requires a sorted array, but the {} at index {} is greater than the next one (checked by --safe-vm)",
+                method, what, i
+            ))),
+            None => Ok(()),
+        }
+    }
+    
+    /// `sortBy()` / `sortedBy()`：每个元素调用一次 keyFn 取出键，再按键的全序稳定排序
+    fn sort_values_by_key(&mut self, elements: Vec<Value>, key_fn: &Arc<Function>) -> Result<Vec<Value>, RuntimeError> {
+        let mut keyed = Vec::with_capacity(elements.len());
+        for element in elements {
+            let key = self.call_closure(key_fn, &[element])?;
+            keyed.push((key, element));
+        }
+        merge_sort_by(&mut keyed, &mut |a: &(Value, Value), b: &(Value, Value)| a.0.total_cmp(&b.0))
+            .map_err(|e| self.runtime_error(&e))?;
+        Ok(keyed.into_iter().map(|(_, element)| element).collect())
+    }
 
     /// 创建运行时错误
     /// 检查类名是否是 Throwable 或其子类
@@ -5372,6 +5975,277 @@ impl VM {
         false
     }
     
+    /// 类或结构体实例的 entries() 方法，for-in 遍历没有内置迭代方式的对象时调用
+    fn entries_method(&self, value: &Value) -> Option<Arc<Function>> {
+        let type_name = if let Some(s) = value.as_struct() {
+            s.lock().type_name.clone()
+        } else if let Some(c) = value.as_class() {
+            c.lock().class_name.clone()
+        } else {
+            return None;
+        };
+        let index = self.chunk.get_method(&type_name, "entries")?;
+        self.chunk.constants[index as usize].as_function().cloned()
+    }
+    
+    /// 跳转到最近的 kind 种类的处理器，压入异常或 panic 的值；没有这样的处理器时返回 false
+    ///
+    /// 跳过的其他种类的处理器随着栈展开一起移除。
+    /// 回调（[`call_closure`](Self::call_closure)）中的异常不会跳到回调之外的处理器：
+    /// 那样会在回调的解释器循环中继续执行外层代码。这时记下异常，
+    /// 回调返回后由调用它的那一层重新抛出（见 [`execute`](Self::execute)）
+    fn unwind(&mut self, kind: HandlerKind, value: Value) -> bool {
+        let boundary = self.frames.iter().rposition(|frame| frame.return_ip == u32::MAX);
+        let index = self.exception_handlers.iter()
+            .rposition(|handler| handler.kind == kind && boundary.is_none_or(|b| handler.frame_depth > b));
+        let Some(index) = index else {
+            if boundary.is_some() {
+                self.escaped = Some((kind, value));
+            }
+            return false;
+        };
+        let handler = self.exception_handlers.swap_remove(index);
+        // 更内层的处理器随着栈展开一起移除
+        self.exception_handlers.truncate(index);
+        // 恢复栈到处理器设置时的深度
+        self.stack.truncate(handler.stack_depth);
+        // 恢复调用帧
+        self.frames.truncate(handler.frame_depth);
+        // 处理块在设置处理器的函数中执行，局部变量相对于该函数的栈基址
+        self.current_base = self.frames.last().map(|f| f.base_slot as usize).unwrap_or(0);
+        // 压入异常或 panic 的值（供处理块使用）
+        self.push(value);
+        self.ip = handler.catch_ip;
+        true
+    }
+    
+    /// 抛出异常：跳转到最近的 catch 块，没有时返回错误
+    fn throw_exception(&mut self, exception: Value) -> Result<(), RuntimeError> {
+        if self.unwind(HandlerKind::Catch, exception) {
+            Ok(())
+        } else {
+            Err(self.runtime_error(&format!("Uncaught exception: {}", exception)))
+        }
+    }
+    
+    /// 引发 panic：跳转到最近的 onPanic 块，catch 块不处理 panic；没有时返回带有 panic 值的错误
+    fn raise_panic(&mut self, value: Value) -> Result<(), RuntimeError> {
+        if self.unwind(HandlerKind::Panic, value) {
+            return Ok(());
+        }
+        let mut error = self.runtime_error(&format!("Panic: {}", value));
+        // 错误可能在虚拟机结束后才被查看
+        gc_escape(&value);
+        error.panic_payload = Some(value);
+        Err(error)
+    }
+    
+    /// 处理标准库调用失败
+    /// 
+    /// 按 `stdlib_exception` 约定编码的错误作为异常抛出，其他错误为运行时错误
+    fn stdlib_error(&mut self, error: &str) -> Result<(), RuntimeError> {
+        use crate::stdlib::exception::{parse_stdlib_exception, new_exception};
+        
+        // Os.exit：不经过 catch 和 onPanic 块，执行 finally 块后结束解释器循环（见 execute）
+        if let Some(code) = crate::stdlib::os::parse_exit_request(error) {
+            return Err(self.exit_error(code));
+        }
+        match parse_stdlib_exception(error) {
+            Some((class_name, message)) => self.throw_exception(new_exception(class_name, message)),
+            None => Err(self.runtime_error(error)),
+        }
+    }
+    
+    /// `Os.exit` 结束程序的错误，由 [`execute`](Self::execute) 先执行 finally 块
+    fn exit_error(&self, code: i32) -> RuntimeError {
+        let mut exit = self.runtime_error(&format!("program exited with code {}", code));
+        exit.exit_code = Some(code);
+        exit
+    }
+    
+    /// 主虚拟机上有协程请求的退出时返回结束程序的错误
+    fn check_exit(&self) -> Result<(), RuntimeError> {
+        match self.handles_exit.then(|| self.pending_exit.take()).flatten() {
+            Some(code) => Err(self.exit_error(code)),
+            None => Ok(()),
+        }
+    }
+    
+    /// 阻塞操作每次等待的时长：主虚拟机定期醒来检查协程的退出请求，其他虚拟机一直等待
+    fn block_interval(&self) -> std::time::Duration {
+        if self.handles_exit {
+            crate::stdlib::os::EXIT_POLL_INTERVAL
+        } else {
+            std::time::Duration::MAX
+        }
+    }
+    
+    /// 向通道发送值，阻塞到接收方取走（无缓冲）或缓冲区有空位
+    ///
+    /// 阻塞前先克隆发送端并释放通道状态锁，否则同一通道上的接收方拿不到锁。
+    /// 通道已关闭时返回 false，由调用方抛出异常
+    fn channel_send(&self, channel: &Value, value: Value) -> Result<bool, RuntimeError> {
+        use crossbeam_channel::SendTimeoutError;
+        
+        let Some(state) = channel.as_channel() else {
+            return Err(self.runtime_error(&format!("Cannot send to {}", channel.type_name())));
+        };
+        let Some(sender) = state.lock().sender.lock().clone() else {
+            return Ok(false);
+        };
+        // 消息可能由其他线程的虚拟机接收
+        gc_escape(&value);
+        let mut value = value;
+        loop {
+            match sender.send_timeout(value, self.block_interval()) {
+                Ok(()) => return Ok(true),
+                Err(SendTimeoutError::Disconnected(_)) => return Ok(false),
+                Err(SendTimeoutError::Timeout(unsent)) => {
+                    self.check_exit()?;
+                    value = unsent;
+                }
+            }
+        }
+    }
+    
+    /// 关闭通道：之后的发送抛出异常，接收方取完缓冲区中的值后收到 null
+    ///
+    /// 通道已经关闭时返回 false，由调用方抛出异常
+    fn channel_close(&self, channel: &Value) -> Result<bool, RuntimeError> {
+        let Some(state) = channel.as_channel() else {
+            return Err(self.runtime_error(&format!("Cannot close {}", channel.type_name())));
+        };
+        let state = state.lock();
+        if state.closed.swap(true, std::sync::atomic::Ordering::AcqRel) {
+            return Ok(false);
+        }
+        // 丢弃发送端，阻塞中的接收方在所有发送端释放后被唤醒
+        *state.sender.lock() = None;
+        Ok(true)
+    }
+    
+    /// 通道已关闭时的操作抛出 IllegalStateException（可被 try/catch 捕获）
+    fn throw_channel_closed(&mut self, message: &str) -> Result<(), RuntimeError> {
+        use crate::stdlib::exception::new_exception;
+        
+        self.throw_exception(new_exception("IllegalStateException", message))
+    }
+    
+    /// 从通道接收值，阻塞到有值可取；通道已关闭且为空时返回 null
+    fn channel_receive(&self, channel: &Value) -> Result<Value, RuntimeError> {
+        let Some(state) = channel.as_channel() else {
+            return Err(self.runtime_error(&format!("Cannot receive from {}", channel.type_name())));
+        };
+        let receiver = state.lock().receiver.lock().clone();
+        match receiver {
+            Some(receiver) => Ok(self.receive_blocking(&receiver)?.unwrap_or(Value::null())),
+            None => Err(self.runtime_error("Channel receiver is closed")),
+        }
+    }
+    
+    /// 阻塞到有值可取；通道关闭且为空时返回 None
+    fn receive_blocking(&self, receiver: &crossbeam_channel::Receiver<Value>) -> Result<Option<Value>, RuntimeError> {
+        use crossbeam_channel::RecvTimeoutError;
+        
+        loop {
+            match receiver.recv_timeout(self.block_interval()) {
+                Ok(value) => return Ok(Some(value)),
+                Err(RecvTimeoutError::Disconnected) => return Ok(None),
+                Err(RecvTimeoutError::Timeout) => self.check_exit()?,
+            }
+        }
+    }
+    
+    /// 执行 select builder 中收集的分支，返回被选中分支的下标（按添加顺序）和接收到的值
+    ///
+    /// 协程是独立的 OS 线程，阻塞交给 crossbeam 的 `Select`：它把当前线程同时挂在所有通道上，
+    /// 任一通道就绪时只唤醒一次并完成恰好一个操作。多个分支同时就绪时随机选择，
+    /// 避免靠前的分支饿死后面的分支。
+    ///
+    /// 有 default 分支或 `blocking` 为 false 时不阻塞：没有分支就绪则选中 default，
+    /// 连 default 也没有时返回下标 -1。选中的分支向已关闭的通道发送时返回 None。
+    fn exec_select(&self, builder: &Value, blocking: bool) -> Result<Option<(i64, Value)>, RuntimeError> {
+        use crossbeam_channel::{Receiver, Select, Sender};
+        
+        enum SelectOp {
+            Send(Sender<Value>, Value),
+            Recv(Receiver<Value>),
+        }
+        
+        let Some(cases) = builder.as_array() else {
+            return Err(self.runtime_error("Invalid select builder"));
+        };
+        let cases = cases.lock().clone();
+        
+        // builder 中每个分支占 3 个槽位：类型 (0=send, 1=recv, 2=default)、通道、待发送的值
+        let mut ops: Vec<(usize, SelectOp)> = Vec::new();
+        let mut default_idx = None;
+        for (case_idx, case) in cases.chunks_exact(3).enumerate() {
+            let case_type = case[0].as_int().unwrap_or(-1);
+            if case_type == 2 {
+                default_idx = Some(case_idx);
+                continue;
+            }
+            let Some(state) = case[1].as_channel() else {
+                return Err(self.runtime_error(&format!("Cannot select on {}", case[1].type_name())));
+            };
+            let state = state.lock();
+            let op = if case_type == 0 {
+                match state.sender.lock().clone() {
+                    Some(sender) => SelectOp::Send(sender, case[2]),
+                    None => return Ok(None),
+                }
+            } else {
+                match state.receiver.lock().clone() {
+                    Some(receiver) => SelectOp::Recv(receiver),
+                    None => return Err(self.runtime_error("Channel receiver is closed")),
+                }
+            };
+            ops.push((case_idx, op));
+        }
+        
+        let fallback = || Some((default_idx.map_or(-1, |i| i as i64), Value::null()));
+        if ops.is_empty() {
+            return Ok(fallback());
+        }
+        
+        let mut select = Select::new();
+        for (_, op) in &ops {
+            match op {
+                SelectOp::Send(sender, _) => select.send(sender),
+                SelectOp::Recv(receiver) => select.recv(receiver),
+            };
+        }
+        
+        let oper = if blocking && default_idx.is_none() {
+            loop {
+                match select.select_timeout(self.block_interval()) {
+                    Ok(oper) => break oper,
+                    Err(_) => self.check_exit()?,
+                }
+            }
+        } else {
+            match select.try_select() {
+                Ok(oper) => oper,
+                Err(_) => return Ok(fallback()),
+            }
+        };
+        
+        let (case_idx, op) = &ops[oper.index()];
+        let value = match op {
+            SelectOp::Send(sender, value) => {
+                gc_escape(value);
+                if oper.send(sender, *value).is_err() {
+                    return Ok(None);
+                }
+                Value::null()
+            }
+            // 通道已关闭且为空时接收到 null
+            SelectOp::Recv(receiver) => oper.recv(receiver).unwrap_or(Value::null()),
+        };
+        Ok(Some((*case_idx as i64, value)))
+    }
+    
     fn runtime_error(&self, message: &str) -> RuntimeError {
         let line = self.chunk.get_line(self.ip.saturating_sub(1));
         let stack_trace = self.capture_stack_trace();
@@ -5382,14 +6256,16 @@ impl VM {
     fn capture_stack_trace(&self) -> Vec<StackFrame> {
         let mut trace = Vec::new();
         
-        // 当前执行位置
-        let current_line = self.chunk.get_line(self.ip.saturating_sub(1));
+        // 当前执行位置：出错时 ip 停在出错的指令之后，用它找到出错的表达式
         let current_func = self.get_current_function_name();
+        let current_ip = self.ip.saturating_sub(1);
+        let span = self.chunk.span_at(self.ip);
         trace.push(StackFrame {
             function_name: current_func,
-            file_name: None, // TODO: 添加文件名跟踪
-            line: current_line,
-            column: None,
+            file_name: self.chunk.file_at(current_ip).map(str::to_string),
+            line: span.map_or_else(|| self.chunk.get_line(current_ip), |span| span.line),
+            column: span.map(|span| span.column),
+            len: span.map_or(0, |span| span.len),
         });
         
         // 遍历调用帧（从最近的到最远的）
@@ -5400,15 +6276,21 @@ impl VM {
             } else {
                 0
             };
+            // 行号为 0 的是编译器生成的代码（如顶层对 main 的调用），不显示
+            if frame_line == 0 {
+                continue;
+            }
             
             // 获取函数名（如果可能）
             let func_name = self.get_function_name_at(return_ip);
+            let call_site = self.chunk.span_at(return_ip);
             
             trace.push(StackFrame {
                 function_name: func_name,
-                file_name: None,
-                line: frame_line,
-                column: None,
+                file_name: self.chunk.file_at(return_ip.saturating_sub(1)).map(str::to_string),
+                line: call_site.map_or(frame_line, |site| site.line),
+                column: call_site.map(|site| site.column),
+                len: call_site.map_or(0, |site| site.len),
             });
         }
         
@@ -5417,450 +6299,150 @@ impl VM {
     
     /// 获取当前执行的函数名
     fn get_current_function_name(&self) -> String {
-        // 尝试从调用帧中推断函数名
-        // 如果没有调用帧，说明在顶层代码
-        if self.frames.is_empty() {
-            "<main>".to_string()
-        } else {
-            "<function>".to_string()
+        self.get_function_name_at(self.ip.saturating_sub(1))
+    }
+    
+    /// 获取指令地址 ip 所在的函数名，顶层代码为 "<main>"
+    fn get_function_name_at(&self, ip: usize) -> String {
+        self.chunk.function_at(ip).unwrap_or("<main>").to_string()
+    }
+    
+    /// 把槽位中的值装进新的 cell（MakeCell）
+    fn make_cell(&mut self, slot: usize) {
+        let slot = self.current_base + slot;
+        self.stack[slot] = Value::cell(self.stack[slot]);
+    }
+    
+    /// 读取槽位中 cell 的值（GetCell）
+    fn get_cell(&mut self, slot: usize) -> Result<(), RuntimeError> {
+        let cell = self.stack[self.current_base + slot];
+        let value = match cell.as_cell() {
+            Some(inner) => *inner.lock(),
+            None => return Err(self.runtime_error("Local slot does not hold a cell")),
+        };
+        self.push_fast(value);
+        Ok(())
+    }
+    
+    /// 把栈顶的值写入槽位中的 cell，不弹出（SetCell）
+    fn set_cell(&mut self, slot: usize) -> Result<(), RuntimeError> {
+        let value = *self.peek()?;
+        let cell = self.stack[self.current_base + slot];
+        match cell.as_cell() {
+            Some(inner) => *inner.lock() = value,
+            None => return Err(self.runtime_error("Local slot does not hold a cell")),
         }
+        gc_write_barrier(&cell);
+        Ok(())
+    }
+    
+    /// 按闭包模板的 upvalues 复制当前帧中的槽位，创建闭包（Closure）
+    fn make_closure(&mut self, index: usize) -> Result<(), RuntimeError> {
+        let template = match self.chunk.constants[index].as_function() {
+            Some(f) => f.clone(),
+            None => return Err(self.runtime_error("Closure operand is not a function")),
+        };
+        let captures = template.upvalues.iter()
+            .map(|upvalue| self.stack[self.current_base + upvalue.index as usize])
+            .collect();
+        let closure = Function { captures, ..(*template).clone() };
+        self.push(Value::function(Arc::new(closure)));
+        Ok(())
     }
     
-    /// 获取指定位置的函数名
-    fn get_function_name_at(&self, _ip: usize) -> String {
-        // TODO: 从字节码元数据中获取函数名
-        // 目前返回通用名称
-        "<caller>".to_string()
+    /// 闭包捕获的变量紧跟在参数之后
+    #[inline(always)]
+    fn push_captures(&mut self, func: &Function) {
+        for value in &func.captures {
+            self.push_fast(*value);
+        }
     }
     
     /// 调用闭包函数并返回结果
     /// 用于高阶数组方法（map、filter、reduce 等）
+    ///
+    /// 压入一个返回地址为 `u32::MAX` 的哨兵帧，在同一个解释器循环中执行函数体（支持全部指令），
+    /// 函数返回到哨兵帧时循环退出，回到调用它的原生方法。
+    /// 多余的参数被忽略，缺少的参数为 null
+    ///
+    /// 回调可以再调用回调（如 filter 的回调中使用 filter）；无论回调正常返回还是出错，
+    /// ip、栈基址、值栈、调用帧和异常处理器都恢复到调用前的状态
     fn call_closure(&mut self, func: &Arc<Function>, args: &[Value]) -> Result<Value, RuntimeError> {
-        // 检查参数数量
-        let provided = args.len();
-        let expected = func.arity;
-        
-        // 保存当前状态
+        if self.frames.len() >= MAX_FRAMES {
+            return Err(self.runtime_error("Stack overflow in closure call"));
+        }
         let saved_ip = self.ip;
         let saved_base = self.current_base;
+        let saved_frames = self.frames.len();
+        let saved_handlers = self.exception_handlers.len();
         
-        // 压入函数值（占位，不实际使用）
+        // 占位的函数值，返回时与参数一起移除
         let callee_idx = self.stack.len();
         self.push_fast(Value::null());
-        
-        // 压入参数
-        let base_slot = callee_idx + 1;
-        let args_to_push = provided.min(expected);
-        for i in 0..args_to_push {
-            self.push_fast(args[i].clone());
-        }
-        
-        // 填充缺失参数为 null
-        for _ in args_to_push..expected {
-            self.push_fast(Value::null());
-        }
-        
-        // 检查调用深度
-        if self.frames.len() >= MAX_FRAMES {
-            return Err(self.runtime_error("Stack overflow in closure call"));
+        let expected = func.arity;
+        for i in 0..expected {
+            self.push_fast(args.get(i).copied().unwrap_or(Value::null()));
         }
         
-        // 创建调用帧
         self.frames.push(CallFrame {
-            return_ip: saved_ip as u32,
-            base_slot: base_slot as u16,
+            return_ip: u32::MAX,
+            base_slot: (callee_idx + 1) as u32,
             is_method_call: false,
         });
-        self.current_base = base_slot;
-        
-        // 跳转到函数体
+        self.current_base = callee_idx + 1;
+        self.push_captures(func);
         self.ip = func.chunk_index;
         
-        // 执行直到返回
-        self.run_until_return()?;
-        
-        // 获取返回值
-        let result = self.pop_fast();
+        self.callback_depth += 1;
+        let result = self.execute();
+        self.callback_depth -= 1;
         
-        // 恢复状态（移除占位的函数值）
+        let value = if result.is_ok() { self.pop_fast() } else { Value::null() };
         self.stack.truncate(callee_idx);
+        self.frames.truncate(saved_frames);
+        self.exception_handlers.truncate(saved_handlers);
         self.current_base = saved_base;
         self.ip = saved_ip;
-        
-        Ok(result)
+        result.map(|()| value)
     }
     
-    /// 执行直到当前帧返回
-    fn run_until_return(&mut self) -> Result<(), RuntimeError> {
-        let target_frame_depth = self.frames.len() - 1;
-        
+    /// 运行静态字段的初始化函数（与回调一样在同一个解释器循环中执行，支持全部指令），记下并返回结果
+    ///
+    /// 每个字段的初始化函数只运行一次（见 [`StaticTable`]）：其他协程正在初始化时停靠并等待它的结果；
+    /// 初始值直接、间接或经由其他协程读取正在初始化的字段时报错，而不是无限递归或互相等待
+    fn run_static_initializer(&mut self, key: String, func: &Arc<Function>) -> Result<Value, RuntimeError> {
         loop {
-            let op = self.read_byte();
-            
-            // 检查是否是返回指令
-            if op == 82 { // OpCode::Return
-                let return_value = self.pop_fast();
-                
-                // 弹出调用帧
-                let frame = unsafe { self.frames.pop().unwrap_unchecked() };
-                let truncate_to = if frame.is_method_call {
-                    frame.base_slot as usize
-                } else {
-                    (frame.base_slot as usize).saturating_sub(1)
-                };
-                self.stack.truncate(truncate_to);
-                self.push_fast(return_value);
-                
-                // 恢复指令指针
-                self.ip = frame.return_ip as usize;
-                self.current_base = self.frames.last().map(|f| f.base_slot as usize).unwrap_or(0);
-                
-                // 检查是否回到目标帧
-                if self.frames.len() <= target_frame_depth {
-                    return Ok(());
+            match self.static_fields.claim(&key) {
+                Claim::Ready(value) => return Ok(value),
+                Claim::Claimed => break,
+                Claim::Busy => {
+                    // 等待期间交出根集，初始化函数所在的虚拟机仍可回收
+                    let parked = self.park_for_native(&Value::null(), &[]);
+                    self.static_fields.wait(&key);
+                    drop(parked);
+                }
+                Claim::Cycle(chain) => {
+                    // 在当前虚拟机内的递归显示完整的初始化顺序
+                    let chain = match self.initializing_statics.iter().position(|k| *k == key) {
+                        Some(start) => self.initializing_statics[start..].iter().chain([&key]).cloned().collect(),
+                        None => chain,
+                    };
+                    return Err(self.runtime_error(&recursive_static_message(&chain)));
                 }
-                continue;
             }
-            
-            // 其他指令回退让主循环处理
-            self.ip -= 1;
-            
-            // 执行单条指令
-            self.execute_single_instruction()?;
-            
-            // 检查帧深度
-            if self.frames.len() <= target_frame_depth {
-                return Ok(());
+        }
+        self.initializing_statics.push(key);
+        let result = self.call_closure(func, &[]);
+        let key = self.initializing_statics.pop().unwrap_or_default();
+        match result {
+            Ok(value) => Ok(self.static_fields.finish(&key, value)),
+            Err(error) => {
+                self.static_fields.abandon(&key);
+                Err(error)
             }
         }
     }
     
-    /// 执行单条指令（用于闭包内部调用）
-    fn execute_single_instruction(&mut self) -> Result<(), RuntimeError> {
-        let op = self.read_byte();
-        let opcode = OpCode::from(op);
-        
-        match opcode {
-            OpCode::ConstInt8 => {
-                let value = self.read_byte() as i8 as i128;
-                self.push_fast(Value::int(value));
-            }
-            OpCode::GetLocal | OpCode::GetLocalInt => {
-                let slot = self.read_u16() as usize;
-                let actual_slot = self.current_base + slot;
-                let value = unsafe { self.stack.get_unchecked(actual_slot).clone() };
-                self.push_fast(value);
-            }
-            OpCode::SetLocal => {
-                let slot = self.read_u16() as usize;
-                let value = self.peek()?.clone();
-                let actual_slot = self.current_base + slot;
-                self.stack[actual_slot] = value;
-            }
-            OpCode::GetUpvalue => {
-                let _index = self.read_u16() as usize;
-                self.push_fast(Value::null());
-            }
-            OpCode::SetUpvalue => {
-                let _index = self.read_u16() as usize;
-            }
-            OpCode::CloseUpvalue => {
-                let _slot = self.read_u16() as usize;
-            }
-            OpCode::Const => {
-                let index = self.read_u16() as usize;
-                let value = unsafe { self.chunk.constants.get_unchecked(index).clone() };
-                self.push_fast(value);
-            }
-            OpCode::Pop => {
-                self.pop()?;
-            }
-            OpCode::AddInt => {
-                let b = self.pop_fast();
-                let a = self.pop_fast();
-                let x = unsafe { a.as_int().unwrap_unchecked() };
-                let y = unsafe { b.as_int().unwrap_unchecked() };
-                self.push_fast(Value::int(x + y));
-            }
-            OpCode::SubInt => {
-                let b = self.pop_fast();
-                let a = self.pop_fast();
-                let x = unsafe { a.as_int().unwrap_unchecked() };
-                let y = unsafe { b.as_int().unwrap_unchecked() };
-                self.push_fast(Value::int(x - y));
-            }
-            OpCode::MulInt => {
-                let b = self.pop_fast();
-                let a = self.pop_fast();
-                let x = unsafe { a.as_int().unwrap_unchecked() };
-                let y = unsafe { b.as_int().unwrap_unchecked() };
-                self.push_fast(Value::int(x * y));
-            }
-            OpCode::LeInt => {
-                let b = self.pop_fast();
-                let a = self.pop_fast();
-                let x = unsafe { a.as_int().unwrap_unchecked() };
-                let y = unsafe { b.as_int().unwrap_unchecked() };
-                self.push_fast(Value::bool(x <= y));
-            }
-            OpCode::LtInt => {
-                let b = self.pop_fast();
-                let a = self.pop_fast();
-                let x = unsafe { a.as_int().unwrap_unchecked() };
-                let y = unsafe { b.as_int().unwrap_unchecked() };
-                self.push_fast(Value::bool(x < y));
-            }
-            OpCode::GtInt => {
-                let b = self.pop_fast();
-                let a = self.pop_fast();
-                let x = unsafe { a.as_int().unwrap_unchecked() };
-                let y = unsafe { b.as_int().unwrap_unchecked() };
-                self.push_fast(Value::bool(x > y));
-            }
-            OpCode::GeInt => {
-                let b = self.pop_fast();
-                let a = self.pop_fast();
-                let x = unsafe { a.as_int().unwrap_unchecked() };
-                let y = unsafe { b.as_int().unwrap_unchecked() };
-                self.push_fast(Value::bool(x >= y));
-            }
-            OpCode::EqInt => {
-                let b = self.pop_fast();
-                let a = self.pop_fast();
-                let x = unsafe { a.as_int().unwrap_unchecked() };
-                let y = unsafe { b.as_int().unwrap_unchecked() };
-                self.push_fast(Value::bool(x == y));
-            }
-            OpCode::Add => {
-                let b = self.pop_fast();
-                let a = self.pop_fast();
-                if let (Some(x), Some(y)) = (a.as_int(), b.as_int()) {
-                    self.push_fast(Value::int(x + y));
-                } else if let (Some(x), Some(y)) = (a.as_float(), b.as_float()) {
-                    self.push_fast(Value::float(x + y));
-                } else {
-                    let result = (a + b).map_err(|e| self.runtime_error(&e))?;
-                    self.push_fast(result);
-                }
-            }
-            OpCode::Sub => {
-                let b = self.pop_fast();
-                let a = self.pop_fast();
-                if let (Some(x), Some(y)) = (a.as_int(), b.as_int()) {
-                    self.push_fast(Value::int(x - y));
-                } else if let (Some(x), Some(y)) = (a.as_float(), b.as_float()) {
-                    self.push_fast(Value::float(x - y));
-                } else {
-                    let result = (a - b).map_err(|e| self.runtime_error(&e))?;
-                    self.push_fast(result);
-                }
-            }
-            OpCode::Mul => {
-                let b = self.pop_fast();
-                let a = self.pop_fast();
-                if let (Some(x), Some(y)) = (a.as_int(), b.as_int()) {
-                    self.push_fast(Value::int(x * y));
-                } else if let (Some(x), Some(y)) = (a.as_float(), b.as_float()) {
-                    self.push_fast(Value::float(x * y));
-                } else {
-                    let result = (a * b).map_err(|e| self.runtime_error(&e))?;
-                    self.push_fast(result);
-                }
-            }
-            OpCode::Div => {
-                let b = self.pop_fast();
-                let a = self.pop_fast();
-                if let (Some(x), Some(y)) = (a.as_int(), b.as_int()) {
-                    if y == 0 {
-                        return Err(self.runtime_error("Division by zero"));
-                    }
-                    self.push_fast(Value::int(x / y));
-                } else if let (Some(x), Some(y)) = (a.as_float(), b.as_float()) {
-                    self.push_fast(Value::float(x / y));
-                } else {
-                    let result = (a / b).map_err(|e| self.runtime_error(&e))?;
-                    self.push_fast(result);
-                }
-            }
-            OpCode::Mod => {
-                let b = self.pop_fast();
-                let a = self.pop_fast();
-                if let (Some(x), Some(y)) = (a.as_int(), b.as_int()) {
-                    if y == 0 {
-                        return Err(self.runtime_error("Modulo by zero"));
-                    }
-                    self.push_fast(Value::int(x % y));
-                } else {
-                    let result = (a % b).map_err(|e| self.runtime_error(&e))?;
-                    self.push_fast(result);
-                }
-            }
-            OpCode::Lt => {
-                let b = self.pop_fast();
-                let a = self.pop_fast();
-                if let (Some(x), Some(y)) = (a.as_int(), b.as_int()) {
-                    self.push_fast(Value::bool(x < y));
-                } else {
-                    let result = a.lt(&b).map_err(|e| self.runtime_error(&e))?;
-                    self.push_fast(result);
-                }
-            }
-            OpCode::Le => {
-                let b = self.pop_fast();
-                let a = self.pop_fast();
-                if let (Some(x), Some(y)) = (a.as_int(), b.as_int()) {
-                    self.push_fast(Value::bool(x <= y));
-                } else {
-                    let result = a.le(&b).map_err(|e| self.runtime_error(&e))?;
-                    self.push_fast(result);
-                }
-            }
-            OpCode::Gt => {
-                let b = self.pop_fast();
-                let a = self.pop_fast();
-                if let (Some(x), Some(y)) = (a.as_int(), b.as_int()) {
-                    self.push_fast(Value::bool(x > y));
-                } else {
-                    let result = a.gt(&b).map_err(|e| self.runtime_error(&e))?;
-                    self.push_fast(result);
-                }
-            }
-            OpCode::Ge => {
-                let b = self.pop_fast();
-                let a = self.pop_fast();
-                if let (Some(x), Some(y)) = (a.as_int(), b.as_int()) {
-                    self.push_fast(Value::bool(x >= y));
-                } else {
-                    let result = a.ge(&b).map_err(|e| self.runtime_error(&e))?;
-                    self.push_fast(result);
-                }
-            }
-            OpCode::Eq => {
-                let b = self.pop_fast();
-                let a = self.pop_fast();
-                self.push_fast(a.eq_value(&b));
-            }
-            OpCode::Ne => {
-                let b = self.pop_fast();
-                let a = self.pop_fast();
-                self.push_fast(a.ne_value(&b));
-            }
-            OpCode::Not => {
-                let a = self.pop_fast();
-                self.push_fast(a.not());
-            }
-            OpCode::Neg => {
-                let a = self.pop_fast();
-                if let Some(x) = a.as_int() {
-                    self.push_fast(Value::int(-x));
-                } else if let Some(x) = a.as_float() {
-                    self.push_fast(Value::float(-x));
-                } else {
-                    let result = (-a).map_err(|e| self.runtime_error(&e))?;
-                    self.push_fast(result);
-                }
-            }
-            OpCode::Jump => {
-                let offset = self.read_u16() as usize;
-                self.ip += offset;
-            }
-            OpCode::JumpIfFalse => {
-                let offset = self.read_u16() as usize;
-                let top = unsafe { self.stack.last().unwrap_unchecked() };
-                if !top.is_truthy() {
-                    self.ip += offset;
-                }
-            }
-            OpCode::JumpIfFalsePop => {
-                let offset = self.read_u16() as usize;
-                let condition = self.pop_fast();
-                if !condition.is_truthy() {
-                    self.ip += offset;
-                }
-            }
-            OpCode::JumpIfTrue => {
-                let offset = self.read_u16() as usize;
-                let top = unsafe { self.stack.last().unwrap_unchecked() };
-                if top.is_truthy() {
-                    self.ip += offset;
-                }
-            }
-            OpCode::Loop => {
-                // 安全点：向后跳转（循环）是抢占检查点
-                if self.should_preempt() {
-                    self.clear_preempt();
-                }
-                let offset = self.read_u16() as usize;
-                self.ip -= offset;
-            }
-            OpCode::GetLocalAddInt => {
-                let slot = self.read_u16() as usize;
-                let add_value = self.read_byte() as i8 as i128;
-                let actual_slot = self.current_base + slot;
-                let base_value = unsafe { self.stack.get_unchecked(actual_slot) };
-                let n = unsafe { base_value.as_int().unwrap_unchecked() };
-                self.push_fast(Value::int(n + add_value));
-            }
-            OpCode::GetLocalSubInt => {
-                let slot = self.read_u16() as usize;
-                let sub_value = self.read_byte() as i8 as i128;
-                let actual_slot = self.current_base + slot;
-                let base_value = unsafe { self.stack.get_unchecked(actual_slot) };
-                let n = unsafe { base_value.as_int().unwrap_unchecked() };
-                self.push_fast(Value::int(n - sub_value));
-            }
-            OpCode::GetLocalLeInt => {
-                let slot = self.read_u16() as usize;
-                let cmp_value = self.read_byte() as i8 as i128;
-                let actual_slot = self.current_base + slot;
-                let local = unsafe { self.stack.get_unchecked(actual_slot) };
-                let n = unsafe { local.as_int().unwrap_unchecked() };
-                self.push_fast(Value::bool(n <= cmp_value));
-            }
-            OpCode::Call => {
-                let arg_count = self.read_byte() as usize;
-                let callee_idx = self.stack.len() - arg_count - 1;
-                let callee = self.stack[callee_idx].clone();
-                
-                if let Some(func) = callee.as_function() {
-                    if self.frames.len() >= MAX_FRAMES {
-                        return Err(self.runtime_error("Stack overflow"));
-                    }
-                    let base_slot = callee_idx + 1;
-                    self.frames.push(CallFrame {
-                        return_ip: self.ip as u32,
-                        base_slot: base_slot as u16,
-                        is_method_call: false,
-                    });
-                    self.current_base = base_slot;
-                    self.ip = func.chunk_index;
-                } else {
-                    return Err(self.runtime_error(&format!("Cannot call {}", callee.type_name())));
-                }
-            }
-            OpCode::Return => {
-                // 由 run_until_return 处理
-                self.ip -= 1;
-            }
-            OpCode::Print => {
-                let value = self.pop_fast();
-                print!("{}", value);
-                self.push_fast(Value::null());
-            }
-            OpCode::PrintLn => {
-                let value = self.pop_fast();
-                println!("{}", value);
-                self.push_fast(Value::null());
-            }
-            _ => {
-                // 其他指令暂不支持在闭包中使用
-                return Err(self.runtime_error(&format!("Unsupported opcode {:?} in closure", opcode)));
-            }
-        }
-        
-        Ok(())
-    }
-    
     /// 尝试将值转换为指定类型，失败返回 null
     fn try_cast_value(&self, value: Value, target_type: &str) -> Value {
         match target_type {
@@ -5872,7 +6454,7 @@ impl VM {
                 } else if let Some(b) = value.as_bool() {
                     Value::int(if b { 1 } else { 0 })
                 } else if let Some(s) = value.as_string() {
-                    s.parse::<i128>().map(Value::int).unwrap_or(Value::null())
+                    convert(s, Target::Int, Mode::Strict).unwrap_or_default()
                 } else if let Some(c) = value.as_char() {
                     Value::int(c as i128)
                 } else {
@@ -5885,7 +6467,7 @@ impl VM {
                 } else if let Some(n) = value.as_int() {
                     Value::float(n as f64)
                 } else if let Some(s) = value.as_string() {
-                    s.parse::<f64>().map(Value::float).unwrap_or(Value::null())
+                    convert(s, Target::Float, Mode::Strict).unwrap_or_default()
                 } else {
                     Value::null()
                 }
@@ -5896,7 +6478,7 @@ impl VM {
                 } else if let Some(n) = value.as_int() {
                     n.to_string()
                 } else if let Some(f) = value.as_float() {
-                    f.to_string()
+                    number::format_float(f)
                 } else if let Some(b) = value.as_bool() {
                     b.to_string()
                 } else if let Some(c) = value.as_char() {
@@ -5924,14 +6506,16 @@ impl VM {
                 }
             },
             _ => {
-                // 对于自定义类型，检查类型名称是否匹配
+                // 对于自定义类型，检查类型名称是否匹配（也可以向上转换为父类、interface 和 trait）
                 if let Some(s) = value.as_struct() {
-                    if s.lock().type_name == target_type {
+                    let type_name = s.lock().type_name.clone();
+                    if self.type_conforms(&type_name, target_type) {
                         return value;
                     }
                 }
                 if let Some(c) = value.as_class() {
-                    if c.lock().class_name == target_type {
+                    let class_name = c.lock().class_name.clone();
+                    if self.type_conforms(&class_name, target_type) {
                         return value;
                     }
                 }
@@ -5957,11 +6541,13 @@ impl VM {
             "map" => value.is_map(),
             "function" => value.is_function(),
             _ => {
-                // 检查自定义类型
+                // 检查自定义类型（父类、实现的 interface 和 trait 也算）
                 if let Some(s) = value.as_struct() {
-                    s.lock().type_name == type_name
+                    let struct_name = s.lock().type_name.clone();
+                    self.type_conforms(&struct_name, type_name)
                 } else if let Some(c) = value.as_class() {
-                    c.lock().class_name == type_name
+                    let class_name = c.lock().class_name.clone();
+                    self.type_conforms(&class_name, type_name)
                 } else if let Some(e) = value.as_enum() {
                     e.enum_name == type_name
                 } else {
@@ -5971,6 +6557,12 @@ impl VM {
         }
     }
     
+    /// 运行时类型能否当作 target 使用：同一类型、target 的子类，或实现了名为 target 的 interface/trait
+    fn type_conforms(&self, type_name: &str, target: &str) -> bool {
+        type_name == target
+            || self.vtable_registry.lookup_by_name(type_name).is_some_and(|vtable| vtable.conforms_to(target))
+    }
+    
     /// 尝试调用运算符重载方法
     /// 返回 Some(result) 如果找到重载方法，否则返回 None
     fn try_operator_overload(&mut self, a: &Value, b: &Value, op_name: &str) -> Result<Option<Value>, RuntimeError> {
@@ -5997,7 +6589,7 @@ impl VM {
                 
                 self.frames.push(CallFrame {
                     return_ip: saved_ip as u32,
-                    base_slot: base_slot as u16,
+                    base_slot: base_slot as u32,
                     is_method_call: true,
                 });
                 
@@ -6394,12 +6986,12 @@ impl VM {
             }
             OpCode::PrintLn => {
                 let value = self.pop_fast();
-                println!("{}", value);
+                self.output.print(&format!("{}\n", value));
                 self.push_fast(Value::null());
             }
             OpCode::Print => {
                 let value = self.pop_fast();
-                print!("{}", value);
+                self.output.print(&value.to_string());
                 self.push_fast(Value::null());
             }
             OpCode::Call => {
@@ -6414,10 +7006,11 @@ impl VM {
                     let base_slot = callee_idx + 1;
                     self.frames.push(CallFrame {
                         return_ip: self.ip as u32,
-                        base_slot: base_slot as u16,
+                        base_slot: base_slot as u32,
                         is_method_call: false,
                     });
                     self.current_base = base_slot;
+                    self.push_captures(func);
                     self.ip = func.chunk_index;
                 } else {
                     return Err(self.runtime_error(&format!("Cannot call {}", callee.type_name())));
@@ -6468,6 +7061,22 @@ impl VM {
                 let value = self.stack[actual_slot].clone();
                 self.push_fast(value);
             }
+            OpCode::Closure => {
+                let index = self.read_u16() as usize;
+                self.make_closure(index)?;
+            }
+            OpCode::MakeCell => {
+                let slot = self.read_u16() as usize;
+                self.make_cell(slot);
+            }
+            OpCode::GetCell => {
+                let slot = self.read_u16() as usize;
+                self.get_cell(slot)?;
+            }
+            OpCode::SetCell => {
+                let slot = self.read_u16() as usize;
+                self.set_cell(slot)?;
+            }
             _ => {
                 return Err(self.runtime_error(&format!(
                     "Unsupported instruction {:?} in coroutine step", opcode
@@ -6526,21 +7135,20 @@ impl VM {
         // 查找 VTable
         if let Some(vtable) = self.vtable_registry.lookup_by_name(&type_name) {
             if let Some(func_index) = vtable.get_method_func_index(method_name) {
-                // 获取函数并克隆以避免借用冲突
-                let func = self.chunk.constants[func_index].as_function().cloned();
-                if let Some(func) = func {
-                    return self.call_closure(&func, &args);
+                // 常量是 Copy 的句柄，从副本借用函数，调用期间不占用 self.chunk
+                let func_value = self.chunk.constants[func_index];
+                if let Some(func) = func_value.as_function() {
+                    return self.call_closure(func, &args);
                 }
             }
         }
         
         // 回退到类型定义中的方法查找
-        if let Some(type_info) = self.chunk.get_type(&type_name).cloned() {
-            if let Some(&method_index) = type_info.methods.get(method_name) {
-                let func = self.chunk.constants[method_index as usize].as_function().cloned();
-                if let Some(func) = func {
-                    return self.call_closure(&func, &args);
-                }
+        let method_index = self.chunk.get_type(&type_name).and_then(|t| t.methods.get(method_name).copied());
+        if let Some(method_index) = method_index {
+            let func_value = self.chunk.constants[method_index as usize];
+            if let Some(func) = func_value.as_function() {
+                return self.call_closure(func, &args);
             }
         }
         
@@ -6552,29 +7160,7 @@ impl VM {
     
     /// 初始化类型的 VTable（从类型信息构建）
     pub fn init_type_vtable(&mut self, type_name: &str) -> Option<std::sync::Arc<super::vtable::VTable>> {
-        let type_info = self.chunk.get_type(type_name)?.clone();
-        
-        let type_id = self.vtable_registry.allocate_type_id();
-        let mut vtable = if let Some(parent) = &type_info.parent {
-            // 如果有父类，先获取或创建父类的 VTable
-            if let Some(parent_vtable) = self.vtable_registry.lookup_by_name(parent) {
-                super::vtable::VTable::with_parent(type_id, type_name, (*parent_vtable).clone())
-            } else {
-                super::vtable::VTable::new(type_id, type_name)
-            }
-        } else {
-            super::vtable::VTable::new(type_id, type_name)
-        };
-        
-        // 注册方法
-        for (method_name, method_index) in &type_info.methods {
-            vtable.register_method(method_name.clone(), *method_index as usize);
-        }
-        
-        // 注：trait 实现需要在编译时处理，通过 TraitVTable 注册
-        // 目前 TypeInfo 没有 traits 字段，trait 支持需要后续版本完善
-        
-        Some(self.vtable_registry.register(vtable))
+        self.vtable_registry.init_type(&self.chunk, type_name, &mut Vec::new())
     }
     
     /// 获取值的运行时类型信息
@@ -6607,15 +7193,18 @@ impl VM {
     fn callback_handler_loop(
         chunk: Arc<crate::compiler::bytecode::Chunk>,
         locale: crate::i18n::Locale,
+        inherited: Inherited,
         callback_channel: Arc<crate::stdlib::CallbackChannel>,
     ) {
         use crate::stdlib::{CallbackRequest, CallbackResponse};
 
         loop {
             match callback_channel.request_rx.recv() {
-                Ok(CallbackRequest::Execute { handler, args, response_tx }) => {
-                    // 执行回调函数
-                    let result = Self::execute_callback(chunk.clone(), locale, handler, args);
+                Ok(CallbackRequest::Execute { handler, args, request_id, response_tx }) => {
+                    // 执行回调函数，期间的日志记录附加请求 ID
+                    let result = crate::stdlib::log::with_request_id(request_id, || {
+                        Self::execute_callback(chunk.clone(), locale, inherited.clone(), handler, args)
+                    });
 
                     // 发送响应（忽略错误）
                     let _ = response_tx.send(result);
@@ -6632,25 +7221,45 @@ impl VM {
         }
     }
 
+    /// 获取回调通道，第一次使用时启动处理线程
+    fn callback_channel(&mut self) -> Arc<crate::stdlib::CallbackChannel> {
+        if let Some(channel) = &self.callback_channel {
+            return channel.clone();
+        }
+        let channel = Arc::new(crate::stdlib::CallbackChannel::new());
+        let chunk = self.chunk.clone();
+        let locale = self.locale;
+        let inherited = self.inherited();
+        let handler_channel = channel.clone();
+        std::thread::spawn(move || {
+            Self::callback_handler_loop(chunk, locale, inherited, handler_channel);
+        });
+        self.callback_channel = Some(channel.clone());
+        channel
+    }
+    
+    /// 进入会回调 Q 代码的原生方法前停靠：交出根集（栈、静态字段和调用参数），
+    /// 执行回调的虚拟机因此可以回收。未启用 GC 时返回 None
+    fn park_for_native(&self, receiver: &Value, args: &[Value]) -> Option<super::gc::ParkGuard> {
+        self.gc_mutator.as_ref()?;
+        let mut roots = vec![*receiver];
+        roots.extend_from_slice(args);
+        self.scan_gc_roots(|value| roots.push(*value));
+        Some(super::gc::park_mutator(roots))
+    }
+    
     /// 执行回调函数
     fn execute_callback(
         chunk: Arc<crate::compiler::bytecode::Chunk>,
         locale: crate::i18n::Locale,
+        inherited: Inherited,
         handler: Value,
         args: Vec<Value>,
     ) -> crate::stdlib::CallbackResponse {
         use crate::stdlib::CallbackResponse;
 
         // 创建新的 VM 实例来执行回调
-        let mut vm = VM::new(chunk, locale);
-
-        // 将 handler 压入栈
-        vm.push(handler.clone());
-
-        // 将参数压入栈
-        for arg in &args {
-            vm.push(arg.clone());
-        }
+        let mut vm = VM::inheriting(chunk, locale, inherited);
 
         // 获取函数信息
         let func = match handler.as_function() {
@@ -6660,14 +7269,28 @@ impl VM {
             }
         };
 
+        // 将 handler 压入栈
+        vm.push(handler.clone());
+
+        // 与 call_closure 一样按形参个数压入参数：多余的参数被忽略，缺少的参数为 null，
+        // 捕获的变量紧跟在形参之后
+        for i in 0..func.arity {
+            vm.push(args.get(i).copied().unwrap_or(Value::null()));
+        }
+
         // 栈布局: [function, arg1, arg2, ...]
         // base_slot 应该指向第一个参数的位置（function + 1）
-        let callee_idx = vm.stack.len() - args.len() - 1;
+        let callee_idx = vm.stack.len() - func.arity - 1;
         let base_slot = callee_idx + 1;
 
-        // 不使用帧 - 直接设置当前base和IP
-        // 函数将作为顶层执行，Return时frames为空会自动停止
+        // 函数体在哨兵帧中执行：函数体中的调用返回后按哨兵帧恢复栈基址
+        vm.frames.push(CallFrame {
+            return_ip: u32::MAX,
+            base_slot: base_slot as u32,
+            is_method_call: false,
+        });
         vm.current_base = base_slot;
+        vm.push_captures(&func);
         vm.ip = func.chunk_index;
 
         // 运行VM，当返回到sentinel帧时会自动停止（因为return_ip == u32::MAX）
@@ -6679,8 +7302,12 @@ impl VM {
                 } else {
                     Value::null()
                 };
+                // 返回值交给调用方线程，之后的回调回收时不能释放它
+                gc_escape(&return_value);
                 CallbackResponse::Success(return_value)
             }
+            // 回调中的 Os.exit 交给等待回调的原生方法，在调用它的虚拟机中继续展开
+            Err(RuntimeError { exit_code: Some(code), .. }) => CallbackResponse::Error(crate::stdlib::os::exit_request(code)),
             Err(e) => CallbackResponse::Error(e.message),
         }
     }
@@ -6750,8 +7377,146 @@ mod tests {
         assert!(run_code("var i = 0\nfor i < 3 { println(i)\ni = i + 1 }").is_ok());
         // 无限循环 + break
         assert!(run_code("var i = 0\nfor { if i >= 3 { break }\nprintln(i)\ni = i + 1 }").is_ok());
+        // C 风格 for 循环中的 continue 先执行递增部分（包括带标签的 continue），
+        // break/continue 跳出循环体时弹出循环体中定义的局部变量
+        let code = r#"
+var odd = 0
+for var i = 0; i < 10; i += 1 {
+    var t = i * 10
+    if i % 2 == 0 { continue }
+    odd += t
+}
+if odd != 250 { throw "continue: " + odd }
+var sum = 0
+for x in [1, 2, 3, 4] {
+    var y = x
+    for z in [1, 2] {
+        if x == 3 { break }
+    }
+    if y == 4 { break }
+    sum += y
+}
+if sum != 6 { throw "break: " + sum }
+var pairs = 0
+outer: for var i = 0; i < 3; i += 1 {
+    for var j = 0; j < 3; j += 1 {
+        if j - i > 0 { continue outer }
+        pairs += 1
+    }
+}
+if pairs != 6 { throw "continue outer: " + pairs }
+"#;
+        let result = run_code(code);
+        assert!(result.is_ok(), "{:?}", result.err());
     }
     
+    #[test]
+    fn test_for_in_pairs() {
+        let code = r#"
+class Inventory {
+    var names: string[]
+    func init() {
+        this.names = ["apple", "pear"]
+    }
+    func entries() string[][] {
+        return [["a", this.names[0]], ["p", this.names[1]]]
+    }
+}
+var ages = {"ann": 30, "bob": 40}
+var total = 0
+var names = ""
+for name, age in ages {
+    total += age
+    names += name
+}
+if total != 70 || names.len() != 6 { throw "map: " + total }
+for entry in {"k": 1} {
+    if entry[0] != "k" || entry[1] != 1 { throw "map entry" }
+}
+var sum = 0
+for a, b in [[1, 2], [3, 4]] {
+    sum += a * b
+}
+if sum != 14 { throw "pairs: " + sum }
+var joined = ""
+for key, name in new Inventory() {
+    joined += key + "=" + name + ";"
+}
+if joined != "a=apple;p=pear;" { throw "entries: " + joined }
+var count = 0
+for item in new Inventory() {
+    if item[0] == "p" && item[1] == "pear" { count += 1 }
+}
+if count != 1 { throw "entries element" }
+"#;
+        let result = run_code(code);
+        assert!(result.is_ok(), "{:?}", result.err());
+
+        let err = run_code("for a, b in [[1, 2], [3, 4], [5, 6], 7] {\n}").unwrap_err();
+        assert_eq!(err.message, "for-in with two variables requires pairs; element 3 is an int");
+        let err = run_code("for a, b in [[1, 2, 3]] {\n}").unwrap_err();
+        assert_eq!(err.message, "for-in with two variables requires pairs; element 0 is an array of 3 elements");
+    }
+
+    #[test]
+    fn test_exception_unwinds_frames() {
+        // 被调函数抛出的异常在调用方捕获后，catch 块中的局部变量仍按调用方的栈基址访问
+        let code = r#"
+func fail(depth: int) {
+    var padding = depth * 2
+    if depth == 0 { throw new IllegalStateException("deep") }
+    fail(depth - 1)
+}
+func run() string {
+    var before = "kept"
+    var message = ""
+    try {
+        fail(3)
+    } catch (e: IllegalStateException) {
+        message = e.message
+    }
+    return before + ":" + message
+}
+var result = run()
+if result != "kept:deep" { throw "unwind: " + result }
+"#;
+        let result = run_code(code);
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+
+    #[test]
+    fn test_panic_payload_and_recovering() {
+        // onPanic 块取得原始的值，之后从 recovering 语句之后继续；catch 块不处理 panic
+        let code = r#"
+func fail(depth: int) {
+    if depth == 0 { panic({"code": 42}) }
+    fail(depth - 1)
+}
+var outside = recover()
+var code = 0
+recovering {
+    try {
+        fail(3)
+    } catch (e: Exception) {
+        code = -1
+    }
+    code = -2
+} onPanic(e) {
+    code = recover()["code"] + e["code"]
+}
+if outside != null || code != 84 { throw "recovering: ${outside} ${code}" }
+"#;
+        let result = run_code(code);
+        assert!(result.is_ok(), "{:?}", result.err());
+
+        // 没有被处理的 panic：错误中保留原始的值
+        let err = run_code("try {\n    panic({\"code\": 7})\n} catch (e: Exception) {\n}").unwrap_err();
+        assert_eq!(err.message, "Panic: {\"code\": 7}");
+        let payload = err.panic_payload.expect("panic payload");
+        assert_eq!(payload.as_map().unwrap().lock().get("code").and_then(|v| v.as_int()), Some(7));
+        assert!(run_code("[1].get(5)").unwrap_err().panic_payload.is_none());
+    }
+
     #[test]
     fn test_scope() {
         // 作用域测试
@@ -6811,6 +7576,289 @@ println(double(add_one(5)))
         assert!(run_code("var x = true && true\nprintln(x)").is_ok());
     }
     
+    #[test]
+    fn test_deep_recursion_with_many_locals() {
+        // 每帧 1500 个局部变量、递归 50 层：栈基址超过 65535，不能被截断
+        let mut code = String::from("func deep(n: int) int {\n");
+        for i in 0..1500 {
+            code.push_str(&format!("    var v{} = n\n", i));
+        }
+        code.push_str("    if n == 0 { return 0 }\n");
+        code.push_str("    return deep(n - 1) + v1499\n}\n");
+        code.push_str("if deep(50) != 1275 { throw \"stack corrupted\" }\n");
+        assert!(run_code(&code).is_ok());
+    }
+    
+    #[test]
+    fn test_string_interpolation() {
+        let code = r#"
+var x = 5
+var s = "x = ${x}, next = ${x + 1}, nested = ${"<${x}>"}, esc = \${x}"
+if s != "x = 5, next = 6, nested = <5>, esc = \${x}" { throw s }
+"#;
+        assert!(run_code(code).is_ok());
+    }
+    
+    #[test]
+    fn test_std_fs_functions_and_exceptions() {
+        let dir = std::env::temp_dir().join(format!("qlang-vm-fs-{}", std::process::id()));
+        let dir = dir.to_string_lossy().replace('\\', "/");
+        let code = format!(r#"
+import std.fs.{{readFile, writeFile, appendFile, exists, mkdir, readDir, remove}}
+mkdir("{dir}")
+var path = "{dir}/a.txt"
+writeFile(path, "one")
+appendFile(path, ",two")
+if readFile(path) != "one,two" {{ throw "bad content" }}
+if readDir("{dir}")[0] != "a.txt" {{ throw "bad listing" }}
+remove(path)
+if exists(path) {{ throw "not removed" }}
+var caught = ""
+try {{
+    readFile(path)
+}} catch (e:FileNotFoundException) {{
+    caught = e.message
+}}
+if caught == "" {{ throw "missing file was not thrown" }}
+var f = new File("{dir}/b.txt", "w")
+f.write("hi")
+f.close()
+"#);
+        let result = run_code(&code);
+        let _ = std::fs::remove_dir_all(&dir);
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+    
+    #[test]
+    fn test_std_json_parse_and_stringify() {
+        let code = r#"
+import std.json
+var data = Json.parse("{\"name\": \"q\", \"tags\": [1, 2.5, null]}")
+if data["name"] != "q" { throw "bad name" }
+if data["tags"][0] != 1 { throw "bad tag" }
+if Json::stringify(data["tags"]) != "[1,2.5,null]" { throw "bad stringify" }
+struct Point {
+    x: int
+    y: int
+}
+var p = Point { x: 1, y: 2 }
+if Json.stringify(p) != "{\"x\":1,\"y\":2}" { throw "bad struct" }
+var caught = ""
+try {
+    Json.parse("[1,")
+} catch (e:IllegalArgumentException) {
+    caught = e.message
+}
+if caught == "" { throw "parse error was not thrown" }
+var arr = [1]
+arr.push(arr)
+try {
+    Json.stringify(arr)
+    throw "cycle was not detected"
+} catch (e:IllegalArgumentException) {
+}
+"#;
+        let result = run_code(code);
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+    
+    #[test]
+    fn test_output_capture_follows_goroutines() {
+        use crate::lexer::Scanner;
+        use crate::parser::Parser;
+        use crate::compiler::Compiler;
+
+        let code = r#"
+func worker(id: int, started: chan<int>, release: chan<int>) {
+    println(id)
+    started.send(id)
+    release.receive()
+    println(id + 1)
+}
+var started = chan<int>()
+var release = chan<int>(1)
+print("main ")
+println(1)
+go worker(7, started, release)
+started.receive()
+"#;
+        let program = Parser::new(Scanner::new(code).scan_tokens(), Locale::En).parse().unwrap();
+        let chunk = Compiler::new(Locale::En).compile(&program).unwrap();
+        let capture = super::super::output::Capture::new();
+        let mut vm = VM::new(Arc::new(chunk), Locale::En);
+        vm.set_output(Output::Capture(capture.clone()));
+        vm.run().unwrap();
+        assert_eq!(capture.finish(), "main 1\n7\n");
+
+        // 放行协程：测试已结束，它之后的输出记为迟到的输出
+        for channel in vm.stack.iter().filter_map(|v| v.as_channel()) {
+            if let Some(sender) = channel.lock().sender.lock().as_ref() {
+                let _ = sender.try_send(Value::int(0));
+            }
+        }
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        let mut late = String::new();
+        while late.is_empty() && std::time::Instant::now() < deadline {
+            std::thread::yield_now();
+            late = capture.take_late();
+        }
+        assert_eq!(late, "8\n");
+    }
+    
+    #[test]
+    fn test_goroutines_share_atomic_counter() {
+        // 协程使用完整的解释器循环：方法调用、条件循环和 go 语句后的局部变量槽位都要正确
+        let code = r#"
+import std.sync.{Atomic, AtomicFlag}
+func worker(counter: Atomic, done: Atomic) {
+    for var i = 0; i < 1000; i = i + 1 {
+        counter.add(1)
+    }
+    done.add(1)
+}
+var counter = new Atomic(0)
+var done = new Atomic(0)
+for var g = 0; g < 100; g = g + 1 {
+    go worker(counter, done)
+}
+var flag = new AtomicFlag()
+for done.get() < 100 {
+}
+if counter.get() != 100000 { throw "lost increments" }
+if !counter.compareAndSwap(100000, 1) { throw "cas failed" }
+if counter.swap(2) != 1 { throw "bad swap" }
+if flag.testAndSet() { throw "flag should start clear" }
+"#;
+        let result = run_code(code);
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+    
+    #[test]
+    fn test_select_fan_in_and_default() {
+        let code = r#"
+func produce(ch: chan<int>, base: int) {
+    for var i = 0; i < 5; i = i + 1 {
+        ch.send(base + i)
+    }
+}
+func consume(ch: chan<int>, done: chan<int>) {
+    var total = 0
+    for var i = 0; i < 3; i = i + 1 {
+        total = total + ch.receive()
+    }
+    done.send(total)
+}
+var a = chan<int>()
+var b = chan<int>()
+go produce(a, 100)
+go produce(b, 200)
+var sum = 0
+for var i = 0; i < 10; i = i + 1 {
+    select {
+        case var x = a.receive() => sum = sum + x
+        case var y = b.receive() => sum = sum + y
+    }
+}
+if sum != 1520 { throw "bad fan-in sum ${sum}" }
+
+var idle = chan<int>()
+var polled = false
+select {
+    case idle.receive() => { throw "nothing was sent" }
+    default => polled = true
+}
+if polled != true { throw "default branch not taken" }
+
+var out = chan<int>()
+var done = chan<int>()
+go consume(out, done)
+for var i = 1; i <= 3; i = i + 1 {
+    select {
+        case out.send(i) => {}
+    }
+}
+if done.receive() != 6 { throw "bad send total" }
+"#;
+        let result = run_code(code);
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+    
+    #[test]
+    fn test_select_picks_ready_cases_randomly() {
+        // 两个通道始终同时就绪时，两个分支都应被选中，不能总是选第一个
+        let code = r#"
+func fill(ch: chan<int>) {
+    for {
+        ch.send(1)
+    }
+}
+var a = chan<int>()
+var b = chan<int>()
+go fill(a)
+go fill(b)
+var fromA = 0
+var fromB = 0
+for var i = 0; i < 400; i = i + 1 {
+    select {
+        case a.receive() => fromA = fromA + 1
+        case b.receive() => fromB = fromB + 1
+    }
+}
+if fromA < 50 || fromB < 50 { throw "starved: ${fromA} vs ${fromB}" }
+"#;
+        let result = run_code(code);
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+    
+    #[test]
+    fn test_buffered_channel_close_and_iteration() {
+        let code = r#"
+func produce(ch: chan<int>) {
+    for var i = 1; i <= 4; i = i + 1 {
+        ch.send(i)
+    }
+    ch.close()
+}
+// 缓冲区未满时发送不阻塞
+var buf = chan<int>(2)
+buf.send(1)
+buf.send(2)
+if buf.receive() + buf.receive() != 3 { throw "bad buffered values" }
+
+// for-in 接收到通道关闭为止
+var jobs = chan<int>(1)
+go produce(jobs)
+var total = 0
+for x in jobs {
+    total = total + x
+}
+if total != 10 { throw "bad iteration total ${total}" }
+if jobs.isClosed() != true { throw "channel should be closed" }
+
+// 关闭后先取完缓冲区，再收到 null
+var last = chan<string>(2)
+last.send("a")
+last.close()
+if last.receive() != "a" { throw "buffered value lost on close" }
+if last.receive() != null { throw "closed channel should yield null" }
+
+var caught = 0
+try {
+    last.send("b")
+} catch (e: IllegalStateException) {
+    caught = caught + 1
+}
+try {
+    last.close()
+} catch (e: IllegalStateException) {
+    caught = caught + 1
+}
+if caught != 2 { throw "closed channel errors not catchable" }
+"#;
+        let result = run_code(code);
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+    
     #[test]
     fn test_logical_short_circuit_or() {
         // || 短路测试：true || 不会执行右侧
@@ -6818,4 +7866,846 @@ println(double(add_one(5)))
         assert!(run_code("var x = false || true\nprintln(x)").is_ok());
         assert!(run_code("var x = false || false\nprintln(x)").is_ok());
     }
+    
+    #[test]
+    fn test_static_initializer_runs_once_across_threads() {
+        use crate::lexer::Scanner;
+        use crate::parser::Parser;
+        use crate::compiler::Compiler;
+        use std::sync::Barrier;
+        use std::sync::atomic::Ordering;
+
+        // 每个线程一个虚拟机，共享同一张静态字段表（与协程相同）
+        let source = "class Config {\n    static var instance: int = host.load()\n}\nif Config::instance != 100 {\n    panic(\"wrong value\")\n}\n";
+        let tokens = Scanner::new(source).scan_tokens();
+        let program = Parser::new(tokens, Locale::En).parse().unwrap();
+        let mut compiler = Compiler::new(Locale::En);
+        compiler.set_host_functions(["host.load".to_string()]);
+        let chunk = Arc::new(compiler.compile(&program).unwrap());
+        let calls = Arc::new(AtomicUsize::new(0));
+        let load: HostFn = {
+            let calls = calls.clone();
+            Arc::new(move |_: &[Value]| {
+                let n = calls.fetch_add(1, Ordering::SeqCst);
+                // 其他线程在此期间读取，等待而不是再次运行初始化函数
+                std::thread::sleep(std::time::Duration::from_millis(20));
+                Ok(Value::int(100 + n as i128))
+            })
+        };
+        let statics = StaticFields::default();
+        let barrier = Arc::new(Barrier::new(8));
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let (chunk, load, statics, barrier) = (chunk.clone(), load.clone(), statics.clone(), barrier.clone());
+                std::thread::spawn(move || {
+                    let mut vm = VM::new(chunk, Locale::En);
+                    vm.set_host_functions(Arc::new([load]));
+                    vm.static_fields = statics;
+                    barrier.wait();
+                    vm.run().map_err(|e| e.message)
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap().unwrap();
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(statics.get("Config::instance").and_then(|v| v.as_int()), Some(100));
+    }
+
+    #[test]
+    fn test_failed_static_initializer_is_retried() {
+        // 初始化函数出错时不记下结果，下一次读取重新运行
+        let code = "import std.lang.Exception\nclass Tries {\n    static var count: int = 0\n}\nfunc load() int {\n    Tries::count += 1\n    if Tries::count == 1 {\n        throw new Exception(\"first\")\n    }\n    return Tries::count\n}\nclass Config {\n    static var value: int = load()\n}\ntry {\n    var v = Config::value\n} catch (e: Exception) {\n}\nif Config::value != 2 || Config::value != 2 {\n    panic(\"not retried\")\n}\n";
+        run_code(code).unwrap();
+    }
+
+    #[test]
+    fn test_consts_and_struct_statics() {
+        // 常量声明在另一个文件里：依赖文件的语句排在主文件前面合并
+        let dependency = r#"
+const MAX_RETRIES = 3
+const LIMIT = MAX_RETRIES * 10 + 2
+"#;
+        let main = r#"
+class Color {
+    const RED = 1
+    const GREEN = RED + 1
+}
+
+struct Point {
+    x: int
+    y: int
+    const ORIGIN_X = LIMIT
+    static func origin() Point {
+        return Point { x: Point::ORIGIN_X, y: 0 }
+    }
+}
+
+var name = ""
+match 2 {
+    Color::RED => { name = "red" }
+    Color::GREEN => { name = "green" }
+    _ => { name = "other" }
+}
+if name != "green" { throw name }
+
+var bucket = 0
+match 32 {
+    LIMIT => { bucket = 1 }
+    _ => { bucket = 2 }
+}
+if bucket != 1 { throw "const pattern did not match" }
+
+var p = Point::origin()
+if p.x != 32 { throw "static factory" }
+if Color::GREEN != 2 { throw "class const" }
+"#;
+        use crate::lexer::Scanner;
+        use crate::parser::Parser;
+        use crate::compiler::Compiler;
+
+        let parse = |source: &str| Parser::new(Scanner::new(source).scan_tokens(), Locale::En).parse().unwrap();
+        let mut program = parse(dependency);
+        program.statements.extend(parse(main).statements);
+        let chunk = Compiler::new(Locale::En).compile(&program).unwrap();
+        let result = VM::new(Arc::new(chunk), Locale::En).run();
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+    
+    #[test]
+    fn test_compound_assignment_targets() {
+        let code = r#"
+class Counter {
+    var count: int
+    func init() {
+        this.count = 1
+    }
+    func bump() {
+        this.count += 2
+        this.count *= 3
+    }
+}
+
+var i = 0
+i += 1
+i -= 3
+i *= -4
+i /= 2
+i %= 3
+i <<= 4
+i >>= 1
+i |= 7
+i &= 13
+i ^= 1
+if i != 12 { throw "local: " + i }
+
+var s = "a"
+s += "b"
+if s != "ab" { throw s }
+
+var c = new Counter()
+c.bump()
+if c.count != 9 { throw "field" }
+
+// 对象和索引表达式只求值一次
+func pick(calls: int[], n: int) int {
+    calls[0] += 1
+    return n
+}
+var calls = [0]
+var xs = [1, 2, 3]
+xs[pick(calls, 1)] += 10
+if xs[1] != 12 || calls[0] != 1 { throw "index" }
+
+var m = {"a": 1}
+m["a"] += 5
+m["a"] <<= 1
+if m["a"] != 12 { throw "map" }
+"#;
+        let result = run_code(code);
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+    
+    #[test]
+    fn test_closures_capture_locals() {
+        let code = r#"
+class Box {
+    var n: int
+    func init(n: int) {
+        this.n = n
+    }
+    func adder() func(int) int {
+        return func(x: int) int { return x + this.n }
+    }
+}
+
+// 被捕获且被赋值的局部变量由闭包和外层函数共享
+func makeCounter() func() int {
+    var count = 0
+    var inc = func() int {
+        count += 1
+        return count
+    }
+    inc()
+    if count != 1 { throw "shared: " + count }
+    return inc
+}
+
+func run() {
+    var next = makeCounter()
+    next()
+    if next() != 3 { throw "counter" }
+
+    // 每次迭代的循环变量是新的变量
+    var fs = []
+    for x in [1, 2, 3] {
+        fs.push(func() int { return x * 10 })
+    }
+    var seen = 0
+    for f in fs {
+        seen = seen * 100 + f()
+    }
+    if seen != 102030 { throw "for-in: " + seen }
+
+    // 嵌套闭包经由外层闭包捕获
+    var base = 5
+    var outer = func(a: int) int {
+        var inner = func(b: int) int { return a + b + base }
+        return inner(1)
+    }
+    if outer(2) != 8 { throw "nested" }
+
+    // 回调中修改捕获的变量
+    var total = 0
+    var doubled = [1, 2, 3].collect(func(v: int) int {
+        total += v
+        return v * 2
+    })
+    if total != 6 || doubled[2] != 6 { throw "callback" }
+
+    if new Box(7).adder()(1) != 8 { throw "this" }
+}
+
+run()
+"#;
+        let result = run_code(code);
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+    
+    #[test]
+    fn test_callbacks_are_reentrant() {
+        let code = r#"
+class Grid {
+    var rows: int[][]
+    func init(rows: int[][]) {
+        this.rows = rows
+    }
+    func evens(row: int[]) int[] {
+        return row.filter(func(x: int) bool { return x % 2 == 0 })
+    }
+    func evenCounts() int[] {
+        return this.rows.collect(func(row: int[]) int { return this.evens(row).len() })
+    }
+}
+
+func check(name: string, actual: string, expected: string) {
+    if (actual != expected) { throw new Exception(name + ": " + actual) }
+}
+
+func show(xs: int[]) string {
+    var out = ""
+    for x in xs {
+        out = out + " ${x}"
+    }
+    return out
 }
+
+func run() {
+    var grid = [[1, 2, 3], [4, 5, 6], [7, 8, 9]]
+
+    // 回调中再调用高阶方法
+    var rows = grid.filter(func(row: int[]) bool {
+        return row.filter(func(x: int) bool { return x % 2 == 0 }).len() > 1
+    })
+    check("nested filter", "${rows.len()}", "1")
+    var sums = grid.collect(func(row: int[]) int {
+        return row.collect(func(x: int) int { return x * 2 }).reduce(func(a: int, b: int) int { return a + b }, 0)
+    })
+    check("collect reduce", show(sums), " 12 30 48")
+    var deep = grid.filter(func(row: int[]) bool {
+        return row.some(func(x: int) bool {
+            return row.filter(func(y: int) bool { return y > x }).len() == 2
+        })
+    })
+    check("three levels", "${deep.len()}", "3")
+
+    // 回调调用另一个闭包
+    var isEven = func(x: int) bool { return [x].filter(func(y: int) bool { return y % 2 == 0 }).len() == 1 }
+    var counts = grid.collect(func(row: int[]) int { return row.filter(isEven).len() })
+    check("closure calling closure", show(counts), " 1 2 1")
+
+    // 回调中调用实例方法，方法中又使用回调
+    var g = new Grid(grid)
+    check("method in callback", show(grid.collect(func(row: int[]) int { return g.evens(row).len() })), " 1 2 1")
+    check("callback in method", show(g.evenCounts()), " 1 2 1")
+
+    // 回调中抛出的异常由回调外的 try 捕获，之后的调用不受影响
+    var caught = ""
+    try {
+        grid.collect(func(row: int[]) int {
+            return row.filter(func(x: int) bool {
+                if x == 5 { throw new Exception("five") }
+                return true
+            }).len()
+        })
+    } catch (e: Exception) {
+        caught = e.getMessage()
+    }
+    check("throw across callbacks", "${caught}", "five")
+    check("after throw", show(grid.collect(func(row: int[]) int { return row.len() })), " 3 3 3")
+
+    // 回调内部捕获内层回调的异常
+    var handled = grid.collect(func(row: int[]) int {
+        try {
+            return row.filter(func(x: int) bool {
+                if x > 7 { throw new Exception("big") }
+                return x > 1
+            }).len()
+        } catch (e: Exception) {
+            return -1
+        }
+    })
+    check("caught inside callback", show(handled), " 2 3 -1")
+
+    var thrown = 0
+    for i in 0..3 {
+        try {
+            [1, 2, 3].forEach(func(x: int) { if x == 2 { throw new Exception("stop") } })
+        } catch (e: Exception) {
+            thrown += 1
+        }
+    }
+    check("repeated throws", "${thrown}", "3")
+}
+
+run()
+"#;
+        let result = run_code(code);
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+
+    #[test]
+    fn test_huge_allocations_are_catchable() {
+        let code = r#"
+var refused = 0
+try {
+    var s = "abc".repeat(1000000000000000)
+} catch (e: RuntimeException) {
+    if e.message != "allocation of 3000000000000000 bytes refused" { throw e.message }
+    refused = refused + 1
+}
+try {
+    var xs = (0..10000000000000).toArray()
+} catch (e: RuntimeException) {
+    if !e.message.startsWith("allocation of ") { throw e.message }
+    refused = refused + 1
+}
+try {
+    var xs = [1, 2].concat((0..10000000000000).toArray())
+} catch (e: RuntimeException) {
+    if !e.message.startsWith("allocation of ") { throw e.message }
+    refused = refused + 1
+}
+try {
+    var s = "x".repeat(-1)
+} catch (e: IllegalArgumentException) {
+    refused = refused + 1
+}
+if refused != 4 { throw "refused " + refused }
+"#;
+        let result = run_code(code);
+        assert!(result.is_ok(), "{:?}", result.err());
+
+        // 在上限之内的大分配照常进行：100MB 的字符串和数组
+        let code = r#"
+var s = "x".repeat(100000000)
+var xs = (0..12500000).toArray()
+if xs[12499999] != 12499999 { throw "toArray" }
+"#;
+        let result = run_code(code);
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+
+    #[test]
+    fn test_join_and_concat() {
+        let code = r#"
+var empty = []
+if empty.join(",") != "" { throw "empty array" }
+if ["a", "b", "c"].join("") != "abc" { throw "empty separator" }
+if ["solo"].join(", ") != "solo" { throw "single element" }
+var mixed = [1, "x", null, 2.5, true, [1, 2]].join(" | ")
+if mixed != "1 | x | null | 2.5 | true | [1, 2]" { throw mixed }
+if "ab".repeat(3) != "ababab" { throw "repeat" }
+if "ab".repeat(0) != "" { throw "repeat 0" }
+"#;
+        let result = run_code(code);
+        assert!(result.is_ok(), "{:?}", result.err());
+
+        // 100 万个短字符串：结果长度事先算出，只分配一次
+        let parts: Vec<Value> = (0..1_000_000).map(|i| Value::string(format!("s{}", i % 10))).collect();
+        let joined = crate::vm::alloc::join_values(&parts, ",").unwrap();
+        assert_eq!(joined.len(), 2 * 1_000_000 + 999_999);
+        assert_eq!(joined.capacity(), joined.len());
+
+        // 两半相加得到 10MB 的字符串
+        let code = r#"
+var s = "0123456789"
+for s.len() < 10000000 {
+    s = s + s
+}
+if s.len() != 10485760 { throw "length " + s.len() }
+"#;
+        let result = run_code(code);
+        assert!(result.is_ok(), "{:?}", result.err());
+        // 每次相加只按结果长度分配一次
+        let half = "0123456789".repeat(524_288);
+        let doubled = crate::vm::alloc::concat_str(&half, &half).unwrap();
+        assert_eq!(doubled.len(), 10_485_760);
+        assert_eq!(doubled.capacity(), doubled.len());
+    }
+    
+    #[test]
+    fn test_conditional_expression() {
+        let code = r#"
+func sign(n: int) string {
+    return n > 0 ? "pos" : n < 0 ? "neg" : "zero"
+}
+if sign(3) != "pos" || sign(-3) != "neg" || sign(0) != "zero" { throw "sign" }
+
+// 只求值选中的分支
+var calls = [0]
+func bump(counter: int[]) int {
+    counter[0] += 1
+    return counter[0]
+}
+var v = calls[0] == 0 ? 10 : bump(calls)
+if v != 10 || calls[0] != 0 { throw "evaluated the other branch" }
+
+var name = null
+var label = name == null ? "anon" : "named"
+if label != "anon" { throw label }
+"#;
+        let result = run_code(code);
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+    
+    #[test]
+    fn test_default_sort_total_order() {
+        let code = r#"
+var nan = 0.0 / 0.0
+var inf = 1.0 / 0.0
+var nums = [3.5, nan, -inf, 2, inf, -0.0, 0.0]
+nums.sort()
+if nums[0] != -inf || nums[4] != 3.5 || nums[5] != inf * 1 { throw "numbers" }
+if nums[6] == nums[6] { throw "NaN should sort last" }
+
+// 相等的 1 和 1.0 保持原有顺序
+var mixed = [1.0, 1, 0, 1.0, 1]
+mixed.sort()
+if !(mixed[1] is float) || !(mixed[2] is int) || !(mixed[3] is float) || !(mixed[4] is int) { throw "unstable" }
+
+var nested = [[2, 1], [1, 5, 0], [1, 5], [], [1, 4, 9]]
+nested.sort()
+if nested != [[], [1, 4, 9], [1, 5], [1, 5, 0], [2, 1]] { throw "nested" }
+
+var words = ["b", "é", "a", "B"]
+words.sort()
+if words != ["B", "a", "b", "é"] { throw "words" }
+"#;
+        let result = run_code(code);
+        assert!(result.is_ok(), "{:?}", result.err());
+
+        let err = run_code("var a = [1, \"a\", 2]\na.sort()\n").unwrap_err();
+        assert!(err.message.contains("Cannot order int and string") || err.message.contains("Cannot order string and int"), "{}", err.message);
+        let err = run_code("var a = [[1, null], [1, 2]]\na.sort()\n").unwrap_err();
+        assert!(err.message.contains("Cannot order"), "{}", err.message);
+    }
+    #[test]
+    fn test_safe_vm_checks_sorted_search() {
+        // 没有 --safe-vm 时结果不确定但不报错；打开后检查数组（或键）是否有序
+        let code = "var a = [5, 1, 3]\nvar i = a.binarySearch(3)\n";
+        assert!(run_code(code).is_ok());
+        crate::vm::set_safe_vm(true);
+        let err = run_code(code).unwrap_err();
+        let by_key = run_code("var a = [1, 2, 3]\nvar i = a.binarySearchBy(func(n: int) int { return -n }, 2)\n").unwrap_err();
+        let insert = run_code("var a = [1, 3, 2]\na.sortedInsert(3)\n").unwrap_err();
+        crate::vm::set_safe_vm(false);
+        assert_eq!(err.message, "binarySearch() requires a sorted array, but the element at index 0 is greater than the next one (checked by --safe-vm)");
+        assert!(by_key.message.contains("the key at index 0"), "{}", by_key.message);
+        assert!(insert.message.contains("the element at index 1"), "{}", insert.message);
+    }
+    #[test]
+    fn test_index_resolution() {
+        let code = r#"
+var a = [10, 20, 30]
+var s = "aé😀"
+if a[-1] != 30 { throw "a[-1]" }
+if a[-0] != 10 { throw "a[-0]" }
+if s.charAt(-1) != "😀" { throw "charAt(-1)" }
+if s.charAt(-3) != "a" { throw "charAt(-3)" }
+if a.slice(-2) != [20, 30] { throw "slice(-2)" }
+if a.slice(0, -1) != [10, 20] { throw "slice(0, -1)" }
+if a.slice(1, 3) != [20, 30] { throw "slice end == len" }
+if a.slice(3) != [] { throw "slice start == len" }
+if a.slice(2, 1) != [] { throw "slice end < start" }
+if s.substring(-2) != "é😀" { throw "substring(-2)" }
+if s.substring(0, -1) != "aé" { throw "substring(0, -1)" }
+if s.substring(1, 3) != "é😀" { throw "substring end == len" }
+if s.indexOf("😀") != 2 { throw "indexOf counts chars" }
+if s.charAt(s.lastIndexOf("é")) != "é" { throw "lastIndexOf" }
+a[-1] = 31
+if a[2] != 31 { throw "a[-1] = 31" }
+"#;
+        let result = run_code(code);
+        assert!(result.is_ok(), "{:?}", result.err());
+
+        let prelude = "var a = [10, 20, 30]\nvar s = \"aé😀\"\n";
+        let error_of = |line: &str| run_code(&format!("{}{}\n", prelude, line)).unwrap_err().message;
+        for (line, expected) in [
+            ("a[3]", "Index 3 out of bounds for array of length 3"),
+            ("a[-4]", "Index -4 out of bounds for array of length 3"),
+            ("a[3] = 1", "Index 3 out of bounds for array of length 3"),
+            ("s[3]", "Index 3 out of bounds for string of length 3"),
+            ("s.charAt(3)", "Index 3 out of bounds for string of length 3"),
+            ("s.charAt(-4)", "Index -4 out of bounds for string of length 3"),
+            ("a[1.0]", "array index must be int, found float"),
+            ("a[1.0] = 2", "array index must be int, found float"),
+            ("a.slice(0.0)", "array index must be int, found float"),
+            ("a.slice(0, 1.0)", "array index must be int, found float"),
+            ("s[0.0]", "string index must be int, found float"),
+            ("s.charAt(0.0)", "string index must be int, found float"),
+            ("s.substring(1.0)", "string index must be int, found float"),
+        ] {
+            let message = error_of(line);
+            assert!(message.contains(expected), "{}: {}", line, message);
+        }
+    }
+
+    #[test]
+    fn test_main_result() {
+        use crate::lexer::Scanner;
+        use crate::parser::Parser;
+        use crate::compiler::Compiler;
+        use crate::stdlib::os::set_args;
+
+        let result_of = |source: &str| {
+            let tokens = Scanner::new(source).scan_tokens();
+            let program = Parser::new(tokens, Locale::En).parse().unwrap();
+            let chunk = Compiler::new(Locale::En).compile(&program).unwrap();
+            let mut vm = VM::new(Arc::new(chunk), Locale::En);
+            vm.run().unwrap();
+            vm.result()
+        };
+        assert_eq!(result_of("func main() int {\n    return 3\n}\n").and_then(|v| v.as_int()), Some(3));
+        assert_eq!(result_of("func main() {\n}\n").and_then(|v| v.as_int()), None);
+
+        set_args(vec!["a".to_string(), "b c".to_string()]);
+        let code = "import std.os\nfunc main() int {\n    var n = 0\n    for arg in Os.args() {\n        n = n + 1\n    }\n    return n\n}\n";
+        assert_eq!(result_of(code).and_then(|v| v.as_int()), Some(2));
+        set_args(Vec::new());
+    }
+
+    #[test]
+    fn test_new_with_fields() {
+        let code = "class A {\n    var x: int = 1\n    var y: int = 2\n}\nvar a = new A { y: 5 }\nif a.x != 1 || a.y != 5 {\n    panic(\"wrong fields\")\n}\n";
+        run_code(code).unwrap();
+        // 未经类型检查时，未声明的字段是运行时错误
+        let err = run_code("class A {\n    var x: int = 0\n}\nvar a = new A { z: 1 }\n").unwrap_err();
+        assert!(err.message.contains("Class 'A' has no field 'z'"), "{}", err.message);
+    }
+
+    #[test]
+    fn test_trait_objects() {
+        use crate::lexer::Scanner;
+        use crate::parser::Parser;
+        use crate::compiler::Compiler;
+
+        let source = r#"
+trait Printable {
+    func format() string
+    func show() string {
+        return "[" + this.format() + "]"
+    }
+}
+interface Named {
+    func name() string
+}
+class Doc implements Named {
+    use Printable
+    func format() string {
+        return "doc"
+    }
+    func name() string {
+        return "d"
+    }
+}
+class Memo extends Doc {
+    override func format() string {
+        return "memo"
+    }
+}
+struct Point implements Named {
+    x: int
+    func name() string {
+        return "p"
+    }
+}
+func show(p: Printable) string {
+    return p.show()
+}
+func main() string {
+    var m = new Memo()
+    var p = m as Printable
+    var items: Named[] = [new Doc(), Point { x: 1 }]
+    var out = show(new Doc()) + show(p) + items[0].name() + items[1].name()
+    var pt = Point { x: 1 }
+    return out + yn(m is Named) + yn(m is Doc) + yn(pt is Printable)
+}
+func yn(b: bool) string {
+    return b ? "Y" : "N"
+}
+"#;
+        let tokens = Scanner::new(source).scan_tokens();
+        let program = Parser::new(tokens, Locale::En).parse().unwrap();
+        let chunk = Compiler::new(Locale::En).compile(&program).unwrap();
+        let mut vm = VM::new(Arc::new(chunk), Locale::En);
+        vm.run().unwrap();
+        let result = vm.result().and_then(|v| v.as_string().cloned());
+        assert_eq!(result.as_deref(), Some("[doc][memo]dpYYN"));
+
+        // 继承来的 trait 按子类覆盖后的方法派发
+        let memo = vm.vtable_registry.lookup_by_name("Memo").unwrap();
+        let doc = vm.vtable_registry.lookup_by_name("Doc").unwrap();
+        assert!(memo.implements_trait("Printable") && memo.implements_trait("Named"));
+        assert_ne!(memo.lookup_trait("Printable").unwrap().lookup_method("format"),
+                   doc.lookup_trait("Printable").unwrap().lookup_method("format"));
+    }
+
+    /// 启用 GC 的测试串行执行：有其他启用了 GC 的虚拟机在运行时不会回收
+    static GC_TESTS: parking_lot::Mutex<()> = parking_lot::Mutex::new(());
+
+    #[test]
+    fn test_gc_reclaims_cycles_at_safepoints() {
+        use crate::lexer::Scanner;
+        use crate::parser::Parser;
+        use crate::compiler::Compiler;
+        use super::super::gc::{gc_stats, get_heap};
+
+        let _serial = GC_TESTS.lock();
+
+        let source = r#"
+class Node {
+    var value: int
+    var next: Node?
+    func init(value: int) {
+        this.value = value
+        this.next = null
+    }
+}
+func main() int {
+    var kept: Node[] = []
+    for var i = 0; i < 20000; i = i + 1 {
+        var a = new Node(i)
+        var b = new Node(i + 1)
+        a.next = b
+        b.next = a
+        if i % 1000 == 0 {
+            kept.push(a)
+        }
+    }
+    var sum = 0
+    for var j = 0; j < kept.len(); j = j + 1 {
+        var b = kept[j].next
+        if b != null {
+            sum = sum + b.value
+        }
+    }
+    return sum
+}
+"#;
+        let tokens = Scanner::new(source).scan_tokens();
+        let program = Parser::new(tokens, Locale::En).parse().unwrap();
+        let chunk = Compiler::new(Locale::En).compile(&program).unwrap();
+        get_heap().set_pacing(16 * 1024, 1.0);
+        let before = gc_stats();
+
+        let mut vm = VM::new(Arc::new(chunk), Locale::En);
+        vm.set_gc_enabled(true);
+        vm.run().unwrap();
+        let after = gc_stats();
+
+        // 保留下来的节点和它们指向的节点都还在
+        assert_eq!(vm.result().and_then(|v| v.as_int()), Some((0..20).map(|k| k * 1000 + 1).sum()));
+        assert!(after.collections() > before.collections());
+        // 每轮的两个节点互相引用，不可达后同样被回收
+        assert!(after.total_frees - before.total_frees > 20000, "{:?}", after);
+    }
+
+    #[test]
+    fn test_runtime_gc_and_memory_stats() {
+        use crate::lexer::Scanner;
+        use crate::parser::Parser;
+        use crate::compiler::Compiler;
+
+        let _serial = GC_TESTS.lock();
+        let source = r#"
+import std.runtime
+class Node {
+    var next: Node?
+    func init() {
+        this.next = null
+    }
+}
+func churn() {
+    for var i = 0; i < 500; i = i + 1 {
+        var a = new Node()
+        var b = new Node()
+        a.next = b
+        b.next = a
+    }
+}
+func stat(m: map[string]int, key: string) int {
+    var v = m[key]
+    if v != null {
+        return v
+    }
+    return -1
+}
+func main() int[] {
+    Runtime.setGcThreshold(1 << 30)
+    var before = Runtime.memoryStats()
+    churn()
+    var grown = Runtime.memoryStats()
+    Runtime.gc()
+    var after = Runtime.memoryStats()
+    var invalid = 0
+    try {
+        Runtime.setGcThreshold(0)
+    } catch (e: IllegalArgumentException) {
+        invalid = 1
+    }
+    return [stat(after, "collections") - stat(before, "collections"), stat(grown, "objects") - stat(after, "objects"), stat(after, "frees") - stat(before, "frees"), invalid]
+}
+"#;
+        let tokens = Scanner::new(source).scan_tokens();
+        let program = Parser::new(tokens, Locale::En).parse().unwrap();
+        let chunk = Compiler::new(Locale::En).compile(&program).unwrap();
+        let mut vm = VM::new(Arc::new(chunk), Locale::En);
+        vm.set_gc_enabled(true);
+        vm.run().unwrap();
+        let result = vm.result().unwrap();
+        let result: Vec<i64> = result.as_array().unwrap().lock().iter().map(|v| v.as_int().unwrap() as i64).collect();
+        // 阈值足够大时只有 Runtime.gc() 触发回收，返回后立即回收，环状的节点都被释放
+        assert_eq!(result[0], 1, "{:?}", result);
+        assert!(result[1] >= 999 && result[2] >= 1000, "{:?}", result);
+        assert_eq!(result[3], 1);
+    }
+
+    /// 同一个字节码块在多个线程的虚拟机上同时运行，结果与单线程相同
+    ///
+    /// 用 ThreadSanitizer 检查数据竞争（需要 nightly）：
+    /// `RUSTFLAGS="-Zsanitizer=thread" cargo +nightly test -Zbuild-std --target x86_64-unknown-linux-gnu test_chunk_shared_across_threads`
+    #[test]
+    fn test_chunk_shared_across_threads() {
+        use crate::lexer::Scanner;
+        use crate::parser::Parser;
+        use crate::compiler::Compiler;
+
+        // 方法调用（含默认参数、继承和覆盖）、构造、静态方法和枚举变体
+        let source = r#"
+enum Color {
+    Red,
+    Green
+}
+
+class Shape {
+    func init(var scale: int) {}
+
+    func area(extra: int = 1) int {
+        return this.scale * extra
+    }
+
+    func unit() int {
+        return 1
+    }
+}
+
+class Square extends Shape {
+    func init(var side: int) {
+        super.init(side)
+    }
+
+    override func area(extra: int = 2) int {
+        return this.side * this.side + extra + this.unit()
+    }
+
+    func half() int {
+        return this.area(0) / 2
+    }
+}
+
+struct Point {
+    x: int
+    y: int
+
+    func sum(bonus: int = 0) int {
+        return this.x + this.y + bonus
+    }
+
+    static func origin() Point {
+        return Point { x: 0, y: 0 }
+    }
+}
+
+func main() int {
+    var total = 0
+    for var i = 0; i < 2000; i = i + 1 {
+        var s = new Square(i % 7)
+        total = total + s.area() + s.half() + new Shape(i % 5).area(3)
+        var p = Point { x: i, y: 1 }
+        total = total + p.sum() + Point::origin().sum(1)
+        var maybe: Square? = null
+        if i % 2 == 0 {
+            maybe = s
+        }
+        if maybe != null {
+            total = total + maybe.half()
+        }
+        if Color::Green == Color::Green {
+            total = total + 1
+        }
+    }
+    return total
+}
+"#;
+        let tokens = Scanner::new(source).scan_tokens();
+        let program = Parser::new(tokens, Locale::En).parse().unwrap();
+        let chunk = Arc::new(Compiler::new(Locale::En).compile(&program).unwrap());
+        let run = |chunk: Arc<Chunk>| {
+            let mut vm = VM::new(chunk, Locale::En);
+            vm.run().unwrap();
+            vm.result().and_then(|v| v.as_int()).unwrap()
+        };
+
+        let expected = run(chunk.clone());
+        assert_eq!(expected, 2069084);
+        let results: Vec<i128> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8).map(|_| scope.spawn(|| run(chunk.clone()))).collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(results, vec![expected; 8]);
+    }
+
+}
+
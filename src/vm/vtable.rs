@@ -5,6 +5,8 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use crate::compiler::Chunk;
+
 /// 类型 ID（用于运行时类型识别）
 pub type TypeId = u32;
 
@@ -52,15 +54,14 @@ impl VTable {
     
     /// 创建带父类的 VTable
     pub fn with_parent(type_id: TypeId, type_name: impl Into<String>, parent: VTable) -> Self {
-        let mut vtable = Self {
+        Self {
             type_id,
             type_name: type_name.into(),
             methods: parent.methods.clone(),
             method_slots: parent.method_slots.clone(),
             trait_impls: HashMap::new(),
             parent: Some(Box::new(parent)),
-        };
-        vtable
+        }
     }
     
     /// 注册方法
@@ -115,6 +116,18 @@ impl VTable {
         self.trait_impls.contains_key(trait_name)
     }
     
+    /// 在实现的 trait 中查找方法的函数索引
+    pub fn lookup_trait_method(&self, name: &str) -> Option<usize> {
+        self.trait_impls.values().find_map(|t| t.lookup_method(name))
+    }
+    
+    /// 是否可以当作 target 类型使用：target 是类型本身、它的祖先类，或它（含祖先类）实现的 interface/trait
+    pub fn conforms_to(&self, target: &str) -> bool {
+        self.type_name == target
+            || self.implements_trait(target)
+            || self.parent.as_ref().is_some_and(|p| p.conforms_to(target))
+    }
+    
     /// 获取父类的方法（用于 super 调用）
     pub fn get_parent_method(&self, name: &str) -> Option<usize> {
         self.parent.as_ref().and_then(|p| p.get_method_func_index(name))
@@ -206,6 +219,74 @@ impl VTableRegistry {
         }
     }
     
+    /// 为字节码块中的所有 struct/class 构建 VTable
+    pub fn from_chunk(chunk: &Chunk) -> Self {
+        let mut registry = Self::new();
+        // 按名称排序，让类型 ID 不受 HashMap 遍历顺序影响
+        let mut names: Vec<&String> = chunk.types.keys().collect();
+        names.sort();
+        for name in names {
+            registry.init_type(chunk, name, &mut Vec::new());
+        }
+        registry
+    }
+    
+    /// 构建类型的 VTable（父类先构建），包括自身和继承的 trait 实现
+    ///
+    /// visiting 记录正在构建的继承链，遇到循环继承时停止向上查找
+    pub fn init_type(&mut self, chunk: &Chunk, type_name: &str, visiting: &mut Vec<String>) -> Option<Arc<VTable>> {
+        if let Some(vtable) = self.lookup_by_name(type_name) {
+            return Some(vtable);
+        }
+        let type_info = chunk.get_type(type_name)?;
+        if visiting.iter().any(|name| name == type_name) {
+            return None;
+        }
+        visiting.push(type_name.to_string());
+        let parent = type_info.parent.as_deref().and_then(|p| self.init_type(chunk, p, visiting));
+        visiting.pop();
+        
+        let type_id = self.allocate_type_id();
+        let mut vtable = match parent {
+            Some(parent) => VTable::with_parent(type_id, type_name, (*parent).clone()),
+            None => VTable::new(type_id, type_name),
+        };
+        // 按名称顺序注册，方法槽位与编译顺序无关
+        let mut methods: Vec<(&String, &u16)> = type_info.methods.iter().collect();
+        methods.sort();
+        for (method_name, func_index) in methods {
+            vtable.register_method(method_name.clone(), *func_index as usize);
+        }
+        
+        // 继承来的 trait 也要按子类覆盖后的方法重新解析
+        let mut trait_names: Vec<String> = vtable.parent.as_ref()
+            .map(|p| p.trait_impls.keys().cloned().collect())
+            .unwrap_or_default();
+        for trait_name in &type_info.traits {
+            if !trait_names.contains(trait_name) {
+                trait_names.push(trait_name.clone());
+            }
+        }
+        for trait_name in trait_names {
+            let method_names: Vec<String> = if let Some(trait_info) = chunk.get_trait(&trait_name) {
+                trait_info.methods.iter().map(|m| m.name.clone()).collect()
+            } else if let Some(interface_info) = chunk.get_interface(&trait_name) {
+                interface_info.methods.iter().map(|m| m.name.clone()).collect()
+            } else {
+                Vec::new()
+            };
+            let mut trait_vtable = TraitVTable::new(trait_name.clone());
+            for method_name in method_names {
+                if let Some(func_index) = vtable.get_method_func_index(&method_name) {
+                    trait_vtable.register_method(method_name, func_index);
+                }
+            }
+            vtable.register_trait_impl(trait_name, trait_vtable);
+        }
+        
+        Some(self.register(vtable))
+    }
+    
     /// 创建带父类的 VTable
     pub fn create_with_parent(&mut self, type_name: &str, parent_name: &str) -> Option<Arc<VTable>> {
         let parent = self.lookup_by_name(parent_name)?;
@@ -0,0 +1,98 @@
+//! `bundle` 命令的端到端测试：打包示例程序，直接运行生成的可执行文件
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn example(path: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("examples").join(path)
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("qlang_bundle_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// 打包 `input`，返回生成的可执行文件
+fn bundle(dir: &Path, input: &str, options: &[&str]) -> PathBuf {
+    let output = dir.join(format!("app{}", std::env::consts::EXE_SUFFIX));
+    let result = Command::new(env!("CARGO_BIN_EXE_mylang"))
+        .arg("bundle")
+        .arg(example(input))
+        .arg("-o")
+        .arg(&output)
+        .args(options)
+        .output()
+        .expect("failed to run mylang");
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    output
+}
+
+/// 在临时目录中运行，确认不依赖源文件
+fn run(exe: &Path, args: &[&str]) -> Output {
+    Command::new(exe).args(args).current_dir(exe.parent().unwrap()).output().expect("failed to run bundle")
+}
+
+#[test]
+fn test_bundle_project() {
+    let dir = temp_dir("project");
+    let exe = bundle(&dir, "inventory", &[]);
+    let output = run(&exe, &[]);
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "north: 4 items, total value 706\nrestock: hinge, gasket\n");
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_bundle_forwards_arguments_and_exit_code() {
+    let dir = temp_dir("args");
+    let exe = bundle(&dir, "data_processing/report.q", &["--compress"]);
+    let csv = example("data_processing/sales.csv");
+    let output = run(&exe, &[csv.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).ends_with("total: 1195, largest sale: 360\n[gadget, gizmo, widget]\n"));
+
+    // 命令行参数全部交给程序，不再解析为 mylang 的命令
+    let usage = run(&exe, &[]);
+    assert_eq!(usage.status.code(), Some(2));
+    assert_eq!(String::from_utf8_lossy(&usage.stdout), "usage: report.q -- <sales.csv>\n");
+    assert_eq!(run(&exe, &["version"]).status.code(), Some(1));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_bundle_detects_corruption() {
+    let dir = temp_dir("corrupt");
+    let exe = bundle(&dir, "inventory", &[]);
+    let mut bytes = fs::read(&exe).unwrap();
+    // 尾部的前 8 字节是载荷偏移
+    let footer = bytes.len() - 33;
+    let offset = u64::from_le_bytes(bytes[footer..footer + 8].try_into().unwrap()) as usize;
+    bytes[offset + 16] ^= 0xff;
+    fs::write(&exe, &bytes).unwrap();
+
+    let output = run(&exe, &[]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "the embedded program is corrupted (checksum mismatch)\n");
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_unbundled_runtime_is_unchanged() {
+    let output = Command::new(env!("CARGO_BIN_EXE_mylang")).arg("version").output().unwrap();
+    assert!(output.status.success());
+    let output = Command::new(env!("CARGO_BIN_EXE_mylang")).arg("run").arg(example("inventory/src/main.q")).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "north: 4 items, total value 706\nrestock: hinge, gasket\n");
+
+    // 运行时选项在打包的程序中不起作用
+    let output = Command::new(env!("CARGO_BIN_EXE_mylang"))
+        .args(["bundle", "--trace"])
+        .arg(example("inventory"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(64));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("bundle only accepts compile options"));
+}
@@ -0,0 +1,279 @@
+//! 语言一致性测试：运行 tests/conformance 下的所有 .q 文件，按文件中的注释检查结果
+//!
+//! 每个文件在单独的进程中通过 `mylang run` 执行，多个文件并行运行，超过 30 秒未结束的算作失败。
+//! 设置 `QLANG_CONFORMANCE_FILTER` 时只运行路径中包含该字符串的文件。支持的注释：
+//!
+//! - `// expect: <文本>`：标准输出的一行，多条按顺序排列，必须与输出的所有行一一对应
+//! - `// expect-error: <子串>`：标准错误中应出现的内容，程序应以失败退出
+//! - `// expect-error-line: N`：错误报告中应指向第 N 行
+//! - `// expect-exit: N`：进程的退出码（默认成功为 0，有 expect-error 时为 1；编译错误为 2，需写明）
+//! - `// compile-only`：只编译不运行（`mylang run --emit=bytecode`），不检查标准输出
+//!
+//! 注释可以单独成行，也可以写在代码行的末尾。
+
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 单个文件的运行时间上限
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 一个文件中的期望
+#[derive(Debug, Default, PartialEq)]
+struct Expectations {
+    stdout: Vec<String>,
+    errors: Vec<String>,
+    error_line: Option<usize>,
+    exit: Option<i32>,
+    compile_only: bool,
+}
+
+impl Expectations {
+    /// 从源码的注释中解析期望，注释格式错误时返回 Err
+    fn parse(source: &str) -> Result<Self, String> {
+        let mut expectations = Expectations::default();
+        for (index, line) in source.lines().enumerate() {
+            let Some(position) = line.find("// ") else {
+                continue;
+            };
+            let comment = &line[position + 3..];
+            let line_number = index + 1;
+            if let Some(text) = comment.strip_prefix("expect: ") {
+                expectations.stdout.push(text.to_string());
+            } else if comment == "expect:" {
+                expectations.stdout.push(String::new());
+            } else if let Some(text) = comment.strip_prefix("expect-error: ") {
+                expectations.errors.push(text.to_string());
+            } else if let Some(value) = comment.strip_prefix("expect-error-line: ") {
+                let value = value.trim().parse().map_err(|_| format!("line {}: invalid expect-error-line", line_number))?;
+                expectations.error_line = Some(value);
+            } else if let Some(value) = comment.strip_prefix("expect-exit: ") {
+                let value = value.trim().parse().map_err(|_| format!("line {}: invalid expect-exit", line_number))?;
+                expectations.exit = Some(value);
+            } else if comment.trim_end() == "compile-only" {
+                expectations.compile_only = true;
+            } else if comment.starts_with("expect-") {
+                return Err(format!("line {}: unknown annotation '{}'", line_number, comment));
+            }
+        }
+        Ok(expectations)
+    }
+
+    fn expected_exit(&self) -> i32 {
+        self.exit.unwrap_or(if self.errors.is_empty() { 0 } else { 1 })
+    }
+}
+
+/// 错误输出是否指向第 line 行：`[3:5]`、`(main.q:3)` 或 `main.q:3:5`
+fn mentions_line(stderr: &str, line: usize) -> bool {
+    [format!("[{}:", line), format!(":{})", line), format!(":{}:", line)]
+        .iter()
+        .any(|pattern| stderr.contains(pattern.as_str()))
+}
+
+/// 期望的输出与实际输出逐行对比，不同时返回带标记的差异
+fn diff_lines(expected: &[String], actual: &[&str]) -> Option<String> {
+    if expected.iter().map(String::as_str).eq(actual.iter().copied()) {
+        return None;
+    }
+    let mut diff = String::new();
+    for i in 0..expected.len().max(actual.len()) {
+        match (expected.get(i), actual.get(i)) {
+            (Some(e), Some(a)) if e == a => diff.push_str(&format!("      {}\n", e)),
+            (e, a) => {
+                if let Some(e) = e {
+                    diff.push_str(&format!("    - {}\n", e));
+                }
+                if let Some(a) = a {
+                    diff.push_str(&format!("    + {}\n", a));
+                }
+            }
+        }
+    }
+    Some(diff)
+}
+
+/// 运行命令并收集输出，超过 TIMEOUT 仍未结束时杀掉进程并返回 Err
+fn run_with_timeout(command: &mut Command) -> Result<(i32, String, String), String> {
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("cannot run mylang: {}", e))?;
+    // 在单独的线程中读取输出，避免管道写满后子进程阻塞
+    let read = |pipe: Option<Box<dyn Read + Send>>| {
+        std::thread::spawn(move || {
+            let mut buffer = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buffer);
+            }
+            String::from_utf8_lossy(&buffer).into_owned()
+        })
+    };
+    let stdout = read(child.stdout.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+    let stderr = read(child.stderr.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(|e| e.to_string())? {
+            break status;
+        }
+        if started.elapsed() > TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!("  timed out after {}s", TIMEOUT.as_secs()));
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    };
+    Ok((status.code().unwrap_or(-1), stdout.join().unwrap(), stderr.join().unwrap()))
+}
+
+/// 运行一个文件，返回不符合期望的地方
+fn check_file(path: &Path) -> Result<(), String> {
+    let source = fs::read_to_string(path).map_err(|e| format!("cannot read: {}", e))?;
+    let expectations = Expectations::parse(&source)?;
+
+    let mut command = Command::new(env!("CARGO_BIN_EXE_mylang"));
+    command.arg("run");
+    if expectations.compile_only {
+        command.arg("--emit=bytecode");
+    }
+    let (exit, stdout, stderr) = run_with_timeout(command.arg(path))?;
+
+    let mut problems = Vec::new();
+    if exit != expectations.expected_exit() {
+        problems.push(format!("  exit code {} (expected {})", exit, expectations.expected_exit()));
+    }
+    if !expectations.compile_only {
+        let actual: Vec<&str> = stdout.lines().collect();
+        if let Some(diff) = diff_lines(&expectations.stdout, &actual) {
+            problems.push(format!("  stdout differs (- expected, + actual):\n{}", diff.trim_end()));
+        }
+    }
+    for error in &expectations.errors {
+        if !stderr.contains(error.as_str()) {
+            problems.push(format!("  stderr does not contain '{}'", error));
+        }
+    }
+    if let Some(line) = expectations.error_line {
+        if !mentions_line(&stderr, line) {
+            problems.push(format!("  error does not point at line {}", line));
+        }
+    }
+
+    if problems.is_empty() {
+        return Ok(());
+    }
+    if !stderr.trim().is_empty() {
+        problems.push(format!("  stderr:\n{}", stderr.trim_end().lines().map(|l| format!("    {}", l)).collect::<Vec<_>>().join("\n")));
+    }
+    Err(problems.join("\n"))
+}
+
+/// tests/conformance 下的所有 .q 文件（按路径排序）
+fn discover(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            discover(&path, files);
+        } else if path.extension().is_some_and(|e| e == "q") {
+            files.push(path);
+        }
+    }
+}
+
+#[test]
+fn test_conformance_suite() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance");
+    let mut files = Vec::new();
+    discover(&root, &mut files);
+    files.sort();
+    assert!(!files.is_empty(), "no conformance tests under {}", root.display());
+
+    // 只运行路径包含 QLANG_CONFORMANCE_FILTER 的文件
+    if let Ok(filter) = std::env::var("QLANG_CONFORMANCE_FILTER") {
+        files.retain(|path| path.to_string_lossy().contains(&filter));
+    }
+
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::new());
+    let workers = std::thread::available_parallelism().map_or(4, |n| n.get()).min(files.len());
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = files.get(index) else {
+                    break;
+                };
+                let result = check_file(path);
+                results.lock().unwrap().push((index, result));
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(index, _)| *index);
+    let mut failures = Vec::new();
+    for (index, result) in &results {
+        let name = files[*index].strip_prefix(&root).unwrap_or(&files[*index]).display().to_string();
+        match result {
+            Ok(()) => println!("PASS {}", name),
+            Err(problems) => {
+                println!("FAIL {}\n{}", name, problems);
+                failures.push(name);
+            }
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{} of {} conformance tests failed:\n  {}",
+        failures.len(),
+        results.len(),
+        failures.join("\n  ")
+    );
+}
+
+#[test]
+fn test_annotation_parser() {
+    let source = "\
+// compile-only
+func main() int {
+    println(1) // expect: 1
+    // expect: a b
+    // expect:
+    // expect-error: boom
+    // expect-error-line: 7
+    // expect-exit: 3
+    return 3
+}
+";
+    let expectations = Expectations::parse(source).unwrap();
+    assert_eq!(
+        expectations,
+        Expectations {
+            stdout: vec!["1".to_string(), "a b".to_string(), String::new()],
+            errors: vec!["boom".to_string()],
+            error_line: Some(7),
+            exit: Some(3),
+            compile_only: true,
+        }
+    );
+    assert_eq!(Expectations::parse("// expect: x").unwrap().expected_exit(), 0);
+    assert_eq!(Expectations::parse("// expect-error: x").unwrap().expected_exit(), 1);
+    assert!(Expectations::parse("// expect-exit: three").is_err());
+    assert!(Expectations::parse("// expect-errors: 1").is_err());
+    assert_eq!(Expectations::parse("// expected output follows").unwrap(), Expectations::default());
+
+    assert!(mentions_line("  [12:5] type mismatch", 12));
+    assert!(mentions_line("  at main (main.q:12)", 12));
+    assert!(!mentions_line("  at main (main.q:120)", 12));
+    assert_eq!(diff_lines(&["a".to_string()], &["a"]), None);
+    assert_eq!(diff_lines(&["a".to_string(), "b".to_string()], &["a", "c"]).unwrap(), "      a\n    - b\n    + c\n");
+}
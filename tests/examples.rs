@@ -0,0 +1,159 @@
+//! examples 目录下示例程序的端到端测试：运行每个示例并检查输出和退出码
+//!
+//! 网络示例绑定端口 0，从服务端打印的第一行读出实际端口，再运行客户端或直接连接
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdout, Command, Output, Stdio};
+
+fn example(path: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("examples").join(path)
+}
+
+fn run_example(path: &str, args: &[&str]) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_mylang"));
+    command.arg("run").arg(example(path));
+    if !args.is_empty() {
+        command.arg("--").args(args);
+    }
+    command.output().expect("failed to run mylang")
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+/// 在后台运行的服务端示例，测试失败时结束进程
+struct Server {
+    child: Child,
+    stdout: BufReader<ChildStdout>,
+    port: u16,
+}
+
+impl Server {
+    /// 启动服务端并读取第一行 `listening on port N`
+    fn start(path: &str) -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_mylang"))
+            .arg("run")
+            .arg(example(path))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .expect("failed to start mylang");
+        let mut stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut line = String::new();
+        stdout.read_line(&mut line).unwrap();
+        let port = line
+            .trim()
+            .strip_prefix("listening on port ")
+            .and_then(|port| port.parse().ok())
+            .unwrap_or_else(|| panic!("unexpected first line: {:?}", line));
+        Self { child, stdout, port }
+    }
+
+    /// 等待服务端退出，返回剩余的输出
+    fn finish(mut self) -> String {
+        let mut rest = String::new();
+        self.stdout.read_to_string(&mut rest).unwrap();
+        let status = self.child.wait().unwrap();
+        assert!(status.success(), "server exited with {}", status);
+        rest
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn test_multi_file_project() {
+    let output = run_example("inventory/src/main.q", &[]);
+    assert_eq!(stdout(&output), "north: 4 items, total value 706\nrestock: hinge, gasket\n");
+}
+
+#[test]
+fn test_data_processing() {
+    let csv = example("data_processing/sales.csv");
+    let output = run_example("data_processing/report.q", &[csv.to_str().unwrap()]);
+    assert_eq!(
+        stdout(&output),
+        "skipping line 10: west,widget,oops,5\n\
+         north      460\n\
+         west       360\n\
+         south      220\n\
+         east       155\n\
+         total: 1195, largest sale: 360\n\
+         [gadget, gizmo, widget]\n"
+    );
+
+    let usage = run_example("data_processing/report.q", &[]);
+    assert_eq!(usage.status.code(), Some(2));
+    assert_eq!(String::from_utf8_lossy(&usage.stdout), "usage: report.q -- <sales.csv>\n");
+}
+
+#[test]
+fn test_class_hierarchy() {
+    let output = run_example("class_hierarchy/shapes.q", &[]);
+    assert_eq!(
+        stdout(&output),
+        "rectangle with area 7\n\
+         side 3, square with area 9\n\
+         circle with area 6.75\n\
+         largest: square\n\
+         rectangles: 1\n\
+         side 1, square with area 1\n"
+    );
+}
+
+#[test]
+fn test_error_handling() {
+    let expected = "processed apple\nreserved 3 apple, 2 left\n\
+                    processed pear\ncannot reserve pear\n\
+                    processed apple\ninvalid quantity: quantity must be positive, got 0\n\
+                    processed plum\ncannot reserve plum\n";
+    let output = run_example("error_handling/orders.q", &[]);
+    assert_eq!(stdout(&output), format!("{}done\n", expected));
+
+    // 未捕获的异常：前面 try 块的 catch 不能处理它，进程以 1 退出
+    let strict = run_example("error_handling/orders.q", &["--strict"]);
+    let stderr = String::from_utf8_lossy(&strict.stderr);
+    assert_eq!(strict.status.code(), Some(1), "{}", stderr);
+    assert_eq!(String::from_utf8_lossy(&strict.stdout), expected);
+    assert!(stderr.contains("Uncaught exception: OutOfStockException"), "{}", stderr);
+}
+
+#[test]
+fn test_http_server_and_client() {
+    let server = Server::start("http/server.q");
+    let client = run_example("http/client.q", &[&server.port.to_string()]);
+    assert_eq!(
+        stdout(&client),
+        "hello, alice\nhello, bob\n200 text/plain ping\nmissing: 404\nbye\n"
+    );
+    assert_eq!(server.finish(), "served 2 greetings\n");
+
+    let usage = run_example("http/client.q", &[]);
+    assert_eq!(usage.status.code(), Some(2));
+}
+
+#[test]
+fn test_tcp_echo_server() {
+    let server = Server::start("tcp_echo/server.q");
+
+    let mut conn = TcpStream::connect(("127.0.0.1", server.port)).unwrap();
+    conn.write_all(b"hello, echo").unwrap();
+    conn.shutdown(Shutdown::Write).unwrap();
+    let mut echoed = Vec::new();
+    conn.read_to_end(&mut echoed).unwrap();
+    assert_eq!(echoed, b"hello, echo");
+
+    // 单个字节 0 让服务端停止
+    let mut stop = TcpStream::connect(("127.0.0.1", server.port)).unwrap();
+    stop.write_all(&[0]).unwrap();
+    assert_eq!(server.finish(), "echoed 11 bytes\nserver stopped\n");
+}
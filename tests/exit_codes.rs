@@ -0,0 +1,95 @@
+//! 进程退出码的端到端测试：程序错误、编译错误和命令行用法错误各有不同的退出码
+
+use assert_cmd::Command;
+use std::fs;
+use std::path::PathBuf;
+
+/// 把 `source` 写入临时文件并运行，返回退出码断言所用的结果
+fn run_source(name: &str, source: &str) -> assert_cmd::assert::Assert {
+    let dir = std::env::temp_dir().join(format!("qlang_exit_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path: PathBuf = dir.join("main.q");
+    fs::write(&path, source).unwrap();
+    let assert = mylang().arg("run").arg(&path).assert();
+    let _ = fs::remove_dir_all(&dir);
+    assert
+}
+
+fn mylang() -> Command {
+    Command::cargo_bin("mylang").unwrap()
+}
+
+fn stderr(assert: &assert_cmd::assert::Assert) -> String {
+    String::from_utf8_lossy(&assert.get_output().stderr).into_owned()
+}
+
+#[test]
+fn test_success_exits_with_zero() {
+    run_source("ok", "func main() {\n    println(\"hi\")\n}\n").success().stdout("hi\n");
+    mylang().arg("version").assert().success();
+}
+
+#[test]
+fn test_runtime_error_exits_with_one() {
+    let source = "import std.lang.Exception\n\nfunc main() {\n    throw new Exception(\"boom\")\n}\n";
+    run_source("runtime", source).code(1);
+}
+
+#[test]
+fn test_compile_errors_exit_with_two() {
+    let syntax = run_source("syntax", "func main() {\n    var x = (1 + 2\n}\n").code(2);
+    assert!(stderr(&syntax).contains("Syntax Error"), "{}", stderr(&syntax));
+    let types = run_source("types", "func main() {\n    var x: int = \"text\"\n}\n").code(2);
+    assert!(stderr(&types).contains("Type Error"), "{}", stderr(&types));
+    // 程序没有运行
+    assert!(types.get_output().stdout.is_empty());
+}
+
+#[test]
+fn test_usage_errors_exit_with_sixty_four() {
+    let unknown = mylang().arg("frobnicate").assert().code(64);
+    assert!(stderr(&unknown).starts_with("Unknown command: frobnicate\n"), "{}", stderr(&unknown));
+    assert!(stderr(&unknown).contains("Run 'mylang help'"), "{}", stderr(&unknown));
+    mylang().args(["run", "--no-such-option", "main.q"]).assert().code(64);
+    mylang().args(["run", "missing.q"]).assert().code(64);
+    mylang().args(["grammar", "--format=yacc"]).assert().code(64);
+}
+
+#[test]
+fn test_main_exit_code_takes_precedence() {
+    // 其他协程中未捕获的错误只输出，不改变 main 指定的退出码
+    let source = "import std.lang.Exception\nimport std.time.Time\n\n\
+                  func main() int {\n    \
+                      go func() {\n        throw new Exception(\"worker failed\")\n    }()\n    \
+                      Time.sleep(10)\n    return 3\n}\n";
+    let assert = run_source("precedence", source).code(3);
+    assert!(stderr(&assert).contains("worker failed"), "{}", stderr(&assert));
+
+    // 与内置退出码相同的值原样使用
+    run_source("two", "func main() int {\n    return 2\n}\n").code(2);
+    run_source("seventy", "func main() int {\n    return 70\n}\n").code(70);
+}
+
+#[test]
+fn test_os_exit_sets_exit_code() {
+    // 退出前的输出（包括没有换行的部分）都已写出，catch 不拦截退出
+    let source = "import std.os.Os\nimport std.lang.Exception\n\n\
+                  func main() {\n    print(\"leaving\")\n    \
+                      try {\n        Os.exit(4)\n    } catch (e: Exception) {\n        println(\"caught\")\n    }\n}\n";
+    run_source("os_exit", source).code(4).stdout("leaving");
+
+    // 协程中调用时结束整个进程
+    let source = "import std.os.Os\nimport std.time.Time\n\n\
+                  func main() {\n    go func() {\n        Os.exit(5)\n    }()\n    \
+                      Time.sleep(5000)\n    println(\"main finished\")\n}\n";
+    run_source("os_exit_goroutine", source).code(5).stdout("");
+}
+
+#[test]
+fn test_help_documents_exit_codes() {
+    let help = mylang().arg("help").assert().success();
+    let stdout = String::from_utf8_lossy(&help.get_output().stdout).into_owned();
+    assert!(stdout.contains("Exit codes:"), "{}", stdout);
+    assert!(stdout.contains("  64             Invalid command line usage"), "{}", stdout);
+}
@@ -0,0 +1,49 @@
+//! 内部错误报告的端到端测试：调试构建中用 `QLANG_INJECT_ICE` 在指定阶段故意 panic
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn fixture() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ice/main.q")
+}
+
+fn run_with_injected_panic(phase: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mylang"))
+        .arg("run")
+        .arg(fixture())
+        .env("QLANG_INJECT_ICE", phase)
+        .output()
+        .expect("failed to run mylang")
+}
+
+#[test]
+fn test_compiler_panic_is_reported_as_internal_error() {
+    let output = run_with_injected_panic("check");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(70), "{}", stderr);
+    assert!(stderr.starts_with("error: internal compiler error: injected internal error in the check phase\n"), "{}", stderr);
+    assert!(stderr.contains(&format!("  file:     {}\n", fixture().display())), "{}", stderr);
+    assert!(stderr.contains("  phase:    check\n"), "{}", stderr);
+    assert!(stderr.contains(&format!("  version:  Q {} (commit ", env!("CARGO_PKG_VERSION"))), "{}", stderr);
+    assert!(stderr.contains("Please report it at https://github.com/tangzhangming/qlang/issues"), "{}", stderr);
+    // 不输出 Rust 默认的 panic 消息
+    assert!(!stderr.contains("thread 'main' panicked"), "{}", stderr);
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_vm_panic_is_reported_as_internal_error() {
+    let output = run_with_injected_panic("run");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(70), "{}", stderr);
+    assert!(stderr.starts_with("error: internal VM error: injected internal error in the run phase\n"), "{}", stderr);
+    assert!(stderr.contains("  phase:    run\n"), "{}", stderr);
+}
+
+#[test]
+fn test_parse_panic_names_the_file() {
+    let output = run_with_injected_panic("parse");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(70), "{}", stderr);
+    assert!(stderr.contains(&format!("  file:     {}\n  phase:    parse\n", fixture().display())), "{}", stderr);
+}
@@ -0,0 +1,240 @@
+//! 依赖加载的端到端测试：运行 tests/fixtures 下的项目
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn run_fixture(main: &str) -> Output {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(main);
+    Command::new(env!("CARGO_BIN_EXE_mylang"))
+        .arg("run")
+        .arg(path)
+        .output()
+        .expect("failed to run mylang")
+}
+
+#[test]
+fn test_import_cycle_is_reported() {
+    let output = run_fixture("import_cycle/src/main.q");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("import cycle: src/a/Order.q -> src/b/Customer.q -> src/a/Order.q"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_files_of_one_package_may_import_each_other() {
+    let output = run_fixture("import_package/src/main.q");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "9\n");
+}
+
+/// 在临时目录中创建一个项目：src/parts 下有多个文件，main.q 导入整个包
+fn write_parts_project(root: &Path, parts: usize) {
+    let _ = fs::remove_dir_all(root);
+    fs::create_dir_all(root.join("src/parts")).unwrap();
+    fs::write(root.join("project.toml"), "[project]\nname = \"parts\"\npackage = \"com.parts\"\n").unwrap();
+    let calls: Vec<String> = (0..parts).map(|i| format!("part{}()", i)).collect();
+    fs::write(
+        root.join("src/main.q"),
+        format!("package com.parts\n\nimport com.parts.parts.*\n\nfunc main() {{\n    println({})\n}}\n", calls.join(" + ")),
+    )
+    .unwrap();
+    for i in 0..parts {
+        fs::write(
+            root.join(format!("src/parts/Part{}.q", i)),
+            format!("package com.parts.parts\n\nfunc part{}() int {{\n    return {}\n}}\n", i, i),
+        )
+        .unwrap();
+    }
+}
+
+fn run_main(root: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mylang"))
+        .arg("run")
+        .arg(root.join("src/main.q"))
+        .output()
+        .expect("failed to run mylang")
+}
+
+fn cache_entries(root: &Path) -> Vec<std::path::PathBuf> {
+    match fs::read_dir(root.join(".qcache/parse")) {
+        Ok(entries) => entries.flatten().map(|entry| entry.path()).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+#[test]
+fn test_imported_files_are_cached_by_content() {
+    let root = std::env::temp_dir().join(format!("qlang_parse_cache_project_{}", std::process::id()));
+    write_parts_project(&root, 12);
+
+    let first = run_main(&root);
+    assert!(first.status.success(), "{}", String::from_utf8_lossy(&first.stderr));
+    assert_eq!(String::from_utf8_lossy(&first.stdout), "66\n");
+    assert_eq!(cache_entries(&root).len(), 12);
+
+    // 第二次运行读取缓存，结果相同
+    let second = run_main(&root);
+    assert_eq!(second.stdout, first.stdout);
+
+    // 修改过的文件重新解析
+    fs::write(root.join("src/parts/Part3.q"), "package com.parts.parts\n\nfunc part3() int {\n    return 103\n}\n").unwrap();
+    let changed = run_main(&root);
+    assert_eq!(String::from_utf8_lossy(&changed.stdout), "166\n");
+    assert_eq!(cache_entries(&root).len(), 13);
+
+    // 损坏的缓存视为未命中
+    for entry in cache_entries(&root) {
+        fs::write(entry, b"QAST garbage").unwrap();
+    }
+    let recovered = run_main(&root);
+    assert_eq!(String::from_utf8_lossy(&recovered.stdout), "166\n");
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_errors_in_all_files_are_reported() {
+    let root = std::env::temp_dir().join(format!("qlang_all_errors_project_{}", std::process::id()));
+    write_parts_project(&root, 3);
+    fs::write(root.join("src/parts/Part0.q"), "package com.parts.parts\n\nfunc part0() int {\n    return 1 +\n}\n").unwrap();
+    fs::write(
+        root.join("src/parts/Part2.q"),
+        "package com.parts.parts\n\nfunc part2() int {\n    var a = ]\n    var b = )\n    return 2\n}\n",
+    )
+    .unwrap();
+
+    let output = run_main(&root);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    // 每个文件的错误都报告，按文件分组
+    let part0 = stderr.find("Part0.q\n[4:").expect(&stderr);
+    let part2 = stderr.find("Part2.q\n[4:").expect(&stderr);
+    assert!(part0 < part2, "{}", stderr);
+    assert!(stderr.contains("[5:13]"), "{}", stderr);
+    assert!(!stderr.contains("Part1.q"), "{}", stderr);
+
+    // 超过上限的错误只报告条数
+    let lines: Vec<String> = (0..60).map(|i| format!("    var x{} = ]", i)).collect();
+    fs::write(root.join("src/main.q"), format!("func main() {{\n{}\n}}\n", lines.join("\n"))).unwrap();
+    let output = run_main(&root);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.matches("Expected expression").count(), 50, "{}", stderr);
+    assert!(stderr.trim_end().ends_with("... and 10 more errors"), "{}", stderr);
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_type_errors_name_their_file() {
+    let root = std::env::temp_dir().join(format!("qlang_type_error_project_{}", std::process::id()));
+    write_parts_project(&root, 2);
+    fs::write(
+        root.join("src/parts/Part1.q"),
+        "package com.parts.parts\n\nfunc part1() int {\n    var flag: bool = 3\n    return 1\n}\n",
+    )
+    .unwrap();
+
+    let output = run_main(&root);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    // 错误归到依赖文件下，并显示出错的源码行
+    let header = stderr.lines().position(|line| line.ends_with("Part1.q")).expect(&stderr);
+    let lines: Vec<&str> = stderr.lines().skip(header + 1).take(3).collect();
+    assert!(lines[0].starts_with("  [4:5] "), "{}", stderr);
+    assert_eq!(lines[1], "  4 |     var flag: bool = 3");
+    assert_eq!(lines[2], "    |     ^^^^^^^^^^^^^^^^^^");
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// `--timings-json` 输出中某一项的数字字段，如 `{"name":"lex",...,"count":38,...}` 中的 count
+fn json_number(object: &str, field: &str) -> u128 {
+    let start = object.find(&format!("\"{}\":", field)).expect(object) + field.len() + 3;
+    let digits: String = object[start..].chars().take_while(char::is_ascii_digit).collect();
+    digits.parse().expect(object)
+}
+
+#[test]
+fn test_timings_json_lists_phases_and_files() {
+    let root = std::env::temp_dir().join(format!("qlang_timings_project_{}", std::process::id()));
+    write_parts_project(&root, 3);
+    fs::write(
+        root.join("src/parts/Part0.q"),
+        "package com.parts.parts\n\nfunc pick<T>(value: T) T {\n    return value\n}\n\nfunc part0() int {\n    return pick(0)\n}\n",
+    )
+    .unwrap();
+    let json_path = root.join("timings.json");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_mylang"))
+        .arg("run")
+        .arg("--timings")
+        .arg("--timings-json")
+        .arg(&json_path)
+        .arg(root.join("src/main.q"))
+        .output()
+        .expect("failed to run mylang");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+    assert!(stderr.contains("type_check"), "{}", stderr);
+
+    let json = fs::read_to_string(&json_path).unwrap();
+    let (phases, files) = json.split_once("\"files\":").expect(&json);
+    let phase = |name: &str| {
+        let start = phases.find(&format!("{{\"name\":\"{}\"", name)).expect(&json);
+        &phases[start..start + phases[start..].find('}').unwrap()]
+    };
+    for (name, count) in [("lex", 0), ("parse", 0), ("load_dependencies", 3), ("type_check", 0), ("codegen", 0)] {
+        let object = phase(name);
+        assert!(json_number(object, "wall_ns") > 0, "{}", object);
+        if count == 0 {
+            assert!(json_number(object, "count") > 0, "{}", object);
+        } else {
+            assert_eq!(json_number(object, "count"), count, "{}", object);
+        }
+    }
+    // 主文件和三个依赖各词法分析一次
+    assert_eq!(json_number(phase("lex"), "runs"), 4);
+    assert_eq!(json_number(phase("monomorphize"), "runs"), 1);
+
+    for file in ["main.q", "Part0.q", "Part1.q", "Part2.q"] {
+        let start = files.find(file).unwrap_or_else(|| panic!("{} missing from {}", file, files));
+        let object = &files[start..start + files[start..].find('}').unwrap()];
+        assert!(json_number(object, "tokens") > 0, "{}", object);
+        assert!(json_number(object, "nodes") > 0, "{}", object);
+    }
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_build_writes_lockfile_matching_the_parse_cache() {
+    let root = std::env::temp_dir().join(format!("qlang_build_lock_project_{}", std::process::id()));
+    write_parts_project(&root, 3);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_mylang"))
+        .arg("build")
+        .arg(root.join("src/main.q"))
+        .output()
+        .expect("failed to run mylang");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    // 只编译不运行
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    // 每个依赖文件的哈希就是它解析缓存的文件名
+    let lock = fs::read_to_string(root.join(".qcache/build.lock")).unwrap();
+    let entries: Vec<(&str, &str)> = lock.lines().skip(2).map(|line| line.split_once("  ").expect(&lock)).collect();
+    let files: Vec<&str> = entries.iter().map(|(_, file)| *file).collect();
+    assert_eq!(files, ["src/main.q", "src/parts/Part0.q", "src/parts/Part1.q", "src/parts/Part2.q"]);
+    for (hash, file) in &entries[1..] {
+        assert!(root.join(format!(".qcache/parse/{}.ast", hash)).is_file(), "{} {}", hash, file);
+    }
+
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,164 @@
+//! 项目发现的端到端测试：在临时目录中搭建目录树，检查 `run` 选用哪个 project.toml
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// 在临时目录中创建文件；`.git/` 这样以 `/` 结尾的路径创建目录
+fn make_tree(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("qlang_projects_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    for (path, content) in files {
+        match path.strip_suffix('/') {
+            Some(dir) => fs::create_dir_all(root.join(dir)).unwrap(),
+            None => {
+                fs::create_dir_all(root.join(path).parent().unwrap()).unwrap();
+                fs::write(root.join(path), content).unwrap();
+            }
+        }
+    }
+    fs::canonicalize(&root).unwrap()
+}
+
+fn project(package: &str) -> String {
+    format!("[project]\nname = \"app\"\npackage = \"{}\"\n", package)
+}
+
+fn run(file: &Path, args: &[&str], project_env: Option<&Path>) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_mylang"));
+    command.arg("run").args(args).arg(file).env_remove("QLANG_PROJECT");
+    if let Some(path) = project_env {
+        command.env("QLANG_PROJECT", path);
+    }
+    command.output().expect("failed to run mylang")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn test_project_outside_repository_is_ignored() {
+    // 仓库外层无关的 project.toml 不影响仓库内的独立文件
+    let outer = project("com.unrelated");
+    let root = make_tree("hijack", &[
+        ("project.toml", outer.as_str()),
+        ("repo/.git/", ""),
+        ("repo/scripts/hello.q", "func main() {\n    println(\"hi\")\n}\n"),
+    ]);
+    let output = run(&root.join("repo/scripts/hello.q"), &[], None);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "hi\n");
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_nearest_project_wins_with_warning() {
+    let outer = project("com.outer");
+    let inner = project("com.inner");
+    let root = make_tree("nested", &[
+        ("project.toml", outer.as_str()),
+        ("tools/project.toml", inner.as_str()),
+        ("tools/src/main.q", "package com.inner\n\nfunc main() {\n    println(1)\n}\n"),
+    ]);
+    let output = run(&root.join("tools/src/main.q"), &[], None);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "1\n");
+    let expected = format!(
+        "using the nearest {}; ignoring {} in an enclosing directory",
+        root.join("tools/project.toml").display(),
+        root.join("project.toml").display(),
+    );
+    assert!(stderr(&output).contains(&expected), "{}", stderr(&output));
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_project_flag_and_env_override_discovery() {
+    // 文件同时位于 com.outer 的 src 下和更近的 com.inner 项目中
+    let outer = project("com.outer");
+    let inner = project("com.inner");
+    let root = make_tree("override", &[
+        ("project.toml", outer.as_str()),
+        ("src/tools/project.toml", inner.as_str()),
+        ("src/tools/src/main.q", "package com.outer.tools.src\n\nfunc main() {\n    println(2)\n}\n"),
+    ]);
+    let main = root.join("src/tools/src/main.q");
+
+    // 默认使用最近的项目，包名不符
+    let output = run(&main, &[], None);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("com.inner"), "{}", stderr(&output));
+
+    // 环境变量可以指向文件或目录
+    for path in [root.join("project.toml"), root.clone()] {
+        let output = run(&main, &[], Some(&path));
+        assert!(output.status.success(), "{}", stderr(&output));
+        assert_eq!(stdout(&output), "2\n");
+    }
+
+    // --project 优先于环境变量
+    let flag = root.join("project.toml");
+    let output = run(&main, &["--project", flag.to_str().unwrap()], Some(&root.join("src/tools")));
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let missing = root.join("nowhere/project.toml");
+    let output = run(&main, &["--project", missing.to_str().unwrap()], None);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains(&format!("--project points to {}, which does not exist", missing.display())), "{}", stderr(&output));
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_file_outside_source_directory_is_an_error() {
+    let app = project("com.app");
+    let root = make_tree("outside_src", &[
+        ("project.toml", app.as_str()),
+        ("scripts/tool.q", "func main() {\n    println(3)\n}\n"),
+    ]);
+    let output = run(&root.join("scripts/tool.q"), &[], None);
+    assert!(!output.status.success());
+    let expected = format!(
+        "{} is not under {}, the source directory of {}",
+        root.join("scripts/tool.q").display(),
+        root.join("src").display(),
+        root.join("project.toml").display(),
+    );
+    assert!(stderr(&output).contains(&expected), "{}", stderr(&output));
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_strict_types_from_project_and_flag() {
+    let strict = format!("{}\n[build]\nstrict-types = true\n", project("com.app"));
+    let source = "package com.app\n\nclass Box {\n    var value = 1\n}\n\nfunc main() {\n    println(new Box().value)\n}\n";
+    let root = make_tree("strict_types", &[
+        ("strict/project.toml", strict.as_str()),
+        ("strict/src/main.q", source),
+        ("loose/project.toml", project("com.app").as_str()),
+        ("loose/src/main.q", source),
+    ]);
+    let warning = "[4:9] W0001: field 'value' of 'Box' is implicitly any; based on its initializer it may be int — annotate it";
+
+    // 默认只是警告
+    let loose = root.join("loose/src/main.q");
+    let output = run(&loose, &[], None);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "1\n");
+    assert!(stderr(&output).contains(warning), "{}", stderr(&output));
+
+    // [build] strict-types 和 --strict-types 都让它成为错误，--set 可以临时关闭
+    for (file, args) in [("strict/src/main.q", &[][..]), ("loose/src/main.q", &["--strict-types"][..])] {
+        let output = run(&root.join(file), args, None);
+        assert!(!output.status.success());
+        assert!(stderr(&output).contains(warning), "{}", stderr(&output));
+        assert!(stderr(&output).contains("implicit any is an error under --strict-types"), "{}", stderr(&output));
+    }
+    let output = run(&root.join("strict/src/main.q"), &["--set", "build.strict-types=false"], None);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let _ = fs::remove_dir_all(&root);
+}